@@ -0,0 +1,560 @@
+use crate::ast::*;
+
+/// Owned walk over the AST that rebuilds it. Expressions and statements
+/// fold to boxed nodes so a pass may replace a node with another kind
+/// (e.g. an `InfixExpression` with an `IntegerLiteral`); identifiers and
+/// blocks sit in typed fields and fold to their own type.
+pub trait Fold {
+    fn fold_program(&mut self, program: Program) -> Program {
+        fold_program(self, program)
+    }
+    fn fold_statement(&mut self, stmt: Box<dyn Statement>) -> Box<dyn Statement> {
+        fold_statement(self, stmt)
+    }
+    fn fold_expression(&mut self, expr: Box<dyn Expression>) -> Box<dyn Expression> {
+        fold_expression(self, expr)
+    }
+    fn fold_let_statement(&mut self, stmt: LetStatement) -> Box<dyn Statement> {
+        Box::new(fold_let_statement(self, stmt))
+    }
+    fn fold_return_statement(&mut self, stmt: ReturnStatement) -> Box<dyn Statement> {
+        Box::new(fold_return_statement(self, stmt))
+    }
+    fn fold_expression_statement(&mut self, stmt: ExpressionStatement) -> Box<dyn Statement> {
+        Box::new(fold_expression_statement(self, stmt))
+    }
+    fn fold_block_statement(&mut self, block: BlockStatement) -> BlockStatement {
+        fold_block_statement(self, block)
+    }
+    fn fold_identifier(&mut self, ident: Identifier) -> Identifier {
+        ident
+    }
+    fn fold_integer_literal(&mut self, lit: IntegerLiteral) -> Box<dyn Expression> {
+        Box::new(lit)
+    }
+    fn fold_float_literal(&mut self, lit: FloatLiteral) -> Box<dyn Expression> {
+        Box::new(lit)
+    }
+    fn fold_boolean(&mut self, lit: Boolean) -> Box<dyn Expression> {
+        Box::new(lit)
+    }
+    fn fold_prefix_expression(&mut self, expr: PrefixExpression) -> Box<dyn Expression> {
+        Box::new(fold_prefix_expression(self, expr))
+    }
+    fn fold_infix_expression(&mut self, expr: InfixExpression) -> Box<dyn Expression> {
+        Box::new(fold_infix_expression(self, expr))
+    }
+    fn fold_if_expression(&mut self, expr: IfExpression) -> Box<dyn Expression> {
+        Box::new(fold_if_expression(self, expr))
+    }
+    fn fold_function_literal(&mut self, func: FunctionLiteral) -> Box<dyn Expression> {
+        Box::new(fold_function_literal(self, func))
+    }
+    fn fold_call_expression(&mut self, call: CallExpression) -> Box<dyn Expression> {
+        Box::new(fold_call_expression(self, call))
+    }
+    fn fold_var_statement(&mut self, stmt: VarStatement) -> Box<dyn Statement> {
+        Box::new(fold_var_statement(self, stmt))
+    }
+    fn fold_type_statement(&mut self, stmt: TypeStatement) -> Box<dyn Statement> {
+        Box::new(fold_type_statement(self, stmt))
+    }
+    fn fold_assign_statement(&mut self, stmt: AssignStatement) -> Box<dyn Statement> {
+        Box::new(fold_assign_statement(self, stmt))
+    }
+    fn fold_for_statement(&mut self, stmt: ForStatement) -> Box<dyn Statement> {
+        Box::new(fold_for_statement(self, stmt))
+    }
+    fn fold_range_statement(&mut self, stmt: RangeStatement) -> Box<dyn Statement> {
+        Box::new(fold_range_statement(self, stmt))
+    }
+    fn fold_branch_statement(&mut self, stmt: BranchStatement) -> Box<dyn Statement> {
+        Box::new(stmt)
+    }
+    fn fold_string_literal(&mut self, lit: StringLiteral) -> Box<dyn Expression> {
+        Box::new(lit)
+    }
+    fn fold_index_expression(&mut self, expr: IndexExpression) -> Box<dyn Expression> {
+        Box::new(fold_index_expression(self, expr))
+    }
+    fn fold_slice_expression(&mut self, expr: SliceExpression) -> Box<dyn Expression> {
+        Box::new(fold_slice_expression(self, expr))
+    }
+    fn fold_selector_expression(&mut self, expr: SelectorExpression) -> Box<dyn Expression> {
+        Box::new(fold_selector_expression(self, expr))
+    }
+    fn fold_type_assert_expression(&mut self, expr: TypeAssertExpression) -> Box<dyn Expression> {
+        Box::new(fold_type_assert_expression(self, expr))
+    }
+    fn fold_composite_literal(&mut self, lit: CompositeLiteral) -> Box<dyn Expression> {
+        Box::new(fold_composite_literal(self, lit))
+    }
+    fn fold_key_value_expression(&mut self, expr: KeyValueExpression) -> Box<dyn Expression> {
+        Box::new(fold_key_value_expression(self, expr))
+    }
+    fn fold_slice_type(&mut self, ty: SliceType) -> Box<dyn Expression> {
+        Box::new(fold_slice_type(self, ty))
+    }
+    fn fold_map_type(&mut self, ty: MapType) -> Box<dyn Expression> {
+        Box::new(fold_map_type(self, ty))
+    }
+    fn fold_struct_type(&mut self, ty: StructType) -> Box<dyn Expression> {
+        Box::new(fold_struct_type(self, ty))
+    }
+    fn fold_interface_type(&mut self, ty: InterfaceType) -> Box<dyn Expression> {
+        Box::new(ty)
+    }
+    fn fold_chan_type(&mut self, ty: ChanType) -> Box<dyn Expression> {
+        Box::new(fold_chan_type(self, ty))
+    }
+    fn fold_go_statement(&mut self, stmt: GoStatement) -> Box<dyn Statement> {
+        Box::new(fold_go_statement(self, stmt))
+    }
+    fn fold_defer_statement(&mut self, stmt: DeferStatement) -> Box<dyn Statement> {
+        Box::new(fold_defer_statement(self, stmt))
+    }
+    fn fold_send_statement(&mut self, stmt: SendStatement) -> Box<dyn Statement> {
+        Box::new(fold_send_statement(self, stmt))
+    }
+    fn fold_select_statement(&mut self, stmt: SelectStatement) -> Box<dyn Statement> {
+        Box::new(fold_select_statement(self, stmt))
+    }
+    fn fold_import_statement(&mut self, stmt: ImportStatement) -> Box<dyn Statement> {
+        Box::new(fold_import_statement(self, stmt))
+    }
+    fn fold_package_clause(&mut self, stmt: PackageClause) -> Box<dyn Statement> {
+        Box::new(fold_package_clause(self, stmt))
+    }
+}
+
+pub fn fold_program<F: Fold + ?Sized>(f: &mut F, program: Program) -> Program {
+    Program {
+        statements: program.statements.into_iter().map(|s| f.fold_statement(s)).collect(),
+    }
+}
+
+/// Dispatches to the `fold_*` method for the statement's concrete type.
+pub fn fold_statement<F: Fold + ?Sized>(f: &mut F, stmt: Box<dyn Statement>) -> Box<dyn Statement> {
+    let any = stmt.into_any();
+    let any = match any.downcast::<LetStatement>() {
+        Ok(s) => return f.fold_let_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<ReturnStatement>() {
+        Ok(s) => return f.fold_return_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<ExpressionStatement>() {
+        Ok(s) => return f.fold_expression_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<BlockStatement>() {
+        Ok(s) => return Box::new(f.fold_block_statement(*s)),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<VarStatement>() {
+        Ok(s) => return f.fold_var_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<TypeStatement>() {
+        Ok(s) => return f.fold_type_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<AssignStatement>() {
+        Ok(s) => return f.fold_assign_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<ForStatement>() {
+        Ok(s) => return f.fold_for_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<RangeStatement>() {
+        Ok(s) => return f.fold_range_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<BranchStatement>() {
+        Ok(s) => return f.fold_branch_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<GoStatement>() {
+        Ok(s) => return f.fold_go_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<DeferStatement>() {
+        Ok(s) => return f.fold_defer_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<SendStatement>() {
+        Ok(s) => return f.fold_send_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<SelectStatement>() {
+        Ok(s) => return f.fold_select_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<ImportStatement>() {
+        Ok(s) => return f.fold_import_statement(*s),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<PackageClause>() {
+        Ok(s) => return f.fold_package_clause(*s),
+        Err(any) => any,
+    };
+    match any.downcast::<Program>() {
+        Ok(s) => Box::new(f.fold_program(*s)),
+        Err(_) => unreachable!("unknown statement type"),
+    }
+}
+
+/// Dispatches to the `fold_*` method for the expression's concrete type.
+pub fn fold_expression<F: Fold + ?Sized>(f: &mut F, expr: Box<dyn Expression>) -> Box<dyn Expression> {
+    let any = expr.into_any();
+    let any = match any.downcast::<Identifier>() {
+        Ok(e) => return Box::new(f.fold_identifier(*e)),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<IntegerLiteral>() {
+        Ok(e) => return f.fold_integer_literal(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<FloatLiteral>() {
+        Ok(e) => return f.fold_float_literal(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<Boolean>() {
+        Ok(e) => return f.fold_boolean(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<PrefixExpression>() {
+        Ok(e) => return f.fold_prefix_expression(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<InfixExpression>() {
+        Ok(e) => return f.fold_infix_expression(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<IfExpression>() {
+        Ok(e) => return f.fold_if_expression(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<FunctionLiteral>() {
+        Ok(e) => return f.fold_function_literal(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<CallExpression>() {
+        Ok(e) => return f.fold_call_expression(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<StringLiteral>() {
+        Ok(e) => return f.fold_string_literal(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<IndexExpression>() {
+        Ok(e) => return f.fold_index_expression(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<SliceExpression>() {
+        Ok(e) => return f.fold_slice_expression(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<SelectorExpression>() {
+        Ok(e) => return f.fold_selector_expression(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<TypeAssertExpression>() {
+        Ok(e) => return f.fold_type_assert_expression(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<CompositeLiteral>() {
+        Ok(e) => return f.fold_composite_literal(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<KeyValueExpression>() {
+        Ok(e) => return f.fold_key_value_expression(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<SliceType>() {
+        Ok(e) => return f.fold_slice_type(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<MapType>() {
+        Ok(e) => return f.fold_map_type(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<StructType>() {
+        Ok(e) => return f.fold_struct_type(*e),
+        Err(any) => any,
+    };
+    let any = match any.downcast::<InterfaceType>() {
+        Ok(e) => return f.fold_interface_type(*e),
+        Err(any) => any,
+    };
+    match any.downcast::<ChanType>() {
+        Ok(e) => f.fold_chan_type(*e),
+        Err(_) => unreachable!("unknown expression type"),
+    }
+}
+
+pub fn fold_let_statement<F: Fold + ?Sized>(f: &mut F, stmt: LetStatement) -> LetStatement {
+    LetStatement {
+        token: stmt.token,
+        name: stmt.name.map(|n| f.fold_identifier(n)),
+        value: stmt.value.map(|v| f.fold_expression(v)),
+    }
+}
+
+pub fn fold_return_statement<F: Fold + ?Sized>(f: &mut F, stmt: ReturnStatement) -> ReturnStatement {
+    ReturnStatement {
+        token: stmt.token,
+        return_value: stmt.return_value.map(|v| f.fold_expression(v)),
+    }
+}
+
+pub fn fold_expression_statement<F: Fold + ?Sized>(f: &mut F, stmt: ExpressionStatement) -> ExpressionStatement {
+    ExpressionStatement {
+        token: stmt.token,
+        expression: stmt.expression.map(|e| f.fold_expression(e)),
+    }
+}
+
+pub fn fold_block_statement<F: Fold + ?Sized>(f: &mut F, block: BlockStatement) -> BlockStatement {
+    BlockStatement {
+        token: block.token,
+        statements: block.statements.into_iter().map(|s| f.fold_statement(s)).collect(),
+        rbrace: block.rbrace,
+    }
+}
+
+pub fn fold_prefix_expression<F: Fold + ?Sized>(f: &mut F, expr: PrefixExpression) -> PrefixExpression {
+    PrefixExpression {
+        token: expr.token,
+        operator: expr.operator,
+        right: expr.right.map(|r| f.fold_expression(r)),
+    }
+}
+
+pub fn fold_infix_expression<F: Fold + ?Sized>(f: &mut F, expr: InfixExpression) -> InfixExpression {
+    InfixExpression {
+        token: expr.token,
+        left: expr.left.map(|l| f.fold_expression(l)),
+        operator: expr.operator,
+        right: expr.right.map(|r| f.fold_expression(r)),
+    }
+}
+
+pub fn fold_if_expression<F: Fold + ?Sized>(f: &mut F, expr: IfExpression) -> IfExpression {
+    IfExpression {
+        token: expr.token,
+        condition: expr.condition.map(|c| f.fold_expression(c)),
+        consequence: expr.consequence.map(|b| f.fold_block_statement(b)),
+        alternative: expr.alternative.map(|b| f.fold_block_statement(b)),
+    }
+}
+
+pub fn fold_function_literal<F: Fold + ?Sized>(f: &mut F, func: FunctionLiteral) -> FunctionLiteral {
+    FunctionLiteral {
+        token: func.token,
+        parameters: func.parameters.into_iter().map(|p| f.fold_identifier(p)).collect(),
+        results: func.results.into_iter().map(|field| fold_field(f, field)).collect(),
+        body: func.body.map(|b| f.fold_block_statement(b)),
+    }
+}
+
+pub fn fold_call_expression<F: Fold + ?Sized>(f: &mut F, call: CallExpression) -> CallExpression {
+    CallExpression {
+        token: call.token,
+        function: call.function.map(|e| f.fold_expression(e)),
+        arguments: call.arguments.into_iter().map(|a| f.fold_expression(a)).collect(),
+        ellipsis: call.ellipsis,
+        rparen: call.rparen,
+    }
+}
+
+pub fn fold_var_statement<F: Fold + ?Sized>(f: &mut F, stmt: VarStatement) -> VarStatement {
+    VarStatement {
+            token: stmt.token,
+            names: stmt.names.into_iter().map(|n| f.fold_identifier(n)).collect(),
+            ty: stmt.ty.map(|e| f.fold_expression(e)),
+            values: stmt.values.into_iter().map(|e| f.fold_expression(e)).collect(),
+    }
+}
+
+pub fn fold_type_statement<F: Fold + ?Sized>(f: &mut F, stmt: TypeStatement) -> TypeStatement {
+    TypeStatement {
+            token: stmt.token,
+            name: stmt.name.map(|n| f.fold_identifier(n)),
+            ty: stmt.ty.map(|e| f.fold_expression(e)),
+    }
+}
+
+pub fn fold_assign_statement<F: Fold + ?Sized>(f: &mut F, stmt: AssignStatement) -> AssignStatement {
+    AssignStatement {
+            token: stmt.token,
+            left: stmt.left.into_iter().map(|e| f.fold_expression(e)).collect(),
+            operator: stmt.operator,
+            right: stmt.right.into_iter().map(|e| f.fold_expression(e)).collect(),
+    }
+}
+
+pub fn fold_for_statement<F: Fold + ?Sized>(f: &mut F, stmt: ForStatement) -> ForStatement {
+    ForStatement {
+            token: stmt.token,
+            init: stmt.init.map(|s| f.fold_statement(s)),
+            condition: stmt.condition.map(|e| f.fold_expression(e)),
+            post: stmt.post.map(|s| f.fold_statement(s)),
+            body: stmt.body.map(|b| f.fold_block_statement(b)),
+    }
+}
+
+pub fn fold_range_statement<F: Fold + ?Sized>(f: &mut F, stmt: RangeStatement) -> RangeStatement {
+    RangeStatement {
+            token: stmt.token,
+            key: stmt.key.map(|e| f.fold_expression(e)),
+            value: stmt.value.map(|e| f.fold_expression(e)),
+            operator: stmt.operator,
+            x: stmt.x.map(|e| f.fold_expression(e)),
+            body: stmt.body.map(|b| f.fold_block_statement(b)),
+    }
+}
+
+pub fn fold_index_expression<F: Fold + ?Sized>(f: &mut F, expr: IndexExpression) -> IndexExpression {
+    IndexExpression {
+            token: expr.token,
+            left: expr.left.map(|e| f.fold_expression(e)),
+            index: expr.index.map(|e| f.fold_expression(e)),
+            rbracket: expr.rbracket,
+    }
+}
+
+pub fn fold_slice_expression<F: Fold + ?Sized>(f: &mut F, expr: SliceExpression) -> SliceExpression {
+    SliceExpression {
+            token: expr.token,
+            left: expr.left.map(|e| f.fold_expression(e)),
+            low: expr.low.map(|e| f.fold_expression(e)),
+            high: expr.high.map(|e| f.fold_expression(e)),
+            rbracket: expr.rbracket,
+    }
+}
+
+pub fn fold_selector_expression<F: Fold + ?Sized>(f: &mut F, expr: SelectorExpression) -> SelectorExpression {
+    SelectorExpression {
+            token: expr.token,
+            left: expr.left.map(|e| f.fold_expression(e)),
+            selector: expr.selector.map(|n| f.fold_identifier(n)),
+    }
+}
+
+pub fn fold_type_assert_expression<F: Fold + ?Sized>(f: &mut F, expr: TypeAssertExpression) -> TypeAssertExpression {
+    TypeAssertExpression {
+            token: expr.token,
+            left: expr.left.map(|e| f.fold_expression(e)),
+            ty: expr.ty.map(|e| f.fold_expression(e)),
+            rparen: expr.rparen,
+    }
+}
+
+pub fn fold_composite_literal<F: Fold + ?Sized>(f: &mut F, lit: CompositeLiteral) -> CompositeLiteral {
+    CompositeLiteral {
+            token: lit.token,
+            ty: lit.ty.map(|e| f.fold_expression(e)),
+            elements: lit.elements.into_iter().map(|e| f.fold_expression(e)).collect(),
+            rbrace: lit.rbrace,
+    }
+}
+
+pub fn fold_key_value_expression<F: Fold + ?Sized>(f: &mut F, expr: KeyValueExpression) -> KeyValueExpression {
+    KeyValueExpression {
+            token: expr.token,
+            key: expr.key.map(|e| f.fold_expression(e)),
+            value: expr.value.map(|e| f.fold_expression(e)),
+    }
+}
+
+pub fn fold_slice_type<F: Fold + ?Sized>(f: &mut F, ty: SliceType) -> SliceType {
+    SliceType {
+            token: ty.token,
+            elem: ty.elem.map(|e| f.fold_expression(e)),
+    }
+}
+
+pub fn fold_map_type<F: Fold + ?Sized>(f: &mut F, ty: MapType) -> MapType {
+    MapType {
+            token: ty.token,
+            key: ty.key.map(|e| f.fold_expression(e)),
+            value: ty.value.map(|e| f.fold_expression(e)),
+    }
+}
+
+pub fn fold_struct_type<F: Fold + ?Sized>(f: &mut F, ty: StructType) -> StructType {
+    StructType {
+            token: ty.token,
+            fields: ty.fields.into_iter().map(|field| fold_field(f, field)).collect(),
+            rbrace: ty.rbrace,
+    }
+}
+
+fn fold_field<F: Fold + ?Sized>(f: &mut F, field: FieldDecl) -> FieldDecl {
+    FieldDecl {
+        names: field.names.into_iter().map(|n| f.fold_identifier(n)).collect(),
+        ty: field.ty.map(|t| f.fold_expression(t)),
+    }
+}
+
+pub fn fold_chan_type<F: Fold + ?Sized>(f: &mut F, ty: ChanType) -> ChanType {
+    ChanType {
+            token: ty.token,
+            elem: ty.elem.map(|e| f.fold_expression(e)),
+    }
+}
+
+pub fn fold_go_statement<F: Fold + ?Sized>(f: &mut F, stmt: GoStatement) -> GoStatement {
+    GoStatement {
+            token: stmt.token,
+            call: stmt.call.map(|e| f.fold_expression(e)),
+    }
+}
+
+pub fn fold_defer_statement<F: Fold + ?Sized>(f: &mut F, stmt: DeferStatement) -> DeferStatement {
+    DeferStatement {
+            token: stmt.token,
+            call: stmt.call.map(|e| f.fold_expression(e)),
+    }
+}
+
+pub fn fold_send_statement<F: Fold + ?Sized>(f: &mut F, stmt: SendStatement) -> SendStatement {
+    SendStatement {
+            token: stmt.token,
+            channel: stmt.channel.map(|e| f.fold_expression(e)),
+            value: stmt.value.map(|e| f.fold_expression(e)),
+    }
+}
+
+pub fn fold_select_statement<F: Fold + ?Sized>(f: &mut F, stmt: SelectStatement) -> SelectStatement {
+    SelectStatement {
+            token: stmt.token,
+            cases: stmt.cases
+            .into_iter()
+            .map(|case| CommClause {
+                token: case.token,
+                comm: case.comm.map(|s| f.fold_statement(s)),
+                body: case.body.into_iter().map(|s| f.fold_statement(s)).collect(),
+            })
+            .collect(),
+            rbrace: stmt.rbrace,
+    }
+}
+
+pub fn fold_import_statement<F: Fold + ?Sized>(f: &mut F, stmt: ImportStatement) -> ImportStatement {
+    ImportStatement {
+            token: stmt.token,
+            specs: stmt.specs
+            .into_iter()
+            .map(|spec| ImportSpec { name: spec.name.map(|n| f.fold_identifier(n)), path: spec.path })
+            .collect(),
+            rparen: stmt.rparen,
+    }
+}
+
+pub fn fold_package_clause<F: Fold + ?Sized>(f: &mut F, stmt: PackageClause) -> PackageClause {
+    PackageClause { token: stmt.token, name: stmt.name.map(|n| f.fold_identifier(n)) }
+}
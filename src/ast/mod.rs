@@ -2,6 +2,13 @@ use crate::token::*;
 use std::clone::Clone;
 use std::any::Any;
 
+pub mod fold;
+pub mod visit;
+pub mod visit_mut;
+
+#[cfg(test)]
+mod test;
+
 
 //TODO: Leave it until Rust supports multiple inheritance for Box<{any struct with interface}>
 /*
@@ -18,6 +25,9 @@ pub trait Statement {
     fn box_clone(&self) -> Box<dyn Statement>;
     fn type_name(&self) -> String;
     fn to_any(&self) -> Box<dyn Any + 'static>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
 }
 
 /// Clone for trait object 
@@ -32,9 +42,12 @@ pub trait Expression {
     fn token_literal(&self) -> String;
     fn expression_node(&self);
     fn string(&self) -> String;
-    fn box_clone(&self) -> Box<dyn Expression>;   
+    fn box_clone(&self) -> Box<dyn Expression>;
     fn type_name(&self) -> String;
     fn to_any(&self) -> Box<dyn Any + 'static>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
 }
 
 
@@ -45,6 +58,30 @@ impl Clone for Box<dyn Expression> {
     }
 }
 
+/// The `Any` plumbing and cloning every node implements the same way.
+macro_rules! node_common {
+    ($node:ident) => {
+        fn box_clone(&self) -> Box<dyn $node> {
+            Box::new((*self).clone())
+        }
+        fn type_name(&self) -> String {
+            type_of(&self)
+        }
+        fn to_any(&self) -> Box<dyn Any + 'static> {
+            Box::new((*self).clone())
+        }
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+        fn into_any(self: Box<Self>) -> Box<dyn Any> {
+            self
+        }
+    };
+}
+
 /// Program after reading the code
 #[derive(Clone)]
 pub struct Program {
@@ -53,29 +90,21 @@ pub struct Program {
 
 impl Statement for Program {
     fn token_literal(&self) -> String {
-        if self.statements.len() > 0 {
-            return self.statements[0].token_literal();
+        if !self.statements.is_empty() {
+            self.statements[0].token_literal()
         } else {
-            return "".to_string();
+            "".to_string()
         }
     }
     fn statement_node(&self) {}
     fn string(&self) -> String {
         let mut out = "".to_string();
-        for s in self.statements.clone() {
-            out.push_str(&s.token_literal());
+        for s in &self.statements {
+            out.push_str(&s.string());
         }
-        return out.to_string();
-    }
-    fn box_clone(&self) -> Box<dyn Statement> {
-        Box::new((*self).clone())
-    }
-    fn type_name(&self) -> String {
-        return type_of(&self);
-    }
-    fn to_any(&self) -> Box<dyn Any + 'static> {
-       Box::new((*self).clone())
+        out
     }
+    node_common!(Statement);
 }
 
 
@@ -89,27 +118,19 @@ pub struct LetStatement {
 
 impl Statement for LetStatement {
     fn token_literal(&self) -> String {
-        return self.token.literal.to_string();
+        self.token.literal.to_string()
     }
     fn statement_node(&self) {}
     fn string(&self) -> String {
-        let out = &[self.token_literal(), " ".to_string(), self.name.clone().unwrap().value, " = ".to_string()].concat();
+        let mut out = [self.token_literal(), " ".to_string(), self.name.clone().unwrap().value, " = ".to_string()].concat();
 
-        if self.value.is_some() {
-            let out2 = [out.clone(), self.value.clone().unwrap().string()].concat();
-            out2.to_string();
+        if let Some(value) = &self.value {
+            out.push_str(&value.string());
         }
-        return out.to_string();
-    }
-    fn box_clone(&self) -> Box<dyn Statement> {
-        Box::new((*self).clone())
+        out.push(';');
+        out
     }
-    fn type_name(&self) -> String {
-        return type_of(&self);
-    }
-    fn to_any(&self) -> Box<dyn Any + 'static> {
-        Box::new((*self).clone())
-     }
+    node_common!(Statement);
 }
 
 
@@ -122,21 +143,13 @@ pub struct Identifier {
 
 impl Expression for Identifier {
     fn token_literal(&self) -> String {
-        return self.token.clone().unwrap().literal;
+        self.token.clone().unwrap().literal
     }
     fn expression_node(&self) {}
     fn string(&self) -> String {
-        return self.value.clone();
-    }
-    fn box_clone(&self) -> Box<dyn Expression> {
-        Box::new((*self).clone())
+        self.value.clone()
     }
-    fn type_name(&self) -> String {
-        return type_of(&self);
-    }
-    fn to_any(&self) -> Box<dyn Any + 'static> {
-        Box::new((*self).clone())
-     }
+    node_common!(Expression);
 }
 
 // Return statement
@@ -148,28 +161,20 @@ pub struct ReturnStatement {
 
 impl Statement for ReturnStatement {
     fn token_literal(&self) -> String {
-        return self.token.literal.to_string();
+        self.token.literal.to_string()
     }
     fn statement_node(&self) {}
     fn string(&self) -> String {
-        let out = &[&self.token_literal(), " "].concat();
-        
-        if self.return_value.is_some() {
-            let out2 = &[out.clone(), self.return_value.clone().unwrap().string()].concat();
-            return out2.to_string();
+        let mut out = self.token_literal();
+
+        if let Some(value) = &self.return_value {
+            out.push(' ');
+            out.push_str(&value.string());
         }
-        
-        return out.to_string();
+        out.push(';');
+        out
     }
-    fn box_clone(&self) -> Box<dyn Statement> {
-        Box::new((*self).clone())
-    }
-    fn type_name(&self) -> String {
-        return type_of(&self);
-    }
-    fn to_any(&self) -> Box<dyn Any + 'static> {
-        Box::new((*self).clone())
-     }
+    node_common!(Statement);
 }
 
 
@@ -183,24 +188,16 @@ pub struct ExpressionStatement {
 
 impl Statement for ExpressionStatement {
     fn token_literal(&self) -> String {
-        return self.token.literal.to_string();
+        self.token.literal.to_string()
     }
     fn statement_node(&self) {}
     fn string(&self) -> String  {
-        if self.expression.is_some() {
-            return self.expression.clone().unwrap().string();
+        match &self.expression {
+            Some(expression) => expression.string(),
+            None => "".to_string(),
         }
-        return "".to_string();
-    }
-    fn box_clone(&self) -> Box<dyn Statement> {
-        Box::new((*self).clone())
     }
-    fn type_name(&self) -> String {
-        return type_of(&self);
-    }
-    fn to_any(&self) -> Box<dyn Any + 'static> {
-        Box::new((*self).clone())
-     }
+    node_common!(Statement);
 }
 
 
@@ -214,23 +211,806 @@ pub struct IntegerLiteral {
 
 impl Expression for IntegerLiteral {
     fn token_literal(&self) -> String {
-        return self.token.clone().literal;
+        self.token.clone().literal
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        self.token.clone().literal
+    }
+    node_common!(Expression);
+}
+
+/// Floating-point literal (e.g. 1.5, .5, 1e9)
+#[derive(Clone)]
+pub struct FloatLiteral {
+    pub token: Token,
+    pub value: Option<f64>
+}
+
+impl Expression for FloatLiteral {
+    fn token_literal(&self) -> String {
+        self.token.clone().literal
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        self.token.clone().literal
+    }
+    node_common!(Expression);
+}
+
+/// Boolean literal
+#[derive(Clone)]
+pub struct Boolean {
+    pub token: Token,
+    pub value: bool,
+}
+
+impl Expression for Boolean {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
     }
     fn expression_node(&self) {}
     fn string(&self) -> String {
-        return self.token.clone().literal;
+        self.token.literal.to_string()
+    }
+    node_common!(Expression);
+}
+
+
+/// Prefix expression (e.g. -5, !ok)
+#[derive(Clone)]
+pub struct PrefixExpression {
+    pub token: Token,
+    pub operator: String,
+    pub right: Option<Box<dyn Expression>>,
+}
+
+impl Expression for PrefixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
     }
-    fn box_clone(&self) -> Box<dyn Expression> {
-        Box::new((*self).clone())
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        let right = self.right.as_ref().map(|r| r.string()).unwrap_or_default();
+        ["(", &self.operator, &right, ")"].concat()
     }
-    fn type_name(&self) -> String {
-        return type_of(&self);
+    node_common!(Expression);
+}
+
+
+/// Infix expression (e.g. 5 + 5)
+#[derive(Clone)]
+pub struct InfixExpression {
+    pub token: Token,
+    pub left: Option<Box<dyn Expression>>,
+    pub operator: String,
+    pub right: Option<Box<dyn Expression>>,
+}
+
+impl Expression for InfixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        let left = self.left.as_ref().map(|l| l.string()).unwrap_or_default();
+        let right = self.right.as_ref().map(|r| r.string()).unwrap_or_default();
+        ["(", &left, " ", &self.operator, " ", &right, ")"].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Block of statements between braces
+#[derive(Clone)]
+pub struct BlockStatement {
+    pub token: Token,
+    pub statements: Vec<Box<dyn Statement>>,
+    /// closing brace, absent when the block was not read from source
+    pub rbrace: Option<Token>,
+}
+
+impl Statement for BlockStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        let mut out = "".to_string();
+        for s in &self.statements {
+            out.push_str(&s.string());
+        }
+        out
+    }
+    node_common!(Statement);
+}
+
+
+/// If expression with an optional else branch
+#[derive(Clone)]
+pub struct IfExpression {
+    pub token: Token,
+    pub condition: Option<Box<dyn Expression>>,
+    pub consequence: Option<BlockStatement>,
+    pub alternative: Option<BlockStatement>,
+}
+
+impl Expression for IfExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        let condition = self.condition.as_ref().map(|c| c.string()).unwrap_or_default();
+        let consequence = self.consequence.as_ref().map(|c| c.string()).unwrap_or_default();
+        let mut out = ["if", &condition, " ", &consequence].concat();
+        if let Some(alternative) = &self.alternative {
+            out.push_str("else ");
+            out.push_str(&alternative.string());
+        }
+        out
+    }
+    node_common!(Expression);
+}
+
+
+/// Function literal (e.g. fn(x, y) { x + y })
+#[derive(Clone)]
+pub struct FunctionLiteral {
+    pub token: Token,
+    pub parameters: Vec<Identifier>,
+    /// named results (e.g. `(n int)`), empty when there are none
+    pub results: Vec<FieldDecl>,
+    pub body: Option<BlockStatement>,
+}
+
+impl Expression for FunctionLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        let params: Vec<String> = self.parameters.iter().map(|p| p.string()).collect();
+        let body = self.body.as_ref().map(|b| b.string()).unwrap_or_default();
+        let results = if self.results.is_empty() { String::new() } else { ["(", &field_list(&self.results), ") "].concat() };
+        [self.token_literal(), "(".to_string(), params.join(", "), ") ".to_string(), results, body].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Call expression (e.g. add(1, 2))
+#[derive(Clone)]
+pub struct CallExpression {
+    pub token: Token,
+    pub function: Option<Box<dyn Expression>>,
+    pub arguments: Vec<Box<dyn Expression>>,
+    /// `...` after the last argument, which passes a slice as the
+    /// variadic arguments (e.g. `append(a, b...)`)
+    pub ellipsis: Option<Token>,
+    /// closing parenthesis, absent when the call was not read from source
+    pub rparen: Option<Token>,
+}
+
+impl Expression for CallExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
     }
-    fn to_any(&self) -> Box<dyn Any + 'static> {
-        Box::new((*self).clone())
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        let function = self.function.as_ref().map(|f| f.string()).unwrap_or_default();
+        let args: Vec<String> = self.arguments.iter().map(|a| a.string()).collect();
+        let ellipsis = if self.ellipsis.is_some() { "..." } else { "" };
+        [function, "(".to_string(), args.join(", "), ellipsis.to_string(), ")".to_string()].concat()
     }
+    node_common!(Expression);
 }
 
 pub fn type_of<T>(_: &T) -> String {
-    format!("{}", std::any::type_name::<T>())
+    std::any::type_name::<T>().to_string()
+}
+
+
+fn join_strings(items: &[Box<dyn Expression>]) -> String {
+    let items: Vec<String> = items.iter().map(|i| i.string()).collect();
+    items.join(", ")
+}
+
+fn optional_string(expr: &Option<Box<dyn Expression>>) -> String {
+    expr.as_ref().map(|e| e.string()).unwrap_or_default()
+}
+
+
+/// Variable declaration (e.g. var a, b int = 1, 2)
+#[derive(Clone)]
+pub struct VarStatement {
+    pub token: Token,
+    pub names: Vec<Identifier>,
+    pub ty: Option<Box<dyn Expression>>,
+    pub values: Vec<Box<dyn Expression>>,
+}
+
+impl Statement for VarStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        let names: Vec<String> = self.names.iter().map(|n| n.string()).collect();
+        let mut out = ["var ", &names.join(", ")].concat();
+        if let Some(ty) = &self.ty {
+            out.push(' ');
+            out.push_str(&ty.string());
+        }
+        if !self.values.is_empty() {
+            out.push_str(" = ");
+            out.push_str(&join_strings(&self.values));
+        }
+        out.push(';');
+        out
+    }
+    node_common!(Statement);
+}
+
+
+/// Type declaration (e.g. type Point struct { X, Y int })
+#[derive(Clone)]
+pub struct TypeStatement {
+    pub token: Token,
+    pub name: Option<Identifier>,
+    pub ty: Option<Box<dyn Expression>>,
+}
+
+impl Statement for TypeStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        let name = self.name.as_ref().map(|n| n.string()).unwrap_or_default();
+        ["type ", &name, " ", &optional_string(&self.ty), ";"].concat()
+    }
+    node_common!(Statement);
+}
+
+
+/// Package clause naming the package a file belongs to (e.g. package
+/// main).
+#[derive(Clone)]
+pub struct PackageClause {
+    pub token: Token,
+    pub name: Option<Identifier>,
+}
+
+impl Statement for PackageClause {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        ["package ", &self.name.as_ref().map(|n| n.string()).unwrap_or_default(), ";"].concat()
+    }
+    node_common!(Statement);
+}
+
+
+/// One package of an import declaration, with the name it is bound to
+/// when that is not the package's own (e.g. str "strings")
+#[derive(Clone)]
+pub struct ImportSpec {
+    pub name: Option<Identifier>,
+    pub path: Option<StringLiteral>,
+}
+
+impl ImportSpec {
+    fn string(&self) -> String {
+        let path = self.path.as_ref().map(|p| p.string()).unwrap_or_default();
+        match &self.name {
+            Some(name) => [name.string(), " ".to_string(), path].concat(),
+            None => path,
+        }
+    }
+}
+
+/// Import declaration (e.g. import "fmt"; import ( "os"; str "strings" )).
+/// `rparen` is set for the parenthesized form.
+#[derive(Clone)]
+pub struct ImportStatement {
+    pub token: Token,
+    pub specs: Vec<ImportSpec>,
+    pub rparen: Option<Token>,
+}
+
+impl Statement for ImportStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        let specs: Vec<String> = self.specs.iter().map(|s| s.string()).collect();
+        if self.rparen.is_some() {
+            ["import (", &specs.join("; "), ");"].concat()
+        } else {
+            ["import ", &specs.join(""), ";"].concat()
+        }
+    }
+    node_common!(Statement);
+}
+
+
+/// Assignment, short variable declaration or increment (e.g. a, b = b, a;
+/// x := 1; i++; n += 2). `token` is the operator.
+#[derive(Clone)]
+pub struct AssignStatement {
+    pub token: Token,
+    pub left: Vec<Box<dyn Expression>>,
+    pub operator: String,
+    /// empty for `++` and `--`
+    pub right: Vec<Box<dyn Expression>>,
+}
+
+impl Statement for AssignStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        if self.right.is_empty() {
+            return [join_strings(&self.left), self.operator.clone(), ";".to_string()].concat();
+        }
+        [join_strings(&self.left), " ".to_string(), self.operator.clone(), " ".to_string(), join_strings(&self.right), ";".to_string()].concat()
+    }
+    node_common!(Statement);
+}
+
+
+/// For loop with optional init statement, condition and post statement
+#[derive(Clone)]
+pub struct ForStatement {
+    pub token: Token,
+    pub init: Option<Box<dyn Statement>>,
+    pub condition: Option<Box<dyn Expression>>,
+    pub post: Option<Box<dyn Statement>>,
+    pub body: Option<BlockStatement>,
+}
+
+impl Statement for ForStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        let body = self.body.as_ref().map(|b| b.string()).unwrap_or_default();
+        if self.init.is_none() && self.post.is_none() {
+            return ["for ", &optional_string(&self.condition), " ", &body].concat();
+        }
+        let clause = |s: &Option<Box<dyn Statement>>| s.as_ref().map(|s| s.string().trim_end_matches(';').to_string()).unwrap_or_default();
+        ["for ", &clause(&self.init), "; ", &optional_string(&self.condition), "; ", &clause(&self.post), " ", &body].concat()
+    }
+    node_common!(Statement);
+}
+
+
+/// For loop over a range (e.g. for k, v := range m { })
+#[derive(Clone)]
+pub struct RangeStatement {
+    pub token: Token,
+    pub key: Option<Box<dyn Expression>>,
+    pub value: Option<Box<dyn Expression>>,
+    /// `:=`, `=`, or empty when there are no iteration variables
+    pub operator: String,
+    pub x: Option<Box<dyn Expression>>,
+    pub body: Option<BlockStatement>,
+}
+
+impl Statement for RangeStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        let mut out = "for ".to_string();
+        if let Some(key) = &self.key {
+            out.push_str(&key.string());
+            if let Some(value) = &self.value {
+                out.push_str(", ");
+                out.push_str(&value.string());
+            }
+            out.push(' ');
+            out.push_str(&self.operator);
+            out.push(' ');
+        }
+        out.push_str("range ");
+        out.push_str(&optional_string(&self.x));
+        out.push(' ');
+        out.push_str(&self.body.as_ref().map(|b| b.string()).unwrap_or_default());
+        out
+    }
+    node_common!(Statement);
+}
+
+
+/// break or continue
+#[derive(Clone)]
+pub struct BranchStatement {
+    pub token: Token,
+}
+
+impl Statement for BranchStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        [self.token_literal(), ";".to_string()].concat()
+    }
+    node_common!(Statement);
+}
+
+
+/// String literal; `value` is decoded, the token keeps the source text
+#[derive(Clone)]
+pub struct StringLiteral {
+    pub token: Token,
+    pub value: String,
+}
+
+impl Expression for StringLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        self.token.literal.to_string()
+    }
+    node_common!(Expression);
+}
+
+
+/// Index expression (e.g. a[i], m["k"])
+#[derive(Clone)]
+pub struct IndexExpression {
+    pub token: Token,
+    pub left: Option<Box<dyn Expression>>,
+    pub index: Option<Box<dyn Expression>>,
+    /// closing bracket, absent when the expression was not read from source
+    pub rbracket: Option<Token>,
+}
+
+impl Expression for IndexExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        ["(", &optional_string(&self.left), "[", &optional_string(&self.index), "])"].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Slice expression with optional bounds (e.g. s[1:], s[:n])
+#[derive(Clone)]
+pub struct SliceExpression {
+    pub token: Token,
+    pub left: Option<Box<dyn Expression>>,
+    pub low: Option<Box<dyn Expression>>,
+    pub high: Option<Box<dyn Expression>>,
+    pub rbracket: Option<Token>,
+}
+
+impl Expression for SliceExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        ["(", &optional_string(&self.left), "[", &optional_string(&self.low), ":", &optional_string(&self.high), "])"].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Selector expression (e.g. p.X)
+#[derive(Clone)]
+pub struct SelectorExpression {
+    pub token: Token,
+    pub left: Option<Box<dyn Expression>>,
+    pub selector: Option<Identifier>,
+}
+
+impl Expression for SelectorExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        let selector = self.selector.as_ref().map(|s| s.string()).unwrap_or_default();
+        [optional_string(&self.left), ".".to_string(), selector].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Type assertion (e.g. x.(int))
+#[derive(Clone)]
+pub struct TypeAssertExpression {
+    pub token: Token,
+    pub left: Option<Box<dyn Expression>>,
+    pub ty: Option<Box<dyn Expression>>,
+    pub rparen: Option<Token>,
+}
+
+impl Expression for TypeAssertExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        [optional_string(&self.left), ".(".to_string(), optional_string(&self.ty), ")".to_string()].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Composite literal (e.g. []int{1, 2}, Point{X: 1}); the type is absent
+/// for elements of an enclosing literal that leave it implied
+#[derive(Clone)]
+pub struct CompositeLiteral {
+    pub token: Token,
+    pub ty: Option<Box<dyn Expression>>,
+    pub elements: Vec<Box<dyn Expression>>,
+    pub rbrace: Option<Token>,
+}
+
+impl Expression for CompositeLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        [optional_string(&self.ty), "{".to_string(), join_strings(&self.elements), "}".to_string()].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Key and value of a composite literal element (e.g. X: 1)
+#[derive(Clone)]
+pub struct KeyValueExpression {
+    pub token: Token,
+    pub key: Option<Box<dyn Expression>>,
+    pub value: Option<Box<dyn Expression>>,
+}
+
+impl Expression for KeyValueExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        [optional_string(&self.key), ": ".to_string(), optional_string(&self.value)].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Slice type (e.g. []int)
+#[derive(Clone)]
+pub struct SliceType {
+    pub token: Token,
+    pub elem: Option<Box<dyn Expression>>,
+}
+
+impl Expression for SliceType {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        ["[]".to_string(), optional_string(&self.elem)].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Map type (e.g. map[string]int)
+#[derive(Clone)]
+pub struct MapType {
+    pub token: Token,
+    pub key: Option<Box<dyn Expression>>,
+    pub value: Option<Box<dyn Expression>>,
+}
+
+impl Expression for MapType {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        ["map[", &optional_string(&self.key), "]", &optional_string(&self.value)].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Field names sharing a type in a struct type (e.g. X, Y int), or
+/// named results sharing one, whose type may be left out
+#[derive(Clone)]
+pub struct FieldDecl {
+    pub names: Vec<Identifier>,
+    pub ty: Option<Box<dyn Expression>>,
+}
+
+fn field_list(fields: &[FieldDecl]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|f| {
+            let names: Vec<String> = f.names.iter().map(|n| n.string()).collect();
+            match &f.ty {
+                Some(ty) => [names.join(", "), " ".to_string(), ty.string()].concat(),
+                None => names.join(", "),
+            }
+        })
+        .collect();
+    fields.join("; ")
+}
+
+/// Struct type (e.g. struct { X, Y int })
+#[derive(Clone)]
+pub struct StructType {
+    pub token: Token,
+    pub fields: Vec<FieldDecl>,
+    pub rbrace: Option<Token>,
+}
+
+impl Expression for StructType {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        ["struct{", &field_list(&self.fields), "}"].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Interface type; only the empty interface{} for now
+#[derive(Clone)]
+pub struct InterfaceType {
+    pub token: Token,
+    pub rbrace: Option<Token>,
+}
+
+impl Expression for InterfaceType {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        "interface{}".to_string()
+    }
+    node_common!(Expression);
+}
+
+
+/// Channel type (e.g. chan int)
+#[derive(Clone)]
+pub struct ChanType {
+    pub token: Token,
+    pub elem: Option<Box<dyn Expression>>,
+}
+
+impl Expression for ChanType {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn expression_node(&self) {}
+    fn string(&self) -> String {
+        ["chan ".to_string(), optional_string(&self.elem)].concat()
+    }
+    node_common!(Expression);
+}
+
+
+/// Go statement (e.g. go worker(ch)); `call` is a call expression
+#[derive(Clone)]
+pub struct GoStatement {
+    pub token: Token,
+    pub call: Option<Box<dyn Expression>>,
+}
+
+impl Statement for GoStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        ["go ", &optional_string(&self.call), ";"].concat()
+    }
+    node_common!(Statement);
+}
+
+
+/// Defer statement (e.g. defer f.Close()); `call` is a call expression
+#[derive(Clone)]
+pub struct DeferStatement {
+    pub token: Token,
+    pub call: Option<Box<dyn Expression>>,
+}
+
+impl Statement for DeferStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        ["defer ", &optional_string(&self.call), ";"].concat()
+    }
+    node_common!(Statement);
+}
+
+
+/// Send statement (e.g. ch <- v); `token` is the arrow
+#[derive(Clone)]
+pub struct SendStatement {
+    pub token: Token,
+    pub channel: Option<Box<dyn Expression>>,
+    pub value: Option<Box<dyn Expression>>,
+}
+
+impl Statement for SendStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        [optional_string(&self.channel), " <- ".to_string(), optional_string(&self.value), ";".to_string()].concat()
+    }
+    node_common!(Statement);
+}
+
+
+/// One case of a select statement. `comm` is the send or receive it
+/// waits for, absent for `default`.
+#[derive(Clone)]
+pub struct CommClause {
+    pub token: Token,
+    pub comm: Option<Box<dyn Statement>>,
+    pub body: Vec<Box<dyn Statement>>,
+}
+
+/// Select statement (e.g. select { case v := <-ch: ... default: ... })
+#[derive(Clone)]
+pub struct SelectStatement {
+    pub token: Token,
+    pub cases: Vec<CommClause>,
+    pub rbrace: Option<Token>,
+}
+
+impl Statement for SelectStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.to_string()
+    }
+    fn statement_node(&self) {}
+    fn string(&self) -> String {
+        let mut out = "select {".to_string();
+        for case in &self.cases {
+            match &case.comm {
+                Some(comm) => {
+                    out.push_str("case ");
+                    out.push_str(comm.string().trim_end_matches(';'));
+                    out.push(':');
+                }
+                None => out.push_str("default:"),
+            }
+            for stmt in &case.body {
+                out.push_str(&stmt.string());
+            }
+        }
+        out.push('}');
+        out
+    }
+    node_common!(Statement);
 }
@@ -0,0 +1,141 @@
+use crate::ast::fold::*;
+use crate::ast::visit::*;
+use crate::ast::visit_mut::*;
+use crate::ast::*;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(input: &str) -> Program {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    if !p.errors().is_empty() {
+      panic!("parser errors: {:?}", p.errors());
+    }
+    program
+  }
+
+  /// Collects identifier names, including binding sites.
+  struct Names(Vec<String>);
+
+  impl Visitor for Names {
+    fn visit_identifier(&mut self, ident: &Identifier) {
+      self.0.push(ident.value.clone());
+    }
+  }
+
+  #[test]
+  fn test_visitor_reaches_every_identifier() {
+    let program = parse("let f = fn(a, b) { if a < b { return g(a) } else { -b } }; f(x, !y);");
+    let mut names = Names(vec![]);
+    names.visit_program(&program);
+
+    let expected = ["f", "a", "b", "a", "b", "g", "a", "b", "f", "x", "y"];
+    if names.0 != expected {
+      panic!("expected={:?}, got={:?}", expected, names.0);
+    }
+  }
+
+  /// Counts calls without looking inside function literals.
+  struct ShallowCalls(usize);
+
+  impl Visitor for ShallowCalls {
+    fn visit_call_expression(&mut self, call: &CallExpression) {
+      self.0 += 1;
+      walk_call_expression(self, call);
+    }
+    fn visit_function_literal(&mut self, _func: &FunctionLiteral) {}
+  }
+
+  #[test]
+  fn test_visitor_override_can_skip_children() {
+    let program = parse("f(g(1), fn() { h() });");
+    let mut calls = ShallowCalls(0);
+    calls.visit_program(&program);
+
+    if calls.0 != 2 {
+      panic!("expected 2 calls outside function literals, got {}", calls.0);
+    }
+  }
+
+  struct Rename;
+
+  impl VisitorMut for Rename {
+    fn visit_identifier_mut(&mut self, ident: &mut Identifier) {
+      if ident.value == "x" {
+        ident.value = "renamed".to_string();
+      }
+    }
+  }
+
+  #[test]
+  fn test_visitor_mut_rewrites_in_place() {
+    let mut program = parse("let x = 1; fn(x) { x + y }(x);");
+    Rename.visit_program_mut(&mut program);
+
+    let expected = "let renamed = 1;fn(renamed) (renamed + y)(renamed)";
+    if program.string() != expected {
+      panic!("expected={:?}, got={:?}", expected, program.string());
+    }
+  }
+
+  /// Replaces arithmetic on integer literals with its result.
+  struct ConstantFolder;
+
+  impl Fold for ConstantFolder {
+    fn fold_infix_expression(&mut self, expr: InfixExpression) -> Box<dyn Expression> {
+      let expr = fold_infix_expression(self, expr);
+      let value = |e: &Option<Box<dyn Expression>>| {
+        e.as_ref().and_then(|e| e.as_any().downcast_ref::<IntegerLiteral>().and_then(|l| l.value))
+      };
+      let result = match (value(&expr.left), value(&expr.right)) {
+        (Some(l), Some(r)) => match expr.operator.as_str() {
+          "+" => Some(l + r),
+          "-" => Some(l - r),
+          "*" => Some(l * r),
+          _ => None,
+        },
+        _ => None,
+      };
+      match result {
+        Some(v) => {
+          let mut token = expr.token.clone();
+          token.r#type = crate::token::INT;
+          token.literal = v.to_string();
+          Box::new(IntegerLiteral { token, value: Some(v) })
+        }
+        None => Box::new(expr),
+      }
+    }
+  }
+
+  #[test]
+  fn test_fold_replaces_nodes() {
+    let program = parse("let a = 1 + 2 * 3; if x { return (4 - 1) * y }");
+    let program = ConstantFolder.fold_program(program);
+
+    let expected = "let a = 7;ifx return (3 * y);";
+    if program.string() != expected {
+      panic!("expected={:?}, got={:?}", expected, program.string());
+    }
+  }
+
+  struct Identity;
+
+  impl Fold for Identity {}
+
+  #[test]
+  fn test_default_fold_is_identity() {
+    let input = "let add = fn(a, b) { a + b }; if add(1, -2) == 3 { true } else { false }";
+    let program = parse(input);
+    let before = program.string();
+    let after = Identity.fold_program(program).string();
+
+    if before != after {
+      panic!("expected={:?}, got={:?}", before, after);
+    }
+  }
+}
@@ -0,0 +1,485 @@
+use crate::ast::*;
+
+/// Read-only walk over the AST. Every `visit_*` method defaults to the
+/// matching `walk_*` function, so a pass overrides only the nodes it needs
+/// and calls `walk_*` itself to keep descending.
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program)
+    }
+    fn visit_statement(&mut self, stmt: &dyn Statement) {
+        walk_statement(self, stmt)
+    }
+    fn visit_expression(&mut self, expr: &dyn Expression) {
+        walk_expression(self, expr)
+    }
+    fn visit_let_statement(&mut self, stmt: &LetStatement) {
+        walk_let_statement(self, stmt)
+    }
+    fn visit_return_statement(&mut self, stmt: &ReturnStatement) {
+        walk_return_statement(self, stmt)
+    }
+    fn visit_expression_statement(&mut self, stmt: &ExpressionStatement) {
+        walk_expression_statement(self, stmt)
+    }
+    fn visit_block_statement(&mut self, block: &BlockStatement) {
+        walk_block_statement(self, block)
+    }
+    fn visit_identifier(&mut self, _ident: &Identifier) {}
+    fn visit_integer_literal(&mut self, _lit: &IntegerLiteral) {}
+    fn visit_float_literal(&mut self, _lit: &FloatLiteral) {}
+    fn visit_boolean(&mut self, _lit: &Boolean) {}
+    fn visit_prefix_expression(&mut self, expr: &PrefixExpression) {
+        walk_prefix_expression(self, expr)
+    }
+    fn visit_infix_expression(&mut self, expr: &InfixExpression) {
+        walk_infix_expression(self, expr)
+    }
+    fn visit_if_expression(&mut self, expr: &IfExpression) {
+        walk_if_expression(self, expr)
+    }
+    fn visit_function_literal(&mut self, func: &FunctionLiteral) {
+        walk_function_literal(self, func)
+    }
+    fn visit_call_expression(&mut self, call: &CallExpression) {
+        walk_call_expression(self, call)
+    }
+    fn visit_var_statement(&mut self, stmt: &VarStatement) {
+        walk_var_statement(self, stmt)
+    }
+    fn visit_type_statement(&mut self, stmt: &TypeStatement) {
+        walk_type_statement(self, stmt)
+    }
+    fn visit_assign_statement(&mut self, stmt: &AssignStatement) {
+        walk_assign_statement(self, stmt)
+    }
+    fn visit_for_statement(&mut self, stmt: &ForStatement) {
+        walk_for_statement(self, stmt)
+    }
+    fn visit_range_statement(&mut self, stmt: &RangeStatement) {
+        walk_range_statement(self, stmt)
+    }
+    fn visit_branch_statement(&mut self, _stmt: &BranchStatement) {}
+    fn visit_string_literal(&mut self, _lit: &StringLiteral) {}
+    fn visit_index_expression(&mut self, expr: &IndexExpression) {
+        walk_index_expression(self, expr)
+    }
+    fn visit_slice_expression(&mut self, expr: &SliceExpression) {
+        walk_slice_expression(self, expr)
+    }
+    fn visit_selector_expression(&mut self, expr: &SelectorExpression) {
+        walk_selector_expression(self, expr)
+    }
+    fn visit_type_assert_expression(&mut self, expr: &TypeAssertExpression) {
+        walk_type_assert_expression(self, expr)
+    }
+    fn visit_composite_literal(&mut self, lit: &CompositeLiteral) {
+        walk_composite_literal(self, lit)
+    }
+    fn visit_key_value_expression(&mut self, expr: &KeyValueExpression) {
+        walk_key_value_expression(self, expr)
+    }
+    fn visit_slice_type(&mut self, ty: &SliceType) {
+        walk_slice_type(self, ty)
+    }
+    fn visit_map_type(&mut self, ty: &MapType) {
+        walk_map_type(self, ty)
+    }
+    fn visit_struct_type(&mut self, ty: &StructType) {
+        walk_struct_type(self, ty)
+    }
+    fn visit_interface_type(&mut self, _ty: &InterfaceType) {}
+    fn visit_chan_type(&mut self, ty: &ChanType) {
+        walk_chan_type(self, ty)
+    }
+    fn visit_go_statement(&mut self, stmt: &GoStatement) {
+        walk_go_statement(self, stmt)
+    }
+    fn visit_defer_statement(&mut self, stmt: &DeferStatement) {
+        walk_defer_statement(self, stmt)
+    }
+    fn visit_send_statement(&mut self, stmt: &SendStatement) {
+        walk_send_statement(self, stmt)
+    }
+    fn visit_select_statement(&mut self, stmt: &SelectStatement) {
+        walk_select_statement(self, stmt)
+    }
+    fn visit_import_statement(&mut self, stmt: &ImportStatement) {
+        walk_import_statement(self, stmt)
+    }
+    fn visit_package_clause(&mut self, stmt: &PackageClause) {
+        walk_package_clause(self, stmt)
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(v: &mut V, program: &Program) {
+    for stmt in &program.statements {
+        v.visit_statement(stmt.as_ref());
+    }
+}
+
+/// Dispatches to the `visit_*` method for the statement's concrete type.
+pub fn walk_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &dyn Statement) {
+    let any = stmt.as_any();
+    if let Some(s) = any.downcast_ref::<LetStatement>() {
+        v.visit_let_statement(s);
+    } else if let Some(s) = any.downcast_ref::<ReturnStatement>() {
+        v.visit_return_statement(s);
+    } else if let Some(s) = any.downcast_ref::<ExpressionStatement>() {
+        v.visit_expression_statement(s);
+    } else if let Some(s) = any.downcast_ref::<BlockStatement>() {
+        v.visit_block_statement(s);
+    } else if let Some(s) = any.downcast_ref::<VarStatement>() {
+        v.visit_var_statement(s);
+    } else if let Some(s) = any.downcast_ref::<TypeStatement>() {
+        v.visit_type_statement(s);
+    } else if let Some(s) = any.downcast_ref::<AssignStatement>() {
+        v.visit_assign_statement(s);
+    } else if let Some(s) = any.downcast_ref::<ForStatement>() {
+        v.visit_for_statement(s);
+    } else if let Some(s) = any.downcast_ref::<RangeStatement>() {
+        v.visit_range_statement(s);
+    } else if let Some(s) = any.downcast_ref::<BranchStatement>() {
+        v.visit_branch_statement(s);
+    } else if let Some(s) = any.downcast_ref::<GoStatement>() {
+        v.visit_go_statement(s);
+    } else if let Some(s) = any.downcast_ref::<DeferStatement>() {
+        v.visit_defer_statement(s);
+    } else if let Some(s) = any.downcast_ref::<SendStatement>() {
+        v.visit_send_statement(s);
+    } else if let Some(s) = any.downcast_ref::<SelectStatement>() {
+        v.visit_select_statement(s);
+    } else if let Some(s) = any.downcast_ref::<ImportStatement>() {
+        v.visit_import_statement(s);
+    } else if let Some(s) = any.downcast_ref::<PackageClause>() {
+        v.visit_package_clause(s);
+    } else if let Some(s) = any.downcast_ref::<Program>() {
+        v.visit_program(s);
+    }
+}
+
+/// Dispatches to the `visit_*` method for the expression's concrete type.
+pub fn walk_expression<V: Visitor + ?Sized>(v: &mut V, expr: &dyn Expression) {
+    let any = expr.as_any();
+    if let Some(e) = any.downcast_ref::<Identifier>() {
+        v.visit_identifier(e);
+    } else if let Some(e) = any.downcast_ref::<IntegerLiteral>() {
+        v.visit_integer_literal(e);
+    } else if let Some(e) = any.downcast_ref::<FloatLiteral>() {
+        v.visit_float_literal(e);
+    } else if let Some(e) = any.downcast_ref::<Boolean>() {
+        v.visit_boolean(e);
+    } else if let Some(e) = any.downcast_ref::<PrefixExpression>() {
+        v.visit_prefix_expression(e);
+    } else if let Some(e) = any.downcast_ref::<InfixExpression>() {
+        v.visit_infix_expression(e);
+    } else if let Some(e) = any.downcast_ref::<IfExpression>() {
+        v.visit_if_expression(e);
+    } else if let Some(e) = any.downcast_ref::<FunctionLiteral>() {
+        v.visit_function_literal(e);
+    } else if let Some(e) = any.downcast_ref::<CallExpression>() {
+        v.visit_call_expression(e);
+    } else if let Some(e) = any.downcast_ref::<StringLiteral>() {
+        v.visit_string_literal(e);
+    } else if let Some(e) = any.downcast_ref::<IndexExpression>() {
+        v.visit_index_expression(e);
+    } else if let Some(e) = any.downcast_ref::<SliceExpression>() {
+        v.visit_slice_expression(e);
+    } else if let Some(e) = any.downcast_ref::<SelectorExpression>() {
+        v.visit_selector_expression(e);
+    } else if let Some(e) = any.downcast_ref::<TypeAssertExpression>() {
+        v.visit_type_assert_expression(e);
+    } else if let Some(e) = any.downcast_ref::<CompositeLiteral>() {
+        v.visit_composite_literal(e);
+    } else if let Some(e) = any.downcast_ref::<KeyValueExpression>() {
+        v.visit_key_value_expression(e);
+    } else if let Some(e) = any.downcast_ref::<SliceType>() {
+        v.visit_slice_type(e);
+    } else if let Some(e) = any.downcast_ref::<MapType>() {
+        v.visit_map_type(e);
+    } else if let Some(e) = any.downcast_ref::<StructType>() {
+        v.visit_struct_type(e);
+    } else if let Some(e) = any.downcast_ref::<InterfaceType>() {
+        v.visit_interface_type(e);
+    } else if let Some(e) = any.downcast_ref::<ChanType>() {
+        v.visit_chan_type(e);
+    }
+}
+
+pub fn walk_let_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &LetStatement) {
+    if let Some(name) = &stmt.name {
+        v.visit_identifier(name);
+    }
+    if let Some(value) = &stmt.value {
+        v.visit_expression(value.as_ref());
+    }
+}
+
+pub fn walk_return_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &ReturnStatement) {
+    if let Some(value) = &stmt.return_value {
+        v.visit_expression(value.as_ref());
+    }
+}
+
+pub fn walk_expression_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &ExpressionStatement) {
+    if let Some(expression) = &stmt.expression {
+        v.visit_expression(expression.as_ref());
+    }
+}
+
+pub fn walk_block_statement<V: Visitor + ?Sized>(v: &mut V, block: &BlockStatement) {
+    for stmt in &block.statements {
+        v.visit_statement(stmt.as_ref());
+    }
+}
+
+pub fn walk_prefix_expression<V: Visitor + ?Sized>(v: &mut V, expr: &PrefixExpression) {
+    if let Some(right) = &expr.right {
+        v.visit_expression(right.as_ref());
+    }
+}
+
+pub fn walk_infix_expression<V: Visitor + ?Sized>(v: &mut V, expr: &InfixExpression) {
+    if let Some(left) = &expr.left {
+        v.visit_expression(left.as_ref());
+    }
+    if let Some(right) = &expr.right {
+        v.visit_expression(right.as_ref());
+    }
+}
+
+pub fn walk_if_expression<V: Visitor + ?Sized>(v: &mut V, expr: &IfExpression) {
+    if let Some(condition) = &expr.condition {
+        v.visit_expression(condition.as_ref());
+    }
+    if let Some(consequence) = &expr.consequence {
+        v.visit_block_statement(consequence);
+    }
+    if let Some(alternative) = &expr.alternative {
+        v.visit_block_statement(alternative);
+    }
+}
+
+pub fn walk_function_literal<V: Visitor + ?Sized>(v: &mut V, func: &FunctionLiteral) {
+    for param in &func.parameters {
+        v.visit_identifier(param);
+    }
+    for result in &func.results {
+        for name in &result.names {
+            v.visit_identifier(name);
+        }
+        if let Some(ty) = &result.ty {
+            v.visit_expression(ty.as_ref());
+        }
+    }
+    if let Some(body) = &func.body {
+        v.visit_block_statement(body);
+    }
+}
+
+pub fn walk_call_expression<V: Visitor + ?Sized>(v: &mut V, call: &CallExpression) {
+    if let Some(function) = &call.function {
+        v.visit_expression(function.as_ref());
+    }
+    for arg in &call.arguments {
+        v.visit_expression(arg.as_ref());
+    }
+}
+
+pub fn walk_var_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &VarStatement) {
+    for name in &stmt.names {
+        v.visit_identifier(name);
+    }
+    if let Some(ty) = &stmt.ty {
+        v.visit_expression(ty.as_ref());
+    }
+    for e in &stmt.values {
+        v.visit_expression(e.as_ref());
+    }
+}
+
+pub fn walk_type_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &TypeStatement) {
+    if let Some(name) = &stmt.name {
+        v.visit_identifier(name);
+    }
+    if let Some(ty) = &stmt.ty {
+        v.visit_expression(ty.as_ref());
+    }
+}
+
+pub fn walk_assign_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &AssignStatement) {
+    for e in &stmt.left {
+        v.visit_expression(e.as_ref());
+    }
+    for e in &stmt.right {
+        v.visit_expression(e.as_ref());
+    }
+}
+
+pub fn walk_for_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &ForStatement) {
+    if let Some(init) = &stmt.init {
+        v.visit_statement(init.as_ref());
+    }
+    if let Some(condition) = &stmt.condition {
+        v.visit_expression(condition.as_ref());
+    }
+    if let Some(post) = &stmt.post {
+        v.visit_statement(post.as_ref());
+    }
+    if let Some(body) = &stmt.body {
+        v.visit_block_statement(body);
+    }
+}
+
+pub fn walk_range_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &RangeStatement) {
+    if let Some(key) = &stmt.key {
+        v.visit_expression(key.as_ref());
+    }
+    if let Some(value) = &stmt.value {
+        v.visit_expression(value.as_ref());
+    }
+    if let Some(x) = &stmt.x {
+        v.visit_expression(x.as_ref());
+    }
+    if let Some(body) = &stmt.body {
+        v.visit_block_statement(body);
+    }
+}
+
+pub fn walk_index_expression<V: Visitor + ?Sized>(v: &mut V, expr: &IndexExpression) {
+    if let Some(left) = &expr.left {
+        v.visit_expression(left.as_ref());
+    }
+    if let Some(index) = &expr.index {
+        v.visit_expression(index.as_ref());
+    }
+}
+
+pub fn walk_slice_expression<V: Visitor + ?Sized>(v: &mut V, expr: &SliceExpression) {
+    if let Some(left) = &expr.left {
+        v.visit_expression(left.as_ref());
+    }
+    if let Some(low) = &expr.low {
+        v.visit_expression(low.as_ref());
+    }
+    if let Some(high) = &expr.high {
+        v.visit_expression(high.as_ref());
+    }
+}
+
+pub fn walk_selector_expression<V: Visitor + ?Sized>(v: &mut V, expr: &SelectorExpression) {
+    if let Some(left) = &expr.left {
+        v.visit_expression(left.as_ref());
+    }
+    if let Some(selector) = &expr.selector {
+        v.visit_identifier(selector);
+    }
+}
+
+pub fn walk_type_assert_expression<V: Visitor + ?Sized>(v: &mut V, expr: &TypeAssertExpression) {
+    if let Some(left) = &expr.left {
+        v.visit_expression(left.as_ref());
+    }
+    if let Some(ty) = &expr.ty {
+        v.visit_expression(ty.as_ref());
+    }
+}
+
+pub fn walk_composite_literal<V: Visitor + ?Sized>(v: &mut V, lit: &CompositeLiteral) {
+    if let Some(ty) = &lit.ty {
+        v.visit_expression(ty.as_ref());
+    }
+    for e in &lit.elements {
+        v.visit_expression(e.as_ref());
+    }
+}
+
+pub fn walk_key_value_expression<V: Visitor + ?Sized>(v: &mut V, expr: &KeyValueExpression) {
+    if let Some(key) = &expr.key {
+        v.visit_expression(key.as_ref());
+    }
+    if let Some(value) = &expr.value {
+        v.visit_expression(value.as_ref());
+    }
+}
+
+pub fn walk_slice_type<V: Visitor + ?Sized>(v: &mut V, ty: &SliceType) {
+    if let Some(elem) = &ty.elem {
+        v.visit_expression(elem.as_ref());
+    }
+}
+
+pub fn walk_map_type<V: Visitor + ?Sized>(v: &mut V, ty: &MapType) {
+    if let Some(key) = &ty.key {
+        v.visit_expression(key.as_ref());
+    }
+    if let Some(value) = &ty.value {
+        v.visit_expression(value.as_ref());
+    }
+}
+
+pub fn walk_struct_type<V: Visitor + ?Sized>(v: &mut V, ty: &StructType) {
+    for field in &ty.fields {
+        for name in &field.names {
+            v.visit_identifier(name);
+        }
+        if let Some(ty) = &field.ty {
+            v.visit_expression(ty.as_ref());
+        }
+    }
+}
+
+pub fn walk_chan_type<V: Visitor + ?Sized>(v: &mut V, ty: &ChanType) {
+    if let Some(elem) = &ty.elem {
+        v.visit_expression(elem.as_ref());
+    }
+}
+
+pub fn walk_go_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &GoStatement) {
+    if let Some(call) = &stmt.call {
+        v.visit_expression(call.as_ref());
+    }
+}
+
+pub fn walk_defer_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &DeferStatement) {
+    if let Some(call) = &stmt.call {
+        v.visit_expression(call.as_ref());
+    }
+}
+
+pub fn walk_send_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &SendStatement) {
+    if let Some(channel) = &stmt.channel {
+        v.visit_expression(channel.as_ref());
+    }
+    if let Some(value) = &stmt.value {
+        v.visit_expression(value.as_ref());
+    }
+}
+
+pub fn walk_select_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &SelectStatement) {
+    for case in &stmt.cases {
+        if let Some(comm) = &case.comm {
+            v.visit_statement(comm.as_ref());
+        }
+        for s in &case.body {
+            v.visit_statement(s.as_ref());
+        }
+    }
+}
+
+pub fn walk_import_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &ImportStatement) {
+    for spec in &stmt.specs {
+        if let Some(name) = &spec.name {
+            v.visit_identifier(name);
+        }
+        if let Some(path) = &spec.path {
+            v.visit_string_literal(path);
+        }
+    }
+}
+
+pub fn walk_package_clause<V: Visitor + ?Sized>(v: &mut V, stmt: &PackageClause) {
+    if let Some(name) = &stmt.name {
+        v.visit_identifier(name);
+    }
+}
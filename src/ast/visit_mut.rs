@@ -0,0 +1,484 @@
+use crate::ast::*;
+
+/// In-place walk over the AST, for passes that rewrite nodes without
+/// changing their kind.
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program)
+    }
+    fn visit_statement_mut(&mut self, stmt: &mut dyn Statement) {
+        walk_statement_mut(self, stmt)
+    }
+    fn visit_expression_mut(&mut self, expr: &mut dyn Expression) {
+        walk_expression_mut(self, expr)
+    }
+    fn visit_let_statement_mut(&mut self, stmt: &mut LetStatement) {
+        walk_let_statement_mut(self, stmt)
+    }
+    fn visit_return_statement_mut(&mut self, stmt: &mut ReturnStatement) {
+        walk_return_statement_mut(self, stmt)
+    }
+    fn visit_expression_statement_mut(&mut self, stmt: &mut ExpressionStatement) {
+        walk_expression_statement_mut(self, stmt)
+    }
+    fn visit_block_statement_mut(&mut self, block: &mut BlockStatement) {
+        walk_block_statement_mut(self, block)
+    }
+    fn visit_identifier_mut(&mut self, _ident: &mut Identifier) {}
+    fn visit_integer_literal_mut(&mut self, _lit: &mut IntegerLiteral) {}
+    fn visit_float_literal_mut(&mut self, _lit: &mut FloatLiteral) {}
+    fn visit_boolean_mut(&mut self, _lit: &mut Boolean) {}
+    fn visit_prefix_expression_mut(&mut self, expr: &mut PrefixExpression) {
+        walk_prefix_expression_mut(self, expr)
+    }
+    fn visit_infix_expression_mut(&mut self, expr: &mut InfixExpression) {
+        walk_infix_expression_mut(self, expr)
+    }
+    fn visit_if_expression_mut(&mut self, expr: &mut IfExpression) {
+        walk_if_expression_mut(self, expr)
+    }
+    fn visit_function_literal_mut(&mut self, func: &mut FunctionLiteral) {
+        walk_function_literal_mut(self, func)
+    }
+    fn visit_call_expression_mut(&mut self, call: &mut CallExpression) {
+        walk_call_expression_mut(self, call)
+    }
+    fn visit_var_statement_mut(&mut self, stmt: &mut VarStatement) {
+        walk_var_statement_mut(self, stmt)
+    }
+    fn visit_type_statement_mut(&mut self, stmt: &mut TypeStatement) {
+        walk_type_statement_mut(self, stmt)
+    }
+    fn visit_assign_statement_mut(&mut self, stmt: &mut AssignStatement) {
+        walk_assign_statement_mut(self, stmt)
+    }
+    fn visit_for_statement_mut(&mut self, stmt: &mut ForStatement) {
+        walk_for_statement_mut(self, stmt)
+    }
+    fn visit_range_statement_mut(&mut self, stmt: &mut RangeStatement) {
+        walk_range_statement_mut(self, stmt)
+    }
+    fn visit_branch_statement_mut(&mut self, _stmt: &mut BranchStatement) {}
+    fn visit_string_literal_mut(&mut self, _lit: &mut StringLiteral) {}
+    fn visit_index_expression_mut(&mut self, expr: &mut IndexExpression) {
+        walk_index_expression_mut(self, expr)
+    }
+    fn visit_slice_expression_mut(&mut self, expr: &mut SliceExpression) {
+        walk_slice_expression_mut(self, expr)
+    }
+    fn visit_selector_expression_mut(&mut self, expr: &mut SelectorExpression) {
+        walk_selector_expression_mut(self, expr)
+    }
+    fn visit_type_assert_expression_mut(&mut self, expr: &mut TypeAssertExpression) {
+        walk_type_assert_expression_mut(self, expr)
+    }
+    fn visit_composite_literal_mut(&mut self, lit: &mut CompositeLiteral) {
+        walk_composite_literal_mut(self, lit)
+    }
+    fn visit_key_value_expression_mut(&mut self, expr: &mut KeyValueExpression) {
+        walk_key_value_expression_mut(self, expr)
+    }
+    fn visit_slice_type_mut(&mut self, ty: &mut SliceType) {
+        walk_slice_type_mut(self, ty)
+    }
+    fn visit_map_type_mut(&mut self, ty: &mut MapType) {
+        walk_map_type_mut(self, ty)
+    }
+    fn visit_struct_type_mut(&mut self, ty: &mut StructType) {
+        walk_struct_type_mut(self, ty)
+    }
+    fn visit_interface_type_mut(&mut self, _ty: &mut InterfaceType) {}
+    fn visit_chan_type_mut(&mut self, ty: &mut ChanType) {
+        walk_chan_type_mut(self, ty)
+    }
+    fn visit_go_statement_mut(&mut self, stmt: &mut GoStatement) {
+        walk_go_statement_mut(self, stmt)
+    }
+    fn visit_defer_statement_mut(&mut self, stmt: &mut DeferStatement) {
+        walk_defer_statement_mut(self, stmt)
+    }
+    fn visit_send_statement_mut(&mut self, stmt: &mut SendStatement) {
+        walk_send_statement_mut(self, stmt)
+    }
+    fn visit_select_statement_mut(&mut self, stmt: &mut SelectStatement) {
+        walk_select_statement_mut(self, stmt)
+    }
+    fn visit_import_statement_mut(&mut self, stmt: &mut ImportStatement) {
+        walk_import_statement_mut(self, stmt)
+    }
+    fn visit_package_clause_mut(&mut self, stmt: &mut PackageClause) {
+        walk_package_clause_mut(self, stmt)
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(v: &mut V, program: &mut Program) {
+    for stmt in &mut program.statements {
+        v.visit_statement_mut(stmt.as_mut());
+    }
+}
+
+/// Dispatches to the `visit_*_mut` method for the statement's concrete type.
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut dyn Statement) {
+    let any = stmt.as_any_mut();
+    if let Some(s) = any.downcast_mut::<LetStatement>() {
+        v.visit_let_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<ReturnStatement>() {
+        v.visit_return_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<ExpressionStatement>() {
+        v.visit_expression_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<BlockStatement>() {
+        v.visit_block_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<VarStatement>() {
+        v.visit_var_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<TypeStatement>() {
+        v.visit_type_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<AssignStatement>() {
+        v.visit_assign_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<ForStatement>() {
+        v.visit_for_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<RangeStatement>() {
+        v.visit_range_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<BranchStatement>() {
+        v.visit_branch_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<GoStatement>() {
+        v.visit_go_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<DeferStatement>() {
+        v.visit_defer_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<SendStatement>() {
+        v.visit_send_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<SelectStatement>() {
+        v.visit_select_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<ImportStatement>() {
+        v.visit_import_statement_mut(s);
+    } else if let Some(s) = any.downcast_mut::<PackageClause>() {
+        v.visit_package_clause_mut(s);
+    } else if let Some(s) = any.downcast_mut::<Program>() {
+        v.visit_program_mut(s);
+    }
+}
+
+/// Dispatches to the `visit_*_mut` method for the expression's concrete type.
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut dyn Expression) {
+    let any = expr.as_any_mut();
+    if let Some(e) = any.downcast_mut::<Identifier>() {
+        v.visit_identifier_mut(e);
+    } else if let Some(e) = any.downcast_mut::<IntegerLiteral>() {
+        v.visit_integer_literal_mut(e);
+    } else if let Some(e) = any.downcast_mut::<FloatLiteral>() {
+        v.visit_float_literal_mut(e);
+    } else if let Some(e) = any.downcast_mut::<Boolean>() {
+        v.visit_boolean_mut(e);
+    } else if let Some(e) = any.downcast_mut::<PrefixExpression>() {
+        v.visit_prefix_expression_mut(e);
+    } else if let Some(e) = any.downcast_mut::<InfixExpression>() {
+        v.visit_infix_expression_mut(e);
+    } else if let Some(e) = any.downcast_mut::<IfExpression>() {
+        v.visit_if_expression_mut(e);
+    } else if let Some(e) = any.downcast_mut::<FunctionLiteral>() {
+        v.visit_function_literal_mut(e);
+    } else if let Some(e) = any.downcast_mut::<CallExpression>() {
+        v.visit_call_expression_mut(e);
+    } else if let Some(e) = any.downcast_mut::<StringLiteral>() {
+        v.visit_string_literal_mut(e);
+    } else if let Some(e) = any.downcast_mut::<IndexExpression>() {
+        v.visit_index_expression_mut(e);
+    } else if let Some(e) = any.downcast_mut::<SliceExpression>() {
+        v.visit_slice_expression_mut(e);
+    } else if let Some(e) = any.downcast_mut::<SelectorExpression>() {
+        v.visit_selector_expression_mut(e);
+    } else if let Some(e) = any.downcast_mut::<TypeAssertExpression>() {
+        v.visit_type_assert_expression_mut(e);
+    } else if let Some(e) = any.downcast_mut::<CompositeLiteral>() {
+        v.visit_composite_literal_mut(e);
+    } else if let Some(e) = any.downcast_mut::<KeyValueExpression>() {
+        v.visit_key_value_expression_mut(e);
+    } else if let Some(e) = any.downcast_mut::<SliceType>() {
+        v.visit_slice_type_mut(e);
+    } else if let Some(e) = any.downcast_mut::<MapType>() {
+        v.visit_map_type_mut(e);
+    } else if let Some(e) = any.downcast_mut::<StructType>() {
+        v.visit_struct_type_mut(e);
+    } else if let Some(e) = any.downcast_mut::<InterfaceType>() {
+        v.visit_interface_type_mut(e);
+    } else if let Some(e) = any.downcast_mut::<ChanType>() {
+        v.visit_chan_type_mut(e);
+    }
+}
+
+pub fn walk_let_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut LetStatement) {
+    if let Some(name) = &mut stmt.name {
+        v.visit_identifier_mut(name);
+    }
+    if let Some(value) = &mut stmt.value {
+        v.visit_expression_mut(value.as_mut());
+    }
+}
+
+pub fn walk_return_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut ReturnStatement) {
+    if let Some(value) = &mut stmt.return_value {
+        v.visit_expression_mut(value.as_mut());
+    }
+}
+
+pub fn walk_expression_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut ExpressionStatement) {
+    if let Some(expression) = &mut stmt.expression {
+        v.visit_expression_mut(expression.as_mut());
+    }
+}
+
+pub fn walk_block_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, block: &mut BlockStatement) {
+    for stmt in &mut block.statements {
+        v.visit_statement_mut(stmt.as_mut());
+    }
+}
+
+pub fn walk_prefix_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut PrefixExpression) {
+    if let Some(right) = &mut expr.right {
+        v.visit_expression_mut(right.as_mut());
+    }
+}
+
+pub fn walk_infix_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut InfixExpression) {
+    if let Some(left) = &mut expr.left {
+        v.visit_expression_mut(left.as_mut());
+    }
+    if let Some(right) = &mut expr.right {
+        v.visit_expression_mut(right.as_mut());
+    }
+}
+
+pub fn walk_if_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut IfExpression) {
+    if let Some(condition) = &mut expr.condition {
+        v.visit_expression_mut(condition.as_mut());
+    }
+    if let Some(consequence) = &mut expr.consequence {
+        v.visit_block_statement_mut(consequence);
+    }
+    if let Some(alternative) = &mut expr.alternative {
+        v.visit_block_statement_mut(alternative);
+    }
+}
+
+pub fn walk_function_literal_mut<V: VisitorMut + ?Sized>(v: &mut V, func: &mut FunctionLiteral) {
+    for param in &mut func.parameters {
+        v.visit_identifier_mut(param);
+    }
+    for result in &mut func.results {
+        for name in &mut result.names {
+            v.visit_identifier_mut(name);
+        }
+        if let Some(ty) = &mut result.ty {
+            v.visit_expression_mut(ty.as_mut());
+        }
+    }
+    if let Some(body) = &mut func.body {
+        v.visit_block_statement_mut(body);
+    }
+}
+
+pub fn walk_call_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, call: &mut CallExpression) {
+    if let Some(function) = &mut call.function {
+        v.visit_expression_mut(function.as_mut());
+    }
+    for arg in &mut call.arguments {
+        v.visit_expression_mut(arg.as_mut());
+    }
+}
+
+pub fn walk_var_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut VarStatement) {
+    for name in &mut stmt.names {
+        v.visit_identifier_mut(name);
+    }
+    if let Some(ty) = &mut stmt.ty {
+        v.visit_expression_mut(ty.as_mut());
+    }
+    for e in &mut stmt.values {
+        v.visit_expression_mut(e.as_mut());
+    }
+}
+
+pub fn walk_type_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut TypeStatement) {
+    if let Some(name) = &mut stmt.name {
+        v.visit_identifier_mut(name);
+    }
+    if let Some(ty) = &mut stmt.ty {
+        v.visit_expression_mut(ty.as_mut());
+    }
+}
+
+pub fn walk_assign_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut AssignStatement) {
+    for e in &mut stmt.left {
+        v.visit_expression_mut(e.as_mut());
+    }
+    for e in &mut stmt.right {
+        v.visit_expression_mut(e.as_mut());
+    }
+}
+
+pub fn walk_for_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut ForStatement) {
+    if let Some(init) = &mut stmt.init {
+        v.visit_statement_mut(init.as_mut());
+    }
+    if let Some(condition) = &mut stmt.condition {
+        v.visit_expression_mut(condition.as_mut());
+    }
+    if let Some(post) = &mut stmt.post {
+        v.visit_statement_mut(post.as_mut());
+    }
+    if let Some(body) = &mut stmt.body {
+        v.visit_block_statement_mut(body);
+    }
+}
+
+pub fn walk_range_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut RangeStatement) {
+    if let Some(key) = &mut stmt.key {
+        v.visit_expression_mut(key.as_mut());
+    }
+    if let Some(value) = &mut stmt.value {
+        v.visit_expression_mut(value.as_mut());
+    }
+    if let Some(x) = &mut stmt.x {
+        v.visit_expression_mut(x.as_mut());
+    }
+    if let Some(body) = &mut stmt.body {
+        v.visit_block_statement_mut(body);
+    }
+}
+
+pub fn walk_index_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut IndexExpression) {
+    if let Some(left) = &mut expr.left {
+        v.visit_expression_mut(left.as_mut());
+    }
+    if let Some(index) = &mut expr.index {
+        v.visit_expression_mut(index.as_mut());
+    }
+}
+
+pub fn walk_slice_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut SliceExpression) {
+    if let Some(left) = &mut expr.left {
+        v.visit_expression_mut(left.as_mut());
+    }
+    if let Some(low) = &mut expr.low {
+        v.visit_expression_mut(low.as_mut());
+    }
+    if let Some(high) = &mut expr.high {
+        v.visit_expression_mut(high.as_mut());
+    }
+}
+
+pub fn walk_selector_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut SelectorExpression) {
+    if let Some(left) = &mut expr.left {
+        v.visit_expression_mut(left.as_mut());
+    }
+    if let Some(selector) = &mut expr.selector {
+        v.visit_identifier_mut(selector);
+    }
+}
+
+pub fn walk_type_assert_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut TypeAssertExpression) {
+    if let Some(left) = &mut expr.left {
+        v.visit_expression_mut(left.as_mut());
+    }
+    if let Some(ty) = &mut expr.ty {
+        v.visit_expression_mut(ty.as_mut());
+    }
+}
+
+pub fn walk_composite_literal_mut<V: VisitorMut + ?Sized>(v: &mut V, lit: &mut CompositeLiteral) {
+    if let Some(ty) = &mut lit.ty {
+        v.visit_expression_mut(ty.as_mut());
+    }
+    for e in &mut lit.elements {
+        v.visit_expression_mut(e.as_mut());
+    }
+}
+
+pub fn walk_key_value_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut KeyValueExpression) {
+    if let Some(key) = &mut expr.key {
+        v.visit_expression_mut(key.as_mut());
+    }
+    if let Some(value) = &mut expr.value {
+        v.visit_expression_mut(value.as_mut());
+    }
+}
+
+pub fn walk_slice_type_mut<V: VisitorMut + ?Sized>(v: &mut V, ty: &mut SliceType) {
+    if let Some(elem) = &mut ty.elem {
+        v.visit_expression_mut(elem.as_mut());
+    }
+}
+
+pub fn walk_map_type_mut<V: VisitorMut + ?Sized>(v: &mut V, ty: &mut MapType) {
+    if let Some(key) = &mut ty.key {
+        v.visit_expression_mut(key.as_mut());
+    }
+    if let Some(value) = &mut ty.value {
+        v.visit_expression_mut(value.as_mut());
+    }
+}
+
+pub fn walk_struct_type_mut<V: VisitorMut + ?Sized>(v: &mut V, ty: &mut StructType) {
+    for field in &mut ty.fields {
+        for name in &mut field.names {
+            v.visit_identifier_mut(name);
+        }
+        if let Some(ty) = &mut field.ty {
+            v.visit_expression_mut(ty.as_mut());
+        }
+    }
+}
+
+pub fn walk_chan_type_mut<V: VisitorMut + ?Sized>(v: &mut V, ty: &mut ChanType) {
+    if let Some(elem) = &mut ty.elem {
+        v.visit_expression_mut(elem.as_mut());
+    }
+}
+
+pub fn walk_go_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut GoStatement) {
+    if let Some(call) = &mut stmt.call {
+        v.visit_expression_mut(call.as_mut());
+    }
+}
+
+pub fn walk_defer_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut DeferStatement) {
+    if let Some(call) = &mut stmt.call {
+        v.visit_expression_mut(call.as_mut());
+    }
+}
+
+pub fn walk_send_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut SendStatement) {
+    if let Some(channel) = &mut stmt.channel {
+        v.visit_expression_mut(channel.as_mut());
+    }
+    if let Some(value) = &mut stmt.value {
+        v.visit_expression_mut(value.as_mut());
+    }
+}
+
+pub fn walk_select_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut SelectStatement) {
+    for case in &mut stmt.cases {
+        if let Some(comm) = &mut case.comm {
+            v.visit_statement_mut(comm.as_mut());
+        }
+        for s in &mut case.body {
+            v.visit_statement_mut(s.as_mut());
+        }
+    }
+}
+
+pub fn walk_import_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut ImportStatement) {
+    for spec in &mut stmt.specs {
+        if let Some(name) = &mut spec.name {
+            v.visit_identifier_mut(name);
+        }
+        if let Some(path) = &mut spec.path {
+            v.visit_string_literal_mut(path);
+        }
+    }
+}
+
+pub fn walk_package_clause_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut PackageClause) {
+    if let Some(name) = &mut stmt.name {
+        v.visit_identifier_mut(name);
+    }
+}
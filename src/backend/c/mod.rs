@@ -0,0 +1,652 @@
+//! The C backend: a module as portable C99, for `namu build -o out.c`.
+//!
+//! The output is the runtime in `runtime.c`, which implements the VM's
+//! values, operators, builtins and standard library in C, followed by
+//! the program: a type descriptor for each type the IR names, a constant
+//! for each string literal, and a C function for each IR function. Every
+//! IR value is an `nm_value` local, phis become copies on the edges into
+//! their blocks, and a function that defers calls keeps a frame whose
+//! `setjmp` a panic unwinds to. Goroutines and channels are not
+//! supported; programs that use them are rejected with an error.
+//!
+//! Build the output with a C99 compiler and the math and thread
+//! libraries, as in `cc -std=c99 -O2 out.c -lm -lpthread`.
+
+use crate::ir::{BinOp, BlockId, Const, FuncId, Function, Inst, Module, Ty, UnOp, ValueId};
+use crate::types::Type;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(test)]
+mod test;
+
+/// The runtime every generated program starts with.
+pub const RUNTIME: &str = include_str!("runtime.c");
+
+/// The builtins the runtime implements as `nm_b_<name>`; `make` and `new`
+/// are lowered to instructions of their own.
+pub(crate) const BUILTINS: &[&str] = &[
+    "len", "cap", "append", "copy", "delete", "min", "max", "clear", "print", "println", "panic", "recover", "close",
+];
+
+/// The standard library types the runtime describes itself, so that its
+/// values and the program's agree on them.
+const RUNTIME_STRUCTS: &[(&str, &str)] = &[
+    ("errors.errorString", "errorString"),
+    ("fmt.wrapError", "wrapError"),
+    ("strconv.NumError", "NumError"),
+    ("fs.PathError", "PathError"),
+    ("os.File", "File"),
+];
+
+/// Translates the module to C, or returns what it uses that the backend
+/// does not support.
+pub fn generate(module: &Module) -> Result<String, Vec<String>> {
+    let mut gen = Generator::default();
+    let mut functions = vec![];
+    for i in 0..module.functions.len() {
+        if let Some(code) = gen.function(module, FuncId(i)) {
+            functions.push(code);
+        }
+    }
+    if !gen.errors.is_empty() {
+        return Err(gen.errors);
+    }
+    let mut globals = vec![];
+    for global in &module.globals {
+        globals.push(gen.type_ref(&global.ty));
+    }
+
+    let mut out = String::from(RUNTIME);
+    out.push('\n');
+    for i in 0..gen.types.len() {
+        out.push_str(&format!("static const nm_type nm_T{};\n", i));
+    }
+    out.push_str(&gen.type_defs);
+    out.push_str(&gen.string_defs);
+    out.push_str(&format!("\nstatic nm_value nm_G[{}];\n\n", module.globals.len().max(1)));
+    for i in 0..module.functions.len() {
+        out.push_str(&format!("static nm_value nm_f{}(nm_func *self, int64_t argc, nm_value *argv);\n", i));
+    }
+    for code in functions {
+        out.push('\n');
+        out.push_str(&code);
+    }
+
+    out.push_str("\nstatic void nm_run(void) {\n");
+    for (i, ty) in globals.iter().enumerate() {
+        out.push_str(&format!("    nm_G[{}] = nm_zero({});\n", i, ty));
+    }
+    for init in &module.inits {
+        out.push_str(&format!("    nm_f{}(NULL, 0, NULL);\n", init.0));
+    }
+    if let Some(main) = module.main {
+        // the VM calls main from a frame of its own, which traces omit
+        out.push_str(&format!(
+            "    if (nm_raw(nm_G[{0}]).type->kind == NM_FUNC && nm_raw(nm_G[{0}]).u.p) {{\n        nm_enter(NULL, 0);\n        nm_call(nm_G[{0}], 0, NULL);\n    }}\n",
+            main
+        ));
+    }
+    out.push_str("}\n\nint main(int argc, char **argv) {\n    return nm_main(argc, argv, nm_run);\n}\n");
+    Ok(out)
+}
+
+/// Builds a native executable at `output` from generated C, with the C
+/// compiler `cc` names.
+pub fn compile(code: &str, output: &Path) -> Result<(), String> {
+    link(&[("main.c", code.as_bytes())], output)
+}
+
+/// The C compiler in `$CC`, `cc` by default.
+pub fn cc() -> String {
+    env::var("CC").ok().filter(|cc| !cc.is_empty()).unwrap_or_else(|| "cc".to_string())
+}
+
+/// Writes the named C sources and objects to a directory of their own
+/// and compiles and links them, with the runtime's libraries, into an
+/// executable at `output`.
+pub(crate) fn link(files: &[(&str, &[u8])], output: &Path) -> Result<(), String> {
+    static BUILDS: AtomicUsize = AtomicUsize::new(0);
+    let dir = env::temp_dir().join(format!("namu-build-{}-{}", std::process::id(), BUILDS.fetch_add(1, Ordering::Relaxed)));
+    let paths: Vec<PathBuf> = files.iter().map(|(name, _)| dir.join(name)).collect();
+    fs::create_dir_all(&dir)
+        .and_then(|_| files.iter().zip(&paths).try_for_each(|((_, bytes), path)| fs::write(path, bytes)))
+        .map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let cc = cc();
+    let result = Command::new(&cc)
+        .args(["-std=c99", "-O2", "-o"])
+        .arg(output)
+        .args(&paths)
+        .args(["-lm", "-lpthread"])
+        .output();
+    let _ = fs::remove_dir_all(&dir);
+    match result {
+        Err(e) => Err(format!("{}: {}", cc, e)),
+        Ok(out) if !out.status.success() => Err(format!("{} failed:\n{}", cc, String::from_utf8_lossy(&out.stderr).trim_end())),
+        Ok(_) => Ok(()),
+    }
+}
+
+#[derive(Default)]
+struct Generator {
+    /// Types with descriptors of the program's own, `nm_T<index>`.
+    types: Vec<Type>,
+    type_defs: String,
+    /// String literals, `nm_S<index>`.
+    strings: HashMap<Rc<[u8]>, usize>,
+    string_defs: String,
+    errors: Vec<String>,
+}
+
+/// A C string literal of the bytes: printable ASCII as it is, the rest in
+/// octal escapes, which take exactly three digits.
+fn c_string(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' | b'?' => out.push_str(&format!("\\{:03o}", b)),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn v(id: ValueId) -> String {
+    format!("v{}", id.0)
+}
+
+fn list(values: &[ValueId]) -> String {
+    values.iter().map(|&id| v(id)).collect::<Vec<_>>().join(", ")
+}
+
+/// Wraps a statement that takes an argument array `a` of the values,
+/// NULL when there are none.
+fn with_args(values: &[ValueId], stmt: impl FnOnce(&str) -> String) -> String {
+    if values.is_empty() {
+        return stmt("NULL");
+    }
+    format!("{{\n        nm_value a[] = {{{}}};\n        {}\n    }}", list(values), stmt("a"))
+}
+
+impl Generator {
+    /// A pointer to the descriptor of the type, defining one the first
+    /// time a type is seen.
+    fn type_ref(&mut self, ty: &Type) -> String {
+        if let Some(name) = runtime_type(ty) {
+            return format!("&nm_t_{}", name);
+        }
+        if let Some(i) = self.types.iter().position(|t| t.identical(ty)) {
+            return format!("&nm_T{}", i);
+        }
+        let i = self.types.len();
+        self.types.push(ty.clone());
+        let name = c_string(ty.to_string().as_bytes());
+        let def = match ty {
+            Type::Slice(elem) | Type::Pointer(elem) | Type::Chan(elem) => {
+                let kind = match ty {
+                    Type::Slice(_) => "NM_SLICE",
+                    Type::Pointer(_) => "NM_POINTER",
+                    _ => "NM_CHAN",
+                };
+                let elem = self.type_ref(elem);
+                format!("static const nm_type nm_T{} = {{{}, {}, {}, 0, 0, 0, 0, 0}};\n", i, kind, name, elem)
+            }
+            Type::Map(key, elem) => {
+                let key = self.type_ref(key);
+                let elem = self.type_ref(elem);
+                format!("static const nm_type nm_T{} = {{NM_MAP, {}, {}, {}, 0, 0, 0, 0}};\n", i, name, elem, key)
+            }
+            Type::Struct(st) => {
+                let fields = st.fields.borrow().clone();
+                let named = st.name.is_some() as u8;
+                if fields.is_empty() {
+                    format!("static const nm_type nm_T{} = {{NM_STRUCT, {}, 0, 0, {}, 0, 0, 0}};\n", i, name, named)
+                } else {
+                    let names: Vec<String> = fields.iter().map(|f| c_string(f.name.as_bytes())).collect();
+                    let types: Vec<String> = fields.iter().map(|f| self.type_ref(&f.ty)).collect();
+                    format!(
+                        "static const char *const nm_T{0}_f[] = {{{1}}};\nstatic const nm_type *const nm_T{0}_t[] = {{{2}}};\nstatic const nm_type nm_T{0} = {{NM_STRUCT, {3}, 0, 0, {4}, {5}, nm_T{0}_f, nm_T{0}_t}};\n",
+                        i,
+                        names.join(", "),
+                        types.join(", "),
+                        name,
+                        named,
+                        fields.len()
+                    )
+                }
+            }
+            Type::Interface(it) => {
+                format!("static const nm_type nm_T{} = {{NM_INTERFACE, {}, 0, 0, {}, 0, 0, 0}};\n", i, name, it.name.is_some() as u8)
+            }
+            // the runtime describes the rest
+            _ => unreachable!("type {} has a runtime descriptor", ty),
+        };
+        self.type_defs.push_str(&def);
+        format!("&nm_T{}", i)
+    }
+
+    /// A pointer to the constant for a string literal.
+    fn string_ref(&mut self, s: &Rc<[u8]>) -> String {
+        if let Some(i) = self.strings.get(s) {
+            return format!("&nm_S{}", i);
+        }
+        let i = self.strings.len();
+        self.strings.insert(Rc::clone(s), i);
+        self.string_defs.push_str(&format!(
+            "static const nm_string nm_S{} = {{{}, (const uint8_t *){}}};\n",
+            i,
+            s.len(),
+            c_string(s)
+        ));
+        format!("&nm_S{}", i)
+    }
+
+    fn unsupported(&mut self, func: &Function, what: &str) -> Option<String> {
+        let msg = format!("{}: {} not supported by the C backend", func.name, what);
+        if !self.errors.contains(&msg) {
+            self.errors.push(msg);
+        }
+        None
+    }
+
+    /// The C function for an IR function, or None after recording why it
+    /// cannot have one.
+    fn function(&mut self, module: &Module, id: FuncId) -> Option<String> {
+        let func = module.function(id);
+        let framed = func.recover.is_some();
+        let volatile = volatile_values(func);
+        let mut out = format!("static nm_value nm_f{}(nm_func *self, int64_t argc, nm_value *argv) {{\n", id.0);
+        for block in &func.blocks {
+            for &value in &block.insts {
+                let decl = match &func.values[value.0].ty {
+                    Ty::Void => continue,
+                    Ty::Value(_) if volatile.contains(&value) => format!("volatile nm_value {}", v(value)),
+                    Ty::Value(_) => format!("nm_value {}", v(value)),
+                    Ty::Tuple(types) => format!("nm_value {}[{}]", v(value), types.len()),
+                    Ty::Iter => format!("nm_iter {}", v(value)),
+                };
+                out.push_str(&format!("    {};\n", decl));
+            }
+        }
+        if framed {
+            out.push_str("    nm_frame fr;\n");
+        }
+        out.push_str("    (void)self;\n    (void)argc;\n    (void)argv;\n");
+        out.push_str(&format!(
+            "    nm_enter({}, {});\n",
+            c_string(func.name.as_bytes()),
+            !func.params.is_empty() as u8
+        ));
+        if let Some(recover) = func.recover {
+            out.push_str(&format!(
+                "    nm_frame_push(&fr);\n    if (setjmp(fr.jb)) {{\n        nm_unwind(&fr);\n        goto b{};\n    }}\n",
+                recover.0
+            ));
+        }
+        for (i, block) in func.blocks.iter().enumerate() {
+            out.push_str(&format!("b{}:;\n", i));
+            for &value in &block.insts {
+                let code = self.inst(module, func, BlockId(i), value)?;
+                if !code.is_empty() {
+                    out.push_str(&format!("    {}\n", code));
+                }
+            }
+        }
+        out.push_str("}\n");
+        Some(out)
+    }
+
+    /// The C for one instruction.
+    fn inst(&mut self, module: &Module, func: &Function, block: BlockId, id: ValueId) -> Option<String> {
+        let data = &func.values[id.0];
+        let dest = v(id);
+        let ty = data.ty.value().cloned();
+        let ty_ref = |gen: &mut Generator| gen.type_ref(ty.as_ref().unwrap_or(&Type::Nil));
+        let static_ty = |value: ValueId| func.ty(value).value().cloned();
+        let code = match &data.inst {
+            Inst::Const(c) => {
+                let value = match c {
+                    Const::Nil => format!("nm_zero({})", ty_ref(self)),
+                    Const::Bool(b) => format!("nm_bool({})", *b as u8),
+                    Const::Int(n) if matches!(ty, Some(Type::Float64)) => float(*n as f64),
+                    Const::Int(n) if *n == i64::MIN => "nm_int(INT64_MIN)".to_string(),
+                    Const::Int(n) => format!("nm_int(INT64_C({}))", n),
+                    Const::Float(x) => float(*x),
+                    Const::String(s) => format!("nm_strconst({})", self.string_ref(s)),
+                };
+                match &ty {
+                    Some(t @ Type::Interface(_)) if *c != Const::Nil => {
+                        format!("{} = nm_convert({}, {});", dest, value, self.type_ref(t))
+                    }
+                    _ => format!("{} = {};", dest, value),
+                }
+            }
+            Inst::Zero => format!("{} = nm_zero({});", dest, ty_ref(self)),
+            Inst::Param(i) => format!("{} = argv[{}];", dest, i),
+            Inst::FreeVar(i) => format!("{} = nm_ptr({}, self->free[{}]);", dest, ty_ref(self), i),
+            Inst::Global(i) => format!("{} = nm_ptr({}, &nm_G[{}]);", dest, ty_ref(self), i),
+            Inst::Current => format!("{} = nm_func_value(self);", dest),
+            Inst::Closure(target, captured) => {
+                let f = module.function(*target);
+                let params: Vec<&str> = f.params.iter().map(|p| p.name.as_str()).collect();
+                let sig = c_string(format!("fn({})", params.join(", ")).as_bytes());
+                let name = c_string(f.name.as_bytes());
+                with_args(captured, |a| {
+                    format!(
+                        "{} = nm_closure(nm_f{}, {}, {}, {}, {}, {});",
+                        dest,
+                        target.0,
+                        name,
+                        sig,
+                        f.params.len(),
+                        captured.len(),
+                        a
+                    )
+                })
+            }
+            Inst::Builtin(name) => {
+                if !BUILTINS.contains(name) {
+                    return self.unsupported(func, &format!("builtin {} as a value is", name));
+                }
+                format!("{} = nm_func_value(&nm_B_{});", dest, name)
+            }
+            Inst::Package(path, name) => {
+                if matches!(ty, Some(Type::Func)) {
+                    if !package_function(path, name) {
+                        return self.unsupported(func, &format!("{}.{} is", path, name));
+                    }
+                    format!("{} = nm_func_value(&nm_P_{}_{});", dest, path, name)
+                } else {
+                    if !package_value(path, name) {
+                        return self.unsupported(func, &format!("{}.{} is", path, name));
+                    }
+                    format!("{} = nm_v_{}_{}();", dest, path, name)
+                }
+            }
+            Inst::Phi(_) => String::new(),
+            Inst::Unary(op, x) => {
+                let f = match op {
+                    UnOp::Neg => "nm_neg",
+                    UnOp::Not => "nm_not",
+                };
+                self.boxed(&dest, &ty, format!("{}({})", f, v(*x)))
+            }
+            Inst::Binary(op, a, b) => {
+                let f = match op {
+                    BinOp::Add => "nm_add",
+                    BinOp::Sub => "nm_sub",
+                    BinOp::Mul => "nm_mul",
+                    BinOp::Div => "nm_div",
+                    BinOp::Rem => "nm_rem",
+                    BinOp::Eq => "nm_eq",
+                    BinOp::Ne => "nm_ne",
+                    BinOp::Lt => "nm_lt",
+                    BinOp::Le => "nm_le",
+                    BinOp::Gt => "nm_gt",
+                    BinOp::Ge => "nm_ge",
+                };
+                self.boxed(&dest, &ty, format!("{}({}, {})", f, v(*a), v(*b)))
+            }
+            Inst::Convert(x) => match (&ty, static_ty(*x)) {
+                (Some(to), Some(from)) if !to.is_interface() && to.identical(&from) => format!("{} = {};", dest, v(*x)),
+                _ => format!("{} = nm_conversion({}, {});", dest, v(*x), ty_ref(self)),
+            },
+            Inst::Assert(x, false) => format!("{} = nm_assert({}, {});", dest, v(*x), ty_ref(self)),
+            Inst::Assert(x, true) => {
+                let target = tuple_first(&data.ty);
+                format!("nm_assert_ok({}, {}, {});", v(*x), self.type_ref(&target), dest)
+            }
+            Inst::Alloc => format!("{} = nm_alloc_ptr({});", dest, ty_ref(self)),
+            Inst::Load(p) => format!("{} = nm_load({});", dest, v(*p)),
+            Inst::Store(p, x) => format!("nm_store({}, {});", v(*p), v(*x)),
+            Inst::Field(x, name) => {
+                format!("{} = nm_field({}, {}, {});", dest, v(*x), c_string(name.as_bytes()), field_hint(static_ty(*x), name))
+            }
+            Inst::FieldAddr(x, name) => format!(
+                "{} = nm_field_addr({}, {}, {}, {});",
+                dest,
+                v(*x),
+                c_string(name.as_bytes()),
+                field_hint(static_ty(*x), name),
+                ty_ref(self)
+            ),
+            Inst::Index(x, i, false) => format!("{} = nm_index({}, {});", dest, v(*x), v(*i)),
+            Inst::Index(x, i, true) => format!("nm_index_ok({}, {}, {});", v(*x), v(*i), dest),
+            Inst::IndexAddr(x, i) => format!("{} = nm_index_addr({}, {}, {});", dest, v(*x), v(*i), ty_ref(self)),
+            Inst::SetIndex(x, i, value) => format!("nm_set_index({}, {}, {});", v(*x), v(*i), v(*value)),
+            Inst::Slice(x, low, high) => {
+                let bound = |b: &Option<ValueId>| b.map_or("nm_nil".to_string(), v);
+                format!("{} = nm_slice_expr({}, {}, {});", dest, v(*x), bound(low), bound(high))
+            }
+            Inst::BoundsCheck(i, n) => format!("nm_check({}, {});", v(*i), v(*n)),
+            Inst::Composite(pairs) => {
+                let t = ty_ref(self);
+                let flat: Vec<ValueId> = pairs.iter().flat_map(|&(k, x)| [k, x]).collect();
+                with_args(&flat, |a| format!("{} = nm_composite({}, {}, {});", dest, t, pairs.len(), a))
+            }
+            Inst::Make(sizes) => {
+                if matches!(ty, Some(Type::Chan(_))) {
+                    return self.unsupported(func, "channels are");
+                }
+                let t = ty_ref(self);
+                with_args(sizes, |a| format!("{} = nm_make({}, {}, {});", dest, t, sizes.len(), a))
+            }
+            Inst::Call(callee, args, spread) => {
+                let call = |a: &str| -> String {
+                    let n = args.len();
+                    if *spread {
+                        return format!("nm_call_spread({}, {}, {})", v(*callee), n, a);
+                    }
+                    match func.inst(*callee) {
+                        Inst::Builtin(name) if BUILTINS.contains(name) => format!("nm_b_{0}(&nm_B_{0}, {1}, {2})", name, n, a),
+                        Inst::Package(path, name) if package_builtin(path, name) => {
+                            format!("nm_p_{0}_{1}(&nm_P_{0}_{1}, {2}, {3})", path, name, n, a)
+                        }
+                        _ => format!("nm_call({}, {}, {})", v(*callee), n, a),
+                    }
+                };
+                match &data.ty {
+                    Ty::Void => with_args(args, |a| format!("{};", call(a))),
+                    Ty::Tuple(types) => with_args(args, |a| format!("nm_untuple({}, {}, {});", dest, types.len(), call(a))),
+                    _ => with_args(args, |a| format!("{} = {};", dest, call(a))),
+                }
+            }
+            Inst::Extract(t, i) => format!("{} = {}[{}];", dest, v(*t), i),
+            Inst::Go(_, _) => return self.unsupported(func, "goroutines are"),
+            Inst::Defer(callee, args) => {
+                with_args(args, |a| format!("nm_defer(&fr, {}, {}, {});", v(*callee), args.len(), a))
+            }
+            Inst::RunDefers => "nm_run_defers(&fr);".to_string(),
+            Inst::Send(_, _) | Inst::Recv(_, _) | Inst::Select(_, _) => return self.unsupported(func, "channels are"),
+            Inst::Range(x) => {
+                if matches!(static_ty(*x), Some(Type::Chan(_))) {
+                    return self.unsupported(func, "channels are");
+                }
+                format!("nm_range(&{}, {});", dest, v(*x))
+            }
+            Inst::Next(it) => format!("nm_next(&{}, {});", v(*it), dest),
+            Inst::Jump(target) => edge(func, block, *target),
+            Inst::Branch(cond, t, f) => format!(
+                "if (nm_truth({})) {{\n        {}\n    }} else {{\n        {}\n    }}",
+                v(*cond),
+                edge(func, block, *t),
+                edge(func, block, *f)
+            ),
+            Inst::Return(value) => {
+                let mut code = String::new();
+                if func.recover.is_some() {
+                    code.push_str("nm_handler = fr.prev;\n    ");
+                }
+                code.push_str("nm_leave();\n    ");
+                match value {
+                    Some(value) => code.push_str(&format!("return {};", v(*value))),
+                    None => code.push_str("return nm_nil;"),
+                }
+                code
+            }
+        };
+        Some(code)
+    }
+
+    /// Assigns an operator's result, boxing it when the IR types it as
+    /// an interface: the runtime computes with the values interfaces hold.
+    fn boxed(&mut self, dest: &str, ty: &Option<Type>, value: String) -> String {
+        match ty {
+            Some(t @ Type::Interface(_)) => format!("{} = nm_convert({}, {});", dest, value, self.type_ref(t)),
+            _ => format!("{} = {};", dest, value),
+        }
+    }
+}
+
+/// The name of the runtime's own descriptor for the type, if it has one.
+pub(crate) fn runtime_type(ty: &Type) -> Option<&'static str> {
+    let runtime_struct = |ty: &Type| match ty {
+        Type::Struct(st) => RUNTIME_STRUCTS.iter().find(|(name, _)| st.name.as_deref() == Some(*name)).map(|(_, c)| *c),
+        _ => None,
+    };
+    Some(match ty {
+        Type::Nil => "nil",
+        Type::Bool => "bool",
+        Type::Int => "int",
+        Type::Byte => "byte",
+        Type::Float64 => "float",
+        Type::String => "string",
+        Type::Func => "func",
+        Type::Interface(it) => match it.name.as_deref() {
+            None => "any",
+            Some("error") => "error",
+            Some(_) => return None,
+        },
+        Type::Slice(elem) => match elem.as_ref() {
+            Type::Byte => "bytes",
+            Type::Int => "ints",
+            Type::Float64 => "floats",
+            Type::String => "strings",
+            _ => return None,
+        },
+        Type::Struct(_) => return runtime_struct(ty),
+        Type::Pointer(elem) => {
+            return match runtime_struct(elem) {
+                Some("errorString") => Some("p_errorString"),
+                Some("wrapError") => Some("p_wrapError"),
+                Some("NumError") => Some("p_NumError"),
+                Some("PathError") => Some("p_PathError"),
+                Some("File") => Some("p_File"),
+                _ => None,
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// Whether the runtime implements the package function, as a builtin of
+/// the VM or as one written in source there.
+pub(crate) fn package_function(path: &str, name: &str) -> bool {
+    package_builtin(path, name) || RUNTIME.contains(&format!("NM_SOURCE({}, {},", path, name))
+}
+
+/// Whether the runtime has the package variable or constant.
+pub(crate) fn package_value(path: &str, name: &str) -> bool {
+    RUNTIME.contains(&format!("NM_API nm_value nm_v_{}_{}(void)", path, name))
+}
+
+/// Whether the runtime implements the package function as a builtin,
+/// which calls can go to directly as they check their own arguments.
+pub(crate) fn package_builtin(path: &str, name: &str) -> bool {
+    let defined = |prefix: &str| RUNTIME.contains(&format!("{}({}, {},", prefix, path, name));
+    let math = |prefix: &str| path == "math" && RUNTIME.contains(&format!("{}({},", prefix, name));
+    defined("NM_PACKAGE") || math("NM_MATH_UNARY") || math("NM_MATH_BINARY")
+}
+
+fn float(x: f64) -> String {
+    format!("nm_float_bits(UINT64_C(0x{:016x}))", x.to_bits())
+}
+
+/// The type of the first element of a tuple, as the comma-ok form of an
+/// assertion yields.
+pub(crate) fn tuple_first(ty: &Ty) -> Type {
+    match ty {
+        Ty::Tuple(types) if !types.is_empty() => types[0].clone(),
+        _ => Type::any(),
+    }
+}
+
+/// Where the field is in the struct the static type names, directly or
+/// through a pointer, or -1 when that is not known.
+pub(crate) fn field_hint(ty: Option<Type>, name: &str) -> i64 {
+    let st = match ty {
+        Some(Type::Struct(st)) => st,
+        Some(Type::Pointer(elem)) => match elem.as_ref() {
+            Type::Struct(st) => Rc::clone(st),
+            _ => return -1,
+        },
+        _ => return -1,
+    };
+    let index = st.field_index(name);
+    index.map_or(-1, |i| i as i64)
+}
+
+/// A jump along the edge, with the copies into the target's phis done
+/// in parallel.
+fn edge(func: &Function, from: BlockId, to: BlockId) -> String {
+    let mut copies = vec![];
+    for &value in &func.blocks[to.0].insts {
+        if let Inst::Phi(edges) = func.inst(value) {
+            if let Some(&(_, operand)) = edges.iter().find(|(b, _)| *b == from) {
+                copies.push((value, operand));
+            }
+        }
+    }
+    let jump = format!("goto b{};", to.0);
+    match copies.as_slice() {
+        [] => jump,
+        [(phi, operand)] => format!("{} = {};\n        {}", v(*phi), v(*operand), jump),
+        _ => {
+            let mut code = String::from("{\n");
+            for (i, (_, operand)) in copies.iter().enumerate() {
+                code.push_str(&format!("            nm_value t{} = {};\n", i, v(*operand)));
+            }
+            for (i, (phi, _)) in copies.iter().enumerate() {
+                code.push_str(&format!("            {} = t{};\n", v(*phi), i));
+            }
+            code.push_str(&format!("        }}\n        {}", jump));
+            code
+        }
+    }
+}
+
+/// The values a recovered panic resumes with: those defined outside the
+/// blocks the recover block reaches and used inside them. `longjmp` would
+/// leave them indeterminate unless they are volatile.
+fn volatile_values(func: &Function) -> HashSet<ValueId> {
+    let mut volatile = HashSet::new();
+    let recover = match func.recover {
+        Some(recover) => recover,
+        None => return volatile,
+    };
+    let mut region = HashSet::new();
+    let mut stack = vec![recover];
+    while let Some(block) = stack.pop() {
+        if region.insert(block) {
+            stack.extend(func.successors(block));
+        }
+    }
+    let defined: HashSet<ValueId> = region.iter().flat_map(|b| func.blocks[b.0].insts.iter().copied()).collect();
+    for block in &region {
+        for &value in &func.blocks[block.0].insts {
+            for operand in func.inst(value).operands() {
+                if !defined.contains(&operand) && func.ty(operand).value().is_some() {
+                    volatile.insert(operand);
+                }
+            }
+        }
+    }
+    volatile
+}
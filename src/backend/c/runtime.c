@@ -0,0 +1,4852 @@
+/* The runtime of programs the C backend compiles: dynamically typed
+ * values as the VM has them, the builtins, panics and deferred calls,
+ * and the standard library packages. It is copied in front of the
+ * generated code, which calls into it for everything but control flow.
+ *
+ * Memory is never freed; programs are expected to be short-lived.
+ *
+ * Its definitions are static, unless NM_API is defined otherwise to
+ * compile the runtime on its own, for code that links with it. */
+
+#define _POSIX_C_SOURCE 200809L
+
+#include <errno.h>
+#include <fcntl.h>
+#include <locale.h>
+#include <math.h>
+#include <pthread.h>
+#include <setjmp.h>
+#include <stdarg.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <strings.h>
+#include <time.h>
+#include <unistd.h>
+#include <wctype.h>
+
+#ifndef NM_API
+#define NM_API static
+#endif
+
+/* Types */
+
+enum {
+    NM_NIL,
+    NM_BOOL,
+    NM_INT,
+    NM_BYTE,
+    NM_FLOAT,
+    NM_STRING,
+    NM_FUNC,
+    NM_SLICE,
+    NM_MAP,
+    NM_POINTER,
+    NM_CHAN,
+    NM_STRUCT,
+    NM_INTERFACE
+};
+
+typedef struct nm_type nm_type;
+
+/* A type as the checker has it. Named structs and interfaces are equal
+ * only to themselves; others compare by structure. */
+struct nm_type {
+    int kind;
+    /* The type as Go prints it. */
+    const char *name;
+    /* Elements of slices, maps, pointers and channels. */
+    const nm_type *elem;
+    const nm_type *key;
+    int named;
+    int nfields;
+    const char *const *fnames;
+    const nm_type *const *ftypes;
+};
+
+/* A value and its dynamic type, which for an integer is always `int`.
+ * Strings, slices, maps, structs and functions point to their data; an
+ * interface points to the value it holds, or is NULL when nil. */
+typedef struct {
+    const nm_type *type;
+    union {
+        int64_t i;
+        double f;
+        void *p;
+    } u;
+} nm_value;
+
+typedef struct {
+    int64_t len;
+    const uint8_t *data;
+} nm_string;
+
+/* A window onto a backing array, which elements past `len` up to `cap`
+ * belong to as well. */
+typedef struct {
+    nm_value *data;
+    int64_t len;
+    int64_t cap;
+} nm_slice;
+
+typedef struct {
+    nm_value key;
+    nm_value val;
+    int live;
+} nm_entry;
+
+/* Entries in insertion order, deleted ones left as holes until there are
+ * more holes than entries, and an open-addressed index of them. */
+typedef struct {
+    nm_entry *entries;
+    int64_t n;
+    int64_t cap;
+    int64_t count;
+    /* Entry index plus one, or 0 for a free slot. */
+    int64_t *slots;
+    int64_t nslots;
+} nm_map;
+
+typedef struct nm_func nm_func;
+typedef nm_value (*nm_fn)(nm_func *self, int64_t argc, nm_value *argv);
+
+/* A function value: a closure of the program, a builtin or package
+ * function, or a method bound to its receiver. */
+struct nm_func {
+    nm_fn fn;
+    const char *name;
+    /* What %v prints: `fn(a, b)`, `builtin len`, `method error.Error`. */
+    const char *sig;
+    /* Parameters checked at calls, or -1 for functions taking any. */
+    int64_t nparams;
+    nm_value recv;
+    int64_t nfree;
+    nm_value **free;
+};
+
+/* Descriptors of the types the runtime makes values of. */
+NM_API const nm_type nm_t_nil = {NM_NIL, "nil", 0, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_bool = {NM_BOOL, "bool", 0, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_int = {NM_INT, "int", 0, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_byte = {NM_BYTE, "uint8", 0, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_float = {NM_FLOAT, "float64", 0, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_string = {NM_STRING, "string", 0, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_func = {NM_FUNC, "fn", 0, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_any = {NM_INTERFACE, "interface {}", 0, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_error = {NM_INTERFACE, "error", 0, 0, 1, 0, 0, 0};
+NM_API const nm_type nm_t_bytes = {NM_SLICE, "[]uint8", &nm_t_byte, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_ints = {NM_SLICE, "[]int", &nm_t_int, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_floats = {NM_SLICE, "[]float64", &nm_t_float, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_strings = {NM_SLICE, "[]string", &nm_t_string, 0, 0, 0, 0, 0};
+/* The results of a package function with several. */
+NM_API const nm_type nm_t_tuple = {NM_NIL, "tuple", 0, 0, 0, 0, 0, 0};
+
+/* The errors the packages make are pointers to these structs. */
+NM_API const char *const nm_f_errorString[] = {"s"};
+NM_API const nm_type *const nm_ft_errorString[] = {&nm_t_string};
+NM_API const nm_type nm_t_errorString = {NM_STRUCT, "errors.errorString", 0, 0, 1, 1, nm_f_errorString, nm_ft_errorString};
+NM_API const char *const nm_f_wrapError[] = {"msg", "err"};
+NM_API const nm_type *const nm_ft_wrapError[] = {&nm_t_string, &nm_t_error};
+NM_API const nm_type nm_t_wrapError = {NM_STRUCT, "fmt.wrapError", 0, 0, 1, 2, nm_f_wrapError, nm_ft_wrapError};
+NM_API const char *const nm_f_NumError[] = {"Func", "Num", "Err"};
+NM_API const nm_type *const nm_ft_NumError[] = {&nm_t_string, &nm_t_string, &nm_t_error};
+NM_API const nm_type nm_t_NumError = {NM_STRUCT, "strconv.NumError", 0, 0, 1, 3, nm_f_NumError, nm_ft_NumError};
+NM_API const char *const nm_f_PathError[] = {"Op", "Path", "Err"};
+NM_API const nm_type *const nm_ft_PathError[] = {&nm_t_string, &nm_t_string, &nm_t_error};
+NM_API const nm_type nm_t_PathError = {NM_STRUCT, "fs.PathError", 0, 0, 1, 3, nm_f_PathError, nm_ft_PathError};
+NM_API const nm_type nm_t_p_errorString = {NM_POINTER, "*errors.errorString", &nm_t_errorString, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_p_wrapError = {NM_POINTER, "*fmt.wrapError", &nm_t_wrapError, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_p_NumError = {NM_POINTER, "*strconv.NumError", &nm_t_NumError, 0, 0, 0, 0, 0};
+NM_API const nm_type nm_t_p_PathError = {NM_POINTER, "*fs.PathError", &nm_t_PathError, 0, 0, 0, 0, 0};
+NM_API const char *const nm_f_File[] = {"fd"};
+NM_API const nm_type *const nm_ft_File[] = {&nm_t_int};
+NM_API const nm_type nm_t_File = {NM_STRUCT, "os.File", 0, 0, 1, 1, nm_f_File, nm_ft_File};
+NM_API const nm_type nm_t_p_File = {NM_POINTER, "*os.File", &nm_t_File, 0, 0, 0, 0, 0};
+
+NM_API const nm_value nm_nil = {&nm_t_nil, {0}};
+
+/* Memory and buffers */
+
+NM_API void nm_fatal(const char *msg);
+
+NM_API void *nm_alloc(size_t size) {
+    void *p = calloc(1, size ? size : 1);
+    if (!p) {
+        nm_fatal("out of memory");
+    }
+    return p;
+}
+
+NM_API void *nm_realloc(void *p, size_t size) {
+    p = realloc(p, size ? size : 1);
+    if (!p) {
+        nm_fatal("out of memory");
+    }
+    return p;
+}
+
+typedef struct {
+    uint8_t *data;
+    int64_t len;
+    int64_t cap;
+} nm_buf;
+
+NM_API void nm_buf_put(nm_buf *b, const void *data, int64_t len) {
+    if (b->len + len > b->cap) {
+        int64_t cap = b->cap ? b->cap * 2 : 64;
+        while (cap < b->len + len) {
+            cap *= 2;
+        }
+        b->data = nm_realloc(b->data, (size_t)cap);
+        b->cap = cap;
+    }
+    if (len) {
+        memcpy(b->data + b->len, data, (size_t)len);
+    }
+    b->len += len;
+}
+
+NM_API void nm_buf_byte(nm_buf *b, uint8_t c) {
+    nm_buf_put(b, &c, 1);
+}
+
+NM_API void nm_buf_str(nm_buf *b, const char *s) {
+    nm_buf_put(b, s, (int64_t)strlen(s));
+}
+
+NM_API void nm_buf_printf(nm_buf *b, const char *fmt, ...) {
+    char small[256];
+    va_list ap;
+    va_start(ap, fmt);
+    int n = vsnprintf(small, sizeof small, fmt, ap);
+    va_end(ap);
+    if (n < (int)sizeof small) {
+        nm_buf_put(b, small, n);
+        return;
+    }
+    char *big = nm_alloc((size_t)n + 1);
+    va_start(ap, fmt);
+    vsnprintf(big, (size_t)n + 1, fmt, ap);
+    va_end(ap);
+    nm_buf_put(b, big, n);
+    free(big);
+}
+
+/* The buffer as a NUL-terminated C string. */
+NM_API const char *nm_buf_cstr(nm_buf *b) {
+    nm_buf_byte(b, 0);
+    b->len--;
+    return (const char *)b->data;
+}
+
+/* Standard output, flushed at exit and before anything goes to standard
+ * error so the two interleave as the program wrote them. */
+NM_API nm_buf nm_stdout;
+
+NM_API void nm_flush(void) {
+    if (nm_stdout.len) {
+        fwrite(nm_stdout.data, 1, (size_t)nm_stdout.len, stdout);
+        nm_stdout.len = 0;
+    }
+    fflush(stdout);
+}
+
+NM_API void nm_write(int fd, const uint8_t *data, int64_t len) {
+    if (fd == 1) {
+        nm_buf_put(&nm_stdout, data, len);
+        if (nm_stdout.len >= 1 << 16) {
+            nm_flush();
+        }
+    } else {
+        nm_flush();
+        fwrite(data, 1, (size_t)len, stderr);
+        fflush(stderr);
+    }
+}
+
+NM_API void nm_fatal(const char *msg) {
+    nm_flush();
+    fprintf(stderr, "fatal error: %s\n", msg);
+    exit(2);
+}
+
+/* Strings and UTF-8 */
+
+NM_API const nm_string nm_empty = {0, (const uint8_t *)""};
+
+NM_API nm_value nm_str(const uint8_t *data, int64_t len) {
+    nm_value v = {&nm_t_string, {0}};
+    if (len == 0) {
+        v.u.p = (void *)&nm_empty;
+        return v;
+    }
+    nm_string *s = nm_alloc(sizeof(nm_string) + (size_t)len);
+    uint8_t *copy = (uint8_t *)(s + 1);
+    memcpy(copy, data, (size_t)len);
+    s->len = len;
+    s->data = copy;
+    v.u.p = s;
+    return v;
+}
+
+NM_API nm_value nm_cstr(const char *s) {
+    return nm_str((const uint8_t *)s, (int64_t)strlen(s));
+}
+
+NM_API nm_value nm_buf_value(nm_buf *b) {
+    return nm_str(b->data, b->len);
+}
+
+NM_API const nm_string *nm_sv(nm_value v) {
+    return (const nm_string *)v.u.p;
+}
+
+/* The first rune of `s` and its length, decoding as Rust does; invalid
+ * UTF-8 decodes as U+FFFD one byte at a time. */
+NM_API int32_t nm_decode(const uint8_t *s, int64_t n, int *size) {
+    uint8_t c = s[0];
+    *size = 1;
+    if (c < 0x80) {
+        return c;
+    }
+    int len;
+    int32_t r;
+    uint8_t lo = 0x80, hi = 0xBF;
+    if (c >= 0xC2 && c <= 0xDF) {
+        len = 2;
+        r = c & 0x1F;
+    } else if (c >= 0xE0 && c <= 0xEF) {
+        len = 3;
+        r = c & 0x0F;
+        if (c == 0xE0) {
+            lo = 0xA0;
+        } else if (c == 0xED) {
+            hi = 0x9F;
+        }
+    } else if (c >= 0xF0 && c <= 0xF4) {
+        len = 4;
+        r = c & 0x07;
+        if (c == 0xF0) {
+            lo = 0x90;
+        } else if (c == 0xF4) {
+            hi = 0x8F;
+        }
+    } else {
+        return 0xFFFD;
+    }
+    if (n < len) {
+        return 0xFFFD;
+    }
+    for (int i = 1; i < len; i++) {
+        uint8_t b = s[i];
+        if (b < lo || b > hi) {
+            return 0xFFFD;
+        }
+        lo = 0x80;
+        hi = 0xBF;
+        r = (r << 6) | (b & 0x3F);
+    }
+    *size = len;
+    return r;
+}
+
+/* Encodes a rune, U+FFFD for a code point there is none of. */
+NM_API int nm_encode(int64_t r, uint8_t *out) {
+    if (r < 0 || r > 0x10FFFF || (r >= 0xD800 && r <= 0xDFFF)) {
+        r = 0xFFFD;
+    }
+    if (r < 0x80) {
+        out[0] = (uint8_t)r;
+        return 1;
+    }
+    if (r < 0x800) {
+        out[0] = (uint8_t)(0xC0 | (r >> 6));
+        out[1] = (uint8_t)(0x80 | (r & 0x3F));
+        return 2;
+    }
+    if (r < 0x10000) {
+        out[0] = (uint8_t)(0xE0 | (r >> 12));
+        out[1] = (uint8_t)(0x80 | ((r >> 6) & 0x3F));
+        out[2] = (uint8_t)(0x80 | (r & 0x3F));
+        return 3;
+    }
+    out[0] = (uint8_t)(0xF0 | (r >> 18));
+    out[1] = (uint8_t)(0x80 | ((r >> 12) & 0x3F));
+    out[2] = (uint8_t)(0x80 | ((r >> 6) & 0x3F));
+    out[3] = (uint8_t)(0x80 | (r & 0x3F));
+    return 4;
+}
+
+NM_API void nm_buf_rune(nm_buf *b, int64_t r) {
+    uint8_t enc[4];
+    nm_buf_put(b, enc, nm_encode(r, enc));
+}
+
+/* Bytes with invalid UTF-8 replaced by U+FFFD, as Rust's lossy
+ * conversion prints them. */
+NM_API void nm_buf_lossy(nm_buf *b, const uint8_t *s, int64_t n) {
+    int64_t i = 0;
+    while (i < n) {
+        int size;
+        int32_t r = nm_decode(s + i, n - i, &size);
+        if (r == 0xFFFD && size == 1) {
+            nm_buf_rune(b, 0xFFFD);
+        } else {
+            nm_buf_put(b, s + i, size);
+        }
+        i += size;
+    }
+}
+
+NM_API int64_t nm_rune_count(const uint8_t *s, int64_t n) {
+    int64_t count = 0;
+    for (int64_t i = 0; i < n; count++) {
+        int size;
+        nm_decode(s + i, n - i, &size);
+        i += size;
+    }
+    return count;
+}
+
+/* Type identity */
+
+NM_API int nm_identical(const nm_type *a, const nm_type *b) {
+    if (a == b) {
+        return 1;
+    }
+    if (a->kind != b->kind) {
+        return 0;
+    }
+    switch (a->kind) {
+    case NM_SLICE:
+    case NM_POINTER:
+    case NM_CHAN:
+        return nm_identical(a->elem, b->elem);
+    case NM_MAP:
+        return nm_identical(a->key, b->key) && nm_identical(a->elem, b->elem);
+    case NM_STRUCT:
+        if (a->named || b->named || a->nfields != b->nfields) {
+            return 0;
+        }
+        for (int i = 0; i < a->nfields; i++) {
+            if (strcmp(a->fnames[i], b->fnames[i]) != 0 || !nm_identical(a->ftypes[i], b->ftypes[i])) {
+                return 0;
+            }
+        }
+        return 1;
+    case NM_INTERFACE:
+        return !a->named && !b->named;
+    default:
+        return 1;
+    }
+}
+
+NM_API int nm_comparable(const nm_type *t) {
+    switch (t->kind) {
+    case NM_SLICE:
+    case NM_MAP:
+    case NM_FUNC:
+        return 0;
+    case NM_STRUCT:
+        for (int i = 0; i < t->nfields; i++) {
+            if (!nm_comparable(t->ftypes[i])) {
+                return 0;
+            }
+        }
+        return 1;
+    default:
+        return 1;
+    }
+}
+
+/* A pointer type made at run time, for the address of a field whose
+ * type the generated code did not know. */
+NM_API const nm_type *nm_pointer_to(const nm_type *elem) {
+    static const nm_type **made;
+    static int nmade;
+    for (int i = 0; i < nmade; i++) {
+        if (made[i]->elem == elem) {
+            return made[i];
+        }
+    }
+    nm_type *t = nm_alloc(sizeof(nm_type));
+    char *name = nm_alloc(strlen(elem->name) + 2);
+    name[0] = '*';
+    strcpy(name + 1, elem->name);
+    t->kind = NM_POINTER;
+    t->name = name;
+    t->elem = elem;
+    made = nm_realloc(made, sizeof(*made) * (size_t)(nmade + 1));
+    made[nmade++] = t;
+    return t;
+}
+
+/* Values */
+
+NM_API nm_value nm_int(int64_t i) {
+    nm_value v = {&nm_t_int, {0}};
+    v.u.i = i;
+    return v;
+}
+
+NM_API nm_value nm_float(double f) {
+    nm_value v = {&nm_t_float, {0}};
+    v.u.f = f;
+    return v;
+}
+
+NM_API nm_value nm_float_bits(uint64_t bits) {
+    double f;
+    memcpy(&f, &bits, sizeof f);
+    return nm_float(f);
+}
+
+NM_API nm_value nm_bool(int b) {
+    nm_value v = {&nm_t_bool, {0}};
+    v.u.i = b != 0;
+    return v;
+}
+
+NM_API nm_value nm_ref(const nm_type *t, void *p) {
+    nm_value v;
+    v.type = t;
+    v.u.p = p;
+    return v;
+}
+
+/* A string constant of the generated code. */
+NM_API nm_value nm_strconst(const nm_string *s) {
+    return nm_ref(&nm_t_string, (void *)s);
+}
+
+/* The results of a package function that has several: their number,
+ * then the results. */
+NM_API nm_value nm_tuple(int64_t n, const nm_value *values) {
+    nm_value *copy = nm_alloc(sizeof(nm_value) * (size_t)(n + 1));
+    copy[0] = nm_int(n);
+    memcpy(copy + 1, values, sizeof(nm_value) * (size_t)n);
+    return nm_ref(&nm_t_tuple, copy);
+}
+
+NM_API void nm_untuple(nm_value *out, int64_t n, nm_value tuple) {
+    memcpy(out, (nm_value *)tuple.u.p + 1, sizeof(nm_value) * (size_t)n);
+}
+
+NM_API nm_value *nm_fields(nm_value v) {
+    return (nm_value *)v.u.p;
+}
+
+NM_API nm_slice *nm_slv(nm_value v) {
+    return (nm_slice *)v.u.p;
+}
+
+NM_API nm_map *nm_mapv(nm_value v) {
+    return (nm_map *)v.u.p;
+}
+
+NM_API nm_func *nm_funcv(nm_value v) {
+    return (nm_func *)v.u.p;
+}
+
+NM_API int nm_is_nil(nm_value v) {
+    return v.type->kind == NM_NIL;
+}
+
+/* The value an interface holds, or the value itself. */
+NM_API nm_value nm_raw(nm_value v) {
+    if (v.type->kind == NM_INTERFACE) {
+        return v.u.p ? *(nm_value *)v.u.p : nm_nil;
+    }
+    return v;
+}
+
+/* The name of the value's type; for an interface, the interface type. */
+NM_API const char *nm_type_name(nm_value v) {
+    return v.type->name;
+}
+
+/* The name of the value's dynamic type as shown to users. */
+NM_API const char *nm_dynamic_name(nm_value v) {
+    if (v.type->kind == NM_INTERFACE) {
+        return v.u.p ? ((nm_value *)v.u.p)->type->name : "nil";
+    }
+    return v.type->name;
+}
+
+NM_API nm_value nm_zero(const nm_type *t) {
+    switch (t->kind) {
+    case NM_NIL:
+    case NM_FUNC:
+        return nm_nil;
+    case NM_BOOL:
+        return nm_bool(0);
+    case NM_INT:
+    case NM_BYTE:
+        return nm_int(0);
+    case NM_FLOAT:
+        return nm_float(0);
+    case NM_STRING:
+        return nm_strconst(&nm_empty);
+    case NM_STRUCT: {
+        nm_value *fields = nm_alloc(sizeof(nm_value) * (size_t)t->nfields);
+        for (int i = 0; i < t->nfields; i++) {
+            fields[i] = nm_zero(t->ftypes[i]);
+        }
+        return nm_ref(t, fields);
+    }
+    default:
+        return nm_ref(t, NULL);
+    }
+}
+
+/* A copy of a struct value, for storing where it can change in place or
+ * reading from there. Other values are shared. */
+NM_API nm_value nm_dup(nm_value v) {
+    if (v.type->kind != NM_STRUCT) {
+        return v;
+    }
+    int n = v.type->nfields;
+    nm_value *fields = nm_alloc(sizeof(nm_value) * (size_t)n);
+    for (int i = 0; i < n; i++) {
+        fields[i] = nm_dup(nm_fields(v)[i]);
+    }
+    return nm_ref(v.type, fields);
+}
+
+/* Stores into a slot. A struct of the same type is copied over the one
+ * there, so that pointers to its fields see the new value. */
+NM_API void nm_put(nm_value *slot, nm_value v) {
+    if (v.type->kind == NM_STRUCT && slot->type == v.type && slot->u.p && slot->u.p != v.u.p) {
+        nm_value *fields = nm_fields(*slot);
+        for (int i = 0; i < v.type->nfields; i++) {
+            nm_put(&fields[i], nm_fields(v)[i]);
+        }
+        return;
+    }
+    *slot = nm_dup(v);
+}
+
+/* Panics */
+
+#define NM_MAX_FRAMES 1024
+
+typedef struct {
+    const char *name;
+    int params;
+} nm_frame_info;
+
+typedef struct nm_deferred {
+    nm_value callee;
+    int64_t argc;
+    nm_value *argv;
+    struct nm_deferred *next;
+} nm_deferred;
+
+/* A function that defers calls: where a panic resumes it, and its
+ * deferred calls, last first. */
+typedef struct nm_frame {
+    jmp_buf jb;
+    struct nm_frame *prev;
+    nm_deferred *defers;
+    int depth;
+} nm_frame;
+
+/* The calls running, for stack traces; entries without a name are the
+ * program's top level, hidden once it has run. */
+NM_API nm_frame_info nm_stack[NM_MAX_FRAMES + 1];
+NM_API int nm_depth;
+NM_API nm_frame *nm_handler;
+NM_API int nm_panicking;
+NM_API nm_value nm_panic_value;
+NM_API const char *nm_trace;
+/* The depth of the deferred call that may recover the panic, or -1. */
+NM_API int nm_recover_depth = -1;
+
+NM_API void nm_throw(nm_value v);
+NM_API void nm_buf_inspect(nm_buf *b, nm_value v, int top, int names);
+NM_API const char *nm_error_message(nm_value v, nm_buf *b);
+
+NM_API const char *nm_traceback(void) {
+    nm_buf b = {0};
+    nm_buf_str(&b, "goroutine 1 [running]:\n");
+    for (int i = nm_depth - 1; i >= 0; i--) {
+        if (nm_stack[i].name) {
+            nm_buf_printf(&b, "%s%s\n", nm_stack[i].name, nm_stack[i].params ? "(...)" : "()");
+        }
+    }
+    return nm_buf_cstr(&b);
+}
+
+/* Panics with the message of a failed operation. Go's runtime errors say
+ * so; its other run-time panics do not. */
+NM_API void nm_errorf(const char *fmt, ...) {
+    static const char *const plain[] = {
+        "assignment to entry in nil map",
+        "send on closed channel",
+        "close of nil channel",
+        "close of closed channel",
+        "interface conversion:",
+        "strings: ",
+        "strconv: ",
+        "errors: ",
+    };
+    nm_buf b = {0};
+    char small[512];
+    va_list ap;
+    va_start(ap, fmt);
+    int n = vsnprintf(small, sizeof small, fmt, ap);
+    va_end(ap);
+    char *msg = small;
+    if (n >= (int)sizeof small) {
+        msg = nm_alloc((size_t)n + 1);
+        va_start(ap, fmt);
+        vsnprintf(msg, (size_t)n + 1, fmt, ap);
+        va_end(ap);
+    }
+    int is_plain = 0;
+    for (size_t i = 0; i < sizeof plain / sizeof plain[0]; i++) {
+        if (strncmp(msg, plain[i], strlen(plain[i])) == 0) {
+            is_plain = 1;
+        }
+    }
+    if (!is_plain) {
+        nm_buf_str(&b, "runtime error: ");
+    }
+    nm_buf_str(&b, msg);
+    nm_throw(nm_buf_value(&b));
+}
+
+NM_API void nm_enter(const char *name, int params) {
+    if (nm_depth >= NM_MAX_FRAMES) {
+        nm_errorf("stack overflow");
+    }
+    nm_stack[nm_depth].name = name;
+    nm_stack[nm_depth].params = params;
+    nm_depth++;
+}
+
+NM_API void nm_leave(void) {
+    nm_depth--;
+}
+
+NM_API void nm_frame_push(nm_frame *fr) {
+    fr->prev = nm_handler;
+    fr->defers = NULL;
+    fr->depth = nm_depth;
+    nm_handler = fr;
+}
+
+/* How a goroutine that dies of a panic reports its value. */
+NM_API void nm_panic_message(nm_buf *b, nm_value v) {
+    if (nm_error_message(v, b)) {
+        return;
+    }
+    switch (v.type->kind) {
+    case NM_INTERFACE:
+        if (v.u.p) {
+            nm_panic_message(b, *(nm_value *)v.u.p);
+            return;
+        }
+        break;
+    case NM_FLOAT: {
+        double f = v.u.f;
+        if (isnan(f)) {
+            nm_buf_str(b, "NaN");
+        } else if (isinf(f)) {
+            nm_buf_str(b, f > 0 ? "+Inf" : "-Inf");
+        } else {
+            char s[64];
+            snprintf(s, sizeof s, "%.6e", fabs(f));
+            char *e = strchr(s, 'e');
+            int exp = atoi(e + 1);
+            *e = 0;
+            nm_buf_printf(b, "%c%se%c%03d", signbit(f) ? '-' : '+', s, exp < 0 ? '-' : '+', abs(exp));
+        }
+        return;
+    }
+    case NM_STRING:
+    case NM_INT:
+    case NM_BOOL:
+        nm_buf_inspect(b, v, 1, 0);
+        return;
+    default:
+        break;
+    }
+    nm_buf_printf(b, "(%s) ", nm_dynamic_name(v));
+    nm_buf_inspect(b, v, 1, 0);
+}
+
+NM_API void nm_die(void) {
+    nm_buf b = {0};
+    nm_buf_str(&b, "panic: ");
+    nm_panic_message(&b, nm_panic_value);
+    nm_buf_str(&b, "\n\n");
+    nm_buf_str(&b, nm_trace);
+    while (b.len && (b.data[b.len - 1] == '\n' || b.data[b.len - 1] == ' ')) {
+        b.len--;
+    }
+    nm_buf_byte(&b, '\n');
+    nm_write(2, b.data, b.len);
+    nm_flush();
+    exit(2);
+}
+
+/* Starts a panic with `v`, in place of any panic already unwinding. */
+NM_API void nm_throw(nm_value v) {
+    nm_trace = nm_traceback();
+    nm_panicking = 1;
+    nm_panic_value = v;
+    if (!nm_handler) {
+        nm_die();
+    }
+    longjmp(nm_handler->jb, 1);
+}
+
+/* Conversions */
+
+NM_API nm_value nm_box(const nm_type *t, nm_value v) {
+    nm_value *box = nm_alloc(sizeof(nm_value));
+    *box = v;
+    return nm_ref(t, box);
+}
+
+/* Converts `v` for storing where a value of type `t` is expected: wraps
+ * it in an interface, gives a nil its type, or panics that it does not
+ * fit. Sets `*failed` instead of panicking when it is given. */
+NM_API nm_value nm_convert_or(nm_value v, const nm_type *t, int *failed) {
+    if (v.type == t && t->kind != NM_INTERFACE) {
+        return v;
+    }
+    switch (t->kind) {
+    case NM_INTERFACE:
+        if (v.type->kind == NM_INTERFACE) {
+            return nm_ref(t, v.u.p);
+        }
+        if (v.type->kind == NM_NIL) {
+            return nm_ref(t, NULL);
+        }
+        return nm_box(t, v);
+    case NM_SLICE:
+    case NM_MAP:
+    case NM_POINTER:
+    case NM_CHAN:
+    case NM_FUNC:
+        if (v.type->kind == NM_NIL) {
+            return nm_zero(t);
+        }
+        break;
+    case NM_BYTE:
+        if (v.type->kind == NM_INT) {
+            return nm_int((uint8_t)v.u.i);
+        }
+        break;
+    case NM_FLOAT:
+        if (v.type->kind == NM_INT) {
+            return nm_float((double)v.u.i);
+        }
+        break;
+    case NM_NIL:
+        return v;
+    default:
+        break;
+    }
+    if (nm_identical(v.type, t)) {
+        return v;
+    }
+    if (v.type->kind == NM_INTERFACE && v.u.p) {
+        /* a value the program's functions took boxed */
+        return nm_convert_or(*(nm_value *)v.u.p, t, failed);
+    }
+    if (failed) {
+        *failed = 1;
+        return nm_zero(t);
+    }
+    if (v.type->kind == NM_INTERFACE) {
+        nm_errorf("cannot use %s value as %s value: need type assertion", v.type->name, t->name);
+    }
+    nm_errorf("cannot use %s value as %s value", v.type->name, t->name);
+    return v;
+}
+
+NM_API nm_value nm_convert(nm_value v, const nm_type *t) {
+    return nm_convert_or(v, t, NULL);
+}
+
+NM_API nm_value nm_slice_of(const nm_type *t, int64_t len, int64_t cap);
+
+NM_API int64_t nm_float_to_int(double f) {
+    if (isnan(f)) {
+        return 0;
+    }
+    if (f >= 9223372036854775807.0) {
+        return INT64_MAX;
+    }
+    if (f <= -9223372036854775808.0) {
+        return INT64_MIN;
+    }
+    return (int64_t)f;
+}
+
+/* A conversion `T(x)`: everything `nm_convert` allows, plus the numeric,
+ * string and byte slice conversions. */
+NM_API nm_value nm_conversion(nm_value v, const nm_type *t) {
+    if (v.type == t && t->kind != NM_INTERFACE) {
+        return v;
+    }
+    if (t->kind != NM_INTERFACE) {
+        v = nm_raw(v);
+    }
+    int k = v.type->kind;
+    switch (t->kind) {
+    case NM_INT:
+        if (k == NM_FLOAT) {
+            return nm_int(nm_float_to_int(v.u.f));
+        }
+        break;
+    case NM_BYTE:
+        if (k == NM_FLOAT) {
+            return nm_int((uint8_t)nm_float_to_int(v.u.f));
+        }
+        break;
+    case NM_STRING:
+        if (k == NM_INT) {
+            uint8_t enc[4];
+            return nm_str(enc, nm_encode(v.u.i, enc));
+        }
+        if (k == NM_SLICE && (v.type->elem->kind == NM_BYTE || v.type->elem->kind == NM_INT)) {
+            nm_buf b = {0};
+            nm_slice *s = nm_slv(v);
+            for (int64_t i = 0; s && i < s->len; i++) {
+                int64_t c = s->data[i].type->kind == NM_INT ? s->data[i].u.i : 0;
+                if (v.type->elem->kind == NM_BYTE) {
+                    nm_buf_byte(&b, (uint8_t)c);
+                } else {
+                    nm_buf_rune(&b, c);
+                }
+            }
+            return nm_buf_value(&b);
+        }
+        break;
+    case NM_SLICE:
+        if (k == NM_STRING && (t->elem->kind == NM_BYTE || t->elem->kind == NM_INT)) {
+            const nm_string *s = nm_sv(v);
+            int bytes = t->elem->kind == NM_BYTE;
+            int64_t n = bytes ? s->len : nm_rune_count(s->data, s->len);
+            nm_value out = nm_slice_of(t, n, n);
+            nm_value *data = n ? nm_slv(out)->data : NULL;
+            int64_t j = 0;
+            for (int64_t i = 0; i < s->len; j++) {
+                int size = 1;
+                int32_t r = bytes ? s->data[i] : nm_decode(s->data + i, s->len - i, &size);
+                data[j] = nm_int(r);
+                i += size;
+            }
+            return out;
+        }
+        break;
+    case NM_STRUCT:
+        if (k == NM_STRUCT) {
+            int same = v.type->nfields == t->nfields;
+            for (int i = 0; same && i < t->nfields; i++) {
+                same = strcmp(t->fnames[i], v.type->fnames[i]) == 0 && nm_identical(t->ftypes[i], v.type->ftypes[i]);
+            }
+            if (same) {
+                nm_value copy = nm_dup(v);
+                copy.type = t;
+                return copy;
+            }
+            nm_errorf("cannot convert %s value to type %s", v.type->name, t->name);
+        }
+        break;
+    default:
+        break;
+    }
+    int failed = 0;
+    nm_value out = nm_convert_or(v, t, &failed);
+    if (failed) {
+        nm_errorf("cannot convert %s value to type %s", v.type->name, t->name);
+    }
+    return out;
+}
+
+/* `x.(T)`, setting `*ok` rather than panicking when it is given. */
+NM_API nm_value nm_assert_or(nm_value x, const nm_type *t, int *ok) {
+    if (x.type->kind != NM_INTERFACE) {
+        if (ok) {
+            /* a value the VM would have held in an interface */
+            if (x.type->kind != NM_NIL && (t->kind == NM_INTERFACE || nm_identical(x.type, t))) {
+                *ok = 1;
+                return nm_convert(x, t);
+            }
+            *ok = 0;
+            return nm_zero(t);
+        }
+        if (t->kind == NM_INTERFACE && x.type->kind != NM_NIL) {
+            return nm_convert(x, t);
+        }
+        if (nm_identical(x.type, t)) {
+            return x;
+        }
+        if (x.type->kind == NM_NIL) {
+            nm_errorf("interface conversion: interface is nil, not %s", t->name);
+        }
+        nm_errorf("interface conversion: interface {} is %s, not %s", x.type->name, t->name);
+    }
+    if (!x.u.p) {
+        if (ok) {
+            *ok = 0;
+            return nm_zero(t);
+        }
+        nm_errorf("interface conversion: interface is nil, not %s", t->name);
+    }
+    nm_value v = *(nm_value *)x.u.p;
+    if (t->kind == NM_INTERFACE) {
+        if (ok) {
+            *ok = 1;
+        }
+        return nm_convert(v, t);
+    }
+    if (nm_identical(v.type, t)) {
+        if (ok) {
+            *ok = 1;
+        }
+        return v;
+    }
+    if (ok) {
+        *ok = 0;
+        return nm_zero(t);
+    }
+    nm_errorf("interface conversion: %s is %s, not %s", x.type->name, v.type->name, t->name);
+    return v;
+}
+
+NM_API nm_value nm_assert(nm_value x, const nm_type *t) {
+    return nm_assert_or(x, t, NULL);
+}
+
+NM_API void nm_assert_ok(nm_value x, const nm_type *t, nm_value *out) {
+    int ok = 0;
+    out[0] = nm_assert_or(x, t, &ok);
+    out[1] = nm_bool(ok);
+}
+
+/* Equality */
+
+NM_API int nm_equals(nm_value a, nm_value b);
+
+/* `==` on the dynamic values of interfaces. */
+NM_API int nm_dynamic_equals(nm_value x, nm_value y) {
+    if (!nm_identical(x.type, y.type)) {
+        return 0;
+    }
+    if (!nm_comparable(x.type)) {
+        nm_errorf("comparing uncomparable type %s", x.type->name);
+    }
+    return nm_equals(x, y);
+}
+
+NM_API int nm_nilable(int kind) {
+    return kind == NM_SLICE || kind == NM_MAP || kind == NM_POINTER || kind == NM_CHAN;
+}
+
+/* Go's `==`. Comparing values whose type has no `==`, other than to nil,
+ * panics. */
+NM_API int nm_equals(nm_value a, nm_value b) {
+    int ka = a.type->kind, kb = b.type->kind;
+    if (ka == NM_INTERFACE && kb == NM_INTERFACE) {
+        if (!a.u.p || !b.u.p) {
+            return !a.u.p && !b.u.p;
+        }
+        return nm_dynamic_equals(*(nm_value *)a.u.p, *(nm_value *)b.u.p);
+    }
+    if (ka == NM_INTERFACE || kb == NM_INTERFACE) {
+        nm_value i = ka == NM_INTERFACE ? a : b;
+        nm_value v = ka == NM_INTERFACE ? b : a;
+        if (v.type->kind == NM_NIL) {
+            return i.u.p == NULL;
+        }
+        return i.u.p ? nm_dynamic_equals(*(nm_value *)i.u.p, v) : 0;
+    }
+    if (ka == NM_INT && kb == NM_INT) {
+        return a.u.i == b.u.i;
+    }
+    if (ka == NM_FLOAT && kb == NM_FLOAT) {
+        return a.u.f == b.u.f;
+    }
+    if (ka == NM_FLOAT && kb == NM_INT) {
+        return a.u.f == (double)b.u.i;
+    }
+    if (ka == NM_INT && kb == NM_FLOAT) {
+        return (double)a.u.i == b.u.f;
+    }
+    if (ka == NM_BOOL && kb == NM_BOOL) {
+        return a.u.i == b.u.i;
+    }
+    if (ka == NM_STRING && kb == NM_STRING) {
+        const nm_string *x = nm_sv(a), *y = nm_sv(b);
+        return x->len == y->len && memcmp(x->data, y->data, (size_t)x->len) == 0;
+    }
+    if (ka == NM_NIL && kb == NM_NIL) {
+        return 1;
+    }
+    if (ka == NM_NIL || kb == NM_NIL) {
+        nm_value v = ka == NM_NIL ? b : a;
+        if (nm_nilable(v.type->kind)) {
+            return v.u.p == NULL;
+        }
+        if (v.type->kind == NM_FUNC) {
+            return 0;
+        }
+    }
+    if ((ka == NM_POINTER || ka == NM_CHAN) && ka == kb && nm_identical(a.type->elem, b.type->elem)) {
+        return a.u.p == b.u.p;
+    }
+    if (ka == NM_STRUCT && kb == NM_STRUCT && nm_identical(a.type, b.type)) {
+        if (!nm_comparable(a.type)) {
+            nm_errorf("invalid operation: struct containing uncomparable fields cannot be compared (%s)", a.type->name);
+        }
+        for (int i = 0; i < a.type->nfields; i++) {
+            if (!nm_equals(nm_fields(a)[i], nm_fields(b)[i])) {
+                return 0;
+            }
+        }
+        return 1;
+    }
+    if (ka == kb && (ka == NM_SLICE || ka == NM_MAP || ka == NM_FUNC)) {
+        nm_errorf("invalid operation: %s can only be compared to nil", ka == NM_SLICE ? "slice" : ka == NM_MAP ? "map" : "func");
+    }
+    nm_errorf("invalid operation: mismatched types %s and %s", a.type->name, b.type->name);
+    return 0;
+}
+
+/* Map keys: equal exactly when the values are `==`, so that a NaN key
+ * matches none, interfaces by dynamic type name and value. Floats hash
+ * by their bits with -0 taken as 0. */
+
+NM_API void nm_check_key(nm_value v) {
+    switch (v.type->kind) {
+    case NM_NIL:
+    case NM_BOOL:
+    case NM_INT:
+    case NM_FLOAT:
+    case NM_STRING:
+    case NM_POINTER:
+    case NM_CHAN:
+        return;
+    case NM_STRUCT:
+        if (nm_comparable(v.type)) {
+            for (int i = 0; i < v.type->nfields; i++) {
+                nm_check_key(nm_fields(v)[i]);
+            }
+            return;
+        }
+        break;
+    case NM_INTERFACE:
+        if (v.u.p) {
+            nm_check_key(*(nm_value *)v.u.p);
+        }
+        return;
+    default:
+        break;
+    }
+    nm_errorf("hash of unhashable type %s", nm_dynamic_name(v));
+}
+
+/* The kind of key a value makes: nil pointers and channels are nil. */
+NM_API int nm_key_kind(nm_value v) {
+    int k = v.type->kind;
+    if ((k == NM_POINTER || k == NM_CHAN) && !v.u.p) {
+        return NM_NIL;
+    }
+    return k;
+}
+
+NM_API uint64_t nm_float_key(double f) {
+    uint64_t bits;
+    if (f == 0) {
+        return 0;
+    }
+    memcpy(&bits, &f, sizeof bits);
+    return bits;
+}
+
+NM_API uint64_t nm_mix(uint64_t h, uint64_t x) {
+    h ^= x + 0x9E3779B97F4A7C15ull + (h << 6) + (h >> 2);
+    return h;
+}
+
+NM_API uint64_t nm_key_hash(nm_value v) {
+    int k = nm_key_kind(v);
+    uint64_t h = (uint64_t)k * 0x100000001B3ull;
+    switch (k) {
+    case NM_BOOL:
+    case NM_INT:
+        return nm_mix(h, (uint64_t)v.u.i);
+    case NM_FLOAT:
+        return nm_mix(h, nm_float_key(v.u.f));
+    case NM_STRING: {
+        const nm_string *s = nm_sv(v);
+        uint64_t x = 0xCBF29CE484222325ull;
+        for (int64_t i = 0; i < s->len; i++) {
+            x = (x ^ s->data[i]) * 0x100000001B3ull;
+        }
+        return nm_mix(h, x);
+    }
+    case NM_POINTER:
+    case NM_CHAN:
+        return nm_mix(h, (uint64_t)(uintptr_t)v.u.p);
+    case NM_STRUCT:
+        for (int i = 0; i < v.type->nfields; i++) {
+            h = nm_mix(h, nm_key_hash(nm_fields(v)[i]));
+        }
+        return h;
+    case NM_INTERFACE:
+        if (v.u.p) {
+            nm_value inner = *(nm_value *)v.u.p;
+            const char *name = inner.type->name;
+            uint64_t x = 0xCBF29CE484222325ull;
+            for (; *name; name++) {
+                x = (x ^ (uint8_t)*name) * 0x100000001B3ull;
+            }
+            return nm_mix(nm_mix(h, x), nm_key_hash(inner));
+        }
+        return h;
+    default:
+        return h;
+    }
+}
+
+NM_API int nm_key_equal(nm_value a, nm_value b) {
+    int k = nm_key_kind(a);
+    if (k != nm_key_kind(b)) {
+        return 0;
+    }
+    switch (k) {
+    case NM_BOOL:
+    case NM_INT:
+        return a.u.i == b.u.i;
+    case NM_FLOAT:
+        return a.u.f == b.u.f;
+    case NM_STRING: {
+        const nm_string *x = nm_sv(a), *y = nm_sv(b);
+        return x->len == y->len && memcmp(x->data, y->data, (size_t)x->len) == 0;
+    }
+    case NM_POINTER:
+    case NM_CHAN:
+        return a.u.p == b.u.p;
+    case NM_STRUCT:
+        if (a.type->nfields != b.type->nfields) {
+            return 0;
+        }
+        for (int i = 0; i < a.type->nfields; i++) {
+            if (!nm_key_equal(nm_fields(a)[i], nm_fields(b)[i])) {
+                return 0;
+            }
+        }
+        return 1;
+    case NM_INTERFACE: {
+        if (!a.u.p || !b.u.p) {
+            return !a.u.p && !b.u.p;
+        }
+        nm_value x = *(nm_value *)a.u.p, y = *(nm_value *)b.u.p;
+        return strcmp(x.type->name, y.type->name) == 0 && nm_key_equal(x, y);
+    }
+    default:
+        return 1;
+    }
+}
+
+/* Maps */
+
+NM_API nm_map *nm_map_new(void) {
+    return nm_alloc(sizeof(nm_map));
+}
+
+/* The entry holding `key`, or -1. */
+NM_API int64_t nm_map_find(nm_map *m, nm_value key) {
+    if (!m || !m->nslots) {
+        return -1;
+    }
+    uint64_t mask = (uint64_t)m->nslots - 1;
+    for (uint64_t i = nm_key_hash(key) & mask;; i = (i + 1) & mask) {
+        int64_t slot = m->slots[i];
+        if (!slot) {
+            return -1;
+        }
+        nm_entry *e = &m->entries[slot - 1];
+        if (e->live && nm_key_equal(e->key, key)) {
+            return slot - 1;
+        }
+    }
+}
+
+/* Rebuilds the index, dropping the holes deleted entries left. */
+NM_API void nm_map_rehash(nm_map *m, int64_t want) {
+    int64_t j = 0;
+    for (int64_t i = 0; i < m->n; i++) {
+        if (m->entries[i].live) {
+            m->entries[j++] = m->entries[i];
+        }
+    }
+    m->n = j;
+    int64_t nslots = 8;
+    while (nslots < 2 * want) {
+        nslots *= 2;
+    }
+    free(m->slots);
+    m->slots = nm_alloc(sizeof(int64_t) * (size_t)nslots);
+    m->nslots = nslots;
+    uint64_t mask = (uint64_t)nslots - 1;
+    for (int64_t i = 0; i < m->n; i++) {
+        uint64_t s = nm_key_hash(m->entries[i].key) & mask;
+        while (m->slots[s]) {
+            s = (s + 1) & mask;
+        }
+        m->slots[s] = i + 1;
+    }
+}
+
+/* Stores a key, converted to the map's key type, and element. */
+NM_API void nm_map_set(nm_map *m, nm_value key, nm_value val) {
+    int64_t i = nm_map_find(m, key);
+    if (i >= 0) {
+        m->entries[i].key = key;
+        m->entries[i].val = val;
+        return;
+    }
+    if (m->n + 1 > m->cap) {
+        m->cap = m->cap ? m->cap * 2 : 8;
+        m->entries = nm_realloc(m->entries, sizeof(nm_entry) * (size_t)m->cap);
+    }
+    if (2 * (m->n + 1) > m->nslots) {
+        nm_map_rehash(m, m->count + 1);
+    }
+    nm_entry *e = &m->entries[m->n];
+    e->key = key;
+    e->val = val;
+    e->live = 1;
+    m->n++;
+    m->count++;
+    uint64_t mask = (uint64_t)m->nslots - 1;
+    uint64_t s = nm_key_hash(key) & mask;
+    while (m->slots[s]) {
+        s = (s + 1) & mask;
+    }
+    m->slots[s] = m->n;
+}
+
+NM_API void nm_map_delete(nm_map *m, nm_value key) {
+    int64_t i = nm_map_find(m, key);
+    if (i < 0) {
+        return;
+    }
+    m->entries[i].live = 0;
+    m->count--;
+    if (m->n > 2 * m->count + 8) {
+        nm_map_rehash(m, m->count);
+    }
+}
+
+NM_API nm_value nm_map_key(nm_value m, nm_value key) {
+    key = nm_convert(key, m.type->key);
+    nm_check_key(key);
+    return key;
+}
+
+/* Slices */
+
+NM_API nm_value nm_slice_of(const nm_type *t, int64_t len, int64_t cap) {
+    nm_slice *s = nm_alloc(sizeof(nm_slice));
+    s->data = nm_alloc(sizeof(nm_value) * (size_t)cap);
+    s->len = len;
+    s->cap = cap;
+    for (int64_t i = 0; i < cap; i++) {
+        s->data[i] = nm_zero(t->elem);
+    }
+    return nm_ref(t, s);
+}
+
+/* The capacity `append` gives a slice that must hold `needed` elements
+ * and has `old_cap`: Go's growth rule for word-sized elements, rounded up
+ * to an allocator size class. */
+NM_API int64_t nm_grow_cap(int64_t old_cap, int64_t needed) {
+    static const int64_t classes[] = {
+        0, 8, 16, 24, 32, 48, 64, 80, 96, 112, 128, 144, 160, 176, 192, 208, 224, 240, 256, 288, 320, 352, 384, 416,
+        448, 480, 512, 576, 640, 704, 768, 896, 1024, 1152, 1280, 1408, 1536, 1792, 2048, 2304, 2688, 3072, 3200, 3456,
+        4096, 4864, 5376, 6144, 6528, 6784, 6912, 8192, 9472, 9728, 10240, 10880, 12288, 13568, 14336, 16384, 18432,
+        19072, 20480, 21760, 24576, 27264, 28672, 32768,
+    };
+    const int64_t threshold = 256;
+    int64_t cap = old_cap;
+    int64_t twice = cap + cap;
+    if (needed > twice) {
+        cap = needed;
+    } else if (old_cap < threshold) {
+        cap = twice;
+    } else {
+        while (cap < needed) {
+            cap += (cap + 3 * threshold) >> 2;
+        }
+    }
+    int64_t size = cap * 8;
+    for (size_t i = 0; i < sizeof classes / sizeof classes[0]; i++) {
+        if (classes[i] >= size) {
+            return classes[i] / 8;
+        }
+    }
+    return (size + 8191) / 8192 * 8192 / 8;
+}
+
+NM_API void nm_buf_inspect(nm_buf *b, nm_value v, int top, int names);
+
+NM_API const char *nm_inspect(nm_value v) {
+    nm_buf b = {0};
+    nm_buf_inspect(&b, v, 1, 0);
+    return nm_buf_cstr(&b);
+}
+
+/* `append(s, values...)`: writes in place while there is capacity, else
+ * copies to a new, larger backing array. */
+NM_API nm_value nm_append_values(nm_value s, int64_t n, const nm_value *values) {
+    s = nm_raw(s);
+    if (s.type->kind != NM_SLICE) {
+        nm_errorf("invalid argument: %s (type %s) is not a slice", nm_inspect(s), s.type->name);
+    }
+    if (n == 0) {
+        return s;
+    }
+    nm_value *converted = nm_alloc(sizeof(nm_value) * (size_t)n);
+    for (int64_t i = 0; i < n; i++) {
+        converted[i] = nm_dup(nm_convert(values[i], s.type->elem));
+    }
+    nm_slice *old = nm_slv(s);
+    int64_t len = old ? old->len : 0, cap = old ? old->cap : 0;
+    int64_t needed = len + n;
+    nm_slice *out = nm_alloc(sizeof(nm_slice));
+    if (needed <= cap) {
+        memcpy(old->data + len, converted, sizeof(nm_value) * (size_t)n);
+        *out = *old;
+        out->len = needed;
+    } else {
+        int64_t newcap = nm_grow_cap(cap, needed);
+        out->data = nm_alloc(sizeof(nm_value) * (size_t)newcap);
+        if (len) {
+            memcpy(out->data, old->data, sizeof(nm_value) * (size_t)len);
+        }
+        memcpy(out->data + len, converted, sizeof(nm_value) * (size_t)n);
+        for (int64_t i = needed; i < newcap; i++) {
+            out->data[i] = nm_zero(s.type->elem);
+        }
+        out->len = needed;
+        out->cap = newcap;
+    }
+    free(converted);
+    return nm_ref(s.type, out);
+}
+
+/* The values `append(s, rest...)` appends: the elements of a slice of the
+ * same type, or the bytes of a string appended to a `[]byte`. */
+NM_API nm_value *nm_spread(nm_value s, nm_value rest, int64_t *n) {
+    s = nm_raw(s);
+    rest = nm_raw(rest);
+    if (s.type->kind != NM_SLICE) {
+        nm_errorf("invalid argument: %s (type %s) is not a slice", nm_inspect(s), s.type->name);
+    }
+    const nm_type *elem = s.type->elem;
+    if (rest.type->kind == NM_SLICE && nm_identical(rest.type->elem, elem)) {
+        nm_slice *r = nm_slv(rest);
+        *n = r ? r->len : 0;
+        return r ? r->data : NULL;
+    }
+    if (rest.type->kind == NM_NIL) {
+        *n = 0;
+        return NULL;
+    }
+    if (rest.type->kind == NM_STRING && elem->kind == NM_BYTE) {
+        const nm_string *str = nm_sv(rest);
+        nm_value *values = nm_alloc(sizeof(nm_value) * (size_t)str->len);
+        for (int64_t i = 0; i < str->len; i++) {
+            values[i] = nm_int(str->data[i]);
+        }
+        *n = str->len;
+        return values;
+    }
+    nm_errorf("cannot use %s (type %s) as []%s value in argument to append", nm_inspect(rest), rest.type->name, elem->name);
+    return NULL;
+}
+
+NM_API int64_t nm_index_int(nm_value i) {
+    i = nm_raw(i);
+    if (i.type->kind != NM_INT) {
+        nm_errorf("invalid argument: index of type %s must be integer", i.type->name);
+    }
+    return i.u.i;
+}
+
+/* Checks `i` is an index below `n`. */
+NM_API int64_t nm_bounds(nm_value i, int64_t n) {
+    int64_t x = nm_index_int(i);
+    if (x < 0 || x >= n) {
+        nm_errorf("index out of range [%lld] with length %lld", (long long)x, (long long)n);
+    }
+    return x;
+}
+
+NM_API void nm_check(nm_value i, nm_value n) {
+    nm_bounds(i, nm_raw(n).u.i);
+}
+
+NM_API int64_t nm_len_of(nm_value x) {
+    switch (x.type->kind) {
+    case NM_STRING:
+        return nm_sv(x)->len;
+    case NM_SLICE:
+        return x.u.p ? nm_slv(x)->len : 0;
+    case NM_MAP:
+        return x.u.p ? nm_mapv(x)->count : 0;
+    default:
+        return -1;
+    }
+}
+
+NM_API nm_value nm_index(nm_value x, nm_value i) {
+    x = nm_raw(x);
+    switch (x.type->kind) {
+    case NM_SLICE: {
+        int64_t at = nm_bounds(i, nm_len_of(x));
+        return nm_dup(nm_slv(x)->data[at]);
+    }
+    case NM_STRING: {
+        int64_t at = nm_bounds(i, nm_len_of(x));
+        return nm_int(nm_sv(x)->data[at]);
+    }
+    case NM_MAP: {
+        nm_value key = nm_map_key(x, i);
+        int64_t e = nm_map_find(nm_mapv(x), key);
+        return e >= 0 ? nm_mapv(x)->entries[e].val : nm_zero(x.type->elem);
+    }
+    default:
+        nm_errorf("invalid operation: cannot index %s value", x.type->name);
+        return nm_nil;
+    }
+}
+
+NM_API void nm_index_ok(nm_value x, nm_value i, nm_value *out) {
+    x = nm_raw(x);
+    if (x.type->kind != NM_MAP) {
+        nm_errorf("invalid operation: comma-ok index of %s value", x.type->name);
+    }
+    nm_value key = nm_map_key(x, i);
+    int64_t e = nm_map_find(nm_mapv(x), key);
+    out[0] = e >= 0 ? nm_mapv(x)->entries[e].val : nm_zero(x.type->elem);
+    out[1] = nm_bool(e >= 0);
+}
+
+NM_API void nm_set_index(nm_value x, nm_value i, nm_value v) {
+    x = nm_raw(x);
+    switch (x.type->kind) {
+    case NM_SLICE: {
+        int64_t at = nm_bounds(i, nm_len_of(x));
+        nm_put(&nm_slv(x)->data[at], nm_convert(v, x.type->elem));
+        return;
+    }
+    case NM_MAP: {
+        nm_value key = nm_map_key(x, i);
+        v = nm_dup(nm_convert(v, x.type->elem));
+        if (!x.u.p) {
+            nm_errorf("assignment to entry in nil map");
+        }
+        nm_map_set(nm_mapv(x), key, v);
+        return;
+    }
+    default:
+        nm_errorf("cannot assign to element of %s value", x.type->name);
+    }
+}
+
+NM_API nm_value nm_index_addr(nm_value x, nm_value i, const nm_type *t) {
+    x = nm_raw(x);
+    if (x.type->kind == NM_SLICE) {
+        int64_t at = nm_bounds(i, nm_len_of(x));
+        if (t->kind != NM_POINTER) {
+            t = nm_pointer_to(x.type->elem);
+        }
+        return nm_ref(t, &nm_slv(x)->data[at]);
+    }
+    if (x.type->kind == NM_MAP) {
+        nm_errorf("cannot take the address of a map element");
+    }
+    nm_errorf("cannot take the address of an element of %s value", x.type->name);
+    return nm_nil;
+}
+
+/* `x[low:high]`; bounds left out are given as nil. */
+NM_API nm_value nm_slice_expr(nm_value x, nm_value low, nm_value high) {
+    x = nm_raw(x);
+    int64_t len, cap;
+    int is_string = x.type->kind == NM_STRING;
+    if (x.type->kind == NM_SLICE) {
+        len = nm_len_of(x);
+        cap = x.u.p ? nm_slv(x)->cap : 0;
+    } else if (is_string) {
+        len = cap = nm_sv(x)->len;
+    } else {
+        nm_errorf("cannot slice %s value", x.type->name);
+        return nm_nil;
+    }
+    low = nm_raw(low);
+    high = nm_raw(high);
+    int64_t lo = low.type->kind == NM_NIL ? 0 : nm_index_int(low);
+    int64_t hi = high.type->kind == NM_NIL ? len : nm_index_int(high);
+    int64_t limit = is_string ? len : cap;
+    if (hi < 0 || hi > limit) {
+        nm_errorf("slice bounds out of range [:%lld] with %s %lld", (long long)hi, is_string ? "length" : "capacity", (long long)limit);
+    }
+    if (lo < 0 || lo > hi) {
+        nm_errorf("slice bounds out of range [%lld:%lld]", (long long)lo, (long long)hi);
+    }
+    if (is_string) {
+        return nm_str(nm_sv(x)->data + lo, hi - lo);
+    }
+    if (!x.u.p) {
+        return x;
+    }
+    nm_slice *s = nm_alloc(sizeof(nm_slice));
+    s->data = nm_slv(x)->data + lo;
+    s->len = hi - lo;
+    s->cap = cap - lo;
+    return nm_ref(x.type, s);
+}
+
+/* Pointers and fields */
+
+NM_API nm_value nm_alloc_ptr(const nm_type *t) {
+    nm_value *slot = nm_alloc(sizeof(nm_value));
+    *slot = nm_zero(t->elem);
+    return nm_ref(t, slot);
+}
+
+NM_API nm_value nm_ptr(const nm_type *t, nm_value *slot) {
+    return nm_ref(t, slot);
+}
+
+NM_API nm_value *nm_deref(nm_value p) {
+    p = nm_raw(p);
+    if (p.type->kind != NM_POINTER) {
+        nm_errorf("invalid operation: cannot indirect %s value", p.type->name);
+    }
+    if (!p.u.p) {
+        nm_errorf("invalid memory address or nil pointer dereference");
+    }
+    return (nm_value *)p.u.p;
+}
+
+NM_API nm_value nm_load(nm_value p) {
+    return nm_dup(*nm_deref(p));
+}
+
+NM_API void nm_store(nm_value p, nm_value v) {
+    p = nm_raw(p);
+    nm_value *slot = nm_deref(p);
+    nm_put(slot, nm_convert(v, p.type->elem));
+}
+
+NM_API int nm_field_index(const nm_type *t, const char *name, int hint) {
+    if (t->kind != NM_STRUCT) {
+        return -1;
+    }
+    if (hint >= 0 && hint < t->nfields && strcmp(t->fnames[hint], name) == 0) {
+        return hint;
+    }
+    for (int i = 0; i < t->nfields; i++) {
+        if (strcmp(t->fnames[i], name) == 0) {
+            return i;
+        }
+    }
+    return -1;
+}
+
+NM_API nm_value nm_error_method(nm_value recv);
+
+/* The field of a struct, or of the struct a pointer points to; also the
+ * method `Error` of an error. `hint` is where the field is expected. */
+NM_API nm_value nm_field(nm_value x, const char *name, int hint) {
+    nm_buf b = {0};
+    if (strcmp(name, "Error") == 0 && nm_error_message(x, &b)) {
+        return nm_error_method(nm_raw(x));
+    }
+    x = nm_raw(x);
+    int via_pointer = x.type->kind == NM_POINTER;
+    if (via_pointer) {
+        x = *nm_deref(x);
+    }
+    int i = nm_field_index(x.type, name, hint);
+    if (i < 0) {
+        nm_errorf("type %s has no field or method %s", x.type->name, name);
+    }
+    nm_value v = nm_fields(x)[i];
+    return via_pointer ? nm_dup(v) : v;
+}
+
+/* `&p.f` for the struct `p` points to, through one more pointer if `p`
+ * points to one. */
+NM_API nm_value nm_field_addr(nm_value p, const char *name, int hint, const nm_type *t) {
+    p = nm_raw(p);
+    if (p.type->kind != NM_POINTER) {
+        nm_errorf("cannot assign to a field of a non-addressable %s value", p.type->name);
+    }
+    nm_value *slot = nm_deref(p);
+    if (slot->type->kind == NM_POINTER) {
+        p = *slot;
+        slot = nm_deref(p);
+    }
+    int i = nm_field_index(slot->type, name, hint);
+    if (i < 0) {
+        nm_errorf("type %s has no field or method %s", p.type->elem->name, name);
+    }
+    if (t->kind != NM_POINTER) {
+        t = nm_pointer_to(slot->type->ftypes[i]);
+    }
+    return nm_ref(t, &nm_fields(*slot)[i]);
+}
+
+/* Composite literals and make */
+
+/* A composite literal of type `t` from key and value pairs: field indexes
+ * for structs, element indexes for slices, keys for maps. */
+NM_API nm_value nm_composite(const nm_type *t, int64_t n, const nm_value *pairs) {
+    switch (t->kind) {
+    case NM_STRUCT: {
+        nm_value v = nm_zero(t);
+        for (int64_t i = 0; i < n; i++) {
+            int64_t f = pairs[2 * i].u.i;
+            nm_fields(v)[f] = nm_convert(pairs[2 * i + 1], t->ftypes[f]);
+        }
+        return v;
+    }
+    case NM_SLICE: {
+        int64_t len = 0;
+        for (int64_t i = 0; i < n; i++) {
+            if (pairs[2 * i].u.i + 1 > len) {
+                len = pairs[2 * i].u.i + 1;
+            }
+        }
+        nm_value v = nm_slice_of(t, len, len);
+        for (int64_t i = 0; i < n; i++) {
+            nm_slv(v)->data[pairs[2 * i].u.i] = nm_dup(nm_convert(pairs[2 * i + 1], t->elem));
+        }
+        return v;
+    }
+    case NM_MAP: {
+        nm_value v = nm_ref(t, nm_map_new());
+        for (int64_t i = 0; i < n; i++) {
+            nm_value key = nm_map_key(v, pairs[2 * i]);
+            nm_map_set(nm_mapv(v), key, nm_dup(nm_convert(pairs[2 * i + 1], t->elem)));
+        }
+        return v;
+    }
+    default:
+        nm_errorf("invalid composite literal type %s", t->name);
+        return nm_nil;
+    }
+}
+
+/* `make(T, sizes...)`. */
+NM_API nm_value nm_make(const nm_type *t, int64_t n, const nm_value *args) {
+    int64_t sizes[2] = {0, 0};
+    for (int64_t i = 0; i < n; i++) {
+        nm_value a = nm_raw(args[i]);
+        if (a.type->kind != NM_INT) {
+            nm_errorf("cannot convert %s (type %s) to type int", nm_inspect(a), a.type->name);
+        }
+        if (a.u.i < 0) {
+            nm_errorf("invalid argument: index %lld must not be negative", (long long)a.u.i);
+        }
+        if (i < 2) {
+            sizes[i] = a.u.i;
+        }
+    }
+    int min = t->kind == NM_SLICE ? 1 : 0, max = min + 1;
+    if (n < min) {
+        nm_errorf("invalid operation: make(%s) expects %d or %d arguments; found %lld", t->name, min + 1, max + 1, (long long)n + 1);
+    }
+    if (n > max) {
+        nm_errorf("invalid operation: make(%s, ...) expects %d or %d arguments; found %lld", t->name, min + 1, max + 1, (long long)n + 1);
+    }
+    switch (t->kind) {
+    case NM_SLICE: {
+        int64_t cap = n > 1 ? sizes[1] : sizes[0];
+        if (sizes[0] > cap) {
+            nm_errorf("invalid argument: length and capacity swapped (%lld > %lld)", (long long)sizes[0], (long long)cap);
+        }
+        /* Go's limit on a single allocation */
+        int64_t most = ((int64_t)1 << 48) / (int64_t)sizeof(nm_value);
+        if (sizes[0] > most) {
+            nm_errorf("makeslice: len out of range");
+        }
+        if (cap > most) {
+            nm_errorf("makeslice: cap out of range");
+        }
+        return nm_slice_of(t, sizes[0], cap);
+    }
+    case NM_MAP:
+        return nm_ref(t, nm_map_new());
+    default:
+        nm_errorf("invalid argument: cannot make %s; type must be slice, map, or channel", t->name);
+        return nm_nil;
+    }
+}
+
+/* Operators. Values the program's functions took boxed are operated on
+ * as the values they hold. */
+
+NM_API double nm_number(nm_value v) {
+    return v.type->kind == NM_FLOAT ? v.u.f : (double)v.u.i;
+}
+
+NM_API int nm_is_number(nm_value v) {
+    return v.type->kind == NM_INT || v.type->kind == NM_FLOAT;
+}
+
+enum { NM_ADD, NM_SUB, NM_MUL, NM_DIV, NM_REM };
+
+NM_API nm_value nm_arith(int op, nm_value a, nm_value b) {
+    static const char *const symbols[] = {"+", "-", "*", "/", "%"};
+    a = nm_raw(a);
+    b = nm_raw(b);
+    int ka = a.type->kind, kb = b.type->kind;
+    if (ka == NM_INT && kb == NM_INT) {
+        uint64_t l = (uint64_t)a.u.i, r = (uint64_t)b.u.i;
+        switch (op) {
+        case NM_ADD:
+            return nm_int((int64_t)(l + r));
+        case NM_SUB:
+            return nm_int((int64_t)(l - r));
+        case NM_MUL:
+            return nm_int((int64_t)(l * r));
+        default:
+            if (b.u.i == 0) {
+                nm_errorf("integer divide by zero");
+            }
+            if (b.u.i == -1) {
+                return nm_int(op == NM_DIV ? (int64_t)(0 - l) : 0);
+            }
+            return nm_int(op == NM_DIV ? a.u.i / b.u.i : a.u.i % b.u.i);
+        }
+    }
+    if (nm_is_number(a) && nm_is_number(b) && (ka == NM_FLOAT || kb == NM_FLOAT) && op != NM_REM) {
+        double l = nm_number(a), r = nm_number(b);
+        switch (op) {
+        case NM_ADD:
+            return nm_float(l + r);
+        case NM_SUB:
+            return nm_float(l - r);
+        case NM_MUL:
+            return nm_float(l * r);
+        default:
+            return nm_float(l / r);
+        }
+    }
+    if (ka == NM_STRING && kb == NM_STRING && op == NM_ADD) {
+        const nm_string *l = nm_sv(a), *r = nm_sv(b);
+        if (!l->len) {
+            return b;
+        }
+        if (!r->len) {
+            return a;
+        }
+        nm_buf out = {0};
+        nm_buf_put(&out, l->data, l->len);
+        nm_buf_put(&out, r->data, r->len);
+        return nm_buf_value(&out);
+    }
+    if (nm_identical(a.type, b.type)) {
+        nm_errorf("invalid operation: operator %s not defined on %s", symbols[op], a.type->name);
+    }
+    nm_errorf("invalid operation: mismatched types %s and %s", a.type->name, b.type->name);
+    return nm_nil;
+}
+
+NM_API nm_value nm_add(nm_value a, nm_value b) {
+    if (a.type == &nm_t_int && b.type == &nm_t_int) {
+        return nm_int((int64_t)((uint64_t)a.u.i + (uint64_t)b.u.i));
+    }
+    return nm_arith(NM_ADD, a, b);
+}
+
+NM_API nm_value nm_sub(nm_value a, nm_value b) {
+    if (a.type == &nm_t_int && b.type == &nm_t_int) {
+        return nm_int((int64_t)((uint64_t)a.u.i - (uint64_t)b.u.i));
+    }
+    return nm_arith(NM_SUB, a, b);
+}
+
+NM_API nm_value nm_mul(nm_value a, nm_value b) {
+    return nm_arith(NM_MUL, a, b);
+}
+
+NM_API nm_value nm_div(nm_value a, nm_value b) {
+    return nm_arith(NM_DIV, a, b);
+}
+
+NM_API nm_value nm_rem(nm_value a, nm_value b) {
+    return nm_arith(NM_REM, a, b);
+}
+
+NM_API int nm_string_compare(const nm_string *a, const nm_string *b) {
+    int64_t n = a->len < b->len ? a->len : b->len;
+    int c = n ? memcmp(a->data, b->data, (size_t)n) : 0;
+    if (c) {
+        return c;
+    }
+    return a->len < b->len ? -1 : a->len > b->len;
+}
+
+/* `a > b`, or `a >= b` when `or_equal` is set; `a < b` is `b > a`. */
+NM_API int nm_greater(nm_value a, nm_value b, int or_equal) {
+    a = nm_raw(a);
+    b = nm_raw(b);
+    int ka = a.type->kind, kb = b.type->kind;
+    if (ka == NM_INT && kb == NM_INT) {
+        return or_equal ? a.u.i >= b.u.i : a.u.i > b.u.i;
+    }
+    if (nm_is_number(a) && nm_is_number(b)) {
+        double l = nm_number(a), r = nm_number(b);
+        return or_equal ? l >= r : l > r;
+    }
+    if (ka == NM_STRING && kb == NM_STRING) {
+        int c = nm_string_compare(nm_sv(a), nm_sv(b));
+        return or_equal ? c >= 0 : c > 0;
+    }
+    if (nm_identical(a.type, b.type)) {
+        nm_errorf("invalid operation: ordering not defined on %s", a.type->name);
+    }
+    nm_errorf("invalid operation: mismatched types %s and %s", a.type->name, b.type->name);
+    return 0;
+}
+
+NM_API nm_value nm_eq(nm_value a, nm_value b) {
+    return nm_bool(nm_equals(a, b));
+}
+
+NM_API nm_value nm_ne(nm_value a, nm_value b) {
+    return nm_bool(!nm_equals(a, b));
+}
+
+NM_API nm_value nm_lt(nm_value a, nm_value b) {
+    if (a.type == &nm_t_int && b.type == &nm_t_int) {
+        return nm_bool(a.u.i < b.u.i);
+    }
+    return nm_bool(nm_greater(b, a, 0));
+}
+
+NM_API nm_value nm_le(nm_value a, nm_value b) {
+    return nm_bool(nm_greater(b, a, 1));
+}
+
+NM_API nm_value nm_gt(nm_value a, nm_value b) {
+    return nm_bool(nm_greater(a, b, 0));
+}
+
+NM_API nm_value nm_ge(nm_value a, nm_value b) {
+    return nm_bool(nm_greater(a, b, 1));
+}
+
+NM_API nm_value nm_neg(nm_value v) {
+    v = nm_raw(v);
+    if (v.type->kind == NM_INT) {
+        return nm_int((int64_t)(0 - (uint64_t)v.u.i));
+    }
+    if (v.type->kind == NM_FLOAT) {
+        return nm_float(-v.u.f);
+    }
+    nm_errorf("invalid operation: operator - not defined on %s", v.type->name);
+    return v;
+}
+
+NM_API nm_value nm_not(nm_value v) {
+    v = nm_raw(v);
+    if (v.type->kind != NM_BOOL) {
+        nm_errorf("invalid operation: operator ! not defined on %s", v.type->name);
+    }
+    return nm_bool(!v.u.i);
+}
+
+/* The condition of a branch. */
+NM_API int nm_truth(nm_value v) {
+    v = nm_raw(v);
+    if (v.type->kind != NM_BOOL) {
+        nm_errorf("non-boolean condition in if expression: %s", v.type->name);
+    }
+    return (int)v.u.i;
+}
+
+/* Calls */
+
+NM_API nm_value nm_call(nm_value callee, int64_t argc, nm_value *argv) {
+    callee = nm_raw(callee);
+    if (callee.type->kind != NM_FUNC || !callee.u.p) {
+        nm_errorf("cannot call non-function (type %s)", callee.type->name);
+    }
+    nm_func *f = nm_funcv(callee);
+    if (argc == 1 && argv[0].type == &nm_t_tuple) {
+        /* f(g()) passes each result of g */
+        nm_value *values = argv[0].u.p;
+        argc = values[0].u.i;
+        argv = values + 1;
+    }
+    if (f->nparams >= 0 && argc != f->nparams) {
+        nm_errorf("wrong number of arguments: want=%lld, got=%lld", (long long)f->nparams, (long long)argc);
+    }
+    return f->fn(f, argc, argv);
+}
+
+/* A call whose last argument is spread, as in `append(s, t...)`. */
+NM_API nm_value nm_call_spread(nm_value callee, int64_t argc, nm_value *argv) {
+    if (argc < 2) {
+        return nm_call(callee, argc, argv);
+    }
+    int64_t n = 0;
+    nm_value *rest = nm_spread(argv[0], argv[argc - 1], &n);
+    nm_value *args = nm_alloc(sizeof(nm_value) * (size_t)(argc - 1 + n));
+    memcpy(args, argv, sizeof(nm_value) * (size_t)(argc - 1));
+    if (n) {
+        memcpy(args + argc - 1, rest, sizeof(nm_value) * (size_t)n);
+    }
+    return nm_call(callee, argc - 1 + n, args);
+}
+
+/* A closure of a function of the program over pointers to the variables
+ * it captures. */
+NM_API nm_value nm_closure(nm_fn fn, const char *name, const char *sig, int64_t nparams, int64_t nfree, const nm_value *free) {
+    nm_func *f = nm_alloc(sizeof(nm_func));
+    f->fn = fn;
+    f->name = name;
+    f->sig = sig;
+    f->nparams = nparams;
+    f->recv = nm_nil;
+    f->nfree = nfree;
+    if (nfree) {
+        f->free = nm_alloc(sizeof(nm_value *) * (size_t)nfree);
+        for (int64_t i = 0; i < nfree; i++) {
+            f->free[i] = (nm_value *)free[i].u.p;
+        }
+    }
+    return nm_ref(&nm_t_func, f);
+}
+
+NM_API nm_value nm_func_value(const nm_func *f) {
+    return nm_ref(&nm_t_func, (void *)f);
+}
+
+/* Deferred calls and recover */
+
+NM_API nm_func nm_B_recover;
+
+NM_API void nm_defer(nm_frame *fr, nm_value callee, int64_t argc, const nm_value *argv) {
+    nm_deferred *d = nm_alloc(sizeof(nm_deferred));
+    d->callee = callee;
+    d->argc = argc;
+    d->argv = nm_alloc(sizeof(nm_value) * (size_t)(argc ? argc : 1));
+    if (argc) {
+        memcpy(d->argv, argv, sizeof(nm_value) * (size_t)argc);
+    }
+    d->next = fr->defers;
+    fr->defers = d;
+}
+
+/* Runs the calls the frame deferred, last first. While a panic unwinds,
+ * the calls may recover it. A deferred call that panics in turn comes
+ * back here through the frame's handler. */
+NM_API void nm_unwind(nm_frame *fr) {
+    nm_depth = fr->depth;
+    nm_handler = fr;
+    while (fr->defers) {
+        nm_deferred *d = fr->defers;
+        fr->defers = d->next;
+        nm_value callee = nm_raw(d->callee);
+        if (callee.type->kind == NM_FUNC && callee.u.p == &nm_B_recover) {
+            /* recover stops a panic only when the deferred function calls it */
+            continue;
+        }
+        int saved = nm_recover_depth;
+        nm_recover_depth = nm_panicking ? fr->depth + 1 : -1;
+        nm_call(d->callee, d->argc, d->argv);
+        nm_recover_depth = saved;
+    }
+    nm_handler = fr->prev;
+    if (nm_panicking) {
+        if (!nm_handler) {
+            nm_die();
+        }
+        nm_depth = nm_handler->depth;
+        longjmp(nm_handler->jb, 1);
+    }
+}
+
+NM_API void nm_run_defers(nm_frame *fr) {
+    nm_unwind(fr);
+}
+
+NM_API nm_value nm_recover(void) {
+    if (nm_panicking && nm_depth == nm_recover_depth) {
+        nm_panicking = 0;
+        return nm_convert(nm_panic_value, &nm_t_any);
+    }
+    return nm_convert(nm_nil, &nm_t_any);
+}
+
+NM_API void nm_panic(nm_value v) {
+    if (v.type->kind == NM_NIL || (v.type->kind == NM_INTERFACE && !v.u.p)) {
+        v = nm_cstr("panic called with nil argument");
+    }
+    nm_throw(v);
+}
+
+/* Builtins */
+
+NM_API void nm_want_args(const char *name, int64_t argc, int64_t n) {
+    if (argc < n) {
+        nm_errorf("not enough arguments for %s: want %lld, got %lld", name, (long long)n, (long long)argc);
+    }
+    if (argc > n) {
+        nm_errorf("too many arguments for %s: want %lld, got %lld", name, (long long)n, (long long)argc);
+    }
+}
+
+NM_API void nm_invalid_argument(const char *name, nm_value arg) {
+    nm_errorf("invalid argument: %s (type %s) for built-in %s", nm_inspect(arg), arg.type->name, name);
+}
+
+NM_API nm_value nm_b_len(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    nm_want_args("len", argc, 1);
+    nm_value x = nm_raw(argv[0]);
+    int64_t n = nm_len_of(x);
+    if (n < 0) {
+        nm_invalid_argument("len", x);
+    }
+    return nm_int(n);
+}
+
+NM_API nm_value nm_b_cap(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    nm_want_args("cap", argc, 1);
+    nm_value x = nm_raw(argv[0]);
+    if (x.type->kind != NM_SLICE) {
+        nm_invalid_argument("cap", x);
+    }
+    return nm_int(x.u.p ? nm_slv(x)->cap : 0);
+}
+
+NM_API nm_value nm_b_append(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    if (argc == 0) {
+        nm_errorf("not enough arguments for append: want at least 1, got 0");
+    }
+    return nm_append_values(argv[0], argc - 1, argv + 1);
+}
+
+NM_API nm_value nm_b_copy(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    nm_want_args("copy", argc, 2);
+    nm_value dst = nm_raw(argv[0]), src = nm_raw(argv[1]);
+    if (dst.type->kind != NM_SLICE || (src.type->kind != NM_SLICE && src.type->kind != NM_STRING)) {
+        nm_errorf("invalid argument: copy expects slice arguments; found %s (type %s) and %s (type %s)", nm_inspect(dst), dst.type->name, nm_inspect(src),
+                  src.type->name);
+    }
+    const nm_type *elem = src.type->kind == NM_SLICE ? src.type->elem : &nm_t_byte;
+    if (!nm_identical(elem, dst.type->elem)) {
+        nm_errorf("invalid argument: arguments to copy %s (type %s) and %s (type %s) have different element types %s and %s", nm_inspect(dst),
+                  dst.type->name, nm_inspect(src), src.type->name, dst.type->elem->name, elem->name);
+    }
+    int64_t n = nm_len_of(src);
+    if (nm_len_of(dst) < n) {
+        n = nm_len_of(dst);
+    }
+    if (n == 0) {
+        return nm_int(0);
+    }
+    nm_value *to = nm_slv(dst)->data;
+    if (src.type->kind == NM_STRING) {
+        for (int64_t i = 0; i < n; i++) {
+            to[i] = nm_int(nm_sv(src)->data[i]);
+        }
+        return nm_int(n);
+    }
+    /* read before writing, as the two may share a backing array */
+    nm_value *values = nm_alloc(sizeof(nm_value) * (size_t)n);
+    for (int64_t i = 0; i < n; i++) {
+        values[i] = nm_dup(nm_slv(src)->data[i]);
+    }
+    for (int64_t i = 0; i < n; i++) {
+        nm_put(&to[i], values[i]);
+    }
+    free(values);
+    return nm_int(n);
+}
+
+NM_API nm_value nm_b_delete(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    nm_want_args("delete", argc, 2);
+    nm_value m = nm_raw(argv[0]);
+    if (m.type->kind != NM_MAP) {
+        nm_errorf("invalid argument: %s (type %s) is not a map", nm_inspect(m), m.type->name);
+    }
+    nm_value key = nm_map_key(m, argv[1]);
+    if (m.u.p) {
+        nm_map_delete(nm_mapv(m), key);
+    }
+    return nm_nil;
+}
+
+/* The argument that `min` or `max` picks, the first of equal ones; a NaN
+ * wins over everything, as in Go. */
+NM_API nm_value nm_extreme(const char *name, int64_t argc, nm_value *argv, int sign) {
+    if (argc == 0) {
+        nm_errorf("not enough arguments for %s: want at least 1, got 0", name);
+    }
+    nm_value best = nm_raw(argv[0]);
+    for (int64_t i = 0; i < argc; i++) {
+        nm_value arg = nm_raw(argv[i]);
+        int ka = arg.type->kind, kb = best.type->kind;
+        int order;
+        if (ka == NM_INT && kb == NM_INT) {
+            order = (arg.u.i > best.u.i) - (arg.u.i < best.u.i);
+        } else if (ka == NM_STRING && kb == NM_STRING) {
+            order = nm_string_compare(nm_sv(arg), nm_sv(best));
+        } else if (ka == NM_FLOAT && isnan(arg.u.f)) {
+            return arg;
+        } else if (nm_is_number(arg) && nm_is_number(best)) {
+            double a = nm_number(arg), b = nm_number(best);
+            if (isnan(b)) {
+                continue;
+            }
+            order = (a > b) - (a < b);
+        } else if (ka == NM_INT || ka == NM_STRING || ka == NM_FLOAT) {
+            nm_errorf("invalid argument: mismatched types %s and %s", best.type->name, arg.type->name);
+            return best;
+        } else {
+            nm_errorf("invalid argument: %s (type %s) cannot be ordered", nm_inspect(arg), arg.type->name);
+            return best;
+        }
+        if (order == sign) {
+            best = arg;
+        }
+    }
+    return best;
+}
+
+NM_API nm_value nm_b_min(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    return nm_extreme("min", argc, argv, -1);
+}
+
+NM_API nm_value nm_b_max(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    return nm_extreme("max", argc, argv, 1);
+}
+
+NM_API nm_value nm_b_clear(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    nm_want_args("clear", argc, 1);
+    nm_value x = nm_raw(argv[0]);
+    if (x.type->kind == NM_MAP) {
+        if (x.u.p) {
+            nm_map *m = nm_mapv(x);
+            free(m->slots);
+            memset(m, 0, sizeof *m);
+        }
+    } else if (x.type->kind == NM_SLICE) {
+        for (int64_t i = 0; i < nm_len_of(x); i++) {
+            nm_put(&nm_slv(x)->data[i], nm_zero(x.type->elem));
+        }
+    } else {
+        nm_errorf("invalid argument: %s (type %s) must be a map or slice", nm_inspect(x), x.type->name);
+    }
+    return nm_nil;
+}
+
+NM_API void nm_print_float(nm_buf *b, double f);
+
+/* The operands of print and println as they print them: basic values as
+ * themselves, references as addresses. */
+NM_API void nm_print_operand(nm_buf *b, const char *name, nm_value arg) {
+    arg = nm_raw(arg);
+    switch (arg.type->kind) {
+    case NM_INT:
+    case NM_STRING:
+    case NM_BOOL:
+    case NM_NIL:
+        nm_buf_inspect(b, arg, 1, 0);
+        return;
+    case NM_FLOAT:
+        nm_print_float(b, arg.u.f);
+        return;
+    case NM_POINTER:
+    case NM_MAP:
+        nm_buf_printf(b, "0x%llx", (unsigned long long)(uintptr_t)arg.u.p);
+        return;
+    default:
+        nm_errorf("illegal types for operand: %s %s (type %s)", name, nm_inspect(arg), arg.type->name);
+    }
+}
+
+NM_API nm_value nm_b_print(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    nm_buf b = {0};
+    for (int64_t i = 0; i < argc; i++) {
+        nm_print_operand(&b, "print", argv[i]);
+    }
+    nm_write(2, b.data, b.len);
+    return nm_nil;
+}
+
+NM_API nm_value nm_b_println(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    nm_buf b = {0};
+    for (int64_t i = 0; i < argc; i++) {
+        if (i) {
+            nm_buf_byte(&b, ' ');
+        }
+        nm_print_operand(&b, "println", argv[i]);
+    }
+    nm_buf_byte(&b, '\n');
+    nm_write(2, b.data, b.len);
+    return nm_nil;
+}
+
+NM_API nm_value nm_b_panic(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    nm_want_args("panic", argc, 1);
+    nm_panic(argv[0]);
+    return nm_nil;
+}
+
+NM_API nm_value nm_b_recover(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    (void)argv;
+    nm_want_args("recover", argc, 0);
+    return nm_recover();
+}
+
+NM_API nm_value nm_b_close(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)self;
+    nm_want_args("close", argc, 1);
+    nm_value c = nm_raw(argv[0]);
+    nm_errorf("invalid operation: non-chan argument %s (type %s) for built-in close", nm_inspect(c), c.type->name);
+    return nm_nil;
+}
+
+#define NM_BUILTIN(name) NM_API nm_func nm_B_##name = {nm_b_##name, #name, "builtin " #name, -1, {&nm_t_nil, {0}}, 0, NULL};
+NM_BUILTIN(len)
+NM_BUILTIN(cap)
+NM_BUILTIN(append)
+NM_BUILTIN(copy)
+NM_BUILTIN(delete)
+NM_BUILTIN(min)
+NM_BUILTIN(max)
+NM_BUILTIN(clear)
+NM_BUILTIN(print)
+NM_BUILTIN(println)
+NM_BUILTIN(panic)
+NM_BUILTIN(close)
+NM_BUILTIN(recover)
+
+/* Errors of the packages: a pointer to one of their struct types. */
+
+NM_API nm_value *nm_error_struct(nm_value v) {
+    v = nm_raw(v);
+    if (v.type->kind != NM_POINTER || !v.u.p) {
+        return NULL;
+    }
+    nm_value *s = (nm_value *)v.u.p;
+    const nm_type *t = s->type;
+    if (t == &nm_t_errorString || t == &nm_t_wrapError || t == &nm_t_NumError || t == &nm_t_PathError) {
+        return nm_fields(*s);
+    }
+    return NULL;
+}
+
+NM_API void nm_buf_quote(nm_buf *b, const uint8_t *s, int64_t n);
+
+NM_API void nm_buf_text(nm_buf *b, nm_value v) {
+    if (v.type->kind == NM_STRING) {
+        nm_buf_put(b, nm_sv(v)->data, nm_sv(v)->len);
+    }
+}
+
+/* Appends what `err.Error()` returns if `v` is an error; NULL if not. */
+NM_API const char *nm_error_message(nm_value v, nm_buf *b) {
+    nm_value *fields = nm_error_struct(v);
+    if (!fields) {
+        return NULL;
+    }
+    const nm_type *t = ((nm_value *)nm_raw(v).u.p)->type;
+    if (t == &nm_t_NumError || t == &nm_t_PathError) {
+        if (t == &nm_t_NumError) {
+            nm_buf_str(b, "strconv.");
+            nm_buf_text(b, fields[0]);
+            nm_buf_str(b, ": parsing ");
+            if (fields[1].type->kind == NM_STRING) {
+                nm_buf_quote(b, nm_sv(fields[1])->data, nm_sv(fields[1])->len);
+            }
+            nm_buf_str(b, ": ");
+        } else {
+            nm_buf_text(b, fields[0]);
+            nm_buf_byte(b, ' ');
+            nm_buf_text(b, fields[1]);
+            nm_buf_str(b, ": ");
+        }
+        if (!nm_error_message(fields[2], b)) {
+            nm_buf_str(b, "<nil>");
+        }
+        return "";
+    }
+    nm_buf_text(b, fields[0]);
+    return "";
+}
+
+/* The error `v` wraps, as its `Unwrap` method would return, or nil. */
+NM_API nm_value nm_error_unwrap(nm_value v) {
+    nm_value *fields = nm_error_struct(v);
+    if (!fields) {
+        return nm_nil;
+    }
+    const nm_type *t = ((nm_value *)nm_raw(v).u.p)->type;
+    nm_value err = nm_nil;
+    if (t == &nm_t_wrapError) {
+        err = fields[1];
+    } else if (t == &nm_t_NumError || t == &nm_t_PathError) {
+        err = fields[2];
+    }
+    return nm_raw(err).type->kind == NM_NIL ? nm_nil : err;
+}
+
+NM_API nm_value nm_make_error(const nm_type *t, int n, const nm_value *fields) {
+    nm_value s = nm_zero(t);
+    for (int i = 0; i < n; i++) {
+        nm_fields(s)[i] = nm_convert(fields[i], t->ftypes[i]);
+    }
+    nm_value *slot = nm_alloc(sizeof(nm_value));
+    *slot = s;
+    return nm_convert(nm_ref(nm_pointer_to(t), slot), &nm_t_error);
+}
+
+NM_API nm_value nm_new_error(nm_value msg) {
+    return nm_make_error(&nm_t_errorString, 1, &msg);
+}
+
+NM_API nm_value nm_new_error_c(const char *msg) {
+    return nm_new_error(nm_cstr(msg));
+}
+
+NM_API nm_value nm_error_method_fn(nm_func *self, int64_t argc, nm_value *argv) {
+    (void)argv;
+    if (argc != 0) {
+        nm_errorf("too many arguments in call to Error");
+    }
+    nm_buf b = {0};
+    nm_error_message(self->recv, &b);
+    return nm_buf_value(&b);
+}
+
+NM_API nm_value nm_error_method(nm_value recv) {
+    nm_func *f = nm_alloc(sizeof(nm_func));
+    f->fn = nm_error_method_fn;
+    f->name = "error.Error";
+    f->sig = "method error.Error";
+    f->nparams = -1;
+    f->recv = recv;
+    return nm_ref(&nm_t_func, f);
+}
+
+/* Floats as strconv.FormatFloat formats them */
+
+/* The significant digits of `a`, which is finite and not negative,
+ * without trailing zeros, and the position of the decimal point relative
+ * to them: `prec` digits after the first, or the fewest that read back as
+ * `a` when `prec` is negative. */
+NM_API int nm_float_digits(double a, int prec, char *digits, int64_t *dp) {
+    char small[64];
+    char *s = small;
+    if (prec < 0) {
+        for (int p = 0; p < 17; p++) {
+            snprintf(small, sizeof small, "%.*e", p, a);
+            if (strtod(small, NULL) == a) {
+                break;
+            }
+        }
+    } else {
+        int n = snprintf(NULL, 0, "%.*e", prec, a);
+        if (n >= (int)sizeof small) {
+            s = nm_alloc((size_t)n + 1);
+        }
+        snprintf(s, (size_t)n + 1, "%.*e", prec, a);
+    }
+    int nd = 0;
+    char *e = strchr(s, 'e');
+    for (char *c = s; c < e; c++) {
+        if (*c >= '0' && *c <= '9') {
+            digits[nd++] = *c;
+        }
+    }
+    while (nd > 0 && digits[nd - 1] == '0') {
+        nd--;
+    }
+    *dp = nd == 0 ? 0 : atoll(e + 1) + 1;
+    return nd;
+}
+
+NM_API char nm_digit_at(const char *digits, int64_t nd, int64_t i) {
+    return i >= 0 && i < nd ? digits[i] : '0';
+}
+
+/* `d.ddde±dd` with `prec` digits after the point. */
+NM_API void nm_format_exponent(nm_buf *b, const char *digits, int64_t nd, int64_t dp, int64_t prec) {
+    nm_buf_byte(b, (uint8_t)nm_digit_at(digits, nd, 0));
+    if (prec > 0) {
+        nm_buf_byte(b, '.');
+        for (int64_t i = 1; i <= prec; i++) {
+            nm_buf_byte(b, (uint8_t)nm_digit_at(digits, nd, i));
+        }
+    }
+    int64_t exp = nd == 0 ? 0 : dp - 1;
+    nm_buf_printf(b, "e%c%02lld", exp < 0 ? '-' : '+', (long long)(exp < 0 ? -exp : exp));
+}
+
+/* `ddd.ddd` with `prec` digits after the point. */
+NM_API void nm_format_fixed(nm_buf *b, const char *digits, int64_t nd, int64_t dp, int64_t prec) {
+    if (dp > 0) {
+        for (int64_t i = 0; i < dp; i++) {
+            nm_buf_byte(b, (uint8_t)nm_digit_at(digits, nd, i));
+        }
+    } else {
+        nm_buf_byte(b, '0');
+    }
+    if (prec > 0) {
+        nm_buf_byte(b, '.');
+        for (int64_t i = 0; i < prec; i++) {
+            nm_buf_byte(b, (uint8_t)nm_digit_at(digits, nd, dp + i));
+        }
+    }
+}
+
+/* Go's `strconv.FormatFloat(f, fmt, prec, 64)` for the formats 'e', 'f'
+ * and 'g'; a negative `prec` asks for the fewest digits. */
+NM_API void nm_format_float(nm_buf *b, double f, int fmt, int64_t prec) {
+    if (isnan(f)) {
+        nm_buf_str(b, "NaN");
+        return;
+    }
+    if (isinf(f)) {
+        nm_buf_str(b, f > 0 ? "+Inf" : "-Inf");
+        return;
+    }
+    if (signbit(f)) {
+        nm_buf_byte(b, '-');
+    }
+    double a = fabs(f);
+    if (fmt == 'f' && prec >= 0) {
+        nm_buf_printf(b, "%.*f", (int)prec, a);
+        return;
+    }
+    int p = -1;
+    if (fmt == 'e' && prec >= 0) {
+        p = (int)prec;
+    } else if (fmt == 'g' && prec >= 0) {
+        p = (int)(prec > 1 ? prec : 1) - 1;
+    }
+    char *digits = nm_alloc((size_t)(p > 17 ? p : 17) + 2);
+    int64_t dp;
+    int64_t nd = nm_float_digits(a, p, digits, &dp);
+    if (fmt == 'e') {
+        nm_format_exponent(b, digits, nd, dp, prec >= 0 ? prec : nd - 1);
+    } else if (fmt == 'f') {
+        nm_format_fixed(b, digits, nd, dp, nd - dp > 0 ? nd - dp : 0);
+    } else {
+        int shortest = prec < 0;
+        int64_t gprec = shortest ? nd : (prec > 1 ? prec : 1);
+        int64_t eprec = gprec;
+        if (eprec > nd && nd >= dp) {
+            eprec = nd;
+        }
+        if (shortest) {
+            eprec = 6;
+        }
+        int64_t exp = dp - 1;
+        if (exp < -4 || exp >= eprec) {
+            if (gprec > nd) {
+                gprec = nd;
+            }
+            nm_format_exponent(b, digits, nd, dp, gprec - 1);
+        } else {
+            if (gprec > dp) {
+                gprec = nd;
+            }
+            nm_format_fixed(b, digits, nd, dp, gprec - dp > 0 ? gprec - dp : 0);
+        }
+    }
+    free(digits);
+}
+
+/* A float as print and println write it: `+1.500000e+000`. */
+NM_API void nm_print_float(nm_buf *b, double f) {
+    nm_panic_message(b, nm_float(f));
+}
+
+/* Inspecting values as %v prints them */
+
+/* The order fmt prints map keys in: numbers and strings ascending, false
+ * before true, anything else as found. */
+NM_API int nm_sort_order(nm_value a, nm_value b) {
+    int ka = a.type->kind, kb = b.type->kind;
+    if (ka == NM_INT && kb == NM_INT) {
+        return (a.u.i > b.u.i) - (a.u.i < b.u.i);
+    }
+    if (ka == NM_FLOAT && kb == NM_FLOAT) {
+        int64_t x, y;
+        memcpy(&x, &a.u.f, sizeof x);
+        memcpy(&y, &b.u.f, sizeof y);
+        x ^= (int64_t)((uint64_t)(x >> 63) >> 1);
+        y ^= (int64_t)((uint64_t)(y >> 63) >> 1);
+        return (x > y) - (x < y);
+    }
+    if (ka == NM_STRING && kb == NM_STRING) {
+        int c = nm_string_compare(nm_sv(a), nm_sv(b));
+        return (c > 0) - (c < 0);
+    }
+    if (ka == NM_BOOL && kb == NM_BOOL) {
+        return (a.u.i > b.u.i) - (a.u.i < b.u.i);
+    }
+    if (ka == NM_INTERFACE && kb == NM_INTERFACE) {
+        if (!a.u.p || !b.u.p) {
+            return (a.u.p != NULL) - (b.u.p != NULL);
+        }
+        nm_value x = *(nm_value *)a.u.p, y = *(nm_value *)b.u.p;
+        int c = strcmp(x.type->name, y.type->name);
+        if (c) {
+            return (c > 0) - (c < 0);
+        }
+        return nm_sort_order(x, y);
+    }
+    return 0;
+}
+
+/* The live entries of a map, sorted stably by key. */
+NM_API nm_entry *nm_sorted_entries(nm_value m, int64_t *n) {
+    nm_map *map = nm_mapv(m);
+    *n = map ? map->count : 0;
+    nm_entry *entries = nm_alloc(sizeof(nm_entry) * (size_t)(*n ? *n : 1));
+    int64_t j = 0;
+    for (int64_t i = 0; map && i < map->n; i++) {
+        if (map->entries[i].live) {
+            entries[j++] = map->entries[i];
+        }
+    }
+    /* insertion sort keeps equal keys in order, and maps printed are small */
+    for (int64_t i = 1; i < j; i++) {
+        nm_entry e = entries[i];
+        int64_t k = i;
+        while (k > 0 && nm_sort_order(entries[k - 1].key, e.key) > 0) {
+            entries[k] = entries[k - 1];
+            k--;
+        }
+        entries[k] = e;
+    }
+    return entries;
+}
+
+NM_API void nm_buf_inspect(nm_buf *b, nm_value v, int top, int names) {
+    if (nm_error_message(v, b)) {
+        return;
+    }
+    switch (v.type->kind) {
+    case NM_INT:
+        nm_buf_printf(b, "%lld", (long long)v.u.i);
+        return;
+    case NM_FLOAT:
+        nm_format_float(b, v.u.f, 'g', -1);
+        return;
+    case NM_BOOL:
+        nm_buf_str(b, v.u.i ? "true" : "false");
+        return;
+    case NM_NIL:
+        nm_buf_str(b, "nil");
+        return;
+    case NM_STRING:
+        nm_buf_lossy(b, nm_sv(v)->data, nm_sv(v)->len);
+        return;
+    case NM_SLICE: {
+        nm_buf_byte(b, '[');
+        for (int64_t i = 0; i < nm_len_of(v); i++) {
+            if (i) {
+                nm_buf_byte(b, ' ');
+            }
+            nm_buf_inspect(b, nm_slv(v)->data[i], 0, names);
+        }
+        nm_buf_byte(b, ']');
+        return;
+    }
+    case NM_MAP: {
+        int64_t n;
+        nm_entry *entries = nm_sorted_entries(v, &n);
+        nm_buf_str(b, "map[");
+        for (int64_t i = 0; i < n; i++) {
+            if (i) {
+                nm_buf_byte(b, ' ');
+            }
+            nm_buf_inspect(b, entries[i].key, 0, names);
+            nm_buf_byte(b, ':');
+            nm_buf_inspect(b, entries[i].val, 0, names);
+        }
+        nm_buf_byte(b, ']');
+        free(entries);
+        return;
+    }
+    case NM_STRUCT:
+        nm_buf_byte(b, '{');
+        for (int i = 0; i < v.type->nfields; i++) {
+            if (i) {
+                nm_buf_byte(b, ' ');
+            }
+            if (names) {
+                nm_buf_printf(b, "%s:", v.type->fnames[i]);
+            }
+            nm_buf_inspect(b, nm_fields(v)[i], 0, names);
+        }
+        nm_buf_byte(b, '}');
+        return;
+    case NM_POINTER:
+        if (!v.u.p) {
+            nm_buf_str(b, "<nil>");
+        } else if (top && ((nm_value *)v.u.p)->type->kind == NM_STRUCT) {
+            nm_buf_byte(b, '&');
+            nm_buf_inspect(b, *(nm_value *)v.u.p, 0, names);
+        } else {
+            nm_buf_printf(b, "0x%llx", (unsigned long long)(uintptr_t)v.u.p);
+        }
+        return;
+    case NM_INTERFACE:
+        if (v.u.p) {
+            nm_buf_inspect(b, *(nm_value *)v.u.p, top, names);
+        } else {
+            nm_buf_str(b, "<nil>");
+        }
+        return;
+    case NM_FUNC:
+        nm_buf_str(b, v.u.p ? nm_funcv(v)->sig : "nil");
+        return;
+    default:
+        nm_buf_printf(b, "0x%llx", (unsigned long long)(uintptr_t)v.u.p);
+        return;
+    }
+}
+
+/* Package fmt */
+
+typedef struct {
+    int minus, plus, plus_v, zero, space, sharp;
+    /* -1 when not given */
+    int64_t width, precision;
+} nm_spec;
+
+NM_API const nm_spec nm_no_spec = {0, 0, 0, 0, 0, 0, -1, -1};
+
+/* The value inside any interfaces. */
+NM_API nm_value nm_dynamic(nm_value v) {
+    while (v.type->kind == NM_INTERFACE && v.u.p) {
+        v = *(nm_value *)v.u.p;
+    }
+    return v;
+}
+
+/* The type as `%T` prints it: the named types the program declares are
+ * qualified by its package, `main`, as those of the packages are by theirs. */
+static void nm_fmt_qualified(nm_buf *b, const nm_type *t) {
+    switch (t->kind) {
+    case NM_SLICE:
+        nm_buf_str(b, "[]");
+        nm_fmt_qualified(b, t->elem);
+        return;
+    case NM_POINTER:
+        nm_buf_str(b, "*");
+        nm_fmt_qualified(b, t->elem);
+        return;
+    case NM_CHAN:
+        nm_buf_str(b, "chan ");
+        nm_fmt_qualified(b, t->elem);
+        return;
+    case NM_MAP:
+        nm_buf_str(b, "map[");
+        nm_fmt_qualified(b, t->key);
+        nm_buf_byte(b, ']');
+        nm_fmt_qualified(b, t->elem);
+        return;
+    case NM_STRUCT:
+        if (!t->named) {
+            nm_buf_str(b, t->nfields ? "struct { " : "struct {}");
+            for (int i = 0; i < t->nfields; i++) {
+                nm_buf_str(b, i ? "; " : "");
+                nm_buf_str(b, t->fnames[i]);
+                nm_buf_byte(b, ' ');
+                nm_fmt_qualified(b, t->ftypes[i]);
+            }
+            nm_buf_str(b, t->nfields ? " }" : "");
+            return;
+        }
+        break;
+    }
+    if (t->named && !strchr(t->name, '.') && strcmp(t->name, "error") != 0) {
+        nm_buf_str(b, "main.");
+    }
+    nm_buf_str(b, t->name);
+}
+
+NM_API void nm_fmt_type_name(nm_buf *b, nm_value v) {
+    v = nm_dynamic(v);
+    if (v.type->kind == NM_NIL || v.type->kind == NM_INTERFACE) {
+        nm_buf_str(b, "<nil>");
+    } else {
+        nm_fmt_qualified(b, v.type);
+    }
+}
+
+NM_API void nm_format_verb(nm_buf *b, int32_t verb, nm_spec spec, nm_value v);
+
+NM_API void nm_bad_verb(nm_buf *b, int32_t verb, nm_value v) {
+    v = nm_dynamic(v);
+    nm_buf_str(b, "%!");
+    nm_buf_rune(b, verb);
+    nm_buf_byte(b, '(');
+    if (v.type->kind == NM_NIL || v.type->kind == NM_INTERFACE) {
+        nm_buf_str(b, "<nil>");
+    } else {
+        nm_fmt_type_name(b, v);
+        nm_buf_byte(b, '=');
+        nm_format_verb(b, 'v', nm_no_spec, v);
+    }
+    nm_buf_byte(b, ')');
+}
+
+/* Pads `body` to the width: on the right for `-`, with zeros after the
+ * sign for `0` on numbers, else with spaces on the left. */
+NM_API void nm_pad(nm_buf *b, const nm_buf *body, nm_spec spec, int numeric) {
+    int64_t len = nm_rune_count(body->data, body->len);
+    if (spec.width < 0 || len >= spec.width) {
+        nm_buf_put(b, body->data, body->len);
+        return;
+    }
+    int64_t fill = spec.width - len;
+    if (spec.minus) {
+        nm_buf_put(b, body->data, body->len);
+        for (int64_t i = 0; i < fill; i++) {
+            nm_buf_byte(b, ' ');
+        }
+    } else if (spec.zero && numeric) {
+        int64_t sign = body->len && (body->data[0] == '-' || body->data[0] == '+');
+        nm_buf_put(b, body->data, sign);
+        for (int64_t i = 0; i < fill; i++) {
+            nm_buf_byte(b, '0');
+        }
+        nm_buf_put(b, body->data + sign, body->len - sign);
+    } else {
+        for (int64_t i = 0; i < fill; i++) {
+            nm_buf_byte(b, ' ');
+        }
+        nm_buf_put(b, body->data, body->len);
+    }
+}
+
+/* `s` as a double-quoted Go string literal. */
+NM_API void nm_quote_char(nm_buf *b, int32_t c, int32_t quote) {
+    switch (c) {
+    case 7:
+        nm_buf_str(b, "\\a");
+        return;
+    case 8:
+        nm_buf_str(b, "\\b");
+        return;
+    case 12:
+        nm_buf_str(b, "\\f");
+        return;
+    case '\n':
+        nm_buf_str(b, "\\n");
+        return;
+    case '\r':
+        nm_buf_str(b, "\\r");
+        return;
+    case '\t':
+        nm_buf_str(b, "\\t");
+        return;
+    case 11:
+        nm_buf_str(b, "\\v");
+        return;
+    case '\\':
+        nm_buf_str(b, "\\\\");
+        return;
+    default:
+        break;
+    }
+    if (c == quote) {
+        nm_buf_byte(b, '\\');
+        nm_buf_byte(b, (uint8_t)c);
+    } else if (c < 0x20 || c == 0x7f) {
+        nm_buf_printf(b, "\\x%02x", c);
+    } else if ((c >= 0x80 && c < 0xa0) || c == 0xfffe || c == 0xffff) {
+        nm_buf_printf(b, "\\u%04x", c);
+    } else {
+        nm_buf_rune(b, c);
+    }
+}
+
+NM_API void nm_buf_quote(nm_buf *b, const uint8_t *s, int64_t n) {
+    nm_buf_byte(b, '"');
+    for (int64_t i = 0; i < n;) {
+        int size;
+        int32_t c = nm_decode(s + i, n - i, &size);
+        if (c == 0xfffd && size == 1) {
+            nm_buf_printf(b, "\\x%02x", s[i]);
+        } else {
+            nm_quote_char(b, c, '"');
+        }
+        i += size;
+    }
+    nm_buf_byte(b, '"');
+}
+
+NM_API int32_t nm_rune_of(int64_t i) {
+    if (i < 0 || i > 0x10ffff || (i >= 0xd800 && i < 0xe000)) {
+        return 0xfffd;
+    }
+    return (int32_t)i;
+}
+
+/* `i` in `base`, with lowercase letters for digits above 9. */
+NM_API void nm_format_radix(nm_buf *b, int64_t i, int base, int upper, int64_t precision) {
+    const char *alphabet = upper ? "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ" : "0123456789abcdefghijklmnopqrstuvwxyz";
+    uint64_t n = i < 0 ? 0 - (uint64_t)i : (uint64_t)i;
+    char digits[72];
+    int nd = 0;
+    do {
+        digits[nd++] = alphabet[n % (uint64_t)base];
+        n /= (uint64_t)base;
+    } while (n);
+    for (int64_t k = nd; k < precision; k++) {
+        nm_buf_byte(b, '0');
+    }
+    while (nd) {
+        nm_buf_byte(b, (uint8_t)digits[--nd]);
+    }
+}
+
+NM_API void nm_element(nm_buf *b, int32_t verb, nm_spec spec, nm_value v, int depth);
+
+NM_API void nm_upper(nm_buf *b, int64_t from) {
+    for (int64_t i = from; i < b->len; i++) {
+        if (b->data[i] >= 'a' && b->data[i] <= 'z') {
+            b->data[i] = (uint8_t)(b->data[i] - 'a' + 'A');
+        }
+    }
+}
+
+/* A string, or the bytes of a `[]byte`, by `verb`; the precision limits
+ * the runes formatted. */
+NM_API int nm_format_bytes(nm_buf *b, int32_t verb, nm_spec spec, const uint8_t *s, int64_t n) {
+    if (spec.precision >= 0 && (verb == 'v' || verb == 's' || verb == 'q')) {
+        int64_t end = 0;
+        for (int64_t k = 0; k < spec.precision && end < n; k++) {
+            int size;
+            nm_decode(s + end, n - end, &size);
+            end += size;
+        }
+        n = end;
+    }
+    switch (verb) {
+    case 'v':
+    case 's':
+        nm_buf_put(b, s, n);
+        return 1;
+    case 'q':
+        nm_buf_quote(b, s, n);
+        return 1;
+    case 'x':
+    case 'X':
+        for (int64_t i = 0; i < n; i++) {
+            if (i && spec.space) {
+                nm_buf_byte(b, ' ');
+            }
+            nm_buf_printf(b, verb == 'x' ? "%02x" : "%02X", s[i]);
+        }
+        return 1;
+    default:
+        return 0;
+    }
+}
+
+/* The operand formatted by `verb`; sets `*numeric` for a number the `0`
+ * flag pads with zeros. Returns 0 if the verb does not apply to its type. */
+NM_API int nm_format_value(nm_buf *b, int32_t verb, nm_spec spec, nm_value v, int depth, int *numeric) {
+    *numeric = 0;
+    if (verb == 'v' || verb == 's' || verb == 'q' || verb == 'x' || verb == 'X') {
+        nm_buf msg = {0};
+        if (nm_error_message(v, &msg)) {
+            return nm_format_bytes(b, verb, spec, msg.data, msg.len);
+        }
+    }
+    switch (v.type->kind) {
+    case NM_INTERFACE:
+        if (v.u.p) {
+            return nm_format_value(b, verb, spec, *(nm_value *)v.u.p, depth, numeric);
+        }
+        /* fallthrough */
+    case NM_NIL:
+        if (verb == 'v') {
+            nm_buf_str(b, "<nil>");
+            return 1;
+        }
+        return 0;
+    case NM_BOOL:
+        if (verb == 'v' || verb == 't') {
+            nm_buf_str(b, v.u.i ? "true" : "false");
+            return 1;
+        }
+        return 0;
+    case NM_INT: {
+        int64_t i = v.u.i;
+        const char *sign = i < 0 ? "-" : spec.plus ? "+" : spec.space ? " " : "";
+        *numeric = 1;
+        switch (verb) {
+        case 'v':
+        case 'd':
+            nm_buf_str(b, sign);
+            nm_format_radix(b, i, 10, 0, spec.precision);
+            return 1;
+        case 'b':
+            nm_buf_str(b, sign);
+            nm_buf_str(b, spec.sharp ? "0b" : "");
+            nm_format_radix(b, i, 2, 0, spec.precision);
+            return 1;
+        case 'o':
+            nm_buf_str(b, sign);
+            nm_buf_str(b, spec.sharp ? "0" : "");
+            nm_format_radix(b, i, 8, 0, spec.precision);
+            return 1;
+        case 'O':
+            nm_buf_str(b, sign);
+            nm_buf_str(b, "0o");
+            nm_format_radix(b, i, 8, 0, spec.precision);
+            return 1;
+        case 'x':
+            nm_buf_str(b, sign);
+            nm_buf_str(b, spec.sharp ? "0x" : "");
+            nm_format_radix(b, i, 16, 0, spec.precision);
+            return 1;
+        case 'X':
+            nm_buf_str(b, sign);
+            nm_buf_str(b, spec.sharp ? "0X" : "");
+            nm_format_radix(b, i, 16, 1, spec.precision);
+            return 1;
+        case 'c':
+            *numeric = 0;
+            nm_buf_rune(b, nm_rune_of(i));
+            return 1;
+        case 'q':
+            *numeric = 0;
+            nm_buf_byte(b, '\'');
+            nm_quote_char(b, nm_rune_of(i), '\'');
+            nm_buf_byte(b, '\'');
+            return 1;
+        case 'U':
+            nm_buf_printf(b, "U+%04llX", (unsigned long long)i);
+            return 1;
+        default:
+            *numeric = 0;
+            return 0;
+        }
+    }
+    case NM_FLOAT: {
+        double f = v.u.f;
+        int64_t start = b->len;
+        if (verb == 'v' || verb == 'g' || verb == 'G') {
+            nm_format_float(b, f, 'g', spec.precision);
+        } else if (verb == 'f' || verb == 'F') {
+            nm_format_float(b, f, 'f', spec.precision >= 0 ? spec.precision : 6);
+        } else if (verb == 'e' || verb == 'E') {
+            nm_format_float(b, f, 'e', spec.precision >= 0 ? spec.precision : 6);
+        } else {
+            return 0;
+        }
+        if (verb == 'G' || verb == 'E') {
+            nm_upper(b, start);
+        }
+        if ((spec.plus || spec.space) && b->data[start] != '-' && b->data[start] != '+') {
+            nm_buf_byte(b, 0);
+            memmove(b->data + start + 1, b->data + start, (size_t)(b->len - start - 1));
+            b->data[start] = spec.plus ? '+' : ' ';
+        }
+        *numeric = isfinite(f);
+        return 1;
+    }
+    case NM_STRING:
+        return nm_format_bytes(b, verb, spec, nm_sv(v)->data, nm_sv(v)->len);
+    case NM_SLICE: {
+        int64_t n = nm_len_of(v);
+        if (v.type->elem->kind == NM_BYTE && (verb == 's' || verb == 'q' || verb == 'x' || verb == 'X')) {
+            uint8_t *bytes = nm_alloc((size_t)(n ? n : 1));
+            for (int64_t i = 0; i < n; i++) {
+                bytes[i] = (uint8_t)nm_slv(v)->data[i].u.i;
+            }
+            int ok = nm_format_bytes(b, verb, spec, bytes, n);
+            free(bytes);
+            return ok;
+        }
+        nm_buf_byte(b, '[');
+        for (int64_t i = 0; i < n; i++) {
+            if (i) {
+                nm_buf_byte(b, ' ');
+            }
+            nm_element(b, verb, spec, nm_slv(v)->data[i], depth);
+        }
+        nm_buf_byte(b, ']');
+        return 1;
+    }
+    case NM_MAP: {
+        int64_t n;
+        nm_entry *entries = nm_sorted_entries(v, &n);
+        nm_buf_str(b, "map[");
+        for (int64_t i = 0; i < n; i++) {
+            if (i) {
+                nm_buf_byte(b, ' ');
+            }
+            nm_element(b, verb, spec, entries[i].key, depth);
+            nm_buf_byte(b, ':');
+            nm_element(b, verb, spec, entries[i].val, depth);
+        }
+        nm_buf_byte(b, ']');
+        free(entries);
+        return 1;
+    }
+    case NM_STRUCT:
+        nm_buf_byte(b, '{');
+        for (int i = 0; i < v.type->nfields; i++) {
+            if (i) {
+                nm_buf_byte(b, ' ');
+            }
+            if (spec.plus_v) {
+                nm_buf_printf(b, "%s:", v.type->fnames[i]);
+            }
+            nm_element(b, verb, spec, nm_fields(v)[i], depth);
+        }
+        nm_buf_byte(b, '}');
+        return 1;
+    case NM_POINTER: {
+        nm_value *target = (nm_value *)v.u.p;
+        if (target && depth == 0 && target->type->kind == NM_STRUCT) {
+            nm_buf body = {0};
+            int inner;
+            if (!nm_format_value(&body, verb, spec, *target, depth + 1, &inner)) {
+                return 0;
+            }
+            nm_buf_byte(b, '&');
+            nm_buf_put(b, body.data, body.len);
+            return 1;
+        }
+        if (verb == 'v' || verb == 'p') {
+            if (target) {
+                nm_buf_printf(b, "0x%llx", (unsigned long long)(uintptr_t)target);
+            } else {
+                nm_buf_str(b, verb == 'v' ? "<nil>" : "0x0");
+            }
+            return 1;
+        }
+        return 0;
+    }
+    default:
+        if (verb == 'v') {
+            nm_buf_inspect(b, v, 1, 0);
+            return 1;
+        }
+        return 0;
+    }
+}
+
+/* An element of a slice, map or struct, formatted by the same verb; a bad
+ * verb shows in place of the element alone. */
+NM_API void nm_element(nm_buf *b, int32_t verb, nm_spec spec, nm_value v, int depth) {
+    nm_spec inner = spec;
+    inner.width = -1;
+    nm_buf body = {0};
+    int numeric;
+    if (nm_format_value(&body, verb, inner, v, depth + 1, &numeric)) {
+        nm_pad(b, &body, spec, numeric);
+    } else {
+        nm_bad_verb(b, verb, v);
+    }
+}
+
+/* One operand formatted by `verb`, then padded to the width. */
+NM_API void nm_format_verb(nm_buf *b, int32_t verb, nm_spec spec, nm_value v) {
+    nm_buf body = {0};
+    if (verb == 'T') {
+        nm_fmt_type_name(&body, v);
+        nm_pad(b, &body, spec, 0);
+        return;
+    }
+    int numeric;
+    if (nm_format_value(&body, verb, spec, v, 0, &numeric)) {
+        nm_pad(b, &body, spec, numeric);
+    } else {
+        nm_bad_verb(b, verb, v);
+    }
+}
+
+/* The operands as `Print` writes them: spaces between operands when
+ * neither is a string. */
+NM_API void nm_sprint(nm_buf *b, int64_t argc, const nm_value *argv) {
+    int prev_string = 0;
+    for (int64_t i = 0; i < argc; i++) {
+        int is_string = nm_dynamic(argv[i]).type->kind == NM_STRING;
+        if (i > 0 && !is_string && !prev_string) {
+            nm_buf_byte(b, ' ');
+        }
+        nm_format_verb(b, 'v', nm_no_spec, argv[i]);
+        prev_string = is_string;
+    }
+}
+
+NM_API void nm_sprintln(nm_buf *b, int64_t argc, const nm_value *argv) {
+    for (int64_t i = 0; i < argc; i++) {
+        if (i > 0) {
+            nm_buf_byte(b, ' ');
+        }
+        nm_format_verb(b, 'v', nm_no_spec, argv[i]);
+    }
+    nm_buf_byte(b, '\n');
+}
+
+/* A width or precision: digits, or `*` for the next operand. Returns 0
+ * for a bad `*` operand. */
+NM_API int nm_fmt_number(const nm_string *f, int64_t *i, int64_t argc, const nm_value *argv, int64_t *next, int64_t *out) {
+    *out = -1;
+    if (*i < f->len && f->data[*i] == '*') {
+        (*i)++;
+        int64_t k = (*next)++;
+        nm_value arg = k < argc ? nm_dynamic(argv[k]) : nm_nil;
+        if (arg.type->kind == NM_INT && arg.u.i >= 0) {
+            *out = arg.u.i;
+            return 1;
+        }
+        return 0;
+    }
+    int64_t n = 0;
+    int any = 0, overflow = 0;
+    while (*i < f->len && f->data[*i] >= '0' && f->data[*i] <= '9') {
+        if (n > (INT64_MAX - 9) / 10) {
+            overflow = 1;
+        }
+        n = n * 10 + (f->data[*i] - '0');
+        any = 1;
+        (*i)++;
+    }
+    if (any && !overflow) {
+        *out = n;
+    }
+    return 1;
+}
+
+/* `format` with its verbs replaced by the formatted operands. Stores the
+ * operand of the first `%w` in `*wrapped` when it is given, for Errorf. */
+NM_API void nm_sprintf(nm_buf *b, const nm_string *f, int64_t argc, const nm_value *argv, nm_value *wrapped) {
+    int64_t next = 0;
+    int64_t i = 0;
+    while (i < f->len) {
+        if (f->data[i] != '%') {
+            int64_t end = i;
+            while (end < f->len && f->data[end] != '%') {
+                end++;
+            }
+            nm_buf_put(b, f->data + i, end - i);
+            i = end;
+            continue;
+        }
+        i++;
+        nm_spec spec = nm_no_spec;
+        for (; i < f->len; i++) {
+            uint8_t c = f->data[i];
+            if (c == '-') {
+                spec.minus = 1;
+            } else if (c == '+') {
+                spec.plus = 1;
+            } else if (c == '0') {
+                spec.zero = 1;
+            } else if (c == ' ') {
+                spec.space = 1;
+            } else if (c == '#') {
+                spec.sharp = 1;
+            } else {
+                break;
+            }
+        }
+        if (!nm_fmt_number(f, &i, argc, argv, &next, &spec.width)) {
+            nm_buf_str(b, "%!(BADWIDTH)");
+        }
+        if (i < f->len && f->data[i] == '.') {
+            i++;
+            int64_t precision;
+            if (nm_fmt_number(f, &i, argc, argv, &next, &precision)) {
+                spec.precision = precision < 0 ? 0 : precision;
+            } else {
+                nm_buf_str(b, "%!(BADPREC)");
+            }
+        }
+        if (i >= f->len) {
+            nm_buf_str(b, "%!(NOVERB)");
+            break;
+        }
+        int size;
+        int32_t verb = nm_decode(f->data + i, f->len - i, &size);
+        i += size;
+        if (verb == '%') {
+            nm_buf_byte(b, '%');
+            continue;
+        }
+        if (next >= argc) {
+            nm_buf_str(b, "%!");
+            nm_buf_rune(b, verb);
+            nm_buf_str(b, "(MISSING)");
+            continue;
+        }
+        nm_value arg = argv[next++];
+        if (verb == 'v' || verb == 'w') {
+            spec.plus_v = spec.plus;
+            spec.plus = 0;
+        }
+        if (verb == 'w') {
+            nm_buf msg = {0};
+            if (wrapped && nm_error_message(arg, &msg)) {
+                if (nm_is_nil(*wrapped)) {
+                    *wrapped = arg;
+                }
+                nm_format_verb(b, 'v', spec, arg);
+            } else {
+                nm_bad_verb(b, 'w', arg);
+            }
+            continue;
+        }
+        nm_format_verb(b, verb, spec, arg);
+    }
+    if (next < argc) {
+        nm_buf_str(b, "%!(EXTRA ");
+        for (int64_t k = next; k < argc; k++) {
+            if (k > next) {
+                nm_buf_str(b, ", ");
+            }
+            nm_fmt_type_name(b, argv[k]);
+            nm_buf_byte(b, '=');
+            nm_format_verb(b, 'v', nm_no_spec, argv[k]);
+        }
+        nm_buf_byte(b, ')');
+    }
+}
+
+NM_API void nm_want_package_args(const char *name, int64_t argc, int64_t n) {
+    if (argc < n) {
+        nm_errorf("not enough arguments in call to %s", name);
+    }
+    if (argc > n) {
+        nm_errorf("too many arguments in call to %s", name);
+    }
+}
+
+NM_API const nm_string *nm_string_arg(const char *name, nm_value arg) {
+    nm_value v = nm_raw(arg);
+    if (v.type->kind != NM_STRING) {
+        nm_errorf("cannot use %s (type %s) as string value in argument to %s", nm_inspect(v), v.type->name, name);
+    }
+    return nm_sv(v);
+}
+
+NM_API int64_t nm_int_arg(const char *name, nm_value arg) {
+    nm_value v = nm_raw(arg);
+    if (v.type->kind != NM_INT) {
+        nm_errorf("cannot use %s (type %s) as int value in argument to %s", nm_inspect(v), v.type->name, name);
+    }
+    return v.u.i;
+}
+
+NM_API double nm_float_arg(const char *name, nm_value arg) {
+    nm_value v = nm_raw(arg);
+    if (!nm_is_number(v)) {
+        nm_errorf("cannot use %s (type %s) as float64 value in argument to %s", nm_inspect(v), v.type->name, name);
+    }
+    return nm_number(v);
+}
+
+NM_API const nm_string *nm_format_arg(const char *name, int64_t argc, nm_value *argv) {
+    if (argc == 0) {
+        nm_errorf("not enough arguments in call to %s", name);
+    }
+    return nm_string_arg(name, argv[0]);
+}
+
+/* Package functions: nm_p_<path>_<Name>, and the function values of them,
+ * nm_P_<path>_<Name>. */
+#define NM_PACKAGE(path, name, sig) \
+    NM_API nm_value nm_p_##path##_##name(nm_func *self, int64_t argc, nm_value *argv); \
+    NM_API nm_func nm_P_##path##_##name = {nm_p_##path##_##name, #path "." #name, sig, -1, {&nm_t_nil, {0}}, 0, NULL}; \
+    NM_API nm_value nm_p_##path##_##name(nm_func *self, int64_t argc, nm_value *argv)
+
+NM_PACKAGE(fmt, Print, "builtin fmt.Print") {
+    (void)self;
+    nm_buf b = {0};
+    nm_sprint(&b, argc, argv);
+    nm_write(1, b.data, b.len);
+    return nm_nil;
+}
+
+NM_PACKAGE(fmt, Println, "builtin fmt.Println") {
+    (void)self;
+    nm_buf b = {0};
+    nm_sprintln(&b, argc, argv);
+    nm_write(1, b.data, b.len);
+    return nm_nil;
+}
+
+NM_PACKAGE(fmt, Printf, "builtin fmt.Printf") {
+    (void)self;
+    const nm_string *f = nm_format_arg("fmt.Printf", argc, argv);
+    nm_buf b = {0};
+    nm_sprintf(&b, f, argc - 1, argv + 1, NULL);
+    nm_write(1, b.data, b.len);
+    return nm_nil;
+}
+
+NM_PACKAGE(fmt, Sprint, "builtin fmt.Sprint") {
+    (void)self;
+    nm_buf b = {0};
+    nm_sprint(&b, argc, argv);
+    return nm_buf_value(&b);
+}
+
+NM_PACKAGE(fmt, Sprintln, "builtin fmt.Sprintln") {
+    (void)self;
+    nm_buf b = {0};
+    nm_sprintln(&b, argc, argv);
+    return nm_buf_value(&b);
+}
+
+NM_PACKAGE(fmt, Sprintf, "builtin fmt.Sprintf") {
+    (void)self;
+    const nm_string *f = nm_format_arg("fmt.Sprintf", argc, argv);
+    nm_buf b = {0};
+    nm_sprintf(&b, f, argc - 1, argv + 1, NULL);
+    return nm_buf_value(&b);
+}
+
+/* The descriptor of `v` if it is an `*os.File`, else -1. */
+NM_API int64_t nm_descriptor(nm_value v) {
+    v = nm_raw(v);
+    if (v.type->kind != NM_POINTER || !v.u.p || ((nm_value *)v.u.p)->type != &nm_t_File) {
+        return -1;
+    }
+    return nm_fields(*(nm_value *)v.u.p)[0].u.i;
+}
+
+NM_API int nm_file_arg(const char *name, int64_t argc, nm_value *argv) {
+    if (argc == 0) {
+        nm_errorf("not enough arguments in call to %s", name);
+    }
+    int64_t fd = nm_descriptor(argv[0]);
+    if (fd < 0) {
+        nm_errorf("cannot use %s (type %s) as io.Writer value in argument to %s", nm_inspect(argv[0]), argv[0].type->name, name);
+    }
+    return fd == 1 ? 1 : 2;
+}
+
+NM_PACKAGE(fmt, Fprint, "builtin fmt.Fprint") {
+    (void)self;
+    if (argc == 0) {
+        nm_errorf("not enough arguments in call to fmt.Fprint");
+    }
+    nm_buf b = {0};
+    nm_sprint(&b, argc - 1, argv + 1);
+    nm_write(nm_file_arg("fmt.Fprint", argc, argv), b.data, b.len);
+    return nm_nil;
+}
+
+NM_PACKAGE(fmt, Fprintln, "builtin fmt.Fprintln") {
+    (void)self;
+    if (argc == 0) {
+        nm_errorf("not enough arguments in call to fmt.Fprintln");
+    }
+    nm_buf b = {0};
+    nm_sprintln(&b, argc - 1, argv + 1);
+    nm_write(nm_file_arg("fmt.Fprintln", argc, argv), b.data, b.len);
+    return nm_nil;
+}
+
+NM_PACKAGE(fmt, Fprintf, "builtin fmt.Fprintf") {
+    (void)self;
+    if (argc == 0) {
+        nm_errorf("not enough arguments in call to fmt.Fprintf");
+    }
+    const nm_string *f = nm_format_arg("fmt.Fprintf", argc - 1, argv + 1);
+    nm_buf b = {0};
+    nm_sprintf(&b, f, argc - 2, argv + 2, NULL);
+    nm_write(nm_file_arg("fmt.Fprintf", argc, argv), b.data, b.len);
+    return nm_nil;
+}
+
+/* `fmt.Errorf(format, args...)`: an error with the formatted message,
+ * wrapping the operand of a `%w`. */
+NM_PACKAGE(fmt, Errorf, "builtin fmt.Errorf") {
+    (void)self;
+    const nm_string *f = nm_format_arg("fmt.Errorf", argc, argv);
+    nm_buf b = {0};
+    nm_value wrapped = nm_nil;
+    nm_sprintf(&b, f, argc - 1, argv + 1, &wrapped);
+    nm_buf lossy = {0};
+    nm_buf_lossy(&lossy, b.data, b.len);
+    if (nm_is_nil(wrapped)) {
+        return nm_new_error(nm_buf_value(&lossy));
+    }
+    nm_value fields[2] = {nm_buf_value(&lossy), nm_convert(wrapped, &nm_t_error)};
+    return nm_make_error(&nm_t_wrapError, 2, fields);
+}
+
+/* Package errors */
+
+NM_PACKAGE(errors, New, "builtin errors.New") {
+    (void)self;
+    nm_want_package_args("errors.New", argc, 1);
+    const nm_string *msg = nm_string_arg("errors.New", argv[0]);
+    nm_buf b = {0};
+    nm_buf_lossy(&b, msg->data, msg->len);
+    return nm_new_error(nm_buf_value(&b));
+}
+
+/* `errors.Is(err, target)`: whether any error in the chain `err` starts
+ * is `target`. */
+NM_PACKAGE(errors, Is, "builtin errors.Is") {
+    (void)self;
+    nm_want_package_args("errors.Is", argc, 2);
+    nm_value err = argv[0];
+    for (;;) {
+        if (nm_equals(err, argv[1])) {
+            return nm_bool(1);
+        }
+        err = nm_error_unwrap(err);
+        if (nm_is_nil(err)) {
+            return nm_bool(0);
+        }
+    }
+}
+
+/* `errors.As(err, &target)`: stores the first error in the chain whose
+ * type is that of `target` into it. */
+NM_PACKAGE(errors, As, "builtin errors.As") {
+    (void)self;
+    nm_want_package_args("errors.As", argc, 2);
+    nm_value target = nm_raw(argv[1]);
+    if (target.type->kind != NM_POINTER || !target.u.p) {
+        nm_errorf("errors: target must be a non-nil pointer");
+    }
+    nm_value err = argv[0];
+    for (;;) {
+        nm_value dynamic = nm_raw(err);
+        if (target.type->elem->kind == NM_INTERFACE || nm_identical(dynamic.type, target.type->elem)) {
+            nm_store(target, dynamic);
+            return nm_bool(1);
+        }
+        err = nm_error_unwrap(err);
+        if (nm_is_nil(err)) {
+            return nm_bool(0);
+        }
+    }
+}
+
+NM_PACKAGE(errors, Unwrap, "builtin errors.Unwrap") {
+    (void)self;
+    nm_want_package_args("errors.Unwrap", argc, 1);
+    nm_value err = nm_error_unwrap(argv[0]);
+    return nm_is_nil(err) ? nm_zero(&nm_t_error) : err;
+}
+
+/* Package strconv */
+
+NM_API nm_value nm_v_strconv_ErrSyntax(void) {
+    static nm_value err;
+    if (!err.type) {
+        err = nm_new_error_c("invalid syntax");
+    }
+    return err;
+}
+
+NM_API nm_value nm_v_strconv_ErrRange(void) {
+    static nm_value err;
+    if (!err.type) {
+        err = nm_new_error_c("value out of range");
+    }
+    return err;
+}
+
+NM_API nm_value nm_num_error(const char *func, const nm_string *num, nm_value err) {
+    nm_value fields[3] = {nm_cstr(func), nm_str(num->data, num->len), err};
+    return nm_make_error(&nm_t_NumError, 3, fields);
+}
+
+NM_API int nm_digit_value(uint8_t c) {
+    if (c >= '0' && c <= '9') {
+        return c - '0';
+    }
+    if (c >= 'a' && c <= 'z') {
+        return c - 'a' + 10;
+    }
+    if (c >= 'A' && c <= 'Z') {
+        return c - 'A' + 10;
+    }
+    return 99;
+}
+
+/* Parses an integer in `base`, or in the base its prefix gives when
+ * `base` is 0, as Go's ParseInt does. Returns the error it fails with,
+ * or nil. */
+NM_API nm_value nm_parse_integer(const nm_string *str, int base, int64_t *out) {
+    const uint8_t *s = str->data;
+    int64_t n = str->len, i = 0;
+    int negative = 0;
+    if (n > 0 && s[0] == '-') {
+        negative = 1;
+        i = 1;
+    } else if (n > 0 && s[0] == '+') {
+        i = 1;
+    }
+    int underscores = 0;
+    if (base == 0) {
+        underscores = 1;
+        base = 10;
+        if (n - i >= 2 && s[i] == '0' && (s[i + 1] | 0x20) == 'x') {
+            base = 16;
+            i += 2;
+        } else if (n - i >= 2 && s[i] == '0' && (s[i + 1] | 0x20) == 'b') {
+            base = 2;
+            i += 2;
+        } else if (n - i >= 2 && s[i] == '0' && (s[i + 1] | 0x20) == 'o') {
+            base = 8;
+            i += 2;
+        } else if (n - i > 1 && s[i] == '0') {
+            base = 8;
+            i += 1;
+        }
+    }
+    uint64_t magnitude = 0;
+    int digits = 0, overflow = 0;
+    for (; i < n; i++) {
+        if (underscores && s[i] == '_') {
+            continue;
+        }
+        int d = nm_digit_value(s[i]);
+        if (d >= base) {
+            return nm_v_strconv_ErrSyntax();
+        }
+        digits++;
+        if (magnitude > (UINT64_MAX - (uint64_t)d) / (uint64_t)base) {
+            overflow = 1;
+        }
+        magnitude = magnitude * (uint64_t)base + (uint64_t)d;
+    }
+    if (!digits) {
+        return nm_v_strconv_ErrSyntax();
+    }
+    if (overflow || magnitude > (negative ? (uint64_t)1 << 63 : (uint64_t)INT64_MAX)) {
+        return nm_v_strconv_ErrRange();
+    }
+    *out = negative ? (int64_t)(0 - magnitude) : (int64_t)magnitude;
+    return nm_nil;
+}
+
+NM_PACKAGE(strconv, Itoa, "builtin strconv.Itoa") {
+    (void)self;
+    nm_want_package_args("strconv.Itoa", argc, 1);
+    nm_buf b = {0};
+    nm_buf_printf(&b, "%lld", (long long)nm_int_arg("strconv.Itoa", argv[0]));
+    return nm_buf_value(&b);
+}
+
+/* `strconv.Atoi(s)`: the int `s` holds in base 10, and a
+ * `*strconv.NumError` if it holds none. */
+NM_PACKAGE(strconv, Atoi, "builtin strconv.Atoi") {
+    (void)self;
+    nm_want_package_args("strconv.Atoi", argc, 1);
+    const nm_string *s = nm_string_arg("strconv.Atoi", argv[0]);
+    int64_t n = 0;
+    nm_value cause = nm_parse_integer(s, 10, &n);
+    nm_value results[2] = {nm_int(n), nm_zero(&nm_t_error)};
+    if (!nm_is_nil(cause)) {
+        int range = nm_equals(cause, nm_v_strconv_ErrRange());
+        results[0] = nm_int(!range ? 0 : s->len && s->data[0] == '-' ? INT64_MIN : INT64_MAX);
+        results[1] = nm_num_error("Atoi", s, cause);
+    }
+    return nm_tuple(2, results);
+}
+
+NM_PACKAGE(strconv, ParseInt, "builtin strconv.ParseInt") {
+    (void)self;
+    nm_want_package_args("strconv.ParseInt", argc, 3);
+    const nm_string *s = nm_string_arg("strconv.ParseInt", argv[0]);
+    int64_t base = nm_int_arg("strconv.ParseInt", argv[1]);
+    int64_t bits = nm_int_arg("strconv.ParseInt", argv[2]);
+    nm_value results[2] = {nm_int(0), nm_zero(&nm_t_error)};
+    if (base != 0 && (base < 2 || base > 36)) {
+        nm_buf b = {0};
+        nm_buf_printf(&b, "invalid base %lld", (long long)base);
+        results[1] = nm_num_error("ParseInt", s, nm_new_error(nm_buf_value(&b)));
+        return nm_tuple(2, results);
+    }
+    bits = bits == 0 ? 64 : bits < 1 ? 1 : bits > 64 ? 64 : bits;
+    int64_t n = 0;
+    nm_value cause = nm_parse_integer(s, (int)base, &n);
+    if (nm_is_nil(cause) && bits < 64) {
+        int64_t max = ((int64_t)1 << (bits - 1)) - 1, min = -max - 1;
+        if (n < min || n > max) {
+            cause = nm_v_strconv_ErrRange();
+        }
+    }
+    if (nm_is_nil(cause)) {
+        results[0] = nm_int(n);
+    } else {
+        results[1] = nm_num_error("ParseInt", s, cause);
+    }
+    return nm_tuple(2, results);
+}
+
+/* Whether `s` is a decimal float as Rust's parser reads it. */
+NM_API int nm_float_syntax(const char *s) {
+    if (*s == '+' || *s == '-') {
+        s++;
+    }
+    int digits = 0;
+    while (*s >= '0' && *s <= '9') {
+        s++;
+        digits++;
+    }
+    if (*s == '.') {
+        s++;
+        while (*s >= '0' && *s <= '9') {
+            s++;
+            digits++;
+        }
+    }
+    if (!digits) {
+        return 0;
+    }
+    if (*s == 'e' || *s == 'E') {
+        s++;
+        if (*s == '+' || *s == '-') {
+            s++;
+        }
+        if (!(*s >= '0' && *s <= '9')) {
+            return 0;
+        }
+        while (*s >= '0' && *s <= '9') {
+            s++;
+        }
+    }
+    return *s == 0;
+}
+
+/* `strconv.ParseFloat(s, bitSize)`. A value too large is ±Inf with a
+ * range error. */
+NM_PACKAGE(strconv, ParseFloat, "builtin strconv.ParseFloat") {
+    (void)self;
+    nm_want_package_args("strconv.ParseFloat", argc, 2);
+    const nm_string *s = nm_string_arg("strconv.ParseFloat", argv[0]);
+    nm_int_arg("strconv.ParseFloat", argv[1]);
+    nm_buf text = {0};
+    for (int64_t i = 0; i < s->len; i++) {
+        if (s->data[i] == '_') {
+            continue;
+        }
+        if (s->data[i] == 0) {
+            nm_buf_byte(&text, '?');
+            continue;
+        }
+        nm_buf_byte(&text, s->data[i]);
+    }
+    const char *t = nm_buf_cstr(&text);
+    const char *u = t;
+    int sign = 1;
+    if (*u == '+' || *u == '-') {
+        sign = *u == '-' ? -1 : 1;
+        u++;
+    }
+    nm_value results[2] = {nm_float(0), nm_zero(&nm_t_error)};
+    if (strcasecmp(u, "inf") == 0 || strcasecmp(u, "infinity") == 0) {
+        results[0] = nm_float(sign * HUGE_VAL);
+    } else if (strcasecmp(u, "nan") == 0) {
+        results[0] = nm_float(NAN);
+    } else if (nm_float_syntax(t)) {
+        double f = strtod(t, NULL);
+        results[0] = nm_float(f);
+        if (isinf(f)) {
+            results[1] = nm_num_error("ParseFloat", s, nm_v_strconv_ErrRange());
+        }
+    } else {
+        results[1] = nm_num_error("ParseFloat", s, nm_v_strconv_ErrSyntax());
+    }
+    return nm_tuple(2, results);
+}
+
+NM_PACKAGE(strconv, ParseBool, "builtin strconv.ParseBool") {
+    (void)self;
+    nm_want_package_args("strconv.ParseBool", argc, 1);
+    const nm_string *s = nm_string_arg("strconv.ParseBool", argv[0]);
+    static const char *const yes[] = {"1", "t", "T", "TRUE", "true", "True"};
+    static const char *const no[] = {"0", "f", "F", "FALSE", "false", "False"};
+    nm_value results[2] = {nm_bool(0), nm_zero(&nm_t_error)};
+    for (int i = 0; i < 6; i++) {
+        if ((int64_t)strlen(yes[i]) == s->len && memcmp(yes[i], s->data, (size_t)s->len) == 0) {
+            results[0] = nm_bool(1);
+            return nm_tuple(2, results);
+        }
+        if ((int64_t)strlen(no[i]) == s->len && memcmp(no[i], s->data, (size_t)s->len) == 0) {
+            return nm_tuple(2, results);
+        }
+    }
+    results[1] = nm_num_error("ParseBool", s, nm_v_strconv_ErrSyntax());
+    return nm_tuple(2, results);
+}
+
+NM_PACKAGE(strconv, FormatInt, "builtin strconv.FormatInt") {
+    (void)self;
+    nm_want_package_args("strconv.FormatInt", argc, 2);
+    int64_t i = nm_int_arg("strconv.FormatInt", argv[0]);
+    int64_t base = nm_int_arg("strconv.FormatInt", argv[1]);
+    if (base < 2 || base > 36) {
+        nm_errorf("strconv: illegal AppendInt/FormatInt base");
+    }
+    nm_buf b = {0};
+    if (i < 0) {
+        nm_buf_byte(&b, '-');
+    }
+    nm_format_radix(&b, i, (int)base, 0, -1);
+    return nm_buf_value(&b);
+}
+
+NM_PACKAGE(strconv, FormatFloat, "builtin strconv.FormatFloat") {
+    (void)self;
+    nm_want_package_args("strconv.FormatFloat", argc, 4);
+    double f = nm_float_arg("strconv.FormatFloat", argv[0]);
+    int64_t fmt = nm_int_arg("strconv.FormatFloat", argv[1]);
+    int64_t prec = nm_int_arg("strconv.FormatFloat", argv[2]);
+    nm_int_arg("strconv.FormatFloat", argv[3]);
+    nm_buf b = {0};
+    if (fmt != 'e' && fmt != 'f' && fmt != 'g') {
+        nm_buf_byte(&b, '%');
+        nm_buf_rune(&b, (uint8_t)fmt);
+        return nm_buf_value(&b);
+    }
+    nm_format_float(&b, f, (int)fmt, prec < 0 ? -1 : prec);
+    return nm_buf_value(&b);
+}
+
+NM_PACKAGE(strconv, FormatBool, "builtin strconv.FormatBool") {
+    (void)self;
+    nm_want_package_args("strconv.FormatBool", argc, 1);
+    nm_value v = nm_raw(argv[0]);
+    if (v.type->kind != NM_BOOL) {
+        nm_errorf("cannot use %s (type %s) as bool value in argument to strconv.FormatBool", nm_inspect(v), v.type->name);
+    }
+    return nm_cstr(v.u.i ? "true" : "false");
+}
+
+NM_PACKAGE(strconv, Quote, "builtin strconv.Quote") {
+    (void)self;
+    nm_want_package_args("strconv.Quote", argc, 1);
+    const nm_string *s = nm_string_arg("strconv.Quote", argv[0]);
+    nm_buf b = {0};
+    nm_buf_quote(&b, s->data, s->len);
+    return nm_buf_value(&b);
+}
+
+/* Package strings. Strings are bytes; functions that work on characters
+ * decode them as UTF-8. */
+
+NM_API const nm_string *nm_strings_arg(const char *name, int64_t argc, nm_value *argv, int64_t n, int64_t i) {
+    if (i == 0) {
+        nm_want_package_args(name, argc, n);
+    }
+    return nm_string_arg(name, argv[i]);
+}
+
+/* Byte offset of the first `sep` in `s` at or after `from`, or -1. */
+NM_API int64_t nm_find(const nm_string *s, const nm_string *sep, int64_t from) {
+    if (sep->len == 0) {
+        return from;
+    }
+    for (int64_t i = from; i + sep->len <= s->len; i++) {
+        if (memcmp(s->data + i, sep->data, (size_t)sep->len) == 0) {
+            return i;
+        }
+    }
+    return -1;
+}
+
+NM_API int nm_has_rune(const nm_string *set, int32_t r) {
+    for (int64_t i = 0; i < set->len;) {
+        int size;
+        if (nm_decode(set->data + i, set->len - i, &size) == r) {
+            return 1;
+        }
+        i += size;
+    }
+    return 0;
+}
+
+NM_API nm_value nm_string_slice(int64_t n, const nm_value *parts) {
+    nm_value s = nm_slice_of(&nm_t_strings, n, n);
+    if (n) {
+        memcpy(nm_slv(s)->data, parts, sizeof(nm_value) * (size_t)n);
+    }
+    return s;
+}
+
+NM_PACKAGE(strings, Contains, "builtin strings.Contains") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.Contains", argc, argv, 2, 0);
+    const nm_string *sep = nm_strings_arg("strings.Contains", argc, argv, 2, 1);
+    return nm_bool(nm_find(s, sep, 0) >= 0);
+}
+
+NM_PACKAGE(strings, ContainsAny, "builtin strings.ContainsAny") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.ContainsAny", argc, argv, 2, 0);
+    const nm_string *chars = nm_strings_arg("strings.ContainsAny", argc, argv, 2, 1);
+    for (int64_t i = 0; i < s->len;) {
+        int size;
+        if (nm_has_rune(chars, nm_decode(s->data + i, s->len - i, &size))) {
+            return nm_bool(1);
+        }
+        i += size;
+    }
+    return nm_bool(0);
+}
+
+/* The byte offset of the first rune `r` in `s`, or -1. */
+NM_API int64_t nm_index_rune(const nm_string *s, int64_t r) {
+    for (int64_t i = 0; i < s->len;) {
+        int size;
+        if (nm_decode(s->data + i, s->len - i, &size) == r) {
+            return i;
+        }
+        i += size;
+    }
+    return -1;
+}
+
+NM_PACKAGE(strings, ContainsRune, "builtin strings.ContainsRune") {
+    (void)self;
+    nm_want_package_args("strings.ContainsRune", argc, 2);
+    const nm_string *s = nm_string_arg("strings.ContainsRune", argv[0]);
+    int64_t r = nm_int_arg("strings.ContainsRune", argv[1]);
+    return nm_bool(nm_index_rune(s, r) >= 0);
+}
+
+NM_PACKAGE(strings, HasPrefix, "builtin strings.HasPrefix") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.HasPrefix", argc, argv, 2, 0);
+    const nm_string *prefix = nm_strings_arg("strings.HasPrefix", argc, argv, 2, 1);
+    return nm_bool(prefix->len <= s->len && memcmp(s->data, prefix->data, (size_t)prefix->len) == 0);
+}
+
+NM_PACKAGE(strings, HasSuffix, "builtin strings.HasSuffix") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.HasSuffix", argc, argv, 2, 0);
+    const nm_string *suffix = nm_strings_arg("strings.HasSuffix", argc, argv, 2, 1);
+    return nm_bool(suffix->len <= s->len && memcmp(s->data + s->len - suffix->len, suffix->data, (size_t)suffix->len) == 0);
+}
+
+NM_PACKAGE(strings, Index, "builtin strings.Index") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.Index", argc, argv, 2, 0);
+    const nm_string *sep = nm_strings_arg("strings.Index", argc, argv, 2, 1);
+    return nm_int(nm_find(s, sep, 0));
+}
+
+NM_PACKAGE(strings, IndexRune, "builtin strings.IndexRune") {
+    (void)self;
+    nm_want_package_args("strings.IndexRune", argc, 2);
+    const nm_string *s = nm_string_arg("strings.IndexRune", argv[0]);
+    int64_t r = nm_int_arg("strings.IndexRune", argv[1]);
+    return nm_int(nm_index_rune(s, r));
+}
+
+NM_PACKAGE(strings, LastIndex, "builtin strings.LastIndex") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.LastIndex", argc, argv, 2, 0);
+    const nm_string *sep = nm_strings_arg("strings.LastIndex", argc, argv, 2, 1);
+    if (sep->len == 0) {
+        return nm_int(s->len);
+    }
+    for (int64_t i = s->len - sep->len; i >= 0; i--) {
+        if (memcmp(s->data + i, sep->data, (size_t)sep->len) == 0) {
+            return nm_int(i);
+        }
+    }
+    return nm_int(-1);
+}
+
+/* The substrings between the first `n - 1` separators, and the rest; all
+ * of them when `n` is negative. An empty separator splits after each
+ * UTF-8 sequence. */
+NM_API nm_value nm_split(const nm_string *s, const nm_string *sep, int64_t n) {
+    int64_t limit = n < 0 ? INT64_MAX : n;
+    nm_value *parts = nm_alloc(sizeof(nm_value) * (size_t)(s->len + 1));
+    int64_t np = 0;
+    if (sep->len == 0) {
+        for (int64_t i = 0; i < s->len;) {
+            if (np + 1 == limit) {
+                parts[np++] = nm_str(s->data + i, s->len - i);
+                break;
+            }
+            int size;
+            nm_decode(s->data + i, s->len - i, &size);
+            parts[np++] = nm_str(s->data + i, size);
+            i += size;
+        }
+        return nm_string_slice(np, parts);
+    }
+    int64_t start = 0;
+    while (np + 1 < limit) {
+        int64_t i = nm_find(s, sep, start);
+        if (i < 0) {
+            break;
+        }
+        parts[np++] = nm_str(s->data + start, i - start);
+        start = i + sep->len;
+    }
+    parts[np++] = nm_str(s->data + start, s->len - start);
+    return nm_string_slice(np, parts);
+}
+
+NM_PACKAGE(strings, Split, "builtin strings.Split") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.Split", argc, argv, 2, 0);
+    const nm_string *sep = nm_strings_arg("strings.Split", argc, argv, 2, 1);
+    return nm_split(s, sep, -1);
+}
+
+NM_PACKAGE(strings, SplitN, "builtin strings.SplitN") {
+    (void)self;
+    nm_want_package_args("strings.SplitN", argc, 3);
+    const nm_string *s = nm_string_arg("strings.SplitN", argv[0]);
+    const nm_string *sep = nm_string_arg("strings.SplitN", argv[1]);
+    int64_t n = nm_int_arg("strings.SplitN", argv[2]);
+    if (n == 0) {
+        return nm_zero(&nm_t_strings);
+    }
+    return nm_split(s, sep, n);
+}
+
+NM_API int nm_is_space(int32_t c) {
+    switch (c) {
+    case '\t':
+    case '\n':
+    case 0x0b:
+    case 0x0c:
+    case '\r':
+    case ' ':
+    case 0x85:
+    case 0xa0:
+    case 0x1680:
+    case 0x2028:
+    case 0x2029:
+    case 0x202f:
+    case 0x205f:
+    case 0x3000:
+        return 1;
+    default:
+        return c >= 0x2000 && c <= 0x200a;
+    }
+}
+
+NM_PACKAGE(strings, Fields, "builtin strings.Fields") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.Fields", argc, argv, 1, 0);
+    nm_value *parts = nm_alloc(sizeof(nm_value) * (size_t)(s->len + 1));
+    int64_t np = 0, start = -1;
+    for (int64_t i = 0; i < s->len;) {
+        int size;
+        int space = nm_is_space(nm_decode(s->data + i, s->len - i, &size));
+        if (space && start >= 0) {
+            parts[np++] = nm_str(s->data + start, i - start);
+            start = -1;
+        } else if (!space && start < 0) {
+            start = i;
+        }
+        i += size;
+    }
+    if (start >= 0) {
+        parts[np++] = nm_str(s->data + start, s->len - start);
+    }
+    return nm_string_slice(np, parts);
+}
+
+NM_PACKAGE(strings, Join, "builtin strings.Join") {
+    (void)self;
+    nm_want_package_args("strings.Join", argc, 2);
+    nm_value elems = nm_raw(argv[0]);
+    if (!(elems.type->kind == NM_SLICE && elems.type->elem->kind == NM_STRING) && elems.type->kind != NM_NIL) {
+        nm_errorf("cannot use %s (type %s) as []string value in argument to strings.Join", nm_inspect(elems), elems.type->name);
+    }
+    const nm_string *sep = nm_string_arg("strings.Join", argv[1]);
+    int64_t n = elems.type->kind == NM_NIL ? 0 : nm_len_of(elems);
+    nm_buf b = {0};
+    for (int64_t i = 0; i < n; i++) {
+        if (i > 0) {
+            nm_buf_put(&b, sep->data, sep->len);
+        }
+        const nm_string *elem = nm_string_arg("strings.Join", nm_slv(elems)->data[i]);
+        nm_buf_put(&b, elem->data, elem->len);
+    }
+    return nm_buf_value(&b);
+}
+
+NM_PACKAGE(strings, Repeat, "builtin strings.Repeat") {
+    (void)self;
+    nm_want_package_args("strings.Repeat", argc, 2);
+    const nm_string *s = nm_string_arg("strings.Repeat", argv[0]);
+    int64_t n = nm_int_arg("strings.Repeat", argv[1]);
+    if (n < 0) {
+        nm_errorf("strings: negative Repeat count");
+    }
+    nm_buf b = {0};
+    for (int64_t i = 0; i < n && s->len; i++) {
+        nm_buf_put(&b, s->data, s->len);
+    }
+    return nm_buf_value(&b);
+}
+
+/* `s` with the first `n` non-overlapping `old` replaced by `new`; all of
+ * them when `n` is negative. */
+NM_API nm_value nm_replace(const nm_string *s, const nm_string *old, const nm_string *new_, int64_t n) {
+    nm_buf b = {0};
+    int64_t start = 0, done = 0;
+    if (old->len == 0) {
+        /* before each rune and at the end */
+        for (int64_t i = 0; i < s->len;) {
+            if (n >= 0 && done >= n) {
+                break;
+            }
+            int size;
+            nm_decode(s->data + i, s->len - i, &size);
+            nm_buf_put(&b, new_->data, new_->len);
+            nm_buf_put(&b, s->data + i, size);
+            i += size;
+            start = i;
+            done++;
+        }
+        nm_buf_put(&b, s->data + start, s->len - start);
+        if (n < 0 || done < n) {
+            nm_buf_put(&b, new_->data, new_->len);
+        }
+        return nm_buf_value(&b);
+    }
+    while (n < 0 || done < n) {
+        int64_t i = nm_find(s, old, start);
+        if (i < 0) {
+            break;
+        }
+        nm_buf_put(&b, s->data + start, i - start);
+        nm_buf_put(&b, new_->data, new_->len);
+        start = i + old->len;
+        done++;
+    }
+    nm_buf_put(&b, s->data + start, s->len - start);
+    return nm_buf_value(&b);
+}
+
+NM_PACKAGE(strings, Replace, "builtin strings.Replace") {
+    (void)self;
+    nm_want_package_args("strings.Replace", argc, 4);
+    const nm_string *s = nm_string_arg("strings.Replace", argv[0]);
+    const nm_string *old = nm_string_arg("strings.Replace", argv[1]);
+    const nm_string *new_ = nm_string_arg("strings.Replace", argv[2]);
+    int64_t n = nm_int_arg("strings.Replace", argv[3]);
+    return nm_replace(s, old, new_, n);
+}
+
+NM_PACKAGE(strings, ReplaceAll, "builtin strings.ReplaceAll") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.ReplaceAll", argc, argv, 3, 0);
+    const nm_string *old = nm_strings_arg("strings.ReplaceAll", argc, argv, 3, 1);
+    const nm_string *new_ = nm_strings_arg("strings.ReplaceAll", argc, argv, 3, 2);
+    return nm_replace(s, old, new_, -1);
+}
+
+/* `s` with each rune mapped; bytes that are not UTF-8 are kept. */
+NM_API nm_value nm_map_runes(const nm_string *s, wint_t (*f)(wint_t)) {
+    nm_buf b = {0};
+    for (int64_t i = 0; i < s->len;) {
+        int size;
+        int32_t c = nm_decode(s->data + i, s->len - i, &size);
+        if (c == 0xfffd && size == 1) {
+            nm_buf_byte(&b, s->data[i]);
+        } else {
+            nm_buf_rune(&b, (int64_t)f((wint_t)c));
+        }
+        i += size;
+    }
+    return nm_buf_value(&b);
+}
+
+NM_PACKAGE(strings, ToUpper, "builtin strings.ToUpper") {
+    (void)self;
+    return nm_map_runes(nm_strings_arg("strings.ToUpper", argc, argv, 1, 0), towupper);
+}
+
+NM_PACKAGE(strings, ToLower, "builtin strings.ToLower") {
+    (void)self;
+    return nm_map_runes(nm_strings_arg("strings.ToLower", argc, argv, 1, 0), towlower);
+}
+
+/* The part of `s` left when leading and trailing runes that are spaces,
+ * or in `cutset` when it is given, are removed from the sides asked for. */
+NM_API nm_value nm_trim(const nm_string *s, int left, int right, const nm_string *cutset) {
+    int64_t *offsets = nm_alloc(sizeof(int64_t) * (size_t)(s->len + 1));
+    int32_t *runes = nm_alloc(sizeof(int32_t) * (size_t)(s->len + 1));
+    int64_t n = 0;
+    for (int64_t i = 0; i < s->len; n++) {
+        int size;
+        offsets[n] = i;
+        runes[n] = nm_decode(s->data + i, s->len - i, &size);
+        i += size;
+    }
+    offsets[n] = s->len;
+    int64_t start = 0, end = n;
+    while (left && start < end && (cutset ? nm_has_rune(cutset, runes[start]) : nm_is_space(runes[start]))) {
+        start++;
+    }
+    while (right && end > start && (cutset ? nm_has_rune(cutset, runes[end - 1]) : nm_is_space(runes[end - 1]))) {
+        end--;
+    }
+    return nm_str(s->data + offsets[start], offsets[end] - offsets[start]);
+}
+
+NM_PACKAGE(strings, TrimSpace, "builtin strings.TrimSpace") {
+    (void)self;
+    return nm_trim(nm_strings_arg("strings.TrimSpace", argc, argv, 1, 0), 1, 1, NULL);
+}
+
+NM_PACKAGE(strings, Trim, "builtin strings.Trim") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.Trim", argc, argv, 2, 0);
+    return nm_trim(s, 1, 1, nm_strings_arg("strings.Trim", argc, argv, 2, 1));
+}
+
+NM_PACKAGE(strings, TrimLeft, "builtin strings.TrimLeft") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.TrimLeft", argc, argv, 2, 0);
+    return nm_trim(s, 1, 0, nm_strings_arg("strings.TrimLeft", argc, argv, 2, 1));
+}
+
+NM_PACKAGE(strings, TrimRight, "builtin strings.TrimRight") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.TrimRight", argc, argv, 2, 0);
+    return nm_trim(s, 0, 1, nm_strings_arg("strings.TrimRight", argc, argv, 2, 1));
+}
+
+NM_PACKAGE(strings, TrimPrefix, "builtin strings.TrimPrefix") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.TrimPrefix", argc, argv, 2, 0);
+    const nm_string *prefix = nm_strings_arg("strings.TrimPrefix", argc, argv, 2, 1);
+    if (prefix->len <= s->len && memcmp(s->data, prefix->data, (size_t)prefix->len) == 0) {
+        return nm_str(s->data + prefix->len, s->len - prefix->len);
+    }
+    return argv[0];
+}
+
+NM_PACKAGE(strings, TrimSuffix, "builtin strings.TrimSuffix") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.TrimSuffix", argc, argv, 2, 0);
+    const nm_string *suffix = nm_strings_arg("strings.TrimSuffix", argc, argv, 2, 1);
+    if (suffix->len <= s->len && memcmp(s->data + s->len - suffix->len, suffix->data, (size_t)suffix->len) == 0) {
+        return nm_str(s->data, s->len - suffix->len);
+    }
+    return argv[0];
+}
+
+/* Non-overlapping instances of `sep` in `s`; one more than the number of
+ * runes when `sep` is empty. */
+NM_PACKAGE(strings, Count, "builtin strings.Count") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.Count", argc, argv, 2, 0);
+    const nm_string *sep = nm_strings_arg("strings.Count", argc, argv, 2, 1);
+    if (sep->len == 0) {
+        return nm_int(nm_rune_count(s->data, s->len) + 1);
+    }
+    int64_t n = 0;
+    for (int64_t i = nm_find(s, sep, 0); i >= 0; i = nm_find(s, sep, i + sep->len)) {
+        n++;
+    }
+    return nm_int(n);
+}
+
+NM_PACKAGE(strings, EqualFold, "builtin strings.EqualFold") {
+    (void)self;
+    const nm_string *a = nm_strings_arg("strings.EqualFold", argc, argv, 2, 0);
+    const nm_string *b = nm_strings_arg("strings.EqualFold", argc, argv, 2, 1);
+    int64_t i = 0, j = 0;
+    while (i < a->len && j < b->len) {
+        int n, m;
+        int32_t x = nm_decode(a->data + i, a->len - i, &n);
+        int32_t y = nm_decode(b->data + j, b->len - j, &m);
+        if (towlower((wint_t)x) != towlower((wint_t)y)) {
+            return nm_bool(0);
+        }
+        i += n;
+        j += m;
+    }
+    return nm_bool(i == a->len && j == b->len);
+}
+
+NM_PACKAGE(strings, Compare, "builtin strings.Compare") {
+    (void)self;
+    const nm_string *a = nm_strings_arg("strings.Compare", argc, argv, 2, 0);
+    const nm_string *b = nm_strings_arg("strings.Compare", argc, argv, 2, 1);
+    int c = nm_string_compare(a, b);
+    return nm_int((c > 0) - (c < 0));
+}
+
+/* `strings.Cut(s, sep)`: the text before and after the first `sep`, and
+ * whether there was one. */
+NM_PACKAGE(strings, Cut, "builtin strings.Cut") {
+    (void)self;
+    const nm_string *s = nm_strings_arg("strings.Cut", argc, argv, 2, 0);
+    const nm_string *sep = nm_strings_arg("strings.Cut", argc, argv, 2, 1);
+    int64_t i = nm_find(s, sep, 0);
+    nm_value results[3];
+    if (i >= 0) {
+        results[0] = nm_str(s->data, i);
+        results[1] = nm_str(s->data + i + sep->len, s->len - i - sep->len);
+        results[2] = nm_bool(1);
+    } else {
+        results[0] = nm_str(s->data, s->len);
+        results[1] = nm_strconst(&nm_empty);
+        results[2] = nm_bool(0);
+    }
+    return nm_tuple(3, results);
+}
+
+/* Package math */
+
+#define NM_MATH_UNARY(name, f) \
+    NM_PACKAGE(math, name, "builtin math." #name) { \
+        (void)self; \
+        nm_want_package_args("math." #name, argc, 1); \
+        return nm_float(f(nm_float_arg("math." #name, argv[0]))); \
+    }
+
+#define NM_MATH_BINARY(name, f) \
+    NM_PACKAGE(math, name, "builtin math." #name) { \
+        (void)self; \
+        nm_want_package_args("math." #name, argc, 2); \
+        double x = nm_float_arg("math." #name, argv[0]); \
+        double y = nm_float_arg("math." #name, argv[1]); \
+        return nm_float(f(x, y)); \
+    }
+
+/* `math.Max`: NaN if either is, and +0 over -0. */
+NM_API double nm_math_max(double x, double y) {
+    if (isnan(x) || isnan(y)) {
+        return NAN;
+    }
+    if (x == 0 && y == 0) {
+        return signbit(x) ? y : x;
+    }
+    return x > y ? x : y;
+}
+
+/* `math.Min`: NaN if either is, and -0 over +0. */
+NM_API double nm_math_min(double x, double y) {
+    if (isnan(x) || isnan(y)) {
+        return NAN;
+    }
+    if (x == 0 && y == 0) {
+        return signbit(x) ? x : y;
+    }
+    return x < y ? x : y;
+}
+
+NM_MATH_UNARY(Abs, fabs)
+NM_MATH_UNARY(Ceil, ceil)
+NM_MATH_UNARY(Floor, floor)
+NM_MATH_UNARY(Trunc, trunc)
+NM_MATH_UNARY(Round, round)
+NM_MATH_UNARY(Sqrt, sqrt)
+NM_MATH_UNARY(Cbrt, cbrt)
+NM_MATH_UNARY(Exp, exp)
+NM_MATH_UNARY(Log, log)
+NM_MATH_UNARY(Log2, log2)
+NM_MATH_UNARY(Log10, log10)
+NM_MATH_UNARY(Sin, sin)
+NM_MATH_UNARY(Cos, cos)
+NM_MATH_UNARY(Tan, tan)
+NM_MATH_UNARY(Atan, atan)
+NM_MATH_BINARY(Pow, pow)
+NM_MATH_BINARY(Atan2, atan2)
+NM_MATH_BINARY(Hypot, hypot)
+NM_MATH_BINARY(Mod, fmod)
+NM_MATH_BINARY(Max, nm_math_max)
+NM_MATH_BINARY(Min, nm_math_min)
+
+/* `math.Inf(sign)`: +Inf for a sign of 0 or more, else -Inf. */
+NM_PACKAGE(math, Inf, "builtin math.Inf") {
+    (void)self;
+    nm_want_package_args("math.Inf", argc, 1);
+    return nm_float(nm_int_arg("math.Inf", argv[0]) >= 0 ? INFINITY : -INFINITY);
+}
+
+/* `math.IsInf(f, sign)`: whether `f` is an infinity of the sign, or of
+ * either sign for 0. */
+NM_PACKAGE(math, IsInf, "builtin math.IsInf") {
+    (void)self;
+    nm_want_package_args("math.IsInf", argc, 2);
+    double f = nm_float_arg("math.IsInf", argv[0]);
+    int64_t sign = nm_int_arg("math.IsInf", argv[1]);
+    return nm_bool((sign >= 0 && f == INFINITY) || (sign <= 0 && f == -INFINITY));
+}
+
+NM_PACKAGE(math, IsNaN, "builtin math.IsNaN") {
+    (void)self;
+    nm_want_package_args("math.IsNaN", argc, 1);
+    return nm_bool(isnan(nm_float_arg("math.IsNaN", argv[0])));
+}
+
+NM_PACKAGE(math, NaN, "builtin math.NaN") {
+    (void)self;
+    (void)argv;
+    nm_want_package_args("math.NaN", argc, 0);
+    return nm_float(NAN);
+}
+
+/* Package sort. Slice, SliceStable and Search call back into the program
+ * the way their source does on the VM. */
+
+/* Functions written in source there: checked like closures. */
+#define NM_SOURCE(path, name, sig, nparams) \
+    NM_API nm_value nm_p_##path##_##name(nm_func *self, int64_t argc, nm_value *argv); \
+    NM_API nm_func nm_P_##path##_##name = {nm_p_##path##_##name, #path "." #name, sig, nparams, {&nm_t_nil, {0}}, 0, NULL}; \
+    NM_API nm_value nm_p_##path##_##name(nm_func *self, int64_t argc, nm_value *argv)
+
+/* Stable merge sort of `n` values by `cmp`. */
+NM_API void nm_merge_sort(nm_value *values, int64_t n, int (*cmp)(nm_value, nm_value)) {
+    if (n < 2) {
+        return;
+    }
+    int64_t half = n / 2;
+    nm_merge_sort(values, half, cmp);
+    nm_merge_sort(values + half, n - half, cmp);
+    nm_value *merged = nm_alloc(sizeof(nm_value) * (size_t)n);
+    int64_t i = 0, j = half, k = 0;
+    while (i < half && j < n) {
+        merged[k++] = cmp(values[j], values[i]) < 0 ? values[j++] : values[i++];
+    }
+    while (i < half) {
+        merged[k++] = values[i++];
+    }
+    while (j < n) {
+        merged[k++] = values[j++];
+    }
+    memcpy(values, merged, sizeof(nm_value) * (size_t)n);
+}
+
+NM_API int nm_int_cmp(nm_value a, nm_value b) {
+    return (a.u.i > b.u.i) - (a.u.i < b.u.i);
+}
+
+NM_API int nm_string_cmp(nm_value a, nm_value b) {
+    return nm_string_compare(nm_sv(a), nm_sv(b));
+}
+
+/* NaNs sort before everything else. */
+NM_API int nm_float_cmp(nm_value a, nm_value b) {
+    int x = isnan(a.u.f), y = isnan(b.u.f);
+    if (x || y) {
+        return y - x;
+    }
+    return (a.u.f > b.u.f) - (a.u.f < b.u.f);
+}
+
+/* Sorts the elements of a slice of `elem` in place by `cmp`. */
+NM_API nm_value nm_sort_slice(const char *name, int64_t argc, nm_value *argv, const nm_type *elem, int (*cmp)(nm_value, nm_value)) {
+    nm_want_package_args(name, argc, 1);
+    nm_value s = nm_raw(argv[0]);
+    if (s.type->kind != NM_SLICE || !nm_identical(s.type->elem, elem)) {
+        nm_errorf("cannot use %s (type %s) as []%s value in argument to %s", nm_inspect(s), s.type->name, elem->name, name);
+    }
+    if (s.u.p) {
+        nm_merge_sort(nm_slv(s)->data, nm_slv(s)->len, cmp);
+    }
+    return nm_nil;
+}
+
+NM_PACKAGE(sort, Ints, "builtin sort.Ints") {
+    (void)self;
+    return nm_sort_slice("sort.Ints", argc, argv, &nm_t_int, nm_int_cmp);
+}
+
+NM_PACKAGE(sort, Strings, "builtin sort.Strings") {
+    (void)self;
+    return nm_sort_slice("sort.Strings", argc, argv, &nm_t_string, nm_string_cmp);
+}
+
+NM_PACKAGE(sort, Float64s, "builtin sort.Float64s") {
+    (void)self;
+    return nm_sort_slice("sort.Float64s", argc, argv, &nm_t_float, nm_float_cmp);
+}
+
+/* `less(i, j)` with the indices as the program passes them. */
+NM_API int nm_less(nm_value less, int64_t i, int64_t j) {
+    nm_value args[2] = {nm_box(&nm_t_any, nm_int(i)), nm_box(&nm_t_any, nm_int(j))};
+    return nm_truth(nm_call(less, 2, args));
+}
+
+NM_API void nm_swap(nm_value x, int64_t i, int64_t j) {
+    nm_value a = nm_index(x, nm_int(i)), b = nm_index(x, nm_int(j));
+    nm_set_index(x, nm_int(i), b);
+    nm_set_index(x, nm_int(j), a);
+}
+
+NM_API void nm_sift_down(nm_value x, nm_value less, int64_t root, int64_t hi) {
+    for (;;) {
+        int64_t child = 2 * root + 1;
+        if (child >= hi) {
+            break;
+        }
+        if (child + 1 < hi && nm_less(less, child, child + 1)) {
+            child++;
+        }
+        if (!nm_less(less, root, child)) {
+            break;
+        }
+        nm_swap(x, root, child);
+        root = child;
+    }
+}
+
+/* A heapsort. */
+NM_SOURCE(sort, Slice, "fn(x, less)", 2) {
+    (void)self;
+    (void)argc;
+    nm_enter("sort.Slice", 1);
+    nm_value x = argv[0], less = argv[1];
+    int64_t n = nm_b_len(NULL, 1, &x).u.i;
+    for (int64_t i = (n - 1) / 2; i >= 0; i--) {
+        nm_sift_down(x, less, i, n);
+    }
+    for (int64_t i = n - 1; i > 0; i--) {
+        nm_swap(x, 0, i);
+        nm_sift_down(x, less, 0, i);
+    }
+    nm_leave();
+    return nm_nil;
+}
+
+/* An insertion sort. */
+NM_SOURCE(sort, SliceStable, "fn(x, less)", 2) {
+    (void)self;
+    (void)argc;
+    nm_enter("sort.SliceStable", 1);
+    nm_value x = argv[0], less = argv[1];
+    for (int64_t i = 1; i < nm_b_len(NULL, 1, &x).u.i; i++) {
+        for (int64_t j = i; j > 0 && nm_less(less, j, j - 1); j--) {
+            nm_swap(x, j, j - 1);
+        }
+    }
+    nm_leave();
+    return nm_nil;
+}
+
+/* The least index in [0, n) for which f is true, or n. */
+NM_SOURCE(sort, Search, "fn(n, f)", 2) {
+    (void)self;
+    (void)argc;
+    nm_enter("sort.Search", 1);
+    nm_value i = nm_int(0), j = argv[0], f = argv[1];
+    while (nm_truth(nm_lt(i, j))) {
+        nm_value h = nm_div(nm_add(i, j), nm_int(2));
+        nm_value arg = nm_box(&nm_t_any, h);
+        if (!nm_truth(nm_call(f, 1, &arg))) {
+            i = nm_add(h, nm_int(1));
+        } else {
+            j = h;
+        }
+    }
+    nm_leave();
+    return i;
+}
+
+/* Package os */
+
+NM_API int nm_argc;
+NM_API char **nm_argv;
+
+NM_API nm_value nm_v_os_Args(void) {
+    nm_value s = nm_slice_of(&nm_t_strings, nm_argc, nm_argc);
+    for (int i = 0; i < nm_argc; i++) {
+        nm_slv(s)->data[i] = nm_cstr(nm_argv[i]);
+    }
+    return s;
+}
+
+/* An `*os.File` for a standard stream. */
+NM_API nm_value nm_file(int64_t fd) {
+    nm_value *fields = nm_alloc(sizeof(nm_value));
+    fields[0] = nm_int(fd);
+    nm_value *slot = nm_alloc(sizeof(nm_value));
+    *slot = nm_ref(&nm_t_File, fields);
+    return nm_ref(&nm_t_p_File, slot);
+}
+
+NM_API nm_value nm_v_os_Stdout(void) {
+    return nm_file(1);
+}
+
+NM_API nm_value nm_v_os_Stderr(void) {
+    return nm_file(2);
+}
+
+NM_PACKAGE(os, Exit, "builtin os.Exit") {
+    (void)self;
+    nm_want_package_args("os.Exit", argc, 1);
+    int64_t code = nm_int_arg("os.Exit", argv[0]);
+    nm_flush();
+    exit((int)code);
+}
+
+/* `os.ReadFile(name)`: the contents of the file, and a `*fs.PathError`
+ * if it cannot be read. */
+NM_PACKAGE(os, ReadFile, "builtin os.ReadFile") {
+    (void)self;
+    nm_want_package_args("os.ReadFile", argc, 1);
+    const nm_string *name = nm_string_arg("os.ReadFile", argv[0]);
+    nm_buf path = {0};
+    nm_buf_lossy(&path, name->data, name->len);
+    const char *cpath = nm_buf_cstr(&path);
+    nm_buf contents = {0};
+    int err = 0;
+    int fd = open(cpath, O_RDONLY);
+    if (fd < 0) {
+        err = errno;
+    } else {
+        uint8_t chunk[65536];
+        for (;;) {
+            ssize_t n = read(fd, chunk, sizeof chunk);
+            if (n < 0) {
+                err = errno;
+                break;
+            }
+            if (n == 0) {
+                break;
+            }
+            nm_buf_put(&contents, chunk, n);
+        }
+        close(fd);
+    }
+    nm_value results[2];
+    if (!err) {
+        nm_value s = nm_slice_of(&nm_t_bytes, contents.len, contents.len);
+        for (int64_t i = 0; i < contents.len; i++) {
+            nm_slv(s)->data[i] = nm_int(contents.data[i]);
+        }
+        results[0] = s;
+        results[1] = nm_zero(&nm_t_error);
+        return nm_tuple(2, results);
+    }
+    nm_buf cause = {0};
+    switch (err) {
+    case ENOENT:
+        nm_buf_str(&cause, "no such file or directory");
+        break;
+    case EACCES:
+        nm_buf_str(&cause, "permission denied");
+        break;
+    case EISDIR:
+        nm_buf_str(&cause, "is a directory");
+        break;
+    default:
+        nm_buf_printf(&cause, "%s (os error %d)", strerror(err), err);
+        break;
+    }
+    nm_value fields[3] = {nm_cstr(err == EISDIR ? "read" : "open"), nm_cstr(cpath), nm_new_error(nm_buf_value(&cause))};
+    results[0] = nm_zero(&nm_t_bytes);
+    results[1] = nm_make_error(&nm_t_PathError, 3, fields);
+    return nm_tuple(2, results);
+}
+
+/* Range loops */
+
+enum { NM_ITER_INT, NM_ITER_STRING, NM_ITER_SLICE, NM_ITER_MAP };
+
+typedef struct {
+    int kind;
+    nm_value x;
+    int64_t i;
+    int64_t n;
+    /* The keys of a map when the loop started, in the order it visits
+     * them. */
+    nm_value *keys;
+} nm_iter;
+
+NM_API uint64_t nm_rng_state;
+
+NM_API uint64_t nm_rand(void) {
+    if (!nm_rng_state) {
+        /* seeded to the nanosecond, as the VM is, so that runs started in
+         * the same second differ too */
+        struct timespec now;
+        clock_gettime(CLOCK_REALTIME, &now);
+        nm_rng_state = ((uint64_t)now.tv_sec * 1000000000u + (uint64_t)now.tv_nsec) * 0x9e3779b97f4a7c15ull | 1;
+    }
+    nm_rng_state ^= nm_rng_state << 13;
+    nm_rng_state ^= nm_rng_state >> 7;
+    nm_rng_state ^= nm_rng_state << 17;
+    return nm_rng_state;
+}
+
+/* Starts a range loop over `x`. A map is visited in insertion order from
+ * a random entry on, so programs cannot rely on the order. */
+NM_API void nm_range(nm_iter *it, nm_value x) {
+    x = nm_raw(x);
+    it->x = x;
+    it->i = 0;
+    it->keys = NULL;
+    switch (x.type->kind) {
+    case NM_INT:
+        it->kind = NM_ITER_INT;
+        it->n = x.u.i;
+        return;
+    case NM_STRING:
+        it->kind = NM_ITER_STRING;
+        it->n = nm_sv(x)->len;
+        return;
+    case NM_SLICE:
+        it->kind = NM_ITER_SLICE;
+        it->n = x.u.p ? nm_slv(x)->len : 0;
+        return;
+    case NM_MAP: {
+        it->kind = NM_ITER_MAP;
+        nm_map *m = nm_mapv(x);
+        it->n = m ? m->count : 0;
+        if (it->n == 0) {
+            return;
+        }
+        nm_value *live = nm_alloc(sizeof(nm_value) * (size_t)it->n);
+        int64_t k = 0;
+        for (int64_t i = 0; i < m->n; i++) {
+            if (m->entries[i].live) {
+                live[k++] = m->entries[i].key;
+            }
+        }
+        int64_t start = (int64_t)(nm_rand() % (uint64_t)it->n);
+        it->keys = nm_alloc(sizeof(nm_value) * (size_t)it->n);
+        for (int64_t i = 0; i < it->n; i++) {
+            it->keys[i] = live[(start + i) % it->n];
+        }
+        return;
+    }
+    case NM_NIL:
+        it->kind = NM_ITER_SLICE;
+        it->n = 0;
+        return;
+    default:
+        nm_errorf("cannot range over %s value", x.type->name);
+    }
+}
+
+/* The next iteration: whether there is one, its key and its value. Map
+ * entries deleted during the loop are skipped. */
+NM_API void nm_next(nm_iter *it, nm_value *out) {
+    out[1] = nm_nil;
+    out[2] = nm_nil;
+    switch (it->kind) {
+    case NM_ITER_INT:
+        if (it->i < it->n) {
+            out[1] = nm_int(it->i++);
+            out[0] = nm_bool(1);
+            return;
+        }
+        break;
+    case NM_ITER_STRING:
+        if (it->i < it->n) {
+            const nm_string *s = nm_sv(it->x);
+            int size;
+            out[1] = nm_int(it->i);
+            out[2] = nm_int(nm_decode(s->data + it->i, s->len - it->i, &size));
+            it->i += size;
+            out[0] = nm_bool(1);
+            return;
+        }
+        break;
+    case NM_ITER_SLICE:
+        if (it->i < it->n && it->i < nm_slv(it->x)->len) {
+            out[1] = nm_int(it->i);
+            out[2] = nm_dup(nm_slv(it->x)->data[it->i]);
+            it->i++;
+            out[0] = nm_bool(1);
+            return;
+        }
+        break;
+    case NM_ITER_MAP:
+        while (it->i < it->n) {
+            nm_value key = it->keys[it->i++];
+            nm_map *m = nm_mapv(it->x);
+            int64_t e = nm_map_find(m, key);
+            if (e >= 0) {
+                out[1] = key;
+                out[2] = nm_dup(m->entries[e].val);
+                out[0] = nm_bool(1);
+                return;
+            }
+        }
+        break;
+    }
+    out[0] = nm_bool(0);
+}
+
+/* Startup */
+
+typedef void (*nm_program)(void);
+
+NM_API nm_program nm_run_program;
+
+NM_API void *nm_thread(void *arg) {
+    (void)arg;
+    nm_run_program();
+    nm_flush();
+    return NULL;
+}
+
+/* Runs the program on a thread with a stack deep enough for the calls
+ * the VM allows. */
+NM_API int nm_main(int argc, char **argv, nm_program run) {
+    setlocale(LC_CTYPE, "C.UTF-8");
+    nm_argc = argc;
+    nm_argv = argv;
+    nm_run_program = run;
+    pthread_attr_t attr;
+    pthread_attr_init(&attr);
+    pthread_attr_setstacksize(&attr, (size_t)256 << 20);
+    pthread_t thread;
+    if (pthread_create(&thread, &attr, nm_thread, NULL) != 0) {
+        nm_thread(NULL);
+        return 0;
+    }
+    pthread_join(thread, NULL);
+    return 0;
+}
+
+/* The generated program */
@@ -0,0 +1,105 @@
+use crate::backend::c::*;
+use crate::ir::test::tests::{lower_source, PROGRAMS};
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn have_cc() -> bool {
+    Command::new(cc()).arg("--version").output().map(|o| o.status.success()).unwrap_or(false)
+  }
+
+  /// Builds `source` into an executable and runs it with `args`, giving its
+  /// stdout, stderr and exit code; None when there is no C compiler.
+  fn run(source: &str, args: &[&str]) -> Option<(String, String, i32)> {
+    if !have_cc() {
+      return None;
+    }
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    let code = generate(&lower_source(source)).unwrap_or_else(|errors| panic!("generate errors: {:?}", errors));
+    let exe = env::temp_dir().join(format!("namu-c-test-{}-{}", std::process::id(), RUNS.fetch_add(1, Ordering::Relaxed)));
+    compile(&code, &exe).unwrap_or_else(|e| panic!("{}", e));
+    let output = Command::new(&exe).args(args).output().unwrap();
+    let _ = fs::remove_file(&exe);
+    Some((
+      String::from_utf8_lossy(&output.stdout).into_owned(),
+      String::from_utf8_lossy(&output.stderr).into_owned(),
+      output.status.code().unwrap_or(-1),
+    ))
+  }
+
+  fn check_output(source: &str, expected: &str) {
+    if let Some((stdout, stderr, code)) = run(source, &[]) {
+      assert_eq!((stdout.as_str(), stderr.as_str(), code), (expected, "", 0));
+    }
+  }
+
+  #[test]
+  fn test_programs() {
+    for (_, source, expected) in PROGRAMS {
+      check_output(source, expected);
+    }
+  }
+
+  #[test]
+  fn test_map_iteration_order_varies() {
+    if !have_cc() {
+      return;
+    }
+    let source = r#"let main = fn() {
+  m := map[int]int{}
+  for i := 0; i < 20; i++ { m[i] = i }
+  for k := range m {
+    println(k)
+    break
+  }
+}"#;
+    let code = generate(&lower_source(source)).unwrap_or_else(|errors| panic!("generate errors: {:?}", errors));
+    let exe = env::temp_dir().join(format!("namu-c-test-{}-order", std::process::id()));
+    compile(&code, &exe).unwrap_or_else(|e| panic!("{}", e));
+    let firsts: std::collections::HashSet<_> = (0..10).map(|_| Command::new(&exe).output().unwrap().stderr).collect();
+    let _ = fs::remove_file(&exe);
+    if firsts.len() < 2 {
+      panic!("iteration always started at {:?}", firsts);
+    }
+  }
+
+  #[test]
+  fn test_panic() {
+    let source = r#"let f = fn(x) { panic("boom") }
+let main = fn() { f(1) }"#;
+    if let Some((stdout, stderr, code)) = run(source, &[]) {
+      assert_eq!(stdout, "");
+      assert_eq!(stderr, "panic: boom\n\ngoroutine 1 [running]:\nmain.f(...)\nmain.main()\n");
+      assert_eq!(code, 2);
+    }
+  }
+
+  #[test]
+  fn test_os() {
+    let source = r#"import "fmt"
+import "os"
+let main = fn() {
+  defer fmt.Println("not run")
+  fmt.Println(os.Args[1:])
+  fmt.Fprintln(os.Stderr, "bye")
+  os.Exit(3)
+}"#;
+    if let Some(output) = run(source, &["a", "b"]) {
+      assert_eq!(output, ("[a b]\n".to_string(), "bye\n".to_string(), 3));
+    }
+  }
+
+  #[test]
+  fn test_unsupported() {
+    let module = lower_source(
+      r#"let main = fn() {
+  c := make(chan int)
+  go fn() { c <- 1 }()
+  <-c
+}"#,
+    );
+    let errors = generate(&module).unwrap_err();
+    assert!(errors.iter().any(|e| e.ends_with("not supported by the C backend")), "{:?}", errors);
+  }
+}
@@ -0,0 +1,6 @@
+//! Code generators: translations of a program's SSA form, see `ir`, into
+//! something that runs without the VM.
+
+pub mod c;
+pub mod wasm;
+pub mod x86_64;
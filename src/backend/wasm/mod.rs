@@ -0,0 +1,914 @@
+//! The WebAssembly backend: a module as a WebAssembly module, for
+//! `namu build -o out.wasm` or, in the text format, `-o out.wat`.
+//!
+//! The output is the runtime in `runtime.wat`, which implements the VM's
+//! values, operators, builtins and standard library over linear memory,
+//! followed by the program: a type descriptor for each type the IR names,
+//! the bytes of each string literal in a data segment, and a function for
+//! each IR function. Every IR value is a pair of locals, the address of
+//! its type's descriptor and its 64 bits; blocks become the arms of a
+//! `br_table` in a loop, and phis copies on the edges into them. A panic
+//! sets a flag that every function checks after what may panic, returning
+//! to the function that deferred calls, if any, to run them. Goroutines
+//! and channels are not supported; programs that use them are rejected
+//! with an error.
+//!
+//! A module imports its I/O, what needs Rust's floats and a random seed
+//! from the `namu` module, which `run` provides to run it in the
+//! interpreter in `vm`, as `namu run out.wasm` does.
+
+pub mod module;
+pub mod validate;
+pub mod vm;
+pub mod wat;
+
+use self::module::{Import, Module as WasmModule};
+use self::vm::{Host, Instance, Trap};
+use crate::backend::c;
+use crate::ir::{BinOp, BlockId, Const, FuncId, Function, Inst, Module, Ty, UnOp, ValueId};
+use crate::types::Type;
+use std::collections::HashMap;
+use std::io::Write;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(test)]
+mod test;
+
+/// The runtime every generated module starts with.
+pub const RUNTIME: &str = include_str!("runtime.wat");
+
+/// The builtins the runtime implements as `$b_<name>`; `make` and `new`
+/// are lowered to instructions of their own.
+const BUILTINS: &[&str] = &[
+    "len", "cap", "append", "copy", "delete", "min", "max", "clear", "print", "println", "panic", "recover", "close",
+];
+
+/// The package functions the runtime writes as their source is written
+/// on the VM, with the signatures closures of them print with.
+const SOURCE: &[(&str, &str, &str)] = &[
+    ("sort", "Slice", "fn(x, less)"),
+    ("sort", "SliceStable", "fn(x, less)"),
+    ("sort", "Search", "fn(n, f)"),
+];
+
+/// The runtime's own descriptors, `$t_<name>`: kind, the type as Go
+/// prints it, whether it is named, the element's descriptor and a
+/// struct's fields.
+#[allow(clippy::type_complexity)]
+const RUNTIME_TYPES: &[(&str, u32, &str, bool, &str, &[(&str, &str)])] = &[
+    ("nil", 0, "nil", false, "", &[]),
+    ("bool", 1, "bool", false, "", &[]),
+    ("int", 2, "int", false, "", &[]),
+    ("byte", 3, "uint8", false, "", &[]),
+    ("float", 4, "float64", false, "", &[]),
+    ("string", 5, "string", false, "", &[]),
+    ("func", 6, "fn", false, "", &[]),
+    ("any", 12, "interface {}", false, "", &[]),
+    ("error", 12, "error", true, "", &[]),
+    ("bytes", 7, "[]uint8", false, "byte", &[]),
+    ("ints", 7, "[]int", false, "int", &[]),
+    ("floats", 7, "[]float64", false, "float", &[]),
+    ("strings", 7, "[]string", false, "string", &[]),
+    // the results of a package function with several
+    ("tuple", 0, "tuple", false, "", &[]),
+    ("errorString", 11, "errors.errorString", true, "", &[("s", "string")]),
+    ("wrapError", 11, "fmt.wrapError", true, "", &[("msg", "string"), ("err", "error")]),
+    ("NumError", 11, "strconv.NumError", true, "", &[("Func", "string"), ("Num", "string"), ("Err", "error")]),
+    ("PathError", 11, "fs.PathError", true, "", &[("Op", "string"), ("Path", "string"), ("Err", "error")]),
+    ("p_errorString", 9, "*errors.errorString", false, "errorString", &[]),
+    ("p_wrapError", 9, "*fmt.wrapError", false, "wrapError", &[]),
+    ("p_NumError", 9, "*strconv.NumError", false, "NumError", &[]),
+    ("p_PathError", 9, "*fs.PathError", false, "PathError", &[]),
+    ("File", 11, "os.File", true, "", &[("fd", "int")]),
+    ("p_File", 9, "*os.File", false, "File", &[]),
+];
+
+/// Where static data starts; the first kilobyte stays unused, so that
+/// no value points there.
+const DATA: u32 = 1024;
+/// The calls a stack trace can name: the VM's limit, and main's frame.
+const FRAMES: u32 = 1025;
+/// The shadow stack the arguments of calls are passed on.
+const STACK: u32 = 1 << 20;
+
+/// Translates the module to the WebAssembly text format, or returns what
+/// it uses that the backend does not support.
+pub fn generate(module: &Module) -> Result<String, Vec<String>> {
+    let mut gen = Generator::default();
+    for (name, ..) in RUNTIME_TYPES {
+        gen.runtime_type(name);
+    }
+    let mut functions = vec![];
+    for i in 0..module.functions.len() {
+        if let Some(code) = gen.function(module, FuncId(i)) {
+            functions.push(code);
+        }
+    }
+    if !gen.errors.is_empty() {
+        return Err(gen.errors);
+    }
+    let globals: Vec<String> = module.globals.iter().map(|global| gen.type_ref(&global.ty)).collect();
+    let runtime = gen.expand_strings(RUNTIME);
+
+    // the table: the program's functions, then the runtime's that
+    // function values refer to
+    let mut table: Vec<String> = (0..module.functions.len()).map(|i| format!("$f{}", i)).collect();
+    let mut objects = String::new();
+    for name in BUILTINS {
+        let object = gen.func_object(table.len(), -1, name, &format!("builtin {}", name));
+        objects.push_str(&format!("(global $B_{} i32 (i32.const {}))\n", name, object));
+        table.push(format!("$b_{}", name));
+    }
+    for (path, name) in runtime_package_functions() {
+        let qualified = format!("{}.{}", path, name);
+        let object = match SOURCE.iter().find(|(p, n, _)| (*p, *n) == (path, name)) {
+            Some((.., sig)) => gen.func_object(table.len(), 2, &qualified, sig),
+            None => gen.func_object(table.len(), -1, &qualified, &format!("builtin {}", qualified)),
+        };
+        objects.push_str(&format!("(global $P_{}_{} i32 (i32.const {}))\n", path, name, object));
+        table.push(format!("$p_{}_{}", path, name));
+    }
+    objects.push_str(&format!("(global $fn_m_error i32 (i32.const {}))\n", table.len()));
+    table.push("$m_error".to_string());
+
+    // the rest of memory: globals, the frames of stack traces, the
+    // shadow stack, then the heap
+    gen.align(16);
+    let globals_at = gen.here();
+    let frames = globals_at + 16 * module.globals.len() as u32;
+    let stack = frames + 16 * FRAMES;
+    let heap = stack + STACK;
+    let pages = heap / 65536 + 1;
+
+    let mut out = String::from("(module\n");
+    out.push_str(&runtime);
+    out.push_str("\n;; The program\n\n");
+    for (name, at) in &gen.runtime_types {
+        out.push_str(&format!("(global $t_{} i32 (i32.const {}))\n", name, at));
+    }
+    for (i, (_, at)) in gen.types.iter().enumerate() {
+        out.push_str(&format!("(global $T{} i32 (i32.const {}))\n", i, at));
+    }
+    out.push_str(&objects);
+    out.push_str(&format!("(global $globals i32 (i32.const {}))\n", globals_at));
+    out.push_str(&format!("(global $frames i32 (i32.const {}))\n", frames));
+    out.push_str(&format!("(global $stack (mut i32) (i32.const {}))\n", stack));
+    out.push_str(&format!("(global $stack_end i32 (i32.const {}))\n", heap));
+    out.push_str(&format!("(global $hp (mut i32) (i32.const {}))\n", heap));
+    out.push_str(&format!("(memory (export \"memory\") {})\n", pages));
+    out.push_str(&format!("(table {} funcref)\n", table.len()));
+    out.push_str("(elem (i32.const 0) func");
+    for (i, name) in table.iter().enumerate() {
+        out.push_str(if i % 8 == 0 { "\n  " } else { " " });
+        out.push_str(name);
+    }
+    out.push_str(")\n");
+    out.push_str(&format!("(data (i32.const {})", DATA));
+    for chunk in gen.data.chunks(64) {
+        out.push_str(&format!("\n  {}", wat_string(chunk)));
+    }
+    out.push_str(")\n");
+    for code in functions {
+        out.push('\n');
+        out.push_str(&code);
+    }
+
+    out.push_str("\n(func $_start (export \"_start\")\n  (local $t i32) (local $x i64)\n");
+    for (i, ty) in globals.iter().enumerate() {
+        out.push_str(&format!("  (call $set (i32.const {}) (call $zero {}))\n", globals_at + 16 * i as u32, ty));
+    }
+    let failed = "  (if (global.get $throwing)\n    (then\n      (call $die)\n      (return)))\n";
+    for init in &module.inits {
+        out.push_str(&format!("  (call $f{} (i32.const 0) (i32.const 0) (i32.const 0))\n  (drop)\n  (drop)\n", init.0));
+        out.push_str(failed);
+    }
+    if let Some(main) = module.main {
+        // the VM calls main from a frame of its own, which traces omit
+        let slot = globals_at + 16 * main as u32;
+        out.push_str(&format!(
+            "  (call $raw (call $get (i32.const {0})))\n  (local.set $x)\n  (local.set $t)\n  (if (i32.and (i32.eq (call $kind (local.get $t)) (i32.const 6)) (i64.ne (local.get $x) (i64.const 0)))\n    (then\n      (drop (call $enter (i64.const 0) (i32.const 0) (i32.const 0)))\n      (call $call (call $get (i32.const {0})) (i32.const 0) (i32.const 0))\n      (drop)\n      (drop)))\n",
+            slot
+        ));
+        out.push_str(failed);
+    }
+    out.push_str(")\n)\n");
+    Ok(out)
+}
+
+/// Assembles generated text into a binary module.
+pub fn assemble(text: &str) -> Result<Vec<u8>, String> {
+    wat::parse(text).map(|module| module.encode())
+}
+
+/// Runs a binary module with `args` as `os.Args`, writing what it prints
+/// to `stdout` and `stderr`, and returns its exit status.
+pub fn run(binary: &[u8], args: &[String], stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<i32, String> {
+    let module = WasmModule::decode(binary)?;
+    let mut instance = Instance::new(module)?;
+    let mut host = Namu { args, stdout, stderr };
+    let result = instance.invoke(&mut host, "_start", &[]);
+    let _ = host.stdout.flush();
+    let _ = host.stderr.flush();
+    match result {
+        Ok(_) => Ok(0),
+        Err(Trap::Exit(code)) => Ok(code),
+        Err(trap) => Err(trap.to_string()),
+    }
+}
+
+/// The `namu` imports.
+struct Namu<'a> {
+    args: &'a [String],
+    stdout: &'a mut dyn Write,
+    stderr: &'a mut dyn Write,
+}
+
+fn bytes_at(memory: &[u8], ptr: u64, len: u64) -> Result<&[u8], Trap> {
+    let (start, end) = (ptr as usize, ptr as usize + len as usize);
+    memory.get(start..end).ok_or_else(|| Trap::Error("out of bounds memory access".to_string()))
+}
+
+fn store_bytes(memory: &mut [u8], ptr: u64, bytes: &[u8]) -> Result<(), Trap> {
+    let start = ptr as usize;
+    match memory.get_mut(start..start + bytes.len()) {
+        Some(dest) => {
+            dest.copy_from_slice(bytes);
+            Ok(())
+        }
+        None => Err(Trap::Error("out of bounds memory access".to_string())),
+    }
+}
+
+impl<'a> Host for Namu<'a> {
+    fn call(&mut self, import: &Import, args: &[u64], memory: &mut Vec<u8>) -> Result<Vec<u64>, Trap> {
+        let arg = |i: usize| args.get(i).copied().unwrap_or(0);
+        let float = |i: usize| f64::from_bits(arg(i));
+        let io = |e: std::io::Error| Trap::Error(e.to_string());
+        match import.name.as_str() {
+            "write" => {
+                let bytes = bytes_at(memory, arg(1), arg(2))?;
+                match arg(0) {
+                    1 => self.stdout.write_all(bytes).map_err(io)?,
+                    _ => self.stderr.write_all(bytes).map_err(io)?,
+                }
+                Ok(vec![])
+            }
+            "exit" => Err(Trap::Exit(arg(0) as i32)),
+            "args" => Ok(vec![self.args.len() as u64]),
+            "arg_len" => Ok(vec![self.args.get(arg(0) as usize).map_or(0, |a| a.len() as u64)]),
+            "arg" => {
+                let a = self.args.get(arg(0) as usize).map_or("", |a| a.as_str());
+                store_bytes(memory, arg(1), a.as_bytes())?;
+                Ok(vec![])
+            }
+            "format_float" => {
+                let prec = arg(2) as u32 as i32;
+                let prec = if prec < 0 { None } else { Some(prec as usize) };
+                let text = crate::value::format_float(float(0), arg(1) as u8 as char, prec);
+                store_bytes(memory, arg(3), text.as_bytes())?;
+                Ok(vec![text.len() as u64])
+            }
+            "parse_float" => {
+                let text = String::from_utf8_lossy(bytes_at(memory, arg(0), arg(1))?).into_owned();
+                Ok(vec![text.parse::<f64>().unwrap_or(f64::NAN).to_bits()])
+            }
+            "math" => {
+                let (x, y) = (float(1), float(2));
+                let r = match arg(0) {
+                    0 => x.round(),
+                    1 => x.cbrt(),
+                    2 => x.exp(),
+                    3 => x.ln(),
+                    4 => x.log2(),
+                    5 => x.log10(),
+                    6 => x.sin(),
+                    7 => x.cos(),
+                    8 => x.tan(),
+                    9 => x.atan(),
+                    10 => x.powf(y),
+                    11 => x.atan2(y),
+                    12 => x.hypot(y),
+                    13 => x % y,
+                    _ => f64::NAN,
+                };
+                Ok(vec![r.to_bits()])
+            }
+            "fold" => {
+                let r = arg(0) as u32;
+                let folded = std::char::from_u32(r).map_or(r, |c| {
+                    let mut mapped: Vec<char> =
+                        if arg(1) != 0 { c.to_uppercase().collect() } else { c.to_lowercase().collect() };
+                    if mapped.len() == 1 {
+                        mapped.pop().unwrap() as u32
+                    } else {
+                        r
+                    }
+                });
+                Ok(vec![folded as u64])
+            }
+            "seed" => {
+                // wasm32 has no clock to seed from, as in the VM
+                let seed = match cfg!(target_arch = "wasm32") {
+                    true => 0,
+                    false => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64),
+                };
+                Ok(vec![seed])
+            }
+            name => Err(Trap::Error(format!("unknown import namu.{}", name))),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Generator {
+    /// Static data, from `DATA` on: strings, descriptors and the
+    /// runtime's function objects.
+    data: Vec<u8>,
+    /// The bits of each string stored.
+    strings: HashMap<Rc<[u8]>, u64>,
+    runtime_types: Vec<(&'static str, u32)>,
+    /// Types with descriptors of the program's own, `$T<index>`, and
+    /// their addresses.
+    types: Vec<(Type, u32)>,
+    errors: Vec<String>,
+    /// The most arguments a call of the function being generated passes.
+    args: usize,
+}
+
+/// A string in the text format: printable ASCII as it is, the rest in
+/// hexadecimal escapes.
+fn wat_string(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => out.push_str(&format!("\\{:02x}", b)),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{:02x}", b)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The bytes of the string literal that starts `text`, after its opening
+/// quote, and the length of the text up to and including its closing one.
+fn unescape(text: &[u8]) -> (Vec<u8>, usize) {
+    let hex = |b: u8| (b as char).to_digit(16).unwrap_or(0) as u8;
+    let mut bytes = vec![];
+    let mut i = 0;
+    while i < text.len() && text[i] != b'"' {
+        if text[i] != b'\\' {
+            bytes.push(text[i]);
+            i += 1;
+            continue;
+        }
+        let e = text.get(i + 1).copied().unwrap_or(b'\\');
+        i += 2;
+        bytes.push(match e {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'"' | b'\'' | b'\\' => e,
+            _ => {
+                i += 1;
+                hex(e) << 4 | hex(text.get(i - 1).copied().unwrap_or(b'0'))
+            }
+        });
+    }
+    (bytes, i + 1)
+}
+
+/// The package functions the runtime defines, by path and name.
+fn runtime_package_functions() -> Vec<(&'static str, &'static str)> {
+    RUNTIME
+        .match_indices("(func $p_")
+        .filter_map(|(i, m)| {
+            let rest = &RUNTIME[i + m.len()..];
+            let end = rest.find(' ')?;
+            let (path, name) = rest[..end].split_once('_')?;
+            Some((path, name))
+        })
+        .collect()
+}
+
+fn v(id: ValueId) -> String {
+    format!("(local.get $v{0}_t) (local.get $v{0})", id.0)
+}
+
+/// Assigns the pair on the stack to the value.
+fn set(id: ValueId) -> String {
+    format!("(local.set $v{0})\n  (local.set $v{0}_t)", id.0)
+}
+
+fn float(x: f64) -> String {
+    format!("(global.get $t_float) (i64.const {})", x.to_bits() as i64)
+}
+
+impl Generator {
+    fn here(&self) -> u32 {
+        DATA + self.data.len() as u32
+    }
+
+    fn align(&mut self, n: usize) {
+        while !self.data.len().is_multiple_of(n) {
+            self.data.push(0);
+        }
+    }
+
+    /// Reserves zeroed data, returning its address.
+    fn reserve(&mut self, size: usize) -> u32 {
+        self.align(8);
+        let at = self.here();
+        self.data.resize(self.data.len() + size, 0);
+        at
+    }
+
+    fn put_u32(&mut self, at: u32, n: u32) {
+        let i = (at - DATA) as usize;
+        self.data[i..i + 4].copy_from_slice(&n.to_le_bytes());
+    }
+
+    fn put_u64(&mut self, at: u32, n: u64) {
+        let i = (at - DATA) as usize;
+        self.data[i..i + 8].copy_from_slice(&n.to_le_bytes());
+    }
+
+    /// The bits of a string, storing its bytes the first time.
+    fn string_bits(&mut self, s: &[u8]) -> u64 {
+        if s.is_empty() {
+            return 0;
+        }
+        if let Some(&bits) = self.strings.get(s) {
+            return bits;
+        }
+        let at = self.here();
+        self.data.extend_from_slice(s);
+        let bits = (s.len() as u64) << 32 | at as u64;
+        self.strings.insert(s.into(), bits);
+        bits
+    }
+
+    fn string_const(&mut self, s: &[u8]) -> String {
+        format!("(i64.const {})", self.string_bits(s) as i64)
+    }
+
+    /// Replaces the runtime's `(string "...")` forms with the constants of
+    /// the strings.
+    fn expand_strings(&mut self, text: &str) -> String {
+        const FORM: &str = "(string \"";
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(i) = rest.find(FORM) {
+            out.push_str(&rest[..i]);
+            let (bytes, len) = unescape(&rest.as_bytes()[i + FORM.len()..]);
+            out.push_str(&self.string_const(&bytes));
+            // past the closing quote and parenthesis
+            rest = &rest[i + FORM.len() + len + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// Lays out the runtime's descriptor of that name, and those it
+    /// refers to, the first time.
+    fn runtime_type(&mut self, name: &str) -> u32 {
+        if let Some(&(_, at)) = self.runtime_types.iter().find(|(n, _)| *n == name) {
+            return at;
+        }
+        let &(name, kind, go_name, named, elem, fields) = RUNTIME_TYPES.iter().find(|t| t.0 == name).expect("runtime type");
+        let at = self.reserve(40);
+        self.runtime_types.push((name, at));
+        let elem = if elem.is_empty() { 0 } else { self.runtime_type(elem) };
+        let field_types: Vec<u32> = fields.iter().map(|(_, ty)| self.runtime_type(ty)).collect();
+        let field_names: Vec<&[u8]> = fields.iter().map(|(name, _)| name.as_bytes()).collect();
+        self.describe(at, kind, go_name.as_bytes(), named, elem, 0, &field_names, &field_types);
+        at
+    }
+
+    /// Fills in the descriptor at `at`.
+    #[allow(clippy::too_many_arguments)]
+    fn describe(&mut self, at: u32, kind: u32, name: &[u8], named: bool, elem: u32, key: u32, fields: &[&[u8]], types: &[u32]) {
+        let name = self.string_bits(name);
+        let names: Vec<u64> = fields.iter().map(|f| self.string_bits(f)).collect();
+        self.put_u32(at, kind);
+        self.put_u32(at + 4, named as u32);
+        self.put_u64(at + 8, name);
+        self.put_u32(at + 16, elem);
+        self.put_u32(at + 20, key);
+        self.put_u32(at + 24, fields.len() as u32);
+        if !fields.is_empty() {
+            let fnames = self.reserve(8 * names.len());
+            for (i, &bits) in names.iter().enumerate() {
+                self.put_u64(fnames + 8 * i as u32, bits);
+            }
+            let ftypes = self.reserve(4 * types.len());
+            for (i, &t) in types.iter().enumerate() {
+                self.put_u32(ftypes + 4 * i as u32, t);
+            }
+            self.put_u32(at + 28, fnames);
+            self.put_u32(at + 32, ftypes);
+        }
+    }
+
+    /// A function object of the runtime's: {table index, parameters, or
+    /// -1 for a builtin, name, signature, a receiver of nil}.
+    fn func_object(&mut self, index: usize, nparams: i32, name: &str, sig: &str) -> u32 {
+        let name = self.string_bits(name.as_bytes());
+        let sig = self.string_bits(sig.as_bytes());
+        let nil = self.runtime_type("nil");
+        let at = self.reserve(48);
+        self.put_u32(at, index as u32);
+        self.put_u32(at + 4, nparams as u32);
+        self.put_u64(at + 8, name);
+        self.put_u64(at + 16, sig);
+        self.put_u32(at + 24, nil);
+        at
+    }
+
+    /// The address of the descriptor of the type, laying one out the
+    /// first time a type is seen.
+    fn type_ref(&mut self, ty: &Type) -> String {
+        if let Some(name) = c::runtime_type(ty) {
+            return format!("(global.get $t_{})", name);
+        }
+        if let Some(i) = self.types.iter().position(|(t, _)| t.identical(ty)) {
+            return format!("(global.get $T{})", i);
+        }
+        let i = self.types.len();
+        let at = self.reserve(40);
+        self.types.push((ty.clone(), at));
+        let name = ty.to_string();
+        let address = |gen: &mut Generator, ty: &Type| -> u32 {
+            let r = gen.type_ref(ty);
+            match r.strip_prefix("(global.get $t_") {
+                Some(rest) => gen.runtime_type(rest.trim_end_matches(')')),
+                None => gen.types[r["(global.get $T".len()..r.len() - 1].parse::<usize>().unwrap()].1,
+            }
+        };
+        match ty {
+            Type::Slice(elem) | Type::Pointer(elem) | Type::Chan(elem) => {
+                let kind = match ty {
+                    Type::Slice(_) => 7,
+                    Type::Pointer(_) => 9,
+                    _ => 10,
+                };
+                let elem = address(self, elem);
+                self.describe(at, kind, name.as_bytes(), false, elem, 0, &[], &[]);
+            }
+            Type::Map(key, elem) => {
+                let key = address(self, key);
+                let elem = address(self, elem);
+                self.describe(at, 8, name.as_bytes(), false, elem, key, &[], &[]);
+            }
+            Type::Struct(st) => {
+                let fields = st.fields.borrow().clone();
+                let types: Vec<u32> = fields.iter().map(|f| address(self, &f.ty)).collect();
+                let names: Vec<&[u8]> = fields.iter().map(|f| f.name.as_bytes()).collect();
+                self.describe(at, 11, name.as_bytes(), st.name.is_some(), 0, 0, &names, &types);
+            }
+            Type::Interface(it) => self.describe(at, 12, name.as_bytes(), it.name.is_some(), 0, 0, &[], &[]),
+            // the runtime describes the rest
+            _ => unreachable!("type {} has a runtime descriptor", ty),
+        }
+        format!("(global.get $T{})", i)
+    }
+
+    fn unsupported(&mut self, func: &Function, what: &str) -> Option<String> {
+        let msg = format!("{}: {} not supported by the WebAssembly backend", func.name, what);
+        if !self.errors.contains(&msg) {
+            self.errors.push(msg);
+        }
+        None
+    }
+
+    /// Stores the values as the arguments of a call, in the function's
+    /// frame on the shadow stack, and returns the code and the address to
+    /// pass.
+    fn args(&mut self, values: &[ValueId]) -> (String, &'static str) {
+        if values.is_empty() {
+            return (String::new(), "(i32.const 0)");
+        }
+        self.args = self.args.max(values.len());
+        let mut code = String::new();
+        for (i, &value) in values.iter().enumerate() {
+            code.push_str(&format!("(call $set (i32.add (local.get $base) (i32.const {})) {})\n  ", 16 * i, v(value)));
+        }
+        (code, "(local.get $base)")
+    }
+
+    /// The function for an IR function, or None after recording why it
+    /// cannot have one.
+    fn function(&mut self, module: &Module, id: FuncId) -> Option<String> {
+        let func = module.function(id);
+        self.args = 0;
+        let mut locals = String::from("  (local $base i32) (local $pc i32) (local $fr i32) (local $r i32)\n");
+        for block in &func.blocks {
+            for &value in &block.insts {
+                match &func.values[value.0].ty {
+                    Ty::Void => continue,
+                    Ty::Value(_) => locals.push_str(&format!("  (local $v{0}_t i32) (local $v{0} i64)\n", value.0)),
+                    Ty::Tuple(_) | Ty::Iter => locals.push_str(&format!("  (local $v{} i32)\n", value.0)),
+                }
+            }
+        }
+        let mut body = String::from("  loop $dispatch\n");
+        for i in (0..func.blocks.len()).rev() {
+            body.push_str(&format!("  block $b{}\n", i));
+        }
+        let labels: Vec<String> = (0..func.blocks.len()).map(|i| format!("$b{}", i)).collect();
+        body.push_str(&format!("  (br_table {} (local.get $pc))\n", labels.join(" ")));
+        for (i, block) in func.blocks.iter().enumerate() {
+            body.push_str("  end\n");
+            for &value in &block.insts {
+                let code = self.inst(module, func, BlockId(i), value)?;
+                if !code.is_empty() {
+                    body.push_str(&format!("  {}\n", code));
+                }
+            }
+        }
+        body.push_str("  end\n  (unreachable))\n");
+
+        let mut out = format!("(func $f{} (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)\n", id.0);
+        out.push_str(&locals);
+        out.push_str(&format!(
+            "  (local.set $base (call $enter {} (i32.const {}) (i32.const {})))\n  (if (i32.eqz (local.get $base))\n    (then (return (call $nil))))\n",
+            self.string_const(func.name.as_bytes()),
+            !func.params.is_empty() as u8,
+            16 * self.args
+        ));
+        if func.recover.is_some() {
+            out.push_str("  (local.set $fr (call $frame_push))\n");
+        }
+        out.push_str(&body);
+        Some(out)
+    }
+
+    /// What follows code that may panic: a return that goes on unwinding,
+    /// or in a function that defers calls, running them.
+    fn check(func: &Function) -> String {
+        match func.recover {
+            None => "\n  (if (global.get $throwing)\n    (then (return (call $nil))))".to_string(),
+            Some(recover) => format!(
+                "\n  (if (global.get $throwing)\n    (then\n      (if (i32.eqz (call $unwind (local.get $fr)))\n        (then (return (call $nil))))\n      (local.set $pc (i32.const {}))\n      (br $dispatch)))",
+                recover.0
+            ),
+        }
+    }
+
+    /// The code for one instruction.
+    fn inst(&mut self, module: &Module, func: &Function, block: BlockId, id: ValueId) -> Option<String> {
+        let data = &func.values[id.0];
+        let ty = data.ty.value().cloned();
+        let ty_ref = |gen: &mut Generator| gen.type_ref(ty.as_ref().unwrap_or(&Type::Nil));
+        let static_ty = |value: ValueId| func.ty(value).value().cloned();
+        let check = Generator::check(func);
+        let code = match &data.inst {
+            Inst::Const(c) => {
+                let value = match c {
+                    Const::Nil => format!("(call $zero {})", ty_ref(self)),
+                    Const::Bool(b) => format!("(global.get $t_bool) (i64.const {})", *b as u8),
+                    Const::Int(n) if matches!(ty, Some(Type::Float64)) => float(*n as f64),
+                    Const::Int(n) => format!("(global.get $t_int) (i64.const {})", n),
+                    Const::Float(x) => float(*x),
+                    Const::String(s) => format!("(global.get $t_string) {}", self.string_const(s)),
+                };
+                match &ty {
+                    Some(t @ Type::Interface(_)) if *c != Const::Nil => {
+                        format!("(call $convert {} {})\n  {}", value, self.type_ref(t), set(id))
+                    }
+                    _ => format!("{}\n  {}", value, set(id)),
+                }
+            }
+            Inst::Zero => format!("(call $zero {})\n  {}", ty_ref(self), set(id)),
+            Inst::Param(i) => format!("(call $get (i32.add (local.get $argv) (i32.const {})))\n  {}", 16 * i, set(id)),
+            Inst::FreeVar(i) => {
+                format!("(call $ref {} (call $free_var (local.get $self) (i32.const {})))\n  {}", ty_ref(self), i, set(id))
+            }
+            Inst::Global(i) => {
+                // globals follow the static data, laid out after the
+                // functions are generated
+                format!(
+                    "(call $ref {} (i32.add (global.get $globals) (i32.const {})))\n  {}",
+                    ty_ref(self),
+                    16 * i,
+                    set(id)
+                )
+            }
+            Inst::Current => format!("(call $ref (global.get $t_func) (local.get $self))\n  {}", set(id)),
+            Inst::Closure(target, captured) => {
+                let f = module.function(*target);
+                let params: Vec<&str> = f.params.iter().map(|p| p.name.as_str()).collect();
+                let sig = self.string_const(format!("fn({})", params.join(", ")).as_bytes());
+                let name = self.string_const(f.name.as_bytes());
+                let (code, argv) = self.args(captured);
+                format!(
+                    "{}(call $closure (i32.const {}) {} {} (i32.const {}) (i32.const {}) {})\n  {}",
+                    code,
+                    target.0,
+                    name,
+                    sig,
+                    f.params.len(),
+                    captured.len(),
+                    argv,
+                    set(id)
+                )
+            }
+            Inst::Builtin(name) => {
+                if !BUILTINS.contains(name) {
+                    return self.unsupported(func, &format!("builtin {} as a value is", name));
+                }
+                format!("(call $ref (global.get $t_func) (global.get $B_{}))\n  {}", name, set(id))
+            }
+            Inst::Package(path, name) => {
+                if matches!(ty, Some(Type::Func)) {
+                    if !package_function(path, name) {
+                        return self.unsupported(func, &format!("{}.{} is", path, name));
+                    }
+                    format!("(call $ref (global.get $t_func) (global.get $P_{}_{}))\n  {}", path, name, set(id))
+                } else {
+                    if !RUNTIME.contains(&format!("(func $v_{}_{} ", path, name)) {
+                        return self.unsupported(func, &format!("{}.{} is", path, name));
+                    }
+                    format!("(call $v_{}_{})\n  {}", path, name, set(id))
+                }
+            }
+            Inst::Phi(_) => String::new(),
+            Inst::Unary(op, x) => {
+                let f = match op {
+                    UnOp::Neg => "$neg",
+                    UnOp::Not => "$not",
+                };
+                self.boxed(id, &ty, format!("(call {} {})", f, v(*x)), &check)
+            }
+            Inst::Binary(op, a, b) => {
+                let f = match op {
+                    BinOp::Add => "$add",
+                    BinOp::Sub => "$sub",
+                    BinOp::Mul => "$mul",
+                    BinOp::Div => "$div",
+                    BinOp::Rem => "$rem",
+                    BinOp::Eq => "$eq",
+                    BinOp::Ne => "$ne",
+                    BinOp::Lt => "$lt",
+                    BinOp::Le => "$le",
+                    BinOp::Gt => "$gt",
+                    BinOp::Ge => "$ge",
+                };
+                self.boxed(id, &ty, format!("(call {} {} {})", f, v(*a), v(*b)), &check)
+            }
+            Inst::Convert(x) => match (&ty, static_ty(*x)) {
+                (Some(to), Some(from)) if !to.is_interface() && to.identical(&from) => format!("{}\n  {}", v(*x), set(id)),
+                _ => format!("(call $conversion {} {})\n  {}{}", v(*x), ty_ref(self), set(id), check),
+            },
+            Inst::Assert(x, false) => format!("(call $assert {} {})\n  {}{}", v(*x), ty_ref(self), set(id), check),
+            Inst::Assert(x, true) => {
+                let target = c::tuple_first(&data.ty);
+                format!("(local.set $v{} (call $assert_ok {} {})){}", id.0, v(*x), self.type_ref(&target), check)
+            }
+            Inst::Alloc => format!("(call $alloc_ptr {})\n  {}", ty_ref(self), set(id)),
+            Inst::Load(p) => format!("(call $load {})\n  {}{}", v(*p), set(id), check),
+            Inst::Store(p, x) => format!("(call $store {} {}){}", v(*p), v(*x), check),
+            Inst::Field(x, name) => format!(
+                "(call $field {} {} (i32.const {}))\n  {}{}",
+                v(*x),
+                self.string_const(name.as_bytes()),
+                c::field_hint(static_ty(*x), name),
+                set(id),
+                check
+            ),
+            Inst::FieldAddr(x, name) => format!(
+                "(call $field_addr {} {} (i32.const {}) {})\n  {}{}",
+                v(*x),
+                self.string_const(name.as_bytes()),
+                c::field_hint(static_ty(*x), name),
+                ty_ref(self),
+                set(id),
+                check
+            ),
+            Inst::Index(x, i, false) => format!("(call $index {} {})\n  {}{}", v(*x), v(*i), set(id), check),
+            Inst::Index(x, i, true) => format!("(local.set $v{} (call $index_ok {} {})){}", id.0, v(*x), v(*i), check),
+            Inst::IndexAddr(x, i) => {
+                format!("(call $index_addr {} {} {})\n  {}{}", v(*x), v(*i), ty_ref(self), set(id), check)
+            }
+            Inst::SetIndex(x, i, value) => format!("(call $set_index {} {} {}){}", v(*x), v(*i), v(*value), check),
+            Inst::Slice(x, low, high) => {
+                let bound = |b: &Option<ValueId>| b.map_or("(call $nil)".to_string(), v);
+                format!("(call $slice_expr {} {} {})\n  {}{}", v(*x), bound(low), bound(high), set(id), check)
+            }
+            Inst::BoundsCheck(i, n) => format!("(call $check {} {}){}", v(*i), v(*n), check),
+            Inst::Composite(pairs) => {
+                let t = ty_ref(self);
+                let flat: Vec<ValueId> = pairs.iter().flat_map(|&(k, x)| [k, x]).collect();
+                let (code, argv) = self.args(&flat);
+                format!("{}(call $composite {} (i32.const {}) {})\n  {}{}", code, t, pairs.len(), argv, set(id), check)
+            }
+            Inst::Make(sizes) => {
+                if matches!(ty, Some(Type::Chan(_))) {
+                    return self.unsupported(func, "channels are");
+                }
+                let t = ty_ref(self);
+                let (code, argv) = self.args(sizes);
+                format!("{}(call $make {} (i32.const {}) {})\n  {}{}", code, t, sizes.len(), argv, set(id), check)
+            }
+            Inst::Call(callee, args, spread) => {
+                let (code, argv) = self.args(args);
+                let n = args.len();
+                let call = match func.inst(*callee) {
+                    _ if *spread => format!("(call $call_spread {} (i32.const {}) {})", v(*callee), n, argv),
+                    Inst::Builtin(name) if BUILTINS.contains(name) => {
+                        format!("(call $b_{0} (global.get $B_{0}) (i32.const {1}) {2})", name, n, argv)
+                    }
+                    Inst::Package(path, name) if package_builtin(path, name) => {
+                        format!("(call $p_{0}_{1} (global.get $P_{0}_{1}) (i32.const {2}) {3})", path, name, n, argv)
+                    }
+                    _ => format!("(call $call {} (i32.const {}) {})", v(*callee), n, argv),
+                };
+                match &data.ty {
+                    Ty::Void => format!("{}{}\n  (drop)\n  (drop){}", code, call, check),
+                    Ty::Tuple(_) => format!("{}(local.set $v{} (call $untuple {})){}", code, id.0, call, check),
+                    _ => format!("{}{}\n  {}{}", code, call, set(id), check),
+                }
+            }
+            Inst::Extract(t, i) => format!("(call $get (i32.add (local.get $v{}) (i32.const {})))\n  {}", t.0, 16 * i, set(id)),
+            Inst::Go(_, _) => return self.unsupported(func, "goroutines are"),
+            Inst::Defer(callee, args) => {
+                let (code, argv) = self.args(args);
+                format!("{}(call $defer (local.get $fr) {} (i32.const {}) {})", code, v(*callee), args.len(), argv)
+            }
+            Inst::RunDefers => format!(
+                "(local.set $r (call $unwind (local.get $fr)))\n  (if (i32.eqz (local.get $r))\n    (then (return (call $nil))))\n  (if (i32.eq (local.get $r) (i32.const 2))\n    (then\n      (local.set $pc (i32.const {}))\n      (br $dispatch)))",
+                func.recover.map_or(0, |b| b.0)
+            ),
+            Inst::Send(_, _) | Inst::Recv(_, _) | Inst::Select(_, _) => return self.unsupported(func, "channels are"),
+            Inst::Range(x) => {
+                if matches!(static_ty(*x), Some(Type::Chan(_))) {
+                    return self.unsupported(func, "channels are");
+                }
+                format!("(local.set $v{} (call $range {})){}", id.0, v(*x), check)
+            }
+            Inst::Next(it) => format!("(local.set $v{} (call $next (local.get $v{})))", id.0, it.0),
+            Inst::Jump(target) => edge(func, block, *target),
+            Inst::Branch(cond, t, f) => format!(
+                "(if (call $truth {})\n    (then\n      {})\n    (else\n      {}))",
+                v(*cond),
+                edge(func, block, *t).replace("\n  ", "\n      "),
+                edge(func, block, *f).replace("\n  ", "\n      ")
+            ),
+            Inst::Return(value) => {
+                let value = value.map_or("(call $nil)".to_string(), v);
+                format!("(call $leave (local.get $base))\n  (return {})", value)
+            }
+        };
+        Some(code)
+    }
+
+    /// Assigns an operator's result, boxing it when the IR types it as
+    /// an interface: the runtime computes with the values interfaces hold.
+    fn boxed(&mut self, id: ValueId, ty: &Option<Type>, value: String, check: &str) -> String {
+        match ty {
+            Some(t @ Type::Interface(_)) => format!("(call $convert {} {})\n  {}{}", value, self.type_ref(t), set(id), check),
+            _ => format!("{}\n  {}{}", value, set(id), check),
+        }
+    }
+}
+
+/// Whether the runtime implements the package function, as a builtin of
+/// the VM or as one written in source there.
+fn package_function(path: &str, name: &str) -> bool {
+    RUNTIME.contains(&format!("(func $p_{}_{} ", path, name))
+}
+
+/// Whether the runtime implements the package function as a builtin,
+/// which calls can go to directly as they check their own arguments.
+fn package_builtin(path: &str, name: &str) -> bool {
+    package_function(path, name) && !SOURCE.iter().any(|(p, n, _)| (*p, *n) == (path, name))
+}
+
+/// A jump along the edge, with the copies into the target's phis done in
+/// parallel: every operand is pushed before any phi is set. The block
+/// after this one is where control falls through to.
+fn edge(func: &Function, from: BlockId, to: BlockId) -> String {
+    let mut copies = vec![];
+    for &value in &func.blocks[to.0].insts {
+        if let Inst::Phi(edges) = func.inst(value) {
+            if let Some(&(_, operand)) = edges.iter().find(|(b, _)| *b == from) {
+                copies.push((value, operand));
+            }
+        }
+    }
+    let mut code = vec![];
+    for (_, operand) in &copies {
+        code.push(v(*operand));
+    }
+    for (phi, _) in copies.iter().rev() {
+        code.push(set(*phi));
+    }
+    if to.0 != from.0 + 1 {
+        code.push(format!("(local.set $pc (i32.const {}))\n  (br $dispatch)", to.0));
+    }
+    if code.is_empty() {
+        return "(nop)".to_string();
+    }
+    code.join("\n  ")
+}
@@ -0,0 +1,984 @@
+//! A WebAssembly module in memory, and its binary encoding.
+//!
+//! The structure covers what the backend produces: functions over `i32`,
+//! `i64` and `f64`, one memory, one table of functions, globals with
+//! constant initializers, and active data and element segments. Decoding
+//! accepts the same subset, which is what the VM runs.
+
+use std::convert::TryInto;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValType {
+    I32,
+    I64,
+    F64,
+}
+
+impl ValType {
+    pub fn name(self) -> &'static str {
+        match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+            ValType::F64 => "f64",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ValType> {
+        match name {
+            "i32" => Some(ValType::I32),
+            "i64" => Some(ValType::I64),
+            "f64" => Some(ValType::F64),
+            _ => None,
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            ValType::I32 => 0x7f,
+            ValType::I64 => 0x7e,
+            ValType::F64 => 0x7c,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<ValType> {
+        match code {
+            0x7f => Some(ValType::I32),
+            0x7e => Some(ValType::I64),
+            0x7c => Some(ValType::F64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct FuncType {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+/// What a block, loop or if leaves on the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockType {
+    Empty,
+    Value(ValType),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemArg {
+    /// The log2 of the alignment.
+    pub align: u32,
+    pub offset: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    Unreachable,
+    Nop,
+    Block(BlockType),
+    Loop(BlockType),
+    If(BlockType),
+    Else,
+    End,
+    Br(u32),
+    BrIf(u32),
+    BrTable(Vec<u32>, u32),
+    Return,
+    Call(u32),
+    /// An indirect call through table 0, by type index.
+    CallIndirect(u32),
+    Drop,
+    Select,
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    /// A load or store by opcode, see `MEMORY_OPS`.
+    Load(u8, MemArg),
+    Store(u8, MemArg),
+    MemorySize,
+    MemoryGrow,
+    I32Const(i32),
+    I64Const(i64),
+    F64Const(f64),
+    /// A numeric instruction without immediates, by opcode; see `OPS`.
+    Op(u8),
+    /// A saturating truncation, by its opcode after the 0xfc prefix.
+    TruncSat(u8),
+    MemoryCopy,
+    MemoryFill,
+}
+
+/// Loads and stores: opcode, name and natural alignment.
+pub const MEMORY_OPS: &[(u8, &str, u32)] = &[
+    (0x28, "i32.load", 2),
+    (0x29, "i64.load", 3),
+    (0x2b, "f64.load", 3),
+    (0x2c, "i32.load8_s", 0),
+    (0x2d, "i32.load8_u", 0),
+    (0x2e, "i32.load16_s", 1),
+    (0x2f, "i32.load16_u", 1),
+    (0x30, "i64.load8_s", 0),
+    (0x31, "i64.load8_u", 0),
+    (0x32, "i64.load16_s", 1),
+    (0x33, "i64.load16_u", 1),
+    (0x34, "i64.load32_s", 2),
+    (0x35, "i64.load32_u", 2),
+    (0x36, "i32.store", 2),
+    (0x37, "i64.store", 3),
+    (0x39, "f64.store", 3),
+    (0x3a, "i32.store8", 0),
+    (0x3b, "i32.store16", 1),
+    (0x3c, "i64.store8", 0),
+    (0x3d, "i64.store16", 1),
+    (0x3e, "i64.store32", 2),
+];
+
+/// Numeric instructions without immediates, leaving out those on `f32`.
+pub const OPS: &[(u8, &str)] = &[
+    (0x45, "i32.eqz"),
+    (0x46, "i32.eq"),
+    (0x47, "i32.ne"),
+    (0x48, "i32.lt_s"),
+    (0x49, "i32.lt_u"),
+    (0x4a, "i32.gt_s"),
+    (0x4b, "i32.gt_u"),
+    (0x4c, "i32.le_s"),
+    (0x4d, "i32.le_u"),
+    (0x4e, "i32.ge_s"),
+    (0x4f, "i32.ge_u"),
+    (0x50, "i64.eqz"),
+    (0x51, "i64.eq"),
+    (0x52, "i64.ne"),
+    (0x53, "i64.lt_s"),
+    (0x54, "i64.lt_u"),
+    (0x55, "i64.gt_s"),
+    (0x56, "i64.gt_u"),
+    (0x57, "i64.le_s"),
+    (0x58, "i64.le_u"),
+    (0x59, "i64.ge_s"),
+    (0x5a, "i64.ge_u"),
+    (0x61, "f64.eq"),
+    (0x62, "f64.ne"),
+    (0x63, "f64.lt"),
+    (0x64, "f64.gt"),
+    (0x65, "f64.le"),
+    (0x66, "f64.ge"),
+    (0x67, "i32.clz"),
+    (0x68, "i32.ctz"),
+    (0x69, "i32.popcnt"),
+    (0x6a, "i32.add"),
+    (0x6b, "i32.sub"),
+    (0x6c, "i32.mul"),
+    (0x6d, "i32.div_s"),
+    (0x6e, "i32.div_u"),
+    (0x6f, "i32.rem_s"),
+    (0x70, "i32.rem_u"),
+    (0x71, "i32.and"),
+    (0x72, "i32.or"),
+    (0x73, "i32.xor"),
+    (0x74, "i32.shl"),
+    (0x75, "i32.shr_s"),
+    (0x76, "i32.shr_u"),
+    (0x77, "i32.rotl"),
+    (0x78, "i32.rotr"),
+    (0x79, "i64.clz"),
+    (0x7a, "i64.ctz"),
+    (0x7b, "i64.popcnt"),
+    (0x7c, "i64.add"),
+    (0x7d, "i64.sub"),
+    (0x7e, "i64.mul"),
+    (0x7f, "i64.div_s"),
+    (0x80, "i64.div_u"),
+    (0x81, "i64.rem_s"),
+    (0x82, "i64.rem_u"),
+    (0x83, "i64.and"),
+    (0x84, "i64.or"),
+    (0x85, "i64.xor"),
+    (0x86, "i64.shl"),
+    (0x87, "i64.shr_s"),
+    (0x88, "i64.shr_u"),
+    (0x89, "i64.rotl"),
+    (0x8a, "i64.rotr"),
+    (0x99, "f64.abs"),
+    (0x9a, "f64.neg"),
+    (0x9b, "f64.ceil"),
+    (0x9c, "f64.floor"),
+    (0x9d, "f64.trunc"),
+    (0x9e, "f64.nearest"),
+    (0x9f, "f64.sqrt"),
+    (0xa0, "f64.add"),
+    (0xa1, "f64.sub"),
+    (0xa2, "f64.mul"),
+    (0xa3, "f64.div"),
+    (0xa4, "f64.min"),
+    (0xa5, "f64.max"),
+    (0xa6, "f64.copysign"),
+    (0xa7, "i32.wrap_i64"),
+    (0xaa, "i32.trunc_f64_s"),
+    (0xab, "i32.trunc_f64_u"),
+    (0xac, "i64.extend_i32_s"),
+    (0xad, "i64.extend_i32_u"),
+    (0xb0, "i64.trunc_f64_s"),
+    (0xb1, "i64.trunc_f64_u"),
+    (0xb7, "f64.convert_i32_s"),
+    (0xb8, "f64.convert_i32_u"),
+    (0xb9, "f64.convert_i64_s"),
+    (0xba, "f64.convert_i64_u"),
+    (0xbd, "i64.reinterpret_f64"),
+    (0xbf, "f64.reinterpret_i64"),
+    (0xc0, "i32.extend8_s"),
+    (0xc1, "i32.extend16_s"),
+    (0xc2, "i64.extend8_s"),
+    (0xc3, "i64.extend16_s"),
+    (0xc4, "i64.extend32_s"),
+];
+
+/// Saturating truncations after the 0xfc prefix, leaving out `f32`.
+pub const TRUNC_SAT_OPS: &[(u8, &str)] = &[
+    (2, "i32.trunc_sat_f64_s"),
+    (3, "i32.trunc_sat_f64_u"),
+    (6, "i64.trunc_sat_f64_s"),
+    (7, "i64.trunc_sat_f64_u"),
+];
+
+pub fn op_name(op: u8) -> &'static str {
+    OPS.iter().find(|(code, _)| *code == op).map_or("?", |(_, name)| name)
+}
+
+pub fn memory_op_name(op: u8) -> &'static str {
+    MEMORY_OPS.iter().find(|(code, _, _)| *code == op).map_or("?", |(_, name, _)| name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    /// The function's type index; only functions are imported.
+    pub ty: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Func {
+    pub ty: u32,
+    /// Locals after the parameters.
+    pub locals: Vec<ValType>,
+    pub body: Vec<Instr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub ty: ValType,
+    pub mutable: bool,
+    /// The constant it starts with.
+    pub init: Instr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    Func,
+    Table,
+    Memory,
+    Global,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Export {
+    pub name: String,
+    pub kind: ExportKind,
+    pub index: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Data {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    pub types: Vec<FuncType>,
+    pub imports: Vec<Import>,
+    pub funcs: Vec<Func>,
+    /// The minimum size of table 0, if there is one.
+    pub table: Option<u32>,
+    /// The minimum pages of memory 0, if there is one.
+    pub memory: Option<u32>,
+    pub globals: Vec<Global>,
+    pub exports: Vec<Export>,
+    pub start: Option<u32>,
+    /// Function indices placed in table 0 from offset 0.
+    pub elems: Vec<u32>,
+    pub data: Vec<Data>,
+}
+
+impl Module {
+    /// The type index of the signature, adding it if it is new.
+    pub fn type_index(&mut self, ty: FuncType) -> u32 {
+        match self.types.iter().position(|t| *t == ty) {
+            Some(i) => i as u32,
+            None => {
+                self.types.push(ty);
+                self.types.len() as u32 - 1
+            }
+        }
+    }
+
+    /// The type of a function by its index, imports first.
+    pub fn func_type(&self, func: u32) -> Option<&FuncType> {
+        let func = func as usize;
+        let ty = if func < self.imports.len() {
+            self.imports[func].ty
+        } else {
+            self.funcs.get(func - self.imports.len())?.ty
+        };
+        self.types.get(ty as usize)
+    }
+
+    pub fn export(&self, name: &str, kind: ExportKind) -> Option<u32> {
+        self.exports.iter().find(|e| e.name == name && e.kind == kind).map(|e| e.index)
+    }
+
+    /// The binary encoding of the module.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = b"\0asm\x01\0\0\0".to_vec();
+        let mut section = |id: u8, body: Vec<u8>| {
+            out.push(id);
+            uleb(&mut out, body.len() as u64);
+            out.extend(body);
+        };
+        if !self.types.is_empty() {
+            let mut b = vec![];
+            vec_len(&mut b, self.types.len());
+            for ty in &self.types {
+                b.push(0x60);
+                vec_len(&mut b, ty.params.len());
+                b.extend(ty.params.iter().map(|t| t.code()));
+                vec_len(&mut b, ty.results.len());
+                b.extend(ty.results.iter().map(|t| t.code()));
+            }
+            section(1, b);
+        }
+        if !self.imports.is_empty() {
+            let mut b = vec![];
+            vec_len(&mut b, self.imports.len());
+            for import in &self.imports {
+                name(&mut b, &import.module);
+                name(&mut b, &import.name);
+                b.push(0);
+                uleb(&mut b, import.ty as u64);
+            }
+            section(2, b);
+        }
+        if !self.funcs.is_empty() {
+            let mut b = vec![];
+            vec_len(&mut b, self.funcs.len());
+            for func in &self.funcs {
+                uleb(&mut b, func.ty as u64);
+            }
+            section(3, b);
+        }
+        if let Some(min) = self.table {
+            let mut b = vec![1, 0x70, 0];
+            uleb(&mut b, min as u64);
+            section(4, b);
+        }
+        if let Some(min) = self.memory {
+            let mut b = vec![1, 0];
+            uleb(&mut b, min as u64);
+            section(5, b);
+        }
+        if !self.globals.is_empty() {
+            let mut b = vec![];
+            vec_len(&mut b, self.globals.len());
+            for global in &self.globals {
+                b.push(global.ty.code());
+                b.push(global.mutable as u8);
+                encode_instr(&mut b, &global.init);
+                b.push(0x0b);
+            }
+            section(6, b);
+        }
+        if !self.exports.is_empty() {
+            let mut b = vec![];
+            vec_len(&mut b, self.exports.len());
+            for export in &self.exports {
+                name(&mut b, &export.name);
+                b.push(match export.kind {
+                    ExportKind::Func => 0,
+                    ExportKind::Table => 1,
+                    ExportKind::Memory => 2,
+                    ExportKind::Global => 3,
+                });
+                uleb(&mut b, export.index as u64);
+            }
+            section(7, b);
+        }
+        if let Some(start) = self.start {
+            let mut b = vec![];
+            uleb(&mut b, start as u64);
+            section(8, b);
+        }
+        if !self.elems.is_empty() {
+            let mut b = vec![1, 0];
+            encode_instr(&mut b, &Instr::I32Const(0));
+            b.push(0x0b);
+            vec_len(&mut b, self.elems.len());
+            for &f in &self.elems {
+                uleb(&mut b, f as u64);
+            }
+            section(9, b);
+        }
+        if !self.funcs.is_empty() {
+            let mut b = vec![];
+            vec_len(&mut b, self.funcs.len());
+            for func in &self.funcs {
+                let mut body = vec![];
+                // runs of locals of the same type
+                let mut runs: Vec<(u32, ValType)> = vec![];
+                for &local in &func.locals {
+                    match runs.last_mut() {
+                        Some((n, ty)) if *ty == local => *n += 1,
+                        _ => runs.push((1, local)),
+                    }
+                }
+                vec_len(&mut body, runs.len());
+                for (n, ty) in runs {
+                    uleb(&mut body, n as u64);
+                    body.push(ty.code());
+                }
+                for instr in &func.body {
+                    encode_instr(&mut body, instr);
+                }
+                vec_len(&mut b, body.len());
+                b.extend(body);
+            }
+            section(10, b);
+        }
+        if !self.data.is_empty() {
+            let mut b = vec![];
+            vec_len(&mut b, self.data.len());
+            for data in &self.data {
+                b.push(0);
+                encode_instr(&mut b, &Instr::I32Const(data.offset as i32));
+                b.push(0x0b);
+                vec_len(&mut b, data.bytes.len());
+                b.extend(&data.bytes);
+            }
+            section(11, b);
+        }
+        out
+    }
+
+    /// Decodes a binary module, rejecting what lies outside the subset
+    /// this module describes.
+    pub fn decode(bytes: &[u8]) -> Result<Module, String> {
+        let mut r = Reader { bytes, pos: 0 };
+        if r.take(8)? != b"\0asm\x01\0\0\0" {
+            return Err("not a WebAssembly module".to_string());
+        }
+        let mut m = Module::default();
+        let mut func_types = vec![];
+        while r.pos < bytes.len() {
+            let id = r.byte()?;
+            let size = r.uleb()? as usize;
+            let end = r.pos + size;
+            if end > bytes.len() {
+                return Err("section extends past the end".to_string());
+            }
+            let mut s = Reader { bytes: &bytes[..end], pos: r.pos };
+            match id {
+                0 => {}
+                1 => {
+                    for _ in 0..s.uleb()? {
+                        if s.byte()? != 0x60 {
+                            return Err("malformed function type".to_string());
+                        }
+                        let params = s.val_types()?;
+                        let results = s.val_types()?;
+                        m.types.push(FuncType { params, results });
+                    }
+                }
+                2 => {
+                    for _ in 0..s.uleb()? {
+                        let module = s.name()?;
+                        let name = s.name()?;
+                        if s.byte()? != 0 {
+                            return Err(format!("import {}.{} is not a function", module, name));
+                        }
+                        let ty = s.uleb()? as u32;
+                        m.imports.push(Import { module, name, ty });
+                    }
+                }
+                3 => {
+                    for _ in 0..s.uleb()? {
+                        func_types.push(s.uleb()? as u32);
+                    }
+                }
+                4 => {
+                    if s.uleb()? != 1 || s.byte()? != 0x70 {
+                        return Err("only one table of functions is supported".to_string());
+                    }
+                    let flags = s.byte()?;
+                    m.table = Some(s.uleb()? as u32);
+                    if flags & 1 != 0 {
+                        s.uleb()?;
+                    }
+                }
+                5 => {
+                    if s.uleb()? != 1 {
+                        return Err("only one memory is supported".to_string());
+                    }
+                    let flags = s.byte()?;
+                    m.memory = Some(s.uleb()? as u32);
+                    if flags & 1 != 0 {
+                        s.uleb()?;
+                    }
+                }
+                6 => {
+                    for _ in 0..s.uleb()? {
+                        let ty = s.val_type()?;
+                        let mutable = s.byte()? != 0;
+                        let init = s.const_expr()?;
+                        m.globals.push(Global { ty, mutable, init });
+                    }
+                }
+                7 => {
+                    for _ in 0..s.uleb()? {
+                        let name = s.name()?;
+                        let kind = match s.byte()? {
+                            0 => ExportKind::Func,
+                            1 => ExportKind::Table,
+                            2 => ExportKind::Memory,
+                            3 => ExportKind::Global,
+                            k => return Err(format!("unknown export kind {}", k)),
+                        };
+                        let index = s.uleb()? as u32;
+                        m.exports.push(Export { name, kind, index });
+                    }
+                }
+                8 => m.start = Some(s.uleb()? as u32),
+                9 => {
+                    for _ in 0..s.uleb()? {
+                        if s.uleb()? != 0 {
+                            return Err("only active element segments are supported".to_string());
+                        }
+                        let offset = match s.const_expr()? {
+                            Instr::I32Const(n) => n as usize,
+                            _ => return Err("malformed element offset".to_string()),
+                        };
+                        let n = s.uleb()? as usize;
+                        if m.elems.len() < offset + n {
+                            m.elems.resize(offset + n, 0);
+                        }
+                        for i in 0..n {
+                            m.elems[offset + i] = s.uleb()? as u32;
+                        }
+                    }
+                }
+                10 => {
+                    let n = s.uleb()? as usize;
+                    if n != func_types.len() {
+                        return Err("function and code sections disagree".to_string());
+                    }
+                    for &ty in &func_types {
+                        let size = s.uleb()? as usize;
+                        let body_end = s.pos + size;
+                        let mut f = Reader { bytes: &s.bytes[..body_end.min(end)], pos: s.pos };
+                        let mut locals = vec![];
+                        for _ in 0..f.uleb()? {
+                            let count = f.uleb()?;
+                            let ty = f.val_type()?;
+                            if count > 50_000 {
+                                return Err("too many locals".to_string());
+                            }
+                            locals.extend(std::iter::repeat_n(ty, count as usize));
+                        }
+                        let mut body = vec![];
+                        while f.pos < body_end {
+                            body.push(f.instr()?);
+                        }
+                        m.funcs.push(Func { ty, locals, body });
+                        s.pos = body_end;
+                    }
+                }
+                11 => {
+                    for _ in 0..s.uleb()? {
+                        if s.uleb()? != 0 {
+                            return Err("only active data segments are supported".to_string());
+                        }
+                        let offset = match s.const_expr()? {
+                            Instr::I32Const(n) => n as u32,
+                            _ => return Err("malformed data offset".to_string()),
+                        };
+                        let n = s.uleb()? as usize;
+                        let bytes = s.take(n)?.to_vec();
+                        m.data.push(Data { offset, bytes });
+                    }
+                }
+                12 => {}
+                _ => return Err(format!("unknown section {}", id)),
+            }
+            r.pos = end;
+        }
+        if m.funcs.len() != func_types.len() {
+            return Err("function section without code".to_string());
+        }
+        Ok(m)
+    }
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let block = |f: &mut fmt::Formatter, name: &str, ty: &BlockType| match ty {
+            BlockType::Empty => write!(f, "{}", name),
+            BlockType::Value(t) => write!(f, "{} (result {})", name, t.name()),
+        };
+        let mem = |f: &mut fmt::Formatter, op: u8, arg: &MemArg| {
+            let natural = MEMORY_OPS.iter().find(|(code, _, _)| *code == op).map_or(0, |(_, _, a)| *a);
+            write!(f, "{}", memory_op_name(op))?;
+            if arg.offset != 0 {
+                write!(f, " offset={}", arg.offset)?;
+            }
+            if arg.align != natural {
+                write!(f, " align={}", 1u32 << arg.align)?;
+            }
+            Ok(())
+        };
+        match self {
+            Instr::Unreachable => write!(f, "unreachable"),
+            Instr::Nop => write!(f, "nop"),
+            Instr::Block(ty) => block(f, "block", ty),
+            Instr::Loop(ty) => block(f, "loop", ty),
+            Instr::If(ty) => block(f, "if", ty),
+            Instr::Else => write!(f, "else"),
+            Instr::End => write!(f, "end"),
+            Instr::Br(l) => write!(f, "br {}", l),
+            Instr::BrIf(l) => write!(f, "br_if {}", l),
+            Instr::BrTable(ls, default) => {
+                write!(f, "br_table")?;
+                for l in ls {
+                    write!(f, " {}", l)?;
+                }
+                write!(f, " {}", default)
+            }
+            Instr::Return => write!(f, "return"),
+            Instr::Call(i) => write!(f, "call {}", i),
+            Instr::CallIndirect(t) => write!(f, "call_indirect (type {})", t),
+            Instr::Drop => write!(f, "drop"),
+            Instr::Select => write!(f, "select"),
+            Instr::LocalGet(i) => write!(f, "local.get {}", i),
+            Instr::LocalSet(i) => write!(f, "local.set {}", i),
+            Instr::LocalTee(i) => write!(f, "local.tee {}", i),
+            Instr::GlobalGet(i) => write!(f, "global.get {}", i),
+            Instr::GlobalSet(i) => write!(f, "global.set {}", i),
+            Instr::Load(op, arg) | Instr::Store(op, arg) => mem(f, *op, arg),
+            Instr::MemorySize => write!(f, "memory.size"),
+            Instr::MemoryGrow => write!(f, "memory.grow"),
+            Instr::I32Const(n) => write!(f, "i32.const {}", n),
+            Instr::I64Const(n) => write!(f, "i64.const {}", n),
+            Instr::F64Const(x) => write!(f, "f64.const {}", float_text(*x)),
+            Instr::Op(op) => write!(f, "{}", op_name(*op)),
+            Instr::TruncSat(op) => {
+                write!(f, "{}", TRUNC_SAT_OPS.iter().find(|(code, _)| code == op).map_or("?", |(_, name)| name))
+            }
+            Instr::MemoryCopy => write!(f, "memory.copy"),
+            Instr::MemoryFill => write!(f, "memory.fill"),
+        }
+    }
+}
+
+/// A float as the text format writes it, exactly.
+pub fn float_text(x: f64) -> String {
+    if x.is_nan() {
+        return if x.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    }
+    if x.is_infinite() {
+        return if x < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    // Rust prints the shortest text that reads back as the same value
+    format!("{:?}", x)
+}
+
+fn encode_instr(b: &mut Vec<u8>, instr: &Instr) {
+    let block_type = |b: &mut Vec<u8>, ty: &BlockType| match ty {
+        BlockType::Empty => b.push(0x40),
+        BlockType::Value(t) => b.push(t.code()),
+    };
+    match instr {
+        Instr::Unreachable => b.push(0x00),
+        Instr::Nop => b.push(0x01),
+        Instr::Block(ty) => {
+            b.push(0x02);
+            block_type(b, ty);
+        }
+        Instr::Loop(ty) => {
+            b.push(0x03);
+            block_type(b, ty);
+        }
+        Instr::If(ty) => {
+            b.push(0x04);
+            block_type(b, ty);
+        }
+        Instr::Else => b.push(0x05),
+        Instr::End => b.push(0x0b),
+        Instr::Br(l) => {
+            b.push(0x0c);
+            uleb(b, *l as u64);
+        }
+        Instr::BrIf(l) => {
+            b.push(0x0d);
+            uleb(b, *l as u64);
+        }
+        Instr::BrTable(ls, default) => {
+            b.push(0x0e);
+            vec_len(b, ls.len());
+            for &l in ls {
+                uleb(b, l as u64);
+            }
+            uleb(b, *default as u64);
+        }
+        Instr::Return => b.push(0x0f),
+        Instr::Call(i) => {
+            b.push(0x10);
+            uleb(b, *i as u64);
+        }
+        Instr::CallIndirect(t) => {
+            b.push(0x11);
+            uleb(b, *t as u64);
+            b.push(0);
+        }
+        Instr::Drop => b.push(0x1a),
+        Instr::Select => b.push(0x1b),
+        Instr::LocalGet(i) | Instr::LocalSet(i) | Instr::LocalTee(i) | Instr::GlobalGet(i) | Instr::GlobalSet(i) => {
+            b.push(match instr {
+                Instr::LocalGet(_) => 0x20,
+                Instr::LocalSet(_) => 0x21,
+                Instr::LocalTee(_) => 0x22,
+                Instr::GlobalGet(_) => 0x23,
+                _ => 0x24,
+            });
+            uleb(b, *i as u64);
+        }
+        Instr::Load(op, arg) | Instr::Store(op, arg) => {
+            b.push(*op);
+            uleb(b, arg.align as u64);
+            uleb(b, arg.offset as u64);
+        }
+        Instr::MemorySize => b.extend([0x3f, 0]),
+        Instr::MemoryGrow => b.extend([0x40, 0]),
+        Instr::I32Const(n) => {
+            b.push(0x41);
+            sleb(b, *n as i64);
+        }
+        Instr::I64Const(n) => {
+            b.push(0x42);
+            sleb(b, *n);
+        }
+        Instr::F64Const(x) => {
+            b.push(0x44);
+            b.extend(x.to_bits().to_le_bytes());
+        }
+        Instr::Op(op) => b.push(*op),
+        Instr::TruncSat(op) => {
+            b.push(0xfc);
+            uleb(b, *op as u64);
+        }
+        Instr::MemoryCopy => b.extend([0xfc, 10, 0, 0]),
+        Instr::MemoryFill => b.extend([0xfc, 11, 0]),
+    }
+}
+
+fn uleb(b: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            b.push(byte);
+            return;
+        }
+        b.push(byte | 0x80);
+    }
+}
+
+fn sleb(b: &mut Vec<u8>, mut n: i64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        let done = (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0);
+        if done {
+            b.push(byte);
+            return;
+        }
+        b.push(byte | 0x80);
+    }
+}
+
+fn vec_len(b: &mut Vec<u8>, n: usize) {
+    uleb(b, n as u64);
+}
+
+fn name(b: &mut Vec<u8>, s: &str) {
+    vec_len(b, s.len());
+    b.extend(s.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let b = *self.bytes.get(self.pos).ok_or("unexpected end of module")?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.pos + n > self.bytes.len() {
+            return Err("unexpected end of module".to_string());
+        }
+        let s = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(s)
+    }
+
+    fn uleb(&mut self) -> Result<u64, String> {
+        let mut n = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            if shift >= 64 {
+                return Err("integer too long".to_string());
+            }
+            n |= ((b & 0x7f) as u64) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+    }
+
+    fn sleb(&mut self) -> Result<i64, String> {
+        let mut n = 0i64;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            if shift >= 64 {
+                return Err("integer too long".to_string());
+            }
+            n |= ((b & 0x7f) as i64) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    n |= -1i64 << shift;
+                }
+                return Ok(n);
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let n = self.uleb()? as usize;
+        String::from_utf8(self.take(n)?.to_vec()).map_err(|_| "malformed name".to_string())
+    }
+
+    fn val_type(&mut self) -> Result<ValType, String> {
+        let code = self.byte()?;
+        ValType::from_code(code).ok_or_else(|| format!("unsupported value type 0x{:02x}", code))
+    }
+
+    fn val_types(&mut self) -> Result<Vec<ValType>, String> {
+        (0..self.uleb()?).map(|_| self.val_type()).collect()
+    }
+
+    fn block_type(&mut self) -> Result<BlockType, String> {
+        match self.byte()? {
+            0x40 => Ok(BlockType::Empty),
+            code => ValType::from_code(code)
+                .map(BlockType::Value)
+                .ok_or_else(|| format!("unsupported block type 0x{:02x}", code)),
+        }
+    }
+
+    fn const_expr(&mut self) -> Result<Instr, String> {
+        let instr = self.instr()?;
+        if !matches!(instr, Instr::I32Const(_) | Instr::I64Const(_) | Instr::F64Const(_)) || self.byte()? != 0x0b {
+            return Err("unsupported constant expression".to_string());
+        }
+        Ok(instr)
+    }
+
+    fn mem_arg(&mut self) -> Result<MemArg, String> {
+        let align = self.uleb()? as u32;
+        let offset = self.uleb()? as u32;
+        Ok(MemArg { align, offset })
+    }
+
+    fn instr(&mut self) -> Result<Instr, String> {
+        let op = self.byte()?;
+        Ok(match op {
+            0x00 => Instr::Unreachable,
+            0x01 => Instr::Nop,
+            0x02 => Instr::Block(self.block_type()?),
+            0x03 => Instr::Loop(self.block_type()?),
+            0x04 => Instr::If(self.block_type()?),
+            0x05 => Instr::Else,
+            0x0b => Instr::End,
+            0x0c => Instr::Br(self.uleb()? as u32),
+            0x0d => Instr::BrIf(self.uleb()? as u32),
+            0x0e => {
+                let n = self.uleb()?;
+                let labels = (0..n).map(|_| self.uleb().map(|l| l as u32)).collect::<Result<_, _>>()?;
+                Instr::BrTable(labels, self.uleb()? as u32)
+            }
+            0x0f => Instr::Return,
+            0x10 => Instr::Call(self.uleb()? as u32),
+            0x11 => {
+                let ty = self.uleb()? as u32;
+                self.byte()?;
+                Instr::CallIndirect(ty)
+            }
+            0x1a => Instr::Drop,
+            0x1b => Instr::Select,
+            0x20 => Instr::LocalGet(self.uleb()? as u32),
+            0x21 => Instr::LocalSet(self.uleb()? as u32),
+            0x22 => Instr::LocalTee(self.uleb()? as u32),
+            0x23 => Instr::GlobalGet(self.uleb()? as u32),
+            0x24 => Instr::GlobalSet(self.uleb()? as u32),
+            0x28..=0x35 if op != 0x2a => Instr::Load(op, self.mem_arg()?),
+            0x36..=0x3e if op != 0x38 => Instr::Store(op, self.mem_arg()?),
+            0x3f => {
+                self.byte()?;
+                Instr::MemorySize
+            }
+            0x40 => {
+                self.byte()?;
+                Instr::MemoryGrow
+            }
+            0x41 => Instr::I32Const(self.sleb()? as i32),
+            0x42 => Instr::I64Const(self.sleb()?),
+            0x44 => {
+                let bytes = self.take(8)?;
+                Instr::F64Const(f64::from_bits(u64::from_le_bytes(bytes.try_into().unwrap())))
+            }
+            0xfc => match self.uleb()? {
+                10 => {
+                    self.byte()?;
+                    self.byte()?;
+                    Instr::MemoryCopy
+                }
+                11 => {
+                    self.byte()?;
+                    Instr::MemoryFill
+                }
+                n if TRUNC_SAT_OPS.iter().any(|(code, _)| *code as u64 == n) => Instr::TruncSat(n as u8),
+                n => return Err(format!("unsupported instruction 0xfc {}", n)),
+            },
+            _ if OPS.iter().any(|(code, _)| *code == op) => Instr::Op(op),
+            _ => return Err(format!("unsupported instruction 0x{:02x}", op)),
+        })
+    }
+}
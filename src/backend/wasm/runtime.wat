@@ -0,0 +1,5544 @@
+;; The runtime every program the WebAssembly backend compiles starts with:
+;; the VM's values, operators, builtins and standard library, as the C
+;; backend's runtime has them, over linear memory.
+;;
+;; A value is a pair of an i32, the address of its type's descriptor, and
+;; an i64 that holds a bool, an int or the bits of a float, a string's
+;; length above its address, or the address of what other values point
+;; to; in memory, a pair takes a 16-byte slot with the type at 0 and the
+;; i64 at 8. Slices point to {data, len, cap} with an array of slots,
+;; structs to a slot per field, interfaces to a slot holding their value
+;; or 0 when nil, and functions to a function object.
+;;
+;; The generator lays out the data this file refers to: type descriptors
+;; `$t_*`, builtin function objects `$B_*`, and `(string "...")`, which it
+;; replaces with the constant of a string it stores. A panic sets
+;; `$throwing`; a function that sees it after a call returns, so that the
+;; panic unwinds to the frames of functions that defer calls.
+;;
+;; Imports are I/O and what needs Rust's float formatting and parsing or
+;; its math: `namu.write(fd, ptr, len)`, `namu.exit(code)`,
+;; `namu.args()`, `namu.arg_len(i)` and `namu.arg(i, buf)`,
+;; `namu.format_float(f, fmt, prec, buf)`, `namu.parse_float(ptr, len)`,
+;; `namu.math(op, x, y)` and `namu.fold(rune, upper)`, and a seed for the
+;; order maps are visited in, `namu.seed()`.
+
+(type $fn (func (param i32 i32 i32) (result i32 i64)))
+
+(import "namu" "write" (func $host_write (param i32 i32 i32)))
+(import "namu" "exit" (func $host_exit (param i32)))
+(import "namu" "args" (func $host_args (result i32)))
+(import "namu" "arg_len" (func $host_arg_len (param i32) (result i32)))
+(import "namu" "arg" (func $host_arg (param i32 i32)))
+(import "namu" "format_float" (func $host_format_float (param f64 i32 i32 i32) (result i32)))
+(import "namu" "parse_float" (func $host_parse_float (param i32 i32) (result f64)))
+(import "namu" "math" (func $host_math (param i32 f64 f64) (result f64)))
+(import "namu" "fold" (func $host_fold (param i32 i32) (result i32)))
+(import "namu" "seed" (func $host_seed (result i64)))
+
+;; Kinds of types, at offset 0 of a descriptor:
+;; 0 nil, 1 bool, 2 int, 3 byte, 4 float, 5 string, 6 func, 7 slice,
+;; 8 map, 9 pointer, 10 chan, 11 struct, 12 interface.
+;; A descriptor is {kind, named, name (string) at 8, elem at 16, key at 20,
+;; nfields at 24, field names (strings) at 28, field types at 32}.
+
+;; The calls running, for stack traces: 16-byte entries of a name, empty
+;; for the program's top level, and whether the function has parameters.
+(global $depth (mut i32) (i32.const 0))
+(global $panicking (mut i32) (i32.const 0))
+(global $panic_t (mut i32) (i32.const 0))
+(global $panic_x (mut i64) (i64.const 0))
+(global $trace (mut i64) (i64.const 0))
+;; The depth of the deferred call that may recover the panic, or -1.
+(global $recover_depth (mut i32) (i32.const -1))
+;; Seeded from the host on first use.
+(global $rng (mut i64) (i64.const 0))
+
+;; Memory and buffers
+
+(func $fatal (param $msg i64)
+  (call $write_str (i32.const 2) (string "fatal error: "))
+  (call $write_str (i32.const 2) (local.get $msg))
+  (call $write_str (i32.const 2) (string "\n"))
+  (call $host_exit (i32.const 2)))
+
+(func $write_str (param $fd i32) (param $s i64)
+  (call $host_write (local.get $fd) (call $str_ptr (local.get $s)) (call $str_len (local.get $s))))
+
+(func $align8 (param $n i32) (result i32)
+  (i32.and (i32.add (local.get $n) (i32.const 7)) (i32.const -8)))
+
+;; Zeroed memory from the heap, which only grows.
+(func $alloc (param $n i32) (result i32)
+  (local $p i32) (local $end i32) (local $have i32) (local $pages i32)
+  (local.set $p (global.get $hp))
+  (local.set $end (i32.add (local.get $p) (call $align8 (local.get $n))))
+  (if (i32.lt_u (local.get $end) (local.get $p))
+    (then (call $fatal (string "out of memory"))))
+  (local.set $have (i32.shl (memory.size) (i32.const 16)))
+  (if (i32.gt_u (local.get $end) (local.get $have))
+    (then
+      (local.set $pages
+        (i32.shr_u (i32.add (i32.sub (local.get $end) (local.get $have)) (i32.const 65535)) (i32.const 16)))
+      ;; grow by a quarter at least, so that growing is rare
+      (if (i32.eq
+            (memory.grow (select (local.get $pages) (i32.shr_u (memory.size) (i32.const 2))
+              (i32.gt_u (local.get $pages) (i32.shr_u (memory.size) (i32.const 2)))))
+            (i32.const -1))
+        (then
+          (if (i32.eq (memory.grow (local.get $pages)) (i32.const -1))
+            (then (call $fatal (string "out of memory"))))))))
+  (global.set $hp (local.get $end))
+  (memory.fill (local.get $p) (i32.const 0) (local.get $n))
+  (local.get $p))
+
+;; Buffers are {data, len, cap}.
+(func $buf_new (result i32)
+  (local $b i32)
+  (local.set $b (call $alloc (i32.const 12)))
+  (i32.store (local.get $b) (call $alloc (i32.const 64)))
+  (i32.store offset=8 (local.get $b) (i32.const 64))
+  (local.get $b))
+
+;; Makes room for `n` more bytes, in place when the data is the last
+;; thing allocated.
+(func $buf_reserve (param $b i32) (param $n i32)
+  (local $need i32) (local $cap i32) (local $new i32) (local $d i32)
+  (local.set $need (i32.add (i32.load offset=4 (local.get $b)) (local.get $n)))
+  (local.set $cap (i32.load offset=8 (local.get $b)))
+  (if (i32.le_u (local.get $need) (local.get $cap))
+    (then (return)))
+  (local.set $new (i32.shl (local.get $cap) (i32.const 1)))
+  (if (i32.lt_u (local.get $new) (local.get $need))
+    (then (local.set $new (local.get $need))))
+  (local.set $d (i32.load (local.get $b)))
+  (if (i32.eq (i32.add (local.get $d) (call $align8 (local.get $cap))) (global.get $hp))
+    (then
+      (drop (call $alloc (i32.sub (call $align8 (local.get $new)) (call $align8 (local.get $cap)))))
+      (i32.store offset=8 (local.get $b) (local.get $new))
+      (return)))
+  (local.set $d (call $alloc (local.get $new)))
+  (memory.copy (local.get $d) (i32.load (local.get $b)) (i32.load offset=4 (local.get $b)))
+  (i32.store (local.get $b) (local.get $d))
+  (i32.store offset=8 (local.get $b) (local.get $new)))
+
+(func $buf_put (param $b i32) (param $p i32) (param $n i32)
+  (call $buf_reserve (local.get $b) (local.get $n))
+  (memory.copy
+    (i32.add (i32.load (local.get $b)) (i32.load offset=4 (local.get $b)))
+    (local.get $p) (local.get $n))
+  (i32.store offset=4 (local.get $b) (i32.add (i32.load offset=4 (local.get $b)) (local.get $n))))
+
+(func $buf_byte (param $b i32) (param $c i32)
+  (call $buf_reserve (local.get $b) (i32.const 1))
+  (i32.store8 (i32.add (i32.load (local.get $b)) (i32.load offset=4 (local.get $b))) (local.get $c))
+  (i32.store offset=4 (local.get $b) (i32.add (i32.load offset=4 (local.get $b)) (i32.const 1))))
+
+(func $buf_str (param $b i32) (param $s i64)
+  (call $buf_put (local.get $b) (call $str_ptr (local.get $s)) (call $str_len (local.get $s))))
+
+(func $buf_len (param $b i32) (result i32)
+  (i32.load offset=4 (local.get $b)))
+
+(func $buf_at (param $b i32) (param $i i32) (result i32)
+  (i32.load8_u (i32.add (i32.load (local.get $b)) (local.get $i))))
+
+(func $buf_value (param $b i32) (result i64)
+  (call $str (i32.load (local.get $b)) (i32.load offset=4 (local.get $b))))
+
+(func $buf_int (param $b i32) (param $i i64)
+  (if (i64.lt_s (local.get $i) (i64.const 0))
+    (then (call $buf_byte (local.get $b) (i32.const 45))))
+  (call $format_radix (local.get $b) (local.get $i) (i32.const 10) (i32.const 0) (i64.const -1)))
+
+;; The magnitude of `i` in `base`, with at least `precision` digits.
+(func $format_radix (param $b i32) (param $i i64) (param $base i32) (param $upper i32) (param $precision i64)
+  (local $n i64) (local $d i32) (local $start i32) (local $nd i64) (local $lo i32) (local $hi i32) (local $c i32)
+  (local.set $n (select (i64.sub (i64.const 0) (local.get $i)) (local.get $i) (i64.lt_s (local.get $i) (i64.const 0))))
+  (local.set $start (call $buf_len (local.get $b)))
+  (loop $digits
+    (local.set $d (i32.wrap_i64 (i64.rem_u (local.get $n) (i64.extend_i32_u (local.get $base)))))
+    (call $buf_byte (local.get $b)
+      (select (i32.add (local.get $d) (i32.const 48))
+        (i32.add (local.get $d) (select (i32.const 55) (i32.const 87) (local.get $upper)))
+        (i32.lt_u (local.get $d) (i32.const 10))))
+    (local.set $nd (i64.add (local.get $nd) (i64.const 1)))
+    (local.set $n (i64.div_u (local.get $n) (i64.extend_i32_u (local.get $base))))
+    (br_if $digits (i64.ne (local.get $n) (i64.const 0))))
+  (block $done
+    (loop $pad
+      (br_if $done (i64.ge_s (local.get $nd) (local.get $precision)))
+      (call $buf_byte (local.get $b) (i32.const 48))
+      (local.set $nd (i64.add (local.get $nd) (i64.const 1)))
+      (br $pad)))
+  ;; the digits went in least significant first
+  (local.set $lo (i32.add (i32.load (local.get $b)) (local.get $start)))
+  (local.set $hi (i32.sub (i32.add (i32.load (local.get $b)) (call $buf_len (local.get $b))) (i32.const 1)))
+  (block $done
+    (loop $swap
+      (br_if $done (i32.ge_u (local.get $lo) (local.get $hi)))
+      (local.set $c (i32.load8_u (local.get $lo)))
+      (i32.store8 (local.get $lo) (i32.load8_u (local.get $hi)))
+      (i32.store8 (local.get $hi) (local.get $c))
+      (local.set $lo (i32.add (local.get $lo) (i32.const 1)))
+      (local.set $hi (i32.sub (local.get $hi) (i32.const 1)))
+      (br $swap))))
+
+(func $buf_hex2 (param $b i32) (param $c i32) (param $upper i32)
+  (call $format_radix (local.get $b) (i64.extend_i32_u (local.get $c)) (i32.const 16) (local.get $upper) (i64.const 2)))
+
+;; Strings
+
+(func $str (param $p i32) (param $n i32) (result i64)
+  (i64.or (i64.shl (i64.extend_i32_u (local.get $n)) (i64.const 32)) (i64.extend_i32_u (local.get $p))))
+
+(func $str_ptr (param $s i64) (result i32)
+  (i32.wrap_i64 (local.get $s)))
+
+(func $str_len (param $s i64) (result i32)
+  (i32.wrap_i64 (i64.shr_u (local.get $s) (i64.const 32))))
+
+(func $substr (param $s i64) (param $from i32) (param $to i32) (result i64)
+  (call $str (i32.add (call $str_ptr (local.get $s)) (local.get $from)) (i32.sub (local.get $to) (local.get $from))))
+
+;; A string value of the bytes, copied.
+(func $str_copy (param $p i32) (param $n i32) (result i64)
+  (local $d i32)
+  (local.set $d (call $alloc (local.get $n)))
+  (memory.copy (local.get $d) (local.get $p) (local.get $n))
+  (call $str (local.get $d) (local.get $n)))
+
+(func $mem_cmp (param $a i32) (param $b i32) (param $n i32) (result i32)
+  (local $i i32) (local $x i32) (local $y i32)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+      (local.set $x (i32.load8_u (i32.add (local.get $a) (local.get $i))))
+      (local.set $y (i32.load8_u (i32.add (local.get $b) (local.get $i))))
+      (if (i32.ne (local.get $x) (local.get $y))
+        (then (return (select (i32.const -1) (i32.const 1) (i32.lt_u (local.get $x) (local.get $y))))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (i32.const 0))
+
+(func $str_eq (param $a i64) (param $b i64) (result i32)
+  (if (i64.eq (local.get $a) (local.get $b))
+    (then (return (i32.const 1))))
+  (if (i32.ne (call $str_len (local.get $a)) (call $str_len (local.get $b)))
+    (then (return (i32.const 0))))
+  (i32.eqz (call $mem_cmp (call $str_ptr (local.get $a)) (call $str_ptr (local.get $b)) (call $str_len (local.get $a)))))
+
+;; -1, 0 or 1 as `a` sorts before, with or after `b`.
+(func $str_cmp (param $a i64) (param $b i64) (result i32)
+  (local $na i32) (local $nb i32) (local $c i32)
+  (local.set $na (call $str_len (local.get $a)))
+  (local.set $nb (call $str_len (local.get $b)))
+  (local.set $c (call $mem_cmp (call $str_ptr (local.get $a)) (call $str_ptr (local.get $b))
+    (select (local.get $na) (local.get $nb) (i32.lt_u (local.get $na) (local.get $nb)))))
+  (if (local.get $c)
+    (then (return (local.get $c))))
+  (i32.sub (i32.gt_u (local.get $na) (local.get $nb)) (i32.lt_u (local.get $na) (local.get $nb))))
+
+(func $concat (param $a i64) (param $b i64) (result i64)
+  (local $na i32) (local $nb i32) (local $d i32)
+  (local.set $na (call $str_len (local.get $a)))
+  (local.set $nb (call $str_len (local.get $b)))
+  (if (i32.eqz (local.get $na))
+    (then (return (local.get $b))))
+  (if (i32.eqz (local.get $nb))
+    (then (return (local.get $a))))
+  (local.set $d (call $alloc (i32.add (local.get $na) (local.get $nb))))
+  (memory.copy (local.get $d) (call $str_ptr (local.get $a)) (local.get $na))
+  (memory.copy (i32.add (local.get $d) (local.get $na)) (call $str_ptr (local.get $b)) (local.get $nb))
+  (call $str (local.get $d) (i32.add (local.get $na) (local.get $nb))))
+
+;; The first rune of the `n` bytes at `s` and its length, decoding as Rust
+;; does; invalid UTF-8 decodes as U+FFFD one byte at a time.
+(func $decode (param $s i32) (param $n i32) (result i32 i32)
+  (local $c i32) (local $len i32) (local $r i32) (local $lo i32) (local $hi i32) (local $i i32) (local $b i32)
+  (local.set $c (i32.load8_u (local.get $s)))
+  (if (i32.lt_u (local.get $c) (i32.const 0x80))
+    (then (return (local.get $c) (i32.const 1))))
+  (local.set $lo (i32.const 0x80))
+  (local.set $hi (i32.const 0xbf))
+  (if (i32.and (i32.ge_u (local.get $c) (i32.const 0xc2)) (i32.le_u (local.get $c) (i32.const 0xdf)))
+    (then
+      (local.set $len (i32.const 2))
+      (local.set $r (i32.and (local.get $c) (i32.const 0x1f))))
+    (else
+      (if (i32.and (i32.ge_u (local.get $c) (i32.const 0xe0)) (i32.le_u (local.get $c) (i32.const 0xef)))
+        (then
+          (local.set $len (i32.const 3))
+          (local.set $r (i32.and (local.get $c) (i32.const 0x0f)))
+          (if (i32.eq (local.get $c) (i32.const 0xe0))
+            (then (local.set $lo (i32.const 0xa0))))
+          (if (i32.eq (local.get $c) (i32.const 0xed))
+            (then (local.set $hi (i32.const 0x9f)))))
+        (else
+          (if (i32.and (i32.ge_u (local.get $c) (i32.const 0xf0)) (i32.le_u (local.get $c) (i32.const 0xf4)))
+            (then
+              (local.set $len (i32.const 4))
+              (local.set $r (i32.and (local.get $c) (i32.const 0x07)))
+              (if (i32.eq (local.get $c) (i32.const 0xf0))
+                (then (local.set $lo (i32.const 0x90))))
+              (if (i32.eq (local.get $c) (i32.const 0xf4))
+                (then (local.set $hi (i32.const 0x8f)))))
+            (else (return (i32.const 0xfffd) (i32.const 1))))))))
+  (if (i32.lt_s (local.get $n) (local.get $len))
+    (then (return (i32.const 0xfffd) (i32.const 1))))
+  (local.set $i (i32.const 1))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+      (local.set $b (i32.load8_u (i32.add (local.get $s) (local.get $i))))
+      (if (i32.or (i32.lt_u (local.get $b) (local.get $lo)) (i32.gt_u (local.get $b) (local.get $hi)))
+        (then (return (i32.const 0xfffd) (i32.const 1))))
+      (local.set $lo (i32.const 0x80))
+      (local.set $hi (i32.const 0xbf))
+      (local.set $r (i32.or (i32.shl (local.get $r) (i32.const 6)) (i32.and (local.get $b) (i32.const 0x3f))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (local.get $r)
+  (local.get $len))
+
+;; The rune at byte `i` of the string, and its length.
+(func $decode_at (param $s i64) (param $i i32) (result i32 i32)
+  (call $decode (i32.add (call $str_ptr (local.get $s)) (local.get $i)) (i32.sub (call $str_len (local.get $s)) (local.get $i))))
+
+;; Encodes a rune, U+FFFD for a code point there is none of.
+(func $buf_rune (param $b i32) (param $r i64)
+  (if (i32.or (i32.or (i64.lt_s (local.get $r) (i64.const 0)) (i64.gt_s (local.get $r) (i64.const 0x10ffff)))
+        (i32.and (i64.ge_s (local.get $r) (i64.const 0xd800)) (i64.le_s (local.get $r) (i64.const 0xdfff))))
+    (then (local.set $r (i64.const 0xfffd))))
+  (if (i64.lt_s (local.get $r) (i64.const 0x80))
+    (then
+      (call $buf_byte (local.get $b) (i32.wrap_i64 (local.get $r)))
+      (return)))
+  (if (i64.lt_s (local.get $r) (i64.const 0x800))
+    (then
+      (call $buf_byte (local.get $b) (i32.or (i32.const 0xc0) (i32.wrap_i64 (i64.shr_u (local.get $r) (i64.const 6)))))
+      (call $buf_byte (local.get $b) (call $cont (local.get $r) (i64.const 0)))
+      (return)))
+  (if (i64.lt_s (local.get $r) (i64.const 0x10000))
+    (then
+      (call $buf_byte (local.get $b) (i32.or (i32.const 0xe0) (i32.wrap_i64 (i64.shr_u (local.get $r) (i64.const 12)))))
+      (call $buf_byte (local.get $b) (call $cont (local.get $r) (i64.const 6)))
+      (call $buf_byte (local.get $b) (call $cont (local.get $r) (i64.const 0)))
+      (return)))
+  (call $buf_byte (local.get $b) (i32.or (i32.const 0xf0) (i32.wrap_i64 (i64.shr_u (local.get $r) (i64.const 18)))))
+  (call $buf_byte (local.get $b) (call $cont (local.get $r) (i64.const 12)))
+  (call $buf_byte (local.get $b) (call $cont (local.get $r) (i64.const 6)))
+  (call $buf_byte (local.get $b) (call $cont (local.get $r) (i64.const 0))))
+
+;; A continuation byte of six bits of `r`.
+(func $cont (param $r i64) (param $shift i64) (result i32)
+  (i32.or (i32.const 0x80) (i32.and (i32.wrap_i64 (i64.shr_u (local.get $r) (local.get $shift))) (i32.const 0x3f))))
+
+;; Bytes with invalid UTF-8 replaced by U+FFFD, as Rust's lossy
+;; conversion prints them.
+(func $buf_lossy (param $b i32) (param $s i64)
+  (local $i i32) (local $n i32) (local $r i32) (local $size i32)
+  (local.set $n (call $str_len (local.get $s)))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+      (call $decode_at (local.get $s) (local.get $i))
+      (local.set $size)
+      (local.set $r)
+      (if (i32.and (i32.eq (local.get $r) (i32.const 0xfffd)) (i32.eq (local.get $size) (i32.const 1)))
+        (then (call $buf_rune (local.get $b) (i64.const 0xfffd)))
+        (else (call $buf_put (local.get $b) (i32.add (call $str_ptr (local.get $s)) (local.get $i)) (local.get $size))))
+      (local.set $i (i32.add (local.get $i) (local.get $size)))
+      (br $next))))
+
+(func $lossy (param $s i64) (result i64)
+  (local $b i32)
+  (local.set $b (call $buf_new))
+  (call $buf_lossy (local.get $b) (local.get $s))
+  (call $buf_value (local.get $b)))
+
+(func $rune_count (param $s i64) (result i32)
+  (local $i i32) (local $count i32)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $s))))
+      (call $decode_at (local.get $s) (local.get $i))
+      (local.set $i (i32.add (local.get $i)))
+      (drop)
+      (local.set $count (i32.add (local.get $count) (i32.const 1)))
+      (br $next)))
+  (local.get $count))
+
+;; Messages of panics, put together from strings.
+(func $cat3 (param $a i64) (param $b i64) (param $c i64) (result i64)
+  (call $concat (call $concat (local.get $a) (local.get $b)) (local.get $c)))
+
+(func $cat4 (param $a i64) (param $b i64) (param $c i64) (param $d i64) (result i64)
+  (call $concat (call $cat3 (local.get $a) (local.get $b) (local.get $c)) (local.get $d)))
+
+(func $cat5 (param $a i64) (param $b i64) (param $c i64) (param $d i64) (param $e i64) (result i64)
+  (call $concat (call $cat4 (local.get $a) (local.get $b) (local.get $c) (local.get $d)) (local.get $e)))
+
+(func $itoa (param $i i64) (result i64)
+  (local $b i32)
+  (local.set $b (call $buf_new))
+  (call $buf_int (local.get $b) (local.get $i))
+  (call $buf_value (local.get $b)))
+
+(func $has_prefix (param $s i64) (param $p i64) (result i32)
+  (if (i32.lt_u (call $str_len (local.get $s)) (call $str_len (local.get $p)))
+    (then (return (i32.const 0))))
+  (i32.eqz (call $mem_cmp (call $str_ptr (local.get $s)) (call $str_ptr (local.get $p)) (call $str_len (local.get $p)))))
+
+;; Types
+
+(func $kind (param $t i32) (result i32)
+  (i32.load (local.get $t)))
+
+(func $named (param $t i32) (result i32)
+  (i32.load offset=4 (local.get $t)))
+
+(func $tname (param $t i32) (result i64)
+  (i64.load offset=8 (local.get $t)))
+
+(func $elem (param $t i32) (result i32)
+  (i32.load offset=16 (local.get $t)))
+
+(func $key (param $t i32) (result i32)
+  (i32.load offset=20 (local.get $t)))
+
+(func $nfields (param $t i32) (result i32)
+  (i32.load offset=24 (local.get $t)))
+
+(func $fname (param $t i32) (param $i i32) (result i64)
+  (i64.load (i32.add (i32.load offset=28 (local.get $t)) (i32.shl (local.get $i) (i32.const 3)))))
+
+(func $ftype (param $t i32) (param $i i32) (result i32)
+  (i32.load (i32.add (i32.load offset=32 (local.get $t)) (i32.shl (local.get $i) (i32.const 2)))))
+
+;; A type as the checker has it. Named structs and interfaces are equal
+;; only to themselves; others compare by structure.
+(func $identical (param $a i32) (param $b i32) (result i32)
+  (local $k i32) (local $i i32)
+  (if (i32.eq (local.get $a) (local.get $b))
+    (then (return (i32.const 1))))
+  (local.set $k (call $kind (local.get $a)))
+  (if (i32.ne (local.get $k) (call $kind (local.get $b)))
+    (then (return (i32.const 0))))
+  (if (i32.or (i32.eq (local.get $k) (i32.const 7)) (i32.or (i32.eq (local.get $k) (i32.const 9)) (i32.eq (local.get $k) (i32.const 10))))
+    (then (return (call $identical (call $elem (local.get $a)) (call $elem (local.get $b))))))
+  (if (i32.eq (local.get $k) (i32.const 8))
+    (then
+      (return (i32.and (call $identical (call $key (local.get $a)) (call $key (local.get $b)))
+        (call $identical (call $elem (local.get $a)) (call $elem (local.get $b)))))))
+  (if (i32.eq (local.get $k) (i32.const 11))
+    (then
+      (if (i32.or (i32.or (call $named (local.get $a)) (call $named (local.get $b)))
+            (i32.ne (call $nfields (local.get $a)) (call $nfields (local.get $b))))
+        (then (return (i32.const 0))))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $a))))
+          (if (i32.eqz (call $str_eq (call $fname (local.get $a) (local.get $i)) (call $fname (local.get $b) (local.get $i))))
+            (then (return (i32.const 0))))
+          (if (i32.eqz (call $identical (call $ftype (local.get $a) (local.get $i)) (call $ftype (local.get $b) (local.get $i))))
+            (then (return (i32.const 0))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return (i32.const 1))))
+  (if (i32.eq (local.get $k) (i32.const 12))
+    (then (return (i32.and (i32.eqz (call $named (local.get $a))) (i32.eqz (call $named (local.get $b)))))))
+  (i32.const 1))
+
+(func $comparable (param $t i32) (result i32)
+  (local $k i32) (local $i i32)
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.or (i32.eq (local.get $k) (i32.const 7)) (i32.or (i32.eq (local.get $k) (i32.const 8)) (i32.eq (local.get $k) (i32.const 6))))
+    (then (return (i32.const 0))))
+  (if (i32.eq (local.get $k) (i32.const 11))
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $t))))
+          (if (i32.eqz (call $comparable (call $ftype (local.get $t) (local.get $i))))
+            (then (return (i32.const 0))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))))
+  (i32.const 1))
+
+;; Pointer types made at run time, for the address of a field whose type
+;; the generated code did not know; a list linked through offset 40.
+(global $made (mut i32) (i32.const 0))
+
+(func $pointer_to (param $elem i32) (result i32)
+  (local $t i32)
+  (local.set $t (global.get $made))
+  (block $done
+    (loop $next
+      (br_if $done (i32.eqz (local.get $t)))
+      (if (i32.eq (call $elem (local.get $t)) (local.get $elem))
+        (then (return (local.get $t))))
+      (local.set $t (i32.load offset=40 (local.get $t)))
+      (br $next)))
+  (local.set $t (call $alloc (i32.const 44)))
+  (i32.store (local.get $t) (i32.const 9))
+  (i64.store offset=8 (local.get $t) (call $concat (string "*") (call $tname (local.get $elem))))
+  (i32.store offset=16 (local.get $t) (local.get $elem))
+  (i32.store offset=40 (local.get $t) (global.get $made))
+  (global.set $made (local.get $t))
+  (local.get $t))
+
+;; Values
+
+(func $nil (result i32 i64)
+  (global.get $t_nil)
+  (i64.const 0))
+
+(func $int (param $i i64) (result i32 i64)
+  (global.get $t_int)
+  (local.get $i))
+
+(func $float (param $f f64) (result i32 i64)
+  (global.get $t_float)
+  (i64.reinterpret_f64 (local.get $f)))
+
+(func $bool (param $b i32) (result i32 i64)
+  (global.get $t_bool)
+  (i64.extend_i32_u (i32.ne (local.get $b) (i32.const 0))))
+
+(func $string (param $s i64) (result i32 i64)
+  (global.get $t_string)
+  (local.get $s))
+
+(func $f64 (param $x i64) (result f64)
+  (f64.reinterpret_i64 (local.get $x)))
+
+;; The address a value of a reference type holds.
+(func $ptr (param $x i64) (result i32)
+  (i32.wrap_i64 (local.get $x)))
+
+(func $ref (param $t i32) (param $p i32) (result i32 i64)
+  (local.get $t)
+  (i64.extend_i32_u (local.get $p)))
+
+(func $get (param $p i32) (result i32 i64)
+  (i32.load (local.get $p))
+  (i64.load offset=8 (local.get $p)))
+
+(func $set (param $p i32) (param $t i32) (param $x i64)
+  (i32.store (local.get $p) (local.get $t))
+  (i64.store offset=8 (local.get $p) (local.get $x)))
+
+;; The i-th of the slots at `p`.
+(func $slot (param $p i32) (param $i i32) (result i32)
+  (i32.add (local.get $p) (i32.shl (local.get $i) (i32.const 4))))
+
+;; The results of a package function that has several: their number,
+;; then the results.
+(func $tuple (param $n i32) (param $values i32) (result i32 i64)
+  (local $p i32)
+  (local.set $p (call $alloc (i32.shl (i32.add (local.get $n) (i32.const 1)) (i32.const 4))))
+  (call $set (local.get $p) (global.get $t_int) (i64.extend_i32_u (local.get $n)))
+  (memory.copy (i32.add (local.get $p) (i32.const 16)) (local.get $values) (i32.shl (local.get $n) (i32.const 4)))
+  (call $ref (global.get $t_tuple) (local.get $p)))
+
+(func $tuple2 (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (local $p i32)
+  (local.set $p (call $alloc (i32.const 48)))
+  (call $set (local.get $p) (global.get $t_int) (i64.const 2))
+  (call $set (i32.add (local.get $p) (i32.const 16)) (local.get $at) (local.get $a))
+  (call $set (i32.add (local.get $p) (i32.const 32)) (local.get $bt) (local.get $b))
+  (call $ref (global.get $t_tuple) (local.get $p)))
+
+;; The slots of a tuple's values.
+(func $untuple (param $t i32) (param $x i64) (result i32)
+  (i32.add (call $ptr (local.get $x)) (i32.const 16)))
+
+;; The value an interface holds, or the value itself.
+(func $raw (param $t i32) (param $x i64) (result i32 i64)
+  (if (i32.eq (call $kind (local.get $t)) (i32.const 12))
+    (then
+      (if (i64.eqz (local.get $x))
+        (then (return (call $nil))))
+      (return (call $get (call $ptr (local.get $x))))))
+  (local.get $t)
+  (local.get $x))
+
+;; The name of the value's dynamic type as shown to users.
+(func $dynamic_name (param $t i32) (param $x i64) (result i64)
+  (if (i32.eq (call $kind (local.get $t)) (i32.const 12))
+    (then
+      (if (i64.eqz (local.get $x))
+        (then (return (string "nil"))))
+      (return (call $tname (i32.load (call $ptr (local.get $x)))))))
+  (call $tname (local.get $t)))
+
+(func $zero (param $t i32) (result i32 i64)
+  (local $k i32) (local $p i32) (local $i i32)
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.or (i32.eqz (local.get $k)) (i32.eq (local.get $k) (i32.const 6)))
+    (then (return (call $nil))))
+  (if (i32.eq (local.get $k) (i32.const 1))
+    (then (return (call $bool (i32.const 0)))))
+  (if (i32.or (i32.eq (local.get $k) (i32.const 2)) (i32.eq (local.get $k) (i32.const 3)))
+    (then (return (call $int (i64.const 0)))))
+  (if (i32.eq (local.get $k) (i32.const 4))
+    (then (return (call $float (f64.const 0)))))
+  (if (i32.eq (local.get $k) (i32.const 5))
+    (then (return (call $string (i64.const 0)))))
+  (if (i32.eq (local.get $k) (i32.const 11))
+    (then
+      (local.set $p (call $alloc (i32.shl (call $nfields (local.get $t)) (i32.const 4))))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $t))))
+          (call $set (call $slot (local.get $p) (local.get $i)) (call $zero (call $ftype (local.get $t) (local.get $i))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return (call $ref (local.get $t) (local.get $p)))))
+  (local.get $t)
+  (i64.const 0))
+
+;; A copy of a struct value, for storing where it can change in place or
+;; reading from there. Other values are shared.
+(func $dup (param $t i32) (param $x i64) (result i32 i64)
+  (local $n i32) (local $p i32) (local $i i32)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 11))
+    (then (return (local.get $t) (local.get $x))))
+  (local.set $n (call $nfields (local.get $t)))
+  (local.set $p (call $alloc (i32.shl (local.get $n) (i32.const 4))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (call $set (call $slot (local.get $p) (local.get $i))
+        (call $dup (call $get (call $slot (call $ptr (local.get $x)) (local.get $i)))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (call $ref (local.get $t) (local.get $p)))
+
+;; Stores into a slot. A struct of the same type is copied over the one
+;; there, so that pointers to its fields see the new value.
+(func $put (param $slot i32) (param $t i32) (param $x i64)
+  (local $old i64) (local $i i32)
+  (local.set $old (i64.load offset=8 (local.get $slot)))
+  (if (i32.and
+        (i32.and (i32.eq (call $kind (local.get $t)) (i32.const 11)) (i32.eq (i32.load (local.get $slot)) (local.get $t)))
+        (i32.and (i64.ne (local.get $old) (i64.const 0)) (i64.ne (local.get $old) (local.get $x))))
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $t))))
+          (call $put (call $slot (call $ptr (local.get $old)) (local.get $i))
+            (call $get (call $slot (call $ptr (local.get $x)) (local.get $i))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return)))
+  (call $set (local.get $slot) (call $dup (local.get $t) (local.get $x))))
+
+;; Panics
+;;
+;; A panic sets `$throwing` as well as `$panicking`; functions return as
+;; soon as they see it, up to a frame whose deferred calls may recover the
+;; panic, which clears it while they run. `$panicking` stays set until a
+;; recover.
+
+(global $throwing (mut i32) (i32.const 0))
+
+(func $traceback (result i64)
+  (local $b i32) (local $i i32) (local $name i64)
+  (local.set $b (call $buf_new))
+  (call $buf_str (local.get $b) (string "goroutine 1 [running]:\n"))
+  (local.set $i (global.get $depth))
+  (block $done
+    (loop $next
+      (br_if $done (i32.le_s (local.get $i) (i32.const 0)))
+      (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+      (local.set $name (i64.load (call $slot (global.get $frames) (local.get $i))))
+      (if (i64.ne (local.get $name) (i64.const 0))
+        (then
+          (call $buf_str (local.get $b) (local.get $name))
+          (call $buf_str (local.get $b)
+            (select (string "(...)\n") (string "()\n") (i32.load offset=8 (call $slot (global.get $frames) (local.get $i)))))))
+      (br $next)))
+  (call $buf_value (local.get $b)))
+
+;; Starts a panic with the value, in place of any panic already unwinding.
+(func $throw (param $t i32) (param $x i64)
+  (global.set $trace (call $traceback))
+  (global.set $panicking (i32.const 1))
+  (global.set $panic_t (local.get $t))
+  (global.set $panic_x (local.get $x))
+  (global.set $throwing (i32.const 1)))
+
+;; Panics with the message of a failed operation. Go's runtime errors say
+;; so; its other run-time panics do not.
+(func $error (param $msg i64)
+  (if (i32.eqz
+        (i32.or
+          (i32.or
+            (i32.or (call $has_prefix (local.get $msg) (string "assignment to entry in nil map"))
+              (call $has_prefix (local.get $msg) (string "send on closed channel")))
+            (i32.or (call $has_prefix (local.get $msg) (string "close of nil channel"))
+              (call $has_prefix (local.get $msg) (string "close of closed channel"))))
+          (i32.or
+            (i32.or (call $has_prefix (local.get $msg) (string "interface conversion:"))
+              (call $has_prefix (local.get $msg) (string "strings: ")))
+            (i32.or (call $has_prefix (local.get $msg) (string "strconv: "))
+              (call $has_prefix (local.get $msg) (string "errors: "))))))
+    (then (local.set $msg (call $concat (string "runtime error: ") (local.get $msg)))))
+  (call $throw (call $string (local.get $msg))))
+
+;; Enters a function named `name`, with a frame of `size` bytes on the
+;; shadow stack for the arguments of its calls, and returns the frame.
+(func $enter (param $name i64) (param $params i32) (param $size i32) (result i32)
+  (local $base i32)
+  (local.set $base (global.get $stack))
+  (if (i32.or (i32.ge_s (global.get $depth) (i32.const 1024))
+        (i32.gt_u (i32.add (local.get $base) (local.get $size)) (global.get $stack_end)))
+    (then
+      (call $error (string "stack overflow"))
+      (return (i32.const 0))))
+  (i64.store (call $slot (global.get $frames) (global.get $depth)) (local.get $name))
+  (i32.store offset=8 (call $slot (global.get $frames) (global.get $depth)) (local.get $params))
+  (global.set $depth (i32.add (global.get $depth) (i32.const 1)))
+  (global.set $stack (i32.add (local.get $base) (local.get $size)))
+  (local.get $base))
+
+(func $leave (param $base i32)
+  (global.set $depth (i32.sub (global.get $depth) (i32.const 1)))
+  (global.set $stack (local.get $base)))
+
+;; The frame of a function that defers calls: {defers, depth, stack}, its
+;; deferred calls last first.
+(func $frame_push (result i32)
+  (local $fr i32)
+  (local.set $fr (call $alloc (i32.const 12)))
+  (i32.store offset=4 (local.get $fr) (global.get $depth))
+  (i32.store offset=8 (local.get $fr) (global.get $stack))
+  (local.get $fr))
+
+;; How a goroutine that dies of a panic reports its value.
+(func $panic_message (param $b i32) (param $t i32) (param $x i64)
+  (local $k i32) (local $s i64) (local $e i32) (local $n i32)
+  (if (call $error_message (local.get $b) (local.get $t) (local.get $x))
+    (then (return)))
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.and (i32.eq (local.get $k) (i32.const 12)) (i64.ne (local.get $x) (i64.const 0)))
+    (then
+      (call $panic_message (local.get $b) (call $get (call $ptr (local.get $x))))
+      (return)))
+  (if (i32.eq (local.get $k) (i32.const 4))
+    (then
+      (if (f64.ne (call $f64 (local.get $x)) (call $f64 (local.get $x)))
+        (then
+          (call $buf_str (local.get $b) (string "NaN"))
+          (return)))
+      (if (f64.eq (f64.abs (call $f64 (local.get $x))) (f64.const inf))
+        (then
+          (call $buf_str (local.get $b)
+            (select (string "+Inf") (string "-Inf") (f64.gt (call $f64 (local.get $x)) (f64.const 0))))
+          (return)))
+      ;; Go's print: a sign, six decimals and an exponent of three digits
+      (local.set $s (call $format_float (f64.abs (call $f64 (local.get $x))) (i32.const 101) (i32.const 6)))
+      (local.set $e (call $index_byte (local.get $s) (i32.const 101)))
+      (call $buf_byte (local.get $b) (select (i32.const 45) (i32.const 43) (i64.lt_s (local.get $x) (i64.const 0))))
+      (call $buf_str (local.get $b) (call $substr (local.get $s) (i32.const 0) (i32.add (local.get $e) (i32.const 2))))
+      (local.set $n (i32.sub (call $str_len (local.get $s)) (i32.add (local.get $e) (i32.const 2))))
+      (block $done
+        (loop $pad
+          (br_if $done (i32.ge_s (local.get $n) (i32.const 3)))
+          (call $buf_byte (local.get $b) (i32.const 48))
+          (local.set $n (i32.add (local.get $n) (i32.const 1)))
+          (br $pad)))
+      (call $buf_str (local.get $b)
+        (call $substr (local.get $s) (i32.add (local.get $e) (i32.const 2)) (call $str_len (local.get $s))))
+      (return)))
+  (if (i32.or (i32.eq (local.get $k) (i32.const 5)) (i32.or (i32.eq (local.get $k) (i32.const 2)) (i32.eq (local.get $k) (i32.const 1))))
+    (then
+      (call $buf_inspect (local.get $b) (local.get $t) (local.get $x) (i32.const 1) (i32.const 0))
+      (return)))
+  (call $buf_byte (local.get $b) (i32.const 40))
+  (call $buf_str (local.get $b) (call $dynamic_name (local.get $t) (local.get $x)))
+  (call $buf_str (local.get $b) (string ") "))
+  (call $buf_inspect (local.get $b) (local.get $t) (local.get $x) (i32.const 1) (i32.const 0)))
+
+;; Reports the panic and exits.
+(func $die
+  (local $b i32)
+  (global.set $throwing (i32.const 0))
+  (local.set $b (call $buf_new))
+  (call $buf_str (local.get $b) (string "panic: "))
+  (call $panic_message (local.get $b) (global.get $panic_t) (global.get $panic_x))
+  (call $buf_str (local.get $b) (string "\n\n"))
+  (call $buf_str (local.get $b) (global.get $trace))
+  (block $done
+    (loop $trim
+      (br_if $done (i32.eqz (call $buf_len (local.get $b))))
+      (br_if $done
+        (i32.and
+          (i32.ne (call $buf_at (local.get $b) (i32.sub (call $buf_len (local.get $b)) (i32.const 1))) (i32.const 10))
+          (i32.ne (call $buf_at (local.get $b) (i32.sub (call $buf_len (local.get $b)) (i32.const 1))) (i32.const 32))))
+      (i32.store offset=4 (local.get $b) (i32.sub (call $buf_len (local.get $b)) (i32.const 1)))
+      (br $trim)))
+  (call $buf_byte (local.get $b) (i32.const 10))
+  (call $write_str (i32.const 2) (call $buf_value (local.get $b)))
+  (call $host_exit (i32.const 2)))
+
+;; Conversions
+
+;; Set by the functions that may fail without panicking when they do.
+(global $failed (mut i32) (i32.const 0))
+
+(func $box (param $to i32) (param $t i32) (param $x i64) (result i32 i64)
+  (local $p i32)
+  (local.set $p (call $alloc (i32.const 16)))
+  (call $set (local.get $p) (local.get $t) (local.get $x))
+  (call $ref (local.get $to) (local.get $p)))
+
+;; Converts a value for storing where one of type `to` is expected: wraps
+;; it in an interface, gives a nil its type, or panics that it does not
+;; fit. Sets `$failed` instead of panicking when `try` is set.
+(func $convert_or (param $t i32) (param $x i64) (param $to i32) (param $try i32) (result i32 i64)
+  (local $k i32) (local $vk i32)
+  (local.set $k (call $kind (local.get $to)))
+  (local.set $vk (call $kind (local.get $t)))
+  (if (i32.and (i32.eq (local.get $t) (local.get $to)) (i32.ne (local.get $k) (i32.const 12)))
+    (then (return (local.get $t) (local.get $x))))
+  (if (i32.eq (local.get $k) (i32.const 12))
+    (then
+      (if (i32.eq (local.get $vk) (i32.const 12))
+        (then (return (local.get $to) (local.get $x))))
+      (if (i32.eqz (local.get $vk))
+        (then (return (local.get $to) (i64.const 0))))
+      (return (call $box (local.get $to) (local.get $t) (local.get $x)))))
+  (if (i32.and (i32.eqz (local.get $vk))
+        (i32.or (i32.or (i32.eq (local.get $k) (i32.const 6)) (i32.eq (local.get $k) (i32.const 7)))
+          (i32.and (i32.ge_u (local.get $k) (i32.const 8)) (i32.le_u (local.get $k) (i32.const 10)))))
+    (then (return (call $zero (local.get $to)))))
+  (if (i32.and (i32.eq (local.get $k) (i32.const 3)) (i32.eq (local.get $vk) (i32.const 2)))
+    (then (return (call $int (i64.and (local.get $x) (i64.const 0xff))))))
+  (if (i32.and (i32.eq (local.get $k) (i32.const 4)) (i32.eq (local.get $vk) (i32.const 2)))
+    (then (return (call $float (f64.convert_i64_s (local.get $x))))))
+  (if (i32.eqz (local.get $k))
+    (then (return (local.get $t) (local.get $x))))
+  (if (call $identical (local.get $t) (local.get $to))
+    (then (return (local.get $t) (local.get $x))))
+  (if (i32.and (i32.eq (local.get $vk) (i32.const 12)) (i64.ne (local.get $x) (i64.const 0)))
+    (then
+      ;; a value the program's functions took boxed
+      (return (call $convert_or (call $get (call $ptr (local.get $x))) (local.get $to) (local.get $try)))))
+  (if (local.get $try)
+    (then
+      (global.set $failed (i32.const 1))
+      (return (call $zero (local.get $to)))))
+  (call $error
+    (call $cat5 (string "cannot use ") (call $tname (local.get $t)) (string " value as ") (call $tname (local.get $to))
+      (select (string " value: need type assertion") (string " value") (i32.eq (local.get $vk) (i32.const 12)))))
+  (call $nil))
+
+(func $convert (param $t i32) (param $x i64) (param $to i32) (result i32 i64)
+  (call $convert_or (local.get $t) (local.get $x) (local.get $to) (i32.const 0)))
+
+;; A conversion `T(x)`: everything `$convert` allows, plus the numeric,
+;; string and byte slice conversions.
+(func $conversion (param $t i32) (param $x i64) (param $to i32) (result i32 i64)
+  (local $k i32) (local $vk i32) (local $b i32) (local $i i32) (local $j i32) (local $n i32)
+  (local $p i32) (local $data i32) (local $bytes i32) (local $r i32) (local $size i32) (local $c i64)
+  (local $out i64) (local $same i32)
+  (local.set $k (call $kind (local.get $to)))
+  (if (i32.and (i32.eq (local.get $t) (local.get $to)) (i32.ne (local.get $k) (i32.const 12)))
+    (then (return (local.get $t) (local.get $x))))
+  (if (i32.ne (local.get $k) (i32.const 12))
+    (then
+      (call $raw (local.get $t) (local.get $x))
+      (local.set $x)
+      (local.set $t)))
+  (local.set $vk (call $kind (local.get $t)))
+  (if (i32.and (i32.eq (local.get $k) (i32.const 2)) (i32.eq (local.get $vk) (i32.const 4)))
+    (then (return (call $int (i64.trunc_sat_f64_s (call $f64 (local.get $x)))))))
+  (if (i32.and (i32.eq (local.get $k) (i32.const 3)) (i32.eq (local.get $vk) (i32.const 4)))
+    (then (return (call $int (i64.and (i64.trunc_sat_f64_s (call $f64 (local.get $x))) (i64.const 0xff))))))
+  (if (i32.eq (local.get $k) (i32.const 5))
+    (then
+      (if (i32.eq (local.get $vk) (i32.const 2))
+        (then
+          (local.set $b (call $buf_new))
+          (call $buf_rune (local.get $b) (local.get $x))
+          (return (call $string (call $buf_value (local.get $b))))))
+      (if (i32.and (i32.eq (local.get $vk) (i32.const 7))
+            (i32.or (i32.eq (call $kind (call $elem (local.get $t))) (i32.const 3))
+              (i32.eq (call $kind (call $elem (local.get $t))) (i32.const 2))))
+        (then
+          (local.set $bytes (i32.eq (call $kind (call $elem (local.get $t))) (i32.const 3)))
+          (local.set $b (call $buf_new))
+          (local.set $n (call $len_of (local.get $t) (local.get $x)))
+          (block $done
+            (loop $next
+              (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+              (local.set $p (call $slot (i32.load (call $ptr (local.get $x))) (local.get $i)))
+              (local.set $c
+                (select (i64.load offset=8 (local.get $p)) (i64.const 0) (i32.eq (call $kind (i32.load (local.get $p))) (i32.const 2))))
+              (if (local.get $bytes)
+                (then (call $buf_byte (local.get $b) (i32.wrap_i64 (local.get $c))))
+                (else (call $buf_rune (local.get $b) (local.get $c))))
+              (local.set $i (i32.add (local.get $i) (i32.const 1)))
+              (br $next)))
+          (return (call $string (call $buf_value (local.get $b))))))))
+  (if (i32.and (i32.and (i32.eq (local.get $k) (i32.const 7)) (i32.eq (local.get $vk) (i32.const 5)))
+        (i32.or (i32.eq (call $kind (call $elem (local.get $to))) (i32.const 3))
+          (i32.eq (call $kind (call $elem (local.get $to))) (i32.const 2))))
+    (then
+      (local.set $bytes (i32.eq (call $kind (call $elem (local.get $to))) (i32.const 3)))
+      (local.set $n
+        (select (call $str_len (local.get $x)) (call $rune_count (local.get $x)) (local.get $bytes)))
+      (call $slice_of (local.get $to) (local.get $n) (local.get $n))
+      (local.set $out)
+      (drop)
+      (local.set $data (i32.load (call $ptr (local.get $out))))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $x))))
+          (if (local.get $bytes)
+            (then
+              (local.set $r (i32.load8_u (i32.add (call $str_ptr (local.get $x)) (local.get $i))))
+              (local.set $size (i32.const 1)))
+            (else
+              (call $decode_at (local.get $x) (local.get $i))
+              (local.set $size)
+              (local.set $r)))
+          (call $set (call $slot (local.get $data) (local.get $j)) (call $int (i64.extend_i32_u (local.get $r))))
+          (local.set $i (i32.add (local.get $i) (local.get $size)))
+          (local.set $j (i32.add (local.get $j) (i32.const 1)))
+          (br $next)))
+      (return (local.get $to) (local.get $out))))
+  (if (i32.and (i32.eq (local.get $k) (i32.const 11)) (i32.eq (local.get $vk) (i32.const 11)))
+    (then
+      (local.set $same (i32.eq (call $nfields (local.get $t)) (call $nfields (local.get $to))))
+      (block $done
+        (loop $next
+          (br_if $done (i32.or (i32.eqz (local.get $same)) (i32.ge_s (local.get $i) (call $nfields (local.get $to)))))
+          (local.set $same
+            (i32.and (call $str_eq (call $fname (local.get $t) (local.get $i)) (call $fname (local.get $to) (local.get $i)))
+              (call $identical (call $ftype (local.get $t) (local.get $i)) (call $ftype (local.get $to) (local.get $i)))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (if (local.get $same)
+        (then
+          (call $dup (local.get $t) (local.get $x))
+          (local.set $out)
+          (drop)
+          (return (local.get $to) (local.get $out))))
+      (call $error (call $cat4 (string "cannot convert ") (call $tname (local.get $t)) (string " value to type ") (call $tname (local.get $to))))
+      (return (call $nil))))
+  (global.set $failed (i32.const 0))
+  (call $convert_or (local.get $t) (local.get $x) (local.get $to) (i32.const 1))
+  (local.set $out)
+  (local.set $r)
+  (if (global.get $failed)
+    (then
+      (call $error (call $cat4 (string "cannot convert ") (call $tname (local.get $t)) (string " value to type ") (call $tname (local.get $to))))
+      (return (call $nil))))
+  (local.get $r)
+  (local.get $out))
+
+;; Set by `$assert_or` when it is tried.
+(global $ok (mut i32) (i32.const 0))
+
+;; `x.(T)`, setting `$ok` rather than panicking when `try` is set.
+(func $assert_or (param $t i32) (param $x i64) (param $to i32) (param $try i32) (result i32 i64)
+  (local $vt i32) (local $v i64)
+  (global.set $ok (i32.const 0))
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 12))
+    (then
+      (if (local.get $try)
+        (then
+          ;; a value the VM would have held in an interface
+          (if (i32.and (i32.ne (call $kind (local.get $t)) (i32.const 0))
+                (i32.or (i32.eq (call $kind (local.get $to)) (i32.const 12)) (call $identical (local.get $t) (local.get $to))))
+            (then
+              (global.set $ok (i32.const 1))
+              (return (call $convert (local.get $t) (local.get $x) (local.get $to)))))
+          (return (call $zero (local.get $to)))))
+      (if (i32.and (i32.eq (call $kind (local.get $to)) (i32.const 12)) (i32.ne (call $kind (local.get $t)) (i32.const 0)))
+        (then (return (call $convert (local.get $t) (local.get $x) (local.get $to)))))
+      (if (call $identical (local.get $t) (local.get $to))
+        (then (return (local.get $t) (local.get $x))))
+      (if (i32.eqz (call $kind (local.get $t)))
+        (then (call $error (call $concat (string "interface conversion: interface is nil, not ") (call $tname (local.get $to)))))
+        (else
+          (call $error
+            (call $cat4 (string "interface conversion: interface {} is ") (call $tname (local.get $t)) (string ", not ") (call $tname (local.get $to))))))
+      (return (call $nil))))
+  (if (i64.eqz (local.get $x))
+    (then
+      (if (local.get $try)
+        (then (return (call $zero (local.get $to)))))
+      (call $error (call $concat (string "interface conversion: interface is nil, not ") (call $tname (local.get $to))))
+      (return (call $nil))))
+  (call $get (call $ptr (local.get $x)))
+  (local.set $v)
+  (local.set $vt)
+  (if (i32.eq (call $kind (local.get $to)) (i32.const 12))
+    (then
+      (global.set $ok (i32.const 1))
+      (return (call $convert (local.get $vt) (local.get $v) (local.get $to)))))
+  (if (call $identical (local.get $vt) (local.get $to))
+    (then
+      (global.set $ok (i32.const 1))
+      (return (local.get $vt) (local.get $v))))
+  (if (local.get $try)
+    (then (return (call $zero (local.get $to)))))
+  (call $error
+    (call $cat5 (string "interface conversion: ") (call $tname (local.get $t))
+      (call $concat (string " is ") (call $tname (local.get $vt))) (string ", not ") (call $tname (local.get $to))))
+  (call $nil))
+
+(func $assert (param $t i32) (param $x i64) (param $to i32) (result i32 i64)
+  (call $assert_or (local.get $t) (local.get $x) (local.get $to) (i32.const 0)))
+
+;; The comma-ok form, as two slots.
+(func $assert_ok (param $t i32) (param $x i64) (param $to i32) (result i32)
+  (local $out i32)
+  (local.set $out (call $alloc (i32.const 32)))
+  (call $set (local.get $out) (call $assert_or (local.get $t) (local.get $x) (local.get $to) (i32.const 1)))
+  (call $set (i32.add (local.get $out) (i32.const 16)) (call $bool (global.get $ok)))
+  (local.get $out))
+
+;; Equality
+
+;; `==` on the dynamic values of interfaces.
+(func $dynamic_equals (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32)
+  (if (i32.eqz (call $identical (local.get $at) (local.get $bt)))
+    (then (return (i32.const 0))))
+  (if (i32.eqz (call $comparable (local.get $at)))
+    (then
+      (call $error (call $concat (string "comparing uncomparable type ") (call $tname (local.get $at))))
+      (return (i32.const 0))))
+  (call $equals (local.get $at) (local.get $a) (local.get $bt) (local.get $b)))
+
+(func $nilable (param $k i32) (result i32)
+  (i32.or (i32.or (i32.eq (local.get $k) (i32.const 7)) (i32.eq (local.get $k) (i32.const 8)))
+    (i32.or (i32.eq (local.get $k) (i32.const 9)) (i32.eq (local.get $k) (i32.const 10)))))
+
+;; Go's `==`. Comparing values whose type has no `==`, other than to nil,
+;; panics.
+(func $equals (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32)
+  (local $ka i32) (local $kb i32) (local $i i32) (local $vt i32) (local $v i64) (local $it i32) (local $ix i64)
+  (local.set $ka (call $kind (local.get $at)))
+  (local.set $kb (call $kind (local.get $bt)))
+  (if (i32.and (i32.eq (local.get $ka) (i32.const 12)) (i32.eq (local.get $kb) (i32.const 12)))
+    (then
+      (if (i32.or (i64.eqz (local.get $a)) (i64.eqz (local.get $b)))
+        (then (return (i32.and (i64.eqz (local.get $a)) (i64.eqz (local.get $b))))))
+      (return (call $dynamic_equals (call $get (call $ptr (local.get $a))) (call $get (call $ptr (local.get $b)))))))
+  (if (i32.or (i32.eq (local.get $ka) (i32.const 12)) (i32.eq (local.get $kb) (i32.const 12)))
+    (then
+      (if (i32.eq (local.get $ka) (i32.const 12))
+        (then
+          (local.set $ix (local.get $a))
+          (local.set $vt (local.get $bt))
+          (local.set $v (local.get $b)))
+        (else
+          (local.set $ix (local.get $b))
+          (local.set $vt (local.get $at))
+          (local.set $v (local.get $a))))
+      (if (i32.eqz (call $kind (local.get $vt)))
+        (then (return (i64.eqz (local.get $ix)))))
+      (if (i64.eqz (local.get $ix))
+        (then (return (i32.const 0))))
+      (return (call $dynamic_equals (call $get (call $ptr (local.get $ix))) (local.get $vt) (local.get $v)))))
+  (if (i32.or
+        (i32.and (i32.eq (local.get $ka) (i32.const 2)) (i32.eq (local.get $kb) (i32.const 2)))
+        (i32.and (i32.eq (local.get $ka) (i32.const 1)) (i32.eq (local.get $kb) (i32.const 1))))
+    (then (return (i64.eq (local.get $a) (local.get $b)))))
+  (if (i32.and (call $is_number (local.get $at)) (call $is_number (local.get $bt)))
+    (then (return (f64.eq (call $number (local.get $at) (local.get $a)) (call $number (local.get $bt) (local.get $b))))))
+  (if (i32.and (i32.eq (local.get $ka) (i32.const 5)) (i32.eq (local.get $kb) (i32.const 5)))
+    (then (return (call $str_eq (local.get $a) (local.get $b)))))
+  (if (i32.and (i32.eqz (local.get $ka)) (i32.eqz (local.get $kb)))
+    (then (return (i32.const 1))))
+  (if (i32.or (i32.eqz (local.get $ka)) (i32.eqz (local.get $kb)))
+    (then
+      (local.set $vt (select (local.get $bt) (local.get $at) (i32.eqz (local.get $ka))))
+      (local.set $v (select (local.get $b) (local.get $a) (i32.eqz (local.get $ka))))
+      (if (call $nilable (call $kind (local.get $vt)))
+        (then (return (i64.eqz (local.get $v)))))
+      (if (i32.eq (call $kind (local.get $vt)) (i32.const 6))
+        (then (return (i32.const 0))))))
+  (if (i32.and
+        (i32.and (i32.or (i32.eq (local.get $ka) (i32.const 9)) (i32.eq (local.get $ka) (i32.const 10))) (i32.eq (local.get $ka) (local.get $kb)))
+        (call $identical (call $elem (local.get $at)) (call $elem (local.get $bt))))
+    (then (return (i64.eq (local.get $a) (local.get $b)))))
+  (if (i32.and (i32.and (i32.eq (local.get $ka) (i32.const 11)) (i32.eq (local.get $kb) (i32.const 11)))
+        (call $identical (local.get $at) (local.get $bt)))
+    (then
+      (if (i32.eqz (call $comparable (local.get $at)))
+        (then
+          (call $error
+            (call $cat3 (string "invalid operation: struct containing uncomparable fields cannot be compared (")
+              (call $tname (local.get $at)) (string ")")))
+          (return (i32.const 0))))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $at))))
+          (if (i32.eqz
+                (call $equals (call $get (call $slot (call $ptr (local.get $a)) (local.get $i)))
+                  (call $get (call $slot (call $ptr (local.get $b)) (local.get $i)))))
+            (then (return (i32.const 0))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return (i32.const 1))))
+  (if (i32.and (i32.eq (local.get $ka) (local.get $kb))
+        (i32.or (i32.eq (local.get $ka) (i32.const 7)) (i32.or (i32.eq (local.get $ka) (i32.const 8)) (i32.eq (local.get $ka) (i32.const 6)))))
+    (then
+      (call $error
+        (call $cat3 (string "invalid operation: ")
+          (select (string "slice") (select (string "map") (string "func") (i32.eq (local.get $ka) (i32.const 8)))
+            (i32.eq (local.get $ka) (i32.const 7)))
+          (string " can only be compared to nil")))
+      (return (i32.const 0))))
+  (call $error (call $mismatched (local.get $at) (local.get $bt)))
+  (i32.const 0))
+
+(func $mismatched (param $a i32) (param $b i32) (result i64)
+  (call $cat4 (string "invalid operation: mismatched types ") (call $tname (local.get $a)) (string " and ") (call $tname (local.get $b))))
+
+;; Map keys: equal exactly when the values are `==`, floats by their bits
+;; with -0 taken as 0, interfaces by dynamic type name and value.
+
+(func $check_key (param $t i32) (param $x i64)
+  (local $k i32) (local $i i32)
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.or (i32.or (i32.le_u (local.get $k) (i32.const 2)) (i32.or (i32.eq (local.get $k) (i32.const 4)) (i32.eq (local.get $k) (i32.const 5))))
+        (i32.or (i32.eq (local.get $k) (i32.const 9)) (i32.eq (local.get $k) (i32.const 10))))
+    (then (return)))
+  (if (i32.and (i32.eq (local.get $k) (i32.const 11)) (call $comparable (local.get $t)))
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $t))))
+          (call $check_key (call $get (call $slot (call $ptr (local.get $x)) (local.get $i))))
+          (if (global.get $throwing)
+            (then (return)))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return)))
+  (if (i32.eq (local.get $k) (i32.const 12))
+    (then
+      (if (i64.ne (local.get $x) (i64.const 0))
+        (then (call $check_key (call $get (call $ptr (local.get $x))))))
+      (return)))
+  (call $error (call $concat (string "hash of unhashable type ") (call $dynamic_name (local.get $t) (local.get $x)))))
+
+;; The kind of key a value makes: nil pointers and channels are nil.
+(func $key_kind (param $t i32) (param $x i64) (result i32)
+  (local $k i32)
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.and (i32.or (i32.eq (local.get $k) (i32.const 9)) (i32.eq (local.get $k) (i32.const 10))) (i64.eqz (local.get $x)))
+    (then (return (i32.const 0))))
+  (local.get $k))
+
+(func $float_key (param $x i64) (result i64)
+  (select (i64.const 0) (local.get $x) (f64.eq (call $f64 (local.get $x)) (f64.const 0))))
+
+(func $mix (param $h i64) (param $x i64) (result i64)
+  (i64.xor (local.get $h)
+    (i64.add (i64.add (i64.add (local.get $x) (i64.const 0x9e3779b97f4a7c15)) (i64.shl (local.get $h) (i64.const 6)))
+      (i64.shr_u (local.get $h) (i64.const 2)))))
+
+(func $fnv (param $s i64) (result i64)
+  (local $x i64) (local $i i32)
+  (local.set $x (i64.const 0xcbf29ce484222325))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $s))))
+      (local.set $x
+        (i64.mul (i64.xor (local.get $x) (i64.load8_u (i32.add (call $str_ptr (local.get $s)) (local.get $i))))
+          (i64.const 0x100000001b3)))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (local.get $x))
+
+(func $key_hash (param $t i32) (param $x i64) (result i64)
+  (local $k i32) (local $h i64) (local $i i32) (local $it i32) (local $ix i64)
+  (local.set $k (call $key_kind (local.get $t) (local.get $x)))
+  (local.set $h (i64.mul (i64.extend_i32_u (local.get $k)) (i64.const 0x100000001b3)))
+  (if (i32.or (i32.eq (local.get $k) (i32.const 1)) (i32.eq (local.get $k) (i32.const 2)))
+    (then (return (call $mix (local.get $h) (local.get $x)))))
+  (if (i32.eq (local.get $k) (i32.const 4))
+    (then (return (call $mix (local.get $h) (call $float_key (local.get $x))))))
+  (if (i32.eq (local.get $k) (i32.const 5))
+    (then (return (call $mix (local.get $h) (call $fnv (local.get $x))))))
+  (if (i32.or (i32.eq (local.get $k) (i32.const 9)) (i32.eq (local.get $k) (i32.const 10)))
+    (then (return (call $mix (local.get $h) (local.get $x)))))
+  (if (i32.eq (local.get $k) (i32.const 11))
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $t))))
+          (local.set $h
+            (call $mix (local.get $h) (call $key_hash (call $get (call $slot (call $ptr (local.get $x)) (local.get $i))))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return (local.get $h))))
+  (if (i32.and (i32.eq (local.get $k) (i32.const 12)) (i64.ne (local.get $x) (i64.const 0)))
+    (then
+      (call $get (call $ptr (local.get $x)))
+      (local.set $ix)
+      (local.set $it)
+      (return
+        (call $mix (call $mix (local.get $h) (call $fnv (call $tname (local.get $it))))
+          (call $key_hash (local.get $it) (local.get $ix))))))
+  (local.get $h))
+
+(func $key_equal (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32)
+  (local $k i32) (local $i i32) (local $xt i32) (local $x i64) (local $yt i32) (local $y i64)
+  (local.set $k (call $key_kind (local.get $at) (local.get $a)))
+  (if (i32.ne (local.get $k) (call $key_kind (local.get $bt) (local.get $b)))
+    (then (return (i32.const 0))))
+  (if (i32.or (i32.or (i32.eq (local.get $k) (i32.const 1)) (i32.eq (local.get $k) (i32.const 2)))
+        (i32.or (i32.eq (local.get $k) (i32.const 9)) (i32.eq (local.get $k) (i32.const 10))))
+    (then (return (i64.eq (local.get $a) (local.get $b)))))
+  (if (i32.eq (local.get $k) (i32.const 4))
+    (then (return (f64.eq (call $f64 (local.get $a)) (call $f64 (local.get $b))))))
+  (if (i32.eq (local.get $k) (i32.const 5))
+    (then (return (call $str_eq (local.get $a) (local.get $b)))))
+  (if (i32.eq (local.get $k) (i32.const 11))
+    (then
+      (if (i32.ne (call $nfields (local.get $at)) (call $nfields (local.get $bt)))
+        (then (return (i32.const 0))))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $at))))
+          (if (i32.eqz
+                (call $key_equal (call $get (call $slot (call $ptr (local.get $a)) (local.get $i)))
+                  (call $get (call $slot (call $ptr (local.get $b)) (local.get $i)))))
+            (then (return (i32.const 0))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return (i32.const 1))))
+  (if (i32.eq (local.get $k) (i32.const 12))
+    (then
+      (if (i32.or (i64.eqz (local.get $a)) (i64.eqz (local.get $b)))
+        (then (return (i32.and (i64.eqz (local.get $a)) (i64.eqz (local.get $b))))))
+      (call $get (call $ptr (local.get $a)))
+      (local.set $x)
+      (local.set $xt)
+      (call $get (call $ptr (local.get $b)))
+      (local.set $y)
+      (local.set $yt)
+      (return
+        (i32.and (call $str_eq (call $tname (local.get $xt)) (call $tname (local.get $yt)))
+          (call $key_equal (local.get $xt) (local.get $x) (local.get $yt) (local.get $y))))))
+  (i32.const 1))
+
+;; Maps: {entries, n, cap, count, slots, nslots}. Entries, of a key slot,
+;; an element slot and whether they are live, are in insertion order, with
+;; deleted ones left as holes until there are more holes than entries;
+;; slots index them open-addressed, holding an entry's index plus one.
+
+(func $map_new (result i32)
+  (call $alloc (i32.const 24)))
+
+(func $entry (param $m i32) (param $i i32) (result i32)
+  (i32.add (i32.load (local.get $m)) (i32.mul (local.get $i) (i32.const 40))))
+
+;; The entry holding the key, or -1.
+(func $map_find (param $m i32) (param $t i32) (param $x i64) (result i32)
+  (local $mask i32) (local $i i32) (local $s i32) (local $e i32)
+  (if (i32.or (i32.eqz (local.get $m)) (i32.eqz (i32.load offset=20 (local.get $m))))
+    (then (return (i32.const -1))))
+  (local.set $mask (i32.sub (i32.load offset=20 (local.get $m)) (i32.const 1)))
+  (local.set $i (i32.and (i32.wrap_i64 (call $key_hash (local.get $t) (local.get $x))) (local.get $mask)))
+  (loop $probe
+    (local.set $s (i32.load (i32.add (i32.load offset=16 (local.get $m)) (i32.shl (local.get $i) (i32.const 2)))))
+    (if (i32.eqz (local.get $s))
+      (then (return (i32.const -1))))
+    (local.set $e (call $entry (local.get $m) (i32.sub (local.get $s) (i32.const 1))))
+    (if (i32.and (i32.load offset=32 (local.get $e))
+          (call $key_equal (call $get (local.get $e)) (local.get $t) (local.get $x)))
+      (then (return (i32.sub (local.get $s) (i32.const 1)))))
+    (local.set $i (i32.and (i32.add (local.get $i) (i32.const 1)) (local.get $mask)))
+    (br $probe))
+  (unreachable))
+
+;; Puts entry `i` in a free slot of the index.
+(func $map_index (param $m i32) (param $i i32)
+  (local $mask i32) (local $s i32) (local $at i32)
+  (local.set $mask (i32.sub (i32.load offset=20 (local.get $m)) (i32.const 1)))
+  (local.set $s
+    (i32.and (i32.wrap_i64 (call $key_hash (call $get (call $entry (local.get $m) (local.get $i))))) (local.get $mask)))
+  (loop $probe
+    (local.set $at (i32.add (i32.load offset=16 (local.get $m)) (i32.shl (local.get $s) (i32.const 2))))
+    (if (i32.load (local.get $at))
+      (then
+        (local.set $s (i32.and (i32.add (local.get $s) (i32.const 1)) (local.get $mask)))
+        (br $probe))))
+  (i32.store (local.get $at) (i32.add (local.get $i) (i32.const 1))))
+
+;; Rebuilds the index, dropping the holes deleted entries left.
+(func $map_rehash (param $m i32) (param $want i32)
+  (local $i i32) (local $j i32) (local $nslots i32)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (i32.load offset=4 (local.get $m))))
+      (if (i32.load offset=32 (call $entry (local.get $m) (local.get $i)))
+        (then
+          (memory.copy (call $entry (local.get $m) (local.get $j)) (call $entry (local.get $m) (local.get $i)) (i32.const 40))
+          (local.set $j (i32.add (local.get $j) (i32.const 1)))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (i32.store offset=4 (local.get $m) (local.get $j))
+  (local.set $nslots (i32.const 8))
+  (block $done
+    (loop $grow
+      (br_if $done (i32.ge_s (local.get $nslots) (i32.shl (local.get $want) (i32.const 1))))
+      (local.set $nslots (i32.shl (local.get $nslots) (i32.const 1)))
+      (br $grow)))
+  (i32.store offset=16 (local.get $m) (call $alloc (i32.shl (local.get $nslots) (i32.const 2))))
+  (i32.store offset=20 (local.get $m) (local.get $nslots))
+  (local.set $i (i32.const 0))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (i32.load offset=4 (local.get $m))))
+      (call $map_index (local.get $m) (local.get $i))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next))))
+
+(func $map_set (param $m i32) (param $kt i32) (param $k i64) (param $vt i32) (param $v i64)
+  (local $i i32) (local $cap i32) (local $entries i32) (local $e i32)
+  (local.set $i (call $map_find (local.get $m) (local.get $kt) (local.get $k)))
+  (if (i32.ge_s (local.get $i) (i32.const 0))
+    (then
+      (local.set $e (call $entry (local.get $m) (local.get $i)))
+      (call $set (local.get $e) (local.get $kt) (local.get $k))
+      (call $set (i32.add (local.get $e) (i32.const 16)) (local.get $vt) (local.get $v))
+      (return)))
+  (if (i32.gt_s (i32.add (i32.load offset=4 (local.get $m)) (i32.const 1)) (i32.load offset=8 (local.get $m)))
+    (then
+      (local.set $cap (select (i32.shl (i32.load offset=8 (local.get $m)) (i32.const 1)) (i32.const 8) (i32.load offset=8 (local.get $m))))
+      (local.set $entries (call $alloc (i32.mul (local.get $cap) (i32.const 40))))
+      (memory.copy (local.get $entries) (i32.load (local.get $m)) (i32.mul (i32.load offset=4 (local.get $m)) (i32.const 40)))
+      (i32.store (local.get $m) (local.get $entries))
+      (i32.store offset=8 (local.get $m) (local.get $cap))))
+  (if (i32.gt_s (i32.shl (i32.add (i32.load offset=4 (local.get $m)) (i32.const 1)) (i32.const 1)) (i32.load offset=20 (local.get $m)))
+    (then (call $map_rehash (local.get $m) (i32.add (i32.load offset=12 (local.get $m)) (i32.const 1)))))
+  (local.set $i (i32.load offset=4 (local.get $m)))
+  (local.set $e (call $entry (local.get $m) (local.get $i)))
+  (call $set (local.get $e) (local.get $kt) (local.get $k))
+  (call $set (i32.add (local.get $e) (i32.const 16)) (local.get $vt) (local.get $v))
+  (i32.store offset=32 (local.get $e) (i32.const 1))
+  (i32.store offset=4 (local.get $m) (i32.add (local.get $i) (i32.const 1)))
+  (i32.store offset=12 (local.get $m) (i32.add (i32.load offset=12 (local.get $m)) (i32.const 1)))
+  (call $map_index (local.get $m) (local.get $i)))
+
+(func $map_delete (param $m i32) (param $t i32) (param $x i64)
+  (local $i i32)
+  (local.set $i (call $map_find (local.get $m) (local.get $t) (local.get $x)))
+  (if (i32.lt_s (local.get $i) (i32.const 0))
+    (then (return)))
+  (i32.store offset=32 (call $entry (local.get $m) (local.get $i)) (i32.const 0))
+  (i32.store offset=12 (local.get $m) (i32.sub (i32.load offset=12 (local.get $m)) (i32.const 1)))
+  (if (i32.gt_s (i32.load offset=4 (local.get $m))
+        (i32.add (i32.shl (i32.load offset=12 (local.get $m)) (i32.const 1)) (i32.const 8)))
+    (then (call $map_rehash (local.get $m) (i32.load offset=12 (local.get $m))))))
+
+;; A key converted to the map's key type and checked to be hashable.
+(func $map_key (param $mt i32) (param $t i32) (param $x i64) (result i32 i64)
+  (call $convert (local.get $t) (local.get $x) (call $key (local.get $mt)))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.eqz (global.get $throwing))
+    (then (call $check_key (local.get $t) (local.get $x))))
+  (local.get $t)
+  (local.get $x))
+
+;; Slices: {data, len, cap}, the data an array of slots.
+
+(func $slice_of (param $t i32) (param $len i32) (param $cap i32) (result i32 i64)
+  (local $s i32) (local $i i32) (local $data i32) (local $et i32) (local $ex i64)
+  (local.set $s (call $alloc (i32.const 12)))
+  (local.set $data (call $alloc (i32.shl (local.get $cap) (i32.const 4))))
+  (i32.store (local.get $s) (local.get $data))
+  (i32.store offset=4 (local.get $s) (local.get $len))
+  (i32.store offset=8 (local.get $s) (local.get $cap))
+  (call $fill (local.get $data) (i32.const 0) (local.get $cap) (call $elem (local.get $t)))
+  (call $ref (local.get $t) (local.get $s)))
+
+;; Sets slots `from` up to `to` to the zero value of the type.
+(func $fill (param $data i32) (param $from i32) (param $to i32) (param $t i32)
+  (local $i i32)
+  (local.set $i (local.get $from))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $to)))
+      (call $set (call $slot (local.get $data) (local.get $i)) (call $zero (local.get $t)))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next))))
+
+(func $slice_data (param $x i64) (result i32)
+  (if (result i32) (i64.eqz (local.get $x))
+    (then (i32.const 0))
+    (else (i32.load (call $ptr (local.get $x))))))
+
+(func $slice_cap (param $x i64) (result i32)
+  (if (result i32) (i64.eqz (local.get $x))
+    (then (i32.const 0))
+    (else (i32.load offset=8 (call $ptr (local.get $x))))))
+
+;; The capacity `append` gives a slice that must hold `needed` elements
+;; and has `old`: Go's growth rule for word-sized elements, rounded up to
+;; an allocator size class.
+(func $grow_cap (param $old i32) (param $needed i32) (result i32)
+  (local $cap i32) (local $size i32) (local $classes i64) (local $i i32) (local $class i32)
+  (local.set $cap (local.get $old))
+  (if (i32.gt_s (local.get $needed) (i32.shl (local.get $cap) (i32.const 1)))
+    (then (local.set $cap (local.get $needed)))
+    (else
+      (if (i32.lt_s (local.get $old) (i32.const 256))
+        (then (local.set $cap (i32.shl (local.get $cap) (i32.const 1))))
+        (else
+          (block $done
+            (loop $grow
+              (br_if $done (i32.ge_s (local.get $cap) (local.get $needed)))
+              (local.set $cap
+                (i32.add (local.get $cap) (i32.shr_s (i32.add (local.get $cap) (i32.const 768)) (i32.const 2))))
+              (br $grow)))))))
+  (local.set $size (i32.shl (local.get $cap) (i32.const 3)))
+  (local.set $classes (string "\00\00\08\00\10\00\18\00\20\00\30\00\40\00\50\00\60\00\70\00\80\00\90\00\a0\00\b0\00\c0\00\d0\00\e0\00\f0\00\00\01\20\01\40\01\60\01\80\01\a0\01\c0\01\e0\01\00\02\40\02\80\02\c0\02\00\03\80\03\00\04\80\04\00\05\80\05\00\06\00\07\00\08\00\09\80\0a\00\0c\80\0c\80\0d\00\10\00\13\00\15\00\18\80\19\80\1a\00\1b\00\20\00\25\00\26\00\28\80\2a\00\30\00\35\00\38\00\40\00\48\80\4a\00\50\00\55\00\60\80\6a\00\70\00\80"))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $classes))))
+      (local.set $class (i32.load16_u (i32.add (call $str_ptr (local.get $classes)) (local.get $i))))
+      (if (i32.ge_s (local.get $class) (local.get $size))
+        (then (return (i32.shr_u (local.get $class) (i32.const 3)))))
+      (local.set $i (i32.add (local.get $i) (i32.const 2)))
+      (br $next)))
+  (i32.shr_u (i32.and (i32.add (local.get $size) (i32.const 8191)) (i32.const -8192)) (i32.const 3)))
+
+(func $not_a_slice (param $t i32) (param $x i64)
+  (call $error
+    (call $cat5 (string "invalid argument: ") (call $inspect (local.get $t) (local.get $x)) (string " (type ")
+      (call $tname (local.get $t)) (string ") is not a slice"))))
+
+;; `append(s, values...)`: writes in place while there is capacity, else
+;; copies to a new, larger backing array.
+(func $append_values (param $t i32) (param $x i64) (param $n i32) (param $values i32) (result i32 i64)
+  (local $conv i32) (local $i i32) (local $len i32) (local $cap i32) (local $needed i32) (local $out i32) (local $newcap i32)
+  (local $et i32) (local $ex i64)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 7))
+    (then
+      (call $not_a_slice (local.get $t) (local.get $x))
+      (return (call $nil))))
+  (if (i32.eqz (local.get $n))
+    (then (return (local.get $t) (local.get $x))))
+  (local.set $conv (call $alloc (i32.shl (local.get $n) (i32.const 4))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (call $convert (call $get (call $slot (local.get $values) (local.get $i))) (call $elem (local.get $t)))
+      (local.set $ex)
+      (local.set $et)
+      (if (global.get $throwing)
+        (then (return (call $nil))))
+      (call $set (call $slot (local.get $conv) (local.get $i)) (call $dup (local.get $et) (local.get $ex)))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (local.set $len (call $len_of (local.get $t) (local.get $x)))
+  (local.set $cap (call $slice_cap (local.get $x)))
+  (local.set $needed (i32.add (local.get $len) (local.get $n)))
+  (local.set $out (call $alloc (i32.const 12)))
+  (if (i32.le_s (local.get $needed) (local.get $cap))
+    (then
+      (memory.copy (call $slot (call $slice_data (local.get $x)) (local.get $len)) (local.get $conv) (i32.shl (local.get $n) (i32.const 4)))
+      (i32.store (local.get $out) (call $slice_data (local.get $x)))
+      (i32.store offset=4 (local.get $out) (local.get $needed))
+      (i32.store offset=8 (local.get $out) (local.get $cap)))
+    (else
+      (local.set $newcap (call $grow_cap (local.get $cap) (local.get $needed)))
+      (i32.store (local.get $out) (call $alloc (i32.shl (local.get $newcap) (i32.const 4))))
+      (memory.copy (i32.load (local.get $out)) (call $slice_data (local.get $x)) (i32.shl (local.get $len) (i32.const 4)))
+      (memory.copy (call $slot (i32.load (local.get $out)) (local.get $len)) (local.get $conv) (i32.shl (local.get $n) (i32.const 4)))
+      (call $fill (i32.load (local.get $out)) (local.get $needed) (local.get $newcap) (call $elem (local.get $t)))
+      (i32.store offset=4 (local.get $out) (local.get $needed))
+      (i32.store offset=8 (local.get $out) (local.get $newcap))))
+  (call $ref (local.get $t) (local.get $out)))
+
+;; The values `append(s, rest...)` appends, setting `$spread_n` to their
+;; number: the elements of a slice of the same type, or the bytes of a
+;; string appended to a `[]byte`.
+(global $spread_n (mut i32) (i32.const 0))
+
+(func $spread (param $t i32) (param $x i64) (param $rt i32) (param $r i64) (result i32)
+  (local $values i32) (local $i i32) (local $n i32)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (call $raw (local.get $rt) (local.get $r))
+  (local.set $r)
+  (local.set $rt)
+  (global.set $spread_n (i32.const 0))
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 7))
+    (then
+      (call $not_a_slice (local.get $t) (local.get $x))
+      (return (i32.const 0))))
+  (if (i32.and (i32.eq (call $kind (local.get $rt)) (i32.const 7))
+        (call $identical (call $elem (local.get $rt)) (call $elem (local.get $t))))
+    (then
+      (global.set $spread_n (call $len_of (local.get $rt) (local.get $r)))
+      (return (call $slice_data (local.get $r)))))
+  (if (i32.eqz (call $kind (local.get $rt)))
+    (then (return (i32.const 0))))
+  (if (i32.and (i32.eq (call $kind (local.get $rt)) (i32.const 5)) (i32.eq (call $kind (call $elem (local.get $t))) (i32.const 3)))
+    (then
+      (local.set $n (call $str_len (local.get $r)))
+      (local.set $values (call $alloc (i32.shl (local.get $n) (i32.const 4))))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+          (call $set (call $slot (local.get $values) (local.get $i))
+            (call $int (i64.load8_u (i32.add (call $str_ptr (local.get $r)) (local.get $i)))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (global.set $spread_n (local.get $n))
+      (return (local.get $values))))
+  (call $error
+    (call $cat5 (string "cannot use ") (call $inspect (local.get $rt) (local.get $r))
+      (call $concat (string " (type ") (call $tname (local.get $rt)))
+      (call $concat (string ") as []") (call $tname (call $elem (local.get $t))))
+      (string " value in argument to append")))
+  (i32.const 0))
+
+(func $index_int (param $t i32) (param $x i64) (result i64)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 2))
+    (then
+      (call $error (call $cat3 (string "invalid argument: index of type ") (call $tname (local.get $t)) (string " must be integer")))
+      (return (i64.const 0))))
+  (local.get $x))
+
+;; Checks the index is below `n`.
+(func $bounds (param $t i32) (param $x i64) (param $n i32) (result i32)
+  (local $i i64)
+  (local.set $i (call $index_int (local.get $t) (local.get $x)))
+  (if (global.get $throwing)
+    (then (return (i32.const 0))))
+  (if (i32.or (i64.lt_s (local.get $i) (i64.const 0)) (i64.ge_s (local.get $i) (i64.extend_i32_s (local.get $n))))
+    (then
+      (call $error
+        (call $cat4 (string "index out of range [") (call $itoa (local.get $i)) (string "] with length ")
+          (call $itoa (i64.extend_i32_s (local.get $n)))))
+      (return (i32.const 0))))
+  (i32.wrap_i64 (local.get $i)))
+
+(func $check (param $it i32) (param $i i64) (param $nt i32) (param $n i64)
+  (call $raw (local.get $nt) (local.get $n))
+  (local.set $n)
+  (drop)
+  (drop (call $bounds (local.get $it) (local.get $i) (i32.wrap_i64 (local.get $n)))))
+
+;; The length of a string, slice or map, or -1.
+(func $len_of (param $t i32) (param $x i64) (result i32)
+  (local $k i32)
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.eq (local.get $k) (i32.const 5))
+    (then (return (call $str_len (local.get $x)))))
+  (if (i32.or (i32.eq (local.get $k) (i32.const 7)) (i32.eq (local.get $k) (i32.const 8)))
+    (then
+      (if (i64.eqz (local.get $x))
+        (then (return (i32.const 0))))
+      (return
+        (i32.load (i32.add (call $ptr (local.get $x)) (select (i32.const 4) (i32.const 12) (i32.eq (local.get $k) (i32.const 7))))))))
+  (i32.const -1))
+
+(func $index (param $t i32) (param $x i64) (param $it i32) (param $i i64) (result i32 i64)
+  (local $k i32) (local $at i32) (local $e i32) (local $kt i32) (local $kx i64)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.eq (local.get $k) (i32.const 7))
+    (then
+      (local.set $at (call $bounds (local.get $it) (local.get $i) (call $len_of (local.get $t) (local.get $x))))
+      (if (global.get $throwing)
+        (then (return (call $nil))))
+      (return (call $dup (call $get (call $slot (call $slice_data (local.get $x)) (local.get $at)))))))
+  (if (i32.eq (local.get $k) (i32.const 5))
+    (then
+      (local.set $at (call $bounds (local.get $it) (local.get $i) (call $len_of (local.get $t) (local.get $x))))
+      (if (global.get $throwing)
+        (then (return (call $nil))))
+      (return (call $int (i64.load8_u (i32.add (call $str_ptr (local.get $x)) (local.get $at)))))))
+  (if (i32.eq (local.get $k) (i32.const 8))
+    (then
+      (call $map_key (local.get $t) (local.get $it) (local.get $i))
+      (local.set $kx)
+      (local.set $kt)
+      (if (global.get $throwing)
+        (then (return (call $nil))))
+      (local.set $e (call $map_find (call $ptr (local.get $x)) (local.get $kt) (local.get $kx)))
+      (if (i32.ge_s (local.get $e) (i32.const 0))
+        (then (return (call $get (i32.add (call $entry (call $ptr (local.get $x)) (local.get $e)) (i32.const 16))))))
+      (return (call $zero (call $elem (local.get $t))))))
+  (call $error (call $cat3 (string "invalid operation: cannot index ") (call $tname (local.get $t)) (string " value")))
+  (call $nil))
+
+;; The comma-ok form, as two slots.
+(func $index_ok (param $t i32) (param $x i64) (param $it i32) (param $i i64) (result i32)
+  (local $out i32) (local $e i32) (local $kt i32) (local $kx i64)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (local.set $out (call $alloc (i32.const 32)))
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 8))
+    (then
+      (call $error (call $cat3 (string "invalid operation: comma-ok index of ") (call $tname (local.get $t)) (string " value")))
+      (return (local.get $out))))
+  (call $map_key (local.get $t) (local.get $it) (local.get $i))
+  (local.set $kx)
+  (local.set $kt)
+  (if (global.get $throwing)
+    (then (return (local.get $out))))
+  (local.set $e (call $map_find (call $ptr (local.get $x)) (local.get $kt) (local.get $kx)))
+  (if (i32.ge_s (local.get $e) (i32.const 0))
+    (then (call $set (local.get $out) (call $get (i32.add (call $entry (call $ptr (local.get $x)) (local.get $e)) (i32.const 16)))))
+    (else (call $set (local.get $out) (call $zero (call $elem (local.get $t))))))
+  (call $set (i32.add (local.get $out) (i32.const 16)) (call $bool (i32.ge_s (local.get $e) (i32.const 0))))
+  (local.get $out))
+
+(func $set_index (param $t i32) (param $x i64) (param $it i32) (param $i i64) (param $vt i32) (param $v i64)
+  (local $k i32) (local $at i32) (local $kt i32) (local $kx i64)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.eq (local.get $k) (i32.const 7))
+    (then
+      (local.set $at (call $bounds (local.get $it) (local.get $i) (call $len_of (local.get $t) (local.get $x))))
+      (if (global.get $throwing)
+        (then (return)))
+      (call $convert (local.get $vt) (local.get $v) (call $elem (local.get $t)))
+      (local.set $v)
+      (local.set $vt)
+      (if (global.get $throwing)
+        (then (return)))
+      (call $put (call $slot (call $slice_data (local.get $x)) (local.get $at)) (local.get $vt) (local.get $v))
+      (return)))
+  (if (i32.eq (local.get $k) (i32.const 8))
+    (then
+      (call $map_key (local.get $t) (local.get $it) (local.get $i))
+      (local.set $kx)
+      (local.set $kt)
+      (if (global.get $throwing)
+        (then (return)))
+      (call $convert (local.get $vt) (local.get $v) (call $elem (local.get $t)))
+      (local.set $v)
+      (local.set $vt)
+      (if (global.get $throwing)
+        (then (return)))
+      (call $dup (local.get $vt) (local.get $v))
+      (local.set $v)
+      (local.set $vt)
+      (if (i64.eqz (local.get $x))
+        (then
+          (call $error (string "assignment to entry in nil map"))
+          (return)))
+      (call $map_set (call $ptr (local.get $x)) (local.get $kt) (local.get $kx) (local.get $vt) (local.get $v))
+      (return)))
+  (call $error (call $cat3 (string "cannot assign to element of ") (call $tname (local.get $t)) (string " value"))))
+
+(func $index_addr (param $t i32) (param $x i64) (param $it i32) (param $i i64) (param $to i32) (result i32 i64)
+  (local $at i32)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.eq (call $kind (local.get $t)) (i32.const 7))
+    (then
+      (local.set $at (call $bounds (local.get $it) (local.get $i) (call $len_of (local.get $t) (local.get $x))))
+      (if (global.get $throwing)
+        (then (return (call $nil))))
+      (if (i32.ne (call $kind (local.get $to)) (i32.const 9))
+        (then (local.set $to (call $pointer_to (call $elem (local.get $t))))))
+      (return (call $ref (local.get $to) (call $slot (call $slice_data (local.get $x)) (local.get $at))))))
+  (if (i32.eq (call $kind (local.get $t)) (i32.const 8))
+    (then (call $error (string "cannot take the address of a map element")))
+    (else
+      (call $error (call $cat3 (string "cannot take the address of an element of ") (call $tname (local.get $t)) (string " value")))))
+  (call $nil))
+
+;; `x[low:high]`; bounds left out are given as nil.
+(func $slice_expr (param $t i32) (param $x i64) (param $lt i32) (param $l i64) (param $ht i32) (param $h i64) (result i32 i64)
+  (local $len i32) (local $cap i32) (local $is_string i32) (local $lo i64) (local $hi i64) (local $limit i32) (local $s i32)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (local.set $is_string (i32.eq (call $kind (local.get $t)) (i32.const 5)))
+  (if (i32.eq (call $kind (local.get $t)) (i32.const 7))
+    (then
+      (local.set $len (call $len_of (local.get $t) (local.get $x)))
+      (local.set $cap (call $slice_cap (local.get $x))))
+    (else
+      (if (local.get $is_string)
+        (then
+          (local.set $len (call $str_len (local.get $x)))
+          (local.set $cap (local.get $len)))
+        (else
+          (call $error (call $cat3 (string "cannot slice ") (call $tname (local.get $t)) (string " value")))
+          (return (call $nil))))))
+  (call $raw (local.get $lt) (local.get $l))
+  (local.set $l)
+  (local.set $lt)
+  (call $raw (local.get $ht) (local.get $h))
+  (local.set $h)
+  (local.set $ht)
+  (if (i32.ne (call $kind (local.get $lt)) (i32.const 0))
+    (then (local.set $lo (call $index_int (local.get $lt) (local.get $l)))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $hi (i64.extend_i32_s (local.get $len)))
+  (if (i32.ne (call $kind (local.get $ht)) (i32.const 0))
+    (then (local.set $hi (call $index_int (local.get $ht) (local.get $h)))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $limit (select (local.get $len) (local.get $cap) (local.get $is_string)))
+  (if (i32.or (i64.lt_s (local.get $hi) (i64.const 0)) (i64.gt_s (local.get $hi) (i64.extend_i32_s (local.get $limit))))
+    (then
+      (call $error
+        (call $cat5 (string "slice bounds out of range [:") (call $itoa (local.get $hi))
+          (select (string "] with length ") (string "] with capacity ") (local.get $is_string))
+          (call $itoa (i64.extend_i32_s (local.get $limit))) (string "")))
+      (return (call $nil))))
+  (if (i32.or (i64.lt_s (local.get $lo) (i64.const 0)) (i64.gt_s (local.get $lo) (local.get $hi)))
+    (then
+      (call $error
+        (call $cat5 (string "slice bounds out of range [") (call $itoa (local.get $lo)) (string ":")
+          (call $itoa (local.get $hi)) (string "]")))
+      (return (call $nil))))
+  (if (local.get $is_string)
+    (then (return (call $string (call $substr (local.get $x) (i32.wrap_i64 (local.get $lo)) (i32.wrap_i64 (local.get $hi)))))))
+  (if (i64.eqz (local.get $x))
+    (then (return (local.get $t) (local.get $x))))
+  (local.set $s (call $alloc (i32.const 12)))
+  (i32.store (local.get $s) (call $slot (call $slice_data (local.get $x)) (i32.wrap_i64 (local.get $lo))))
+  (i32.store offset=4 (local.get $s) (i32.wrap_i64 (i64.sub (local.get $hi) (local.get $lo))))
+  (i32.store offset=8 (local.get $s) (i32.sub (local.get $cap) (i32.wrap_i64 (local.get $lo))))
+  (call $ref (local.get $t) (local.get $s)))
+
+;; Pointers and fields
+
+(func $alloc_ptr (param $t i32) (result i32 i64)
+  (local $p i32)
+  (local.set $p (call $alloc (i32.const 16)))
+  (call $set (local.get $p) (call $zero (call $elem (local.get $t))))
+  (call $ref (local.get $t) (local.get $p)))
+
+(func $deref (param $t i32) (param $x i64) (result i32)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 9))
+    (then
+      (call $error (call $cat3 (string "invalid operation: cannot indirect ") (call $tname (local.get $t)) (string " value")))
+      (return (i32.const 0))))
+  (if (i64.eqz (local.get $x))
+    (then
+      (call $error (string "invalid memory address or nil pointer dereference"))
+      (return (i32.const 0))))
+  (call $ptr (local.get $x)))
+
+(func $load (param $t i32) (param $x i64) (result i32 i64)
+  (local $p i32)
+  (local.set $p (call $deref (local.get $t) (local.get $x)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $dup (call $get (local.get $p))))
+
+(func $store (param $t i32) (param $x i64) (param $vt i32) (param $v i64)
+  (local $p i32)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (local.set $p (call $deref (local.get $t) (local.get $x)))
+  (if (global.get $throwing)
+    (then (return)))
+  (call $convert (local.get $vt) (local.get $v) (call $elem (local.get $t)))
+  (local.set $v)
+  (local.set $vt)
+  (if (global.get $throwing)
+    (then (return)))
+  (call $put (local.get $p) (local.get $vt) (local.get $v)))
+
+(func $field_index (param $t i32) (param $name i64) (param $hint i32) (result i32)
+  (local $i i32)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 11))
+    (then (return (i32.const -1))))
+  (if (i32.and (i32.and (i32.ge_s (local.get $hint) (i32.const 0)) (i32.lt_s (local.get $hint) (call $nfields (local.get $t))))
+        (call $str_eq (call $fname (local.get $t) (local.get $hint)) (local.get $name)))
+    (then (return (local.get $hint))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $t))))
+      (if (call $str_eq (call $fname (local.get $t) (local.get $i)) (local.get $name))
+        (then (return (local.get $i))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (i32.const -1))
+
+(func $no_field (param $t i32) (param $name i64)
+  (call $error (call $cat4 (string "type ") (call $tname (local.get $t)) (string " has no field or method ") (local.get $name))))
+
+;; The field of a struct, or of the struct a pointer points to; also the
+;; method `Error` of an error. `hint` is where the field is expected.
+(func $field (param $t i32) (param $x i64) (param $name i64) (param $hint i32) (result i32 i64)
+  (local $via_pointer i32) (local $p i32) (local $i i32)
+  (if (call $str_eq (local.get $name) (string "Error"))
+    (then
+      (if (call $error_message (call $buf_new) (local.get $t) (local.get $x))
+        (then (return (call $error_method (call $raw (local.get $t) (local.get $x))))))))
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (local.set $via_pointer (i32.eq (call $kind (local.get $t)) (i32.const 9)))
+  (if (local.get $via_pointer)
+    (then
+      (local.set $p (call $deref (local.get $t) (local.get $x)))
+      (if (global.get $throwing)
+        (then (return (call $nil))))
+      (call $get (local.get $p))
+      (local.set $x)
+      (local.set $t)))
+  (local.set $i (call $field_index (local.get $t) (local.get $name) (local.get $hint)))
+  (if (i32.lt_s (local.get $i) (i32.const 0))
+    (then
+      (call $no_field (local.get $t) (local.get $name))
+      (return (call $nil))))
+  (call $get (call $slot (call $ptr (local.get $x)) (local.get $i)))
+  (local.set $x)
+  (local.set $t)
+  (if (local.get $via_pointer)
+    (then (return (call $dup (local.get $t) (local.get $x)))))
+  (local.get $t)
+  (local.get $x))
+
+;; `&p.f` for the struct `p` points to, through one more pointer if `p`
+;; points to one.
+(func $field_addr (param $t i32) (param $x i64) (param $name i64) (param $hint i32) (param $to i32) (result i32 i64)
+  (local $p i32) (local $st i32) (local $i i32)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 9))
+    (then
+      (call $error
+        (call $cat3 (string "cannot assign to a field of a non-addressable ") (call $tname (local.get $t)) (string " value")))
+      (return (call $nil))))
+  (local.set $p (call $deref (local.get $t) (local.get $x)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (i32.eq (call $kind (i32.load (local.get $p))) (i32.const 9))
+    (then
+      (call $get (local.get $p))
+      (local.set $x)
+      (local.set $t)
+      (local.set $p (call $deref (local.get $t) (local.get $x)))
+      (if (global.get $throwing)
+        (then (return (call $nil))))))
+  (local.set $st (i32.load (local.get $p)))
+  (local.set $i (call $field_index (local.get $st) (local.get $name) (local.get $hint)))
+  (if (i32.lt_s (local.get $i) (i32.const 0))
+    (then
+      (call $no_field (call $elem (local.get $t)) (local.get $name))
+      (return (call $nil))))
+  (if (i32.ne (call $kind (local.get $to)) (i32.const 9))
+    (then (local.set $to (call $pointer_to (call $ftype (local.get $st) (local.get $i))))))
+  (call $ref (local.get $to) (call $slot (call $ptr (i64.load offset=8 (local.get $p))) (local.get $i))))
+
+;; Composite literals and make
+
+;; A composite literal of type `t` from `n` key and value pairs: field
+;; indexes for structs, element indexes for slices, keys for maps.
+(func $composite (param $t i32) (param $n i32) (param $pairs i32) (result i32 i64)
+  (local $k i32) (local $i i32) (local $f i32) (local $len i32) (local $x i64) (local $m i32)
+  (local $vt i32) (local $v i64) (local $kt i32) (local $kx i64)
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.eq (local.get $k) (i32.const 11))
+    (then
+      (call $zero (local.get $t))
+      (local.set $x)
+      (drop)
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+          (local.set $f (i32.wrap_i64 (i64.load offset=8 (call $slot (local.get $pairs) (i32.shl (local.get $i) (i32.const 1))))))
+          (call $convert (call $get (call $slot (local.get $pairs) (i32.add (i32.shl (local.get $i) (i32.const 1)) (i32.const 1))))
+            (call $ftype (local.get $t) (local.get $f)))
+          (local.set $v)
+          (local.set $vt)
+          (if (global.get $throwing)
+            (then (return (call $nil))))
+          (call $set (call $slot (call $ptr (local.get $x)) (local.get $f)) (local.get $vt) (local.get $v))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return (local.get $t) (local.get $x))))
+  (if (i32.eq (local.get $k) (i32.const 7))
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+          (local.set $f (i32.wrap_i64 (i64.load offset=8 (call $slot (local.get $pairs) (i32.shl (local.get $i) (i32.const 1))))))
+          (if (i32.gt_s (i32.add (local.get $f) (i32.const 1)) (local.get $len))
+            (then (local.set $len (i32.add (local.get $f) (i32.const 1)))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (call $slice_of (local.get $t) (local.get $len) (local.get $len))
+      (local.set $x)
+      (drop)
+      (local.set $i (i32.const 0))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+          (local.set $f (i32.wrap_i64 (i64.load offset=8 (call $slot (local.get $pairs) (i32.shl (local.get $i) (i32.const 1))))))
+          (call $convert (call $get (call $slot (local.get $pairs) (i32.add (i32.shl (local.get $i) (i32.const 1)) (i32.const 1))))
+            (call $elem (local.get $t)))
+          (local.set $v)
+          (local.set $vt)
+          (if (global.get $throwing)
+            (then (return (call $nil))))
+          (call $set (call $slot (call $slice_data (local.get $x)) (local.get $f)) (call $dup (local.get $vt) (local.get $v)))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return (local.get $t) (local.get $x))))
+  (if (i32.eq (local.get $k) (i32.const 8))
+    (then
+      (local.set $m (call $map_new))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+          (call $map_key (local.get $t) (call $get (call $slot (local.get $pairs) (i32.shl (local.get $i) (i32.const 1)))))
+          (local.set $kx)
+          (local.set $kt)
+          (if (global.get $throwing)
+            (then (return (call $nil))))
+          (call $convert (call $get (call $slot (local.get $pairs) (i32.add (i32.shl (local.get $i) (i32.const 1)) (i32.const 1))))
+            (call $elem (local.get $t)))
+          (local.set $v)
+          (local.set $vt)
+          (if (global.get $throwing)
+            (then (return (call $nil))))
+          (call $dup (local.get $vt) (local.get $v))
+          (local.set $v)
+          (local.set $vt)
+          (call $map_set (local.get $m) (local.get $kt) (local.get $kx) (local.get $vt) (local.get $v))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return (call $ref (local.get $t) (local.get $m)))))
+  (call $error (call $concat (string "invalid composite literal type ") (call $tname (local.get $t))))
+  (call $nil))
+
+;; `make(T, sizes...)`, with the `n` sizes at `args`.
+(func $make (param $t i32) (param $n i32) (param $args i32) (result i32 i64)
+  (local $i i32) (local $at i32) (local $a i64) (local $s0 i64) (local $s1 i64) (local $min i32) (local $cap i64)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (call $raw (call $get (call $slot (local.get $args) (local.get $i))))
+      (local.set $a)
+      (local.set $at)
+      (if (i32.ne (call $kind (local.get $at)) (i32.const 2))
+        (then
+          (call $error
+            (call $cat5 (string "cannot convert ") (call $inspect (local.get $at) (local.get $a)) (string " (type ")
+              (call $tname (local.get $at)) (string ") to type int")))
+          (return (call $nil))))
+      (if (i64.lt_s (local.get $a) (i64.const 0))
+        (then
+          (call $error (call $cat3 (string "invalid argument: index ") (call $itoa (local.get $a)) (string " must not be negative")))
+          (return (call $nil))))
+      (if (i32.eqz (local.get $i))
+        (then (local.set $s0 (local.get $a))))
+      (if (i32.eq (local.get $i) (i32.const 1))
+        (then (local.set $s1 (local.get $a))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (local.set $min (i32.eq (call $kind (local.get $t)) (i32.const 7)))
+  (if (i32.or (i32.lt_s (local.get $n) (local.get $min)) (i32.gt_s (local.get $n) (i32.add (local.get $min) (i32.const 1))))
+    (then
+      (call $error
+        (call $cat5 (string "invalid operation: make(") (call $tname (local.get $t))
+          (select (string ") expects ") (string ", ...) expects ") (i32.lt_s (local.get $n) (local.get $min)))
+          (call $cat4 (call $itoa (i64.extend_i32_s (i32.add (local.get $min) (i32.const 1)))) (string " or ")
+            (call $itoa (i64.extend_i32_s (i32.add (local.get $min) (i32.const 2)))) (string " arguments; found "))
+          (call $itoa (i64.extend_i32_s (i32.add (local.get $n) (i32.const 1))))))
+      (return (call $nil))))
+  (if (i32.eq (call $kind (local.get $t)) (i32.const 7))
+    (then
+      (local.set $cap (select (local.get $s1) (local.get $s0) (i32.gt_s (local.get $n) (i32.const 1))))
+      (if (i64.gt_s (local.get $s0) (local.get $cap))
+        (then
+          (call $error
+            (call $cat5 (string "invalid argument: length and capacity swapped (") (call $itoa (local.get $s0)) (string " > ")
+              (call $itoa (local.get $cap)) (string ")")))
+          (return (call $nil))))
+      ;; Go's limit on a single allocation, of 1<<48 bytes
+      (if (i64.gt_s (local.get $s0) (i64.const 0x100000000000))
+        (then
+          (call $error (string "makeslice: len out of range"))
+          (return (call $nil))))
+      (if (i64.gt_s (local.get $cap) (i64.const 0x100000000000))
+        (then
+          (call $error (string "makeslice: cap out of range"))
+          (return (call $nil))))
+      (if (i64.gt_s (local.get $cap) (i64.const 0x7ffffff))
+        (then
+          (call $fatal (string "out of memory"))))
+      (return (call $slice_of (local.get $t) (i32.wrap_i64 (local.get $s0)) (i32.wrap_i64 (local.get $cap))))))
+  (if (i32.eq (call $kind (local.get $t)) (i32.const 8))
+    (then (return (call $ref (local.get $t) (call $map_new)))))
+  (call $error
+    (call $cat3 (string "invalid argument: cannot make ") (call $tname (local.get $t)) (string "; type must be slice, map, or channel")))
+  (call $nil))
+
+;; Operators. Values the program's functions took boxed are operated on
+;; as the values they hold.
+
+(func $is_number (param $t i32) (result i32)
+  (i32.or (i32.eq (call $kind (local.get $t)) (i32.const 2)) (i32.eq (call $kind (local.get $t)) (i32.const 4))))
+
+(func $number (param $t i32) (param $x i64) (result f64)
+  (if (result f64) (i32.eq (call $kind (local.get $t)) (i32.const 4))
+    (then (call $f64 (local.get $x)))
+    (else (f64.convert_i64_s (local.get $x)))))
+
+;; `op` is 0 for +, 1 for -, 2 for *, 3 for / and 4 for %.
+(func $arith (param $op i32) (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (local $ka i32) (local $kb i32) (local $l f64) (local $r f64)
+  (call $raw (local.get $at) (local.get $a))
+  (local.set $a)
+  (local.set $at)
+  (call $raw (local.get $bt) (local.get $b))
+  (local.set $b)
+  (local.set $bt)
+  (local.set $ka (call $kind (local.get $at)))
+  (local.set $kb (call $kind (local.get $bt)))
+  (if (i32.and (i32.eq (local.get $ka) (i32.const 2)) (i32.eq (local.get $kb) (i32.const 2)))
+    (then
+      (if (i32.eqz (local.get $op))
+        (then (return (call $int (i64.add (local.get $a) (local.get $b))))))
+      (if (i32.eq (local.get $op) (i32.const 1))
+        (then (return (call $int (i64.sub (local.get $a) (local.get $b))))))
+      (if (i32.eq (local.get $op) (i32.const 2))
+        (then (return (call $int (i64.mul (local.get $a) (local.get $b))))))
+      (if (i64.eqz (local.get $b))
+        (then
+          (call $error (string "integer divide by zero"))
+          (return (call $nil))))
+      (if (i64.eq (local.get $b) (i64.const -1))
+        (then
+          (return (call $int (select (i64.sub (i64.const 0) (local.get $a)) (i64.const 0) (i32.eq (local.get $op) (i32.const 3)))))))
+      (return
+        (call $int
+          (select (i64.div_s (local.get $a) (local.get $b)) (i64.rem_s (local.get $a) (local.get $b)) (i32.eq (local.get $op) (i32.const 3)))))))
+  (if (i32.and (i32.and (call $is_number (local.get $at)) (call $is_number (local.get $bt)))
+        (i32.and (i32.or (i32.eq (local.get $ka) (i32.const 4)) (i32.eq (local.get $kb) (i32.const 4))) (i32.ne (local.get $op) (i32.const 4))))
+    (then
+      (local.set $l (call $number (local.get $at) (local.get $a)))
+      (local.set $r (call $number (local.get $bt) (local.get $b)))
+      (if (i32.eqz (local.get $op))
+        (then (return (call $float (f64.add (local.get $l) (local.get $r))))))
+      (if (i32.eq (local.get $op) (i32.const 1))
+        (then (return (call $float (f64.sub (local.get $l) (local.get $r))))))
+      (if (i32.eq (local.get $op) (i32.const 2))
+        (then (return (call $float (f64.mul (local.get $l) (local.get $r))))))
+      (return (call $float (f64.div (local.get $l) (local.get $r))))))
+  (if (i32.and (i32.and (i32.eq (local.get $ka) (i32.const 5)) (i32.eq (local.get $kb) (i32.const 5))) (i32.eqz (local.get $op)))
+    (then (return (call $string (call $concat (local.get $a) (local.get $b))))))
+  (if (call $identical (local.get $at) (local.get $bt))
+    (then
+      (call $error
+        (call $cat4 (string "invalid operation: operator ") (call $substr (string "+-*/%") (local.get $op) (i32.add (local.get $op) (i32.const 1)))
+          (string " not defined on ") (call $tname (local.get $at)))))
+    (else (call $error (call $mismatched (local.get $at) (local.get $bt)))))
+  (call $nil))
+
+(func $add (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (if (i32.and (i32.eq (local.get $at) (global.get $t_int)) (i32.eq (local.get $bt) (global.get $t_int)))
+    (then (return (call $int (i64.add (local.get $a) (local.get $b))))))
+  (call $arith (i32.const 0) (local.get $at) (local.get $a) (local.get $bt) (local.get $b)))
+
+(func $sub (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (if (i32.and (i32.eq (local.get $at) (global.get $t_int)) (i32.eq (local.get $bt) (global.get $t_int)))
+    (then (return (call $int (i64.sub (local.get $a) (local.get $b))))))
+  (call $arith (i32.const 1) (local.get $at) (local.get $a) (local.get $bt) (local.get $b)))
+
+(func $mul (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (call $arith (i32.const 2) (local.get $at) (local.get $a) (local.get $bt) (local.get $b)))
+
+(func $div (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (call $arith (i32.const 3) (local.get $at) (local.get $a) (local.get $bt) (local.get $b)))
+
+(func $rem (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (call $arith (i32.const 4) (local.get $at) (local.get $a) (local.get $bt) (local.get $b)))
+
+;; `a > b`, or `a >= b` when `or_equal` is set; `a < b` is `b > a`.
+(func $greater (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (param $or_equal i32) (result i32)
+  (local $c i32) (local $l f64) (local $r f64)
+  (call $raw (local.get $at) (local.get $a))
+  (local.set $a)
+  (local.set $at)
+  (call $raw (local.get $bt) (local.get $b))
+  (local.set $b)
+  (local.set $bt)
+  (if (i32.and (i32.eq (call $kind (local.get $at)) (i32.const 2)) (i32.eq (call $kind (local.get $bt)) (i32.const 2)))
+    (then
+      (return
+        (select (i64.ge_s (local.get $a) (local.get $b)) (i64.gt_s (local.get $a) (local.get $b)) (local.get $or_equal)))))
+  (if (i32.and (call $is_number (local.get $at)) (call $is_number (local.get $bt)))
+    (then
+      (local.set $l (call $number (local.get $at) (local.get $a)))
+      (local.set $r (call $number (local.get $bt) (local.get $b)))
+      (return (select (f64.ge (local.get $l) (local.get $r)) (f64.gt (local.get $l) (local.get $r)) (local.get $or_equal)))))
+  (if (i32.and (i32.eq (call $kind (local.get $at)) (i32.const 5)) (i32.eq (call $kind (local.get $bt)) (i32.const 5)))
+    (then
+      (local.set $c (call $str_cmp (local.get $a) (local.get $b)))
+      (return (select (i32.ge_s (local.get $c) (i32.const 0)) (i32.gt_s (local.get $c) (i32.const 0)) (local.get $or_equal)))))
+  (if (call $identical (local.get $at) (local.get $bt))
+    (then (call $error (call $concat (string "invalid operation: ordering not defined on ") (call $tname (local.get $at)))))
+    (else (call $error (call $mismatched (local.get $at) (local.get $bt)))))
+  (i32.const 0))
+
+(func $eq (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (call $bool (call $equals (local.get $at) (local.get $a) (local.get $bt) (local.get $b))))
+
+(func $ne (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (call $bool (i32.eqz (call $equals (local.get $at) (local.get $a) (local.get $bt) (local.get $b)))))
+
+(func $lt (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (if (i32.and (i32.eq (local.get $at) (global.get $t_int)) (i32.eq (local.get $bt) (global.get $t_int)))
+    (then (return (call $bool (i64.lt_s (local.get $a) (local.get $b))))))
+  (call $bool (call $greater (local.get $bt) (local.get $b) (local.get $at) (local.get $a) (i32.const 0))))
+
+(func $le (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (call $bool (call $greater (local.get $bt) (local.get $b) (local.get $at) (local.get $a) (i32.const 1))))
+
+(func $gt (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (call $bool (call $greater (local.get $at) (local.get $a) (local.get $bt) (local.get $b) (i32.const 0))))
+
+(func $ge (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32 i64)
+  (call $bool (call $greater (local.get $at) (local.get $a) (local.get $bt) (local.get $b) (i32.const 1))))
+
+(func $neg (param $t i32) (param $x i64) (result i32 i64)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.eq (call $kind (local.get $t)) (i32.const 2))
+    (then (return (call $int (i64.sub (i64.const 0) (local.get $x))))))
+  (if (i32.eq (call $kind (local.get $t)) (i32.const 4))
+    (then (return (call $float (f64.neg (call $f64 (local.get $x)))))))
+  (call $error (call $concat (string "invalid operation: operator - not defined on ") (call $tname (local.get $t))))
+  (call $nil))
+
+(func $not (param $t i32) (param $x i64) (result i32 i64)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 1))
+    (then
+      (call $error (call $concat (string "invalid operation: operator ! not defined on ") (call $tname (local.get $t))))
+      (return (call $nil))))
+  (call $bool (i64.eqz (local.get $x))))
+
+;; The condition of a branch.
+(func $truth (param $t i32) (param $x i64) (result i32)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 1))
+    (then
+      (call $error (call $concat (string "non-boolean condition in if expression: ") (call $tname (local.get $t))))
+      (return (i32.const 0))))
+  (i32.wrap_i64 (local.get $x)))
+
+;; Calls
+;;
+;; A function object is {index in the table, parameters checked at calls
+;; or -1 for functions taking any, name (string) at 8, what %v prints at
+;; 16, the receiver of a bound method at 24, the number of variables it
+;; captures at 40 and their addresses at 44}.
+
+(func $call (param $t i32) (param $x i64) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $f i32) (local $values i32)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.or (i32.ne (call $kind (local.get $t)) (i32.const 6)) (i64.eqz (local.get $x)))
+    (then
+      (call $error (call $cat3 (string "cannot call non-function (type ") (call $tname (local.get $t)) (string ")")))
+      (return (call $nil))))
+  (local.set $f (call $ptr (local.get $x)))
+  (if (i32.and (i32.eq (local.get $argc) (i32.const 1)) (i32.eq (i32.load (local.get $argv)) (global.get $t_tuple)))
+    (then
+      ;; f(g()) passes each result of g
+      (local.set $values (call $ptr (i64.load offset=8 (local.get $argv))))
+      (local.set $argc (i32.wrap_i64 (i64.load offset=8 (local.get $values))))
+      (local.set $argv (i32.add (local.get $values) (i32.const 16)))))
+  (if (i32.and (i32.ge_s (i32.load offset=4 (local.get $f)) (i32.const 0)) (i32.ne (local.get $argc) (i32.load offset=4 (local.get $f))))
+    (then
+      (call $error
+        (call $cat4 (string "wrong number of arguments: want=") (call $itoa (i64.load32_s offset=4 (local.get $f)))
+          (string ", got=") (call $itoa (i64.extend_i32_s (local.get $argc)))))
+      (return (call $nil))))
+  (call_indirect (type $fn) (local.get $f) (local.get $argc) (local.get $argv) (i32.load (local.get $f))))
+
+;; A call whose last argument is spread, as in `append(s, t...)`.
+(func $call_spread (param $t i32) (param $x i64) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $rest i32) (local $n i32) (local $args i32)
+  (if (i32.lt_s (local.get $argc) (i32.const 2))
+    (then (return (call $call (local.get $t) (local.get $x) (local.get $argc) (local.get $argv)))))
+  (local.set $rest
+    (call $spread (call $get (local.get $argv)) (call $get (call $slot (local.get $argv) (i32.sub (local.get $argc) (i32.const 1))))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $n (global.get $spread_n))
+  (local.set $args (call $alloc (i32.shl (i32.add (i32.sub (local.get $argc) (i32.const 1)) (local.get $n)) (i32.const 4))))
+  (memory.copy (local.get $args) (local.get $argv) (i32.shl (i32.sub (local.get $argc) (i32.const 1)) (i32.const 4)))
+  (memory.copy (call $slot (local.get $args) (i32.sub (local.get $argc) (i32.const 1))) (local.get $rest) (i32.shl (local.get $n) (i32.const 4)))
+  (call $call (local.get $t) (local.get $x) (i32.add (i32.sub (local.get $argc) (i32.const 1)) (local.get $n)) (local.get $args)))
+
+;; A closure of a function of the program over the variables it
+;; captures, given as pointers.
+(func $closure (param $index i32) (param $name i64) (param $sig i64) (param $nparams i32) (param $nfree i32) (param $free i32) (result i32 i64)
+  (local $f i32) (local $vars i32) (local $i i32)
+  (local.set $f (call $alloc (i32.const 48)))
+  (i32.store (local.get $f) (local.get $index))
+  (i32.store offset=4 (local.get $f) (local.get $nparams))
+  (i64.store offset=8 (local.get $f) (local.get $name))
+  (i64.store offset=16 (local.get $f) (local.get $sig))
+  (i32.store offset=24 (local.get $f) (global.get $t_nil))
+  (i32.store offset=40 (local.get $f) (local.get $nfree))
+  (if (local.get $nfree)
+    (then
+      (local.set $vars (call $alloc (i32.shl (local.get $nfree) (i32.const 2))))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (local.get $nfree)))
+          (i32.store (i32.add (local.get $vars) (i32.shl (local.get $i) (i32.const 2)))
+            (call $ptr (i64.load offset=8 (call $slot (local.get $free) (local.get $i)))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (i32.store offset=44 (local.get $f) (local.get $vars))))
+  (call $ref (global.get $t_func) (local.get $f)))
+
+;; The address of the i-th variable a closure captures.
+(func $free_var (param $self i32) (param $i i32) (result i32)
+  (i32.load (i32.add (i32.load offset=44 (local.get $self)) (i32.shl (local.get $i) (i32.const 2)))))
+
+;; Deferred calls and recover
+;;
+;; A deferred call is {callee slot, argc at 16, argv at 20, next at 24}.
+
+(func $defer (param $fr i32) (param $t i32) (param $x i64) (param $argc i32) (param $argv i32)
+  (local $d i32) (local $args i32)
+  (local.set $d (call $alloc (i32.const 32)))
+  (call $set (local.get $d) (local.get $t) (local.get $x))
+  (local.set $args (call $alloc (i32.shl (local.get $argc) (i32.const 4))))
+  (memory.copy (local.get $args) (local.get $argv) (i32.shl (local.get $argc) (i32.const 4)))
+  (i32.store offset=16 (local.get $d) (local.get $argc))
+  (i32.store offset=20 (local.get $d) (local.get $args))
+  (i32.store offset=24 (local.get $d) (i32.load (local.get $fr)))
+  (i32.store (local.get $fr) (local.get $d)))
+
+;; Runs the calls the frame deferred, last first. While a panic unwinds,
+;; the calls may recover it, and a deferred call that panics in turn
+;; replaces the panic. Returns 0 for a panic that goes on unwinding, 1
+;; when the calls returned, and 2 when a panic was recovered, which
+;; resumes the function at its recover block.
+(func $unwind (param $fr i32) (result i32)
+  (local $d i32) (local $saved i32) (local $thrown i32) (local $t i32) (local $x i64)
+  (local.set $thrown (global.get $throwing))
+  (global.set $throwing (i32.const 0))
+  (global.set $depth (i32.load offset=4 (local.get $fr)))
+  (global.set $stack (i32.load offset=8 (local.get $fr)))
+  (block $done
+    (loop $next
+      (local.set $d (i32.load (local.get $fr)))
+      (br_if $done (i32.eqz (local.get $d)))
+      (i32.store (local.get $fr) (i32.load offset=24 (local.get $d)))
+      (call $raw (call $get (local.get $d)))
+      (local.set $x)
+      (local.set $t)
+      ;; recover stops a panic only when the deferred function calls it
+      (br_if $next
+        (i32.and (i32.eq (call $kind (local.get $t)) (i32.const 6)) (i64.eq (local.get $x) (i64.extend_i32_u (global.get $B_recover)))))
+      (local.set $saved (global.get $recover_depth))
+      (global.set $recover_depth
+        (select (i32.add (i32.load offset=4 (local.get $fr)) (i32.const 1)) (i32.const -1) (global.get $panicking)))
+      (call $call (call $get (local.get $d)) (i32.load offset=16 (local.get $d)) (i32.load offset=20 (local.get $d)))
+      (drop)
+      (drop)
+      (global.set $recover_depth (local.get $saved))
+      (if (global.get $throwing)
+        (then
+          (global.set $throwing (i32.const 0))
+          (local.set $thrown (i32.const 1))
+          (global.set $depth (i32.load offset=4 (local.get $fr)))
+          (global.set $stack (i32.load offset=8 (local.get $fr)))))
+      (br $next)))
+  (if (global.get $panicking)
+    (then
+      (global.set $throwing (i32.const 1))
+      (return (i32.const 0))))
+  (select (i32.const 2) (i32.const 1) (local.get $thrown)))
+
+(func $recover (result i32 i64)
+  (if (i32.and (global.get $panicking) (i32.eq (global.get $depth) (global.get $recover_depth)))
+    (then
+      (global.set $panicking (i32.const 0))
+      (return (call $convert (global.get $panic_t) (global.get $panic_x) (global.get $t_any)))))
+  (global.get $t_any)
+  (i64.const 0))
+
+(func $panic (param $t i32) (param $x i64)
+  (if (i32.or (i32.eqz (call $kind (local.get $t)))
+        (i32.and (i32.eq (call $kind (local.get $t)) (i32.const 12)) (i64.eqz (local.get $x))))
+    (then
+      (local.set $t (global.get $t_string))
+      (local.set $x (string "panic called with nil argument"))))
+  (call $throw (local.get $t) (local.get $x)))
+
+;; Builtins
+
+(func $want_args (param $name i64) (param $argc i32) (param $n i32) (result i32)
+  (if (i32.eq (local.get $argc) (local.get $n))
+    (then (return (i32.const 1))))
+  (call $error
+    (call $cat5 (select (string "not enough arguments for ") (string "too many arguments for ") (i32.lt_s (local.get $argc) (local.get $n)))
+      (local.get $name)
+      (call $concat (string ": want ") (call $itoa (i64.extend_i32_s (local.get $n))))
+      (string ", got ") (call $itoa (i64.extend_i32_s (local.get $argc)))))
+  (i32.const 0))
+
+(func $invalid_argument (param $name i64) (param $t i32) (param $x i64)
+  (call $error
+    (call $cat5 (string "invalid argument: ") (call $inspect (local.get $t) (local.get $x))
+      (call $concat (string " (type ") (call $tname (local.get $t))) (string ") for built-in ") (local.get $name))))
+
+(func $b_len (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $t i32) (local $x i64) (local $n i32)
+  (if (i32.eqz (call $want_args (string "len") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (call $raw (call $get (local.get $argv)))
+  (local.set $x)
+  (local.set $t)
+  (local.set $n (call $len_of (local.get $t) (local.get $x)))
+  (if (i32.lt_s (local.get $n) (i32.const 0))
+    (then
+      (call $invalid_argument (string "len") (local.get $t) (local.get $x))
+      (return (call $nil))))
+  (call $int (i64.extend_i32_s (local.get $n))))
+
+(func $b_cap (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $t i32) (local $x i64)
+  (if (i32.eqz (call $want_args (string "cap") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (call $raw (call $get (local.get $argv)))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 7))
+    (then
+      (call $invalid_argument (string "cap") (local.get $t) (local.get $x))
+      (return (call $nil))))
+  (call $int (i64.extend_i32_s (call $slice_cap (local.get $x)))))
+
+(func $b_append (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (if (i32.eqz (local.get $argc))
+    (then
+      (call $error (string "not enough arguments for append: want at least 1, got 0"))
+      (return (call $nil))))
+  (call $append_values (call $get (local.get $argv)) (i32.sub (local.get $argc) (i32.const 1)) (i32.add (local.get $argv) (i32.const 16))))
+
+(func $b_copy (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $dt i32) (local $d i64) (local $st i32) (local $s i64) (local $elem i32) (local $n i32) (local $i i32)
+  (local $to i32) (local $values i32)
+  (if (i32.eqz (call $want_args (string "copy") (local.get $argc) (i32.const 2)))
+    (then (return (call $nil))))
+  (call $raw (call $get (local.get $argv)))
+  (local.set $d)
+  (local.set $dt)
+  (call $raw (call $get (i32.add (local.get $argv) (i32.const 16))))
+  (local.set $s)
+  (local.set $st)
+  (if (i32.or (i32.ne (call $kind (local.get $dt)) (i32.const 7))
+        (i32.and (i32.ne (call $kind (local.get $st)) (i32.const 7)) (i32.ne (call $kind (local.get $st)) (i32.const 5))))
+    (then
+      (call $error
+        (call $cat5 (string "invalid argument: copy expects slice arguments; found ")
+          (call $cat4 (call $inspect (local.get $dt) (local.get $d)) (string " (type ") (call $tname (local.get $dt)) (string ") and "))
+          (call $inspect (local.get $st) (local.get $s))
+          (string " (type ")
+          (call $concat (call $tname (local.get $st)) (string ")"))))
+      (return (call $nil))))
+  (local.set $elem (select (call $elem (local.get $st)) (global.get $t_byte) (i32.eq (call $kind (local.get $st)) (i32.const 7))))
+  (if (i32.eqz (call $identical (local.get $elem) (call $elem (local.get $dt))))
+    (then
+      (call $error
+        (call $cat5 (string "invalid argument: arguments to copy ")
+          (call $cat4 (call $inspect (local.get $dt) (local.get $d)) (string " (type ") (call $tname (local.get $dt)) (string ") and "))
+          (call $cat4 (call $inspect (local.get $st) (local.get $s)) (string " (type ") (call $tname (local.get $st)) (string ")"))
+          (string " have different element types ")
+          (call $cat3 (call $tname (call $elem (local.get $dt))) (string " and ") (call $tname (local.get $elem)))))
+      (return (call $nil))))
+  (local.set $n (call $len_of (local.get $st) (local.get $s)))
+  (if (i32.lt_s (call $len_of (local.get $dt) (local.get $d)) (local.get $n))
+    (then (local.set $n (call $len_of (local.get $dt) (local.get $d)))))
+  (if (i32.eqz (local.get $n))
+    (then (return (call $int (i64.const 0)))))
+  (local.set $to (call $slice_data (local.get $d)))
+  (if (i32.eq (call $kind (local.get $st)) (i32.const 5))
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+          (call $set (call $slot (local.get $to) (local.get $i))
+            (call $int (i64.load8_u (i32.add (call $str_ptr (local.get $s)) (local.get $i)))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return (call $int (i64.extend_i32_s (local.get $n))))))
+  ;; read before writing, as the two may share a backing array
+  (local.set $values (call $alloc (i32.shl (local.get $n) (i32.const 4))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (call $set (call $slot (local.get $values) (local.get $i))
+        (call $dup (call $get (call $slot (call $slice_data (local.get $s)) (local.get $i)))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (local.set $i (i32.const 0))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (call $put (call $slot (local.get $to) (local.get $i)) (call $get (call $slot (local.get $values) (local.get $i))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (call $int (i64.extend_i32_s (local.get $n))))
+
+(func $b_delete (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $t i32) (local $x i64) (local $kt i32) (local $k i64)
+  (if (i32.eqz (call $want_args (string "delete") (local.get $argc) (i32.const 2)))
+    (then (return (call $nil))))
+  (call $raw (call $get (local.get $argv)))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 8))
+    (then
+      (call $error
+        (call $cat5 (string "invalid argument: ") (call $inspect (local.get $t) (local.get $x)) (string " (type ")
+          (call $tname (local.get $t)) (string ") is not a map")))
+      (return (call $nil))))
+  (call $map_key (local.get $t) (call $get (i32.add (local.get $argv) (i32.const 16))))
+  (local.set $k)
+  (local.set $kt)
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (i64.ne (local.get $x) (i64.const 0))
+    (then (call $map_delete (call $ptr (local.get $x)) (local.get $kt) (local.get $k))))
+  (call $nil))
+
+;; The argument that `min` or `max` picks, the first of equal ones; a NaN
+;; wins over everything, as in Go.
+(func $extreme (param $name i64) (param $argc i32) (param $argv i32) (param $sign i32) (result i32 i64)
+  (local $bt i32) (local $b i64) (local $at i32) (local $a i64) (local $i i32) (local $ka i32) (local $kb i32) (local $order i32)
+  (local $l f64) (local $r f64)
+  (if (i32.eqz (local.get $argc))
+    (then
+      (call $error (call $cat3 (string "not enough arguments for ") (local.get $name) (string ": want at least 1, got 0")))
+      (return (call $nil))))
+  (call $raw (call $get (local.get $argv)))
+  (local.set $b)
+  (local.set $bt)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $argc)))
+      (call $raw (call $get (call $slot (local.get $argv) (local.get $i))))
+      (local.set $a)
+      (local.set $at)
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (local.set $ka (call $kind (local.get $at)))
+      (local.set $kb (call $kind (local.get $bt)))
+      (block $ordered
+        (if (i32.and (i32.eq (local.get $ka) (i32.const 2)) (i32.eq (local.get $kb) (i32.const 2)))
+          (then
+            (local.set $order (i32.sub (i64.gt_s (local.get $a) (local.get $b)) (i64.lt_s (local.get $a) (local.get $b))))
+            (br $ordered)))
+        (if (i32.and (i32.eq (local.get $ka) (i32.const 5)) (i32.eq (local.get $kb) (i32.const 5)))
+          (then
+            (local.set $order (call $str_cmp (local.get $a) (local.get $b)))
+            (br $ordered)))
+        (if (i32.and (i32.eq (local.get $ka) (i32.const 4)) (f64.ne (call $f64 (local.get $a)) (call $f64 (local.get $a))))
+          (then (return (local.get $at) (local.get $a))))
+        (if (i32.and (call $is_number (local.get $at)) (call $is_number (local.get $bt)))
+          (then
+            (local.set $l (call $number (local.get $at) (local.get $a)))
+            (local.set $r (call $number (local.get $bt) (local.get $b)))
+            (br_if $next (f64.ne (local.get $r) (local.get $r)))
+            (local.set $order (i32.sub (f64.gt (local.get $l) (local.get $r)) (f64.lt (local.get $l) (local.get $r))))
+            (br $ordered)))
+        (if (i32.or (i32.eq (local.get $ka) (i32.const 2)) (i32.or (i32.eq (local.get $ka) (i32.const 5)) (i32.eq (local.get $ka) (i32.const 4))))
+          (then
+            (call $error
+              (call $cat4 (string "invalid argument: mismatched types ") (call $tname (local.get $bt)) (string " and ") (call $tname (local.get $at))))
+            (return (local.get $bt) (local.get $b))))
+        (call $error
+          (call $cat5 (string "invalid argument: ") (call $inspect (local.get $at) (local.get $a)) (string " (type ")
+            (call $tname (local.get $at)) (string ") cannot be ordered")))
+        (return (local.get $bt) (local.get $b)))
+      (if (i32.eq (local.get $order) (local.get $sign))
+        (then
+          (local.set $bt (local.get $at))
+          (local.set $b (local.get $a))))
+      (br $next)))
+  (local.get $bt)
+  (local.get $b))
+
+(func $b_min (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (call $extreme (string "min") (local.get $argc) (local.get $argv) (i32.const -1)))
+
+(func $b_max (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (call $extreme (string "max") (local.get $argc) (local.get $argv) (i32.const 1)))
+
+(func $b_clear (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $t i32) (local $x i64) (local $i i32)
+  (if (i32.eqz (call $want_args (string "clear") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (call $raw (call $get (local.get $argv)))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.eq (call $kind (local.get $t)) (i32.const 8))
+    (then
+      (if (i64.ne (local.get $x) (i64.const 0))
+        (then (memory.fill (call $ptr (local.get $x)) (i32.const 0) (i32.const 24))))
+      (return (call $nil))))
+  (if (i32.eq (call $kind (local.get $t)) (i32.const 7))
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (call $len_of (local.get $t) (local.get $x))))
+          (call $put (call $slot (call $slice_data (local.get $x)) (local.get $i)) (call $zero (call $elem (local.get $t))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return (call $nil))))
+  (call $error
+    (call $cat5 (string "invalid argument: ") (call $inspect (local.get $t) (local.get $x)) (string " (type ")
+      (call $tname (local.get $t)) (string ") must be a map or slice")))
+  (call $nil))
+
+(func $buf_address (param $b i32) (param $p i64)
+  (call $buf_str (local.get $b) (string "0x"))
+  (call $format_radix (local.get $b) (local.get $p) (i32.const 16) (i32.const 0) (i64.const -1)))
+
+;; The operands of print and println as they print them: basic values as
+;; themselves, references as addresses.
+(func $print_operand (param $b i32) (param $name i64) (param $t i32) (param $x i64)
+  (local $k i32)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.or (i32.le_u (local.get $k) (i32.const 2)) (i32.eq (local.get $k) (i32.const 5)))
+    (then
+      (call $buf_inspect (local.get $b) (local.get $t) (local.get $x) (i32.const 1) (i32.const 0))
+      (return)))
+  (if (i32.eq (local.get $k) (i32.const 4))
+    (then
+      (call $panic_message (local.get $b) (local.get $t) (local.get $x))
+      (return)))
+  (if (i32.or (i32.eq (local.get $k) (i32.const 9)) (i32.eq (local.get $k) (i32.const 8)))
+    (then
+      (call $buf_address (local.get $b) (local.get $x))
+      (return)))
+  (call $error
+    (call $cat5 (string "illegal types for operand: ") (local.get $name) (string " ")
+      (call $inspect (local.get $t) (local.get $x))
+      (call $cat3 (string " (type ") (call $tname (local.get $t)) (string ")")))))
+
+(func $b_print (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $b i32) (local $i i32)
+  (local.set $b (call $buf_new))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $argc)))
+      (call $print_operand (local.get $b) (string "print") (call $get (call $slot (local.get $argv) (local.get $i))))
+      (if (global.get $throwing)
+        (then (return (call $nil))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (call $write_str (i32.const 2) (call $buf_value (local.get $b)))
+  (call $nil))
+
+(func $b_println (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $b i32) (local $i i32)
+  (local.set $b (call $buf_new))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $argc)))
+      (if (local.get $i)
+        (then (call $buf_byte (local.get $b) (i32.const 32))))
+      (call $print_operand (local.get $b) (string "println") (call $get (call $slot (local.get $argv) (local.get $i))))
+      (if (global.get $throwing)
+        (then (return (call $nil))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (call $buf_byte (local.get $b) (i32.const 10))
+  (call $write_str (i32.const 2) (call $buf_value (local.get $b)))
+  (call $nil))
+
+(func $b_panic (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (if (call $want_args (string "panic") (local.get $argc) (i32.const 1))
+    (then (call $panic (call $get (local.get $argv)))))
+  (call $nil))
+
+(func $b_recover (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (if (i32.eqz (call $want_args (string "recover") (local.get $argc) (i32.const 0)))
+    (then (return (call $nil))))
+  (call $recover))
+
+(func $b_close (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $t i32) (local $x i64)
+  (if (i32.eqz (call $want_args (string "close") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (call $raw (call $get (local.get $argv)))
+  (local.set $x)
+  (local.set $t)
+  (call $error
+    (call $cat5 (string "invalid operation: non-chan argument ") (call $inspect (local.get $t) (local.get $x)) (string " (type ")
+      (call $tname (local.get $t)) (string ") for built-in close")))
+  (call $nil))
+
+;; Errors of the packages: a pointer to one of their struct types.
+
+;; The fields of the struct, or 0 if the value is not such an error.
+(func $error_struct (param $t i32) (param $x i64) (result i32)
+  (local $st i32)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.or (i32.ne (call $kind (local.get $t)) (i32.const 9)) (i64.eqz (local.get $x)))
+    (then (return (i32.const 0))))
+  (local.set $st (i32.load (call $ptr (local.get $x))))
+  (if (i32.or (i32.or (i32.eq (local.get $st) (global.get $t_errorString)) (i32.eq (local.get $st) (global.get $t_wrapError)))
+        (i32.or (i32.eq (local.get $st) (global.get $t_NumError)) (i32.eq (local.get $st) (global.get $t_PathError))))
+    (then (return (call $ptr (i64.load offset=8 (call $ptr (local.get $x)))))))
+  (i32.const 0))
+
+(func $buf_text (param $b i32) (param $p i32)
+  (if (i32.eq (call $kind (i32.load (local.get $p))) (i32.const 5))
+    (then (call $buf_str (local.get $b) (i64.load offset=8 (local.get $p))))))
+
+;; Appends what `err.Error()` returns if the value is an error; returns 0
+;; if not.
+(func $error_message (param $b i32) (param $t i32) (param $x i64) (result i32)
+  (local $fields i32) (local $st i32)
+  (local.set $fields (call $error_struct (local.get $t) (local.get $x)))
+  (if (i32.eqz (local.get $fields))
+    (then (return (i32.const 0))))
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (local.set $st (i32.load (call $ptr (local.get $x))))
+  (if (i32.or (i32.eq (local.get $st) (global.get $t_NumError)) (i32.eq (local.get $st) (global.get $t_PathError)))
+    (then
+      (if (i32.eq (local.get $st) (global.get $t_NumError))
+        (then
+          (call $buf_str (local.get $b) (string "strconv."))
+          (call $buf_text (local.get $b) (local.get $fields))
+          (call $buf_str (local.get $b) (string ": parsing "))
+          (if (i32.eq (call $kind (i32.load offset=16 (local.get $fields))) (i32.const 5))
+            (then (call $buf_quote (local.get $b) (i64.load offset=24 (local.get $fields)))))
+          (call $buf_str (local.get $b) (string ": ")))
+        (else
+          (call $buf_text (local.get $b) (local.get $fields))
+          (call $buf_byte (local.get $b) (i32.const 32))
+          (call $buf_text (local.get $b) (i32.add (local.get $fields) (i32.const 16)))
+          (call $buf_str (local.get $b) (string ": "))))
+      (if (i32.eqz (call $error_message (local.get $b) (call $get (i32.add (local.get $fields) (i32.const 32)))))
+        (then (call $buf_str (local.get $b) (string "<nil>"))))
+      (return (i32.const 1))))
+  (call $buf_text (local.get $b) (local.get $fields))
+  (i32.const 1))
+
+;; The error the value wraps, as its `Unwrap` method would return, or nil.
+(func $error_unwrap (param $t i32) (param $x i64) (result i32 i64)
+  (local $fields i32) (local $st i32) (local $et i32) (local $e i64)
+  (local.set $fields (call $error_struct (local.get $t) (local.get $x)))
+  (if (i32.eqz (local.get $fields))
+    (then (return (call $nil))))
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (local.set $st (i32.load (call $ptr (local.get $x))))
+  (local.set $et (global.get $t_nil))
+  (if (i32.eq (local.get $st) (global.get $t_wrapError))
+    (then
+      (call $get (i32.add (local.get $fields) (i32.const 16)))
+      (local.set $e)
+      (local.set $et)))
+  (if (i32.or (i32.eq (local.get $st) (global.get $t_NumError)) (i32.eq (local.get $st) (global.get $t_PathError)))
+    (then
+      (call $get (i32.add (local.get $fields) (i32.const 32)))
+      (local.set $e)
+      (local.set $et)))
+  (call $raw (local.get $et) (local.get $e))
+  (drop)
+  (if (i32.eqz (call $kind))
+    (then (return (call $nil))))
+  (local.get $et)
+  (local.get $e))
+
+;; An error of the struct type `t`, a pointer to one with the `n` fields
+;; at `fields`.
+(func $make_error (param $t i32) (param $n i32) (param $fields i32) (result i32 i64)
+  (local $x i64) (local $i i32) (local $slot i32)
+  (call $zero (local.get $t))
+  (local.set $x)
+  (drop)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (call $set (call $slot (call $ptr (local.get $x)) (local.get $i))
+        (call $convert (call $get (call $slot (local.get $fields) (local.get $i))) (call $ftype (local.get $t) (local.get $i))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (local.set $slot (call $alloc (i32.const 16)))
+  (call $set (local.get $slot) (local.get $t) (local.get $x))
+  (call $convert (call $ref (call $pointer_to (local.get $t)) (local.get $slot)) (global.get $t_error)))
+
+(func $new_error (param $msg i64) (result i32 i64)
+  (local $fields i32)
+  (local.set $fields (call $alloc (i32.const 16)))
+  (call $set (local.get $fields) (call $string (local.get $msg)))
+  (call $make_error (global.get $t_errorString) (i32.const 1) (local.get $fields)))
+
+(func $m_error (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $b i32)
+  (if (local.get $argc)
+    (then
+      (call $error (string "too many arguments in call to Error"))
+      (return (call $nil))))
+  (local.set $b (call $buf_new))
+  (drop (call $error_message (local.get $b) (call $get (i32.add (local.get $self) (i32.const 24)))))
+  (call $string (call $buf_value (local.get $b))))
+
+;; The method `Error` bound to an error.
+(func $error_method (param $t i32) (param $x i64) (result i32 i64)
+  (local $f i32)
+  (local.set $f (call $alloc (i32.const 48)))
+  (i32.store (local.get $f) (global.get $fn_m_error))
+  (i32.store offset=4 (local.get $f) (i32.const -1))
+  (i64.store offset=8 (local.get $f) (string "error.Error"))
+  (i64.store offset=16 (local.get $f) (string "method error.Error"))
+  (call $set (i32.add (local.get $f) (i32.const 24)) (local.get $t) (local.get $x))
+  (call $ref (global.get $t_func) (local.get $f)))
+
+;; Floats as strconv.FormatFloat formats them, 'e', 'f' or 'g' with `prec`
+;; digits, or the fewest that read back the same when it is negative.
+(func $format_float (param $f f64) (param $fmt i32) (param $prec i32) (result i64)
+  (local $p i32) (local $n i32)
+  (local.set $p
+    (call $alloc (i32.add (select (local.get $prec) (i32.const 0) (i32.gt_s (local.get $prec) (i32.const 0))) (i32.const 420))))
+  (local.set $n (call $host_format_float (local.get $f) (local.get $fmt) (local.get $prec) (local.get $p)))
+  ;; give back what the digits did not take
+  (global.set $hp (i32.add (local.get $p) (call $align8 (local.get $n))))
+  (call $str (local.get $p) (local.get $n)))
+
+(func $index_byte (param $s i64) (param $c i32) (result i32)
+  (local $i i32)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $s))))
+      (if (i32.eq (i32.load8_u (i32.add (call $str_ptr (local.get $s)) (local.get $i))) (local.get $c))
+        (then (return (local.get $i))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (i32.const -1))
+
+;; Inspecting values as %v prints them
+
+;; The order fmt prints map keys in: numbers and strings ascending, false
+;; before true, anything else as found.
+(func $sort_order (param $at i32) (param $a i64) (param $bt i32) (param $b i64) (result i32)
+  (local $ka i32) (local $kb i32) (local $c i32)
+  (local.set $ka (call $kind (local.get $at)))
+  (local.set $kb (call $kind (local.get $bt)))
+  (if (i32.and (i32.eq (local.get $ka) (local.get $kb))
+        (i32.or (i32.or (i32.eq (local.get $ka) (i32.const 1)) (i32.eq (local.get $ka) (i32.const 2))) (i32.eq (local.get $ka) (i32.const 3))))
+    (then (return (i32.sub (i64.gt_s (local.get $a) (local.get $b)) (i64.lt_s (local.get $a) (local.get $b))))))
+  (if (i32.and (i32.eq (local.get $ka) (i32.const 4)) (i32.eq (local.get $kb) (i32.const 4)))
+    (then
+      ;; the bits of floats order as integers once negative ones flip
+      (local.set $a (i64.xor (local.get $a) (i64.shr_u (i64.shr_s (local.get $a) (i64.const 63)) (i64.const 1))))
+      (local.set $b (i64.xor (local.get $b) (i64.shr_u (i64.shr_s (local.get $b) (i64.const 63)) (i64.const 1))))
+      (return (i32.sub (i64.gt_s (local.get $a) (local.get $b)) (i64.lt_s (local.get $a) (local.get $b))))))
+  (if (i32.and (i32.eq (local.get $ka) (i32.const 5)) (i32.eq (local.get $kb) (i32.const 5)))
+    (then
+      (local.set $c (call $str_cmp (local.get $a) (local.get $b)))
+      (return (i32.sub (i32.gt_s (local.get $c) (i32.const 0)) (i32.lt_s (local.get $c) (i32.const 0))))))
+  (if (i32.and (i32.eq (local.get $ka) (i32.const 12)) (i32.eq (local.get $kb) (i32.const 12)))
+    (then
+      (if (i32.or (i64.eqz (local.get $a)) (i64.eqz (local.get $b)))
+        (then (return (i32.sub (i64.ne (local.get $a) (i64.const 0)) (i64.ne (local.get $b) (i64.const 0))))))
+      (local.set $c
+        (call $str_cmp (call $tname (i32.load (call $ptr (local.get $a)))) (call $tname (i32.load (call $ptr (local.get $b))))))
+      (if (local.get $c)
+        (then (return (i32.sub (i32.gt_s (local.get $c) (i32.const 0)) (i32.lt_s (local.get $c) (i32.const 0))))))
+      (return (call $sort_order (call $get (call $ptr (local.get $a))) (call $get (call $ptr (local.get $b)))))))
+  (i32.const 0))
+
+;; The live entries of a map, sorted stably by key: an array of their
+;; addresses, and its length.
+(func $sorted_entries (param $x i64) (result i32 i32)
+  (local $m i32) (local $list i32) (local $n i32) (local $i i32) (local $k i32) (local $e i32)
+  (local.set $m (call $ptr (local.get $x)))
+  (if (i32.eqz (local.get $m))
+    (then (return (i32.const 0) (i32.const 0))))
+  (local.set $list (call $alloc (i32.shl (i32.load offset=12 (local.get $m)) (i32.const 2))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (i32.load offset=4 (local.get $m))))
+      (local.set $e (call $entry (local.get $m) (local.get $i)))
+      (if (i32.load offset=32 (local.get $e))
+        (then
+          (i32.store (i32.add (local.get $list) (i32.shl (local.get $n) (i32.const 2))) (local.get $e))
+          (local.set $n (i32.add (local.get $n) (i32.const 1)))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  ;; insertion sort keeps equal keys in order, and maps printed are small
+  (local.set $i (i32.const 1))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (local.set $e (i32.load (i32.add (local.get $list) (i32.shl (local.get $i) (i32.const 2)))))
+      (local.set $k (local.get $i))
+      (block $placed
+        (loop $shift
+          (br_if $placed (i32.eqz (local.get $k)))
+          (br_if $placed
+            (i32.le_s
+              (call $sort_order
+                (call $get (i32.load (i32.add (local.get $list) (i32.shl (i32.sub (local.get $k) (i32.const 1)) (i32.const 2)))))
+                (call $get (local.get $e)))
+              (i32.const 0)))
+          (i32.store (i32.add (local.get $list) (i32.shl (local.get $k) (i32.const 2)))
+            (i32.load (i32.add (local.get $list) (i32.shl (i32.sub (local.get $k) (i32.const 1)) (i32.const 2)))))
+          (local.set $k (i32.sub (local.get $k) (i32.const 1)))
+          (br $shift)))
+      (i32.store (i32.add (local.get $list) (i32.shl (local.get $k) (i32.const 2))) (local.get $e))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (local.get $list)
+  (local.get $n))
+
+(func $buf_inspect (param $b i32) (param $t i32) (param $x i64) (param $top i32) (param $names i32)
+  (local $k i32) (local $i i32) (local $n i32) (local $list i32) (local $e i32) (local $target i32)
+  (if (call $error_message (local.get $b) (local.get $t) (local.get $x))
+    (then (return)))
+  (local.set $k (call $kind (local.get $t)))
+  (block $kinds
+    (if (i32.or (i32.eq (local.get $k) (i32.const 2)) (i32.eq (local.get $k) (i32.const 3)))
+      (then
+        (call $buf_int (local.get $b) (local.get $x))
+        (br $kinds)))
+    (if (i32.eq (local.get $k) (i32.const 4))
+      (then
+        (call $buf_str (local.get $b) (call $format_float (call $f64 (local.get $x)) (i32.const 103) (i32.const -1)))
+        (br $kinds)))
+    (if (i32.eq (local.get $k) (i32.const 1))
+      (then
+        (call $buf_str (local.get $b) (select (string "true") (string "false") (i32.wrap_i64 (local.get $x))))
+        (br $kinds)))
+    (if (i32.eqz (local.get $k))
+      (then
+        (call $buf_str (local.get $b) (string "nil"))
+        (br $kinds)))
+    (if (i32.eq (local.get $k) (i32.const 5))
+      (then
+        (call $buf_lossy (local.get $b) (local.get $x))
+        (br $kinds)))
+    (if (i32.eq (local.get $k) (i32.const 7))
+      (then
+        (call $buf_byte (local.get $b) (i32.const 91))
+        (local.set $n (call $len_of (local.get $t) (local.get $x)))
+        (block $done
+          (loop $next
+            (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+            (if (local.get $i)
+              (then (call $buf_byte (local.get $b) (i32.const 32))))
+            (call $buf_inspect (local.get $b) (call $get (call $slot (call $slice_data (local.get $x)) (local.get $i)))
+              (i32.const 0) (local.get $names))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br $next)))
+        (call $buf_byte (local.get $b) (i32.const 93))
+        (br $kinds)))
+    (if (i32.eq (local.get $k) (i32.const 8))
+      (then
+        (call $sorted_entries (local.get $x))
+        (local.set $n)
+        (local.set $list)
+        (call $buf_str (local.get $b) (string "map["))
+        (block $done
+          (loop $next
+            (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+            (if (local.get $i)
+              (then (call $buf_byte (local.get $b) (i32.const 32))))
+            (local.set $e (i32.load (i32.add (local.get $list) (i32.shl (local.get $i) (i32.const 2)))))
+            (call $buf_inspect (local.get $b) (call $get (local.get $e)) (i32.const 0) (local.get $names))
+            (call $buf_byte (local.get $b) (i32.const 58))
+            (call $buf_inspect (local.get $b) (call $get (i32.add (local.get $e) (i32.const 16))) (i32.const 0) (local.get $names))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br $next)))
+        (call $buf_byte (local.get $b) (i32.const 93))
+        (br $kinds)))
+    (if (i32.eq (local.get $k) (i32.const 11))
+      (then
+        (call $buf_byte (local.get $b) (i32.const 123))
+        (block $done
+          (loop $next
+            (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $t))))
+            (if (local.get $i)
+              (then (call $buf_byte (local.get $b) (i32.const 32))))
+            (if (local.get $names)
+              (then
+                (call $buf_str (local.get $b) (call $fname (local.get $t) (local.get $i)))
+                (call $buf_byte (local.get $b) (i32.const 58))))
+            (call $buf_inspect (local.get $b) (call $get (call $slot (call $ptr (local.get $x)) (local.get $i)))
+              (i32.const 0) (local.get $names))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br $next)))
+        (call $buf_byte (local.get $b) (i32.const 125))
+        (br $kinds)))
+    (if (i32.eq (local.get $k) (i32.const 9))
+      (then
+        (local.set $target (call $ptr (local.get $x)))
+        (if (i32.eqz (local.get $target))
+          (then (call $buf_str (local.get $b) (string "<nil>")))
+          (else
+            (if (i32.and (local.get $top) (i32.eq (call $kind (i32.load (local.get $target))) (i32.const 11)))
+              (then
+                (call $buf_byte (local.get $b) (i32.const 38))
+                (call $buf_inspect (local.get $b) (call $get (local.get $target)) (i32.const 0) (local.get $names)))
+              (else (call $buf_address (local.get $b) (local.get $x))))))
+        (br $kinds)))
+    (if (i32.eq (local.get $k) (i32.const 12))
+      (then
+        (if (i64.eqz (local.get $x))
+          (then (call $buf_str (local.get $b) (string "<nil>")))
+          (else (call $buf_inspect (local.get $b) (call $get (call $ptr (local.get $x))) (local.get $top) (local.get $names))))
+        (br $kinds)))
+    (if (i32.eq (local.get $k) (i32.const 6))
+      (then
+        (if (i64.eqz (local.get $x))
+          (then (call $buf_str (local.get $b) (string "nil")))
+          (else (call $buf_str (local.get $b) (i64.load offset=16 (call $ptr (local.get $x))))))
+        (br $kinds)))
+    (call $buf_address (local.get $b) (local.get $x))))
+
+(func $inspect (param $t i32) (param $x i64) (result i64)
+  (local $b i32)
+  (local.set $b (call $buf_new))
+  (call $buf_inspect (local.get $b) (local.get $t) (local.get $x) (i32.const 1) (i32.const 0))
+  (call $buf_value (local.get $b)))
+
+;; Package fmt
+;;
+;; A verb's spec is {flags, width at 8, precision at 16}, the width and
+;; precision -1 when not given. The flags are bits: 1 for `-`, 2 for `+`,
+;; 4 for `+` on %v, 8 for `0`, 16 for ` ` and 32 for `#`.
+
+(func $spec_new (result i32)
+  (local $spec i32)
+  (local.set $spec (call $alloc (i32.const 24)))
+  (i64.store offset=8 (local.get $spec) (i64.const -1))
+  (i64.store offset=16 (local.get $spec) (i64.const -1))
+  (local.get $spec))
+
+(func $flag (param $spec i32) (param $bit i32) (result i32)
+  (i32.ne (i32.and (i32.load (local.get $spec)) (local.get $bit)) (i32.const 0)))
+
+;; Set by $format_value for a number the `0` flag pads with zeros.
+(global $numeric (mut i32) (i32.const 0))
+
+;; The value inside any interfaces.
+(func $dynamic (param $t i32) (param $x i64) (result i32 i64)
+  (block $done
+    (loop $next
+      (br_if $done (i32.or (i32.ne (call $kind (local.get $t)) (i32.const 12)) (i64.eqz (local.get $x))))
+      (call $get (call $ptr (local.get $x)))
+      (local.set $x)
+      (local.set $t)
+      (br $next)))
+  (local.get $t)
+  (local.get $x))
+
+;; The type as `%T` prints it: the named types the program declares are
+;; qualified by its package, `main`, as those of the packages are by theirs.
+(func $fmt_qualified (param $b i32) (param $t i32)
+  (local $k i32) (local $i i32)
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.eq (local.get $k) (i32.const 7))
+    (then
+      (call $buf_str (local.get $b) (string "[]"))
+      (call $fmt_qualified (local.get $b) (call $elem (local.get $t)))
+      (return)))
+  (if (i32.eq (local.get $k) (i32.const 9))
+    (then
+      (call $buf_str (local.get $b) (string "*"))
+      (call $fmt_qualified (local.get $b) (call $elem (local.get $t)))
+      (return)))
+  (if (i32.eq (local.get $k) (i32.const 10))
+    (then
+      (call $buf_str (local.get $b) (string "chan "))
+      (call $fmt_qualified (local.get $b) (call $elem (local.get $t)))
+      (return)))
+  (if (i32.eq (local.get $k) (i32.const 8))
+    (then
+      (call $buf_str (local.get $b) (string "map["))
+      (call $fmt_qualified (local.get $b) (call $key (local.get $t)))
+      (call $buf_byte (local.get $b) (i32.const 93))
+      (call $fmt_qualified (local.get $b) (call $elem (local.get $t)))
+      (return)))
+  (if (i32.and (i32.eq (local.get $k) (i32.const 11)) (i32.eqz (call $named (local.get $t))))
+    (then
+      (if (i32.eqz (call $nfields (local.get $t)))
+        (then
+          (call $buf_str (local.get $b) (string "struct {}"))
+          (return)))
+      (call $buf_str (local.get $b) (string "struct { "))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $t))))
+          (if (i32.gt_s (local.get $i) (i32.const 0))
+            (then (call $buf_str (local.get $b) (string "; "))))
+          (call $buf_str (local.get $b) (call $fname (local.get $t) (local.get $i)))
+          (call $buf_byte (local.get $b) (i32.const 32))
+          (call $fmt_qualified (local.get $b) (call $ftype (local.get $t) (local.get $i)))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (call $buf_str (local.get $b) (string " }"))
+      (return)))
+  (if (i32.and (call $named (local.get $t))
+        (i32.and (i32.lt_s (call $index_byte (call $tname (local.get $t)) (i32.const 46)) (i32.const 0))
+          (i32.eqz (call $str_eq (call $tname (local.get $t)) (string "error")))))
+    (then (call $buf_str (local.get $b) (string "main."))))
+  (call $buf_str (local.get $b) (call $tname (local.get $t))))
+
+(func $fmt_type_name (param $b i32) (param $t i32) (param $x i64)
+  (call $dynamic (local.get $t) (local.get $x))
+  (drop)
+  (local.set $t)
+  (if (i32.or (i32.eqz (call $kind (local.get $t))) (i32.eq (call $kind (local.get $t)) (i32.const 12)))
+    (then (call $buf_str (local.get $b) (string "<nil>")))
+    (else (call $fmt_qualified (local.get $b) (local.get $t)))))
+
+(func $bad_verb (param $b i32) (param $verb i32) (param $t i32) (param $x i64)
+  (call $dynamic (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (call $buf_str (local.get $b) (string "%!"))
+  (call $buf_rune (local.get $b) (i64.extend_i32_u (local.get $verb)))
+  (call $buf_byte (local.get $b) (i32.const 40))
+  (if (i32.or (i32.eqz (call $kind (local.get $t))) (i32.eq (call $kind (local.get $t)) (i32.const 12)))
+    (then (call $buf_str (local.get $b) (string "<nil>")))
+    (else
+      (call $fmt_type_name (local.get $b) (local.get $t) (local.get $x))
+      (call $buf_byte (local.get $b) (i32.const 61))
+      (call $format_verb (local.get $b) (i32.const 118) (call $spec_new) (local.get $t) (local.get $x))))
+  (call $buf_byte (local.get $b) (i32.const 41)))
+
+(func $buf_repeat (param $b i32) (param $c i32) (param $n i64)
+  (block $done
+    (loop $next
+      (br_if $done (i64.le_s (local.get $n) (i64.const 0)))
+      (call $buf_byte (local.get $b) (local.get $c))
+      (local.set $n (i64.sub (local.get $n) (i64.const 1)))
+      (br $next))))
+
+;; Pads `body` to the width: on the right for `-`, with zeros after the
+;; sign for `0` on numbers, else with spaces on the left.
+(func $pad (param $b i32) (param $body i64) (param $spec i32) (param $numeric i32)
+  (local $width i64) (local $fill i64) (local $sign i32)
+  (local.set $width (i64.load offset=8 (local.get $spec)))
+  (local.set $fill (i64.sub (local.get $width) (i64.extend_i32_u (call $rune_count (local.get $body)))))
+  (if (i32.or (i64.lt_s (local.get $width) (i64.const 0)) (i64.le_s (local.get $fill) (i64.const 0)))
+    (then
+      (call $buf_str (local.get $b) (local.get $body))
+      (return)))
+  (if (call $flag (local.get $spec) (i32.const 1))
+    (then
+      (call $buf_str (local.get $b) (local.get $body))
+      (call $buf_repeat (local.get $b) (i32.const 32) (local.get $fill))
+      (return)))
+  (if (i32.and (call $flag (local.get $spec) (i32.const 8)) (local.get $numeric))
+    (then
+      (if (call $str_len (local.get $body))
+        (then
+          (local.set $sign
+            (i32.or (i32.eq (i32.load8_u (call $str_ptr (local.get $body))) (i32.const 45))
+              (i32.eq (i32.load8_u (call $str_ptr (local.get $body))) (i32.const 43))))))
+      (call $buf_str (local.get $b) (call $substr (local.get $body) (i32.const 0) (local.get $sign)))
+      (call $buf_repeat (local.get $b) (i32.const 48) (local.get $fill))
+      (call $buf_str (local.get $b) (call $substr (local.get $body) (local.get $sign) (call $str_len (local.get $body))))
+      (return)))
+  (call $buf_repeat (local.get $b) (i32.const 32) (local.get $fill))
+  (call $buf_str (local.get $b) (local.get $body)))
+
+(func $quote_char (param $b i32) (param $c i32) (param $quote i32)
+  (local $escape i32)
+  (local.set $escape
+    (select (i32.const 97) (i32.const 0) (i32.eq (local.get $c) (i32.const 7))))
+  (if (i32.eq (local.get $c) (i32.const 8)) (then (local.set $escape (i32.const 98))))
+  (if (i32.eq (local.get $c) (i32.const 12)) (then (local.set $escape (i32.const 102))))
+  (if (i32.eq (local.get $c) (i32.const 10)) (then (local.set $escape (i32.const 110))))
+  (if (i32.eq (local.get $c) (i32.const 13)) (then (local.set $escape (i32.const 114))))
+  (if (i32.eq (local.get $c) (i32.const 9)) (then (local.set $escape (i32.const 116))))
+  (if (i32.eq (local.get $c) (i32.const 11)) (then (local.set $escape (i32.const 118))))
+  (if (i32.or (i32.eq (local.get $c) (i32.const 92)) (i32.eq (local.get $c) (local.get $quote)))
+    (then (local.set $escape (local.get $c))))
+  (if (local.get $escape)
+    (then
+      (call $buf_byte (local.get $b) (i32.const 92))
+      (call $buf_byte (local.get $b) (local.get $escape))
+      (return)))
+  (if (i32.or (i32.lt_s (local.get $c) (i32.const 0x20)) (i32.eq (local.get $c) (i32.const 0x7f)))
+    (then
+      (call $buf_str (local.get $b) (string "\\x"))
+      (call $buf_hex2 (local.get $b) (local.get $c) (i32.const 0))
+      (return)))
+  (if (i32.or (i32.and (i32.ge_s (local.get $c) (i32.const 0x80)) (i32.lt_s (local.get $c) (i32.const 0xa0)))
+        (i32.or (i32.eq (local.get $c) (i32.const 0xfffe)) (i32.eq (local.get $c) (i32.const 0xffff))))
+    (then
+      (call $buf_str (local.get $b) (string "\\u"))
+      (call $format_radix (local.get $b) (i64.extend_i32_u (local.get $c)) (i32.const 16) (i32.const 0) (i64.const 4))
+      (return)))
+  (call $buf_rune (local.get $b) (i64.extend_i32_u (local.get $c))))
+
+;; `s` as a double-quoted Go string literal.
+(func $buf_quote (param $b i32) (param $s i64)
+  (local $i i32) (local $r i32) (local $size i32)
+  (call $buf_byte (local.get $b) (i32.const 34))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $s))))
+      (call $decode_at (local.get $s) (local.get $i))
+      (local.set $size)
+      (local.set $r)
+      (if (i32.and (i32.eq (local.get $r) (i32.const 0xfffd)) (i32.eq (local.get $size) (i32.const 1)))
+        (then
+          (call $buf_str (local.get $b) (string "\\x"))
+          (call $buf_hex2 (local.get $b) (i32.load8_u (i32.add (call $str_ptr (local.get $s)) (local.get $i))) (i32.const 0)))
+        (else (call $quote_char (local.get $b) (local.get $r) (i32.const 34))))
+      (local.set $i (i32.add (local.get $i) (local.get $size)))
+      (br $next)))
+  (call $buf_byte (local.get $b) (i32.const 34)))
+
+(func $rune_of (param $i i64) (result i64)
+  (if (i32.or (i32.or (i64.lt_s (local.get $i) (i64.const 0)) (i64.gt_s (local.get $i) (i64.const 0x10ffff)))
+        (i32.and (i64.ge_s (local.get $i) (i64.const 0xd800)) (i64.lt_s (local.get $i) (i64.const 0xe000))))
+    (then (return (i64.const 0xfffd))))
+  (local.get $i))
+
+(func $upper (param $b i32) (param $from i32)
+  (local $p i32)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $from) (call $buf_len (local.get $b))))
+      (local.set $p (i32.add (i32.load (local.get $b)) (local.get $from)))
+      (if (i32.and (i32.ge_u (i32.load8_u (local.get $p)) (i32.const 97)) (i32.le_u (i32.load8_u (local.get $p)) (i32.const 122)))
+        (then (i32.store8 (local.get $p) (i32.sub (i32.load8_u (local.get $p)) (i32.const 32)))))
+      (local.set $from (i32.add (local.get $from) (i32.const 1)))
+      (br $next))))
+
+;; A string, or the bytes of a `[]byte`, by `verb`; the precision limits
+;; the runes formatted.
+(func $format_bytes (param $b i32) (param $verb i32) (param $spec i32) (param $s i64) (result i32)
+  (local $end i32) (local $k i64) (local $i i32) (local $n i32)
+  (local.set $n (call $str_len (local.get $s)))
+  (if (i32.and (i64.ge_s (i64.load offset=16 (local.get $spec)) (i64.const 0))
+        (i32.or (i32.or (i32.eq (local.get $verb) (i32.const 118)) (i32.eq (local.get $verb) (i32.const 115))) (i32.eq (local.get $verb) (i32.const 113))))
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.or (i64.ge_s (local.get $k) (i64.load offset=16 (local.get $spec))) (i32.ge_u (local.get $end) (local.get $n))))
+          (call $decode_at (local.get $s) (local.get $end))
+          (local.set $end (i32.add (local.get $end)))
+          (drop)
+          (local.set $k (i64.add (local.get $k) (i64.const 1)))
+          (br $next)))
+      (local.set $s (call $substr (local.get $s) (i32.const 0) (local.get $end)))
+      (local.set $n (local.get $end))))
+  (if (i32.or (i32.eq (local.get $verb) (i32.const 118)) (i32.eq (local.get $verb) (i32.const 115)))
+    (then
+      (call $buf_str (local.get $b) (local.get $s))
+      (return (i32.const 1))))
+  (if (i32.eq (local.get $verb) (i32.const 113))
+    (then
+      (call $buf_quote (local.get $b) (local.get $s))
+      (return (i32.const 1))))
+  (if (i32.or (i32.eq (local.get $verb) (i32.const 120)) (i32.eq (local.get $verb) (i32.const 88)))
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+          (if (i32.and (i32.ne (local.get $i) (i32.const 0)) (call $flag (local.get $spec) (i32.const 16)))
+            (then (call $buf_byte (local.get $b) (i32.const 32))))
+          (call $buf_hex2 (local.get $b) (i32.load8_u (i32.add (call $str_ptr (local.get $s)) (local.get $i)))
+            (i32.eq (local.get $verb) (i32.const 88)))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (return (i32.const 1))))
+  (i32.const 0))
+
+;; An integer by `verb`.
+(func $format_int (param $b i32) (param $verb i32) (param $spec i32) (param $i i64) (result i32)
+  (local $sign i64) (local $prefix i64) (local $base i32)
+  (global.set $numeric (i32.const 1))
+  (local.set $sign
+    (select (string "-")
+      (select (string "+") (select (string " ") (i64.const 0) (call $flag (local.get $spec) (i32.const 16)))
+        (call $flag (local.get $spec) (i32.const 2)))
+      (i64.lt_s (local.get $i) (i64.const 0))))
+  (block $radix
+    (if (i32.or (i32.eq (local.get $verb) (i32.const 118)) (i32.eq (local.get $verb) (i32.const 100)))
+      (then
+        (local.set $base (i32.const 10))
+        (br $radix)))
+    (if (i32.eq (local.get $verb) (i32.const 98))
+      (then
+        (local.set $base (i32.const 2))
+        (if (call $flag (local.get $spec) (i32.const 32))
+          (then (local.set $prefix (string "0b"))))
+        (br $radix)))
+    (if (i32.eq (local.get $verb) (i32.const 111))
+      (then
+        (local.set $base (i32.const 8))
+        (if (call $flag (local.get $spec) (i32.const 32))
+          (then (local.set $prefix (string "0"))))
+        (br $radix)))
+    (if (i32.eq (local.get $verb) (i32.const 79))
+      (then
+        (local.set $base (i32.const 8))
+        (local.set $prefix (string "0o"))
+        (br $radix)))
+    (if (i32.eq (local.get $verb) (i32.const 120))
+      (then
+        (local.set $base (i32.const 16))
+        (if (call $flag (local.get $spec) (i32.const 32))
+          (then (local.set $prefix (string "0x"))))
+        (br $radix)))
+    (if (i32.eq (local.get $verb) (i32.const 88))
+      (then
+        (local.set $base (i32.const 16))
+        (if (call $flag (local.get $spec) (i32.const 32))
+          (then (local.set $prefix (string "0X"))))
+        (br $radix)))
+    (global.set $numeric (i32.const 0))
+    (if (i32.eq (local.get $verb) (i32.const 99))
+      (then
+        (call $buf_rune (local.get $b) (call $rune_of (local.get $i)))
+        (return (i32.const 1))))
+    (if (i32.eq (local.get $verb) (i32.const 113))
+      (then
+        (call $buf_byte (local.get $b) (i32.const 39))
+        (call $quote_char (local.get $b) (i32.wrap_i64 (call $rune_of (local.get $i))) (i32.const 39))
+        (call $buf_byte (local.get $b) (i32.const 39))
+        (return (i32.const 1))))
+    (if (i32.eq (local.get $verb) (i32.const 85))
+      (then
+        (global.set $numeric (i32.const 1))
+        (call $buf_str (local.get $b) (string "U+"))
+        (call $format_radix (local.get $b) (local.get $i) (i32.const 16) (i32.const 1) (i64.const 4))
+        (return (i32.const 1))))
+    (return (i32.const 0)))
+  (call $buf_str (local.get $b) (local.get $sign))
+  (call $buf_str (local.get $b) (local.get $prefix))
+  (call $format_radix (local.get $b) (local.get $i) (local.get $base) (i32.eq (local.get $verb) (i32.const 88))
+    (i64.load offset=16 (local.get $spec)))
+  (i32.const 1))
+
+;; A float by `verb`.
+(func $format_float_verb (param $b i32) (param $verb i32) (param $spec i32) (param $f f64) (result i32)
+  (local $start i32) (local $prec i32) (local $fmt i32) (local $c i32) (local $body i64)
+  (local.set $start (call $buf_len (local.get $b)))
+  (local.set $prec (i32.wrap_i64 (i64.load offset=16 (local.get $spec))))
+  (block $chosen
+    (if (i32.or (i32.eq (local.get $verb) (i32.const 118)) (i32.or (i32.eq (local.get $verb) (i32.const 103)) (i32.eq (local.get $verb) (i32.const 71))))
+      (then
+        (local.set $fmt (i32.const 103))
+        (br $chosen)))
+    (if (i32.lt_s (local.get $prec) (i32.const 0))
+      (then (local.set $prec (i32.const 6))))
+    (if (i32.or (i32.eq (local.get $verb) (i32.const 102)) (i32.eq (local.get $verb) (i32.const 70)))
+      (then
+        (local.set $fmt (i32.const 102))
+        (br $chosen)))
+    (if (i32.or (i32.eq (local.get $verb) (i32.const 101)) (i32.eq (local.get $verb) (i32.const 69)))
+      (then
+        (local.set $fmt (i32.const 101))
+        (br $chosen)))
+    (return (i32.const 0)))
+  (local.set $body (call $format_float (local.get $f) (local.get $fmt) (local.get $prec)))
+  (local.set $c (i32.load8_u (call $str_ptr (local.get $body))))
+  (if (i32.and (i32.or (call $flag (local.get $spec) (i32.const 2)) (call $flag (local.get $spec) (i32.const 16)))
+        (i32.and (i32.ne (local.get $c) (i32.const 45)) (i32.ne (local.get $c) (i32.const 43))))
+    (then
+      (call $buf_byte (local.get $b) (select (i32.const 43) (i32.const 32) (call $flag (local.get $spec) (i32.const 2))))))
+  (call $buf_str (local.get $b) (local.get $body))
+  (if (i32.or (i32.eq (local.get $verb) (i32.const 71)) (i32.eq (local.get $verb) (i32.const 69)))
+    (then (call $upper (local.get $b) (local.get $start))))
+  (global.set $numeric (f64.eq (f64.sub (local.get $f) (local.get $f)) (f64.const 0)))
+  (i32.const 1))
+
+;; The bytes of a `[]byte` as a string.
+(func $slice_bytes (param $t i32) (param $x i64) (result i64)
+  (local $n i32) (local $p i32) (local $i i32)
+  (local.set $n (call $len_of (local.get $t) (local.get $x)))
+  (local.set $p (call $alloc (local.get $n)))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (i32.store8 (i32.add (local.get $p) (local.get $i))
+        (i32.wrap_i64 (i64.load offset=8 (call $slot (call $slice_data (local.get $x)) (local.get $i)))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (call $str (local.get $p) (local.get $n)))
+
+;; The operand formatted by `verb`; sets `$numeric` for a number the `0`
+;; flag pads with zeros. Returns 0 if the verb does not apply to its type.
+(func $format_value (param $b i32) (param $verb i32) (param $spec i32) (param $t i32) (param $x i64) (param $depth i32) (result i32)
+  (local $k i32) (local $msg i32) (local $i i32) (local $n i32) (local $list i32) (local $e i32) (local $target i32)
+  (local $body i32)
+  (global.set $numeric (i32.const 0))
+  (if (i32.or (i32.or (i32.eq (local.get $verb) (i32.const 118)) (i32.eq (local.get $verb) (i32.const 115)))
+        (i32.or (i32.eq (local.get $verb) (i32.const 113)) (i32.or (i32.eq (local.get $verb) (i32.const 120)) (i32.eq (local.get $verb) (i32.const 88)))))
+    (then
+      (local.set $msg (call $buf_new))
+      (if (call $error_message (local.get $msg) (local.get $t) (local.get $x))
+        (then (return (call $format_bytes (local.get $b) (local.get $verb) (local.get $spec) (call $buf_value (local.get $msg))))))))
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.and (i32.eq (local.get $k) (i32.const 12)) (i64.ne (local.get $x) (i64.const 0)))
+    (then
+      (return (call $format_value (local.get $b) (local.get $verb) (local.get $spec) (call $get (call $ptr (local.get $x))) (local.get $depth)))))
+  (if (i32.or (i32.eqz (local.get $k)) (i32.eq (local.get $k) (i32.const 12)))
+    (then
+      (if (i32.eq (local.get $verb) (i32.const 118))
+        (then
+          (call $buf_str (local.get $b) (string "<nil>"))
+          (return (i32.const 1))))
+      (return (i32.const 0))))
+  (if (i32.eq (local.get $k) (i32.const 1))
+    (then
+      (if (i32.or (i32.eq (local.get $verb) (i32.const 118)) (i32.eq (local.get $verb) (i32.const 116)))
+        (then
+          (call $buf_str (local.get $b) (select (string "true") (string "false") (i32.wrap_i64 (local.get $x))))
+          (return (i32.const 1))))
+      (return (i32.const 0))))
+  (if (i32.or (i32.eq (local.get $k) (i32.const 2)) (i32.eq (local.get $k) (i32.const 3)))
+    (then (return (call $format_int (local.get $b) (local.get $verb) (local.get $spec) (local.get $x)))))
+  (if (i32.eq (local.get $k) (i32.const 4))
+    (then (return (call $format_float_verb (local.get $b) (local.get $verb) (local.get $spec) (call $f64 (local.get $x))))))
+  (if (i32.eq (local.get $k) (i32.const 5))
+    (then (return (call $format_bytes (local.get $b) (local.get $verb) (local.get $spec) (local.get $x)))))
+  (if (i32.eq (local.get $k) (i32.const 7))
+    (then
+      (if (i32.and (i32.eq (call $kind (call $elem (local.get $t))) (i32.const 3))
+            (i32.or (i32.or (i32.eq (local.get $verb) (i32.const 115)) (i32.eq (local.get $verb) (i32.const 113)))
+              (i32.or (i32.eq (local.get $verb) (i32.const 120)) (i32.eq (local.get $verb) (i32.const 88)))))
+        (then
+          (return (call $format_bytes (local.get $b) (local.get $verb) (local.get $spec) (call $slice_bytes (local.get $t) (local.get $x))))))
+      (call $buf_byte (local.get $b) (i32.const 91))
+      (local.set $n (call $len_of (local.get $t) (local.get $x)))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+          (if (local.get $i)
+            (then (call $buf_byte (local.get $b) (i32.const 32))))
+          (call $element (local.get $b) (local.get $verb) (local.get $spec)
+            (call $get (call $slot (call $slice_data (local.get $x)) (local.get $i))) (local.get $depth))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (call $buf_byte (local.get $b) (i32.const 93))
+      (return (i32.const 1))))
+  (if (i32.eq (local.get $k) (i32.const 8))
+    (then
+      (call $sorted_entries (local.get $x))
+      (local.set $n)
+      (local.set $list)
+      (call $buf_str (local.get $b) (string "map["))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+          (if (local.get $i)
+            (then (call $buf_byte (local.get $b) (i32.const 32))))
+          (local.set $e (i32.load (i32.add (local.get $list) (i32.shl (local.get $i) (i32.const 2)))))
+          (call $element (local.get $b) (local.get $verb) (local.get $spec) (call $get (local.get $e)) (local.get $depth))
+          (call $buf_byte (local.get $b) (i32.const 58))
+          (call $element (local.get $b) (local.get $verb) (local.get $spec)
+            (call $get (i32.add (local.get $e) (i32.const 16))) (local.get $depth))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (call $buf_byte (local.get $b) (i32.const 93))
+      (return (i32.const 1))))
+  (if (i32.eq (local.get $k) (i32.const 11))
+    (then
+      (call $buf_byte (local.get $b) (i32.const 123))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $i) (call $nfields (local.get $t))))
+          (if (local.get $i)
+            (then (call $buf_byte (local.get $b) (i32.const 32))))
+          (if (call $flag (local.get $spec) (i32.const 4))
+            (then
+              (call $buf_str (local.get $b) (call $fname (local.get $t) (local.get $i)))
+              (call $buf_byte (local.get $b) (i32.const 58))))
+          (call $element (local.get $b) (local.get $verb) (local.get $spec)
+            (call $get (call $slot (call $ptr (local.get $x)) (local.get $i))) (local.get $depth))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (call $buf_byte (local.get $b) (i32.const 125))
+      (return (i32.const 1))))
+  (if (i32.eq (local.get $k) (i32.const 9))
+    (then
+      (local.set $target (call $ptr (local.get $x)))
+      (if (i32.and (i32.ne (local.get $target) (i32.const 0))
+            (i32.and (i32.eqz (local.get $depth)) (i32.eq (call $kind (i32.load (local.get $target))) (i32.const 11))))
+        (then
+          (local.set $body (call $buf_new))
+          (if (i32.eqz (call $format_value (local.get $body) (local.get $verb) (local.get $spec)
+                (call $get (local.get $target)) (i32.add (local.get $depth) (i32.const 1))))
+            (then (return (i32.const 0))))
+          (global.set $numeric (i32.const 0))
+          (call $buf_byte (local.get $b) (i32.const 38))
+          (call $buf_str (local.get $b) (call $buf_value (local.get $body)))
+          (return (i32.const 1))))
+      (if (i32.or (i32.eq (local.get $verb) (i32.const 118)) (i32.eq (local.get $verb) (i32.const 112)))
+        (then
+          (if (local.get $target)
+            (then (call $buf_address (local.get $b) (local.get $x)))
+            (else (call $buf_str (local.get $b) (select (string "<nil>") (string "0x0") (i32.eq (local.get $verb) (i32.const 118))))))
+          (return (i32.const 1))))
+      (return (i32.const 0))))
+  (if (i32.eq (local.get $verb) (i32.const 118))
+    (then
+      (call $buf_inspect (local.get $b) (local.get $t) (local.get $x) (i32.const 1) (i32.const 0))
+      (return (i32.const 1))))
+  (i32.const 0))
+
+;; An element of a slice, map or struct, formatted by the same verb; a bad
+;; verb shows in place of the element alone.
+(func $element (param $b i32) (param $verb i32) (param $spec i32) (param $t i32) (param $x i64) (param $depth i32)
+  (local $inner i32) (local $body i32)
+  (local.set $inner (call $alloc (i32.const 24)))
+  (memory.copy (local.get $inner) (local.get $spec) (i32.const 24))
+  (i64.store offset=8 (local.get $inner) (i64.const -1))
+  (local.set $body (call $buf_new))
+  (if (call $format_value (local.get $body) (local.get $verb) (local.get $inner) (local.get $t) (local.get $x)
+        (i32.add (local.get $depth) (i32.const 1)))
+    (then (call $pad (local.get $b) (call $buf_value (local.get $body)) (local.get $spec) (global.get $numeric)))
+    (else (call $bad_verb (local.get $b) (local.get $verb) (local.get $t) (local.get $x)))))
+
+;; One operand formatted by `verb`, then padded to the width.
+(func $format_verb (param $b i32) (param $verb i32) (param $spec i32) (param $t i32) (param $x i64)
+  (local $body i32)
+  (local.set $body (call $buf_new))
+  (if (i32.eq (local.get $verb) (i32.const 84))
+    (then
+      (call $fmt_type_name (local.get $body) (local.get $t) (local.get $x))
+      (call $pad (local.get $b) (call $buf_value (local.get $body)) (local.get $spec) (i32.const 0))
+      (return)))
+  (if (call $format_value (local.get $body) (local.get $verb) (local.get $spec) (local.get $t) (local.get $x) (i32.const 0))
+    (then (call $pad (local.get $b) (call $buf_value (local.get $body)) (local.get $spec) (global.get $numeric)))
+    (else (call $bad_verb (local.get $b) (local.get $verb) (local.get $t) (local.get $x)))))
+
+;; The operands as `Print` writes them: spaces between operands when
+;; neither is a string.
+(func $sprint (param $b i32) (param $argc i32) (param $argv i32)
+  (local $i i32) (local $prev i32) (local $is i32)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $argc)))
+      (call $dynamic (call $get (call $slot (local.get $argv) (local.get $i))))
+      (drop)
+      (local.set $is (i32.eq (call $kind) (i32.const 5)))
+      (if (i32.and (i32.gt_s (local.get $i) (i32.const 0)) (i32.and (i32.eqz (local.get $is)) (i32.eqz (local.get $prev))))
+        (then (call $buf_byte (local.get $b) (i32.const 32))))
+      (call $format_verb (local.get $b) (i32.const 118) (call $spec_new) (call $get (call $slot (local.get $argv) (local.get $i))))
+      (local.set $prev (local.get $is))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next))))
+
+(func $sprintln (param $b i32) (param $argc i32) (param $argv i32)
+  (local $i i32)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $argc)))
+      (if (local.get $i)
+        (then (call $buf_byte (local.get $b) (i32.const 32))))
+      (call $format_verb (local.get $b) (i32.const 118) (call $spec_new) (call $get (call $slot (local.get $argv) (local.get $i))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (call $buf_byte (local.get $b) (i32.const 10)))
+
+;; Where $fmt_number leaves off in the format, and the next operand.
+(global $fmt_i (mut i32) (i32.const 0))
+(global $fmt_next (mut i32) (i32.const 0))
+(global $fmt_out (mut i64) (i64.const 0))
+
+;; A width or precision: digits, or `*` for the next operand, left in
+;; `$fmt_out`, -1 when not given. Returns 0 for a bad `*` operand.
+(func $fmt_number (param $f i64) (param $argc i32) (param $argv i32) (result i32)
+  (local $n i64) (local $any i32) (local $overflow i32) (local $c i32) (local $t i32) (local $x i64)
+  (global.set $fmt_out (i64.const -1))
+  (if (i32.and (i32.lt_u (global.get $fmt_i) (call $str_len (local.get $f)))
+        (i32.eq (i32.load8_u (i32.add (call $str_ptr (local.get $f)) (global.get $fmt_i))) (i32.const 42)))
+    (then
+      (global.set $fmt_i (i32.add (global.get $fmt_i) (i32.const 1)))
+      (local.set $t (global.get $t_nil))
+      (if (i32.lt_s (global.get $fmt_next) (local.get $argc))
+        (then
+          (call $dynamic (call $get (call $slot (local.get $argv) (global.get $fmt_next))))
+          (local.set $x)
+          (local.set $t)))
+      (global.set $fmt_next (i32.add (global.get $fmt_next) (i32.const 1)))
+      (if (i32.and (i32.eq (call $kind (local.get $t)) (i32.const 2)) (i64.ge_s (local.get $x) (i64.const 0)))
+        (then
+          (global.set $fmt_out (local.get $x))
+          (return (i32.const 1))))
+      (return (i32.const 0))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (global.get $fmt_i) (call $str_len (local.get $f))))
+      (local.set $c (i32.load8_u (i32.add (call $str_ptr (local.get $f)) (global.get $fmt_i))))
+      (br_if $done (i32.or (i32.lt_u (local.get $c) (i32.const 48)) (i32.gt_u (local.get $c) (i32.const 57))))
+      (if (i64.gt_s (local.get $n) (i64.div_s (i64.sub (i64.const 0x7fffffffffffffff) (i64.const 9)) (i64.const 10)))
+        (then (local.set $overflow (i32.const 1))))
+      (local.set $n (i64.add (i64.mul (local.get $n) (i64.const 10)) (i64.extend_i32_u (i32.sub (local.get $c) (i32.const 48)))))
+      (local.set $any (i32.const 1))
+      (global.set $fmt_i (i32.add (global.get $fmt_i) (i32.const 1)))
+      (br $next)))
+  (if (i32.and (local.get $any) (i32.eqz (local.get $overflow)))
+    (then (global.set $fmt_out (local.get $n))))
+  (i32.const 1))
+
+;; The operand of the first `%w` $sprintf saw, when it looks for one.
+(global $wrapped_t (mut i32) (i32.const 0))
+(global $wrapped_x (mut i64) (i64.const 0))
+
+;; `format` with its verbs replaced by the formatted operands. Keeps the
+;; operand of the first `%w` when `wrap` is set, for Errorf.
+(func $sprintf (param $b i32) (param $f i64) (param $argc i32) (param $argv i32) (param $wrap i32)
+  (local $end i32) (local $n i32) (local $p i32) (local $c i32) (local $spec i32) (local $verb i32) (local $size i32)
+  (local $t i32) (local $x i64) (local $k i32)
+  (local.set $n (call $str_len (local.get $f)))
+  (local.set $p (call $str_ptr (local.get $f)))
+  (global.set $fmt_i (i32.const 0))
+  (global.set $fmt_next (i32.const 0))
+  (global.set $wrapped_t (global.get $t_nil))
+  (global.set $wrapped_x (i64.const 0))
+  (block $finished
+    (loop $verbs
+      (br_if $finished (i32.ge_u (global.get $fmt_i) (local.get $n)))
+      (if (i32.ne (i32.load8_u (i32.add (local.get $p) (global.get $fmt_i))) (i32.const 37))
+        (then
+          (local.set $end (global.get $fmt_i))
+          (block $done
+            (loop $next
+              (br_if $done (i32.ge_u (local.get $end) (local.get $n)))
+              (br_if $done (i32.eq (i32.load8_u (i32.add (local.get $p) (local.get $end))) (i32.const 37)))
+              (local.set $end (i32.add (local.get $end) (i32.const 1)))
+              (br $next)))
+          (call $buf_put (local.get $b) (i32.add (local.get $p) (global.get $fmt_i)) (i32.sub (local.get $end) (global.get $fmt_i)))
+          (global.set $fmt_i (local.get $end))
+          (br $verbs)))
+      (global.set $fmt_i (i32.add (global.get $fmt_i) (i32.const 1)))
+      (local.set $spec (call $spec_new))
+      (block $done
+        (loop $flags
+          (br_if $done (i32.ge_u (global.get $fmt_i) (local.get $n)))
+          (local.set $c (i32.load8_u (i32.add (local.get $p) (global.get $fmt_i))))
+          (local.set $k (i32.const 0))
+          (if (i32.eq (local.get $c) (i32.const 45)) (then (local.set $k (i32.const 1))))
+          (if (i32.eq (local.get $c) (i32.const 43)) (then (local.set $k (i32.const 2))))
+          (if (i32.eq (local.get $c) (i32.const 48)) (then (local.set $k (i32.const 8))))
+          (if (i32.eq (local.get $c) (i32.const 32)) (then (local.set $k (i32.const 16))))
+          (if (i32.eq (local.get $c) (i32.const 35)) (then (local.set $k (i32.const 32))))
+          (br_if $done (i32.eqz (local.get $k)))
+          (i32.store (local.get $spec) (i32.or (i32.load (local.get $spec)) (local.get $k)))
+          (global.set $fmt_i (i32.add (global.get $fmt_i) (i32.const 1)))
+          (br $flags)))
+      (if (i32.eqz (call $fmt_number (local.get $f) (local.get $argc) (local.get $argv)))
+        (then (call $buf_str (local.get $b) (string "%!(BADWIDTH)"))))
+      (i64.store offset=8 (local.get $spec) (global.get $fmt_out))
+      (if (i32.and (i32.lt_u (global.get $fmt_i) (local.get $n))
+            (i32.eq (i32.load8_u (i32.add (local.get $p) (global.get $fmt_i))) (i32.const 46)))
+        (then
+          (global.set $fmt_i (i32.add (global.get $fmt_i) (i32.const 1)))
+          (if (call $fmt_number (local.get $f) (local.get $argc) (local.get $argv))
+            (then
+              (i64.store offset=16 (local.get $spec)
+                (select (i64.const 0) (global.get $fmt_out) (i64.lt_s (global.get $fmt_out) (i64.const 0)))))
+            (else (call $buf_str (local.get $b) (string "%!(BADPREC)"))))))
+      (if (i32.ge_u (global.get $fmt_i) (local.get $n))
+        (then
+          (call $buf_str (local.get $b) (string "%!(NOVERB)"))
+          (br $finished)))
+      (call $decode_at (local.get $f) (global.get $fmt_i))
+      (local.set $size)
+      (local.set $verb)
+      (global.set $fmt_i (i32.add (global.get $fmt_i) (local.get $size)))
+      (if (i32.eq (local.get $verb) (i32.const 37))
+        (then
+          (call $buf_byte (local.get $b) (i32.const 37))
+          (br $verbs)))
+      (if (i32.ge_s (global.get $fmt_next) (local.get $argc))
+        (then
+          (call $buf_str (local.get $b) (string "%!"))
+          (call $buf_rune (local.get $b) (i64.extend_i32_u (local.get $verb)))
+          (call $buf_str (local.get $b) (string "(MISSING)"))
+          (br $verbs)))
+      (call $get (call $slot (local.get $argv) (global.get $fmt_next)))
+      (local.set $x)
+      (local.set $t)
+      (global.set $fmt_next (i32.add (global.get $fmt_next) (i32.const 1)))
+      (if (i32.or (i32.eq (local.get $verb) (i32.const 118)) (i32.eq (local.get $verb) (i32.const 119)))
+        (then
+          ;; `+` on %v names the fields instead
+          (if (call $flag (local.get $spec) (i32.const 2))
+            (then (i32.store (local.get $spec) (i32.xor (i32.load (local.get $spec)) (i32.const 6)))))))
+      (if (i32.eq (local.get $verb) (i32.const 119))
+        (then
+          (if (i32.and (local.get $wrap) (call $error_message (call $buf_new) (local.get $t) (local.get $x)))
+            (then
+              (if (i32.eqz (call $kind (global.get $wrapped_t)))
+                (then
+                  (global.set $wrapped_t (local.get $t))
+                  (global.set $wrapped_x (local.get $x))))
+              (call $format_verb (local.get $b) (i32.const 118) (local.get $spec) (local.get $t) (local.get $x)))
+            (else (call $bad_verb (local.get $b) (i32.const 119) (local.get $t) (local.get $x))))
+          (br $verbs)))
+      (call $format_verb (local.get $b) (local.get $verb) (local.get $spec) (local.get $t) (local.get $x))
+      (br $verbs)))
+  (if (i32.lt_s (global.get $fmt_next) (local.get $argc))
+    (then
+      (call $buf_str (local.get $b) (string "%!(EXTRA "))
+      (local.set $k (global.get $fmt_next))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_s (local.get $k) (local.get $argc)))
+          (if (i32.gt_s (local.get $k) (global.get $fmt_next))
+            (then (call $buf_str (local.get $b) (string ", "))))
+          (call $fmt_type_name (local.get $b) (call $get (call $slot (local.get $argv) (local.get $k))))
+          (call $buf_byte (local.get $b) (i32.const 61))
+          (call $format_verb (local.get $b) (i32.const 118) (call $spec_new) (call $get (call $slot (local.get $argv) (local.get $k))))
+          (local.set $k (i32.add (local.get $k) (i32.const 1)))
+          (br $next)))
+      (call $buf_byte (local.get $b) (i32.const 41)))))
+
+;; The arguments of package functions
+
+(func $package_args (param $name i64) (param $argc i32) (param $n i32) (result i32)
+  (if (i32.lt_s (local.get $argc) (local.get $n))
+    (then
+      (call $error (call $concat (string "not enough arguments in call to ") (local.get $name)))
+      (return (i32.const 0))))
+  (if (i32.gt_s (local.get $argc) (local.get $n))
+    (then
+      (call $error (call $concat (string "too many arguments in call to ") (local.get $name)))
+      (return (i32.const 0))))
+  (i32.const 1))
+
+(func $arg (param $argv i32) (param $i i32) (result i32 i64)
+  (call $get (call $slot (local.get $argv) (local.get $i))))
+
+(func $bad_arg (param $name i64) (param $want i64) (param $t i32) (param $x i64)
+  (call $error
+    (call $cat5 (string "cannot use ") (call $inspect (local.get $t) (local.get $x))
+      (call $cat3 (string " (type ") (call $tname (local.get $t)) (string ") as "))
+      (local.get $want)
+      (call $concat (string " value in argument to ") (local.get $name)))))
+
+(func $string_arg (param $name i64) (param $t i32) (param $x i64) (result i64)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 5))
+    (then
+      (call $bad_arg (local.get $name) (string "string") (local.get $t) (local.get $x))
+      (return (i64.const 0))))
+  (local.get $x))
+
+(func $int_arg (param $name i64) (param $t i32) (param $x i64) (result i64)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 2))
+    (then
+      (call $bad_arg (local.get $name) (string "int") (local.get $t) (local.get $x))
+      (return (i64.const 0))))
+  (local.get $x))
+
+(func $float_arg (param $name i64) (param $t i32) (param $x i64) (result f64)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.eqz (call $is_number (local.get $t)))
+    (then
+      (call $bad_arg (local.get $name) (string "float64") (local.get $t) (local.get $x))
+      (return (f64.const 0))))
+  (call $number (local.get $t) (local.get $x)))
+
+;; The format a Printf-like function takes first.
+(func $format_arg (param $name i64) (param $argc i32) (param $argv i32) (result i64)
+  (if (i32.eqz (local.get $argc))
+    (then
+      (call $error (call $concat (string "not enough arguments in call to ") (local.get $name)))
+      (return (i64.const 0))))
+  (call $string_arg (local.get $name) (call $arg (local.get $argv) (i32.const 0))))
+
+;; Package fmt
+
+(func $p_fmt_Print (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $b i32)
+  (local.set $b (call $buf_new))
+  (call $sprint (local.get $b) (local.get $argc) (local.get $argv))
+  (call $write_str (i32.const 1) (call $buf_value (local.get $b)))
+  (call $nil))
+
+(func $p_fmt_Println (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $b i32)
+  (local.set $b (call $buf_new))
+  (call $sprintln (local.get $b) (local.get $argc) (local.get $argv))
+  (call $write_str (i32.const 1) (call $buf_value (local.get $b)))
+  (call $nil))
+
+(func $p_fmt_Printf (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $f i64) (local $b i32)
+  (local.set $f (call $format_arg (string "fmt.Printf") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $b (call $buf_new))
+  (call $sprintf (local.get $b) (local.get $f) (i32.sub (local.get $argc) (i32.const 1)) (i32.add (local.get $argv) (i32.const 16)) (i32.const 0))
+  (call $write_str (i32.const 1) (call $buf_value (local.get $b)))
+  (call $nil))
+
+(func $p_fmt_Sprint (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $b i32)
+  (local.set $b (call $buf_new))
+  (call $sprint (local.get $b) (local.get $argc) (local.get $argv))
+  (call $string (call $buf_value (local.get $b))))
+
+(func $p_fmt_Sprintln (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $b i32)
+  (local.set $b (call $buf_new))
+  (call $sprintln (local.get $b) (local.get $argc) (local.get $argv))
+  (call $string (call $buf_value (local.get $b))))
+
+(func $p_fmt_Sprintf (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $f i64) (local $b i32)
+  (local.set $f (call $format_arg (string "fmt.Sprintf") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $b (call $buf_new))
+  (call $sprintf (local.get $b) (local.get $f) (i32.sub (local.get $argc) (i32.const 1)) (i32.add (local.get $argv) (i32.const 16)) (i32.const 0))
+  (call $string (call $buf_value (local.get $b))))
+
+;; The descriptor of the value if it is an `*os.File`, else -1.
+(func $descriptor (param $t i32) (param $x i64) (result i64)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.or (i32.ne (call $kind (local.get $t)) (i32.const 9)) (i64.eqz (local.get $x)))
+    (then (return (i64.const -1))))
+  (if (i32.ne (i32.load (call $ptr (local.get $x))) (global.get $t_File))
+    (then (return (i64.const -1))))
+  (i64.load offset=8 (call $ptr (i64.load offset=8 (call $ptr (local.get $x))))))
+
+;; The stream the first argument of an Fprint-like function writes to.
+(func $file_arg (param $name i64) (param $argc i32) (param $argv i32) (result i32)
+  (local $fd i64)
+  (if (i32.eqz (local.get $argc))
+    (then
+      (call $error (call $concat (string "not enough arguments in call to ") (local.get $name)))
+      (return (i32.const 0))))
+  (local.set $fd (call $descriptor (call $arg (local.get $argv) (i32.const 0))))
+  (if (i64.lt_s (local.get $fd) (i64.const 0))
+    (then
+      (call $bad_arg (local.get $name) (string "io.Writer") (call $arg (local.get $argv) (i32.const 0)))
+      (return (i32.const 0))))
+  (select (i32.const 1) (i32.const 2) (i64.eq (local.get $fd) (i64.const 1))))
+
+(func $p_fmt_Fprint (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $fd i32) (local $b i32)
+  (local.set $fd (call $file_arg (string "fmt.Fprint") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $b (call $buf_new))
+  (call $sprint (local.get $b) (i32.sub (local.get $argc) (i32.const 1)) (i32.add (local.get $argv) (i32.const 16)))
+  (call $write_str (local.get $fd) (call $buf_value (local.get $b)))
+  (call $nil))
+
+(func $p_fmt_Fprintln (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $fd i32) (local $b i32)
+  (local.set $fd (call $file_arg (string "fmt.Fprintln") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $b (call $buf_new))
+  (call $sprintln (local.get $b) (i32.sub (local.get $argc) (i32.const 1)) (i32.add (local.get $argv) (i32.const 16)))
+  (call $write_str (local.get $fd) (call $buf_value (local.get $b)))
+  (call $nil))
+
+(func $p_fmt_Fprintf (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $fd i32) (local $f i64) (local $b i32)
+  (if (i32.eqz (local.get $argc))
+    (then
+      (call $error (string "not enough arguments in call to fmt.Fprintf"))
+      (return (call $nil))))
+  (local.set $f
+    (call $format_arg (string "fmt.Fprintf") (i32.sub (local.get $argc) (i32.const 1)) (i32.add (local.get $argv) (i32.const 16))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $b (call $buf_new))
+  (call $sprintf (local.get $b) (local.get $f) (i32.sub (local.get $argc) (i32.const 2)) (i32.add (local.get $argv) (i32.const 32)) (i32.const 0))
+  (local.set $fd (call $file_arg (string "fmt.Fprintf") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $write_str (local.get $fd) (call $buf_value (local.get $b)))
+  (call $nil))
+
+;; `fmt.Errorf(format, args...)`: an error with the formatted message,
+;; wrapping the operand of a `%w`.
+(func $p_fmt_Errorf (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $f i64) (local $b i32) (local $msg i64) (local $fields i32)
+  (local.set $f (call $format_arg (string "fmt.Errorf") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $b (call $buf_new))
+  (call $sprintf (local.get $b) (local.get $f) (i32.sub (local.get $argc) (i32.const 1)) (i32.add (local.get $argv) (i32.const 16)) (i32.const 1))
+  (local.set $msg (call $lossy (call $buf_value (local.get $b))))
+  (if (i32.eqz (call $kind (global.get $wrapped_t)))
+    (then (return (call $new_error (local.get $msg)))))
+  (local.set $fields (call $alloc (i32.const 32)))
+  (call $set (local.get $fields) (call $string (local.get $msg)))
+  (call $set (i32.add (local.get $fields) (i32.const 16))
+    (call $convert (global.get $wrapped_t) (global.get $wrapped_x) (global.get $t_error)))
+  (call $make_error (global.get $t_wrapError) (i32.const 2) (local.get $fields)))
+
+;; Package errors
+
+(func $p_errors_New (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $msg i64)
+  (if (i32.eqz (call $package_args (string "errors.New") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (local.set $msg (call $string_arg (string "errors.New") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $new_error (call $lossy (local.get $msg))))
+
+;; `errors.Is(err, target)`: whether any error in the chain `err` starts
+;; is `target`.
+(func $p_errors_Is (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $t i32) (local $x i64)
+  (if (i32.eqz (call $package_args (string "errors.Is") (local.get $argc) (i32.const 2)))
+    (then (return (call $nil))))
+  (call $arg (local.get $argv) (i32.const 0))
+  (local.set $x)
+  (local.set $t)
+  (loop $next
+    (if (call $equals (local.get $t) (local.get $x) (call $arg (local.get $argv) (i32.const 1)))
+      (then (return (call $bool (i32.const 1)))))
+    (if (global.get $throwing)
+      (then (return (call $nil))))
+    (call $error_unwrap (local.get $t) (local.get $x))
+    (local.set $x)
+    (local.set $t)
+    (br_if $next (call $kind (local.get $t))))
+  (call $bool (i32.const 0)))
+
+;; `errors.As(err, &target)`: stores the first error in the chain whose
+;; type is that of `target` into it.
+(func $p_errors_As (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $tt i32) (local $target i64) (local $t i32) (local $x i64) (local $dt i32) (local $d i64)
+  (if (i32.eqz (call $package_args (string "errors.As") (local.get $argc) (i32.const 2)))
+    (then (return (call $nil))))
+  (call $raw (call $arg (local.get $argv) (i32.const 1)))
+  (local.set $target)
+  (local.set $tt)
+  (if (i32.or (i32.ne (call $kind (local.get $tt)) (i32.const 9)) (i64.eqz (local.get $target)))
+    (then
+      (call $error (string "errors: target must be a non-nil pointer"))
+      (return (call $nil))))
+  (call $arg (local.get $argv) (i32.const 0))
+  (local.set $x)
+  (local.set $t)
+  (loop $next
+    (call $raw (local.get $t) (local.get $x))
+    (local.set $d)
+    (local.set $dt)
+    (if (i32.or (i32.eq (call $kind (call $elem (local.get $tt))) (i32.const 12)) (call $identical (local.get $dt) (call $elem (local.get $tt))))
+      (then
+        (call $store (local.get $tt) (local.get $target) (local.get $dt) (local.get $d))
+        (return (call $bool (i32.const 1)))))
+    (call $error_unwrap (local.get $t) (local.get $x))
+    (local.set $x)
+    (local.set $t)
+    (br_if $next (call $kind (local.get $t))))
+  (call $bool (i32.const 0)))
+
+(func $p_errors_Unwrap (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $t i32) (local $x i64)
+  (if (i32.eqz (call $package_args (string "errors.Unwrap") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (call $error_unwrap (call $arg (local.get $argv) (i32.const 0)))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.eqz (call $kind (local.get $t)))
+    (then (return (call $zero (global.get $t_error)))))
+  (local.get $t)
+  (local.get $x))
+
+;; Package strconv
+
+(global $err_syntax_t (mut i32) (i32.const 0))
+(global $err_syntax_x (mut i64) (i64.const 0))
+(global $err_range_t (mut i32) (i32.const 0))
+(global $err_range_x (mut i64) (i64.const 0))
+
+(func $v_strconv_ErrSyntax (result i32 i64)
+  (if (i32.eqz (global.get $err_syntax_t))
+    (then
+      (call $new_error (string "invalid syntax"))
+      (global.set $err_syntax_x)
+      (global.set $err_syntax_t)))
+  (global.get $err_syntax_t)
+  (global.get $err_syntax_x))
+
+(func $v_strconv_ErrRange (result i32 i64)
+  (if (i32.eqz (global.get $err_range_t))
+    (then
+      (call $new_error (string "value out of range"))
+      (global.set $err_range_x)
+      (global.set $err_range_t)))
+  (global.get $err_range_t)
+  (global.get $err_range_x))
+
+(func $num_error (param $func i64) (param $num i64) (param $et i32) (param $e i64) (result i32 i64)
+  (local $fields i32)
+  (local.set $fields (call $alloc (i32.const 48)))
+  (call $set (local.get $fields) (call $string (local.get $func)))
+  (call $set (i32.add (local.get $fields) (i32.const 16)) (call $string (local.get $num)))
+  (call $set (i32.add (local.get $fields) (i32.const 32)) (local.get $et) (local.get $e))
+  (call $make_error (global.get $t_NumError) (i32.const 3) (local.get $fields)))
+
+(func $digit_value (param $c i32) (result i32)
+  (if (i32.and (i32.ge_u (local.get $c) (i32.const 48)) (i32.le_u (local.get $c) (i32.const 57)))
+    (then (return (i32.sub (local.get $c) (i32.const 48)))))
+  (if (i32.and (i32.ge_u (local.get $c) (i32.const 97)) (i32.le_u (local.get $c) (i32.const 122)))
+    (then (return (i32.sub (local.get $c) (i32.const 87)))))
+  (if (i32.and (i32.ge_u (local.get $c) (i32.const 65)) (i32.le_u (local.get $c) (i32.const 90)))
+    (then (return (i32.sub (local.get $c) (i32.const 55)))))
+  (i32.const 99))
+
+;; What $parse_integer parsed.
+(global $parsed (mut i64) (i64.const 0))
+
+;; Parses an integer in `base`, or in the base its prefix gives when
+;; `base` is 0, as Go's ParseInt does, into `$parsed`. Returns 0 if it
+;; succeeds, 1 for a syntax error and 2 for one of range.
+(func $parse_integer (param $str i64) (param $base i32) (result i32)
+  (local $s i32) (local $n i32) (local $i i32) (local $negative i32) (local $underscores i32)
+  (local $magnitude i64) (local $digits i32) (local $overflow i32) (local $d i32) (local $c i32)
+  (local.set $s (call $str_ptr (local.get $str)))
+  (local.set $n (call $str_len (local.get $str)))
+  (if (local.get $n)
+    (then
+      (local.set $c (i32.load8_u (local.get $s)))
+      (if (i32.or (i32.eq (local.get $c) (i32.const 45)) (i32.eq (local.get $c) (i32.const 43)))
+        (then
+          (local.set $negative (i32.eq (local.get $c) (i32.const 45)))
+          (local.set $i (i32.const 1))))))
+  (if (i32.eqz (local.get $base))
+    (then
+      (local.set $underscores (i32.const 1))
+      (local.set $base (i32.const 10))
+      (if (i32.and (i32.ge_s (i32.sub (local.get $n) (local.get $i)) (i32.const 2))
+            (i32.eq (i32.load8_u (i32.add (local.get $s) (local.get $i))) (i32.const 48)))
+        (then
+          (local.set $c (i32.or (i32.load8_u (i32.add (i32.add (local.get $s) (local.get $i)) (i32.const 1))) (i32.const 0x20)))
+          (if (i32.eq (local.get $c) (i32.const 120))
+            (then
+              (local.set $base (i32.const 16))
+              (local.set $i (i32.add (local.get $i) (i32.const 2))))
+            (else
+              (if (i32.eq (local.get $c) (i32.const 98))
+                (then
+                  (local.set $base (i32.const 2))
+                  (local.set $i (i32.add (local.get $i) (i32.const 2))))
+                (else
+                  (if (i32.eq (local.get $c) (i32.const 111))
+                    (then
+                      (local.set $base (i32.const 8))
+                      (local.set $i (i32.add (local.get $i) (i32.const 2))))
+                    (else
+                      (local.set $base (i32.const 8))
+                      (local.set $i (i32.add (local.get $i) (i32.const 1)))))))))))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (local.set $c (i32.load8_u (i32.add (local.get $s) (local.get $i))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br_if $next (i32.and (local.get $underscores) (i32.eq (local.get $c) (i32.const 95))))
+      (local.set $d (call $digit_value (local.get $c)))
+      (if (i32.ge_s (local.get $d) (local.get $base))
+        (then (return (i32.const 1))))
+      (local.set $digits (i32.add (local.get $digits) (i32.const 1)))
+      (if (i64.gt_u (local.get $magnitude)
+            (i64.div_u (i64.sub (i64.const -1) (i64.extend_i32_u (local.get $d))) (i64.extend_i32_u (local.get $base))))
+        (then (local.set $overflow (i32.const 1))))
+      (local.set $magnitude
+        (i64.add (i64.mul (local.get $magnitude) (i64.extend_i32_u (local.get $base))) (i64.extend_i32_u (local.get $d))))
+      (br $next)))
+  (if (i32.eqz (local.get $digits))
+    (then (return (i32.const 1))))
+  (if (i32.or (local.get $overflow)
+        (i64.gt_u (local.get $magnitude)
+          (select (i64.const 0x8000000000000000) (i64.const 0x7fffffffffffffff) (local.get $negative))))
+    (then (return (i32.const 2))))
+  (global.set $parsed
+    (select (i64.sub (i64.const 0) (local.get $magnitude)) (local.get $magnitude) (local.get $negative)))
+  (i32.const 0))
+
+;; The error $parse_integer's result stands for.
+(func $parse_error (param $code i32) (result i32 i64)
+  (if (i32.eq (local.get $code) (i32.const 2))
+    (then (return (call $v_strconv_ErrRange))))
+  (call $v_strconv_ErrSyntax))
+
+(func $p_strconv_Itoa (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $i i64)
+  (if (i32.eqz (call $package_args (string "strconv.Itoa") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (local.set $i (call $int_arg (string "strconv.Itoa") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $string (call $itoa (local.get $i))))
+
+;; `strconv.Atoi(s)`: the int `s` holds in base 10, and a
+;; `*strconv.NumError` if it holds none.
+(func $p_strconv_Atoi (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $code i32) (local $n i64)
+  (if (i32.eqz (call $package_args (string "strconv.Atoi") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (local.set $s (call $string_arg (string "strconv.Atoi") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $code (call $parse_integer (local.get $s) (i32.const 10)))
+  (if (i32.eqz (local.get $code))
+    (then (return (call $tuple2 (call $int (global.get $parsed)) (call $zero (global.get $t_error))))))
+  (if (i32.eq (local.get $code) (i32.const 2))
+    (then
+      (local.set $n
+        (select (i64.const 0x8000000000000000) (i64.const 0x7fffffffffffffff)
+          (i32.and (i32.ne (call $str_len (local.get $s)) (i32.const 0))
+            (i32.eq (i32.load8_u (call $str_ptr (local.get $s))) (i32.const 45)))))))
+  (call $tuple2 (call $int (local.get $n))
+    (call $num_error (string "Atoi") (local.get $s) (call $parse_error (local.get $code)))))
+
+(func $p_strconv_ParseInt (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $base i64) (local $bits i64) (local $code i32) (local $max i64)
+  (if (i32.eqz (call $package_args (string "strconv.ParseInt") (local.get $argc) (i32.const 3)))
+    (then (return (call $nil))))
+  (local.set $s (call $string_arg (string "strconv.ParseInt") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $base (call $int_arg (string "strconv.ParseInt") (call $arg (local.get $argv) (i32.const 1))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $bits (call $int_arg (string "strconv.ParseInt") (call $arg (local.get $argv) (i32.const 2))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (i32.and (i64.ne (local.get $base) (i64.const 0))
+        (i32.or (i64.lt_s (local.get $base) (i64.const 2)) (i64.gt_s (local.get $base) (i64.const 36))))
+    (then
+      (return
+        (call $tuple2 (call $int (i64.const 0))
+          (call $num_error (string "ParseInt") (local.get $s)
+            (call $new_error (call $concat (string "invalid base ") (call $itoa (local.get $base)))))))))
+  (if (i64.eqz (local.get $bits))
+    (then (local.set $bits (i64.const 64))))
+  (if (i64.lt_s (local.get $bits) (i64.const 1))
+    (then (local.set $bits (i64.const 1))))
+  (if (i64.gt_s (local.get $bits) (i64.const 64))
+    (then (local.set $bits (i64.const 64))))
+  (local.set $code (call $parse_integer (local.get $s) (i32.wrap_i64 (local.get $base))))
+  (if (i32.and (i32.eqz (local.get $code)) (i64.lt_s (local.get $bits) (i64.const 64)))
+    (then
+      (local.set $max (i64.sub (i64.shl (i64.const 1) (i64.sub (local.get $bits) (i64.const 1))) (i64.const 1)))
+      (if (i32.or (i64.gt_s (global.get $parsed) (local.get $max))
+            (i64.lt_s (global.get $parsed) (i64.sub (i64.sub (i64.const 0) (local.get $max)) (i64.const 1))))
+        (then (local.set $code (i32.const 2))))))
+  (if (i32.eqz (local.get $code))
+    (then (return (call $tuple2 (call $int (global.get $parsed)) (call $zero (global.get $t_error))))))
+  (call $tuple2 (call $int (i64.const 0))
+    (call $num_error (string "ParseInt") (local.get $s) (call $parse_error (local.get $code)))))
+
+(func $is_digit (param $c i32) (result i32)
+  (i32.and (i32.ge_u (local.get $c) (i32.const 48)) (i32.le_u (local.get $c) (i32.const 57))))
+
+;; The byte at `i` of the string, or 0 past its end.
+(func $byte_at (param $s i64) (param $i i32) (result i32)
+  (if (i32.ge_u (local.get $i) (call $str_len (local.get $s)))
+    (then (return (i32.const 0))))
+  (i32.load8_u (i32.add (call $str_ptr (local.get $s)) (local.get $i))))
+
+;; Whether `s` is a decimal float as Rust's parser reads it.
+(func $float_syntax (param $s i64) (result i32)
+  (local $i i32) (local $digits i32) (local $c i32)
+  (local.set $c (call $byte_at (local.get $s) (i32.const 0)))
+  (if (i32.or (i32.eq (local.get $c) (i32.const 43)) (i32.eq (local.get $c) (i32.const 45)))
+    (then (local.set $i (i32.const 1))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.eqz (call $is_digit (call $byte_at (local.get $s) (local.get $i)))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (local.set $digits (i32.add (local.get $digits) (i32.const 1)))
+      (br $next)))
+  (if (i32.eq (call $byte_at (local.get $s) (local.get $i)) (i32.const 46))
+    (then
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (block $done
+        (loop $next
+          (br_if $done (i32.eqz (call $is_digit (call $byte_at (local.get $s) (local.get $i)))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (local.set $digits (i32.add (local.get $digits) (i32.const 1)))
+          (br $next)))))
+  (if (i32.eqz (local.get $digits))
+    (then (return (i32.const 0))))
+  (local.set $c (call $byte_at (local.get $s) (local.get $i)))
+  (if (i32.or (i32.eq (local.get $c) (i32.const 101)) (i32.eq (local.get $c) (i32.const 69)))
+    (then
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (local.set $c (call $byte_at (local.get $s) (local.get $i)))
+      (if (i32.or (i32.eq (local.get $c) (i32.const 43)) (i32.eq (local.get $c) (i32.const 45)))
+        (then (local.set $i (i32.add (local.get $i) (i32.const 1)))))
+      (if (i32.eqz (call $is_digit (call $byte_at (local.get $s) (local.get $i))))
+        (then (return (i32.const 0))))
+      (block $done
+        (loop $next
+          (br_if $done (i32.eqz (call $is_digit (call $byte_at (local.get $s) (local.get $i)))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))))
+  (i32.eq (local.get $i) (call $str_len (local.get $s))))
+
+;; Whether the string is `word`, in ASCII letters of either case.
+(func $ascii_fold (param $s i64) (param $word i64) (result i32)
+  (local $i i32)
+  (if (i32.ne (call $str_len (local.get $s)) (call $str_len (local.get $word)))
+    (then (return (i32.const 0))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $s))))
+      (if (i32.ne (i32.or (call $byte_at (local.get $s) (local.get $i)) (i32.const 0x20)) (call $byte_at (local.get $word) (local.get $i)))
+        (then (return (i32.const 0))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (i32.const 1))
+
+;; `strconv.ParseFloat(s, bitSize)`. A value too large is ±Inf with a
+;; range error.
+(func $p_strconv_ParseFloat (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $b i32) (local $i i32) (local $c i32) (local $text i64) (local $u i64) (local $sign f64) (local $f f64)
+  (if (i32.eqz (call $package_args (string "strconv.ParseFloat") (local.get $argc) (i32.const 2)))
+    (then (return (call $nil))))
+  (local.set $s (call $string_arg (string "strconv.ParseFloat") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (drop (call $int_arg (string "strconv.ParseFloat") (call $arg (local.get $argv) (i32.const 1))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $b (call $buf_new))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $s))))
+      (local.set $c (call $byte_at (local.get $s) (local.get $i)))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br_if $next (i32.eq (local.get $c) (i32.const 95)))
+      (call $buf_byte (local.get $b) (select (local.get $c) (i32.const 63) (local.get $c)))
+      (br $next)))
+  (local.set $text (call $buf_value (local.get $b)))
+  (local.set $u (local.get $text))
+  (local.set $sign (f64.const 1))
+  (local.set $c (call $byte_at (local.get $text) (i32.const 0)))
+  (if (i32.or (i32.eq (local.get $c) (i32.const 43)) (i32.eq (local.get $c) (i32.const 45)))
+    (then
+      (if (i32.eq (local.get $c) (i32.const 45))
+        (then (local.set $sign (f64.const -1))))
+      (local.set $u (call $substr (local.get $text) (i32.const 1) (call $str_len (local.get $text))))))
+  (if (i32.or (call $ascii_fold (local.get $u) (string "inf")) (call $ascii_fold (local.get $u) (string "infinity")))
+    (then
+      (return (call $tuple2 (call $float (f64.mul (local.get $sign) (f64.const inf))) (call $zero (global.get $t_error))))))
+  (if (call $ascii_fold (local.get $u) (string "nan"))
+    (then (return (call $tuple2 (call $float (f64.const nan)) (call $zero (global.get $t_error))))))
+  (if (i32.eqz (call $float_syntax (local.get $text)))
+    (then
+      (return
+        (call $tuple2 (call $float (f64.const 0))
+          (call $num_error (string "ParseFloat") (local.get $s) (call $v_strconv_ErrSyntax))))))
+  (local.set $f (call $host_parse_float (call $str_ptr (local.get $text)) (call $str_len (local.get $text))))
+  (if (f64.eq (f64.abs (local.get $f)) (f64.const inf))
+    (then
+      (return
+        (call $tuple2 (call $float (local.get $f))
+          (call $num_error (string "ParseFloat") (local.get $s) (call $v_strconv_ErrRange))))))
+  (call $tuple2 (call $float (local.get $f)) (call $zero (global.get $t_error))))
+
+(func $p_strconv_ParseBool (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64)
+  (if (i32.eqz (call $package_args (string "strconv.ParseBool") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (local.set $s (call $string_arg (string "strconv.ParseBool") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (i32.or
+        (i32.or (i32.or (call $str_eq (local.get $s) (string "1")) (call $str_eq (local.get $s) (string "t")))
+          (i32.or (call $str_eq (local.get $s) (string "T")) (call $str_eq (local.get $s) (string "TRUE"))))
+        (i32.or (call $str_eq (local.get $s) (string "true")) (call $str_eq (local.get $s) (string "True"))))
+    (then (return (call $tuple2 (call $bool (i32.const 1)) (call $zero (global.get $t_error))))))
+  (if (i32.or
+        (i32.or (i32.or (call $str_eq (local.get $s) (string "0")) (call $str_eq (local.get $s) (string "f")))
+          (i32.or (call $str_eq (local.get $s) (string "F")) (call $str_eq (local.get $s) (string "FALSE"))))
+        (i32.or (call $str_eq (local.get $s) (string "false")) (call $str_eq (local.get $s) (string "False"))))
+    (then (return (call $tuple2 (call $bool (i32.const 0)) (call $zero (global.get $t_error))))))
+  (call $tuple2 (call $bool (i32.const 0))
+    (call $num_error (string "ParseBool") (local.get $s) (call $v_strconv_ErrSyntax))))
+
+(func $p_strconv_FormatInt (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $i i64) (local $base i64) (local $b i32)
+  (if (i32.eqz (call $package_args (string "strconv.FormatInt") (local.get $argc) (i32.const 2)))
+    (then (return (call $nil))))
+  (local.set $i (call $int_arg (string "strconv.FormatInt") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $base (call $int_arg (string "strconv.FormatInt") (call $arg (local.get $argv) (i32.const 1))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (i32.or (i64.lt_s (local.get $base) (i64.const 2)) (i64.gt_s (local.get $base) (i64.const 36)))
+    (then
+      (call $error (string "strconv: illegal AppendInt/FormatInt base"))
+      (return (call $nil))))
+  (local.set $b (call $buf_new))
+  (if (i64.lt_s (local.get $i) (i64.const 0))
+    (then (call $buf_byte (local.get $b) (i32.const 45))))
+  (call $format_radix (local.get $b) (local.get $i) (i32.wrap_i64 (local.get $base)) (i32.const 0) (i64.const -1))
+  (call $string (call $buf_value (local.get $b))))
+
+(func $p_strconv_FormatFloat (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $f f64) (local $fmt i64) (local $prec i64) (local $b i32)
+  (if (i32.eqz (call $package_args (string "strconv.FormatFloat") (local.get $argc) (i32.const 4)))
+    (then (return (call $nil))))
+  (local.set $f (call $float_arg (string "strconv.FormatFloat") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $fmt (call $int_arg (string "strconv.FormatFloat") (call $arg (local.get $argv) (i32.const 1))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $prec (call $int_arg (string "strconv.FormatFloat") (call $arg (local.get $argv) (i32.const 2))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (drop (call $int_arg (string "strconv.FormatFloat") (call $arg (local.get $argv) (i32.const 3))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (i32.and (i64.ne (local.get $fmt) (i64.const 101))
+        (i32.and (i64.ne (local.get $fmt) (i64.const 102)) (i64.ne (local.get $fmt) (i64.const 103))))
+    (then
+      (local.set $b (call $buf_new))
+      (call $buf_byte (local.get $b) (i32.const 37))
+      (call $buf_rune (local.get $b) (i64.and (local.get $fmt) (i64.const 0xff)))
+      (return (call $string (call $buf_value (local.get $b))))))
+  (if (i64.lt_s (local.get $prec) (i64.const 0))
+    (then (local.set $prec (i64.const -1))))
+  (call $string (call $format_float (local.get $f) (i32.wrap_i64 (local.get $fmt)) (i32.wrap_i64 (local.get $prec)))))
+
+(func $p_strconv_FormatBool (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $t i32) (local $x i64)
+  (if (i32.eqz (call $package_args (string "strconv.FormatBool") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (call $raw (call $arg (local.get $argv) (i32.const 0)))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.ne (call $kind (local.get $t)) (i32.const 1))
+    (then
+      (call $bad_arg (string "strconv.FormatBool") (string "bool") (local.get $t) (local.get $x))
+      (return (call $nil))))
+  (call $string (select (string "true") (string "false") (i32.wrap_i64 (local.get $x)))))
+
+(func $p_strconv_Quote (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $b i32)
+  (if (i32.eqz (call $package_args (string "strconv.Quote") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (local.set $s (call $string_arg (string "strconv.Quote") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $b (call $buf_new))
+  (call $buf_quote (local.get $b) (local.get $s))
+  (call $string (call $buf_value (local.get $b))))
+
+;; Package strings. Strings are bytes; functions that work on characters
+;; decode them as UTF-8.
+
+;; The i-th of `n` string arguments, checking their number at the first.
+(func $strings_arg (param $name i64) (param $argc i32) (param $argv i32) (param $n i32) (param $i i32) (result i64)
+  (if (i32.eqz (local.get $i))
+    (then
+      (if (i32.eqz (call $package_args (local.get $name) (local.get $argc) (local.get $n)))
+        (then (return (i64.const 0))))))
+  (call $string_arg (local.get $name) (call $arg (local.get $argv) (local.get $i))))
+
+;; Byte offset of the first `sep` in `s` at or after `from`, or -1.
+(func $find (param $s i64) (param $sep i64) (param $from i32) (result i32)
+  (local $i i32) (local $n i32)
+  (local.set $n (call $str_len (local.get $sep)))
+  (if (i32.eqz (local.get $n))
+    (then (return (local.get $from))))
+  (local.set $i (local.get $from))
+  (block $done
+    (loop $next
+      (br_if $done (i32.gt_s (i32.add (local.get $i) (local.get $n)) (call $str_len (local.get $s))))
+      (if (i32.eqz (call $mem_cmp (i32.add (call $str_ptr (local.get $s)) (local.get $i)) (call $str_ptr (local.get $sep)) (local.get $n)))
+        (then (return (local.get $i))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (i32.const -1))
+
+;; The byte offset of the first rune `r` in `s`, or -1.
+(func $index_rune (param $s i64) (param $r i64) (result i32)
+  (local $i i32) (local $c i32) (local $size i32)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $s))))
+      (call $decode_at (local.get $s) (local.get $i))
+      (local.set $size)
+      (local.set $c)
+      (if (i64.eq (i64.extend_i32_s (local.get $c)) (local.get $r))
+        (then (return (local.get $i))))
+      (local.set $i (i32.add (local.get $i) (local.get $size)))
+      (br $next)))
+  (i32.const -1))
+
+(func $has_rune (param $set i64) (param $r i32) (result i32)
+  (i32.ge_s (call $index_rune (local.get $set) (i64.extend_i32_s (local.get $r))) (i32.const 0)))
+
+(func $string_slice (param $n i32) (param $parts i32) (result i32 i64)
+  (local $x i64)
+  (call $slice_of (global.get $t_strings) (local.get $n) (local.get $n))
+  (local.set $x)
+  (drop)
+  (if (local.get $n)
+    (then (memory.copy (call $slice_data (local.get $x)) (local.get $parts) (i32.shl (local.get $n) (i32.const 4)))))
+  (global.get $t_strings)
+  (local.get $x))
+
+;; Appends a part of `s` to the slots at `parts`, the `np`-th.
+(func $part (param $parts i32) (param $np i32) (param $s i64) (param $from i32) (param $to i32) (result i32)
+  (call $set (call $slot (local.get $parts) (local.get $np)) (call $string (call $substr (local.get $s) (local.get $from) (local.get $to))))
+  (i32.add (local.get $np) (i32.const 1)))
+
+;; The substrings between the first `n - 1` separators, and the rest; all
+;; of them when `n` is negative. An empty separator splits after each
+;; UTF-8 sequence.
+(func $split (param $s i64) (param $sep i64) (param $n i64) (result i32 i64)
+  (local $limit i64) (local $parts i32) (local $np i32) (local $i i32) (local $size i32) (local $start i32) (local $len i32)
+  (local.set $limit (select (i64.const 0x7fffffffffffffff) (local.get $n) (i64.lt_s (local.get $n) (i64.const 0))))
+  (local.set $len (call $str_len (local.get $s)))
+  (local.set $parts (call $alloc (i32.shl (i32.add (local.get $len) (i32.const 1)) (i32.const 4))))
+  (if (i32.eqz (call $str_len (local.get $sep)))
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+          (if (i64.eq (i64.extend_i32_u (i32.add (local.get $np) (i32.const 1))) (local.get $limit))
+            (then
+              (local.set $np (call $part (local.get $parts) (local.get $np) (local.get $s) (local.get $i) (local.get $len)))
+              (br $done)))
+          (call $decode_at (local.get $s) (local.get $i))
+          (local.set $size)
+          (drop)
+          (local.set $np
+            (call $part (local.get $parts) (local.get $np) (local.get $s) (local.get $i) (i32.add (local.get $i) (local.get $size))))
+          (local.set $i (i32.add (local.get $i) (local.get $size)))
+          (br $next)))
+      (return (call $string_slice (local.get $np) (local.get $parts)))))
+  (block $done
+    (loop $next
+      (br_if $done (i64.ge_s (i64.extend_i32_u (i32.add (local.get $np) (i32.const 1))) (local.get $limit)))
+      (local.set $i (call $find (local.get $s) (local.get $sep) (local.get $start)))
+      (br_if $done (i32.lt_s (local.get $i) (i32.const 0)))
+      (local.set $np (call $part (local.get $parts) (local.get $np) (local.get $s) (local.get $start) (local.get $i)))
+      (local.set $start (i32.add (local.get $i) (call $str_len (local.get $sep))))
+      (br $next)))
+  (local.set $np (call $part (local.get $parts) (local.get $np) (local.get $s) (local.get $start) (local.get $len)))
+  (call $string_slice (local.get $np) (local.get $parts)))
+
+(func $is_space (param $c i32) (result i32)
+  (if (i32.or (i32.and (i32.ge_u (local.get $c) (i32.const 9)) (i32.le_u (local.get $c) (i32.const 13)))
+        (i32.or (i32.eq (local.get $c) (i32.const 32)) (i32.eq (local.get $c) (i32.const 0x85))))
+    (then (return (i32.const 1))))
+  (if (i32.or (i32.or (i32.eq (local.get $c) (i32.const 0xa0)) (i32.eq (local.get $c) (i32.const 0x1680)))
+        (i32.or (i32.eq (local.get $c) (i32.const 0x2028)) (i32.eq (local.get $c) (i32.const 0x2029))))
+    (then (return (i32.const 1))))
+  (if (i32.or (i32.or (i32.eq (local.get $c) (i32.const 0x202f)) (i32.eq (local.get $c) (i32.const 0x205f)))
+        (i32.eq (local.get $c) (i32.const 0x3000)))
+    (then (return (i32.const 1))))
+  (i32.and (i32.ge_u (local.get $c) (i32.const 0x2000)) (i32.le_u (local.get $c) (i32.const 0x200a))))
+
+(func $p_strings_Contains (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $sep i64)
+  (local.set $s (call $strings_arg (string "strings.Contains") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $sep (call $strings_arg (string "strings.Contains") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $bool (i32.ge_s (call $find (local.get $s) (local.get $sep) (i32.const 0)) (i32.const 0))))
+
+(func $p_strings_ContainsAny (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $chars i64) (local $i i32) (local $c i32) (local $size i32)
+  (local.set $s (call $strings_arg (string "strings.ContainsAny") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $chars (call $strings_arg (string "strings.ContainsAny") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $s))))
+      (call $decode_at (local.get $s) (local.get $i))
+      (local.set $size)
+      (local.set $c)
+      (if (call $has_rune (local.get $chars) (local.get $c))
+        (then (return (call $bool (i32.const 1)))))
+      (local.set $i (i32.add (local.get $i) (local.get $size)))
+      (br $next)))
+  (call $bool (i32.const 0)))
+
+(func $p_strings_ContainsRune (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $r i64)
+  (if (i32.eqz (call $package_args (string "strings.ContainsRune") (local.get $argc) (i32.const 2)))
+    (then (return (call $nil))))
+  (local.set $s (call $string_arg (string "strings.ContainsRune") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $r (call $int_arg (string "strings.ContainsRune") (call $arg (local.get $argv) (i32.const 1))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $bool (i32.ge_s (call $index_rune (local.get $s) (local.get $r)) (i32.const 0))))
+
+(func $has_suffix (param $s i64) (param $suffix i64) (result i32)
+  (if (i32.gt_u (call $str_len (local.get $suffix)) (call $str_len (local.get $s)))
+    (then (return (i32.const 0))))
+  (i32.eqz
+    (call $mem_cmp
+      (i32.add (call $str_ptr (local.get $s)) (i32.sub (call $str_len (local.get $s)) (call $str_len (local.get $suffix))))
+      (call $str_ptr (local.get $suffix)) (call $str_len (local.get $suffix)))))
+
+(func $p_strings_HasPrefix (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $prefix i64)
+  (local.set $s (call $strings_arg (string "strings.HasPrefix") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $prefix (call $strings_arg (string "strings.HasPrefix") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $bool (call $has_prefix (local.get $s) (local.get $prefix))))
+
+(func $p_strings_HasSuffix (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $suffix i64)
+  (local.set $s (call $strings_arg (string "strings.HasSuffix") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $suffix (call $strings_arg (string "strings.HasSuffix") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $bool (call $has_suffix (local.get $s) (local.get $suffix))))
+
+(func $p_strings_Index (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $sep i64)
+  (local.set $s (call $strings_arg (string "strings.Index") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $sep (call $strings_arg (string "strings.Index") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $int (i64.extend_i32_s (call $find (local.get $s) (local.get $sep) (i32.const 0)))))
+
+(func $p_strings_IndexRune (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $r i64)
+  (if (i32.eqz (call $package_args (string "strings.IndexRune") (local.get $argc) (i32.const 2)))
+    (then (return (call $nil))))
+  (local.set $s (call $string_arg (string "strings.IndexRune") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $r (call $int_arg (string "strings.IndexRune") (call $arg (local.get $argv) (i32.const 1))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $int (i64.extend_i32_s (call $index_rune (local.get $s) (local.get $r)))))
+
+(func $p_strings_LastIndex (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $sep i64) (local $i i32)
+  (local.set $s (call $strings_arg (string "strings.LastIndex") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $sep (call $strings_arg (string "strings.LastIndex") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $i (i32.sub (call $str_len (local.get $s)) (call $str_len (local.get $sep))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.lt_s (local.get $i) (i32.const 0)))
+      (if (i32.eqz (call $mem_cmp (i32.add (call $str_ptr (local.get $s)) (local.get $i)) (call $str_ptr (local.get $sep)) (call $str_len (local.get $sep))))
+        (then (return (call $int (i64.extend_i32_s (local.get $i))))))
+      (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+      (br $next)))
+  (call $int (i64.const -1)))
+
+(func $p_strings_Split (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $sep i64)
+  (local.set $s (call $strings_arg (string "strings.Split") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $sep (call $strings_arg (string "strings.Split") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $split (local.get $s) (local.get $sep) (i64.const -1)))
+
+(func $p_strings_SplitN (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $sep i64) (local $n i64)
+  (if (i32.eqz (call $package_args (string "strings.SplitN") (local.get $argc) (i32.const 3)))
+    (then (return (call $nil))))
+  (local.set $s (call $string_arg (string "strings.SplitN") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $sep (call $string_arg (string "strings.SplitN") (call $arg (local.get $argv) (i32.const 1))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $n (call $int_arg (string "strings.SplitN") (call $arg (local.get $argv) (i32.const 2))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (i64.eqz (local.get $n))
+    (then (return (call $zero (global.get $t_strings)))))
+  (call $split (local.get $s) (local.get $sep) (local.get $n)))
+
+(func $p_strings_Fields (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $parts i32) (local $np i32) (local $start i32) (local $i i32) (local $c i32) (local $size i32) (local $space i32)
+  (local.set $s (call $strings_arg (string "strings.Fields") (local.get $argc) (local.get $argv) (i32.const 1) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $parts (call $alloc (i32.shl (i32.add (call $str_len (local.get $s)) (i32.const 1)) (i32.const 4))))
+  (local.set $start (i32.const -1))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $s))))
+      (call $decode_at (local.get $s) (local.get $i))
+      (local.set $size)
+      (local.set $c)
+      (local.set $space (call $is_space (local.get $c)))
+      (if (i32.and (local.get $space) (i32.ge_s (local.get $start) (i32.const 0)))
+        (then
+          (local.set $np (call $part (local.get $parts) (local.get $np) (local.get $s) (local.get $start) (local.get $i)))
+          (local.set $start (i32.const -1))))
+      (if (i32.and (i32.eqz (local.get $space)) (i32.lt_s (local.get $start) (i32.const 0)))
+        (then (local.set $start (local.get $i))))
+      (local.set $i (i32.add (local.get $i) (local.get $size)))
+      (br $next)))
+  (if (i32.ge_s (local.get $start) (i32.const 0))
+    (then
+      (local.set $np (call $part (local.get $parts) (local.get $np) (local.get $s) (local.get $start) (call $str_len (local.get $s))))))
+  (call $string_slice (local.get $np) (local.get $parts)))
+
+(func $p_strings_Join (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $t i32) (local $x i64) (local $sep i64) (local $n i32) (local $i i32) (local $b i32) (local $elem i64)
+  (if (i32.eqz (call $package_args (string "strings.Join") (local.get $argc) (i32.const 2)))
+    (then (return (call $nil))))
+  (call $raw (call $arg (local.get $argv) (i32.const 0)))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.and (i32.ne (call $kind (local.get $t)) (i32.const 0))
+        (i32.eqz (i32.and (i32.eq (call $kind (local.get $t)) (i32.const 7)) (i32.eq (call $kind (call $elem (local.get $t))) (i32.const 5)))))
+    (then
+      (call $bad_arg (string "strings.Join") (string "[]string") (local.get $t) (local.get $x))
+      (return (call $nil))))
+  (local.set $sep (call $string_arg (string "strings.Join") (call $arg (local.get $argv) (i32.const 1))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (call $kind (local.get $t))
+    (then (local.set $n (call $len_of (local.get $t) (local.get $x)))))
+  (local.set $b (call $buf_new))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (if (local.get $i)
+        (then (call $buf_str (local.get $b) (local.get $sep))))
+      (local.set $elem (call $string_arg (string "strings.Join") (call $arg (call $slice_data (local.get $x)) (local.get $i))))
+      (if (global.get $throwing)
+        (then (return (call $nil))))
+      (call $buf_str (local.get $b) (local.get $elem))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (call $string (call $buf_value (local.get $b))))
+
+(func $p_strings_Repeat (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $n i64) (local $b i32)
+  (if (i32.eqz (call $package_args (string "strings.Repeat") (local.get $argc) (i32.const 2)))
+    (then (return (call $nil))))
+  (local.set $s (call $string_arg (string "strings.Repeat") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $n (call $int_arg (string "strings.Repeat") (call $arg (local.get $argv) (i32.const 1))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (i64.lt_s (local.get $n) (i64.const 0))
+    (then
+      (call $error (string "strings: negative Repeat count"))
+      (return (call $nil))))
+  (local.set $b (call $buf_new))
+  (if (call $str_len (local.get $s))
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i64.le_s (local.get $n) (i64.const 0)))
+          (call $buf_str (local.get $b) (local.get $s))
+          (local.set $n (i64.sub (local.get $n) (i64.const 1)))
+          (br $next)))))
+  (call $string (call $buf_value (local.get $b))))
+
+;; `s` with the first `n` non-overlapping `old` replaced by `new`; all of
+;; them when `n` is negative.
+(func $replace (param $s i64) (param $old i64) (param $new i64) (param $n i64) (result i32 i64)
+  (local $b i32) (local $start i32) (local $done i64) (local $i i32) (local $size i32) (local $len i32)
+  (local.set $b (call $buf_new))
+  (local.set $len (call $str_len (local.get $s)))
+  (if (i32.eqz (call $str_len (local.get $old)))
+    (then
+      ;; before each rune and at the end
+      (block $stop
+        (loop $next
+          (br_if $stop (i32.ge_u (local.get $i) (local.get $len)))
+          (br_if $stop (i32.and (i64.ge_s (local.get $n) (i64.const 0)) (i64.ge_s (local.get $done) (local.get $n))))
+          (call $decode_at (local.get $s) (local.get $i))
+          (local.set $size)
+          (drop)
+          (call $buf_str (local.get $b) (local.get $new))
+          (call $buf_str (local.get $b) (call $substr (local.get $s) (local.get $i) (i32.add (local.get $i) (local.get $size))))
+          (local.set $i (i32.add (local.get $i) (local.get $size)))
+          (local.set $start (local.get $i))
+          (local.set $done (i64.add (local.get $done) (i64.const 1)))
+          (br $next)))
+      (call $buf_str (local.get $b) (call $substr (local.get $s) (local.get $start) (local.get $len)))
+      (if (i32.or (i64.lt_s (local.get $n) (i64.const 0)) (i64.lt_s (local.get $done) (local.get $n)))
+        (then (call $buf_str (local.get $b) (local.get $new))))
+      (return (call $string (call $buf_value (local.get $b))))))
+  (block $stop
+    (loop $next
+      (br_if $stop (i32.and (i64.ge_s (local.get $n) (i64.const 0)) (i64.ge_s (local.get $done) (local.get $n))))
+      (local.set $i (call $find (local.get $s) (local.get $old) (local.get $start)))
+      (br_if $stop (i32.lt_s (local.get $i) (i32.const 0)))
+      (call $buf_str (local.get $b) (call $substr (local.get $s) (local.get $start) (local.get $i)))
+      (call $buf_str (local.get $b) (local.get $new))
+      (local.set $start (i32.add (local.get $i) (call $str_len (local.get $old))))
+      (local.set $done (i64.add (local.get $done) (i64.const 1)))
+      (br $next)))
+  (call $buf_str (local.get $b) (call $substr (local.get $s) (local.get $start) (local.get $len)))
+  (call $string (call $buf_value (local.get $b))))
+
+(func $p_strings_Replace (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $old i64) (local $new i64) (local $n i64)
+  (if (i32.eqz (call $package_args (string "strings.Replace") (local.get $argc) (i32.const 4)))
+    (then (return (call $nil))))
+  (local.set $s (call $string_arg (string "strings.Replace") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $old (call $string_arg (string "strings.Replace") (call $arg (local.get $argv) (i32.const 1))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $new (call $string_arg (string "strings.Replace") (call $arg (local.get $argv) (i32.const 2))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $n (call $int_arg (string "strings.Replace") (call $arg (local.get $argv) (i32.const 3))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $replace (local.get $s) (local.get $old) (local.get $new) (local.get $n)))
+
+(func $p_strings_ReplaceAll (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $old i64) (local $new i64)
+  (local.set $s (call $strings_arg (string "strings.ReplaceAll") (local.get $argc) (local.get $argv) (i32.const 3) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $old (call $strings_arg (string "strings.ReplaceAll") (local.get $argc) (local.get $argv) (i32.const 3) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $new (call $strings_arg (string "strings.ReplaceAll") (local.get $argc) (local.get $argv) (i32.const 3) (i32.const 2)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $replace (local.get $s) (local.get $old) (local.get $new) (i64.const -1)))
+
+;; `s` with each rune in upper or lower case; bytes that are not UTF-8 are
+;; kept.
+(func $map_runes (param $s i64) (param $upper i32) (result i32 i64)
+  (local $b i32) (local $i i32) (local $c i32) (local $size i32)
+  (local.set $b (call $buf_new))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_u (local.get $i) (call $str_len (local.get $s))))
+      (call $decode_at (local.get $s) (local.get $i))
+      (local.set $size)
+      (local.set $c)
+      (if (i32.and (i32.eq (local.get $c) (i32.const 0xfffd)) (i32.eq (local.get $size) (i32.const 1)))
+        (then (call $buf_byte (local.get $b) (call $byte_at (local.get $s) (local.get $i))))
+        (else (call $buf_rune (local.get $b) (i64.extend_i32_u (call $host_fold (local.get $c) (local.get $upper))))))
+      (local.set $i (i32.add (local.get $i) (local.get $size)))
+      (br $next)))
+  (call $string (call $buf_value (local.get $b))))
+
+(func $p_strings_ToUpper (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64)
+  (local.set $s (call $strings_arg (string "strings.ToUpper") (local.get $argc) (local.get $argv) (i32.const 1) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $map_runes (local.get $s) (i32.const 1)))
+
+(func $p_strings_ToLower (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64)
+  (local.set $s (call $strings_arg (string "strings.ToLower") (local.get $argc) (local.get $argv) (i32.const 1) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $map_runes (local.get $s) (i32.const 0)))
+
+;; Whether the rune is trimmed: a space, or in `cutset` when there is one.
+(func $trimmed (param $c i32) (param $cut i32) (param $cutset i64) (result i32)
+  (if (result i32) (local.get $cut)
+    (then (call $has_rune (local.get $cutset) (local.get $c)))
+    (else (call $is_space (local.get $c)))))
+
+;; The part of `s` left when leading and trailing runes that are spaces,
+;; or in `cutset` when `cut` is set, are removed from the sides asked for.
+(func $trim (param $s i64) (param $left i32) (param $right i32) (param $cut i32) (param $cutset i64) (result i32 i64)
+  (local $start i32) (local $end i32) (local $i i32) (local $c i32) (local $size i32) (local $last i32)
+  (local.set $end (call $str_len (local.get $s)))
+  (if (local.get $left)
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_u (local.get $start) (local.get $end)))
+          (call $decode_at (local.get $s) (local.get $start))
+          (local.set $size)
+          (local.set $c)
+          (br_if $done (i32.eqz (call $trimmed (local.get $c) (local.get $cut) (local.get $cutset))))
+          (local.set $start (i32.add (local.get $start) (local.get $size)))
+          (br $next)))))
+  (if (local.get $right)
+    (then
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_u (local.get $start) (local.get $end)))
+          ;; the start of the last rune before `end`, decoding forward
+          (local.set $i (local.get $start))
+          (local.set $last (local.get $start))
+          (block $found
+            (loop $scan
+              (br_if $found (i32.ge_u (local.get $i) (local.get $end)))
+              (local.set $last (local.get $i))
+              (call $decode_at (local.get $s) (local.get $i))
+              (local.set $size)
+              (drop)
+              (local.set $i (i32.add (local.get $i) (local.get $size)))
+              (br $scan)))
+          (call $decode_at (local.get $s) (local.get $last))
+          (drop)
+          (local.set $c)
+          (br_if $done (i32.eqz (call $trimmed (local.get $c) (local.get $cut) (local.get $cutset))))
+          (local.set $end (local.get $last))
+          (br $next)))))
+  (call $string (call $substr (local.get $s) (local.get $start) (local.get $end))))
+
+(func $p_strings_TrimSpace (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64)
+  (local.set $s (call $strings_arg (string "strings.TrimSpace") (local.get $argc) (local.get $argv) (i32.const 1) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $trim (local.get $s) (i32.const 1) (i32.const 1) (i32.const 0) (i64.const 0)))
+
+(func $trim_cutset (param $name i64) (param $argc i32) (param $argv i32) (param $left i32) (param $right i32) (result i32 i64)
+  (local $s i64) (local $cutset i64)
+  (local.set $s (call $strings_arg (local.get $name) (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $cutset (call $strings_arg (local.get $name) (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $trim (local.get $s) (local.get $left) (local.get $right) (i32.const 1) (local.get $cutset)))
+
+(func $p_strings_Trim (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (call $trim_cutset (string "strings.Trim") (local.get $argc) (local.get $argv) (i32.const 1) (i32.const 1)))
+
+(func $p_strings_TrimLeft (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (call $trim_cutset (string "strings.TrimLeft") (local.get $argc) (local.get $argv) (i32.const 1) (i32.const 0)))
+
+(func $p_strings_TrimRight (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (call $trim_cutset (string "strings.TrimRight") (local.get $argc) (local.get $argv) (i32.const 0) (i32.const 1)))
+
+(func $p_strings_TrimPrefix (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $prefix i64)
+  (local.set $s (call $strings_arg (string "strings.TrimPrefix") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $prefix (call $strings_arg (string "strings.TrimPrefix") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (call $has_prefix (local.get $s) (local.get $prefix))
+    (then
+      (return (call $string (call $substr (local.get $s) (call $str_len (local.get $prefix)) (call $str_len (local.get $s)))))))
+  (call $arg (local.get $argv) (i32.const 0)))
+
+(func $p_strings_TrimSuffix (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $suffix i64)
+  (local.set $s (call $strings_arg (string "strings.TrimSuffix") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $suffix (call $strings_arg (string "strings.TrimSuffix") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (call $has_suffix (local.get $s) (local.get $suffix))
+    (then
+      (return
+        (call $string (call $substr (local.get $s) (i32.const 0) (i32.sub (call $str_len (local.get $s)) (call $str_len (local.get $suffix))))))))
+  (call $arg (local.get $argv) (i32.const 0)))
+
+;; Non-overlapping instances of `sep` in `s`; one more than the number of
+;; runes when `sep` is empty.
+(func $p_strings_Count (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $sep i64) (local $n i64) (local $i i32)
+  (local.set $s (call $strings_arg (string "strings.Count") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $sep (call $strings_arg (string "strings.Count") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (if (i32.eqz (call $str_len (local.get $sep)))
+    (then (return (call $int (i64.extend_i32_u (i32.add (call $rune_count (local.get $s)) (i32.const 1)))))))
+  (local.set $i (call $find (local.get $s) (local.get $sep) (i32.const 0)))
+  (block $done
+    (loop $next
+      (br_if $done (i32.lt_s (local.get $i) (i32.const 0)))
+      (local.set $n (i64.add (local.get $n) (i64.const 1)))
+      (local.set $i (call $find (local.get $s) (local.get $sep) (i32.add (local.get $i) (call $str_len (local.get $sep)))))
+      (br $next)))
+  (call $int (local.get $n)))
+
+(func $p_strings_EqualFold (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $a i64) (local $b i64) (local $i i32) (local $j i32) (local $x i32) (local $y i32) (local $n i32) (local $m i32)
+  (local.set $a (call $strings_arg (string "strings.EqualFold") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $b (call $strings_arg (string "strings.EqualFold") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (block $done
+    (loop $next
+      (br_if $done (i32.or (i32.ge_u (local.get $i) (call $str_len (local.get $a))) (i32.ge_u (local.get $j) (call $str_len (local.get $b)))))
+      (call $decode_at (local.get $a) (local.get $i))
+      (local.set $n)
+      (local.set $x)
+      (call $decode_at (local.get $b) (local.get $j))
+      (local.set $m)
+      (local.set $y)
+      (if (i32.ne (call $host_fold (local.get $x) (i32.const 0)) (call $host_fold (local.get $y) (i32.const 0)))
+        (then (return (call $bool (i32.const 0)))))
+      (local.set $i (i32.add (local.get $i) (local.get $n)))
+      (local.set $j (i32.add (local.get $j) (local.get $m)))
+      (br $next)))
+  (call $bool
+    (i32.and (i32.eq (local.get $i) (call $str_len (local.get $a))) (i32.eq (local.get $j) (call $str_len (local.get $b))))))
+
+(func $p_strings_Compare (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $a i64) (local $b i64) (local $c i32)
+  (local.set $a (call $strings_arg (string "strings.Compare") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $b (call $strings_arg (string "strings.Compare") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $c (call $str_cmp (local.get $a) (local.get $b)))
+  (call $int (i64.extend_i32_s (i32.sub (i32.gt_s (local.get $c) (i32.const 0)) (i32.lt_s (local.get $c) (i32.const 0))))))
+
+;; `strings.Cut(s, sep)`: the text before and after the first `sep`, and
+;; whether there was one.
+(func $p_strings_Cut (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $s i64) (local $sep i64) (local $i i32) (local $results i32)
+  (local.set $s (call $strings_arg (string "strings.Cut") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 0)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $sep (call $strings_arg (string "strings.Cut") (local.get $argc) (local.get $argv) (i32.const 2) (i32.const 1)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $i (call $find (local.get $s) (local.get $sep) (i32.const 0)))
+  (local.set $results (call $alloc (i32.const 48)))
+  (if (i32.ge_s (local.get $i) (i32.const 0))
+    (then
+      (call $set (local.get $results) (call $string (call $substr (local.get $s) (i32.const 0) (local.get $i))))
+      (call $set (i32.add (local.get $results) (i32.const 16))
+        (call $string
+          (call $substr (local.get $s) (i32.add (local.get $i) (call $str_len (local.get $sep))) (call $str_len (local.get $s)))))
+      (call $set (i32.add (local.get $results) (i32.const 32)) (call $bool (i32.const 1))))
+    (else
+      (call $set (local.get $results) (call $string (local.get $s)))
+      (call $set (i32.add (local.get $results) (i32.const 16)) (call $string (i64.const 0)))
+      (call $set (i32.add (local.get $results) (i32.const 32)) (call $bool (i32.const 0)))))
+  (call $tuple (i32.const 3) (local.get $results)))
+
+;; Package math. What wasm has no instruction for, the host computes:
+;; `namu.math` takes 0 round, 1 cbrt, 2 exp, 3 log, 4 log2, 5 log10,
+;; 6 sin, 7 cos, 8 tan, 9 atan, 10 pow, 11 atan2, 12 hypot and 13 mod.
+
+;; The argument of a function of one float.
+(func $math_arg (param $name i64) (param $argc i32) (param $argv i32) (result f64)
+  (if (i32.eqz (call $package_args (local.get $name) (local.get $argc) (i32.const 1)))
+    (then (return (f64.const 0))))
+  (call $float_arg (local.get $name) (call $arg (local.get $argv) (i32.const 0))))
+
+;; The arguments of a function of two floats.
+(func $math_args (param $name i64) (param $argc i32) (param $argv i32) (result f64 f64)
+  (local $x f64)
+  (if (i32.eqz (call $package_args (local.get $name) (local.get $argc) (i32.const 2)))
+    (then (return (f64.const 0) (f64.const 0))))
+  (local.set $x (call $float_arg (local.get $name) (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (f64.const 0) (f64.const 0))))
+  (local.get $x)
+  (call $float_arg (local.get $name) (call $arg (local.get $argv) (i32.const 1))))
+
+(func $p_math_Abs (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Abs") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (f64.abs (local.get $x))))
+
+(func $p_math_Ceil (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Ceil") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (f64.ceil (local.get $x))))
+
+(func $p_math_Floor (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Floor") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (f64.floor (local.get $x))))
+
+(func $p_math_Trunc (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Trunc") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (f64.trunc (local.get $x))))
+
+(func $p_math_Round (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Round") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 0) (local.get $x) (f64.const 0))))
+
+(func $p_math_Cbrt (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Cbrt") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 1) (local.get $x) (f64.const 0))))
+
+(func $p_math_Exp (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Exp") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 2) (local.get $x) (f64.const 0))))
+
+(func $p_math_Log (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Log") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 3) (local.get $x) (f64.const 0))))
+
+(func $p_math_Log2 (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Log2") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 4) (local.get $x) (f64.const 0))))
+
+(func $p_math_Log10 (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Log10") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 5) (local.get $x) (f64.const 0))))
+
+(func $p_math_Sin (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Sin") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 6) (local.get $x) (f64.const 0))))
+
+(func $p_math_Cos (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Cos") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 7) (local.get $x) (f64.const 0))))
+
+(func $p_math_Tan (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Tan") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 8) (local.get $x) (f64.const 0))))
+
+(func $p_math_Atan (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Atan") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 9) (local.get $x) (f64.const 0))))
+
+(func $p_math_Sqrt (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.Sqrt") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (f64.sqrt (local.get $x))))
+
+(func $p_math_Pow (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64) (local $y f64)
+  (call $math_args (string "math.Pow") (local.get $argc) (local.get $argv))
+  (local.set $y)
+  (local.set $x)
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 10) (local.get $x) (local.get $y))))
+
+(func $p_math_Atan2 (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64) (local $y f64)
+  (call $math_args (string "math.Atan2") (local.get $argc) (local.get $argv))
+  (local.set $y)
+  (local.set $x)
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 11) (local.get $x) (local.get $y))))
+
+(func $p_math_Hypot (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64) (local $y f64)
+  (call $math_args (string "math.Hypot") (local.get $argc) (local.get $argv))
+  (local.set $y)
+  (local.set $x)
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 12) (local.get $x) (local.get $y))))
+
+(func $p_math_Mod (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64) (local $y f64)
+  (call $math_args (string "math.Mod") (local.get $argc) (local.get $argv))
+  (local.set $y)
+  (local.set $x)
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (call $host_math (i32.const 13) (local.get $x) (local.get $y))))
+
+(func $p_math_Max (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64) (local $y f64)
+  (call $math_args (string "math.Max") (local.get $argc) (local.get $argv))
+  (local.set $y)
+  (local.set $x)
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (f64.max (local.get $x) (local.get $y))))
+
+(func $p_math_Min (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64) (local $y f64)
+  (call $math_args (string "math.Min") (local.get $argc) (local.get $argv))
+  (local.set $y)
+  (local.set $x)
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (f64.min (local.get $x) (local.get $y))))
+
+;; `math.Inf(sign)`: +Inf for a sign of 0 or more, else -Inf.
+(func $p_math_Inf (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $sign i64)
+  (if (i32.eqz (call $package_args (string "math.Inf") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (local.set $sign (call $int_arg (string "math.Inf") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $float (select (f64.const inf) (f64.const -inf) (i64.ge_s (local.get $sign) (i64.const 0)))))
+
+;; `math.IsInf(f, sign)`: whether `f` is an infinity of the sign, or of
+;; either sign for 0.
+(func $p_math_IsInf (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $f f64) (local $sign i64)
+  (if (i32.eqz (call $package_args (string "math.IsInf") (local.get $argc) (i32.const 2)))
+    (then (return (call $nil))))
+  (local.set $f (call $float_arg (string "math.IsInf") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (local.set $sign (call $int_arg (string "math.IsInf") (call $arg (local.get $argv) (i32.const 1))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $bool
+    (i32.or
+      (i32.and (i64.ge_s (local.get $sign) (i64.const 0)) (f64.eq (local.get $f) (f64.const inf)))
+      (i32.and (i64.le_s (local.get $sign) (i64.const 0)) (f64.eq (local.get $f) (f64.const -inf))))))
+
+(func $p_math_IsNaN (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $x f64)
+  (local.set $x (call $math_arg (string "math.IsNaN") (local.get $argc) (local.get $argv)))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $bool (f64.ne (local.get $x) (local.get $x))))
+
+(func $p_math_NaN (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (if (i32.eqz (call $package_args (string "math.NaN") (local.get $argc) (i32.const 0)))
+    (then (return (call $nil))))
+  (call $float (f64.const nan)))
+
+;; Package sort. Slice, SliceStable and Search call back into the program
+;; the way their source does on the VM.
+
+;; Compares the values in two slots as ints (0), strings (1) or floats
+;; (2); NaNs sort before everything else.
+(func $sort_cmp (param $mode i32) (param $a i32) (param $b i32) (result i32)
+  (local $x f64) (local $y f64)
+  (if (i32.eqz (local.get $mode))
+    (then
+      (return
+        (i32.sub (i64.gt_s (i64.load offset=8 (local.get $a)) (i64.load offset=8 (local.get $b)))
+          (i64.lt_s (i64.load offset=8 (local.get $a)) (i64.load offset=8 (local.get $b)))))))
+  (if (i32.eq (local.get $mode) (i32.const 1))
+    (then (return (call $str_cmp (i64.load offset=8 (local.get $a)) (i64.load offset=8 (local.get $b))))))
+  (local.set $x (f64.load offset=8 (local.get $a)))
+  (local.set $y (f64.load offset=8 (local.get $b)))
+  (if (i32.or (f64.ne (local.get $x) (local.get $x)) (f64.ne (local.get $y) (local.get $y)))
+    (then (return (i32.sub (f64.ne (local.get $y) (local.get $y)) (f64.ne (local.get $x) (local.get $x))))))
+  (i32.sub (f64.gt (local.get $x) (local.get $y)) (f64.lt (local.get $x) (local.get $y))))
+
+;; Stable merge sort of `n` slots at `p`, through `tmp` of the same size.
+(func $merge_sort (param $p i32) (param $n i32) (param $tmp i32) (param $mode i32)
+  (local $half i32) (local $i i32) (local $j i32) (local $k i32)
+  (if (i32.lt_s (local.get $n) (i32.const 2))
+    (then (return)))
+  (local.set $half (i32.shr_u (local.get $n) (i32.const 1)))
+  (call $merge_sort (local.get $p) (local.get $half) (local.get $tmp) (local.get $mode))
+  (call $merge_sort (call $slot (local.get $p) (local.get $half)) (i32.sub (local.get $n) (local.get $half)) (local.get $tmp) (local.get $mode))
+  (local.set $j (local.get $half))
+  (block $done
+    (loop $next
+      (br_if $done (i32.or (i32.ge_s (local.get $i) (local.get $half)) (i32.ge_s (local.get $j) (local.get $n))))
+      (if (i32.lt_s (call $sort_cmp (local.get $mode) (call $slot (local.get $p) (local.get $j)) (call $slot (local.get $p) (local.get $i))) (i32.const 0))
+        (then
+          (memory.copy (call $slot (local.get $tmp) (local.get $k)) (call $slot (local.get $p) (local.get $j)) (i32.const 16))
+          (local.set $j (i32.add (local.get $j) (i32.const 1))))
+        (else
+          (memory.copy (call $slot (local.get $tmp) (local.get $k)) (call $slot (local.get $p) (local.get $i)) (i32.const 16))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))))
+      (local.set $k (i32.add (local.get $k) (i32.const 1)))
+      (br $next)))
+  (memory.copy (call $slot (local.get $tmp) (local.get $k)) (call $slot (local.get $p) (local.get $i))
+    (i32.shl (i32.sub (local.get $half) (local.get $i)) (i32.const 4)))
+  (local.set $k (i32.add (local.get $k) (i32.sub (local.get $half) (local.get $i))))
+  (memory.copy (call $slot (local.get $tmp) (local.get $k)) (call $slot (local.get $p) (local.get $j))
+    (i32.shl (i32.sub (local.get $n) (local.get $j)) (i32.const 4)))
+  (memory.copy (local.get $p) (local.get $tmp) (i32.shl (local.get $n) (i32.const 4))))
+
+;; Sorts the elements of a slice of `elem` in place.
+(func $sort_slice (param $name i64) (param $argc i32) (param $argv i32) (param $elem i32) (param $mode i32) (result i32 i64)
+  (local $t i32) (local $x i64) (local $n i32)
+  (if (i32.eqz (call $package_args (local.get $name) (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (call $raw (call $arg (local.get $argv) (i32.const 0)))
+  (local.set $x)
+  (local.set $t)
+  (if (i32.or (i32.ne (call $kind (local.get $t)) (i32.const 7)) (i32.eqz (call $identical (call $elem (local.get $t)) (local.get $elem))))
+    (then
+      (call $bad_arg (local.get $name) (call $concat (string "[]") (call $tname (local.get $elem))) (local.get $t) (local.get $x))
+      (return (call $nil))))
+  (if (i64.ne (local.get $x) (i64.const 0))
+    (then
+      (local.set $n (i32.load offset=4 (call $ptr (local.get $x))))
+      (call $merge_sort (call $slice_data (local.get $x)) (local.get $n) (call $alloc (i32.shl (local.get $n) (i32.const 4))) (local.get $mode))))
+  (call $nil))
+
+(func $p_sort_Ints (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (call $sort_slice (string "sort.Ints") (local.get $argc) (local.get $argv) (global.get $t_int) (i32.const 0)))
+
+(func $p_sort_Strings (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (call $sort_slice (string "sort.Strings") (local.get $argc) (local.get $argv) (global.get $t_string) (i32.const 1)))
+
+(func $p_sort_Float64s (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (call $sort_slice (string "sort.Float64s") (local.get $argc) (local.get $argv) (global.get $t_float) (i32.const 2)))
+
+;; `less(i, j)` with the indices as the program passes them.
+(func $less (param $lt i32) (param $lx i64) (param $i i32) (param $j i32) (result i32)
+  (local $args i32)
+  (local.set $args (call $alloc (i32.const 32)))
+  (call $set (local.get $args) (call $box (global.get $t_any) (global.get $t_int) (i64.extend_i32_s (local.get $i))))
+  (call $set (i32.add (local.get $args) (i32.const 16))
+    (call $box (global.get $t_any) (global.get $t_int) (i64.extend_i32_s (local.get $j))))
+  (call $truth (call $call (local.get $lt) (local.get $lx) (i32.const 2) (local.get $args))))
+
+(func $swap (param $t i32) (param $x i64) (param $i i32) (param $j i32)
+  (local $at i32) (local $a i64) (local $bt i32) (local $b i64)
+  (call $index (local.get $t) (local.get $x) (global.get $t_int) (i64.extend_i32_s (local.get $i)))
+  (local.set $a)
+  (local.set $at)
+  (if (global.get $throwing)
+    (then (return)))
+  (call $index (local.get $t) (local.get $x) (global.get $t_int) (i64.extend_i32_s (local.get $j)))
+  (local.set $b)
+  (local.set $bt)
+  (if (global.get $throwing)
+    (then (return)))
+  (call $set_index (local.get $t) (local.get $x) (global.get $t_int) (i64.extend_i32_s (local.get $i)) (local.get $bt) (local.get $b))
+  (if (global.get $throwing)
+    (then (return)))
+  (call $set_index (local.get $t) (local.get $x) (global.get $t_int) (i64.extend_i32_s (local.get $j)) (local.get $at) (local.get $a)))
+
+(func $sift_down (param $t i32) (param $x i64) (param $lt i32) (param $lx i64) (param $root i32) (param $hi i32)
+  (local $child i32)
+  (loop $next
+    (local.set $child (i32.add (i32.shl (local.get $root) (i32.const 1)) (i32.const 1)))
+    (if (i32.ge_s (local.get $child) (local.get $hi))
+      (then (return)))
+    (if (i32.lt_s (i32.add (local.get $child) (i32.const 1)) (local.get $hi))
+      (then
+        (if (call $less (local.get $lt) (local.get $lx) (local.get $child) (i32.add (local.get $child) (i32.const 1)))
+          (then (local.set $child (i32.add (local.get $child) (i32.const 1)))))
+        (if (global.get $throwing)
+          (then (return)))))
+    (if (i32.eqz (call $less (local.get $lt) (local.get $lx) (local.get $root) (local.get $child)))
+      (then (return)))
+    (if (global.get $throwing)
+      (then (return)))
+    (call $swap (local.get $t) (local.get $x) (local.get $root) (local.get $child))
+    (if (global.get $throwing)
+      (then (return)))
+    (local.set $root (local.get $child))
+    (br $next)))
+
+;; The length of a sort function's slice, as len(x) takes it.
+(func $sort_len (param $argv i32) (result i32)
+  (local $n i64)
+  (call $b_len (i32.const 0) (i32.const 1) (local.get $argv))
+  (local.set $n)
+  (drop)
+  (i32.wrap_i64 (local.get $n)))
+
+;; A heapsort.
+(func $p_sort_Slice (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $base i32) (local $t i32) (local $x i64) (local $lt i32) (local $lx i64) (local $n i32) (local $i i32)
+  (local.set $base (call $enter (string "sort.Slice") (i32.const 1) (i32.const 0)))
+  (if (i32.eqz (local.get $base))
+    (then (return (call $nil))))
+  (call $get (local.get $argv))
+  (local.set $x)
+  (local.set $t)
+  (call $get (i32.add (local.get $argv) (i32.const 16)))
+  (local.set $lx)
+  (local.set $lt)
+  (local.set $n (call $sort_len (local.get $argv)))
+  (block $fail
+    (br_if $fail (global.get $throwing))
+    (local.set $i (i32.div_s (i32.sub (local.get $n) (i32.const 1)) (i32.const 2)))
+    (block $done
+      (loop $next
+        (br_if $done (i32.lt_s (local.get $i) (i32.const 0)))
+        (call $sift_down (local.get $t) (local.get $x) (local.get $lt) (local.get $lx) (local.get $i) (local.get $n))
+        (br_if $fail (global.get $throwing))
+        (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+        (br $next)))
+    (local.set $i (i32.sub (local.get $n) (i32.const 1)))
+    (block $done
+      (loop $next
+        (br_if $done (i32.le_s (local.get $i) (i32.const 0)))
+        (call $swap (local.get $t) (local.get $x) (i32.const 0) (local.get $i))
+        (br_if $fail (global.get $throwing))
+        (call $sift_down (local.get $t) (local.get $x) (local.get $lt) (local.get $lx) (i32.const 0) (local.get $i))
+        (br_if $fail (global.get $throwing))
+        (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+        (br $next)))
+    (call $leave (local.get $base)))
+  (call $nil))
+
+;; An insertion sort.
+(func $p_sort_SliceStable (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $base i32) (local $t i32) (local $x i64) (local $lt i32) (local $lx i64) (local $i i32) (local $j i32) (local $n i32)
+  (local.set $base (call $enter (string "sort.SliceStable") (i32.const 1) (i32.const 0)))
+  (if (i32.eqz (local.get $base))
+    (then (return (call $nil))))
+  (call $get (local.get $argv))
+  (local.set $x)
+  (local.set $t)
+  (call $get (i32.add (local.get $argv) (i32.const 16)))
+  (local.set $lx)
+  (local.set $lt)
+  (block $fail
+    (local.set $i (i32.const 1))
+    (block $done
+      (loop $next
+        (local.set $n (call $sort_len (local.get $argv)))
+        (br_if $fail (global.get $throwing))
+        (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+        (local.set $j (local.get $i))
+        (block $placed
+          (loop $down
+            (br_if $placed (i32.le_s (local.get $j) (i32.const 0)))
+            (br_if $placed
+              (i32.eqz (call $less (local.get $lt) (local.get $lx) (local.get $j) (i32.sub (local.get $j) (i32.const 1)))))
+            (br_if $fail (global.get $throwing))
+            (call $swap (local.get $t) (local.get $x) (local.get $j) (i32.sub (local.get $j) (i32.const 1)))
+            (br_if $fail (global.get $throwing))
+            (local.set $j (i32.sub (local.get $j) (i32.const 1)))
+            (br $down)))
+        (br_if $fail (global.get $throwing))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (call $leave (local.get $base)))
+  (call $nil))
+
+;; The least index in [0, n) for which f is true, or n.
+(func $p_sort_Search (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $base i32) (local $it i32) (local $i i64) (local $jt i32) (local $j i64) (local $ft i32) (local $fx i64)
+  (local $ht i32) (local $h i64) (local $arg i32)
+  (local.set $base (call $enter (string "sort.Search") (i32.const 1) (i32.const 0)))
+  (if (i32.eqz (local.get $base))
+    (then (return (call $nil))))
+  (local.set $it (global.get $t_int))
+  (call $get (local.get $argv))
+  (local.set $j)
+  (local.set $jt)
+  (call $get (i32.add (local.get $argv) (i32.const 16)))
+  (local.set $fx)
+  (local.set $ft)
+  (local.set $arg (call $alloc (i32.const 16)))
+  (block $fail
+    (loop $next
+      (call $truth (call $lt (local.get $it) (local.get $i) (local.get $jt) (local.get $j)))
+      (br_if $fail (global.get $throwing))
+      (if (i32.eqz)
+        (then
+          (call $leave (local.get $base))
+          (return (local.get $it) (local.get $i))))
+      (call $div (call $add (local.get $it) (local.get $i) (local.get $jt) (local.get $j)) (global.get $t_int) (i64.const 2))
+      (local.set $h)
+      (local.set $ht)
+      (br_if $fail (global.get $throwing))
+      (call $set (local.get $arg) (call $box (global.get $t_any) (local.get $ht) (local.get $h)))
+      (call $truth (call $call (local.get $ft) (local.get $fx) (i32.const 1) (local.get $arg)))
+      (br_if $fail (global.get $throwing))
+      (if (i32.eqz)
+        (then
+          (call $add (local.get $ht) (local.get $h) (global.get $t_int) (i64.const 1))
+          (local.set $i)
+          (local.set $it)
+          (br_if $fail (global.get $throwing)))
+        (else
+          (local.set $jt (local.get $ht))
+          (local.set $j (local.get $h))))
+      (br $next)))
+  (call $nil))
+
+;; Package os
+
+(func $v_os_Args (result i32 i64)
+  (local $n i32) (local $x i64) (local $i i32) (local $len i32) (local $p i32)
+  (local.set $n (call $host_args))
+  (call $slice_of (global.get $t_strings) (local.get $n) (local.get $n))
+  (local.set $x)
+  (drop)
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (local.set $len (call $host_arg_len (local.get $i)))
+      (local.set $p (call $alloc (local.get $len)))
+      (call $host_arg (local.get $i) (local.get $p))
+      (call $set (call $slot (call $slice_data (local.get $x)) (local.get $i)) (call $string (call $str (local.get $p) (local.get $len))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (global.get $t_strings)
+  (local.get $x))
+
+;; An `*os.File` for a standard stream.
+(func $file (param $fd i64) (result i32 i64)
+  (local $fields i32) (local $slot i32)
+  (local.set $fields (call $alloc (i32.const 16)))
+  (call $set (local.get $fields) (global.get $t_int) (local.get $fd))
+  (local.set $slot (call $alloc (i32.const 16)))
+  (call $set (local.get $slot) (call $ref (global.get $t_File) (local.get $fields)))
+  (call $ref (global.get $t_p_File) (local.get $slot)))
+
+(func $v_os_Stdout (result i32 i64)
+  (call $file (i64.const 1)))
+
+(func $v_os_Stderr (result i32 i64)
+  (call $file (i64.const 2)))
+
+(func $p_os_Exit (type $fn) (param $self i32) (param $argc i32) (param $argv i32) (result i32 i64)
+  (local $code i64)
+  (if (i32.eqz (call $package_args (string "os.Exit") (local.get $argc) (i32.const 1)))
+    (then (return (call $nil))))
+  (local.set $code (call $int_arg (string "os.Exit") (call $arg (local.get $argv) (i32.const 0))))
+  (if (global.get $throwing)
+    (then (return (call $nil))))
+  (call $host_exit (i32.wrap_i64 (local.get $code)))
+  (unreachable))
+
+;; Range loops. An iterator is {kind, keys, i (i64) at 8, n (i64) at 16,
+;; the value ranged over at 24, and the slots `$next` fills at 40}: 0
+;; ints, 1 strings, 2 slices and 3 maps.
+
+(func $rand (result i64)
+  (if (i64.eqz (global.get $rng))
+    (then (global.set $rng (i64.or (i64.mul (call $host_seed) (i64.const 0x9e3779b97f4a7c15)) (i64.const 1)))))
+  (global.set $rng (i64.xor (global.get $rng) (i64.shl (global.get $rng) (i64.const 13))))
+  (global.set $rng (i64.xor (global.get $rng) (i64.shr_u (global.get $rng) (i64.const 7))))
+  (global.set $rng (i64.xor (global.get $rng) (i64.shl (global.get $rng) (i64.const 17))))
+  (global.get $rng))
+
+;; Starts a range loop over a value. A map is visited in insertion order
+;; from a random entry on, so programs cannot rely on the order.
+(func $range (param $t i32) (param $x i64) (result i32)
+  (local $it i32) (local $k i32) (local $m i32) (local $n i32) (local $i i32) (local $live i32) (local $e i32) (local $start i32)
+  (call $raw (local.get $t) (local.get $x))
+  (local.set $x)
+  (local.set $t)
+  (local.set $it (call $alloc (i32.const 88)))
+  (call $set (i32.add (local.get $it) (i32.const 24)) (local.get $t) (local.get $x))
+  (local.set $k (call $kind (local.get $t)))
+  (if (i32.eq (local.get $k) (i32.const 2))
+    (then
+      (i64.store offset=16 (local.get $it) (local.get $x))
+      (return (local.get $it))))
+  (if (i32.eq (local.get $k) (i32.const 5))
+    (then
+      (i32.store (local.get $it) (i32.const 1))
+      (i64.store offset=16 (local.get $it) (i64.extend_i32_u (call $str_len (local.get $x))))
+      (return (local.get $it))))
+  (if (i32.or (i32.eq (local.get $k) (i32.const 7)) (i32.eqz (local.get $k)))
+    (then
+      (i32.store (local.get $it) (i32.const 2))
+      (if (i64.ne (local.get $x) (i64.const 0))
+        (then (i64.store offset=16 (local.get $it) (i64.extend_i32_u (i32.load offset=4 (call $ptr (local.get $x)))))))
+      (return (local.get $it))))
+  (if (i32.ne (local.get $k) (i32.const 8))
+    (then
+      (call $error (call $cat3 (string "cannot range over ") (call $tname (local.get $t)) (string " value")))
+      (return (local.get $it))))
+  (i32.store (local.get $it) (i32.const 3))
+  (local.set $m (call $ptr (local.get $x)))
+  (if (local.get $m)
+    (then (local.set $n (i32.load offset=12 (local.get $m)))))
+  (i64.store offset=16 (local.get $it) (i64.extend_i32_u (local.get $n)))
+  (if (i32.eqz (local.get $n))
+    (then (return (local.get $it))))
+  (local.set $live (call $alloc (i32.shl (local.get $n) (i32.const 4))))
+  (local.set $k (i32.const 0))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (i32.load offset=4 (local.get $m))))
+      (local.set $e (call $entry (local.get $m) (local.get $i)))
+      (if (i32.load offset=32 (local.get $e))
+        (then
+          (memory.copy (call $slot (local.get $live) (local.get $k)) (local.get $e) (i32.const 16))
+          (local.set $k (i32.add (local.get $k) (i32.const 1)))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (local.set $start (i32.wrap_i64 (i64.rem_u (call $rand) (i64.extend_i32_u (local.get $n)))))
+  (i32.store offset=4 (local.get $it) (call $alloc (i32.shl (local.get $n) (i32.const 4))))
+  (local.set $i (i32.const 0))
+  (block $done
+    (loop $next
+      (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+      (memory.copy (call $slot (i32.load offset=4 (local.get $it)) (local.get $i))
+        (call $slot (local.get $live) (i32.rem_u (i32.add (local.get $start) (local.get $i)) (local.get $n))) (i32.const 16))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $next)))
+  (local.get $it))
+
+;; The next iteration: three slots of whether there is one, its key and
+;; its value. Map entries deleted during the loop are skipped.
+(func $next (param $it i32) (result i32)
+  (local $out i32) (local $i i64) (local $x i64) (local $c i32) (local $size i32) (local $kt i32) (local $kx i64) (local $e i32)
+  (local.set $out (i32.add (local.get $it) (i32.const 40)))
+  (call $set (i32.add (local.get $out) (i32.const 16)) (call $nil))
+  (call $set (i32.add (local.get $out) (i32.const 32)) (call $nil))
+  (local.set $i (i64.load offset=8 (local.get $it)))
+  (local.set $x (i64.load offset=32 (local.get $it)))
+  (block $end
+    (block $map
+      (block $slice
+        (block $string
+          (block $int
+            (br_table $int $string $slice $map (i32.load (local.get $it))))
+          (br_if $end (i64.ge_s (local.get $i) (i64.load offset=16 (local.get $it))))
+          (call $set (i32.add (local.get $out) (i32.const 16)) (call $int (local.get $i)))
+          (i64.store offset=8 (local.get $it) (i64.add (local.get $i) (i64.const 1)))
+          (call $set (local.get $out) (call $bool (i32.const 1)))
+          (return (local.get $out)))
+        (br_if $end (i64.ge_s (local.get $i) (i64.load offset=16 (local.get $it))))
+        (call $decode_at (local.get $x) (i32.wrap_i64 (local.get $i)))
+        (local.set $size)
+        (local.set $c)
+        (call $set (i32.add (local.get $out) (i32.const 16)) (call $int (local.get $i)))
+        (call $set (i32.add (local.get $out) (i32.const 32)) (call $int (i64.extend_i32_u (local.get $c))))
+        (i64.store offset=8 (local.get $it) (i64.add (local.get $i) (i64.extend_i32_u (local.get $size))))
+        (call $set (local.get $out) (call $bool (i32.const 1)))
+        (return (local.get $out)))
+      (br_if $end (i64.ge_s (local.get $i) (i64.load offset=16 (local.get $it))))
+      (br_if $end (i64.ge_s (local.get $i) (i64.extend_i32_u (i32.load offset=4 (call $ptr (local.get $x))))))
+      (call $set (i32.add (local.get $out) (i32.const 16)) (call $int (local.get $i)))
+      (call $set (i32.add (local.get $out) (i32.const 32))
+        (call $dup (call $get (call $slot (call $slice_data (local.get $x)) (i32.wrap_i64 (local.get $i))))))
+      (i64.store offset=8 (local.get $it) (i64.add (local.get $i) (i64.const 1)))
+      (call $set (local.get $out) (call $bool (i32.const 1)))
+      (return (local.get $out)))
+    (block $done
+      (loop $keys
+        (br_if $done (i64.ge_s (local.get $i) (i64.load offset=16 (local.get $it))))
+        (call $get (call $slot (i32.load offset=4 (local.get $it)) (i32.wrap_i64 (local.get $i))))
+        (local.set $kx)
+        (local.set $kt)
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (i64.store offset=8 (local.get $it) (local.get $i))
+        (local.set $e (call $map_find (call $ptr (local.get $x)) (local.get $kt) (local.get $kx)))
+        (if (i32.ge_s (local.get $e) (i32.const 0))
+          (then
+            (local.set $e (call $entry (call $ptr (local.get $x)) (local.get $e)))
+            (call $set (i32.add (local.get $out) (i32.const 16)) (local.get $kt) (local.get $kx))
+            (call $set (i32.add (local.get $out) (i32.const 32)) (call $dup (call $get (i32.add (local.get $e) (i32.const 16)))))
+            (call $set (local.get $out) (call $bool (i32.const 1)))
+            (return (local.get $out))))
+        (br $keys))))
+  (call $set (local.get $out) (call $bool (i32.const 0)))
+  (local.get $out))
@@ -0,0 +1,136 @@
+use crate::backend::wasm::*;
+use crate::ir::test::tests::{lower_source, PROGRAMS};
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Builds `source` into a binary module and runs it in the VM with
+  /// `args`, giving its stdout, stderr and exit code.
+  fn run_source(source: &str, args: &[&str]) -> (String, String, i32) {
+    let text = generate(&lower_source(source)).unwrap_or_else(|errors| panic!("generate errors: {:?}", errors));
+    let binary = assemble(&text).unwrap_or_else(|e| panic!("{}", e));
+    let args: Vec<String> = std::iter::once("main").chain(args.iter().copied()).map(String::from).collect();
+    let (mut stdout, mut stderr) = (vec![], vec![]);
+    let code = run(&binary, &args, &mut stdout, &mut stderr).unwrap_or_else(|e| panic!("{}", e));
+    (String::from_utf8_lossy(&stdout).into_owned(), String::from_utf8_lossy(&stderr).into_owned(), code)
+  }
+
+  fn check_output(source: &str, expected: &str) {
+    let (stdout, stderr, code) = run_source(source, &[]);
+    assert_eq!((stdout.as_str(), stderr.as_str(), code), (expected, "", 0));
+  }
+
+  #[test]
+  fn test_programs() {
+    for (_, source, expected) in PROGRAMS {
+      check_output(source, expected);
+    }
+  }
+
+  #[test]
+  fn test_map_iteration_order_varies() {
+    let source = r#"let main = fn() {
+  m := map[int]int{}
+  for i := 0; i < 20; i++ { m[i] = i }
+  for k := range m {
+    println(k)
+    break
+  }
+}"#;
+    let firsts: std::collections::HashSet<_> = (0..10).map(|_| run_source(source, &[]).1).collect();
+    if firsts.len() < 2 {
+      panic!("iteration always started at {:?}", firsts);
+    }
+  }
+
+  #[test]
+  fn test_panic() {
+    let source = r#"let f = fn(x) { panic("boom") }
+let main = fn() { f(1) }"#;
+    let (stdout, stderr, code) = run_source(source, &[]);
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "panic: boom\n\ngoroutine 1 [running]:\nmain.f(...)\nmain.main()\n");
+    assert_eq!(code, 2);
+  }
+
+  #[test]
+  fn test_os() {
+    let source = r#"import "fmt"
+import "os"
+let main = fn() {
+  defer fmt.Println("not run")
+  fmt.Println(os.Args[1:])
+  fmt.Fprintln(os.Stderr, "bye")
+  os.Exit(3)
+}"#;
+    assert_eq!(run_source(source, &["a", "b"]), ("[a b]\n".to_string(), "bye\n".to_string(), 3));
+  }
+
+  #[test]
+  fn test_packages() {
+    check_output(
+      r#"import "fmt"
+import "math"
+import "sort"
+import "strconv"
+import "strings"
+let main = fn() {
+  fmt.Println(strings.Split("a,b,c", ","), strings.Fields("  x  y "), strings.ToUpper("héllo"), strings.TrimLeft("xxhi", "x"))
+  before, after, found := strings.Cut("k=v", "=")
+  fmt.Println(before, after, found, strings.Replace("aaaa", "a", "b", 2), strings.Count("cheese", "e"))
+  n, err := strconv.Atoi("12x")
+  fmt.Println(n, err, strconv.Quote("hi\n"), strconv.FormatInt(255, 16))
+  fmt.Println(math.Sqrt(2), math.Floor(-1.5), math.Pow(2, 10), math.Inf(1), math.Round(2.5))
+  ys := []string{"pear", "fig", "apple"}
+  sort.Slice(ys, fn(i, j) { return len(ys[i]) < len(ys[j]) })
+  fmt.Println(ys, sort.Search(100, fn(i) { return i*i >= 50 }))
+  for i, r := range "hé" { fmt.Print(i, ":", string(r), " ") }
+  fmt.Println()
+}"#,
+      "[a b c] [x y] HÉLLO hi\nk v true bbaa 3\n0 strconv.Atoi: parsing \"12x\": invalid syntax \"hi\\n\" ff\n1.4142135623730951 -2 1024 +Inf 3\n[fig pear apple] 8\n0:h 1:é \n",
+    );
+  }
+
+  #[test]
+  fn test_binary_round_trip() {
+    let text = generate(&lower_source("let main = fn() {}")).unwrap();
+    let binary = assemble(&text).unwrap();
+    assert_eq!(&binary[..8], b"\0asm\x01\0\0\0");
+    assert_eq!(module::Module::decode(&binary).unwrap().encode(), binary);
+  }
+
+  #[test]
+  fn test_output_validates() {
+    let text = generate(&lower_source(r#"import "fmt"
+let main = fn() { fmt.Println(float64(3) / 2, string([]byte{104, 105})) }"#))
+    .unwrap();
+    let module = module::Module::decode(&assemble(&text).unwrap()).unwrap();
+    assert_eq!(validate::validate(&module), Ok(()));
+
+    // a then branch cannot reach operands pushed before the if
+    let invalid = [
+      "(func $f (result i32) (i32.const 1) (if (i32.const 0) (then (drop))) (i32.const 2))",
+      "(func $f (result i64) (i32.const 1))",
+      "(func $f (block (result i32) (i32.const 1) (i32.const 2)) (drop))",
+      "(func $f (if (result i32) (i32.const 1) (then (i32.const 2))) (drop))",
+    ];
+    for source in invalid.iter() {
+      let module = wat::parse(&format!("(module {})", source)).unwrap();
+      assert!(validate::validate(&module).is_err(), "{} validates", source);
+    }
+  }
+
+  #[test]
+  fn test_unsupported() {
+    let module = lower_source(
+      r#"let main = fn() {
+  c := make(chan int)
+  go fn() { c <- 1 }()
+  <-c
+}"#,
+    );
+    let errors = generate(&module).unwrap_err();
+    assert!(errors.iter().any(|e| e.ends_with("not supported by the WebAssembly backend")), "{:?}", errors);
+  }
+}
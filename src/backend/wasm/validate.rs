@@ -0,0 +1,350 @@
+//! Validation of a module, as an engine does before it runs one: every
+//! instruction finds operands of the types it takes on the stack, and
+//! every block leaves what its type says. It follows the algorithm in
+//! the appendix of the WebAssembly specification, over the subset of
+//! the language `module` covers.
+
+use super::module::*;
+
+/// Reports the first way the module is not valid, naming the function
+/// by its index, imports first, as engines do.
+pub fn validate(module: &Module) -> Result<(), String> {
+    for import in &module.imports {
+        if module.types.get(import.ty as usize).is_none() {
+            return Err(format!("import {}.{} has an unknown type", import.module, import.name));
+        }
+    }
+    for (i, global) in module.globals.iter().enumerate() {
+        let ty = match global.init {
+            Instr::I32Const(_) => ValType::I32,
+            Instr::I64Const(_) => ValType::I64,
+            Instr::F64Const(_) => ValType::F64,
+            _ => return Err(format!("global {} is not initialized with a constant", i)),
+        };
+        if ty != global.ty {
+            return Err(format!("global {} of type {} is initialized with {}", i, global.ty.name(), ty.name()));
+        }
+    }
+    let functions = (module.imports.len() + module.funcs.len()) as u32;
+    for export in &module.exports {
+        let count = match export.kind {
+            ExportKind::Func => functions,
+            ExportKind::Table => module.table.map_or(0, |_| 1),
+            ExportKind::Memory => module.memory.map_or(0, |_| 1),
+            ExportKind::Global => module.globals.len() as u32,
+        };
+        if export.index >= count {
+            return Err(format!("export {} names an unknown {:?}", export.name, export.kind));
+        }
+    }
+    if let Some(start) = module.start {
+        match module.func_type(start) {
+            Some(ty) if ty.params.is_empty() && ty.results.is_empty() => {}
+            _ => return Err("start function must take and return nothing".to_string()),
+        }
+    }
+    if !module.elems.is_empty() && module.table.is_none() {
+        return Err("element segment without a table".to_string());
+    }
+    if !module.data.is_empty() && module.memory.is_none() {
+        return Err("data segment without a memory".to_string());
+    }
+    for (i, func) in module.funcs.iter().enumerate() {
+        let index = module.imports.len() + i;
+        let ty = module.types.get(func.ty as usize).ok_or_else(|| format!("function #{} has an unknown type", index))?;
+        Validator::new(module, ty, func).body(&func.body).map_err(|(at, msg)| format!("function #{} at instruction {}: {}", index, at, msg))?;
+    }
+    Ok(())
+}
+
+/// A block, loop, if or the function body being validated.
+struct Control {
+    is_loop: bool,
+    is_if: bool,
+    results: Vec<ValType>,
+    /// The operand stack's height when it began.
+    height: usize,
+    /// An instruction that does not fall through has run, after which
+    /// the stack takes any operands.
+    unreachable: bool,
+}
+
+struct Validator<'a> {
+    module: &'a Module,
+    locals: Vec<ValType>,
+    results: Vec<ValType>,
+    /// None is an operand of unknown type, popped from the stack of
+    /// unreachable code.
+    stack: Vec<Option<ValType>>,
+    controls: Vec<Control>,
+}
+
+fn block_results(ty: BlockType) -> Vec<ValType> {
+    match ty {
+        BlockType::Empty => vec![],
+        BlockType::Value(ty) => vec![ty],
+    }
+}
+
+impl<'a> Validator<'a> {
+    fn new(module: &'a Module, ty: &FuncType, func: &Func) -> Validator<'a> {
+        let mut locals = ty.params.clone();
+        locals.extend(&func.locals);
+        Validator { module, locals, results: ty.results.clone(), stack: vec![], controls: vec![] }
+    }
+
+    fn body(mut self, body: &[Instr]) -> Result<(), (usize, String)> {
+        self.controls.push(Control { is_loop: false, is_if: false, results: self.results.clone(), height: 0, unreachable: false });
+        for (at, instr) in body.iter().enumerate() {
+            if self.controls.is_empty() {
+                return Err((at, "instruction after the end of the function".to_string()));
+            }
+            self.instr(instr).map_err(|msg| (at, msg))?;
+        }
+        if !self.controls.is_empty() {
+            return Err((body.len(), "function body without end".to_string()));
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, ty: ValType) {
+        self.stack.push(Some(ty));
+    }
+
+    fn pop_any(&mut self) -> Result<Option<ValType>, String> {
+        let control = self.controls.last().unwrap();
+        if self.stack.len() == control.height {
+            return match control.unreachable {
+                true => Ok(None),
+                false => Err("not enough operands on the stack".to_string()),
+            };
+        }
+        Ok(self.stack.pop().unwrap())
+    }
+
+    fn pop(&mut self, want: ValType) -> Result<(), String> {
+        match self.pop_any()? {
+            Some(got) if got != want => Err(format!("expected {} operand, found {}", want.name(), got.name())),
+            _ => Ok(()),
+        }
+    }
+
+    fn pop_all(&mut self, types: &[ValType]) -> Result<(), String> {
+        types.iter().rev().try_for_each(|&ty| self.pop(ty))
+    }
+
+    fn unreachable(&mut self) {
+        let control = self.controls.last_mut().unwrap();
+        self.stack.truncate(control.height);
+        control.unreachable = true;
+    }
+
+    /// What a branch to label `depth` carries.
+    fn label(&self, depth: u32) -> Result<Vec<ValType>, String> {
+        let control = self.controls.iter().rev().nth(depth as usize).ok_or_else(|| format!("unknown label {}", depth))?;
+        Ok(if control.is_loop { vec![] } else { control.results.clone() })
+    }
+
+    fn local(&self, index: u32) -> Result<ValType, String> {
+        self.locals.get(index as usize).copied().ok_or_else(|| format!("unknown local {}", index))
+    }
+
+    fn global(&self, index: u32) -> Result<&Global, String> {
+        self.module.globals.get(index as usize).ok_or_else(|| format!("unknown global {}", index))
+    }
+
+    fn memory(&self) -> Result<(), String> {
+        self.module.memory.map(|_| ()).ok_or_else(|| "memory instruction without a memory".to_string())
+    }
+
+    fn call(&mut self, ty: &FuncType) -> Result<(), String> {
+        self.pop_all(&ty.params)?;
+        ty.results.iter().for_each(|&ty| self.push(ty));
+        Ok(())
+    }
+
+    fn begin(&mut self, ty: BlockType, is_loop: bool, is_if: bool) {
+        let height = self.stack.len();
+        self.controls.push(Control { is_loop, is_if, results: block_results(ty), height, unreachable: false });
+    }
+
+    /// Checks the block about to end or turn to its else branch leaves
+    /// its results.
+    fn finish(&mut self) -> Result<(), String> {
+        let results = self.controls.last().unwrap().results.clone();
+        self.pop_all(&results)?;
+        if self.stack.len() != self.controls.last().unwrap().height {
+            return Err("values left on the stack at the end of a block".to_string());
+        }
+        Ok(())
+    }
+
+    fn instr(&mut self, instr: &Instr) -> Result<(), String> {
+        use ValType::*;
+        match instr {
+            Instr::Unreachable => self.unreachable(),
+            Instr::Nop => {}
+            Instr::Block(ty) => self.begin(*ty, false, false),
+            Instr::Loop(ty) => self.begin(*ty, true, false),
+            Instr::If(ty) => {
+                self.pop(I32)?;
+                self.begin(*ty, false, true);
+            }
+            Instr::Else => {
+                if !self.controls.last().unwrap().is_if {
+                    return Err("else outside an if".to_string());
+                }
+                self.finish()?;
+                let control = self.controls.last_mut().unwrap();
+                control.is_if = false;
+                control.unreachable = false;
+            }
+            Instr::End => {
+                self.finish()?;
+                let control = self.controls.pop().unwrap();
+                if control.is_if && !control.results.is_empty() {
+                    return Err("if with a result but no else".to_string());
+                }
+                control.results.iter().for_each(|&ty| self.push(ty));
+            }
+            Instr::Br(depth) => {
+                let types = self.label(*depth)?;
+                self.pop_all(&types)?;
+                self.unreachable();
+            }
+            Instr::BrIf(depth) => {
+                self.pop(I32)?;
+                let types = self.label(*depth)?;
+                self.pop_all(&types)?;
+                types.iter().for_each(|&ty| self.push(ty));
+            }
+            Instr::BrTable(depths, default) => {
+                self.pop(I32)?;
+                let types = self.label(*default)?;
+                for &depth in depths {
+                    if self.label(depth)? != types {
+                        return Err("br_table targets of different types".to_string());
+                    }
+                }
+                self.pop_all(&types)?;
+                self.unreachable();
+            }
+            Instr::Return => {
+                let results = self.results.clone();
+                self.pop_all(&results)?;
+                self.unreachable();
+            }
+            Instr::Call(func) => {
+                let ty = self.module.func_type(*func).ok_or_else(|| format!("call to unknown function {}", func))?;
+                self.call(&ty.clone())?;
+            }
+            Instr::CallIndirect(ty) => {
+                if self.module.table.is_none() {
+                    return Err("call_indirect without a table".to_string());
+                }
+                let ty = self.module.types.get(*ty as usize).ok_or_else(|| format!("call_indirect of unknown type {}", ty))?;
+                self.pop(I32)?;
+                self.call(&ty.clone())?;
+            }
+            Instr::Drop => {
+                self.pop_any()?;
+            }
+            Instr::Select => {
+                self.pop(I32)?;
+                let a = self.pop_any()?;
+                let b = self.pop_any()?;
+                match (a, b) {
+                    (Some(a), Some(b)) if a != b => return Err("select of operands of different types".to_string()),
+                    (Some(ty), _) | (_, Some(ty)) => self.push(ty),
+                    (None, None) => self.stack.push(None),
+                }
+            }
+            Instr::LocalGet(i) => {
+                let ty = self.local(*i)?;
+                self.push(ty);
+            }
+            Instr::LocalSet(i) => {
+                let ty = self.local(*i)?;
+                self.pop(ty)?;
+            }
+            Instr::LocalTee(i) => {
+                let ty = self.local(*i)?;
+                self.pop(ty)?;
+                self.push(ty);
+            }
+            Instr::GlobalGet(i) => {
+                let ty = self.global(*i)?.ty;
+                self.push(ty);
+            }
+            Instr::GlobalSet(i) => {
+                let global = self.global(*i)?;
+                if !global.mutable {
+                    return Err(format!("global {} is immutable", i));
+                }
+                let ty = global.ty;
+                self.pop(ty)?;
+            }
+            Instr::Load(op, arg) | Instr::Store(op, arg) => {
+                self.memory()?;
+                let (_, name, natural) = MEMORY_OPS.iter().find(|(code, _, _)| code == op).ok_or("unknown memory instruction")?;
+                if arg.align > *natural {
+                    return Err(format!("{} alignment larger than natural", name));
+                }
+                let ty = ValType::from_name(&name[..3]).unwrap();
+                if let Instr::Load(..) = instr {
+                    self.pop(I32)?;
+                    self.push(ty);
+                } else {
+                    self.pop(ty)?;
+                    self.pop(I32)?;
+                }
+            }
+            Instr::MemorySize => {
+                self.memory()?;
+                self.push(I32);
+            }
+            Instr::MemoryGrow => {
+                self.memory()?;
+                self.pop(I32)?;
+                self.push(I32);
+            }
+            Instr::MemoryCopy | Instr::MemoryFill => {
+                self.memory()?;
+                self.pop_all(&[I32, I32, I32])?;
+            }
+            Instr::I32Const(_) => self.push(I32),
+            Instr::I64Const(_) => self.push(I64),
+            Instr::F64Const(_) => self.push(F64),
+            Instr::Op(op) => {
+                let (params, result) = signature(op_name(*op)).ok_or("unknown numeric instruction")?;
+                self.call(&FuncType { params, results: vec![result] })?;
+            }
+            Instr::TruncSat(op) => {
+                let name = TRUNC_SAT_OPS.iter().find(|(code, _)| code == op).map(|(_, name)| *name);
+                let (params, result) = name.and_then(signature).ok_or("unknown saturating truncation")?;
+                self.call(&FuncType { params, results: vec![result] })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The operand and result types of a numeric instruction, by its name:
+/// conversions name the type they convert from after the operation, as
+/// in `i32.wrap_i64`, and the rest work on the type they are prefixed
+/// with.
+fn signature(name: &str) -> Option<(Vec<ValType>, ValType)> {
+    let (ty, op) = name.split_at(name.find('.')?);
+    let (ty, op) = (ValType::from_name(ty)?, &op[1..]);
+    if let Some(from) = op.split('_').skip(1).find_map(ValType::from_name) {
+        return Some((vec![from], ty));
+    }
+    let base = op.split('_').next().unwrap_or(op);
+    Some(match base {
+        "eqz" => (vec![ty], ValType::I32),
+        "eq" | "ne" | "lt" | "gt" | "le" | "ge" => (vec![ty, ty], ValType::I32),
+        "clz" | "ctz" | "popcnt" | "abs" | "neg" | "ceil" | "floor" | "trunc" | "nearest" | "sqrt" | "extend8" | "extend16"
+        | "extend32" => (vec![ty], ty),
+        _ => (vec![ty, ty], ty),
+    })
+}
@@ -0,0 +1,738 @@
+//! A WebAssembly interpreter, to run what the backend produces without
+//! an engine outside the crate.
+//!
+//! Functions run on one value stack of raw 64-bit cells, `i32` values
+//! zero-extended and `f64` values by their bits, with the frames of the
+//! calls in progress kept in a vector rather than on the Rust stack.
+//! Before a function first runs, a pass over its body pairs every block
+//! with its `end` and every `if` with its `else`, so branches jump
+//! directly.
+
+use super::module::*;
+use super::validate::validate;
+use std::fmt;
+use std::rc::Rc;
+
+const PAGE: usize = 65536;
+/// The most pages memory grows to, 1GB.
+const MAX_PAGES: usize = 16384;
+const MAX_FRAMES: usize = 100_000;
+
+/// Why a call did not return normally.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trap {
+    /// The program asked to exit with the status.
+    Exit(i32),
+    Error(String),
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trap::Exit(code) => write!(f, "exit status {}", code),
+            Trap::Error(msg) => write!(f, "wasm trap: {}", msg),
+        }
+    }
+}
+
+fn trap<T>(msg: &str) -> Result<T, Trap> {
+    Err(Trap::Error(msg.to_string()))
+}
+
+/// What the module imports, provided by whoever runs it.
+pub trait Host {
+    fn call(&mut self, import: &Import, args: &[u64], memory: &mut Vec<u8>) -> Result<Vec<u64>, Trap>;
+}
+
+/// A function body ready to run.
+struct Code {
+    params: usize,
+    results: usize,
+    locals: usize,
+    body: Vec<Instr>,
+    /// For each block, loop and if: where its `end` is. For each `if`
+    /// with an else branch: where `else` is, in `elses`.
+    ends: Vec<u32>,
+    elses: Vec<u32>,
+}
+
+struct Frame {
+    func: usize,
+    pc: usize,
+    locals: usize,
+    labels: usize,
+    stack: usize,
+}
+
+struct Label {
+    /// Where a branch to the label goes.
+    target: usize,
+    /// Values a branch carries: a block's results; none for a loop.
+    arity: usize,
+    height: usize,
+    is_loop: bool,
+}
+
+pub struct Instance {
+    module: Rc<Module>,
+    code: Rc<Vec<Code>>,
+    pub memory: Vec<u8>,
+    pub globals: Vec<u64>,
+    stack: Vec<u64>,
+    locals: Vec<u64>,
+    labels: Vec<Label>,
+    frames: Vec<Frame>,
+}
+
+impl Instance {
+    /// Validates a module and sets up its memory, globals and table to
+    /// run it.
+    pub fn new(module: Module) -> Result<Instance, String> {
+        validate(&module)?;
+        let mut code = vec![];
+        for func in &module.funcs {
+            let ty = module.types.get(func.ty as usize).ok_or("function with an unknown type")?;
+            let (ends, elses) = match_blocks(&func.body)?;
+            code.push(Code {
+                params: ty.params.len(),
+                results: ty.results.len(),
+                locals: func.locals.len(),
+                body: func.body.clone(),
+                ends,
+                elses,
+            });
+        }
+        let pages = module.memory.unwrap_or(0) as usize;
+        let mut memory = vec![0; pages * PAGE];
+        for data in &module.data {
+            let start = data.offset as usize;
+            let end = start + data.bytes.len();
+            if end > memory.len() {
+                return Err("data segment does not fit in memory".to_string());
+            }
+            memory[start..end].copy_from_slice(&data.bytes);
+        }
+        let globals = module
+            .globals
+            .iter()
+            .map(|g| match g.init {
+                Instr::I32Const(n) => n as u32 as u64,
+                Instr::I64Const(n) => n as u64,
+                Instr::F64Const(x) => x.to_bits(),
+                _ => 0,
+            })
+            .collect();
+        let functions = module.imports.len() + module.funcs.len();
+        if module.elems.iter().any(|&f| f as usize >= functions) {
+            return Err("table element names an unknown function".to_string());
+        }
+        Ok(Instance {
+            module: Rc::new(module),
+            code: Rc::new(code),
+            memory,
+            globals,
+            stack: vec![],
+            locals: vec![],
+            labels: vec![],
+            frames: vec![],
+        })
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// Calls the exported function with the arguments, giving its
+    /// results.
+    pub fn invoke(&mut self, host: &mut dyn Host, name: &str, args: &[u64]) -> Result<Vec<u64>, Trap> {
+        let func = match self.module.export(name, ExportKind::Func) {
+            Some(func) => func,
+            None => return trap(&format!("no exported function {}", name)),
+        };
+        self.call(host, func, args)
+    }
+
+    /// Calls the function by its index, imports first.
+    pub fn call(&mut self, host: &mut dyn Host, func: u32, args: &[u64]) -> Result<Vec<u64>, Trap> {
+        self.stack.clear();
+        self.locals.clear();
+        self.labels.clear();
+        self.frames.clear();
+        self.stack.extend_from_slice(args);
+        let imports = self.module.imports.len();
+        if (func as usize) < imports {
+            let module = Rc::clone(&self.module);
+            return host.call(&module.imports[func as usize], args, &mut self.memory);
+        }
+        self.enter(func as usize - imports)?;
+        let result = self.execute(host);
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+        }
+        result?;
+        Ok(std::mem::take(&mut self.stack))
+    }
+
+    /// Pushes a frame for the defined function, taking its arguments off
+    /// the stack.
+    fn enter(&mut self, func: usize) -> Result<(), Trap> {
+        if self.frames.len() >= MAX_FRAMES {
+            return trap("call stack exhausted");
+        }
+        let code = &self.code[func];
+        if self.stack.len() < code.params {
+            return trap("not enough arguments");
+        }
+        let base = self.stack.len() - code.params;
+        let locals = self.locals.len();
+        self.locals.extend_from_slice(&self.stack[base..]);
+        self.locals.resize(locals + code.params + code.locals, 0);
+        self.stack.truncate(base);
+        self.frames.push(Frame { func, pc: 0, locals, labels: self.labels.len(), stack: base });
+        Ok(())
+    }
+
+    fn execute(&mut self, host: &mut dyn Host) -> Result<(), Trap> {
+        let code = Rc::clone(&self.code);
+        let module = Rc::clone(&self.module);
+        let imports = module.imports.len();
+        let frame = self.frames.last().unwrap();
+        let mut f = &code[frame.func];
+        let mut pc = frame.pc;
+        let mut locals = frame.locals;
+        macro_rules! pop {
+            () => {
+                self.stack.pop().unwrap_or(0)
+            };
+        }
+        macro_rules! push {
+            ($v:expr) => {{
+                let v = $v;
+                self.stack.push(v)
+            }};
+        }
+        macro_rules! unop {
+            ($t:ty, $from:ident, $to:ident, $f:expr) => {{
+                let a = $from(pop!());
+                let op: fn($t) -> _ = $f;
+                push!($to(op(a)));
+            }};
+        }
+        macro_rules! binop {
+            ($t:ty, $from:ident, $to:ident, $f:expr) => {{
+                let b = $from(pop!());
+                let a = $from(pop!());
+                let op: fn($t, $t) -> _ = $f;
+                push!($to(op(a, b)));
+            }};
+        }
+        macro_rules! checked {
+            ($t:ty, $from:ident, $to:ident, $f:expr) => {{
+                let b = $from(pop!());
+                let a = $from(pop!());
+                let op: fn($t, $t) -> Result<_, Trap> = $f;
+                push!($to(op(a, b)?));
+            }};
+        }
+        loop {
+            let instr = &f.body[pc];
+            pc += 1;
+            match instr {
+                Instr::Unreachable => return trap("unreachable"),
+                Instr::Nop => {}
+                Instr::Block(ty) | Instr::Loop(ty) => {
+                    let is_loop = matches!(instr, Instr::Loop(_));
+                    let start = pc - 1;
+                    self.labels.push(Label {
+                        target: if is_loop { start + 1 } else { f.ends[start] as usize + 1 },
+                        arity: if is_loop { 0 } else { arity(ty) },
+                        height: self.stack.len(),
+                        is_loop,
+                    });
+                }
+                Instr::If(ty) => {
+                    let start = pc - 1;
+                    let cond = pop!() as u32;
+                    self.labels.push(Label {
+                        target: f.ends[start] as usize + 1,
+                        arity: arity(ty),
+                        height: self.stack.len(),
+                        is_loop: false,
+                    });
+                    if cond == 0 {
+                        match f.elses[start] {
+                            u32::MAX => {
+                                self.labels.pop();
+                                pc = f.ends[start] as usize + 1;
+                            }
+                            e => pc = e as usize + 1,
+                        }
+                    }
+                }
+                Instr::Else => {
+                    // the end of the then branch: skip the else branch
+                    let label = self.labels.pop().unwrap();
+                    pc = label.target;
+                }
+                Instr::End => {
+                    let frame = self.frames.last().unwrap();
+                    if self.labels.len() > frame.labels {
+                        self.labels.pop();
+                    } else {
+                        // the end of the function
+                        self.ret(f.results);
+                        match self.frames.last() {
+                            Some(frame) => {
+                                f = &code[frame.func];
+                                pc = frame.pc;
+                                locals = frame.locals;
+                            }
+                            None => return Ok(()),
+                        }
+                    }
+                }
+                Instr::Br(depth) => pc = self.branch(*depth as usize),
+                Instr::BrIf(depth) => {
+                    if pop!() as u32 != 0 {
+                        pc = self.branch(*depth as usize);
+                    }
+                }
+                Instr::BrTable(labels, default) => {
+                    let i = pop!() as u32 as usize;
+                    let depth = labels.get(i).unwrap_or(default);
+                    pc = self.branch(*depth as usize);
+                }
+                Instr::Return => {
+                    self.ret(f.results);
+                    match self.frames.last() {
+                        Some(frame) => {
+                            f = &code[frame.func];
+                            pc = frame.pc;
+                            locals = frame.locals;
+                        }
+                        None => return Ok(()),
+                    }
+                }
+                Instr::Call(_) | Instr::CallIndirect(_) => {
+                    let callee = match instr {
+                        Instr::Call(callee) => *callee as usize,
+                        Instr::CallIndirect(ty) => {
+                            let slot = pop!() as u32 as usize;
+                            let callee = match module.elems.get(slot) {
+                                Some(&callee) if slot < module.table.unwrap_or(0) as usize => callee,
+                                _ => return trap("undefined element"),
+                            };
+                            let want = module.types.get(*ty as usize);
+                            if want.is_none() || module.func_type(callee) != want {
+                                return trap("indirect call type mismatch");
+                            }
+                            callee as usize
+                        }
+                        _ => unreachable!(),
+                    };
+                    if callee < imports {
+                        let import = &module.imports[callee];
+                        let ty = &module.types[import.ty as usize];
+                        let base = self.stack.len().saturating_sub(ty.params.len());
+                        let args = self.stack.split_off(base);
+                        let results = host.call(import, &args, &mut self.memory)?;
+                        if results.len() != ty.results.len() {
+                            return trap(&format!("import {} returned the wrong number of results", import.name));
+                        }
+                        self.stack.extend(results);
+                    } else {
+                        self.frames.last_mut().unwrap().pc = pc;
+                        self.enter(callee - imports)?;
+                        let frame = self.frames.last().unwrap();
+                        f = &code[frame.func];
+                        pc = 0;
+                        locals = frame.locals;
+                    }
+                }
+                Instr::Drop => {
+                    pop!();
+                }
+                Instr::Select => {
+                    let c = pop!() as u32;
+                    let b = pop!();
+                    let a = pop!();
+                    push!(if c != 0 { a } else { b });
+                }
+                Instr::LocalGet(i) => push!(self.locals[locals + *i as usize]),
+                Instr::LocalSet(i) => self.locals[locals + *i as usize] = pop!(),
+                Instr::LocalTee(i) => self.locals[locals + *i as usize] = *self.stack.last().unwrap_or(&0),
+                Instr::GlobalGet(i) => push!(self.globals[*i as usize]),
+                Instr::GlobalSet(i) => self.globals[*i as usize] = pop!(),
+                Instr::Load(op, arg) => {
+                    let base = pop!() as u32;
+                    let v = self.load(*op, base, arg.offset)?;
+                    push!(v);
+                }
+                Instr::Store(op, arg) => {
+                    let v = pop!();
+                    let base = pop!() as u32;
+                    self.store(*op, base, arg.offset, v)?;
+                }
+                Instr::MemorySize => push!((self.memory.len() / PAGE) as u64),
+                Instr::MemoryGrow => {
+                    let n = pop!() as u32 as usize;
+                    let pages = self.memory.len() / PAGE;
+                    if pages + n > MAX_PAGES {
+                        push!(u32::MAX as u64);
+                    } else {
+                        self.memory.resize((pages + n) * PAGE, 0);
+                        push!(pages as u64);
+                    }
+                }
+                Instr::MemoryCopy => {
+                    let n = pop!() as u32 as usize;
+                    let src = pop!() as u32 as usize;
+                    let dst = pop!() as u32 as usize;
+                    if src + n > self.memory.len() || dst + n > self.memory.len() {
+                        return trap("out of bounds memory access");
+                    }
+                    self.memory.copy_within(src..src + n, dst);
+                }
+                Instr::MemoryFill => {
+                    let n = pop!() as u32 as usize;
+                    let value = pop!() as u8;
+                    let dst = pop!() as u32 as usize;
+                    if dst + n > self.memory.len() {
+                        return trap("out of bounds memory access");
+                    }
+                    self.memory[dst..dst + n].iter_mut().for_each(|b| *b = value);
+                }
+                Instr::I32Const(n) => push!(*n as u32 as u64),
+                Instr::I64Const(n) => push!(*n as u64),
+                Instr::F64Const(x) => push!(x.to_bits()),
+                Instr::TruncSat(op) => {
+                    let x = f64::from_bits(pop!());
+                    push!(match op {
+                        2 => x as i32 as u32 as u64,
+                        3 => x as u32 as u64,
+                        6 => x as i64 as u64,
+                        _ => x as u64,
+                    });
+                }
+                Instr::Op(op) => match op {
+                    0x45 => unop!(u32, u32_, bool_, |a| a == 0),
+                    0x46 => binop!(u32, u32_, bool_, |a, b| a == b),
+                    0x47 => binop!(u32, u32_, bool_, |a, b| a != b),
+                    0x48 => binop!(i32, i32_, bool_, |a, b| a < b),
+                    0x49 => binop!(u32, u32_, bool_, |a, b| a < b),
+                    0x4a => binop!(i32, i32_, bool_, |a, b| a > b),
+                    0x4b => binop!(u32, u32_, bool_, |a, b| a > b),
+                    0x4c => binop!(i32, i32_, bool_, |a, b| a <= b),
+                    0x4d => binop!(u32, u32_, bool_, |a, b| a <= b),
+                    0x4e => binop!(i32, i32_, bool_, |a, b| a >= b),
+                    0x4f => binop!(u32, u32_, bool_, |a, b| a >= b),
+                    0x50 => unop!(u64, u64_, bool_, |a| a == 0),
+                    0x51 => binop!(u64, u64_, bool_, |a, b| a == b),
+                    0x52 => binop!(u64, u64_, bool_, |a, b| a != b),
+                    0x53 => binop!(i64, i64_, bool_, |a, b| a < b),
+                    0x54 => binop!(u64, u64_, bool_, |a, b| a < b),
+                    0x55 => binop!(i64, i64_, bool_, |a, b| a > b),
+                    0x56 => binop!(u64, u64_, bool_, |a, b| a > b),
+                    0x57 => binop!(i64, i64_, bool_, |a, b| a <= b),
+                    0x58 => binop!(u64, u64_, bool_, |a, b| a <= b),
+                    0x59 => binop!(i64, i64_, bool_, |a, b| a >= b),
+                    0x5a => binop!(u64, u64_, bool_, |a, b| a >= b),
+                    0x61 => binop!(f64, f64_, bool_, |a, b| a == b),
+                    0x62 => binop!(f64, f64_, bool_, |a, b| a != b),
+                    0x63 => binop!(f64, f64_, bool_, |a, b| a < b),
+                    0x64 => binop!(f64, f64_, bool_, |a, b| a > b),
+                    0x65 => binop!(f64, f64_, bool_, |a, b| a <= b),
+                    0x66 => binop!(f64, f64_, bool_, |a, b| a >= b),
+                    0x67 => unop!(u32, u32_, from_u32, |a| a.leading_zeros()),
+                    0x68 => unop!(u32, u32_, from_u32, |a| a.trailing_zeros()),
+                    0x69 => unop!(u32, u32_, from_u32, |a| a.count_ones()),
+                    0x6a => binop!(u32, u32_, from_u32, |a, b| a.wrapping_add(b)),
+                    0x6b => binop!(u32, u32_, from_u32, |a, b| a.wrapping_sub(b)),
+                    0x6c => binop!(u32, u32_, from_u32, |a, b| a.wrapping_mul(b)),
+                    0x6d => checked!(i32, i32_, from_i32, |a, b| match (a, b) {
+                        (_, 0) => trap("integer divide by zero"),
+                        (i32::MIN, -1) => trap("integer overflow"),
+                        _ => Ok(a / b),
+                    }),
+                    0x6e => checked!(u32, u32_, from_u32, |a, b| a.checked_div(b).map_or_else(|| trap("integer divide by zero"), Ok)),
+                    0x6f => checked!(i32, i32_, from_i32, |a, b| match b {
+                        0 => trap("integer divide by zero"),
+                        _ => Ok(a.wrapping_rem(b)),
+                    }),
+                    0x70 => checked!(u32, u32_, from_u32, |a, b| a.checked_rem(b).map_or_else(|| trap("integer divide by zero"), Ok)),
+                    0x71 => binop!(u32, u32_, from_u32, |a, b| a & b),
+                    0x72 => binop!(u32, u32_, from_u32, |a, b| a | b),
+                    0x73 => binop!(u32, u32_, from_u32, |a, b| a ^ b),
+                    0x74 => binop!(u32, u32_, from_u32, |a, b| a.wrapping_shl(b)),
+                    0x75 => binop!(i32, i32_, from_i32, |a, b| a.wrapping_shr(b as u32)),
+                    0x76 => binop!(u32, u32_, from_u32, |a, b| a.wrapping_shr(b)),
+                    0x77 => binop!(u32, u32_, from_u32, |a, b| a.rotate_left(b % 32)),
+                    0x78 => binop!(u32, u32_, from_u32, |a, b| a.rotate_right(b % 32)),
+                    0x79 => unop!(u64, u64_, from_u64, |a| a.leading_zeros() as u64),
+                    0x7a => unop!(u64, u64_, from_u64, |a| a.trailing_zeros() as u64),
+                    0x7b => unop!(u64, u64_, from_u64, |a| a.count_ones() as u64),
+                    0x7c => binop!(u64, u64_, from_u64, |a, b| a.wrapping_add(b)),
+                    0x7d => binop!(u64, u64_, from_u64, |a, b| a.wrapping_sub(b)),
+                    0x7e => binop!(u64, u64_, from_u64, |a, b| a.wrapping_mul(b)),
+                    0x7f => checked!(i64, i64_, from_i64, |a, b| match (a, b) {
+                        (_, 0) => trap("integer divide by zero"),
+                        (i64::MIN, -1) => trap("integer overflow"),
+                        _ => Ok(a / b),
+                    }),
+                    0x80 => checked!(u64, u64_, from_u64, |a, b| a.checked_div(b).map_or_else(|| trap("integer divide by zero"), Ok)),
+                    0x81 => checked!(i64, i64_, from_i64, |a, b| match b {
+                        0 => trap("integer divide by zero"),
+                        _ => Ok(a.wrapping_rem(b)),
+                    }),
+                    0x82 => checked!(u64, u64_, from_u64, |a, b| a.checked_rem(b).map_or_else(|| trap("integer divide by zero"), Ok)),
+                    0x83 => binop!(u64, u64_, from_u64, |a, b| a & b),
+                    0x84 => binop!(u64, u64_, from_u64, |a, b| a | b),
+                    0x85 => binop!(u64, u64_, from_u64, |a, b| a ^ b),
+                    0x86 => binop!(u64, u64_, from_u64, |a, b| a.wrapping_shl(b as u32)),
+                    0x87 => binop!(i64, i64_, from_i64, |a, b| a.wrapping_shr(b as u32)),
+                    0x88 => binop!(u64, u64_, from_u64, |a, b| a.wrapping_shr(b as u32)),
+                    0x89 => binop!(u64, u64_, from_u64, |a, b| a.rotate_left((b % 64) as u32)),
+                    0x8a => binop!(u64, u64_, from_u64, |a, b| a.rotate_right((b % 64) as u32)),
+                    0x99 => unop!(f64, f64_, from_f64, |a| a.abs()),
+                    0x9a => unop!(f64, f64_, from_f64, |a| -a),
+                    0x9b => unop!(f64, f64_, from_f64, |a| a.ceil()),
+                    0x9c => unop!(f64, f64_, from_f64, |a| a.floor()),
+                    0x9d => unop!(f64, f64_, from_f64, |a| a.trunc()),
+                    0x9e => unop!(f64, f64_, from_f64, nearest),
+                    0x9f => unop!(f64, f64_, from_f64, |a| a.sqrt()),
+                    0xa0 => binop!(f64, f64_, from_f64, |a, b| a + b),
+                    0xa1 => binop!(f64, f64_, from_f64, |a, b| a - b),
+                    0xa2 => binop!(f64, f64_, from_f64, |a, b| a * b),
+                    0xa3 => binop!(f64, f64_, from_f64, |a, b| a / b),
+                    0xa4 => binop!(f64, f64_, from_f64, fmin),
+                    0xa5 => binop!(f64, f64_, from_f64, fmax),
+                    0xa6 => binop!(f64, f64_, from_f64, |a, b| a.copysign(b)),
+                    0xa7 => unop!(u64, u64_, from_u32, |a| a as u32),
+                    0xaa => {
+                        let x = f64_(pop!());
+                        push!(from_i32(truncate(x, -2147483649.0, 2147483648.0)? as i32));
+                    }
+                    0xab => {
+                        let x = f64_(pop!());
+                        push!(from_u32(truncate(x, -1.0, 4294967296.0)? as u32));
+                    }
+                    0xac => unop!(i32, i32_, from_i64, |a| a as i64),
+                    0xad => unop!(u32, u32_, from_u64, |a| a as u64),
+                    0xb0 => {
+                        let x = f64_(pop!());
+                        push!(from_i64(truncate(x, -9223372036854777856.0, 9223372036854775808.0)? as i64));
+                    }
+                    0xb1 => {
+                        let x = f64_(pop!());
+                        push!(from_u64(truncate(x, -1.0, 18446744073709551616.0)? as u64));
+                    }
+                    0xb7 => unop!(i32, i32_, from_f64, |a| a as f64),
+                    0xb8 => unop!(u32, u32_, from_f64, |a| a as f64),
+                    0xb9 => unop!(i64, i64_, from_f64, |a| a as f64),
+                    0xba => unop!(u64, u64_, from_f64, |a| a as f64),
+                    // the bits are the same
+                    0xbd | 0xbf => {}
+                    0xc0 => unop!(u32, u32_, from_i32, |a| a as i8 as i32),
+                    0xc1 => unop!(u32, u32_, from_i32, |a| a as i16 as i32),
+                    0xc2 => unop!(u64, u64_, from_i64, |a| a as i8 as i64),
+                    0xc3 => unop!(u64, u64_, from_i64, |a| a as i16 as i64),
+                    0xc4 => unop!(u64, u64_, from_i64, |a| a as i32 as i64),
+                    _ => return trap(&format!("unsupported instruction {}", op_name(*op))),
+                },
+            }
+        }
+    }
+
+    /// Branches to the label `depth` blocks out, returning where to
+    /// continue.
+    fn branch(&mut self, depth: usize) -> usize {
+        let index = self.labels.len() - 1 - depth;
+        let label = &self.labels[index];
+        let (target, arity, height, is_loop) = (label.target, label.arity, label.height, label.is_loop);
+        let from = self.stack.len() - arity;
+        self.stack.drain(height..from);
+        self.labels.truncate(if is_loop { index + 1 } else { index });
+        target
+    }
+
+    /// Returns from the innermost frame with its results.
+    fn ret(&mut self, results: usize) {
+        let frame = self.frames.pop().unwrap();
+        let from = self.stack.len() - results;
+        self.stack.drain(frame.stack..from);
+        self.locals.truncate(frame.locals);
+        self.labels.truncate(frame.labels);
+    }
+
+    fn address(&self, base: u32, offset: u32, size: usize) -> Result<usize, Trap> {
+        let addr = base as usize + offset as usize;
+        if addr + size > self.memory.len() {
+            return trap("out of bounds memory access");
+        }
+        Ok(addr)
+    }
+
+    fn load(&self, op: u8, base: u32, offset: u32) -> Result<u64, Trap> {
+        let size = match op {
+            0x29 | 0x2b => 8,
+            0x28 | 0x34 | 0x35 => 4,
+            0x2e | 0x2f | 0x32 | 0x33 => 2,
+            _ => 1,
+        };
+        let addr = self.address(base, offset, size)?;
+        let mut bytes = [0; 8];
+        bytes[..size].copy_from_slice(&self.memory[addr..addr + size]);
+        let raw = u64::from_le_bytes(bytes);
+        Ok(match op {
+            0x2c => raw as i8 as i32 as u32 as u64,
+            0x2e => raw as i16 as i32 as u32 as u64,
+            0x30 => raw as i8 as i64 as u64,
+            0x32 => raw as i16 as i64 as u64,
+            0x34 => raw as i32 as i64 as u64,
+            _ => raw,
+        })
+    }
+
+    fn store(&mut self, op: u8, base: u32, offset: u32, value: u64) -> Result<(), Trap> {
+        let size = match op {
+            0x37 | 0x39 => 8,
+            0x36 | 0x3e => 4,
+            0x3b | 0x3d => 2,
+            _ => 1,
+        };
+        let addr = self.address(base, offset, size)?;
+        self.memory[addr..addr + size].copy_from_slice(&value.to_le_bytes()[..size]);
+        Ok(())
+    }
+}
+
+/// Pairs blocks with their ends and ifs with their elses.
+fn match_blocks(body: &[Instr]) -> Result<(Vec<u32>, Vec<u32>), String> {
+    let mut ends = vec![0; body.len()];
+    let mut elses = vec![u32::MAX; body.len()];
+    let mut open = vec![];
+    for (i, instr) in body.iter().enumerate() {
+        match instr {
+            Instr::Block(_) | Instr::Loop(_) | Instr::If(_) => open.push(i),
+            Instr::Else => match open.last() {
+                Some(&start) if matches!(body[start], Instr::If(_)) => elses[start] = i as u32,
+                _ => return Err("else outside an if".to_string()),
+            },
+            Instr::End => {
+                if let Some(start) = open.pop() {
+                    ends[start] = i as u32;
+                } else if i != body.len() - 1 {
+                    return Err("end outside a block".to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    if !open.is_empty() || !matches!(body.last(), Some(Instr::End)) {
+        return Err("function body without end".to_string());
+    }
+    Ok((ends, elses))
+}
+
+fn arity(ty: &BlockType) -> usize {
+    match ty {
+        BlockType::Empty => 0,
+        BlockType::Value(_) => 1,
+    }
+}
+
+fn u32_(v: u64) -> u32 {
+    v as u32
+}
+
+fn i32_(v: u64) -> i32 {
+    v as u32 as i32
+}
+
+fn u64_(v: u64) -> u64 {
+    v
+}
+
+fn i64_(v: u64) -> i64 {
+    v as i64
+}
+
+fn f64_(v: u64) -> f64 {
+    f64::from_bits(v)
+}
+
+fn bool_(b: bool) -> u64 {
+    b as u64
+}
+
+fn from_u32(v: u32) -> u64 {
+    v as u64
+}
+
+fn from_i32(v: i32) -> u64 {
+    v as u32 as u64
+}
+
+fn from_u64(v: u64) -> u64 {
+    v
+}
+
+fn from_i64(v: i64) -> u64 {
+    v as u64
+}
+
+fn from_f64(v: f64) -> u64 {
+    v.to_bits()
+}
+
+/// The value of a float truncated toward zero, trapping unless it lies
+/// strictly between the bounds.
+fn truncate(x: f64, low: f64, high: f64) -> Result<f64, Trap> {
+    if x.is_nan() {
+        return trap("invalid conversion to integer");
+    }
+    if x <= low || x >= high {
+        return trap("integer overflow");
+    }
+    Ok(x.trunc())
+}
+
+/// Rounds to the nearest integer, ties to even.
+fn nearest(x: f64) -> f64 {
+    let r = x.round();
+    if (x - x.trunc()).abs() == 0.5 && r % 2.0 != 0.0 {
+        r - x.signum()
+    } else {
+        r
+    }
+}
+
+fn fmin(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if a == b {
+        // -0 is less than 0
+        if a.is_sign_negative() {
+            a
+        } else {
+            b
+        }
+    } else {
+        a.min(b)
+    }
+}
+
+fn fmax(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if a == b {
+        if a.is_sign_positive() {
+            a
+        } else {
+            b
+        }
+    } else {
+        a.max(b)
+    }
+}
@@ -0,0 +1,807 @@
+//! An assembler for the WebAssembly text format, as much of it as the
+//! backend writes: module fields in their usual forms, and instructions
+//! in the flat form, one after another, or folded around their operands.
+//! Functions, locals, globals, types and block labels may be named with
+//! `$names`.
+
+use super::module::*;
+use std::collections::HashMap;
+
+/// Assembles a module from its text.
+pub fn parse(text: &str) -> Result<Module, String> {
+    let tokens = tokenize(text)?;
+    let mut pos = 0;
+    let sexp = read(&tokens, &mut pos)?;
+    if pos != tokens.len() {
+        return Err(format!("line {}: text after the module", tokens[pos].1));
+    }
+    let fields = match &sexp {
+        Sexp::List(items, _) if matches!(items.first(), Some(Sexp::Atom(a, _)) if a == "module") => &items[1..],
+        _ => return Err("expected (module ...)".to_string()),
+    };
+    Assembler::default().module(fields)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Atom(String),
+    Str(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+enum Sexp {
+    List(Vec<Sexp>, usize),
+    Atom(String, usize),
+    Str(Vec<u8>, usize),
+}
+
+impl Sexp {
+    fn line(&self) -> usize {
+        match self {
+            Sexp::List(_, line) | Sexp::Atom(_, line) | Sexp::Str(_, line) => *line,
+        }
+    }
+
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexp::Atom(a, _) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// The items of a list that starts with the keyword.
+    fn form(&self, keyword: &str) -> Option<&[Sexp]> {
+        match self {
+            Sexp::List(items, _) if items.first().and_then(Sexp::atom) == Some(keyword) => Some(&items[1..]),
+            _ => None,
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let bytes = text.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+    let mut line = 1;
+    while i < bytes.len() {
+        let c = bytes[i];
+        match c {
+            b'\n' => {
+                line += 1;
+                i += 1;
+            }
+            b' ' | b'\t' | b'\r' => i += 1,
+            b';' if bytes.get(i + 1) == Some(&b';') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'(' if bytes.get(i + 1) == Some(&b';') => {
+                let mut depth = 0;
+                loop {
+                    if i + 1 >= bytes.len() {
+                        return Err(format!("line {}: unterminated comment", line));
+                    }
+                    if bytes[i] == b'(' && bytes[i + 1] == b';' {
+                        depth += 1;
+                        i += 2;
+                    } else if bytes[i] == b';' && bytes[i + 1] == b')' {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        if bytes[i] == b'\n' {
+                            line += 1;
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            b'(' => {
+                tokens.push((Token::Open, line));
+                i += 1;
+            }
+            b')' => {
+                tokens.push((Token::Close, line));
+                i += 1;
+            }
+            b'"' => {
+                i += 1;
+                let mut s = vec![];
+                loop {
+                    let c = *bytes.get(i).ok_or_else(|| format!("line {}: unterminated string", line))?;
+                    i += 1;
+                    match c {
+                        b'"' => break,
+                        b'\n' => return Err(format!("line {}: newline in string", line)),
+                        b'\\' => {
+                            let e = *bytes.get(i).ok_or_else(|| format!("line {}: unterminated string", line))?;
+                            i += 1;
+                            match e {
+                                b'n' => s.push(b'\n'),
+                                b't' => s.push(b'\t'),
+                                b'r' => s.push(b'\r'),
+                                b'"' => s.push(b'"'),
+                                b'\'' => s.push(b'\''),
+                                b'\\' => s.push(b'\\'),
+                                _ => {
+                                    let hex = |b: u8| (b as char).to_digit(16);
+                                    match (hex(e), bytes.get(i).and_then(|&b| hex(b))) {
+                                        (Some(h), Some(l)) => {
+                                            s.push((h * 16 + l) as u8);
+                                            i += 1;
+                                        }
+                                        _ => return Err(format!("line {}: bad escape in string", line)),
+                                    }
+                                }
+                            }
+                        }
+                        _ => s.push(c),
+                    }
+                }
+                tokens.push((Token::Str(s), line));
+            }
+            _ => {
+                let start = i;
+                while i < bytes.len() && !matches!(bytes[i], b' ' | b'\t' | b'\r' | b'\n' | b'(' | b')' | b'"' | b';') {
+                    i += 1;
+                }
+                tokens.push((Token::Atom(text[start..i].to_string()), line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn read(tokens: &[(Token, usize)], pos: &mut usize) -> Result<Sexp, String> {
+    let (token, line) = tokens.get(*pos).ok_or("unexpected end of text")?;
+    *pos += 1;
+    match token {
+        Token::Open => {
+            let mut items = vec![];
+            loop {
+                match tokens.get(*pos) {
+                    None => return Err(format!("line {}: unclosed parenthesis", line)),
+                    Some((Token::Close, _)) => {
+                        *pos += 1;
+                        return Ok(Sexp::List(items, *line));
+                    }
+                    Some(_) => items.push(read(tokens, pos)?),
+                }
+            }
+        }
+        Token::Close => Err(format!("line {}: unexpected )", line)),
+        Token::Atom(a) => Ok(Sexp::Atom(a.clone(), *line)),
+        Token::Str(s) => Ok(Sexp::Str(s.clone(), *line)),
+    }
+}
+
+fn error<T>(at: &Sexp, msg: impl Into<String>) -> Result<T, String> {
+    Err(format!("line {}: {}", at.line(), msg.into()))
+}
+
+/// Parses an integer literal: decimal or `0x` hexadecimal, with an
+/// optional sign and `_` separators, wrapping to 64 bits.
+fn int(text: &str) -> Option<i64> {
+    let text = text.replace('_', "");
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(&text)),
+    };
+    let n = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<u64>().ok()?,
+    };
+    Some(if negative { (n as i64).wrapping_neg() } else { n as i64 })
+}
+
+fn float(text: &str) -> Option<f64> {
+    let text = text.replace('_', "");
+    match text.as_str() {
+        "inf" | "+inf" => Some(f64::INFINITY),
+        "-inf" => Some(f64::NEG_INFINITY),
+        "nan" | "+nan" => Some(f64::NAN),
+        "-nan" => Some(-f64::NAN),
+        _ if text.contains("0x") => int(&text).map(|n| n as f64),
+        _ => text.parse().ok(),
+    }
+}
+
+#[derive(Default)]
+struct Assembler {
+    module: Module,
+    types: HashMap<String, u32>,
+    funcs: HashMap<String, u32>,
+    globals: HashMap<String, u32>,
+}
+
+/// The names in scope inside a function body.
+struct Body<'a> {
+    locals: HashMap<String, u32>,
+    /// Labels of the blocks enclosing the current instruction, innermost
+    /// last.
+    labels: Vec<Option<String>>,
+    code: Vec<Instr>,
+    asm: &'a Assembler,
+}
+
+impl Assembler {
+    fn module(mut self, fields: &[Sexp]) -> Result<Module, String> {
+        // first the names and signatures, as fields refer to later ones
+        let mut imports = vec![];
+        let mut funcs = vec![];
+        for field in fields {
+            if let Some(items) = field.form("type") {
+                let (name, rest) = name_of(items);
+                let sig = match rest.first().and_then(|s| s.form("func")) {
+                    Some(sig) => sig,
+                    None => return error(field, "expected (type (func ...))"),
+                };
+                let ty = FuncType {
+                    params: self.value_types(sig, "param")?,
+                    results: self.value_types(sig, "result")?,
+                };
+                let index = self.module.types.len() as u32;
+                self.module.types.push(ty);
+                if let Some(name) = name {
+                    self.types.insert(name.to_string(), index);
+                }
+            } else if let Some(items) = field.form("import") {
+                imports.push((field, items));
+            } else if let Some(items) = field.form("func") {
+                funcs.push((field, items));
+            }
+        }
+        for (field, items) in &imports {
+            let (module, name, desc) = match items {
+                [Sexp::Str(m, _), Sexp::Str(n, _), desc] => (m, n, desc),
+                _ => return error(field, "expected (import \"module\" \"name\" (func ...))"),
+            };
+            let desc = match desc.form("func") {
+                Some(desc) => desc,
+                None => return error(field, "only functions can be imported"),
+            };
+            let (id, rest) = name_of(desc);
+            let ty = self.type_use(rest)?;
+            let index = self.module.imports.len() as u32;
+            if let Some(id) = id {
+                self.funcs.insert(id.to_string(), index);
+            }
+            self.module.imports.push(Import {
+                module: String::from_utf8_lossy(module).into_owned(),
+                name: String::from_utf8_lossy(name).into_owned(),
+                ty,
+            });
+        }
+        let base = self.module.imports.len() as u32;
+        let mut types = vec![];
+        for (i, (_, items)) in funcs.iter().enumerate() {
+            let (id, rest) = name_of(items);
+            if let Some(id) = id {
+                self.funcs.insert(id.to_string(), base + i as u32);
+            }
+            types.push(self.type_use(rest)?);
+        }
+        for field in fields {
+            if let Some(items) = field.form("global") {
+                let (id, rest) = name_of(items);
+                let index = self.module.globals.len() as u32;
+                if let Some(id) = id {
+                    self.globals.insert(id.to_string(), index);
+                }
+                let rest = self.inline_exports(rest, ExportKind::Global, index);
+                let (ty, mutable) = match rest.first() {
+                    Some(Sexp::Atom(t, _)) => (ValType::from_name(t), false),
+                    Some(s) => match s.form("mut") {
+                        Some([Sexp::Atom(t, _)]) => (ValType::from_name(t), true),
+                        _ => (None, false),
+                    },
+                    None => (None, false),
+                };
+                let ty = match ty {
+                    Some(ty) => ty,
+                    None => return error(field, "expected a global type"),
+                };
+                let init = match rest.get(1) {
+                    Some(init) => self.const_expr(init)?,
+                    None => return error(field, "expected an initializer"),
+                };
+                self.module.globals.push(Global { ty, mutable, init });
+            }
+        }
+        // then everything else, in order
+        for field in fields {
+            let keyword = match field {
+                Sexp::List(items, _) => items.first().and_then(Sexp::atom).unwrap_or(""),
+                _ => return error(field, "expected a module field"),
+            };
+            let items = field.form(keyword).unwrap_or(&[]);
+            match keyword {
+                "type" | "import" | "global" => {}
+                "func" => {
+                    let index = self.module.funcs.len();
+                    let ty = types[index];
+                    let (_, rest) = name_of(items);
+                    let rest = self.inline_exports(rest, ExportKind::Func, base + index as u32);
+                    let func = self.func(ty, rest)?;
+                    self.module.funcs.push(func);
+                }
+                "memory" => {
+                    let (_, rest) = name_of(items);
+                    let rest = self.inline_exports(rest, ExportKind::Memory, 0);
+                    match rest.first().and_then(Sexp::atom).and_then(int) {
+                        Some(min) => self.module.memory = Some(min as u32),
+                        None => return error(field, "expected the memory's size"),
+                    }
+                }
+                "table" => {
+                    let (_, rest) = name_of(items);
+                    match rest.first().and_then(Sexp::atom).and_then(int) {
+                        Some(min) => self.module.table = Some(min as u32),
+                        None => return error(field, "expected the table's size"),
+                    }
+                }
+                "export" => {
+                    let (name, desc) = match items {
+                        [Sexp::Str(name, _), desc] => (String::from_utf8_lossy(name).into_owned(), desc),
+                        _ => return error(field, "expected (export \"name\" (kind index))"),
+                    };
+                    let (kind, index) = match desc {
+                        Sexp::List(d, _) => match (d.first().and_then(Sexp::atom), d.get(1)) {
+                            (Some("func"), Some(i)) => (ExportKind::Func, self.func_index(i)?),
+                            (Some("global"), Some(i)) => (ExportKind::Global, self.index(i, &self.globals, "global")?),
+                            (Some("memory"), _) => (ExportKind::Memory, 0),
+                            (Some("table"), _) => (ExportKind::Table, 0),
+                            _ => return error(desc, "unknown export"),
+                        },
+                        _ => return error(desc, "unknown export"),
+                    };
+                    self.module.exports.push(Export { name, kind, index });
+                }
+                "start" => match items.first() {
+                    Some(f) => self.module.start = Some(self.func_index(f)?),
+                    None => return error(field, "expected a function"),
+                },
+                "elem" => {
+                    let offset = match items.first() {
+                        Some(e) => match self.const_expr(e)? {
+                            Instr::I32Const(n) => n as usize,
+                            _ => return error(e, "expected an i32 offset"),
+                        },
+                        None => return error(field, "expected an offset"),
+                    };
+                    let mut i = offset;
+                    for f in &items[1..] {
+                        if f.atom() == Some("func") {
+                            continue;
+                        }
+                        if self.module.elems.len() <= i {
+                            self.module.elems.resize(i + 1, 0);
+                        }
+                        self.module.elems[i] = self.func_index(f)?;
+                        i += 1;
+                    }
+                }
+                "data" => {
+                    let offset = match items.first() {
+                        Some(e) => match self.const_expr(e)? {
+                            Instr::I32Const(n) => n as u32,
+                            _ => return error(e, "expected an i32 offset"),
+                        },
+                        None => return error(field, "expected an offset"),
+                    };
+                    let mut bytes = vec![];
+                    for s in &items[1..] {
+                        match s {
+                            Sexp::Str(b, _) => bytes.extend(b),
+                            _ => return error(s, "expected a string"),
+                        }
+                    }
+                    self.module.data.push(Data { offset, bytes });
+                }
+                _ => return error(field, format!("unknown module field {}", keyword)),
+            }
+        }
+        Ok(self.module)
+    }
+
+    /// Records `(export "name")` abbreviations at the start of a field,
+    /// returning the rest.
+    fn inline_exports<'s>(&mut self, mut items: &'s [Sexp], kind: ExportKind, index: u32) -> &'s [Sexp] {
+        while let Some([Sexp::Str(name, _)]) = items.first().and_then(|s| s.form("export")) {
+            let name = String::from_utf8_lossy(name).into_owned();
+            self.module.exports.push(Export { name, kind, index });
+            items = &items[1..];
+        }
+        items
+    }
+
+    /// The types in the `(param ...)` or `(result ...)` forms of a
+    /// signature, skipping parameter names.
+    fn value_types(&self, items: &[Sexp], keyword: &str) -> Result<Vec<ValType>, String> {
+        let mut types = vec![];
+        for item in items {
+            if let Some(list) = item.form(keyword) {
+                for t in list {
+                    match t.atom() {
+                        Some(name) if name.starts_with('$') => {}
+                        Some(name) => match ValType::from_name(name) {
+                            Some(ty) => types.push(ty),
+                            None => return error(t, format!("unknown value type {}", name)),
+                        },
+                        None => return error(t, "expected a value type"),
+                    }
+                }
+            }
+        }
+        Ok(types)
+    }
+
+    /// The type index of a `(type $t)` reference or of the signature the
+    /// params and results spell out.
+    fn type_use(&mut self, items: &[Sexp]) -> Result<u32, String> {
+        let items = header(items);
+        for item in items {
+            if let Some([t]) = item.form("type") {
+                return self.index(t, &self.types, "type");
+            }
+        }
+        let ty = FuncType {
+            params: self.value_types(items, "param")?,
+            results: self.value_types(items, "result")?,
+        };
+        Ok(self.module.type_index(ty))
+    }
+
+    fn index(&self, at: &Sexp, names: &HashMap<String, u32>, what: &str) -> Result<u32, String> {
+        match at.atom() {
+            Some(name) if name.starts_with('$') => match names.get(name) {
+                Some(&i) => Ok(i),
+                None => error(at, format!("unknown {} {}", what, name)),
+            },
+            Some(n) => match int(n) {
+                Some(i) => Ok(i as u32),
+                None => error(at, format!("expected a {}", what)),
+            },
+            None => error(at, format!("expected a {}", what)),
+        }
+    }
+
+    fn func_index(&self, at: &Sexp) -> Result<u32, String> {
+        self.index(at, &self.funcs, "function")
+    }
+
+    fn const_expr(&self, e: &Sexp) -> Result<Instr, String> {
+        let (op, arg) = match e {
+            Sexp::List(items, _) => match items.as_slice() {
+                [Sexp::Atom(op, _), Sexp::Atom(arg, _)] => (op.as_str(), arg.as_str()),
+                _ => return error(e, "expected a constant"),
+            },
+            _ => return error(e, "expected a constant"),
+        };
+        let instr = match op {
+            "i32.const" => int(arg).map(|n| Instr::I32Const(n as i32)),
+            "i64.const" => int(arg).map(Instr::I64Const),
+            "f64.const" => float(arg).map(Instr::F64Const),
+            _ => None,
+        };
+        instr.ok_or_else(|| format!("line {}: expected a constant", e.line()))
+    }
+
+    fn func(&self, ty: u32, items: &[Sexp]) -> Result<Func, String> {
+        let mut body = Body { locals: HashMap::new(), labels: vec![], code: vec![], asm: self };
+        let mut locals = vec![];
+        let mut count = 0u32;
+        let mut rest = items;
+        while let Some(item) = rest.first() {
+            let (keyword, list) = match item {
+                Sexp::List(list, _) => match list.first().and_then(Sexp::atom) {
+                    Some(k @ ("param" | "local")) => (k, &list[1..]),
+                    Some("type") | Some("result") => {
+                        rest = &rest[1..];
+                        continue;
+                    }
+                    _ => break,
+                },
+                _ => break,
+            };
+            match list {
+                [Sexp::Atom(name, _), Sexp::Atom(t, _)] if name.starts_with('$') => {
+                    let t = ValType::from_name(t).ok_or_else(|| format!("line {}: unknown value type {}", item.line(), t))?;
+                    body.locals.insert(name.clone(), count);
+                    if keyword == "local" {
+                        locals.push(t);
+                    }
+                    count += 1;
+                }
+                _ => {
+                    for t in list {
+                        let ty = t.atom().and_then(ValType::from_name);
+                        let ty = ty.ok_or_else(|| format!("line {}: expected a value type", t.line()))?;
+                        if keyword == "local" {
+                            locals.push(ty);
+                        }
+                        count += 1;
+                    }
+                }
+            }
+            rest = &rest[1..];
+        }
+        let params = self.module.types[ty as usize].params.len() as u32;
+        if count < params {
+            return Err(format!("line {}: fewer parameters than the type has", items.first().map_or(0, Sexp::line)));
+        }
+        body.instrs(rest)?;
+        if !body.labels.is_empty() {
+            return Err(format!("line {}: block without end", items.last().map_or(0, Sexp::line)));
+        }
+        body.code.push(Instr::End);
+        Ok(Func { ty, locals, body: body.code })
+    }
+}
+
+impl<'a> Body<'a> {
+    fn label(&self, at: &Sexp) -> Result<u32, String> {
+        match at.atom() {
+            Some(name) if name.starts_with('$') => {
+                match self.labels.iter().rev().position(|l| l.as_deref() == Some(name)) {
+                    Some(depth) => Ok(depth as u32),
+                    None => error(at, format!("unknown label {}", name)),
+                }
+            }
+            Some(n) => int(n).map(|n| n as u32).ok_or_else(|| format!("line {}: expected a label", at.line())),
+            None => error(at, "expected a label"),
+        }
+    }
+
+    /// A folded instruction: its operands, themselves instructions, come
+    /// first, as in `(i32.add (local.get $a) (i32.const 1))`. Blocks hold
+    /// their bodies, and an `if` its `(then ...)` and `(else ...)`.
+    fn folded(&mut self, at: &Sexp, list: &[Sexp]) -> Result<(), String> {
+        let op = match list.first().and_then(Sexp::atom) {
+            Some(op) => op,
+            None => return error(at, "expected an instruction"),
+        };
+        // the label and block type, or the immediates
+        let mut n = 1;
+        while let Some(item) = list.get(n) {
+            let immediate = match item {
+                Sexp::Atom(..) => true,
+                Sexp::List(..) => ["type", "param", "result"].iter().any(|k| item.form(k).is_some()),
+                Sexp::Str(..) => false,
+            };
+            if !immediate {
+                break;
+            }
+            n += 1;
+        }
+        let head = &list[..n];
+        let rest = &list[n..];
+        match op {
+            "block" | "loop" => {
+                self.instrs(head)?;
+                self.instrs(rest)?;
+                self.labels.pop();
+                self.code.push(Instr::End);
+            }
+            "if" => {
+                let (mut then, mut otherwise) = (None, None);
+                for item in rest {
+                    if let Some(body) = item.form("then") {
+                        then = Some(body);
+                    } else if let Some(body) = item.form("else") {
+                        otherwise = Some(body);
+                    } else {
+                        self.instrs(std::slice::from_ref(item))?;
+                    }
+                }
+                self.instrs(head)?;
+                self.instrs(then.unwrap_or(&[]))?;
+                if let Some(body) = otherwise {
+                    self.code.push(Instr::Else);
+                    self.instrs(body)?;
+                }
+                self.labels.pop();
+                self.code.push(Instr::End);
+            }
+            _ => {
+                self.instrs(rest)?;
+                self.instrs(head)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn instrs(&mut self, items: &[Sexp]) -> Result<(), String> {
+        let mut i = 0;
+        while i < items.len() {
+            let at = &items[i];
+            let op = match at {
+                Sexp::Atom(op, _) => op.as_str(),
+                Sexp::List(list, _) => {
+                    self.folded(at, list)?;
+                    i += 1;
+                    continue;
+                }
+                Sexp::Str(..) => return error(at, "unexpected string"),
+            };
+            i += 1;
+            let mut arg = |what: &str| -> Result<&Sexp, String> {
+                let a = items.get(i).ok_or_else(|| format!("line {}: {} needs {}", at.line(), op, what))?;
+                i += 1;
+                Ok(a)
+            };
+            let instr = match op {
+                "block" | "loop" | "if" => {
+                    let mut label = None;
+                    if let Some(Sexp::Atom(name, _)) = items.get(i) {
+                        if name.starts_with('$') {
+                            label = Some(name.clone());
+                            i += 1;
+                        }
+                    }
+                    let mut ty = BlockType::Empty;
+                    if let Some(list) = items.get(i).and_then(|s| s.form("result")) {
+                        match list {
+                            [Sexp::Atom(t, _)] => match ValType::from_name(t) {
+                                Some(t) => ty = BlockType::Value(t),
+                                None => return error(at, format!("unknown value type {}", t)),
+                            },
+                            [] => {}
+                            _ => return error(at, "blocks yield at most one value"),
+                        }
+                        i += 1;
+                    }
+                    self.labels.push(label);
+                    match op {
+                        "block" => Instr::Block(ty),
+                        "loop" => Instr::Loop(ty),
+                        _ => Instr::If(ty),
+                    }
+                }
+                "else" | "end" => {
+                    if let Some(Sexp::Atom(name, _)) = items.get(i) {
+                        if name.starts_with('$') {
+                            i += 1;
+                        }
+                    }
+                    if op == "else" {
+                        Instr::Else
+                    } else {
+                        if self.labels.pop().is_none() {
+                            return error(at, "end without a block");
+                        }
+                        Instr::End
+                    }
+                }
+                "br" => Instr::Br(self.label(arg("a label")?)?),
+                "br_if" => Instr::BrIf(self.label(arg("a label")?)?),
+                "br_table" => {
+                    let mut labels = vec![];
+                    while let Some(Sexp::Atom(_, _)) = items.get(i) {
+                        match self.label(&items[i]) {
+                            Ok(l) => labels.push(l),
+                            Err(_) => break,
+                        }
+                        i += 1;
+                    }
+                    match labels.pop() {
+                        Some(default) => Instr::BrTable(labels, default),
+                        None => return error(at, "br_table needs labels"),
+                    }
+                }
+                "call" => Instr::Call(self.asm.func_index(arg("a function")?)?),
+                "call_indirect" => {
+                    let start = i;
+                    while let Some(Sexp::List(..)) = items.get(i) {
+                        i += 1;
+                    }
+                    let sig = &items[start..i];
+                    let ty = match sig.iter().find_map(|s| s.form("type")) {
+                        Some([t]) => self.asm.index(t, &self.asm.types, "type")?,
+                        _ => {
+                            let ty = FuncType {
+                                params: self.asm.value_types(sig, "param")?,
+                                results: self.asm.value_types(sig, "result")?,
+                            };
+                            match self.asm.module.types.iter().position(|t| *t == ty) {
+                                Some(i) => i as u32,
+                                None => return error(at, "call_indirect needs a declared type"),
+                            }
+                        }
+                    };
+                    Instr::CallIndirect(ty)
+                }
+                "local.get" | "local.set" | "local.tee" => {
+                    let index = self.asm.index(arg("a local")?, &self.locals, "local")?;
+                    match op {
+                        "local.get" => Instr::LocalGet(index),
+                        "local.set" => Instr::LocalSet(index),
+                        _ => Instr::LocalTee(index),
+                    }
+                }
+                "global.get" | "global.set" => {
+                    let index = self.asm.index(arg("a global")?, &self.asm.globals, "global")?;
+                    if op == "global.get" {
+                        Instr::GlobalGet(index)
+                    } else {
+                        Instr::GlobalSet(index)
+                    }
+                }
+                "i32.const" | "i64.const" => {
+                    let a = arg("a number")?;
+                    let n = a.atom().and_then(int).ok_or_else(|| format!("line {}: expected an integer", a.line()))?;
+                    if op == "i32.const" {
+                        Instr::I32Const(n as i32)
+                    } else {
+                        Instr::I64Const(n)
+                    }
+                }
+                "f64.const" => {
+                    let a = arg("a number")?;
+                    Instr::F64Const(a.atom().and_then(float).ok_or_else(|| format!("line {}: expected a number", a.line()))?)
+                }
+                "unreachable" => Instr::Unreachable,
+                "nop" => Instr::Nop,
+                "return" => Instr::Return,
+                "drop" => Instr::Drop,
+                "select" => Instr::Select,
+                "memory.size" => Instr::MemorySize,
+                "memory.grow" => Instr::MemoryGrow,
+                "memory.copy" => Instr::MemoryCopy,
+                "memory.fill" => Instr::MemoryFill,
+                _ => {
+                    if let Some(&(code, _, natural)) = MEMORY_OPS.iter().find(|(_, name, _)| *name == op) {
+                        let mut mem = MemArg { align: natural, offset: 0 };
+                        while let Some(Sexp::Atom(a, _)) = items.get(i) {
+                            if let Some(n) = a.strip_prefix("offset=") {
+                                mem.offset = int(n).ok_or_else(|| format!("line {}: bad offset", at.line()))? as u32;
+                            } else if let Some(n) = a.strip_prefix("align=") {
+                                let n = int(n).filter(|n| *n > 0 && n.count_ones() == 1);
+                                mem.align = n.ok_or_else(|| format!("line {}: bad alignment", at.line()))?.trailing_zeros();
+                            } else {
+                                break;
+                            }
+                            i += 1;
+                        }
+                        if code < 0x36 {
+                            Instr::Load(code, mem)
+                        } else {
+                            Instr::Store(code, mem)
+                        }
+                    } else if let Some(&(code, _)) = OPS.iter().find(|(_, name)| *name == op) {
+                        Instr::Op(code)
+                    } else if let Some(&(code, _)) = TRUNC_SAT_OPS.iter().find(|(_, name)| *name == op) {
+                        Instr::TruncSat(code)
+                    } else {
+                        return error(at, format!("unknown instruction {}", op));
+                    }
+                }
+            };
+            self.code.push(instr);
+        }
+        Ok(())
+    }
+}
+
+/// The forms that start a function or import: its exports, type use,
+/// parameters, results and locals, before any instruction.
+fn header(items: &[Sexp]) -> &[Sexp] {
+    let n = items
+        .iter()
+        .take_while(|s| ["export", "type", "param", "result", "local"].iter().any(|k| s.form(k).is_some()))
+        .count();
+    &items[..n]
+}
+
+/// Splits an optional `$name` off the front of a field's items.
+fn name_of(items: &[Sexp]) -> (Option<&str>, &[Sexp]) {
+    match items.first() {
+        Some(Sexp::Atom(name, _)) if name.starts_with('$') => (Some(name), &items[1..]),
+        _ => (None, items),
+    }
+}
@@ -0,0 +1,435 @@
+//! The x86-64 instructions the backend selects, and a program of them:
+//! functions and data, which print as GNU assembler text in Intel syntax
+//! and encode into a relocatable ELF object, see `elf`.
+
+use super::elf::{Object, Relocation, RelocationKind, Section, Symbol};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reg {
+    Rax,
+    Rcx,
+    Rdx,
+    Rbx,
+    Rsp,
+    Rbp,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+    R10,
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+}
+
+impl Reg {
+    /// The register's number in encodings.
+    fn code(self) -> u8 {
+        self as u8
+    }
+
+    fn name8(self) -> &'static str {
+        const NAMES: [&str; 16] = [
+            "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b", "r13b", "r14b", "r15b",
+        ];
+        NAMES[self as usize]
+    }
+
+    fn name32(self) -> &'static str {
+        const NAMES: [&str; 16] = [
+            "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d", "r13d", "r14d",
+            "r15d",
+        ];
+        NAMES[self as usize]
+    }
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const NAMES: [&str; 16] = [
+            "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+        ];
+        f.write_str(NAMES[*self as usize])
+    }
+}
+
+/// The System V integer argument registers, in order.
+pub const ARGS: [Reg; 6] = [Reg::Rdi, Reg::Rsi, Reg::Rdx, Reg::Rcx, Reg::R8, Reg::R9];
+
+/// A condition on the flags a `cmp` of signed integers left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cond {
+    E,
+    Ne,
+    L,
+    Le,
+    G,
+    Ge,
+}
+
+impl Cond {
+    /// The low nibble of the condition's `setcc` and `jcc` opcodes.
+    fn code(self) -> u8 {
+        match self {
+            Cond::E => 0x4,
+            Cond::Ne => 0x5,
+            Cond::L => 0xc,
+            Cond::Ge => 0xd,
+            Cond::Le => 0xe,
+            Cond::G => 0xf,
+        }
+    }
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Cond::E => "e",
+            Cond::Ne => "ne",
+            Cond::L => "l",
+            Cond::Le => "le",
+            Cond::G => "g",
+            Cond::Ge => "ge",
+        })
+    }
+}
+
+/// `[base + disp]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mem {
+    pub base: Reg,
+    pub disp: i32,
+}
+
+impl Mem {
+    pub fn new(base: Reg, disp: i32) -> Mem {
+        Mem { base, disp }
+    }
+
+    pub fn offset(self, n: i32) -> Mem {
+        Mem::new(self.base, self.disp + n)
+    }
+}
+
+impl fmt::Display for Mem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.disp {
+            0 => write!(f, "[{}]", self.base),
+            d if d < 0 => write!(f, "[{}-{}]", self.base, -(d as i64)),
+            d => write!(f, "[{}+{}]", self.base, d),
+        }
+    }
+}
+
+/// A label local to the program, `.L<n>`.
+pub type Label = usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+    Label(Label),
+    Push(Reg),
+    Pop(Reg),
+    /// `mov dst, src`.
+    Mov(Reg, Reg),
+    MovImm(Reg, i64),
+    Load(Reg, Mem),
+    Store(Mem, Reg),
+    Lea(Reg, Mem),
+    /// The address of a symbol, plus an addend, relative to the
+    /// instruction pointer.
+    LeaSym(Reg, String, i64),
+    Call(String),
+    Jmp(Label),
+    /// Jumps if the last test found zero.
+    Jz(Label),
+    /// `test` of the registers' low 32 bits.
+    Test32(Reg, Reg),
+    /// `add dst, src`, and the same for `Sub` and `Imul`.
+    Add(Reg, Reg),
+    Sub(Reg, Reg),
+    Imul(Reg, Reg),
+    Neg(Reg),
+    AddImm(Reg, i32),
+    SubImm(Reg, i32),
+    XorImm(Reg, i32),
+    /// Compares the registers for a `Set` after.
+    Cmp(Reg, Reg),
+    /// Sets the register's low byte to whether the condition holds.
+    Set(Cond, Reg),
+    /// Zero-extends the register's low byte into all of it.
+    MovzxByte(Reg),
+    Ret,
+}
+
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Inst::Label(l) => write!(f, ".L{}:", l),
+            Inst::Push(r) => write!(f, "    push {}", r),
+            Inst::Pop(r) => write!(f, "    pop {}", r),
+            Inst::Mov(d, s) => write!(f, "    mov {}, {}", d, s),
+            Inst::MovImm(d, n) if i32::try_from(*n).is_ok() => write!(f, "    mov {}, {}", d, n),
+            Inst::MovImm(d, n) => write!(f, "    movabs {}, {}", d, n),
+            Inst::Load(d, m) => write!(f, "    mov {}, QWORD PTR {}", d, m),
+            Inst::Store(m, s) => write!(f, "    mov QWORD PTR {}, {}", m, s),
+            Inst::Lea(d, m) => write!(f, "    lea {}, {}", d, m),
+            Inst::LeaSym(d, sym, 0) => write!(f, "    lea {}, [rip+{}]", d, sym),
+            Inst::LeaSym(d, sym, n) => write!(f, "    lea {}, [rip+{}+{}]", d, sym, n),
+            Inst::Call(sym) => write!(f, "    call {}", sym),
+            Inst::Jmp(l) => write!(f, "    jmp .L{}", l),
+            Inst::Jz(l) => write!(f, "    jz .L{}", l),
+            Inst::Test32(a, b) => write!(f, "    test {}, {}", a.name32(), b.name32()),
+            Inst::Add(d, s) => write!(f, "    add {}, {}", d, s),
+            Inst::Sub(d, s) => write!(f, "    sub {}, {}", d, s),
+            Inst::Imul(d, s) => write!(f, "    imul {}, {}", d, s),
+            Inst::Neg(r) => write!(f, "    neg {}", r),
+            Inst::AddImm(r, n) => write!(f, "    add {}, {}", r, n),
+            Inst::SubImm(r, n) => write!(f, "    sub {}, {}", r, n),
+            Inst::XorImm(r, n) => write!(f, "    xor {}, {}", r, n),
+            Inst::Cmp(a, b) => write!(f, "    cmp {}, {}", a, b),
+            Inst::Set(c, r) => write!(f, "    set{} {}", c, r.name8()),
+            Inst::MovzxByte(r) => write!(f, "    movzx {}, {}", r.name32(), r.name8()),
+            Inst::Ret => write!(f, "    ret"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    /// Whether other objects may refer to the function.
+    pub global: bool,
+    pub code: Vec<Inst>,
+}
+
+/// Initialized data: bytes with the addresses of symbols at some of
+/// their offsets, eight bytes each.
+#[derive(Debug)]
+pub struct Data {
+    pub name: String,
+    pub align: usize,
+    pub bytes: Vec<u8>,
+    pub addresses: Vec<(usize, String)>,
+}
+
+#[derive(Debug, Default)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    pub data: Vec<Data>,
+}
+
+impl Program {
+    /// The program as GNU assembler text.
+    pub fn assembly(&self) -> String {
+        let mut out = String::from("    .intel_syntax noprefix\n    .text\n");
+        for func in &self.functions {
+            out.push('\n');
+            if func.global {
+                let _ = writeln!(out, "    .globl {}", func.name);
+            }
+            let _ = writeln!(out, "    .p2align 4\n{}:", func.name);
+            for inst in &func.code {
+                let _ = writeln!(out, "{}", inst);
+            }
+        }
+        out.push_str("\n    .data\n");
+        for data in &self.data {
+            let _ = writeln!(out, "    .p2align {}\n{}:", data.align.trailing_zeros(), data.name);
+            let mut at = 0;
+            for (offset, symbol) in data.addresses.iter().chain(std::iter::once(&(data.bytes.len(), String::new()))) {
+                for chunk in data.bytes[at..*offset].chunks(16) {
+                    let bytes: Vec<String> = chunk.iter().map(|b| b.to_string()).collect();
+                    let _ = writeln!(out, "    .byte {}", bytes.join(", "));
+                }
+                if !symbol.is_empty() {
+                    let _ = writeln!(out, "    .quad {}", symbol);
+                }
+                at = offset + 8;
+            }
+        }
+        out.push_str("\n    .section .note.GNU-stack,\"\",@progbits\n");
+        out
+    }
+
+    /// The program as a relocatable ELF object.
+    pub fn object(&self) -> Vec<u8> {
+        let mut object = Object::default();
+        let mut labels = HashMap::new();
+        let mut jumps = vec![];
+        for func in &self.functions {
+            align(&mut object.text, 16, 0x90);
+            object.symbols.push(Symbol {
+                name: func.name.clone(),
+                section: Some(Section::Text),
+                value: object.text.len() as u64,
+                global: func.global,
+            });
+            for inst in &func.code {
+                encode(inst, &mut object, &mut labels, &mut jumps);
+            }
+        }
+        for (at, label) in jumps {
+            let rel = labels[&label] as i64 - (at as i64 + 4);
+            object.text[at..at + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+        }
+        for data in &self.data {
+            align(&mut object.data, data.align, 0);
+            let start = object.data.len();
+            object.symbols.push(Symbol {
+                name: data.name.clone(),
+                section: Some(Section::Data),
+                value: start as u64,
+                global: false,
+            });
+            object.data.extend_from_slice(&data.bytes);
+            for (offset, symbol) in &data.addresses {
+                object.relocations.push(Relocation {
+                    section: Section::Data,
+                    offset: (start + offset) as u64,
+                    symbol: symbol.clone(),
+                    kind: RelocationKind::Absolute64,
+                    addend: 0,
+                });
+            }
+        }
+        object.write()
+    }
+}
+
+fn align(bytes: &mut Vec<u8>, n: usize, fill: u8) {
+    while !bytes.len().is_multiple_of(n) {
+        bytes.push(fill);
+    }
+}
+
+/// A REX prefix with W set, and the high bits of the registers in the
+/// ModRM reg and rm fields.
+fn rex_w(reg: u8, rm: u8) -> u8 {
+    0x48 | (reg >> 3) << 2 | rm >> 3
+}
+
+/// ModRM for two registers.
+fn modrm_reg(reg: u8, rm: u8) -> u8 {
+    0xc0 | (reg & 7) << 3 | (rm & 7)
+}
+
+/// ModRM, and SIB and displacement as needed, for `[base + disp]`.
+fn modrm_mem(out: &mut Vec<u8>, reg: u8, mem: Mem) {
+    let base = mem.base.code();
+    let short = i8::try_from(mem.disp).is_ok();
+    out.push(if short { 0x40 } else { 0x80 } | (reg & 7) << 3 | (base & 7));
+    if base & 7 == 4 {
+        // rsp and r12 as a base take a SIB byte
+        out.push(0x24);
+    }
+    if short {
+        out.push(mem.disp as i8 as u8);
+    } else {
+        out.extend_from_slice(&mem.disp.to_le_bytes());
+    }
+}
+
+fn encode(inst: &Inst, object: &mut Object, labels: &mut HashMap<Label, usize>, jumps: &mut Vec<(usize, Label)>) {
+    let out = &mut object.text;
+    match inst {
+        Inst::Label(l) => {
+            labels.insert(*l, out.len());
+        }
+        Inst::Push(r) | Inst::Pop(r) => {
+            let r = r.code();
+            if r >= 8 {
+                out.push(0x41);
+            }
+            out.push(if matches!(inst, Inst::Push(_)) { 0x50 } else { 0x58 } + (r & 7));
+        }
+        Inst::Mov(d, s) => out.extend_from_slice(&[rex_w(s.code(), d.code()), 0x89, modrm_reg(s.code(), d.code())]),
+        Inst::MovImm(d, n) => match i32::try_from(*n) {
+            Ok(n) => {
+                out.extend_from_slice(&[rex_w(0, d.code()), 0xc7, modrm_reg(0, d.code())]);
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+            Err(_) => {
+                out.extend_from_slice(&[rex_w(0, d.code()), 0xb8 + (d.code() & 7)]);
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+        },
+        Inst::Load(d, m) | Inst::Lea(d, m) => {
+            out.extend_from_slice(&[rex_w(d.code(), m.base.code()), if matches!(inst, Inst::Load(..)) { 0x8b } else { 0x8d }]);
+            modrm_mem(out, d.code(), *m);
+        }
+        Inst::Store(m, s) => {
+            out.extend_from_slice(&[rex_w(s.code(), m.base.code()), 0x89]);
+            modrm_mem(out, s.code(), *m);
+        }
+        Inst::LeaSym(d, symbol, addend) => {
+            // mod 00, rm 101: rip-relative
+            out.extend_from_slice(&[rex_w(d.code(), 0), 0x8d, (d.code() & 7) << 3 | 5]);
+            object.relocations.push(Relocation {
+                section: Section::Text,
+                offset: out.len() as u64,
+                symbol: symbol.clone(),
+                kind: RelocationKind::Pc32,
+                addend: addend - 4,
+            });
+            out.extend_from_slice(&[0; 4]);
+        }
+        Inst::Call(symbol) => {
+            out.push(0xe8);
+            object.relocations.push(Relocation {
+                section: Section::Text,
+                offset: out.len() as u64,
+                symbol: symbol.clone(),
+                kind: RelocationKind::Plt32,
+                addend: -4,
+            });
+            out.extend_from_slice(&[0; 4]);
+        }
+        Inst::Jmp(l) | Inst::Jz(l) => {
+            match inst {
+                Inst::Jmp(_) => out.push(0xe9),
+                _ => out.extend_from_slice(&[0x0f, 0x84]),
+            }
+            jumps.push((out.len(), *l));
+            out.extend_from_slice(&[0; 4]);
+        }
+        Inst::Test32(a, b) => {
+            let (a, b) = (a.code(), b.code());
+            if a >= 8 || b >= 8 {
+                out.push(0x40 | (b >> 3) << 2 | a >> 3);
+            }
+            out.extend_from_slice(&[0x85, modrm_reg(b, a)]);
+        }
+        Inst::Add(d, s) | Inst::Sub(d, s) | Inst::Cmp(d, s) => {
+            let op = match inst {
+                Inst::Add(..) => 0x01,
+                Inst::Sub(..) => 0x29,
+                _ => 0x39,
+            };
+            out.extend_from_slice(&[rex_w(s.code(), d.code()), op, modrm_reg(s.code(), d.code())]);
+        }
+        Inst::Imul(d, s) => out.extend_from_slice(&[rex_w(d.code(), s.code()), 0x0f, 0xaf, modrm_reg(d.code(), s.code())]),
+        Inst::Neg(r) => out.extend_from_slice(&[rex_w(0, r.code()), 0xf7, modrm_reg(3, r.code())]),
+        Inst::AddImm(r, n) | Inst::SubImm(r, n) | Inst::XorImm(r, n) => {
+            let ext = match inst {
+                Inst::AddImm(..) => 0,
+                Inst::SubImm(..) => 5,
+                _ => 6,
+            };
+            out.extend_from_slice(&[rex_w(0, r.code()), 0x81, modrm_reg(ext, r.code())]);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        // a REX prefix, even an empty one, makes the byte registers of
+        // rsp, rbp, rsi and rdi spl, bpl, sil and dil
+        Inst::Set(c, r) => out.extend_from_slice(&[0x40 | r.code() >> 3, 0x0f, 0x90 | c.code(), modrm_reg(0, r.code())]),
+        Inst::MovzxByte(r) => {
+            let r = r.code();
+            out.extend_from_slice(&[0x40 | (r >> 3) << 2 | r >> 3, 0x0f, 0xb6, modrm_reg(r, r)]);
+        }
+        Inst::Ret => out.push(0xc3),
+    }
+}
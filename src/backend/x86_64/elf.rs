@@ -0,0 +1,235 @@
+//! Relocatable ELF objects for x86-64 Linux: a text and a data section,
+//! their symbols, and relocations against those and symbols left for the
+//! linker to find.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Text,
+    Data,
+}
+
+/// A symbol the object defines.
+pub struct Symbol {
+    pub name: String,
+    pub section: Option<Section>,
+    pub value: u64,
+    pub global: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocationKind {
+    /// `R_X86_64_64`: the symbol's address.
+    Absolute64,
+    /// `R_X86_64_PC32`: the address relative to the place.
+    Pc32,
+    /// `R_X86_64_PLT32`: as `Pc32`, through the PLT for a function in a
+    /// shared library.
+    Plt32,
+}
+
+pub struct Relocation {
+    pub section: Section,
+    pub offset: u64,
+    pub symbol: String,
+    pub kind: RelocationKind,
+    pub addend: i64,
+}
+
+#[derive(Default)]
+pub struct Object {
+    pub text: Vec<u8>,
+    pub data: Vec<u8>,
+    pub symbols: Vec<Symbol>,
+    pub relocations: Vec<Relocation>,
+}
+
+/// A string table: names at offsets, after an empty one.
+struct Strings(Vec<u8>);
+
+impl Strings {
+    fn new() -> Strings {
+        Strings(vec![0])
+    }
+
+    fn add(&mut self, s: &str) -> u32 {
+        let at = self.0.len() as u32;
+        self.0.extend_from_slice(s.as_bytes());
+        self.0.push(0);
+        at
+    }
+}
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHF_WRITE: u64 = 1;
+const SHF_ALLOC: u64 = 2;
+const SHF_EXECINSTR: u64 = 4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+// Section indices.
+const TEXT: u16 = 1;
+const DATA: u16 = 2;
+const RELA_TEXT: u16 = 3;
+const RELA_DATA: u16 = 4;
+const SYMTAB: u32 = 5;
+const STRTAB: u32 = 6;
+const SHSTRTAB: u16 = 7;
+const NOTE_STACK: u16 = 8;
+const SECTIONS: u16 = 9;
+
+struct Header {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+fn put16(out: &mut Vec<u8>, n: u16) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+fn put32(out: &mut Vec<u8>, n: u32) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+fn put64(out: &mut Vec<u8>, n: u64) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+impl Object {
+    /// The object file. Symbols the object refers to without defining
+    /// them are global and undefined.
+    pub fn write(&self) -> Vec<u8> {
+        let mut strtab = Strings::new();
+        let mut symtab = vec![0; 24];
+        let mut index = HashMap::new();
+        let entry = |symtab: &mut Vec<u8>, name: u32, global: bool, shndx: u16, value: u64| {
+            put32(symtab, name);
+            // STT_NOTYPE for undefined symbols, else STT_FUNC or STT_OBJECT
+            let kind = match shndx {
+                TEXT => 2,
+                DATA => 1,
+                _ => 0,
+            };
+            symtab.push((global as u8) << 4 | kind);
+            symtab.push(0);
+            put16(symtab, shndx);
+            put64(symtab, value);
+            put64(symtab, 0);
+        };
+        // locals come first
+        let (locals, globals): (Vec<&Symbol>, Vec<&Symbol>) = self.symbols.iter().partition(|s| !s.global);
+        for symbol in locals.iter().chain(globals.iter()) {
+            index.insert(symbol.name.as_str(), (symtab.len() / 24) as u32);
+            let shndx = match symbol.section {
+                Some(Section::Text) => TEXT,
+                Some(Section::Data) => DATA,
+                None => 0,
+            };
+            entry(&mut symtab, strtab.add(&symbol.name), symbol.global, shndx, symbol.value);
+        }
+        let first_global = 1 + locals.len() as u32;
+        for relocation in &self.relocations {
+            if !index.contains_key(relocation.symbol.as_str()) {
+                index.insert(relocation.symbol.as_str(), (symtab.len() / 24) as u32);
+                entry(&mut symtab, strtab.add(&relocation.symbol), true, 0, 0);
+            }
+        }
+        let rela = |section: Section| -> Vec<u8> {
+            let mut out = vec![];
+            for relocation in self.relocations.iter().filter(|r| r.section == section) {
+                let kind = match relocation.kind {
+                    RelocationKind::Absolute64 => 1,
+                    RelocationKind::Pc32 => 2,
+                    RelocationKind::Plt32 => 4,
+                };
+                put64(&mut out, relocation.offset);
+                put64(&mut out, (index[relocation.symbol.as_str()] as u64) << 32 | kind);
+                put64(&mut out, relocation.addend as u64);
+            }
+            out
+        };
+        let (rela_text, rela_data) = (rela(Section::Text), rela(Section::Data));
+
+        let mut shstrtab = Strings::new();
+        let names = [".text", ".data", ".rela.text", ".rela.data", ".symtab", ".strtab", ".shstrtab", ".note.GNU-stack"];
+        let names: Vec<u32> = names.iter().map(|n| shstrtab.add(n)).collect();
+        let contents: [&[u8]; 8] = [&self.text, &self.data, &rela_text, &rela_data, &symtab, &strtab.0, &shstrtab.0, &[]];
+
+        let mut out = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0];
+        out.resize(16, 0);
+        put16(&mut out, 1); // ET_REL
+        put16(&mut out, 62); // EM_X86_64
+        put32(&mut out, 1);
+        put64(&mut out, 0);
+        put64(&mut out, 0);
+        let shoff_at = out.len();
+        put64(&mut out, 0);
+        put32(&mut out, 0);
+        put16(&mut out, 64);
+        put16(&mut out, 0);
+        put16(&mut out, 0);
+        put16(&mut out, 64);
+        put16(&mut out, SECTIONS);
+        put16(&mut out, SHSTRTAB);
+
+        let mut headers = vec![];
+        for (i, bytes) in contents.iter().enumerate() {
+            while !out.len().is_multiple_of(16) {
+                out.push(0);
+            }
+            let offset = out.len() as u64;
+            out.extend_from_slice(bytes);
+            let section = i as u16 + 1;
+            let (kind, flags, link, info, align, entsize) = match section {
+                TEXT => (SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0, 0, 16, 0),
+                DATA => (SHT_PROGBITS, SHF_WRITE | SHF_ALLOC, 0, 0, 16, 0),
+                RELA_TEXT => (SHT_RELA, SHF_INFO_LINK, SYMTAB, TEXT as u32, 8, 24),
+                RELA_DATA => (SHT_RELA, SHF_INFO_LINK, SYMTAB, DATA as u32, 8, 24),
+                SHSTRTAB => (SHT_STRTAB, 0, 0, 0, 1, 0),
+                NOTE_STACK => (SHT_PROGBITS, 0, 0, 0, 1, 0),
+                _ if section as u32 == SYMTAB => (SHT_SYMTAB, 0, STRTAB, first_global, 8, 24),
+                _ => (SHT_STRTAB, 0, 0, 0, 1, 0),
+            };
+            headers.push(Header {
+                name: names[i],
+                kind,
+                flags,
+                offset,
+                size: bytes.len() as u64,
+                link,
+                info,
+                align,
+                entsize,
+            });
+        }
+        while !out.len().is_multiple_of(8) {
+            out.push(0);
+        }
+        let shoff = out.len() as u64;
+        out[shoff_at..shoff_at + 8].copy_from_slice(&shoff.to_le_bytes());
+        out.resize(out.len() + 64, 0);
+        for h in headers {
+            put32(&mut out, h.name);
+            put32(&mut out, h.kind);
+            put64(&mut out, h.flags);
+            put64(&mut out, 0);
+            put64(&mut out, h.offset);
+            put64(&mut out, h.size);
+            put32(&mut out, h.link);
+            put32(&mut out, h.info);
+            put64(&mut out, h.align);
+            put64(&mut out, h.entsize);
+        }
+        out
+    }
+}
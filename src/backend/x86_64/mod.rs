@@ -0,0 +1,869 @@
+//! The x86-64 backend: a module as x86-64 code for Linux, for
+//! `namu build -o out.s`, which writes GNU assembler text, `-o out.o`,
+//! which writes a relocatable ELF object, or `-native -o out`, which links
+//! that with the runtime into an executable.
+//!
+//! The code calls into the C backend's runtime, compiled on its own with
+//! `runtime.c` here, for everything but control flow, moving values
+//! about, and the arithmetic and comparisons on ints and bools whose
+//! types the IR knows, which it does in registers. Every IR value is a pair of words, as the runtime's `nm_value`,
+//! kept in callee-saved registers or a stack slot, see `regalloc`; calls
+//! pass values in two argument registers and return them in `rax` and
+//! `rdx`, as the System V ABI passes the struct. Functions have the
+//! runtime's `nm_fn` signature, so that its closures call them. A
+//! function that defers calls `setjmp`s into a frame a panic unwinds to,
+//! and keeps its values in memory, which `longjmp` leaves alone.
+//! Goroutines and channels are not supported; programs that use them are
+//! rejected with an error.
+
+pub mod asm;
+pub mod elf;
+mod regalloc;
+
+use self::asm::{Cond, Data, Function as AsmFunction, Inst as Asm, Label, Mem, Program, Reg, ARGS};
+use self::regalloc::{Allocation, Loc};
+use crate::backend::c::{self, BUILTINS};
+use crate::ir::{BinOp, BlockId, Const, FuncId, Function, Inst, Module, Ty, UnOp, ValueId};
+use crate::types::Type;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+#[cfg(test)]
+mod test;
+
+/// What the generated code needs of the runtime besides the C backend's.
+pub const RUNTIME: &str = include_str!("runtime.c");
+
+/// The bytes `runtime.c` checks an `nm_frame` and an `nm_iter` fit in.
+const FRAME_SIZE: i32 = 256;
+const ITER_SIZE: i32 = 64;
+
+/// The offset in the runtime's `nm_func` of its array of pointers to the
+/// variables it captures.
+const FUNC_FREE: i32 = 56;
+
+/// Translates the module to x86-64 code, or returns what it uses that
+/// the backend does not support.
+pub fn generate(module: &Module) -> Result<Program, Vec<String>> {
+    let mut gen = Generator::default();
+    for i in 0..module.functions.len() {
+        if let Some(code) = gen.function(module, FuncId(i)) {
+            gen.program.functions.push(AsmFunction { name: format!("nm_f{}", i), global: false, code });
+        }
+    }
+    if !gen.errors.is_empty() {
+        return Err(gen.errors);
+    }
+
+    // nm_run: zeroes the globals, runs the package initializers and
+    // then main
+    let mut code = vec![Asm::Push(Reg::Rbp), Asm::Mov(Reg::Rbp, Reg::Rsp)];
+    for (i, global) in module.globals.iter().enumerate() {
+        let ty = gen.type_ref(&global.ty);
+        code.push(Asm::LeaSym(Reg::Rdi, ty, 0));
+        code.push(Asm::Call("nm_zero".to_string()));
+        code.push(Asm::LeaSym(Reg::R10, "nm_G".to_string(), 16 * i as i64));
+        code.push(Asm::Store(Mem::new(Reg::R10, 0), Reg::Rax));
+        code.push(Asm::Store(Mem::new(Reg::R10, 8), Reg::Rdx));
+    }
+    for init in &module.inits {
+        for reg in &ARGS[..3] {
+            code.push(Asm::MovImm(*reg, 0));
+        }
+        code.push(Asm::Call(format!("nm_f{}", init.0)));
+    }
+    if let Some(main) = module.main {
+        code.push(Asm::LeaSym(Reg::R10, "nm_G".to_string(), 16 * main as i64));
+        code.push(Asm::Load(Reg::Rdi, Mem::new(Reg::R10, 0)));
+        code.push(Asm::Load(Reg::Rsi, Mem::new(Reg::R10, 8)));
+        code.push(Asm::Call("nm_call_main".to_string()));
+    }
+    code.extend([Asm::Pop(Reg::Rbp), Asm::Ret]);
+    gen.program.functions.push(AsmFunction { name: "nm_run".to_string(), global: true, code });
+    gen.program.data.push(Data {
+        name: "nm_G".to_string(),
+        align: 16,
+        bytes: vec![0; 16 * module.globals.len().max(1)],
+        addresses: vec![],
+    });
+    Ok(gen.program)
+}
+
+/// Links an object `generate` made with the runtime into a native
+/// executable at `output`, with the C compiler `c::cc` names.
+pub fn compile(object: &[u8], output: &Path) -> Result<(), String> {
+    let runtime = format!("#define NM_API\n{}\n{}", c::RUNTIME, RUNTIME);
+    c::link(&[("runtime.c", runtime.as_bytes()), ("main.o", object)], output)
+}
+
+#[derive(Default)]
+struct Generator {
+    program: Program,
+    /// Types with descriptors of the program's own, `nm_T<index>`.
+    types: Vec<Type>,
+    /// String literals, `nm_S<index>`.
+    strings: HashMap<Rc<[u8]>, usize>,
+    /// NUL-terminated strings, `nm_C<index>`.
+    cstrings: HashMap<Vec<u8>, usize>,
+    labels: usize,
+    errors: Vec<String>,
+}
+
+/// Where one word of a value is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Place {
+    Reg(Reg),
+    Mem(Mem),
+}
+
+/// An argument of a call into the runtime.
+enum Arg {
+    /// A value, in two registers.
+    Value(ValueId),
+    /// A value in `rax` and `rdx`, as the last call returned it.
+    Result,
+    Imm(i64),
+    Sym(String),
+    /// The address of part of the stack frame.
+    Addr(Mem),
+}
+
+/// The code for one function, and where its values are.
+struct Frame<'a> {
+    func: &'a Function,
+    alloc: Allocation,
+    code: Vec<Asm>,
+    blocks: Vec<Label>,
+    self_at: Mem,
+    argv_at: Mem,
+    slots_at: i32,
+    /// The memory of tuples and iterators.
+    areas: HashMap<ValueId, Mem>,
+    fr: Mem,
+}
+
+impl<'a> Frame<'a> {
+    fn emit(&mut self, inst: Asm) {
+        self.code.push(inst);
+    }
+
+    /// Where the word of the value is: 0 for its type, 1 for its bits.
+    fn place(&self, value: ValueId, word: usize) -> Place {
+        match self.alloc.locs[value.0].expect("value has a location") {
+            Loc::Regs(t, x) => Place::Reg(if word == 0 { t } else { x }),
+            Loc::Slot(n) => Place::Mem(Mem::new(Reg::Rbp, self.slots_at - 16 * n as i32 + 8 * word as i32)),
+        }
+    }
+
+    fn mov(&mut self, dst: Place, src: Place) {
+        match (dst, src) {
+            _ if dst == src => {}
+            (Place::Reg(d), Place::Reg(s)) => self.emit(Asm::Mov(d, s)),
+            (Place::Reg(d), Place::Mem(m)) => self.emit(Asm::Load(d, m)),
+            (Place::Mem(m), Place::Reg(s)) => self.emit(Asm::Store(m, s)),
+            (Place::Mem(d), Place::Mem(s)) => {
+                self.emit(Asm::Load(Reg::R10, s));
+                self.emit(Asm::Store(d, Reg::R10));
+            }
+        }
+    }
+
+    /// Moves words from places to places all at once: each move goes when
+    /// no other reads what it overwrites, and a cycle is broken by saving
+    /// one of its words in `r11`.
+    fn parallel_move(&mut self, mut moves: Vec<(Place, Place)>) {
+        moves.retain(|(dst, src)| dst != src);
+        while !moves.is_empty() {
+            match moves.iter().position(|(dst, _)| !moves.iter().any(|(_, src)| src == dst)) {
+                Some(i) => {
+                    let (dst, src) = moves.remove(i);
+                    self.mov(dst, src);
+                }
+                None => {
+                    let dst = moves[0].0;
+                    self.mov(Place::Reg(Reg::R11), dst);
+                    for m in &mut moves {
+                        if m.1 == dst {
+                            m.1 = Place::Reg(Reg::R11);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sets a word of the value, by way of `r11` when it is in memory.
+    fn set_word(&mut self, value: ValueId, word: usize, inst: impl FnOnce(Reg) -> Asm) {
+        match self.place(value, word) {
+            Place::Reg(r) => self.emit(inst(r)),
+            Place::Mem(m) => {
+                self.emit(inst(Reg::R11));
+                self.emit(Asm::Store(m, Reg::R11));
+            }
+        }
+    }
+
+    fn set_sym(&mut self, value: ValueId, word: usize, symbol: &str, addend: i64) {
+        self.set_word(value, word, |r| Asm::LeaSym(r, symbol.to_string(), addend));
+    }
+
+    fn set_imm(&mut self, value: ValueId, word: usize, n: i64) {
+        self.set_word(value, word, |r| Asm::MovImm(r, n));
+    }
+
+    /// Sets the value to the result of the last call.
+    fn set_result(&mut self, value: ValueId) {
+        let (t, x) = (self.place(value, 0), self.place(value, 1));
+        self.mov(t, Place::Reg(Reg::Rax));
+        self.mov(x, Place::Reg(Reg::Rdx));
+    }
+
+    /// Copies the values into the argument array at the bottom of the
+    /// frame, giving its address, or NULL when there are none.
+    fn array(&mut self, values: &[ValueId]) -> Arg {
+        if values.is_empty() {
+            return Arg::Imm(0);
+        }
+        for (i, &value) in values.iter().enumerate() {
+            for word in 0..2 {
+                let src = self.place(value, word);
+                self.mov(Place::Mem(Mem::new(Reg::Rsp, 16 * i as i32 + 8 * word as i32)), src);
+            }
+        }
+        Arg::Addr(Mem::new(Reg::Rsp, 0))
+    }
+
+    /// Calls the runtime function with the arguments, which take the
+    /// argument registers in order, a value two of them.
+    fn call(&mut self, symbol: &str, args: Vec<Arg>) {
+        let mut regs = ARGS.iter().copied();
+        let mut assigned = vec![];
+        for arg in args {
+            let words = if matches!(arg, Arg::Value(_) | Arg::Result) { 2 } else { 1 };
+            let regs: Vec<Reg> = regs.by_ref().take(words).collect();
+            assert_eq!(regs.len(), words, "{} takes arguments in registers", symbol);
+            assigned.push((arg, regs));
+        }
+        // the result first, before its registers are overwritten; rdx
+        // goes before rax, which may move into rdx
+        for (arg, regs) in &assigned {
+            if let Arg::Result = arg {
+                self.mov(Place::Reg(regs[1]), Place::Reg(Reg::Rdx));
+                self.mov(Place::Reg(regs[0]), Place::Reg(Reg::Rax));
+            }
+        }
+        for (arg, regs) in assigned {
+            match arg {
+                Arg::Value(value) => {
+                    for (word, reg) in regs.into_iter().enumerate() {
+                        let src = self.place(value, word);
+                        self.mov(Place::Reg(reg), src);
+                    }
+                }
+                Arg::Result => {}
+                Arg::Imm(n) => self.emit(Asm::MovImm(regs[0], n)),
+                Arg::Sym(symbol) => self.emit(Asm::LeaSym(regs[0], symbol, 0)),
+                Arg::Addr(mem) => self.emit(Asm::Lea(regs[0], mem)),
+            }
+        }
+        self.emit(Asm::Call(symbol.to_string()));
+    }
+
+    /// Sets the value to an int or bool of type `ty` that `code` computes
+    /// in `r10`, from the bits of the operands in `r10` and `r11`.
+    fn native(&mut self, value: ValueId, ty: &str, operands: &[ValueId], code: Vec<Asm>) {
+        for (&operand, reg) in operands.iter().zip([Reg::R10, Reg::R11]) {
+            let src = self.place(operand, 1);
+            self.mov(Place::Reg(reg), src);
+        }
+        for inst in code {
+            self.emit(inst);
+        }
+        self.set_sym(value, 0, ty, 0);
+        let dst = self.place(value, 1);
+        self.mov(dst, Place::Reg(Reg::R10));
+    }
+
+    /// Calls the runtime function and sets the value to its result.
+    fn call_value(&mut self, value: ValueId, symbol: &str, args: Vec<Arg>) {
+        self.call(symbol, args);
+        self.set_result(value);
+    }
+
+    /// The copies into the phis of `to` along the edge from `from`, and a
+    /// jump to it unless it comes next.
+    fn edge(&mut self, from: BlockId, to: BlockId, falls_through: bool) {
+        let mut moves = vec![];
+        for &value in &self.func.blocks[to.0].insts {
+            if let Inst::Phi(edges) = self.func.inst(value) {
+                if let Some(&(_, operand)) = edges.iter().find(|(b, _)| *b == from) {
+                    for word in 0..2 {
+                        moves.push((self.place(value, word), self.place(operand, word)));
+                    }
+                }
+            }
+        }
+        self.parallel_move(moves);
+        if !(falls_through && to.0 == from.0 + 1) {
+            self.emit(Asm::Jmp(self.blocks[to.0]));
+        }
+    }
+}
+
+/// The most argument slots a call in the function passes in an array.
+fn max_args(func: &Function) -> usize {
+    let mut most = 0;
+    for block in &func.blocks {
+        for &value in &block.insts {
+            let n = match func.inst(value) {
+                Inst::Closure(_, values) | Inst::Make(values) | Inst::Call(_, values, _) | Inst::Defer(_, values) => values.len(),
+                Inst::Composite(pairs) => 2 * pairs.len(),
+                _ => 0,
+            };
+            most = most.max(n);
+        }
+    }
+    most
+}
+
+/// The type and the code of a binary operator the machine does itself
+/// for `Frame::native`: `+`, `-` and `*` on ints, which wrap as Go's do,
+/// and comparisons of ints and bools. Division, which panics on zero, and
+/// operands of other types or not known statically are the runtime's.
+fn native_binary(op: BinOp, x: Option<Type>, y: Option<Type>, result: Option<&Type>) -> Option<(&'static str, Vec<Asm>)> {
+    let (r10, r11) = (Reg::R10, Reg::R11);
+    let ints = match (x, y) {
+        (Some(Type::Int), Some(Type::Int)) => true,
+        (Some(Type::Bool), Some(Type::Bool)) => false,
+        _ => return None,
+    };
+    match (op, result) {
+        (BinOp::Add, Some(Type::Int)) if ints => Some(("nm_t_int", vec![Asm::Add(r10, r11)])),
+        (BinOp::Sub, Some(Type::Int)) if ints => Some(("nm_t_int", vec![Asm::Sub(r10, r11)])),
+        (BinOp::Mul, Some(Type::Int)) if ints => Some(("nm_t_int", vec![Asm::Imul(r10, r11)])),
+        (_, Some(Type::Bool)) => {
+            let cond = match op {
+                BinOp::Eq => Cond::E,
+                BinOp::Ne => Cond::Ne,
+                BinOp::Lt if ints => Cond::L,
+                BinOp::Le if ints => Cond::Le,
+                BinOp::Gt if ints => Cond::G,
+                BinOp::Ge if ints => Cond::Ge,
+                _ => return None,
+            };
+            Some(("nm_t_bool", vec![Asm::Cmp(r10, r11), Asm::Set(cond, r10), Asm::MovzxByte(r10)]))
+        }
+        _ => None,
+    }
+}
+
+fn round_up(n: i32, to: i32) -> i32 {
+    (n + to - 1) / to * to
+}
+
+impl Generator {
+    fn label(&mut self) -> Label {
+        self.labels += 1;
+        self.labels
+    }
+
+    /// The symbol of a NUL-terminated copy of the bytes.
+    fn cstring(&mut self, bytes: &[u8]) -> String {
+        if let Some(i) = self.cstrings.get(bytes) {
+            return format!("nm_C{}", i);
+        }
+        let i = self.cstrings.len();
+        self.cstrings.insert(bytes.to_vec(), i);
+        let mut data = bytes.to_vec();
+        data.push(0);
+        self.program.data.push(Data { name: format!("nm_C{}", i), align: 1, bytes: data, addresses: vec![] });
+        format!("nm_C{}", i)
+    }
+
+    /// The symbol of the runtime's `nm_string` for a string literal.
+    fn string_ref(&mut self, s: &Rc<[u8]>) -> String {
+        if let Some(i) = self.strings.get(s) {
+            return format!("nm_S{}", i);
+        }
+        let i = self.strings.len();
+        self.strings.insert(Rc::clone(s), i);
+        let chars = self.cstring(s);
+        let mut bytes = (s.len() as u64).to_le_bytes().to_vec();
+        bytes.resize(16, 0);
+        self.program.data.push(Data { name: format!("nm_S{}", i), align: 16, bytes, addresses: vec![(8, chars)] });
+        format!("nm_S{}", i)
+    }
+
+    /// The symbol of the descriptor of the type, laying one out the first
+    /// time a type is seen. Descriptors are the runtime's `nm_type`.
+    fn type_ref(&mut self, ty: &Type) -> String {
+        if let Some(name) = c::runtime_type(ty) {
+            return format!("nm_t_{}", name);
+        }
+        if let Some(i) = self.types.iter().position(|t| t.identical(ty)) {
+            return format!("nm_T{}", i);
+        }
+        let i = self.types.len();
+        self.types.push(ty.clone());
+        let name = self.cstring(ty.to_string().as_bytes());
+        let mut addresses = vec![(8, name)];
+        let (kind, named, nfields) = match ty {
+            Type::Slice(elem) | Type::Pointer(elem) | Type::Chan(elem) => {
+                addresses.push((16, self.type_ref(elem)));
+                let kind = match ty {
+                    Type::Slice(_) => 7,
+                    Type::Pointer(_) => 9,
+                    _ => 10,
+                };
+                (kind, false, 0)
+            }
+            Type::Map(key, elem) => {
+                addresses.push((16, self.type_ref(elem)));
+                addresses.push((24, self.type_ref(key)));
+                (8, false, 0)
+            }
+            Type::Struct(st) => {
+                let fields = st.fields.borrow().clone();
+                if !fields.is_empty() {
+                    let names: Vec<String> = fields.iter().map(|f| self.cstring(f.name.as_bytes())).collect();
+                    let types: Vec<String> = fields.iter().map(|f| self.type_ref(&f.ty)).collect();
+                    for (suffix, symbols) in [("f", names), ("t", types)] {
+                        self.program.data.push(Data {
+                            name: format!("nm_T{}_{}", i, suffix),
+                            align: 8,
+                            bytes: vec![0; 8 * fields.len()],
+                            addresses: symbols.into_iter().enumerate().map(|(j, s)| (8 * j, s)).collect(),
+                        });
+                    }
+                    addresses.push((40, format!("nm_T{}_f", i)));
+                    addresses.push((48, format!("nm_T{}_t", i)));
+                }
+                (11, st.name.is_some(), fields.len())
+            }
+            Type::Interface(it) => (12, it.name.is_some(), 0),
+            // the runtime describes the rest
+            _ => unreachable!("type {} has a runtime descriptor", ty),
+        };
+        let mut bytes = vec![0; 56];
+        bytes[0..4].copy_from_slice(&(kind as u32).to_le_bytes());
+        bytes[32..36].copy_from_slice(&(named as u32).to_le_bytes());
+        bytes[36..40].copy_from_slice(&(nfields as u32).to_le_bytes());
+        self.program.data.push(Data { name: format!("nm_T{}", i), align: 16, bytes, addresses });
+        format!("nm_T{}", i)
+    }
+
+    fn unsupported(&mut self, func: &Function, what: &str) -> Option<()> {
+        let msg = format!("{}: {} not supported by the x86-64 backend", func.name, what);
+        if !self.errors.contains(&msg) {
+            self.errors.push(msg);
+        }
+        None
+    }
+
+    /// The code for an IR function, or None after recording why it cannot
+    /// have any.
+    fn function(&mut self, module: &Module, id: FuncId) -> Option<Vec<Asm>> {
+        let func = module.function(id);
+        let framed = func.recover.is_some();
+        let alloc = regalloc::allocate(func, framed);
+
+        // the frame, below the saved registers: self and argv, the
+        // deferred calls' frame, the slots, then tuples and iterators;
+        // argument arrays at the bottom
+        let saved = alloc.saved.clone();
+        let mut used = 8 * saved.len() as i32;
+        let mut reserve = |size: i32| {
+            used = round_up(used + size, if size >= 16 { 16 } else { 8 });
+            Mem::new(Reg::Rbp, -used)
+        };
+        let self_at = reserve(8);
+        let argv_at = reserve(8);
+        let fr = if framed { reserve(FRAME_SIZE) } else { Mem::new(Reg::Rbp, 0) };
+        let slots = reserve(16 * alloc.slots as i32);
+        let slots_at = slots.disp + 16 * (alloc.slots as i32 - 1);
+        let mut areas = HashMap::new();
+        for block in &func.blocks {
+            for &value in &block.insts {
+                match func.ty(value) {
+                    Ty::Tuple(types) => {
+                        areas.insert(value, reserve(16 * types.len().max(1) as i32));
+                    }
+                    Ty::Iter => {
+                        areas.insert(value, reserve(ITER_SIZE));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let size = round_up(used + 16 * max_args(func) as i32, 16) - 8 * saved.len() as i32;
+
+        let blocks = (0..func.blocks.len()).map(|_| self.label()).collect();
+        let mut f = Frame { func, alloc, code: vec![], blocks, self_at, argv_at, slots_at, areas, fr };
+        f.emit(Asm::Push(Reg::Rbp));
+        f.emit(Asm::Mov(Reg::Rbp, Reg::Rsp));
+        for &reg in &saved {
+            f.emit(Asm::Push(reg));
+        }
+        f.emit(Asm::SubImm(Reg::Rsp, size));
+        f.emit(Asm::Store(self_at, Reg::Rdi));
+        f.emit(Asm::Store(argv_at, Reg::Rdx));
+        let name = self.cstring(func.name.as_bytes());
+        f.call("nm_enter", vec![Arg::Sym(name), Arg::Imm(!func.params.is_empty() as i64)]);
+        if let Some(recover) = func.recover {
+            let body = self.label();
+            f.call("nm_frame_push", vec![Arg::Addr(fr)]);
+            f.call("_setjmp", vec![Arg::Addr(fr)]);
+            f.emit(Asm::Test32(Reg::Rax, Reg::Rax));
+            f.emit(Asm::Jz(body));
+            f.call("nm_unwind", vec![Arg::Addr(fr)]);
+            f.emit(Asm::Jmp(f.blocks[recover.0]));
+            f.emit(Asm::Label(body));
+        }
+        for (i, block) in func.blocks.iter().enumerate() {
+            f.emit(Asm::Label(f.blocks[i]));
+            for &value in &block.insts {
+                self.inst(module, &mut f, BlockId(i), value)?;
+            }
+        }
+        // each return restores the registers it saved
+        let mut epilogue = vec![Asm::Lea(Reg::Rsp, Mem::new(Reg::Rbp, -8 * saved.len() as i32))];
+        epilogue.extend(saved.iter().rev().map(|&reg| Asm::Pop(reg)));
+        epilogue.extend([Asm::Pop(Reg::Rbp), Asm::Ret]);
+        let mut out = vec![];
+        for inst in f.code {
+            match inst {
+                Asm::Ret => out.extend(epilogue.iter().cloned()),
+                inst => out.push(inst),
+            }
+        }
+        Some(out)
+    }
+
+    /// The code for one instruction.
+    fn inst(&mut self, module: &Module, f: &mut Frame, block: BlockId, id: ValueId) -> Option<()> {
+        let func = f.func;
+        let data = &func.values[id.0];
+        let ty = data.ty.value().cloned();
+        let ty_ref = |gen: &mut Generator| gen.type_ref(ty.as_ref().unwrap_or(&Type::Nil));
+        let static_ty = |value: ValueId| func.ty(value).value().cloned();
+        match &data.inst {
+            Inst::Const(c) => {
+                match c {
+                    Const::Nil => {
+                        let t = ty_ref(self);
+                        f.call_value(id, "nm_zero", vec![Arg::Sym(t)]);
+                    }
+                    Const::Bool(b) => {
+                        f.set_sym(id, 0, "nm_t_bool", 0);
+                        f.set_imm(id, 1, *b as i64);
+                    }
+                    Const::Int(n) if matches!(ty, Some(Type::Float64)) => {
+                        f.set_sym(id, 0, "nm_t_float", 0);
+                        f.set_imm(id, 1, (*n as f64).to_bits() as i64);
+                    }
+                    Const::Int(n) => {
+                        f.set_sym(id, 0, "nm_t_int", 0);
+                        f.set_imm(id, 1, *n);
+                    }
+                    Const::Float(x) => {
+                        f.set_sym(id, 0, "nm_t_float", 0);
+                        f.set_imm(id, 1, x.to_bits() as i64);
+                    }
+                    Const::String(s) => {
+                        let s = self.string_ref(s);
+                        f.set_sym(id, 0, "nm_t_string", 0);
+                        f.set_sym(id, 1, &s, 0);
+                    }
+                }
+                if let Some(t @ Type::Interface(_)) = &ty {
+                    if *c != Const::Nil {
+                        let t = self.type_ref(t);
+                        f.call_value(id, "nm_convert", vec![Arg::Value(id), Arg::Sym(t)]);
+                    }
+                }
+            }
+            Inst::Zero => {
+                let t = ty_ref(self);
+                f.call_value(id, "nm_zero", vec![Arg::Sym(t)]);
+            }
+            Inst::Param(i) => {
+                // not r10, which moves between memory
+                f.emit(Asm::Load(Reg::R11, f.argv_at));
+                for word in 0..2 {
+                    let at = Mem::new(Reg::R11, 16 * *i as i32 + 8 * word as i32);
+                    let dst = f.place(id, word);
+                    f.mov(dst, Place::Mem(at));
+                }
+            }
+            Inst::FreeVar(i) => {
+                f.emit(Asm::Load(Reg::R10, f.self_at));
+                f.emit(Asm::Load(Reg::R10, Mem::new(Reg::R10, FUNC_FREE)));
+                f.emit(Asm::Load(Reg::R10, Mem::new(Reg::R10, 8 * *i as i32)));
+                let dst = f.place(id, 1);
+                f.mov(dst, Place::Reg(Reg::R10));
+                let t = ty_ref(self);
+                f.set_sym(id, 0, &t, 0);
+            }
+            Inst::Global(i) => {
+                let t = ty_ref(self);
+                f.set_sym(id, 0, &t, 0);
+                f.set_sym(id, 1, "nm_G", 16 * *i as i64);
+            }
+            Inst::Current => {
+                f.emit(Asm::Load(Reg::R10, f.self_at));
+                let dst = f.place(id, 1);
+                f.mov(dst, Place::Reg(Reg::R10));
+                f.set_sym(id, 0, "nm_t_func", 0);
+            }
+            Inst::Closure(target, captured) => {
+                let callee = module.function(*target);
+                let params: Vec<&str> = callee.params.iter().map(|p| p.name.as_str()).collect();
+                let sig = self.cstring(format!("fn({})", params.join(", ")).as_bytes());
+                let name = self.cstring(callee.name.as_bytes());
+                let free = f.array(captured);
+                let args = vec![
+                    Arg::Sym(format!("nm_f{}", target.0)),
+                    Arg::Sym(name),
+                    Arg::Sym(sig),
+                    Arg::Imm(callee.params.len() as i64),
+                    Arg::Imm(captured.len() as i64),
+                    free,
+                ];
+                f.call_value(id, "nm_closure", args);
+            }
+            Inst::Builtin(name) => {
+                if !BUILTINS.contains(name) {
+                    return self.unsupported(func, &format!("builtin {} as a value is", name));
+                }
+                f.set_sym(id, 0, "nm_t_func", 0);
+                f.set_sym(id, 1, &format!("nm_B_{}", name), 0);
+            }
+            Inst::Package(path, name) => {
+                if matches!(ty, Some(Type::Func)) {
+                    if !c::package_function(path, name) {
+                        return self.unsupported(func, &format!("{}.{} is", path, name));
+                    }
+                    f.set_sym(id, 0, "nm_t_func", 0);
+                    f.set_sym(id, 1, &format!("nm_P_{}_{}", path, name), 0);
+                } else {
+                    if !c::package_value(path, name) {
+                        return self.unsupported(func, &format!("{}.{} is", path, name));
+                    }
+                    f.call_value(id, &format!("nm_v_{}_{}", path, name), vec![]);
+                }
+            }
+            Inst::Phi(_) => {}
+            Inst::Unary(UnOp::Neg, x) if matches!((static_ty(*x), &ty), (Some(Type::Int), Some(Type::Int))) => {
+                f.native(id, "nm_t_int", &[*x], vec![Asm::Neg(Reg::R10)]);
+            }
+            Inst::Unary(UnOp::Not, x) if matches!((static_ty(*x), &ty), (Some(Type::Bool), Some(Type::Bool))) => {
+                f.native(id, "nm_t_bool", &[*x], vec![Asm::XorImm(Reg::R10, 1)]);
+            }
+            Inst::Unary(op, x) => {
+                let name = match op {
+                    UnOp::Neg => "nm_neg",
+                    UnOp::Not => "nm_not",
+                };
+                f.call(name, vec![Arg::Value(*x)]);
+                self.boxed(f, id, &ty);
+            }
+            Inst::Binary(op, a, b) => match native_binary(*op, static_ty(*a), static_ty(*b), ty.as_ref()) {
+                Some((t, code)) => f.native(id, t, &[*a, *b], code),
+                None => {
+                    let name = match op {
+                        BinOp::Add => "nm_add",
+                        BinOp::Sub => "nm_sub",
+                        BinOp::Mul => "nm_mul",
+                        BinOp::Div => "nm_div",
+                        BinOp::Rem => "nm_rem",
+                        BinOp::Eq => "nm_eq",
+                        BinOp::Ne => "nm_ne",
+                        BinOp::Lt => "nm_lt",
+                        BinOp::Le => "nm_le",
+                        BinOp::Gt => "nm_gt",
+                        BinOp::Ge => "nm_ge",
+                    };
+                    f.call(name, vec![Arg::Value(*a), Arg::Value(*b)]);
+                    self.boxed(f, id, &ty);
+                }
+            },
+            Inst::Convert(x) => match (&ty, static_ty(*x)) {
+                (Some(to), Some(from)) if !to.is_interface() && to.identical(&from) => {
+                    let moves = (0..2).map(|word| (f.place(id, word), f.place(*x, word))).collect();
+                    f.parallel_move(moves);
+                }
+                _ => {
+                    let t = ty_ref(self);
+                    f.call_value(id, "nm_conversion", vec![Arg::Value(*x), Arg::Sym(t)]);
+                }
+            },
+            Inst::Assert(x, false) => {
+                let t = ty_ref(self);
+                f.call_value(id, "nm_assert", vec![Arg::Value(*x), Arg::Sym(t)]);
+            }
+            Inst::Assert(x, true) => {
+                let t = self.type_ref(&c::tuple_first(&data.ty));
+                let out = f.areas[&id];
+                f.call("nm_assert_ok", vec![Arg::Value(*x), Arg::Sym(t), Arg::Addr(out)]);
+            }
+            Inst::Alloc => {
+                let t = ty_ref(self);
+                f.call_value(id, "nm_alloc_ptr", vec![Arg::Sym(t)]);
+            }
+            Inst::Load(p) => f.call_value(id, "nm_load", vec![Arg::Value(*p)]),
+            Inst::Store(p, x) => f.call("nm_store", vec![Arg::Value(*p), Arg::Value(*x)]),
+            Inst::Field(x, name) => {
+                let args = vec![
+                    Arg::Value(*x),
+                    Arg::Sym(self.cstring(name.as_bytes())),
+                    Arg::Imm(c::field_hint(static_ty(*x), name)),
+                ];
+                f.call_value(id, "nm_field", args);
+            }
+            Inst::FieldAddr(x, name) => {
+                let args = vec![
+                    Arg::Value(*x),
+                    Arg::Sym(self.cstring(name.as_bytes())),
+                    Arg::Imm(c::field_hint(static_ty(*x), name)),
+                    Arg::Sym(ty_ref(self)),
+                ];
+                f.call_value(id, "nm_field_addr", args);
+            }
+            Inst::Index(x, i, false) => f.call_value(id, "nm_index", vec![Arg::Value(*x), Arg::Value(*i)]),
+            Inst::Index(x, i, true) => {
+                let out = f.areas[&id];
+                f.call("nm_index_ok", vec![Arg::Value(*x), Arg::Value(*i), Arg::Addr(out)]);
+            }
+            Inst::IndexAddr(x, i) => {
+                let t = ty_ref(self);
+                f.call_value(id, "nm_index_addr", vec![Arg::Value(*x), Arg::Value(*i), Arg::Sym(t)]);
+            }
+            Inst::SetIndex(x, i, value) => f.call("nm_set_index", vec![Arg::Value(*x), Arg::Value(*i), Arg::Value(*value)]),
+            Inst::Slice(x, low, high) => {
+                let mut args = vec![Arg::Value(*x)];
+                for bound in [low, high] {
+                    match bound {
+                        Some(b) => args.push(Arg::Value(*b)),
+                        None => args.extend([Arg::Sym("nm_t_nil".to_string()), Arg::Imm(0)]),
+                    }
+                }
+                f.call_value(id, "nm_slice_expr", args);
+            }
+            Inst::BoundsCheck(i, n) => f.call("nm_check", vec![Arg::Value(*i), Arg::Value(*n)]),
+            Inst::Composite(pairs) => {
+                let t = ty_ref(self);
+                let flat: Vec<ValueId> = pairs.iter().flat_map(|&(k, x)| [k, x]).collect();
+                let array = f.array(&flat);
+                f.call_value(id, "nm_composite", vec![Arg::Sym(t), Arg::Imm(pairs.len() as i64), array]);
+            }
+            Inst::Make(sizes) => {
+                if matches!(ty, Some(Type::Chan(_))) {
+                    return self.unsupported(func, "channels are");
+                }
+                let t = ty_ref(self);
+                let array = f.array(sizes);
+                f.call_value(id, "nm_make", vec![Arg::Sym(t), Arg::Imm(sizes.len() as i64), array]);
+            }
+            Inst::Call(callee, args, spread) => {
+                let array = f.array(args);
+                let n = Arg::Imm(args.len() as i64);
+                match func.inst(*callee) {
+                    _ if *spread => f.call("nm_call_spread", vec![Arg::Value(*callee), n, array]),
+                    Inst::Builtin(name) if BUILTINS.contains(name) => {
+                        f.call(&format!("nm_b_{}", name), vec![Arg::Sym(format!("nm_B_{}", name)), n, array])
+                    }
+                    Inst::Package(path, name) if c::package_builtin(path, name) => f.call(
+                        &format!("nm_p_{}_{}", path, name),
+                        vec![Arg::Sym(format!("nm_P_{}_{}", path, name)), n, array],
+                    ),
+                    _ => f.call("nm_call", vec![Arg::Value(*callee), n, array]),
+                }
+                match &data.ty {
+                    Ty::Void => {}
+                    Ty::Tuple(types) => {
+                        let out = f.areas[&id];
+                        f.call("nm_untuple", vec![Arg::Addr(out), Arg::Imm(types.len() as i64), Arg::Result]);
+                    }
+                    _ => f.set_result(id),
+                }
+            }
+            Inst::Extract(t, i) => {
+                let at = f.areas[t];
+                for word in 0..2 {
+                    let dst = f.place(id, word);
+                    f.mov(dst, Place::Mem(at.offset(16 * *i as i32 + 8 * word as i32)));
+                }
+            }
+            Inst::Go(_, _) => return self.unsupported(func, "goroutines are"),
+            Inst::Defer(callee, args) => {
+                let array = f.array(args);
+                let fr = f.fr;
+                f.call("nm_defer", vec![Arg::Addr(fr), Arg::Value(*callee), Arg::Imm(args.len() as i64), array]);
+            }
+            Inst::RunDefers => {
+                let fr = f.fr;
+                f.call("nm_run_defers", vec![Arg::Addr(fr)]);
+            }
+            Inst::Send(_, _) | Inst::Recv(_, _) | Inst::Select(_, _) => return self.unsupported(func, "channels are"),
+            Inst::Range(x) => {
+                if matches!(static_ty(*x), Some(Type::Chan(_))) {
+                    return self.unsupported(func, "channels are");
+                }
+                let it = f.areas[&id];
+                f.call("nm_range", vec![Arg::Addr(it), Arg::Value(*x)]);
+            }
+            Inst::Next(it) => {
+                let (it, out) = (f.areas[it], f.areas[&id]);
+                f.call("nm_next", vec![Arg::Addr(it), Arg::Addr(out)]);
+            }
+            Inst::Jump(target) => f.edge(block, *target, true),
+            Inst::Branch(cond, t, e) => {
+                let otherwise = self.label();
+                if let Some(Type::Bool) = static_ty(*cond) {
+                    let bits = f.place(*cond, 1);
+                    f.mov(Place::Reg(Reg::Rax), bits);
+                } else {
+                    f.call("nm_truth", vec![Arg::Value(*cond)]);
+                }
+                f.emit(Asm::Test32(Reg::Rax, Reg::Rax));
+                f.emit(Asm::Jz(otherwise));
+                f.edge(block, *t, false);
+                f.emit(Asm::Label(otherwise));
+                f.edge(block, *e, true);
+            }
+            Inst::Return(value) => {
+                if func.recover.is_some() {
+                    let fr = f.fr;
+                    f.call("nm_frame_pop", vec![Arg::Addr(fr)]);
+                }
+                f.call("nm_leave", vec![]);
+                match value {
+                    Some(value) => {
+                        let (t, x) = (f.place(*value, 0), f.place(*value, 1));
+                        f.mov(Place::Reg(Reg::Rax), t);
+                        f.mov(Place::Reg(Reg::Rdx), x);
+                    }
+                    None => {
+                        f.emit(Asm::LeaSym(Reg::Rax, "nm_t_nil".to_string(), 0));
+                        f.emit(Asm::MovImm(Reg::Rdx, 0));
+                    }
+                }
+                // the epilogue replaces it
+                f.emit(Asm::Ret);
+            }
+        }
+        Some(())
+    }
+
+    /// Sets the value to an operator's result, boxing it when the IR
+    /// types it as an interface: the runtime computes with the values
+    /// interfaces hold.
+    fn boxed(&mut self, f: &mut Frame, id: ValueId, ty: &Option<Type>) {
+        if let Some(t @ Type::Interface(_)) = ty {
+            let t = self.type_ref(t);
+            f.call("nm_convert", vec![Arg::Result, Arg::Sym(t)]);
+        }
+        f.set_result(id);
+    }
+}
@@ -0,0 +1,214 @@
+//! Register allocation by linear scan. A value is a pair of 64-bit
+//! words, its type's descriptor and its bits, so it takes two registers or
+//! a 16-byte stack slot laid out as the runtime's `nm_value`. Nearly every
+//! instruction calls into the runtime, so only the callee-saved registers
+//! hold values; the caller-saved ones pass arguments and serve as scratch.
+//!
+//! Each value's live interval is the hull of the positions it is live at,
+//! from liveness over the blocks in layout order. Intervals are visited by
+//! start; when no pair is free, the one of those competing that ends last
+//! goes to the stack for its whole life.
+
+use super::asm::Reg;
+use crate::ir::{BlockId, Function, Inst, ValueId};
+use std::collections::HashSet;
+
+/// The registers values live in.
+const POOL: [Reg; 5] = [Reg::Rbx, Reg::R12, Reg::R13, Reg::R14, Reg::R15];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Loc {
+    /// The type's descriptor in the first, the bits in the second.
+    Regs(Reg, Reg),
+    /// The stack slot of that index.
+    Slot(usize),
+}
+
+pub struct Allocation {
+    /// Where each value is, for those that yield one value.
+    pub locs: Vec<Option<Loc>>,
+    pub slots: usize,
+    /// The callee-saved registers used, which the function saves.
+    pub saved: Vec<Reg>,
+}
+
+/// Allocates the function's values, all to the stack when `in_memory`
+/// is set, as they must be where a `longjmp` can resume the function.
+pub fn allocate(func: &Function, in_memory: bool) -> Allocation {
+    let n = func.values.len();
+    let has_loc: Vec<bool> = func.values.iter().map(|v| v.ty.value().is_some()).collect();
+    let mut alloc = Allocation { locs: vec![None; n], slots: 0, saved: vec![] };
+    if in_memory {
+        for block in &func.blocks {
+            for &value in &block.insts {
+                if has_loc[value.0] {
+                    alloc.locs[value.0] = Some(Loc::Slot(alloc.slots));
+                    alloc.slots += 1;
+                }
+            }
+        }
+        return alloc;
+    }
+
+    let intervals = intervals(func, &has_loc);
+    let mut order: Vec<(usize, usize, ValueId)> =
+        intervals.iter().enumerate().filter_map(|(i, iv)| iv.map(|(start, end)| (start, end, ValueId(i)))).collect();
+    order.sort();
+    let mut free: Vec<Reg> = POOL.iter().rev().copied().collect();
+    let mut active: Vec<(usize, ValueId)> = vec![];
+    for (start, end, value) in order {
+        // an interval that ends where this one starts still holds its
+        // registers, as an instruction may read its operands after it
+        // writes part of its result
+        active.retain(|&(active_end, other)| {
+            if active_end < start {
+                if let Some(Loc::Regs(t, x)) = alloc.locs[other.0] {
+                    free.push(x);
+                    free.push(t);
+                }
+                false
+            } else {
+                true
+            }
+        });
+        if free.len() < 2 {
+            let (i, &(last_end, last)) = active.iter().enumerate().max_by_key(|(_, (end, _))| *end).expect("active interval");
+            if last_end <= end {
+                alloc.locs[value.0] = Some(Loc::Slot(alloc.slots));
+                alloc.slots += 1;
+                continue;
+            }
+            if let Some(Loc::Regs(t, x)) = alloc.locs[last.0] {
+                free.push(x);
+                free.push(t);
+            }
+            alloc.locs[last.0] = Some(Loc::Slot(alloc.slots));
+            alloc.slots += 1;
+            active.remove(i);
+        }
+        let (t, x) = (free.pop().unwrap(), free.pop().unwrap());
+        alloc.locs[value.0] = Some(Loc::Regs(t, x));
+        active.push((end, value));
+    }
+    for reg in POOL {
+        if alloc.locs.iter().any(|loc| matches!(loc, Some(Loc::Regs(t, x)) if *t == reg || *x == reg)) {
+            alloc.saved.push(reg);
+        }
+    }
+    alloc
+}
+
+/// The live interval of each value that has a location: the first and
+/// last positions it is live at, where the instructions are numbered in
+/// layout order and a block's terminator is where the copies into its
+/// successors' phis happen.
+fn intervals(func: &Function, has_loc: &[bool]) -> Vec<Option<(usize, usize)>> {
+    let nblocks = func.blocks.len();
+    let mut pos = vec![0; func.values.len()];
+    let mut ranges = vec![(0, 0); nblocks];
+    let mut next: usize = 0;
+    for (b, block) in func.blocks.iter().enumerate() {
+        let start = next;
+        for &value in &block.insts {
+            pos[value.0] = next;
+            next += 1;
+        }
+        ranges[b] = (start, next.saturating_sub(1).max(start));
+    }
+
+    // defs and uses, with phi operands used at the ends of the
+    // predecessors they come from
+    let mut defs = vec![HashSet::new(); nblocks];
+    let mut uses = vec![HashSet::new(); nblocks];
+    let mut phi_uses = vec![HashSet::new(); nblocks];
+    for (b, block) in func.blocks.iter().enumerate() {
+        for &value in &block.insts {
+            match func.inst(value) {
+                Inst::Phi(edges) => {
+                    for &(pred, operand) in edges {
+                        if has_loc[operand.0] {
+                            phi_uses[pred.0].insert(operand);
+                        }
+                    }
+                }
+                inst => {
+                    for operand in inst.operands() {
+                        if has_loc[operand.0] && !defs[b].contains(&operand) {
+                            uses[b].insert(operand);
+                        }
+                    }
+                }
+            }
+            if has_loc[value.0] {
+                defs[b].insert(value);
+            }
+        }
+    }
+    let successors: Vec<Vec<usize>> = (0..nblocks).map(|b| func.successors(BlockId(b)).iter().map(|s| s.0).collect()).collect();
+    let mut live_in: Vec<HashSet<ValueId>> = vec![HashSet::new(); nblocks];
+    let mut live_out: Vec<HashSet<ValueId>> = vec![HashSet::new(); nblocks];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for b in (0..nblocks).rev() {
+            let mut out: HashSet<ValueId> = phi_uses[b].clone();
+            for &s in &successors[b] {
+                out.extend(live_in[s].iter().copied());
+            }
+            let mut inn: HashSet<ValueId> = uses[b].clone();
+            inn.extend(out.iter().filter(|v| !defs[b].contains(*v)));
+            if out != live_out[b] || inn != live_in[b] {
+                live_out[b] = out;
+                live_in[b] = inn;
+                changed = true;
+            }
+        }
+    }
+
+    let mut intervals: Vec<Option<(usize, usize)>> = vec![None; func.values.len()];
+    let mut cover = |value: ValueId, at: usize| {
+        let iv = &mut intervals[value.0];
+        *iv = Some(match *iv {
+            Some((start, end)) => (start.min(at), end.max(at)),
+            None => (at, at),
+        });
+    };
+    for (b, block) in func.blocks.iter().enumerate() {
+        let (start, end) = ranges[b];
+        for &value in &live_in[b] {
+            cover(value, start);
+        }
+        for &value in &live_out[b] {
+            cover(value, end);
+        }
+        for &value in &block.insts {
+            if has_loc[value.0] {
+                cover(value, pos[value.0]);
+            }
+            match func.inst(value) {
+                Inst::Phi(edges) => {
+                    // written by the copies at the ends of predecessors
+                    for &(pred, operand) in edges {
+                        if has_loc[value.0] {
+                            cover(value, ranges[pred.0].1);
+                        }
+                        if has_loc[operand.0] {
+                            cover(operand, ranges[pred.0].1);
+                        }
+                    }
+                    if has_loc[value.0] {
+                        cover(value, start);
+                    }
+                }
+                inst => {
+                    for operand in inst.operands() {
+                        if has_loc[operand.0] {
+                            cover(operand, pos[value.0]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    intervals
+}
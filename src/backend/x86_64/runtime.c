@@ -0,0 +1,31 @@
+/* What the x86-64 backend's code needs besides the C backend's runtime,
+ * which is compiled in front of this with NM_API empty, so that its
+ * definitions are there to link with. */
+
+/* The generated code keeps a frame or an iterator in its own stack
+ * frame, in as many bytes as these allow. */
+typedef char nm_frame_fits[sizeof(nm_frame) <= 256 ? 1 : -1];
+typedef char nm_iter_fits[sizeof(nm_iter) <= 64 ? 1 : -1];
+
+/* Unlinks a frame as its function returns. */
+void nm_frame_pop(nm_frame *fr) {
+    nm_handler = fr->prev;
+}
+
+/* Calls main, as the VM does, from a frame of its own, which traces
+ * omit. */
+void nm_call_main(nm_value main) {
+    nm_value f = nm_raw(main);
+    if (f.type->kind == NM_FUNC && f.u.p) {
+        nm_enter(NULL, 0);
+        nm_call(main, 0, NULL);
+    }
+}
+
+/* The generated program */
+
+void nm_run(void);
+
+int main(int argc, char **argv) {
+    return nm_main(argc, argv, nm_run);
+}
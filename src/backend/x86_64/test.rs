@@ -0,0 +1,177 @@
+use crate::backend::x86_64::*;
+use crate::ir::test::tests::{lower_source, PROGRAMS};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Whether there is a C compiler to link with, and executables for
+  /// x86-64 Linux run here.
+  fn can_link() -> bool {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+      return false;
+    }
+    Command::new(c::cc()).arg("--version").output().map(|o| o.status.success()).unwrap_or(false)
+  }
+
+  fn execute(exe: &Path, args: &[&str]) -> (String, String, i32) {
+    let output = Command::new(exe).args(args).output().unwrap();
+    let _ = fs::remove_file(exe);
+    (
+      String::from_utf8_lossy(&output.stdout).into_owned(),
+      String::from_utf8_lossy(&output.stderr).into_owned(),
+      output.status.code().unwrap_or(-1),
+    )
+  }
+
+  fn temp(suffix: &str) -> PathBuf {
+    static FILES: AtomicUsize = AtomicUsize::new(0);
+    env::temp_dir().join(format!("namu-x86-test-{}-{}{}", std::process::id(), FILES.fetch_add(1, Ordering::Relaxed), suffix))
+  }
+
+  /// Builds `source` into an object, links it into an executable and runs
+  /// that with `args`, giving its stdout, stderr and exit code; None when
+  /// it cannot be linked or run here.
+  fn run(source: &str, args: &[&str]) -> Option<(String, String, i32)> {
+    if !can_link() {
+      return None;
+    }
+    let program = generate(&lower_source(source)).unwrap_or_else(|errors| panic!("generate errors: {:?}", errors));
+    let exe = temp("");
+    compile(&program.object(), &exe).unwrap_or_else(|e| panic!("{}", e));
+    Some(execute(&exe, args))
+  }
+
+  fn check_output(source: &str, expected: &str) {
+    if let Some((stdout, stderr, code)) = run(source, &[]) {
+      assert_eq!((stdout.as_str(), stderr.as_str(), code), (expected, "", 0));
+    }
+  }
+
+  #[test]
+  fn test_programs() {
+    for (_, source, expected) in PROGRAMS {
+      check_output(source, expected);
+    }
+  }
+
+  #[test]
+  fn test_panic() {
+    let source = r#"let f = fn(x) { panic("boom") }
+let main = fn() { f(1) }"#;
+    if let Some((stdout, stderr, code)) = run(source, &[]) {
+      assert_eq!(stdout, "");
+      assert_eq!(stderr, "panic: boom\n\ngoroutine 1 [running]:\nmain.f(...)\nmain.main()\n");
+      assert_eq!(code, 2);
+    }
+  }
+
+  #[test]
+  fn test_os() {
+    let source = r#"import "fmt"
+import "os"
+let main = fn() {
+  defer fmt.Println("not run")
+  fmt.Println(os.Args[1:])
+  fmt.Fprintln(os.Stderr, "bye")
+  os.Exit(3)
+}"#;
+    if let Some(output) = run(source, &["a", "b"]) {
+      assert_eq!(output, ("[a b]\n".to_string(), "bye\n".to_string(), 3));
+    }
+  }
+
+  #[test]
+  fn test_loops_and_phis() {
+    check_output(
+      r#"import "fmt"
+let fib = fn(n) {
+  a, b := 0, 1
+  for i := 0; i < n; i++ { a, b = b, a+b }
+  return a
+}
+let main = fn() {
+  total := 0
+  for _, x := range []int{1, 2, 3, 4} {
+    if x%2 == 0 { total += x } else { total -= x }
+  }
+  fmt.Println(fib(50), total)
+  m := map[string]int{"a": 1}
+  v, ok := m["b"]
+  fmt.Println(v, ok)
+}"#,
+      "12586269025 2\n0 false\n",
+    );
+  }
+
+  #[test]
+  fn test_native_arithmetic() {
+    let source = r#"import "fmt"
+let main = fn() {
+  a, b := 7, -3
+  for i := 0; i < 3; i++ { a = a*2 - b + i }
+  p, q := a > b, a <= b
+  fmt.Println(a, -a, a == b, a != b, a < b, a >= b, p, !q, p == q)
+  x := 9223372036854775807
+  fmt.Println(x + 1, x * 3, a / b, a % b)
+}"#;
+    // typed ints and bools stay in registers; division goes to the runtime
+    let asm = generate(&lower_source(source)).unwrap().assembly();
+    for inst in ["add r10, r11", "imul r10, r11", "setl r10b", "neg r10", "xor r10, 1"] {
+      assert!(asm.contains(inst), "no {} in:\n{}", inst, asm);
+    }
+    for call in ["nm_add", "nm_mul", "nm_lt", "nm_eq", "nm_neg", "nm_not", "nm_truth"] {
+      assert!(!asm.contains(&format!("call {}\n", call)), "{} called in:\n{}", call, asm);
+    }
+    assert!(asm.contains("call nm_div\n"), "{}", asm);
+    check_output(source, "81 -81 false true false true true true false\n-9223372036854775808 9223372036854775805 -27 0\n");
+  }
+
+  #[test]
+  fn test_assembly() {
+    // the assembler's object runs as the backend's own does
+    if !can_link() {
+      return;
+    }
+    let program = generate(&lower_source(
+      r#"import "fmt"
+let main = fn() { n := 1; fmt.Println("hi", 1+2, n*3 - n, -n < n, !(n == 1)) }"#,
+    ))
+    .unwrap();
+    let (asm, runtime, exe) = (temp(".s"), temp(".c"), temp(""));
+    fs::write(&asm, program.assembly()).unwrap();
+    fs::write(&runtime, format!("#define NM_API\n{}\n{}", c::RUNTIME, RUNTIME)).unwrap();
+    let status = Command::new(c::cc()).arg("-o").arg(&exe).arg(&runtime).arg(&asm).args(["-lm", "-lpthread"]).status().unwrap();
+    let _ = fs::remove_file(&asm);
+    let _ = fs::remove_file(&runtime);
+    assert!(status.success());
+    assert_eq!(execute(&exe, &[]), ("hi 3 2 true false\n".to_string(), String::new(), 0));
+  }
+
+  #[test]
+  fn test_object() {
+    let program = generate(&lower_source("let main = fn() { println(1) }")).unwrap();
+    let object = program.object();
+    assert_eq!(&object[..4], b"\x7fELF");
+    // ET_REL, EM_X86_64
+    assert_eq!(&object[16..20], &[1, 0, 62, 0]);
+  }
+
+  #[test]
+  fn test_unsupported() {
+    let module = lower_source(
+      r#"let main = fn() {
+  c := make(chan int)
+  go fn() { c <- 1 }()
+  <-c
+}"#,
+    );
+    let errors = generate(&module).unwrap_err();
+    assert!(errors.iter().any(|e| e.ends_with("not supported by the x86-64 backend")), "{:?}", errors);
+  }
+}
@@ -0,0 +1,447 @@
+//! Build constraints: what decides whether a file is part of a build for
+//! a target. A `_test.go` name leaves a file out; a `_GOOS`, `_GOARCH` or
+//! `_GOOS_GOARCH` name suffix must match the target; and a `//go:build`
+//! line, or the legacy `// +build` lines it replaced, must hold.
+
+use std::fmt;
+
+#[cfg(test)]
+mod test;
+
+/// Latest Go release whose `go1.N` tag namu satisfies.
+pub const GO_MINOR: u32 = 21;
+
+/// What a build is for: the system and architecture `//go:build` lines
+/// are tested against, plus any extra tags.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub goos: String,
+    pub goarch: String,
+    pub tags: Vec<String>,
+}
+
+/// Systems that satisfy the `unix` tag.
+const UNIX: &[&str] = &["aix", "android", "darwin", "dragonfly", "freebsd", "hurd", "illumos", "ios", "linux", "netbsd", "openbsd", "solaris"];
+
+/// Values of GOOS that a file name suffix can name.
+pub const KNOWN_OS: &[&str] = &[
+    "aix", "android", "darwin", "dragonfly", "freebsd", "hurd", "illumos", "ios", "js", "linux", "nacl", "netbsd", "openbsd", "plan9", "solaris",
+    "wasip1", "windows", "zos",
+];
+
+/// Values of GOARCH that a file name suffix can name.
+pub const KNOWN_ARCH: &[&str] = &[
+    "386", "amd64", "amd64p32", "arm", "armbe", "arm64", "arm64be", "loong64", "mips", "mipsle", "mips64", "mips64le", "mips64p32",
+    "mips64p32le", "ppc", "ppc64", "ppc64le", "riscv", "riscv64", "s390", "s390x", "sparc", "sparc64", "wasm",
+];
+
+impl Target {
+    /// A target for `goos` and `goarch`, which must be known values.
+    pub fn new(goos: &str, goarch: &str, tags: Vec<String>) -> Result<Target, String> {
+        if !KNOWN_OS.contains(&goos) || !KNOWN_ARCH.contains(&goarch) {
+            return Err(format!("unsupported GOOS/GOARCH pair {}/{}", goos, goarch));
+        }
+        Ok(Target { goos: goos.to_string(), goarch: goarch.to_string(), tags })
+    }
+
+    /// The machine namu runs on, named as Go names it.
+    pub fn host() -> Target {
+        let goos = match std::env::consts::OS {
+            "macos" => "darwin",
+            os => os,
+        };
+        let goarch = match std::env::consts::ARCH {
+            "x86_64" => "amd64",
+            "x86" => "386",
+            "aarch64" => "arm64",
+            "powerpc64" => "ppc64",
+            "wasm32" => "wasm",
+            arch => arch,
+        };
+        Target { goos: goos.to_string(), goarch: goarch.to_string(), tags: vec![] }
+    }
+
+    /// Reports whether `tag` holds for the target: its system (or one the
+    /// system implies), its architecture, `unix`, a `go1.N` release up to
+    /// `GO_MINOR`, or an extra tag.
+    pub fn satisfies(&self, tag: &str) -> bool {
+        if tag == self.goos || tag == self.goarch || self.tags.iter().any(|t| t == tag) {
+            return true;
+        }
+        match tag {
+            "unix" => UNIX.contains(&self.goos.as_str()),
+            "linux" => self.goos == "android",
+            "darwin" => self.goos == "ios",
+            "solaris" => self.goos == "illumos",
+            _ => match tag.strip_prefix("go1.").map(str::parse::<u32>) {
+                Some(Ok(minor)) => (1..=GO_MINOR).contains(&minor),
+                _ => false,
+            },
+        }
+    }
+
+    /// Reports whether the file `name` holding `source` is built for the
+    /// target.
+    pub fn includes(&self, name: &str, source: &str) -> Result<bool, String> {
+        self.select(name, source).map(|s| s.included)
+    }
+
+    /// Decides whether the file `name` holding `source` is built for the
+    /// target, and why. Tests and names starting with `_` or `.` are left
+    /// out before anything else is looked at, and a name suffix that does
+    /// not match before the source is.
+    pub fn select(&self, name: &str, source: &str) -> Result<Selection, String> {
+        let base = name.rsplit(['/', '\\']).next().unwrap_or(name);
+        if base.starts_with(['_', '.']) {
+            return Ok(Selection::new(vec![Reason::Ignored]));
+        }
+        if base.ends_with("_test.go") {
+            return Ok(Selection::new(vec![Reason::Test]));
+        }
+        let mut reasons = vec![];
+        if let Some(suffix) = name_suffix(base) {
+            let matches = suffix.iter().all(|part| self.satisfies(part));
+            reasons.push(Reason::Suffix { suffix: format!("_{}", suffix.join("_")), matches });
+            if !matches {
+                return Ok(Selection::new(reasons));
+            }
+        }
+        if let Some(Constraint { expr, legacy }) = file_constraint(source)? {
+            let holds = expr.eval(&|tag| self.satisfies(tag));
+            reasons.push(Reason::Constraint { expr, legacy, holds });
+        }
+        Ok(Selection::new(reasons))
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.goos, self.goarch)
+    }
+}
+
+/// The `_GOOS`, `_GOARCH` or `_GOOS_GOARCH` suffix of a file name, split
+/// at the underscore. Everything up to the first underscore is the name
+/// proper, so `linux.go` has no suffix, and neither has `_test`.
+pub fn name_suffix(name: &str) -> Option<Vec<&str>> {
+    let stem = name.split('.').next().unwrap_or(name);
+    let rest = &stem[stem.find('_')?..];
+    let mut parts: Vec<&str> = rest.split('_').collect();
+    if parts.last() == Some(&"test") {
+        parts.pop();
+    }
+    let n = parts.len();
+    if n >= 3 && KNOWN_OS.contains(&parts[n - 2]) && KNOWN_ARCH.contains(&parts[n - 1]) {
+        return Some(parts[n - 2..].to_vec());
+    }
+    if n >= 2 && (KNOWN_OS.contains(&parts[n - 1]) || KNOWN_ARCH.contains(&parts[n - 1])) {
+        return Some(parts[n - 1..].to_vec());
+    }
+    None
+}
+
+/// Whether a file is built for a target, with the reasons that decided
+/// it, in the order they were looked at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub included: bool,
+    pub reasons: Vec<Reason>,
+}
+
+impl Selection {
+    fn new(reasons: Vec<Reason>) -> Selection {
+        Selection { included: reasons.iter().all(|r| !r.excludes()), reasons }
+    }
+}
+
+/// "no constraints", or the reasons separated by semicolons.
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.reasons.is_empty() {
+            return write!(f, "no constraints");
+        }
+        for (i, reason) in self.reasons.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Something that bears on whether a file is built.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// The name starts with `_` or `.`.
+    Ignored,
+    /// A `_test.go` file; namu has no test command to build it.
+    Test,
+    /// A name suffix, and whether the target matches it.
+    Suffix { suffix: String, matches: bool },
+    /// The file's constraint, and whether it holds for the target.
+    Constraint { expr: Expr, legacy: bool, holds: bool },
+}
+
+impl Reason {
+    pub fn excludes(&self) -> bool {
+        match self {
+            Reason::Ignored | Reason::Test => true,
+            Reason::Suffix { matches, .. } => !matches,
+            Reason::Constraint { holds, .. } => !holds,
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::Ignored => write!(f, "name starts with _ or ."),
+            Reason::Test => write!(f, "test file"),
+            Reason::Suffix { suffix, matches: true } => write!(f, "{} suffix matches", suffix),
+            Reason::Suffix { suffix, matches: false } => write!(f, "{} suffix does not match", suffix),
+            Reason::Constraint { expr, legacy, holds } => {
+                let line = if *legacy { "// +build" } else { "//go:build" };
+                let verb = if *holds { "holds" } else { "does not hold" };
+                write!(f, "{} {} {}", line, expr, verb)
+            }
+        }
+    }
+}
+
+/// A `//go:build` expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Tag(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn eval(&self, tag: &dyn Fn(&str) -> bool) -> bool {
+        match self {
+            Expr::Tag(name) => tag(name),
+            Expr::Not(x) => !x.eval(tag),
+            Expr::And(x, y) => x.eval(tag) && y.eval(tag),
+            Expr::Or(x, y) => x.eval(tag) || y.eval(tag),
+        }
+    }
+}
+
+/// Parenthesizes only where precedence needs it, as gofmt writes them.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Tag(name) => write!(f, "{}", name),
+            Expr::Not(x) => match **x {
+                Expr::And(..) | Expr::Or(..) => write!(f, "!({})", x),
+                _ => write!(f, "!{}", x),
+            },
+            Expr::And(x, y) => {
+                for (i, side) in [x, y].iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " && ")?;
+                    }
+                    match ***side {
+                        Expr::Or(..) => write!(f, "({})", side)?,
+                        _ => write!(f, "{}", side)?,
+                    }
+                }
+                Ok(())
+            }
+            Expr::Or(x, y) => write!(f, "{} || {}", x, y),
+        }
+    }
+}
+
+/// Parses the expression of a `//go:build` line: tags combined with `!`,
+/// `&&`, `||` and parentheses.
+pub fn parse_expr(text: &str) -> Result<Expr, String> {
+    let tokens = tokenize(text)?;
+    if tokens.is_empty() {
+        return Err("empty //go:build expression".to_string());
+    }
+    let mut p = ExprParser { tokens, pos: 0 };
+    let expr = p.or()?;
+    match p.tokens.get(p.pos) {
+        Some(token) => Err(format!("unexpected {} in //go:build expression", token)),
+        None => Ok(expr),
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '!' || c == '(' || c == ')' {
+            tokens.push(c.to_string());
+            chars.next();
+        } else if c == '&' || c == '|' {
+            chars.next();
+            if chars.next() != Some(c) {
+                return Err(format!("invalid operator {} in //go:build expression; want {}{}", c, c, c));
+            }
+            tokens.push(format!("{}{}", c, c));
+        } else if c.is_alphanumeric() || c == '_' || c == '.' {
+            let mut tag = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_' || **c == '.') {
+                tag.push(c);
+                chars.next();
+            }
+            tokens.push(tag);
+        } else {
+            return Err(format!("invalid character {:?} in //go:build expression", c));
+        }
+    }
+    Ok(tokens)
+}
+
+struct ExprParser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.as_str())
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut x = self.and()?;
+        while self.peek() == Some("||") {
+            self.pos += 1;
+            x = Expr::Or(Box::new(x), Box::new(self.and()?));
+        }
+        Ok(x)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut x = self.not()?;
+        while self.peek() == Some("&&") {
+            self.pos += 1;
+            x = Expr::And(Box::new(x), Box::new(self.not()?));
+        }
+        Ok(x)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        let token = match self.peek() {
+            Some(token) => token.to_string(),
+            None => return Err("unexpected end of //go:build expression".to_string()),
+        };
+        self.pos += 1;
+        match token.as_str() {
+            "!" => Ok(Expr::Not(Box::new(self.not()?))),
+            "(" => {
+                let x = self.or()?;
+                if self.peek() != Some(")") {
+                    return Err("missing ) in //go:build expression".to_string());
+                }
+                self.pos += 1;
+                Ok(x)
+            }
+            "&&" | "||" | ")" => Err(format!("unexpected {} in //go:build expression", token)),
+            _ => Ok(Expr::Tag(token)),
+        }
+    }
+}
+
+/// The constraint of a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub expr: Expr,
+    /// Made of `// +build` lines rather than a `//go:build` line.
+    pub legacy: bool,
+}
+
+/// The constraint among the comments that open `source`, before its
+/// package clause. A `//go:build` line wins over `// +build` lines, which
+/// only count when a blank line follows them, and all of which must hold.
+pub fn file_constraint(source: &str) -> Result<Option<Constraint>, String> {
+    let mut found = None;
+    // +build lines, each with whether a blank line came after it
+    let mut plus = vec![];
+    let mut in_block = false;
+    for line in source.lines() {
+        let line = line.trim();
+        if in_block {
+            in_block = !line.contains("*/");
+            continue;
+        }
+        if line.is_empty() {
+            plus.iter_mut().for_each(|(_, blank)| *blank = true);
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("/*") {
+            in_block = !rest.contains("*/");
+            continue;
+        }
+        let comment = match line.strip_prefix("//") {
+            Some(comment) => comment,
+            None => break,
+        };
+        if let Some(rest) = comment.trim_start().strip_prefix("+build") {
+            if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+                plus.push((rest, false));
+            }
+            continue;
+        }
+        let expr = match comment.strip_prefix("go:build") {
+            Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => rest,
+            _ => continue,
+        };
+        if found.is_some() {
+            return Err("multiple //go:build comments".to_string());
+        }
+        found = Some(parse_expr(expr)?);
+    }
+    if let Some(expr) = found {
+        return Ok(Some(Constraint { expr, legacy: false }));
+    }
+    let mut legacy = None;
+    for (line, _) in plus.into_iter().filter(|(_, blank)| *blank) {
+        let expr = parse_plus_build(line)?;
+        legacy = Some(match legacy {
+            Some(x) => Expr::And(Box::new(x), Box::new(expr)),
+            None => expr,
+        });
+    }
+    Ok(legacy.map(|expr| Constraint { expr, legacy: true }))
+}
+
+/// Parses the text of a `// +build` line: options separated by spaces,
+/// any of which may hold, each made of terms separated by commas, all of
+/// which must, each a tag or a negated one.
+pub fn parse_plus_build(text: &str) -> Result<Expr, String> {
+    let mut line = None;
+    for option in text.split_whitespace() {
+        let mut all = None;
+        for term in option.split(',') {
+            let (negated, tag) = match term.strip_prefix('!') {
+                Some(tag) => (true, tag),
+                None => (false, term),
+            };
+            if tag.is_empty() || !tag.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+                return Err(format!("invalid term {:?} in // +build line", term));
+            }
+            let mut x = Expr::Tag(tag.to_string());
+            if negated {
+                x = Expr::Not(Box::new(x));
+            }
+            all = Some(match all {
+                Some(all) => Expr::And(Box::new(all), Box::new(x)),
+                None => x,
+            });
+        }
+        if let Some(all) = all {
+            line = Some(match line {
+                Some(line) => Expr::Or(Box::new(line), Box::new(all)),
+                None => all,
+            });
+        }
+    }
+    line.ok_or_else(|| "empty // +build line".to_string())
+}
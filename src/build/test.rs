@@ -0,0 +1,188 @@
+use crate::build::*;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn linux() -> Target {
+    Target { goos: "linux".to_string(), goarch: "amd64".to_string(), tags: vec!["integration".to_string()] }
+  }
+
+  #[test]
+  fn test_parse_and_eval() {
+    let target = linux();
+    let tests = [
+      ("linux", true),
+      ("!windows", true),
+      ("linux && amd64", true),
+      ("linux && arm64", false),
+      ("darwin || amd64", true),
+      ("unix && !(arm || 386)", true),
+      ("go1.18", true),
+      ("go1.99", false),
+      ("integration", true),
+      ("ignore", false),
+      ("!linux || windows && amd64", false),
+    ];
+    for (input, expected) in tests.iter() {
+      let expr = parse_expr(input).unwrap_or_else(|e| panic!("{:?}: {}", input, e));
+      if expr.eval(&|tag| target.satisfies(tag)) != *expected {
+        panic!("{:?}: expected {}", input, expected);
+      }
+    }
+  }
+
+  #[test]
+  fn test_expr_display() {
+    let tests = [
+      ("a&&b", "a && b"),
+      ("(a || b) && !c", "(a || b) && !c"),
+      ("!(a && b)", "!(a && b)"),
+      ("a || b && c", "a || b && c"),
+    ];
+    for (input, expected) in tests.iter() {
+      let got = parse_expr(input).unwrap().to_string();
+      if got != *expected {
+        panic!("{:?}: expected={:?}, got={:?}", input, expected, got);
+      }
+    }
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    let tests = [
+      ("", "empty //go:build expression"),
+      ("linux &&", "unexpected end of //go:build expression"),
+      ("(linux", "missing ) in //go:build expression"),
+      ("linux & amd64", "invalid operator & in //go:build expression; want &&"),
+      ("linux)", "unexpected ) in //go:build expression"),
+      ("a,b", "invalid character ',' in //go:build expression"),
+    ];
+    for (input, expected) in tests.iter() {
+      match parse_expr(input) {
+        Err(msg) if msg == *expected => {}
+        got => panic!("{:?}: expected error {:?}, got={:?}", input, expected, got),
+      }
+    }
+  }
+
+  #[test]
+  fn test_file_constraint() {
+    let target = linux();
+    let tests = [
+      ("package main\n", Ok(true)),
+      ("// Copyright\n\n//go:build windows\n\npackage main\n", Ok(false)),
+      ("/* block\n comment */\n//go:build linux\npackage main\n", Ok(true)),
+      ("package main\n\n//go:build windows\n", Ok(true)),
+      ("//go:buildx windows\npackage main\n", Ok(true)),
+      ("//go:build linux\n//go:build amd64\npackage main\n", Err("multiple //go:build comments".to_string())),
+    ];
+    for (source, expected) in tests {
+      let got = target.includes("a.go", source);
+      if got != expected {
+        panic!("{:?}: expected={:?}, got={:?}", source, expected, got);
+      }
+    }
+    if target.includes("a_test.go", "package main\n") != Ok(false) {
+      panic!("test files should be excluded");
+    }
+  }
+
+  #[test]
+  fn test_plus_build() {
+    let tests = [
+      ("linux,amd64 darwin", "linux && amd64 || darwin"),
+      ("!windows,!plan9", "!windows && !plan9"),
+      ("  ignore  ", "ignore"),
+    ];
+    for (input, expected) in tests.iter() {
+      let got = parse_plus_build(input).unwrap_or_else(|e| panic!("{:?}: {}", input, e)).to_string();
+      if got != *expected {
+        panic!("{:?}: expected={:?}, got={:?}", input, expected, got);
+      }
+    }
+    for (input, expected) in [("", "empty // +build line"), ("linux,", "invalid term \"\" in // +build line"), ("!!linux", "invalid term \"!!linux\" in // +build line")] {
+      match parse_plus_build(input) {
+        Err(msg) if msg == expected => {}
+        got => panic!("{:?}: expected error {:?}, got={:?}", input, expected, got),
+      }
+    }
+  }
+
+  #[test]
+  fn test_plus_build_placement() {
+    let target = linux();
+    let tests = [
+      ("// +build windows\n\npackage main\n", false),
+      ("// +build windows\npackage main\n", true),
+      ("// +build linux\n// +build 386\n\npackage main\n", false),
+      ("// +build windows\n\n//go:build linux\n\npackage main\n", true),
+      ("// Package doc.\n// +build windows\npackage main\n", true),
+      ("package main\n\n// +build windows\n\n", true),
+    ];
+    for (source, expected) in tests {
+      match target.includes("a.go", source) {
+        Ok(got) if got == expected => {}
+        got => panic!("{:?}: expected={:?}, got={:?}", source, expected, got),
+      }
+    }
+  }
+
+  #[test]
+  fn test_name_suffix() {
+    let tests: [(&str, Option<Vec<&str>>); 9] = [
+      ("a.go", None),
+      ("linux.go", None),
+      ("a_linux.go", Some(vec!["linux"])),
+      ("a_linux_test.go", Some(vec!["linux"])),
+      ("a_linux_arm64.go", Some(vec!["linux", "arm64"])),
+      ("a_arm64_linux.go", Some(vec!["linux"])),
+      ("a_amd64.go", Some(vec!["amd64"])),
+      ("a_unix.go", None),
+      ("a.b_linux.go", None),
+    ];
+    for (name, expected) in tests.iter() {
+      if name_suffix(name) != *expected {
+        panic!("{:?}: expected={:?}, got={:?}", name, expected, name_suffix(name));
+      }
+    }
+  }
+
+  #[test]
+  fn test_select() {
+    let target = linux();
+    let tests = [
+      ("a.go", "package a\n", true, "no constraints"),
+      ("dir/_a.go", "not go\n", false, "name starts with _ or ."),
+      ("a_test.go", "package a\n", false, "test file"),
+      ("a_windows.go", "//go:build !!!\n", false, "_windows suffix does not match"),
+      ("a_android.go", "package a\n", false, "_android suffix does not match"),
+      ("a_amd64.go", "//go:build !cgo\n\npackage a\n", true, "_amd64 suffix matches; //go:build !cgo holds"),
+      ("a_linux.go", "// +build 386\n\npackage a\n", false, "_linux suffix matches; // +build 386 does not hold"),
+    ];
+    for (name, source, included, why) in tests.iter() {
+      let got = target.select(name, source).unwrap_or_else(|e| panic!("{}: {}", name, e));
+      if got.included != *included || got.to_string() != *why {
+        panic!("{}: expected={} ({}), got={} ({})", name, included, why, got.included, got);
+      }
+    }
+    let android = Target::new("android", "arm64", vec![]).unwrap();
+    if !android.includes("a_linux.go", "package a\n").unwrap() {
+      panic!("android should build _linux files");
+    }
+  }
+
+  #[test]
+  fn test_new_target() {
+    if Target::new("linux", "riscv64", vec![]).is_err() {
+      panic!("linux/riscv64 should be supported");
+    }
+    match Target::new("linux", "z80", vec![]) {
+      Err(msg) if msg == "unsupported GOOS/GOARCH pair linux/z80" => {}
+      got => panic!("expected error, got={:?}", got),
+    }
+    if Target::host().to_string().is_empty() {
+      panic!("host target has no name");
+    }
+  }
+}
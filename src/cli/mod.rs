@@ -0,0 +1,735 @@
+use crate::ast::{self, Program};
+use crate::build::{Reason, Selection, Target};
+use crate::compiler::Compiler;
+use crate::diagnostic::Diagnostic;
+use crate::highlight::ColorChoice;
+use crate::loader::{self, Loader, Module, Package};
+use crate::parser::Parser;
+use crate::vm::Vm;
+use crate::{backend, dump, format, ir, lexer, repl};
+use colored::Colorize;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Exit status when every input was processed without errors.
+pub const EXIT_OK: i32 = 0;
+/// Exit status when a program has parse or compile errors.
+pub const EXIT_DIAGNOSTICS: i32 = 1;
+/// Exit status for usage and I/O errors, and for runtime errors.
+pub const EXIT_FAILURE: i32 = 2;
+
+const USAGE: &str = "usage: namu [--color=auto|always|never] <command> [arguments]
+
+commands:
+  run     compile and run a program
+  build   compile a program, to C, WebAssembly or a native executable with -o
+  check   parse and compile a program
+  list    list packages and the files they are built from
+  ir      print the SSA form of a program
+  fmt     format sources
+  tokens  print the token stream
+  ast     print the syntax tree
+  repl    start an interactive session
+
+Each command reads the named files or package directory, or standard input
+when none are given. Files are built for the GOOS and GOARCH in the
+environment, which default to the host's, and --tags=a,b adds build tags.
+Colors are used on terminals unless NO_COLOR is set.";
+
+/// Dispatches `namu <command> [arguments]` and returns the exit status.
+/// `--color=` may appear anywhere on the command line.
+pub fn main(args: &[String]) -> i32 {
+    let mut color = ColorChoice::Auto;
+    let mut args = args.to_vec();
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].strip_prefix("--color=") {
+            Some(value) => {
+                color = match ColorChoice::parse(value) {
+                    Some(choice) => choice,
+                    None => {
+                        eprintln!("invalid --color={}; want auto, always or never", value);
+                        return EXIT_FAILURE;
+                    }
+                };
+                args.remove(i);
+            }
+            None => i += 1,
+        }
+    }
+    color.apply();
+
+    let rest = if args.len() > 2 { &args[2..] } else { &[] };
+    match args.get(1).map(|a| a.as_str()) {
+        Some("run") => run_command(rest),
+        Some("build") => build_command(rest),
+        Some("check") => check_command(rest),
+        Some("list") => list_command(rest),
+        Some("ir") => ir_command(rest),
+        Some("fmt") => fmt_command(rest),
+        Some("tokens") => tokens_command(rest),
+        Some("ast") => ast_command(rest),
+        Some("repl") | None => repl_command(rest),
+        Some("help") | Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            EXIT_OK
+        }
+        Some(command) => {
+            eprintln!("namu {}: unknown command\n{}", command, USAGE);
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn print_error(msg: &str) {
+    eprintln!("{}", msg.red());
+}
+
+fn print_diagnostics(file: &str, source: &str, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprint!("{}", diagnostic.render(file, source));
+    }
+}
+
+/// Splits flags from file names; any flag not in `known` is a usage error.
+fn parse_args(args: &[String], known: &[&str], usage: &str) -> Result<(Vec<String>, Vec<String>), i32> {
+    let mut flags = vec![];
+    let mut files = vec![];
+    for arg in args {
+        if arg.starts_with('-') && arg != "-" {
+            if !known.contains(&arg.as_str()) {
+                eprintln!("{}", usage);
+                return Err(EXIT_FAILURE);
+            }
+            flags.push(arg.clone());
+        } else {
+            files.push(arg.clone());
+        }
+    }
+    Ok((flags, files))
+}
+
+/// Reads each named file, or standard input when there are none.
+fn read_inputs(files: &[String]) -> Result<Vec<(String, String)>, String> {
+    if files.is_empty() || files == ["-"] {
+        let mut input = String::new();
+        return match io::stdin().read_to_string(&mut input) {
+            Ok(_) => Ok(vec![("<standard input>".to_string(), input)]),
+            Err(e) => Err(format!("<standard input>: {}", e)),
+        };
+    }
+    let mut inputs = vec![];
+    for file in files {
+        match fs::read_to_string(file) {
+            Ok(input) => inputs.push((file.clone(), input)),
+            Err(e) => return Err(format!("{}: {}", file, e)),
+        }
+    }
+    Ok(inputs)
+}
+
+fn parse(input: &str) -> Result<Program, Vec<Diagnostic>> {
+    let mut p = Parser::new(lexer::Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    if p.diagnostics().is_empty() {
+        Ok(program)
+    } else {
+        Err(p.diagnostics())
+    }
+}
+
+fn print_load_errors(errors: &[loader::Error]) {
+    for error in errors {
+        match error {
+            loader::Error::File { name, source, diagnostics } => print_diagnostics(name, source, diagnostics),
+            loader::Error::Message(msg) => print_error(msg),
+        }
+    }
+}
+
+/// Takes `--tags=` out of the arguments and returns the target it and
+/// the GOOS and GOARCH environment variables describe, with the rest.
+fn target_args(args: &[String]) -> Result<(Target, Vec<String>), i32> {
+    let host = Target::host();
+    let mut tags = vec![];
+    let mut rest = vec![];
+    for arg in args {
+        match arg.strip_prefix("--tags=") {
+            Some(list) => tags.extend(list.split(',').filter(|t| !t.is_empty()).map(str::to_string)),
+            None => rest.push(arg.clone()),
+        }
+    }
+    let goos = std::env::var("GOOS").ok().filter(|v| !v.is_empty()).unwrap_or(host.goos);
+    let goarch = std::env::var("GOARCH").ok().filter(|v| !v.is_empty()).unwrap_or(host.goarch);
+    match Target::new(&goos, &goarch, tags) {
+        Ok(target) => Ok((target, rest)),
+        Err(msg) => {
+            print_error(&msg);
+            Err(EXIT_FAILURE)
+        }
+    }
+}
+
+/// Loads the program the arguments name, with every package it imports:
+/// one package directory, or files that form one package. Imports outside
+/// the standard library resolve through the enclosing go.mod.
+fn load_packages(args: &[String], target: Target) -> Result<Vec<Package>, i32> {
+    let dir = match args {
+        [arg] if Path::new(arg).is_dir() => Some(Path::new(arg)),
+        _ if args.iter().any(|arg| Path::new(arg).is_dir()) => {
+            print_error("cannot mix directories and files; name one package directory or its files");
+            return Err(EXIT_FAILURE);
+        }
+        _ => None,
+    };
+    let inputs = match dir {
+        Some(_) => vec![],
+        None => read_inputs(args).map_err(|msg| {
+            print_error(&msg);
+            EXIT_FAILURE
+        })?,
+    };
+    let module_dir = match (dir, args.first()) {
+        (Some(dir), _) => dir,
+        (None, Some(file)) if file != "-" => Path::new(file).parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new(".")),
+        _ => Path::new("."),
+    };
+    let module = Module::find(module_dir).map_err(|msg| {
+        print_error(&msg);
+        EXIT_DIAGNOSTICS
+    })?;
+    let loader = Loader::new(module, target);
+    let loaded = match dir {
+        Some(dir) => loader.load_dir(dir),
+        None => loader.load_files(module_dir, inputs),
+    };
+    loaded.map_err(|errors| {
+        print_load_errors(&errors);
+        EXIT_DIAGNOSTICS
+    })
+}
+
+/// Loads and compiles the program the arguments name, packages in
+/// dependency order. Diagnostics are printed as they are found; on any
+/// error the exit status is returned instead of the compiler.
+fn compile_packages(args: &[String], target: Target) -> Result<(Vec<Package>, Compiler), i32> {
+    let packages = load_packages(args, target)?;
+    match loader::compile(&packages) {
+        Ok(compiler) => Ok((packages, compiler)),
+        Err(errors) => {
+            print_load_errors(&errors);
+            Err(EXIT_DIAGNOSTICS)
+        }
+    }
+}
+
+/// `namu run [--seed=N] [--gcstress] [--tags=list] [dir | files] [-- args]`: runs
+/// the top-level statements of each package, imported ones first, then
+/// `main` when the program defines one. A seed makes map iteration and
+/// goroutine scheduling repeatable; `--gcstress` collects garbage after
+/// every allocation; the arguments after `--` are the program's `os.Args`.
+pub fn run_command(args: &[String]) -> i32 {
+    let usage = "usage: namu run [--seed=N] [--gcstress] [--tags=list] [path ...] [-- arg ...]";
+    let (args, program_args) = match args.iter().position(|a| a == "--") {
+        Some(i) => (&args[..i], &args[i + 1..]),
+        None => (args, &[][..]),
+    };
+    let (target, args) = match target_args(args) {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let mut seed = None;
+    let mut gc_stress = false;
+    let mut rest = vec![];
+    for arg in &args {
+        if arg == "--gcstress" {
+            gc_stress = true;
+            continue;
+        }
+        match arg.strip_prefix("--seed=") {
+            Some(n) => match n.parse::<u64>() {
+                Ok(n) => seed = Some(n),
+                Err(_) => {
+                    print_error(&format!("invalid --seed={}; want a non-negative integer", n));
+                    return EXIT_FAILURE;
+                }
+            },
+            None => rest.push(arg.clone()),
+        }
+    }
+    let (_, files) = match parse_args(&rest, &[], usage) {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    if let [file] = files.as_slice() {
+        if file.ends_with(".wasm") {
+            return run_wasm(file, program_args);
+        }
+    }
+    let (packages, mut compiler) = match compile_packages(&files, target) {
+        Ok(compiled) => compiled,
+        Err(status) => return status,
+    };
+    if let Some(package) = packages.last().filter(|p| p.name != "main") {
+        print_error(&format!("package {} is not a main package", package.path));
+        return EXIT_FAILURE;
+    }
+
+    let main_symbol = compiler.symbol_table.resolve("main");
+    let mut vm = Vm::new(compiler.bytecode());
+    if let Some(seed) = seed {
+        vm.set_seed(seed);
+    }
+    vm.set_gc_stress(gc_stress);
+    let name = files.first().map_or("main", |f| f.trim_end_matches(".go"));
+    vm.set_args(std::iter::once(name.to_string()).chain(program_args.iter().cloned()).collect());
+    match vm.run_main(main_symbol.map(|symbol| symbol.index)) {
+        Ok(code) => code,
+        Err(msg) => {
+            print_error(&msg);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// Runs a module `namu build` wrote, in the WebAssembly interpreter, with
+/// the file's name less `.wasm` as `os.Args[0]`.
+fn run_wasm(path: &str, program_args: &[String]) -> i32 {
+    let binary = match fs::read(path) {
+        Ok(binary) => binary,
+        Err(e) => {
+            print_error(&format!("{}: {}", path, e));
+            return EXIT_FAILURE;
+        }
+    };
+    let name = path.trim_end_matches(".wasm");
+    let args: Vec<String> = std::iter::once(name.to_string()).chain(program_args.iter().cloned()).collect();
+    match backend::wasm::run(&binary, &args, &mut io::stdout(), &mut io::stderr()) {
+        Ok(code) => code,
+        Err(msg) => {
+            print_error(&format!("{}: {}", path, msg));
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// `namu build [-O0|-O1|-O2] [-o output] [--tags=list] [files]`: compiles
+/// the program and reports errors. With `-o`, the program is translated,
+/// optimized at the level `-O` names: an output ending in `.wasm` gets a
+/// WebAssembly module and one in `.wat` its text; one ending in `.c` gets
+/// C source, any other a native executable the C compiler in `$CC`, `cc`
+/// by default, builds from it. With `-native`, or an output ending in `.s`
+/// or `.o`, the x86-64 backend generates the code instead: assembly text,
+/// an ELF object, or an executable the C compiler links with its runtime.
+pub fn build_command(args: &[String]) -> i32 {
+    let usage = "usage: namu build [-O0|-O1|-O2] [-native] [-o output] [--tags=list] [path ...]";
+    let (target, args) = match target_args(args) {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let mut output = None;
+    let mut rest = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "-o" {
+            match args.next() {
+                Some(path) => output = Some(path),
+                None => {
+                    eprintln!("{}", usage);
+                    return EXIT_FAILURE;
+                }
+            }
+        } else {
+            rest.push(arg);
+        }
+    }
+    let (flags, files) = match parse_args(&rest, &["-O0", "-O1", "-O2", "-native"], usage) {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let level = flags.iter().rfind(|flag| flag.starts_with("-O")).map_or(0, |flag| flag[2..].parse().unwrap_or(0));
+    let packages = match compile_packages(&files, target) {
+        Ok((packages, _)) => packages,
+        Err(status) => return status,
+    };
+    let output = match output {
+        Some(output) => output,
+        None => return EXIT_OK,
+    };
+    if let Some(package) = packages.last().filter(|p| p.name != "main") {
+        print_error(&format!("package {} is not a main package", package.path));
+        return EXIT_FAILURE;
+    }
+    let mut module = ir::lower(&packages);
+    let checked = ir::verify(&module).and_then(|_| ir::opt::PassManager::for_level(level).verify_each().run(&mut module));
+    if flags.iter().any(|flag| flag == "-native") || output.ends_with(".s") || output.ends_with(".o") {
+        return build_native(&module, checked, &output);
+    }
+    let wasm = output.ends_with(".wasm") || output.ends_with(".wat");
+    let generated = checked.and_then(|_| match wasm {
+        true => backend::wasm::generate(&module),
+        false => backend::c::generate(&module),
+    });
+    let code = match generated {
+        Ok(code) => code,
+        Err(errors) => {
+            for error in errors {
+                print_error(&error);
+            }
+            return EXIT_FAILURE;
+        }
+    };
+    if wasm || output.ends_with(".c") {
+        let written = match output.ends_with(".wasm") {
+            true => backend::wasm::assemble(&code).map(|binary| fs::write(&output, binary)),
+            false => Ok(fs::write(&output, &code)),
+        };
+        return match written.and_then(|result| result.map_err(|e| format!("{}: {}", output, e))) {
+            Ok(()) => EXIT_OK,
+            Err(msg) => {
+                print_error(&msg);
+                EXIT_FAILURE
+            }
+        };
+    }
+    match backend::c::compile(&code, Path::new(&output)) {
+        Ok(()) => EXIT_OK,
+        Err(msg) => {
+            print_error(&msg);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// The x86-64 half of `build_command`, for the module once it checks.
+fn build_native(module: &ir::Module, checked: Result<(), Vec<String>>, output: &str) -> i32 {
+    let program = match checked.and_then(|_| backend::x86_64::generate(module)) {
+        Ok(program) => program,
+        Err(errors) => {
+            for error in errors {
+                print_error(&error);
+            }
+            return EXIT_FAILURE;
+        }
+    };
+    let built = if output.ends_with(".s") {
+        fs::write(output, program.assembly()).map_err(|e| format!("{}: {}", output, e))
+    } else if output.ends_with(".o") {
+        fs::write(output, program.object()).map_err(|e| format!("{}: {}", output, e))
+    } else {
+        backend::x86_64::compile(&program.object(), Path::new(output))
+    };
+    match built {
+        Ok(()) => EXIT_OK,
+        Err(msg) => {
+            print_error(&msg);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// `namu check [--tags=list] [files]`: reports every parse and compile error without
+/// running anything.
+pub fn check_command(args: &[String]) -> i32 {
+    let (target, args) = match target_args(args) {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let (_, files) = match parse_args(&args, &[], "usage: namu check [--tags=list] [path ...]") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    match compile_packages(&files, target) {
+        Ok(_) => EXIT_OK,
+        Err(status) => status,
+    }
+}
+
+/// `namu ir [-O0|-O1|-O2] [--tags=list] [files]`: prints the SSA form the
+/// program lowers to, after checking it is well formed, optimized at the
+/// level `-O` names, none by default. Each pass is verified after it runs.
+pub fn ir_command(args: &[String]) -> i32 {
+    let (target, args) = match target_args(args) {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let (flags, files) = match parse_args(&args, &["-O0", "-O1", "-O2"], "usage: namu ir [-O0|-O1|-O2] [--tags=list] [path ...]") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let level = flags.last().map_or(0, |flag| flag[2..].parse().unwrap_or(0));
+    let packages = match compile_packages(&files, target) {
+        Ok((packages, _)) => packages,
+        Err(status) => return status,
+    };
+    let mut module = ir::lower(&packages);
+    let checked = ir::verify(&module).and_then(|_| ir::opt::PassManager::for_level(level).verify_each().run(&mut module));
+    if let Err(errors) = checked {
+        for error in errors {
+            print_error(&error);
+        }
+        return EXIT_FAILURE;
+    }
+    print!("{}", module);
+    EXIT_OK
+}
+
+/// `namu list [-deps] [-f format] [--tags=list] [dir | files]`: prints
+/// the import path of the package the arguments name, the one in the
+/// current directory by default, or with `-deps` of
+/// it and every package it imports, dependencies first. `-f` prints each
+/// package with a template of `{{.Field}}` actions instead, as `go list`
+/// does; `{{.Files}}` tells which files were built for the target and
+/// why.
+pub fn list_command(args: &[String]) -> i32 {
+    let usage = "usage: namu list [-deps] [-f format] [--tags=list] [path ...]";
+    let (target, args) = match target_args(args) {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let mut template = "{{.ImportPath}}".to_string();
+    let mut rest = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "-f" {
+            match args.next() {
+                Some(format) => template = format,
+                None => {
+                    eprintln!("{}", usage);
+                    return EXIT_FAILURE;
+                }
+            }
+        } else if let Some(format) = arg.strip_prefix("-f=") {
+            template = format.to_string();
+        } else {
+            rest.push(arg);
+        }
+    }
+    let (flags, files) = match parse_args(&rest, &["-deps"], usage) {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    // like go list, the package in the current directory by default
+    let files = if files.is_empty() { vec![".".to_string()] } else { files };
+    let packages = match load_packages(&files, target) {
+        Ok(packages) => packages,
+        Err(status) => return status,
+    };
+    let listed = if flags.is_empty() { &packages[packages.len() - 1..] } else { &packages[..] };
+    for package in listed {
+        match list_format(&template, package) {
+            Ok(text) => println!("{}", text),
+            Err(msg) => {
+                print_error(&msg);
+                return EXIT_FAILURE;
+            }
+        }
+    }
+    EXIT_OK
+}
+
+/// Expands the `{{.Field}}` actions of a `namu list -f` template for
+/// `package`. Lists print as `[a b]`, as Go prints slices.
+fn list_format(template: &str, package: &Package) -> Result<String, String> {
+    let names = |keep: &dyn Fn(&Selection) -> bool| {
+        let names: Vec<&str> = package.selections.iter().filter(|(_, s)| keep(s)).map(|(name, _)| name.as_str()).collect();
+        format!("[{}]", names.join(" "))
+    };
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => return Err("template: list: unclosed action".to_string()),
+        };
+        let action = rest[start + 2..end].trim();
+        let field = match action.strip_prefix('.') {
+            Some(field) if !field.is_empty() && field.chars().all(char::is_alphanumeric) => field,
+            _ => return Err(format!("template: list: unsupported action {{{{{}}}}}; want {{{{.Field}}}}", action)),
+        };
+        let value = match field {
+            "ImportPath" => package.path.clone(),
+            "Name" => package.name.clone(),
+            "Dir" => package.dir.display().to_string(),
+            "GoFiles" => names(&|s| s.included),
+            "TestGoFiles" => names(&|s| s.reasons == [Reason::Test]),
+            "IgnoredGoFiles" => names(&|s| !s.included && s.reasons != [Reason::Test] && s.reasons != [Reason::Ignored]),
+            "Imports" => {
+                let mut imports = package.imports.clone();
+                imports.sort();
+                format!("[{}]", imports.join(" "))
+            }
+            "Files" => {
+                let lines: Vec<String> = package
+                    .selections
+                    .iter()
+                    .map(|(name, s)| format!("{} {} ({})", if s.included { "+" } else { "-" }, name, s))
+                    .collect();
+                lines.join("\n")
+            }
+            _ => return Err(format!("template: list: can't evaluate field {} in type Package", field)),
+        };
+        out.push_str(&value);
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// `namu fmt [-l] [-w] [-d] [files]`: gofmt for namu sources. Without
+/// files, formats standard input to standard output. Exits with 2 when a
+/// file cannot be read or parsed.
+pub fn fmt_command(args: &[String]) -> i32 {
+    let (flags, files) = match parse_args(args, &["-l", "-w", "-d"], "usage: namu fmt [-l] [-w] [-d] [path ...]") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let list = flags.iter().any(|f| f == "-l");
+    let write = flags.iter().any(|f| f == "-w");
+    let diff = flags.iter().any(|f| f == "-d");
+
+    if files.is_empty() {
+        if write {
+            print_error("error: cannot use -w with standard input");
+            return EXIT_FAILURE;
+        }
+        let mut input = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut input) {
+            print_error(&format!("<standard input>: {}", e));
+            return EXIT_FAILURE;
+        }
+        return fmt_file("<standard input>", &input, list, false, diff);
+    }
+
+    let mut status = EXIT_OK;
+    for file in files {
+        match fs::read_to_string(&file) {
+            Ok(input) => status = status.max(fmt_file(&file, &input, list, write, diff)),
+            Err(e) => {
+                print_error(&format!("{}: {}", file, e));
+                status = EXIT_FAILURE;
+            }
+        }
+    }
+    status
+}
+
+fn fmt_file(name: &str, input: &str, list: bool, write: bool, diff: bool) -> i32 {
+    let formatted = match format::format_source(input) {
+        Ok(formatted) => formatted,
+        Err(errors) => {
+            for msg in errors {
+                print_error(&format!("{}: {}", name, msg));
+            }
+            return EXIT_FAILURE;
+        }
+    };
+
+    let changed = formatted != input;
+    if list && changed {
+        println!("{}", name);
+    }
+    if write && changed {
+        if let Err(e) = fs::write(name, &formatted) {
+            print_error(&format!("{}: {}", name, e));
+            return EXIT_FAILURE;
+        }
+    }
+    if diff && changed {
+        let a = format!("{}.orig", name);
+        print!("{}", format::diff::unified(&a, name, input, &formatted));
+    }
+    if !list && !write && !diff {
+        let _ = io::stdout().write_all(formatted.as_bytes());
+    }
+    EXIT_OK
+}
+
+/// `namu tokens [--json] [files]`: prints the token stream.
+pub fn tokens_command(args: &[String]) -> i32 {
+    let (flags, files) = match parse_args(args, &["--json"], "usage: namu tokens [--json] [path ...]") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let json = !flags.is_empty();
+
+    let inputs = match read_inputs(&files) {
+        Ok(inputs) => inputs,
+        Err(msg) => {
+            print_error(&msg);
+            return EXIT_FAILURE;
+        }
+    };
+    for (_, input) in inputs {
+        let tokens = dump::tokenize(&input);
+        if json {
+            print!("{}", dump::tokens_json(&tokens));
+        } else {
+            print!("{}", dump::tokens_text(&tokens));
+        }
+    }
+    EXIT_OK
+}
+
+/// `namu ast [--format=sexp|json|string] [files]`: prints the syntax tree.
+pub fn ast_command(args: &[String]) -> i32 {
+    let usage = "usage: namu ast [--format=sexp|json|string] [path ...]";
+    let mut format = "sexp".to_string();
+    let mut rest = vec![];
+    for arg in args {
+        match arg.strip_prefix("--format=") {
+            Some(f) => format = f.to_string(),
+            None => rest.push(arg.clone()),
+        }
+    }
+    let (_, files) = match parse_args(&rest, &[], usage) {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    if format != "sexp" && format != "json" && format != "string" {
+        print_error(&format!("unknown format {:?}; want sexp, json or string", format));
+        return EXIT_FAILURE;
+    }
+
+    let inputs = match read_inputs(&files) {
+        Ok(inputs) => inputs,
+        Err(msg) => {
+            print_error(&msg);
+            return EXIT_FAILURE;
+        }
+    };
+    let mut status = EXIT_OK;
+    for (name, input) in inputs {
+        let program = match parse(&input) {
+            Ok(program) => program,
+            Err(diagnostics) => {
+                print_diagnostics(&name, &input, &diagnostics);
+                status = EXIT_DIAGNOSTICS;
+                continue;
+            }
+        };
+        match format.as_str() {
+            "json" => print!("{}", dump::ast_json(&program)),
+            "string" => println!("{}", ast::Statement::string(&program)),
+            _ => print!("{}", dump::sexp(&program)),
+        }
+    }
+    status
+}
+
+/// `namu repl`: evaluates inputs interactively.
+pub fn repl_command(args: &[String]) -> i32 {
+    if !args.is_empty() {
+        eprintln!("usage: namu repl");
+        return EXIT_FAILURE;
+    }
+    match repl::start() {
+        Ok(exit) => exit.unwrap_or(EXIT_OK),
+        Err(e) => {
+            print_error(&format!("repl: {}", e));
+            EXIT_FAILURE
+        }
+    }
+}
@@ -1 +1,246 @@
-// TODO: Build lexer and parser first.
\ No newline at end of file
+use iota::iota;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[cfg(test)]
+mod test;
+
+pub type Instructions = Vec<u8>;
+pub type Opcode = u8;
+
+iota! {
+    pub const OP_CONSTANT: Opcode = iota;
+    ,OP_POP
+    ,OP_ADD
+    ,OP_SUB
+    ,OP_MUL
+    ,OP_DIV
+    ,OP_TRUE
+    ,OP_FALSE
+    ,OP_NULL
+    ,OP_EQUAL
+    ,OP_NOT_EQUAL
+    ,OP_GREATER_THAN
+    ,OP_MINUS
+    ,OP_BANG
+    ,OP_JUMP_NOT_TRUTHY
+    ,OP_JUMP
+    ,OP_GET_GLOBAL
+    ,OP_SET_GLOBAL
+    ,OP_GET_LOCAL
+    ,OP_SET_LOCAL
+    ,OP_GET_FREE
+    ,OP_CURRENT_CLOSURE
+    ,OP_CLOSURE
+    ,OP_CALL
+    ,OP_RETURN_VALUE
+    ,OP_RETURN
+    ,OP_MOD
+    ,OP_GREATER_EQUAL
+    ,OP_ASSIGN_GLOBAL
+    ,OP_ASSIGN_LOCAL
+    ,OP_ASSIGN_FREE
+    ,OP_ADDR_GLOBAL
+    ,OP_ADDR_LOCAL
+    ,OP_ADDR_FREE
+    ,OP_GET_BUILTIN
+    ,OP_INDEX
+    ,OP_INDEX_OK
+    ,OP_SET_INDEX
+    ,OP_INDEX_ADDR
+    ,OP_SLICE
+    ,OP_FIELD
+    ,OP_FIELD_ADDR
+    ,OP_DEREF
+    ,OP_STORE
+    ,OP_NEW_POINTER
+    ,OP_COMPOSITE
+    ,OP_ASSERT
+    ,OP_ASSERT_OK
+    ,OP_CONVERT
+    ,OP_RANGE
+    ,OP_NEXT
+    ,OP_GO
+    ,OP_SEND
+    ,OP_RECV
+    ,OP_RECV_OK
+    ,OP_SELECT
+    ,OP_DEFER
+    ,OP_CALL_SPREAD
+    ,OP_UNPACK
+}
+
+/// Where the instructions from `offset` on came from in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourcePos {
+    pub offset: usize,
+    pub file: Rc<str>,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The position of the instruction at `ip` in a line table sorted by
+/// offset.
+pub fn position_at(positions: &[SourcePos], ip: usize) -> Option<&SourcePos> {
+    let i = positions.partition_point(|p| p.offset <= ip);
+    i.checked_sub(1).map(|i| &positions[i])
+}
+
+/// Name and operand sizes in bytes of an opcode.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: Vec<usize>,
+}
+
+lazy_static! {
+    pub static ref DEFINITIONS: HashMap<Opcode, Definition> = {
+        let mut m = HashMap::new();
+        let mut def = |op: Opcode, name: &'static str, operand_widths: &[usize]| {
+            m.insert(op, Definition { name, operand_widths: operand_widths.to_vec() });
+        };
+        def(OP_CONSTANT, "OpConstant", &[2]);
+        def(OP_POP, "OpPop", &[]);
+        def(OP_ADD, "OpAdd", &[]);
+        def(OP_SUB, "OpSub", &[]);
+        def(OP_MUL, "OpMul", &[]);
+        def(OP_DIV, "OpDiv", &[]);
+        def(OP_TRUE, "OpTrue", &[]);
+        def(OP_FALSE, "OpFalse", &[]);
+        def(OP_NULL, "OpNull", &[]);
+        def(OP_EQUAL, "OpEqual", &[]);
+        def(OP_NOT_EQUAL, "OpNotEqual", &[]);
+        def(OP_GREATER_THAN, "OpGreaterThan", &[]);
+        def(OP_MINUS, "OpMinus", &[]);
+        def(OP_BANG, "OpBang", &[]);
+        def(OP_JUMP_NOT_TRUTHY, "OpJumpNotTruthy", &[2]);
+        def(OP_JUMP, "OpJump", &[2]);
+        def(OP_GET_GLOBAL, "OpGetGlobal", &[2]);
+        def(OP_SET_GLOBAL, "OpSetGlobal", &[2]);
+        def(OP_GET_LOCAL, "OpGetLocal", &[1]);
+        def(OP_SET_LOCAL, "OpSetLocal", &[1]);
+        def(OP_GET_FREE, "OpGetFree", &[1]);
+        def(OP_CURRENT_CLOSURE, "OpCurrentClosure", &[]);
+        // constant index of the function, number of free variables
+        def(OP_CLOSURE, "OpClosure", &[2, 1]);
+        def(OP_CALL, "OpCall", &[1]);
+        def(OP_RETURN_VALUE, "OpReturnValue", &[]);
+        def(OP_RETURN, "OpReturn", &[]);
+        def(OP_MOD, "OpMod", &[]);
+        def(OP_GREATER_EQUAL, "OpGreaterEqual", &[]);
+        // OpSet* defines a new variable; OpAssign* stores into an existing one
+        def(OP_ASSIGN_GLOBAL, "OpAssignGlobal", &[2]);
+        def(OP_ASSIGN_LOCAL, "OpAssignLocal", &[1]);
+        def(OP_ASSIGN_FREE, "OpAssignFree", &[1]);
+        def(OP_ADDR_GLOBAL, "OpAddrGlobal", &[2]);
+        def(OP_ADDR_LOCAL, "OpAddrLocal", &[1]);
+        def(OP_ADDR_FREE, "OpAddrFree", &[1]);
+        def(OP_GET_BUILTIN, "OpGetBuiltin", &[1]);
+        def(OP_INDEX, "OpIndex", &[]);
+        def(OP_INDEX_OK, "OpIndexOk", &[]);
+        // value, container, index
+        def(OP_SET_INDEX, "OpSetIndex", &[]);
+        def(OP_INDEX_ADDR, "OpIndexAddr", &[]);
+        def(OP_SLICE, "OpSlice", &[]);
+        // constant index of the field name
+        def(OP_FIELD, "OpField", &[2]);
+        def(OP_FIELD_ADDR, "OpFieldAddr", &[2]);
+        def(OP_DEREF, "OpDeref", &[]);
+        // value, pointer
+        def(OP_STORE, "OpStore", &[]);
+        def(OP_NEW_POINTER, "OpNewPointer", &[]);
+        // constant index of the type, number of (key, value) pairs
+        def(OP_COMPOSITE, "OpComposite", &[2, 2]);
+        def(OP_ASSERT, "OpAssert", &[2]);
+        def(OP_ASSERT_OK, "OpAssertOk", &[2]);
+        // constant index of the type, 1 for an explicit conversion T(x)
+        def(OP_CONVERT, "OpConvert", &[2, 1]);
+        def(OP_RANGE, "OpRange", &[]);
+        // jump target for when the range loop is done
+        def(OP_NEXT, "OpNext", &[2]);
+        // number of arguments, as OpCall
+        def(OP_GO, "OpGo", &[1]);
+        // channel, value
+        def(OP_SEND, "OpSend", &[]);
+        def(OP_RECV, "OpRecv", &[]);
+        def(OP_RECV_OK, "OpRecvOk", &[]);
+        // number of (is send, channel, value) cases, 1 when there is a default
+        def(OP_SELECT, "OpSelect", &[1, 1]);
+        // number of arguments, as OpCall
+        def(OP_DEFER, "OpDefer", &[1]);
+        // number of arguments, the last a slice whose elements are passed
+        def(OP_CALL_SPREAD, "OpCallSpread", &[1]);
+        // number of results the tuple on the stack must hold
+        def(OP_UNPACK, "OpUnpack", &[1]);
+        m
+    };
+}
+
+pub fn lookup(op: Opcode) -> Option<&'static Definition> {
+    DEFINITIONS.get(&op)
+}
+
+/// Encodes one instruction; operands are big-endian.
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let def = match lookup(op) {
+        Some(def) => def,
+        None => return vec![],
+    };
+
+    let mut instruction = vec![op];
+    for (operand, width) in operands.iter().zip(def.operand_widths.iter()) {
+        match width {
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            1 => instruction.push(*operand as u8),
+            _ => {}
+        }
+    }
+    instruction
+}
+
+/// Decodes the operands following an opcode, returning them and the
+/// number of bytes read.
+pub fn read_operands(def: &Definition, ins: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = vec![];
+    let mut offset = 0;
+    for width in &def.operand_widths {
+        match width {
+            2 => operands.push(read_u16(&ins[offset..]) as usize),
+            1 => operands.push(ins[offset] as usize),
+            _ => {}
+        }
+        offset += width;
+    }
+    (operands, offset)
+}
+
+pub fn read_u16(ins: &[u8]) -> u16 {
+    u16::from_be_bytes([ins[0], ins[1]])
+}
+
+/// Disassembly, one instruction per line prefixed with its offset.
+pub fn instructions_string(ins: &[u8]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < ins.len() {
+        let def = match lookup(ins[i]) {
+            Some(def) => def,
+            None => {
+                out.push_str(&format!("ERROR: opcode {} undefined\n", ins[i]));
+                i += 1;
+                continue;
+            }
+        };
+        let (operands, read) = read_operands(def, &ins[i + 1..]);
+        let operands: Vec<String> = operands.iter().map(|o| o.to_string()).collect();
+        let mut line = format!("{:04} {}", i, def.name);
+        if !operands.is_empty() {
+            line.push(' ');
+            line.push_str(&operands.join(" "));
+        }
+        out.push_str(&line);
+        out.push('\n');
+        i += 1 + read;
+    }
+    out
+}
@@ -0,0 +1,66 @@
+use crate::code::*;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_make() {
+    let tests: Vec<(Opcode, Vec<usize>, Vec<u8>)> = vec![
+      (OP_CONSTANT, vec![65534], vec![OP_CONSTANT, 255, 254]),
+      (OP_ADD, vec![], vec![OP_ADD]),
+      (OP_GET_LOCAL, vec![255], vec![OP_GET_LOCAL, 255]),
+      (OP_CLOSURE, vec![65534, 255], vec![OP_CLOSURE, 255, 254, 255]),
+    ];
+
+    for (op, operands, expected) in tests {
+      let instruction = make(op, &operands);
+      if instruction != expected {
+        panic!("make({:?}, {:?}) expected={:?}, got={:?}", op, operands, expected, instruction);
+      }
+    }
+  }
+
+  #[test]
+  fn test_read_operands() {
+    let tests: Vec<(Opcode, Vec<usize>, usize)> = vec![
+      (OP_CONSTANT, vec![65535], 2),
+      (OP_GET_LOCAL, vec![255], 1),
+      (OP_CLOSURE, vec![65535, 255], 3),
+    ];
+
+    for (op, operands, bytes_read) in tests {
+      let instruction = make(op, &operands);
+      let def = lookup(op).unwrap();
+      let (read, n) = read_operands(def, &instruction[1..]);
+      if n != bytes_read {
+        panic!("n wrong. want={}, got={}", bytes_read, n);
+      }
+      if read != operands {
+        panic!("operands wrong. want={:?}, got={:?}", operands, read);
+      }
+    }
+  }
+
+  #[test]
+  fn test_instructions_string() {
+    let instructions = [
+      make(OP_ADD, &[]),
+      make(OP_GET_LOCAL, &[1]),
+      make(OP_CONSTANT, &[2]),
+      make(OP_CONSTANT, &[65535]),
+      make(OP_CLOSURE, &[65535, 255]),
+    ].concat();
+
+    let expected = "0000 OpAdd
+0001 OpGetLocal 1
+0003 OpConstant 2
+0006 OpConstant 65535
+0009 OpClosure 65535 255
+";
+
+    if instructions_string(&instructions) != expected {
+      panic!("instructions wrongly formatted.\nwant={:?}\ngot={:?}", expected, instructions_string(&instructions));
+    }
+  }
+}
@@ -0,0 +1,2019 @@
+use crate::ast::visit::{self, Visitor};
+use crate::ast::*;
+use crate::code::*;
+use crate::diagnostic::{self, Diagnostic};
+use crate::dump::{span_of_expression, span_of_statement};
+use crate::format::format_expression;
+use crate::lexer::{unquote, Lexer};
+use crate::parser::Parser;
+use crate::stdlib::{self, Member};
+use crate::token::Token;
+use crate::types::{self, Field, Signature, Type};
+use crate::value::{builtins, CompiledFunction, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub mod symbol_table;
+
+#[cfg(test)]
+mod test;
+
+use symbol_table::*;
+
+/// Instructions of the top-level program plus the constant pool they index.
+#[derive(Debug, Clone)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub constants: Vec<Value>,
+    /// Line table of the top-level instructions.
+    pub positions: Vec<SourcePos>,
+}
+
+/// Stack trace name of the top-level program.
+pub const TOP_LEVEL_NAME: &str = "main.init";
+
+#[derive(Debug, Clone, Copy)]
+struct EmittedInstruction {
+    opcode: Opcode,
+    position: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    last_instruction: Option<EmittedInstruction>,
+    previous_instruction: Option<EmittedInstruction>,
+    /// Enclosing loops of the function, innermost last.
+    loops: Vec<Loop>,
+    positions: Vec<SourcePos>,
+    /// Stack trace name of the function.
+    name: String,
+    /// Function literals compiled so far that have no name of their own.
+    anonymous: usize,
+}
+
+/// Jumps out of a loop body, patched once their targets are known.
+#[derive(Debug, Clone, Default)]
+struct Loop {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+    /// A select statement, which `break` leaves but `continue` passes
+    /// through to the enclosing loop.
+    select: bool,
+}
+
+/// Name of the variable holding a range loop's iterator; not a name
+/// programs can write.
+const RANGE_ITERATOR: &str = "range*";
+
+/// Names of the variables holding the value received by a select, its
+/// ok flag and the index of the case chosen.
+const SELECT_RESULTS: [&str; 3] = ["select*value", "select*ok", "select*case"];
+
+/// A package compiled earlier, or one of the embedding program, which
+/// later packages import by path.
+struct LoadedPackage {
+    path: String,
+    name: String,
+    /// Variables and types declared at its top level.
+    members: HashMap<String, Symbol>,
+    /// Signatures of the functions of a package of the embedding
+    /// program, which calls to them are checked against.
+    signatures: HashMap<String, Signature>,
+}
+
+/// Translates an AST to bytecode for the `vm`. Compiling never stops at
+/// the first error; every problem found, up to `diagnostic::MAX_ERRORS`,
+/// is kept in `errors()`.
+pub struct Compiler {
+    constants: Vec<Value>,
+    pub symbol_table: SymbolTable,
+    scopes: Vec<CompilationScope>,
+    errors: Vec<Diagnostic>,
+    /// Name a `let` is binding the function literal being compiled to.
+    function_name: Option<String>,
+    /// File being compiled, for the line tables.
+    file: Rc<str>,
+    /// Line and column the next instructions come from.
+    position: (usize, usize),
+    /// Package the functions being compiled belong to, for their names in
+    /// stack traces.
+    package: String,
+    /// Constants of the package functions written in source compiled so
+    /// far, by package index and name.
+    sources: HashMap<(usize, &'static str), usize>,
+    /// Set while compiling a call whose several results are spread, so
+    /// that the call does not report being used as a single value.
+    unpack: bool,
+    /// Packages compiled before the current one.
+    packages: Vec<LoadedPackage>,
+    /// Path and name of the package `begin_package` started.
+    current: Option<(String, String)>,
+    /// Paths of the packages programs may not import.
+    denied: Vec<String>,
+    /// Names `declare` declared at the top level of the current package,
+    /// which their declarations then assign rather than define.
+    declared: HashMap<String, Symbol>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler::new_with_state(SymbolTable::new(), vec![])
+    }
+
+    /// A compiler that continues from the globals and constants of an
+    /// earlier compilation, as the REPL does between lines.
+    pub fn new_with_state(symbol_table: SymbolTable, constants: Vec<Value>) -> Compiler {
+        Compiler {
+            constants,
+            symbol_table,
+            scopes: vec![CompilationScope { name: TOP_LEVEL_NAME.to_string(), ..Default::default() }],
+            errors: vec![],
+            function_name: None,
+            file: Rc::from(""),
+            position: (0, 0),
+            package: "main".to_string(),
+            sources: HashMap::new(),
+            unpack: false,
+            packages: vec![],
+            current: None,
+            denied: vec![],
+            declared: HashMap::new(),
+        }
+    }
+
+    /// Starts the package `path` named `name`: the files compiled next
+    /// share its top-level scope, and may import the packages begun
+    /// before it. Packages must be compiled in dependency order.
+    pub fn begin_package(&mut self, path: &str, name: &str) {
+        let table = SymbolTable::after(&self.symbol_table);
+        let previous = std::mem::replace(&mut self.symbol_table, table);
+        if let Some((path, name)) = self.current.take() {
+            let members = previous.declarations();
+            self.packages.push(LoadedPackage { path, name, members, signatures: HashMap::new() });
+        }
+        self.package = if name == "main" { name.to_string() } else { path.to_string() };
+        self.current = Some((path.to_string(), name.to_string()));
+        self.declared.clear();
+    }
+
+    /// Declares the package-level variables and types of one of the
+    /// current package's files, so that the files may use each other's
+    /// names, and a file those it declares further down, as Go's may.
+    /// Every file is declared, then every file's types are resolved with
+    /// `resolve_types`, before any is compiled.
+    pub fn declare(&mut self, program: &Program) {
+        for stmt in &program.statements {
+            let any = stmt.as_any();
+            if let Some(stmt) = any.downcast_ref::<LetStatement>() {
+                if let Some(name) = &stmt.name {
+                    self.declare_name(name, |c| Some(c.symbol_table.define(&name.value)));
+                }
+            } else if let Some(stmt) = any.downcast_ref::<VarStatement>() {
+                for name in &stmt.names {
+                    self.declare_name(name, |c| Some(c.symbol_table.define(&name.value)));
+                }
+            } else if let Some(stmt) = any.downcast_ref::<TypeStatement>() {
+                // compiling the statement reports a type that cannot be declared
+                if let (Some(name), Some(ty)) = (&stmt.name, &stmt.ty) {
+                    self.declare_name(name, |c| {
+                        let constant = c.declare_type(&name.value, ty.as_ref())?;
+                        Some(c.symbol_table.define_type(&name.value, constant))
+                    });
+                }
+            }
+        }
+    }
+
+    fn declare_name(&mut self, name: &Identifier, define: impl FnOnce(&mut Compiler) -> Option<Symbol>) {
+        if name.value == "_" {
+            return;
+        }
+        if self.declared.contains_key(&name.value) {
+            self.error(name.token.as_ref(), format!("{} redeclared in this block", name.value));
+            return;
+        }
+        if let Some(symbol) = define(self) {
+            self.declared.insert(name.value.clone(), symbol);
+        }
+    }
+
+    /// Resolves the fields of the struct types `declare` declared for
+    /// `program`, which may refer to any type of the package.
+    pub fn resolve_types(&mut self, program: &Program) {
+        for stmt in &program.statements {
+            let stmt = match stmt.as_any().downcast_ref::<TypeStatement>() {
+                Some(stmt) => stmt,
+                None => continue,
+            };
+            let (name, decl) = match (&stmt.name, stmt.ty.as_ref().and_then(|ty| ty.as_any().downcast_ref::<StructType>())) {
+                (Some(name), Some(decl)) => (name, decl),
+                _ => continue,
+            };
+            let st = match self.declared.get(&name.value) {
+                Some(symbol) if symbol.scope == TYPE_SCOPE => match &self.constants[symbol.index] {
+                    Value::Type(Type::Struct(st)) => Rc::clone(st),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            self.resolve_fields(decl, &st);
+        }
+    }
+
+    /// The package-level symbol `declare` gave `name`, when a declaration
+    /// of it is being compiled at the top level of the package.
+    fn predeclared(&mut self, name: &str) -> Option<Symbol> {
+        let symbol = self.declared.get(name)?.clone();
+        if self.scopes.len() == 1 && self.symbol_table.declared_in_block(name) && self.symbol_table.resolve(name).as_ref() == Some(&symbol) {
+            Some(symbol)
+        } else {
+            None
+        }
+    }
+
+    /// Makes the package `path` of the embedding program importable. Its
+    /// members are globals and types defined before compiling, and calls
+    /// to its functions are checked against `signatures`.
+    pub fn add_host_package(
+        &mut self,
+        path: &str,
+        name: &str,
+        members: HashMap<String, Symbol>,
+        signatures: HashMap<String, Signature>,
+    ) {
+        self.packages.push(LoadedPackage { path: path.to_string(), name: name.to_string(), members, signatures });
+    }
+
+    /// Refuses imports of the packages `paths`, such as those of
+    /// `stdlib::SYSTEM_PACKAGES` for scripts that may not use the system.
+    pub fn deny_imports(&mut self, paths: &[&str]) {
+        self.denied.extend(paths.iter().map(|path| path.to_string()));
+    }
+
+    /// Names the file the programs compiled next come from.
+    pub fn set_file(&mut self, file: &str) {
+        self.file = Rc::from(file);
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<(), Vec<Diagnostic>> {
+        self.visit_program(program);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors.clone())
+        }
+    }
+
+    pub fn errors(&self) -> Vec<Diagnostic> {
+        self.errors.clone()
+    }
+
+    pub fn bytecode(&self) -> Bytecode {
+        Bytecode {
+            instructions: self.scopes[0].instructions.clone(),
+            constants: self.constants.clone(),
+            positions: self.scopes[0].positions.clone(),
+        }
+    }
+
+    /// Hands back the state `new_with_state` takes.
+    pub fn into_state(self) -> (SymbolTable, Vec<Value>) {
+        (self.symbol_table, self.constants)
+    }
+
+    fn error(&mut self, tok: Option<&Token>, msg: String) {
+        let diagnostic = match tok {
+            Some(tok) => Diagnostic::at(tok, msg),
+            None => Diagnostic::unpositioned(msg),
+        };
+        diagnostic::report(&mut self.errors, diagnostic);
+    }
+
+    fn add_constant(&mut self, obj: Value) -> usize {
+        self.constants.push(obj);
+        self.constants.len() - 1
+    }
+
+    fn scope(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().unwrap()
+    }
+
+    /// Attributes the instructions emitted next to `tok`.
+    fn mark(&mut self, tok: &Token) {
+        // hand-built nodes carry tokens without a position
+        if tok.line > 0 {
+            self.position = (tok.line, tok.column);
+        }
+    }
+
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> usize {
+        let ins = make(op, operands);
+        let (line, column) = self.position;
+        let file = Rc::clone(&self.file);
+        let scope = self.scope();
+        let position = scope.instructions.len();
+        let moved = scope.positions.last().is_none_or(|p| (p.line, p.column) != (line, column) || p.file != file);
+        if line > 0 && moved {
+            scope.positions.push(SourcePos { offset: position, file, line, column });
+        }
+        scope.instructions.extend_from_slice(&ins);
+        scope.previous_instruction = scope.last_instruction;
+        scope.last_instruction = Some(EmittedInstruction { opcode: op, position });
+        position
+    }
+
+    fn last_instruction_is(&self, op: Opcode) -> bool {
+        match self.scopes.last().unwrap().last_instruction {
+            Some(last) => last.opcode == op,
+            None => false,
+        }
+    }
+
+    fn remove_last_pop(&mut self) {
+        let scope = self.scope();
+        let last = scope.last_instruction.unwrap();
+        scope.instructions.truncate(last.position);
+        scope.positions.retain(|p| p.offset < last.position);
+        scope.last_instruction = scope.previous_instruction;
+    }
+
+    fn replace_last_pop_with_return(&mut self) {
+        let scope = self.scope();
+        let position = scope.last_instruction.unwrap().position;
+        scope.instructions[position] = OP_RETURN_VALUE;
+        scope.last_instruction = Some(EmittedInstruction { opcode: OP_RETURN_VALUE, position });
+    }
+
+    /// Rewrites the operand of the jump at `position`.
+    fn change_operand(&mut self, position: usize, operand: usize) {
+        let scope = self.scope();
+        let op = scope.instructions[position];
+        let ins = make(op, &[operand]);
+        scope.instructions[position..position + ins.len()].copy_from_slice(&ins);
+    }
+
+    fn enter_scope(&mut self, name: String) {
+        self.scopes.push(CompilationScope { name, ..Default::default() });
+        let outer = std::mem::take(&mut self.symbol_table);
+        self.symbol_table = SymbolTable::new_enclosed(outer);
+    }
+
+    fn leave_scope(&mut self) -> CompilationScope {
+        let scope = self.scopes.pop().unwrap();
+        let outer = self.symbol_table.outer.take().unwrap();
+        self.symbol_table = *outer;
+        scope
+    }
+
+    /// Stack trace name for a function literal: `main.f` when a top-level
+    /// `let` binds it, else numbered within the enclosing function.
+    fn function_trace_name(&mut self, name: Option<&str>) -> String {
+        match name {
+            Some(name) if self.scopes.len() == 1 => format!("{}.{}", self.package, name),
+            _ => {
+                let scope = self.scope();
+                scope.anonymous += 1;
+                format!("{}.func{}", scope.name, scope.anonymous)
+            }
+        }
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol) {
+        match symbol.scope {
+            GLOBAL_SCOPE => self.emit(OP_GET_GLOBAL, &[symbol.index]),
+            LOCAL_SCOPE => self.emit(OP_GET_LOCAL, &[symbol.index]),
+            FREE_SCOPE => self.emit(OP_GET_FREE, &[symbol.index]),
+            BUILTIN_SCOPE => self.emit(OP_GET_BUILTIN, &[symbol.index]),
+            _ => self.emit(OP_CURRENT_CLOSURE, &[]),
+        };
+    }
+
+    /// Pushes a pointer to the variable, as `&x` and closures capturing
+    /// it need.
+    fn load_address(&mut self, symbol: &Symbol) {
+        match symbol.scope {
+            GLOBAL_SCOPE => self.emit(OP_ADDR_GLOBAL, &[symbol.index]),
+            LOCAL_SCOPE => self.emit(OP_ADDR_LOCAL, &[symbol.index]),
+            FREE_SCOPE => self.emit(OP_ADDR_FREE, &[symbol.index]),
+            _ => {
+                // the current closure lives in no variable; give it one
+                self.emit(OP_CURRENT_CLOSURE, &[]);
+                self.emit(OP_NEW_POINTER, &[])
+            }
+        };
+    }
+
+    /// Stores the value on top of the stack into an existing variable.
+    fn store_symbol(&mut self, symbol: &Symbol, at: &dyn Expression) {
+        match symbol.scope {
+            GLOBAL_SCOPE => self.emit(OP_ASSIGN_GLOBAL, &[symbol.index]),
+            LOCAL_SCOPE => self.emit(OP_ASSIGN_LOCAL, &[symbol.index]),
+            FREE_SCOPE => self.emit(OP_ASSIGN_FREE, &[symbol.index]),
+            _ => {
+                self.error_at(at, format!("cannot assign to {}", symbol.name));
+                self.emit(OP_POP, &[])
+            }
+        };
+    }
+
+    /// Declares `name` in the current block and moves the value on top of
+    /// the stack into it. `_` discards the value.
+    fn define_variable(&mut self, name: &str) {
+        if name == "_" {
+            self.emit(OP_POP, &[]);
+            return;
+        }
+        let symbol = match self.predeclared(name) {
+            Some(symbol) => symbol,
+            None => self.symbol_table.define(name),
+        };
+        self.set_symbol(&symbol);
+    }
+
+    /// Moves the value on top of the stack into a fresh variable for
+    /// `symbol`, leaving any closure or pointer to the old one with it.
+    fn set_symbol(&mut self, symbol: &Symbol) {
+        if symbol.scope == GLOBAL_SCOPE {
+            self.emit(OP_SET_GLOBAL, &[symbol.index]);
+        } else {
+            self.emit(OP_SET_LOCAL, &[symbol.index]);
+        }
+    }
+
+    fn error_at(&mut self, at: &dyn Expression, msg: String) {
+        let diagnostic = match span_of_expression(at) {
+            Some(span) => Diagnostic::new(span.start.0, span.start.1, msg),
+            None => Diagnostic::unpositioned(msg),
+        };
+        diagnostic::report(&mut self.errors, diagnostic);
+    }
+
+    fn type_constant(&mut self, ty: Type) -> usize {
+        self.add_constant(Value::Type(ty))
+    }
+
+    fn name_constant(&mut self, name: &str) -> usize {
+        self.add_constant(Value::string(name))
+    }
+
+    /// The type an identifier names, if it names one.
+    fn named_type(&mut self, name: &str) -> Option<Type> {
+        match self.symbol_table.resolve(name) {
+            Some(symbol) if symbol.scope == TYPE_SCOPE => match &self.constants[symbol.index] {
+                Value::Type(ty) => Some(ty.clone()),
+                _ => None,
+            },
+            Some(_) => None,
+            None => universe_type(name),
+        }
+    }
+
+    /// Reports whether `expr` denotes a type rather than a value.
+    fn is_type(&mut self, expr: &dyn Expression) -> bool {
+        if self.std_type(expr).is_some() {
+            return true;
+        }
+        if let Some(member) = self.loaded_member(expr) {
+            return member.is_ok_and(|symbol| symbol.scope == TYPE_SCOPE);
+        }
+        if let Some(ident) = expr.as_any().downcast_ref::<Identifier>() {
+            return self.named_type(&ident.value).is_some();
+        }
+        if let Some(prefix) = expr.as_any().downcast_ref::<PrefixExpression>() {
+            return prefix.operator == "*" && prefix.right.as_ref().is_some_and(|r| self.is_type(r.as_ref()));
+        }
+        let any = expr.as_any();
+        any.is::<SliceType>() || any.is::<MapType>() || any.is::<ChanType>() || any.is::<StructType>() || any.is::<InterfaceType>()
+    }
+
+    /// The type `expr` denotes, reporting an error when it is not one.
+    fn resolve_type(&mut self, expr: &dyn Expression) -> Option<Type> {
+        let any = expr.as_any();
+        if let Some(ident) = any.downcast_ref::<Identifier>() {
+            let ty = self.named_type(&ident.value);
+            if ty.is_none() {
+                if self.symbol_table.resolve(&ident.value).is_some() {
+                    self.error_at(expr, format!("{} is not a type", ident.value));
+                } else {
+                    self.error_at(expr, format!("undefined: {}", ident.value));
+                }
+            }
+            return ty;
+        }
+        if let Some(ty) = self.std_type(expr) {
+            return Some(ty);
+        }
+        if let Some(member) = self.loaded_member(expr) {
+            match member {
+                Ok(symbol) if symbol.scope == TYPE_SCOPE => match &self.constants[symbol.index] {
+                    Value::Type(ty) => return Some(ty.clone()),
+                    _ => return None,
+                },
+                Ok(_) => self.error_at(expr, format!("{} is not a type", format_expression(expr))),
+                Err(msg) => self.error_at(expr, msg),
+            }
+            return None;
+        }
+        if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+            if prefix.operator == "*" {
+                let elem = self.resolve_type(prefix.right.as_deref()?)?;
+                return Some(Type::Pointer(Rc::new(elem)));
+            }
+        }
+        if let Some(ty) = any.downcast_ref::<SliceType>() {
+            let elem = self.resolve_type(ty.elem.as_deref()?)?;
+            return Some(Type::Slice(Rc::new(elem)));
+        }
+        if let Some(ty) = any.downcast_ref::<MapType>() {
+            let key = self.resolve_type(ty.key.as_deref()?)?;
+            let value = self.resolve_type(ty.value.as_deref()?)?;
+            if !key.comparable() {
+                self.error_at(expr, format!("invalid map key type {}", key));
+                return None;
+            }
+            return Some(Type::Map(Rc::new(key), Rc::new(value)));
+        }
+        if let Some(ty) = any.downcast_ref::<ChanType>() {
+            let elem = self.resolve_type(ty.elem.as_deref()?)?;
+            return Some(Type::Chan(Rc::new(elem)));
+        }
+        if let Some(ty) = any.downcast_ref::<StructType>() {
+            let st = Rc::new(types::StructType { name: None, fields: RefCell::new(vec![]) });
+            self.resolve_fields(ty, &st);
+            return Some(Type::Struct(st));
+        }
+        if any.is::<InterfaceType>() {
+            return Some(Type::any());
+        }
+        self.error_at(expr, format!("{} is not a type", format_expression(expr)));
+        None
+    }
+
+    /// The constant of a new struct type without fields yet, or interface
+    /// type, named `name`; None when `ty` is neither.
+    fn declare_type(&mut self, name: &str, ty: &dyn Expression) -> Option<usize> {
+        // types of imported packages print qualified, as in util.Point
+        let qualified = match &self.current {
+            Some((_, package)) if package != "main" => format!("{}.{}", package, name),
+            _ => name.to_string(),
+        };
+        let ty = if ty.as_any().is::<StructType>() {
+            Type::Struct(Rc::new(types::StructType { name: Some(qualified), fields: RefCell::new(vec![]) }))
+        } else if ty.as_any().is::<InterfaceType>() {
+            Type::Interface(Rc::new(types::InterfaceType { name: Some(qualified), methods: vec![] }))
+        } else {
+            return None;
+        };
+        Some(self.type_constant(ty))
+    }
+
+    fn resolve_fields(&mut self, decl: &StructType, st: &types::StructType) {
+        let mut fields: Vec<Field> = vec![];
+        for field in &decl.fields {
+            let ty = match field.ty.as_deref().and_then(|ty| self.resolve_type(ty)) {
+                Some(ty) => ty,
+                None => continue,
+            };
+            for name in &field.names {
+                if fields.iter().any(|f| f.name == name.value) {
+                    self.error(name.token.as_ref(), format!("{} redeclared", name.value));
+                }
+                fields.push(Field { name: name.value.clone(), ty: ty.clone() });
+            }
+        }
+        *st.fields.borrow_mut() = fields;
+    }
+
+    /// Compiles the right-hand side of an assignment to `n` variables,
+    /// leaving `n` values on the stack. One map index, type assertion or
+    /// receive gives two values, the second reporting success.
+    fn compile_values(&mut self, values: &[Box<dyn Expression>], n: usize, tok: &Token) -> bool {
+        if let ([value], Some((name, results))) = (values, values.first().and_then(|v| self.multiple_results(v.as_ref()))) {
+            if results.len() != n {
+                let variables = if n == 1 { "1 variable".to_string() } else { format!("{} variables", n) };
+                self.error(Some(tok), format!("assignment mismatch: {} but {} returns {} values", variables, name, results.len()));
+                return false;
+            }
+            self.unpack = true;
+            self.visit_expression(value.as_ref());
+            self.emit(OP_UNPACK, &[n]);
+            return true;
+        }
+        if n == 2 && values.len() == 1 {
+            let value = values[0].as_any();
+            if let Some(index) = value.downcast_ref::<IndexExpression>() {
+                visit::walk_index_expression(self, index);
+                self.emit(OP_INDEX_OK, &[]);
+                return true;
+            }
+            if let Some(assert) = value.downcast_ref::<TypeAssertExpression>() {
+                if let Some(ty) = self.assert_operands(assert) {
+                    let constant = self.type_constant(ty);
+                    self.emit(OP_ASSERT_OK, &[constant]);
+                }
+                return true;
+            }
+            if let Some(channel) = receive_operand(values[0].as_ref()) {
+                self.visit_expression(channel);
+                self.emit(OP_RECV_OK, &[]);
+                return true;
+            }
+        }
+        if values.len() != n {
+            let plural = |n: usize, word: &str| if n == 1 { format!("1 {}", word) } else { format!("{} {}s", n, word) };
+            self.error(Some(tok), format!("assignment mismatch: {} but {}", plural(n, "variable"), plural(values.len(), "value")));
+            return false;
+        }
+        for value in values {
+            self.visit_expression(value.as_ref());
+        }
+        true
+    }
+
+    /// Stores the value on top of the stack into `target`, which must be
+    /// a variable, `_`, an index expression, a field or a dereference.
+    fn store(&mut self, target: &dyn Expression) {
+        let any = target.as_any();
+        if let Some(ident) = any.downcast_ref::<Identifier>() {
+            if ident.value == "_" {
+                self.emit(OP_POP, &[]);
+                return;
+            }
+            match self.symbol_table.resolve(&ident.value) {
+                Some(symbol) => self.store_symbol(&symbol, target),
+                None => {
+                    self.error_at(target, format!("undefined: {}", ident.value));
+                    self.emit(OP_POP, &[]);
+                }
+            }
+            return;
+        }
+        if let Some(index) = any.downcast_ref::<IndexExpression>() {
+            visit::walk_index_expression(self, index);
+            self.emit(OP_SET_INDEX, &[]);
+            return;
+        }
+        if let Some(member) = self.loaded_member(target) {
+            match member {
+                Ok(symbol) => self.store_symbol(&symbol, target),
+                Err(msg) => {
+                    self.error_at(target, msg);
+                    self.emit(OP_POP, &[]);
+                }
+            }
+            return;
+        }
+        if is_addressable(target) {
+            self.compile_address(target);
+            self.emit(OP_STORE, &[]);
+            return;
+        }
+        self.error_at(target, format!("cannot assign to {}", format_expression(target)));
+        self.emit(OP_POP, &[]);
+    }
+
+    /// Pushes a pointer to what `expr` denotes.
+    fn compile_address(&mut self, expr: &dyn Expression) {
+        let any = expr.as_any();
+        if let Some(ident) = any.downcast_ref::<Identifier>() {
+            match self.symbol_table.resolve(&ident.value) {
+                Some(symbol) if [GLOBAL_SCOPE, LOCAL_SCOPE, FREE_SCOPE, FUNCTION_SCOPE].contains(&symbol.scope) => {
+                    self.load_address(&symbol)
+                }
+                Some(_) => {
+                    self.error_at(expr, format!("invalid operation: cannot take address of {}", ident.value));
+                    self.emit(OP_NULL, &[]);
+                }
+                None => {
+                    self.error_at(expr, format!("undefined: {}", ident.value));
+                    self.emit(OP_NULL, &[]);
+                }
+            }
+        } else if let Some(member) = self.loaded_member(expr) {
+            match member {
+                Ok(symbol) if symbol.scope == GLOBAL_SCOPE => self.load_address(&symbol),
+                Ok(_) => {
+                    self.error_at(expr, format!("invalid operation: cannot take address of {}", format_expression(expr)));
+                    self.emit(OP_NULL, &[]);
+                }
+                Err(msg) => {
+                    self.error_at(expr, msg);
+                    self.emit(OP_NULL, &[]);
+                }
+            }
+        } else if let Some(selector) = any.downcast_ref::<SelectorExpression>() {
+            if let Some(left) = &selector.left {
+                if is_addressable(left.as_ref()) {
+                    self.compile_address(left.as_ref());
+                } else {
+                    self.visit_expression(left.as_ref());
+                }
+            }
+            let name = selector.selector.as_ref().map(|s| s.value.clone()).unwrap_or_default();
+            let constant = self.name_constant(&name);
+            self.emit(OP_FIELD_ADDR, &[constant]);
+        } else if let Some(index) = any.downcast_ref::<IndexExpression>() {
+            visit::walk_index_expression(self, index);
+            self.emit(OP_INDEX_ADDR, &[]);
+        } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>().filter(|p| p.operator == "*") {
+            if let Some(right) = &prefix.right {
+                self.visit_expression(right.as_ref());
+            }
+        } else if let Some(lit) = any.downcast_ref::<CompositeLiteral>() {
+            self.visit_composite_literal(lit);
+            self.emit(OP_NEW_POINTER, &[]);
+        } else {
+            self.error_at(expr, format!("invalid operation: cannot take address of {}", format_expression(expr)));
+            self.emit(OP_NULL, &[]);
+        }
+    }
+
+    /// `x op= value`, and `x++`/`x--` when `value` is None.
+    fn compile_op_assign(&mut self, target: &dyn Expression, op: &str, value: Option<&dyn Expression>, tok: &Token) {
+        self.visit_expression(target);
+        match value {
+            Some(value) => self.visit_expression(value),
+            None => {
+                let one = self.add_constant(Value::Integer(1));
+                self.emit(OP_CONSTANT, &[one]);
+            }
+        }
+        self.emit_operator(op, tok);
+        self.store(target);
+    }
+
+    fn emit_operator(&mut self, op: &str, tok: &Token) {
+        self.mark(tok);
+        match op {
+            "+" => self.emit(OP_ADD, &[]),
+            "-" => self.emit(OP_SUB, &[]),
+            "*" => self.emit(OP_MUL, &[]),
+            "/" => self.emit(OP_DIV, &[]),
+            "%" => self.emit(OP_MOD, &[]),
+            _ => {
+                self.error(Some(tok), format!("unknown operator {}", op));
+                0
+            }
+        };
+    }
+
+    /// Compiles the operand of a type assertion and resolves its type.
+    fn assert_operands(&mut self, assert: &TypeAssertExpression) -> Option<Type> {
+        if let Some(left) = &assert.left {
+            self.visit_expression(left.as_ref());
+        }
+        self.resolve_type(assert.ty.as_deref()?)
+    }
+
+    /// Compiles a composite literal whose type is `ty` when it does not
+    /// spell one out itself.
+    fn compile_composite(&mut self, lit: &CompositeLiteral, ty: Option<Type>) {
+        let ty = match &lit.ty {
+            Some(t) => self.resolve_type(t.as_ref()),
+            None => {
+                if ty.is_none() {
+                    self.error(Some(&lit.token), "invalid composite literal type: missing type".to_string());
+                }
+                ty
+            }
+        };
+        let ty = match ty {
+            Some(ty) => ty,
+            None => {
+                self.emit(OP_NULL, &[]);
+                return;
+            }
+        };
+
+        let mut pairs = 0;
+        match &ty {
+            Type::Struct(st) => {
+                let fields = st.fields.borrow().clone();
+                let keyed = lit.elements.first().is_some_and(|e| e.as_any().is::<KeyValueExpression>());
+                for (i, element) in lit.elements.iter().enumerate() {
+                    let kv = element.as_any().downcast_ref::<KeyValueExpression>();
+                    let (index, value) = match (keyed, kv) {
+                        (true, Some(kv)) => {
+                            let key = kv.key.as_deref().and_then(|k| k.as_any().downcast_ref::<Identifier>());
+                            let index = match key {
+                                Some(key) => st.field_index(&key.value),
+                                None => None,
+                            };
+                            match (index, key) {
+                                (Some(index), _) => (index, kv.value.as_deref()),
+                                (None, Some(key)) => {
+                                    self.error(key.token.as_ref(), format!("unknown field {} in struct literal of type {}", key.value, ty));
+                                    continue;
+                                }
+                                (None, None) => {
+                                    self.error(Some(&kv.token), "invalid field name in struct literal".to_string());
+                                    continue;
+                                }
+                            }
+                        }
+                        (false, None) if i < fields.len() => (i, Some(element.as_ref())),
+                        (false, None) => {
+                            self.error_at(element.as_ref(), format!("too many values in struct literal of type {}", ty));
+                            break;
+                        }
+                        _ => {
+                            self.error_at(element.as_ref(), "mixture of field:value and value elements in struct literal".to_string());
+                            continue;
+                        }
+                    };
+                    let constant = self.add_constant(Value::Integer(index as i64));
+                    self.emit(OP_CONSTANT, &[constant]);
+                    self.compile_element(value, &fields[index].ty);
+                    pairs += 1;
+                }
+                if !keyed && !lit.elements.is_empty() && lit.elements.len() < fields.len() {
+                    let at = lit.rbrace.as_ref().unwrap_or(&lit.token).clone();
+                    self.error(Some(&at), format!("too few values in struct literal of type {}", ty));
+                }
+            }
+            Type::Slice(elem) => {
+                let mut next = 0;
+                for element in &lit.elements {
+                    let (index, value) = match element.as_any().downcast_ref::<KeyValueExpression>() {
+                        Some(kv) => {
+                            let key = kv.key.as_deref().and_then(|k| k.as_any().downcast_ref::<IntegerLiteral>());
+                            match key.and_then(|k| k.value) {
+                                Some(index) if index >= 0 => (index, kv.value.as_deref()),
+                                _ => {
+                                    self.error(Some(&kv.token), "index must be non-negative integer constant".to_string());
+                                    continue;
+                                }
+                            }
+                        }
+                        None => (next, Some(element.as_ref())),
+                    };
+                    next = index + 1;
+                    let constant = self.add_constant(Value::Integer(index));
+                    self.emit(OP_CONSTANT, &[constant]);
+                    self.compile_element(value, elem);
+                    pairs += 1;
+                }
+            }
+            Type::Map(key, elem) => {
+                for element in &lit.elements {
+                    match element.as_any().downcast_ref::<KeyValueExpression>() {
+                        Some(kv) => {
+                            self.compile_element(kv.key.as_deref(), key);
+                            self.compile_element(kv.value.as_deref(), elem);
+                            pairs += 1;
+                        }
+                        None => self.error_at(element.as_ref(), "missing key in map literal".to_string()),
+                    }
+                }
+            }
+            _ => {
+                self.error(Some(&lit.token), format!("invalid composite literal type {}", ty));
+                self.emit(OP_NULL, &[]);
+                return;
+            }
+        }
+        let constant = self.type_constant(ty);
+        self.emit(OP_COMPOSITE, &[constant, pairs]);
+    }
+
+    /// An element of a composite literal of element type `ty`, whose own
+    /// composite literals may leave the type out.
+    fn compile_element(&mut self, element: Option<&dyn Expression>, ty: &Type) {
+        let element = match element {
+            Some(element) => element,
+            None => {
+                self.emit(OP_NULL, &[]);
+                return;
+            }
+        };
+        match element.as_any().downcast_ref::<CompositeLiteral>() {
+            Some(lit) if lit.ty.is_none() => match ty {
+                // {...} for a *T element means &T{...}
+                Type::Pointer(elem) => {
+                    self.compile_composite(lit, Some(elem.as_ref().clone()));
+                    self.emit(OP_NEW_POINTER, &[]);
+                }
+                _ => self.compile_composite(lit, Some(ty.clone())),
+            },
+            _ => self.visit_expression(element),
+        }
+    }
+
+    /// Pushes the (is send, channel, value) operands of a select case,
+    /// returning the variables a receive assigns to and its operator.
+    fn compile_comm<'a>(&mut self, comm: &'a dyn Statement, tok: &Token) -> Option<(Vec<&'a dyn Expression>, &'a str)> {
+        let any = comm.as_any();
+        if let Some(send) = any.downcast_ref::<SendStatement>() {
+            self.emit(OP_TRUE, &[]);
+            for operand in [&send.channel, &send.value] {
+                match operand {
+                    Some(operand) => self.visit_expression(operand.as_ref()),
+                    None => {
+                        self.emit(OP_NULL, &[]);
+                    }
+                }
+            }
+            return Some((vec![], ""));
+        }
+        let (left, operator, right): (Vec<&dyn Expression>, &str, _) =
+            if let Some(stmt) = any.downcast_ref::<ExpressionStatement>() {
+                (vec![], "", stmt.expression.as_deref())
+            } else if let Some(assign) = any.downcast_ref::<AssignStatement>() {
+                let right = match assign.right.as_slice() {
+                    [right] if (1..=2).contains(&assign.left.len()) && ["=", ":="].contains(&assign.operator.as_str()) => {
+                        Some(right.as_ref())
+                    }
+                    _ => None,
+                };
+                (assign.left.iter().map(|l| l.as_ref()).collect(), assign.operator.as_str(), right)
+            } else {
+                (vec![], "", None)
+            };
+        match right.and_then(receive_operand) {
+            Some(channel) => {
+                self.emit(OP_FALSE, &[]);
+                self.visit_expression(channel);
+                self.emit(OP_NULL, &[]);
+                Some((left, operator))
+            }
+            None => {
+                self.error(Some(tok), "select case must be receive, send or assign recv".to_string());
+                None
+            }
+        }
+    }
+
+    /// Declares the function's named result, set to its zero value, and
+    /// returns its local index. `(T)` alone is an unnamed result type.
+    fn compile_named_results(&mut self, func: &FunctionLiteral) -> Option<usize> {
+        let results: Vec<(&Identifier, Option<&dyn Expression>)> =
+            func.results.iter().flat_map(|f| f.names.iter().map(move |name| (name, f.ty.as_deref()))).collect();
+        match results.as_slice() {
+            [] => return None,
+            [(name, None)] if self.named_type(&name.value).is_some() => return None,
+            [_] => {}
+            _ => self.error(Some(&func.token), "multiple results are not supported; return a single value".to_string()),
+        }
+        let mut index = None;
+        for (name, ty) in results {
+            match ty.and_then(|ty| self.resolve_type(ty)) {
+                Some(ty) => {
+                    let zero = self.add_constant(ty.zero());
+                    self.emit(OP_CONSTANT, &[zero]);
+                }
+                None => {
+                    self.emit(OP_NULL, &[]);
+                }
+            }
+            let symbol = self.symbol_table.define(&name.value);
+            self.set_symbol(&symbol);
+            index = Some(symbol.index);
+        }
+        index
+    }
+
+    fn visit_loop_body(&mut self, body: Option<&BlockStatement>) {
+        self.scope().loops.push(Loop::default());
+        if let Some(body) = body {
+            self.visit_block_statement(body);
+        }
+    }
+
+    /// Points the loop's `break`s at `exit` and `continue`s at `next`.
+    fn finish_loop(&mut self, next: usize, exit: usize) {
+        let l = self.scope().loops.pop().unwrap();
+        for position in l.breaks {
+            self.change_operand(position, exit);
+        }
+        for position in l.continues {
+            self.change_operand(position, next);
+        }
+    }
+
+    /// Compiles a block used as a value, leaving exactly one value on the
+    /// stack: its last expression, or nil.
+    fn compile_value_block(&mut self, block: Option<&BlockStatement>) {
+        if let Some(block) = block {
+            self.visit_block_statement(block);
+        }
+        if self.last_instruction_is(OP_POP) {
+            self.remove_last_pop();
+        } else if !self.last_instruction_is(OP_RETURN_VALUE) {
+            self.emit(OP_NULL, &[]);
+        }
+    }
+
+    /// The package and member name of a selector `pkg.Name` on an
+    /// imported package.
+    fn package_member(&mut self, expr: &dyn Expression) -> Option<(usize, String)> {
+        let selector = expr.as_any().downcast_ref::<SelectorExpression>()?;
+        let left = selector.left.as_deref()?.as_any().downcast_ref::<Identifier>()?;
+        let symbol = self.symbol_table.resolve(&left.value).filter(|symbol| symbol.scope == PACKAGE_SCOPE)?;
+        let name = selector.selector.as_ref().map(|s| s.value.clone()).unwrap_or_default();
+        Some((symbol.index, name))
+    }
+
+    /// The name and result types of the function `call` calls, if it is
+    /// a package function with more than one result.
+    fn multiple_results(&mut self, call: &dyn Expression) -> Option<(String, Vec<String>)> {
+        let function = call.as_any().downcast_ref::<CallExpression>()?.function.as_deref()?;
+        let results: Vec<String> = match self.package_member(function)? {
+            (package, name) if package < stdlib::PACKAGES.len() => match stdlib::PACKAGES[package].member(&name)? {
+                Member::Func(_, results) => results.iter().map(|r| r.to_string()).collect(),
+                _ => return None,
+            },
+            _ => self.host_signature(function)?.results.iter().map(|t| t.to_string()).collect(),
+        };
+        if results.len() > 1 {
+            Some((format_expression(function), results))
+        } else {
+            None
+        }
+    }
+
+    /// The signature of `pkg.Name` when it is a function of a package of
+    /// the embedding program.
+    fn host_signature(&mut self, function: &dyn Expression) -> Option<Signature> {
+        let (index, name) = self.package_member(function)?;
+        let pkg = self.packages.get(index.checked_sub(stdlib::PACKAGES.len())?)?;
+        pkg.signatures.get(&name).cloned()
+    }
+
+    /// Reports a call passing `args` arguments to a function of the
+    /// embedding program that takes another number; the slice of a call
+    /// `f(a, s...)` counts as one. Returns whether the number is right.
+    fn check_arity(&mut self, call: &CallExpression, signature: &Signature, args: usize) -> bool {
+        let params = signature.params.len();
+        let (min, max) = match (signature.variadic, call.ellipsis.is_some()) {
+            (true, false) => (params - 1, usize::MAX),
+            _ => (params, params),
+        };
+        let msg = if args < min {
+            "not enough arguments"
+        } else if args > max {
+            "too many arguments"
+        } else {
+            return true;
+        };
+        let function = call.function.as_deref().map(format_expression).unwrap_or_default();
+        self.error(Some(&call.token), format!("{} in call to {} (want {})", msg, function, signature));
+        false
+    }
+
+    /// The type `pkg.Name` names when it is a type of the standard library.
+    fn std_type(&mut self, expr: &dyn Expression) -> Option<Type> {
+        let (index, name) = self.package_member(expr)?;
+        match stdlib::PACKAGES.get(index)?.member(&name)? {
+            Member::Type(ty) => Some(ty()),
+            _ => None,
+        }
+    }
+
+    /// The symbol of `pkg.Name` when `pkg` is a package compiled earlier,
+    /// or the error naming it.
+    fn loaded_member(&mut self, expr: &dyn Expression) -> Option<Result<Symbol, String>> {
+        let (index, name) = self.package_member(expr)?;
+        let pkg = &self.packages[index.checked_sub(stdlib::PACKAGES.len())?];
+        if !name.starts_with(|c: char| c.is_uppercase()) {
+            return Some(Err(format!("name {} not exported by package {}", name, pkg.name)));
+        }
+        Some(pkg.members.get(&name).cloned().ok_or_else(|| format!("undefined: {}.{}", pkg.name, name)))
+    }
+
+    /// Pushes the value of the package member `name`.
+    fn compile_package_member(&mut self, package: usize, name: &str, expr: &SelectorExpression) {
+        let pkg = stdlib::PACKAGES[package];
+        let member = match pkg.member(name) {
+            Some(member) => member,
+            None => {
+                self.error(expr.selector.as_ref().and_then(|s| s.token.as_ref()), format!("undefined: {}.{}", pkg.name(), name));
+                self.emit(OP_NULL, &[]);
+                return;
+            }
+        };
+        match member {
+            Member::Func(builtin, _) => {
+                let constant = self.add_constant(Value::Builtin(*builtin));
+                self.emit(OP_CONSTANT, &[constant]);
+            }
+            Member::Value(value) => {
+                let constant = self.add_constant(value());
+                self.emit(OP_CONSTANT, &[constant]);
+            }
+            Member::Var(builtin) => {
+                let constant = self.add_constant(Value::Builtin(*builtin));
+                self.emit(OP_CONSTANT, &[constant]);
+                self.mark(&expr.token);
+                self.emit(OP_CALL, &[0]);
+            }
+            Member::Source(source) => {
+                let key = (package, pkg.members.iter().find(|(n, _)| *n == name).map_or("", |(n, _)| *n));
+                let constant = match self.sources.get(&key) {
+                    Some(&constant) => constant,
+                    None => {
+                        let constant = self.compile_source(pkg, key.1, source);
+                        self.sources.insert(key, constant);
+                        constant
+                    }
+                };
+                self.emit(OP_CLOSURE, &[constant, 0]);
+            }
+            Member::Type(_) => {
+                self.error_at(expr, format!("{} (type) is not an expression", format_expression(expr)));
+                self.emit(OP_NULL, &[]);
+            }
+        }
+    }
+
+    /// Compiles the function literal `source` of a package apart from the
+    /// program, as if declared at the top level of the package, and
+    /// returns its constant.
+    fn compile_source(&mut self, pkg: &stdlib::Package, name: &str, source: &str) -> usize {
+        let mut parser = Parser::new(Lexer::new(source.to_string()));
+        let program = parser.parse_program();
+        let func = program
+            .statements
+            .first()
+            .and_then(|stmt| stmt.as_any().downcast_ref::<ExpressionStatement>())
+            .and_then(|stmt| stmt.expression.as_deref())
+            .and_then(|expr| expr.as_any().downcast_ref::<FunctionLiteral>());
+        let func = match func {
+            Some(func) if parser.errors().is_empty() => func,
+            _ => {
+                self.error(None, format!("{}.{}: bad package source: {:?}", pkg.path, name, parser.errors()));
+                return self.add_constant(Value::Null);
+            }
+        };
+        let symbol_table = std::mem::take(&mut self.symbol_table);
+        let scopes = std::mem::replace(&mut self.scopes, vec![CompilationScope { name: pkg.name().to_string(), ..Default::default() }]);
+        let package = std::mem::replace(&mut self.package, pkg.name().to_string());
+        let file = std::mem::replace(&mut self.file, Rc::from(format!("{}/{}.go", pkg.path, pkg.name())));
+        let position = self.position;
+        self.function_name = Some(name.to_string());
+        self.visit_function_literal(func);
+        self.symbol_table = symbol_table;
+        self.scopes = scopes;
+        self.package = package;
+        self.file = file;
+        self.position = position;
+        self.constants.len() - 1
+    }
+}
+
+/// Types declared in the universe scope.
+pub(crate) fn universe_type(name: &str) -> Option<Type> {
+    match name {
+        "int" | "rune" => Some(Type::Int),
+        "byte" => Some(Type::Byte),
+        "bool" => Some(Type::Bool),
+        "string" => Some(Type::String),
+        "float64" => Some(Type::Float64),
+        "any" => Some(Type::any()),
+        "error" => Some(Type::error()),
+        _ => None,
+    }
+}
+
+/// The channel of a receive expression `<-ch`.
+pub(crate) fn receive_operand(expr: &dyn Expression) -> Option<&dyn Expression> {
+    match expr.as_any().downcast_ref::<PrefixExpression>() {
+        Some(prefix) if prefix.operator == "<-" => prefix.right.as_deref(),
+        _ => None,
+    }
+}
+
+/// Reports whether `expr` denotes a place a pointer can refer to.
+pub(crate) fn is_addressable(expr: &dyn Expression) -> bool {
+    let any = expr.as_any();
+    any.is::<Identifier>()
+        || any.is::<SelectorExpression>()
+        || any.is::<IndexExpression>()
+        || any.downcast_ref::<PrefixExpression>().is_some_and(|p| p.operator == "*")
+}
+
+impl Visitor for Compiler {
+    fn visit_statement(&mut self, stmt: &dyn Statement) {
+        if let Some(span) = span_of_statement(stmt) {
+            self.position = span.start;
+        }
+        visit::walk_statement(self, stmt);
+    }
+
+    fn visit_let_statement(&mut self, stmt: &LetStatement) {
+        let name = match &stmt.name {
+            Some(name) => name,
+            None => return,
+        };
+        if let Some(value) = &stmt.value {
+            if value.as_any().is::<FunctionLiteral>() {
+                self.function_name = Some(name.value.clone());
+            }
+            self.visit_expression(value.as_ref());
+        } else {
+            self.emit(OP_NULL, &[]);
+        }
+        // defined after the value so `let x = x + 1` reads the outer x
+        self.define_variable(&name.value);
+    }
+
+    fn visit_return_statement(&mut self, stmt: &ReturnStatement) {
+        if self.scopes.len() == 1 {
+            self.error(Some(&stmt.token), "return statement outside function".to_string());
+            return;
+        }
+        match &stmt.return_value {
+            Some(value) => {
+                self.visit_expression(value.as_ref());
+                self.emit(OP_RETURN_VALUE, &[]);
+            }
+            None => {
+                self.emit(OP_RETURN, &[]);
+            }
+        }
+    }
+
+    fn visit_expression_statement(&mut self, stmt: &ExpressionStatement) {
+        if let Some(expression) = &stmt.expression {
+            // the results of a call are discarded, however many
+            self.unpack = expression.as_any().is::<CallExpression>();
+            self.visit_expression(expression.as_ref());
+            self.emit(OP_POP, &[]);
+        }
+    }
+
+    fn visit_block_statement(&mut self, block: &BlockStatement) {
+        self.symbol_table.push_block();
+        visit::walk_block_statement(self, block);
+        self.symbol_table.pop_block();
+    }
+
+    fn visit_var_statement(&mut self, stmt: &VarStatement) {
+        let ty = match &stmt.ty {
+            Some(ty) => match self.resolve_type(ty.as_ref()) {
+                Some(ty) => Some(ty),
+                None => return,
+            },
+            None => None,
+        };
+        if stmt.values.is_empty() {
+            let ty = match ty {
+                Some(ty) => ty,
+                None => return,
+            };
+            for name in &stmt.names {
+                let zero = self.add_constant(ty.zero());
+                self.emit(OP_CONSTANT, &[zero]);
+                self.define_variable(&name.value);
+            }
+            return;
+        }
+        if !self.compile_values(&stmt.values, stmt.names.len(), &stmt.token) {
+            return;
+        }
+        for name in stmt.names.iter().rev() {
+            if let Some(ty) = &ty {
+                let constant = self.type_constant(ty.clone());
+                self.emit(OP_CONVERT, &[constant, 0]);
+            }
+            self.define_variable(&name.value);
+        }
+    }
+
+    fn visit_type_statement(&mut self, stmt: &TypeStatement) {
+        let (name, ty) = match (&stmt.name, &stmt.ty) {
+            (Some(name), Some(ty)) => (name, ty),
+            _ => return,
+        };
+        if self.predeclared(&name.value).is_some() {
+            return;
+        }
+        match self.declare_type(&name.value, ty.as_ref()) {
+            Some(constant) => {
+                // declared before its fields resolve, which may refer to it
+                self.symbol_table.define_type(&name.value, constant);
+                if let Some(decl) = ty.as_any().downcast_ref::<StructType>() {
+                    if let Value::Type(Type::Struct(st)) = &self.constants[constant] {
+                        let st = Rc::clone(st);
+                        self.resolve_fields(decl, &st);
+                    }
+                }
+            }
+            None => self.error(Some(&stmt.token), "only struct and interface types can be declared".to_string()),
+        }
+    }
+
+    fn visit_assign_statement(&mut self, stmt: &AssignStatement) {
+        match stmt.operator.as_str() {
+            ":=" => {
+                let mut names = vec![];
+                for left in &stmt.left {
+                    match left.as_any().downcast_ref::<Identifier>() {
+                        Some(ident) => names.push(ident.value.clone()),
+                        None => {
+                            self.error_at(left.as_ref(), format!("non-name {} on left side of :=", format_expression(left.as_ref())));
+                            return;
+                        }
+                    }
+                }
+                if names.iter().all(|n| n == "_" || self.symbol_table.declared_in_block(n)) {
+                    self.error(Some(&stmt.token), "no new variables on left side of :=".to_string());
+                    return;
+                }
+                if !self.compile_values(&stmt.right, names.len(), &stmt.token) {
+                    return;
+                }
+                for (name, left) in names.iter().zip(stmt.left.iter()).rev() {
+                    if self.symbol_table.declared_in_block(name) {
+                        self.store(left.as_ref());
+                    } else {
+                        self.define_variable(name);
+                    }
+                }
+            }
+            "=" => {
+                if !self.compile_values(&stmt.right, stmt.left.len(), &stmt.token) {
+                    return;
+                }
+                for left in stmt.left.iter().rev() {
+                    self.store(left.as_ref());
+                }
+            }
+            op => {
+                let target = match stmt.left.first() {
+                    Some(target) => target,
+                    None => return,
+                };
+                let value = stmt.right.first().map(|v| v.as_ref());
+                let operator = match op {
+                    "++" => "+",
+                    "--" => "-",
+                    _ => op.trim_end_matches('='),
+                };
+                self.compile_op_assign(target.as_ref(), operator, value, &stmt.token);
+            }
+        }
+    }
+
+    fn visit_for_statement(&mut self, stmt: &ForStatement) {
+        self.symbol_table.push_block();
+        if let Some(init) = &stmt.init {
+            self.visit_statement(init.as_ref());
+        }
+        // variables declared by the init statement, copied into fresh
+        // variables for every iteration
+        let mut per_iteration = vec![];
+        if let Some(assign) = stmt.init.as_ref().and_then(|i| i.as_any().downcast_ref::<AssignStatement>()) {
+            if assign.operator == ":=" {
+                for left in &assign.left {
+                    if let Some(symbol) = left
+                        .as_any()
+                        .downcast_ref::<Identifier>()
+                        .and_then(|ident| self.symbol_table.resolve(&ident.value))
+                    {
+                        per_iteration.push(symbol);
+                    }
+                }
+            }
+        }
+
+        let start = self.scope().instructions.len();
+        let exit_jump = stmt.condition.as_ref().map(|condition| {
+            self.visit_expression(condition.as_ref());
+            self.emit(OP_JUMP_NOT_TRUTHY, &[9999])
+        });
+        self.visit_loop_body(stmt.body.as_ref());
+        let next = self.scope().instructions.len();
+        for symbol in &per_iteration {
+            self.load_symbol(symbol);
+            self.set_symbol(symbol);
+        }
+        if let Some(post) = &stmt.post {
+            self.visit_statement(post.as_ref());
+        }
+        self.emit(OP_JUMP, &[start]);
+        let exit = self.scope().instructions.len();
+        if let Some(exit_jump) = exit_jump {
+            self.change_operand(exit_jump, exit);
+        }
+        self.finish_loop(next, exit);
+        self.symbol_table.pop_block();
+    }
+
+    fn visit_range_statement(&mut self, stmt: &RangeStatement) {
+        if let Some(x) = &stmt.x {
+            self.visit_expression(x.as_ref());
+        }
+        // a range over a channel receives into the key
+        self.emit(OP_RANGE, &[]);
+        self.symbol_table.push_block();
+        let iterator = self.symbol_table.define(RANGE_ITERATOR);
+        self.set_symbol(&iterator);
+
+        let start = self.scope().instructions.len();
+        self.load_symbol(&iterator);
+        let exit_jump = self.emit(OP_NEXT, &[9999]);
+        // the key is below the value on the stack
+        for target in [&stmt.value, &stmt.key] {
+            match (target, stmt.operator.as_str()) {
+                (Some(target), ":=") => match target.as_any().downcast_ref::<Identifier>() {
+                    Some(ident) => self.define_variable(&ident.value),
+                    None => {
+                        self.error_at(target.as_ref(), format!("non-name {} on left side of :=", format_expression(target.as_ref())));
+                        self.emit(OP_POP, &[]);
+                    }
+                },
+                (Some(target), "=") => self.store(target.as_ref()),
+                _ => {
+                    self.emit(OP_POP, &[]);
+                }
+            }
+        }
+        self.visit_loop_body(stmt.body.as_ref());
+        self.emit(OP_JUMP, &[start]);
+        let exit = self.scope().instructions.len();
+        self.change_operand(exit_jump, exit);
+        self.finish_loop(start, exit);
+        self.symbol_table.pop_block();
+    }
+
+    fn visit_branch_statement(&mut self, stmt: &BranchStatement) {
+        let is_break = stmt.token.literal == "break";
+        let loops = &self.scope().loops;
+        let target = if is_break { loops.len().checked_sub(1) } else { loops.iter().rposition(|l| !l.select) };
+        let target = match target {
+            Some(target) => target,
+            None => {
+                let msg = if is_break { "break is not in a loop, switch, or select" } else { "continue is not in a loop" };
+                self.error(Some(&stmt.token), msg.to_string());
+                return;
+            }
+        };
+        let position = self.emit(OP_JUMP, &[9999]);
+        let l = &mut self.scope().loops[target];
+        if is_break {
+            l.breaks.push(position);
+        } else {
+            l.continues.push(position);
+        }
+    }
+
+    fn visit_go_statement(&mut self, stmt: &GoStatement) {
+        let call = match stmt.call.as_deref().and_then(|c| c.as_any().downcast_ref::<CallExpression>()) {
+            Some(call) => call,
+            None => return,
+        };
+        if call.function.as_deref().is_some_and(|f| self.is_type(f)) {
+            self.error(Some(&stmt.token), "go requires function call, not conversion".to_string());
+            return;
+        }
+        // the function and arguments are evaluated here, the call runs in
+        // the new goroutine
+        visit::walk_call_expression(self, call);
+        self.mark(&call.token);
+        self.emit(OP_GO, &[call.arguments.len()]);
+    }
+
+    fn visit_defer_statement(&mut self, stmt: &DeferStatement) {
+        if self.scopes.len() == 1 {
+            self.error(Some(&stmt.token), "defer statement outside function".to_string());
+            return;
+        }
+        let call = match stmt.call.as_deref().and_then(|c| c.as_any().downcast_ref::<CallExpression>()) {
+            Some(call) => call,
+            None => return,
+        };
+        if call.function.as_deref().is_some_and(|f| self.is_type(f)) {
+            self.error(Some(&stmt.token), "defer requires function call, not conversion".to_string());
+            return;
+        }
+        // the function and arguments are evaluated now, the call runs
+        // when the function returns
+        visit::walk_call_expression(self, call);
+        self.mark(&call.token);
+        self.emit(OP_DEFER, &[call.arguments.len()]);
+    }
+
+    fn visit_send_statement(&mut self, stmt: &SendStatement) {
+        visit::walk_send_statement(self, stmt);
+        self.emit(OP_SEND, &[]);
+    }
+
+    fn visit_select_statement(&mut self, stmt: &SelectStatement) {
+        let mut cases = vec![];
+        let mut default = None;
+        for (i, clause) in stmt.cases.iter().enumerate() {
+            match &clause.comm {
+                Some(comm) => {
+                    if let Some(receive) = self.compile_comm(comm.as_ref(), &clause.token) {
+                        cases.push((i, receive));
+                    }
+                }
+                None if default.is_some() => {
+                    self.error(Some(&clause.token), "multiple defaults in select".to_string());
+                }
+                None => default = Some(i),
+            }
+        }
+        self.emit(OP_SELECT, &[cases.len(), default.is_some() as usize]);
+
+        // the value, ok flag and chosen case are on the stack, case on top
+        self.symbol_table.push_block();
+        let mut results = vec![];
+        for name in SELECT_RESULTS.iter().rev() {
+            let symbol = self.symbol_table.define(name);
+            self.set_symbol(&symbol);
+            results.push(symbol);
+        }
+        results.reverse();
+        // the VM numbers the default case after the others
+        let default = default.map(|i| (i, (vec![], "")));
+
+        self.scope().loops.push(Loop { select: true, ..Loop::default() });
+        let mut end_jumps = vec![];
+        for (index, (i, (left, operator))) in cases.into_iter().chain(default).enumerate() {
+            self.load_symbol(&results[2]);
+            let constant = self.add_constant(Value::Integer(index as i64));
+            self.emit(OP_CONSTANT, &[constant]);
+            self.emit(OP_EQUAL, &[]);
+            let next_case = self.emit(OP_JUMP_NOT_TRUTHY, &[9999]);
+
+            self.symbol_table.push_block();
+            for symbol in &results[..left.len()] {
+                self.load_symbol(symbol);
+            }
+            for target in left.iter().rev() {
+                match target.as_any().downcast_ref::<Identifier>() {
+                    Some(ident) if operator == ":=" => self.define_variable(&ident.value),
+                    _ if operator == "=" => self.store(*target),
+                    _ => {
+                        self.error_at(*target, format!("non-name {} on left side of :=", format_expression(*target)));
+                        self.emit(OP_POP, &[]);
+                    }
+                }
+            }
+            for body in &stmt.cases[i].body {
+                self.visit_statement(body.as_ref());
+            }
+            self.symbol_table.pop_block();
+
+            end_jumps.push(self.emit(OP_JUMP, &[9999]));
+            let position = self.scope().instructions.len();
+            self.change_operand(next_case, position);
+        }
+        let end = self.scope().instructions.len();
+        for position in end_jumps {
+            self.change_operand(position, end);
+        }
+        self.finish_loop(end, end);
+        self.symbol_table.pop_block();
+    }
+
+    fn visit_import_statement(&mut self, stmt: &ImportStatement) {
+        if self.scopes.len() > 1 {
+            self.error(Some(&stmt.token), "import declarations must be at top level".to_string());
+            return;
+        }
+        for spec in &stmt.specs {
+            let path = match &spec.path {
+                Some(path) => path,
+                None => continue,
+            };
+            if self.denied.contains(&path.value) {
+                self.error(Some(&path.token), format!("use of package {} is not allowed", path.value));
+                continue;
+            }
+            let loaded = self.packages.iter().position(|p| p.path == path.value);
+            let (index, own_name) = match (stdlib::lookup(&path.value), loaded) {
+                (Some(index), _) => (index, stdlib::PACKAGES[index].name()),
+                (None, Some(i)) => (stdlib::PACKAGES.len() + i, self.packages[i].name.as_str()),
+                (None, None) => {
+                    let msg = if path.value.split('/').next().is_some_and(|first| first.contains('.')) {
+                        format!("cannot find package {}", path.value)
+                    } else {
+                        format!("package {} is not in std", path.value)
+                    };
+                    self.error(Some(&path.token), msg);
+                    continue;
+                }
+            };
+            let own_name = own_name.to_string();
+            let name = spec.name.as_ref().map_or(own_name.as_str(), |name| name.value.as_str());
+            match name {
+                "_" => {}
+                "." => self.error(Some(&path.token), "dot imports are not supported".to_string()),
+                name => {
+                    self.symbol_table.define_package(name, index);
+                }
+            }
+        }
+    }
+
+    /// The loader checks that the files of a package agree on its name.
+    fn visit_package_clause(&mut self, stmt: &PackageClause) {
+        if self.scopes.len() > 1 {
+            self.error(Some(&stmt.token), "syntax error: package statement must be first".to_string());
+        }
+    }
+
+    fn visit_identifier(&mut self, ident: &Identifier) {
+        match self.symbol_table.resolve(&ident.value) {
+            Some(symbol) if symbol.scope == TYPE_SCOPE => {
+                self.error(ident.token.as_ref(), format!("{} (type) is not an expression", ident.value));
+                self.emit(OP_NULL, &[]);
+            }
+            Some(symbol) if symbol.scope == PACKAGE_SCOPE => {
+                self.error(ident.token.as_ref(), format!("use of package {} without selector", ident.value));
+                self.emit(OP_NULL, &[]);
+            }
+            Some(symbol) => self.load_symbol(&symbol),
+            None if ident.value == "nil" => {
+                self.emit(OP_NULL, &[]);
+            }
+            None => {
+                let msg = if ident.value == "_" {
+                    "cannot use _ as value".to_string()
+                } else if universe_type(&ident.value).is_some() {
+                    format!("{} (type) is not an expression", ident.value)
+                } else {
+                    format!("undefined: {}", ident.value)
+                };
+                self.error(ident.token.as_ref(), msg);
+                self.emit(OP_NULL, &[]);
+            }
+        }
+    }
+
+    fn visit_integer_literal(&mut self, lit: &IntegerLiteral) {
+        let constant = self.add_constant(Value::Integer(lit.value.unwrap_or(0)));
+        self.emit(OP_CONSTANT, &[constant]);
+    }
+
+    fn visit_float_literal(&mut self, lit: &FloatLiteral) {
+        let constant = self.add_constant(Value::Float(lit.value.unwrap_or(0.0)));
+        self.emit(OP_CONSTANT, &[constant]);
+    }
+
+    fn visit_string_literal(&mut self, lit: &StringLiteral) {
+        let bytes = unquote(&lit.token.literal).unwrap_or_default();
+        let constant = self.add_constant(Value::String(Rc::from(bytes)));
+        self.emit(OP_CONSTANT, &[constant]);
+    }
+
+    fn visit_boolean(&mut self, lit: &Boolean) {
+        if lit.value {
+            self.emit(OP_TRUE, &[]);
+        } else {
+            self.emit(OP_FALSE, &[]);
+        }
+    }
+
+    fn visit_prefix_expression(&mut self, expr: &PrefixExpression) {
+        if expr.operator == "&" {
+            if let Some(right) = &expr.right {
+                self.compile_address(right.as_ref());
+            }
+            return;
+        }
+        visit::walk_prefix_expression(self, expr);
+        self.mark(&expr.token);
+        match expr.operator.as_str() {
+            "!" => self.emit(OP_BANG, &[]),
+            "-" => self.emit(OP_MINUS, &[]),
+            "*" => self.emit(OP_DEREF, &[]),
+            "<-" => self.emit(OP_RECV, &[]),
+            op => {
+                self.error(Some(&expr.token), format!("unknown operator {}", op));
+                0
+            }
+        };
+    }
+
+    fn visit_infix_expression(&mut self, expr: &InfixExpression) {
+        let (left, right) = match (&expr.left, &expr.right) {
+            (Some(left), Some(right)) => (left.as_ref(), right.as_ref()),
+            _ => return,
+        };
+        match expr.operator.as_str() {
+            // a < b is compiled as b > a
+            "<" | "<=" => {
+                self.visit_expression(right);
+                self.visit_expression(left);
+                let op = if expr.operator == "<" { OP_GREATER_THAN } else { OP_GREATER_EQUAL };
+                self.emit(op, &[]);
+                return;
+            }
+            "&&" => {
+                self.visit_expression(left);
+                let jump_false = self.emit(OP_JUMP_NOT_TRUTHY, &[9999]);
+                self.visit_expression(right);
+                let jump_end = self.emit(OP_JUMP, &[9999]);
+                let position = self.scope().instructions.len();
+                self.change_operand(jump_false, position);
+                self.emit(OP_FALSE, &[]);
+                let position = self.scope().instructions.len();
+                self.change_operand(jump_end, position);
+                return;
+            }
+            "||" => {
+                self.visit_expression(left);
+                let jump_right = self.emit(OP_JUMP_NOT_TRUTHY, &[9999]);
+                self.emit(OP_TRUE, &[]);
+                let jump_end = self.emit(OP_JUMP, &[9999]);
+                let position = self.scope().instructions.len();
+                self.change_operand(jump_right, position);
+                self.visit_expression(right);
+                let position = self.scope().instructions.len();
+                self.change_operand(jump_end, position);
+                return;
+            }
+            _ => {}
+        }
+
+        self.visit_expression(left);
+        self.visit_expression(right);
+        self.mark(&expr.token);
+        match expr.operator.as_str() {
+            ">" => self.emit(OP_GREATER_THAN, &[]),
+            ">=" => self.emit(OP_GREATER_EQUAL, &[]),
+            "==" => self.emit(OP_EQUAL, &[]),
+            "!=" => self.emit(OP_NOT_EQUAL, &[]),
+            op => {
+                self.emit_operator(op, &expr.token);
+                0
+            }
+        };
+    }
+
+    fn visit_if_expression(&mut self, expr: &IfExpression) {
+        if let Some(condition) = &expr.condition {
+            self.visit_expression(condition.as_ref());
+        }
+        // placeholder operands, patched once the targets are known
+        let jump_not_truthy = self.emit(OP_JUMP_NOT_TRUTHY, &[9999]);
+        self.compile_value_block(expr.consequence.as_ref());
+
+        let jump = self.emit(OP_JUMP, &[9999]);
+        let after_consequence = self.scope().instructions.len();
+        self.change_operand(jump_not_truthy, after_consequence);
+
+        match &expr.alternative {
+            Some(alternative) => self.compile_value_block(Some(alternative)),
+            None => {
+                self.emit(OP_NULL, &[]);
+            }
+        }
+        let after_alternative = self.scope().instructions.len();
+        self.change_operand(jump, after_alternative);
+    }
+
+    fn visit_function_literal(&mut self, func: &FunctionLiteral) {
+        let name = self.function_name.take();
+        let trace_name = self.function_trace_name(name.as_deref());
+        self.enter_scope(trace_name);
+        if let Some(name) = &name {
+            self.symbol_table.define_function_name(name);
+        }
+        for param in &func.parameters {
+            self.symbol_table.define(&param.value);
+        }
+        let result = self.compile_named_results(func);
+        if let Some(body) = &func.body {
+            self.visit_block_statement(body);
+        }
+        // a function with a named result returns it, not its last value
+        if result.is_none() && self.last_instruction_is(OP_POP) {
+            self.replace_last_pop_with_return();
+        }
+        if !self.last_instruction_is(OP_RETURN_VALUE) {
+            self.emit(OP_RETURN, &[]);
+        }
+
+        let free_symbols = self.symbol_table.free_symbols.clone();
+        let num_locals = self.symbol_table.num_definitions;
+        let scope = self.leave_scope();
+        // closures share the variables they capture
+        for symbol in &free_symbols {
+            self.load_address(symbol);
+        }
+
+        let compiled = CompiledFunction {
+            instructions: scope.instructions,
+            num_locals,
+            parameters: func.parameters.iter().map(|p| p.value.clone()).collect(),
+            name: scope.name,
+            result,
+            positions: scope.positions,
+        };
+        let constant = self.add_constant(Value::CompiledFunction(Rc::new(compiled)));
+        self.emit(OP_CLOSURE, &[constant, free_symbols.len()]);
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression) {
+        let unpack = std::mem::take(&mut self.unpack);
+        if !unpack {
+            if let Some((_, results)) = self.multiple_results(call) {
+                let msg = format!(
+                    "multiple-value {} (value of type ({})) in single-value context",
+                    format_expression(call),
+                    results.join(", ")
+                );
+                self.error(Some(&call.token), msg);
+            }
+        }
+        if let Some(function) = call.function.as_deref().filter(|f| self.is_type(*f)) {
+            // T(x) is a conversion
+            if let Some(ellipsis) = &call.ellipsis {
+                self.error(Some(ellipsis), format!("invalid use of ... in conversion to {}", format_expression(function)));
+                return;
+            }
+            let ty = self.resolve_type(function);
+            match call.arguments.as_slice() {
+                [arg] => self.visit_expression(arg.as_ref()),
+                [] => {
+                    self.error(Some(&call.token), format!("missing argument in conversion to {}", format_expression(function)));
+                    return;
+                }
+                _ => {
+                    self.error(Some(&call.token), format!("too many arguments in conversion to {}", format_expression(function)));
+                    return;
+                }
+            }
+            if let Some(ty) = ty {
+                let constant = self.type_constant(ty);
+                self.emit(OP_CONVERT, &[constant, 1]);
+            }
+            return;
+        }
+        let builtin = call
+            .function
+            .as_deref()
+            .and_then(|f| f.as_any().downcast_ref::<Identifier>())
+            .filter(|ident| self.symbol_table.resolve(&ident.value).is_some_and(|symbol| symbol.scope == BUILTIN_SCOPE))
+            .map(|ident| ident.value.clone());
+        if let Some(ellipsis) = &call.ellipsis {
+            // only append is variadic
+            match (builtin.as_deref(), &call.function) {
+                (Some("append"), _) if call.arguments.len() == 2 => {
+                    visit::walk_call_expression(self, call);
+                    self.mark(&call.token);
+                    self.emit(OP_CALL_SPREAD, &[call.arguments.len()]);
+                }
+                (Some("append"), _) => self.error(Some(ellipsis), "can only use ... as append(s, t...)".to_string()),
+                (Some(name), _) => self.error(Some(ellipsis), format!("invalid use of ... with built-in {}", name)),
+                (None, Some(function)) if self.host_signature(function.as_ref()).is_some_and(|s| s.variadic) => {
+                    // the elements of the slice are the extra arguments
+                    let signature = self.host_signature(function.as_ref()).unwrap();
+                    if !self.check_arity(call, &signature, call.arguments.len()) {
+                        return;
+                    }
+                    visit::walk_call_expression(self, call);
+                    self.mark(&call.token);
+                    self.emit(OP_CALL_SPREAD, &[call.arguments.len()]);
+                }
+                (None, Some(function)) => {
+                    let function = format_expression(function.as_ref());
+                    self.error(Some(ellipsis), format!("cannot use ... in call to non-variadic {}", function));
+                }
+                (None, None) => {}
+            }
+            return;
+        }
+        if builtin.as_deref().is_some_and(|name| builtins::TYPE_ARGUMENT.contains(&name)) {
+            // the first argument is a type, passed as a value
+            if let Some(function) = &call.function {
+                self.visit_expression(function.as_ref());
+            }
+            for (i, arg) in call.arguments.iter().enumerate() {
+                if i > 0 {
+                    self.visit_expression(arg.as_ref());
+                } else if let Some(ty) = self.resolve_type(arg.as_ref()) {
+                    let constant = self.type_constant(ty);
+                    self.emit(OP_CONSTANT, &[constant]);
+                } else {
+                    self.emit(OP_NULL, &[]);
+                }
+            }
+            self.mark(&call.token);
+            self.emit(OP_CALL, &[call.arguments.len()]);
+            return;
+        }
+        if let Some(function) = &call.function {
+            if let Some(signature) = self.host_signature(function.as_ref()) {
+                let args = match call.arguments.as_slice() {
+                    [arg] => self.multiple_results(arg.as_ref()).map_or(1, |(_, results)| results.len()),
+                    args => args.len(),
+                };
+                self.check_arity(call, &signature, args);
+            }
+            self.visit_expression(function.as_ref());
+        }
+        for arg in &call.arguments {
+            // f(g()) passes all the results of g
+            self.unpack = call.arguments.len() == 1 && self.multiple_results(arg.as_ref()).is_some();
+            self.visit_expression(arg.as_ref());
+        }
+        self.mark(&call.token);
+        self.emit(OP_CALL, &[call.arguments.len()]);
+    }
+
+    fn visit_index_expression(&mut self, expr: &IndexExpression) {
+        visit::walk_index_expression(self, expr);
+        self.mark(&expr.token);
+        self.emit(OP_INDEX, &[]);
+    }
+
+    fn visit_slice_expression(&mut self, expr: &SliceExpression) {
+        if let Some(left) = &expr.left {
+            self.visit_expression(left.as_ref());
+        }
+        for bound in [&expr.low, &expr.high] {
+            match bound {
+                Some(bound) => self.visit_expression(bound.as_ref()),
+                None => {
+                    self.emit(OP_NULL, &[]);
+                }
+            }
+        }
+        self.mark(&expr.token);
+        self.emit(OP_SLICE, &[]);
+    }
+
+    fn visit_selector_expression(&mut self, expr: &SelectorExpression) {
+        if let Some(member) = self.loaded_member(expr) {
+            match member {
+                Ok(symbol) if symbol.scope == TYPE_SCOPE => {
+                    self.error_at(expr, format!("{} (type) is not an expression", format_expression(expr)));
+                    self.emit(OP_NULL, &[]);
+                }
+                Ok(symbol) => self.load_symbol(&symbol),
+                Err(msg) => {
+                    self.error_at(expr, msg);
+                    self.emit(OP_NULL, &[]);
+                }
+            }
+            return;
+        }
+        if let Some((package, name)) = self.package_member(expr) {
+            self.mark(&expr.token);
+            self.compile_package_member(package, &name, expr);
+            return;
+        }
+        if let Some(left) = &expr.left {
+            self.visit_expression(left.as_ref());
+        }
+        let name = expr.selector.as_ref().map(|s| s.value.clone()).unwrap_or_default();
+        let constant = self.name_constant(&name);
+        self.mark(&expr.token);
+        self.emit(OP_FIELD, &[constant]);
+    }
+
+    fn visit_type_assert_expression(&mut self, expr: &TypeAssertExpression) {
+        if let Some(ty) = self.assert_operands(expr) {
+            let constant = self.type_constant(ty);
+            self.mark(&expr.token);
+            self.emit(OP_ASSERT, &[constant]);
+        }
+    }
+
+    fn visit_composite_literal(&mut self, lit: &CompositeLiteral) {
+        self.compile_composite(lit, None);
+    }
+
+    fn visit_key_value_expression(&mut self, expr: &KeyValueExpression) {
+        self.error(Some(&expr.token), "unexpected key:value outside a composite literal".to_string());
+        self.emit(OP_NULL, &[]);
+    }
+
+    fn visit_slice_type(&mut self, ty: &SliceType) {
+        self.error(Some(&ty.token), format!("{} (type) is not an expression", ty.string()));
+        self.emit(OP_NULL, &[]);
+    }
+
+    fn visit_map_type(&mut self, ty: &MapType) {
+        self.error(Some(&ty.token), format!("{} (type) is not an expression", ty.string()));
+        self.emit(OP_NULL, &[]);
+    }
+
+    fn visit_chan_type(&mut self, ty: &ChanType) {
+        self.error(Some(&ty.token), format!("{} (type) is not an expression", ty.string()));
+        self.emit(OP_NULL, &[]);
+    }
+
+    fn visit_struct_type(&mut self, ty: &StructType) {
+        self.error(Some(&ty.token), format!("{} (type) is not an expression", ty.string()));
+        self.emit(OP_NULL, &[]);
+    }
+
+    fn visit_interface_type(&mut self, ty: &InterfaceType) {
+        self.error(Some(&ty.token), format!("{} (type) is not an expression", ty.string()));
+        self.emit(OP_NULL, &[]);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::value::builtins;
+use std::collections::HashMap;
+
+pub type SymbolScope = &'static str;
+
+pub const GLOBAL_SCOPE: SymbolScope = "GLOBAL";
+pub const LOCAL_SCOPE: SymbolScope = "LOCAL";
+pub const FREE_SCOPE: SymbolScope = "FREE";
+pub const FUNCTION_SCOPE: SymbolScope = "FUNCTION";
+pub const BUILTIN_SCOPE: SymbolScope = "BUILTIN";
+/// A declared type; the index is that of its constant.
+pub const TYPE_SCOPE: SymbolScope = "TYPE";
+/// An imported package; the index is that of `stdlib::PACKAGES`, or past
+/// its end that of a package compiled earlier.
+pub const PACKAGE_SCOPE: SymbolScope = "PACKAGE";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+/// Names visible in one function body (or the top level), chained to the
+/// table of the enclosing function.
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    pub outer: Option<Box<SymbolTable>>,
+    /// Names declared in each open block, the function's own first.
+    blocks: Vec<HashMap<String, Symbol>>,
+    pub num_definitions: usize,
+    /// Symbols of enclosing functions this function captures, in the order
+    /// the closure expects them.
+    pub free_symbols: Vec<Symbol>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        SymbolTable { outer: None, blocks: vec![HashMap::new()], num_definitions: 0, free_symbols: vec![] }
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// The top-level table of another package, whose globals are numbered
+    /// after those of `previous`.
+    pub fn after(previous: &SymbolTable) -> SymbolTable {
+        SymbolTable { num_definitions: previous.num_definitions, ..SymbolTable::default() }
+    }
+
+    pub fn new_enclosed(outer: SymbolTable) -> SymbolTable {
+        SymbolTable { outer: Some(Box::new(outer)), ..SymbolTable::default() }
+    }
+
+    /// Opens the scope of a block inside the function. Its variables still
+    /// count towards the function's `num_definitions`.
+    pub fn push_block(&mut self) {
+        self.blocks.push(HashMap::new());
+    }
+
+    pub fn pop_block(&mut self) {
+        if self.blocks.len() > 1 {
+            self.blocks.pop();
+        }
+    }
+
+    fn insert(&mut self, symbol: Symbol) {
+        self.blocks.last_mut().unwrap().insert(symbol.name.clone(), symbol);
+    }
+
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let scope = if self.outer.is_some() { LOCAL_SCOPE } else { GLOBAL_SCOPE };
+        let symbol = Symbol { name: name.to_string(), scope, index: self.num_definitions };
+        self.insert(symbol.clone());
+        self.num_definitions += 1;
+        symbol
+    }
+
+    /// Declares a type whose constant is at `index`.
+    pub fn define_type(&mut self, name: &str, index: usize) -> Symbol {
+        let symbol = Symbol { name: name.to_string(), scope: TYPE_SCOPE, index };
+        self.insert(symbol.clone());
+        symbol
+    }
+
+    /// Declares the name an imported package is known by.
+    pub fn define_package(&mut self, name: &str, index: usize) -> Symbol {
+        let symbol = Symbol { name: name.to_string(), scope: PACKAGE_SCOPE, index };
+        self.insert(symbol.clone());
+        symbol
+    }
+
+    /// Reports whether `name` is declared in the innermost block, where
+    /// declaring it again is an error.
+    pub fn declared_in_block(&self, name: &str) -> bool {
+        self.blocks.last().unwrap().contains_key(name)
+    }
+
+    /// Makes a function's own name resolve to the closure being executed,
+    /// so a function bound with `let` can call itself.
+    pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol { name: name.to_string(), scope: FUNCTION_SCOPE, index: 0 };
+        self.insert(symbol.clone());
+        symbol
+    }
+
+    fn define_free(&mut self, original: Symbol) -> Symbol {
+        let symbol = Symbol { name: original.name.clone(), scope: FREE_SCOPE, index: self.free_symbols.len() };
+        self.free_symbols.push(original);
+        self.blocks[0].insert(symbol.name.clone(), symbol.clone());
+        symbol
+    }
+
+    /// The innermost declaration of `name`, falling back to the builtins.
+    pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.blocks.iter().rev().find_map(|block| block.get(name)) {
+            return Some(symbol.clone());
+        }
+        let outer = match self.outer.as_mut() {
+            Some(outer) => outer,
+            None => {
+                let index = builtins::lookup(name)?;
+                return Some(Symbol { name: name.to_string(), scope: BUILTIN_SCOPE, index });
+            }
+        };
+        let symbol = outer.resolve(name)?;
+        if [GLOBAL_SCOPE, BUILTIN_SCOPE, TYPE_SCOPE, PACKAGE_SCOPE].contains(&symbol.scope) {
+            return Some(symbol);
+        }
+        Some(self.define_free(symbol))
+    }
+
+    /// The variables and types declared at the top level.
+    pub fn declarations(&self) -> HashMap<String, Symbol> {
+        self.blocks[0]
+            .iter()
+            .filter(|(_, s)| s.scope == GLOBAL_SCOPE || s.scope == TYPE_SCOPE)
+            .map(|(name, s)| (name.clone(), s.clone()))
+            .collect()
+    }
+
+    /// Global names in definition order.
+    pub fn globals(&self) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> =
+            self.blocks.iter().flat_map(|block| block.values()).filter(|s| s.scope == GLOBAL_SCOPE).cloned().collect();
+        symbols.sort_by_key(|s| s.index);
+        symbols
+    }
+}
@@ -0,0 +1,229 @@
+use crate::code::*;
+use crate::compiler::*;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn compile(input: &str) -> Bytecode {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    if !p.errors().is_empty() {
+      panic!("parser errors: {:?}", p.errors());
+    }
+    let mut c = Compiler::new();
+    if let Err(errors) = c.compile(&program) {
+      panic!("compiler errors: {:?}", errors);
+    }
+    c.bytecode()
+  }
+
+  fn check_instructions(input: &str, expected: Vec<Instructions>) {
+    let bytecode = compile(input);
+    let expected = expected.concat();
+    if bytecode.instructions != expected {
+      panic!(
+        "wrong instructions for {:?}.\nwant=\n{}got=\n{}",
+        input,
+        instructions_string(&expected),
+        instructions_string(&bytecode.instructions)
+      );
+    }
+  }
+
+  fn function_constant(bytecode: &Bytecode, index: usize) -> Instructions {
+    match &bytecode.constants[index] {
+      Value::CompiledFunction(f) => f.instructions.clone(),
+      obj => panic!("constant {} is not a function: {:?}", index, obj),
+    }
+  }
+
+  #[test]
+  fn test_integer_arithmetic() {
+    check_instructions("1 + 2", vec![
+      make(OP_CONSTANT, &[0]),
+      make(OP_CONSTANT, &[1]),
+      make(OP_ADD, &[]),
+      make(OP_POP, &[]),
+    ]);
+    check_instructions("-1", vec![
+      make(OP_CONSTANT, &[0]),
+      make(OP_MINUS, &[]),
+      make(OP_POP, &[]),
+    ]);
+  }
+
+  #[test]
+  fn test_less_than_swaps_operands() {
+    let bytecode = compile("1 < 2");
+    if bytecode.constants != vec![Value::Integer(2), Value::Integer(1)] {
+      panic!("wrong constants: {:?}", bytecode.constants);
+    }
+    check_instructions("1 < 2", vec![
+      make(OP_CONSTANT, &[0]),
+      make(OP_CONSTANT, &[1]),
+      make(OP_GREATER_THAN, &[]),
+      make(OP_POP, &[]),
+    ]);
+  }
+
+  #[test]
+  fn test_conditionals() {
+    check_instructions("if true { 10 }; 3333;", vec![
+      make(OP_TRUE, &[]),
+      make(OP_JUMP_NOT_TRUTHY, &[10]),
+      make(OP_CONSTANT, &[0]),
+      make(OP_JUMP, &[11]),
+      make(OP_NULL, &[]),
+      make(OP_POP, &[]),
+      make(OP_CONSTANT, &[1]),
+      make(OP_POP, &[]),
+    ]);
+    check_instructions("if true { let a = 1; } else { 20 }", vec![
+      make(OP_TRUE, &[]),
+      make(OP_JUMP_NOT_TRUTHY, &[14]),
+      make(OP_CONSTANT, &[0]),
+      make(OP_SET_GLOBAL, &[0]),
+      make(OP_NULL, &[]),
+      make(OP_JUMP, &[17]),
+      make(OP_CONSTANT, &[1]),
+      make(OP_POP, &[]),
+    ]);
+  }
+
+  #[test]
+  fn test_global_let_statements() {
+    check_instructions("let one = 1; let two = one;", vec![
+      make(OP_CONSTANT, &[0]),
+      make(OP_SET_GLOBAL, &[0]),
+      make(OP_GET_GLOBAL, &[0]),
+      make(OP_SET_GLOBAL, &[1]),
+    ]);
+  }
+
+  #[test]
+  fn test_functions_and_closures() {
+    let bytecode = compile("fn(a) { fn(b) { a + b } }");
+    let inner = function_constant(&bytecode, 0);
+    let expected = [
+      make(OP_GET_FREE, &[0]),
+      make(OP_GET_LOCAL, &[0]),
+      make(OP_ADD, &[]),
+      make(OP_RETURN_VALUE, &[]),
+    ].concat();
+    if inner != expected {
+      panic!("wrong inner function.\nwant=\n{}got=\n{}", instructions_string(&expected), instructions_string(&inner));
+    }
+    let outer = function_constant(&bytecode, 1);
+    // the closure captures the variable a itself, not its value
+    let expected = [
+      make(OP_ADDR_LOCAL, &[0]),
+      make(OP_CLOSURE, &[0, 1]),
+      make(OP_RETURN_VALUE, &[]),
+    ].concat();
+    if outer != expected {
+      panic!("wrong outer function.\nwant=\n{}got=\n{}", instructions_string(&expected), instructions_string(&outer));
+    }
+  }
+
+  #[test]
+  fn test_recursive_function_uses_current_closure() {
+    let bytecode = compile("let f = fn(x) { f(x) };");
+    let body = function_constant(&bytecode, 0);
+    let expected = [
+      make(OP_CURRENT_CLOSURE, &[]),
+      make(OP_GET_LOCAL, &[0]),
+      make(OP_CALL, &[1]),
+      make(OP_RETURN_VALUE, &[]),
+    ].concat();
+    if body != expected {
+      panic!("wrong body.\nwant=\n{}got=\n{}", instructions_string(&expected), instructions_string(&body));
+    }
+  }
+
+  #[test]
+  fn test_errors_have_positions() {
+    let mut p = Parser::new(Lexer::new("let a = 1;\nb + fn() { return; }\nreturn 1;".to_string()));
+    let program = p.parse_program();
+    let errors = Compiler::new().compile(&program).unwrap_err();
+    let got: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    let expected = vec!["2:1: undefined: b", "3:1: return statement outside function"];
+    if got != expected {
+      panic!("wrong errors. want={:?}, got={:?}", expected, got);
+    }
+  }
+
+  #[test]
+  fn test_select_errors() {
+    let input = "ch := make(chan int)\nselect { case x := 1: }\nselect { default: continue }\nselect { default:\ndefault: }";
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    let errors = Compiler::new().compile(&program).unwrap_err();
+    let got: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    let expected = vec![
+      "2:10: select case must be receive, send or assign recv",
+      "3:19: continue is not in a loop",
+      "5:1: multiple defaults in select",
+    ];
+    if got != expected {
+      panic!("wrong errors. want={:?}, got={:?}", expected, got);
+    }
+  }
+
+  #[test]
+  fn test_defer_errors() {
+    let input = "defer f()\nlet g = fn() { defer int(1) }\nlet h = fn() (a, b int) { a }";
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    let errors = Compiler::new().compile(&program).unwrap_err();
+    let got: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    let expected = vec![
+      "1:1: defer statement outside function",
+      "2:16: defer requires function call, not conversion",
+      "3:9: multiple results are not supported; return a single value",
+    ];
+    if got != expected {
+      panic!("wrong errors. want={:?}, got={:?}", expected, got);
+    }
+  }
+
+  #[test]
+  fn test_ellipsis_errors() {
+    let input = "let f = fn(a) { a }\nf([]int{}...)\nlen(\"a\"...)\nappend([]int{}...)\nint(1...)";
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    let errors = Compiler::new().compile(&program).unwrap_err();
+    let got: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    let expected = vec![
+      "2:10: cannot use ... in call to non-variadic f",
+      "3:8: invalid use of ... with built-in len",
+      "4:15: can only use ... as append(s, t...)",
+      "5:6: invalid use of ... in conversion to int",
+    ];
+    if got != expected {
+      panic!("wrong errors. want={:?}, got={:?}", expected, got);
+    }
+  }
+
+  #[test]
+  fn test_import_errors() {
+    let input = "import \"net/http\"\nimport . \"fmt\"\nimport \"strconv\"\nlet f = fn() { import \"os\" }\nstrconv\nx := strconv.Atoi(\"1\") + 1\na, b, c := strconv.Atoi(\"1\")";
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    let errors = Compiler::new().compile(&program).unwrap_err();
+    let got: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    let expected = vec![
+      "1:8: package net/http is not in std",
+      "2:10: dot imports are not supported",
+      "4:16: import declarations must be at top level",
+      "5:1: use of package strconv without selector",
+      "6:18: multiple-value strconv.Atoi(\"1\") (value of type (int, error)) in single-value context",
+      "7:9: assignment mismatch: 3 variables but strconv.Atoi returns 2 values",
+    ];
+    if got != expected {
+      panic!("wrong errors. want={:?}, got={:?}", expected, got);
+    }
+  }
+}
@@ -0,0 +1,101 @@
+use crate::highlight;
+use crate::token::Token;
+use colored::Colorize;
+use std::fmt;
+
+/// How many errors are reported before giving up, as Go's compiler stops.
+pub const MAX_ERRORS: usize = 10;
+
+/// Adds `diagnostic` to `errors` unless there are `MAX_ERRORS` already, in
+/// which case a single "too many errors" takes the place of the rest.
+pub fn report(errors: &mut Vec<Diagnostic>, diagnostic: Diagnostic) {
+    if errors.len() < MAX_ERRORS {
+        errors.push(diagnostic);
+    } else if errors.len() == MAX_ERRORS {
+        errors.push(Diagnostic::unpositioned("too many errors".to_string()));
+    }
+}
+
+/// Whether `errors` has hit the cap, so there is no point going on.
+pub fn too_many(errors: &[Diagnostic]) -> bool {
+    errors.len() > MAX_ERRORS
+}
+
+/// An error found while reading, compiling or running a program, with the
+/// 1-based position it refers to. Line 0 means the position is unknown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(line: usize, column: usize, message: String) -> Diagnostic {
+        Diagnostic { line, column, message }
+    }
+
+    pub fn at(tok: &Token, message: String) -> Diagnostic {
+        Diagnostic::new(tok.line, tok.column, message)
+    }
+
+    /// A diagnostic with no source position, e.g. a runtime error.
+    pub fn unpositioned(message: String) -> Diagnostic {
+        Diagnostic::new(0, 0, message)
+    }
+
+    /// `file:line:column: message`, the form compilers and editors expect.
+    pub fn with_file(&self, file: &str) -> String {
+        if self.line == 0 {
+            format!("{}: {}", file, self.message)
+        } else {
+            format!("{}:{}:{}: {}", file, self.line, self.column, self.message)
+        }
+    }
+
+    /// Multi-line report quoting the offending source line with a caret
+    /// under the reported column:
+    ///
+    /// ```text
+    /// error: undefined: y
+    ///  --> main.go:1:9
+    ///   |
+    /// 1 | let x = y
+    ///   |         ^
+    /// ```
+    pub fn render(&self, file: &str, source: &str) -> String {
+        let mut out = format!("{}: {}\n", "error".red().bold(), self.message.bold());
+        if self.line == 0 {
+            return out;
+        }
+        out.push_str(&format!(" {} {}:{}:{}\n", "-->".blue().bold(), file, self.line, self.column));
+        let text = match source.lines().nth(self.line - 1) {
+            Some(text) => text,
+            None => return out,
+        };
+        let code = highlight::highlight_line(source, self.line).unwrap_or_else(|| text.to_string());
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let bar = "|".blue().bold();
+        // keep tabs so the caret lines up however the terminal renders them
+        let padding: String = text
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        out.push_str(&format!("{} {}\n", gutter, bar));
+        out.push_str(&format!("{} {} {}\n", number.blue().bold(), bar, code));
+        out.push_str(&format!("{} {} {}{}\n", gutter, bar, padding, "^".red().bold()));
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.line, self.column, self.message)
+        }
+    }
+}
@@ -0,0 +1,1087 @@
+use crate::ast::visit::*;
+use crate::ast::*;
+use crate::lexer::*;
+use crate::token::*;
+
+#[cfg(test)]
+mod test;
+
+/// Start and end (exclusive) of a piece of source, as 1-based line and column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl Span {
+    pub fn of_token(tok: &Token) -> Span {
+        let width = if tok.r#type == EOF { 0 } else { tok.literal.chars().count() };
+        Span { start: (tok.line, tok.column), end: (tok.line, tok.column + width) }
+    }
+
+    fn json(&self) -> String {
+        format!(
+            "{{\"start\":{{\"line\":{},\"column\":{}}},\"end\":{{\"line\":{},\"column\":{}}}}}",
+            self.start.0, self.start.1, self.end.0, self.end.1
+        )
+    }
+}
+
+/// Span covering every token kept in the node's subtree.
+pub fn span_of_statement(stmt: &dyn Statement) -> Option<Span> {
+    let mut finder = SpanFinder(None);
+    finder.visit_statement(stmt);
+    finder.0
+}
+
+/// Span covering every token kept in the node's subtree.
+pub fn span_of_expression(expr: &dyn Expression) -> Option<Span> {
+    let mut finder = SpanFinder(None);
+    finder.visit_expression(expr);
+    finder.0
+}
+
+struct SpanFinder(Option<Span>);
+
+impl SpanFinder {
+    fn add(&mut self, tok: &Token) {
+        // Hand-built nodes carry tokens without a position.
+        if tok.line == 0 {
+            return;
+        }
+        let span = Span::of_token(tok);
+        self.0 = Some(match self.0 {
+            Some(s) => Span { start: s.start.min(span.start), end: s.end.max(span.end) },
+            None => span,
+        });
+    }
+}
+
+impl Visitor for SpanFinder {
+    fn visit_let_statement(&mut self, stmt: &LetStatement) {
+        self.add(&stmt.token);
+        walk_let_statement(self, stmt);
+    }
+    fn visit_return_statement(&mut self, stmt: &ReturnStatement) {
+        self.add(&stmt.token);
+        walk_return_statement(self, stmt);
+    }
+    fn visit_block_statement(&mut self, block: &BlockStatement) {
+        self.add(&block.token);
+        if let Some(rbrace) = &block.rbrace {
+            self.add(rbrace);
+        }
+        walk_block_statement(self, block);
+    }
+    fn visit_identifier(&mut self, ident: &Identifier) {
+        if let Some(tok) = &ident.token {
+            self.add(tok);
+        }
+    }
+    fn visit_integer_literal(&mut self, lit: &IntegerLiteral) {
+        self.add(&lit.token);
+    }
+    fn visit_float_literal(&mut self, lit: &FloatLiteral) {
+        self.add(&lit.token);
+    }
+    fn visit_boolean(&mut self, lit: &Boolean) {
+        self.add(&lit.token);
+    }
+    fn visit_prefix_expression(&mut self, expr: &PrefixExpression) {
+        self.add(&expr.token);
+        walk_prefix_expression(self, expr);
+    }
+    fn visit_if_expression(&mut self, expr: &IfExpression) {
+        self.add(&expr.token);
+        walk_if_expression(self, expr);
+    }
+    fn visit_function_literal(&mut self, func: &FunctionLiteral) {
+        self.add(&func.token);
+        walk_function_literal(self, func);
+    }
+    fn visit_call_expression(&mut self, call: &CallExpression) {
+        if let Some(rparen) = &call.rparen {
+            self.add(rparen);
+        }
+        walk_call_expression(self, call);
+    }
+    fn visit_var_statement(&mut self, stmt: &VarStatement) {
+        self.add(&stmt.token);
+        walk_var_statement(self, stmt);
+    }
+    fn visit_type_statement(&mut self, stmt: &TypeStatement) {
+        self.add(&stmt.token);
+        walk_type_statement(self, stmt);
+    }
+    fn visit_assign_statement(&mut self, stmt: &AssignStatement) {
+        self.add(&stmt.token);
+        walk_assign_statement(self, stmt);
+    }
+    fn visit_for_statement(&mut self, stmt: &ForStatement) {
+        self.add(&stmt.token);
+        walk_for_statement(self, stmt);
+    }
+    fn visit_range_statement(&mut self, stmt: &RangeStatement) {
+        self.add(&stmt.token);
+        walk_range_statement(self, stmt);
+    }
+    fn visit_branch_statement(&mut self, stmt: &BranchStatement) {
+        self.add(&stmt.token);
+    }
+    fn visit_string_literal(&mut self, lit: &StringLiteral) {
+        self.add(&lit.token);
+    }
+    fn visit_index_expression(&mut self, expr: &IndexExpression) {
+        if let Some(rbracket) = &expr.rbracket {
+            self.add(rbracket);
+        }
+        walk_index_expression(self, expr);
+    }
+    fn visit_slice_expression(&mut self, expr: &SliceExpression) {
+        if let Some(rbracket) = &expr.rbracket {
+            self.add(rbracket);
+        }
+        walk_slice_expression(self, expr);
+    }
+    fn visit_type_assert_expression(&mut self, expr: &TypeAssertExpression) {
+        if let Some(rparen) = &expr.rparen {
+            self.add(rparen);
+        }
+        walk_type_assert_expression(self, expr);
+    }
+    fn visit_composite_literal(&mut self, lit: &CompositeLiteral) {
+        self.add(&lit.token);
+        if let Some(rbrace) = &lit.rbrace {
+            self.add(rbrace);
+        }
+        walk_composite_literal(self, lit);
+    }
+    fn visit_slice_type(&mut self, ty: &SliceType) {
+        self.add(&ty.token);
+        walk_slice_type(self, ty);
+    }
+    fn visit_map_type(&mut self, ty: &MapType) {
+        self.add(&ty.token);
+        walk_map_type(self, ty);
+    }
+    fn visit_struct_type(&mut self, ty: &StructType) {
+        self.add(&ty.token);
+        if let Some(rbrace) = &ty.rbrace {
+            self.add(rbrace);
+        }
+        walk_struct_type(self, ty);
+    }
+    fn visit_interface_type(&mut self, ty: &InterfaceType) {
+        self.add(&ty.token);
+        if let Some(rbrace) = &ty.rbrace {
+            self.add(rbrace);
+        }
+    }
+    fn visit_chan_type(&mut self, ty: &ChanType) {
+        self.add(&ty.token);
+        walk_chan_type(self, ty);
+    }
+    fn visit_go_statement(&mut self, stmt: &GoStatement) {
+        self.add(&stmt.token);
+        walk_go_statement(self, stmt);
+    }
+    fn visit_defer_statement(&mut self, stmt: &DeferStatement) {
+        self.add(&stmt.token);
+        walk_defer_statement(self, stmt);
+    }
+    fn visit_import_statement(&mut self, stmt: &ImportStatement) {
+        self.add(&stmt.token);
+        if let Some(rparen) = &stmt.rparen {
+            self.add(rparen);
+        }
+        walk_import_statement(self, stmt);
+    }
+    fn visit_package_clause(&mut self, stmt: &PackageClause) {
+        self.add(&stmt.token);
+        walk_package_clause(self, stmt);
+    }
+    fn visit_send_statement(&mut self, stmt: &SendStatement) {
+        self.add(&stmt.token);
+        walk_send_statement(self, stmt);
+    }
+    fn visit_select_statement(&mut self, stmt: &SelectStatement) {
+        self.add(&stmt.token);
+        if let Some(rbrace) = &stmt.rbrace {
+            self.add(rbrace);
+        }
+        walk_select_statement(self, stmt);
+    }
+}
+
+/// Quotes `s` as a JSON string.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Reads every token of `input`, up to and including EOF.
+pub fn tokenize(input: &str) -> Vec<Token> {
+    let mut l = Lexer::new(input.to_string());
+    let mut tokens = vec![];
+    loop {
+        let tok = l.next_token();
+        let done = tok.r#type == EOF;
+        tokens.push(tok);
+        if done {
+            return tokens;
+        }
+    }
+}
+
+/// One token per line: position, type and literal.
+pub fn tokens_text(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for tok in tokens {
+        out.push_str(&format!("{}:{}\t{}\t{:?}\n", tok.line, tok.column, tok.r#type, tok.literal));
+    }
+    out
+}
+
+/// The token stream as a JSON array.
+pub fn tokens_json(tokens: &[Token]) -> String {
+    let items: Vec<String> = tokens
+        .iter()
+        .map(|tok| {
+            format!(
+                "{{\"type\":{},\"literal\":{},\"span\":{}}}",
+                json_string(tok.r#type),
+                json_string(&tok.literal),
+                Span::of_token(tok).json()
+            )
+        })
+        .collect();
+    ["[", &items.join(","), "]\n"].concat()
+}
+
+/// The AST as JSON: every node is an object with its `kind`, `span` and
+/// one field per child.
+pub fn ast_json(program: &Program) -> String {
+    let mut writer = JsonWriter { out: String::new() };
+    writer.visit_program(program);
+    writer.out.push('\n');
+    writer.out
+}
+
+struct JsonWriter {
+    out: String,
+}
+
+impl JsonWriter {
+    fn open(&mut self, kind: &str, span: Option<Span>) {
+        self.out.push_str("{\"kind\":");
+        self.out.push_str(&json_string(kind));
+        if let Some(span) = span {
+            self.out.push_str(",\"span\":");
+            self.out.push_str(&span.json());
+        }
+    }
+
+    fn field(&mut self, name: &str) {
+        self.out.push(',');
+        self.out.push_str(&json_string(name));
+        self.out.push(':');
+    }
+
+    fn string_field(&mut self, name: &str, value: &str) {
+        self.field(name);
+        self.out.push_str(&json_string(value));
+    }
+
+    fn expression_field(&mut self, name: &str, expr: &Option<Box<dyn Expression>>) {
+        self.field(name);
+        match expr {
+            Some(expr) => self.visit_expression(expr.as_ref()),
+            None => self.out.push_str("null"),
+        }
+    }
+
+    fn block_field(&mut self, name: &str, block: &Option<BlockStatement>) {
+        self.field(name);
+        match block {
+            Some(block) => self.visit_block_statement(block),
+            None => self.out.push_str("null"),
+        }
+    }
+
+    fn expression_list_field(&mut self, name: &str, list: &[Box<dyn Expression>]) {
+        self.field(name);
+        self.out.push('[');
+        for (i, expr) in list.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.visit_expression(expr.as_ref());
+        }
+        self.out.push(']');
+    }
+
+    fn identifier_list_field(&mut self, name: &str, list: &[Identifier]) {
+        self.field(name);
+        self.out.push('[');
+        for (i, ident) in list.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.visit_identifier(ident);
+        }
+        self.out.push(']');
+    }
+
+    fn field_list(&mut self, name: &str, fields: &[FieldDecl]) {
+        self.field(name);
+        self.out.push('[');
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.out.push('{');
+            self.out.push_str("\"kind\":\"Field\"");
+            self.identifier_list_field("names", &field.names);
+            self.expression_field("type", &field.ty);
+            self.out.push('}');
+        }
+        self.out.push(']');
+    }
+
+    fn statement_field(&mut self, name: &str, stmt: &Option<Box<dyn Statement>>) {
+        self.field(name);
+        match stmt {
+            Some(stmt) => self.visit_statement(stmt.as_ref()),
+            None => self.out.push_str("null"),
+        }
+    }
+
+    fn statements(&mut self, statements: &[Box<dyn Statement>]) {
+        self.field("statements");
+        self.out.push('[');
+        for (i, stmt) in statements.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.visit_statement(stmt.as_ref());
+        }
+        self.out.push(']');
+    }
+}
+
+impl Visitor for JsonWriter {
+    fn visit_program(&mut self, program: &Program) {
+        self.open("Program", None);
+        self.statements(&program.statements);
+        self.out.push('}');
+    }
+
+    fn visit_let_statement(&mut self, stmt: &LetStatement) {
+        self.open("LetStatement", span_of_statement(stmt));
+        self.field("name");
+        match &stmt.name {
+            Some(name) => self.visit_identifier(name),
+            None => self.out.push_str("null"),
+        }
+        self.expression_field("value", &stmt.value);
+        self.out.push('}');
+    }
+
+    fn visit_return_statement(&mut self, stmt: &ReturnStatement) {
+        self.open("ReturnStatement", span_of_statement(stmt));
+        self.expression_field("value", &stmt.return_value);
+        self.out.push('}');
+    }
+
+    fn visit_expression_statement(&mut self, stmt: &ExpressionStatement) {
+        self.open("ExpressionStatement", span_of_statement(stmt));
+        self.expression_field("expression", &stmt.expression);
+        self.out.push('}');
+    }
+
+    fn visit_block_statement(&mut self, block: &BlockStatement) {
+        self.open("BlockStatement", span_of_statement(block));
+        self.statements(&block.statements);
+        self.out.push('}');
+    }
+
+    fn visit_identifier(&mut self, ident: &Identifier) {
+        self.open("Identifier", span_of_expression(ident));
+        self.string_field("value", &ident.value);
+        self.out.push('}');
+    }
+
+    fn visit_integer_literal(&mut self, lit: &IntegerLiteral) {
+        self.open("IntegerLiteral", span_of_expression(lit));
+        self.field("value");
+        match lit.value {
+            Some(v) => self.out.push_str(&v.to_string()),
+            None => self.out.push_str("null"),
+        }
+        self.out.push('}');
+    }
+
+    fn visit_float_literal(&mut self, lit: &FloatLiteral) {
+        self.open("FloatLiteral", span_of_expression(lit));
+        self.field("value");
+        match lit.value {
+            // JSON has no infinities; a literal too large for a float is one
+            Some(v) if v.is_finite() => self.out.push_str(&format!("{:?}", v)),
+            _ => self.out.push_str("null"),
+        }
+        self.out.push('}');
+    }
+
+    fn visit_boolean(&mut self, lit: &Boolean) {
+        self.open("Boolean", span_of_expression(lit));
+        self.field("value");
+        self.out.push_str(if lit.value { "true" } else { "false" });
+        self.out.push('}');
+    }
+
+    fn visit_prefix_expression(&mut self, expr: &PrefixExpression) {
+        self.open("PrefixExpression", span_of_expression(expr));
+        self.string_field("operator", &expr.operator);
+        self.expression_field("right", &expr.right);
+        self.out.push('}');
+    }
+
+    fn visit_infix_expression(&mut self, expr: &InfixExpression) {
+        self.open("InfixExpression", span_of_expression(expr));
+        self.string_field("operator", &expr.operator);
+        self.expression_field("left", &expr.left);
+        self.expression_field("right", &expr.right);
+        self.out.push('}');
+    }
+
+    fn visit_if_expression(&mut self, expr: &IfExpression) {
+        self.open("IfExpression", span_of_expression(expr));
+        self.expression_field("condition", &expr.condition);
+        self.block_field("consequence", &expr.consequence);
+        self.block_field("alternative", &expr.alternative);
+        self.out.push('}');
+    }
+
+    fn visit_function_literal(&mut self, func: &FunctionLiteral) {
+        self.open("FunctionLiteral", span_of_expression(func));
+        self.field("parameters");
+        self.out.push('[');
+        for (i, param) in func.parameters.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.visit_identifier(param);
+        }
+        self.out.push(']');
+        self.field_list("results", &func.results);
+        self.block_field("body", &func.body);
+        self.out.push('}');
+    }
+
+    fn visit_var_statement(&mut self, stmt: &VarStatement) {
+        self.open("VarStatement", span_of_statement(stmt));
+        self.identifier_list_field("names", &stmt.names);
+        self.expression_field("type", &stmt.ty);
+        self.expression_list_field("values", &stmt.values);
+        self.out.push('}');
+    }
+
+    fn visit_type_statement(&mut self, stmt: &TypeStatement) {
+        self.open("TypeStatement", span_of_statement(stmt));
+        self.field("name");
+        match &stmt.name {
+            Some(name) => self.visit_identifier(name),
+            None => self.out.push_str("null"),
+        }
+        self.expression_field("type", &stmt.ty);
+        self.out.push('}');
+    }
+
+    fn visit_assign_statement(&mut self, stmt: &AssignStatement) {
+        self.open("AssignStatement", span_of_statement(stmt));
+        self.string_field("operator", &stmt.operator);
+        self.expression_list_field("left", &stmt.left);
+        self.expression_list_field("right", &stmt.right);
+        self.out.push('}');
+    }
+
+    fn visit_for_statement(&mut self, stmt: &ForStatement) {
+        self.open("ForStatement", span_of_statement(stmt));
+        self.statement_field("init", &stmt.init);
+        self.expression_field("condition", &stmt.condition);
+        self.statement_field("post", &stmt.post);
+        self.block_field("body", &stmt.body);
+        self.out.push('}');
+    }
+
+    fn visit_range_statement(&mut self, stmt: &RangeStatement) {
+        self.open("RangeStatement", span_of_statement(stmt));
+        self.expression_field("key", &stmt.key);
+        self.expression_field("value", &stmt.value);
+        self.string_field("operator", &stmt.operator);
+        self.expression_field("x", &stmt.x);
+        self.block_field("body", &stmt.body);
+        self.out.push('}');
+    }
+
+    fn visit_branch_statement(&mut self, stmt: &BranchStatement) {
+        self.open("BranchStatement", span_of_statement(stmt));
+        self.string_field("keyword", &stmt.token.literal);
+        self.out.push('}');
+    }
+
+    fn visit_string_literal(&mut self, lit: &StringLiteral) {
+        self.open("StringLiteral", span_of_expression(lit));
+        self.string_field("value", &lit.value);
+        self.out.push('}');
+    }
+
+    fn visit_index_expression(&mut self, expr: &IndexExpression) {
+        self.open("IndexExpression", span_of_expression(expr));
+        self.expression_field("left", &expr.left);
+        self.expression_field("index", &expr.index);
+        self.out.push('}');
+    }
+
+    fn visit_slice_expression(&mut self, expr: &SliceExpression) {
+        self.open("SliceExpression", span_of_expression(expr));
+        self.expression_field("left", &expr.left);
+        self.expression_field("low", &expr.low);
+        self.expression_field("high", &expr.high);
+        self.out.push('}');
+    }
+
+    fn visit_selector_expression(&mut self, expr: &SelectorExpression) {
+        self.open("SelectorExpression", span_of_expression(expr));
+        self.expression_field("left", &expr.left);
+        self.field("selector");
+        match &expr.selector {
+            Some(selector) => self.visit_identifier(selector),
+            None => self.out.push_str("null"),
+        }
+        self.out.push('}');
+    }
+
+    fn visit_type_assert_expression(&mut self, expr: &TypeAssertExpression) {
+        self.open("TypeAssertExpression", span_of_expression(expr));
+        self.expression_field("left", &expr.left);
+        self.expression_field("type", &expr.ty);
+        self.out.push('}');
+    }
+
+    fn visit_composite_literal(&mut self, lit: &CompositeLiteral) {
+        self.open("CompositeLiteral", span_of_expression(lit));
+        self.expression_field("type", &lit.ty);
+        self.expression_list_field("elements", &lit.elements);
+        self.out.push('}');
+    }
+
+    fn visit_key_value_expression(&mut self, expr: &KeyValueExpression) {
+        self.open("KeyValueExpression", span_of_expression(expr));
+        self.expression_field("key", &expr.key);
+        self.expression_field("value", &expr.value);
+        self.out.push('}');
+    }
+
+    fn visit_slice_type(&mut self, ty: &SliceType) {
+        self.open("SliceType", span_of_expression(ty));
+        self.expression_field("elem", &ty.elem);
+        self.out.push('}');
+    }
+
+    fn visit_map_type(&mut self, ty: &MapType) {
+        self.open("MapType", span_of_expression(ty));
+        self.expression_field("key", &ty.key);
+        self.expression_field("value", &ty.value);
+        self.out.push('}');
+    }
+
+    fn visit_struct_type(&mut self, ty: &StructType) {
+        self.open("StructType", span_of_expression(ty));
+        self.field_list("fields", &ty.fields);
+        self.out.push('}');
+    }
+
+    fn visit_interface_type(&mut self, ty: &InterfaceType) {
+        self.open("InterfaceType", span_of_expression(ty));
+        self.out.push('}');
+    }
+
+    fn visit_chan_type(&mut self, ty: &ChanType) {
+        self.open("ChanType", span_of_expression(ty));
+        self.expression_field("elem", &ty.elem);
+        self.out.push('}');
+    }
+
+    fn visit_go_statement(&mut self, stmt: &GoStatement) {
+        self.open("GoStatement", span_of_statement(stmt));
+        self.expression_field("call", &stmt.call);
+        self.out.push('}');
+    }
+
+    fn visit_defer_statement(&mut self, stmt: &DeferStatement) {
+        self.open("DeferStatement", span_of_statement(stmt));
+        self.expression_field("call", &stmt.call);
+        self.out.push('}');
+    }
+
+    fn visit_package_clause(&mut self, stmt: &PackageClause) {
+        self.open("PackageClause", span_of_statement(stmt));
+        self.field("name");
+        match &stmt.name {
+            Some(name) => self.visit_identifier(name),
+            None => self.out.push_str("null"),
+        }
+        self.out.push('}');
+    }
+
+    fn visit_import_statement(&mut self, stmt: &ImportStatement) {
+        self.open("ImportStatement", span_of_statement(stmt));
+        self.field("specs");
+        self.out.push('[');
+        for (i, spec) in stmt.specs.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.out.push_str("{\"kind\":\"ImportSpec\"");
+            self.field("name");
+            match &spec.name {
+                Some(name) => self.visit_identifier(name),
+                None => self.out.push_str("null"),
+            }
+            self.field("path");
+            match &spec.path {
+                Some(path) => self.visit_string_literal(path),
+                None => self.out.push_str("null"),
+            }
+            self.out.push('}');
+        }
+        self.out.push(']');
+        self.out.push('}');
+    }
+
+    fn visit_send_statement(&mut self, stmt: &SendStatement) {
+        self.open("SendStatement", span_of_statement(stmt));
+        self.expression_field("channel", &stmt.channel);
+        self.expression_field("value", &stmt.value);
+        self.out.push('}');
+    }
+
+    fn visit_select_statement(&mut self, stmt: &SelectStatement) {
+        self.open("SelectStatement", span_of_statement(stmt));
+        self.field("cases");
+        self.out.push('[');
+        for (i, case) in stmt.cases.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.out.push('{');
+            self.out.push_str("\"kind\":\"CommClause\"");
+            self.statement_field("comm", &case.comm);
+            self.statements(&case.body);
+            self.out.push('}');
+        }
+        self.out.push(']');
+        self.out.push('}');
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression) {
+        self.open("CallExpression", span_of_expression(call));
+        self.expression_field("function", &call.function);
+        self.field("arguments");
+        self.out.push('[');
+        for (i, arg) in call.arguments.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.visit_expression(arg.as_ref());
+        }
+        self.out.push(']');
+        self.field("ellipsis");
+        self.out.push_str(if call.ellipsis.is_some() { "true" } else { "false" });
+        self.out.push('}');
+    }
+}
+
+/// The AST as S-expressions, one top-level statement per line, with every
+/// operator application parenthesized: `5 + 5 * 2` is `(+ 5 (* 5 2))`.
+pub fn sexp(program: &Program) -> String {
+    let mut out = String::new();
+    for stmt in &program.statements {
+        let mut writer = SexpWriter { out: String::new() };
+        writer.visit_statement(stmt.as_ref());
+        out.push_str(&writer.out);
+        out.push('\n');
+    }
+    out
+}
+
+/// S-expression of a single expression.
+pub fn expression_sexp(expr: &dyn Expression) -> String {
+    let mut writer = SexpWriter { out: String::new() };
+    writer.visit_expression(expr);
+    writer.out
+}
+
+struct SexpWriter {
+    out: String,
+}
+
+impl SexpWriter {
+    fn list<F: FnOnce(&mut SexpWriter)>(&mut self, head: &str, items: F) {
+        self.out.push('(');
+        self.out.push_str(head);
+        items(self);
+        self.out.push(')');
+    }
+
+    /// A list of one item prints as the item alone.
+    fn items(&mut self, list: &[Box<dyn Expression>]) {
+        self.out.push(' ');
+        if list.len() == 1 {
+            self.visit_expression(list[0].as_ref());
+            return;
+        }
+        self.out.push('(');
+        for (i, expr) in list.iter().enumerate() {
+            if i > 0 {
+                self.out.push(' ');
+            }
+            self.visit_expression(expr.as_ref());
+        }
+        self.out.push(')');
+    }
+
+    fn statement_item(&mut self, stmt: &Option<Box<dyn Statement>>) {
+        self.out.push(' ');
+        match stmt {
+            Some(stmt) => self.visit_statement(stmt.as_ref()),
+            None => self.out.push_str("nil"),
+        }
+    }
+
+    fn block_item(&mut self, block: &Option<BlockStatement>) {
+        if let Some(block) = block {
+            self.out.push(' ');
+            self.visit_block_statement(block);
+        }
+    }
+
+    fn item(&mut self, expr: &Option<Box<dyn Expression>>) {
+        self.out.push(' ');
+        match expr {
+            Some(expr) => self.visit_expression(expr.as_ref()),
+            None => self.out.push_str("nil"),
+        }
+    }
+}
+
+impl Visitor for SexpWriter {
+    fn visit_let_statement(&mut self, stmt: &LetStatement) {
+        self.list("let", |w| {
+            w.out.push(' ');
+            w.out.push_str(&stmt.name.as_ref().map(|n| n.value.clone()).unwrap_or_default());
+            w.item(&stmt.value);
+        });
+    }
+
+    fn visit_return_statement(&mut self, stmt: &ReturnStatement) {
+        self.list("return", |w| {
+            if stmt.return_value.is_some() {
+                w.item(&stmt.return_value);
+            }
+        });
+    }
+
+    fn visit_expression_statement(&mut self, stmt: &ExpressionStatement) {
+        if let Some(expression) = &stmt.expression {
+            self.visit_expression(expression.as_ref());
+        }
+    }
+
+    fn visit_block_statement(&mut self, block: &BlockStatement) {
+        self.list("block", |w| {
+            for stmt in &block.statements {
+                w.out.push(' ');
+                w.visit_statement(stmt.as_ref());
+            }
+        });
+    }
+
+    fn visit_identifier(&mut self, ident: &Identifier) {
+        self.out.push_str(&ident.value);
+    }
+
+    fn visit_integer_literal(&mut self, lit: &IntegerLiteral) {
+        self.out.push_str(&lit.token.literal);
+    }
+
+    fn visit_float_literal(&mut self, lit: &FloatLiteral) {
+        self.out.push_str(&lit.token.literal);
+    }
+
+    fn visit_boolean(&mut self, lit: &Boolean) {
+        self.out.push_str(if lit.value { "true" } else { "false" });
+    }
+
+    fn visit_prefix_expression(&mut self, expr: &PrefixExpression) {
+        self.list(&expr.operator, |w| w.item(&expr.right));
+    }
+
+    fn visit_infix_expression(&mut self, expr: &InfixExpression) {
+        self.list(&expr.operator, |w| {
+            w.item(&expr.left);
+            w.item(&expr.right);
+        });
+    }
+
+    fn visit_if_expression(&mut self, expr: &IfExpression) {
+        self.list("if", |w| {
+            w.item(&expr.condition);
+            for block in [&expr.consequence, &expr.alternative].iter().copied().flatten() {
+                w.out.push(' ');
+                w.visit_block_statement(block);
+            }
+        });
+    }
+
+    fn visit_function_literal(&mut self, func: &FunctionLiteral) {
+        self.list("fn", |w| {
+            let params: Vec<String> = func.parameters.iter().map(|p| p.value.clone()).collect();
+            w.out.push_str(" (");
+            w.out.push_str(&params.join(" "));
+            w.out.push(')');
+            if !func.results.is_empty() {
+                w.out.push(' ');
+                w.list("results", |w| {
+                    for result in &func.results {
+                        for name in &result.names {
+                            w.out.push_str(&format!(" ({}", name.value));
+                            if result.ty.is_some() {
+                                w.item(&result.ty);
+                            }
+                            w.out.push(')');
+                        }
+                    }
+                });
+            }
+            if let Some(body) = &func.body {
+                w.out.push(' ');
+                w.visit_block_statement(body);
+            }
+        });
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression) {
+        self.list("call", |w| {
+            w.item(&call.function);
+            for arg in &call.arguments {
+                w.out.push(' ');
+                w.visit_expression(arg.as_ref());
+            }
+            if call.ellipsis.is_some() {
+                w.out.push_str(" ...");
+            }
+        });
+    }
+
+    fn visit_var_statement(&mut self, stmt: &VarStatement) {
+        self.list("var", |w| {
+            let names: Vec<String> = stmt.names.iter().map(|n| n.value.clone()).collect();
+            w.out.push_str(&format!(" ({})", names.join(" ")));
+            w.item(&stmt.ty);
+            for value in &stmt.values {
+                w.out.push(' ');
+                w.visit_expression(value.as_ref());
+            }
+        });
+    }
+
+    fn visit_type_statement(&mut self, stmt: &TypeStatement) {
+        self.list("type", |w| {
+            w.out.push(' ');
+            w.out.push_str(&stmt.name.as_ref().map(|n| n.value.clone()).unwrap_or_default());
+            w.item(&stmt.ty);
+        });
+    }
+
+    fn visit_assign_statement(&mut self, stmt: &AssignStatement) {
+        self.list(&stmt.operator, |w| {
+            w.items(&stmt.left);
+            if !stmt.right.is_empty() {
+                w.items(&stmt.right);
+            }
+        });
+    }
+
+    fn visit_for_statement(&mut self, stmt: &ForStatement) {
+        self.list("for", |w| {
+            w.statement_item(&stmt.init);
+            w.item(&stmt.condition);
+            w.statement_item(&stmt.post);
+            w.block_item(&stmt.body);
+        });
+    }
+
+    fn visit_range_statement(&mut self, stmt: &RangeStatement) {
+        self.list("range", |w| {
+            w.item(&stmt.key);
+            w.item(&stmt.value);
+            w.item(&stmt.x);
+            w.block_item(&stmt.body);
+        });
+    }
+
+    fn visit_branch_statement(&mut self, stmt: &BranchStatement) {
+        self.list(&stmt.token.literal, |_| {});
+    }
+
+    fn visit_string_literal(&mut self, lit: &StringLiteral) {
+        self.out.push_str(&lit.token.literal);
+    }
+
+    fn visit_index_expression(&mut self, expr: &IndexExpression) {
+        self.list("index", |w| {
+            w.item(&expr.left);
+            w.item(&expr.index);
+        });
+    }
+
+    fn visit_slice_expression(&mut self, expr: &SliceExpression) {
+        self.list("slice", |w| {
+            w.item(&expr.left);
+            w.item(&expr.low);
+            w.item(&expr.high);
+        });
+    }
+
+    fn visit_selector_expression(&mut self, expr: &SelectorExpression) {
+        self.list(".", |w| {
+            w.item(&expr.left);
+            w.out.push(' ');
+            w.out.push_str(&expr.selector.as_ref().map(|s| s.value.clone()).unwrap_or_default());
+        });
+    }
+
+    fn visit_type_assert_expression(&mut self, expr: &TypeAssertExpression) {
+        self.list("assert", |w| {
+            w.item(&expr.left);
+            w.item(&expr.ty);
+        });
+    }
+
+    fn visit_composite_literal(&mut self, lit: &CompositeLiteral) {
+        self.list("composite", |w| {
+            w.item(&lit.ty);
+            for element in &lit.elements {
+                w.out.push(' ');
+                w.visit_expression(element.as_ref());
+            }
+        });
+    }
+
+    fn visit_key_value_expression(&mut self, expr: &KeyValueExpression) {
+        self.list(":", |w| {
+            w.item(&expr.key);
+            w.item(&expr.value);
+        });
+    }
+
+    fn visit_slice_type(&mut self, ty: &SliceType) {
+        self.list("[]", |w| w.item(&ty.elem));
+    }
+
+    fn visit_map_type(&mut self, ty: &MapType) {
+        self.list("map", |w| {
+            w.item(&ty.key);
+            w.item(&ty.value);
+        });
+    }
+
+    fn visit_struct_type(&mut self, ty: &StructType) {
+        self.list("struct", |w| {
+            for field in &ty.fields {
+                for name in &field.names {
+                    w.out.push_str(&format!(" ({}", name.value));
+                    w.item(&field.ty);
+                    w.out.push(')');
+                }
+            }
+        });
+    }
+
+    fn visit_interface_type(&mut self, _ty: &InterfaceType) {
+        self.out.push_str("interface{}");
+    }
+
+    fn visit_chan_type(&mut self, ty: &ChanType) {
+        self.list("chan", |w| w.item(&ty.elem));
+    }
+
+    fn visit_go_statement(&mut self, stmt: &GoStatement) {
+        self.list("go", |w| w.item(&stmt.call));
+    }
+
+    fn visit_defer_statement(&mut self, stmt: &DeferStatement) {
+        self.list("defer", |w| w.item(&stmt.call));
+    }
+
+    fn visit_package_clause(&mut self, stmt: &PackageClause) {
+        self.list("package", |w| {
+            w.out.push(' ');
+            w.out.push_str(&stmt.name.as_ref().map(|n| n.value.clone()).unwrap_or_default());
+        });
+    }
+
+    /// A renamed package is a `(name "path")` pair.
+    fn visit_import_statement(&mut self, stmt: &ImportStatement) {
+        self.list("import", |w| {
+            for spec in &stmt.specs {
+                w.out.push(' ');
+                let path = spec.path.as_ref().map(|p| p.token.literal.clone()).unwrap_or_default();
+                match &spec.name {
+                    Some(name) => w.out.push_str(&format!("({} {})", name.value, path)),
+                    None => w.out.push_str(&path),
+                }
+            }
+        });
+    }
+
+    fn visit_send_statement(&mut self, stmt: &SendStatement) {
+        self.list("send", |w| {
+            w.item(&stmt.channel);
+            w.item(&stmt.value);
+        });
+    }
+
+    fn visit_select_statement(&mut self, stmt: &SelectStatement) {
+        self.list("select", |w| {
+            for case in &stmt.cases {
+                w.out.push(' ');
+                let head = if case.comm.is_some() { "case" } else { "default" };
+                w.list(head, |w| {
+                    if case.comm.is_some() {
+                        w.statement_item(&case.comm);
+                    }
+                    for stmt in &case.body {
+                        w.out.push(' ');
+                        w.visit_statement(stmt.as_ref());
+                    }
+                });
+            }
+        });
+    }
+}
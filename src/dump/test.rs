@@ -0,0 +1,145 @@
+use crate::dump::*;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(input: &str) -> Program {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    if !p.errors().is_empty() {
+      panic!("parser errors: {:?}", p.errors());
+    }
+    program
+  }
+
+  #[test]
+  fn test_sexp_shows_precedence() {
+    let tests = [
+      ("5 + 5 * 2", "(+ 5 (* 5 2))"),
+      ("(5 + 5) * 2", "(* (+ 5 5) 2)"),
+      ("-a * !b", "(* (- a) (! b))"),
+      ("let x = add(1, 2 * 3);", "(let x (call add 1 (* 2 3)))"),
+      ("return;", "(return)"),
+      ("if x < y { x } else { y }", "(if (< x y) (block x) (block y))"),
+      ("fn(a, b) { return a + b; }", "(fn (a b) (block (return (+ a b))))"),
+    ];
+    for (input, expected) in tests.iter() {
+      let got = sexp(&parse(input));
+      if got != [*expected, "\n"].concat() {
+        panic!("sexp({:?}) expected={:?}, got={:?}", input, expected, got);
+      }
+    }
+  }
+
+  #[test]
+  fn test_sexp_value_syntax() {
+    let tests = [
+      ("x := []int{1, 2}", "(:= x (composite ([] int) 1 2))"),
+      ("a, b = b, a", "(= (a b) (b a))"),
+      ("s[1:]", "(slice s 1 nil)"),
+      ("p.X", "(. p X)"),
+      ("m[\"k\"]", "(index m \"k\")"),
+      ("v.(int)", "(assert v int)"),
+      ("type P struct { X int }", "(type P (struct (X int)))"),
+      ("var m map[string]int", "(var (m) (map string int))"),
+      ("for k, v := range m { break }", "(range k v m (block (break)))"),
+    ];
+    for (input, expected) in tests.iter() {
+      let got = sexp(&parse(input));
+      if got != [*expected, "\n"].concat() {
+        panic!("sexp({:?}) expected={:?}, got={:?}", input, expected, got);
+      }
+    }
+  }
+
+
+  #[test]
+  fn test_sexp_concurrency_syntax() {
+    let tests = [
+      ("ch := make(chan int, 1)", "(:= ch (call make (chan int) 1))"),
+      ("go f(x)", "(go (call f x))"),
+      ("ch <- v + 1", "(send ch (+ v 1))"),
+      ("v, ok := <-ch", "(:= (v ok) (<- ch))"),
+      ("select { case v := <-ch: v; default: }", "(select (case (:= v (<- ch)) v) (default))"),
+    ];
+    for (input, expected) in tests.iter() {
+      let got = sexp(&parse(input));
+      if got != [*expected, "\n"].concat() {
+        panic!("sexp({:?}) expected={:?}, got={:?}", input, expected, got);
+      }
+    }
+  }
+
+  #[test]
+  fn test_sexp_defer_and_named_results() {
+    let tests = [
+      ("defer f(x)", "(defer (call f x))"),
+      ("append(s, t...)", "(call append s t ...)"),
+      ("fn(x) (r int, err) { r }", "(fn (x) (results (r int) (err)) (block r))"),
+    ];
+    for (input, expected) in tests.iter() {
+      let got = sexp(&parse(input));
+      if got != [*expected, "\n"].concat() {
+        panic!("sexp({:?}) expected={:?}, got={:?}", input, expected, got);
+      }
+    }
+  }
+
+  #[test]
+  fn test_sexp_imports_and_floats() {
+    let tests = [
+      ("import \"fmt\"", "(import \"fmt\")"),
+      ("package geo", "(package geo)"),
+      ("import (\n\tm \"math\"\n\t_ \"os\"\n)", "(import (m \"math\") (_ \"os\"))"),
+      ("1.5 * 2e-3", "(* 1.5 2e-3)"),
+    ];
+    for (input, expected) in tests.iter() {
+      let got = sexp(&parse(input));
+      if got != [*expected, "\n"].concat() {
+        panic!("sexp({:?}) expected={:?}, got={:?}", input, expected, got);
+      }
+    }
+  }
+
+  #[test]
+  fn test_tokens_json() {
+    let tokens = tokenize("let x = 1;");
+    let json = tokens_json(&tokens[..2]);
+    let expected = concat!(
+      "[{\"type\":\"LET\",\"literal\":\"let\",\"span\":{\"start\":{\"line\":1,\"column\":1},\"end\":{\"line\":1,\"column\":4}}},",
+      "{\"type\":\"IDENT\",\"literal\":\"x\",\"span\":{\"start\":{\"line\":1,\"column\":5},\"end\":{\"line\":1,\"column\":6}}}]\n"
+    );
+    if json != expected {
+      panic!("expected={:?}, got={:?}", expected, json);
+    }
+    if tokens.last().unwrap().r#type != crate::token::EOF {
+      panic!("token stream does not end with EOF");
+    }
+  }
+
+  #[test]
+  fn test_ast_json_spans_cover_children() {
+    let program = parse("let y = f(1,\n  x)");
+    let json = ast_json(&program);
+    let call = concat!(
+      "{\"kind\":\"CallExpression\",\"span\":{\"start\":{\"line\":1,\"column\":9},\"end\":{\"line\":2,\"column\":5}},",
+      "\"function\":{\"kind\":\"Identifier\",\"span\":{\"start\":{\"line\":1,\"column\":9},\"end\":{\"line\":1,\"column\":10}},\"value\":\"f\"},"
+    );
+    if !json.starts_with("{\"kind\":\"Program\",\"statements\":[{\"kind\":\"LetStatement\",\"span\":{\"start\":{\"line\":1,\"column\":1},\"end\":{\"line\":2,\"column\":5}}") {
+      panic!("unexpected program json: {}", json);
+    }
+    if !json.contains(call) {
+      panic!("call expression json missing from: {}", json);
+    }
+  }
+
+  #[test]
+  fn test_json_string_escapes() {
+    if json_string("a\"b\\c\n\u{1}") != "\"a\\\"b\\\\c\\n\\u0001\"" {
+      panic!("got {}", json_string("a\"b\\c\n\u{1}"));
+    }
+  }
+}
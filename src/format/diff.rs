@@ -0,0 +1,127 @@
+/// Number of unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// Line-based unified diff between `a` and `b`, empty when they are equal.
+pub fn unified(a_name: &str, b_name: &str, a: &str, b: &str) -> String {
+    if a == b {
+        return "".to_string();
+    }
+    let a: Vec<&str> = a.lines().collect();
+    let b: Vec<&str> = b.lines().collect();
+    let edits = edit_script(&a, &b);
+
+    let mut out = format!("--- {}\n+++ {}\n", a_name, b_name);
+
+    // Walk the script, emitting a hunk for each group of changes that are
+    // less than 2 * CONTEXT lines apart.
+    let mut i = 0;
+    while i < edits.len() {
+        if edits[i] == Edit::Keep {
+            i += 1;
+            continue;
+        }
+        let start = i.saturating_sub(CONTEXT);
+        let mut end = i;
+        let mut keeps = 0;
+        while end < edits.len() && keeps <= 2 * CONTEXT {
+            if edits[end] == Edit::Keep {
+                keeps += 1;
+            } else {
+                keeps = 0;
+            }
+            end += 1;
+        }
+        // Trim the context following the last change to CONTEXT lines.
+        let end = end - keeps.saturating_sub(CONTEXT);
+
+        let (a_start, b_start) = positions(&edits[..start]);
+        let (a_len, b_len) = positions(&edits[start..end]);
+        out.push_str(&format!("@@ -{} +{} @@\n", range(a_start, a_len), range(b_start, b_len)));
+
+        let (mut ai, mut bi) = (a_start, b_start);
+        for edit in &edits[start..end] {
+            match edit {
+                Edit::Keep => {
+                    out.push_str(&format!(" {}\n", a[ai]));
+                    ai += 1;
+                    bi += 1;
+                }
+                Edit::Delete => {
+                    out.push_str(&format!("-{}\n", a[ai]));
+                    ai += 1;
+                }
+                Edit::Insert => {
+                    out.push_str(&format!("+{}\n", b[bi]));
+                    bi += 1;
+                }
+            }
+        }
+        i = end;
+    }
+    out
+}
+
+/// Lines of `a` and `b` consumed by `edits`.
+fn positions(edits: &[Edit]) -> (usize, usize) {
+    let mut a = 0;
+    let mut b = 0;
+    for edit in edits {
+        match edit {
+            Edit::Keep => {
+                a += 1;
+                b += 1;
+            }
+            Edit::Delete => a += 1,
+            Edit::Insert => b += 1,
+        }
+    }
+    (a, b)
+}
+
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Shortest edit script from the longest common subsequence table.
+fn edit_script(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            edits.push(Edit::Keep);
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            edits.push(Edit::Delete);
+            i += 1;
+        } else {
+            edits.push(Edit::Insert);
+            j += 1;
+        }
+    }
+    edits.extend(std::iter::repeat_n(Edit::Delete, a.len() - i));
+    edits.extend(std::iter::repeat_n(Edit::Insert, b.len() - j));
+    edits
+}
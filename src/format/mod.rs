@@ -0,0 +1,1158 @@
+use crate::ast::visit::*;
+use crate::ast::*;
+use crate::lexer::*;
+use crate::parser::*;
+use crate::token::*;
+use std::collections::{HashMap, HashSet};
+
+pub mod diff;
+
+#[cfg(test)]
+mod test;
+
+/// Canonical source printer, following gofmt's layout rules: one statement
+/// per line, tab indentation, no semicolons, binary operators spaced by
+/// precedence depth and trailing comments aligned in columns.
+pub struct Printer {
+    out: Vec<Line>,
+    indent: usize,
+    /// source lines, used to keep a blank line where the author left one
+    source: Vec<String>,
+    /// comments not printed yet, in source order
+    comments: Vec<Token>,
+    /// lines that hold a comment preceded by code
+    trailing_lines: HashMap<usize, bool>,
+    /// for a comment that follows code on its line, the column of the last
+    /// token before it
+    code_before: HashMap<(usize, usize), usize>,
+    /// comments with code after them on their line, printed with that code
+    inline: HashSet<(usize, usize)>,
+    /// last source line printed on the current output line, and the column
+    /// of the last token printed from it
+    last_line: usize,
+    last_column: usize,
+    /// closing lines of the blocks being printed, innermost last
+    open_blocks: Vec<usize>,
+    /// precedence and depth of the operand being printed
+    prec1: u8,
+    depth: usize,
+}
+
+/// One output line; the trailing comment is kept apart so it can be aligned.
+struct Line {
+    indent: usize,
+    code: String,
+    comment: Option<String>,
+}
+
+/// Formats `input`, returning the parser errors if it does not parse.
+pub fn format_source(input: &str) -> Result<String, Vec<String>> {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    if !p.errors().is_empty() {
+        return Err(p.errors());
+    }
+
+    let mut printer = Printer::with_source(input);
+    printer.program(&program);
+    Ok(printer.finish())
+}
+
+/// Formats an AST that was not read from source; comments and blank lines
+/// are unknown and therefore not printed.
+pub fn format_program(program: &Program) -> String {
+    let mut printer = Printer::new();
+    printer.program(program);
+    printer.finish()
+}
+
+/// One expression in gofmt style, as error messages quote it.
+pub fn format_expression(expr: &dyn Expression) -> String {
+    let mut printer = Printer::new();
+    printer.visit_expression(expr);
+    printer.finish().trim_end().to_string()
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Printer::new()
+    }
+}
+
+impl Printer {
+    pub fn new() -> Printer {
+        Printer {
+            out: vec![Line { indent: 0, code: "".to_string(), comment: None }],
+            indent: 0,
+            source: vec![],
+            comments: vec![],
+            trailing_lines: HashMap::new(),
+            code_before: HashMap::new(),
+            inline: HashSet::new(),
+            last_line: 0,
+            last_column: 0,
+            open_blocks: vec![],
+            prec1: LOWEST,
+            depth: 1,
+        }
+    }
+
+    pub fn with_source(input: &str) -> Printer {
+        let mut printer = Printer::new();
+        printer.source = input.lines().map(|l| l.to_string()).collect();
+
+        // Lex the source once more to find the comments, which are not
+        // kept in the AST, and the lines where they follow code.
+        let mut l = Lexer::new(input.to_string());
+        let mut code_lines: HashMap<usize, Vec<usize>> = HashMap::new();
+        loop {
+            let tok = l.next_token();
+            if tok.r#type == EOF {
+                break;
+            }
+            if tok.r#type != SEMICOLON || tok.literal != "\n" {
+                code_lines.entry(tok.line).or_default().push(tok.column);
+            }
+        }
+        for comment in &l.comments {
+            let columns = code_lines.get(&comment.line).map(|c| c.as_slice()).unwrap_or(&[]);
+            let position = (comment.line, comment.column);
+            if let Some(before) = columns.iter().filter(|c| **c < comment.column).max() {
+                printer.trailing_lines.insert(comment.line, true);
+                printer.code_before.insert(position, *before);
+            }
+            if columns.iter().any(|c| *c > comment.column) && !comment.literal.contains('\n') {
+                printer.inline.insert(position);
+            }
+        }
+        printer.comments = l.comments;
+        printer
+    }
+
+    /// Joins the output lines, aligning runs of trailing comments.
+    pub fn finish(mut self) -> String {
+        self.flush_comments(usize::MAX);
+
+        let mut widths = vec![0; self.out.len()];
+        let mut start = 0;
+        while start < self.out.len() {
+            let mut end = start;
+            while end < self.out.len()
+                && self.out[end].comment.is_some()
+                && self.out[end].indent == self.out[start].indent
+            {
+                end += 1;
+            }
+            if end == start {
+                start += 1;
+                continue;
+            }
+            let width = self.out[start..end].iter().map(|l| l.code.chars().count()).max().unwrap_or(0);
+            for w in widths.iter_mut().take(end).skip(start) {
+                *w = width;
+            }
+            start = end;
+        }
+
+        let mut text = String::new();
+        for (line, width) in self.out.iter().zip(widths) {
+            if line.code.is_empty() && line.comment.is_none() {
+                text.push('\n');
+                continue;
+            }
+            text.push_str(&"\t".repeat(line.indent));
+            text.push_str(&line.code);
+            if let Some(comment) = &line.comment {
+                if !line.code.is_empty() {
+                    let pad = width - line.code.chars().count() + 1;
+                    text.push_str(&" ".repeat(pad));
+                }
+                text.push_str(comment);
+            }
+            text.push('\n');
+        }
+        // The last (empty) line only terminates the previous one.
+        text.pop();
+        while text.ends_with("\n\n") {
+            text.pop();
+        }
+        text
+    }
+
+    fn write(&mut self, s: &str) {
+        let line = self.out.last_mut().unwrap();
+        if line.code.is_empty() {
+            line.indent = self.indent;
+        }
+        line.code.push_str(s);
+    }
+
+    /// Writes a token's text and remembers where in the source it was. The
+    /// comments that come before it on its line are written first.
+    fn write_token(&mut self, tok: &Token, s: &str) {
+        if tok.line > 0 {
+            while let Some(comment) = self.comments.first() {
+                let position = (comment.line, comment.column);
+                if position >= (tok.line, tok.column) || !self.inline.contains(&position) {
+                    break;
+                }
+                let comment = self.comments.remove(0);
+                let code = &self.out.last().unwrap().code;
+                if !code.is_empty() && !code.ends_with(' ') && !code.ends_with('(') {
+                    self.write(" ");
+                }
+                self.write(&comment.literal);
+                if !s.starts_with(')') {
+                    self.write(" ");
+                }
+            }
+            self.mark(tok);
+        }
+        self.write(s);
+    }
+
+    /// Remembers that the source up to `tok` has been printed.
+    fn mark(&mut self, tok: &Token) {
+        if tok.line > self.last_line {
+            self.last_line = tok.line;
+            self.last_column = tok.column;
+        } else if tok.line == self.last_line {
+            self.last_column = self.last_column.max(tok.column);
+        }
+    }
+
+    /// Remembers that the source up to the end of `line` has been printed.
+    fn printed_through(&mut self, line: usize) {
+        self.last_line = line;
+        self.last_column = usize::MAX;
+    }
+
+    fn newline(&mut self) {
+        // Comments that trail code on the lines just printed go with it.
+        // Those on the line of a pending `}`, or after code of their line
+        // that is still to be printed, wait for it.
+        let limit = self.open_blocks.last().cloned().unwrap_or(usize::MAX);
+        let mut trailing = vec![];
+        while let Some(comment) = self.comments.first() {
+            let is_trailing = self.trailing_lines.contains_key(&comment.line);
+            let pending = comment.line == self.last_line
+                && self.code_before.get(&(comment.line, comment.column)).is_some_and(|c| *c > self.last_column);
+            if comment.line > self.last_line || comment.line >= limit || !is_trailing || pending {
+                break;
+            }
+            trailing.push(self.comments.remove(0).literal);
+        }
+        let line = self.out.last_mut().unwrap();
+        if !trailing.is_empty() {
+            line.comment = Some(trailing.join(" "));
+        }
+        self.out.push(Line { indent: self.indent, code: "".to_string(), comment: None });
+    }
+
+    /// Separates what follows with one empty line, unless it would open a
+    /// block or the program, or double an empty line.
+    fn blank_line(&mut self) {
+        let n = self.out.len();
+        if n < 2 || self.out[n - 2].code.ends_with('{') {
+            return;
+        }
+        if self.out[n - 2].code.is_empty() && self.out[n - 2].comment.is_none() {
+            return;
+        }
+        self.newline();
+    }
+
+    fn is_blank_source_line(&self, line: usize) -> bool {
+        line >= 1 && line <= self.source.len() && self.source[line - 1].trim().is_empty()
+    }
+
+    /// Prints the comments on their own lines that come before `line`.
+    fn flush_comments(&mut self, line: usize) {
+        while let Some(comment) = self.comments.first() {
+            if comment.line >= line {
+                break;
+            }
+            let comment = self.comments.remove(0);
+            if self.trailing_lines.contains_key(&comment.line) && comment.line <= self.last_line {
+                // A trailing comment whose line was printed already.
+                let n = self.out.len();
+                let line = &mut self.out[n.saturating_sub(2)];
+                line.comment = match line.comment.take() {
+                    Some(previous) => Some([previous, comment.literal].join(" ")),
+                    None => Some(comment.literal),
+                };
+                continue;
+            }
+            if self.is_blank_source_line(comment.line - 1) {
+                self.blank_line();
+            }
+            self.write(&comment.literal);
+            self.printed_through(comment.line);
+            self.newline();
+        }
+    }
+
+    pub fn program(&mut self, program: &Program) {
+        self.statement_list(&program.statements);
+    }
+
+    fn statement_list(&mut self, statements: &[Box<dyn Statement>]) {
+        for (i, stmt) in statements.iter().enumerate() {
+            let line = statement_line(stmt.as_ref());
+            // The blank line after the package clause goes before any comments, so
+            // a doc comment stays attached to the declaration it documents.
+            let after_package = i > 0 && statements[i - 1].as_any().is::<PackageClause>();
+            if after_package {
+                self.blank_line();
+            }
+            self.flush_comments(line);
+            if !after_package && i > 0 && line > 0 && self.is_blank_source_line(line - 1) {
+                self.blank_line();
+            }
+            self.statement(stmt.as_ref());
+            self.newline();
+        }
+    }
+
+    pub fn statement(&mut self, stmt: &dyn Statement) {
+        self.visit_statement(stmt);
+    }
+
+    fn block(&mut self, block: &BlockStatement) {
+        self.write_token(&block.token, "{");
+        let closing = block.rbrace.as_ref().map(|t| t.line);
+        if block.statements.is_empty() && !self.has_comments_before(closing) {
+            self.write("}");
+            return;
+        }
+        self.open_blocks.push(closing.unwrap_or(usize::MAX));
+        self.indent += 1;
+        self.newline();
+        self.statement_list(&block.statements);
+        if let Some(line) = closing {
+            self.flush_comments(line);
+        }
+        self.indent -= 1;
+        self.open_blocks.pop();
+        self.write("}");
+        if let Some(line) = closing {
+            self.printed_through(line);
+        }
+    }
+
+    fn has_comments_before(&self, line: Option<usize>) -> bool {
+        match (line, self.comments.first()) {
+            (Some(line), Some(comment)) => comment.line < line,
+            _ => false,
+        }
+    }
+
+    fn expr0(&mut self, e: &dyn Expression, depth: usize) {
+        self.expr1(e, LOWEST, depth);
+    }
+
+    /// Prints `e` as an operand of an operator with precedence `prec1`,
+    /// `depth` levels of binary expressions and argument lists down.
+    fn expr1(&mut self, e: &dyn Expression, prec1: u8, depth: usize) {
+        self.prec1 = prec1;
+        self.depth = depth;
+        self.visit_expression(e);
+    }
+
+    /// Branches always go on their own lines, even when empty.
+    fn if_block(&mut self, block: &BlockStatement) {
+        if block.statements.is_empty() {
+            self.write_token(&block.token, "{");
+            let closing = block.rbrace.as_ref().map(|t| t.line);
+            self.open_blocks.push(closing.unwrap_or(usize::MAX));
+            self.indent += 1;
+            self.newline();
+            if let Some(line) = closing {
+                self.flush_comments(line);
+                self.printed_through(line);
+            }
+            self.indent -= 1;
+            self.open_blocks.pop();
+            self.write("}");
+        } else {
+            self.block(block);
+        }
+    }
+
+    fn binary_expr(&mut self, x: &InfixExpression, prec1: u8, cutoff: u8, depth: usize) {
+        let prec = precedence_of(x.token.r#type);
+        if prec < prec1 {
+            self.write("(");
+            self.expr0_infix(x, reduce_depth(depth));
+            self.write(")");
+            return;
+        }
+
+        let print_blank = go_precedence(&x.operator) < cutoff;
+
+        if let Some(left) = &x.left {
+            self.expr1(left.as_ref(), prec, depth + diff_prec(left.as_ref(), prec));
+        }
+        if print_blank {
+            self.write(" ");
+        }
+        self.write_token(&x.token, &x.operator);
+        // a line break after the operator stays, indenting the rest
+        let right_line = x.right.as_ref().and_then(|r| span_start(r.as_ref())).unwrap_or(0);
+        if x.token.line > 0 && right_line > x.token.line {
+            self.indent += 1;
+            self.newline();
+            self.flush_comments(right_line);
+        } else if print_blank {
+            self.write(" ");
+        }
+        if let Some(right) = &x.right {
+            self.expr1(right.as_ref(), prec + 1, depth + 1);
+        }
+        if x.token.line > 0 && right_line > x.token.line {
+            self.indent -= 1;
+        }
+    }
+
+    fn expr0_infix(&mut self, x: &InfixExpression, depth: usize) {
+        self.binary_expr(x, LOWEST, cutoff(x, depth), depth);
+    }
+
+    fn expr_list(&mut self, list: &[Box<dyn Expression>], depth: usize) {
+        for (i, e) in list.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.expr0(e.as_ref(), depth);
+        }
+    }
+
+    fn optional_expr(&mut self, e: &Option<Box<dyn Expression>>, depth: usize) {
+        if let Some(e) = e {
+            self.expr0(e.as_ref(), depth);
+        }
+    }
+
+    /// Prints a statement of a `for` header without its line break.
+    fn header_statement(&mut self, stmt: &Option<Box<dyn Statement>>) {
+        if let Some(stmt) = stmt {
+            self.visit_statement(stmt.as_ref());
+        }
+    }
+
+    fn import_spec(&mut self, spec: &ImportSpec) {
+        if let Some(name) = &spec.name {
+            self.visit_identifier(name);
+            self.write(" ");
+        }
+        if let Some(path) = &spec.path {
+            self.write_token(&path.token, &path.token.literal);
+        }
+    }
+
+    /// Prints a group of import specs sorted by path, each with the
+    /// comment that trails it in the source.
+    fn import_group(&mut self, specs: &[ImportSpec], after_group: bool) {
+        let line = import_line(&specs[0]);
+        self.flush_comments(line);
+        if after_group && line > 0 && self.is_blank_source_line(line - 1) {
+            self.blank_line();
+        }
+        let mut sorted: Vec<(&ImportSpec, Vec<String>)> = specs
+            .iter()
+            .map(|spec| {
+                let line = import_line(spec);
+                let mut trailing = vec![];
+                if self.trailing_lines.contains_key(&line) {
+                    while let Some(i) = self.comments.iter().position(|c| c.line == line) {
+                        trailing.push(self.comments.remove(i).literal);
+                    }
+                }
+                (spec, trailing)
+            })
+            .collect();
+        sorted.sort_by(|(a, _), (b, _)| import_key(a).cmp(&import_key(b)));
+        for (spec, trailing) in sorted {
+            self.import_spec(spec);
+            if !trailing.is_empty() {
+                self.out.last_mut().unwrap().comment = Some(trailing.join(" "));
+            }
+            self.newline();
+        }
+    }
+
+    /// Reports whether a blank line or a comment of its own comes between
+    /// the import specs on lines `prev` and `line`.
+    fn separates_imports(&self, prev: usize, line: usize) -> bool {
+        (prev + 1..line).any(|l| self.is_blank_source_line(l))
+            || self.comments.iter().any(|c| {
+                c.line > prev && (c.line < line || c.line == line && !self.trailing_lines.contains_key(&line))
+            })
+    }
+
+    fn optional_block(&mut self, block: &Option<BlockStatement>) {
+        if let Some(block) = block {
+            self.if_block(block);
+        }
+    }
+}
+
+impl Visitor for Printer {
+    fn visit_let_statement(&mut self, stmt: &LetStatement) {
+        self.write_token(&stmt.token, "let ");
+        if let Some(name) = &stmt.name {
+            self.write(&name.value);
+        }
+        self.write(" = ");
+        if let Some(value) = &stmt.value {
+            self.expr0(value.as_ref(), 1);
+        }
+    }
+
+    fn visit_return_statement(&mut self, stmt: &ReturnStatement) {
+        self.write_token(&stmt.token, "return");
+        if let Some(value) = &stmt.return_value {
+            self.write(" ");
+            self.expr0(value.as_ref(), 1);
+        }
+    }
+
+    fn visit_expression_statement(&mut self, stmt: &ExpressionStatement) {
+        if let Some(expression) = &stmt.expression {
+            self.expr0(expression.as_ref(), 1);
+        }
+    }
+
+    fn visit_block_statement(&mut self, block: &BlockStatement) {
+        self.block(block);
+    }
+
+    fn visit_identifier(&mut self, ident: &Identifier) {
+        match &ident.token {
+            Some(tok) => self.write_token(tok, &ident.value),
+            None => self.write(&ident.value),
+        }
+    }
+
+    fn visit_integer_literal(&mut self, lit: &IntegerLiteral) {
+        self.write_token(&lit.token, &lit.token.literal);
+    }
+
+    fn visit_float_literal(&mut self, lit: &FloatLiteral) {
+        self.write_token(&lit.token, &lit.token.literal);
+    }
+
+    fn visit_boolean(&mut self, lit: &Boolean) {
+        self.write_token(&lit.token, &lit.token.literal);
+    }
+
+    fn visit_prefix_expression(&mut self, expr: &PrefixExpression) {
+        let depth = self.depth;
+        if PREFIX < self.prec1 {
+            self.write("(");
+            self.expr0(expr, reduce_depth(depth));
+            self.write(")");
+            return;
+        }
+        self.write_token(&expr.token, &expr.operator);
+        if let Some(right) = &expr.right {
+            // Keep `- -x` from reading as a decrement.
+            let glued = right.as_any().downcast_ref::<PrefixExpression>()
+                .map(|r| r.operator == expr.operator && expr.operator == "-")
+                .unwrap_or(false);
+            if glued {
+                self.write("(");
+                self.expr0(right.as_ref(), depth);
+                self.write(")");
+            } else {
+                self.expr1(right.as_ref(), PREFIX, depth);
+            }
+        }
+    }
+
+    fn visit_infix_expression(&mut self, expr: &InfixExpression) {
+        let (prec1, depth) = (self.prec1, self.depth);
+        self.binary_expr(expr, prec1, cutoff(expr, depth), depth);
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression) {
+        let depth = self.depth;
+        if let Some(function) = &call.function {
+            self.expr1(function.as_ref(), CALL, depth);
+        }
+        let depth = if call.arguments.len() > 1 { depth + 1 } else { depth };
+        self.write_token(&call.token, "(");
+        // arguments keep the line breaks of the source, indented one more,
+        // and a `)` of its own line stays on one after a trailing comma
+        let closing = call.rparen.as_ref().map(|t| t.line).unwrap_or(0);
+        let mut end = call.token.line;
+        let mut broken = false;
+        for (i, arg) in call.arguments.iter().enumerate() {
+            let start = span_start(arg.as_ref()).unwrap_or(0);
+            if call.token.line > 0 && start > end {
+                if i > 0 {
+                    self.write(",");
+                }
+                if !broken {
+                    broken = true;
+                    self.open_blocks.push(closing);
+                    self.indent += 1;
+                }
+                self.printed_through(end);
+                self.newline();
+            } else if i > 0 {
+                self.write(", ");
+            }
+            self.expr0(arg.as_ref(), depth);
+            end = span_end(arg.as_ref()).unwrap_or(end);
+        }
+        if call.ellipsis.is_some() {
+            self.write("...");
+        }
+        if broken {
+            if closing > end {
+                self.write(",");
+                self.printed_through(end);
+                self.newline();
+                self.flush_comments(closing);
+            }
+            self.indent -= 1;
+            self.open_blocks.pop();
+        }
+        match &call.rparen {
+            Some(rparen) => self.write_token(rparen, ")"),
+            None => self.write(")"),
+        }
+    }
+
+    fn visit_function_literal(&mut self, func: &FunctionLiteral) {
+        self.write_token(&func.token, "fn(");
+        for (i, param) in func.parameters.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.visit_identifier(param);
+        }
+        self.write(") ");
+        if !func.results.is_empty() {
+            self.write("(");
+            for (i, result) in func.results.iter().enumerate() {
+                if i > 0 {
+                    self.write(", ");
+                }
+                let names: Vec<String> = result.names.iter().map(|n| n.value.clone()).collect();
+                self.write(&names.join(", "));
+                if let Some(ty) = &result.ty {
+                    self.write(" ");
+                    self.expr0(ty.as_ref(), 1);
+                }
+            }
+            self.write(") ");
+        }
+        if let Some(body) = &func.body {
+            self.block(body);
+        }
+    }
+
+    fn visit_var_statement(&mut self, stmt: &VarStatement) {
+        self.write_token(&stmt.token, "var ");
+        let names: Vec<String> = stmt.names.iter().map(|n| n.value.clone()).collect();
+        self.write(&names.join(", "));
+        if let Some(ty) = &stmt.ty {
+            self.write(" ");
+            self.expr0(ty.as_ref(), 1);
+        }
+        if !stmt.values.is_empty() {
+            self.write(" = ");
+            let depth = if stmt.values.len() > 1 { 2 } else { 1 };
+            self.expr_list(&stmt.values, depth);
+        }
+    }
+
+    fn visit_type_statement(&mut self, stmt: &TypeStatement) {
+        self.write_token(&stmt.token, "type ");
+        if let Some(name) = &stmt.name {
+            self.write(&name.value);
+        }
+        self.write(" ");
+        self.optional_expr(&stmt.ty, 1);
+    }
+
+    fn visit_assign_statement(&mut self, stmt: &AssignStatement) {
+        let depth = if stmt.left.len() > 1 || stmt.right.len() > 1 { 2 } else { 1 };
+        self.expr_list(&stmt.left, depth);
+        if stmt.right.is_empty() {
+            self.write_token(&stmt.token, &stmt.operator);
+            return;
+        }
+        self.write(" ");
+        self.write_token(&stmt.token, &stmt.operator);
+        self.write(" ");
+        self.expr_list(&stmt.right, depth);
+    }
+
+    fn visit_for_statement(&mut self, stmt: &ForStatement) {
+        self.write_token(&stmt.token, "for ");
+        if stmt.init.is_some() || stmt.post.is_some() {
+            self.header_statement(&stmt.init);
+            self.write("; ");
+            self.optional_expr(&stmt.condition, 1);
+            self.write("; ");
+            self.header_statement(&stmt.post);
+            if stmt.post.is_some() {
+                self.write(" ");
+            }
+        } else if let Some(condition) = &stmt.condition {
+            self.expr0(condition.as_ref(), 1);
+            self.write(" ");
+        }
+        self.optional_block(&stmt.body);
+    }
+
+    fn visit_range_statement(&mut self, stmt: &RangeStatement) {
+        self.write_token(&stmt.token, "for ");
+        if let Some(key) = &stmt.key {
+            self.expr0(key.as_ref(), 1);
+            if let Some(value) = &stmt.value {
+                self.write(", ");
+                self.expr0(value.as_ref(), 1);
+            }
+            self.write(" ");
+            self.write(&stmt.operator);
+            self.write(" ");
+        }
+        self.write("range ");
+        self.optional_expr(&stmt.x, 1);
+        self.write(" ");
+        self.optional_block(&stmt.body);
+    }
+
+    fn visit_branch_statement(&mut self, stmt: &BranchStatement) {
+        self.write_token(&stmt.token, &stmt.token.literal);
+    }
+
+    fn visit_string_literal(&mut self, lit: &StringLiteral) {
+        self.write_token(&lit.token, &lit.token.literal);
+    }
+
+    fn visit_index_expression(&mut self, expr: &IndexExpression) {
+        let depth = self.depth;
+        if let Some(left) = &expr.left {
+            self.expr1(left.as_ref(), CALL, depth);
+        }
+        self.write_token(&expr.token, "[");
+        self.optional_expr(&expr.index, depth + 1);
+        self.write("]");
+    }
+
+    fn visit_slice_expression(&mut self, expr: &SliceExpression) {
+        let depth = self.depth;
+        if let Some(left) = &expr.left {
+            self.expr1(left.as_ref(), CALL, depth);
+        }
+        self.write_token(&expr.token, "[");
+        // gofmt puts blanks around the colon when a bound is a binary expression
+        let is_binary = |e: &Option<Box<dyn Expression>>| e.as_ref().map(|e| e.as_any().is::<InfixExpression>()).unwrap_or(false);
+        let spaced = is_binary(&expr.low) || is_binary(&expr.high);
+        self.optional_expr(&expr.low, depth + 1);
+        match (spaced, expr.low.is_some(), expr.high.is_some()) {
+            (false, _, _) => self.write(":"),
+            (true, true, true) => self.write(" : "),
+            (true, true, false) => self.write(" :"),
+            (true, false, _) => self.write(": "),
+        }
+        self.optional_expr(&expr.high, depth + 1);
+        self.write("]");
+    }
+
+    fn visit_selector_expression(&mut self, expr: &SelectorExpression) {
+        let depth = self.depth;
+        if let Some(left) = &expr.left {
+            self.expr1(left.as_ref(), CALL, depth);
+        }
+        self.write_token(&expr.token, ".");
+        if let Some(selector) = &expr.selector {
+            self.visit_identifier(selector);
+        }
+    }
+
+    fn visit_type_assert_expression(&mut self, expr: &TypeAssertExpression) {
+        let depth = self.depth;
+        if let Some(left) = &expr.left {
+            self.expr1(left.as_ref(), CALL, depth);
+        }
+        self.write_token(&expr.token, ".(");
+        self.optional_expr(&expr.ty, 1);
+        self.write(")");
+    }
+
+    fn visit_composite_literal(&mut self, lit: &CompositeLiteral) {
+        let depth = self.depth;
+        self.optional_expr(&lit.ty, depth);
+        self.write_token(&lit.token, "{");
+        // one element per line when the source breaks the line after `{`
+        let closing = lit.rbrace.as_ref().map(|t| t.line).unwrap_or(0);
+        let first = lit.elements.first().and_then(|e| span_start(e.as_ref())).unwrap_or(closing);
+        if lit.elements.is_empty() || first == lit.token.line || lit.token.line == 0 {
+            self.expr_list(&lit.elements, depth + 1);
+            self.write("}");
+            return;
+        }
+        self.open_blocks.push(closing);
+        self.indent += 1;
+        self.newline();
+        for (i, element) in lit.elements.iter().enumerate() {
+            self.expr0(element.as_ref(), 1);
+            self.write(",");
+            // the comma is not in the AST; the rest of the element's last
+            // line, with its comment, is printed once the next element
+            // starts on a line of its own
+            let next = lit.elements.get(i + 1).and_then(|e| span_start(e.as_ref())).unwrap_or(closing);
+            if let Some(end) = span_end(element.as_ref()).filter(|end| *end < next) {
+                self.printed_through(end);
+            }
+            self.newline();
+        }
+        self.flush_comments(closing);
+        self.indent -= 1;
+        self.open_blocks.pop();
+        self.write("}");
+        self.printed_through(closing);
+    }
+
+    fn visit_key_value_expression(&mut self, expr: &KeyValueExpression) {
+        let depth = self.depth;
+        self.optional_expr(&expr.key, depth);
+        self.write_token(&expr.token, ": ");
+        self.optional_expr(&expr.value, depth);
+    }
+
+    fn visit_slice_type(&mut self, ty: &SliceType) {
+        self.write_token(&ty.token, "[]");
+        self.optional_expr(&ty.elem, 1);
+    }
+
+    fn visit_map_type(&mut self, ty: &MapType) {
+        self.write_token(&ty.token, "map[");
+        self.optional_expr(&ty.key, 1);
+        self.write("]");
+        self.optional_expr(&ty.value, 1);
+    }
+
+    fn visit_struct_type(&mut self, ty: &StructType) {
+        self.write_token(&ty.token, "struct");
+        if ty.fields.is_empty() {
+            self.write("{}");
+            return;
+        }
+        self.write(" {");
+        let closing = ty.rbrace.as_ref().map(|t| t.line).unwrap_or(usize::MAX);
+        self.open_blocks.push(closing);
+        self.indent += 1;
+        self.newline();
+        // field types line up in a column, as gofmt's tabwriter does
+        let names: Vec<String> = ty
+            .fields
+            .iter()
+            .map(|f| f.names.iter().map(|n| n.value.clone()).collect::<Vec<_>>().join(", "))
+            .collect();
+        let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+        for (field, names) in ty.fields.iter().zip(names) {
+            if let Some(tok) = field.names.first().and_then(|n| n.token.as_ref()) {
+                self.flush_comments(tok.line);
+                self.mark(tok);
+            }
+            self.write(&format!("{:<width$} ", names, width = width));
+            self.optional_expr(&field.ty, 1);
+            self.newline();
+        }
+        if let Some(rbrace) = &ty.rbrace {
+            self.flush_comments(rbrace.line);
+        }
+        self.indent -= 1;
+        self.open_blocks.pop();
+        self.write("}");
+        if let Some(rbrace) = &ty.rbrace {
+            self.printed_through(rbrace.line);
+        }
+    }
+
+    fn visit_interface_type(&mut self, ty: &InterfaceType) {
+        self.write_token(&ty.token, "interface{}");
+    }
+
+    fn visit_chan_type(&mut self, ty: &ChanType) {
+        self.write_token(&ty.token, "chan ");
+        self.optional_expr(&ty.elem, 1);
+    }
+
+    fn visit_go_statement(&mut self, stmt: &GoStatement) {
+        self.write_token(&stmt.token, "go ");
+        self.optional_expr(&stmt.call, 1);
+    }
+
+    fn visit_defer_statement(&mut self, stmt: &DeferStatement) {
+        self.write_token(&stmt.token, "defer ");
+        self.optional_expr(&stmt.call, 1);
+    }
+
+    fn visit_package_clause(&mut self, stmt: &PackageClause) {
+        self.write_token(&stmt.token, "package ");
+        if let Some(name) = &stmt.name {
+            self.write(&name.value);
+        }
+    }
+
+    /// The parenthesized form keeps one package per line, sorted by path
+    /// within each group of specs that a blank line or comment separates.
+    fn visit_import_statement(&mut self, stmt: &ImportStatement) {
+        self.write_token(&stmt.token, "import ");
+        let rparen = match &stmt.rparen {
+            Some(rparen) => rparen,
+            None => {
+                if let Some(spec) = stmt.specs.first() {
+                    self.import_spec(spec);
+                }
+                return;
+            }
+        };
+        self.write("(");
+        self.open_blocks.push(rparen.line);
+        self.indent += 1;
+        self.newline();
+        let mut start = 0;
+        while start < stmt.specs.len() {
+            let mut end = start + 1;
+            while end < stmt.specs.len()
+                && !self.separates_imports(import_line(&stmt.specs[end - 1]), import_line(&stmt.specs[end]))
+            {
+                end += 1;
+            }
+            self.import_group(&stmt.specs[start..end], start > 0);
+            start = end;
+        }
+        self.flush_comments(rparen.line);
+        self.indent -= 1;
+        self.open_blocks.pop();
+        self.write(")");
+        self.printed_through(rparen.line);
+    }
+
+    fn visit_send_statement(&mut self, stmt: &SendStatement) {
+        self.optional_expr(&stmt.channel, 1);
+        self.write(" ");
+        self.write_token(&stmt.token, "<-");
+        self.write(" ");
+        self.optional_expr(&stmt.value, 1);
+    }
+
+    /// Cases line up with `select`; their statements are indented.
+    fn visit_select_statement(&mut self, stmt: &SelectStatement) {
+        self.write_token(&stmt.token, "select {");
+        let closing = stmt.rbrace.as_ref().map(|t| t.line);
+        if stmt.cases.is_empty() && !self.has_comments_before(closing) {
+            self.write("}");
+            return;
+        }
+        self.open_blocks.push(closing.unwrap_or(usize::MAX));
+        self.newline();
+        for case in &stmt.cases {
+            self.flush_comments(case.token.line);
+            match &case.comm {
+                Some(_) => self.write_token(&case.token, "case "),
+                None => self.write_token(&case.token, "default"),
+            }
+            self.header_statement(&case.comm);
+            self.write(":");
+            self.indent += 1;
+            self.newline();
+            self.statement_list(&case.body);
+            self.indent -= 1;
+        }
+        if let Some(line) = closing {
+            self.flush_comments(line);
+        }
+        self.open_blocks.pop();
+        self.write("}");
+        if let Some(line) = closing {
+            self.printed_through(line);
+        }
+    }
+
+    fn visit_if_expression(&mut self, expr: &IfExpression) {
+        self.write_token(&expr.token, "if ");
+        if let Some(condition) = &expr.condition {
+            self.expr0(condition.as_ref(), 1);
+        }
+        self.write(" ");
+        if let Some(consequence) = &expr.consequence {
+            self.if_block(consequence);
+        }
+        if let Some(alternative) = &expr.alternative {
+            self.write(" else ");
+            self.if_block(alternative);
+        }
+    }
+}
+
+/// Line where a statement starts, or 0 when it was built by hand.
+fn import_line(spec: &ImportSpec) -> usize {
+    match (&spec.path, &spec.name) {
+        (Some(path), _) => path.token.line,
+        (None, Some(Identifier { token: Some(token), .. })) => token.line,
+        _ => 0,
+    }
+}
+
+/// Specs sort by path, then by name.
+fn import_key(spec: &ImportSpec) -> (&str, &str) {
+    let path = spec.path.as_ref().map(|p| p.value.as_str()).unwrap_or("");
+    let name = spec.name.as_ref().map(|n| n.value.as_str()).unwrap_or("");
+    (path, name)
+}
+
+fn statement_line(stmt: &dyn Statement) -> usize {
+    let any = stmt.as_any();
+    if let Some(s) = any.downcast_ref::<ExpressionStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<LetStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<ReturnStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<BlockStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<VarStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<TypeStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<ForStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<RangeStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<BranchStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<GoStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<DeferStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<SelectStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<ImportStatement>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<PackageClause>() {
+        return s.token.line;
+    }
+    if let Some(s) = any.downcast_ref::<SendStatement>() {
+        return s.channel.as_ref().and_then(|e| span_start(e.as_ref())).unwrap_or(s.token.line);
+    }
+    if let Some(s) = any.downcast_ref::<AssignStatement>() {
+        return s.left.first().and_then(|e| span_start(e.as_ref())).unwrap_or(s.token.line);
+    }
+    0
+}
+
+/// Line of the first token of a hand-written expression.
+fn span_start(e: &dyn Expression) -> Option<usize> {
+    crate::dump::span_of_expression(e).map(|span| span.start.0)
+}
+
+fn span_end(e: &dyn Expression) -> Option<usize> {
+    crate::dump::span_of_expression(e).map(|span| span.end.0)
+}
+
+/// Go's precedence for an operator; comparisons share one level.
+fn go_precedence(op: &str) -> u8 {
+    match op {
+        "*" | "/" | "%" => 5,
+        "+" | "-" => 4,
+        "&&" => 2,
+        "||" => 1,
+        _ => 3,
+    }
+}
+
+/// Parentheses undo one level of depth.
+fn reduce_depth(depth: usize) -> usize {
+    if depth > 1 { depth - 1 } else { 1 }
+}
+
+fn diff_prec(e: &dyn Expression, prec: u8) -> usize {
+    match e.as_any().downcast_ref::<InfixExpression>() {
+        Some(x) if precedence_of(x.token.r#type) == prec => 0,
+        _ => 1,
+    }
+}
+
+/// Reports whether the expression mixes additive and multiplicative
+/// operators, and the precedence that would glue two operators into a
+/// different token if the blanks were dropped.
+fn walk_binary(e: &InfixExpression) -> (bool, bool, u8) {
+    let prec = precedence_of(e.token.r#type);
+    let mut has4 = go_precedence(&e.operator) == 4;
+    let mut has5 = go_precedence(&e.operator) == 5;
+    let mut max_problem = 0;
+
+    if let Some(left) = &e.left {
+        if let Some(l) = left.as_any().downcast_ref::<InfixExpression>() {
+            // parentheses will be inserted otherwise
+            if precedence_of(l.token.r#type) >= prec {
+                let (h4, h5, mp) = walk_binary(l);
+                has4 = has4 || h4;
+                has5 = has5 || h5;
+                max_problem = max_problem.max(mp);
+            }
+        }
+    }
+
+    if let Some(right) = &e.right {
+        let any = right.as_any();
+        if let Some(r) = any.downcast_ref::<InfixExpression>() {
+            if precedence_of(r.token.r#type) > prec {
+                let (h4, h5, mp) = walk_binary(r);
+                has4 = has4 || h4;
+                has5 = has5 || h5;
+                max_problem = max_problem.max(mp);
+            }
+        } else if let Some(r) = any.downcast_ref::<PrefixExpression>() {
+            match [e.operator.as_str(), r.operator.as_str()].concat().as_str() {
+                "--" | "++" => max_problem = max_problem.max(4),
+                "/*" => max_problem = 5,
+                _ => {}
+            }
+        }
+    }
+
+    (has4, has5, max_problem)
+}
+
+/// Operators binding tighter than the returned precedence are printed
+/// without surrounding blanks.
+fn cutoff(e: &InfixExpression, depth: usize) -> u8 {
+    let (has4, has5, max_problem) = walk_binary(e);
+    if max_problem > 0 {
+        return max_problem + 1;
+    }
+    if has4 && has5 {
+        if depth == 1 {
+            return 5;
+        }
+        return 4;
+    }
+    if depth == 1 {
+        return 6;
+    }
+    4
+}
@@ -0,0 +1,248 @@
+use crate::format::diff::unified;
+use crate::format::*;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn check_format(input: &str, expected: &str) {
+    let formatted = match format_source(input) {
+      Ok(formatted) => formatted,
+      Err(errors) => panic!("format_source({:?}) failed: {:?}", input, errors),
+    };
+    if formatted != expected {
+      panic!("format_source({:?})\nexpected={:?}\n     got={:?}", input, expected, formatted);
+    }
+
+    // canonical output is a fixed point
+    let again = format_source(&formatted).unwrap();
+    if again != formatted {
+      panic!("formatting is not idempotent\nfirst={:?}\nsecond={:?}", formatted, again);
+    }
+  }
+
+  #[test]
+  fn test_statements() {
+    check_format("let x = 5;", "let x = 5\n");
+    check_format("let  x=5;let y = x;\nreturn y;", "let x = 5\nlet y = x\nreturn y\n");
+    check_format("return;", "return\n");
+    check_format("", "");
+  }
+
+  #[test]
+  fn test_spacing_by_precedence_depth() {
+    let tests = [
+      ("a+b", "a + b"),
+      ("a*b", "a * b"),
+      ("a + b * c", "a + b*c"),
+      ("a*b + c*d", "a*b + c*d"),
+      ("(a + b) * c", "(a + b) * c"),
+      ("x == a + b * c", "x == a+b*c"),
+      ("a - (b - c)", "a - (b - c)"),
+      ("a - -b", "a - -b"),
+      ("- -a", "-(-a)"),
+      ("-(a + b)", "-(a + b)"),
+      ("f(a + b)", "f(a + b)"),
+      ("f(a + b, c * d)", "f(a+b, c*d)"),
+      ("f(a + b * c, d)", "f(a+b*c, d)"),
+      ("5 > 4 == 3 < 4", "5 > 4 == 3 < 4"),
+      ("a == (b < c)", "a == b < c"),
+      ("(a == b) < c", "(a == b) < c"),
+    ];
+    for (input, expected) in tests.iter() {
+      check_format(input, &[*expected, "\n"].concat());
+    }
+  }
+
+  #[test]
+  fn test_value_syntax() {
+    let tests = [
+      ("x:=[]int{1,2}", "x := []int{1, 2}"),
+      ("var m map[string]int", "var m map[string]int"),
+      ("p := &Point{X:1}", "p := &Point{X: 1}"),
+      ("s[1:len(s)]", "s[1:len(s)]"),
+      ("n, ok := v.(int)", "n, ok := v.(int)"),
+      ("i++", "i++"),
+      ("x += 2", "x += 2"),
+    ];
+    for (input, expected) in tests.iter() {
+      check_format(input, &[*expected, "\n"].concat());
+    }
+  }
+
+
+  #[test]
+  fn test_concurrency_syntax() {
+    let tests = [
+      ("var ch chan  []int", "var ch chan []int\n"),
+      ("go f(1,2)", "go f(1, 2)\n"),
+      ("ch<-1", "ch <- 1\n"),
+      ("v := <- ch", "v := <-ch\n"),
+      ("select {}", "select {}\n"),
+      (
+        "select { case v := <-a: f(v)\ncase b <- 1:\ndefault: g() }",
+        "select {\ncase v := <-a:\n\tf(v)\ncase b <- 1:\ndefault:\n\tg()\n}\n",
+      ),
+    ];
+    for (input, expected) in tests.iter() {
+      check_format(input, expected);
+    }
+  }
+
+  #[test]
+  fn test_defer_and_named_results() {
+    let tests = [
+      ("let f = fn(x)(r  int){ defer g(x); return }", "let f = fn(x) (r int) {\n\tdefer g(x)\n\treturn\n}\n"),
+      ("fn() (a, b int, c) {}", "fn() (a, b int, c) {}\n"),
+      ("defer fn() { recover() }()", "defer fn() {\n\trecover()\n}()\n"),
+      ("append(s,t ...)", "append(s, t...)\n"),
+    ];
+    for (input, expected) in tests.iter() {
+      check_format(input, expected);
+    }
+  }
+
+  #[test]
+  fn test_imports_and_floats() {
+    let tests = [
+      ("package  main\nimport  \"fmt\"", "package main\n\nimport \"fmt\"\n"),
+      ("package main\n// Imports.\nimport \"fmt\"", "package main\n\n// Imports.\nimport \"fmt\"\n"),
+      ("package main\n\n// Count.\nvar n int", "package main\n\n// Count.\nvar n int\n"),
+      ("import (m \"math\"\n\"os\")", "import (\n\tm \"math\"\n\t\"os\"\n)\n"),
+      (
+        "import (\n\"fmt\"\n\"errors\" // e\n\"strconv\"\n\n\"os\"\n\"math\"\n)",
+        "import (\n\t\"errors\" // e\n\t\"fmt\"\n\t\"strconv\"\n\n\t\"math\"\n\t\"os\"\n)\n",
+      ),
+      (
+        "import (\n\"os\"\n// Formatting.\n\"fmt\"\n)",
+        "import (\n\t\"os\"\n\t// Formatting.\n\t\"fmt\"\n)\n",
+      ),
+      ("x:=1.5e3+.25", "x := 1.5e3 + .25\n"),
+    ];
+    for (input, expected) in tests.iter() {
+      check_format(input, expected);
+    }
+  }
+
+  #[test]
+  fn test_blocks_use_tab_indentation() {
+    check_format(
+      "let add = fn(x, y) { return x + y; };",
+      "let add = fn(x, y) {\n\treturn x + y\n}\n",
+    );
+    check_format(
+      "if (x < y) { x } else { if (y) { 1 } }",
+      "if x < y {\n\tx\n} else {\n\tif y {\n\t\t1\n\t}\n}\n",
+    );
+    check_format("let f = fn() {};", "let f = fn() {}\n");
+    check_format("if x {}", "if x {\n}\n");
+  }
+
+  #[test]
+  fn test_blank_lines_are_kept_once() {
+    check_format(
+      "let a = 1;\n\n\n\nlet b = 2;\nlet c = 3;\n",
+      "let a = 1\n\nlet b = 2\nlet c = 3\n",
+    );
+    check_format(
+      "let f = fn() {\n\n\tlet a = 1;\n\n\treturn a;\n\n};\n",
+      "let f = fn() {\n\tlet a = 1\n\n\treturn a\n}\n",
+    );
+  }
+
+  #[test]
+  fn test_comments() {
+    check_format(
+      "// header\nlet a = 1; // one\nlet bbbb = 2; // two\n\n// footer\n",
+      "// header\nlet a = 1    // one\nlet bbbb = 2 // two\n\n// footer\n",
+    );
+    check_format(
+      "let f = fn() {\n  return 1; /* done */\n  // last\n};\n",
+      "let f = fn() {\n\treturn 1 /* done */\n\t// last\n}\n",
+    );
+    check_format(
+      "let f = fn(a,b){return a+b;} // add\nf(1, 2)\n",
+      "let f = fn(a, b) {\n\treturn a + b\n} // add\nf(1, 2)\n",
+    );
+    check_format(
+      "if x {\n// nothing yet\n}\n",
+      "if x {\n\t// nothing yet\n}\n",
+    );
+    check_format(
+      "let f = fn(a, /* inline */ b) {\n}\n/* c */ x := 1 /* a */ + 2\n",
+      "let f = fn(a, /* inline */ b) {}\n/* c */ x := 1 /* a */ + 2\n",
+    );
+    check_format("type P struct { X int // x\n}\n", "type P struct {\n\tX int // x\n}\n");
+    check_format(
+      "let f = fn() {\n\treturn a + // c\n\t\tb\n}\nz := a &&\n// why\nb\n",
+      "let f = fn() {\n\treturn a + // c\n\t\tb\n}\nz := a &&\n\t// why\n\tb\n",
+    );
+    check_format(
+      "s := []int{\n1, // one\n2, // two\n3, // three\n}\n",
+      "s := []int{\n\t1, // one\n\t2, // two\n\t3, // three\n}\n",
+    );
+    check_format("s := []int{\n1, 2, // two\n}\n", "s := []int{\n\t1,\n\t2, // two\n}\n");
+  }
+
+  #[test]
+  fn test_call_line_breaks() {
+    check_format("f(\na, // first\nb,\n)\n", "f(\n\ta, // first\n\tb,\n)\n");
+    check_format("f(a,\nb)\n", "f(a,\n\tb)\n");
+    check_format("append(s,\nt...)\n", "append(s,\n\tt...)\n");
+    check_format("f(a, b) // c\n", "f(a, b) // c\n");
+    check_format("f(a /* x */)\n", "f(a /* x */)\n");
+  }
+
+  #[test]
+  fn test_unterminated_comment() {
+    for input in ["x := 1 /* open", "x := 1\n/*/\n"] {
+      assert_eq!(format_source(input), Err(vec!["comment not terminated".to_string()]), "{:?}", input);
+    }
+  }
+
+  #[test]
+  fn test_format_preserves_meaning() {
+    use crate::ast::Statement;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    let input = "let r = add(1, 2 * 3) - -(4 + 5) / f(a)(b); if (!(a == b)) { return -a } else { fn(x) { x }(1) }";
+
+    let parse = |src: &str| {
+      let mut p = Parser::new(Lexer::new(src.to_string()));
+      let program = p.parse_program();
+      if !p.errors().is_empty() {
+        panic!("parser errors for {:?}: {:?}", src, p.errors());
+      }
+      program.string()
+    };
+
+    let formatted = format_source(input).unwrap();
+    if parse(&formatted) != parse(input) {
+      panic!("formatting changed the program\nbefore={:?}\nafter={:?}", parse(input), parse(&formatted));
+    }
+  }
+
+  #[test]
+  fn test_format_program_without_source() {
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    let mut p = Parser::new(Lexer::new("let x = 1 + 2 * 3;".to_string()));
+    let program = p.parse_program();
+    if format_program(&program) != "let x = 1 + 2*3\n" {
+      panic!("format_program wrong. got={:?}", format_program(&program));
+    }
+  }
+
+  #[test]
+  fn test_unified_diff() {
+    let a = "a\nb\nc\n";
+    let b = "a\nB\nc\n";
+    let expected = "--- x.orig\n+++ x\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n";
+    if unified("x.orig", "x", a, b) != expected {
+      panic!("expected={:?}, got={:?}", expected, unified("x.orig", "x", a, b));
+    }
+    if !unified("x.orig", "x", a, a).is_empty() {
+      panic!("diff of equal inputs should be empty");
+    }
+  }
+}
@@ -0,0 +1,157 @@
+use crate::lexer::Lexer;
+use crate::token::*;
+use colored::{control, Colorize};
+use std::env;
+
+#[cfg(test)]
+mod test;
+
+/// What a piece of source text is, for choosing its color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    Keyword,
+    Literal,
+    Comment,
+    Operator,
+    Identifier,
+    /// Whitespace, and anything the lexer could not make sense of.
+    Plain,
+}
+
+/// Splits `source` into classified pieces that concatenate back to it
+/// exactly.
+pub fn classify(source: &str) -> Vec<(Class, String)> {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut spans = vec![];
+    loop {
+        let tok = lexer.next_token();
+        if tok.r#type == EOF {
+            break;
+        }
+        // semicolons inserted at line ends have no text of their own
+        if tok.r#type == SEMICOLON && tok.literal != ";" {
+            continue;
+        }
+        spans.push((class_of(tok.r#type), tok.line, tok.column, tok.literal.chars().count()));
+    }
+    for comment in &lexer.comments {
+        spans.push((Class::Comment, comment.line, comment.column, comment.literal.chars().count()));
+    }
+    spans.sort_by_key(|&(_, line, column, _)| (line, column));
+
+    let chars: Vec<char> = source.chars().collect();
+    let mut line_starts = vec![0];
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '\n' {
+            line_starts.push(i + 1);
+        }
+    }
+
+    let mut pieces = vec![];
+    let mut at = 0;
+    for (class, line, column, len) in spans {
+        let start = match line_starts.get(line - 1) {
+            Some(line_start) => line_start + column - 1,
+            None => continue,
+        };
+        if start < at || start >= chars.len() {
+            continue;
+        }
+        if start > at {
+            pieces.push((Class::Plain, chars[at..start].iter().collect()));
+        }
+        let end = (start + len).min(chars.len());
+        pieces.push((class, chars[start..end].iter().collect()));
+        at = end;
+    }
+    if at < chars.len() {
+        pieces.push((Class::Plain, chars[at..].iter().collect()));
+    }
+    pieces
+}
+
+fn class_of(t: TokenType) -> Class {
+    match t {
+        IDENT => Class::Identifier,
+        INT | FLOAT | STRING | TRUE | FALSE => Class::Literal,
+        ILLEGAL => Class::Plain,
+        _ if KEYWORDS.values().any(|k| *k == t) => Class::Keyword,
+        _ => Class::Operator,
+    }
+}
+
+fn paint(class: Class, text: &str) -> String {
+    match class {
+        Class::Keyword => text.magenta().bold().to_string(),
+        Class::Literal => text.yellow().to_string(),
+        Class::Comment => text.bright_black().to_string(),
+        Class::Operator => text.cyan().to_string(),
+        Class::Identifier => text.blue().to_string(),
+        Class::Plain => text.to_string(),
+    }
+}
+
+/// `source` with ANSI colors, or unchanged when colors are off. Colors
+/// never span a line break, so the result can be split into lines.
+pub fn highlight(source: &str) -> String {
+    let mut out = String::new();
+    for (class, text) in classify(source) {
+        let mut first = true;
+        for piece in text.split('\n') {
+            if !first {
+                out.push('\n');
+            }
+            first = false;
+            if !piece.is_empty() {
+                out.push_str(&paint(class, piece));
+            }
+        }
+    }
+    out
+}
+
+/// Line `line` (1-based) of `source`, highlighted in the context of the
+/// whole source so multi-line comments keep their color.
+pub fn highlight_line(source: &str, line: usize) -> Option<String> {
+    highlight(source).split('\n').nth(line.checked_sub(1)?).map(|s| s.to_string())
+}
+
+/// The `--color` setting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    /// Color when standard output is a terminal and `NO_COLOR` is unset.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn parse(s: &str) -> Option<ColorChoice> {
+        match s {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    /// Applies the choice to everything printed through `colored`.
+    pub fn apply(self) {
+        match self {
+            ColorChoice::Always => control::set_override(true),
+            ColorChoice::Never => control::set_override(false),
+            ColorChoice::Auto => {
+                // https://no-color.org: set to anything non-empty to disable
+                if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+                    control::set_override(false);
+                } else {
+                    control::unset_override();
+                }
+            }
+        }
+    }
+}
+
+pub fn colors_enabled() -> bool {
+    control::SHOULD_COLORIZE.should_colorize()
+}
@@ -0,0 +1,73 @@
+use crate::highlight::*;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_classify() {
+    let input = "let x = fn(a) { a + 10 } // add\nif true {}";
+    let expected = vec![
+      (Class::Keyword, "let"),
+      (Class::Plain, " "),
+      (Class::Identifier, "x"),
+      (Class::Plain, " "),
+      (Class::Operator, "="),
+      (Class::Plain, " "),
+      (Class::Keyword, "fn"),
+      (Class::Operator, "("),
+      (Class::Identifier, "a"),
+      (Class::Operator, ")"),
+      (Class::Plain, " "),
+      (Class::Operator, "{"),
+      (Class::Plain, " "),
+      (Class::Identifier, "a"),
+      (Class::Plain, " "),
+      (Class::Operator, "+"),
+      (Class::Plain, " "),
+      (Class::Literal, "10"),
+      (Class::Plain, " "),
+      (Class::Operator, "}"),
+      (Class::Plain, " "),
+      (Class::Comment, "// add"),
+      (Class::Plain, "\n"),
+      (Class::Keyword, "if"),
+      (Class::Plain, " "),
+      (Class::Literal, "true"),
+      (Class::Plain, " "),
+      (Class::Operator, "{"),
+      (Class::Operator, "}"),
+    ];
+    let got = classify(input);
+    let got: Vec<(Class, &str)> = got.iter().map(|(c, s)| (*c, s.as_str())).collect();
+    if got != expected {
+      panic!("classify expected={:?}\ngot={:?}", expected, got);
+    }
+  }
+
+  #[test]
+  fn test_classify_round_trips() {
+    let inputs = [
+      "let a = 1;\n\n/* multi\n   line */ a\n",
+      "x @ y",
+      "  \t",
+      "/* open",
+    ];
+    for input in inputs.iter() {
+      let joined: String = classify(input).into_iter().map(|(_, s)| s).collect();
+      if joined != *input {
+        panic!("classify({:?}) does not round-trip, got={:?}", input, joined);
+      }
+    }
+  }
+
+  #[test]
+  fn test_color_choice() {
+    let tests = [("auto", Some(ColorChoice::Auto)), ("always", Some(ColorChoice::Always)), ("never", Some(ColorChoice::Never)), ("yes", None)];
+    for (input, expected) in tests.iter() {
+      if ColorChoice::parse(input) != *expected {
+        panic!("ColorChoice::parse({:?}) expected={:?}", input, expected);
+      }
+    }
+  }
+}
@@ -0,0 +1,265 @@
+//! Conversions between Rust values and namu's, for the arguments and
+//! results of host functions and the values `Interpreter` sets and gets.
+//! Integers are `int`, floats `float64`, vectors slices and hash maps
+//! maps, with elements converted in turn; `Value` passes through as is.
+//! Tuples are the results of functions with more than one, and a
+//! `Result` is Go's `(T, error)`.
+
+use crate::stdlib::errors;
+use crate::types::Type;
+use crate::value::{Interface, Map, Slice, Value};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::rc::Rc;
+
+/// A Rust value a namu value is made from.
+pub trait IntoValue {
+    /// The namu type of the values made, for the elements of slices and
+    /// maps of them.
+    fn ty() -> Type
+    where
+        Self: Sized;
+
+    fn into_value(self) -> Value;
+}
+
+/// A Rust value made from a namu value, which the conversion may reject
+/// as not of the type wanted.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, String>;
+}
+
+/// The value an interface holds, or the value itself.
+fn unwrap(value: &Value) -> &Value {
+    match value {
+        Value::Interface(Interface { value: Some(v), .. }) => unwrap(v),
+        v => v,
+    }
+}
+
+fn mismatch(value: &Value, want: &str) -> String {
+    format!("cannot use {} (type {}) as {} value", value.inspect(), value.ty(), want)
+}
+
+impl IntoValue for Value {
+    fn ty() -> Type {
+        Type::any()
+    }
+
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Value, String> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoValue for () {
+    fn ty() -> Type {
+        Type::Nil
+    }
+
+    fn into_value(self) -> Value {
+        Value::Null
+    }
+}
+
+impl FromValue for () {
+    fn from_value(_: &Value) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+macro_rules! integer {
+    ($($t:ty),*) => {$(
+        impl IntoValue for $t {
+            fn ty() -> Type {
+                Type::Int
+            }
+
+            fn into_value(self) -> Value {
+                Value::Integer(self as i64)
+            }
+        }
+
+        impl FromValue for $t {
+            fn from_value(value: &Value) -> Result<$t, String> {
+                match unwrap(value) {
+                    Value::Integer(n) => <$t>::try_from(*n).map_err(|_| format!("{} overflows {}", n, stringify!($t))),
+                    v => Err(mismatch(v, "int")),
+                }
+            }
+        }
+    )*};
+}
+
+integer!(i64, i32, u32, usize);
+
+impl IntoValue for f64 {
+    fn ty() -> Type {
+        Type::Float64
+    }
+
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<f64, String> {
+        match unwrap(value) {
+            Value::Float(f) => Ok(*f),
+            Value::Integer(n) => Ok(*n as f64),
+            v => Err(mismatch(v, "float64")),
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn ty() -> Type {
+        Type::Bool
+    }
+
+    fn into_value(self) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<bool, String> {
+        match unwrap(value) {
+            Value::Boolean(b) => Ok(*b),
+            v => Err(mismatch(v, "bool")),
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn ty() -> Type {
+        Type::String
+    }
+
+    fn into_value(self) -> Value {
+        Value::String(Rc::from(self.into_bytes()))
+    }
+}
+
+impl IntoValue for &str {
+    fn ty() -> Type {
+        Type::String
+    }
+
+    fn into_value(self) -> Value {
+        Value::string(self)
+    }
+}
+
+/// Strings that are not UTF-8 have their bad bytes replaced.
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<String, String> {
+        match unwrap(value) {
+            Value::String(s) => Ok(String::from_utf8_lossy(s).into_owned()),
+            v => Err(mismatch(v, "string")),
+        }
+    }
+}
+
+/// None is nil.
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn ty() -> Type {
+        T::ty()
+    }
+
+    fn into_value(self) -> Value {
+        self.map_or(Value::Null, T::into_value)
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Option<T>, String> {
+        match unwrap(value) {
+            Value::Null | Value::Interface(Interface { value: None, .. }) => Ok(None),
+            v => T::from_value(v).map(Some),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn ty() -> Type {
+        Type::Slice(Rc::new(T::ty()))
+    }
+
+    fn into_value(self) -> Value {
+        Value::Slice(Slice::from_values(T::ty(), self.into_iter().map(T::into_value).collect()))
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Vec<T>, String> {
+        match unwrap(value) {
+            Value::Slice(s) => s.values().iter().map(T::from_value).collect(),
+            v => Err(mismatch(v, "slice")),
+        }
+    }
+}
+
+/// Panics on keys Go cannot compare, such as slices.
+impl<K: IntoValue, V: IntoValue> IntoValue for HashMap<K, V> {
+    fn ty() -> Type {
+        Type::Map(Rc::new(K::ty()), Rc::new(V::ty()))
+    }
+
+    fn into_value(self) -> Value {
+        let map = Map::new(K::ty(), V::ty());
+        for (k, v) in self {
+            map.insert(k.into_value(), v.into_value()).unwrap_or_else(|e| panic!("{}", e));
+        }
+        Value::Map(map)
+    }
+}
+
+impl<K: FromValue + Eq + Hash, V: FromValue> FromValue for HashMap<K, V> {
+    fn from_value(value: &Value) -> Result<HashMap<K, V>, String> {
+        match unwrap(value) {
+            Value::Map(m) => m.entries().iter().map(|(k, v)| Ok((K::from_value(k)?, V::from_value(v)?))).collect(),
+            v => Err(mismatch(v, "map")),
+        }
+    }
+}
+
+macro_rules! tuple {
+    ($($t:ident . $i:tt),*) => {
+        /// The results of a host function with more than one.
+        impl<$($t: IntoValue),*> IntoValue for ($($t,)*) {
+            fn ty() -> Type {
+                Type::Nil
+            }
+
+            fn into_value(self) -> Value {
+                Value::Tuple(Rc::from(vec![$(self.$i.into_value()),*]))
+            }
+        }
+    };
+}
+
+tuple!(A.0, B.1);
+tuple!(A.0, B.1, C.2);
+
+/// A value and a nil error, or the zero value and an error with the
+/// message, as Go functions return them.
+impl<T: IntoValue> IntoValue for Result<T, String> {
+    fn ty() -> Type {
+        Type::Nil
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Ok(v) => (v, errors::nil_error()).into_value(),
+            Err(msg) => (T::ty().zero(), errors::new_error(&msg)).into_value(),
+        }
+    }
+}
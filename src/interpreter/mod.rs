@@ -0,0 +1,187 @@
+//! Embedding namu in a Rust program: parsing and checking its sources,
+//! and `Interpreter`, which evaluates Go-syntax scripts with functions of
+//! the host program and converts values between the two, see `convert`,
+//! and whole packages of it scripts import, see `package`.
+//! These are what the crate root re-exports, and what stays stable as the
+//! modules under them change.
+//!
+//! ```
+//! use namu::{FromValue, IntoValue, Interpreter};
+//!
+//! let mut interp = Interpreter::new();
+//! interp.register("double", |args| Ok((i64::from_value(&args[0])? * 2).into_value()));
+//! interp.eval("let f = fn(x) { double(x) + 1 }").unwrap();
+//! let result = interp.call("f", vec![20.into_value()]).unwrap();
+//! assert_eq!(i64::from_value(&result), Ok(41));
+//! ```
+
+use crate::ast::Program;
+use crate::build::Target;
+use crate::lexer::Lexer;
+use crate::loader::{self, Loader, Module};
+use crate::parser::Parser;
+use crate::value::{Host, Value};
+use std::error;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+pub mod convert;
+pub mod package;
+pub mod session;
+
+#[cfg(test)]
+mod test;
+
+pub use crate::types::Signature;
+pub use crate::vm::{Limit, Limits};
+pub use convert::{FromValue, IntoValue};
+pub use package::{HostType, Package};
+use session::{EvalError, Session};
+
+/// File name diagnostics for `Interpreter::eval` input are reported
+/// against.
+pub const INPUT_NAME: &str = "<eval>";
+
+/// Why sources could not be checked, or a script run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The sources do not parse or compile; nothing was run.
+    Compile(Vec<loader::Error>),
+    /// A panic, or a host function's error, ended the run.
+    Runtime(String),
+    /// The run went past one of the limits `Interpreter::set_limits` set.
+    Limit(Limit),
+    /// The script called `os.Exit` with this status.
+    Exit(i32),
+}
+
+impl fmt::Display for Error {
+    /// The errors as `namu check` prints them, one after another.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Compile(errors) => {
+                for error in errors {
+                    match error {
+                        loader::Error::File { name, source, diagnostics } => {
+                            for diagnostic in diagnostics {
+                                write!(f, "{}", diagnostic.render(name, source))?;
+                            }
+                        }
+                        loader::Error::Message(msg) => writeln!(f, "{}", msg)?,
+                    }
+                }
+                Ok(())
+            }
+            Error::Runtime(msg) => write!(f, "panic: {}", msg),
+            Error::Limit(limit) => write!(f, "{}", limit),
+            Error::Exit(code) => write!(f, "exit status {}", code),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Parses one source file, reporting its problems against `name`.
+pub fn parse_file(name: &str, source: &str) -> Result<Program, Error> {
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    match p.diagnostics() {
+        diagnostics if diagnostics.is_empty() => Ok(program),
+        diagnostics => Err(Error::Compile(vec![loader::Error::File {
+            name: name.to_string(),
+            source: source.to_string(),
+            diagnostics,
+        }])),
+    }
+}
+
+/// Loads the package in `dir`, and the packages of its module it
+/// imports, for the machine namu runs on, and parses and compiles them, as
+/// `namu check dir` does.
+pub fn check_package(dir: &Path) -> Result<(), Error> {
+    let module = Module::find(dir).map_err(|msg| Error::Compile(vec![loader::Error::Message(msg)]))?;
+    let packages = Loader::new(module, Target::host()).load_dir(dir).map_err(Error::Compile)?;
+    loader::compile(&packages).map(|_| ()).map_err(Error::Compile)
+}
+
+/// Evaluates scripts one after another, each seeing the globals the ones
+/// before it defined, and the host functions and values the embedding
+/// program set.
+#[derive(Default)]
+pub struct Interpreter {
+    session: Session,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter::default()
+    }
+
+    /// Runs a script: its statements in order, definitions kept for the
+    /// scripts after it only when all of it ran. The result is the value
+    /// of its last statement when that is an expression, else nil.
+    pub fn eval(&mut self, source: &str) -> Result<Value, Error> {
+        match self.session.eval(source) {
+            Ok(value) => Ok(value.unwrap_or(Value::Null)),
+            Err(EvalError::Diagnostics(diagnostics)) => Err(Error::Compile(vec![loader::Error::File {
+                name: INPUT_NAME.to_string(),
+                source: source.to_string(),
+                diagnostics,
+            }])),
+            Err(EvalError::Runtime(msg)) => Err(Error::Runtime(msg)),
+            Err(EvalError::Limit(limit)) => Err(Error::Limit(limit)),
+            Err(EvalError::Exit(code)) => Err(Error::Exit(code)),
+        }
+    }
+
+    /// Makes `func` a global function scripts call as `name`. An error
+    /// it returns panics in the script, where `recover` may stop it.
+    pub fn register<F>(&mut self, name: &str, func: F)
+    where
+        F: Fn(Vec<Value>) -> Result<Value, String> + 'static,
+    {
+        let host = Host { name: name.to_string(), func: Box::new(func) };
+        self.session.define(name, Value::Host(Rc::new(host)));
+    }
+
+    /// Holds the scripts evaluated and the calls made after to `limits`,
+    /// for running code that is not trusted. Each `eval` and `call` gets
+    /// the whole of them.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.session.set_limits(limits);
+    }
+
+    /// Lets the scripts evaluated after import `os`, to read files and the
+    /// process's arguments and to exit, or not. They may by default.
+    pub fn allow_system(&mut self, allow: bool) {
+        self.session.allow_system(allow);
+    }
+
+    /// Makes `package` importable by the scripts evaluated after, by its
+    /// path, in place of one registered before with the same path.
+    pub fn register_package(&mut self, package: Package) {
+        self.session.add_package(&package.path, &package.name, package.funcs, package.types);
+    }
+
+    /// Sets the global `name`, defining it if scripts have not.
+    pub fn set(&mut self, name: &str, value: impl IntoValue) {
+        self.session.define(name, value.into_value());
+    }
+
+    /// The value of the global `name`, if it is defined.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.session.get(name)
+    }
+
+    /// Calls the function the global `name` holds.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, Error> {
+        let callee = self.get(name).ok_or_else(|| Error::Runtime(format!("undefined: {}", name)))?;
+        self.session.call(callee, args).map_err(|e| match e {
+            EvalError::Runtime(msg) => Error::Runtime(msg),
+            EvalError::Limit(limit) => Error::Limit(limit),
+            EvalError::Exit(code) => Error::Exit(code),
+            EvalError::Diagnostics(_) => unreachable!("calls compile nothing"),
+        })
+    }
+}
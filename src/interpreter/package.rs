@@ -0,0 +1,149 @@
+//! Packages of the embedding program, which scripts import by path as
+//! they would a package of the standard library. Their functions have
+//! signatures the compiler checks calls against, and their types wrap
+//! Rust data scripts pass around and call the methods of.
+//!
+//! Arguments are converted to the parameter types before a function sees
+//! them, so an `any` parameter receives an interface; a call with an
+//! argument of another type panics in the script, as does a result that
+//! is not of its result type, other than nil, which is taken as the zero
+//! value. A function with several results returns them as a tuple, see
+//! `IntoValue for (A, B)`.
+
+use crate::types::{Signature, StructType, Type};
+use crate::value::{convert, Host, Interface, Opaque, OpaqueType, Value};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A package scripts import as `import "host/db"`, known by the last
+/// element of its path unless the import names it.
+pub struct Package {
+    pub(crate) path: String,
+    pub(crate) name: String,
+    pub(crate) funcs: Vec<(String, Signature, Value)>,
+    pub(crate) types: Vec<(String, Type)>,
+}
+
+impl Package {
+    pub fn new(path: &str) -> Package {
+        let name = path.rsplit('/').next().unwrap_or(path).to_string();
+        Package { path: path.to_string(), name, funcs: vec![], types: vec![] }
+    }
+
+    /// Adds the function `name`, which an error ends a script's call to
+    /// with a panic, as `Interpreter::register`'s do.
+    pub fn func<F>(&mut self, name: &str, signature: Signature, func: F) -> &mut Package
+    where
+        F: Fn(Vec<Value>) -> Result<Value, String> + 'static,
+    {
+        let host = typed(format!("{}.{}", self.name, name), signature.clone(), func);
+        self.funcs.push((name.to_string(), signature, Value::Host(Rc::new(host))));
+        self
+    }
+
+    /// Adds the type `name`, for signatures to use and functions to make
+    /// values of.
+    pub fn new_type(&mut self, name: &str) -> HostType {
+        let ty = StructType { name: Some(format!("{}.{}", self.name, name)), fields: RefCell::new(vec![]) };
+        let ty = HostType(Rc::new(OpaqueType { ty: Rc::new(ty), methods: RefCell::new(HashMap::new()) }));
+        self.types.push((name.to_string(), ty.ty()));
+        ty
+    }
+}
+
+/// A type of a package of the embedding program, whose values are Rust
+/// data scripts cannot look inside, as a `*sql.DB` is to Go programs.
+#[derive(Clone)]
+pub struct HostType(Rc<OpaqueType>);
+
+impl HostType {
+    /// The type as signatures name it.
+    pub fn ty(&self) -> Type {
+        Type::Struct(Rc::clone(&self.0.ty))
+    }
+
+    /// A value of the type holding `data`. Copies of it share the data.
+    pub fn wrap<T: Any>(&self, data: T) -> Value {
+        Value::Opaque(Opaque { ty: Rc::clone(&self.0), data: Rc::new(data) })
+    }
+
+    /// The data `value` holds, if it is a value of the type holding a `T`.
+    pub fn unwrap<T: Any>(&self, value: &Value) -> Result<Rc<T>, String> {
+        match value {
+            Value::Opaque(o) if Rc::ptr_eq(&o.ty, &self.0) => {
+                Rc::clone(&o.data).downcast().map_err(|_| format!("{} does not hold a {}", self.ty(), std::any::type_name::<T>()))
+            }
+            v => Err(format!("cannot use {} value as {} value", v.ty(), self.ty())),
+        }
+    }
+
+    /// Adds the method `name`, which `func` is called with the receiver
+    /// and then the arguments for. `signature` leaves out the receiver.
+    pub fn method<F>(&self, name: &str, signature: Signature, func: F) -> &HostType
+    where
+        F: Fn(Vec<Value>) -> Result<Value, String> + 'static,
+    {
+        let mut params = vec![self.ty()];
+        params.extend(signature.params);
+        let signature = Signature { params, ..signature };
+        let host = typed(format!("{}.{}", self.ty(), name), signature, func);
+        self.0.methods.borrow_mut().insert(name.to_string(), Rc::new(host));
+        self
+    }
+}
+
+/// `func` with its arguments and results checked against `signature`.
+fn typed<F>(name: String, signature: Signature, func: F) -> Host
+where
+    F: Fn(Vec<Value>) -> Result<Value, String> + 'static,
+{
+    let call = {
+        let name = name.clone();
+        move |args: Vec<Value>| {
+            let args = check_args(&name, &signature, args)?;
+            check_results(&name, &signature, func(args)?)
+        }
+    };
+    Host { name, func: Box::new(call) }
+}
+
+fn check_args(name: &str, signature: &Signature, args: Vec<Value>) -> Result<Vec<Value>, String> {
+    let params = signature.params.len();
+    let enough = if signature.variadic { args.len() + 1 >= params } else { args.len() == params };
+    if !enough {
+        return Err(format!("wrong number of arguments in call to {}: have {}, want {}", name, args.len(), signature));
+    }
+    args.into_iter()
+        .enumerate()
+        .map(|(i, arg)| convert(arg, &signature.params[i.min(params - 1)]).map_err(|e| format!("{} in argument to {}", e, name)))
+        .collect()
+}
+
+fn check_results(name: &str, signature: &Signature, result: Value) -> Result<Value, String> {
+    match signature.results.as_slice() {
+        [] => Ok(Value::Null),
+        [ty] => convert_result(name, result, ty),
+        types => match result {
+            Value::Tuple(values) if values.len() == types.len() => {
+                let values = values
+                    .iter()
+                    .zip(types)
+                    .map(|(v, ty)| convert_result(name, v.clone(), ty))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Tuple(Rc::from(values)))
+            }
+            v => Err(format!("{} returned {} value, want {} results", name, v.ty(), types.len())),
+        },
+    }
+}
+
+/// A nil result is the zero value of its type, as for the value that
+/// goes with an error.
+fn convert_result(name: &str, result: Value, ty: &Type) -> Result<Value, String> {
+    match result {
+        Value::Null | Value::Interface(Interface { value: None, .. }) if !ty.is_interface() => Ok(ty.zero()),
+        result => convert(result, ty).map_err(|e| format!("{} in result of {}", e, name)),
+    }
+}
@@ -0,0 +1,254 @@
+//! The state the REPL and `Interpreter` keep between inputs: the globals
+//! defined so far, their values, and the constants compiled for them,
+//! the packages of the embedding program scripts may import, and the
+//! limits runs are held to.
+
+use crate::ast::{ExpressionStatement, Program};
+use crate::compiler::symbol_table::{Symbol, SymbolTable, GLOBAL_SCOPE, TYPE_SCOPE};
+use crate::compiler::{Bytecode, Compiler};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::stdlib::SYSTEM_PACKAGES;
+use crate::types::{Signature, Type};
+use crate::value::{new_var, Value, Var};
+use crate::vm::{Limit, Limits, Vm};
+use std::collections::HashMap;
+
+/// Why an input could not be evaluated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// The input does not parse or compile; nothing was run.
+    Diagnostics(Vec<Diagnostic>),
+    Runtime(String),
+    /// The run went past one of the session's limits.
+    Limit(Limit),
+    /// The run called `os.Exit` with this status.
+    Exit(i32),
+}
+
+/// Globals, constants and their values carried from one input to the next.
+pub struct Session {
+    symbol_table: SymbolTable,
+    constants: Vec<Value>,
+    globals: Vec<Var>,
+    packages: Vec<HostPackage>,
+    limits: Limits,
+    /// Whether inputs may import `SYSTEM_PACKAGES`.
+    system: bool,
+}
+
+/// A package of the embedding program. Its functions are globals and its
+/// types constants, under names scripts cannot write.
+#[derive(Clone)]
+struct HostPackage {
+    path: String,
+    name: String,
+    members: HashMap<String, Symbol>,
+    signatures: HashMap<String, Signature>,
+}
+
+impl Clone for Session {
+    /// A session whose globals are copies, so that defining or assigning
+    /// them in it leaves this one unchanged. The copies are shallow: maps,
+    /// slices, pointers and channels in them share what they refer to.
+    fn clone(&self) -> Session {
+        Session {
+            symbol_table: self.symbol_table.clone(),
+            constants: self.constants.clone(),
+            globals: self.globals.iter().map(|var| new_var(var.borrow().clone())).collect(),
+            packages: self.packages.clone(),
+            limits: self.limits.clone(),
+            system: self.system,
+        }
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session {
+            symbol_table: SymbolTable::new(),
+            constants: vec![],
+            globals: vec![],
+            packages: vec![],
+            limits: Limits::default(),
+            system: true,
+        }
+    }
+
+    /// Holds each input run and call after to `limits`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Lets the inputs after import the packages that reach outside the
+    /// program, or not.
+    pub fn allow_system(&mut self, allow: bool) {
+        self.system = allow;
+    }
+
+    /// A VM to run `bytecode` in, with the session's globals and limits.
+    fn vm(&mut self, bytecode: Bytecode) -> Vm {
+        let mut vm = Vm::new_with_globals(bytecode, std::mem::take(&mut self.globals));
+        vm.set_limits(self.limits.clone());
+        vm
+    }
+
+    /// The error a run that failed with `msg` ended in.
+    fn run_error(vm: &Vm, msg: String) -> EvalError {
+        match vm.exceeded() {
+            Some(limit) => EvalError::Limit(limit),
+            None => EvalError::Runtime(msg),
+        }
+    }
+
+    /// `result` of a run, or the status it exited with.
+    fn finish<T>(vm: &Vm, result: Result<T, String>) -> Result<T, EvalError> {
+        let value = result.map_err(|msg| Session::run_error(vm, msg))?;
+        match vm.exit_code() {
+            Some(code) => Err(EvalError::Exit(code)),
+            None => Ok(value),
+        }
+    }
+
+    /// Runs one complete input. Returns the value of its last statement
+    /// when that is an expression. Definitions are kept only when the
+    /// whole input ran without error or exiting.
+    pub fn eval(&mut self, input: &str) -> Result<Option<Value>, EvalError> {
+        let mut p = Parser::new(Lexer::new(input.to_string()));
+        let program = p.parse_program();
+        if !p.diagnostics().is_empty() {
+            return Err(EvalError::Diagnostics(p.diagnostics()));
+        }
+
+        let mut compiler = Compiler::new_with_state(self.symbol_table.clone(), self.constants.clone());
+        for pkg in &self.packages {
+            compiler.add_host_package(&pkg.path, &pkg.name, pkg.members.clone(), pkg.signatures.clone());
+        }
+        if !self.system {
+            compiler.deny_imports(SYSTEM_PACKAGES);
+        }
+        if let Err(diagnostics) = compiler.compile(&program) {
+            return Err(EvalError::Diagnostics(diagnostics));
+        }
+        let bytecode = compiler.bytecode();
+
+        let mut vm = self.vm(bytecode);
+        let result = vm.run();
+        self.globals = std::mem::take(&mut vm.globals);
+        Session::finish(&vm, result)?;
+
+        let (symbol_table, constants) = compiler.into_state();
+        self.symbol_table = symbol_table;
+        self.constants = constants;
+        if ends_with_expression(&program) {
+            Ok(Some(vm.last_popped_stack_elem()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Global names in the order they were first defined.
+    pub fn names(&self) -> Vec<String> {
+        self.symbol_table.globals().into_iter().map(|s| s.name).collect()
+    }
+
+    /// Global names with their current values.
+    pub fn env(&self) -> Vec<(String, Value)> {
+        self.symbol_table
+            .globals()
+            .into_iter()
+            .map(|s| (s.name, self.global(s.index)))
+            .collect()
+    }
+
+    /// The value of the global `name`, if it is defined.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        let symbol = self.symbol_table.declarations().remove(name).filter(|s| s.scope == GLOBAL_SCOPE)?;
+        Some(self.global(symbol.index))
+    }
+
+    /// Defines the global `name` to hold `value`, or sets it when it is
+    /// defined already, so that code compiled after refers to it.
+    pub fn define(&mut self, name: &str, value: Value) -> Symbol {
+        let symbol = match self.symbol_table.declarations().remove(name) {
+            Some(symbol) if symbol.scope == GLOBAL_SCOPE => symbol,
+            _ => self.symbol_table.define(name),
+        };
+        if symbol.index >= self.globals.len() {
+            self.globals.resize_with(symbol.index + 1, || new_var(Value::Null));
+        }
+        *self.globals[symbol.index].borrow_mut() = value;
+        symbol
+    }
+
+    /// Makes the package `path` of the embedding program importable by
+    /// the inputs after, replacing one registered with the same path.
+    pub fn add_package(&mut self, path: &str, name: &str, funcs: Vec<(String, Signature, Value)>, types: Vec<(String, Type)>) {
+        let mut members = HashMap::new();
+        let mut signatures = HashMap::new();
+        for (member, signature, value) in funcs {
+            members.insert(member.clone(), self.define(&format!("{}.{}", path, member), value));
+            signatures.insert(member, signature);
+        }
+        for (member, ty) in types {
+            let symbol = Symbol { name: member.clone(), scope: TYPE_SCOPE, index: self.constants.len() };
+            self.constants.push(Value::Type(ty));
+            members.insert(member, symbol);
+        }
+        let pkg = HostPackage { path: path.to_string(), name: name.to_string(), members, signatures };
+        // a package keeps its index, which imports compiled earlier refer to
+        match self.packages.iter_mut().find(|p| p.path == path) {
+            Some(p) => *p = pkg,
+            None => self.packages.push(pkg),
+        }
+    }
+
+    /// Calls a function value, which sees the session's globals.
+    pub fn call(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, EvalError> {
+        let bytecode = Compiler::new_with_state(self.symbol_table.clone(), self.constants.clone()).bytecode();
+        let mut vm = self.vm(bytecode);
+        let result = vm.call(callee, args);
+        self.globals = std::mem::take(&mut vm.globals);
+        Session::finish(&vm, result)
+    }
+
+    fn global(&self, index: usize) -> Value {
+        match self.globals.get(index) {
+            Some(var) => var.borrow().clone(),
+            None => Value::Null,
+        }
+    }
+
+    /// Field names of the globals holding a struct or a pointer to one,
+    /// for completing selectors.
+    pub fn fields(&self) -> HashMap<String, Vec<String>> {
+        let mut fields = HashMap::new();
+        for (name, value) in self.env() {
+            let value = match value {
+                Value::Pointer(p) => p.load().unwrap_or(Value::Null),
+                value => value,
+            };
+            if let Value::Struct(s) = value {
+                fields.insert(name, s.ty.fields.borrow().iter().map(|f| f.name.clone()).collect());
+            }
+        }
+        fields
+    }
+}
+
+fn ends_with_expression(program: &Program) -> bool {
+    match program.statements.last() {
+        Some(stmt) => match stmt.as_any().downcast_ref::<ExpressionStatement>() {
+            Some(stmt) => stmt.expression.is_some(),
+            None => false,
+        },
+        None => false,
+    }
+}
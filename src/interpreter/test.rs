@@ -0,0 +1,222 @@
+use crate::interpreter::*;
+use crate::types::Type;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::rc::Rc;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_eval() {
+    let mut interp = Interpreter::new();
+    assert_eq!(interp.eval("let add = fn(a, b) { a + b }"), Ok(Value::Null));
+    assert_eq!(interp.eval("add(2, 3) * 2"), Ok(Value::Integer(10)));
+    assert_eq!(interp.call("add", vec![1.into_value(), 2.into_value()]), Ok(Value::Integer(3)));
+
+    let err = interp.eval("let x = y").unwrap_err();
+    assert!(matches!(&err, Error::Compile(_)), "{:?}", err);
+    assert!(err.to_string().contains("undefined: y"), "{}", err);
+    assert_eq!(interp.eval("[]int{1}[3]"), Err(Error::Runtime("runtime error: index out of range [3] with length 1".to_string())));
+  }
+
+  #[test]
+  fn test_host_functions() {
+    let mut interp = Interpreter::new();
+    let log = Rc::new(RefCell::new(vec![]));
+    let sink = Rc::clone(&log);
+    interp.register("record", move |args| {
+      sink.borrow_mut().push(String::from_value(&args[0])?);
+      Ok(Value::Null)
+    });
+    interp.register("check", |args| match bool::from_value(&args[0])? {
+      true => Ok(Value::Null),
+      false => Err("check failed".to_string()),
+    });
+    let source = r#"
+let safe = fn() (r any) {
+  defer fn() { r = recover() }()
+  check(false)
+  return nil
+}
+record("a")
+record(safe())
+"#;
+    assert_eq!(interp.eval(source), Ok(Value::Null));
+    assert_eq!(*log.borrow(), vec!["a".to_string(), "runtime error: check failed".to_string()]);
+    assert_eq!(interp.eval("check(1 > 2)"), Err(Error::Runtime("runtime error: check failed".to_string())));
+  }
+
+  #[test]
+  fn test_values() {
+    let mut interp = Interpreter::new();
+    interp.set("names", vec!["b", "a"]);
+    interp.set("limit", 2.5);
+    let mut ages = HashMap::new();
+    ages.insert("x".to_string(), 1i64);
+    interp.set("ages", ages.clone());
+    interp.eval(r#"names = append(names, "c"); ages["y"] = len(names)"#).unwrap();
+
+    let names = interp.get("names").unwrap();
+    assert_eq!(Vec::<String>::from_value(&names), Ok(vec!["b".to_string(), "a".to_string(), "c".to_string()]));
+    ages.insert("y".to_string(), 3);
+    assert_eq!(HashMap::<String, i64>::from_value(&interp.get("ages").unwrap()), Ok(ages));
+    assert_eq!(f64::from_value(&interp.eval("limit * 2").unwrap()), Ok(5.0));
+    assert_eq!(Option::<i64>::from_value(&interp.eval("var e error\ne").unwrap()), Ok(None));
+    assert_eq!(
+      i64::from_value(&Value::string("x")),
+      Err("cannot use x (type string) as int value".to_string())
+    );
+    assert_eq!(u32::from_value(&Value::Integer(-1)), Err("-1 overflows u32".to_string()));
+    assert!(interp.get("missing").is_none());
+  }
+
+  #[test]
+  fn test_parse_and_check() {
+    assert!(parse_file("ok.go", "let x = 1").is_ok());
+    let err = parse_file("bad.go", "let = 1").err().unwrap();
+    assert!(err.to_string().contains("bad.go:1:"), "{}", err);
+
+    let dir = env::temp_dir().join(format!("namu-check-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.go"), "package main\nlet main = fn() { undefinedName() }\n").unwrap();
+    let result = check_package(&dir);
+    fs::write(dir.join("main.go"), "package main\nlet main = fn() {}\n").unwrap();
+    let fixed = check_package(&dir);
+    let _ = fs::remove_dir_all(&dir);
+    assert!(result.unwrap_err().to_string().contains("undefined: undefinedName"));
+    assert_eq!(fixed, Ok(()));
+  }
+
+  struct Conn {
+    name: String,
+    rows: RefCell<Vec<String>>,
+  }
+
+  fn db_package() -> Package {
+    let mut db = Package::new("host/db");
+    let conn = db.new_type("Conn");
+    let open = conn.clone();
+    db.func("Open", Signature::new(vec![Type::String], vec![conn.ty(), Type::error()]), move |args| {
+      let name = String::from_value(&args[0])?;
+      if name.is_empty() {
+        return Ok(Result::<Value, String>::Err("empty name".to_string()).into_value());
+      }
+      Ok((open.wrap(Conn { name, rows: RefCell::new(vec![]) }), Value::Null).into_value())
+    });
+    db.func("Join", Signature::variadic(vec![Type::String, Type::any()], vec![Type::String]), |args| {
+      let sep = String::from_value(&args[0])?;
+      let parts: Vec<String> = args[1..].iter().map(|v| v.inspect()).collect();
+      Ok(parts.join(&sep).into_value())
+    });
+    let recv = conn.clone();
+    conn.method("Insert", Signature::new(vec![Type::String], vec![Type::Int]), move |args| {
+      let c = recv.unwrap::<Conn>(&args[0])?;
+      let mut rows = c.rows.borrow_mut();
+      rows.push(String::from_value(&args[1])?);
+      Ok(rows.len().into_value())
+    });
+    let recv = conn.clone();
+    conn.method("Name", Signature::new(vec![], vec![Type::String]), move |args| Ok(recv.unwrap::<Conn>(&args[0])?.name.clone().into_value()));
+    db
+  }
+
+  #[test]
+  fn test_packages() {
+    let mut interp = Interpreter::new();
+    interp.register_package(db_package());
+    let source = r#"
+import "host/db"
+c, err := db.Open("main")
+n := c.Insert("a") + c.Insert("b")
+let open = fn(name) {
+  var c db.Conn
+  c, _ = db.Open(name)
+  return c
+}
+"#;
+    assert_eq!(interp.eval(source), Ok(Value::Null));
+    assert_eq!(interp.eval("n"), Ok(Value::Integer(3)));
+    assert_eq!(interp.eval("err == nil"), Ok(Value::Boolean(true)));
+    assert_eq!(interp.eval(r#"import "fmt"; fmt.Sprintf("%v %T %s", c, c, c.Name())"#), Ok(Value::string("<db.Conn> db.Conn main")));
+    assert_eq!(interp.eval(r#"open("x") == open("x") || c != c"#), Ok(Value::Boolean(false)));
+    assert_eq!(interp.eval(r#"_, err = db.Open(""); err.Error()"#), Ok(Value::string("empty name")));
+    assert_eq!(interp.eval(r#"db.Join("-", 1, "a", true)"#), Ok(Value::string("1-a-true")));
+    assert_eq!(interp.eval(r#"db.Join(",", []any{1, 2}...) + db.Join(",")"#), Ok(Value::string("1,2")));
+    assert_eq!(interp.eval(r#"len(db.Join(",", make([]any, 5000)...)) > 5000"#), Ok(Value::Boolean(true)));
+  }
+
+  #[test]
+  fn test_package_errors() {
+    let mut interp = Interpreter::new();
+    interp.register_package(db_package());
+    interp.eval(r#"import "host/db"; c, _ := db.Open("main")"#).unwrap();
+    let compile_errors = [
+      ("db.Open()", "not enough arguments in call to db.Open (want func(string) (db.Conn, error))"),
+      (r#"db.Open("a", "b")"#, "too many arguments in call to db.Open"),
+      ("db.Join()", "not enough arguments in call to db.Join"),
+      (r#"let x = db.Open("a")"#, "multiple-value db.Open(\"a\") (value of type (db.Conn, error)) in single-value context"),
+      (r#"var x, y, z = db.Open("a")"#, "assignment mismatch: 3 variables but db.Open returns 2 values"),
+      (r#"db.Join("", "a", []any{}...)"#, "too many arguments in call to db.Join"),
+      ("db.Missing()", "undefined: db.Missing"),
+    ];
+    for (source, expected) in compile_errors.iter() {
+      let err = interp.eval(source).unwrap_err();
+      assert!(err.to_string().contains(expected), "{}: {}", source, err);
+    }
+    let runtime_errors = [
+      ("db.Open(1)", "runtime error: cannot use int value as string value in argument to db.Open"),
+      ("c.Insert(true)", "runtime error: cannot use bool value as string value in argument to db.Conn.Insert"),
+      ("c.Rows", "runtime error: type db.Conn has no field or method Rows"),
+    ];
+    for (source, expected) in runtime_errors.iter() {
+      assert_eq!(interp.eval(source), Err(Error::Runtime(expected.to_string())), "{}", source);
+    }
+    let conn = interp.get("c").unwrap();
+    let other = Package::new("host/other").new_type("Conn");
+    assert_eq!(other.unwrap::<Conn>(&conn).err(), Some("cannot use db.Conn value as other.Conn value".to_string()));
+  }
+
+  #[test]
+  fn test_limits() {
+    let mut interp = Interpreter::new();
+    interp.set_limits(Limits { instructions: Some(1000), call_depth: Some(20), ..Default::default() });
+    assert_eq!(interp.eval("for {}"), Err(Error::Limit(Limit::Instructions(1000))));
+    assert_eq!(interp.eval("1 + 1"), Ok(Value::Integer(2)));
+    interp.eval("let down = fn(n) { if n == 0 { return 0 }; return down(n - 1) }").unwrap();
+    assert_eq!(interp.call("down", vec![10.into_value()]), Ok(Value::Integer(0)));
+    let err = interp.call("down", vec![100.into_value()]).unwrap_err();
+    assert_eq!(err, Error::Limit(Limit::CallDepth(20)));
+    assert_eq!(err.to_string(), "call depth limit of 20 exceeded");
+
+    interp.allow_system(false);
+    let err = interp.eval(r#"import "os"; os.ReadFile("/etc/passwd")"#).unwrap_err();
+    assert!(err.to_string().contains("use of package os is not allowed"), "{}", err);
+    interp.allow_system(true);
+    assert!(interp.eval(r#"import "os"; len(os.Args)"#).is_ok());
+
+    let err = interp.eval(r#"import "os"; os.Exit(3)"#).unwrap_err();
+    assert_eq!(err, Error::Exit(3));
+    assert_eq!(err.to_string(), "exit status 3");
+    interp.eval(r#"import "os"; let quit = fn() { os.Exit(4) }"#).unwrap();
+    assert_eq!(interp.call("quit", vec![]), Err(Error::Exit(4)));
+  }
+
+  #[test]
+  fn test_hostile_input() {
+    let mut interp = Interpreter::new();
+    let source = format!("{}1{}", "(".repeat(20000), ")".repeat(20000));
+    let err = interp.eval(&source).unwrap_err();
+    assert!(err.to_string().contains("exceeded max nesting depth"), "{}", err);
+    let err = interp.eval(&"-- ".repeat(3000)).unwrap_err();
+    assert!(err.to_string().contains("too many errors"), "{}", err);
+
+    // dropping a long chain must not recurse once per link
+    interp.eval("type N struct { next *N }; var h *N").unwrap();
+    assert_eq!(interp.eval("for i := 0; i < 100000; i++ { h = &N{h} }; h = nil; 1"), Ok(Value::Integer(1)));
+    assert_eq!(interp.eval("var s []any; for i := 0; i < 100000; i++ { s = []any{s} }; s = nil; 1"), Ok(Value::Integer(1)));
+  }
+}
@@ -0,0 +1,2333 @@
+//! Lowering of a checked program to the IR.
+//!
+//! SSA form is built directly from the AST, after Braun et al., "Simple
+//! and Efficient Construction of Static Single Assignment Form": each
+//! block records the value a variable was last assigned, reads look
+//! through predecessors and place phis where definitions meet, and a
+//! block's phis are completed once it is sealed, that is once all its
+//! predecessors are known.
+//!
+//! Types are as static as the program lets them be. A variable takes the
+//! type of the value it is declared with; when a later assignment does
+//! not fit that type the declaration is widened to `any` and the program
+//! lowered again, until every assignment fits. Parameters and results of
+//! the program's own functions are `any`.
+
+use super::*;
+use crate::ast::visit::{self, Visitor};
+use crate::ast::*;
+use crate::compiler::{is_addressable, receive_operand, universe_type};
+use crate::lexer::unquote;
+use crate::loader::Package;
+use crate::stdlib::{self, Member};
+use crate::types::{self, Field};
+use crate::value::{builtins, Value};
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+/// Lowers packages, in dependency order, that compiled without errors.
+/// Lowering a program with errors gives a module, but not a useful one.
+pub fn lower(packages: &[Package]) -> Module {
+    let mut widened = HashSet::new();
+    loop {
+        let mut lowerer = Lowerer::new(&widened);
+        lowerer.packages(packages);
+        if lowerer.widen.is_subset(&widened) {
+            return lowerer.module;
+        }
+        widened.extend(lowerer.widen);
+    }
+}
+
+/// Index of an SSA variable of the function being lowered.
+type VarId = usize;
+
+/// What a name denotes while lowering.
+#[derive(Debug, Clone)]
+enum Binding {
+    /// A variable held in SSA values.
+    Local(VarId),
+    /// A variable in a cell; the SSA variable holds the pointer.
+    Cell(VarId),
+    /// A variable captured from an enclosing function.
+    Free(usize),
+    Global(usize),
+    Type(Type),
+    /// An imported package, numbered as the compiler numbers them.
+    Package(usize),
+    /// The function being lowered, by the name its `let` gives it.
+    Current,
+}
+
+struct Var {
+    /// Type of the variable's values; a cell holds a pointer to one.
+    ty: Type,
+    cell: bool,
+    /// The declaration, when widening may change the type.
+    decl: Option<usize>,
+}
+
+/// Where `break` and `continue` go; a select has no `continue`.
+struct Loop {
+    exit: BlockId,
+    next: Option<BlockId>,
+}
+
+/// A top-level package compiled earlier, which later ones import.
+struct Loaded {
+    path: String,
+    name: String,
+    members: HashMap<String, Binding>,
+}
+
+/// A function being lowered, with the state of SSA construction.
+struct FnState {
+    func: Function,
+    /// Block instructions go to; None after a jump or return until the
+    /// next block starts.
+    current: Option<BlockId>,
+    vars: Vec<Var>,
+    /// The value of each variable at the end of each block so far.
+    defs: HashMap<(VarId, BlockId), ValueId>,
+    /// Phis placed in blocks not sealed yet, to complete when they are.
+    incomplete: HashMap<BlockId, Vec<(VarId, ValueId)>>,
+    sealed: HashSet<BlockId>,
+    phi_blocks: HashMap<ValueId, BlockId>,
+    /// Trivial phis removed, and the value each stands for.
+    forward: HashMap<ValueId, ValueId>,
+    scopes: Vec<HashMap<String, Binding>>,
+    loops: Vec<Loop>,
+    escapes: HashSet<String>,
+    defers: bool,
+    /// For each free variable, what it captures in the enclosing function.
+    captures: Vec<Binding>,
+    free_decls: Vec<Option<usize>>,
+    /// The named result.
+    result: Option<Binding>,
+    /// Function literals lowered so far that have no name of their own.
+    anonymous: usize,
+}
+
+impl FnState {
+    fn new(name: &str, scan: Scan) -> FnState {
+        FnState {
+            func: Function::new(name),
+            current: Some(BlockId(0)),
+            vars: vec![],
+            defs: HashMap::new(),
+            incomplete: HashMap::new(),
+            sealed: HashSet::from([BlockId(0)]),
+            phi_blocks: HashMap::new(),
+            forward: HashMap::new(),
+            scopes: vec![HashMap::new()],
+            loops: vec![],
+            escapes: scan.escapes,
+            defers: scan.defers,
+            captures: vec![],
+            free_decls: vec![],
+            result: None,
+            anonymous: 0,
+        }
+    }
+
+    /// The block being filled, starting an unreachable one if control
+    /// cannot get here.
+    fn block(&mut self) -> BlockId {
+        match self.current {
+            Some(block) => block,
+            None => {
+                let block = self.func.add_block();
+                self.sealed.insert(block);
+                self.current = Some(block);
+                block
+            }
+        }
+    }
+
+    fn emit(&mut self, inst: Inst, ty: Ty) -> ValueId {
+        let block = self.block();
+        self.func.append(block, inst, ty)
+    }
+
+    /// Ends the block with a terminator, recording the edges it adds.
+    fn terminate(&mut self, inst: Inst) {
+        let block = self.block();
+        for target in inst.targets() {
+            debug_assert!(!self.sealed.contains(&target), "edge into sealed block {}", target);
+            self.func.blocks[target.0].preds.push(block);
+        }
+        self.func.append(block, inst, Ty::Void);
+        self.current = None;
+    }
+
+    fn jump(&mut self, target: BlockId) {
+        self.terminate(Inst::Jump(target));
+    }
+
+    fn is_open(&self) -> bool {
+        self.current.is_some()
+    }
+
+    fn ty(&self, value: ValueId) -> Type {
+        self.func.ty(value).value().cloned().unwrap_or_else(Type::any)
+    }
+
+    fn insert_before_terminator(&mut self, block: BlockId, inst: Inst, ty: Type) -> ValueId {
+        let value = self.func.add_value(inst, Ty::Value(ty));
+        let insts = &self.func.blocks[block.0].insts;
+        let at = match insts.last() {
+            Some(&last) if self.func.values[last.0].inst.is_terminator() => insts.len() - 1,
+            _ => insts.len(),
+        };
+        self.func.blocks[block.0].insts.insert(at, value);
+        value
+    }
+
+    fn var_type(&self, var: VarId) -> Type {
+        let v = &self.vars[var];
+        if v.cell {
+            Type::Pointer(Rc::new(v.ty.clone()))
+        } else {
+            v.ty.clone()
+        }
+    }
+
+    fn write(&mut self, var: VarId, value: ValueId) {
+        let block = self.block();
+        self.defs.insert((var, block), value);
+    }
+
+    fn read(&mut self, var: VarId) -> ValueId {
+        let block = self.block();
+        self.read_in(var, block)
+    }
+
+    fn read_in(&mut self, var: VarId, block: BlockId) -> ValueId {
+        match self.defs.get(&(var, block)) {
+            Some(&value) => resolve(&self.forward, value),
+            None => self.read_recursive(var, block),
+        }
+    }
+
+    fn read_recursive(&mut self, var: VarId, block: BlockId) -> ValueId {
+        let preds = self.func.blocks[block.0].preds.clone();
+        let value = if !self.sealed.contains(&block) {
+            let phi = self.insert_phi(block, var);
+            self.incomplete.entry(block).or_default().push((var, phi));
+            phi
+        } else if preds.len() == 1 {
+            self.read_in(var, preds[0])
+        } else if preds.is_empty() {
+            self.undefined(block, self.var_type(var))
+        } else {
+            let phi = self.insert_phi(block, var);
+            self.defs.insert((var, block), phi);
+            self.add_phi_operands(var, phi)
+        };
+        self.defs.insert((var, block), value);
+        value
+    }
+
+    fn insert_phi(&mut self, block: BlockId, var: VarId) -> ValueId {
+        let phi = self.func.add_value(Inst::Phi(vec![]), Ty::Value(self.var_type(var)));
+        self.func.blocks[block.0].insts.insert(0, phi);
+        self.phi_blocks.insert(phi, block);
+        phi
+    }
+
+    /// The value of a variable read where it has none, which only happens
+    /// in code control cannot reach.
+    fn undefined(&mut self, block: BlockId, ty: Type) -> ValueId {
+        let value = self.func.add_value(Inst::Zero, Ty::Value(ty));
+        let insts = &self.func.blocks[block.0].insts;
+        let at = insts.iter().position(|v| !self.func.values[v.0].inst.is_phi()).unwrap_or(insts.len());
+        self.func.blocks[block.0].insts.insert(at, value);
+        value
+    }
+
+    fn add_phi_operands(&mut self, var: VarId, phi: ValueId) -> ValueId {
+        let block = self.phi_blocks[&phi];
+        for pred in self.func.blocks[block.0].preds.clone() {
+            let value = self.read_in(var, pred);
+            if let Inst::Phi(edges) = &mut self.func.values[phi.0].inst {
+                edges.push((pred, value));
+            }
+        }
+        self.remove_trivial_phi(phi)
+    }
+
+    /// Replaces a phi whose operands are one value, or itself, by that
+    /// value, then the phis that used it if they become trivial in turn.
+    fn remove_trivial_phi(&mut self, phi: ValueId) -> ValueId {
+        let edges = match &self.func.values[phi.0].inst {
+            Inst::Phi(edges) => edges.clone(),
+            _ => return phi,
+        };
+        let mut same = None;
+        for (_, op) in edges {
+            let op = resolve(&self.forward, op);
+            if Some(op) == same || op == phi {
+                continue;
+            }
+            if same.is_some() {
+                return phi;
+            }
+            same = Some(op);
+        }
+        let block = self.phi_blocks[&phi];
+        let same = match same {
+            Some(same) => same,
+            None => self.undefined(block, self.ty(phi)),
+        };
+        let users: Vec<ValueId> = self
+            .phi_blocks
+            .keys()
+            .copied()
+            .filter(|&user| user != phi && !self.forward.contains_key(&user))
+            .filter(|user| self.func.values[user.0].inst.operands().iter().any(|&op| resolve(&self.forward, op) == phi))
+            .collect();
+        self.forward.insert(phi, same);
+        self.func.blocks[block.0].insts.retain(|&v| v != phi);
+        for user in users {
+            if !self.forward.contains_key(&user) {
+                self.remove_trivial_phi(user);
+            }
+        }
+        resolve(&self.forward, same)
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.func.add_block()
+    }
+
+    fn seal(&mut self, block: BlockId) {
+        if !self.sealed.insert(block) {
+            return;
+        }
+        for (var, phi) in self.incomplete.remove(&block).unwrap_or_default() {
+            self.add_phi_operands(var, phi);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned()
+    }
+
+    /// The function, its operands rewritten past removed phis and tidied.
+    fn finish(mut self) -> (Function, Vec<Binding>) {
+        let blocks: Vec<BlockId> = (0..self.func.blocks.len()).map(BlockId).collect();
+        for block in blocks {
+            self.seal(block);
+        }
+        let forward = std::mem::take(&mut self.forward);
+        for data in &mut self.func.values {
+            data.inst.for_each_operand(|v| *v = resolve(&forward, *v));
+        }
+        self.func.compact();
+        (self.func, self.captures)
+    }
+}
+
+/// What lowering a function body needs to know before it starts: names
+/// whose variables must live in cells, because a nested function refers
+/// to them or their address is taken, and whether it defers calls.
+#[derive(Default)]
+struct Scan {
+    escapes: HashSet<String>,
+    defers: bool,
+}
+
+impl Scan {
+    fn function(func: &FunctionLiteral) -> Scan {
+        let mut scan = Scan::default();
+        if let Some(body) = &func.body {
+            scan.visit_block_statement(body);
+        }
+        scan
+    }
+}
+
+/// The variable an address is taken of in `&x`, `&x.f` and `x.f = v`.
+fn root_name(expr: &dyn Expression) -> Option<&str> {
+    let any = expr.as_any();
+    if let Some(ident) = any.downcast_ref::<Identifier>() {
+        return Some(&ident.value);
+    }
+    root_name(any.downcast_ref::<SelectorExpression>()?.left.as_deref()?)
+}
+
+fn field_root(expr: &dyn Expression) -> Option<&str> {
+    expr.as_any().downcast_ref::<SelectorExpression>().and_then(|_| root_name(expr))
+}
+
+impl Visitor for Scan {
+    fn visit_function_literal(&mut self, func: &FunctionLiteral) {
+        // the names are resolved only when the literal is lowered; any
+        // of them may be a variable of this function
+        let mut names = Names::default();
+        visit::walk_function_literal(&mut names, func);
+        self.escapes.extend(names.0);
+    }
+
+    fn visit_defer_statement(&mut self, stmt: &DeferStatement) {
+        self.defers = true;
+        visit::walk_defer_statement(self, stmt);
+    }
+
+    fn visit_prefix_expression(&mut self, expr: &PrefixExpression) {
+        if expr.operator == "&" {
+            if let Some(name) = expr.right.as_deref().and_then(root_name) {
+                self.escapes.insert(name.to_string());
+            }
+        }
+        visit::walk_prefix_expression(self, expr);
+    }
+
+    fn visit_assign_statement(&mut self, stmt: &AssignStatement) {
+        for left in &stmt.left {
+            if let Some(name) = field_root(left.as_ref()) {
+                self.escapes.insert(name.to_string());
+            }
+        }
+        visit::walk_assign_statement(self, stmt);
+    }
+
+    fn visit_range_statement(&mut self, stmt: &RangeStatement) {
+        for target in stmt.key.iter().chain(&stmt.value) {
+            if let Some(name) = field_root(target.as_ref()) {
+                self.escapes.insert(name.to_string());
+            }
+        }
+        visit::walk_range_statement(self, stmt);
+    }
+}
+
+/// Every identifier in a subtree.
+#[derive(Default)]
+struct Names(HashSet<String>);
+
+impl Visitor for Names {
+    fn visit_identifier(&mut self, ident: &Identifier) {
+        self.0.insert(ident.value.clone());
+    }
+}
+
+struct Lowerer<'a> {
+    module: Module,
+    /// Declarations earlier rounds found must hold `any`.
+    widened: &'a HashSet<usize>,
+    /// Declarations this round found an assignment that does not fit.
+    widen: HashSet<usize>,
+    /// Declarations lowered so far, numbering them.
+    decls: usize,
+    global_decls: Vec<Option<usize>>,
+    /// The functions being lowered, the package initializer outermost.
+    fns: Vec<FnState>,
+    /// Prefix of function names: `main`, or the import path.
+    package: String,
+    /// Name of the package being lowered.
+    name: String,
+    loaded: Vec<Loaded>,
+    /// Globals of the package declared before their statements, as a
+    /// statement above refers to them.
+    forward: HashMap<String, usize>,
+    /// The value of the expression visited last, for `expr_inner`.
+    value: Option<ValueId>,
+}
+
+fn any() -> Type {
+    Type::any()
+}
+
+fn pointer(ty: Type) -> Type {
+    Type::Pointer(Rc::new(ty))
+}
+
+fn pointee(ty: &Type) -> Type {
+    match ty {
+        Type::Pointer(elem) => elem.as_ref().clone(),
+        _ => any(),
+    }
+}
+
+fn chan_elem(ty: &Type) -> Type {
+    match ty {
+        Type::Chan(elem) => elem.as_ref().clone(),
+        _ => any(),
+    }
+}
+
+fn nilable(ty: &Type) -> bool {
+    matches!(ty, Type::Nil | Type::Func | Type::Slice(_) | Type::Map(_, _) | Type::Pointer(_) | Type::Chan(_) | Type::Interface(_))
+}
+
+fn numeric(ty: &Type) -> bool {
+    matches!(ty, Type::Int | Type::Byte | Type::Float64)
+}
+
+/// The type of a field or method selected from a value of type `ty`.
+fn field_type(ty: &Type, name: &str) -> Type {
+    let st = match ty {
+        Type::Struct(st) => st,
+        Type::Pointer(elem) => match elem.as_ref() {
+            Type::Struct(st) => st,
+            _ => return any(),
+        },
+        _ => return any(),
+    };
+    let fields = st.fields.borrow();
+    fields.iter().find(|f| f.name == name).map_or_else(any, |f| f.ty.clone())
+}
+
+/// The type of an element of a value of type `ty`, indexed.
+fn elem_type(ty: &Type) -> Type {
+    match ty {
+        Type::Slice(elem) | Type::Map(_, elem) => elem.as_ref().clone(),
+        Type::String => Type::Byte,
+        _ => any(),
+    }
+}
+
+/// A result type as the standard packages spell it.
+fn type_name(name: &str) -> Type {
+    match name.strip_prefix("[]") {
+        Some(elem) => Type::Slice(Rc::new(type_name(elem))),
+        None => universe_type(name).unwrap_or_else(any),
+    }
+}
+
+/// The type of what a builtin call yields, given its arguments.
+fn builtin_result(name: &str, args: &[Type]) -> Ty {
+    match name {
+        "len" | "cap" | "copy" => Ty::Value(Type::Int),
+        "append" => Ty::Value(args.first().cloned().unwrap_or_else(any)),
+        "min" | "max" => match args.split_first() {
+            Some((first, rest)) if rest.iter().all(|t| t.identical(first)) => Ty::Value(first.clone()),
+            _ => Ty::Value(any()),
+        },
+        "recover" => Ty::Value(any()),
+        _ => Ty::Void,
+    }
+}
+
+impl<'a> Lowerer<'a> {
+    fn new(widened: &'a HashSet<usize>) -> Lowerer<'a> {
+        Lowerer {
+            module: Module::default(),
+            widened,
+            widen: HashSet::new(),
+            decls: 0,
+            global_decls: vec![],
+            fns: vec![],
+            package: "main".to_string(),
+            name: "main".to_string(),
+            loaded: vec![],
+            forward: HashMap::new(),
+            value: None,
+        }
+    }
+
+    fn packages(&mut self, packages: &[Package]) {
+        for package in packages {
+            self.package = if package.name == "main" { package.name.clone() } else { package.path.clone() };
+            self.name = package.name.clone();
+            let id = FuncId(self.module.functions.len());
+            let name = format!("{}.init", self.package);
+            self.module.functions.push(Function::new(&name));
+            self.fns.push(FnState::new(&name, Scan::default()));
+            self.declare_package(package);
+            for file in &package.files {
+                for stmt in &file.program.statements {
+                    self.statement(stmt.as_ref());
+                }
+            }
+            self.f().terminate(Inst::Return(None));
+            let state = self.fns.pop().unwrap();
+            let members: HashMap<String, Binding> = state.scopes[0]
+                .iter()
+                .filter(|(_, b)| matches!(b, Binding::Global(_) | Binding::Type(_)))
+                .map(|(name, b)| (name.clone(), b.clone()))
+                .collect();
+            if package.name == "main" {
+                if let Some(Binding::Global(index)) = members.get("main") {
+                    self.module.main = Some(*index);
+                }
+            }
+            let (func, _) = state.finish();
+            self.module.functions[id.0] = func;
+            self.module.inits.push(id);
+            self.loaded.push(Loaded { path: package.path.clone(), name: package.name.clone(), members });
+        }
+    }
+
+    /// Declares what the package's statements may use before the one
+    /// declaring it: its types, and the variables a statement further up
+    /// refers to. Those are lowered with the type they are declared with,
+    /// or one `let` gives a function, and else as `any`, widened.
+    fn declare_package(&mut self, package: &Package) {
+        self.forward.clear();
+        let statements: Vec<&dyn Statement> =
+            package.files.iter().flat_map(|file| file.program.statements.iter().map(|stmt| stmt.as_ref())).collect();
+        let types: Vec<(&Identifier, &dyn Expression)> = statements
+            .iter()
+            .filter_map(|stmt| stmt.as_any().downcast_ref::<TypeStatement>())
+            .filter_map(|stmt| Some((stmt.name.as_ref()?, stmt.ty.as_deref()?)))
+            .collect();
+        for &(name, ty) in &types {
+            self.declare_type(&name.value, ty);
+        }
+        for &(name, ty) in &types {
+            if let (Some(decl), Some(Binding::Type(Type::Struct(st)))) = (ty.as_any().downcast_ref::<StructType>(), self.lookup(&name.value)) {
+                self.resolve_fields(decl, &st);
+            }
+        }
+
+        let mut used = Names::default();
+        for stmt in statements {
+            let any_stmt = stmt.as_any();
+            // the type, and whether a later assignment may widen it
+            let declared: Vec<(&Identifier, Option<Type>, bool)> = if let Some(s) = any_stmt.downcast_ref::<LetStatement>() {
+                let func = s.value.as_deref().is_some_and(|value| value.as_any().is::<FunctionLiteral>());
+                s.name.iter().map(|name| (name, func.then_some(Type::Func), func)).collect()
+            } else if let Some(s) = any_stmt.downcast_ref::<VarStatement>() {
+                let ty = s.ty.as_deref().map(|ty| self.resolve_type(ty));
+                s.names.iter().map(|name| (name, ty.clone(), false)).collect()
+            } else {
+                vec![]
+            };
+            for (name, ty, widens) in declared {
+                if !used.0.contains(&name.value) || self.forward.contains_key(&name.value) {
+                    continue;
+                }
+                let (ty, decl) = match ty {
+                    Some(ty) if widens => {
+                        let decl = self.decls;
+                        self.decls += 1;
+                        (if self.widened.contains(&decl) { any() } else { ty }, Some(decl))
+                    }
+                    Some(ty) => (ty, None),
+                    None => (any(), None),
+                };
+                let index = self.new_global(&name.value, ty, decl);
+                self.forward.insert(name.value.clone(), index);
+                self.bind(&name.value, Binding::Global(index));
+            }
+            used.visit_statement(stmt);
+        }
+    }
+
+    fn f(&mut self) -> &mut FnState {
+        self.fns.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, inst: Inst, ty: Type) -> ValueId {
+        self.f().emit(inst, Ty::Value(ty))
+    }
+
+    fn emit_void(&mut self, inst: Inst) {
+        self.f().emit(inst, Ty::Void);
+    }
+
+    fn ty(&mut self, value: ValueId) -> Type {
+        self.f().ty(value)
+    }
+
+    fn constant(&mut self, c: Const) -> ValueId {
+        let ty = match &c {
+            Const::Nil => Type::Nil,
+            Const::Bool(_) => Type::Bool,
+            Const::Int(_) => Type::Int,
+            Const::Float(_) => Type::Float64,
+            Const::String(_) => Type::String,
+        };
+        self.emit(Inst::Const(c), ty)
+    }
+
+    fn nil(&mut self) -> ValueId {
+        self.constant(Const::Nil)
+    }
+
+    fn zero(&mut self, ty: &Type) -> ValueId {
+        let c = match ty {
+            Type::Bool => Const::Bool(false),
+            Type::Int | Type::Byte => Const::Int(0),
+            Type::Float64 => Const::Float(0.0),
+            Type::String => Const::String(Rc::from(&b""[..])),
+            ty if nilable(ty) => Const::Nil,
+            _ => return self.emit(Inst::Zero, ty.clone()),
+        };
+        self.emit(Inst::Const(c), ty.clone())
+    }
+
+    fn bind(&mut self, name: &str, binding: Binding) {
+        self.f().scopes.last_mut().unwrap().insert(name.to_string(), binding);
+    }
+
+    fn push_scope(&mut self) {
+        self.f().scopes.push(HashMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.f().scopes.pop();
+    }
+
+    // Conversions
+
+    /// A constant of another type with the same value, for an untyped
+    /// constant used where a typed value is wanted.
+    fn retyped(&self, value: ValueId, to: &Type) -> Option<Inst> {
+        let state = self.fns.last().unwrap();
+        match (state.func.inst(value), to) {
+            (Inst::Const(Const::Int(n)), Type::Byte) => Some(Inst::Const(Const::Int(*n))),
+            (Inst::Const(Const::Int(n)), Type::Float64) => Some(Inst::Const(Const::Float(*n as f64))),
+            // a nil that has a type already, such as a nil *P, converts
+            // to an interface holding it, which is not a nil interface
+            (Inst::Const(Const::Nil), to) if nilable(to) && matches!(state.ty(value), Type::Nil) => {
+                Some(Inst::Const(Const::Nil))
+            }
+            _ => None,
+        }
+    }
+
+    /// The instruction converting `value` to `to`, unless it has that type.
+    fn conversion(&mut self, value: ValueId, to: &Type) -> Option<Inst> {
+        if self.ty(value).identical(to) {
+            return None;
+        }
+        Some(self.retyped(value, to).unwrap_or(Inst::Convert(value)))
+    }
+
+    fn coerce(&mut self, value: ValueId, to: &Type) -> ValueId {
+        match self.conversion(value, to) {
+            Some(inst) => self.emit(inst, to.clone()),
+            None => value,
+        }
+    }
+
+    /// `coerce` at the end of `block`, which may have been left already.
+    fn coerce_at(&mut self, block: BlockId, value: ValueId, to: &Type) -> ValueId {
+        match self.conversion(value, to) {
+            Some(inst) => self.f().insert_before_terminator(block, inst, to.clone()),
+            None => value,
+        }
+    }
+
+    fn assignable(&mut self, value: ValueId, to: &Type) -> bool {
+        let from = self.ty(value);
+        from.identical(to) || to.is_interface() || (matches!(from, Type::Nil) && nilable(to)) || self.retyped(value, to).is_some()
+    }
+
+    /// Converts a value assigned to a variable of type `to`. When it does
+    /// not fit and the variable's declaration `decl` gave it that type,
+    /// the declaration is widened for the next round.
+    fn fit(&mut self, value: ValueId, to: &Type, decl: Option<usize>) -> ValueId {
+        if !self.assignable(value, to) {
+            if let Some(decl) = decl {
+                self.widen.insert(decl);
+            }
+        }
+        self.coerce(value, to)
+    }
+
+    /// Brings two operands to one type: an untyped constant to the other
+    /// operand's, a concrete value to the interface it is compared with,
+    /// and else both to `any`.
+    fn unify(&mut self, a: ValueId, b: ValueId) -> (ValueId, ValueId) {
+        let (ta, tb) = (self.ty(a), self.ty(b));
+        if ta.identical(&tb) {
+            (a, b)
+        } else if self.retyped(a, &tb).is_some() {
+            (self.coerce(a, &tb), b)
+        } else if self.retyped(b, &ta).is_some() || ta.is_interface() {
+            (a, self.coerce(b, &ta))
+        } else if tb.is_interface() {
+            (self.coerce(a, &tb), b)
+        } else {
+            (self.coerce(a, &any()), self.coerce(b, &any()))
+        }
+    }
+
+    /// Joins the values control brings to the current block from each of
+    /// the blocks in `incoming`.
+    fn merge(&mut self, incoming: Vec<(BlockId, ValueId)>) -> ValueId {
+        let first = match incoming.first() {
+            Some(&(_, first)) => first,
+            None => return self.nil(),
+        };
+        if incoming.iter().all(|&(_, v)| v == first) {
+            return first;
+        }
+        let types: Vec<Type> = incoming.iter().map(|&(_, v)| self.ty(v)).collect();
+        let typed: Vec<&Type> = types.iter().filter(|t| !matches!(t, Type::Nil)).collect();
+        let ty = match typed.first() {
+            None => Type::Nil,
+            Some(&first) if typed.iter().all(|t| t.identical(first)) && (typed.len() == types.len() || nilable(first)) => first.clone(),
+            Some(_) => any(),
+        };
+        let mut edges = vec![];
+        for (block, value) in incoming {
+            let value = self.coerce_at(block, value, &ty);
+            edges.push((block, value));
+        }
+        let block = self.f().block();
+        let phi = self.f().func.add_value(Inst::Phi(edges), Ty::Value(ty));
+        self.f().func.blocks[block.0].insts.insert(0, phi);
+        phi
+    }
+
+    /// A condition: a bool, or an interface holding one.
+    fn cond(&mut self, expr: &dyn Expression) -> ValueId {
+        let value = self.expr(expr);
+        match self.ty(value) {
+            Type::Bool | Type::Interface(_) => value,
+            _ => self.coerce(value, &any()),
+        }
+    }
+
+    // Names
+
+    fn lookup(&self, name: &str) -> Option<Binding> {
+        self.fns.iter().rev().find_map(|state| state.lookup(name))
+    }
+
+    /// What `name` denotes in the function being lowered, capturing it
+    /// from the enclosing functions it is declared in.
+    fn resolve(&mut self, name: &str) -> Option<Binding> {
+        let n = self.fns.len();
+        let (level, binding) = (0..n).rev().find_map(|i| self.fns[i].lookup(name).map(|b| (i, b)))?;
+        if level == n - 1 || matches!(binding, Binding::Global(_) | Binding::Type(_) | Binding::Package(_)) {
+            return Some(binding);
+        }
+        let mut binding = binding;
+        for k in level + 1..n {
+            binding = self.capture(k, name, binding);
+        }
+        Some(binding)
+    }
+
+    /// Makes the function at `level` capture `binding` of the function
+    /// enclosing it.
+    fn capture(&mut self, level: usize, name: &str, binding: Binding) -> Binding {
+        let outer = &self.fns[level - 1];
+        let (ty, decl) = match &binding {
+            Binding::Cell(var) | Binding::Local(var) => (outer.vars[*var].ty.clone(), outer.vars[*var].decl),
+            Binding::Free(i) => (pointee(&outer.func.free[*i].ty), outer.free_decls[*i]),
+            _ => (Type::Func, None),
+        };
+        let state = &mut self.fns[level];
+        let index = state.func.free.len();
+        state.func.free.push(Variable { name: name.to_string(), ty: pointer(ty) });
+        state.captures.push(binding);
+        state.free_decls.push(decl);
+        state.scopes[0].insert(name.to_string(), Binding::Free(index));
+        Binding::Free(index)
+    }
+
+    /// A pointer to what `binding` holds, for a closure capturing it.
+    fn capture_pointer(&mut self, binding: &Binding) -> ValueId {
+        match binding {
+            Binding::Cell(var) => self.f().read(*var),
+            Binding::Free(i) => {
+                let ty = self.f().func.free[*i].ty.clone();
+                self.emit(Inst::FreeVar(*i), ty)
+            }
+            _ => {
+                // a variable no cell was made for, or the function
+                // itself: capture a copy
+                let value = self.load_binding(binding);
+                self.heap(value)
+            }
+        }
+    }
+
+    /// A new cell holding `value`.
+    fn heap(&mut self, value: ValueId) -> ValueId {
+        let ty = self.ty(value);
+        let ptr = self.emit(Inst::Alloc, pointer(ty));
+        self.emit_void(Inst::Store(ptr, value));
+        ptr
+    }
+
+    fn global(&mut self, index: usize) -> ValueId {
+        let ty = self.module.globals[index].ty.clone();
+        self.emit(Inst::Global(index), pointer(ty))
+    }
+
+    fn load_binding(&mut self, binding: &Binding) -> ValueId {
+        match binding {
+            Binding::Local(var) => self.f().read(*var),
+            Binding::Cell(var) => {
+                let ptr = self.f().read(*var);
+                let ty = self.f().vars[*var].ty.clone();
+                self.emit(Inst::Load(ptr), ty)
+            }
+            Binding::Free(_) | Binding::Global(_) => {
+                let ptr = self.address_of(binding);
+                let ty = pointee(&self.ty(ptr));
+                self.emit(Inst::Load(ptr), ty)
+            }
+            Binding::Current => self.emit(Inst::Current, Type::Func),
+            Binding::Type(_) | Binding::Package(_) => self.nil(),
+        }
+    }
+
+    fn store_binding(&mut self, binding: &Binding, value: ValueId) {
+        let (ty, decl) = match binding {
+            Binding::Local(var) | Binding::Cell(var) => {
+                let v = &self.f().vars[*var];
+                (v.ty.clone(), v.decl)
+            }
+            Binding::Free(i) => {
+                let state = self.f();
+                (pointee(&state.func.free[*i].ty), state.free_decls[*i])
+            }
+            Binding::Global(i) => (self.module.globals[*i].ty.clone(), self.global_decls[*i]),
+            Binding::Current | Binding::Type(_) | Binding::Package(_) => return,
+        };
+        let value = self.fit(value, &ty, decl);
+        match binding {
+            Binding::Local(var) => self.f().write(*var, value),
+            _ => {
+                let ptr = self.address_of(binding);
+                self.emit_void(Inst::Store(ptr, value));
+            }
+        }
+    }
+
+    /// A pointer to the variable `binding` denotes.
+    fn address_of(&mut self, binding: &Binding) -> ValueId {
+        match binding {
+            Binding::Cell(var) => self.f().read(*var),
+            Binding::Free(i) => {
+                let ty = self.f().func.free[*i].ty.clone();
+                self.emit(Inst::FreeVar(*i), ty)
+            }
+            Binding::Global(i) => self.global(*i),
+            _ => {
+                let value = self.load_binding(binding);
+                self.heap(value)
+            }
+        }
+    }
+
+    /// Declares `name` with the value it starts with: a global in a
+    /// package initializer, else a variable of the function, in a cell if
+    /// it escapes. Without an explicit type it takes the value's.
+    fn declare(&mut self, name: &str, value: ValueId, explicit: Option<Type>) {
+        self.declare_variable(name, value, explicit, false);
+    }
+
+    fn declare_variable(&mut self, name: &str, value: ValueId, explicit: Option<Type>, cell: bool) {
+        if name == "_" {
+            return;
+        }
+        if self.fns.len() == 1 && self.f().scopes.len() == 1 {
+            if let Some(&index) = self.forward.get(name) {
+                let ty = self.module.globals[index].ty.clone();
+                let value = self.fit(value, &ty, self.global_decls[index]);
+                let ptr = self.global(index);
+                self.emit_void(Inst::Store(ptr, value));
+                return;
+            }
+        }
+        let decl = self.decls;
+        self.decls += 1;
+        let (ty, decl) = match explicit {
+            Some(ty) => (ty, None),
+            None if self.widened.contains(&decl) => (any(), Some(decl)),
+            None => (self.ty(value), Some(decl)),
+        };
+        let value = self.fit(value, &ty, decl);
+        if self.fns.len() == 1 {
+            let index = self.new_global(name, ty, decl);
+            let ptr = self.global(index);
+            self.emit_void(Inst::Store(ptr, value));
+            self.bind(name, Binding::Global(index));
+            return;
+        }
+        let cell = cell || self.f().escapes.contains(name);
+        let state = self.f();
+        let var = state.vars.len();
+        state.vars.push(Var { ty, cell, decl });
+        if cell {
+            let ptr = self.heap(value);
+            self.f().write(var, ptr);
+            self.bind(name, Binding::Cell(var));
+        } else {
+            self.f().write(var, value);
+            self.bind(name, Binding::Local(var));
+        }
+    }
+
+    /// Adds a global of the package for the variable `name`.
+    fn new_global(&mut self, name: &str, ty: Type, decl: Option<usize>) -> usize {
+        let base = format!("{}.{}", self.package, name);
+        let count = self.module.globals.iter().filter(|g| g.name == base || g.name.starts_with(&format!("{}#", base))).count();
+        let unique = if count == 0 { base } else { format!("{}#{}", base, count) };
+        let index = self.module.globals.len();
+        self.module.globals.push(Variable { name: unique, ty });
+        self.global_decls.push(decl);
+        index
+    }
+
+    // Types
+
+    fn resolve_type(&mut self, expr: &dyn Expression) -> Type {
+        let any_expr = expr.as_any();
+        if let Some(ident) = any_expr.downcast_ref::<Identifier>() {
+            return match self.lookup(&ident.value) {
+                Some(Binding::Type(ty)) => ty,
+                Some(_) => any(),
+                None => universe_type(&ident.value).unwrap_or_else(any),
+            };
+        }
+        if let Some((_, _, Member::Type(ty))) = self.std_member(expr) {
+            return ty();
+        }
+        if let Some(Binding::Type(ty)) = self.loaded_member(expr) {
+            return ty;
+        }
+        if let Some(prefix) = any_expr.downcast_ref::<PrefixExpression>() {
+            if let (Some(right), "*") = (&prefix.right, prefix.operator.as_str()) {
+                return pointer(self.resolve_type(right.as_ref()));
+            }
+        }
+        let elem = |this: &mut Self, e: &Option<Box<dyn Expression>>| match e {
+            Some(e) => this.resolve_type(e.as_ref()),
+            None => any(),
+        };
+        if let Some(ty) = any_expr.downcast_ref::<SliceType>() {
+            return Type::Slice(Rc::new(elem(self, &ty.elem)));
+        }
+        if let Some(ty) = any_expr.downcast_ref::<MapType>() {
+            let key = elem(self, &ty.key);
+            return Type::Map(Rc::new(key), Rc::new(elem(self, &ty.value)));
+        }
+        if let Some(ty) = any_expr.downcast_ref::<ChanType>() {
+            return Type::Chan(Rc::new(elem(self, &ty.elem)));
+        }
+        if let Some(decl) = any_expr.downcast_ref::<StructType>() {
+            let st = Rc::new(types::StructType { name: None, fields: RefCell::new(vec![]) });
+            self.resolve_fields(decl, &st);
+            return Type::Struct(st);
+        }
+        any()
+    }
+
+    fn resolve_fields(&mut self, decl: &StructType, st: &types::StructType) {
+        let mut fields = vec![];
+        for field in &decl.fields {
+            let ty = match &field.ty {
+                Some(ty) => self.resolve_type(ty.as_ref()),
+                None => any(),
+            };
+            for name in &field.names {
+                fields.push(Field { name: name.value.clone(), ty: ty.clone() });
+            }
+        }
+        *st.fields.borrow_mut() = fields;
+    }
+
+    fn is_type(&self, expr: &dyn Expression) -> bool {
+        let any_expr = expr.as_any();
+        if let Some(ident) = any_expr.downcast_ref::<Identifier>() {
+            return match self.lookup(&ident.value) {
+                Some(binding) => matches!(binding, Binding::Type(_)),
+                None => universe_type(&ident.value).is_some(),
+            };
+        }
+        if let Some((_, _, Member::Type(_))) = self.std_member(expr) {
+            return true;
+        }
+        if let Some(member) = self.loaded_member(expr) {
+            return matches!(member, Binding::Type(_));
+        }
+        if let Some(prefix) = any_expr.downcast_ref::<PrefixExpression>() {
+            return prefix.operator == "*" && prefix.right.as_ref().is_some_and(|r| self.is_type(r.as_ref()));
+        }
+        any_expr.is::<SliceType>()
+            || any_expr.is::<MapType>()
+            || any_expr.is::<ChanType>()
+            || any_expr.is::<StructType>()
+            || any_expr.is::<InterfaceType>()
+    }
+
+    // Packages
+
+    /// The package index and member name of a selector `pkg.Name`.
+    fn package_member(&self, expr: &dyn Expression) -> Option<(usize, String)> {
+        let selector = expr.as_any().downcast_ref::<SelectorExpression>()?;
+        let left = selector.left.as_deref()?.as_any().downcast_ref::<Identifier>()?;
+        match self.lookup(&left.value)? {
+            Binding::Package(index) => Some((index, selector.selector.as_ref()?.value.clone())),
+            _ => None,
+        }
+    }
+
+    /// The member of a standard package `expr` selects.
+    fn std_member(&self, expr: &dyn Expression) -> Option<(&'static stdlib::Package, &'static str, &'static Member)> {
+        let (index, name) = self.package_member(expr)?;
+        let pkg = *stdlib::PACKAGES.get(index)?;
+        pkg.members.iter().find(|(n, _)| *n == name).map(|(n, m)| (pkg, *n, m))
+    }
+
+    /// The member of a package of the program `expr` selects.
+    fn loaded_member(&self, expr: &dyn Expression) -> Option<Binding> {
+        let (index, name) = self.package_member(expr)?;
+        let loaded = self.loaded.get(index.checked_sub(stdlib::PACKAGES.len())?)?;
+        loaded.members.get(&name).cloned()
+    }
+
+    /// The result types of a call to a package function with several.
+    fn multiple_results(&self, expr: &dyn Expression) -> Option<Vec<Type>> {
+        let function = expr.as_any().downcast_ref::<CallExpression>()?.function.as_deref()?;
+        match self.std_member(function)? {
+            (_, _, Member::Func(_, results)) if results.len() > 1 => Some(results.iter().map(|r| type_name(r)).collect()),
+            _ => None,
+        }
+    }
+
+    fn package_value(&mut self, pkg: &'static stdlib::Package, name: &'static str, member: &Member) -> ValueId {
+        match member {
+            Member::Func(_, _) | Member::Source(_) => self.emit(Inst::Package(pkg.path, name), Type::Func),
+            Member::Value(value) => match value() {
+                Value::Integer(n) => self.constant(Const::Int(n)),
+                Value::Float(x) => self.constant(Const::Float(x)),
+                Value::Boolean(b) => self.constant(Const::Bool(b)),
+                Value::String(s) => self.constant(Const::String(s)),
+                _ => self.emit(Inst::Package(pkg.path, name), any()),
+            },
+            Member::Var(_) | Member::Type(_) => self.emit(Inst::Package(pkg.path, name), any()),
+        }
+    }
+
+    // Statements
+
+    fn statement(&mut self, stmt: &dyn Statement) {
+        self.visit_statement(stmt);
+    }
+
+    fn block(&mut self, block: &BlockStatement) {
+        self.push_scope();
+        for stmt in &block.statements {
+            self.statement(stmt.as_ref());
+        }
+        self.pop_scope();
+    }
+
+    /// Lowers a block used as a value, giving its last expression when
+    /// `want` asks for it.
+    fn value_block(&mut self, block: Option<&BlockStatement>, want: bool) -> Option<ValueId> {
+        let block = block?;
+        self.push_scope();
+        let mut value = None;
+        for (i, stmt) in block.statements.iter().enumerate() {
+            let last = match stmt.as_any().downcast_ref::<ExpressionStatement>().and_then(|s| s.expression.as_deref()) {
+                Some(expr) if want && i + 1 == block.statements.len() => expr,
+                _ => {
+                    self.statement(stmt.as_ref());
+                    continue;
+                }
+            };
+            value = Some(self.expr(last));
+        }
+        self.pop_scope();
+        value
+    }
+
+    fn let_statement(&mut self, stmt: &LetStatement) {
+        let name = match &stmt.name {
+            Some(name) => name,
+            None => return,
+        };
+        let value = match stmt.value.as_deref() {
+            Some(value) => match value.as_any().downcast_ref::<FunctionLiteral>() {
+                Some(func) => self.function(func, Some(&name.value)),
+                None => self.expr(value),
+            },
+            None => self.nil(),
+        };
+        // declared after the value so `let x = x + 1` reads the outer x
+        self.declare(&name.value, value, None);
+    }
+
+    fn return_statement(&mut self, stmt: &ReturnStatement) {
+        if self.fns.len() == 1 {
+            return;
+        }
+        let value = stmt.return_value.as_deref().map(|value| self.expr(value));
+        self.return_with(value);
+    }
+
+    /// Returns from a function of the program: a named result takes the
+    /// value before the deferred calls run, and is returned after them.
+    fn return_with(&mut self, value: Option<ValueId>) {
+        let result = self.f().result.clone();
+        let value = match (&result, value) {
+            (Some(result), value) => {
+                if let Some(value) = value {
+                    self.store_binding(result, value);
+                }
+                None
+            }
+            (None, Some(value)) => Some(self.coerce(value, &any())),
+            (None, None) => Some(self.emit(Inst::Const(Const::Nil), any())),
+        };
+        if self.f().defers {
+            self.emit_void(Inst::RunDefers);
+        }
+        let value = match (value, &result) {
+            (Some(value), _) => value,
+            (None, Some(result)) => {
+                let value = self.load_binding(result);
+                self.coerce(value, &any())
+            }
+            (None, None) => unreachable!(),
+        };
+        self.f().terminate(Inst::Return(Some(value)));
+    }
+
+    fn var_statement(&mut self, stmt: &VarStatement) {
+        let ty = stmt.ty.as_deref().map(|ty| self.resolve_type(ty));
+        if stmt.values.is_empty() {
+            let ty = match ty {
+                Some(ty) => ty,
+                None => return,
+            };
+            for name in &stmt.names {
+                let zero = self.zero(&ty);
+                self.declare(&name.value, zero, Some(ty.clone()));
+            }
+            return;
+        }
+        let values = self.values(&stmt.values, stmt.names.len());
+        for (name, value) in stmt.names.iter().zip(values) {
+            let value = match &ty {
+                Some(ty) => self.coerce(value, ty),
+                None => value,
+            };
+            self.declare(&name.value, value, ty.clone());
+        }
+    }
+
+    fn type_statement(&mut self, stmt: &TypeStatement) {
+        let (name, ty) = match (&stmt.name, &stmt.ty) {
+            (Some(name), Some(ty)) => (name, ty),
+            _ => return,
+        };
+        // `declare_package` declared the package's own
+        if self.fns.len() == 1 && self.f().scopes.len() == 1 {
+            return;
+        }
+        if let Some(Type::Struct(st)) = self.declare_type(&name.value, ty.as_ref()) {
+            if let Some(decl) = ty.as_any().downcast_ref::<StructType>() {
+                self.resolve_fields(decl, &st);
+            }
+        }
+    }
+
+    /// Binds `name` to a new struct type without fields yet, or interface
+    /// type, as `ty` declares.
+    fn declare_type(&mut self, name: &str, ty: &dyn Expression) -> Option<Type> {
+        let qualified = if self.name != "main" { format!("{}.{}", self.name, name) } else { name.to_string() };
+        let ty = if ty.as_any().is::<StructType>() {
+            Type::Struct(Rc::new(types::StructType { name: Some(qualified), fields: RefCell::new(vec![]) }))
+        } else if ty.as_any().is::<InterfaceType>() {
+            Type::Interface(Rc::new(types::InterfaceType { name: Some(qualified), methods: vec![] }))
+        } else {
+            return None;
+        };
+        self.bind(name, Binding::Type(ty.clone()));
+        Some(ty)
+    }
+
+    fn assign_statement(&mut self, stmt: &AssignStatement) {
+        match stmt.operator.as_str() {
+            ":=" => {
+                let names: Vec<&str> = stmt
+                    .left
+                    .iter()
+                    .filter_map(|left| left.as_any().downcast_ref::<Identifier>())
+                    .map(|ident| ident.value.as_str())
+                    .collect();
+                if names.len() != stmt.left.len() {
+                    return;
+                }
+                let values = self.values(&stmt.right, names.len());
+                for ((name, left), value) in names.iter().zip(&stmt.left).zip(values).rev() {
+                    if self.f().scopes.last().unwrap().contains_key(*name) {
+                        self.store(left.as_ref(), value);
+                    } else {
+                        self.declare(name, value, None);
+                    }
+                }
+            }
+            "=" => {
+                let values = self.values(&stmt.right, stmt.left.len());
+                for (left, value) in stmt.left.iter().zip(values).rev() {
+                    self.store(left.as_ref(), value);
+                }
+            }
+            op => {
+                let target = match stmt.left.first() {
+                    Some(target) => target.as_ref(),
+                    None => return,
+                };
+                let op = match op {
+                    "++" | "+=" => BinOp::Add,
+                    "--" | "-=" => BinOp::Sub,
+                    "*=" => BinOp::Mul,
+                    "/=" => BinOp::Div,
+                    _ => BinOp::Rem,
+                };
+                let current = self.expr(target);
+                let operand = match stmt.right.first() {
+                    Some(value) => self.expr(value.as_ref()),
+                    None => self.constant(Const::Int(1)),
+                };
+                let value = self.binary(op, current, operand);
+                self.store(target, value);
+            }
+        }
+    }
+
+    /// Stores `value` into a variable, `_`, an index expression, a field
+    /// or a dereference.
+    fn store(&mut self, target: &dyn Expression, value: ValueId) {
+        let any_target = target.as_any();
+        if let Some(ident) = any_target.downcast_ref::<Identifier>() {
+            if let Some(binding) = self.resolve(&ident.value) {
+                self.store_binding(&binding, value);
+            }
+            return;
+        }
+        if let Some(index) = any_target.downcast_ref::<IndexExpression>() {
+            let (x, i) = self.index_operands(index);
+            let value = match self.ty(x) {
+                Type::Slice(elem) | Type::Map(_, elem) => self.coerce(value, &elem),
+                _ => value,
+            };
+            self.emit_void(Inst::SetIndex(x, i, value));
+            return;
+        }
+        if let Some(member) = self.loaded_member(target) {
+            self.store_binding(&member, value);
+            return;
+        }
+        if is_addressable(target) {
+            let ptr = self.address(target);
+            let ty = pointee(&self.ty(ptr));
+            let value = self.coerce(value, &ty);
+            self.emit_void(Inst::Store(ptr, value));
+        }
+    }
+
+    fn for_statement(&mut self, stmt: &ForStatement) {
+        self.push_scope();
+        if let Some(init) = &stmt.init {
+            self.statement(init.as_ref());
+        }
+        // variables declared by the init statement, copied into fresh
+        // variables for every iteration
+        let mut per_iteration = vec![];
+        if let Some(assign) = stmt.init.as_ref().and_then(|i| i.as_any().downcast_ref::<AssignStatement>()) {
+            if assign.operator == ":=" {
+                for left in &assign.left {
+                    if let Some(Binding::Cell(var)) =
+                        left.as_any().downcast_ref::<Identifier>().and_then(|ident| self.f().lookup(&ident.value))
+                    {
+                        per_iteration.push(var);
+                    }
+                }
+            }
+        }
+
+        let header = self.f().new_block();
+        self.f().jump(header);
+        self.f().current = Some(header);
+        let exit = self.f().new_block();
+        if let Some(condition) = &stmt.condition {
+            let cond = self.cond(condition.as_ref());
+            let body = self.f().new_block();
+            self.f().terminate(Inst::Branch(cond, body, exit));
+            self.f().seal(body);
+            self.f().current = Some(body);
+        }
+        let next = self.f().new_block();
+        self.f().loops.push(Loop { exit, next: Some(next) });
+        if let Some(body) = &stmt.body {
+            self.block(body);
+        }
+        self.f().loops.pop();
+        if self.f().is_open() {
+            self.f().jump(next);
+        }
+        self.f().seal(next);
+        self.f().current = Some(next);
+        for var in per_iteration {
+            let ptr = self.f().read(var);
+            let ty = self.f().vars[var].ty.clone();
+            let value = self.emit(Inst::Load(ptr), ty);
+            let fresh = self.heap(value);
+            self.f().write(var, fresh);
+        }
+        if let Some(post) = &stmt.post {
+            self.statement(post.as_ref());
+        }
+        self.f().jump(header);
+        self.f().seal(header);
+        self.f().seal(exit);
+        self.f().current = Some(exit);
+        self.pop_scope();
+    }
+
+    fn range_statement(&mut self, stmt: &RangeStatement) {
+        let x = match &stmt.x {
+            Some(x) => self.expr(x.as_ref()),
+            None => self.nil(),
+        };
+        // a range over a channel receives into the key
+        let (key_ty, value_ty) = match self.ty(x) {
+            Type::Slice(elem) => (Type::Int, elem.as_ref().clone()),
+            Type::String => (Type::Int, Type::Int),
+            Type::Map(key, elem) => (key.as_ref().clone(), elem.as_ref().clone()),
+            Type::Int => (Type::Int, any()),
+            Type::Chan(elem) => (elem.as_ref().clone(), any()),
+            _ => (any(), any()),
+        };
+        let iter = self.f().emit(Inst::Range(x), Ty::Iter);
+        let header = self.f().new_block();
+        self.f().jump(header);
+        self.f().current = Some(header);
+        let next = self.f().emit(Inst::Next(iter), Ty::Tuple(vec![Type::Bool, key_ty.clone(), value_ty.clone()]));
+        let ok = self.emit(Inst::Extract(next, 0), Type::Bool);
+        let body = self.f().new_block();
+        let exit = self.f().new_block();
+        self.f().terminate(Inst::Branch(ok, body, exit));
+        self.f().seal(body);
+        self.f().current = Some(body);
+
+        self.push_scope();
+        for (target, index, ty) in [(&stmt.value, 2, value_ty), (&stmt.key, 1, key_ty)] {
+            let target = match target {
+                Some(target) => target.as_ref(),
+                None => continue,
+            };
+            let value = self.emit(Inst::Extract(next, index), ty);
+            match (stmt.operator.as_str(), target.as_any().downcast_ref::<Identifier>()) {
+                (":=", Some(ident)) => self.declare(&ident.value, value, None),
+                ("=", _) => self.store(target, value),
+                _ => {}
+            }
+        }
+        self.f().loops.push(Loop { exit, next: Some(header) });
+        if let Some(body) = &stmt.body {
+            self.block(body);
+        }
+        self.f().loops.pop();
+        self.pop_scope();
+        if self.f().is_open() {
+            self.f().jump(header);
+        }
+        self.f().seal(header);
+        self.f().seal(exit);
+        self.f().current = Some(exit);
+    }
+
+    fn branch_statement(&mut self, stmt: &BranchStatement) {
+        let loops = &self.f().loops;
+        let target = if stmt.token.literal == "break" {
+            loops.last().map(|l| l.exit)
+        } else {
+            loops.iter().rev().find_map(|l| l.next)
+        };
+        if let Some(target) = target {
+            self.f().jump(target);
+        }
+    }
+
+    /// `go` or, when `go` is false, `defer`: the function and arguments
+    /// are evaluated now, the call runs later.
+    fn go_statement(&mut self, call: Option<&dyn Expression>, go: bool) {
+        let call = match call.and_then(|c| c.as_any().downcast_ref::<CallExpression>()) {
+            Some(call) => call,
+            None => return,
+        };
+        let function = match call.function.as_deref() {
+            Some(function) => function,
+            None => return,
+        };
+        let (callee, boxed) = self.callee(function);
+        let args = self.arguments(&call.arguments, boxed);
+        self.emit_void(if go { Inst::Go(callee, args) } else { Inst::Defer(callee, args) });
+    }
+
+    fn send_statement(&mut self, stmt: &SendStatement) {
+        let (channel, value) = match (&stmt.channel, &stmt.value) {
+            (Some(channel), Some(value)) => (channel, value),
+            _ => return,
+        };
+        let channel = self.expr(channel.as_ref());
+        let value = self.expr(value.as_ref());
+        let elem = chan_elem(&self.ty(channel));
+        let value = self.coerce(value, &elem);
+        self.emit_void(Inst::Send(channel, value));
+    }
+
+    fn select_statement(&mut self, stmt: &SelectStatement) {
+        let mut cases = vec![];
+        let mut chosen = vec![];
+        let mut default = None;
+        let mut received = vec![];
+        for (i, clause) in stmt.cases.iter().enumerate() {
+            let comm = match &clause.comm {
+                Some(comm) => comm.as_ref(),
+                None => {
+                    default = Some(i);
+                    continue;
+                }
+            };
+            let any_comm = comm.as_any();
+            if let Some(send) = any_comm.downcast_ref::<SendStatement>() {
+                let (channel, value) = match (&send.channel, &send.value) {
+                    (Some(channel), Some(value)) => (channel, value),
+                    _ => continue,
+                };
+                let chan = self.expr(channel.as_ref());
+                let value = self.expr(value.as_ref());
+                let elem = chan_elem(&self.ty(chan));
+                let value = self.coerce(value, &elem);
+                cases.push(SelectCase { chan, send: Some(value) });
+                chosen.push((i, vec![], ""));
+                continue;
+            }
+            let (left, operator, right): (Vec<&dyn Expression>, &str, _) =
+                if let Some(stmt) = any_comm.downcast_ref::<ExpressionStatement>() {
+                    (vec![], "", stmt.expression.as_deref())
+                } else if let Some(assign) = any_comm.downcast_ref::<AssignStatement>() {
+                    (assign.left.iter().map(|l| l.as_ref()).collect(), assign.operator.as_str(), assign.right.first().map(|r| r.as_ref()))
+                } else {
+                    continue;
+                };
+            let channel = match right.and_then(receive_operand) {
+                Some(channel) => channel,
+                None => continue,
+            };
+            let chan = self.expr(channel);
+            received.push(chan_elem(&self.ty(chan)));
+            cases.push(SelectCase { chan, send: None });
+            chosen.push((i, left, operator));
+        }
+        let value_ty = match received.split_first() {
+            Some((first, rest)) if rest.iter().all(|t| t.identical(first)) => first.clone(),
+            _ => any(),
+        };
+        let select = self.f().emit(Inst::Select(cases, default.is_some()), Ty::Tuple(vec![Type::Int, value_ty.clone(), Type::Bool]));
+        let index = self.emit(Inst::Extract(select, 0), Type::Int);
+        let value = self.emit(Inst::Extract(select, 1), value_ty);
+        let ok = self.emit(Inst::Extract(select, 2), Type::Bool);
+
+        // the VM numbers the default case after the others
+        chosen.extend(default.map(|i| (i, vec![], "")));
+        let end = self.f().new_block();
+        self.f().loops.push(Loop { exit: end, next: None });
+        let count = chosen.len();
+        for (n, (i, left, operator)) in chosen.into_iter().enumerate() {
+            let body = self.f().new_block();
+            let mut next_case = None;
+            if n + 1 < count {
+                let constant = self.constant(Const::Int(n as i64));
+                let matches = self.emit(Inst::Binary(BinOp::Eq, index, constant), Type::Bool);
+                let next = self.f().new_block();
+                self.f().terminate(Inst::Branch(matches, body, next));
+                self.f().seal(next);
+                next_case = Some(next);
+            } else {
+                self.f().jump(body);
+            }
+            self.f().seal(body);
+            self.f().current = Some(body);
+
+            self.push_scope();
+            for (target, value) in left.iter().zip([value, ok]).rev() {
+                match target.as_any().downcast_ref::<Identifier>() {
+                    Some(ident) if operator == ":=" => self.declare(&ident.value, value, None),
+                    _ if operator == "=" => self.store(*target, value),
+                    _ => {}
+                }
+            }
+            for stmt in &stmt.cases[i].body {
+                self.statement(stmt.as_ref());
+            }
+            self.pop_scope();
+            if self.f().is_open() {
+                self.f().jump(end);
+            }
+            self.f().current = next_case;
+        }
+        if count == 0 {
+            self.f().jump(end);
+        }
+        self.f().loops.pop();
+        self.f().seal(end);
+        self.f().current = Some(end);
+    }
+
+    fn import_statement(&mut self, stmt: &ImportStatement) {
+        for spec in &stmt.specs {
+            let path = match &spec.path {
+                Some(path) => path.value.as_str(),
+                None => continue,
+            };
+            let loaded = self.loaded.iter().position(|p| p.path == path);
+            let (index, own_name) = match (stdlib::lookup(path), loaded) {
+                (Some(index), _) => (index, stdlib::PACKAGES[index].name().to_string()),
+                (None, Some(i)) => (stdlib::PACKAGES.len() + i, self.loaded[i].name.clone()),
+                (None, None) => continue,
+            };
+            match spec.name.as_ref().map_or(own_name.as_str(), |name| name.value.as_str()) {
+                "_" | "." => {}
+                name => self.bind(name, Binding::Package(index)),
+            }
+        }
+    }
+
+    // Functions
+
+    /// Stack trace name for a function literal: `main.f` when a top-level
+    /// `let` binds it, else numbered within the enclosing function.
+    fn function_name(&mut self, name: Option<&str>) -> String {
+        match name {
+            Some(name) if self.fns.len() == 1 => format!("{}.{}", self.package, name),
+            _ => {
+                let state = self.f();
+                state.anonymous += 1;
+                format!("{}.func{}", state.func.name, state.anonymous)
+            }
+        }
+    }
+
+    /// Lowers a function literal to a function of the module and yields
+    /// a closure of it.
+    fn function(&mut self, func: &FunctionLiteral, name: Option<&str>) -> ValueId {
+        let trace_name = self.function_name(name);
+        let id = FuncId(self.module.functions.len());
+        self.module.functions.push(Function::new(&trace_name));
+        let mut state = FnState::new(&trace_name, Scan::function(func));
+        state.func.params = func.parameters.iter().map(|p| Variable { name: p.value.clone(), ty: any() }).collect();
+        state.func.result = Some(any());
+        self.fns.push(state);
+
+        if let Some(name) = name {
+            self.bind(name, Binding::Current);
+        }
+        for (i, param) in func.parameters.iter().enumerate() {
+            let value = self.emit(Inst::Param(i), any());
+            self.declare(&param.value, value, Some(any()));
+        }
+        let results: Vec<(&Identifier, Option<&dyn Expression>)> =
+            func.results.iter().flat_map(|f| f.names.iter().map(move |name| (name, f.ty.as_deref()))).collect();
+        if let [(name, ty)] = results.as_slice() {
+            // `(T)` alone is an unnamed result type
+            if ty.is_some() || !self.is_type(*name) {
+                let ty = ty.map_or_else(any, |ty| self.resolve_type(ty));
+                let zero = self.zero(&ty);
+                // the recover block returns it, so it must be in memory
+                let cell = self.f().defers;
+                self.declare_variable(&name.value, zero, Some(ty), cell);
+                self.f().result = self.f().lookup(&name.value);
+            }
+        }
+
+        let mut implicit = None;
+        if let Some(body) = &func.body {
+            // a function with a named result returns it, not its last value
+            let want = self.f().result.is_none();
+            implicit = self.value_block(Some(body), want);
+        }
+        if self.f().is_open() {
+            self.return_with(implicit);
+        }
+        if self.f().defers {
+            self.recover_block();
+        }
+
+        let state = self.fns.pop().unwrap();
+        let (function, captures) = state.finish();
+        self.module.functions[id.0] = function;
+        let captured = captures.iter().map(|binding| self.capture_pointer(binding)).collect();
+        self.emit(Inst::Closure(id, captured), Type::Func)
+    }
+
+    /// The block a function that defers calls resumes in after one of
+    /// them recovers: it returns the named result, or nil.
+    fn recover_block(&mut self) {
+        let block = self.f().new_block();
+        self.f().sealed.insert(block);
+        self.f().current = Some(block);
+        let value = match self.f().result.clone() {
+            Some(Binding::Cell(var)) => {
+                let state = self.f();
+                let ptr = resolve(&state.forward, state.defs[&(var, BlockId(0))]);
+                let ty = state.vars[var].ty.clone();
+                let value = self.emit(Inst::Load(ptr), ty);
+                self.coerce(value, &any())
+            }
+            _ => self.emit(Inst::Const(Const::Nil), any()),
+        };
+        self.f().terminate(Inst::Return(Some(value)));
+        self.f().func.recover = Some(block);
+    }
+
+    // Expressions
+
+    /// Lowers an expression for its value.
+    fn expr(&mut self, expr: &dyn Expression) -> ValueId {
+        let value = self.expr_inner(expr);
+        match self.f().func.ty(value) {
+            Ty::Value(_) => value,
+            _ => self.nil(),
+        }
+    }
+
+    /// Lowers an expression whose value is not used.
+    fn effect(&mut self, expr: &dyn Expression) {
+        match expr.as_any().downcast_ref::<IfExpression>() {
+            Some(expr) => {
+                self.if_expression(expr, false);
+            }
+            None => {
+                self.expr_inner(expr);
+            }
+        }
+    }
+
+    /// Lowers an expression, which for a call may yield no value or a
+    /// tuple of them.
+    fn expr_inner(&mut self, expr: &dyn Expression) -> ValueId {
+        self.visit_expression(expr);
+        match self.value.take() {
+            Some(value) => value,
+            None => panic!("cannot lower {}", expr.string()),
+        }
+    }
+
+    fn identifier(&mut self, name: &str) -> ValueId {
+        if let Some(binding) = self.resolve(name) {
+            return self.load_binding(&binding);
+        }
+        match builtins::lookup(name) {
+            Some(index) => self.emit(Inst::Builtin(builtins::BUILTINS[index].name), Type::Func),
+            None => self.nil(),
+        }
+    }
+
+    fn prefix(&mut self, expr: &PrefixExpression) -> ValueId {
+        let right = match &expr.right {
+            Some(right) => right.as_ref(),
+            None => return self.nil(),
+        };
+        match expr.operator.as_str() {
+            "&" => self.address(right),
+            "!" => {
+                let value = self.cond(right);
+                self.emit(Inst::Unary(UnOp::Not, value), Type::Bool)
+            }
+            "-" => {
+                let value = self.expr(right);
+                let value = match self.ty(value) {
+                    ty if numeric(&ty) || ty.is_interface() => value,
+                    _ => self.coerce(value, &any()),
+                };
+                let ty = self.ty(value);
+                self.emit(Inst::Unary(UnOp::Neg, value), ty)
+            }
+            "*" => {
+                let ptr = self.expr(right);
+                let ty = pointee(&self.ty(ptr));
+                self.emit(Inst::Load(ptr), ty)
+            }
+            "<-" => {
+                let channel = self.expr(right);
+                let ty = chan_elem(&self.ty(channel));
+                self.emit(Inst::Recv(channel, false), ty)
+            }
+            _ => self.nil(),
+        }
+    }
+
+    fn infix(&mut self, expr: &InfixExpression) -> ValueId {
+        let (left, right) = match (&expr.left, &expr.right) {
+            (Some(left), Some(right)) => (left.as_ref(), right.as_ref()),
+            _ => return self.nil(),
+        };
+        let op = match expr.operator.as_str() {
+            "&&" | "||" => return self.logical(expr.operator == "&&", left, right),
+            "+" => BinOp::Add,
+            "-" => BinOp::Sub,
+            "*" => BinOp::Mul,
+            "/" => BinOp::Div,
+            "%" => BinOp::Rem,
+            "==" => BinOp::Eq,
+            "!=" => BinOp::Ne,
+            "<" => BinOp::Lt,
+            "<=" => BinOp::Le,
+            ">" => BinOp::Gt,
+            ">=" => BinOp::Ge,
+            _ => return self.nil(),
+        };
+        // the VM evaluates a < b as b > a, right operand first
+        let (a, b) = if matches!(op, BinOp::Lt | BinOp::Le) {
+            let b = self.expr(right);
+            (self.expr(left), b)
+        } else {
+            let a = self.expr(left);
+            (a, self.expr(right))
+        };
+        self.binary(op, a, b)
+    }
+
+    fn binary(&mut self, op: BinOp, a: ValueId, b: ValueId) -> ValueId {
+        let (mut a, mut b) = self.unify(a, b);
+        let ty = self.ty(a);
+        let valid = if op.is_comparison() {
+            true
+        } else {
+            numeric(&ty) || ty.is_interface() || (op == BinOp::Add && matches!(ty, Type::String))
+        };
+        if !valid {
+            a = self.coerce(a, &any());
+            b = self.coerce(b, &any());
+        }
+        let ty = if op.is_comparison() { Type::Bool } else { self.ty(a) };
+        self.emit(Inst::Binary(op, a, b), ty)
+    }
+
+    /// `a && b` and `a || b`, evaluating `b` only when `a` does not
+    /// decide the result.
+    fn logical(&mut self, and: bool, left: &dyn Expression, right: &dyn Expression) -> ValueId {
+        let a = self.cond(left);
+        let decided = self.constant(Const::Bool(!and));
+        let from = self.f().block();
+        let rhs = self.f().new_block();
+        let join = self.f().new_block();
+        let branch = if and { Inst::Branch(a, rhs, join) } else { Inst::Branch(a, join, rhs) };
+        self.f().terminate(branch);
+        self.f().seal(rhs);
+        self.f().current = Some(rhs);
+        let b = self.expr(right);
+        let end = self.f().block();
+        self.f().jump(join);
+        self.f().seal(join);
+        self.f().current = Some(join);
+        self.merge(vec![(from, decided), (end, b)])
+    }
+
+    /// An if expression; its value, when `want` asks for it, is that of
+    /// the last expression of the branch taken, or nil.
+    fn if_expression(&mut self, expr: &IfExpression, want: bool) -> Option<ValueId> {
+        let cond = match &expr.condition {
+            Some(condition) => self.cond(condition.as_ref()),
+            None => self.constant(Const::Bool(false)),
+        };
+        let then = self.f().new_block();
+        let otherwise = (expr.alternative.is_some() || want).then(|| self.f().new_block());
+        let join = self.f().new_block();
+        self.f().terminate(Inst::Branch(cond, then, otherwise.unwrap_or(join)));
+        let mut incoming = vec![];
+        for (block, body) in [(Some(then), expr.consequence.as_ref()), (otherwise, expr.alternative.as_ref())] {
+            let block = match block {
+                Some(block) => block,
+                None => continue,
+            };
+            self.f().seal(block);
+            self.f().current = Some(block);
+            let value = self.value_block(body, want);
+            if self.f().is_open() {
+                if want {
+                    let value = value.unwrap_or_else(|| self.nil());
+                    let end = self.f().block();
+                    incoming.push((end, value));
+                }
+                self.f().jump(join);
+            }
+        }
+        self.f().seal(join);
+        self.f().current = Some(join);
+        want.then(|| self.merge(incoming))
+    }
+
+    /// The function a call or `go`/`defer` statement calls, and whether
+    /// the arguments are boxed to `any` as the program's own functions
+    /// take them. Builtins and package functions take them as they are.
+    fn callee(&mut self, function: &dyn Expression) -> (ValueId, bool) {
+        if let Some(ident) = function.as_any().downcast_ref::<Identifier>() {
+            if self.lookup(&ident.value).is_none() {
+                if let Some(index) = builtins::lookup(&ident.value) {
+                    return (self.emit(Inst::Builtin(builtins::BUILTINS[index].name), Type::Func), false);
+                }
+            }
+        }
+        if let Some((pkg, name, member)) = self.std_member(function) {
+            let boxed = !matches!(member, Member::Func(_, _));
+            return (self.package_value(pkg, name, member), boxed);
+        }
+        (self.expr(function), true)
+    }
+
+    /// The arguments of a call; `f(g())` passes all the results of `g`.
+    fn arguments(&mut self, args: &[Box<dyn Expression>], boxed: bool) -> Vec<ValueId> {
+        let values = match args {
+            [arg] if self.multiple_results(arg.as_ref()).is_some() => {
+                let n = self.multiple_results(arg.as_ref()).map_or(0, |r| r.len());
+                self.values(args, n)
+            }
+            _ => args.iter().map(|arg| self.expr(arg.as_ref())).collect(),
+        };
+        if !boxed {
+            return values;
+        }
+        values.into_iter().map(|v| self.coerce(v, &any())).collect()
+    }
+
+    fn call(&mut self, call: &CallExpression) -> ValueId {
+        let function = match call.function.as_deref() {
+            Some(function) => function,
+            None => return self.nil(),
+        };
+        if self.is_type(function) {
+            // T(x) is a conversion
+            let ty = self.resolve_type(function);
+            let value = match call.arguments.first() {
+                Some(arg) => self.expr(arg.as_ref()),
+                None => self.nil(),
+            };
+            return self.coerce(value, &ty);
+        }
+        let builtin = function
+            .as_any()
+            .downcast_ref::<Identifier>()
+            .filter(|ident| self.lookup(&ident.value).is_none())
+            .map(|ident| ident.value.as_str());
+        if let (Some(name @ ("make" | "new")), Some(first)) = (builtin, call.arguments.first()) {
+            // the first argument is a type
+            let ty = self.resolve_type(first.as_ref());
+            if name == "new" {
+                return self.emit(Inst::Alloc, pointer(ty));
+            }
+            let sizes = call.arguments[1..].iter().map(|arg| self.expr(arg.as_ref())).collect();
+            return self.emit(Inst::Make(sizes), ty);
+        }
+        let (callee, boxed) = self.callee(function);
+        let args = self.arguments(&call.arguments, boxed);
+        let ty = match (builtin, self.std_member(function)) {
+            (Some(name), _) if builtins::lookup(name).is_some() => {
+                let types: Vec<Type> = args.iter().map(|&arg| self.ty(arg)).collect();
+                builtin_result(name, &types)
+            }
+            (_, Some((_, _, Member::Func(_, results)))) if results.len() > 1 => {
+                Ty::Tuple(results.iter().map(|r| type_name(r)).collect())
+            }
+            _ => Ty::Value(any()),
+        };
+        self.f().emit(Inst::Call(callee, args, call.ellipsis.is_some()), ty)
+    }
+
+    /// The values of the right-hand side of an assignment to `n`
+    /// variables. One call of a function with `n` results gives them all;
+    /// one map index, type assertion or receive for two variables gives
+    /// the value and whether it was there.
+    fn values(&mut self, values: &[Box<dyn Expression>], n: usize) -> Vec<ValueId> {
+        if let [value] = values {
+            if let Some(types) = self.multiple_results(value.as_ref()) {
+                let tuple = self.expr_inner(value.as_ref());
+                return (0..n).map(|i| self.emit(Inst::Extract(tuple, i), types.get(i).cloned().unwrap_or_else(any))).collect();
+            }
+            if n == 2 {
+                let any_value = value.as_any();
+                let tuple = if let Some(index) = any_value.downcast_ref::<IndexExpression>() {
+                    let (x, i) = self.index_operands(index);
+                    let ty = elem_type(&self.ty(x));
+                    Some(self.f().emit(Inst::Index(x, i, true), Ty::Tuple(vec![ty, Type::Bool])))
+                } else if let Some(assert) = any_value.downcast_ref::<TypeAssertExpression>() {
+                    let (x, ty) = self.assert_operands(assert);
+                    Some(self.f().emit(Inst::Assert(x, true), Ty::Tuple(vec![ty, Type::Bool])))
+                } else if let Some(channel) = receive_operand(value.as_ref()) {
+                    let channel = self.expr(channel);
+                    let ty = chan_elem(&self.ty(channel));
+                    Some(self.f().emit(Inst::Recv(channel, true), Ty::Tuple(vec![ty, Type::Bool])))
+                } else {
+                    None
+                };
+                if let Some(tuple) = tuple {
+                    let ty = match self.f().func.ty(tuple) {
+                        Ty::Tuple(types) => types[0].clone(),
+                        _ => any(),
+                    };
+                    let value = self.emit(Inst::Extract(tuple, 0), ty);
+                    let ok = self.emit(Inst::Extract(tuple, 1), Type::Bool);
+                    return vec![value, ok];
+                }
+            }
+        }
+        let mut lowered: Vec<ValueId> = values.iter().map(|value| self.expr(value.as_ref())).collect();
+        while lowered.len() < n {
+            lowered.push(self.nil());
+        }
+        lowered
+    }
+
+    /// The operands of `x[i]`, checking `i` is in range when `x` is a
+    /// slice or string.
+    fn index_operands(&mut self, index: &IndexExpression) -> (ValueId, ValueId) {
+        let x = match &index.left {
+            Some(left) => self.expr(left.as_ref()),
+            None => self.nil(),
+        };
+        let i = match &index.index {
+            Some(i) => self.expr(i.as_ref()),
+            None => self.nil(),
+        };
+        match self.ty(x) {
+            Type::Map(key, _) => (x, self.coerce(i, &key)),
+            Type::Slice(_) | Type::String => {
+                let i = self.coerce(i, &Type::Int);
+                let len = self.emit(Inst::Builtin("len"), Type::Func);
+                let n = self.emit(Inst::Call(len, vec![x], false), Type::Int);
+                self.emit_void(Inst::BoundsCheck(i, n));
+                (x, i)
+            }
+            _ => (x, i),
+        }
+    }
+
+    fn assert_operands(&mut self, assert: &TypeAssertExpression) -> (ValueId, Type) {
+        let x = match &assert.left {
+            Some(left) => self.expr(left.as_ref()),
+            None => self.nil(),
+        };
+        let ty = assert.ty.as_deref().map_or_else(any, |ty| self.resolve_type(ty));
+        (x, ty)
+    }
+
+    fn slice(&mut self, expr: &SliceExpression) -> ValueId {
+        let x = match &expr.left {
+            Some(left) => self.expr(left.as_ref()),
+            None => self.nil(),
+        };
+        let low = expr.low.as_deref().map(|low| self.expr(low));
+        let high = expr.high.as_deref().map(|high| self.expr(high));
+        let ty = match self.ty(x) {
+            ty @ (Type::Slice(_) | Type::String) => ty,
+            _ => any(),
+        };
+        self.emit(Inst::Slice(x, low, high), ty)
+    }
+
+    fn selector(&mut self, expr: &SelectorExpression) -> ValueId {
+        if let Some(member) = self.loaded_member(expr) {
+            return self.load_binding(&member);
+        }
+        if let Some((pkg, name, member)) = self.std_member(expr) {
+            return self.package_value(pkg, name, member);
+        }
+        let left = match &expr.left {
+            Some(left) => self.expr(left.as_ref()),
+            None => self.nil(),
+        };
+        let name = expr.selector.as_ref().map(|s| s.value.clone()).unwrap_or_default();
+        let ty = field_type(&self.ty(left), &name);
+        self.emit(Inst::Field(left, name), ty)
+    }
+
+    /// A pointer to what `expr` denotes.
+    fn address(&mut self, expr: &dyn Expression) -> ValueId {
+        let any_expr = expr.as_any();
+        if let Some(ident) = any_expr.downcast_ref::<Identifier>() {
+            return match self.resolve(&ident.value) {
+                Some(binding) => self.address_of(&binding),
+                None => self.nil(),
+            };
+        }
+        if let Some(member) = self.loaded_member(expr) {
+            return self.address_of(&member);
+        }
+        if let Some(selector) = any_expr.downcast_ref::<SelectorExpression>() {
+            let base = match selector.left.as_deref() {
+                Some(left) if is_addressable(left) => {
+                    let ptr = self.address(left);
+                    match pointee(&self.ty(ptr)) {
+                        Type::Struct(_) => ptr,
+                        ty => self.emit(Inst::Load(ptr), ty),
+                    }
+                }
+                Some(left) => self.expr(left),
+                None => self.nil(),
+            };
+            let name = selector.selector.as_ref().map(|s| s.value.clone()).unwrap_or_default();
+            let ty = field_type(&self.ty(base), &name);
+            return self.emit(Inst::FieldAddr(base, name), pointer(ty));
+        }
+        if let Some(index) = any_expr.downcast_ref::<IndexExpression>() {
+            let (x, i) = self.index_operands(index);
+            let ty = elem_type(&self.ty(x));
+            return self.emit(Inst::IndexAddr(x, i), pointer(ty));
+        }
+        if let Some(prefix) = any_expr.downcast_ref::<PrefixExpression>().filter(|p| p.operator == "*") {
+            return match &prefix.right {
+                Some(right) => self.expr(right.as_ref()),
+                None => self.nil(),
+            };
+        }
+        if let Some(lit) = any_expr.downcast_ref::<CompositeLiteral>() {
+            let value = self.composite(lit, None);
+            return self.heap(value);
+        }
+        self.nil()
+    }
+
+    /// A composite literal whose type is `ty` when it does not spell one
+    /// out itself.
+    fn composite(&mut self, lit: &CompositeLiteral, ty: Option<Type>) -> ValueId {
+        let ty = match (&lit.ty, ty) {
+            (Some(t), _) => self.resolve_type(t.as_ref()),
+            (None, Some(ty)) => ty,
+            (None, None) => return self.nil(),
+        };
+        let mut pairs = vec![];
+        match &ty {
+            Type::Struct(st) => {
+                let fields = st.fields.borrow().clone();
+                for (i, element) in lit.elements.iter().enumerate() {
+                    let (index, value) = match element.as_any().downcast_ref::<KeyValueExpression>() {
+                        Some(kv) => {
+                            let key = kv.key.as_deref().and_then(|k| k.as_any().downcast_ref::<Identifier>());
+                            match key.and_then(|key| st.field_index(&key.value)) {
+                                Some(index) => (index, kv.value.as_deref()),
+                                None => continue,
+                            }
+                        }
+                        None if i < fields.len() => (i, Some(element.as_ref())),
+                        None => break,
+                    };
+                    let key = self.constant(Const::Int(index as i64));
+                    let value = self.element(value, &fields[index].ty);
+                    pairs.push((key, value));
+                }
+            }
+            Type::Slice(elem) => {
+                let mut next = 0;
+                for element in &lit.elements {
+                    let (index, value) = match element.as_any().downcast_ref::<KeyValueExpression>() {
+                        Some(kv) => {
+                            let key = kv.key.as_deref().and_then(|k| k.as_any().downcast_ref::<IntegerLiteral>());
+                            match key.and_then(|k| k.value) {
+                                Some(index) => (index, kv.value.as_deref()),
+                                None => continue,
+                            }
+                        }
+                        None => (next, Some(element.as_ref())),
+                    };
+                    next = index + 1;
+                    let key = self.constant(Const::Int(index));
+                    let value = self.element(value, elem);
+                    pairs.push((key, value));
+                }
+            }
+            Type::Map(key_ty, elem) => {
+                for element in &lit.elements {
+                    if let Some(kv) = element.as_any().downcast_ref::<KeyValueExpression>() {
+                        let key = self.element(kv.key.as_deref(), key_ty);
+                        let value = self.element(kv.value.as_deref(), elem);
+                        pairs.push((key, value));
+                    }
+                }
+            }
+            _ => return self.nil(),
+        }
+        self.emit(Inst::Composite(pairs), ty)
+    }
+
+    /// An element of a composite literal of element type `ty`, whose own
+    /// composite literals may leave the type out.
+    fn element(&mut self, element: Option<&dyn Expression>, ty: &Type) -> ValueId {
+        let element = match element {
+            Some(element) => element,
+            None => return self.zero(ty),
+        };
+        let value = match element.as_any().downcast_ref::<CompositeLiteral>() {
+            Some(lit) if lit.ty.is_none() => match ty {
+                // {...} for a *T element means &T{...}
+                Type::Pointer(elem) => {
+                    let value = self.composite(lit, Some(elem.as_ref().clone()));
+                    self.heap(value)
+                }
+                _ => self.composite(lit, Some(ty.clone())),
+            },
+            _ => self.expr(element),
+        };
+        self.coerce(value, ty)
+    }
+}
+
+/// Statements lower where they are; expressions leave their value in
+/// `value`.
+impl<'a> Visitor for Lowerer<'a> {
+    fn visit_let_statement(&mut self, stmt: &LetStatement) {
+        self.let_statement(stmt);
+    }
+
+    fn visit_return_statement(&mut self, stmt: &ReturnStatement) {
+        self.return_statement(stmt);
+    }
+
+    fn visit_expression_statement(&mut self, stmt: &ExpressionStatement) {
+        if let Some(expr) = &stmt.expression {
+            self.effect(expr.as_ref());
+        }
+    }
+
+    fn visit_block_statement(&mut self, block: &BlockStatement) {
+        self.block(block);
+    }
+
+    fn visit_var_statement(&mut self, stmt: &VarStatement) {
+        self.var_statement(stmt);
+    }
+
+    fn visit_type_statement(&mut self, stmt: &TypeStatement) {
+        self.type_statement(stmt);
+    }
+
+    fn visit_assign_statement(&mut self, stmt: &AssignStatement) {
+        self.assign_statement(stmt);
+    }
+
+    fn visit_for_statement(&mut self, stmt: &ForStatement) {
+        self.for_statement(stmt);
+    }
+
+    fn visit_range_statement(&mut self, stmt: &RangeStatement) {
+        self.range_statement(stmt);
+    }
+
+    fn visit_branch_statement(&mut self, stmt: &BranchStatement) {
+        self.branch_statement(stmt);
+    }
+
+    fn visit_go_statement(&mut self, stmt: &GoStatement) {
+        self.go_statement(stmt.call.as_deref(), true);
+    }
+
+    fn visit_defer_statement(&mut self, stmt: &DeferStatement) {
+        self.go_statement(stmt.call.as_deref(), false);
+    }
+
+    fn visit_send_statement(&mut self, stmt: &SendStatement) {
+        self.send_statement(stmt);
+    }
+
+    fn visit_select_statement(&mut self, stmt: &SelectStatement) {
+        self.select_statement(stmt);
+    }
+
+    fn visit_import_statement(&mut self, stmt: &ImportStatement) {
+        self.import_statement(stmt);
+    }
+
+    // the loader has the package's name already
+    fn visit_package_clause(&mut self, _stmt: &PackageClause) {}
+
+    fn visit_identifier(&mut self, ident: &Identifier) {
+        self.value = Some(self.identifier(&ident.value));
+    }
+
+    fn visit_integer_literal(&mut self, lit: &IntegerLiteral) {
+        self.value = Some(self.constant(Const::Int(lit.value.unwrap_or(0))));
+    }
+
+    fn visit_float_literal(&mut self, lit: &FloatLiteral) {
+        self.value = Some(self.constant(Const::Float(lit.value.unwrap_or(0.0))));
+    }
+
+    fn visit_string_literal(&mut self, lit: &StringLiteral) {
+        let bytes = unquote(&lit.token.literal).unwrap_or_default();
+        self.value = Some(self.constant(Const::String(Rc::from(bytes))));
+    }
+
+    fn visit_boolean(&mut self, lit: &Boolean) {
+        self.value = Some(self.constant(Const::Bool(lit.value)));
+    }
+
+    fn visit_prefix_expression(&mut self, expr: &PrefixExpression) {
+        self.value = Some(self.prefix(expr));
+    }
+
+    fn visit_infix_expression(&mut self, expr: &InfixExpression) {
+        self.value = Some(self.infix(expr));
+    }
+
+    fn visit_if_expression(&mut self, expr: &IfExpression) {
+        let value = self.if_expression(expr, true);
+        self.value = Some(value.unwrap_or_else(|| self.nil()));
+    }
+
+    fn visit_function_literal(&mut self, func: &FunctionLiteral) {
+        self.value = Some(self.function(func, None));
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression) {
+        self.value = Some(self.call(call));
+    }
+
+    fn visit_index_expression(&mut self, expr: &IndexExpression) {
+        let (x, i) = self.index_operands(expr);
+        let ty = elem_type(&self.ty(x));
+        self.value = Some(self.emit(Inst::Index(x, i, false), ty));
+    }
+
+    fn visit_slice_expression(&mut self, expr: &SliceExpression) {
+        self.value = Some(self.slice(expr));
+    }
+
+    fn visit_selector_expression(&mut self, expr: &SelectorExpression) {
+        self.value = Some(self.selector(expr));
+    }
+
+    fn visit_type_assert_expression(&mut self, expr: &TypeAssertExpression) {
+        let (x, ty) = self.assert_operands(expr);
+        self.value = Some(self.emit(Inst::Assert(x, false), ty));
+    }
+
+    fn visit_composite_literal(&mut self, lit: &CompositeLiteral) {
+        self.value = Some(self.composite(lit, None));
+    }
+
+    // Types and key:value pairs are no values. The compiler reports them
+    // where a value is wanted, and a program with errors lowers to nil
+    // for them.
+
+    fn visit_key_value_expression(&mut self, _expr: &KeyValueExpression) {
+        self.value = Some(self.nil());
+    }
+
+    fn visit_slice_type(&mut self, _ty: &SliceType) {
+        self.value = Some(self.nil());
+    }
+
+    fn visit_map_type(&mut self, _ty: &MapType) {
+        self.value = Some(self.nil());
+    }
+
+    fn visit_chan_type(&mut self, _ty: &ChanType) {
+        self.value = Some(self.nil());
+    }
+
+    fn visit_struct_type(&mut self, _ty: &StructType) {
+        self.value = Some(self.nil());
+    }
+
+    fn visit_interface_type(&mut self, _ty: &InterfaceType) {
+        self.value = Some(self.nil());
+    }
+}
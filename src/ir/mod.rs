@@ -0,0 +1,790 @@
+//! The SSA form of a program, between the AST and code generation.
+//!
+//! A `Module` holds the program's globals and functions. A function is a
+//! list of basic blocks of instructions; every instruction that yields a
+//! value defines it exactly once, with a static type, and blocks end in
+//! an explicit jump, branch or return. Values flowing into a block from
+//! several predecessors meet in phi instructions at its start.
+//!
+//! Variables that closures capture or whose address is taken live in
+//! heap cells (`alloc`), which closures receive as free variables; every
+//! other variable is an SSA value.
+
+use crate::stdlib::strconv;
+use crate::types::Type;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+pub mod lower;
+pub mod opt;
+pub mod verify;
+
+#[cfg(test)]
+pub(crate) mod test;
+
+pub use lower::lower;
+pub use verify::verify;
+
+/// A value defined by an instruction, printed `vN`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ValueId(pub usize);
+
+/// A basic block, printed `bN`. The entry block is `b0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+/// A function of the module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FuncId(pub usize);
+
+impl fmt::Display for ValueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "b{}", self.0)
+    }
+}
+
+/// The type of what an instruction yields.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ty {
+    /// Nothing: stores, sends, terminators and calls of builtins without
+    /// results.
+    Void,
+    Value(Type),
+    /// The results of a call to a package function with several, taken
+    /// apart with `extract`.
+    Tuple(Vec<Type>),
+    /// The iterator of a range loop.
+    Iter,
+}
+
+impl Ty {
+    /// The value type, if the instruction yields one value.
+    pub fn value(&self) -> Option<&Type> {
+        match self {
+            Ty::Value(ty) => Some(ty),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Const {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(Rc<[u8]>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl BinOp {
+    /// Reports whether the operator compares its operands, yielding a
+    /// bool, rather than computing a value of their type.
+    pub fn is_comparison(self) -> bool {
+        !matches!(self, BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::Div => "div",
+            BinOp::Rem => "rem",
+            BinOp::Eq => "eq",
+            BinOp::Ne => "ne",
+            BinOp::Lt => "lt",
+            BinOp::Le => "le",
+            BinOp::Gt => "gt",
+            BinOp::Ge => "ge",
+        }
+    }
+}
+
+/// One case of a `select`: a receive from `chan`, or a send of `send`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectCase {
+    pub chan: ValueId,
+    pub send: Option<ValueId>,
+}
+
+/// An instruction. Which operands it takes, and the type of the value it
+/// yields, follow the VM operation it stands for; values of interface
+/// type are operated on dynamically.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+    Const(Const),
+    /// The zero value of the instruction's type.
+    Zero,
+    /// The function's parameter of that index.
+    Param(usize),
+    /// The pointer the closure captured at that index.
+    FreeVar(usize),
+    /// A pointer to the module global of that index.
+    Global(usize),
+    /// The closure being executed, for a function that refers to itself
+    /// by the name a `let` gave it.
+    Current,
+    /// A closure of the function over pointers to the variables it
+    /// captures.
+    Closure(FuncId, Vec<ValueId>),
+    Builtin(&'static str),
+    /// A member of a standard library package, by path and name: a
+    /// function, or the current value of a variable or constant.
+    Package(&'static str, &'static str),
+    /// The value from whichever predecessor control came from.
+    Phi(Vec<(BlockId, ValueId)>),
+    Unary(UnOp, ValueId),
+    Binary(BinOp, ValueId, ValueId),
+    /// Conversion to the instruction's type: `T(x)`, and the implicit
+    /// conversions of assignment, such as boxing into an interface.
+    Convert(ValueId),
+    /// `x.(T)` for the instruction's type, or its `(T, bool)` tuple
+    /// when the flag asks for the comma-ok form.
+    Assert(ValueId, bool),
+    /// A pointer to a fresh variable of the pointed-to type, zeroed.
+    Alloc,
+    Load(ValueId),
+    /// Stores the second operand through the pointer.
+    Store(ValueId, ValueId),
+    /// The field of a struct, or of the struct a pointer points to; also
+    /// a method value such as `err.Error`.
+    Field(ValueId, String),
+    FieldAddr(ValueId, String),
+    /// `x[i]`, or its `(value, bool)` tuple for the comma-ok form. On a
+    /// slice or string a bounds check precedes it.
+    Index(ValueId, ValueId, bool),
+    IndexAddr(ValueId, ValueId),
+    /// `x[i] = v` on a slice or map.
+    SetIndex(ValueId, ValueId, ValueId),
+    Slice(ValueId, Option<ValueId>, Option<ValueId>),
+    /// Panics unless the index is at least zero and less than the length,
+    /// before an index of a slice or string.
+    BoundsCheck(ValueId, ValueId),
+    /// A composite literal of the instruction's type from key and value
+    /// pairs; struct fields and slice elements are keyed by index.
+    Composite(Vec<(ValueId, ValueId)>),
+    /// `make` of the instruction's type with its size arguments.
+    Make(Vec<ValueId>),
+    /// Calls the function with the arguments; with the flag set the last
+    /// one is spread, as in `append(s, t...)`.
+    Call(ValueId, Vec<ValueId>, bool),
+    /// The result of a tuple at that index.
+    Extract(ValueId, usize),
+    Go(ValueId, Vec<ValueId>),
+    Defer(ValueId, Vec<ValueId>),
+    /// Runs the calls deferred so far, last first, before a return.
+    RunDefers,
+    Send(ValueId, ValueId),
+    /// `<-ch`, or its `(value, bool)` tuple for the comma-ok form.
+    Recv(ValueId, bool),
+    /// Blocks until a case can proceed, or takes the default when there
+    /// is one. Yields the index of the case chosen, numbering the default
+    /// last, with the value and ok flag a receive got.
+    Select(Vec<SelectCase>, bool),
+    /// An iterator over a slice, string, map, channel or integer.
+    Range(ValueId),
+    /// Advances an iterator, yielding whether there was another element
+    /// and its key and value.
+    Next(ValueId),
+    Jump(BlockId),
+    Branch(ValueId, BlockId, BlockId),
+    Return(Option<ValueId>),
+}
+
+impl Inst {
+    pub fn is_terminator(&self) -> bool {
+        matches!(self, Inst::Jump(_) | Inst::Branch(_, _, _) | Inst::Return(_))
+    }
+
+    pub fn is_phi(&self) -> bool {
+        matches!(self, Inst::Phi(_))
+    }
+
+    /// Blocks a terminator transfers control to.
+    pub fn targets(&self) -> Vec<BlockId> {
+        match self {
+            Inst::Jump(target) => vec![*target],
+            Inst::Branch(_, t, f) => vec![*t, *f],
+            _ => vec![],
+        }
+    }
+
+    /// The values the instruction uses, in order.
+    pub fn operands(&self) -> Vec<ValueId> {
+        let mut operands = vec![];
+        let mut inst = self.clone();
+        inst.for_each_operand(|v| operands.push(*v));
+        operands
+    }
+
+    /// Calls `f` on every value the instruction uses, which it may
+    /// replace.
+    pub fn for_each_operand(&mut self, mut f: impl FnMut(&mut ValueId)) {
+        match self {
+            Inst::Const(_)
+            | Inst::Zero
+            | Inst::Param(_)
+            | Inst::FreeVar(_)
+            | Inst::Global(_)
+            | Inst::Current
+            | Inst::Builtin(_)
+            | Inst::Package(_, _)
+            | Inst::Alloc
+            | Inst::RunDefers
+            | Inst::Jump(_)
+            | Inst::Return(None) => {}
+            Inst::Closure(_, values) | Inst::Make(values) => values.iter_mut().for_each(f),
+            Inst::Phi(edges) => edges.iter_mut().for_each(|(_, v)| f(v)),
+            Inst::Unary(_, v)
+            | Inst::Convert(v)
+            | Inst::Assert(v, _)
+            | Inst::Load(v)
+            | Inst::Field(v, _)
+            | Inst::FieldAddr(v, _)
+            | Inst::Extract(v, _)
+            | Inst::Recv(v, _)
+            | Inst::Range(v)
+            | Inst::Next(v)
+            | Inst::Branch(v, _, _)
+            | Inst::Return(Some(v)) => f(v),
+            Inst::Binary(_, a, b)
+            | Inst::Store(a, b)
+            | Inst::Index(a, b, _)
+            | Inst::IndexAddr(a, b)
+            | Inst::BoundsCheck(a, b)
+            | Inst::Send(a, b) => {
+                f(a);
+                f(b);
+            }
+            Inst::SetIndex(a, b, c) => {
+                f(a);
+                f(b);
+                f(c);
+            }
+            Inst::Slice(x, low, high) => {
+                f(x);
+                low.iter_mut().chain(high.iter_mut()).for_each(f);
+            }
+            Inst::Composite(pairs) => {
+                for (k, v) in pairs {
+                    f(k);
+                    f(v);
+                }
+            }
+            Inst::Call(callee, args, _) | Inst::Go(callee, args) | Inst::Defer(callee, args) => {
+                f(callee);
+                args.iter_mut().for_each(f);
+            }
+            Inst::Select(cases, _) => {
+                for case in cases {
+                    f(&mut case.chan);
+                    if let Some(send) = &mut case.send {
+                        f(send);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// What an instruction computes and the type of the result.
+#[derive(Debug, Clone)]
+pub struct ValueData {
+    pub inst: Inst,
+    pub ty: Ty,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Block {
+    /// Instructions in order: phis first, a terminator last.
+    pub insts: Vec<ValueId>,
+    /// Blocks that end by transferring control here, each once per
+    /// edge; phis list their operands by these.
+    pub preds: Vec<BlockId>,
+}
+
+/// A named and typed slot: a parameter, a captured variable or a global.
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    /// The name in stack traces, such as `main.f` or `main.f.func1`.
+    pub name: String,
+    pub params: Vec<Variable>,
+    /// Pointers to the variables the closure captures.
+    pub free: Vec<Variable>,
+    /// None for a package initializer, which returns nothing.
+    pub result: Option<Type>,
+    pub blocks: Vec<Block>,
+    /// Every value, placed in a block or not.
+    pub values: Vec<ValueData>,
+    /// For a function that defers calls: the block control resumes in
+    /// when a deferred call recovers from a panic, to return the result.
+    /// It has no predecessors and counts as reachable from the entry.
+    pub recover: Option<BlockId>,
+}
+
+impl Function {
+    pub fn new(name: &str) -> Function {
+        Function {
+            name: name.to_string(),
+            params: vec![],
+            free: vec![],
+            result: None,
+            blocks: vec![Block::default()],
+            values: vec![],
+            recover: None,
+        }
+    }
+
+    pub fn add_block(&mut self) -> BlockId {
+        self.blocks.push(Block::default());
+        BlockId(self.blocks.len() - 1)
+    }
+
+    /// A value not yet placed in any block.
+    pub fn add_value(&mut self, inst: Inst, ty: Ty) -> ValueId {
+        self.values.push(ValueData { inst, ty });
+        ValueId(self.values.len() - 1)
+    }
+
+    /// Appends an instruction to the block.
+    pub fn append(&mut self, block: BlockId, inst: Inst, ty: Ty) -> ValueId {
+        let value = self.add_value(inst, ty);
+        self.blocks[block.0].insts.push(value);
+        value
+    }
+
+    pub fn inst(&self, value: ValueId) -> &Inst {
+        &self.values[value.0].inst
+    }
+
+    pub fn ty(&self, value: ValueId) -> &Ty {
+        &self.values[value.0].ty
+    }
+
+    /// The last instruction of the block, if it is a terminator.
+    pub fn terminator(&self, block: BlockId) -> Option<&Inst> {
+        let last = *self.blocks[block.0].insts.last()?;
+        Some(self.inst(last)).filter(|inst| inst.is_terminator())
+    }
+
+    pub fn successors(&self, block: BlockId) -> Vec<BlockId> {
+        self.terminator(block).map_or(vec![], |inst| inst.targets())
+    }
+
+    /// Blocks reachable from the entry in reverse postorder, the recover
+    /// block counting as a successor of the entry.
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let successors = |block: BlockId| {
+            let mut succs = self.successors(block);
+            if block == BlockId(0) {
+                succs.extend(self.recover);
+            }
+            succs
+        };
+        let mut visited = vec![false; self.blocks.len()];
+        let mut order = vec![];
+        visited[0] = true;
+        let mut stack = vec![(BlockId(0), successors(BlockId(0)), 0)];
+        while let Some((block, succs, next)) = stack.last_mut() {
+            match succs.get(*next).copied() {
+                Some(succ) => {
+                    *next += 1;
+                    if !visited[succ.0] {
+                        visited[succ.0] = true;
+                        let succs = successors(succ);
+                        stack.push((succ, succs, 0));
+                    }
+                }
+                None => {
+                    order.push(*block);
+                    stack.pop();
+                }
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// Immediate dominators of the blocks reachable from the entry, by
+    /// the iterative algorithm of Cooper, Harvey and Kennedy, the entry
+    /// being its own. The recover block counts as a successor of the entry.
+    pub fn dominators(&self) -> Vec<Option<BlockId>> {
+        let order = self.reverse_postorder();
+        let mut number = vec![usize::MAX; self.blocks.len()];
+        for (i, block) in order.iter().enumerate() {
+            number[block.0] = i;
+        }
+        let preds = |block: BlockId| -> Vec<BlockId> {
+            let mut preds = self.blocks[block.0].preds.clone();
+            if Some(block) == self.recover {
+                preds.push(BlockId(0));
+            }
+            preds
+        };
+        let mut idom: Vec<Option<BlockId>> = vec![None; self.blocks.len()];
+        idom[0] = Some(BlockId(0));
+        let intersect = |idom: &[Option<BlockId>], mut a: BlockId, mut b: BlockId| {
+            while a != b {
+                while number[a.0] > number[b.0] {
+                    a = idom[a.0].unwrap();
+                }
+                while number[b.0] > number[a.0] {
+                    b = idom[b.0].unwrap();
+                }
+            }
+            a
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut new = None;
+                for pred in preds(block) {
+                    if idom[pred.0].is_none() || number[pred.0] == usize::MAX {
+                        continue;
+                    }
+                    new = Some(match new {
+                        None => pred,
+                        Some(other) => intersect(&idom, pred, other),
+                    });
+                }
+                if new.is_some() && idom[block.0] != new {
+                    idom[block.0] = new;
+                    changed = true;
+                }
+            }
+        }
+        idom
+    }
+
+    /// Drops unreachable blocks and the phi operands coming from them,
+    /// replaces phis whose operands are all one value by that value, and
+    /// renumbers blocks and values in order.
+    pub fn compact(&mut self) {
+        let mut reachable = vec![false; self.blocks.len()];
+        for block in self.reverse_postorder() {
+            reachable[block.0] = true;
+        }
+        for (i, block) in self.blocks.iter_mut().enumerate() {
+            if reachable[i] {
+                block.preds.retain(|p| reachable[p.0]);
+            } else {
+                block.insts.clear();
+                block.preds.clear();
+            }
+        }
+        for i in 0..self.blocks.len() {
+            for v in self.blocks[i].insts.clone() {
+                if let Inst::Phi(edges) = &mut self.values[v.0].inst {
+                    edges.retain(|(b, _)| reachable[b.0]);
+                }
+            }
+        }
+
+        // phis left with one distinct operand, themselves aside
+        let mut forward: HashMap<ValueId, ValueId> = HashMap::new();
+        loop {
+            let mut changed = false;
+            for block in 0..self.blocks.len() {
+                for v in self.blocks[block].insts.clone() {
+                    let edges = match &self.values[v.0].inst {
+                        Inst::Phi(edges) => edges.clone(),
+                        _ => continue,
+                    };
+                    let mut same = None;
+                    let mut trivial = true;
+                    for (_, op) in edges {
+                        let op = resolve(&forward, op);
+                        if op == v || Some(op) == same {
+                            continue;
+                        }
+                        if same.is_some() {
+                            trivial = false;
+                            break;
+                        }
+                        same = Some(op);
+                    }
+                    if let (true, Some(same)) = (trivial, same) {
+                        forward.insert(v, same);
+                        self.blocks[block].insts.retain(|&i| i != v);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut block_map = HashMap::new();
+        let mut blocks = vec![];
+        for (i, block) in self.blocks.iter().enumerate() {
+            if reachable[i] {
+                block_map.insert(BlockId(i), BlockId(blocks.len()));
+                blocks.push(block.clone());
+            }
+        }
+        let mut value_map = HashMap::new();
+        let mut values = vec![];
+        for block in &blocks {
+            for &v in &block.insts {
+                value_map.insert(v, ValueId(values.len()));
+                values.push(self.values[v.0].clone());
+            }
+        }
+        let renumber = |v: &mut ValueId| *v = value_map[&resolve(&forward, *v)];
+        for data in &mut values {
+            data.inst.for_each_operand(renumber);
+            match &mut data.inst {
+                Inst::Phi(edges) => edges.iter_mut().for_each(|(b, _)| *b = block_map[b]),
+                Inst::Jump(target) => *target = block_map[target],
+                Inst::Branch(_, t, f) => {
+                    *t = block_map[t];
+                    *f = block_map[f];
+                }
+                _ => {}
+            }
+        }
+        for block in &mut blocks {
+            block.insts.iter_mut().for_each(|v| *v = value_map[v]);
+            block.preds.iter_mut().for_each(|b| *b = block_map[b]);
+        }
+        self.recover = self.recover.map(|b| block_map[&b]);
+        self.blocks = blocks;
+        self.values = values;
+    }
+}
+
+/// Reports whether block `a` dominates block `b`, given the immediate
+/// dominators.
+pub fn dominates(idom: &[Option<BlockId>], a: BlockId, mut b: BlockId) -> bool {
+    loop {
+        if a == b {
+            return true;
+        }
+        match idom[b.0] {
+            Some(up) if up != b => b = up,
+            _ => return false,
+        }
+    }
+}
+
+/// Follows the replacements in `forward` to the value `v` stands for.
+pub(crate) fn resolve(forward: &HashMap<ValueId, ValueId>, mut v: ValueId) -> ValueId {
+    while let Some(&next) = forward.get(&v) {
+        v = next;
+    }
+    v
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub globals: Vec<Variable>,
+    pub functions: Vec<Function>,
+    /// Initializers of the packages in the order they run: their
+    /// top-level statements.
+    pub inits: Vec<FuncId>,
+    /// The global holding `main` of the main package, called after the
+    /// initializers when it is a function.
+    pub main: Option<usize>,
+}
+
+impl Module {
+    pub fn function(&self, id: FuncId) -> &Function {
+        &self.functions[id.0]
+    }
+
+    /// The function as text, naming the globals and functions it refers
+    /// to.
+    pub fn function_string(&self, id: FuncId) -> String {
+        let func = self.function(id);
+        let mut out = String::new();
+        let params: Vec<String> = func.params.iter().map(|p| format!("{} {}", p.name, type_string(&p.ty))).collect();
+        out.push_str(&format!("func {}({})", func.name, params.join(", ")));
+        if let Some(result) = &func.result {
+            out.push_str(&format!(" {}", type_string(result)));
+        }
+        out.push('\n');
+        for free in &func.free {
+            out.push_str(&format!("  free {} {}\n", free.name, type_string(&free.ty)));
+        }
+        for (i, block) in func.blocks.iter().enumerate() {
+            let id = BlockId(i);
+            out.push_str(&format!("{}:", id));
+            if !block.preds.is_empty() {
+                let preds: Vec<String> = block.preds.iter().map(|p| p.to_string()).collect();
+                out.push_str(&format!(" ; preds {}", preds.join(", ")));
+            }
+            if func.recover == Some(id) {
+                out.push_str(" ; recover");
+            }
+            out.push('\n');
+            for &v in &block.insts {
+                let data = &func.values[v.0];
+                let text = self.inst_string(func, &data.inst);
+                match &data.ty {
+                    Ty::Void => out.push_str(&format!("  {}\n", text)),
+                    ty => out.push_str(&format!("  {}: {} = {}\n", v, ty_string(ty), text)),
+                }
+            }
+        }
+        out
+    }
+
+    fn inst_string(&self, func: &Function, inst: &Inst) -> String {
+        let list = |values: &[ValueId]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ");
+        let ok = |flag: bool| if flag { ", ok" } else { "" };
+        match inst {
+            Inst::Const(c) => format!("const {}", const_string(c)),
+            Inst::Zero => "zero".to_string(),
+            Inst::Param(i) => format!("param {}", func.params.get(*i).map_or("?", |p| p.name.as_str())),
+            Inst::FreeVar(i) => format!("free {}", func.free.get(*i).map_or("?", |p| p.name.as_str())),
+            Inst::Global(i) => format!("global {}", self.globals.get(*i).map_or("?", |g| g.name.as_str())),
+            Inst::Current => "current".to_string(),
+            Inst::Closure(id, captured) => {
+                let name = self.functions.get(id.0).map_or("?", |f| f.name.as_str());
+                format!("closure {} [{}]", name, list(captured))
+            }
+            Inst::Builtin(name) => format!("builtin {}", name),
+            Inst::Package(path, name) => format!("package {}.{}", path, name),
+            Inst::Phi(edges) => {
+                let edges: Vec<String> = edges.iter().map(|(b, v)| format!("{}: {}", b, v)).collect();
+                format!("phi [{}]", edges.join(", "))
+            }
+            Inst::Unary(UnOp::Neg, v) => format!("neg {}", v),
+            Inst::Unary(UnOp::Not, v) => format!("not {}", v),
+            Inst::Binary(op, a, b) => format!("{} {}, {}", op.name(), a, b),
+            Inst::Convert(v) => format!("convert {}", v),
+            Inst::Assert(v, flag) => format!("assert {}{}", v, ok(*flag)),
+            Inst::Alloc => "alloc".to_string(),
+            Inst::Load(p) => format!("load {}", p),
+            Inst::Store(p, v) => format!("store {}, {}", p, v),
+            Inst::Field(v, name) => format!("field {}, {}", v, name),
+            Inst::FieldAddr(v, name) => format!("fieldaddr {}, {}", v, name),
+            Inst::Index(x, i, flag) => format!("index {}, {}{}", x, i, ok(*flag)),
+            Inst::IndexAddr(x, i) => format!("indexaddr {}, {}", x, i),
+            Inst::SetIndex(x, i, v) => format!("setindex {}, {}, {}", x, i, v),
+            Inst::BoundsCheck(i, n) => format!("check {}, {}", i, n),
+            Inst::Slice(x, low, high) => {
+                let bound = |b: &Option<ValueId>| b.map_or("_".to_string(), |v| v.to_string());
+                format!("slice {}, {}, {}", x, bound(low), bound(high))
+            }
+            Inst::Composite(pairs) => {
+                let pairs: Vec<String> = pairs.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                format!("composite [{}]", pairs.join(", "))
+            }
+            Inst::Make(args) if args.is_empty() => "make".to_string(),
+            Inst::Make(args) => format!("make {}", list(args)),
+            Inst::Call(callee, args, spread) => format!("call {}({}{})", callee, list(args), if *spread { "..." } else { "" }),
+            Inst::Extract(v, i) => format!("extract {}, {}", v, i),
+            Inst::Go(callee, args) => format!("go {}({})", callee, list(args)),
+            Inst::Defer(callee, args) => format!("defer {}({})", callee, list(args)),
+            Inst::RunDefers => "rundefers".to_string(),
+            Inst::Send(ch, v) => format!("send {}, {}", ch, v),
+            Inst::Recv(ch, flag) => format!("recv {}{}", ch, ok(*flag)),
+            Inst::Select(cases, default) => {
+                let mut cases: Vec<String> = cases
+                    .iter()
+                    .map(|c| match c.send {
+                        Some(v) => format!("send {}, {}", c.chan, v),
+                        None => format!("recv {}", c.chan),
+                    })
+                    .collect();
+                if *default {
+                    cases.push("default".to_string());
+                }
+                format!("select [{}]", cases.join(", "))
+            }
+            Inst::Range(v) => format!("range {}", v),
+            Inst::Next(v) => format!("next {}", v),
+            Inst::Jump(target) => format!("jump {}", target),
+            Inst::Branch(cond, t, f) => format!("branch {}, {}, {}", cond, t, f),
+            Inst::Return(Some(v)) => format!("ret {}", v),
+            Inst::Return(None) => "ret".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for global in &self.globals {
+            writeln!(f, "global {} {}", global.name, type_string(&global.ty))?;
+        }
+        for i in 0..self.functions.len() {
+            if i > 0 || !self.globals.is_empty() {
+                writeln!(f)?;
+            }
+            write!(f, "{}", self.function_string(FuncId(i)))?;
+        }
+        Ok(())
+    }
+}
+
+/// A type as the IR prints it, with `any` for the empty interface.
+pub fn type_string(ty: &Type) -> String {
+    match ty {
+        Type::Interface(it) if it.name.is_none() => "any".to_string(),
+        Type::Slice(elem) => format!("[]{}", type_string(elem)),
+        Type::Map(key, elem) => format!("map[{}]{}", type_string(key), type_string(elem)),
+        Type::Pointer(elem) => format!("*{}", type_string(elem)),
+        Type::Chan(elem) => format!("chan {}", type_string(elem)),
+        Type::Struct(st) if st.name.is_none() => {
+            let fields: Vec<String> = st.fields.borrow().iter().map(|f| format!("{} {}", f.name, type_string(&f.ty))).collect();
+            format!("struct {{ {} }}", fields.join("; "))
+        }
+        _ => ty.to_string(),
+    }
+}
+
+fn ty_string(ty: &Ty) -> String {
+    match ty {
+        Ty::Void => "void".to_string(),
+        Ty::Value(ty) => type_string(ty),
+        Ty::Tuple(types) => format!("({})", types.iter().map(type_string).collect::<Vec<_>>().join(", ")),
+        Ty::Iter => "iter".to_string(),
+    }
+}
+
+fn const_string(c: &Const) -> String {
+    match c {
+        Const::Nil => "nil".to_string(),
+        Const::Bool(b) => b.to_string(),
+        Const::Int(n) => n.to_string(),
+        Const::Float(x) => format!("{:?}", x),
+        Const::String(s) => strconv::quote(s),
+    }
+}
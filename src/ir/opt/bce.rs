@@ -0,0 +1,105 @@
+//! Bounds check elimination for loops over the length of a slice or
+//! string.
+//!
+//! A check that `i` indexes `s` goes when the check compares against
+//! `len(s)` and `i` is either the key a `range` over `s` yields, or the
+//! variable of a loop such as `for i := 0; i < len(s); i++`: a phi in the
+//! loop header starting at a constant at least zero and stepped up by a
+//! positive constant, with the header leaving the loop unless it is less
+//! than `len(s)`, and the check in the part of the loop that comparison
+//! guards.
+
+use super::*;
+
+/// The largest step of a loop variable, small enough that adding it to an
+/// index cannot overflow.
+const MAX_STEP: i64 = 1 << 32;
+
+pub fn run(func: &mut Function) {
+    let idom = func.dominators();
+    let mut block_of = vec![None; func.values.len()];
+    for (b, block) in func.blocks.iter().enumerate() {
+        for &v in &block.insts {
+            block_of[v.0] = Some(BlockId(b));
+        }
+    }
+    let mut redundant = vec![];
+    for (b, block) in func.blocks.iter().enumerate() {
+        for &v in &block.insts {
+            if let Inst::BoundsCheck(i, n) = func.inst(v) {
+                let s = match len_operand(func, *n) {
+                    Some(s) => s,
+                    None => continue,
+                };
+                if is_range_key(func, *i, s) || is_loop_index(func, &idom, &block_of, *i, s, BlockId(b)) {
+                    redundant.push(v);
+                }
+            }
+        }
+    }
+    if redundant.is_empty() {
+        return;
+    }
+    for block in &mut func.blocks {
+        block.insts.retain(|v| !redundant.contains(v));
+    }
+    func.compact();
+}
+
+/// Reports whether `i` is the key of a `range` over `s`.
+fn is_range_key(func: &Function, i: ValueId, s: ValueId) -> bool {
+    let next = match func.inst(i) {
+        Inst::Extract(next, 1) => *next,
+        _ => return false,
+    };
+    match func.inst(next) {
+        Inst::Next(iter) => matches!(func.inst(*iter), Inst::Range(x) if *x == s),
+        _ => false,
+    }
+}
+
+fn is_loop_index(
+    func: &Function,
+    idom: &[Option<BlockId>],
+    block_of: &[Option<BlockId>],
+    i: ValueId,
+    s: ValueId,
+    at: BlockId,
+) -> bool {
+    let header = match block_of[i.0] {
+        Some(header) => header,
+        None => return false,
+    };
+    let edges = match func.inst(i) {
+        Inst::Phi(edges) if edges.len() == 2 => edges,
+        _ => return false,
+    };
+    // the header leaves the loop unless i < len(s)
+    let body = match func.terminator(header) {
+        Some(Inst::Branch(cond, t, _)) => match func.inst(*cond) {
+            Inst::Binary(BinOp::Lt, a, m) if *a == i && len_operand(func, *m) == Some(s) => *t,
+            Inst::Binary(BinOp::Gt, m, a) if *a == i && len_operand(func, *m) == Some(s) => *t,
+            _ => return false,
+        },
+        _ => return false,
+    };
+    if func.blocks[body.0].preds != [header] || !dominates(idom, body, at) {
+        return false;
+    }
+    let constant = |v: ValueId| match func.inst(v) {
+        Inst::Const(Const::Int(n)) => Some(*n),
+        _ => None,
+    };
+    // one edge enters with a start of at least zero, the other steps
+    // from inside the part of the loop where i < len(s)
+    let enters = |v: ValueId| constant(v).is_some_and(|n| n >= 0);
+    let steps = |pred: BlockId, v: ValueId| {
+        dominates(idom, body, pred)
+            && match func.inst(v) {
+                Inst::Binary(BinOp::Add, a, step) if *a == i => constant(*step).is_some_and(|n| n > 0 && n <= MAX_STEP),
+                _ => false,
+            }
+    };
+    let [(p, x), (q, y)] = [edges[0], edges[1]];
+    (enters(x) && steps(q, y)) || (enters(y) && steps(p, x))
+}
@@ -0,0 +1,78 @@
+//! Common subexpression elimination by dominator-based value numbering.
+//!
+//! Blocks are visited down the dominator tree with a table of the
+//! expressions available: those computed in the blocks that dominate
+//! the current one. An expression already in the table is replaced by
+//! the value computing it. Only instructions whose value depends on
+//! nothing but their operands take part; reads of memory, which a store
+//! in between may change, and allocations do not.
+
+use super::*;
+
+/// Reports whether two instructions with the same operands yield the
+/// same value.
+fn is_expression(func: &Function, value: ValueId) -> bool {
+    match func.inst(value) {
+        Inst::Const(_)
+        | Inst::Param(_)
+        | Inst::FreeVar(_)
+        | Inst::Global(_)
+        | Inst::Current
+        | Inst::Builtin(_)
+        | Inst::Unary(_, _)
+        | Inst::Binary(_, _, _)
+        | Inst::Convert(_)
+        | Inst::Extract(_, _) => is_pure(func, value),
+        Inst::Package(_, _) => matches!(func.ty(value).value(), Some(Type::Func)),
+        // slices and strings do not change length
+        Inst::Call(_, _, _) => len_operand(func, value).is_some(),
+        _ => false,
+    }
+}
+
+pub fn run(func: &mut Function) {
+    let idom = func.dominators();
+    let mut children = vec![vec![]; func.blocks.len()];
+    for block in func.reverse_postorder().into_iter().skip(1) {
+        if let Some(parent) = idom[block.0] {
+            children[parent.0].push(block);
+        }
+    }
+
+    let mut forward = HashMap::new();
+    let mut available: HashMap<String, ValueId> = HashMap::new();
+    // entering a block, or leaving it with the expressions it added
+    let mut stack = vec![(BlockId(0), None)];
+    while let Some((block, added)) = stack.pop() {
+        if let Some(added) = added {
+            for key in added {
+                available.remove(&key);
+            }
+            continue;
+        }
+        let mut added = vec![];
+        for v in func.blocks[block.0].insts.clone() {
+            func.values[v.0].inst.for_each_operand(|op| *op = resolve(&forward, *op));
+            if !is_expression(func, v) {
+                continue;
+            }
+            let data = &func.values[v.0];
+            let key = format!("{:?} {:?}", data.inst, data.ty);
+            match available.get(&key) {
+                Some(&earlier) => {
+                    forward.insert(v, earlier);
+                }
+                None => {
+                    available.insert(key.clone(), v);
+                    added.push(key);
+                }
+            }
+        }
+        stack.push((block, Some(added)));
+        for &child in children[block.0].iter().rev() {
+            stack.push((child, None));
+        }
+    }
+    replace(func, &forward);
+    func.compact();
+}
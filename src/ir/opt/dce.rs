@@ -0,0 +1,32 @@
+//! Dead code elimination: instructions with effects are live, and so is
+//! every value a live instruction uses; the rest go. Phis that only feed
+//! each other around a loop go too. Blocks left joined by nothing but a
+//! jump are merged.
+
+use super::*;
+
+pub fn run(func: &mut Function) {
+    let mut live = vec![false; func.values.len()];
+    let mut work = vec![];
+    for block in &func.blocks {
+        for &v in &block.insts {
+            if !is_pure(func, v) {
+                live[v.0] = true;
+                work.push(v);
+            }
+        }
+    }
+    while let Some(v) = work.pop() {
+        for op in func.inst(v).operands() {
+            if !live[op.0] {
+                live[op.0] = true;
+                work.push(op);
+            }
+        }
+    }
+    for block in &mut func.blocks {
+        block.insts.retain(|v| live[v.0]);
+    }
+    merge_blocks(func);
+    func.compact();
+}
@@ -0,0 +1,219 @@
+//! Inlining of calls to small functions.
+//!
+//! A call is inlined when the function it calls is known: a closure made
+//! in the same function, or a top-level function of the program, that is
+//! a global only ever assigned once, by its package initializer. The
+//! callee must cost no more than `THRESHOLD`, counting calls as several
+//! instructions and constants and jumps as none, and must not defer
+//! calls or refer to itself. Inlining copies the callee's blocks between
+//! the two halves of the block the call was in, with its parameters and
+//! free variables replaced by the arguments and captured pointers, and
+//! its returns jumping to the second half.
+//!
+//! Only the calls in a function before the pass runs are inlined, so a
+//! recursive function is unrolled at most once.
+
+use super::*;
+
+/// The highest cost of a function that is inlined.
+pub const THRESHOLD: usize = 40;
+
+/// The number of instructions beyond which a caller takes no more
+/// inlined calls.
+pub const CALLER_LIMIT: usize = 2000;
+
+/// The size of a function for inlining, in rough instructions executed.
+pub fn cost(func: &Function) -> usize {
+    let mut cost = 0;
+    for block in &func.blocks {
+        for &v in &block.insts {
+            cost += match func.inst(v) {
+                Inst::Const(_) | Inst::Param(_) | Inst::FreeVar(_) | Inst::Phi(_) | Inst::Jump(_) => 0,
+                Inst::Call(_, _, _) | Inst::Go(_, _) | Inst::Select(_, _) => 5,
+                _ => 1,
+            };
+        }
+    }
+    cost
+}
+
+/// Reports whether calls to `func` can be replaced by its body.
+fn inlinable(func: &Function) -> bool {
+    func.result.is_some()
+        && func.recover.is_none()
+        && cost(func) <= THRESHOLD
+        && func.blocks.iter().flat_map(|b| &b.insts).all(|&v| {
+            !matches!(func.inst(v), Inst::Defer(_, _) | Inst::RunDefers | Inst::Current | Inst::Return(None))
+        })
+}
+
+/// For each global, the function it always holds: a closure without
+/// captures stored into it once by an initializer, and never otherwise
+/// assigned or referred to but to be loaded.
+fn known_globals(module: &Module) -> Vec<Option<FuncId>> {
+    let mut stores: Vec<Vec<Option<FuncId>>> = vec![vec![]; module.globals.len()];
+    let mut escapes = vec![false; module.globals.len()];
+    for (id, func) in module.functions.iter().enumerate() {
+        let init = module.inits.contains(&FuncId(id));
+        let global = |v: ValueId| match func.inst(v) {
+            Inst::Global(g) => Some(*g),
+            _ => None,
+        };
+        for &v in func.blocks.iter().flat_map(|b| &b.insts) {
+            match func.inst(v) {
+                Inst::Store(p, value) => {
+                    if let Some(g) = global(*p) {
+                        let closure = match func.inst(*value) {
+                            Inst::Closure(callee, captured) if init && captured.is_empty() => Some(*callee),
+                            _ => None,
+                        };
+                        stores[g].push(closure);
+                    }
+                    if let Some(g) = global(*value) {
+                        escapes[g] = true;
+                    }
+                }
+                Inst::Load(_) => {}
+                inst => {
+                    for op in inst.operands() {
+                        if let Some(g) = global(op) {
+                            escapes[g] = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    stores
+        .into_iter()
+        .zip(escapes)
+        .map(|(stores, escapes)| match stores.as_slice() {
+            [Some(callee)] if !escapes => Some(*callee),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn run(module: &mut Module) {
+    let known = known_globals(module);
+    for caller in 0..module.functions.len() {
+        let calls: Vec<ValueId> = {
+            let func = &module.functions[caller];
+            func.blocks.iter().flat_map(|b| &b.insts).copied().filter(|&v| matches!(func.inst(v), Inst::Call(_, _, false))).collect()
+        };
+        let mut changed = false;
+        for call in calls {
+            let func = &module.functions[caller];
+            if func.blocks.iter().map(|b| b.insts.len()).sum::<usize>() > CALLER_LIMIT {
+                break;
+            }
+            let (callee, args) = match func.inst(call) {
+                Inst::Call(callee, args, false) => (*callee, args.clone()),
+                _ => continue,
+            };
+            let (id, captured) = match func.inst(callee) {
+                Inst::Closure(id, captured) => (*id, captured.clone()),
+                Inst::Load(p) => match func.inst(*p) {
+                    Inst::Global(g) => match known[*g] {
+                        Some(id) => (id, vec![]),
+                        None => continue,
+                    },
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let target = &module.functions[id.0];
+            if id.0 == caller || !inlinable(target) || target.params.len() != args.len() || target.free.len() != captured.len() {
+                continue;
+            }
+            let target = target.clone();
+            inline(&mut module.functions[caller], call, &target, &args, &captured);
+            changed = true;
+        }
+        if changed {
+            merge_blocks(&mut module.functions[caller]);
+            module.functions[caller].compact();
+        }
+    }
+}
+
+/// Replaces `call` in `func` by a copy of the body of `callee`.
+fn inline(func: &mut Function, call: ValueId, callee: &Function, args: &[ValueId], captured: &[ValueId]) {
+    let block = match func.blocks.iter().position(|b| b.insts.contains(&call)) {
+        Some(block) => BlockId(block),
+        None => return,
+    };
+    // the instructions after the call move to a block of their own
+    let rest = func.add_block();
+    let at = func.blocks[block.0].insts.iter().position(|&v| v == call).unwrap();
+    let after = func.blocks[block.0].insts.split_off(at + 1);
+    func.blocks[block.0].insts.pop();
+    func.blocks[rest.0].insts = after;
+    for succ in func.successors(rest) {
+        for pred in &mut func.blocks[succ.0].preds {
+            if *pred == block {
+                *pred = rest;
+            }
+        }
+        for v in func.blocks[succ.0].insts.clone() {
+            if let Inst::Phi(edges) = &mut func.values[v.0].inst {
+                for (pred, _) in edges {
+                    if *pred == block {
+                        *pred = rest;
+                    }
+                }
+            }
+        }
+    }
+
+    let blocks: Vec<BlockId> = callee.blocks.iter().map(|_| func.add_block()).collect();
+    let mut values = HashMap::new();
+    for &v in callee.blocks.iter().flat_map(|b| &b.insts) {
+        let copy = match callee.inst(v) {
+            Inst::Param(i) => args[*i],
+            Inst::FreeVar(i) => captured[*i],
+            inst => func.add_value(inst.clone(), callee.ty(v).clone()),
+        };
+        values.insert(v, copy);
+    }
+    let mut returns = vec![];
+    for (b, body) in callee.blocks.iter().enumerate() {
+        let copy = blocks[b];
+        func.blocks[copy.0].preds = body.preds.iter().map(|p| blocks[p.0]).collect();
+        for &v in &body.insts {
+            if matches!(callee.inst(v), Inst::Param(_) | Inst::FreeVar(_)) {
+                continue;
+            }
+            let new = values[&v];
+            let inst = &mut func.values[new.0].inst;
+            inst.for_each_operand(|op| *op = values[op]);
+            match inst {
+                Inst::Phi(edges) => edges.iter_mut().for_each(|(pred, _)| *pred = blocks[pred.0]),
+                Inst::Jump(target) => *target = blocks[target.0],
+                Inst::Branch(_, t, f) => {
+                    *t = blocks[t.0];
+                    *f = blocks[f.0];
+                }
+                Inst::Return(Some(result)) => {
+                    returns.push((copy, *result));
+                    *inst = Inst::Jump(rest);
+                    func.blocks[rest.0].preds.push(copy);
+                }
+                _ => {}
+            }
+            func.blocks[copy.0].insts.push(new);
+        }
+    }
+    func.append(block, Inst::Jump(blocks[0]), Ty::Void);
+    func.blocks[blocks[0].0].preds.push(block);
+
+    let result = match returns.as_slice() {
+        [(_, result)] => *result,
+        _ => {
+            let phi = func.add_value(Inst::Phi(returns), func.ty(call).clone());
+            func.blocks[rest.0].insts.insert(0, phi);
+            phi
+        }
+    };
+    replace(func, &HashMap::from([(call, result)]));
+}
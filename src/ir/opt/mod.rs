@@ -0,0 +1,248 @@
+//! Optimization passes over the IR, and the pass manager that runs them
+//! for an optimization level.
+//!
+//! `-O0` runs none. `-O1` propagates constants, folding the branches they
+//! decide, then removes common subexpressions and dead code. `-O2` first
+//! inlines small functions into their callers, and before removing dead
+//! code drops the bounds checks loops over `len(s)` make redundant.
+//!
+//! Every pass leaves the functions it changes compacted, so the IR they
+//! print is numbered in order.
+
+use super::*;
+use std::collections::HashMap;
+
+pub mod bce;
+pub mod cse;
+pub mod dce;
+pub mod inline;
+pub mod sccp;
+
+#[cfg(test)]
+mod test;
+
+/// The highest optimization level; higher ones run the same passes.
+pub const MAX_LEVEL: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Inlining of small functions, see `inline`.
+    Inline,
+    /// Sparse conditional constant propagation, see `sccp`.
+    Sccp,
+    /// Common subexpression elimination, see `cse`.
+    Cse,
+    /// Bounds check elimination, see `bce`.
+    Bce,
+    /// Dead code elimination, see `dce`.
+    Dce,
+}
+
+impl Pass {
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Inline => "inline",
+            Pass::Sccp => "sccp",
+            Pass::Cse => "cse",
+            Pass::Bce => "bce",
+            Pass::Dce => "dce",
+        }
+    }
+
+    pub fn run(self, module: &mut Module) {
+        if self == Pass::Inline {
+            inline::run(module);
+            return;
+        }
+        for func in &mut module.functions {
+            match self {
+                Pass::Sccp => sccp::run(func),
+                Pass::Cse => cse::run(func),
+                Pass::Bce => bce::run(func),
+                _ => dce::run(func),
+            }
+        }
+    }
+}
+
+/// The passes `-O<level>` runs, in order.
+pub fn passes(level: u32) -> Vec<Pass> {
+    match level {
+        0 => vec![],
+        1 => vec![Pass::Sccp, Pass::Cse, Pass::Dce],
+        _ => vec![Pass::Inline, Pass::Sccp, Pass::Cse, Pass::Bce, Pass::Dce],
+    }
+}
+
+/// Runs passes over a module in order, optionally verifying it after
+/// each one.
+pub struct PassManager {
+    passes: Vec<Pass>,
+    verify: bool,
+}
+
+impl PassManager {
+    pub fn new(passes: Vec<Pass>) -> PassManager {
+        PassManager { passes, verify: false }
+    }
+
+    pub fn for_level(level: u32) -> PassManager {
+        PassManager::new(passes(level))
+    }
+
+    /// Verifies the module after every pass, so an error names the pass
+    /// that caused it.
+    pub fn verify_each(mut self) -> PassManager {
+        self.verify = true;
+        self
+    }
+
+    pub fn run(&self, module: &mut Module) -> Result<(), Vec<String>> {
+        for &pass in &self.passes {
+            pass.run(module);
+            if self.verify {
+                verify(module).map_err(|errors| errors.into_iter().map(|e| format!("after {}: {}", pass.name(), e)).collect::<Vec<_>>())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_builtin(func: &Function, value: ValueId, names: &[&str]) -> bool {
+    matches!(func.inst(value), Inst::Builtin(name) if names.contains(name))
+}
+
+/// The slice or string `len` is called on, when `value` is such a call.
+pub(crate) fn len_operand(func: &Function, value: ValueId) -> Option<ValueId> {
+    match func.inst(value) {
+        Inst::Call(callee, args, false) if is_builtin(func, *callee, &["len"]) && args.len() == 1 => {
+            matches!(func.ty(args[0]).value(), Some(Type::Slice(_) | Type::String)).then_some(args[0])
+        }
+        _ => None,
+    }
+}
+
+/// Reports whether the instruction of `value` has no effect besides the
+/// value and cannot panic, so that it can go when nothing uses it.
+pub fn is_pure(func: &Function, value: ValueId) -> bool {
+    let ty = |v: ValueId| func.ty(v).value();
+    let is_struct = |v: ValueId| matches!(ty(v), Some(Type::Struct(_)));
+    match func.inst(value) {
+        Inst::Const(_)
+        | Inst::Zero
+        | Inst::Param(_)
+        | Inst::FreeVar(_)
+        | Inst::Global(_)
+        | Inst::Current
+        | Inst::Closure(_, _)
+        | Inst::Builtin(_)
+        | Inst::Package(_, _)
+        | Inst::Phi(_)
+        | Inst::Alloc
+        | Inst::Composite(_)
+        | Inst::Extract(_, _) => true,
+        Inst::Unary(_, v) => !ty(*v).is_some_and(Type::is_interface),
+        Inst::Binary(op, a, b) => {
+            if ty(*a).is_some_and(Type::is_interface) {
+                // dynamic operands may not support the operator, or
+                // hold values that are not comparable
+                false
+            } else if matches!(op, BinOp::Div | BinOp::Rem) {
+                match ty(*a) {
+                    Some(Type::Float64) => true,
+                    _ => matches!(func.inst(*b), Inst::Const(Const::Int(n)) if *n != 0),
+                }
+            } else {
+                true
+            }
+        }
+        // boxing cannot fail, unboxing can
+        Inst::Convert(v) => value_is_interface(func, value) || !ty(*v).is_some_and(Type::is_interface),
+        Inst::Assert(_, ok) => *ok,
+        Inst::Load(p) => matches!(func.inst(*p), Inst::Alloc | Inst::Global(_) | Inst::FreeVar(_)),
+        Inst::Field(v, _) => is_struct(*v),
+        // the bounds check before it is what panics
+        Inst::Index(x, _, _) => matches!(ty(*x), Some(Type::Slice(_) | Type::String | Type::Map(_, _))),
+        Inst::Make(sizes) => sizes.is_empty(),
+        Inst::Call(_, _, _) => len_operand(func, value).is_some(),
+        _ => false,
+    }
+}
+
+fn value_is_interface(func: &Function, value: ValueId) -> bool {
+    func.ty(value).value().is_some_and(Type::is_interface)
+}
+
+/// Replaces the uses of values by the values `forward` maps them to,
+/// following chains, and drops the replaced values from their blocks.
+pub(crate) fn replace(func: &mut Function, forward: &HashMap<ValueId, ValueId>) {
+    if forward.is_empty() {
+        return;
+    }
+    for data in &mut func.values {
+        data.inst.for_each_operand(|v| *v = resolve(forward, *v));
+    }
+    for block in &mut func.blocks {
+        block.insts.retain(|v| !forward.contains_key(v));
+    }
+}
+
+/// Removes one edge from `from` to `to`: a predecessor of `to`, and the
+/// operand its phis take along it.
+pub(crate) fn remove_edge(func: &mut Function, from: BlockId, to: BlockId) {
+    let preds = &mut func.blocks[to.0].preds;
+    if let Some(i) = preds.iter().position(|&p| p == from) {
+        preds.remove(i);
+    }
+    for v in func.blocks[to.0].insts.clone() {
+        if let Inst::Phi(edges) = &mut func.values[v.0].inst {
+            if let Some(i) = edges.iter().position(|&(b, _)| b == from) {
+                edges.remove(i);
+            }
+        }
+    }
+}
+
+/// Merges each block that ends by jumping to a block with no other
+/// predecessor with that block, as inlining and folded branches leave
+/// them.
+pub(crate) fn merge_blocks(func: &mut Function) {
+    let mut reachable = vec![false; func.blocks.len()];
+    for block in func.reverse_postorder() {
+        reachable[block.0] = true;
+    }
+    let mut forward = HashMap::new();
+    for a in (0..func.blocks.len()).map(BlockId).filter(|a| reachable[a.0]) {
+        while let Some(&jump) = func.blocks[a.0].insts.last() {
+            let b = match func.inst(jump) {
+                Inst::Jump(b) if *b != a && func.blocks[b.0].preds.iter().filter(|p| reachable[p.0]).eq([&a]) => *b,
+                _ => break,
+            };
+            func.blocks[a.0].insts.pop();
+            let mut insts = std::mem::take(&mut func.blocks[b.0].insts);
+            insts.retain(|&v| match func.inst(v) {
+                Inst::Phi(edges) => {
+                    let (_, value) = edges.iter().find(|(pred, _)| *pred == a).unwrap();
+                    forward.insert(v, *value);
+                    false
+                }
+                _ => true,
+            });
+            func.blocks[a.0].insts.extend(insts);
+            func.blocks[b.0].preds.clear();
+            for succ in func.successors(a) {
+                for pred in &mut func.blocks[succ.0].preds {
+                    if *pred == b {
+                        *pred = a;
+                    }
+                }
+                for v in func.blocks[succ.0].insts.clone() {
+                    if let Inst::Phi(edges) = &mut func.values[v.0].inst {
+                        edges.iter_mut().filter(|(pred, _)| *pred == b).for_each(|(pred, _)| *pred = a);
+                    }
+                }
+            }
+        }
+    }
+    replace(func, &forward);
+}
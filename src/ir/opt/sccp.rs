@@ -0,0 +1,290 @@
+//! Sparse conditional constant propagation, after Wegman and Zadeck,
+//! "Constant Propagation with Conditional Branches".
+//!
+//! Every value starts undefined and every block unreachable. Blocks
+//! become reachable along the edges of reachable branches whose
+//! condition is not a known constant, and values are evaluated as their
+//! blocks are reached and their operands change, from undefined to a
+//! constant to varying. A constant flowing around a loop, or a branch on
+//! one, is found even when the two depend on each other. Values found
+//! constant then become constants, and branches on them jumps; `len` of
+//! a constant string counts as one.
+
+use super::*;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone)]
+enum Lattice {
+    Undefined,
+    Const(Const),
+    Varying,
+}
+
+/// Compares constants by value, floats by their bits so that NaNs are
+/// one constant and zeros of either sign two.
+fn same(a: &Const, b: &Const) -> bool {
+    match (a, b) {
+        (Const::Float(x), Const::Float(y)) => x.to_bits() == y.to_bits(),
+        _ => a == b,
+    }
+}
+
+impl PartialEq for Lattice {
+    fn eq(&self, other: &Lattice) -> bool {
+        match (self, other) {
+            (Lattice::Undefined, Lattice::Undefined) | (Lattice::Varying, Lattice::Varying) => true,
+            (Lattice::Const(a), Lattice::Const(b)) => same(a, b),
+            _ => false,
+        }
+    }
+}
+
+fn meet(a: &Lattice, b: &Lattice) -> Lattice {
+    match (a, b) {
+        (Lattice::Undefined, x) | (x, Lattice::Undefined) => x.clone(),
+        (Lattice::Const(x), Lattice::Const(y)) if same(x, y) => a.clone(),
+        _ => Lattice::Varying,
+    }
+}
+
+/// Reports whether a value of type `ty` can be the constant `c`.
+fn fits(c: &Const, ty: Option<&Type>) -> bool {
+    matches!(
+        (c, ty),
+        (Const::Int(_), Some(Type::Int | Type::Byte))
+            | (Const::Float(_), Some(Type::Float64))
+            | (Const::Bool(_), Some(Type::Bool))
+            | (Const::String(_), Some(Type::String))
+    )
+}
+
+fn int(n: i64, ty: &Type) -> Const {
+    match ty {
+        Type::Byte => Const::Int(n as u8 as i64),
+        _ => Const::Int(n),
+    }
+}
+
+/// What `op` yields for constant operands of type `ty`, as the VM
+/// computes it; None when it panics.
+pub fn fold_binary(op: BinOp, a: &Const, b: &Const, ty: &Type) -> Option<Const> {
+    use std::cmp::Ordering;
+    let order = match (a, b) {
+        (Const::Int(x), Const::Int(y)) => Some(x.cmp(y)),
+        (Const::Float(x), Const::Float(y)) => x.partial_cmp(y),
+        (Const::String(x), Const::String(y)) => Some(x.cmp(y)),
+        (Const::Bool(x), Const::Bool(y)) if matches!(op, BinOp::Eq | BinOp::Ne) => Some(x.cmp(y)),
+        _ => return None,
+    };
+    if op.is_comparison() {
+        let result = match (op, order) {
+            (BinOp::Eq, order) => order == Some(Ordering::Equal),
+            (BinOp::Ne, order) => order != Some(Ordering::Equal),
+            (_, None) => false,
+            (BinOp::Lt, Some(order)) => order == Ordering::Less,
+            (BinOp::Le, Some(order)) => order != Ordering::Greater,
+            (BinOp::Gt, Some(order)) => order == Ordering::Greater,
+            (_, Some(order)) => order != Ordering::Less,
+        };
+        return Some(Const::Bool(result));
+    }
+    match (a, b) {
+        (Const::Int(x), Const::Int(y)) => {
+            let (x, y) = (*x, *y);
+            let n = match op {
+                BinOp::Add => x.wrapping_add(y),
+                BinOp::Sub => x.wrapping_sub(y),
+                BinOp::Mul => x.wrapping_mul(y),
+                BinOp::Div if y != 0 => x.wrapping_div(y),
+                BinOp::Rem if y != 0 => x.wrapping_rem(y),
+                _ => return None,
+            };
+            Some(int(n, ty))
+        }
+        (Const::Float(x), Const::Float(y)) => Some(Const::Float(match op {
+            BinOp::Add => x + y,
+            BinOp::Sub => x - y,
+            BinOp::Mul => x * y,
+            BinOp::Div => x / y,
+            _ => return None,
+        })),
+        (Const::String(x), Const::String(y)) if op == BinOp::Add => Some(Const::String([&x[..], &y[..]].concat().into())),
+        _ => None,
+    }
+}
+
+pub fn fold_unary(op: UnOp, a: &Const, ty: &Type) -> Option<Const> {
+    match (op, a) {
+        (UnOp::Not, Const::Bool(b)) => Some(Const::Bool(!b)),
+        (UnOp::Neg, Const::Int(n)) => Some(int(n.wrapping_neg(), ty)),
+        (UnOp::Neg, Const::Float(x)) => Some(Const::Float(-x)),
+        _ => None,
+    }
+}
+
+/// The constant `c` converted to type `to`, for the numeric conversions.
+pub fn fold_convert(c: &Const, to: &Type) -> Option<Const> {
+    match (c, to) {
+        (Const::Int(n), Type::Int | Type::Byte) => Some(int(*n, to)),
+        (Const::Int(n), Type::Float64) => Some(Const::Float(*n as f64)),
+        (Const::Float(x), Type::Int | Type::Byte) if x.is_finite() => Some(int(*x as i64, to)),
+        (Const::Float(x), Type::Float64) => Some(Const::Float(*x)),
+        (Const::String(_) | Const::Bool(_), _) if fits(c, Some(to)) => Some(c.clone()),
+        _ => None,
+    }
+}
+
+struct Sccp<'a> {
+    func: &'a Function,
+    block_of: Vec<Option<BlockId>>,
+    users: Vec<Vec<ValueId>>,
+    lattice: Vec<Lattice>,
+    reached: Vec<bool>,
+    edges: HashSet<(BlockId, BlockId)>,
+    flow: Vec<(Option<BlockId>, BlockId)>,
+    ssa: Vec<ValueId>,
+}
+
+impl Sccp<'_> {
+    fn solve(&mut self) {
+        self.flow.push((None, BlockId(0)));
+        self.flow.extend(self.func.recover.map(|b| (None, b)));
+        while !self.flow.is_empty() || !self.ssa.is_empty() {
+            while let Some((from, to)) = self.flow.pop() {
+                if let Some(from) = from {
+                    if !self.edges.insert((from, to)) {
+                        continue;
+                    }
+                }
+                let first = !self.reached[to.0];
+                self.reached[to.0] = true;
+                for &v in &self.func.blocks[to.0].insts {
+                    if first || self.func.inst(v).is_phi() {
+                        self.visit(v);
+                    }
+                }
+            }
+            while let Some(v) = self.ssa.pop() {
+                if self.block_of[v.0].is_some_and(|b| self.reached[b.0]) {
+                    self.visit(v);
+                }
+            }
+        }
+    }
+
+    fn visit(&mut self, value: ValueId) {
+        let block = self.block_of[value.0].unwrap();
+        match self.func.inst(value) {
+            Inst::Branch(cond, t, f) => match &self.lattice[cond.0] {
+                Lattice::Const(Const::Bool(true)) => self.flow.push((Some(block), *t)),
+                Lattice::Const(Const::Bool(false)) => self.flow.push((Some(block), *f)),
+                Lattice::Undefined => {}
+                _ => {
+                    self.flow.push((Some(block), *t));
+                    self.flow.push((Some(block), *f));
+                }
+            },
+            Inst::Jump(target) => self.flow.push((Some(block), *target)),
+            _ => {
+                let new = self.evaluate(value, block);
+                if new != self.lattice[value.0] {
+                    self.lattice[value.0] = new;
+                    self.ssa.extend(self.users[value.0].iter().copied());
+                }
+            }
+        }
+    }
+
+    fn evaluate(&self, value: ValueId, block: BlockId) -> Lattice {
+        let func = self.func;
+        let ty = func.ty(value).value();
+        let of = |v: &ValueId| &self.lattice[v.0];
+        let folded = |c: Option<Const>| c.filter(|c| fits(c, ty)).map_or(Lattice::Varying, Lattice::Const);
+        match func.inst(value) {
+            Inst::Const(c) if fits(c, ty) => Lattice::Const(c.clone()),
+            Inst::Phi(edges) => edges
+                .iter()
+                .filter(|(pred, _)| self.edges.contains(&(*pred, block)))
+                .fold(Lattice::Undefined, |acc, (_, v)| meet(&acc, of(v))),
+            Inst::Unary(op, a) => match (of(a), ty) {
+                (Lattice::Const(a), Some(ty)) => folded(fold_unary(*op, a, ty)),
+                (lattice, _) => lattice.clone(),
+            },
+            Inst::Binary(op, a, b) => match (of(a), of(b), func.ty(*a).value()) {
+                (Lattice::Const(a), Lattice::Const(b), Some(operands)) => folded(fold_binary(*op, a, b, operands)),
+                (Lattice::Varying, _, _) | (_, Lattice::Varying, _) => Lattice::Varying,
+                _ => Lattice::Undefined,
+            },
+            Inst::Convert(a) => match (of(a), ty) {
+                (Lattice::Const(c), Some(to)) => folded(fold_convert(c, to)),
+                (Lattice::Undefined, _) => Lattice::Undefined,
+                _ => Lattice::Varying,
+            },
+            Inst::Call(_, _, _) => match len_operand(func, value).map(|s| of(&s)) {
+                Some(Lattice::Const(Const::String(s))) => Lattice::Const(Const::Int(s.len() as i64)),
+                Some(Lattice::Undefined) => Lattice::Undefined,
+                _ => Lattice::Varying,
+            },
+            _ => Lattice::Varying,
+        }
+    }
+}
+
+pub fn run(func: &mut Function) {
+    let n = func.values.len();
+    let mut block_of = vec![None; n];
+    let mut users = vec![vec![]; n];
+    for (b, block) in func.blocks.iter().enumerate() {
+        for &v in &block.insts {
+            block_of[v.0] = Some(BlockId(b));
+            for op in func.inst(v).operands() {
+                users[op.0].push(v);
+            }
+        }
+    }
+    let mut sccp = Sccp {
+        func,
+        block_of,
+        users,
+        lattice: vec![Lattice::Undefined; n],
+        reached: vec![false; func.blocks.len()],
+        edges: HashSet::new(),
+        flow: vec![],
+        ssa: vec![],
+    };
+    sccp.solve();
+    let Sccp { lattice, reached, .. } = sccp;
+
+    for b in 0..func.blocks.len() {
+        if !reached[b] {
+            continue;
+        }
+        for v in func.blocks[b].insts.clone() {
+            match (&lattice[v.0], func.inst(v).clone()) {
+                (_, Inst::Const(_)) => {}
+                (Lattice::Const(c), Inst::Phi(_)) => {
+                    // a constant goes after the phis
+                    let Function { blocks, values, .. } = &mut *func;
+                    let insts = &mut blocks[b].insts;
+                    insts.retain(|&i| i != v);
+                    let at = insts.iter().position(|&i| !values[i.0].inst.is_phi()).unwrap_or(insts.len());
+                    insts.insert(at, v);
+                    func.values[v.0].inst = Inst::Const(c.clone());
+                }
+                (_, Inst::Branch(cond, t, f)) => {
+                    let taken = match &lattice[cond.0] {
+                        Lattice::Const(Const::Bool(taken)) => *taken,
+                        _ => continue,
+                    };
+                    let (keep, drop) = if taken { (t, f) } else { (f, t) };
+                    func.values[v.0].inst = Inst::Jump(keep);
+                    remove_edge(func, BlockId(b), drop);
+                }
+                (Lattice::Const(c), _) => func.values[v.0].inst = Inst::Const(c.clone()),
+                _ => {}
+            }
+        }
+    }
+    merge_blocks(func);
+    func.compact();
+}
@@ -0,0 +1,126 @@
+use crate::ir::opt::*;
+use crate::ir::test::tests::lower_source;
+use std::fs;
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The functions of the module but its initializers.
+  fn functions(module: &Module) -> String {
+    let mut out = String::new();
+    for id in (0..module.functions.len()).map(FuncId).filter(|id| !module.inits.contains(id)) {
+      out.push_str(&module.function_string(id));
+      out.push('\n');
+    }
+    out
+  }
+
+  fn optimize(module: &mut Module, passes: Vec<Pass>) {
+    if let Err(errors) = PassManager::new(passes).verify_each().run(module) {
+      panic!("{}\nverify errors: {:?}", module, errors);
+    }
+  }
+
+  /// Runs the passes over `testdata/{name}.go`, comparing the functions
+  /// before and after with `testdata/{name}.golden`. Setting
+  /// NAMU_UPDATE_GOLDEN writes the golden file instead.
+  fn check_golden(name: &str, passes: Vec<Pass>) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/ir/opt/testdata");
+    let source = fs::read_to_string(dir.join(format!("{}.go", name))).unwrap();
+    let mut module = lower_source(&source);
+    let before = functions(&module);
+    optimize(&mut module, passes);
+    let got = format!("-- before --\n{}-- after --\n{}", before, functions(&module));
+    let golden = dir.join(format!("{}.golden", name));
+    if std::env::var_os("NAMU_UPDATE_GOLDEN").is_some() {
+      fs::write(&golden, &got).unwrap();
+      return;
+    }
+    let expected = fs::read_to_string(&golden).unwrap_or_default();
+    assert_eq!(got, expected, "{}.golden differs; got\n{}", name, got);
+  }
+
+  #[test]
+  fn test_sccp() {
+    check_golden("sccp", vec![Pass::Sccp]);
+  }
+
+  #[test]
+  fn test_dce() {
+    check_golden("dce", vec![Pass::Dce]);
+  }
+
+  #[test]
+  fn test_cse() {
+    check_golden("cse", vec![Pass::Cse]);
+  }
+
+  #[test]
+  fn test_inline() {
+    check_golden("inline", vec![Pass::Inline]);
+  }
+
+  #[test]
+  fn test_bce() {
+    check_golden("bce", vec![Pass::Bce]);
+  }
+
+  #[test]
+  fn test_o2() {
+    check_golden("o2", passes(2));
+  }
+
+  #[test]
+  fn test_levels() {
+    assert!(passes(0).is_empty());
+    assert_eq!(passes(1), vec![Pass::Sccp, Pass::Cse, Pass::Dce]);
+    assert_eq!(passes(MAX_LEVEL), passes(MAX_LEVEL + 1));
+    let module = lower_source("let f = fn() { 1 + 2 }");
+    let mut unchanged = module.clone();
+    optimize(&mut unchanged, passes(0));
+    assert_eq!(unchanged.to_string(), module.to_string());
+  }
+
+  #[test]
+  fn test_levels_verify() {
+    let sources = [
+      "let fib = fn(n) { if n < 2 { return n }; fib(n - 1) + fib(n - 2) }; fib(10)",
+      "let counter = fn() { n := 0; fn() { n++; n } }\nlet f = fn() { c := counter(); c(); c() }",
+      "let f = fn() (r any) { defer fn() { r = recover() }(); panic(\"x\") }",
+      "let f = fn(a, b) { select { case v := <-a: v; case b <- 1: 2; default: 3 } }",
+      "let f = fn() { s := []int{3, 1, 2}; for i := range s { for j := 0; j < len(s) - i; j++ { s[j] += i } }; s }",
+      "let f = fn(m) { for k, v := range map[string]int{\"a\": 1} { m[k] = v }; v, ok := m[\"a\"]; ok && v == 1 }",
+      "let f = fn() { x := 1; for { if x > 100 { break }; x = x * 2 }; var b byte = 255; b++; x + int(b) }",
+      "let id = fn(x) { x }\nlet f = fn() { g := fn() { id(1) }; id(g()) + id(2) }",
+      "let f = fn() { ch := make(chan int); go fn() { ch <- 1; close(ch) }(); v, ok := <-ch; for x := range ch { v += x }; ok }",
+    ];
+    for source in sources {
+      for level in 0..=MAX_LEVEL {
+        let mut module = lower_source(source);
+        if let Err(errors) = PassManager::for_level(level).verify_each().run(&mut module) {
+          panic!("{}\n-O{}: {:?}\n{}", source, level, errors, module);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_fold() {
+    use sccp::{fold_binary, fold_convert, fold_unary};
+    assert_eq!(fold_binary(BinOp::Add, &Const::Int(i64::MAX), &Const::Int(1), &Type::Int), Some(Const::Int(i64::MIN)));
+    assert_eq!(fold_binary(BinOp::Div, &Const::Int(1), &Const::Int(0), &Type::Int), None);
+    assert_eq!(fold_binary(BinOp::Add, &Const::Int(255), &Const::Int(1), &Type::Byte), Some(Const::Int(0)));
+    assert_eq!(fold_binary(BinOp::Lt, &Const::Float(f64::NAN), &Const::Float(1.0), &Type::Float64), Some(Const::Bool(false)));
+    assert_eq!(fold_binary(BinOp::Ne, &Const::Float(f64::NAN), &Const::Float(f64::NAN), &Type::Float64), Some(Const::Bool(true)));
+    assert_eq!(
+      fold_binary(BinOp::Add, &Const::String(b"a"[..].into()), &Const::String(b"b"[..].into()), &Type::String),
+      Some(Const::String(b"ab"[..].into()))
+    );
+    assert_eq!(fold_unary(UnOp::Neg, &Const::Int(1), &Type::Byte), Some(Const::Int(255)));
+    assert_eq!(fold_convert(&Const::Float(2.9), &Type::Int), Some(Const::Int(2)));
+    assert_eq!(fold_convert(&Const::Int(300), &Type::Byte), Some(Const::Int(44)));
+    assert_eq!(fold_convert(&Const::Float(f64::INFINITY), &Type::Int), None);
+  }
+}
@@ -0,0 +1,22 @@
+// Bounds checks go for loops over len(s) and range keys, and stay for
+// indexes the loop does not bound.
+let f = fn(x) {
+	s := x.([]int)
+	t := 0
+	for i := 0; i < len(s); i++ {
+		t += s[i]
+	}
+	for i := 0; len(s) > i; i += 2 {
+		t += s[i]
+	}
+	for i, v := range s {
+		t += s[i] * v
+	}
+	for i := 0; i <= len(s); i++ {
+		t += s[i]
+	}
+	for i := 0; i < len(s); i++ {
+		t += s[i+1]
+	}
+	t + s[0]
+}
@@ -0,0 +1,14 @@
+// Repeated expressions are computed once where the first dominates the
+// rest, but not across branches that do not dominate each other.
+let f = fn(s) {
+	t := s.([]int)
+	a := len(t) * 2 + len(t) * 2
+	b := 0
+	if a > 4 {
+		b = len(t) - 1
+	} else {
+		b = len(t) - 1
+	}
+	c := len(t) - 1
+	a + b + c
+}
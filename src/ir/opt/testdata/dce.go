@@ -0,0 +1,15 @@
+// Values nothing uses go, calls and stores stay, and so does a division
+// that may panic.
+let f = fn(a) {
+	n := 10
+	unused := n * 2 + 1
+	s := []int{1, 2}
+	m := len(s)
+	k := m / n
+	d := m / (m - 2)
+	println(a)
+	for i := 0; i < 3; i++ {
+		unused = unused + i
+	}
+	n
+}
@@ -0,0 +1,21 @@
+// Small functions are inlined into their callers; a large one, a
+// recursive one, and one that defers are not.
+let sq = fn(x) { x * x }
+let abs = fn(x) { if x < 0 { return -x }; x }
+let fact = fn(n) { if n < 2 { return 1 }; n * fact(n - 1) }
+let logged = fn(x) { defer println("done"); x }
+let big = fn(x) {
+	println(x, x, x)
+	println(x, x, x)
+	println(x, x, x)
+	println(x, x, x)
+	println(x, x, x)
+	println(x, x, x)
+	println(x, x, x)
+	println(x, x, x)
+	x
+}
+let f = fn(y) {
+	add := fn(a, b) { a + b }
+	add(sq(y), abs(y)) + fact(y) + logged(y) + big(y)
+}
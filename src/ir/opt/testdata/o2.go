@@ -0,0 +1,12 @@
+// All the passes together, as -O2 runs them.
+let get = fn(s, i) { s[i] }
+let sum = fn(x) {
+	s := x.([]int)
+	total := 0
+	for i := 0; i < len(s); i++ {
+		if 1 < 2 {
+			total += s[i]
+		}
+	}
+	total + get(s, 0)
+}
@@ -0,0 +1,18 @@
+// Constants fold through arithmetic, around a loop whose variable never
+// changes, and into the branches they decide.
+let f = fn() {
+	x := 2 * 3 + 1
+	y := 0
+	for i := 0; i < 4; i++ {
+		if x > 5 {
+			y = y + 0
+		} else {
+			y = y + i
+		}
+	}
+	s := "a" + "b"
+	if len(s) == 2 && y == 0 {
+		return x / 7
+	}
+	x % 0
+}
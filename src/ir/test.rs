@@ -0,0 +1,480 @@
+use crate::ir::*;
+use crate::lexer::Lexer;
+use crate::loader::{self, Package, SourceFile, COMMAND_LINE_PACKAGE};
+use crate::playground;
+use crate::parser::Parser;
+use crate::types::Type;
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+
+  pub(crate) fn package(path: &str, name: &str, source: &str) -> Package {
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    if !p.errors().is_empty() {
+      panic!("parser errors: {:?}", p.errors());
+    }
+    Package {
+      path: path.to_string(),
+      name: name.to_string(),
+      dir: ".".into(),
+      files: vec![SourceFile { name: format!("{}.go", name), source: source.to_string(), program }],
+      imports: vec![],
+      selections: vec![],
+    }
+  }
+
+  /// Lowers packages that compile, checking the result verifies.
+  pub(crate) fn lower_packages(packages: &[Package]) -> Module {
+    if let Err(errors) = loader::compile(packages) {
+      panic!("compile errors: {:?}", errors);
+    }
+    let module = lower(packages);
+    if let Err(errors) = verify(&module) {
+      panic!("{}\nverify errors: {:?}", module, errors);
+    }
+    module
+  }
+
+  /// Lowers a program of one file, as `namu build` does.
+  pub(crate) fn lower_source(source: &str) -> Module {
+    lower_packages(&[package(COMMAND_LINE_PACKAGE, "main", source)])
+  }
+
+  /// Programs and the output every backend must give for them, which is
+  /// the VM's.
+  pub(crate) const PROGRAMS: &[(&str, &str, &str)] = &[
+    (
+      "fmt",
+      r#"import "fmt"
+let main = fn() {
+  fmt.Println("hello", 1, 2.5, true, nil)
+  fmt.Printf("%5.2f|%-4s|%03d|%x|%q\n", 2.0/3, "ab", 7, "hi", "a\tb")
+}"#,
+      "hello 1 2.5 true <nil>\n 0.67|ab  |007|6869|\"a\\tb\"\n",
+    ),
+    (
+      "structs_maps_and_closures",
+      r#"import "fmt"
+import "sort"
+type P struct { X int; Name string }
+let main = fn() {
+  ps := []P{{1, "a"}, {2, "b"}}
+  q := ps[0]
+  q.X = 9
+  p := &ps[1]
+  p.X = 7
+  m := map[string]int{}
+  for _, w := range []string{"b", "a", "b"} { m[w]++ }
+  keys := []string{}
+  for k := range m { keys = append(keys, k) }
+  sort.Strings(keys)
+  n := 0
+  let next = fn() { n++; n }
+  next()
+  fmt.Println(ps, q, m, keys, next())
+}"#,
+      "[{1 a} {7 b}] {9 a} map[a:1 b:2] [a b] 2\n",
+    ),
+    (
+      "nan_keys",
+      r#"import "fmt"
+let main = fn() {
+  z := 0.0
+  nan := z / z
+  m := map[float64]int{}
+  m[nan] = 1
+  m[nan] = 2
+  m[-z] = 3
+  m[z] = 4
+  delete(m, nan)
+  fmt.Println(len(m), m[nan], m[0])
+}"#,
+      "3 0 4\n",
+    ),
+    (
+      "defer_and_recover",
+      r#"import "fmt"
+let safe = fn(xs, i) (r any) {
+  defer fn() {
+    e := recover()
+    if e != nil { r = fmt.Sprint("recovered: ", e) }
+  }()
+  return xs[i]
+}
+let main = fn() {
+  defer fmt.Println("deferred")
+  fmt.Println(safe([]int{1, 2}, 1))
+  fmt.Println(safe([]int{1, 2}, 5))
+}"#,
+      "2\nrecovered: runtime error: index out of range [5] with length 2\ndeferred\n",
+    ),
+    (
+      "nil_pointer_in_interface",
+      r#"import "fmt"
+type P struct { X int }
+let main = fn() {
+  var np *P
+  var e interface{} = np
+  var n interface{}
+  fmt.Println(e == nil, n == nil, np == nil)
+}"#,
+      "false true true\n",
+    ),
+    (
+      "make_out_of_range",
+      r#"import "fmt"
+let main = fn() {
+  defer fn() { fmt.Println(recover()) }()
+  s := make([]int, 0, 9223372036854775807)
+  fmt.Println(len(s))
+}"#,
+      "runtime error: makeslice: cap out of range\n",
+    ),
+    (
+      "type_names",
+      r#"import "fmt"
+type P struct { X int }
+let main = fn() {
+  fmt.Printf("%T %T %T %T\n", P{}, &P{}, map[string]P{}, []error{})
+  fmt.Printf("%T %v\n", struct{ A P }{}, 1)
+}"#,
+      "main.P *main.P map[string]main.P []error\nstruct { A main.P } 1\n",
+    ),
+  ];
+
+  #[test]
+  fn test_programs_run_on_the_vm() {
+    for (name, source, expected) in PROGRAMS {
+      assert_eq!(playground::run(source), *expected, "program {}", name);
+    }
+  }
+
+  /// The text of the module's function named `name`.
+  fn function(module: &Module, name: &str) -> String {
+    match module.functions.iter().position(|f| f.name == name) {
+      Some(i) => module.function_string(FuncId(i)),
+      None => panic!("no function {} in\n{}", name, module),
+    }
+  }
+
+  fn check_function(source: &str, name: &str, expected: &str) {
+    let module = lower_source(source);
+    let got = function(&module, name);
+    assert_eq!(got, expected, "\n{}", got);
+  }
+
+  #[test]
+  fn test_straight_line() {
+    check_function(
+      "let add = fn(a, b) { a + b * 2 }",
+      "main.add",
+      "func main.add(a any, b any) any
+b0:
+  v0: any = param a
+  v1: any = param b
+  v2: int = const 2
+  v3: any = convert v2
+  v4: any = mul v1, v3
+  v5: any = add v0, v4
+  ret v5
+",
+    );
+  }
+
+  #[test]
+  fn test_if_joins_with_phi() {
+    check_function(
+      "let f = fn(c) { x := 1; if c { x = 2 }; x }",
+      "main.f",
+      "func main.f(c any) any
+b0:
+  v0: any = param c
+  v1: int = const 1
+  branch v0, b1, b2
+b1: ; preds b0
+  v3: int = const 2
+  jump b2
+b2: ; preds b0, b1
+  v5: int = phi [b0: v1, b1: v3]
+  v6: any = convert v5
+  ret v6
+",
+    );
+  }
+
+  #[test]
+  fn test_if_expression_value() {
+    check_function(
+      "let f = fn(c) { if c { 1 } else { 2.5 } }",
+      "main.f",
+      "func main.f(c any) any
+b0:
+  v0: any = param c
+  branch v0, b1, b2
+b1: ; preds b0
+  v2: int = const 1
+  v3: any = convert v2
+  jump b3
+b2: ; preds b0
+  v5: float64 = const 2.5
+  v6: any = convert v5
+  jump b3
+b3: ; preds b1, b2
+  v8: any = phi [b1: v3, b2: v6]
+  ret v8
+",
+    );
+  }
+
+  #[test]
+  fn test_loop_places_header_phis() {
+    check_function(
+      "let f = fn() { s := 0; for i := 0; i < 10; i++ { s += i }; s }",
+      "main.f",
+      "func main.f() any
+b0:
+  v0: int = const 0
+  v1: int = const 0
+  jump b1
+b1: ; preds b0, b4
+  v3: int = phi [b0: v0, b4: v10]
+  v4: int = phi [b0: v1, b4: v13]
+  v5: int = const 10
+  v6: bool = lt v4, v5
+  branch v6, b3, b2
+b2: ; preds b1
+  v8: any = convert v3
+  ret v8
+b3: ; preds b1
+  v10: int = add v3, v4
+  jump b4
+b4: ; preds b3
+  v12: int = const 1
+  v13: int = add v4, v12
+  jump b1
+",
+    );
+  }
+
+  #[test]
+  fn test_closures_capture_cells() {
+    let module = lower_source("let counter = fn() { n := 0; fn() { n++; n } }");
+    assert_eq!(
+      function(&module, "main.counter"),
+      "func main.counter() any
+b0:
+  v0: int = const 0
+  v1: *int = alloc
+  store v1, v0
+  v3: fn = closure main.counter.func1 [v1]
+  v4: any = convert v3
+  ret v4
+"
+    );
+    assert_eq!(
+      function(&module, "main.counter.func1"),
+      "func main.counter.func1() any
+  free n *int
+b0:
+  v0: *int = free n
+  v1: int = load v0
+  v2: int = const 1
+  v3: int = add v1, v2
+  v4: *int = free n
+  store v4, v3
+  v6: *int = free n
+  v7: int = load v6
+  v8: any = convert v7
+  ret v8
+"
+    );
+  }
+
+  #[test]
+  fn test_defer_and_named_result() {
+    check_function(
+      "let f = fn() (n int) { defer fn() { n = 2 }(); return 1 }",
+      "main.f",
+      "func main.f() any
+b0:
+  v0: int = const 0
+  v1: *int = alloc
+  store v1, v0
+  v3: fn = closure main.f.func1 [v1]
+  defer v3()
+  v5: int = const 1
+  store v1, v5
+  rundefers
+  v8: int = load v1
+  v9: any = convert v8
+  ret v9
+b1: ; recover
+  v11: int = load v1
+  v12: any = convert v11
+  ret v12
+",
+    );
+  }
+
+  #[test]
+  fn test_multiple_results() {
+    check_function(
+      "import \"strconv\"\nlet f = fn(s) { n, err := strconv.Atoi(s); if err != nil { return 0 }; n }",
+      "main.f",
+      "func main.f(s any) any
+b0:
+  v0: any = param s
+  v1: fn = package strconv.Atoi
+  v2: (int, error) = call v1(v0)
+  v3: int = extract v2, 0
+  v4: error = extract v2, 1
+  v5: nil = const nil
+  v6: error = const nil
+  v7: bool = ne v4, v6
+  branch v7, b1, b2
+b1: ; preds b0
+  v9: int = const 0
+  v10: any = convert v9
+  ret v10
+b2: ; preds b0
+  v12: any = convert v3
+  ret v12
+",
+    );
+  }
+
+  #[test]
+  fn test_globals_and_init() {
+    let module = lower_source("let x = 1\nx = x + 1\nlet main = fn() { x }");
+    assert_eq!(module.globals.len(), 2);
+    assert_eq!(module.globals[0].name, "main.x");
+    assert!(module.globals[0].ty.identical(&Type::Int));
+    assert_eq!(module.main, Some(1));
+    assert_eq!(
+      function(&module, "main.init"),
+      "func main.init()
+b0:
+  v0: int = const 1
+  v1: *int = global main.x
+  store v1, v0
+  v3: *int = global main.x
+  v4: int = load v3
+  v5: int = const 1
+  v6: int = add v4, v5
+  v7: *int = global main.x
+  store v7, v6
+  v9: fn = closure main.main []
+  v10: *fn = global main.main
+  store v10, v9
+  ret
+"
+    );
+  }
+
+  #[test]
+  fn test_forward_declarations() {
+    let module = lower_source("let main = fn() { helper(count) }\nlet helper = fn(n) { n + 1 }\nvar count int = 2\nlet later = 3");
+    let globals: Vec<(&str, String)> = module.globals.iter().map(|g| (g.name.as_str(), g.ty.to_string())).collect();
+    assert_eq!(globals, [("main.helper", "fn".to_string()), ("main.count", "int".to_string()), ("main.main", "fn".to_string()), ("main.later", "int".to_string())]);
+    let main = function(&module, "main.main");
+    assert!(main.contains("global main.helper") && main.contains("global main.count"), "{}", main);
+  }
+
+  #[test]
+  fn test_assignment_widens_declaration() {
+    // x starts as an int, then holds a string, so it is an any throughout
+    check_function(
+      "let f = fn() { x := 1; x = \"s\"; x }",
+      "main.f",
+      "func main.f() any
+b0:
+  v0: int = const 1
+  v1: any = convert v0
+  v2: string = const \"s\"
+  v3: any = convert v2
+  ret v3
+",
+    );
+  }
+
+  #[test]
+  fn test_imported_package() {
+    let lib = package("example.com/lib", "lib", "package lib\nlet Scale = 3\nlet Mul = fn(x) { x * Scale }");
+    let main = package("command-line-arguments", "main", "import \"example.com/lib\"\nlet main = fn() { lib.Scale = 4; lib.Mul(2) }");
+    let module = lower_packages(&[lib, main]);
+    let names: Vec<&str> = module.inits.iter().map(|&id| module.function(id).name.as_str()).collect();
+    assert_eq!(names, ["example.com/lib.init", "main.init"]);
+    assert_eq!(module.globals[0].name, "example.com/lib.Scale");
+    assert!(function(&module, "main.main").contains("global example.com/lib.Scale"));
+  }
+
+  #[test]
+  fn test_verify_accepts_programs() {
+    let sources = [
+      "let fib = fn(n) { if n < 2 { return n }; fib(n - 1) + fib(n - 2) }; fib(10)",
+      "let f = fn() { let g = fn(n) { if n == 0 { 0 } else { g(n - 1) } }; g(3) }",
+      "let f = fn() { fs := []any{}; for i := 0; i < 3; i++ { fs = append(fs, fn() { println(i) }) }; fs }",
+      "let f = fn(xs) { t := 0; for _, x := range xs { if x == nil { continue }; if t > 10 { break }; t += 1 }; t }",
+      "let f = fn(m) { for k, v := range map[string]int{\"a\": 1} { m[k] = v }; v, ok := m[\"a\"]; ok && v == 1 }",
+      "let f = fn() { for i := range 3 { println(i) }; for r := range \"héllo\" { println(r) } }",
+      "type P struct { X int; Next *P }\nlet f = fn() { p := &P{X: 1}; p.Next = &P{2, nil}; q := p.Next; q.X += p.X; (*q).X }",
+      "type P struct { X int }\nlet f = fn() { ps := []*P{{1}, {2}}; ps[0].X = 3; s := []int{1, 2, 3}; s[1:]; len(s[:2]) }",
+      "let f = fn() { ch := make(chan int); go fn() { ch <- 1; close(ch) }(); v, ok := <-ch; for x := range ch { v += x }; ok }",
+      "let f = fn(a, b) { select { case v := <-a: v; case b <- 1: 2; default: 3 } }",
+      "let f = fn(a) { for { select { case <-a: break; } } }",
+      "let f = fn() (r any) { defer fn() { r = recover() }(); panic(\"x\") }",
+      "let f = fn() { defer println(\"done\"); for i := 0; i < 3; i++ { defer println(i) } }",
+      "import \"strings\"\nimport \"sort\"\nlet f = fn(s) { parts := strings.Split(s, \",\"); sort.Strings(parts); strings.Join(parts, \"-\") }",
+      "import \"fmt\"\nimport \"errors\"\nlet f = fn() { err := errors.New(\"x\"); e := fmt.Errorf(\"wrap: %w\", err); errors.Is(e, err) }",
+      "import \"math\"\nlet f = fn(x) { math.Sqrt(x) * math.Pi }",
+      "let f = fn(x) { s, ok := x.(string); if !ok { return len(x.([]int)) }; len(s) }",
+      "let f = fn() { x := 0; p := &x; *p = 5; x }",
+      "let f = fn() { var a []int; var m map[string]int; var b byte = 97; var g float64 = 1; a = append(a, 1); m = make(map[string]int); b++; g / 2 }",
+      "let f = fn() { a, b := 1, 2; a, b = b, a; c := min(a, b); max(c, 3) }",
+      "let f = fn(c) { x := 1; for c { if x > 3 { x = 0 } else { x++ } }; -x }",
+      "let f = fn(c) { c || !c && c }",
+      "let f = fn() { x := 1; g := fn() { y := 2; fn() { x + y } }; g() }",
+      "let f = fn(xs) { t := 0; for i := 0; i < len(xs); i++ { t += xs[i] }; for { break }; return t; t }",
+    ];
+    for source in sources {
+      lower_source(source);
+    }
+  }
+
+  #[test]
+  fn test_verify_rejects_bad_ir() {
+    // a use its definition does not dominate
+    let mut func = Function::new("bad");
+    func.result = Some(Type::any());
+    let b1 = func.add_block();
+    let b2 = func.add_block();
+    let b3 = func.add_block();
+    let c = func.append(BlockId(0), Inst::Const(Const::Bool(true)), Ty::Value(Type::Bool));
+    func.append(BlockId(0), Inst::Branch(c, b1, b2), Ty::Void);
+    let v = func.append(b1, Inst::Const(Const::Nil), Ty::Value(Type::any()));
+    func.append(b1, Inst::Jump(b3), Ty::Void);
+    func.append(b2, Inst::Jump(b3), Ty::Void);
+    func.append(b3, Inst::Return(Some(v)), Ty::Void);
+    for (block, preds) in [(b1, vec![BlockId(0)]), (b2, vec![BlockId(0)]), (b3, vec![b1, b2])] {
+      func.blocks[block.0].preds = preds;
+    }
+    let module = Module { functions: vec![func], ..Module::default() };
+    assert_eq!(verify(&module), Err(vec!["bad: b3: v5: operand v2 does not dominate its use".to_string()]));
+
+    // an operand of the wrong type, and a block without a terminator
+    let mut func = Function::new("bad");
+    let a = func.append(BlockId(0), Inst::Const(Const::Int(1)), Ty::Value(Type::Int));
+    let b = func.append(BlockId(0), Inst::Const(Const::String("s".as_bytes().into())), Ty::Value(Type::String));
+    func.append(BlockId(0), Inst::Binary(BinOp::Add, a, b), Ty::Value(Type::Int));
+    let module = Module { functions: vec![func], ..Module::default() };
+    assert_eq!(
+      verify(&module),
+      Err(vec!["bad: b0: missing terminator".to_string()])
+    );
+    let mut module = module;
+    module.functions[0].append(BlockId(0), Inst::Return(None), Ty::Void);
+    assert_eq!(verify(&module), Err(vec!["bad: b0: v2: operands of different types".to_string()]));
+  }
+}
@@ -0,0 +1,306 @@
+//! Checks of the invariants of the IR, for tests and for passes to
+//! assert they keep: well-formed blocks, definitions dominating their
+//! uses, and operands of the types their instructions take.
+
+use super::*;
+
+/// Verifies every function of the module, listing what is wrong with it.
+pub fn verify(module: &Module) -> Result<(), Vec<String>> {
+    let mut errors = vec![];
+    for func in &module.functions {
+        Verifier { module, func, errors: &mut errors }.function();
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+struct Verifier<'a> {
+    module: &'a Module,
+    func: &'a Function,
+    errors: &'a mut Vec<String>,
+}
+
+impl Verifier<'_> {
+    fn error(&mut self, block: BlockId, value: Option<ValueId>, message: String) {
+        let at = match value {
+            Some(value) => format!("{}: {}: {}", self.func.name, block, value),
+            None => format!("{}: {}", self.func.name, block),
+        };
+        self.errors.push(format!("{}: {}", at, message));
+    }
+
+    fn function(&mut self) {
+        let func = self.func;
+        // where each value is placed
+        let mut placed: Vec<Option<(BlockId, usize)>> = vec![None; func.values.len()];
+        for (b, block) in func.blocks.iter().enumerate() {
+            for (i, &value) in block.insts.iter().enumerate() {
+                if value.0 >= func.values.len() {
+                    self.error(BlockId(b), Some(value), "undefined value".to_string());
+                    continue;
+                }
+                if placed[value.0].is_some() {
+                    self.error(BlockId(b), Some(value), "placed twice".to_string());
+                }
+                placed[value.0] = Some((BlockId(b), i));
+            }
+        }
+        if self.blocks() {
+            let idom = func.dominators();
+            for (b, block) in func.blocks.iter().enumerate() {
+                for &value in &block.insts {
+                    if value.0 < func.values.len() {
+                        self.uses(BlockId(b), value, &placed, &idom);
+                        self.types(BlockId(b), value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks the shape of the blocks and the edges between them,
+    /// reporting whether the rest can be checked.
+    fn blocks(&mut self) -> bool {
+        let func = self.func;
+        let mut ok = true;
+        let mut preds = vec![vec![]; func.blocks.len()];
+        for (b, block) in func.blocks.iter().enumerate() {
+            let id = BlockId(b);
+            let insts: Vec<&Inst> = block.insts.iter().filter(|v| v.0 < func.values.len()).map(|&v| func.inst(v)).collect();
+            match insts.last() {
+                Some(last) if last.is_terminator() => {
+                    for target in last.targets() {
+                        if target.0 >= func.blocks.len() {
+                            self.error(id, None, format!("jump to undefined block {}", target));
+                            ok = false;
+                        } else {
+                            preds[target.0].push(id);
+                        }
+                    }
+                }
+                _ => {
+                    self.error(id, None, "missing terminator".to_string());
+                    ok = false;
+                }
+            }
+            for (i, &value) in block.insts.iter().enumerate() {
+                let inst = func.inst(value);
+                if inst.is_terminator() && i + 1 != block.insts.len() {
+                    self.error(id, Some(value), "terminator before the end of the block".to_string());
+                }
+                if inst.is_phi() && i > 0 && !func.inst(block.insts[i - 1]).is_phi() {
+                    self.error(id, Some(value), "phi after other instructions".to_string());
+                }
+                if let Inst::Phi(edges) = inst {
+                    let mut from: Vec<BlockId> = edges.iter().map(|&(b, _)| b).collect();
+                    let mut expected = block.preds.clone();
+                    from.sort();
+                    expected.sort();
+                    if from != expected {
+                        self.error(id, Some(value), "phi operands do not match the predecessors".to_string());
+                    }
+                }
+            }
+        }
+        for (b, block) in func.blocks.iter().enumerate() {
+            let mut expected = preds[b].clone();
+            let mut actual = block.preds.clone();
+            expected.sort();
+            actual.sort();
+            if ok && expected != actual {
+                self.error(BlockId(b), None, "predecessors do not match the jumps".to_string());
+            }
+        }
+        if let Some(recover) = func.recover {
+            if recover.0 >= func.blocks.len() || !func.blocks[recover.0].preds.is_empty() {
+                self.error(recover, None, "recover block with predecessors".to_string());
+            }
+        }
+        if ok {
+            let mut reachable = vec![false; func.blocks.len()];
+            for block in func.reverse_postorder() {
+                reachable[block.0] = true;
+            }
+            for (b, reachable) in reachable.into_iter().enumerate() {
+                if !reachable {
+                    self.error(BlockId(b), None, "unreachable block".to_string());
+                }
+            }
+        }
+        ok
+    }
+
+    /// Checks that the operands of `value` are defined before it.
+    fn uses(&mut self, block: BlockId, value: ValueId, placed: &[Option<(BlockId, usize)>], idom: &[Option<BlockId>]) {
+        let func = self.func;
+        let position = placed[value.0].map_or(0, |(_, i)| i);
+        let uses: Vec<(ValueId, BlockId, usize)> = match func.inst(value) {
+            // a phi uses its operand at the end of the predecessor
+            Inst::Phi(edges) => edges.iter().map(|&(pred, v)| (v, pred, usize::MAX)).collect(),
+            inst => inst.operands().into_iter().map(|v| (v, block, position)).collect(),
+        };
+        for (operand, at, index) in uses {
+            let def = match placed.get(operand.0).copied().flatten() {
+                Some(def) => def,
+                None => {
+                    self.error(block, Some(value), format!("operand {} is not defined", operand));
+                    continue;
+                }
+            };
+            let dominated = if def.0 == at { def.1 < index } else { dominates(idom, def.0, at) };
+            if !dominated {
+                self.error(block, Some(value), format!("operand {} does not dominate its use", operand));
+            }
+            let expects_aggregate = matches!(func.inst(value), Inst::Extract(_, _) | Inst::Next(_));
+            match func.ty(operand) {
+                Ty::Value(_) => {}
+                Ty::Tuple(_) | Ty::Iter if expects_aggregate => {}
+                ty => self.error(block, Some(value), format!("operand {} of type {} is not a value", operand, ty_string(ty))),
+            }
+        }
+    }
+
+    /// The type of a value operand, or None for one the use checks
+    /// already reported.
+    fn ty(&self, value: ValueId) -> Option<&Type> {
+        self.func.values.get(value.0).and_then(|data| data.ty.value())
+    }
+
+    /// Checks that `value` has a type its instruction can yield, from
+    /// operands of the types it takes.
+    fn types(&mut self, block: BlockId, value: ValueId) {
+        let func = self.func;
+        let data = &func.values[value.0];
+        let ty = data.ty.value();
+        let mut errors = vec![];
+        let mut expect = |ok: bool, message: &str| {
+            if !ok {
+                errors.push(message.to_string());
+            }
+        };
+        let dynamic = |ty: Option<&Type>| ty.is_some_and(Type::is_interface);
+        match &data.inst {
+            Inst::Const(c) => {
+                let ok = match (c, ty) {
+                    (Const::Nil, Some(ty)) => matches!(
+                        ty,
+                        Type::Nil | Type::Func | Type::Slice(_) | Type::Map(_, _) | Type::Pointer(_) | Type::Chan(_) | Type::Interface(_)
+                    ),
+                    (Const::Bool(_), Some(ty)) => matches!(ty, Type::Bool),
+                    (Const::Int(_), Some(ty)) => matches!(ty, Type::Int | Type::Byte),
+                    (Const::Float(_), Some(ty)) => matches!(ty, Type::Float64),
+                    (Const::String(_), Some(ty)) => matches!(ty, Type::String),
+                    (_, None) => false,
+                };
+                expect(ok, "constant of another type");
+            }
+            Inst::Param(i) => {
+                let param = func.params.get(*i);
+                expect(param.is_some_and(|p| Some(&p.ty) == ty || ty.is_some_and(|ty| ty.identical(&p.ty))), "parameter of another type");
+            }
+            Inst::FreeVar(i) => {
+                let free = func.free.get(*i);
+                expect(free.is_some_and(|f| ty.is_some_and(|ty| ty.identical(&f.ty))), "free variable of another type");
+            }
+            Inst::Global(i) => {
+                let global = self.module.globals.get(*i);
+                expect(
+                    global.is_some_and(|g| matches!(ty, Some(Type::Pointer(elem)) if elem.identical(&g.ty))),
+                    "global of another type",
+                );
+            }
+            Inst::Closure(id, captured) => match self.module.functions.get(id.0) {
+                Some(callee) => {
+                    expect(callee.free.len() == captured.len(), "wrong number of captured variables");
+                    for (free, &v) in callee.free.iter().zip(captured) {
+                        expect(self.ty(v).is_some_and(|t| t.identical(&free.ty)), "captured variable of another type");
+                    }
+                    expect(matches!(ty, Some(Type::Func)), "closure is not a func");
+                }
+                None => expect(false, "closure of an undefined function"),
+            },
+            Inst::Phi(edges) => {
+                for &(_, v) in edges {
+                    expect(self.ty(v).zip(ty).is_some_and(|(a, b)| a.identical(b)), "phi operand of another type");
+                }
+            }
+            Inst::Unary(op, v) => {
+                let operand = self.ty(*v);
+                match op {
+                    UnOp::Not => expect(matches!(operand, Some(Type::Bool)) || dynamic(operand), "not of a non-bool"),
+                    UnOp::Neg => expect(operand.zip(ty).is_some_and(|(a, b)| a.identical(b)), "neg of another type"),
+                }
+            }
+            Inst::Binary(op, a, b) => {
+                let (a, b) = (self.ty(*a), self.ty(*b));
+                expect(a.zip(b).is_some_and(|(a, b)| a.identical(b)), "operands of different types");
+                if op.is_comparison() {
+                    expect(matches!(ty, Some(Type::Bool)), "comparison is not a bool");
+                } else {
+                    expect(a.zip(ty).is_some_and(|(a, b)| a.identical(b)), "result of another type than the operands");
+                }
+            }
+            Inst::Load(p) => {
+                let ptr = self.ty(*p);
+                let ok = match ptr {
+                    Some(Type::Pointer(elem)) => ty.is_some_and(|ty| ty.identical(elem)),
+                    _ => dynamic(ptr),
+                };
+                expect(ok, "load of another type than the pointer's");
+            }
+            Inst::Store(p, v) => {
+                let ptr = self.ty(*p);
+                let ok = match ptr {
+                    Some(Type::Pointer(elem)) => self.ty(*v).is_some_and(|ty| ty.identical(elem)),
+                    _ => dynamic(ptr),
+                };
+                expect(ok, "store of another type than the pointer's");
+            }
+            Inst::BoundsCheck(i, n) => {
+                expect(matches!(self.ty(*i), Some(Type::Int)) && matches!(self.ty(*n), Some(Type::Int)), "bounds check of a non-int");
+            }
+            Inst::Alloc => expect(matches!(ty, Some(Type::Pointer(_))), "alloc is not a pointer"),
+            Inst::FieldAddr(_, _) | Inst::IndexAddr(_, _) => expect(matches!(ty, Some(Type::Pointer(_))), "address is not a pointer"),
+            Inst::Extract(t, i) => {
+                let ok = match &func.values.get(t.0).map(|d| &d.ty) {
+                    Some(Ty::Tuple(types)) => types.get(*i).zip(ty).is_some_and(|(a, b)| a.identical(b)),
+                    _ => false,
+                };
+                expect(ok, "extract of another type than the tuple's");
+            }
+            Inst::Range(_) => expect(data.ty == Ty::Iter, "range is not an iterator"),
+            Inst::Next(it) => {
+                let iter = func.values.get(it.0).is_some_and(|d| d.ty == Ty::Iter);
+                expect(iter, "next of a non-iterator");
+                expect(matches!(&data.ty, Ty::Tuple(types) if types.len() == 3 && matches!(types[0], Type::Bool)), "next is not a (bool, key, value) tuple");
+            }
+            Inst::Branch(c, _, _) => {
+                let cond = self.ty(*c);
+                expect(matches!(cond, Some(Type::Bool)) || dynamic(cond), "branch on a non-bool");
+            }
+            Inst::Return(v) => {
+                let ok = match (v, &func.result) {
+                    (None, None) => true,
+                    (Some(v), Some(result)) => self.ty(*v).is_some_and(|ty| ty.identical(result)),
+                    _ => false,
+                };
+                expect(ok, "return of another type than the result");
+            }
+            Inst::Call(callee, args, _) | Inst::Go(callee, args) | Inst::Defer(callee, args) => {
+                let native = matches!(func.values.get(callee.0).map(|d| &d.inst), Some(Inst::Builtin(_) | Inst::Package(_, _)));
+                if !native {
+                    for &arg in args {
+                        expect(dynamic(self.ty(arg)), "argument of a function of the program is not boxed");
+                    }
+                }
+            }
+            _ => {}
+        }
+        for message in errors {
+            self.error(block, Some(value), message);
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use crate::token::*;
 
+#[cfg(test)]
+mod test;
+
 #[derive(Debug, Clone)]
 pub struct Lexer {
     /// input string of code
@@ -10,6 +13,18 @@ pub struct Lexer {
     pub read_position: usize,
     /// next character to read
     pub ch: char,
+    /// type of the last token returned, used for automatic semicolon insertion
+    pub last_type: TokenType,
+    /// line of the character at `position`
+    pub line: usize,
+    /// offset where the current line starts
+    pub line_start: usize,
+    /// line and column where the token being scanned starts
+    token_start: (usize, usize),
+    /// comments skipped so far, in source order
+    pub comments: Vec<Token>,
+    /// `input` split into characters; positions index this
+    chars: Vec<char>,
 }
 
 impl Lexer {
@@ -19,77 +34,130 @@ impl Lexer {
             position: 0,
             read_position: 0,
             ch: ' ',
+            last_type: ILLEGAL,
+            line: 1,
+            line_start: 0,
+            token_start: (1, 1),
+            comments: vec![],
+            chars: vec![],
         };
+        l.chars = l.input.chars().collect();
         l.read_char();
-        return l;
+        l
     }
 
     pub fn peek_char(&mut self) -> char {
-        if self.read_position >= self.input.len() {
-            '\0'
-        } else {
-            self.input.chars().nth(self.read_position).unwrap()
-        }
+        self.chars.get(self.read_position).copied().unwrap_or('\0')
     }
 
     pub fn read_char(&mut self) {
-        if self.read_position >= self.input.len() {
-            self.ch = '\0';
-        } else {
-            self.ch = self.input.chars().nth(self.read_position).unwrap();
+        if self.ch == '\n' {
+            self.line += 1;
+            self.line_start = self.read_position;
         }
+        self.ch = self.chars.get(self.read_position).copied().unwrap_or('\0');
 
         self.position = self.read_position;
         self.read_position += 1;
     }
 
     pub fn new_token(&mut self, r#type: TokenType, literal: String) -> Token {
-        Token { r#type, literal }
+        let (line, column) = self.token_start;
+        Token { r#type, literal, line, column }
+    }
+
+    fn mark_token_start(&mut self) {
+        self.token_start = (self.line, self.position - self.line_start + 1);
     }
 
     pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
-        let ch: char = self.ch.clone();
-        let tok = match ch {
-            '=' => {
-                if self.peek_char() == '=' {
-                    self.read_char();
-                    let token_literal = [ch.clone().to_string(), ch.clone().to_string()].concat();
-                    self.new_token(EQ, token_literal)
-                } else {
-                    self.new_token(ASSIGN, self.ch.to_string())
+        let tok = self.scan_token();
+        self.last_type = tok.r#type;
+        tok
+    }
+
+    fn scan_token(&mut self) -> Token {
+        loop {
+            self.skip_whitespace();
+            self.mark_token_start();
+            let line_break = match (self.ch, self.peek_char()) {
+                ('/', '/') => {
+                    self.read_line_comment();
+                    continue;
                 }
-            }
-            ';' => self.new_token(SEMICOLON, self.ch.to_string()),
-            '(' => self.new_token(LPAREN, self.ch.to_string()),
-            ')' => self.new_token(RPAREN, self.ch.to_string()),
-            ',' => self.new_token(COMMA, self.ch.to_string()),
-            '+' => self.new_token(PLUS, self.ch.to_string()),
-            '-' => self.new_token(MINUS, self.ch.to_string()),
-            '!' => {
-                if self.peek_char() == '=' {
+                ('/', '*') => self.read_block_comment(),
+                ('\n', _) => {
                     self.read_char();
-                    let token_literal = [ch.clone().to_string(), '='.to_string()].concat();
-                    self.new_token(NOT_EQ, token_literal)
-                } else {
-                    self.new_token(BANG, self.ch.to_string())
+                    true
                 }
+                ('\0', _) => true,
+                _ => break,
+            };
+            // A line break after a token that can end a statement terminates
+            // it, as in Go.
+            if line_break && inserts_semicolon(self.last_type) {
+                return self.new_token(SEMICOLON, "\n".to_string());
+            }
+            if self.ch == '\0' {
+                break;
+            }
+        }
+        let ch: char = self.ch;
+        let tok = match ch {
+            '=' => self.either('=', EQ, ASSIGN),
+            ':' => self.either('=', DEFINE, COLON),
+            '+' => match self.peek_char() {
+                '+' => self.pair(INC),
+                '=' => self.pair(ADD_ASSIGN),
+                _ => self.new_token(PLUS, ch.to_string()),
+            },
+            '-' => match self.peek_char() {
+                '-' => self.pair(DEC),
+                '=' => self.pair(SUB_ASSIGN),
+                _ => self.new_token(MINUS, ch.to_string()),
+            },
+            '!' => self.either('=', NOT_EQ, BANG),
+            '/' => self.either('=', QUO_ASSIGN, SLASH),
+            '*' => self.either('=', MUL_ASSIGN, ASTERISK),
+            '%' => self.either('=', REM_ASSIGN, PERCENT),
+            '<' => match self.peek_char() {
+                '=' => self.pair(LT_EQ),
+                '-' => self.pair(ARROW),
+                _ => self.new_token(LT, ch.to_string()),
+            },
+            '>' => self.either('=', GT_EQ, GT),
+            '&' => self.either('&', AND, AMPERSAND),
+            '|' => self.either('|', OR, ILLEGAL),
+            ';' => self.new_token(SEMICOLON, ch.to_string()),
+            '(' => self.new_token(LPAREN, ch.to_string()),
+            ')' => self.new_token(RPAREN, ch.to_string()),
+            ',' => self.new_token(COMMA, ch.to_string()),
+            '{' => self.new_token(LBRACE, ch.to_string()),
+            '}' => self.new_token(RBRACE, ch.to_string()),
+            '[' => self.new_token(LBRACKET, ch.to_string()),
+            ']' => self.new_token(RBRACKET, ch.to_string()),
+            '.' if self.peek_char() == '.' && self.chars.get(self.read_position + 1) == Some(&'.') => {
+                self.read_char();
+                self.read_char();
+                self.new_token(ELLIPSIS, "...".to_string())
+            }
+            '.' if is_digit(self.peek_char()) => {
+                let (token_literal, _) = self.read_number();
+                return self.new_token(FLOAT, token_literal);
+            }
+            '.' => self.new_token(PERIOD, ch.to_string()),
+            '"' | '`' => {
+                let token_literal = self.read_string();
+                return self.new_token(STRING, token_literal);
             }
-            '/' => self.new_token(SLASH, self.ch.to_string()),
-            '*' => self.new_token(ASTERISK, self.ch.to_string()),
-            '<' => self.new_token(LT, self.ch.to_string()),
-            '>' => self.new_token(GT, self.ch.to_string()),
-            '{' => self.new_token(LBRACE, self.ch.to_string()),
-            '}' => self.new_token(RBRACE, self.ch.to_string()),
-            '\0' => self.new_token(EOF, self.ch.to_string()),
+            '\0' => self.new_token(EOF, ch.to_string()),
             _ => {
-                if is_letter(ch.clone()) {
-                    let token_literal = self.read_identifier().to_string();
-                    self.position += token_literal.len();
+                if is_letter(ch) {
+                    let token_literal = self.read_identifier();
                     return self.new_token(lookup_ident(&token_literal), token_literal);
-                } else if is_digit(ch.clone()) {
-                    let token_literal = self.read_number().to_string();
-                    return self.new_token(INT, token_literal);
+                } else if is_digit(ch) {
+                    let (token_literal, is_float) = self.read_number();
+                    return self.new_token(if is_float { FLOAT } else { INT }, token_literal);
                 } else {
                     self.read_char();
                     return self.new_token(ILLEGAL, ch.to_string());
@@ -97,27 +165,119 @@ impl Lexer {
             }
         };
         self.read_char();
-        return tok;
+        tok
+    }
+
+    /// A two-character token when the next character is `second`, else
+    /// the one-character token `single`.
+    fn either(&mut self, second: char, double: TokenType, single: TokenType) -> Token {
+        if self.peek_char() == second {
+            self.pair(double)
+        } else {
+            self.new_token(single, self.ch.to_string())
+        }
+    }
+
+    /// The token made of the current and the next character.
+    fn pair(&mut self, t: TokenType) -> Token {
+        let first = self.ch;
+        self.read_char();
+        self.new_token(t, [first, self.ch].iter().collect())
     }
-    pub fn read_identifier(&mut self) -> &str {
+
+    fn text(&self, start: usize, end: usize) -> String {
+        self.chars[start..end.min(self.chars.len())].iter().collect()
+    }
+
+    pub fn read_identifier(&mut self) -> String {
         let position = self.position;
-        while is_letter(self.ch) {
+        while is_letter(self.ch) || is_digit(self.ch) {
             self.read_char()
         }
-        return &self.input[position..self.position];
+        self.text(position, self.position)
     }
 
-    pub fn read_number(&mut self) -> &str {
+    /// Reads a decimal literal and reports whether it is a floating-point
+    /// one, with a fraction (`1.5`, `.5`) or an exponent (`1e9`).
+    pub fn read_number(&mut self) -> (String, bool) {
         let position = self.position;
+        let mut is_float = false;
         while is_digit(self.ch) {
             self.read_char()
         }
-        &self.input[position..self.position]
+        if self.ch == '.' && is_digit(self.peek_char()) {
+            is_float = true;
+            self.read_char();
+            while is_digit(self.ch) {
+                self.read_char()
+            }
+        }
+        if self.ch == 'e' || self.ch == 'E' {
+            let after = self.chars.get(self.read_position + 1).copied().unwrap_or('\0');
+            let peek = self.peek_char();
+            if is_digit(peek) || ((peek == '+' || peek == '-') && is_digit(after)) {
+                is_float = true;
+                self.read_char();
+                self.read_char();
+                while is_digit(self.ch) {
+                    self.read_char()
+                }
+            }
+        }
+        (self.text(position, self.position), is_float)
+    }
+
+    /// Reads an interpreted (`"..."`) or raw (`` `...` ``) string literal
+    /// and returns its source text, quotes included. An interpreted string
+    /// stops at the end of its line; `unquote` reports it unterminated.
+    pub fn read_string(&mut self) -> String {
+        let position = self.position;
+        let quote = self.ch;
+        self.read_char();
+        while self.ch != quote && self.ch != '\0' {
+            if quote == '"' && self.ch == '\n' {
+                return self.text(position, self.position);
+            }
+            if quote == '"' && self.ch == '\\' {
+                self.read_char();
+            }
+            self.read_char();
+        }
+        self.read_char();
+        self.text(position, self.position)
+    }
+
+    /// Records a `// ...` comment, stopping before the line break.
+    pub fn read_line_comment(&mut self) {
+        let position = self.position;
+        while self.ch != '\n' && self.ch != '\0' {
+            self.read_char();
+        }
+        let text = self.text(position, self.position);
+        let tok = self.new_token(COMMENT, text);
+        self.comments.push(tok);
+    }
+
+    /// Records a `/* ... */` comment and reports whether it spans lines.
+    pub fn read_block_comment(&mut self) -> bool {
+        let position = self.position;
+        let line = self.line;
+        self.read_char();
+        self.read_char();
+        while !(self.ch == '*' && self.peek_char() == '/') && self.ch != '\0' {
+            self.read_char();
+        }
+        self.read_char();
+        self.read_char();
+        let text = self.text(position, self.position);
+        let tok = self.new_token(COMMENT, text);
+        self.comments.push(tok);
+        self.line != line
     }
 
     pub fn skip_whitespace(&mut self) {
         loop {
-            if self.ch == ' ' || self.ch == '\t' || self.ch == '\n' || self.ch == '\r' {
+            if self.ch == ' ' || self.ch == '\t' || self.ch == '\r' {
                 self.read_char();
             } else {
                 break;
@@ -127,9 +287,94 @@ impl Lexer {
 }
 
 pub fn is_letter(ch: char) -> bool {
-    'a' <= ch && ch <= 'z' || 'A' <= ch && ch <= 'Z' || ch == '_'
+    ch.is_ascii_alphabetic() || ch == '_'
 }
 
 pub fn is_digit(ch: char) -> bool {
-    '0' <= ch && ch <= '9'
+    ch.is_ascii_digit()
+}
+
+/// Whether a newline following a token of type `t` ends the statement.
+pub fn inserts_semicolon(t: TokenType) -> bool {
+    matches!(t, IDENT | INT | FLOAT | STRING | TRUE | FALSE | RETURN | BREAK | CONTINUE | INC | DEC | RPAREN | RBRACKET | RBRACE)
+}
+
+/// The bytes of a string literal's source text, decoding the escapes of
+/// an interpreted string. `\x` and octal escapes give single bytes, which
+/// need not form UTF-8, as in Go.
+pub fn unquote(literal: &str) -> Result<Vec<u8>, String> {
+    let mut chars = literal.chars().peekable();
+    let quote = chars.next();
+    if quote == Some('`') {
+        if literal.len() < 2 || !literal.ends_with('`') {
+            return Err("raw string literal not terminated".to_string());
+        }
+        return Ok(literal[1..literal.len() - 1].replace('\r', "").into_bytes());
+    }
+    let mut out = vec![];
+    loop {
+        let c = match chars.next() {
+            Some('"') => break,
+            Some(c) => c,
+            None => return Err("string literal not terminated".to_string()),
+        };
+        if c != '\\' {
+            push_char(&mut out, c);
+            continue;
+        }
+        let c = match chars.next() {
+            Some(c) => c,
+            None => return Err("string literal not terminated".to_string()),
+        };
+        let simple = match c {
+            'n' => Some(b'\n'),
+            't' => Some(b'\t'),
+            'r' => Some(b'\r'),
+            'a' => Some(7),
+            'b' => Some(8),
+            'f' => Some(12),
+            'v' => Some(11),
+            '\\' => Some(b'\\'),
+            '"' => Some(b'"'),
+            '\'' => Some(b'\''),
+            _ => None,
+        };
+        if let Some(b) = simple {
+            out.push(b);
+            continue;
+        }
+        let (digits, radix) = match c {
+            'x' => (2, 16),
+            'u' => (4, 16),
+            'U' => (8, 16),
+            '0'..='7' => (2, 8),
+            _ => return Err(format!("unknown escape sequence \\{}", c)),
+        };
+        let mut text: String = chars.by_ref().take(digits).collect();
+        if radix == 8 {
+            text.insert(0, c);
+        }
+        let code = match u32::from_str_radix(&text, radix) {
+            Ok(code) if text.len() == digits + (radix == 8) as usize => code,
+            _ => return Err(format!("invalid escape sequence \\{}", if radix == 8 { text } else { format!("{}{}", c, text) })),
+        };
+        match c {
+            'x' => out.push(code as u8),
+            '0'..='7' if code <= 255 => out.push(code as u8),
+            'u' | 'U' => match char::from_u32(code) {
+                Some(ch) => push_char(&mut out, ch),
+                None => return Err(format!("escape sequence is invalid Unicode code point {:#x}", code)),
+            },
+            _ => return Err(format!("octal escape value {} > 255", code)),
+        }
+    }
+    if chars.next().is_some() {
+        return Err("string literal not terminated".to_string());
+    }
+    Ok(out)
+}
+
+fn push_char(out: &mut Vec<u8>, c: char) {
+    let mut buf = [0; 4];
+    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
 }
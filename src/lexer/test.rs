@@ -1,8 +1,22 @@
+use crate::lexer::Lexer;
+
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  fn print_token(input: String) {
+    let mut lexer = Lexer::new(input.clone());
+    for _i in 0..input.len() {
+      if lexer.read_position > input.len() {
+        break;
+      } else {
+        let next_token = lexer.next_token();
+        println!("{:?} {:?}", lexer, next_token);
+      }
+    }
+  }
+
   #[test]
   fn test_next_token() {
   let input = "let five = 5;
@@ -0,0 +1,39 @@
+//! The namu language: its lexer, parser, bytecode compiler
+//! and VM, the SSA form and the backends that translate it, as a library.
+//! The `namu` binary is the command line over it; on `wasm32` the library
+//! leaves out the command line and the REPL, and `playground` runs
+//! programs for a browser.
+//!
+//! Programs embedding namu use what is re-exported here, see
+//! `interpreter`; the modules' own items may change between releases.
+
+#[macro_use]
+extern crate lazy_static;
+
+pub mod ast;
+pub mod backend;
+pub mod build;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+pub mod code;
+pub mod compiler;
+pub mod diagnostic;
+pub mod dump;
+pub mod format;
+pub mod highlight;
+pub mod interpreter;
+pub mod ir;
+pub mod lexer;
+pub mod loader;
+pub mod parser;
+pub mod playground;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod repl;
+pub mod stdlib;
+pub mod token;
+pub mod types;
+pub mod value;
+pub mod vm;
+
+pub use interpreter::{check_package, parse_file, Error, FromValue, HostType, Interpreter, IntoValue, Limit, Limits, Package, Signature};
+pub use value::Value;
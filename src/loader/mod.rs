@@ -0,0 +1,438 @@
+//! Loading the packages of a program: finding the module's go.mod,
+//! mapping import paths to directories, parsing the files of each package
+//! built for the target, and ordering packages so that each comes after
+//! everything it imports.
+
+use crate::ast::{ImportStatement, PackageClause, Program};
+use crate::build::{Reason, Selection, Target};
+use crate::compiler::Compiler;
+use crate::diagnostic::Diagnostic;
+use crate::dump::span_of_statement;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::stdlib;
+use crate::token::Token;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub mod modfile;
+
+#[cfg(test)]
+mod test;
+
+use modfile::ModFile;
+
+/// Import path of the package made of files named on the command line.
+pub const COMMAND_LINE_PACKAGE: &str = "command-line-arguments";
+
+/// A module on disk: the directory holding its go.mod, and the file.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub dir: PathBuf,
+    pub file: ModFile,
+}
+
+impl Module {
+    /// The module whose go.mod is in `dir` or the nearest directory
+    /// above it; None when there is none.
+    pub fn find(dir: &Path) -> Result<Option<Module>, String> {
+        let start = fs::canonicalize(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        for dir in start.ancestors() {
+            let path = dir.join("go.mod");
+            if path.is_file() {
+                let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                let file = modfile::parse(&path.display().to_string(), &text).map_err(|errors| errors.join("\n"))?;
+                return Ok(Some(Module { dir: dir.to_path_buf(), file }));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.file.module
+    }
+
+    /// The import path of the package in `dir`, if `dir` is inside the
+    /// module.
+    pub fn import_path(&self, dir: &Path) -> Option<String> {
+        let dir = fs::canonicalize(dir).ok()?;
+        let rel = dir.strip_prefix(&self.dir).ok()?;
+        let mut path = self.path().to_string();
+        for part in rel.components() {
+            path.push('/');
+            path.push_str(&part.as_os_str().to_string_lossy());
+        }
+        Some(path)
+    }
+
+    /// The directory of the package `path`: inside this module, or inside
+    /// a required module that a `replace` directive maps to a directory.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        if let Some(rest) = within(path, self.path()) {
+            return Ok(self.dir.join(rest));
+        }
+        // the longest required module path that is a prefix of `path`
+        let require = self.file.requires.iter().filter(|r| within(path, &r.path).is_some()).max_by_key(|r| r.path.len());
+        let require = match require {
+            Some(require) => require,
+            None if is_std_path(path) => return Err(format!("package {} is not in std", path)),
+            None => return Err(format!("no required module provides package {}", path)),
+        };
+        match self.file.replace(&require.path, &require.version) {
+            Some(replace) if replace.is_local() => {
+                let rest = within(path, &require.path).unwrap_or_default();
+                Ok(self.dir.join(&replace.new).join(rest))
+            }
+            _ => Err(format!(
+                "cannot load {}: module {}@{} is not replaced by a local directory, and namu does not download modules",
+                path, require.path, require.version
+            )),
+        }
+    }
+}
+
+/// The part of `path` below the module `module`, or None when `path` is
+/// not inside it.
+fn within<'a>(path: &'a str, module: &str) -> Option<&'a str> {
+    if path == module {
+        return Some("");
+    }
+    path.strip_prefix(module)?.strip_prefix('/')
+}
+
+/// Reports whether `path` would belong to the standard library: only
+/// paths whose first element looks like a domain name can be modules.
+fn is_std_path(path: &str) -> bool {
+    !path.split('/').next().is_some_and(|first| first.contains('.'))
+}
+
+/// A source file of a package, parsed.
+pub struct SourceFile {
+    /// The name diagnostics refer to the file by.
+    pub name: String,
+    pub source: String,
+    pub program: Program,
+}
+
+/// The files of a package built for the target, parsed, and why each
+/// file considered was or was not.
+type Selected = (Vec<SourceFile>, Vec<(String, Selection)>);
+
+/// A package ready to compile.
+pub struct Package {
+    /// Import path; `command-line-arguments` for files named on the
+    /// command line.
+    pub path: String,
+    /// Name from the package clauses.
+    pub name: String,
+    pub dir: PathBuf,
+    pub files: Vec<SourceFile>,
+    /// Paths of the packages the files import, standard ones included,
+    /// in the order first imported.
+    pub imports: Vec<String>,
+    /// Every `.go` file considered, by name, with whether it was built
+    /// for the target and why.
+    pub selections: Vec<(String, Selection)>,
+}
+
+/// A problem found while loading or compiling packages.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Diagnostics positioned in one file.
+    File { name: String, source: String, diagnostics: Vec<Diagnostic> },
+    Message(String),
+}
+
+impl Error {
+    fn at(file: &SourceFile, tok: &Token, message: String) -> Error {
+        Error::File { name: file.name.clone(), source: file.source.clone(), diagnostics: vec![Diagnostic::at(tok, message)] }
+    }
+}
+
+/// Loads packages and the packages they import, each once. Imports
+/// outside the standard library resolve through the module, when there is
+/// one.
+pub struct Loader {
+    module: Option<Module>,
+    target: Target,
+    /// Packages loaded, each after all it imports.
+    loaded: Vec<Package>,
+    /// Paths of the packages being loaded, importers first.
+    stack: Vec<String>,
+    errors: Vec<Error>,
+}
+
+impl Loader {
+    pub fn new(module: Option<Module>, target: Target) -> Loader {
+        Loader { module, target, loaded: vec![], stack: vec![], errors: vec![] }
+    }
+
+    pub fn module(&self) -> Option<&Module> {
+        self.module.as_ref()
+    }
+
+    /// Loads the package in `dir` and everything it imports, returning the
+    /// packages in dependency order, the one in `dir` last.
+    pub fn load_dir(mut self, dir: &Path) -> Result<Vec<Package>, Vec<Error>> {
+        let path = self.module.as_ref().and_then(|m| m.import_path(dir)).unwrap_or_else(|| dir.display().to_string());
+        self.load(&path, dir.to_path_buf());
+        self.finish()
+    }
+
+    /// Loads the named files, given with their contents, as one package
+    /// and everything it imports. The files may leave out the package
+    /// clause, which makes them package main.
+    pub fn load_files(mut self, dir: &Path, files: Vec<(String, String)>) -> Result<Vec<Package>, Vec<Error>> {
+        let (files, selections) = match self.select(dir, files) {
+            Some(selected) => selected,
+            None => return Err(self.errors),
+        };
+        let name = match self.package_name(&files, dir, false) {
+            Some(name) => name,
+            None => return Err(self.errors),
+        };
+        self.stack.push(COMMAND_LINE_PACKAGE.to_string());
+        self.add(Package { path: COMMAND_LINE_PACKAGE.to_string(), name, dir: dir.to_path_buf(), files, imports: vec![], selections });
+        self.finish()
+    }
+
+    fn finish(self) -> Result<Vec<Package>, Vec<Error>> {
+        if self.errors.is_empty() {
+            Ok(self.loaded)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    /// Loads the package `path` from `dir`, unless it already is.
+    fn load(&mut self, path: &str, dir: PathBuf) {
+        if self.loaded.iter().any(|p| p.path == path) {
+            return;
+        }
+        if let Some(start) = self.stack.iter().position(|p| p == path) {
+            let mut msg = format!("package {}", self.stack[start]);
+            for p in &self.stack[start + 1..] {
+                msg.push_str(&format!("\n\timports {}", p));
+            }
+            msg.push_str(&format!("\n\timports {}: import cycle not allowed", path));
+            self.errors.push(Error::Message(msg));
+            return;
+        }
+        let (files, selections) = match self.read_dir(path, &dir) {
+            Some(selected) => selected,
+            None => return,
+        };
+        let name = match self.package_name(&files, &dir, true) {
+            Some(name) => name,
+            None => return,
+        };
+        self.stack.push(path.to_string());
+        self.add(Package { path: path.to_string(), name, dir, files, imports: vec![], selections });
+    }
+
+    /// Loads what the package on top of the stack imports, then adds it
+    /// after them.
+    fn add(&mut self, mut package: Package) {
+        for file in &package.files {
+            for (tok, import) in imports(&file.program) {
+                if !package.imports.contains(&import) {
+                    package.imports.push(import.clone());
+                }
+                if stdlib::lookup(&import).is_some() {
+                    continue;
+                }
+                let dir = match &self.module {
+                    Some(module) => module.resolve(&import),
+                    None if is_std_path(&import) => Err(format!("package {} is not in std", import)),
+                    None => Err(format!(
+                        "no required module provides package {}: go.mod file not found in current directory or any parent directory",
+                        import
+                    )),
+                };
+                match dir {
+                    Ok(dir) if dir.is_dir() => self.load(&import, dir),
+                    Ok(dir) => self.errors.push(Error::at(file, &tok, format!("package {}: directory {} does not exist", import, dir.display()))),
+                    Err(msg) => self.errors.push(Error::at(file, &tok, msg)),
+                }
+            }
+        }
+        self.stack.pop();
+        self.loaded.push(package);
+    }
+
+    /// Reads the `.go` files of the package in `dir`, in name order, and
+    /// selects those built for the target.
+    fn read_dir(&mut self, path: &str, dir: &Path) -> Option<Selected> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.errors.push(Error::Message(format!("package {}: {}: {}", path, dir.display(), e)));
+                return None;
+            }
+        };
+        let mut names: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "go"))
+            .collect();
+        names.sort();
+        let mut files = vec![];
+        for name in names {
+            let display = name.display().to_string();
+            match fs::read_to_string(&name) {
+                Ok(source) => files.push((display, source)),
+                Err(e) => self.errors.push(Error::Message(format!("{}: {}", display, e))),
+            }
+        }
+        self.select(dir, files)
+    }
+
+    /// Parses the files built for the target, recording for every file
+    /// why it was or was not. None when there are errors, or no file is
+    /// left.
+    fn select(&mut self, dir: &Path, files: Vec<(String, String)>) -> Option<Selected> {
+        let before = self.errors.len();
+        let mut parsed = vec![];
+        let mut selections = vec![];
+        for (name, source) in files {
+            let selection = match self.target.select(&name, &source) {
+                Ok(selection) => selection,
+                Err(msg) => {
+                    self.errors.push(Error::File { name, source, diagnostics: vec![Diagnostic::new(1, 1, msg)] });
+                    continue;
+                }
+            };
+            let included = selection.included;
+            selections.push((base_name(&name), selection));
+            if included {
+                if let Some(file) = self.parse(name, source) {
+                    parsed.push(file);
+                }
+            }
+        }
+        if self.errors.len() != before {
+            return None;
+        }
+        if parsed.is_empty() {
+            // files starting with _ or . do not count, as in go
+            let counted: Vec<&Selection> = selections.iter().map(|(_, s)| s).filter(|s| s.reasons != [Reason::Ignored]).collect();
+            let msg = if counted.is_empty() {
+                "no Go files in"
+            } else if counted.iter().all(|s| s.reasons == [Reason::Test]) {
+                "no non-test Go files in"
+            } else {
+                "build constraints exclude all Go files in"
+            };
+            self.errors.push(Error::Message(format!("{} {}", msg, dir.display())));
+            return None;
+        }
+        Some((parsed, selections))
+    }
+
+    fn parse(&mut self, name: String, source: String) -> Option<SourceFile> {
+        let mut p = Parser::new(Lexer::new(source.clone()));
+        let program = p.parse_program();
+        let diagnostics = p.diagnostics();
+        if diagnostics.is_empty() {
+            Some(SourceFile { name, source, program })
+        } else {
+            self.errors.push(Error::File { name, source, diagnostics });
+            None
+        }
+    }
+
+    /// The package name the files agree on. Files of a package directory
+    /// must start with a package clause; files named on the command line
+    /// default to main.
+    fn package_name(&mut self, files: &[SourceFile], dir: &Path, required: bool) -> Option<String> {
+        let mut found: Option<(String, &SourceFile)> = None;
+        for file in files {
+            let name = match package_clause(&file.program) {
+                Some(clause) => clause.name.as_ref().map_or(String::new(), |n| n.value.clone()),
+                None if required => {
+                    let first = file.program.statements.first();
+                    let diagnostic = match first.and_then(|s| span_of_statement(s.as_ref())) {
+                        Some(span) => {
+                            let found = first.map(|s| s.token_literal()).unwrap_or_default();
+                            Diagnostic::new(span.start.0, span.start.1, format!("expected 'package', found {}", found))
+                        }
+                        None => Diagnostic::new(1, 1, "expected 'package', found 'EOF'".to_string()),
+                    };
+                    self.errors.push(Error::File { name: file.name.clone(), source: file.source.clone(), diagnostics: vec![diagnostic] });
+                    return None;
+                }
+                None => "main".to_string(),
+            };
+            match &found {
+                Some((first, first_file)) if *first != name => {
+                    self.errors.push(Error::Message(format!(
+                        "found packages {} ({}) and {} ({}) in {}",
+                        first,
+                        base_name(&first_file.name),
+                        name,
+                        base_name(&file.name),
+                        dir.display()
+                    )));
+                    return None;
+                }
+                Some(_) => {}
+                None => found = Some((name, file)),
+            }
+        }
+        found.map(|(name, _)| name)
+    }
+}
+
+/// The package clause opening `program`, if it has one.
+fn package_clause(program: &Program) -> Option<&PackageClause> {
+    program.statements.first()?.as_any().downcast_ref::<PackageClause>()
+}
+
+/// The import paths of `program`, each with the token of its path.
+fn imports(program: &Program) -> Vec<(Token, String)> {
+    program
+        .statements
+        .iter()
+        .filter_map(|s| s.as_any().downcast_ref::<ImportStatement>())
+        .flat_map(|stmt| stmt.specs.iter())
+        .filter_map(|spec| spec.path.as_ref())
+        .map(|path| (path.token.clone(), path.value.clone()))
+        .collect()
+}
+
+fn base_name(name: &str) -> String {
+    Path::new(name).file_name().map_or(name.to_string(), |n| n.to_string_lossy().into_owned())
+}
+
+/// Compiles the packages in order, each into the top-level scope of its
+/// own, stopping after the first package with errors. The declarations of
+/// all of a package's files come first, so a file may use the names of
+/// those that sort after it.
+pub fn compile(packages: &[Package]) -> Result<Compiler, Vec<Error>> {
+    let mut compiler = Compiler::new();
+    for package in packages {
+        compiler.begin_package(&package.path, &package.name);
+        let mut diagnostics = vec![vec![]; package.files.len()];
+        let passes: [fn(&mut Compiler, &Program); 3] = [Compiler::declare, Compiler::resolve_types, |c, program| {
+            let _ = c.compile(program);
+        }];
+        for pass in passes {
+            for (file, diagnostics) in package.files.iter().zip(&mut diagnostics) {
+                let before = compiler.errors().len();
+                compiler.set_file(&file.name);
+                pass(&mut compiler, &file.program);
+                diagnostics.extend_from_slice(&compiler.errors()[before..]);
+            }
+        }
+        let errors: Vec<Error> = package
+            .files
+            .iter()
+            .zip(diagnostics)
+            .filter(|(_, diagnostics)| !diagnostics.is_empty())
+            .map(|(file, diagnostics)| Error::File { name: file.name.clone(), source: file.source.clone(), diagnostics })
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+    }
+    Ok(compiler)
+}
@@ -0,0 +1,254 @@
+//! go.mod files: the module path, Go version, requirements and
+//! replacements. Directives namu has no use for (`toolchain`, `exclude`,
+//! `retract`, `godebug`) are checked for shape only.
+
+/// The directives of a go.mod file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModFile {
+    pub module: String,
+    pub go: Option<String>,
+    pub requires: Vec<Require>,
+    pub replaces: Vec<Replace>,
+}
+
+/// `require path version`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Require {
+    pub path: String,
+    pub version: String,
+}
+
+/// `replace old [version] => new [version]`; without a version, `new` is
+/// a directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replace {
+    pub old: String,
+    pub old_version: Option<String>,
+    pub new: String,
+    pub new_version: Option<String>,
+}
+
+impl Replace {
+    /// Reports whether the replacement is a directory on disk.
+    pub fn is_local(&self) -> bool {
+        self.new_version.is_none()
+    }
+}
+
+impl ModFile {
+    /// The requirement on the module `path`, if any.
+    pub fn require(&self, path: &str) -> Option<&Require> {
+        self.requires.iter().find(|r| r.path == path)
+    }
+
+    /// The replacement that applies to `path` at `version`; one for a
+    /// specific version wins over one for every version.
+    pub fn replace(&self, path: &str, version: &str) -> Option<&Replace> {
+        let matching = self.replaces.iter().filter(|r| r.old == path);
+        let mut any = None;
+        for r in matching {
+            match &r.old_version {
+                Some(v) if v == version => return Some(r),
+                Some(_) => {}
+                None => any = Some(r),
+            }
+        }
+        any
+    }
+}
+
+/// Parses go.mod `text`; errors read `name:line: message`, as go reports
+/// them.
+pub fn parse(name: &str, text: &str) -> Result<ModFile, Vec<String>> {
+    let mut file = ModFile::default();
+    let mut errors = vec![];
+    let mut module_line = None;
+    let mut block: Option<(String, usize)> = None;
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let mut error = |msg: String| errors.push(format!("{}:{}: {}", name, number, msg));
+        let words = match split(line) {
+            Ok(words) => words,
+            Err(msg) => {
+                error(msg);
+                continue;
+            }
+        };
+        if words.is_empty() {
+            continue;
+        }
+        if let Some((verb, _)) = &block {
+            if words == [")"] {
+                block = None;
+            } else {
+                let verb = verb.clone();
+                directive(&mut file, &verb, &words, &mut error);
+            }
+            continue;
+        }
+        let verb = words[0].as_str();
+        if words.len() == 2 && words[1] == "(" {
+            if !matches!(verb, "require" | "replace" | "exclude" | "retract" | "godebug") {
+                error(format!("unknown block type: {}", verb));
+            }
+            block = Some((verb.to_string(), number));
+            continue;
+        }
+        match verb {
+            "module" => {
+                if module_line.is_some() {
+                    error("repeated module statement".to_string());
+                } else if words.len() != 2 {
+                    error("usage: module module/path".to_string());
+                } else {
+                    file.module = words[1].clone();
+                    module_line = Some(number);
+                }
+            }
+            "go" => {
+                if file.go.is_some() {
+                    error("repeated go statement".to_string());
+                } else if words.len() != 2 {
+                    error("usage: go 1.23".to_string());
+                } else if !is_go_version(&words[1]) {
+                    error(format!("invalid go version '{}': must match format 1.23.0", words[1]));
+                } else {
+                    file.go = Some(words[1].clone());
+                }
+            }
+            "toolchain" => {
+                if words.len() != 2 {
+                    error("usage: toolchain name".to_string());
+                }
+            }
+            "require" | "replace" | "exclude" | "retract" | "godebug" => {
+                directive(&mut file, verb, &words[1..], &mut error);
+            }
+            _ => error(format!("unknown directive: {}", verb)),
+        }
+    }
+    if let Some((_, line)) = block {
+        errors.push(format!("{}:{}: unterminated block", name, line));
+    }
+    if module_line.is_none() && errors.is_empty() {
+        errors.push(format!("{}: no module declaration in go.mod", name));
+    }
+    if errors.is_empty() {
+        Ok(file)
+    } else {
+        Err(errors)
+    }
+}
+
+/// One line of a `require`, `replace` or other block directive, without
+/// the directive's name.
+fn directive(file: &mut ModFile, verb: &str, args: &[String], error: &mut dyn FnMut(String)) {
+    match verb {
+        "require" => {
+            if args.len() != 2 {
+                error("usage: require module/path v1.2.3".to_string());
+            } else if !is_version(&args[1]) {
+                error(format!("invalid version {}: must be of the form v1.2.3", args[1]));
+            } else {
+                file.requires.push(Require { path: args[0].clone(), version: args[1].clone() });
+            }
+        }
+        "replace" => {
+            let arrow = args.iter().position(|a| a == "=>");
+            let (old, new) = match arrow {
+                Some(i) if (1..=2).contains(&i) && (1..=2).contains(&(args.len() - i - 1)) => (&args[..i], &args[i + 1..]),
+                _ => {
+                    error("usage: replace module/path [v1.2.3] => other/module v1.4\n\t or replace module/path [v1.2.3] => ../local/directory".to_string());
+                    return;
+                }
+            };
+            let replace = Replace {
+                old: old[0].clone(),
+                old_version: old.get(1).cloned(),
+                new: new[0].clone(),
+                new_version: new.get(1).cloned(),
+            };
+            let local = replace.new.starts_with("./") || replace.new.starts_with("../") || replace.new.starts_with('/');
+            if replace.new_version.is_none() && !local {
+                error("replacement module without version must be directory path (rooted or starting with ./ or ../)".to_string());
+                return;
+            }
+            file.replaces.push(replace);
+        }
+        "exclude" if args.len() != 2 => error("usage: exclude module/path v1.2.3".to_string()),
+        "retract" | "godebug" if args.is_empty() => error(format!("usage: {} ...", verb)),
+        _ => {}
+    }
+}
+
+/// The words of a line: `//` starts a comment, and a word may be quoted
+/// with `"` or `` ` ``.
+fn split(line: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut words = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            break;
+        } else if c == '(' || c == ')' {
+            words.push(c.to_string());
+            i += 1;
+        } else if c == '"' || c == '`' {
+            let mut word = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    Some(&q) if q == c => break,
+                    Some('\\') if c == '"' && i + 1 < chars.len() => {
+                        word.push(chars[i + 1]);
+                        i += 1;
+                    }
+                    Some(&ch) => word.push(ch),
+                    None => return Err("unterminated quoted string".to_string()),
+                }
+                i += 1;
+            }
+            words.push(word);
+            i += 1;
+        } else {
+            let start = i;
+            let ends_word = |i: usize| {
+                chars[i].is_whitespace() || matches!(chars[i], '(' | ')' | '"' | '`') || chars[i] == '/' && chars.get(i + 1) == Some(&'/')
+            };
+            while i < chars.len() && !ends_word(i) {
+                i += 1;
+            }
+            words.push(chars[start..i].iter().collect());
+        }
+    }
+    Ok(words)
+}
+
+/// `1.21` or `1.21.3`; the last number may carry a prerelease, as in
+/// `1.21rc1`.
+fn is_go_version(v: &str) -> bool {
+    let parts: Vec<&str> = v.split('.').collect();
+    if parts.len() < 2 || parts.len() > 3 || parts[0] != "1" {
+        return false;
+    }
+    let last = parts.len() - 1;
+    parts.iter().enumerate().skip(1).all(|(i, p)| {
+        let digits = p.chars().take_while(char::is_ascii_digit).count();
+        digits > 0 && (digits == p.len() || i == last && p[digits..].chars().all(|c| c.is_ascii_alphanumeric()))
+    })
+}
+
+/// A semantic version `vMAJOR.MINOR.PATCH`, with an optional prerelease
+/// or build suffix.
+fn is_version(v: &str) -> bool {
+    let rest = match v.strip_prefix('v') {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let core = rest.split(['-', '+']).next().unwrap_or_default();
+    let parts: Vec<&str> = core.split('.').collect();
+    parts.len() == 3 && parts.iter().all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+}
@@ -0,0 +1,264 @@
+use crate::build::Target;
+use crate::loader::modfile::{self, Replace, Require};
+use crate::loader::*;
+use crate::vm::Vm;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  static TREES: AtomicUsize = AtomicUsize::new(0);
+
+  /// Writes the files into a fresh directory and returns it.
+  fn tree(files: &[(&str, &str)]) -> PathBuf {
+    let n = TREES.fetch_add(1, Ordering::SeqCst);
+    let root = std::env::temp_dir().join(format!("namu-loader-{}-{}", std::process::id(), n));
+    let _ = fs::remove_dir_all(&root);
+    for (name, text) in files {
+      let path = root.join(name);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, text).unwrap();
+    }
+    root
+  }
+
+  fn linux() -> Target {
+    Target { goos: "linux".to_string(), goarch: "amd64".to_string(), tags: vec![] }
+  }
+
+  fn load(root: &Path, dir: &str) -> Result<Vec<Package>, Vec<Error>> {
+    let module = Module::find(&root.join(dir)).unwrap();
+    Loader::new(module, linux()).load_dir(&root.join(dir))
+  }
+
+  /// The messages of the errors, without file names.
+  fn messages(errors: Vec<Error>) -> Vec<String> {
+    errors
+      .into_iter()
+      .flat_map(|e| match e {
+        Error::File { diagnostics, .. } => diagnostics.iter().map(|d| d.to_string()).collect(),
+        Error::Message(msg) => vec![msg],
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_parse_mod_file() {
+    let text = "// the app\nmodule example.com/app\n\ngo 1.21\ntoolchain go1.21.3\n\nrequire (\n\texample.com/lib v0.1.0 // indirect\n\t\"example.com/quoted\" v1.2.3-pre\n)\nrequire example.com/one v1.0.0\n\nreplace example.com/lib => ../lib\nreplace (\n\texample.com/one v1.0.0 => /abs/one\n\texample.com/quoted => example.com/fork v1.3.0\n)\nexclude example.com/bad v0.0.1\n";
+    let file = modfile::parse("go.mod", text).unwrap();
+    assert_eq!(file.module, "example.com/app");
+    assert_eq!(file.go.as_deref(), Some("1.21"));
+    assert_eq!(
+      file.requires,
+      vec![
+        Require { path: "example.com/lib".to_string(), version: "v0.1.0".to_string() },
+        Require { path: "example.com/quoted".to_string(), version: "v1.2.3-pre".to_string() },
+        Require { path: "example.com/one".to_string(), version: "v1.0.0".to_string() },
+      ]
+    );
+    assert_eq!(
+      file.replaces[1],
+      Replace {
+        old: "example.com/one".to_string(),
+        old_version: Some("v1.0.0".to_string()),
+        new: "/abs/one".to_string(),
+        new_version: None,
+      }
+    );
+    assert!(file.replace("example.com/lib", "v0.1.0").is_some_and(|r| r.is_local()));
+    assert!(file.replace("example.com/quoted", "v1.2.3-pre").is_some_and(|r| !r.is_local()));
+    assert!(file.replace("example.com/one", "v2.0.0").is_none());
+  }
+
+  #[test]
+  fn test_mod_file_errors() {
+    let tests = [
+      ("go 1.21\n", vec!["go.mod: no module declaration in go.mod"]),
+      ("module a\nmodule b\n", vec!["go.mod:2: repeated module statement"]),
+      ("module a\ngo 1\n", vec!["go.mod:2: invalid go version '1': must match format 1.23.0"]),
+      ("module a\nrequire b\n", vec!["go.mod:2: usage: require module/path v1.2.3"]),
+      ("module a\nrequire b 1.0\n", vec!["go.mod:2: invalid version 1.0: must be of the form v1.2.3"]),
+      (
+        "module a\nreplace b => lib\n",
+        vec!["go.mod:2: replacement module without version must be directory path (rooted or starting with ./ or ../)"],
+      ),
+      ("module a\nfrobnicate x\n", vec!["go.mod:2: unknown directive: frobnicate"]),
+      ("module a\nrequire (\nb v1.0.0\n", vec!["go.mod:2: unterminated block"]),
+      ("module \"a\n", vec!["go.mod:1: unterminated quoted string"]),
+    ];
+    for (text, expected) in tests.iter() {
+      match modfile::parse("go.mod", text) {
+        Err(errors) if errors == *expected => {}
+        got => panic!("{:?}: expected errors {:?}, got={:?}", text, expected, got),
+      }
+    }
+  }
+
+  #[test]
+  fn test_resolve() {
+    let file = modfile::parse(
+      "go.mod",
+      "module example.com/app\nrequire (\n\texample.com/lib v1.0.0\n\texample.com/remote v1.0.0\n)\nreplace example.com/lib => ../lib\n",
+    )
+    .unwrap();
+    let module = Module { dir: PathBuf::from("/src/app"), file };
+    assert_eq!(module.resolve("example.com/app"), Ok(PathBuf::from("/src/app/")));
+    assert_eq!(module.resolve("example.com/app/util/str"), Ok(PathBuf::from("/src/app/util/str")));
+    assert_eq!(module.resolve("example.com/lib/shapes"), Ok(PathBuf::from("/src/app/../lib/shapes")));
+    assert_eq!(module.resolve("example.com/application"), Err("no required module provides package example.com/application".to_string()));
+    assert_eq!(module.resolve("notstd/x"), Err("package notstd/x is not in std".to_string()));
+    assert_eq!(
+      module.resolve("example.com/remote/x"),
+      Err("cannot load example.com/remote/x: module example.com/remote@v1.0.0 is not replaced by a local directory, and namu does not download modules".to_string())
+    );
+  }
+
+  #[test]
+  fn test_load_in_dependency_order() {
+    let root = tree(&[
+      ("app/go.mod", "module example.com/app\n\ngo 1.21\n\nrequire example.com/lib v0.1.0\n\nreplace example.com/lib => ../lib\n"),
+      ("app/main.go", "package main\n\nimport (\n\t\"fmt\"\n\t\"example.com/app/b\"\n\t\"example.com/app/a\"\n)\n"),
+      ("app/a/a.go", "package a\n\nimport \"example.com/app/b\"\n"),
+      ("app/a/a_test.go", "package a\n\nnot go at all\n"),
+      ("app/a/a_windows.go", "//go:build windows\n\npackage a\n\nimport \"example.com/app/missing\"\n"),
+      ("app/b/b.go", "package b\n\nimport \"example.com/lib/c\"\n"),
+      ("app/b/_skip.go", "not go either\n"),
+      ("lib/go.mod", "module example.com/lib\n"),
+      ("lib/c/c.go", "package c\n"),
+    ]);
+    let packages = load(&root, "app").unwrap_or_else(|e| panic!("{:?}", e));
+    let paths: Vec<&str> = packages.iter().map(|p| p.path.as_str()).collect();
+    assert_eq!(paths, vec!["example.com/lib/c", "example.com/app/b", "example.com/app/a", "example.com/app"]);
+    let a = &packages[2];
+    assert_eq!(a.files.len(), 1);
+    assert_eq!(a.imports, vec!["example.com/app/b"]);
+    let why: Vec<String> = a.selections.iter().map(|(name, s)| format!("{} {} ({})", s.included, name, s)).collect();
+    assert_eq!(
+      why,
+      vec!["true a.go (no constraints)", "false a_test.go (test file)", "false a_windows.go (_windows suffix does not match)"]
+    );
+    assert_eq!(packages[3].imports, vec!["fmt", "example.com/app/b", "example.com/app/a"]);
+    let _ = fs::remove_dir_all(root);
+  }
+
+  #[test]
+  fn test_load_errors() {
+    let root = tree(&[
+      ("go.mod", "module m\n"),
+      ("cycle/a/a.go", "package a\nimport \"m/cycle/b\"\n"),
+      ("cycle/b/b.go", "package b\nimport \"m/cycle/c\"\n"),
+      ("cycle/c/c.go", "package c\nimport \"m/cycle/a\"\n"),
+      ("mixed/x.go", "package x\n"),
+      ("mixed/y.go", "package y\n"),
+      ("clause/a.go", "let x = 1\n"),
+      ("excluded/a.go", "//go:build ignore\n\npackage excluded\n"),
+      ("imports/a.go", "package imports\n\nimport \"m/nope\"\nimport \"example.com/x\"\nimport \"nope\"\n"),
+      ("syntax/a.go", "package syntax\n\nlet = 1\n"),
+      ("constraint/a.go", "//go:build linux &&\n\npackage constraint\n"),
+      ("tests/a_test.go", "package tests\n"),
+      ("tests/_a.go", "package tests\n"),
+      ("hidden/_a.go", "package hidden\n"),
+      ("suffix/a_windows.go", "package suffix\n"),
+      ("suffix/b_test.go", "package suffix\n"),
+    ]);
+    let dir = root.display();
+    let tests: Vec<(&str, Vec<String>)> = vec![
+      ("cycle/a", vec!["package m/cycle/a\n\timports m/cycle/b\n\timports m/cycle/c\n\timports m/cycle/a: import cycle not allowed".to_string()]),
+      ("mixed", vec![format!("found packages x (x.go) and y (y.go) in {}/mixed", dir)]),
+      ("clause", vec!["1:1: expected 'package', found let".to_string()]),
+      ("excluded", vec![format!("build constraints exclude all Go files in {}/excluded", dir)]),
+      ("empty", vec![format!("no Go files in {}/empty", dir)]),
+      (
+        "imports",
+        vec![
+          format!("3:8: package m/nope: directory {}/nope does not exist", fs::canonicalize(&root).unwrap().display()),
+          "4:8: no required module provides package example.com/x".to_string(),
+          "5:8: package nope is not in std".to_string(),
+        ],
+      ),
+      (
+        "syntax",
+        vec!["3:5: expected next token to be \"IDENT\", got \"=\" instead".to_string(), "3:5: no prefix parse function for \"=\" found".to_string()],
+      ),
+      ("constraint", vec!["1:1: unexpected end of //go:build expression".to_string()]),
+      ("tests", vec![format!("no non-test Go files in {}/tests", dir)]),
+      ("hidden", vec![format!("no Go files in {}/hidden", dir)]),
+      ("suffix", vec![format!("build constraints exclude all Go files in {}/suffix", dir)]),
+    ];
+    fs::create_dir_all(root.join("empty")).unwrap();
+    for (dir, expected) in tests {
+      match load(&root, dir) {
+        Err(errors) if messages(errors.clone()) == expected => {}
+        Err(errors) => panic!("{}: expected errors {:?}, got={:?}", dir, expected, messages(errors)),
+        Ok(_) => panic!("{}: expected errors {:?}", dir, expected),
+      }
+    }
+    let _ = fs::remove_dir_all(root);
+  }
+
+  #[test]
+  fn test_compile_and_run_packages() {
+    let root = tree(&[
+      ("go.mod", "module example.com/m\n\ngo 1.21\n"),
+      ("geo/geo.go", "package geo\n\nvar Made = 0\n\nlet New = fn(x, y) {\n\tMade++\n\tPoint{X: x, Y: y}\n}\n"),
+      ("geo/sum.go", "package geo\n\ntype Point struct { X int; Y int }\n\nlet offset = 100\nlet Sum = fn(p) { p.X + p.Y + offset }\n"),
+      ("main.go", "package main\n\nimport (\n\t\"fmt\"\n\tg \"example.com/m/geo\"\n)\n\nlet offset = 1\nlet p = g.New(2, 3)\nvar q g.Point = g.Point{X: 1}\ng.Made = g.Made + 10\n[]any{g.Sum(p), g.Sum(q), g.Made, offset, p, fmt.Sprintf(\"%T\", q)}\n"),
+    ]);
+    let packages = load(&root, ".").unwrap_or_else(|e| panic!("{:?}", e));
+    let compiler = compile(&packages).unwrap_or_else(|e| panic!("{:?}", messages(e)));
+    let mut vm = Vm::new(compiler.bytecode());
+    vm.run().unwrap();
+    assert_eq!(vm.last_popped_stack_elem().inspect(), "[105 101 11 1 {2 3} geo.Point]");
+    let _ = fs::remove_dir_all(root);
+  }
+
+  #[test]
+  fn test_package_scope() {
+    // main.go sorts first, and uses what util.go and its own later lines declare
+    let root = tree(&[
+      ("go.mod", "module m\n"),
+      ("main.go", "package main\n\nlet main = fn() {\n\tp := Pair{A: later(), B: count}\n\thelper(p)\n}\n\nlet later = fn() { 40 }\n"),
+      ("util.go", "package main\n\nvar count = 2\n\ntype Pair struct { A int; B int; next *Node }\ntype Node struct { p *Pair }\n\nlet helper = fn(p) { p.A + p.B }\n"),
+    ]);
+    let packages = load(&root, ".").unwrap();
+    let compiler = compile(&packages).unwrap_or_else(|e| panic!("{:?}", messages(e)));
+    let mut compiler = compiler;
+    let main = compiler.symbol_table.resolve("main").unwrap();
+    let mut vm = Vm::new(compiler.bytecode());
+    vm.run().unwrap();
+    let main = vm.global(main.index);
+    assert_eq!(vm.call(main, vec![]).map(|v| v.inspect()), Ok("42".to_string()));
+
+    fs::write(root.join("zz.go"), "package main\n\nvar count = 3\ntype helper struct {}\n").unwrap();
+    let packages = load(&root, ".").unwrap();
+    let errors = match compile(&packages) {
+      Err(errors) => messages(errors),
+      Ok(_) => panic!("expected errors"),
+    };
+    assert_eq!(errors, ["3:5: count redeclared in this block", "4:6: helper redeclared in this block"]);
+    let _ = fs::remove_dir_all(root);
+  }
+
+  #[test]
+  fn test_compile_errors_across_packages() {
+    let root = tree(&[
+      ("go.mod", "module m\n"),
+      ("lib/lib.go", "package lib\n\nlet hidden = 1\ntype T struct { A int }\n"),
+      ("main.go", "package main\n\nimport \"m/lib\"\n\nlib.hidden\nlib.Missing\nlib.T\nvar x lib.Missing\n"),
+    ]);
+    let packages = load(&root, ".").unwrap();
+    let errors = match compile(&packages) {
+      Err(errors) => messages(errors),
+      Ok(_) => panic!("expected errors"),
+    };
+    let expected = vec![
+      "5:1: name hidden not exported by package lib",
+      "6:1: undefined: lib.Missing",
+      "7:1: lib.T (type) is not an expression",
+      "8:7: undefined: lib.Missing",
+    ];
+    assert_eq!(errors, expected);
+    let _ = fs::remove_dir_all(root);
+  }
+}
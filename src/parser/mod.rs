@@ -1,11 +1,19 @@
 use crate::ast::*;
+use crate::diagnostic::{self, Diagnostic};
 use crate::lexer::*;
 use crate::token::*;
 use iota::iota;
 use std::collections::HashMap;
 
+#[cfg(test)]
+mod test_expression;
+#[cfg(test)]
+mod test_parser;
+
 iota!{
-    const LOWEST: u8 = 1;
+    pub const LOWEST: u8 = iota + 1;
+    ,LOGICAL_OR
+    ,LOGICAL_AND
     ,EQUALS
     ,LESSGREATER
     ,SUM
@@ -14,19 +22,68 @@ iota!{
     ,CALL
 }
 
-type PrefixParseFn = for<'r> fn(&'r mut Parser) -> Box<dyn Expression>;
-type InfixParseFn = fn(Box<dyn Expression>) -> Box<dyn Expression>;
+lazy_static! {
+    pub static ref PRECEDENCES: HashMap<TokenType, u8> = {
+        let mut m = HashMap::new();
+        m.insert(OR, LOGICAL_OR);
+        m.insert(AND, LOGICAL_AND);
+        m.insert(EQ, EQUALS);
+        m.insert(NOT_EQ, EQUALS);
+        m.insert(LT, LESSGREATER);
+        m.insert(GT, LESSGREATER);
+        m.insert(LT_EQ, LESSGREATER);
+        m.insert(GT_EQ, LESSGREATER);
+        m.insert(PLUS, SUM);
+        m.insert(MINUS, SUM);
+        m.insert(SLASH, PRODUCT);
+        m.insert(ASTERISK, PRODUCT);
+        m.insert(PERCENT, PRODUCT);
+        m.insert(LPAREN, CALL);
+        m.insert(LBRACKET, CALL);
+        m.insert(PERIOD, CALL);
+        m.insert(LBRACE, CALL);
+        m
+    };
+}
+
+/// Binding power of an infix operator, `LOWEST` for anything else.
+pub fn precedence_of(t: TokenType) -> u8 {
+    *PRECEDENCES.get(t).unwrap_or(&LOWEST)
+}
+
+/// What follows `for`: a range clause, or the optional init statement,
+/// condition and post statement.
+enum ForHeader {
+    Range(Box<RangeStatement>),
+    Clauses(Option<Box<dyn Statement>>, Option<Box<dyn Expression>>, Option<Box<dyn Statement>>),
+}
+
+type PrefixParseFn = for<'r> fn(&'r mut Parser) -> Option<Box<dyn Expression>>;
+type InfixParseFn = for<'r> fn(&'r mut Parser, Box<dyn Expression>) -> Option<Box<dyn Expression>>;
+/// Arguments of a call and the `...` after the last one, if any.
+pub type CallArguments = (Vec<Box<dyn Expression>>, Option<Token>);
 
 #[derive(Clone)]
 pub struct Parser {
     lexer: Lexer,
     cur_token: Option<Token>,
     peek_token: Option<Token>,
-    errors: Vec<String>,
+    errors: Vec<Diagnostic>,
     prefix_parse_fns: HashMap<TokenType, PrefixParseFn>,
-    infix_parse_fns: HashMap<TokenType, InfixParseFn>
+    infix_parse_fns: HashMap<TokenType, InfixParseFn>,
+    /// Whether `T {` starts a composite literal. It does not in the header
+    /// of an `if` or `for`, where the brace opens the block, unless the
+    /// literal is inside parentheses or brackets.
+    composite_ok: bool,
+    /// How many expressions, statements and types enclose the current one,
+    /// counting each operand an operator applies to.
+    depth: usize,
 }
 
+/// The deepest nesting the parser follows. Parsing, compiling and printing
+/// all recurse on the tree, so anything deeper would overflow the stack.
+pub const MAX_DEPTH: usize = 300;
+
 impl Parser {
     pub fn new(l: Lexer) -> Parser {
         let mut p = Parser {
@@ -35,13 +92,41 @@ impl Parser {
             peek_token: None,
             errors: vec![],
             prefix_parse_fns: HashMap::new(),
-            infix_parse_fns: HashMap::new()
+            infix_parse_fns: HashMap::new(),
+            composite_ok: true,
+            depth: 0,
         };
         p.register_prefix(IDENT, Parser::parse_identifier);
         p.register_prefix(INT, Parser::parse_integer_literal);
+        p.register_prefix(FLOAT, Parser::parse_float_literal);
+        p.register_prefix(TRUE, Parser::parse_boolean);
+        p.register_prefix(FALSE, Parser::parse_boolean);
+        p.register_prefix(BANG, Parser::parse_prefix_expression);
+        p.register_prefix(MINUS, Parser::parse_prefix_expression);
+        p.register_prefix(LPAREN, Parser::parse_grouped_expression);
+        p.register_prefix(IF, Parser::parse_if_expression);
+        p.register_prefix(FUNCTION, Parser::parse_function_literal);
+        p.register_prefix(STRING, Parser::parse_string_literal);
+        p.register_prefix(ASTERISK, Parser::parse_prefix_expression);
+        p.register_prefix(AMPERSAND, Parser::parse_prefix_expression);
+        p.register_prefix(LBRACKET, Parser::parse_slice_type);
+        p.register_prefix(MAP, Parser::parse_map_type);
+        p.register_prefix(STRUCT, Parser::parse_struct_type);
+        p.register_prefix(INTERFACE, Parser::parse_interface_type);
+        p.register_prefix(CHAN, Parser::parse_chan_type);
+        p.register_prefix(ARROW, Parser::parse_prefix_expression);
+
+        for t in &[PLUS, MINUS, SLASH, ASTERISK, PERCENT, EQ, NOT_EQ, LT, GT, LT_EQ, GT_EQ, AND, OR] {
+            p.register_infix(t, Parser::parse_infix_expression);
+        }
+        p.register_infix(LPAREN, Parser::parse_call_expression);
+        p.register_infix(LBRACKET, Parser::parse_index_expression);
+        p.register_infix(PERIOD, Parser::parse_selector_expression);
+        p.register_infix(LBRACE, Parser::parse_composite_literal);
+
         p.next_token();
         p.next_token();
-        return p;
+        p
     }
 
     pub fn next_token(&mut self) {
@@ -57,71 +142,950 @@ impl Parser {
         self.infix_parse_fns.insert(token, infix_fn);
     }
 
-    pub fn parse_identifier(&mut self) -> Box<dyn Expression> {
-        return Box::new(Identifier{token: self.cur_token.clone(), value: self.cur_token.clone().unwrap().literal});
+    pub fn parse_identifier(&mut self) -> Option<Box<dyn Expression>> {
+        Some(Box::new(Identifier{token: self.cur_token.clone(), value: self.cur_token.clone().unwrap().literal}))
     }
 
 
-    pub fn parse_integer_literal(&mut self) -> Box<dyn Expression> {
+    pub fn parse_integer_literal(&mut self) -> Option<Box<dyn Expression>> {
         let mut lit = IntegerLiteral {
             token: self.cur_token.clone().unwrap(),
             value: None
         };
-        
+
         let value = match self.cur_token.clone().unwrap().literal.parse::<i64>() {
             Ok(v) => Some(v),
             Err(_e) => {
                 let msg = format!("could not parse {:?} as integer", self.cur_token.clone().unwrap().literal);
-                self.errors.push(msg);
+                self.error_at_cur(msg);
                 None
             }
         };
 
         lit.value = value;
-        return Box::new(lit);
+        Some(Box::new(lit))
+    }
+
+    pub fn parse_float_literal(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        let value = match token.literal.parse::<f64>() {
+            Ok(v) => Some(v),
+            Err(_e) => {
+                let msg = format!("could not parse {:?} as float", token.literal);
+                self.error_at_cur(msg);
+                None
+            }
+        };
+        Some(Box::new(FloatLiteral { token, value }))
+    }
+
+    pub fn parse_boolean(&mut self) -> Option<Box<dyn Expression>> {
+        Some(Box::new(Boolean {
+            token: self.cur_token.clone().unwrap(),
+            value: self.is_cur_token(TRUE),
+        }))
+    }
+
+    pub fn parse_prefix_expression(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        let operator = token.literal.clone();
+
+        self.next_token();
+
+        let right = self.parse_expression(PREFIX);
+        Some(Box::new(PrefixExpression { token, operator, right }))
+    }
+
+    pub fn parse_infix_expression(&mut self, left: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        let operator = token.literal.clone();
+        let precedence = self.cur_precedence();
+
+        self.next_token();
+
+        let right = self.parse_expression(precedence);
+        Some(Box::new(InfixExpression { token, left: Some(left), operator, right }))
+    }
+
+    pub fn parse_grouped_expression(&mut self) -> Option<Box<dyn Expression>> {
+        self.next_token();
+
+        let exp = self.with_composites(true, |p| p.parse_expression(LOWEST));
+
+        if !self.expect_peek(RPAREN) {
+            return None;
+        }
+        exp
+    }
+
+    /// Runs `f` with composite literals allowed or not, restoring the
+    /// setting afterwards.
+    fn with_composites<T>(&mut self, ok: bool, f: impl FnOnce(&mut Parser) -> T) -> T {
+        let saved = self.composite_ok;
+        self.composite_ok = ok;
+        let result = f(self);
+        self.composite_ok = saved;
+        result
+    }
+
+    pub fn parse_if_expression(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+
+        self.next_token();
+        let condition = self.with_composites(false, |p| p.parse_expression(LOWEST));
+
+        if !self.expect_peek(LBRACE) {
+            return None;
+        }
+        let consequence = Some(self.parse_block_statement());
+
+        let mut alternative = None;
+        if self.is_peek_token(ELSE) {
+            self.next_token();
+
+            if !self.expect_peek(LBRACE) {
+                return None;
+            }
+            alternative = Some(self.parse_block_statement());
+        }
+
+        Some(Box::new(IfExpression { token, condition, consequence, alternative }))
+    }
+
+    pub fn parse_block_statement(&mut self) -> BlockStatement {
+        let mut block = BlockStatement {
+            token: self.cur_token.clone().unwrap(),
+            statements: vec![],
+            rbrace: None,
+        };
+
+        self.next_token();
+
+        while !self.is_cur_token(RBRACE) && !self.is_cur_token(EOF) && !diagnostic::too_many(&self.errors) {
+            // the block is a level of its own, below the statement it is
+            // the body of
+            if let Some(stmt) = self.nested(Parser::parse_statement) {
+                block.statements.push(stmt);
+            }
+            self.next_token();
+        }
+
+        if self.is_cur_token(RBRACE) {
+            block.rbrace = self.cur_token.clone();
+        } else {
+            self.error_at_cur("expected \"}\" to close block, got EOF instead".to_string());
+        }
+
+        block
+    }
+
+    pub fn parse_function_literal(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+
+        if !self.expect_peek(LPAREN) {
+            return None;
+        }
+
+        let parameters = self.parse_function_parameters()?;
+
+        let mut results = vec![];
+        if self.is_peek_token(LPAREN) {
+            self.next_token();
+            results = self.parse_function_results()?;
+        }
+
+        if !self.expect_peek(LBRACE) {
+            return None;
+        }
+
+        let body = Some(self.parse_block_statement());
+        Some(Box::new(FunctionLiteral { token, parameters, results, body }))
+    }
+
+    /// Named results, `(a, b T, c)`: names, each group optionally ending
+    /// in their type.
+    pub fn parse_function_results(&mut self) -> Option<Vec<FieldDecl>> {
+        let mut results = vec![];
+        let mut names = vec![];
+        loop {
+            if !self.expect_peek(IDENT) {
+                return None;
+            }
+            names.push(self.cur_identifier());
+            if !self.is_peek_token(COMMA) && !self.is_peek_token(RPAREN) {
+                self.next_token();
+                let ty = Some(self.parse_type()?);
+                results.push(FieldDecl { names: std::mem::take(&mut names), ty });
+            }
+            if !self.is_peek_token(COMMA) {
+                break;
+            }
+            self.next_token();
+        }
+        if !names.is_empty() {
+            results.push(FieldDecl { names, ty: None });
+        }
+        if !self.expect_peek(RPAREN) {
+            return None;
+        }
+        Some(results)
+    }
+
+    pub fn parse_function_parameters(&mut self) -> Option<Vec<Identifier>> {
+        let mut identifiers = vec![];
+
+        if self.is_peek_token(RPAREN) {
+            self.next_token();
+            return Some(identifiers);
+        }
+
+        if !self.expect_peek(IDENT) {
+            return None;
+        }
+        identifiers.push(Identifier { token: self.cur_token.clone(), value: self.cur_token.clone().unwrap().literal });
+
+        while self.is_peek_token(COMMA) {
+            self.next_token();
+            if !self.expect_peek(IDENT) {
+                return None;
+            }
+            identifiers.push(Identifier { token: self.cur_token.clone(), value: self.cur_token.clone().unwrap().literal });
+        }
+
+        if !self.expect_peek(RPAREN) {
+            return None;
+        }
+
+        Some(identifiers)
+    }
+
+    pub fn parse_call_expression(&mut self, function: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        let (arguments, ellipsis) = self.parse_call_arguments()?;
+        let rparen = self.cur_token.clone();
+        Some(Box::new(CallExpression { token, function: Some(function), arguments, ellipsis, rparen }))
+    }
+
+    /// The arguments of a call up to its `)`, and the `...` that may
+    /// follow the last one.
+    pub fn parse_call_arguments(&mut self) -> Option<CallArguments> {
+        let mut args = vec![];
+
+        if self.is_peek_token(RPAREN) {
+            self.next_token();
+            return Some((args, None));
+        }
+
+        self.next_token();
+        args.push(self.with_composites(true, |p| p.parse_expression(LOWEST))?);
+
+        let mut ellipsis = None;
+        loop {
+            if self.is_peek_token(ELLIPSIS) {
+                self.next_token();
+                ellipsis = self.cur_token.clone();
+                // a trailing comma may follow
+                if self.is_peek_token(COMMA) {
+                    self.next_token();
+                }
+                break;
+            }
+            if !self.is_peek_token(COMMA) {
+                break;
+            }
+            self.next_token();
+            if self.is_peek_token(RPAREN) {
+                break;
+            }
+            self.next_token();
+            args.push(self.with_composites(true, |p| p.parse_expression(LOWEST))?);
+        }
+
+        if !self.expect_peek(RPAREN) {
+            return None;
+        }
+
+        Some((args, ellipsis))
+    }
+
+    pub fn parse_string_literal(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        let value = match unquote(&token.literal) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(msg) => {
+                self.error_at_cur(msg);
+                String::new()
+            }
+        };
+        Some(Box::new(StringLiteral { token, value }))
+    }
+
+    /// `a[i]`, or a slice expression `a[lo:hi]` with either bound optional.
+    pub fn parse_index_expression(&mut self, left: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        self.with_composites(true, |p| {
+            let mut low = None;
+            if !p.is_peek_token(COLON) {
+                p.next_token();
+                low = Some(p.parse_expression(LOWEST)?);
+            }
+            if !p.is_peek_token(COLON) {
+                if !p.expect_peek(RBRACKET) {
+                    return None;
+                }
+                let rbracket = p.cur_token.clone();
+                return Some(Box::new(IndexExpression { token, left: Some(left), index: low, rbracket }) as Box<dyn Expression>);
+            }
+            p.next_token();
+            let mut high = None;
+            if !p.is_peek_token(RBRACKET) {
+                p.next_token();
+                high = Some(p.parse_expression(LOWEST)?);
+            }
+            if !p.expect_peek(RBRACKET) {
+                return None;
+            }
+            let rbracket = p.cur_token.clone();
+            Some(Box::new(SliceExpression { token, left: Some(left), low, high, rbracket }) as Box<dyn Expression>)
+        })
+    }
+
+    /// `x.name`, or a type assertion `x.(T)`.
+    pub fn parse_selector_expression(&mut self, left: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        if self.is_peek_token(LPAREN) {
+            self.next_token();
+            self.next_token();
+            let ty = Some(self.parse_type()?);
+            if !self.expect_peek(RPAREN) {
+                return None;
+            }
+            let rparen = self.cur_token.clone();
+            return Some(Box::new(TypeAssertExpression { token, left: Some(left), ty, rparen }));
+        }
+        if !self.expect_peek(IDENT) {
+            return None;
+        }
+        let selector = Some(self.cur_identifier());
+        Some(Box::new(SelectorExpression { token, left: Some(left), selector }))
+    }
+
+    /// Whether a `{` after `left` opens a composite literal of type `left`.
+    fn starts_composite(&self, left: &dyn Expression) -> bool {
+        let any = left.as_any();
+        if any.is::<SliceType>() || any.is::<MapType>() || any.is::<StructType>() {
+            return true;
+        }
+        self.composite_ok && (any.is::<Identifier>() || any.is::<SelectorExpression>())
+    }
+
+    pub fn parse_composite_literal(&mut self, ty: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
+        self.parse_composite_body(Some(ty))
+    }
+
+    /// The elements from `{` to `}`; the type is `None` for a literal
+    /// nested in another that leaves it implied.
+    fn parse_composite_body(&mut self, ty: Option<Box<dyn Expression>>) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        let mut elements = vec![];
+        let ok = self.with_composites(true, |p| {
+            while !p.is_peek_token(RBRACE) {
+                p.next_token();
+                let element = p.parse_element()?;
+                if p.is_peek_token(COLON) {
+                    p.next_token();
+                    let colon = p.cur_token.clone().unwrap();
+                    p.next_token();
+                    let value = p.parse_element()?;
+                    elements.push(Box::new(KeyValueExpression { token: colon, key: Some(element), value: Some(value) }) as Box<dyn Expression>);
+                } else {
+                    elements.push(element);
+                }
+                if !p.is_peek_token(COMMA) {
+                    break;
+                }
+                p.next_token();
+            }
+            Some(())
+        });
+        ok?;
+        if !self.is_peek_token(RBRACE) {
+            let tok = self.peek_token.clone().unwrap();
+            let found = if tok.literal == "\n" { "newline".to_string() } else { tok.literal.clone() };
+            let msg = format!("unexpected {} in composite literal; possibly missing comma or }}", found);
+            self.error(&tok, msg);
+            return None;
+        }
+        self.next_token();
+        let rbrace = self.cur_token.clone();
+        Some(Box::new(CompositeLiteral { token, ty, elements, rbrace }))
+    }
+
+    fn parse_element(&mut self) -> Option<Box<dyn Expression>> {
+        if self.is_cur_token(LBRACE) {
+            return self.nested(|p| p.parse_composite_body(None));
+        }
+        self.parse_expression(LOWEST)
+    }
+
+    /// A type: a name, a qualified name, `*T`, `[]T`, `map[K]V`, `chan T`,
+    /// a struct or interface type, or a type in parentheses.
+    pub fn parse_type(&mut self) -> Option<Box<dyn Expression>> {
+        self.nested(Parser::parse_type_kind)
+    }
+
+    fn parse_type_kind(&mut self) -> Option<Box<dyn Expression>> {
+        let tok = self.cur_token.clone().unwrap();
+        match tok.r#type {
+            IDENT => {
+                let name = self.cur_identifier();
+                if !self.is_peek_token(PERIOD) {
+                    return Some(Box::new(name));
+                }
+                self.next_token();
+                let token = self.cur_token.clone().unwrap();
+                if !self.expect_peek(IDENT) {
+                    return None;
+                }
+                let selector = Some(self.cur_identifier());
+                Some(Box::new(SelectorExpression { token, left: Some(Box::new(name)), selector }))
+            }
+            ASTERISK => {
+                self.next_token();
+                let right = Some(self.parse_type()?);
+                Some(Box::new(PrefixExpression { token: tok, operator: "*".to_string(), right }))
+            }
+            LBRACKET => self.parse_slice_type(),
+            MAP => self.parse_map_type(),
+            STRUCT => self.parse_struct_type(),
+            INTERFACE => self.parse_interface_type(),
+            CHAN => self.parse_chan_type(),
+            LPAREN => {
+                self.next_token();
+                let ty = self.parse_type();
+                if !self.expect_peek(RPAREN) {
+                    return None;
+                }
+                ty
+            }
+            _ => {
+                let found = if tok.literal == "\n" { "newline".to_string() } else { tok.literal.clone() };
+                self.error_at_cur(format!("expected type, got {} instead", found));
+                None
+            }
+        }
+    }
+
+    pub fn parse_slice_type(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        if !self.is_peek_token(RBRACKET) {
+            let tok = self.peek_token.clone().unwrap();
+            self.error(&tok, "array types are not supported; use a slice type []T".to_string());
+            return None;
+        }
+        self.next_token();
+        self.next_token();
+        let elem = Some(self.parse_type()?);
+        Some(Box::new(SliceType { token, elem }))
+    }
+
+    pub fn parse_map_type(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        if !self.expect_peek(LBRACKET) {
+            return None;
+        }
+        self.next_token();
+        let key = Some(self.parse_type()?);
+        if !self.expect_peek(RBRACKET) {
+            return None;
+        }
+        self.next_token();
+        let value = Some(self.parse_type()?);
+        Some(Box::new(MapType { token, key, value }))
+    }
+
+    pub fn parse_struct_type(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        if !self.expect_peek(LBRACE) {
+            return None;
+        }
+        let mut fields = vec![];
+        self.next_token();
+        while !self.is_cur_token(RBRACE) {
+            if self.is_cur_token(SEMICOLON) {
+                self.next_token();
+                continue;
+            }
+            if !self.is_cur_token(IDENT) {
+                let found = self.cur_token.clone().unwrap().literal;
+                self.error_at_cur(format!("expected field name, got {:?} instead", found));
+                return None;
+            }
+            let mut names = vec![self.cur_identifier()];
+            while self.is_peek_token(COMMA) {
+                self.next_token();
+                if !self.expect_peek(IDENT) {
+                    return None;
+                }
+                names.push(self.cur_identifier());
+            }
+            if self.is_peek_token(SEMICOLON) || self.is_peek_token(RBRACE) {
+                self.error_at_cur("embedded fields are not supported".to_string());
+                return None;
+            }
+            self.next_token();
+            let ty = Some(self.parse_type()?);
+            fields.push(FieldDecl { names, ty });
+            self.next_token();
+        }
+        let rbrace = self.cur_token.clone();
+        Some(Box::new(StructType { token, fields, rbrace }))
+    }
+
+    pub fn parse_interface_type(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        if !self.expect_peek(LBRACE) {
+            return None;
+        }
+        while self.is_peek_token(SEMICOLON) {
+            self.next_token();
+        }
+        if !self.is_peek_token(RBRACE) {
+            let tok = self.peek_token.clone().unwrap();
+            self.error(&tok, "interface methods are not supported; only interface{} is".to_string());
+            return None;
+        }
+        self.next_token();
+        let rbrace = self.cur_token.clone();
+        Some(Box::new(InterfaceType { token, rbrace }))
+    }
+
+    pub fn parse_chan_type(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone().unwrap();
+        if self.is_peek_token(ARROW) {
+            let tok = self.peek_token.clone().unwrap();
+            self.error(&tok, "directional channel types are not supported; use chan T".to_string());
+            return None;
+        }
+        self.next_token();
+        let elem = Some(self.parse_type()?);
+        Some(Box::new(ChanType { token, elem }))
     }
 
     pub fn parse_program(&mut self) -> Program {
         let mut program = Program { statements: vec![] };
 
-        while self.cur_token.clone().unwrap().r#type != EOF {
-            let stmt = self.parse_statement();
-            if stmt.is_some() {
-                program.statements.push(stmt.unwrap());
+        while !self.is_cur_token(EOF) && !diagnostic::too_many(&self.errors) {
+            if self.is_cur_token(PACKAGE) && !program.statements.is_empty() {
+                self.error_at_cur("syntax error: package statement must be first".to_string());
+            }
+            if let Some(stmt) = self.parse_statement() {
+                program.statements.push(stmt);
             }
             self.next_token();
         }
+        // an unterminated `/*` runs to the end of the source, so only the
+        // last comment can be one
+        if let Some(comment) = self.lexer.comments.last().cloned() {
+            if comment.literal.starts_with("/*") && (comment.literal.len() < 4 || !comment.literal.ends_with("*/")) {
+                self.error(&comment, "comment not terminated".to_string());
+            }
+        }
 
-        return program;
+        program
     }
 
     pub fn parse_statement(&mut self) -> Option<Box<dyn Statement>> {
+        self.nested(Parser::parse_statement_kind)
+    }
+
+    fn parse_statement_kind(&mut self) -> Option<Box<dyn Statement>> {
         match self.cur_token.clone().unwrap().r#type {
             LET => {
-                let stmt = self.parse_let_statement();
-                if stmt.is_some() {
-                    Some(Box::new(stmt.unwrap()))
-                } else {
-                    None
-                }
+                let stmt = self.parse_let_statement()?;
+                Some(Box::new(stmt))
             }
             RETURN => {
-                let stmt = self.parse_return_statement();
-                if stmt.is_some() {
-                    Some(Box::new(stmt.unwrap()))
-                } else {
-                    None
+                let stmt = self.parse_return_statement()?;
+                Some(Box::new(stmt))
+            }
+            VAR => {
+                let stmt = self.parse_var_statement()?;
+                Some(Box::new(stmt))
+            }
+            TYPE => {
+                let stmt = self.parse_type_statement()?;
+                Some(Box::new(stmt))
+            }
+            FOR => self.parse_for_statement(),
+            GO => {
+                let stmt = self.parse_go_statement()?;
+                Some(Box::new(stmt))
+            }
+            DEFER => {
+                let stmt = self.parse_defer_statement()?;
+                Some(Box::new(stmt))
+            }
+            IMPORT => {
+                let stmt = self.parse_import_statement()?;
+                Some(Box::new(stmt))
+            }
+            PACKAGE => {
+                let stmt = self.parse_package_clause()?;
+                Some(Box::new(stmt))
+            }
+            SELECT => {
+                let stmt = self.parse_select_statement()?;
+                Some(Box::new(stmt))
+            }
+            BREAK | CONTINUE => {
+                let stmt = BranchStatement { token: self.cur_token.clone().unwrap() };
+                if self.is_peek_token(SEMICOLON) {
+                    self.next_token();
                 }
+                Some(Box::new(stmt))
             }
+            // empty statement
+            SEMICOLON => None,
             _ => {
-                let stmt = self.parse_expression_statement();
-                if stmt.is_some() {
-                    Some(Box::new(stmt.unwrap()))
-                } else {
-                    None
+                let stmt = self.parse_simple_statement(false)?;
+                if self.is_peek_token(SEMICOLON) {
+                    self.next_token();
+                }
+                Some(stmt)
+            }
+        }
+    }
+
+    /// An expression, send, assignment, short variable declaration or
+    /// increment, without the semicolon that ends it. With `range_ok`, an
+    /// assignment from `range x` gives a range clause, as in a `for`
+    /// header.
+    fn parse_simple_statement(&mut self, range_ok: bool) -> Option<Box<dyn Statement>> {
+        let start = self.cur_token.clone().unwrap();
+        let first = self.parse_expression(LOWEST);
+        let is_assignment = |t| matches!(t, ASSIGN | DEFINE | ADD_ASSIGN | SUB_ASSIGN | MUL_ASSIGN | QUO_ASSIGN | REM_ASSIGN);
+        let peek = self.peek_token.clone().unwrap().r#type;
+        if peek == ARROW {
+            self.next_token();
+            let token = self.cur_token.clone().unwrap();
+            self.next_token();
+            let value = self.parse_expression(LOWEST);
+            return Some(Box::new(SendStatement { token, channel: first, value }));
+        }
+        if !is_assignment(peek) && peek != COMMA && peek != INC && peek != DEC {
+            return Some(Box::new(ExpressionStatement { token: start, expression: first }));
+        }
+
+        let mut left = vec![first?];
+        while self.is_peek_token(COMMA) {
+            self.next_token();
+            self.next_token();
+            left.push(self.parse_expression(LOWEST)?);
+        }
+        self.next_token();
+        let token = self.cur_token.clone().unwrap();
+        if token.r#type == INC || token.r#type == DEC {
+            return Some(Box::new(AssignStatement { token: token.clone(), left, operator: token.literal, right: vec![] }));
+        }
+        if !is_assignment(token.r#type) {
+            self.error_at_cur(format!("expected := or = or comma, got {:?} instead", token.r#type));
+            return None;
+        }
+        if range_ok && (token.r#type == ASSIGN || token.r#type == DEFINE) && self.is_peek_token(RANGE) {
+            if left.len() > 2 {
+                self.error_at_cur(format!("range clause permits at most two iteration variables, got {}", left.len()));
+                return None;
+            }
+            self.next_token();
+            self.next_token();
+            let x = self.parse_expression(LOWEST);
+            let mut vars = left.into_iter();
+            return Some(Box::new(RangeStatement {
+                token: start,
+                key: vars.next(),
+                value: vars.next(),
+                operator: token.literal,
+                x,
+                body: None,
+            }));
+        }
+
+        let mut right = vec![];
+        self.next_token();
+        right.push(self.parse_expression(LOWEST)?);
+        while self.is_peek_token(COMMA) {
+            self.next_token();
+            self.next_token();
+            right.push(self.parse_expression(LOWEST)?);
+        }
+        Some(Box::new(AssignStatement { token: token.clone(), left, operator: token.literal, right }))
+    }
+
+    pub fn parse_var_statement(&mut self) -> Option<VarStatement> {
+        let token = self.cur_token.clone().unwrap();
+        let mut names = vec![];
+        loop {
+            if !self.expect_peek(IDENT) {
+                return None;
+            }
+            names.push(self.cur_identifier());
+            if !self.is_peek_token(COMMA) {
+                break;
+            }
+            self.next_token();
+        }
+
+        let mut ty = None;
+        if !self.is_peek_token(ASSIGN) {
+            self.next_token();
+            ty = Some(self.parse_type()?);
+        }
+        let mut values = vec![];
+        if self.is_peek_token(ASSIGN) {
+            self.next_token();
+            self.next_token();
+            values.push(self.parse_expression(LOWEST)?);
+            while self.is_peek_token(COMMA) {
+                self.next_token();
+                self.next_token();
+                values.push(self.parse_expression(LOWEST)?);
+            }
+        }
+
+        if self.is_peek_token(SEMICOLON) {
+            self.next_token();
+        }
+        Some(VarStatement { token, names, ty, values })
+    }
+
+    pub fn parse_type_statement(&mut self) -> Option<TypeStatement> {
+        let token = self.cur_token.clone().unwrap();
+        if !self.expect_peek(IDENT) {
+            return None;
+        }
+        let name = Some(self.cur_identifier());
+        self.next_token();
+        let ty = Some(self.parse_type()?);
+        if self.is_peek_token(SEMICOLON) {
+            self.next_token();
+        }
+        Some(TypeStatement { token, name, ty })
+    }
+
+    pub fn parse_go_statement(&mut self) -> Option<GoStatement> {
+        let token = self.cur_token.clone().unwrap();
+        self.next_token();
+        let call = self.parse_expression(LOWEST)?;
+        if !call.as_any().is::<CallExpression>() {
+            self.error(&token, "expression in go must be function call".to_string());
+            return None;
+        }
+        if self.is_peek_token(SEMICOLON) {
+            self.next_token();
+        }
+        Some(GoStatement { token, call: Some(call) })
+    }
+
+    /// `package name`.
+    pub fn parse_package_clause(&mut self) -> Option<PackageClause> {
+        let token = self.cur_token.clone().unwrap();
+        if !self.expect_peek(IDENT) {
+            return None;
+        }
+        let name = Some(self.cur_identifier());
+        if self.is_peek_token(SEMICOLON) {
+            self.next_token();
+        }
+        Some(PackageClause { token, name })
+    }
+
+    /// `import "path"`, `import name "path"` or a parenthesized list of
+    /// either, one per line.
+    pub fn parse_import_statement(&mut self) -> Option<ImportStatement> {
+        let token = self.cur_token.clone().unwrap();
+        let mut specs = vec![];
+        let mut rparen = None;
+        self.next_token();
+        if self.is_cur_token(LPAREN) {
+            loop {
+                self.next_token();
+                match self.cur_token.clone().unwrap().r#type {
+                    RPAREN => {
+                        rparen = self.cur_token.clone();
+                        break;
+                    }
+                    SEMICOLON => continue,
+                    EOF => {
+                        self.error_at_cur("expected next token to be \")\", got \"EOF\" instead".to_string());
+                        return None;
+                    }
+                    _ => specs.push(self.parse_import_spec()?),
+                }
+            }
+        } else {
+            specs.push(self.parse_import_spec()?);
+        }
+        if self.is_peek_token(SEMICOLON) {
+            self.next_token();
+        }
+        Some(ImportStatement { token, specs, rparen })
+    }
+
+    fn parse_import_spec(&mut self) -> Option<ImportSpec> {
+        let mut name = None;
+        // `.` parses as a name so the compiler can reject dot imports
+        if self.is_cur_token(IDENT) || self.is_cur_token(PERIOD) {
+            name = Some(self.cur_identifier());
+            self.next_token();
+        }
+        if !self.is_cur_token(STRING) {
+            self.error_at_cur("missing import path; require quoted string".to_string());
+            return None;
+        }
+        let token = self.cur_token.clone().unwrap();
+        let value = match unquote(&token.literal) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(msg) => {
+                self.error_at_cur(msg);
+                String::new()
+            }
+        };
+        Some(ImportSpec { name, path: Some(StringLiteral { token, value }) })
+    }
+
+    pub fn parse_defer_statement(&mut self) -> Option<DeferStatement> {
+        let token = self.cur_token.clone().unwrap();
+        self.next_token();
+        let call = self.parse_expression(LOWEST)?;
+        if !call.as_any().is::<CallExpression>() {
+            self.error(&token, "expression in defer must be function call".to_string());
+            return None;
+        }
+        if self.is_peek_token(SEMICOLON) {
+            self.next_token();
+        }
+        Some(DeferStatement { token, call: Some(call) })
+    }
+
+    /// `select { case comm: ... default: ... }`, where each comm is a send
+    /// or a receive, possibly assigned.
+    pub fn parse_select_statement(&mut self) -> Option<SelectStatement> {
+        let token = self.cur_token.clone().unwrap();
+        if !self.expect_peek(LBRACE) {
+            return None;
+        }
+        self.next_token();
+        let mut cases = vec![];
+        while !self.is_cur_token(RBRACE) {
+            if self.is_cur_token(SEMICOLON) {
+                self.next_token();
+                continue;
+            }
+            let case_token = self.cur_token.clone().unwrap();
+            let comm = match case_token.r#type {
+                CASE => {
+                    self.next_token();
+                    Some(self.parse_simple_statement(false)?)
+                }
+                DEFAULT => None,
+                _ => {
+                    let found = if case_token.literal == "\n" { "newline".to_string() } else { case_token.literal.clone() };
+                    self.error_at_cur(format!("expected case or default or }}, got {} instead", found));
+                    return None;
+                }
+            };
+            if !self.expect_peek(COLON) {
+                return None;
+            }
+            self.next_token();
+            let mut body = vec![];
+            while !self.is_cur_token(CASE) && !self.is_cur_token(DEFAULT) && !self.is_cur_token(RBRACE) {
+                if self.is_cur_token(EOF) || diagnostic::too_many(&self.errors) {
+                    self.error_at_cur("expected \"}\" to close select, got EOF instead".to_string());
+                    return None;
+                }
+                if let Some(stmt) = self.nested(Parser::parse_statement) {
+                    body.push(stmt);
                 }
+                self.next_token();
+            }
+            cases.push(CommClause { token: case_token, comm, body });
+        }
+        let rbrace = self.cur_token.clone();
+        Some(SelectStatement { token, cases, rbrace })
+    }
+
+    /// `for {}`, `for cond {}`, `for init; cond; post {}` or
+    /// `for k, v := range x {}`.
+    pub fn parse_for_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.cur_token.clone().unwrap();
+        let header = self.with_composites(false, |p| p.parse_for_header(token.clone()))?;
+        if !self.expect_peek(LBRACE) {
+            return None;
+        }
+        let body = Some(self.parse_block_statement());
+        match header {
+            ForHeader::Range(mut stmt) => {
+                stmt.body = body;
+                Some(stmt)
             }
+            ForHeader::Clauses(init, condition, post) => {
+                Some(Box::new(ForStatement { token, init, condition, post, body }))
+            }
+        }
+    }
+
+    fn parse_for_header(&mut self, token: Token) -> Option<ForHeader> {
+        if self.is_peek_token(LBRACE) {
+            return Some(ForHeader::Clauses(None, None, None));
+        }
+        if self.is_peek_token(RANGE) {
+            self.next_token();
+            self.next_token();
+            let x = self.parse_expression(LOWEST);
+            return Some(ForHeader::Range(Box::new(RangeStatement { token, key: None, value: None, operator: "".to_string(), x, body: None })));
+        }
+
+        let mut init = None;
+        if !self.is_peek_token(SEMICOLON) {
+            self.next_token();
+            let stmt = self.parse_simple_statement(true)?;
+            let stmt = match stmt.into_any().downcast::<RangeStatement>() {
+                Ok(range) => return Some(ForHeader::Range(range)),
+                Err(stmt) => stmt,
+            };
+            let stmt = match stmt.downcast::<ExpressionStatement>() {
+                Ok(cond) if self.is_peek_token(LBRACE) => return Some(ForHeader::Clauses(None, cond.expression, None)),
+                Ok(stmt) => Box::new(*stmt) as Box<dyn Statement>,
+                Err(stmt) => match stmt.downcast::<AssignStatement>() {
+                    Ok(stmt) => Box::new(*stmt) as Box<dyn Statement>,
+                    Err(_) => return None,
+                },
+            };
+            init = Some(stmt);
+        }
+        if !self.expect_peek(SEMICOLON) {
+            return None;
+        }
+        let mut condition = None;
+        if !self.is_peek_token(SEMICOLON) {
+            self.next_token();
+            condition = Some(self.parse_expression(LOWEST)?);
+        }
+        if !self.expect_peek(SEMICOLON) {
+            return None;
         }
+        let mut post = None;
+        if !self.is_peek_token(LBRACE) {
+            self.next_token();
+            post = Some(self.parse_simple_statement(false)?);
+        }
+        Some(ForHeader::Clauses(init, condition, post))
+    }
+
+    fn cur_identifier(&self) -> Identifier {
+        Identifier { token: self.cur_token.clone(), value: self.cur_token.clone().unwrap().literal }
     }
 
     pub fn parse_let_statement(&mut self) -> Option<LetStatement> {
@@ -144,30 +1108,39 @@ impl Parser {
             return None;
         }
 
-        // TODO: We're skipping the expressions until we
-        // encounter a semicolon
-        while !self.is_cur_token(SEMICOLON) {
+        self.next_token();
+
+        stmt.value = self.parse_expression(LOWEST);
+
+        if self.is_peek_token(SEMICOLON) {
             self.next_token();
         }
 
-        return Some(stmt);
+        Some(stmt)
     }
 
     pub fn parse_return_statement(&mut self) -> Option<ReturnStatement> {
-        let stmt = ReturnStatement {
+        let mut stmt = ReturnStatement {
             token: self.cur_token.clone().unwrap(),
             return_value: None,
         };
 
+        if self.is_peek_token(SEMICOLON) || self.is_peek_token(RBRACE) || self.is_peek_token(EOF) {
+            if self.is_peek_token(SEMICOLON) {
+                self.next_token();
+            }
+            return Some(stmt);
+        }
+
         self.next_token();
 
-        // TODO: We're skipping the expressions until we
-        // encounter a semicolon
-        while !self.is_cur_token(SEMICOLON) {
+        stmt.return_value = self.parse_expression(LOWEST);
+
+        if self.is_peek_token(SEMICOLON) {
             self.next_token();
         }
 
-        return Some(stmt);
+        Some(stmt)
     }
 
     pub fn parse_expression_statement(&mut self) -> Option<ExpressionStatement> {
@@ -180,49 +1153,123 @@ impl Parser {
             self.next_token();
         }
 
-        return Some(stmt);
+        Some(stmt)
     }
 
-    
-    pub fn parse_expression(&mut self, _precedence: u8) -> Option<Box<dyn Expression>> {
-        if !self.prefix_parse_fns.contains_key(self.cur_token.clone().unwrap().r#type) {
-            return None;
+
+    pub fn parse_expression(&mut self, precedence: u8) -> Option<Box<dyn Expression>> {
+        self.nested(|p| p.parse_operand_and_operators(precedence))
+    }
+
+    fn parse_operand_and_operators(&mut self, precedence: u8) -> Option<Box<dyn Expression>> {
+        let cur_type = self.cur_token.clone().unwrap().r#type;
+        let prefix = match self.prefix_parse_fns.get(cur_type) {
+            Some(prefix) => *prefix,
+            None => {
+                self.no_prefix_parse_fn_error(cur_type);
+                return None;
+            }
+        };
+        let mut left_exp = prefix(self)?;
+
+        while !self.is_peek_token(SEMICOLON) && precedence < self.peek_precedence() {
+            let peek_type = self.peek_token.clone().unwrap().r#type;
+            if peek_type == LBRACE && !self.starts_composite(left_exp.as_ref()) {
+                break;
+            }
+            let infix = match self.infix_parse_fns.get(peek_type) {
+                Some(infix) => *infix,
+                None => return Some(left_exp),
+            };
+
+            // `a + b + c` nests to the left as deep as `(a + b) + c` does,
+            // and what walks the tree later recurses all the same
+            if !self.deeper() {
+                return None;
+            }
+            self.next_token();
+
+            left_exp = infix(self, left_exp)?;
         }
-        let prefix = self.prefix_parse_fns[self.cur_token.clone().unwrap().r#type];
-        let left_exp = prefix(self);
-        return Some(left_exp);
+
+        Some(left_exp)
     }
 
-    
+
 
     pub fn is_cur_token(&self, t: TokenType) -> bool {
-        return self.cur_token.clone().unwrap().r#type == t;
+        self.cur_token.clone().unwrap().r#type == t
     }
 
     pub fn is_peek_token(&self, t: TokenType) -> bool {
-        return self.peek_token.clone().unwrap().r#type == t;
+        self.peek_token.clone().unwrap().r#type == t
+    }
+
+    pub fn peek_precedence(&self) -> u8 {
+        precedence_of(self.peek_token.clone().unwrap().r#type)
+    }
+
+    pub fn cur_precedence(&self) -> u8 {
+        precedence_of(self.cur_token.clone().unwrap().r#type)
     }
 
     pub fn expect_peek(&mut self, t: TokenType) -> bool {
         if self.is_peek_token(t) {
             self.next_token();
-            return true;
+            true
         } else {
             self.peek_error(t);
-            return false;
+            false
         }
     }
 
     pub fn errors(&self) -> Vec<String> {
-        return self.errors.clone();
+        self.errors.iter().map(|d| d.message.clone()).collect()
+    }
+
+    /// The errors with the position of the token each one was reported at.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.errors.clone()
+    }
+
+    /// Runs `parse` one level deeper, or reports the nesting as too deep.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Parser) -> Option<T>) -> Option<T> {
+        let depth = self.depth;
+        if !self.deeper() {
+            return None;
+        }
+        let parsed = parse(self);
+        self.depth = depth;
+        parsed
+    }
+
+    /// Goes a level deeper, unless that is too deep.
+    fn deeper(&mut self) -> bool {
+        if self.depth >= MAX_DEPTH {
+            self.error_at_cur("exceeded max nesting depth".to_string());
+            return false;
+        }
+        self.depth += 1;
+        true
+    }
+
+    fn error(&mut self, tok: &Token, msg: String) {
+        diagnostic::report(&mut self.errors, Diagnostic::at(tok, msg));
+    }
+
+    fn error_at_cur(&mut self, msg: String) {
+        let tok = self.cur_token.clone().unwrap();
+        self.error(&tok, msg);
     }
 
     pub fn peek_error(&mut self, t: TokenType) {
-        let msg = format!(
-            "expected next token to be {:?}, got {:?} instead",
-            t,
-            self.peek_token.clone().unwrap().r#type
-        );
-        self.errors.push(msg);
+        let tok = self.peek_token.clone().unwrap();
+        let msg = format!("expected next token to be {:?}, got {:?} instead", t, tok.r#type);
+        self.error(&tok, msg);
+    }
+
+    pub fn no_prefix_parse_fn_error(&mut self, t: TokenType) {
+        let msg = format!("no prefix parse function for {:?} found", t);
+        self.error_at_cur(msg);
     }
 }
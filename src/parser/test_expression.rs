@@ -1,3 +1,7 @@
+use crate::ast::visit::Visitor;
+use crate::{ast, lexer, parser};
+use colored::Colorize;
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -11,9 +15,17 @@ mod tests {
     check_parser_errors(p);
   }
 
+  /// Collects the integer literals of a program in source order.
+  struct IntegerLiterals(Vec<ast::IntegerLiteral>);
+
+  impl Visitor for IntegerLiterals {
+    fn visit_integer_literal(&mut self, lit: &ast::IntegerLiteral) {
+      self.0.push(lit.clone());
+    }
+  }
+
   #[test]
   fn test_integer_literal_expression() {
-    use crate::ast::Statement;
     use crate::ast::Expression;
     let input = "5;";
     let l = lexer::Lexer::new(input.to_string());
@@ -21,39 +33,98 @@ mod tests {
     let program = p.parse_program();
 
     check_parser_errors(p);
-    
+
     if program.statements.len() != 1 {
         panic!("program has not enough statements. got={:?}", program.statements.len());
     }
- 
-    let stmt: ast::ExpressionStatement;
-    if let Ok(ok) = program.statements[0].to_any().downcast::<ast::ExpressionStatement>() {
-      stmt = *ok;
-    } else {
-      panic!("program.statements[0] is not ast::ExpressionStatement. got {:?}", program.statements[0].token_literal());
-    }
 
-    let literal: ast::IntegerLiteral;
-    if let Ok(ok) = stmt.expression.unwrap().to_any().downcast::<ast::IntegerLiteral>() {
-      literal = *ok;
-    } else {
-        panic!("Expression is not ast::IntegerLiteral. got {:?}", "None");
+    let mut literals = IntegerLiterals(vec![]);
+    literals.visit_program(&program);
+    if literals.0.len() != 1 {
+        panic!("program does not contain one ast::IntegerLiteral. got={:?}", literals.0.len());
     }
-   
+    let literal = &literals.0[0];
 
     if literal.value != Some(5) {
-        panic!("literal.value not {:?}. got={:?}", 5, literal.value); 
+        panic!("literal.value not {:?}. got={:?}", 5, literal.value);
     }
-   
+
     if literal.token_literal() != "5" {
-        panic!("literal.token_literal() not {:?}. got={:?}", "5", literal.token_literal()); 
+        panic!("literal.token_literal() not {:?}. got={:?}", "5", literal.token_literal());
+    }
+
+  }
+
+  #[test]
+  fn test_operator_precedence_parsing() {
+    use crate::ast::Statement;
+    let tests = [
+      ("-a * b", "((-a) * b)"),
+      ("!-a", "(!(-a))"),
+      ("a + b + c", "((a + b) + c)"),
+      ("a + b - c", "((a + b) - c)"),
+      ("a * b * c", "((a * b) * c)"),
+      ("a * b / c", "((a * b) / c)"),
+      ("a + b / c", "(a + (b / c))"),
+      ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
+      ("3 + 4; -5 * 5", "(3 + 4)((-5) * 5)"),
+      ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+      ("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4))"),
+      ("3 + 4 * 5 == 3 * 1 + 4 * 5", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))"),
+      ("true", "true"),
+      ("3 > 5 == false", "((3 > 5) == false)"),
+      ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)"),
+      ("(5 + 5) * 2", "((5 + 5) * 2)"),
+      ("-(5 + 5)", "(-(5 + 5))"),
+      ("!(true == true)", "(!(true == true))"),
+      ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+      ("add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))", "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))"),
+      ("add(a + b + c * d / f + g)", "add((((a + b) + ((c * d) / f)) + g))"),
+    ];
+
+    for (input, expected) in tests.iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = parser::Parser::new(l);
+      let program = p.parse_program();
+      check_parser_errors(p);
+
+      if program.string() != *expected {
+        panic!("expected={:?}, got={:?}", expected, program.string());
+      }
+    }
+  }
+
+  #[test]
+  fn test_if_else_expression() {
+    use crate::ast::Statement;
+    let input = "if (x < y) { x } else { y }";
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = parser::Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(p);
+
+    if program.string() != "if(x < y) xelse y" {
+      panic!("program.string() wrong. got={:?}", program.string());
+    }
+  }
+
+  #[test]
+  fn test_function_literal_parsing() {
+    use crate::ast::Statement;
+    let input = "fn(x, y) { x + y; }";
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = parser::Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(p);
+
+    if program.string() != "fn(x, y) (x + y)" {
+      panic!("program.string() wrong. got={:?}", program.string());
     }
-  
   }
 
   fn check_parser_errors(p: parser::Parser) {
     let errors = p.errors();
-    if errors.len() == 0 {
+    if errors.is_empty() {
       return;
     }
 
@@ -1,3 +1,7 @@
+use crate::ast::Statement;
+use crate::{lexer, parser};
+use colored::Colorize;
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -15,11 +19,96 @@ mod tests {
 
     let program = p.parse_program();
     check_parser_errors(p);
+
+    if program.statements.len() != 4 {
+      panic!("program.statements does not contain 4 statements. got={:?}", program.statements.len());
+    }
+
+    let expected = ["let x = 5;", "let y = 10;", "let foobar = 838383;", "return;"];
+    for (stmt, want) in program.statements.iter().zip(expected.iter()) {
+      if stmt.string() != *want {
+        panic!("stmt.string() not {:?}. got={:?}", want, stmt.string());
+      }
+    }
+  }
+
+  #[test]
+  fn test_program_string() {
+    let input = "let myVar = anotherVar;";
+
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = parser::Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(p);
+
+    if program.string() != input {
+      panic!("program.string() wrong. got={:?}", program.string());
+    }
+  }
+
+  #[test]
+  fn test_newline_ends_statement() {
+    let input = "let a = 1
+    let b = a
+    -b
+    ";
+
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = parser::Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(p);
+
+    if program.string() != "let a = 1;let b = a;(-b)" {
+      panic!("program.string() wrong. got={:?}", program.string());
+    }
+  }
+
+  #[test]
+  fn test_package_clause() {
+    let l = lexer::Lexer::new("package geo\nlet x = 1".to_string());
+    let mut p = parser::Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(p);
+
+    if program.string() != "package geo;let x = 1;" {
+      panic!("program.string() wrong. got={:?}", program.string());
+    }
+
+    let l = lexer::Lexer::new("let x = 1\npackage geo".to_string());
+    let mut p = parser::Parser::new(l);
+    p.parse_program();
+    if p.errors() != ["syntax error: package statement must be first"] {
+      panic!("wrong errors. got={:?}", p.errors());
+    }
+  }
+
+  #[test]
+  fn test_limits() {
+    let nested = |n: usize| format!("let x = {}1{}", "(".repeat(n), ")".repeat(n));
+    let l = lexer::Lexer::new(nested(100));
+    let mut p = parser::Parser::new(l);
+    p.parse_program();
+    check_parser_errors(p);
+
+    let l = lexer::Lexer::new(nested(20000));
+    let mut p = parser::Parser::new(l);
+    p.parse_program();
+    if p.errors().first().map(String::as_str) != Some("exceeded max nesting depth") {
+      panic!("wrong errors. got={:?}", p.errors());
+    }
+
+    let l = lexer::Lexer::new("-- ".repeat(3000));
+    let mut p = parser::Parser::new(l);
+    p.parse_program();
+    let errors = p.errors();
+    if errors.len() != 11 || errors[10] != "too many errors" {
+      panic!("wrong errors. got={:?}", errors);
+    }
   }
 
   fn check_parser_errors(p: parser::Parser) {
     let errors = p.errors();
-    if errors.len() == 0 {
+    if errors.is_empty() {
       return;
     }
 
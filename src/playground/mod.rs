@@ -0,0 +1,128 @@
+//! Running programs for a playground in a browser, where the library is
+//! compiled for `wasm32-unknown-unknown`. `run` takes a program's source
+//! and gives what running it writes, as `namu run` would show it; the
+//! functions below export it with a C ABI, for JavaScript to call with
+//! buffers in the module's memory:
+//!
+//! ```text
+//! src = namu_alloc(len)             write the source's UTF-8 at src
+//! out = namu_run(src, len, &n)      read n bytes of output at out
+//! namu_free(src, len); namu_free(out, n)
+//! ```
+
+use crate::build::Target;
+use crate::highlight::ColorChoice;
+use crate::loader::{self, Loader};
+use crate::stdlib;
+use crate::vm::{Limits, Vm};
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+#[cfg(test)]
+mod test;
+
+/// What a program may use, so that one that loops or allocates without
+/// end gives the page back. wasm32 has no clock to time it out by, so
+/// instructions are counted instead.
+const LIMITS: Limits = Limits {
+    instructions: Some(100_000_000),
+    heap: Some(64 << 20),
+    goroutines: Some(10_000),
+    call_depth: None,
+    timeout: None,
+};
+
+/// Runs a program of one file as `namu run` does: its top-level
+/// statements, then `main` when it defines one, within `LIMITS`. The
+/// output is what the program writes to standard output and error, then
+/// any errors it did not compile with or a panic ended it with, then its
+/// exit status when that is not zero.
+pub fn run(source: &str) -> String {
+    run_within(source, LIMITS)
+}
+
+/// Runs a program as `run` does, within `limits`.
+fn run_within(source: &str, limits: Limits) -> String {
+    let (result, output) = stdlib::capture(|| execute(source, limits));
+    let mut output = String::from_utf8_lossy(&output).into_owned();
+    let status = match result {
+        Ok(status) => status,
+        Err((errors, status)) => {
+            output.push_str(&errors);
+            status
+        }
+    };
+    if status != 0 {
+        output.push_str(&format!("exit status {}\n", status));
+    }
+    output
+}
+
+/// The program's exit status, or the errors that ended it and the status.
+fn execute(source: &str, limits: Limits) -> Result<i32, (String, i32)> {
+    // the program is built for the playground's target, as Go's is
+    let target = Target::new("js", "wasm", vec![]).expect("js/wasm is a known target");
+    let files = vec![("main.go".to_string(), source.to_string())];
+    let compiled = Loader::new(None, target).load_files(Path::new("."), files).and_then(|packages| loader::compile(&packages));
+    let mut compiler = compiled.map_err(|errors| (render_errors(&errors), 1))?;
+    let main_symbol = compiler.symbol_table.resolve("main");
+    let mut vm = Vm::new(compiler.bytecode());
+    vm.set_args(vec!["main".to_string()]);
+    vm.set_limits(limits);
+    vm.run_main(main_symbol.map(|symbol| symbol.index)).map_err(|msg| (format!("{}\n", msg), 2))
+}
+
+fn render_errors(errors: &[loader::Error]) -> String {
+    let mut out = String::new();
+    for error in errors {
+        match error {
+            loader::Error::File { name, source, diagnostics } => {
+                for diagnostic in diagnostics {
+                    out.push_str(&diagnostic.render(name, source));
+                }
+            }
+            loader::Error::Message(msg) => {
+                out.push_str(msg);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Allocates `len` bytes in the module's memory, for the host to write a
+/// program's source into.
+#[no_mangle]
+pub extern "C" fn namu_alloc(len: usize) -> *mut u8 {
+    Box::into_raw(vec![0u8; len].into_boxed_slice()) as *mut u8
+}
+
+/// Frees what `namu_alloc` allocated, or the output of `namu_run`.
+///
+/// # Safety
+///
+/// `ptr` and `len` must be those of a buffer one of them returned, which
+/// is not used after.
+#[no_mangle]
+pub unsafe extern "C" fn namu_free(ptr: *mut u8, len: usize) {
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)));
+}
+
+/// Runs the program whose source is the `len` bytes at `source`, returning
+/// its output, see `run`, and storing the output's length at `output_len`.
+/// Source that is not UTF-8 has its bad bytes replaced.
+///
+/// # Safety
+///
+/// `source` must point to `len` readable bytes, and `output_len` to a
+/// writable `usize`.
+#[no_mangle]
+pub unsafe extern "C" fn namu_run(source: *const u8, len: usize, output_len: *mut usize) -> *mut u8 {
+    // a page has no terminal to color for
+    ColorChoice::Never.apply();
+    let source = String::from_utf8_lossy(slice::from_raw_parts(source, len)).into_owned();
+    let output = run(&source).into_bytes().into_boxed_slice();
+    *output_len = output.len();
+    Box::into_raw(output) as *mut u8
+}
@@ -0,0 +1,63 @@
+use crate::playground::*;
+use crate::vm::Limits;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_run() {
+    let source = r#"import "fmt"
+let main = fn() {
+  fmt.Println("hello", 1+2)
+  println("to stderr")
+  fmt.Print("done\n")
+}"#;
+    assert_eq!(run(source), "hello 3\nto stderr\ndone\n");
+  }
+
+  #[test]
+  fn test_errors() {
+    let output = run("let main = fn() { x := y }");
+    assert!(output.contains("undefined: y"), "{}", output);
+    assert!(output.ends_with("exit status 1\n"), "{}", output);
+
+    let output = run(r#"import "fmt"
+let main = fn() {
+  fmt.Println("before")
+  panic("boom")
+}"#);
+    assert!(output.starts_with("before\npanic: boom\n\ngoroutine 1 [running]:\n"), "{}", output);
+    assert!(output.ends_with("exit status 2\n"), "{}", output);
+
+    let output = run(r#"import "os"
+let main = fn() { os.Exit(3) }"#);
+    assert_eq!(output, "exit status 3\n");
+  }
+
+  #[test]
+  fn test_limits() {
+    let limits = Limits { instructions: Some(100_000), ..Default::default() };
+    let output = run_within("let main = fn() { for {} }", limits);
+    assert!(output.contains("instruction limit of 100000 exceeded"), "{}", output);
+    assert!(output.ends_with("exit status 2\n"), "{}", output);
+
+    let output = run("let main = fn() { s := make([]int, 100000000)\nprintln(len(s)) }");
+    assert!(output.contains("heap limit of 67108864 bytes exceeded"), "{}", output);
+    assert!(output.ends_with("exit status 2\n"), "{}", output);
+  }
+
+  #[test]
+  fn test_c_abi() {
+    let source = b"import \"fmt\"\nlet main = fn() { fmt.Println(\"abi\") }";
+    unsafe {
+      let buffer = namu_alloc(source.len());
+      buffer.copy_from_nonoverlapping(source.as_ptr(), source.len());
+      let mut len = 0;
+      let output = namu_run(buffer, source.len(), &mut len);
+      assert_eq!(std::slice::from_raw_parts(output, len), b"abi\n");
+      namu_free(output, len);
+      namu_free(buffer, source.len());
+    }
+  }
+}
@@ -0,0 +1,124 @@
+use crate::dump;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::repl::{describe, EvalError, Session};
+use colored::Colorize;
+use std::fs;
+
+/// Meta-commands with their argument and a one-line description.
+pub const COMMANDS: &[(&str, &str, &str)] = &[
+    (":help", "", "show this help"),
+    (":tokens", "<expr>", "print the tokens of an expression"),
+    (":ast", "<expr>", "print the syntax tree of an expression"),
+    (":type", "<expr>", "evaluate an expression and print the type of its value"),
+    (":env", "", "list global names with their values"),
+    (":load", "<file>", "evaluate a file in this session"),
+    (":reset", "", "forget every definition"),
+    (":quit", "", "leave the REPL"),
+];
+
+/// What the REPL should do after a meta-command. `Error` text is ready
+/// to print, colors included.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Output(String),
+    Error(String),
+    Quit,
+}
+
+/// Resolves a command name or an unambiguous prefix of one (`:q`).
+fn lookup(name: &str) -> Result<&'static str, String> {
+    if let Some((exact, _, _)) = COMMANDS.iter().find(|(n, _, _)| *n == name) {
+        return Ok(exact);
+    }
+    let matches: Vec<&str> = COMMANDS.iter().map(|(n, _, _)| *n).filter(|n| n.starts_with(name)).collect();
+    match matches.as_slice() {
+        [only] => Ok(only),
+        [] => Err(format!("unknown command {}; try :help", name)),
+        _ => Err(format!("ambiguous command {}: {}", name, matches.join(", "))),
+    }
+}
+
+/// Runs a line starting with `:`.
+pub fn execute(session: &mut Session, line: &str) -> Reply {
+    let line = line.trim();
+    let (name, arg) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    };
+    let name = match lookup(name) {
+        Ok(name) => name,
+        Err(msg) => return failure(msg),
+    };
+    let needs_arg = COMMANDS.iter().any(|(n, a, _)| *n == name && !a.is_empty());
+    if needs_arg && arg.is_empty() {
+        return failure(format!("{} needs an argument", name));
+    }
+
+    match name {
+        ":help" => Reply::Output(help()),
+        ":tokens" => Reply::Output(dump::tokens_text(&dump::tokenize(arg))),
+        ":ast" => {
+            let mut p = Parser::new(Lexer::new(arg.to_string()));
+            let program = p.parse_program();
+            if !p.diagnostics().is_empty() {
+                return Reply::Error(render(&EvalError::Diagnostics(p.diagnostics()), super::INPUT_NAME, arg));
+            }
+            Reply::Output(dump::sexp(&program))
+        }
+        ":type" => {
+            // The expression runs, on a copy so that names it defines and
+            // globals it assigns do not stick. Its writes through maps,
+            // slices and pointers, and its output, do.
+            let mut scratch = session.clone();
+            match scratch.eval(arg) {
+                Ok(Some(value)) => Reply::Output(format!("{}\n", value.type_name())),
+                Ok(None) => failure(format!("{} is not an expression", arg)),
+                Err(e) => Reply::Error(render(&e, super::INPUT_NAME, arg)),
+            }
+        }
+        ":env" => {
+            let out: String = session.env().iter().map(|(name, value)| format!("{} = {}\n", name, describe(value))).collect();
+            Reply::Output(out)
+        }
+        ":load" => {
+            let source = match fs::read_to_string(arg) {
+                Ok(source) => source,
+                Err(e) => return failure(format!("{}: {}", arg, e)),
+            };
+            match session.eval(&source) {
+                Ok(Some(value)) => Reply::Output(format!("{}\n", describe(&value))),
+                Ok(None) => Reply::Output(String::new()),
+                Err(e) => Reply::Error(render(&e, arg, &source)),
+            }
+        }
+        ":reset" => {
+            *session = Session::new();
+            Reply::Output(String::new())
+        }
+        _ => Reply::Quit,
+    }
+}
+
+fn failure(msg: String) -> Reply {
+    Reply::Error(msg.red().to_string())
+}
+
+fn help() -> String {
+    let mut out = String::from("Enter expressions or statements; an open brace or a trailing operator continues on the next line.\n\n");
+    for (name, arg, description) in COMMANDS {
+        out.push_str(&format!("  {:<16}{}\n", format!("{} {}", name, arg).trim_end(), description));
+    }
+    out
+}
+
+/// Error text for an input that failed, with source excerpts for
+/// diagnostics.
+pub fn render(e: &EvalError, file: &str, source: &str) -> String {
+    match e {
+        EvalError::Diagnostics(diagnostics) => diagnostics.iter().map(|d| d.render(file, source)).collect(),
+        EvalError::Runtime(msg) => format!("{}\n", format!("panic: {}", msg).red()),
+        EvalError::Limit(limit) => format!("{}\n", limit.to_string().red()),
+        EvalError::Exit(code) => format!("{}\n", format!("exit status {}", code).red()),
+    }
+}
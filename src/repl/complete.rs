@@ -0,0 +1,80 @@
+use crate::repl::command::COMMANDS;
+use crate::token::KEYWORDS;
+use linefeed::complete::{Completer, Completion};
+use linefeed::prompter::Prompter;
+use linefeed::terminal::Terminal;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Tab completion for the REPL: meta-commands at the start of a line,
+/// otherwise keywords and the session's global names.
+pub struct ReplCompleter {
+    /// Global names of the session, refreshed after every input. The
+    /// completer runs on linefeed's side and must be `Send`, so it keeps a
+    /// copy rather than a reference to the session.
+    pub names: Arc<Mutex<Vec<String>>>,
+    /// Field names of the struct-valued globals, refreshed likewise.
+    pub fields: Arc<Mutex<HashMap<String, Vec<String>>>>,
+}
+
+impl<Term: Terminal> Completer<Term> for ReplCompleter {
+    fn complete(&self, word: &str, prompter: &Prompter<Term>, start: usize, _end: usize) -> Option<Vec<Completion>> {
+        let names = self.names.lock().unwrap();
+        let fields = self.fields.lock().unwrap();
+        let found = candidates(&prompter.buffer()[..start], word, &names, &fields);
+        if found.is_empty() {
+            None
+        } else {
+            Some(found.into_iter().map(Completion::simple).collect())
+        }
+    }
+
+    fn word_start(&self, line: &str, end: usize, _prompter: &Prompter<Term>) -> usize {
+        word_start(&line[..end])
+    }
+}
+
+/// Start of the identifier (or `:command` at the start of the line) that
+/// ends at the end of `line`.
+pub fn word_start(line: &str) -> usize {
+    let start = line
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+        .last()
+        .map_or(line.len(), |(i, _)| i);
+    if line[..start].trim_start() == ":" {
+        return line.find(':').unwrap();
+    }
+    start
+}
+
+/// Sorted completions of `word`, given the text before it.
+pub fn candidates(before: &str, word: &str, names: &[String], fields: &HashMap<String, Vec<String>>) -> Vec<String> {
+    if word.starts_with(':') {
+        return COMMANDS
+            .iter()
+            .map(|(name, _, _)| name.to_string())
+            .filter(|name| name.starts_with(word))
+            .collect();
+    }
+    if let Some(operand) = before.strip_suffix('.') {
+        let operand = &operand[word_start(operand)..];
+        let mut found: Vec<String> = match fields.get(operand) {
+            Some(fields) => fields.iter().filter(|f| f.starts_with(word)).cloned().collect(),
+            None => vec![],
+        };
+        found.sort();
+        return found;
+    }
+
+    let mut found: Vec<String> = KEYWORDS
+        .keys()
+        .map(|k| k.to_string())
+        .chain(names.iter().cloned())
+        .filter(|name| name.starts_with(word))
+        .collect();
+    found.sort();
+    found.dedup();
+    found
+}
@@ -0,0 +1,180 @@
+use crate::highlight;
+use crate::lexer::Lexer;
+use crate::token::*;
+use crate::value::Value;
+use colored::Colorize;
+use linefeed::{Interface, ReadResult};
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+pub mod command;
+pub mod complete;
+
+#[cfg(test)]
+mod test;
+
+pub use crate::interpreter::session::{EvalError, Session};
+
+pub const PROMPT: &str = ">> ";
+pub const CONTINUATION_PROMPT: &str = ".. ";
+/// File name diagnostics for REPL input are reported against.
+pub const INPUT_NAME: &str = "<repl>";
+
+/// Reports whether `input` stops part-way through a construct: an open
+/// brace, parenthesis, block comment or raw string, or a trailing
+/// operator or keyword that needs something after it.
+pub fn is_incomplete(input: &str) -> bool {
+    let mut lexer = Lexer::new(input.to_string());
+    let mut depth: i32 = 0;
+    let mut last = EOF;
+    loop {
+        let tok = lexer.next_token();
+        match tok.r#type {
+            EOF => break,
+            LBRACE | LPAREN | LBRACKET => depth += 1,
+            RBRACE | RPAREN | RBRACKET => depth -= 1,
+            // a raw string runs to the end of the input until it is closed
+            STRING if tok.literal.starts_with('`') && (tok.literal.len() < 2 || !tok.literal.ends_with('`')) => {
+                return true
+            }
+            _ => {}
+        }
+        if tok.r#type != SEMICOLON || tok.literal == ";" {
+            last = tok.r#type;
+        }
+    }
+    if let Some(comment) = lexer.comments.last() {
+        if comment.literal.starts_with("/*") && !comment.literal.ends_with("*/") {
+            return true;
+        }
+    }
+    let dangling = [
+        ASSIGN, DEFINE, ADD_ASSIGN, SUB_ASSIGN, MUL_ASSIGN, QUO_ASSIGN, REM_ASSIGN, PLUS, MINUS, BANG, ASTERISK, SLASH,
+        PERCENT, AMPERSAND, LT, GT, LT_EQ, GT_EQ, EQ, NOT_EQ, AND, OR, COMMA, PERIOD, ARROW, LET, VAR, IF, ELSE, FOR,
+        FUNCTION,
+    ];
+    depth > 0 || dangling.contains(&last)
+}
+
+/// `5 (int)`; nil has no type worth showing.
+pub fn describe(value: &Value) -> String {
+    match value {
+        Value::Null => value.inspect(),
+        _ => format!("{} ({})", value.inspect(), value.type_name()),
+    }
+}
+
+/// Where history is kept between sessions: `$NAMU_HISTORY`, else
+/// `~/.namu_history`.
+pub fn history_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("NAMU_HISTORY") {
+        return Some(PathBuf::from(path));
+    }
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".namu_history"))
+}
+
+/// Reads inputs until end of file, `:quit` or `os.Exit`, evaluating each
+/// as soon as it is complete and printing its value or errors. Returns
+/// the status an input exited with, if one did.
+pub fn start() -> io::Result<Option<i32>> {
+    let reader = Interface::new("namu")?;
+    let mut session = Session::new();
+    let mut buffer = String::new();
+    let names = Arc::new(Mutex::new(vec![]));
+    let fields = Arc::new(Mutex::new(HashMap::new()));
+
+    reader.set_completer(Arc::new(complete::ReplCompleter { names: Arc::clone(&names), fields: Arc::clone(&fields) }));
+    let history = history_path();
+    if let Some(path) = &history {
+        if let Err(e) = reader.load_history(path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                eprintln!("{}", format!("cannot load history from {}: {}", path.display(), e).red());
+            }
+        }
+    }
+    reader.set_prompt(PROMPT)?;
+
+    let mut exit = None;
+    loop {
+        let line = match reader.read_line()? {
+            ReadResult::Input(line) => line,
+            ReadResult::Signal(_) => {
+                buffer.clear();
+                reader.set_prompt(PROMPT)?;
+                continue;
+            }
+            ReadResult::Eof => break,
+        };
+        if buffer.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+        if buffer.is_empty() && line.trim_start().starts_with(':') {
+            reader.add_history_unique(line.trim().to_string());
+            match command::execute(&mut session, &line) {
+                command::Reply::Output(out) => print!("{}", out),
+                command::Reply::Error(msg) => eprint!("{}", with_newline(msg)),
+                command::Reply::Quit => break,
+            }
+            *names.lock().unwrap() = session.names();
+            *fields.lock().unwrap() = session.fields();
+            continue;
+        }
+        buffer.push_str(&line);
+        buffer.push('\n');
+        // an empty line gives up on an unfinished input
+        if is_incomplete(&buffer) && !line.trim().is_empty() {
+            reader.set_prompt(CONTINUATION_PROMPT)?;
+            continue;
+        }
+
+        reader.add_history_unique(buffer.trim_end().to_string());
+        if highlight::colors_enabled() {
+            echo(&buffer);
+        }
+        match session.eval(&buffer) {
+            Ok(Some(value)) => println!("{}", describe(&value)),
+            Ok(None) => {}
+            Err(EvalError::Exit(code)) => {
+                exit = Some(code);
+                break;
+            }
+            Err(e) => eprint!("{}", command::render(&e, INPUT_NAME, &buffer)),
+        }
+        *names.lock().unwrap() = session.names();
+        *fields.lock().unwrap() = session.fields();
+        buffer.clear();
+        reader.set_prompt(PROMPT)?;
+    }
+
+    if let Some(path) = &history {
+        if let Err(e) = reader.save_history(path) {
+            eprintln!("{}", format!("cannot save history to {}: {}", path.display(), e).red());
+        }
+    }
+    if exit.is_none() {
+        println!("Goodbye.");
+    }
+
+    Ok(exit)
+}
+
+/// Redraws the input just entered, prompts included, with highlighting.
+fn echo(buffer: &str) {
+    let lines = buffer.trim_end_matches('\n').split('\n').count();
+    let mut out = format!("\x1b[{}A", lines);
+    for (i, line) in highlight::highlight(buffer).split('\n').take(lines).enumerate() {
+        let prompt = if i == 0 { PROMPT } else { CONTINUATION_PROMPT };
+        out.push_str(&format!("\r\x1b[2K{}{}\n", prompt, line));
+    }
+    print!("{}", out);
+}
+
+fn with_newline(mut msg: String) -> String {
+    if !msg.ends_with('\n') {
+        msg.push('\n');
+    }
+    msg
+}
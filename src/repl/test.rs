@@ -0,0 +1,163 @@
+use crate::diagnostic::Diagnostic;
+use crate::value::Value;
+use crate::repl::command::{execute, Reply};
+use crate::repl::complete::{candidates, word_start};
+use crate::repl::*;
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_session_keeps_state() {
+    let mut session = Session::new();
+    let inputs = [
+      ("let add = fn(a, b) { a + b };", None),
+      ("let x = add(1, 2)", None),
+      ("x * 2", Some(Value::Integer(6))),
+      ("x > 2", Some(Value::Boolean(true))),
+    ];
+    for (input, expected) in inputs.iter() {
+      match session.eval(input) {
+        Ok(got) if got == *expected => {}
+        got => panic!("{:?}: expected={:?}, got={:?}", input, expected, got),
+      }
+    }
+  }
+
+  #[test]
+  fn test_failed_input_defines_nothing() {
+    let mut session = Session::new();
+    if session.eval("let y = 1; let z = q").is_ok() {
+      panic!("expected an error for undefined q");
+    }
+    match session.eval("y") {
+      Err(EvalError::Diagnostics(d)) if d == vec![Diagnostic::new(1, 1, "undefined: y".to_string())] => {}
+      got => panic!("expected y to be undefined, got={:?}", got),
+    }
+    assert_eq!(session.eval("import \"os\"; let w = 1; os.Exit(3)"), Err(EvalError::Exit(3)));
+    assert!(session.get("w").is_none());
+  }
+
+  #[test]
+  fn test_is_incomplete() {
+    let tests = [
+      ("let f = fn(x) {", true),
+      ("let f = fn(x) {\n  x\n}", false),
+      ("add(1,", true),
+      ("1 +", true),
+      ("let x =", true),
+      ("if x { 1 } else", true),
+      ("/* open", true),
+      ("1 + 2 // done", false),
+      ("x)", false),
+      ("", false),
+      ("x +=", true),
+      ("x %=", true),
+      ("fmt.", true),
+      ("p := &", true),
+      ("s := `abc", true),
+      ("s := `abc\ndef`", false),
+      ("s := \"abc", false),
+      ("c <-", true),
+      ("v := <-c", false),
+    ];
+    for (input, expected) in tests.iter() {
+      if is_incomplete(input) != *expected {
+        panic!("is_incomplete({:?}) expected={}", input, expected);
+      }
+    }
+  }
+
+  #[test]
+  fn test_describe() {
+    let tests = [
+      (Value::Integer(5), "5 (int)"),
+      (Value::Boolean(false), "false (bool)"),
+      (Value::Null, "nil"),
+    ];
+    for (obj, expected) in tests.iter() {
+      if describe(obj) != *expected {
+        panic!("describe({:?}) expected={:?}, got={:?}", obj, expected, describe(obj));
+      }
+    }
+  }
+
+  #[test]
+  fn test_render_diagnostic() {
+    let d = Diagnostic::new(2, 9, "undefined: y".to_string());
+    let expected = "error: undefined: y
+ --> <repl>:2:9
+  |
+2 | let x = y
+  |         ^
+";
+    let got = d.render(INPUT_NAME, "let a = 1\nlet x = y\n");
+    if got != expected {
+      panic!("render expected=\n{}got=\n{}", expected, got);
+    }
+  }
+
+  #[test]
+  fn test_meta_commands() {
+    let mut session = Session::new();
+    session.eval("let x = 2; let f = fn(a) { a }").unwrap();
+    let tests = [
+      (":env", Reply::Output("x = 2 (int)\nf = fn(a) (fn)\n".to_string())),
+      (":type x > 1", Reply::Output("bool\n".to_string())),
+      (":type let y = 1", Reply::Error("let y = 1 is not an expression".to_string())),
+      (":ast 1 + x", Reply::Output("(+ 1 x)\n".to_string())),
+      (":tokens x", Reply::Output("1:1\tIDENT\t\"x\"\n1:2\t;\t\"\\n\"\n1:2\tEOF\t\"\\0\"\n".to_string())),
+      (":t x", Reply::Error("ambiguous command :t: :tokens, :type".to_string())),
+      (":nope", Reply::Error("unknown command :nope; try :help".to_string())),
+      (":load", Reply::Error(":load needs an argument".to_string())),
+      (":q", Reply::Quit),
+    ];
+    for (line, expected) in tests.iter() {
+      let got = execute(&mut session, line);
+      if got != *expected {
+        panic!("{:?}: expected={:?}, got={:?}", line, expected, got);
+      }
+    }
+    if !session.names().contains(&"x".to_string()) || session.names().contains(&"y".to_string()) {
+      panic!(":type must not define names, got {:?}", session.names());
+    }
+    session.eval("let m = map[string]int{}; let bump = fn() { x++; m[\"k\"]++; x }").unwrap();
+    execute(&mut session, ":type bump()");
+    if session.get("x") != Some(Value::Integer(2)) || session.eval("m[\"k\"]") != Ok(Some(Value::Integer(1))) {
+      panic!(":type kept an assignment or copied a map, got x={:?}", session.get("x"));
+    }
+    execute(&mut session, ":reset");
+    if !session.names().is_empty() {
+      panic!(":reset left {:?}", session.names());
+    }
+  }
+
+  #[test]
+  fn test_completion() {
+    let names = vec!["fib".to_string(), "first".to_string(), "total".to_string(), "p".to_string()];
+    let fields = HashMap::from([("p".to_string(), vec!["X".to_string(), "Y".to_string(), "Name".to_string()])]);
+    let tests = [
+      ("", "f", vec!["false", "fib", "first", "fn", "for"]),
+      ("1 + ", "t", vec!["total", "true", "type"]),
+      ("", ":l", vec![":load"]),
+      ("x.", "f", vec![]),
+      ("a + p.", "", vec!["Name", "X", "Y"]),
+      ("p.", "N", vec!["Name"]),
+    ];
+    for (before, word, expected) in tests.iter() {
+      let got = candidates(before, word, &names, &fields);
+      if got != *expected {
+        panic!("candidates({:?}, {:?}) expected={:?}, got={:?}", before, word, expected, got);
+      }
+    }
+
+    let starts = [("fib(to", 4), (":he", 0), ("  :he", 2), ("a+b", 2), ("x.", 2)];
+    for (line, expected) in starts.iter() {
+      if word_start(line) != *expected {
+        panic!("word_start({:?}) expected={}, got={}", line, expected, word_start(line));
+      }
+    }
+  }
+}
@@ -0,0 +1,204 @@
+//! Package errors, and the error values every package returns.
+
+use super::{string_arg, want_args, Member, Package};
+use crate::types::{Field, StructType, Type};
+use crate::value::builtins::Builtin;
+use crate::value::{convert, new_var, Interface, Pointer, Struct, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub const PACKAGE: Package = Package {
+    path: "errors",
+    members: &[
+        ("New", Member::Func(Builtin { name: "errors.New", func: new }, &[])),
+        ("Is", Member::Func(Builtin { name: "errors.Is", func: is }, &[])),
+        ("As", Member::Func(Builtin { name: "errors.As", func: as_ }, &[])),
+        ("Unwrap", Member::Func(Builtin { name: "errors.Unwrap", func: unwrap_ }, &[])),
+    ],
+};
+
+/// `err.Error`, bound to an error value by `stdlib::method`.
+pub const ERROR_METHOD: Builtin = Builtin { name: "error.Error", func: error_method };
+
+/// The struct types of the errors the packages make; an error is a
+/// pointer to one. Their names have a package prefix, which no type a
+/// program declares can have.
+struct ErrorTypes {
+    /// `errors.New`: the message.
+    string: Rc<StructType>,
+    /// `fmt.Errorf` with `%w`: the message and the wrapped error.
+    wrap: Rc<StructType>,
+    /// The strconv parse errors: the function, the input and the cause.
+    num: Rc<StructType>,
+    /// The os file errors: the operation, the path and the cause.
+    path: Rc<StructType>,
+}
+
+thread_local! {
+    static TYPES: ErrorTypes = ErrorTypes {
+        string: struct_type("errors.errorString", &[("s", Type::String)]),
+        wrap: struct_type("fmt.wrapError", &[("msg", Type::String), ("err", Type::error())]),
+        num: struct_type("strconv.NumError", &[("Func", Type::String), ("Num", Type::String), ("Err", Type::error())]),
+        path: struct_type("fs.PathError", &[("Op", Type::String), ("Path", Type::String), ("Err", Type::error())]),
+    };
+}
+
+fn struct_type(name: &str, fields: &[(&str, Type)]) -> Rc<StructType> {
+    let fields = fields.iter().map(|(name, ty)| Field { name: name.to_string(), ty: ty.clone() }).collect();
+    Rc::new(StructType { name: Some(name.to_string()), fields: RefCell::new(fields) })
+}
+
+fn make(ty: fn(&ErrorTypes) -> &Rc<StructType>, fields: Vec<Value>) -> Value {
+    let ty = TYPES.with(|types| Rc::clone(ty(types)));
+    let value = Value::Struct(Box::new(Struct { ty, fields }));
+    error_value(Value::Pointer(Pointer::to_var(new_var(value))))
+}
+
+/// `value` as an `error`.
+pub fn error_value(value: Value) -> Value {
+    convert(value, &Type::error()).unwrap_or(Value::Null)
+}
+
+/// The nil `error`.
+pub fn nil_error() -> Value {
+    Type::error().zero()
+}
+
+/// `errors.New(msg)`.
+pub fn new_error(msg: &str) -> Value {
+    make(|t| &t.string, vec![Value::string(msg)])
+}
+
+/// The error `fmt.Errorf` makes for a `%w` of `err`.
+pub fn wrap_error(msg: &str, err: Value) -> Value {
+    make(|t| &t.wrap, vec![Value::string(msg), err])
+}
+
+/// `strconv.NumError`, as programs name it.
+pub fn num_error_type() -> Type {
+    TYPES.with(|types| Type::Struct(Rc::clone(&types.num)))
+}
+
+/// `fs.PathError`, which programs name as `os.PathError`.
+pub fn path_error_type() -> Type {
+    TYPES.with(|types| Type::Struct(Rc::clone(&types.path)))
+}
+
+/// A `*strconv.NumError`.
+pub fn num_error(func: &str, num: &[u8], err: Value) -> Value {
+    make(|t| &t.num, vec![Value::string(func), Value::String(Rc::from(num)), err])
+}
+
+/// A `*fs.PathError`.
+pub fn path_error(op: &str, path: &str, err: Value) -> Value {
+    make(|t| &t.path, vec![Value::string(op), Value::string(path), err])
+}
+
+/// The struct an error value points to, if `value` is one of the errors
+/// the packages make.
+fn error_struct(value: &Value) -> Option<Struct> {
+    match value {
+        Value::Interface(Interface { value: Some(v), .. }) => error_struct(v),
+        Value::Pointer(Pointer { target: Some(place), .. }) => match place.load() {
+            Value::Struct(s) if is_error_type(&s.ty) => Some(*s),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_error_type(ty: &Rc<StructType>) -> bool {
+    TYPES.with(|t| [&t.string, &t.wrap, &t.num, &t.path].iter().any(|e| Rc::ptr_eq(e, ty)))
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => String::from_utf8_lossy(s).into_owned(),
+        _ => String::new(),
+    }
+}
+
+/// What `err.Error()` returns, if `value` is an error.
+pub fn message(value: &Value) -> Option<String> {
+    let s = error_struct(value)?;
+    let cause = || message(&s.fields[2]).unwrap_or_else(|| "<nil>".to_string());
+    Some(match s.ty.name.as_deref() {
+        Some("strconv.NumError") => {
+            let num = match &s.fields[1] {
+                Value::String(num) => super::strconv::quote(num),
+                _ => String::new(),
+            };
+            format!("strconv.{}: parsing {}: {}", text(&s.fields[0]), num, cause())
+        }
+        Some("fs.PathError") => format!("{} {}: {}", text(&s.fields[0]), text(&s.fields[1]), cause()),
+        _ => text(&s.fields[0]),
+    })
+}
+
+/// The error `value` wraps, as its `Unwrap` method would return.
+pub fn unwrap(value: &Value) -> Option<Value> {
+    let s = error_struct(value)?;
+    let err = match s.ty.name.as_deref() {
+        Some("fmt.wrapError") => s.fields[1].clone(),
+        Some("strconv.NumError") | Some("fs.PathError") => s.fields[2].clone(),
+        _ => return None,
+    };
+    match &err {
+        Value::Interface(Interface { value: None, .. }) | Value::Null => None,
+        _ => Some(err),
+    }
+}
+
+fn error_method(args: Vec<Value>) -> Result<Value, String> {
+    want_args("Error", &args[1..], 0)?;
+    Ok(Value::string(&message(&args[0]).unwrap_or_default()))
+}
+
+fn new(args: Vec<Value>) -> Result<Value, String> {
+    want_args("errors.New", &args, 1)?;
+    let msg = string_arg("errors.New", &args[0])?;
+    Ok(new_error(&String::from_utf8_lossy(msg)))
+}
+
+/// `errors.Is(err, target)`: whether any error in the chain `err` starts
+/// is `target`.
+fn is(args: Vec<Value>) -> Result<Value, String> {
+    want_args("errors.Is", &args, 2)?;
+    let target = &args[1];
+    let mut err = Some(args[0].clone());
+    while let Some(e) = err {
+        if e.equals(target)? {
+            return Ok(Value::Boolean(true));
+        }
+        err = unwrap(&e);
+    }
+    Ok(Value::Boolean(false))
+}
+
+/// `errors.As(err, &target)`: stores the first error in the chain whose
+/// type is that of `target` into it.
+fn as_(args: Vec<Value>) -> Result<Value, String> {
+    want_args("errors.As", &args, 2)?;
+    let target = match &args[1] {
+        Value::Pointer(p) if p.target.is_some() => p,
+        _ => return Err("errors: target must be a non-nil pointer".to_string()),
+    };
+    let mut err = Some(args[0].clone());
+    while let Some(e) = err {
+        let dynamic = match &e {
+            Value::Interface(Interface { value: Some(v), .. }) => v.as_ref().clone(),
+            v => v.clone(),
+        };
+        if target.elem.is_interface() || dynamic.ty().identical(&target.elem) {
+            target.store(dynamic)?;
+            return Ok(Value::Boolean(true));
+        }
+        err = unwrap(&e);
+    }
+    Ok(Value::Boolean(false))
+}
+
+fn unwrap_(args: Vec<Value>) -> Result<Value, String> {
+    want_args("errors.Unwrap", &args, 1)?;
+    Ok(unwrap(&args[0]).unwrap_or_else(nil_error))
+}
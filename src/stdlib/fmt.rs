@@ -0,0 +1,486 @@
+//! Package fmt: printing with Go's verbs, flags, widths and precisions,
+//! and its way of reporting bad format strings in the output.
+
+use super::errors::{error_value, message, new_error, wrap_error};
+use super::strconv::{format_radix, quote, quote_rune};
+use super::{os, string_arg, write_stderr, write_stdout, Member, Package};
+use crate::types::Type;
+use crate::value::builtins::Builtin;
+use crate::value::{format_float, heap, rune_char, Interface, Value};
+use std::rc::Rc;
+
+pub const PACKAGE: Package = Package {
+    path: "fmt",
+    members: &[
+        ("Print", Member::Func(Builtin { name: "fmt.Print", func: print }, &[])),
+        ("Println", Member::Func(Builtin { name: "fmt.Println", func: println }, &[])),
+        ("Printf", Member::Func(Builtin { name: "fmt.Printf", func: printf }, &[])),
+        ("Sprint", Member::Func(Builtin { name: "fmt.Sprint", func: sprint }, &[])),
+        ("Sprintln", Member::Func(Builtin { name: "fmt.Sprintln", func: sprintln }, &[])),
+        ("Sprintf", Member::Func(Builtin { name: "fmt.Sprintf", func: sprintf }, &[])),
+        ("Fprint", Member::Func(Builtin { name: "fmt.Fprint", func: fprint }, &[])),
+        ("Fprintln", Member::Func(Builtin { name: "fmt.Fprintln", func: fprintln }, &[])),
+        ("Fprintf", Member::Func(Builtin { name: "fmt.Fprintf", func: fprintf }, &[])),
+        ("Errorf", Member::Func(Builtin { name: "fmt.Errorf", func: errorf }, &[])),
+    ],
+};
+
+/// The flags, width and precision of one verb.
+#[derive(Default, Clone, Copy)]
+struct Spec {
+    minus: bool,
+    plus: bool,
+    /// `+` on `%v`: struct field names rather than signs.
+    plus_v: bool,
+    zero: bool,
+    space: bool,
+    sharp: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+/// The operands as `Print` writes them: spaces between operands when
+/// neither is a string.
+pub fn sprint_values(args: &[Value]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut prev_string = false;
+    for (i, arg) in args.iter().enumerate() {
+        let is_string = matches!(dynamic(arg), Value::String(_));
+        if i > 0 && !is_string && !prev_string {
+            out.push(b' ');
+        }
+        out.extend(format_verb('v', Spec::default(), arg));
+        prev_string = is_string;
+    }
+    out
+}
+
+/// The operands as `Println` writes them: spaces between all operands,
+/// then a newline.
+pub fn sprintln_values(args: &[Value]) -> Vec<u8> {
+    let mut out = vec![];
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            out.push(b' ');
+        }
+        out.extend(format_verb('v', Spec::default(), arg));
+    }
+    out.push(b'\n');
+    out
+}
+
+/// `format` with its verbs replaced by the formatted operands. Returns the
+/// operand of the first `%w` as well, for `Errorf`.
+pub fn sprintf_values(format: &[u8], args: &[Value], wrap: bool) -> (Vec<u8>, Option<Value>) {
+    let mut out = vec![];
+    let mut wrapped = None;
+    let mut next = 0;
+    let mut i = 0;
+    while i < format.len() {
+        if format[i] != b'%' {
+            let end = format[i..].iter().position(|&b| b == b'%').map_or(format.len(), |n| i + n);
+            out.extend_from_slice(&format[i..end]);
+            i = end;
+            continue;
+        }
+        i += 1;
+        let mut spec = Spec::default();
+        while i < format.len() {
+            match format[i] {
+                b'-' => spec.minus = true,
+                b'+' => spec.plus = true,
+                b'0' => spec.zero = true,
+                b' ' => spec.space = true,
+                b'#' => spec.sharp = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        match number(format, &mut i, args, &mut next) {
+            Ok(width) => spec.width = width,
+            Err(()) => out.extend_from_slice(b"%!(BADWIDTH)"),
+        }
+        if i < format.len() && format[i] == b'.' {
+            i += 1;
+            match number(format, &mut i, args, &mut next) {
+                Ok(precision) => spec.precision = Some(precision.unwrap_or(0)),
+                Err(()) => out.extend_from_slice(b"%!(BADPREC)"),
+            }
+        }
+        if i >= format.len() {
+            out.extend_from_slice(b"%!(NOVERB)");
+            break;
+        }
+        let (verb, len) = crate::value::decode_rune(&format[i..]);
+        i += len;
+        if verb == '%' {
+            out.push(b'%');
+            continue;
+        }
+        let arg = match args.get(next) {
+            Some(arg) => arg,
+            None => {
+                out.extend(format!("%!{}(MISSING)", verb).bytes());
+                continue;
+            }
+        };
+        next += 1;
+        if matches!(verb, 'v' | 'w') {
+            spec.plus_v = spec.plus;
+            spec.plus = false;
+        }
+        if verb == 'w' {
+            let is_error = message(arg).is_some();
+            if wrap && is_error {
+                if wrapped.is_none() {
+                    wrapped = Some(arg.clone());
+                }
+                out.extend(format_verb('v', spec, arg));
+            } else {
+                out.extend(bad_verb('w', arg));
+            }
+            continue;
+        }
+        out.extend(format_verb(verb, spec, arg));
+    }
+    if next < args.len() {
+        let extra: Vec<String> = args[next..].iter().map(|arg| format!("{}={}", type_name(arg), text('v', arg))).collect();
+        out.extend(format!("%!(EXTRA {})", extra.join(", ")).bytes());
+    }
+    (out, wrapped)
+}
+
+/// The largest width or precision, as Go's; a larger one is bad, so one
+/// verb cannot pad its operand out to any size.
+const MAX_NUMBER: usize = 1_000_000;
+
+/// A width or precision: digits, or `*` for the next operand.
+fn number(format: &[u8], i: &mut usize, args: &[Value], next: &mut usize) -> Result<Option<usize>, ()> {
+    if *i < format.len() && format[*i] == b'*' {
+        *i += 1;
+        let arg = args.get(*next);
+        *next += 1;
+        return match arg.map(dynamic) {
+            Some(Value::Integer(n)) if *n >= 0 && *n as usize <= MAX_NUMBER => Ok(Some(*n as usize)),
+            _ => Err(()),
+        };
+    }
+    let start = *i;
+    while *i < format.len() && format[*i].is_ascii_digit() {
+        *i += 1;
+    }
+    if *i == start {
+        return Ok(None);
+    }
+    match std::str::from_utf8(&format[start..*i]).ok().and_then(|s| s.parse().ok()) {
+        Some(n) if n <= MAX_NUMBER => Ok(Some(n)),
+        _ => Err(()),
+    }
+}
+
+/// The value inside an interface.
+fn dynamic(value: &Value) -> &Value {
+    match value {
+        Value::Interface(Interface { value: Some(v), .. }) => dynamic(v),
+        v => v,
+    }
+}
+
+fn type_name(value: &Value) -> String {
+    match dynamic(value) {
+        Value::Null | Value::Interface(_) => "<nil>".to_string(),
+        v => v.ty().qualified(),
+    }
+}
+
+fn text(verb: char, value: &Value) -> String {
+    String::from_utf8_lossy(&format_verb(verb, Spec::default(), value)).into_owned()
+}
+
+/// `%!verb(type=value)`, for a verb the operand's type has no meaning for.
+fn bad_verb(verb: char, value: &Value) -> Vec<u8> {
+    match dynamic(value) {
+        Value::Null | Value::Interface(_) => format!("%!{}(<nil>)", verb).into_bytes(),
+        v => format!("%!{}({}={})", verb, type_name(v), text('v', v)).into_bytes(),
+    }
+}
+
+/// One operand formatted by `verb`, then padded to the width.
+fn format_verb(verb: char, spec: Spec, value: &Value) -> Vec<u8> {
+    if verb == 'T' {
+        return pad(type_name(value).into_bytes(), spec, false);
+    }
+    match format_value(verb, spec, value, 0) {
+        Some((body, numeric)) => pad(body, spec, numeric),
+        None => bad_verb(verb, value),
+    }
+}
+
+/// Pads `body` to the width: on the right for `-`, with zeros after the
+/// sign for `0` on numbers, else with spaces on the left.
+fn pad(mut body: Vec<u8>, spec: Spec, numeric: bool) -> Vec<u8> {
+    let width = match spec.width {
+        Some(width) => width,
+        None => return body,
+    };
+    let len = String::from_utf8_lossy(&body).chars().count();
+    if len >= width {
+        return body;
+    }
+    let fill = width - len;
+    if spec.minus {
+        body.extend(std::iter::repeat_n(b' ', fill));
+        body
+    } else if spec.zero && numeric {
+        let sign = matches!(body.first(), Some(b'-' | b'+')) as usize;
+        let mut out = body[..sign].to_vec();
+        out.extend(std::iter::repeat_n(b'0', fill));
+        out.extend_from_slice(&body[sign..]);
+        out
+    } else {
+        let mut out = vec![b' '; fill];
+        out.extend(body);
+        out
+    }
+}
+
+/// The operand formatted by `verb`, and whether it is a number the `0`
+/// flag pads with zeros; None if the verb does not apply to its type.
+fn format_value(verb: char, spec: Spec, value: &Value, depth: usize) -> Option<(Vec<u8>, bool)> {
+    if let Some(msg) = message(value) {
+        if matches!(verb, 'v' | 's' | 'q' | 'x' | 'X') {
+            return format_value(verb, spec, &Value::string(&msg), depth);
+        }
+    }
+    let text = |s: String| Some((s.into_bytes(), false));
+    let number = |s: String| Some((s.into_bytes(), true));
+    match value {
+        Value::Interface(Interface { value: Some(v), .. }) => format_value(verb, spec, v, depth),
+        Value::Interface(Interface { value: None, .. }) | Value::Null if verb == 'v' => text("<nil>".to_string()),
+        Value::Interface(_) | Value::Null => None,
+        Value::Boolean(b) if matches!(verb, 'v' | 't') => text(b.to_string()),
+        Value::Integer(i) => {
+            let i = *i;
+            let sign = if i < 0 {
+                "-"
+            } else if spec.plus {
+                "+"
+            } else if spec.space {
+                " "
+            } else {
+                ""
+            };
+            let digits = |base: u32, upper: bool| {
+                let mut digits = format_radix(i, base).trim_start_matches('-').to_string();
+                if upper {
+                    digits = digits.to_uppercase();
+                }
+                if let Some(precision) = spec.precision {
+                    if digits.len() < precision {
+                        digits = "0".repeat(precision - digits.len()) + &digits;
+                    }
+                }
+                digits
+            };
+            let prefix = |p: &str| if spec.sharp { p.to_string() } else { String::new() };
+            match verb {
+                'v' | 'd' => number(format!("{}{}", sign, digits(10, false))),
+                'b' => number(format!("{}{}{}", sign, prefix("0b"), digits(2, false))),
+                'o' => number(format!("{}{}{}", sign, prefix("0"), digits(8, false))),
+                'O' => number(format!("{}0o{}", sign, digits(8, false))),
+                'x' => number(format!("{}{}{}", sign, prefix("0x"), digits(16, false))),
+                'X' => number(format!("{}{}{}", sign, prefix("0X"), digits(16, true))),
+                'c' => text(rune_char(i).to_string()),
+                'q' => text(quote_rune(rune_char(i))),
+                'U' => number(format!("U+{:04X}", i)),
+                _ => None,
+            }
+        }
+        Value::Float(f) => {
+            let f = *f;
+            let formatted = match verb {
+                'v' | 'g' => format_float(f, 'g', spec.precision),
+                'G' => format_float(f, 'g', spec.precision).to_uppercase(),
+                'f' | 'F' => format_float(f, 'f', Some(spec.precision.unwrap_or(6))),
+                'e' => format_float(f, 'e', Some(spec.precision.unwrap_or(6))),
+                'E' => format_float(f, 'e', Some(spec.precision.unwrap_or(6))).to_uppercase(),
+                _ => return None,
+            };
+            let formatted = if spec.plus && !formatted.starts_with(['-', '+']) {
+                format!("+{}", formatted)
+            } else if spec.space && !formatted.starts_with(['-', '+']) {
+                format!(" {}", formatted)
+            } else {
+                formatted
+            };
+            // Inf and NaN are not padded with zeros
+            number(formatted).map(|(body, _)| (body, f.is_finite()))
+        }
+        Value::String(s) => format_bytes(verb, spec, s),
+        Value::Slice(s) if s.elem == Type::Byte && matches!(verb, 's' | 'q' | 'x' | 'X') => {
+            let bytes: Vec<u8> = s.values().iter().map(|v| if let Value::Integer(i) = v { *i as u8 } else { 0 }).collect();
+            format_bytes(verb, spec, &bytes)
+        }
+        Value::Slice(s) => {
+            let elems = s.values().iter().map(|v| element(verb, spec, v, depth)).collect::<Option<Vec<_>>>()?;
+            text(format!("[{}]", elems.join(" ")))
+        }
+        Value::Map(m) => {
+            let mut entries = vec![];
+            for (k, v) in m.sorted_entries() {
+                entries.push(format!("{}:{}", element(verb, spec, &k, depth)?, element(verb, spec, &v, depth)?));
+            }
+            text(format!("map[{}]", entries.join(" ")))
+        }
+        Value::Struct(s) => {
+            let names = s.ty.fields.borrow();
+            let mut fields = vec![];
+            for (v, field) in s.fields.iter().zip(names.iter()) {
+                let v = element(verb, spec, v, depth)?;
+                fields.push(if spec.plus_v { format!("{}:{}", field.name, v) } else { v });
+            }
+            text(format!("{{{}}}", fields.join(" ")))
+        }
+        Value::Pointer(p) => match (&p.target, verb) {
+            (Some(place), _) if depth == 0 && matches!(place.load(), Value::Struct(_)) => {
+                let (body, _) = format_value(verb, spec, &place.load(), depth + 1)?;
+                Some(([b"&".as_slice(), &body].concat(), false))
+            }
+            (Some(place), 'v' | 'p') => text(format!("0x{:x}", place.address())),
+            (None, 'v') => text("<nil>".to_string()),
+            (None, 'p') => text("0x0".to_string()),
+            _ => None,
+        },
+        Value::Tuple(values) => {
+            let values = values.iter().map(|v| element(verb, spec, v, depth)).collect::<Option<Vec<_>>>()?;
+            text(values.join(" "))
+        }
+        _ if verb == 'v' => text(value.inspect()),
+        _ => None,
+    }
+}
+
+/// An element of a slice, map or struct, formatted by the same verb; a
+/// bad verb shows in place of the element alone.
+fn element(verb: char, spec: Spec, value: &Value, depth: usize) -> Option<String> {
+    let body = match format_value(verb, Spec { width: None, ..spec }, value, depth + 1) {
+        Some((body, numeric)) => pad(body, spec, numeric),
+        None => bad_verb(verb, value),
+    };
+    Some(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// A string, or the bytes of a `[]byte`, by `verb`; the precision limits
+/// the runes formatted.
+fn format_bytes(verb: char, spec: Spec, s: &[u8]) -> Option<(Vec<u8>, bool)> {
+    let s = match spec.precision {
+        Some(precision) if matches!(verb, 'v' | 's' | 'q') => {
+            let mut end = 0;
+            for _ in 0..precision {
+                if end >= s.len() {
+                    break;
+                }
+                end += crate::value::decode_rune(&s[end..]).1;
+            }
+            &s[..end]
+        }
+        _ => s,
+    };
+    match verb {
+        'v' | 's' => Some((s.to_vec(), false)),
+        'q' => Some((quote(s).into_bytes(), false)),
+        'x' | 'X' => {
+            let hex: Vec<String> = s.iter().map(|b| if verb == 'x' { format!("{:02x}", b) } else { format!("{:02X}", b) }).collect();
+            Some((hex.join(if spec.space { " " } else { "" }).into_bytes(), false))
+        }
+        _ => None,
+    }
+}
+
+/// The arguments after the format string.
+fn format_args<'a>(name: &str, args: &'a [Value]) -> Result<(&'a [u8], &'a [Value]), String> {
+    match args.split_first() {
+        Some((format, rest)) => Ok((string_arg(name, format)?, rest)),
+        None => Err(format!("not enough arguments in call to {}", name)),
+    }
+}
+
+fn print(args: Vec<Value>) -> Result<Value, String> {
+    write_stdout(&sprint_values(&args))?;
+    Ok(Value::Null)
+}
+
+fn println(args: Vec<Value>) -> Result<Value, String> {
+    write_stdout(&sprintln_values(&args))?;
+    Ok(Value::Null)
+}
+
+fn printf(args: Vec<Value>) -> Result<Value, String> {
+    let (format, rest) = format_args("fmt.Printf", &args)?;
+    write_stdout(&sprintf_values(format, rest, false).0)?;
+    Ok(Value::Null)
+}
+
+/// The string a Sprint function returns, charged to the heap.
+fn string(bytes: Vec<u8>) -> Result<Value, String> {
+    heap::charge(bytes.len())?;
+    Ok(Value::String(Rc::from(bytes)))
+}
+
+fn sprint(args: Vec<Value>) -> Result<Value, String> {
+    string(sprint_values(&args))
+}
+
+fn sprintln(args: Vec<Value>) -> Result<Value, String> {
+    string(sprintln_values(&args))
+}
+
+fn sprintf(args: Vec<Value>) -> Result<Value, String> {
+    let (format, rest) = format_args("fmt.Sprintf", &args)?;
+    string(sprintf_values(format, rest, false).0)
+}
+
+/// Writes to the `*os.File` the first argument is.
+fn write_file(name: &str, args: &[Value], bytes: impl FnOnce(&[Value]) -> Result<Vec<u8>, String>) -> Result<Value, String> {
+    let (file, rest) = match args.split_first() {
+        Some(split) => split,
+        None => return Err(format!("not enough arguments in call to {}", name)),
+    };
+    let bytes = bytes(rest)?;
+    match os::descriptor(file) {
+        Some(1) => write_stdout(&bytes)?,
+        Some(_) => write_stderr(&bytes)?,
+        None => {
+            return Err(format!("cannot use {} (type {}) as io.Writer value in argument to {}", file.inspect(), file.ty(), name))
+        }
+    }
+    Ok(Value::Null)
+}
+
+fn fprint(args: Vec<Value>) -> Result<Value, String> {
+    write_file("fmt.Fprint", &args, |rest| Ok(sprint_values(rest)))
+}
+
+fn fprintln(args: Vec<Value>) -> Result<Value, String> {
+    write_file("fmt.Fprintln", &args, |rest| Ok(sprintln_values(rest)))
+}
+
+fn fprintf(args: Vec<Value>) -> Result<Value, String> {
+    write_file("fmt.Fprintf", &args, |rest| {
+        let (format, rest) = format_args("fmt.Fprintf", rest)?;
+        Ok(sprintf_values(format, rest, false).0)
+    })
+}
+
+/// `fmt.Errorf(format, args...)`: an error with the formatted message,
+/// wrapping the operand of a `%w`.
+fn errorf(args: Vec<Value>) -> Result<Value, String> {
+    let (format, rest) = format_args("fmt.Errorf", &args)?;
+    let (msg, wrapped) = sprintf_values(format, rest, true);
+    heap::charge(msg.len())?;
+    let msg = String::from_utf8_lossy(&msg);
+    Ok(match wrapped {
+        Some(err) => wrap_error(&msg, error_value(err)),
+        None => new_error(&msg),
+    })
+}
@@ -0,0 +1,133 @@
+//! Package math.
+
+use super::{float_arg, int_arg, want_args, Member, Package};
+use crate::value::builtins::Builtin;
+use crate::value::Value;
+
+/// A function of one float64 argument.
+macro_rules! unary {
+    ($name:literal, $f:expr) => {
+        Member::Func(
+            Builtin {
+                name: concat!("math.", $name),
+                func: |args| {
+                    want_args(concat!("math.", $name), &args, 1)?;
+                    let x = float_arg(concat!("math.", $name), &args[0])?;
+                    let f: fn(f64) -> f64 = $f;
+                    Ok(Value::Float(f(x)))
+                },
+            },
+            &[],
+        )
+    };
+}
+
+/// A function of two float64 arguments.
+macro_rules! binary {
+    ($name:literal, $f:expr) => {
+        Member::Func(
+            Builtin {
+                name: concat!("math.", $name),
+                func: |args| {
+                    want_args(concat!("math.", $name), &args, 2)?;
+                    let x = float_arg(concat!("math.", $name), &args[0])?;
+                    let y = float_arg(concat!("math.", $name), &args[1])?;
+                    let f: fn(f64, f64) -> f64 = $f;
+                    Ok(Value::Float(f(x, y)))
+                },
+            },
+            &[],
+        )
+    };
+}
+
+pub const PACKAGE: Package = Package {
+    path: "math",
+    members: &[
+        ("Abs", unary!("Abs", f64::abs)),
+        ("Ceil", unary!("Ceil", f64::ceil)),
+        ("Floor", unary!("Floor", f64::floor)),
+        ("Trunc", unary!("Trunc", f64::trunc)),
+        ("Round", unary!("Round", f64::round)),
+        ("Sqrt", unary!("Sqrt", f64::sqrt)),
+        ("Cbrt", unary!("Cbrt", f64::cbrt)),
+        ("Exp", unary!("Exp", f64::exp)),
+        ("Log", unary!("Log", f64::ln)),
+        ("Log2", unary!("Log2", f64::log2)),
+        ("Log10", unary!("Log10", f64::log10)),
+        ("Sin", unary!("Sin", f64::sin)),
+        ("Cos", unary!("Cos", f64::cos)),
+        ("Tan", unary!("Tan", f64::tan)),
+        ("Atan", unary!("Atan", f64::atan)),
+        ("Pow", binary!("Pow", f64::powf)),
+        ("Atan2", binary!("Atan2", f64::atan2)),
+        ("Hypot", binary!("Hypot", f64::hypot)),
+        ("Mod", binary!("Mod", |x, y| x % y)),
+        ("Max", binary!("Max", max)),
+        ("Min", binary!("Min", min)),
+        ("Inf", Member::Func(Builtin { name: "math.Inf", func: inf }, &[])),
+        ("IsInf", Member::Func(Builtin { name: "math.IsInf", func: is_inf }, &[])),
+        ("IsNaN", Member::Func(Builtin { name: "math.IsNaN", func: is_nan }, &[])),
+        ("NaN", Member::Func(Builtin { name: "math.NaN", func: nan }, &[])),
+        ("Pi", Member::Value(|| Value::Float(std::f64::consts::PI))),
+        ("E", Member::Value(|| Value::Float(std::f64::consts::E))),
+        ("Sqrt2", Member::Value(|| Value::Float(std::f64::consts::SQRT_2))),
+        ("Ln2", Member::Value(|| Value::Float(std::f64::consts::LN_2))),
+        ("MaxInt", Member::Value(|| Value::Integer(i64::MAX))),
+        ("MinInt", Member::Value(|| Value::Integer(i64::MIN))),
+        ("MaxInt64", Member::Value(|| Value::Integer(i64::MAX))),
+        ("MinInt64", Member::Value(|| Value::Integer(i64::MIN))),
+        ("MaxInt32", Member::Value(|| Value::Integer(i32::MAX as i64))),
+        ("MinInt32", Member::Value(|| Value::Integer(i32::MIN as i64))),
+        ("MaxFloat64", Member::Value(|| Value::Float(f64::MAX))),
+        ("SmallestNonzeroFloat64", Member::Value(|| Value::Float(f64::from_bits(1)))),
+    ],
+};
+
+/// `math.Max`: NaN if either is, and +0 over -0.
+fn max(x: f64, y: f64) -> f64 {
+    if x.is_nan() || y.is_nan() {
+        return f64::NAN;
+    }
+    if x == 0.0 && y == 0.0 {
+        return if x.is_sign_negative() { y } else { x };
+    }
+    x.max(y)
+}
+
+/// `math.Min`: NaN if either is, and -0 over +0.
+fn min(x: f64, y: f64) -> f64 {
+    if x.is_nan() || y.is_nan() {
+        return f64::NAN;
+    }
+    if x == 0.0 && y == 0.0 {
+        return if x.is_sign_negative() { x } else { y };
+    }
+    x.min(y)
+}
+
+/// `math.Inf(sign)`: +Inf for a sign of 0 or more, else -Inf.
+fn inf(args: Vec<Value>) -> Result<Value, String> {
+    want_args("math.Inf", &args, 1)?;
+    let sign = int_arg("math.Inf", &args[0])?;
+    Ok(Value::Float(if sign >= 0 { f64::INFINITY } else { f64::NEG_INFINITY }))
+}
+
+/// `math.IsInf(f, sign)`: whether `f` is an infinity of the sign, or of
+/// either sign for 0.
+fn is_inf(args: Vec<Value>) -> Result<Value, String> {
+    want_args("math.IsInf", &args, 2)?;
+    let f = float_arg("math.IsInf", &args[0])?;
+    let sign = int_arg("math.IsInf", &args[1])?;
+    Ok(Value::Boolean(sign >= 0 && f == f64::INFINITY || sign <= 0 && f == f64::NEG_INFINITY))
+}
+
+fn is_nan(args: Vec<Value>) -> Result<Value, String> {
+    want_args("math.IsNaN", &args, 1)?;
+    Ok(Value::Boolean(float_arg("math.IsNaN", &args[0])?.is_nan()))
+}
+
+fn nan(args: Vec<Value>) -> Result<Value, String> {
+    want_args("math.NaN", &args, 0)?;
+    Ok(Value::Float(f64::NAN))
+}
@@ -0,0 +1,170 @@
+use crate::types::Type;
+use crate::value::builtins::Builtin;
+use crate::value::{Interface, Method, Value};
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+pub mod errors;
+pub mod fmt;
+mod math;
+mod os;
+mod runtime;
+mod sort;
+pub mod strconv;
+mod strings;
+
+#[cfg(test)]
+mod test;
+
+/// A package of the standard library a program can import. Its members
+/// are implemented in Rust, or in source compiled where they are used.
+pub struct Package {
+    pub path: &'static str,
+    pub members: &'static [(&'static str, Member)],
+}
+
+pub enum Member {
+    /// A function, with the types of its results when it has more than
+    /// one.
+    Func(Builtin, &'static [&'static str]),
+    /// A constant, or a variable programs do not assign to.
+    Value(fn() -> Value),
+    /// A variable the VM holds, such as `os.Args`, read by calling the
+    /// builtin without arguments.
+    Var(Builtin),
+    /// A function literal, for functions that call back into the program.
+    Source(&'static str),
+    /// A named type, such as `runtime.MemStats`.
+    Type(fn() -> Type),
+}
+
+/// Packages by import path.
+pub const PACKAGES: &[&Package] = &[
+    &errors::PACKAGE,
+    &fmt::PACKAGE,
+    &math::PACKAGE,
+    &os::PACKAGE,
+    &runtime::PACKAGE,
+    &sort::PACKAGE,
+    &strconv::PACKAGE,
+    &strings::PACKAGE,
+];
+
+/// Packages that reach outside the program: to its files, the process's
+/// arguments and its exit. Sandboxed scripts may not import them.
+pub const SYSTEM_PACKAGES: &[&str] = &["os"];
+
+/// Index of the package imported as `path`.
+pub fn lookup(path: &str) -> Option<usize> {
+    PACKAGES.iter().position(|p| p.path == path)
+}
+
+impl Package {
+    /// The name a package is known by unless the import renames it: the
+    /// last element of its path.
+    pub fn name(&self) -> &'static str {
+        self.path.rsplit('/').next().unwrap_or(self.path)
+    }
+
+    pub fn member(&self, name: &str) -> Option<&'static Member> {
+        self.members.iter().find(|(n, _)| *n == name).map(|(_, m)| m)
+    }
+}
+
+/// The method `name` of a value of a type the packages define, bound to
+/// the value; `err.Error` is the one there is.
+pub fn method(recv: &Value, name: &str) -> Option<Value> {
+    if name != "Error" || errors::message(recv).is_none() {
+        return None;
+    }
+    let recv = match recv {
+        Value::Interface(Interface { value: Some(v), .. }) => v.as_ref().clone(),
+        v => v.clone(),
+    };
+    Some(Value::Method(Rc::new(Method { recv, func: errors::ERROR_METHOD })))
+}
+
+thread_local! {
+    /// What the program has written while `capture` runs, which it
+    /// writes instead of standard output and error.
+    static CAPTURED: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, collecting what programs write to standard output and error
+/// meanwhile, in the order they write it, rather than writing it out.
+pub fn capture<R>(f: impl FnOnce() -> R) -> (R, Vec<u8>) {
+    let outer = CAPTURED.with(|c| c.replace(Some(vec![])));
+    let result = f();
+    let output = CAPTURED.with(|c| c.replace(outer)).unwrap_or_default();
+    (result, output)
+}
+
+/// Appends to the captured output; false when nothing is being captured.
+fn write_captured(bytes: &[u8]) -> bool {
+    CAPTURED.with(|c| match c.borrow_mut().as_mut() {
+        Some(out) => {
+            out.extend_from_slice(bytes);
+            true
+        }
+        None => false,
+    })
+}
+
+/// Writes to standard output as the program runs.
+pub fn write_stdout(bytes: &[u8]) -> Result<(), String> {
+    if write_captured(bytes) {
+        return Ok(());
+    }
+    let mut stdout = io::stdout();
+    stdout.write_all(bytes).and_then(|_| stdout.flush()).map_err(|e| e.to_string())
+}
+
+/// Writes to standard error as the program runs.
+pub fn write_stderr(bytes: &[u8]) -> Result<(), String> {
+    if write_captured(bytes) {
+        return Ok(());
+    }
+    io::stderr().write_all(bytes).map_err(|e| e.to_string())
+}
+
+/// A float argument; integers are taken as untyped constants.
+fn float_arg(name: &str, arg: &Value) -> Result<f64, String> {
+    match arg {
+        Value::Float(f) => Ok(*f),
+        Value::Integer(i) => Ok(*i as f64),
+        Value::Interface(Interface { value: Some(v), .. }) => float_arg(name, v),
+        _ => Err(format!("cannot use {} (type {}) as float64 value in argument to {}", arg.inspect(), arg.ty(), name)),
+    }
+}
+
+fn int_arg(name: &str, arg: &Value) -> Result<i64, String> {
+    match arg {
+        Value::Integer(i) => Ok(*i),
+        Value::Interface(Interface { value: Some(v), .. }) => int_arg(name, v),
+        _ => Err(format!("cannot use {} (type {}) as int value in argument to {}", arg.inspect(), arg.ty(), name)),
+    }
+}
+
+fn string_arg<'a>(name: &str, arg: &'a Value) -> Result<&'a [u8], String> {
+    match arg {
+        Value::String(s) => Ok(s),
+        Value::Interface(Interface { value: Some(v), .. }) => string_arg(name, v),
+        _ => Err(format!("cannot use {} (type {}) as string value in argument to {}", arg.inspect(), arg.ty(), name)),
+    }
+}
+
+fn want_args(name: &str, args: &[Value], n: usize) -> Result<(), String> {
+    if args.len() < n {
+        return Err(format!("not enough arguments in call to {}", name));
+    }
+    if args.len() > n {
+        return Err(format!("too many arguments in call to {}", name));
+    }
+    Ok(())
+}
+
+/// The results of a function with more than one.
+fn tuple(values: Vec<Value>) -> Value {
+    Value::Tuple(Rc::from(values))
+}
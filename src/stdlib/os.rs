@@ -0,0 +1,86 @@
+//! Package os. `os.Exit` and `os.Args` reach into the VM, which handles
+//! calls to them by name.
+
+use super::errors::{new_error, nil_error, path_error, path_error_type};
+use super::{int_arg, string_arg, tuple, want_args, Member, Package};
+use crate::types::{Field, StructType, Type};
+use crate::value::builtins::Builtin;
+use crate::value::{new_var, Pointer, Slice, Struct, Value};
+use std::cell::RefCell;
+use std::io::ErrorKind;
+use std::rc::Rc;
+
+pub const PACKAGE: Package = Package {
+    path: "os",
+    members: &[
+        ("Args", Member::Var(Builtin { name: "os.Args", func: args })),
+        ("Exit", Member::Func(Builtin { name: "os.Exit", func: exit }, &[])),
+        ("ReadFile", Member::Func(Builtin { name: "os.ReadFile", func: read_file }, &["[]byte", "error"])),
+        ("Stdout", Member::Value(|| file(1))),
+        ("Stderr", Member::Value(|| file(2))),
+        ("PathError", Member::Type(path_error_type)),
+    ],
+};
+
+thread_local! {
+    static FILE: Rc<StructType> = Rc::new(StructType {
+        name: Some("os.File".to_string()),
+        fields: RefCell::new(vec![Field { name: "fd".to_string(), ty: Type::Int }]),
+    });
+}
+
+/// An `*os.File` for a standard stream.
+fn file(fd: i64) -> Value {
+    let ty = FILE.with(Rc::clone);
+    Value::Pointer(Pointer::to_var(new_var(Value::Struct(Box::new(Struct { ty, fields: vec![Value::Integer(fd)] })))))
+}
+
+/// The descriptor of `value` if it is an `*os.File`.
+pub fn descriptor(value: &Value) -> Option<i64> {
+    let place = match value {
+        Value::Pointer(Pointer { target: Some(place), .. }) => place,
+        _ => return None,
+    };
+    match place.load() {
+        Value::Struct(s) if FILE.with(|f| Rc::ptr_eq(f, &s.ty)) => match s.fields[0] {
+            Value::Integer(fd) => Some(fd),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `os.Args` outside the VM: no arguments.
+fn args(args: Vec<Value>) -> Result<Value, String> {
+    want_args("os.Args", &args, 0)?;
+    Ok(Value::Slice(Slice::nil(Type::String)))
+}
+
+/// `os.Exit(code)`: checks the code, which the VM then exits with.
+fn exit(args: Vec<Value>) -> Result<Value, String> {
+    want_args("os.Exit", &args, 1)?;
+    Ok(Value::Integer(int_arg("os.Exit", &args[0])?))
+}
+
+/// `os.ReadFile(name)`: the contents of the file, and a `*fs.PathError`
+/// if it cannot be read.
+fn read_file(args: Vec<Value>) -> Result<Value, String> {
+    want_args("os.ReadFile", &args, 1)?;
+    let name = String::from_utf8_lossy(string_arg("os.ReadFile", &args[0])?).into_owned();
+    Ok(match std::fs::read(&name) {
+        Ok(bytes) => {
+            let values = bytes.into_iter().map(|b| Value::Integer(b as i64)).collect();
+            tuple(vec![Value::Slice(Slice::from_values(Type::Byte, values)), nil_error()])
+        }
+        Err(e) => {
+            let cause = match e.kind() {
+                ErrorKind::NotFound => "no such file or directory".to_string(),
+                ErrorKind::PermissionDenied => "permission denied".to_string(),
+                _ if e.raw_os_error() == Some(21) => "is a directory".to_string(),
+                _ => e.to_string(),
+            };
+            let op = if e.raw_os_error() == Some(21) { "read" } else { "open" };
+            tuple(vec![Value::Slice(Slice::nil(Type::Byte)), path_error(op, &name, new_error(&cause))])
+        }
+    })
+}
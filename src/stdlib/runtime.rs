@@ -0,0 +1,61 @@
+//! Package runtime: the collector, and what the heap holds. `runtime.GC`
+//! needs the VM's roots, so the VM handles calls to it by name.
+
+use super::{want_args, Member, Package};
+use crate::types::{Field, StructType, Type};
+use crate::value::builtins::Builtin;
+use crate::value::heap::{self, Stats};
+use crate::value::{Pointer, Struct, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub const PACKAGE: Package = Package {
+    path: "runtime",
+    members: &[
+        ("GC", Member::Func(Builtin { name: "runtime.GC", func: gc }, &[])),
+        ("MemStats", Member::Type(mem_stats_type)),
+        ("ReadMemStats", Member::Func(Builtin { name: "runtime.ReadMemStats", func: read_mem_stats }, &[])),
+    ],
+};
+
+/// The fields of `runtime.MemStats`, of those Go's has.
+const FIELDS: &[&str] = &["Alloc", "TotalAlloc", "Mallocs", "Frees", "HeapAlloc", "HeapObjects", "NumGC", "PauseTotalNs"];
+
+thread_local! {
+    static MEM_STATS: Rc<StructType> = Rc::new(StructType {
+        name: Some("runtime.MemStats".to_string()),
+        fields: RefCell::new(FIELDS.iter().map(|name| Field { name: name.to_string(), ty: Type::Int }).collect()),
+    });
+}
+
+fn mem_stats_type() -> Type {
+    Type::Struct(MEM_STATS.with(Rc::clone))
+}
+
+/// `runtime.GC()` outside the VM: there are no roots to collect from.
+fn gc(args: Vec<Value>) -> Result<Value, String> {
+    want_args("runtime.GC", &args, 0)?;
+    Ok(Value::Null)
+}
+
+/// `runtime.ReadMemStats(&m)`: fills `m` in with the statistics of the
+/// running program's heap. Objects are those the collector tracks, so
+/// HeapObjects is Mallocs less Frees.
+fn read_mem_stats(args: Vec<Value>) -> Result<Value, String> {
+    want_args("runtime.ReadMemStats", &args, 1)?;
+    let ptr = match &args[0] {
+        Value::Pointer(p @ Pointer { elem: Type::Struct(st), .. }) if MEM_STATS.with(|t| Rc::ptr_eq(t, st)) => p,
+        arg => {
+            return Err(format!(
+                "cannot use {} (type {}) as *runtime.MemStats value in argument to runtime.ReadMemStats",
+                arg.inspect(),
+                arg.ty()
+            ))
+        }
+    };
+    let Stats { heap_alloc, total_alloc, mallocs, frees, num_gc, pause_total } = heap::stats();
+    let values = [heap_alloc, total_alloc, mallocs, frees, heap_alloc, mallocs - frees, num_gc, pause_total.as_nanos() as u64];
+    let fields = values.iter().map(|&n| Value::Integer(n.min(i64::MAX as u64) as i64)).collect();
+    ptr.store(Value::Struct(Box::new(Struct { ty: MEM_STATS.with(Rc::clone), fields })))?;
+    Ok(Value::Null)
+}
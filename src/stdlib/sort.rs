@@ -0,0 +1,111 @@
+//! Package sort. The functions that call back into the program are
+//! written in source, so that the callbacks run on the VM like any other
+//! call; `Slice` is a heapsort, `SliceStable` an insertion sort.
+
+use super::{want_args, Member, Package};
+use crate::types::Type;
+use crate::value::builtins::Builtin;
+use crate::value::Value;
+use std::cmp::Ordering;
+
+pub const PACKAGE: Package = Package {
+    path: "sort",
+    members: &[
+        ("Slice", Member::Source(SLICE)),
+        ("SliceStable", Member::Source(SLICE_STABLE)),
+        ("Search", Member::Source(SEARCH)),
+        ("Ints", Member::Func(Builtin { name: "sort.Ints", func: ints }, &[])),
+        ("Strings", Member::Func(Builtin { name: "sort.Strings", func: strings }, &[])),
+        ("Float64s", Member::Func(Builtin { name: "sort.Float64s", func: float64s }, &[])),
+    ],
+};
+
+const SLICE: &str = "fn(x, less) {
+    let down = fn(root, hi) {
+        for {
+            child := 2*root + 1
+            if child >= hi {
+                break
+            }
+            if child+1 < hi && less(child, child+1) {
+                child++
+            }
+            if !less(root, child) {
+                break
+            }
+            x[root], x[child] = x[child], x[root]
+            root = child
+        }
+    }
+    n := len(x)
+    for i := (n - 1) / 2; i >= 0; i-- {
+        down(i, n)
+    }
+    for i := n - 1; i > 0; i-- {
+        x[0], x[i] = x[i], x[0]
+        down(0, i)
+    }
+}";
+
+const SLICE_STABLE: &str = "fn(x, less) {
+    for i := 1; i < len(x); i++ {
+        for j := i; j > 0 && less(j, j-1); j-- {
+            x[j], x[j-1] = x[j-1], x[j]
+        }
+    }
+}";
+
+const SEARCH: &str = "fn(n, f) {
+    i, j := 0, n
+    for i < j {
+        h := (i + j) / 2
+        if !f(h) {
+            i = h + 1
+        } else {
+            j = h
+        }
+    }
+    i
+}";
+
+/// Sorts the elements of a slice of `elem` in place by `cmp`.
+fn sort_slice(name: &str, args: Vec<Value>, elem: Type, cmp: fn(&Value, &Value) -> Ordering) -> Result<Value, String> {
+    want_args(name, &args, 1)?;
+    let s = match &args[0] {
+        Value::Slice(s) if s.elem == elem => s,
+        arg => return Err(format!("cannot use {} (type {}) as []{} value in argument to {}", arg.inspect(), arg.ty(), elem, name)),
+    };
+    let mut values = s.values();
+    values.sort_by(cmp);
+    for (i, v) in values.into_iter().enumerate() {
+        s.set(i, v)?;
+    }
+    Ok(Value::Null)
+}
+
+fn ints(args: Vec<Value>) -> Result<Value, String> {
+    sort_slice("sort.Ints", args, Type::Int, |a, b| match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    })
+}
+
+fn strings(args: Vec<Value>) -> Result<Value, String> {
+    sort_slice("sort.Strings", args, Type::String, |a, b| match (a, b) {
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    })
+}
+
+/// `sort.Float64s`: NaNs sort before everything else.
+fn float64s(args: Vec<Value>) -> Result<Value, String> {
+    sort_slice("sort.Float64s", args, Type::Float64, |a, b| match (a, b) {
+        (Value::Float(a), Value::Float(b)) => match (a.is_nan(), b.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        },
+        _ => Ordering::Equal,
+    })
+}
@@ -0,0 +1,286 @@
+//! Package strconv.
+
+use super::errors::{error_value, new_error, nil_error, num_error, num_error_type};
+use super::{float_arg, int_arg, string_arg, tuple, want_args, Member, Package};
+use crate::value::builtins::Builtin;
+use crate::value::{decode_rune, format_float, heap, Value};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+
+pub const PACKAGE: Package = Package {
+    path: "strconv",
+    members: &[
+        ("Itoa", Member::Func(Builtin { name: "strconv.Itoa", func: itoa }, &[])),
+        ("Atoi", Member::Func(Builtin { name: "strconv.Atoi", func: atoi }, &["int", "error"])),
+        ("ParseInt", Member::Func(Builtin { name: "strconv.ParseInt", func: parse_int }, &["int64", "error"])),
+        ("ParseFloat", Member::Func(Builtin { name: "strconv.ParseFloat", func: parse_float }, &["float64", "error"])),
+        ("ParseBool", Member::Func(Builtin { name: "strconv.ParseBool", func: parse_bool }, &["bool", "error"])),
+        ("FormatInt", Member::Func(Builtin { name: "strconv.FormatInt", func: format_int }, &[])),
+        ("FormatFloat", Member::Func(Builtin { name: "strconv.FormatFloat", func: format_float_ }, &[])),
+        ("FormatBool", Member::Func(Builtin { name: "strconv.FormatBool", func: format_bool }, &[])),
+        ("Quote", Member::Func(Builtin { name: "strconv.Quote", func: quote_ }, &[])),
+        ("ErrSyntax", Member::Value(err_syntax)),
+        ("ErrRange", Member::Value(err_range)),
+        ("NumError", Member::Type(num_error_type)),
+    ],
+};
+
+thread_local! {
+    // the same values every time, for errors.Is
+    static ERR_SYNTAX: RefCell<Option<Value>> = const { RefCell::new(None) };
+    static ERR_RANGE: RefCell<Option<Value>> = const { RefCell::new(None) };
+}
+
+fn err_syntax() -> Value {
+    ERR_SYNTAX.with(|e| e.borrow_mut().get_or_insert_with(|| new_error("invalid syntax")).clone())
+}
+
+fn err_range() -> Value {
+    ERR_RANGE.with(|e| e.borrow_mut().get_or_insert_with(|| new_error("value out of range")).clone())
+}
+
+/// `s` as a double-quoted Go string literal, escaping what is not
+/// printable and bytes that are not UTF-8.
+pub fn quote(s: &[u8]) -> String {
+    let mut out = String::from("\"");
+    let mut i = 0;
+    while i < s.len() {
+        let (c, len) = decode_rune(&s[i..]);
+        if c == '\u{FFFD}' && len == 1 && !s[i..].starts_with("\u{FFFD}".as_bytes()) {
+            out.push_str(&format!("\\x{:02x}", s[i]));
+        } else {
+            quote_char(&mut out, c, '"');
+        }
+        i += len;
+    }
+    out.push('"');
+    out
+}
+
+/// `c` as a single-quoted Go rune literal.
+pub fn quote_rune(c: char) -> String {
+    let mut out = String::from("'");
+    quote_char(&mut out, c, '\'');
+    out.push('\'');
+    out
+}
+
+fn quote_char(out: &mut String, c: char, quote: char) {
+    match c {
+        '\x07' => out.push_str("\\a"),
+        '\x08' => out.push_str("\\b"),
+        '\x0c' => out.push_str("\\f"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        '\x0b' => out.push_str("\\v"),
+        '\\' => out.push_str("\\\\"),
+        c if c == quote => {
+            out.push('\\');
+            out.push(c);
+        }
+        c if (c as u32) < 0x20 || c == '\x7f' => out.push_str(&format!("\\x{:02x}", c as u32)),
+        c if c.is_control() || (c as u32) >= 0xFFFE && (c as u32) <= 0xFFFF => {
+            if (c as u32) <= 0xFFFF {
+                out.push_str(&format!("\\u{:04x}", c as u32));
+            } else {
+                out.push_str(&format!("\\U{:08x}", c as u32));
+            }
+        }
+        c => out.push(c),
+    }
+}
+
+/// Parses an integer in `base`, or in the base its prefix gives when
+/// `base` is 0, as Go's ParseInt does.
+pub fn parse_integer(s: &[u8], base: u32) -> Result<i64, Value> {
+    let text = String::from_utf8_lossy(s);
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(&text)),
+    };
+    let (base, digits) = match base {
+        0 => {
+            let lower = digits.to_ascii_lowercase();
+            if lower.starts_with("0x") {
+                (16, digits[2..].replace('_', ""))
+            } else if lower.starts_with("0b") {
+                (2, digits[2..].replace('_', ""))
+            } else if lower.starts_with("0o") {
+                (8, digits[2..].replace('_', ""))
+            } else if digits.len() > 1 && digits.starts_with('0') {
+                (8, digits[1..].replace('_', ""))
+            } else {
+                (10, digits.replace('_', ""))
+            }
+        }
+        base => (base, digits.to_string()),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(base)) {
+        return Err(err_syntax());
+    }
+    let magnitude = match u128::from_str_radix(&digits, base) {
+        Ok(n) => n,
+        Err(_) => return Err(err_range()),
+    };
+    if negative {
+        if magnitude > 1 << 63 {
+            return Err(err_range());
+        }
+        Ok((magnitude as i128).wrapping_neg() as i64)
+    } else if magnitude > i64::MAX as u128 {
+        Err(err_range())
+    } else {
+        Ok(magnitude as i64)
+    }
+}
+
+/// A new string of `s`, charged to the heap.
+fn string(s: &str) -> Result<Value, String> {
+    heap::charge(s.len())?;
+    Ok(Value::string(s))
+}
+
+fn itoa(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strconv.Itoa", &args, 1)?;
+    string(&int_arg("strconv.Itoa", &args[0])?.to_string())
+}
+
+/// `strconv.Atoi(s)`: the int `s` holds in base 10, and a
+/// `*strconv.NumError` if it holds none.
+fn atoi(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strconv.Atoi", &args, 1)?;
+    let s = string_arg("strconv.Atoi", &args[0])?;
+    Ok(match parse_integer(s, 10) {
+        Ok(n) => tuple(vec![Value::Integer(n), nil_error()]),
+        Err(cause) => {
+            let n = if is_range(&cause) { saturated(s) } else { 0 };
+            tuple(vec![Value::Integer(n), num_error("Atoi", s, cause)])
+        }
+    })
+}
+
+fn parse_int(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strconv.ParseInt", &args, 3)?;
+    let s = string_arg("strconv.ParseInt", &args[0])?;
+    let base = int_arg("strconv.ParseInt", &args[1])?;
+    let bits = int_arg("strconv.ParseInt", &args[2])?;
+    if base != 0 && !(2..=36).contains(&base) {
+        let err = error_value(new_error(&format!("invalid base {}", base)));
+        return Ok(tuple(vec![Value::Integer(0), num_error("ParseInt", s, err)]));
+    }
+    let bits = if bits == 0 { 64 } else { bits.clamp(1, 64) };
+    let result = parse_integer(s, base as u32).and_then(|n| {
+        let (min, max) = (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1);
+        if (n as i128) < min || (n as i128) > max {
+            Err(err_range())
+        } else {
+            Ok(n)
+        }
+    });
+    Ok(match result {
+        Ok(n) => tuple(vec![Value::Integer(n), nil_error()]),
+        Err(cause) => tuple(vec![Value::Integer(0), num_error("ParseInt", s, cause)]),
+    })
+}
+
+fn is_range(err: &Value) -> bool {
+    err.equals(&err_range()) == Ok(true)
+}
+
+/// The int nearest to the out of range value `s` holds.
+fn saturated(s: &[u8]) -> i64 {
+    if s.first() == Some(&b'-') {
+        i64::MIN
+    } else {
+        i64::MAX
+    }
+}
+
+/// `strconv.ParseFloat(s, bitSize)`. A value too large is ±Inf with a
+/// range error.
+fn parse_float(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strconv.ParseFloat", &args, 2)?;
+    let s = string_arg("strconv.ParseFloat", &args[0])?;
+    int_arg("strconv.ParseFloat", &args[1])?;
+    let text = String::from_utf8_lossy(s).replace('_', "");
+    let lower = text.trim_start_matches(['+', '-']).to_ascii_lowercase();
+    let named = ["inf", "infinity", "nan"].contains(&lower.as_str());
+    let digits = lower.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | '+' | '-'));
+    Ok(match text.parse::<f64>() {
+        Ok(f) if named || digits => {
+            if f.is_infinite() && !named {
+                tuple(vec![Value::Float(f), num_error("ParseFloat", s, err_range())])
+            } else {
+                tuple(vec![Value::Float(f), nil_error()])
+            }
+        }
+        _ => tuple(vec![Value::Float(0.0), num_error("ParseFloat", s, err_syntax())]),
+    })
+}
+
+fn parse_bool(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strconv.ParseBool", &args, 1)?;
+    let s = string_arg("strconv.ParseBool", &args[0])?;
+    Ok(match s {
+        b"1" | b"t" | b"T" | b"TRUE" | b"true" | b"True" => tuple(vec![Value::Boolean(true), nil_error()]),
+        b"0" | b"f" | b"F" | b"FALSE" | b"false" | b"False" => tuple(vec![Value::Boolean(false), nil_error()]),
+        _ => tuple(vec![Value::Boolean(false), num_error("ParseBool", s, err_syntax())]),
+    })
+}
+
+fn format_int(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strconv.FormatInt", &args, 2)?;
+    let i = int_arg("strconv.FormatInt", &args[0])?;
+    let base = int_arg("strconv.FormatInt", &args[1])?;
+    if !(2..=36).contains(&base) {
+        return Err("strconv: illegal AppendInt/FormatInt base".to_string());
+    }
+    string(&format_radix(i, base as u32))
+}
+
+/// `i` in `base`, with lowercase letters for digits above 9.
+pub fn format_radix(i: i64, base: u32) -> String {
+    let mut n = i.unsigned_abs();
+    let mut digits = vec![];
+    loop {
+        digits.push(std::char::from_digit((n % base as u64) as u32, base).unwrap());
+        n /= base as u64;
+        if n == 0 {
+            break;
+        }
+    }
+    if i < 0 {
+        digits.push('-');
+    }
+    digits.iter().rev().collect()
+}
+
+fn format_float_(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strconv.FormatFloat", &args, 4)?;
+    let f = float_arg("strconv.FormatFloat", &args[0])?;
+    let fmt = int_arg("strconv.FormatFloat", &args[1])?;
+    let prec = int_arg("strconv.FormatFloat", &args[2])?;
+    int_arg("strconv.FormatFloat", &args[3])?;
+    let fmt = match u8::try_from(fmt).map(char::from) {
+        Ok(c @ ('e' | 'f' | 'g')) => c,
+        _ => return string(&format!("%{}", char::from(fmt as u8))),
+    };
+    let prec = usize::try_from(prec).ok();
+    // the digits asked for are charged before they are formatted
+    heap::charge(prec.unwrap_or(0))?;
+    Ok(Value::string(&format_float(f, fmt, prec)))
+}
+
+fn format_bool(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strconv.FormatBool", &args, 1)?;
+    match &args[0] {
+        Value::Boolean(b) => string(&b.to_string()),
+        arg => Err(format!("cannot use {} (type {}) as bool value in argument to strconv.FormatBool", arg.inspect(), arg.ty())),
+    }
+}
+
+fn quote_(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strconv.Quote", &args, 1)?;
+    string(&quote(string_arg("strconv.Quote", &args[0])?))
+}
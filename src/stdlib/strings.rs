@@ -0,0 +1,406 @@
+//! Package strings. Strings are bytes; functions that work on characters
+//! decode them as UTF-8.
+
+use super::{int_arg, string_arg, tuple, want_args, Member, Package};
+use crate::types::Type;
+use crate::value::builtins::Builtin;
+use crate::value::{decode_rune, heap, Slice, Value};
+use std::rc::Rc;
+
+pub const PACKAGE: Package = Package {
+    path: "strings",
+    members: &[
+        ("Contains", Member::Func(Builtin { name: "strings.Contains", func: contains }, &[])),
+        ("ContainsAny", Member::Func(Builtin { name: "strings.ContainsAny", func: contains_any }, &[])),
+        ("ContainsRune", Member::Func(Builtin { name: "strings.ContainsRune", func: contains_rune }, &[])),
+        ("HasPrefix", Member::Func(Builtin { name: "strings.HasPrefix", func: has_prefix }, &[])),
+        ("HasSuffix", Member::Func(Builtin { name: "strings.HasSuffix", func: has_suffix }, &[])),
+        ("Index", Member::Func(Builtin { name: "strings.Index", func: index }, &[])),
+        ("IndexRune", Member::Func(Builtin { name: "strings.IndexRune", func: index_rune }, &[])),
+        ("LastIndex", Member::Func(Builtin { name: "strings.LastIndex", func: last_index }, &[])),
+        ("Split", Member::Func(Builtin { name: "strings.Split", func: split }, &[])),
+        ("SplitN", Member::Func(Builtin { name: "strings.SplitN", func: split_n }, &[])),
+        ("Fields", Member::Func(Builtin { name: "strings.Fields", func: fields }, &[])),
+        ("Join", Member::Func(Builtin { name: "strings.Join", func: join }, &[])),
+        ("Repeat", Member::Func(Builtin { name: "strings.Repeat", func: repeat }, &[])),
+        ("Replace", Member::Func(Builtin { name: "strings.Replace", func: replace }, &[])),
+        ("ReplaceAll", Member::Func(Builtin { name: "strings.ReplaceAll", func: replace_all }, &[])),
+        ("ToUpper", Member::Func(Builtin { name: "strings.ToUpper", func: to_upper }, &[])),
+        ("ToLower", Member::Func(Builtin { name: "strings.ToLower", func: to_lower }, &[])),
+        ("TrimSpace", Member::Func(Builtin { name: "strings.TrimSpace", func: trim_space }, &[])),
+        ("Trim", Member::Func(Builtin { name: "strings.Trim", func: trim }, &[])),
+        ("TrimLeft", Member::Func(Builtin { name: "strings.TrimLeft", func: trim_left }, &[])),
+        ("TrimRight", Member::Func(Builtin { name: "strings.TrimRight", func: trim_right }, &[])),
+        ("TrimPrefix", Member::Func(Builtin { name: "strings.TrimPrefix", func: trim_prefix }, &[])),
+        ("TrimSuffix", Member::Func(Builtin { name: "strings.TrimSuffix", func: trim_suffix }, &[])),
+        ("Count", Member::Func(Builtin { name: "strings.Count", func: count }, &[])),
+        ("EqualFold", Member::Func(Builtin { name: "strings.EqualFold", func: equal_fold }, &[])),
+        ("Compare", Member::Func(Builtin { name: "strings.Compare", func: compare }, &[])),
+        ("Cut", Member::Func(Builtin { name: "strings.Cut", func: cut }, &["string", "string", "bool"])),
+    ],
+};
+
+/// A new string of `s`, charged to the heap.
+fn bytes(s: &[u8]) -> Result<Value, String> {
+    heap::charge(s.len())?;
+    Ok(Value::String(Rc::from(s)))
+}
+
+fn strings(values: Vec<&[u8]>) -> Result<Value, String> {
+    heap::charge_slots(values.len())?;
+    let values = values.into_iter().map(bytes).collect::<Result<_, _>>()?;
+    Ok(Value::Slice(Slice::from_values(Type::String, values)))
+}
+
+/// The arguments of a function taking `n` strings.
+fn string_args<'a>(name: &str, args: &'a [Value], n: usize) -> Result<Vec<&'a [u8]>, String> {
+    want_args(name, args, n)?;
+    args.iter().map(|arg| string_arg(name, arg)).collect()
+}
+
+/// Byte offset of the first `sep` in `s` at or after `from`.
+fn find(s: &[u8], sep: &[u8], from: usize) -> Option<usize> {
+    if sep.is_empty() {
+        return Some(from);
+    }
+    s[from..].windows(sep.len()).position(|w| w == sep).map(|i| i + from)
+}
+
+fn runes(s: &[u8]) -> Vec<(usize, char, usize)> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < s.len() {
+        let (c, len) = decode_rune(&s[i..]);
+        out.push((i, c, len));
+        i += len;
+    }
+    out
+}
+
+fn contains(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.Contains", &args, 2)?;
+    Ok(Value::Boolean(find(a[0], a[1], 0).is_some()))
+}
+
+fn contains_any(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.ContainsAny", &args, 2)?;
+    let chars: Vec<char> = runes(a[1]).into_iter().map(|(_, c, _)| c).collect();
+    Ok(Value::Boolean(runes(a[0]).iter().any(|(_, c, _)| chars.contains(c))))
+}
+
+fn contains_rune(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strings.ContainsRune", &args, 2)?;
+    let s = string_arg("strings.ContainsRune", &args[0])?;
+    let r = int_arg("strings.ContainsRune", &args[1])?;
+    Ok(Value::Boolean(runes(s).iter().any(|(_, c, _)| *c as i64 == r)))
+}
+
+fn has_prefix(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.HasPrefix", &args, 2)?;
+    Ok(Value::Boolean(a[0].starts_with(a[1])))
+}
+
+fn has_suffix(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.HasSuffix", &args, 2)?;
+    Ok(Value::Boolean(a[0].ends_with(a[1])))
+}
+
+fn index(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.Index", &args, 2)?;
+    Ok(Value::Integer(find(a[0], a[1], 0).map_or(-1, |i| i as i64)))
+}
+
+fn index_rune(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strings.IndexRune", &args, 2)?;
+    let s = string_arg("strings.IndexRune", &args[0])?;
+    let r = int_arg("strings.IndexRune", &args[1])?;
+    let i = runes(s).into_iter().find(|(_, c, _)| *c as i64 == r).map_or(-1, |(i, _, _)| i as i64);
+    Ok(Value::Integer(i))
+}
+
+fn last_index(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.LastIndex", &args, 2)?;
+    let (s, sep) = (a[0], a[1]);
+    let i = if sep.is_empty() {
+        Some(s.len())
+    } else {
+        s.windows(sep.len()).rposition(|w| w == sep)
+    };
+    Ok(Value::Integer(i.map_or(-1, |i| i as i64)))
+}
+
+/// The substrings between the first `n - 1` separators, and the rest; all
+/// of them when `n` is negative. An empty separator splits after each
+/// UTF-8 sequence.
+fn split_bytes<'a>(s: &'a [u8], sep: &[u8], n: i64) -> Vec<&'a [u8]> {
+    if n == 0 {
+        return vec![];
+    }
+    let limit = if n < 0 { usize::MAX } else { n as usize };
+    let mut parts = vec![];
+    if sep.is_empty() {
+        for (i, _, len) in runes(s) {
+            if parts.len() + 1 == limit {
+                parts.push(&s[i..]);
+                break;
+            }
+            parts.push(&s[i..i + len]);
+        }
+        return parts;
+    }
+    let mut start = 0;
+    while parts.len() + 1 < limit {
+        match find(s, sep, start) {
+            Some(i) => {
+                parts.push(&s[start..i]);
+                start = i + sep.len();
+            }
+            None => break,
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn split(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.Split", &args, 2)?;
+    strings(split_bytes(a[0], a[1], -1))
+}
+
+fn split_n(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strings.SplitN", &args, 3)?;
+    let s = string_arg("strings.SplitN", &args[0])?;
+    let sep = string_arg("strings.SplitN", &args[1])?;
+    let n = int_arg("strings.SplitN", &args[2])?;
+    if n == 0 {
+        return Ok(Value::Slice(Slice::nil(Type::String)));
+    }
+    strings(split_bytes(s, sep, n))
+}
+
+fn is_space(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0b' | '\x0c' | '\r' | ' ' | '\u{85}' | '\u{A0}') || (c > '\u{FF}' && c.is_whitespace())
+}
+
+fn fields(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.Fields", &args, 1)?;
+    let s = a[0];
+    let mut parts = vec![];
+    let mut start = None;
+    for (i, c, _) in runes(s) {
+        match (is_space(c), start) {
+            (true, Some(st)) => {
+                parts.push(&s[st..i]);
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(st) = start {
+        parts.push(&s[st..]);
+    }
+    strings(parts)
+}
+
+fn join(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strings.Join", &args, 2)?;
+    let elems = match &args[0] {
+        Value::Slice(s) if s.elem == Type::String => s.values(),
+        Value::Null => vec![],
+        arg => return Err(format!("cannot use {} (type {}) as []string value in argument to strings.Join", arg.inspect(), arg.ty())),
+    };
+    let sep = string_arg("strings.Join", &args[1])?;
+    let mut out = vec![];
+    for (i, elem) in elems.iter().enumerate() {
+        if i > 0 {
+            out.extend_from_slice(sep);
+        }
+        out.extend_from_slice(string_arg("strings.Join", elem)?);
+    }
+    bytes(&out)
+}
+
+fn repeat(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strings.Repeat", &args, 2)?;
+    let s = string_arg("strings.Repeat", &args[0])?;
+    let n = int_arg("strings.Repeat", &args[1])?;
+    if n < 0 {
+        return Err("strings: negative Repeat count".to_string());
+    }
+    let len = s.len().checked_mul(n as usize).ok_or_else(|| "strings: Repeat output length overflow".to_string())?;
+    heap::charge(len)?;
+    Ok(Value::String(Rc::from(s.repeat(n as usize))))
+}
+
+/// `s` with the first `n` non-overlapping `old` replaced by `new`; all of
+/// them when `n` is negative.
+fn replace_bytes(s: &[u8], old: &[u8], new: &[u8], n: i64) -> Vec<u8> {
+    let mut out = vec![];
+    let mut start = 0;
+    let mut done = 0;
+    if old.is_empty() {
+        // before each rune and at the end
+        for (i, _, len) in runes(s) {
+            if n >= 0 && done >= n {
+                break;
+            }
+            out.extend_from_slice(new);
+            out.extend_from_slice(&s[i..i + len]);
+            start = i + len;
+            done += 1;
+        }
+        if n < 0 || done < n {
+            out.extend_from_slice(&s[start..]);
+            out.extend_from_slice(new);
+            return out;
+        }
+        out.extend_from_slice(&s[start..]);
+        return out;
+    }
+    while n < 0 || done < n {
+        match find(s, old, start) {
+            Some(i) => {
+                out.extend_from_slice(&s[start..i]);
+                out.extend_from_slice(new);
+                start = i + old.len();
+                done += 1;
+            }
+            None => break,
+        }
+    }
+    out.extend_from_slice(&s[start..]);
+    out
+}
+
+fn replace(args: Vec<Value>) -> Result<Value, String> {
+    want_args("strings.Replace", &args, 4)?;
+    let a = string_args("strings.Replace", &args[..3], 3)?;
+    let n = int_arg("strings.Replace", &args[3])?;
+    bytes(&replace_bytes(a[0], a[1], a[2], n))
+}
+
+fn replace_all(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.ReplaceAll", &args, 3)?;
+    bytes(&replace_bytes(a[0], a[1], a[2], -1))
+}
+
+/// `s` with each rune mapped by `f`; bytes that are not UTF-8 are kept.
+fn map_runes(s: &[u8], f: impl Fn(char) -> String) -> Result<Value, String> {
+    let mut out = vec![];
+    for (i, c, len) in runes(s) {
+        if c == '\u{FFFD}' && len == 1 {
+            out.push(s[i]);
+        } else {
+            out.extend_from_slice(f(c).as_bytes());
+        }
+    }
+    bytes(&out)
+}
+
+fn to_upper(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.ToUpper", &args, 1)?;
+    map_runes(a[0], |c| c.to_uppercase().collect())
+}
+
+fn to_lower(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.ToLower", &args, 1)?;
+    map_runes(a[0], |c| c.to_lowercase().collect())
+}
+
+/// The byte range of `s` left when leading and trailing runes matching
+/// `f` are removed from the sides asked for.
+fn trim_range(s: &[u8], left: bool, right: bool, f: impl Fn(char) -> bool) -> &[u8] {
+    let runes = runes(s);
+    let mut start = 0;
+    let mut end = runes.len();
+    if left {
+        while start < end && f(runes[start].1) {
+            start += 1;
+        }
+    }
+    if right {
+        while end > start && f(runes[end - 1].1) {
+            end -= 1;
+        }
+    }
+    if start == end {
+        return &[];
+    }
+    let (from, _, _) = runes[start];
+    let (last, _, len) = runes[end - 1];
+    &s[from..last + len]
+}
+
+fn trim_space(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.TrimSpace", &args, 1)?;
+    bytes(trim_range(a[0], true, true, is_space))
+}
+
+fn trim_cutset(name: &str, args: Vec<Value>, left: bool, right: bool) -> Result<Value, String> {
+    let a = string_args(name, &args, 2)?;
+    let cutset: Vec<char> = runes(a[1]).into_iter().map(|(_, c, _)| c).collect();
+    bytes(trim_range(a[0], left, right, |c| cutset.contains(&c)))
+}
+
+fn trim(args: Vec<Value>) -> Result<Value, String> {
+    trim_cutset("strings.Trim", args, true, true)
+}
+
+fn trim_left(args: Vec<Value>) -> Result<Value, String> {
+    trim_cutset("strings.TrimLeft", args, true, false)
+}
+
+fn trim_right(args: Vec<Value>) -> Result<Value, String> {
+    trim_cutset("strings.TrimRight", args, false, true)
+}
+
+fn trim_prefix(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.TrimPrefix", &args, 2)?;
+    bytes(a[0].strip_prefix(a[1]).unwrap_or(a[0]))
+}
+
+fn trim_suffix(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.TrimSuffix", &args, 2)?;
+    bytes(a[0].strip_suffix(a[1]).unwrap_or(a[0]))
+}
+
+/// Non-overlapping instances of `sep` in `s`; one more than the number of
+/// runes when `sep` is empty.
+fn count(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.Count", &args, 2)?;
+    let (s, sep) = (a[0], a[1]);
+    if sep.is_empty() {
+        return Ok(Value::Integer(runes(s).len() as i64 + 1));
+    }
+    let mut n = 0;
+    let mut start = 0;
+    while let Some(i) = find(s, sep, start) {
+        n += 1;
+        start = i + sep.len();
+    }
+    Ok(Value::Integer(n))
+}
+
+/// Whether the strings are equal under simple Unicode case folding.
+fn equal_fold(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.EqualFold", &args, 2)?;
+    let fold = |s: &[u8]| -> Vec<char> { runes(s).into_iter().flat_map(|(_, c, _)| c.to_lowercase()).collect() };
+    Ok(Value::Boolean(fold(a[0]) == fold(a[1])))
+}
+
+fn compare(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.Compare", &args, 2)?;
+    Ok(Value::Integer(a[0].cmp(a[1]) as i64))
+}
+
+/// `strings.Cut(s, sep)`: the text before and after the first `sep`, and
+/// whether there was one.
+fn cut(args: Vec<Value>) -> Result<Value, String> {
+    let a = string_args("strings.Cut", &args, 2)?;
+    let (s, sep) = (a[0], a[1]);
+    Ok(match find(s, sep, 0) {
+        Some(i) => tuple(vec![bytes(&s[..i])?, bytes(&s[i + sep.len()..])?, Value::Boolean(true)]),
+        None => tuple(vec![bytes(s)?, bytes(&[])?, Value::Boolean(false)]),
+    })
+}
@@ -0,0 +1,256 @@
+use crate::compiler::Compiler;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::vm::{self, Limit, Limits, Vm};
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Runs `check_inspect` of the VM's tests on each input after `imports`.
+  fn check_inspect(imports: &str, tests: &[(&str, &str)]) {
+    for (input, expected) in tests {
+      vm::test::tests::check_inspect(&[(&format!("{}\n{}", imports, input), expected)]);
+    }
+  }
+
+  fn check_error(imports: &str, tests: &[(&str, &str)]) {
+    for (input, expected) in tests {
+      vm::test::tests::check_error(&[(&format!("{}\n{}", imports, input), expected)]);
+    }
+  }
+
+  #[test]
+  fn test_sprintf_verbs() {
+    check_inspect(
+      "import \"fmt\"\ntype P struct { X int; Y string }",
+      &[
+        (r#"fmt.Sprintf("%d|%5d|%-5d|%05d", 42, 42, 42, -42)"#, "42|   42|42   |-0042"),
+        (r#"fmt.Sprintf("%x %X %#x %b %o %c %U", 255, 255, 255, 5, 8, 65, 128512)"#, "ff FF 0xff 101 10 A U+1F600"),
+        (r#"fmt.Sprintf("%s|%q|%8s|%-4s|%x", "hi", "a\"b\n", "go", "x", "hi")"#, "hi|\"a\\\"b\\n\"|      go|x   |6869"),
+        (r#"fmt.Sprintf("%v %.2f %8.3f %e %g %+d", 2.5, 3.14159, -1.5, 123456.789, 1e21, 3)"#, "2.5 3.14   -1.500 1.234568e+05 1e+21 +3"),
+        (r#"fmt.Sprintf("%v %+v %T", P{1, "a"}, P{1, "a"}, P{})"#, "{1 a} {X:1 Y:a} main.P"),
+        (r#"fmt.Sprintf("%T %T %T", &P{}, map[string]P{}, []any{})"#, "*main.P map[string]main.P []interface {}"),
+        (r#"fmt.Sprintf("%v %+v", &P{2, "b"}, 5)"#, "&{2 b} 5"),
+        (r#"fmt.Sprintf("%v %v %d", []int{1, 2}, map[string]int{"b": 2, "a": 1}, []int{3, 4})"#, "[1 2] map[a:1 b:2] [3 4]"),
+        (r#"fmt.Sprintf("%T %T %T %T", 1, "s", 1.5, nil)"#, "int string float64 <nil>"),
+        (r#"fmt.Sprintf("%*d|%.*f|%%", 4, 7, 1, 2.25)"#, "   7|2.2|%"),
+        (r#"fmt.Sprintf("%t %v", true, nil)"#, "true <nil>"),
+      ],
+    );
+  }
+
+  #[test]
+  fn test_sprintf_bad_verbs() {
+    check_inspect(
+      "import \"fmt\"",
+      &[
+        (r#"fmt.Sprintf("%d %d", 1)"#, "1 %!d(MISSING)"),
+        (r#"fmt.Sprintf("%d", 1, "x", 2)"#, "1%!(EXTRA string=x, int=2)"),
+        (r#"fmt.Sprintf("%d|%s", "x", 1)"#, "%!d(string=x)|%!s(int=1)"),
+        (r#"fmt.Sprintf("%d", nil)"#, "%!d(<nil>)"),
+        (r#"fmt.Sprintf("%")"#, "%!(NOVERB)"),
+        (r#"fmt.Sprintf("%w", 1)"#, "%!w(int=1)"),
+        (r#"fmt.Sprintf("%*d", 100000000, 1)"#, "%!(BADWIDTH)1"),
+        (r#"fmt.Sprintf("%.10000000f", 1.5)"#, "%!(BADPREC)1.500000"),
+      ],
+    );
+  }
+
+  #[test]
+  fn test_sprint_and_sprintln() {
+    check_inspect(
+      "import \"fmt\"",
+      &[
+        (r#"fmt.Sprint("a", 1, 2, "b", 3.5)"#, "a1 2b3.5"),
+        (r#"fmt.Sprintln("a", 1, true)"#, "a 1 true\n"),
+      ],
+    );
+  }
+
+  #[test]
+  fn test_strings() {
+    check_inspect(
+      "import \"strings\"",
+      &[
+        (r#"strings.Contains("seafood", "foo")"#, "true"),
+        (r#"strings.Index("chicken", "ken")"#, "4"),
+        (r#"strings.Index("héllo", "l")"#, "3"),
+        (r#"strings.Split("a,b,c", ",")"#, "[a b c]"),
+        (r#"len(strings.Split("", ","))"#, "1"),
+        (r#"strings.Fields("  a b  c ")"#, "[a b c]"),
+        (r#"strings.Join([]string{"x", "y", "z"}, "-")"#, "x-y-z"),
+        (r#"strings.Repeat("ab", 3)"#, "ababab"),
+        (r#"strings.ReplaceAll("oink oink", "k", "ky")"#, "oinky oinky"),
+        (r#"strings.Replace("aaa", "a", "b", 2)"#, "bba"),
+        (r#"strings.ToUpper("héllo")"#, "HÉLLO"),
+        (r#"strings.TrimSpace("\t x \n")"#, "x"),
+        (r#"strings.Trim("xxhixx", "x")"#, "hi"),
+        (r#"strings.TrimPrefix("prefix-body", "prefix-")"#, "body"),
+        (r#"strings.HasSuffix("main.go", ".go")"#, "true"),
+        (r#"strings.EqualFold("Go", "GO")"#, "true"),
+        (r#"before, after, found := strings.Cut("k=v", "="); before + "|" + after"#, "k|v"),
+      ],
+    );
+    check_error("import \"strings\"", &[(r#"strings.Repeat("a", -1)"#, "strings: negative Repeat count")]);
+  }
+
+  #[test]
+  fn test_strconv() {
+    check_inspect(
+      "import \"strconv\"",
+      &[
+        ("strconv.Itoa(-45)", "-45"),
+        (r#"n, err := strconv.Atoi("123"); n"#, "123"),
+        (r#"n, err := strconv.Atoi("123"); err == nil"#, "true"),
+        (r#"n, err := strconv.Atoi("12a"); err.Error()"#, "strconv.Atoi: parsing \"12a\": invalid syntax"),
+        (r#"n, err := strconv.Atoi("99999999999999999999"); err"#, "strconv.Atoi: parsing \"99999999999999999999\": value out of range"),
+        (r#"f, err := strconv.ParseFloat("3.25", 64); f * 2"#, "6.5"),
+        (r#"f, err := strconv.ParseFloat("x", 64); err"#, "strconv.ParseFloat: parsing \"x\": invalid syntax"),
+        (r#"n, err := strconv.ParseInt("-ff", 16, 64); n"#, "-255"),
+        (r#"n, err := strconv.ParseInt("200", 10, 8); err"#, "strconv.ParseInt: parsing \"200\": value out of range"),
+        (r#"b, err := strconv.ParseBool("T"); b"#, "true"),
+        ("strconv.FormatInt(-10, 2)", "-1010"),
+        (r#"strconv.Quote("tab\there")"#, "\"tab\\there\""),
+      ],
+    );
+  }
+
+  #[test]
+  fn test_errors() {
+    check_inspect(
+      "import (\n\"errors\"\n\"fmt\"\n\"strconv\"\n)",
+      &[
+        (r#"errors.New("boom")"#, "boom"),
+        (r#"errors.New("a") == errors.New("a")"#, "false"),
+        (r#"e := errors.New("a"); e == e"#, "true"),
+        (r#"e := errors.New("boom"); fmt.Errorf("ctx: %w", e)"#, "ctx: boom"),
+        (r#"e := errors.New("boom"); errors.Is(fmt.Errorf("a: %w", fmt.Errorf("b: %w", e)), e)"#, "true"),
+        (r#"e := errors.New("boom"); errors.Is(fmt.Errorf("a: %v", e), e)"#, "false"),
+        (r#"e := errors.New("boom"); errors.Unwrap(fmt.Errorf("a: %w", e)) == e"#, "true"),
+        (r#"errors.Unwrap(errors.New("x")) == nil"#, "true"),
+        (r#"n, err := strconv.Atoi("x"); errors.Is(err, strconv.ErrSyntax)"#, "true"),
+        (r#"fmt.Sprintf("%v|%s|%q", errors.New("e"), errors.New("e"), errors.New("e"))"#, "e|e|\"e\""),
+        (r#"fmt.Errorf("%d items", 3)"#, "3 items"),
+        (
+          r#"n, err := strconv.Atoi("x"); var ne *strconv.NumError; ok := errors.As(fmt.Errorf("w: %w", err), &ne); fmt.Sprint(ok, " ", ne.Func, " ", ne.Num)"#,
+          "true Atoi x",
+        ),
+        (r#"var ne *strconv.NumError; ok := errors.As(errors.New("x"), &ne); fmt.Sprint(ok, " ", ne == nil)"#, "false true"),
+        (r#"var e error; errors.As(fmt.Errorf("w: %w", errors.New("x")), &e); e"#, "w: x"),
+        (r#"n, err := strconv.Atoi("x"); fmt.Sprintf("%T", err)"#, "*strconv.NumError"),
+      ],
+    );
+  }
+
+  #[test]
+  fn test_math() {
+    check_inspect(
+      "import \"math\"",
+      &[
+        ("math.Sqrt(2)", "1.4142135623730951"),
+        ("math.Pi", "3.141592653589793"),
+        ("math.Floor(-2.5)", "-3"),
+        ("math.Max(3, 7)", "7"),
+        ("math.Pow(2, 10)", "1024"),
+        ("math.Abs(-1.5)", "1.5"),
+        ("math.Inf(-1)", "-Inf"),
+        ("math.IsNaN(math.NaN())", "true"),
+        ("math.MaxInt64", "9223372036854775807"),
+        ("math.Hypot(3, 4)", "5"),
+      ],
+    );
+  }
+
+  #[test]
+  fn test_sort() {
+    check_inspect(
+      "import \"sort\"\ntype P struct { Name string; Age int }",
+      &[
+        ("s := []int{5, 2, 8, 1, 9, 3}; sort.Slice(s, fn(i, j) { s[i] < s[j] }); s", "[1 2 3 5 8 9]"),
+        ("s := []int{}; sort.Slice(s, fn(i, j) { s[i] < s[j] }); s", "[]"),
+        (
+          r#"s := []P{{"a", 3}, {"b", 1}, {"c", 3}, {"d", 1}}; sort.SliceStable(s, fn(i, j) { s[i].Age < s[j].Age }); s"#,
+          "[{b 1} {d 1} {a 3} {c 3}]",
+        ),
+        ("sort.Search(100, fn(i) { i * i >= 50 })", "8"),
+        ("s := []int{3, 1, 2}; sort.Ints(s); s", "[1 2 3]"),
+        (r#"s := []string{"b", "c", "a"}; sort.Strings(s); s"#, "[a b c]"),
+        ("s := []float64{2.5, -1, 0.5}; sort.Float64s(s); s", "[-1 0.5 2.5]"),
+      ],
+    );
+  }
+
+  #[test]
+  fn test_os() {
+    check_inspect(
+      "import (\n\"errors\"\n\"os\"\n)",
+      &[
+        (r#"data, err := os.ReadFile("/nonexistent/file"); err"#, "open /nonexistent/file: no such file or directory"),
+        (r#"data, err := os.ReadFile("/nonexistent/file"); len(data)"#, "0"),
+        (r#"data, err := os.ReadFile("/nonexistent/file"); var pe *os.PathError; errors.As(err, &pe); pe.Op + " " + pe.Path"#, "open /nonexistent/file"),
+        ("len(os.Args)", "0"),
+      ],
+    );
+  }
+
+  #[test]
+  fn test_runtime() {
+    check_inspect(
+      "import \"runtime\"\ntype Node struct { next *Node }",
+      &[
+        ("var m runtime.MemStats; runtime.ReadMemStats(&m); m.NumGC", "0"),
+        ("s := make([]int, 100); var m runtime.MemStats; runtime.ReadMemStats(&m); m.TotalAlloc >= 800 && m.HeapAlloc == m.Alloc", "true"),
+        (
+          "for i := 0; i < 10; i++ { n := &Node{}; n.next = n }; runtime.GC(); m := &runtime.MemStats{}; runtime.ReadMemStats(m); m.NumGC == 1 && m.Frees >= 9",
+          "true",
+        ),
+        ("var m runtime.MemStats; runtime.ReadMemStats(&m); m.HeapObjects == m.Mallocs - m.Frees", "true"),
+      ],
+    );
+    check_error(
+      "import \"runtime\"",
+      &[("runtime.ReadMemStats(1)", "runtime error: cannot use 1 (type int) as *runtime.MemStats value in argument to runtime.ReadMemStats")],
+    );
+  }
+
+  #[test]
+  fn test_heap_limit() {
+    let imports = "import (\n\"fmt\"\n\"strconv\"\n\"strings\"\n)";
+    let tests = [
+      r#"x := "ab"; for { x = fmt.Sprint(x, x) }"#,
+      r#"x := "ab"; for { x = fmt.Sprintf("%s%s", x, x) }"#,
+      r#"x := "ab"; for { x = fmt.Sprintln(x, x) }"#,
+      r#"x := "ab"; for { x = fmt.Errorf("%s%s", x, x).Error() }"#,
+      r#"x := "ab"; for { x = strings.ReplaceAll(x, "a", "aa") }"#,
+      r#"x := "ab"; for { x = strings.Join([]string{x, x}, "") }"#,
+      r#"x := "ab"; for { x = strings.ToUpper(x) + strings.ToLower(x) }"#,
+      r#"x := "ab"; var keep [][]string; for { keep = append(keep, strings.Split(x, "")); x = x + x }"#,
+      r#"x := "ab"; for { x = strconv.Quote(x) }"#,
+      "strconv.FormatFloat(1, 102, 100000000, 64)",
+      r#"b := []byte(strings.Repeat("a", 524288))"#,
+      "b := make([]byte, 100000); var keep []string; for { keep = append(keep, string(b)) }",
+    ];
+    for input in tests.iter() {
+      let program = Parser::new(Lexer::new(format!("{}\n{}", imports, input))).parse_program();
+      let mut c = Compiler::new();
+      c.compile(&program).unwrap();
+      let mut vm = Vm::new(c.bytecode());
+      vm.set_limits(Limits { heap: Some(1 << 20), ..Default::default() });
+      let result = vm.run();
+      assert_eq!((result.is_err(), vm.exceeded()), (true, Some(Limit::Heap(1 << 20))), "{:?}", input);
+    }
+  }
+
+  #[test]
+  fn test_package_call_errors() {
+    check_error(
+      "import (\n\"math\"\n\"strings\"\n)",
+      &[
+        ("math.Sqrt()", "runtime error: not enough arguments in call to math.Sqrt"),
+        (r#"strings.ToUpper("a", "b")"#, "runtime error: too many arguments in call to strings.ToUpper"),
+        (r#"math.Sqrt("x")"#, "runtime error: cannot use x (type string) as float64 value in argument to math.Sqrt"),
+      ],
+    );
+  }
+}
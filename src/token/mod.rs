@@ -6,16 +6,23 @@ pub type TokenType = &'static str;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub r#type: TokenType,
-    pub literal: String
+    pub literal: String,
+    /// 1-based line of the first character
+    pub line: usize,
+    /// 1-based column of the first character
+    pub column: usize,
 }
 
 
 pub const ILLEGAL: &str = "ILLEGAL";
 pub const EOF: &str = "EOF";
+pub const COMMENT: &str = "COMMENT";
 
 // Identifiers + literals
 pub const  IDENT: &str = "IDENT";
 pub const  INT: &str = "INT";
+pub const  FLOAT: &str = "FLOAT";
+pub const  STRING: &str = "STRING";
 
 // Operators
 pub const ASSIGN: &str = "=";
@@ -24,9 +31,22 @@ pub const MINUS: &str = "-";
 pub const BANG: &str = "!";
 pub const ASTERISK: &str = "*";
 pub const SLASH: &str = "/";
+pub const PERCENT: &str = "%";
+pub const AMPERSAND: &str = "&";
+pub const INC: &str = "++";
+pub const DEC: &str = "--";
+pub const DEFINE: &str = ":=";
+pub const ADD_ASSIGN: &str = "+=";
+pub const SUB_ASSIGN: &str = "-=";
+pub const MUL_ASSIGN: &str = "*=";
+pub const QUO_ASSIGN: &str = "/=";
+pub const REM_ASSIGN: &str = "%=";
+pub const ARROW: &str = "<-";
 
 pub const LT: &str = "<";
 pub const GT: &str = ">";
+pub const LT_EQ: &str = "<=";
+pub const GT_EQ: &str = ">=";
 
 // Delimiters
 pub const COMMA: &str = ",";
@@ -35,10 +55,17 @@ pub const LPAREN: &str = "(";
 pub const RPAREN: &str = ")";
 pub const LBRACE: &str = "{";
 pub const RBRACE: &str = "}";
+pub const LBRACKET: &str = "[";
+pub const RBRACKET: &str = "]";
+pub const COLON: &str = ":";
+pub const PERIOD: &str = ".";
+pub const ELLIPSIS: &str = "...";
 
 // Logic
 pub const EQ: &str = "==";
 pub const NOT_EQ: &str = "!=";
+pub const AND: &str = "&&";
+pub const OR: &str = "||";
 
 
 // Keywords
@@ -49,6 +76,23 @@ pub const FALSE: &str = "FALSE";
 pub const IF: &str = "IF";
 pub const ELSE: &str = "ELSE";
 pub const RETURN: &str = "RETURN";
+pub const VAR: &str = "VAR";
+pub const TYPE: &str = "TYPE";
+pub const STRUCT: &str = "STRUCT";
+pub const MAP: &str = "MAP";
+pub const INTERFACE: &str = "INTERFACE";
+pub const FOR: &str = "FOR";
+pub const RANGE: &str = "RANGE";
+pub const BREAK: &str = "BREAK";
+pub const CONTINUE: &str = "CONTINUE";
+pub const GO: &str = "GO";
+pub const CHAN: &str = "CHAN";
+pub const SELECT: &str = "SELECT";
+pub const CASE: &str = "CASE";
+pub const DEFAULT: &str = "DEFAULT";
+pub const DEFER: &str = "DEFER";
+pub const IMPORT: &str = "IMPORT";
+pub const PACKAGE: &str = "PACKAGE";
 
 
 // Keyword HashMap
@@ -62,15 +106,32 @@ lazy_static! {
         m.insert("if", IF);
         m.insert("else", ELSE);
         m.insert("return", RETURN);
+        m.insert("var", VAR);
+        m.insert("type", TYPE);
+        m.insert("struct", STRUCT);
+        m.insert("map", MAP);
+        m.insert("interface", INTERFACE);
+        m.insert("for", FOR);
+        m.insert("range", RANGE);
+        m.insert("break", BREAK);
+        m.insert("continue", CONTINUE);
+        m.insert("go", GO);
+        m.insert("chan", CHAN);
+        m.insert("select", SELECT);
+        m.insert("case", CASE);
+        m.insert("default", DEFAULT);
+        m.insert("defer", DEFER);
+        m.insert("import", IMPORT);
+        m.insert("package", PACKAGE);
         m
     };
 }
 
 pub fn lookup_ident(ident: &str) -> TokenType {
     if KEYWORDS.contains_key(ident) {
-        return KEYWORDS[ident];
+        KEYWORDS[ident]
     } else {
-        return IDENT;
+        IDENT
     }
 }
 
@@ -0,0 +1,284 @@
+use crate::value::{Chan, Interface, Map, Pointer, Slice, Struct, Value};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+#[cfg(test)]
+mod test;
+
+/// The type of a runtime value, as far as the interpreter tracks it.
+#[derive(Clone)]
+pub enum Type {
+    /// The type of the untyped `nil`.
+    Nil,
+    Bool,
+    Int,
+    /// `byte`, Go's alias for uint8. Its values are integers kept in
+    /// 0..=255.
+    Byte,
+    Float64,
+    String,
+    Func,
+    Slice(Rc<Type>),
+    Map(Rc<Type>, Rc<Type>),
+    Pointer(Rc<Type>),
+    Chan(Rc<Type>),
+    Struct(Rc<StructType>),
+    Interface(Rc<InterfaceType>),
+}
+
+/// A struct type. Fields sit behind a `RefCell` so a named type can be
+/// created first and filled in once its own name resolves, as in
+/// `type Node struct { next *Node }`.
+pub struct StructType {
+    pub name: Option<String>,
+    pub fields: RefCell<Vec<Field>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub ty: Type,
+}
+
+/// An interface type. Method sets are not checked, so every value
+/// implements every interface; `error` is the only one with a method.
+pub struct InterfaceType {
+    pub name: Option<String>,
+    pub methods: Vec<String>,
+}
+
+/// The parameter and result types of a function the embedding program
+/// registers, which calls to it are checked against. A variadic
+/// function's last parameter type is that of each of its extra
+/// arguments, as in `func(format string, args ...any)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub params: Vec<Type>,
+    pub variadic: bool,
+    pub results: Vec<Type>,
+}
+
+impl Signature {
+    pub fn new(params: Vec<Type>, results: Vec<Type>) -> Signature {
+        Signature { params, variadic: false, results }
+    }
+
+    pub fn variadic(params: Vec<Type>, results: Vec<Type>) -> Signature {
+        assert!(!params.is_empty(), "a variadic signature needs a parameter for its extra arguments");
+        Signature { params, variadic: true, results }
+    }
+}
+
+impl StructType {
+    /// Index of the field called `name`.
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.borrow().iter().position(|f| f.name == name)
+    }
+}
+
+impl Type {
+    /// The empty interface, `any`.
+    pub fn any() -> Type {
+        Type::Interface(Rc::new(InterfaceType { name: None, methods: vec![] }))
+    }
+
+    /// The predeclared `error` interface. There is one, as named types
+    /// are identical only to themselves.
+    pub fn error() -> Type {
+        thread_local! {
+            static ERROR: Rc<InterfaceType> =
+                Rc::new(InterfaceType { name: Some("error".to_string()), methods: vec!["Error".to_string()] });
+        }
+        ERROR.with(|it| Type::Interface(Rc::clone(it)))
+    }
+
+    /// Go's type identity: named types are identical only to themselves,
+    /// unnamed ones when their structure is.
+    pub fn identical(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Nil, Type::Nil)
+            | (Type::Bool, Type::Bool)
+            | (Type::Int, Type::Int)
+            | (Type::Byte, Type::Byte)
+            | (Type::Float64, Type::Float64)
+            | (Type::String, Type::String)
+            | (Type::Func, Type::Func) => true,
+            (Type::Slice(a), Type::Slice(b)) | (Type::Pointer(a), Type::Pointer(b)) | (Type::Chan(a), Type::Chan(b)) => {
+                a.identical(b)
+            }
+            (Type::Map(ak, av), Type::Map(bk, bv)) => ak.identical(bk) && av.identical(bv),
+            (Type::Struct(a), Type::Struct(b)) => {
+                if Rc::ptr_eq(a, b) {
+                    return true;
+                }
+                if a.name.is_some() || b.name.is_some() {
+                    return false;
+                }
+                let (af, bf) = (a.fields.borrow(), b.fields.borrow());
+                af.len() == bf.len() && af.iter().zip(bf.iter()).all(|(x, y)| x.name == y.name && x.ty.identical(&y.ty))
+            }
+            (Type::Interface(a), Type::Interface(b)) => {
+                Rc::ptr_eq(a, b) || (a.name.is_none() && b.name.is_none() && a.methods == b.methods)
+            }
+            _ => false,
+        }
+    }
+
+    /// Reports whether `==` is defined on values of the type.
+    pub fn comparable(&self) -> bool {
+        match self {
+            Type::Slice(_) | Type::Map(_, _) | Type::Func => false,
+            Type::Struct(st) => st.fields.borrow().iter().all(|f| f.ty.comparable()),
+            _ => true,
+        }
+    }
+
+    /// The type as `%T` prints it: the named types the program declares
+    /// are qualified by its package, `main`, as those of the packages are
+    /// by theirs.
+    pub fn qualified(&self) -> String {
+        let named = |name: &str| {
+            if name.contains('.') || name == "error" {
+                name.to_string()
+            } else {
+                format!("main.{}", name)
+            }
+        };
+        match self {
+            Type::Slice(elem) => format!("[]{}", elem.qualified()),
+            Type::Map(key, elem) => format!("map[{}]{}", key.qualified(), elem.qualified()),
+            Type::Pointer(elem) => format!("*{}", elem.qualified()),
+            Type::Chan(elem) => format!("chan {}", elem.qualified()),
+            Type::Struct(st) => match &st.name {
+                Some(name) => named(name),
+                None => {
+                    let fields: Vec<String> =
+                        st.fields.borrow().iter().map(|fd| format!("{} {}", fd.name, fd.ty.qualified())).collect();
+                    if fields.is_empty() {
+                        "struct {}".to_string()
+                    } else {
+                        format!("struct {{ {} }}", fields.join("; "))
+                    }
+                }
+            },
+            Type::Interface(it) => match &it.name {
+                Some(name) => named(name),
+                None => "interface {}".to_string(),
+            },
+            ty => ty.to_string(),
+        }
+    }
+
+    pub fn is_interface(&self) -> bool {
+        matches!(self, Type::Interface(_))
+    }
+
+    /// The value a variable of the type holds before anything is stored
+    /// in it.
+    pub fn zero(&self) -> Value {
+        match self {
+            Type::Nil | Type::Func => Value::Null,
+            Type::Bool => Value::Boolean(false),
+            Type::Int | Type::Byte => Value::Integer(0),
+            Type::Float64 => Value::Float(0.0),
+            Type::String => Value::String(Rc::from(&b""[..])),
+            Type::Slice(elem) => Value::Slice(Slice::nil(elem.as_ref().clone())),
+            Type::Map(key, elem) => Value::Map(Map::nil(key.as_ref().clone(), elem.as_ref().clone())),
+            Type::Pointer(elem) => Value::Pointer(Pointer::nil(elem.as_ref().clone())),
+            Type::Chan(elem) => Value::Chan(Chan::nil(elem.as_ref().clone())),
+            Type::Struct(st) => {
+                let fields = st.fields.borrow().iter().map(|f| f.ty.zero()).collect();
+                Value::Struct(Box::new(Struct { ty: Rc::clone(st), fields }))
+            }
+            Type::Interface(it) => Value::Interface(Interface { ty: Rc::clone(it), value: None }),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Nil => write!(f, "nil"),
+            Type::Bool => write!(f, "bool"),
+            Type::Int => write!(f, "int"),
+            Type::Byte => write!(f, "uint8"),
+            Type::Float64 => write!(f, "float64"),
+            Type::String => write!(f, "string"),
+            Type::Func => write!(f, "fn"),
+            Type::Slice(elem) => write!(f, "[]{}", elem),
+            Type::Map(key, elem) => write!(f, "map[{}]{}", key, elem),
+            Type::Pointer(elem) => write!(f, "*{}", elem),
+            Type::Chan(elem) => write!(f, "chan {}", elem),
+            Type::Struct(st) => match &st.name {
+                Some(name) => write!(f, "{}", name),
+                None => {
+                    let fields: Vec<String> = st.fields.borrow().iter().map(|fd| format!("{} {}", fd.name, fd.ty)).collect();
+                    if fields.is_empty() {
+                        write!(f, "struct {{}}")
+                    } else {
+                        write!(f, "struct {{ {} }}", fields.join("; "))
+                    }
+                }
+            },
+            Type::Interface(it) => match &it.name {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "interface {{}}"),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Signature {
+    /// Go's notation, as in `func(string, ...any) (int, error)`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let last = self.params.len().wrapping_sub(1);
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, t)| if self.variadic && i == last { format!("...{}", t) } else { t.to_string() })
+            .collect();
+        write!(f, "func({})", params.join(", "))?;
+        match self.results.as_slice() {
+            [] => Ok(()),
+            [result] => write!(f, " {}", result),
+            results => {
+                let results: Vec<String> = results.iter().map(|t| t.to_string()).collect();
+                write!(f, " ({})", results.join(", "))
+            }
+        }
+    }
+}
+
+// Written by hand: a derived Debug would recurse forever on
+// self-referential struct types.
+impl fmt::Debug for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Debug for StructType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "struct {{..}}"),
+        }
+    }
+}
+
+impl fmt::Debug for InterfaceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "interface {{}}"),
+        }
+    }
+}
+
+impl PartialEq for Type {
+    fn eq(&self, other: &Type) -> bool {
+        self.identical(other)
+    }
+}
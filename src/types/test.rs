@@ -0,0 +1,67 @@
+use crate::types::*;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn named(name: &str) -> Type {
+    Type::Struct(Rc::new(StructType { name: Some(name.to_string()), fields: RefCell::new(vec![]) }))
+  }
+
+  #[test]
+  fn test_display() {
+    let tests = [
+      (Type::Slice(Rc::new(Type::Int)), "[]int"),
+      (Type::Map(Rc::new(Type::String), Rc::new(Type::any())), "map[string]interface {}"),
+      (Type::Pointer(Rc::new(named("Point"))), "*Point"),
+      (Type::Slice(Rc::new(Type::Byte)), "[]uint8"),
+    ];
+    for (ty, expected) in tests.iter() {
+      if ty.to_string() != *expected {
+        panic!("expected={:?}, got={:?}", expected, ty.to_string());
+      }
+    }
+  }
+
+  #[test]
+  fn test_identical() {
+    let a = named("A");
+    let tests = [
+      (Type::Slice(Rc::new(Type::Int)), Type::Slice(Rc::new(Type::Int)), true),
+      (Type::Slice(Rc::new(Type::Int)), Type::Slice(Rc::new(Type::Byte)), false),
+      (a.clone(), a.clone(), true),
+      (a, named("A"), false),
+      (Type::any(), Type::any(), true),
+    ];
+    for (x, y, expected) in tests.iter() {
+      if x.identical(y) != *expected {
+        panic!("identical({}, {}) expected={}", x, y, expected);
+      }
+    }
+  }
+
+  #[test]
+  fn test_comparable() {
+    let st = Rc::new(StructType {
+      name: None,
+      fields: RefCell::new(vec![Field { name: "s".to_string(), ty: Type::Slice(Rc::new(Type::Int)) }]),
+    });
+    if Type::Struct(st).comparable() || Type::Func.comparable() || !Type::any().comparable() {
+      panic!("wrong comparability");
+    }
+  }
+
+  #[test]
+  fn test_signature_display() {
+    let tests = [
+      (Signature::new(vec![], vec![]), "func()"),
+      (Signature::new(vec![Type::String], vec![Type::Int]), "func(string) int"),
+      (Signature::variadic(vec![Type::String, Type::any()], vec![Type::Int, Type::error()]), "func(string, ...interface {}) (int, error)"),
+    ];
+    for (sig, expected) in tests.iter() {
+      if sig.to_string() != *expected {
+        panic!("expected={:?}, got={:?}", expected, sig.to_string());
+      }
+    }
+  }
+}
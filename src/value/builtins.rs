@@ -0,0 +1,406 @@
+use crate::stdlib;
+use crate::types::Type;
+use crate::value::{convert, heap, new_var, Chan, Interface, Map, Place, Pointer, Root, Slice, Value};
+use std::cmp::Ordering;
+use std::fmt;
+
+pub type BuiltinFn = fn(Vec<Value>) -> Result<Value, String>;
+
+/// A function of the universe scope, implemented in Rust.
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub func: BuiltinFn,
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+/// Builtins by index, as `OpGetBuiltin` refers to them.
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "len", func: len },
+    Builtin { name: "cap", func: cap },
+    Builtin { name: "append", func: append },
+    Builtin { name: "make", func: make },
+    Builtin { name: "close", func: close },
+    Builtin { name: "panic", func: panic },
+    Builtin { name: "recover", func: recover },
+    Builtin { name: "copy", func: copy },
+    Builtin { name: "delete", func: delete },
+    Builtin { name: "new", func: new },
+    Builtin { name: "min", func: min },
+    Builtin { name: "max", func: max },
+    Builtin { name: "clear", func: clear },
+    Builtin { name: "print", func: print },
+    Builtin { name: "println", func: println },
+];
+
+/// Builtins whose first argument is a type, which the compiler passes
+/// as a value.
+pub const TYPE_ARGUMENT: &[&str] = &["make", "new"];
+
+pub fn lookup(name: &str) -> Option<usize> {
+    BUILTINS.iter().position(|b| b.name == name)
+}
+
+fn want_args(name: &str, args: &[Value], n: usize) -> Result<(), String> {
+    if args.len() < n {
+        return Err(format!("not enough arguments for {}: want {}, got {}", name, n, args.len()));
+    }
+    if args.len() > n {
+        return Err(format!("too many arguments for {}: want {}, got {}", name, n, args.len()));
+    }
+    Ok(())
+}
+
+fn invalid_argument(name: &str, arg: &Value) -> String {
+    format!("invalid argument: {} (type {}) for built-in {}", arg.inspect(), arg.ty(), name)
+}
+
+fn len(args: Vec<Value>) -> Result<Value, String> {
+    want_args("len", &args, 1)?;
+    let n = match &args[0] {
+        Value::String(s) => s.len(),
+        Value::Slice(s) => s.len,
+        Value::Map(m) => m.len(),
+        Value::Chan(c) => c.len(),
+        arg => return Err(invalid_argument("len", arg)),
+    };
+    Ok(Value::Integer(n as i64))
+}
+
+fn cap(args: Vec<Value>) -> Result<Value, String> {
+    want_args("cap", &args, 1)?;
+    match &args[0] {
+        Value::Slice(s) => Ok(Value::Integer(s.cap as i64)),
+        Value::Chan(c) => Ok(Value::Integer(c.cap() as i64)),
+        arg => Err(invalid_argument("cap", arg)),
+    }
+}
+
+fn append(mut args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err("not enough arguments for append: want at least 1, got 0".to_string());
+    }
+    let values = args.split_off(1);
+    match &args[0] {
+        Value::Slice(s) => Ok(Value::Slice(s.append(values)?)),
+        arg => Err(format!("invalid argument: {} (type {}) is not a slice", arg.inspect(), arg.ty())),
+    }
+}
+
+/// `append(s, rest...)`: appends the elements of a slice of the same
+/// type, read from its backing array, or the bytes of a string to a
+/// `[]byte`.
+pub fn append_spread(s: &Value, rest: &Value) -> Result<Value, String> {
+    let s = match s {
+        Value::Slice(s) => s,
+        _ => return Err(format!("invalid argument: {} (type {}) is not a slice", s.inspect(), s.ty())),
+    };
+    let values = match rest {
+        Value::Slice(r) if r.elem.identical(&s.elem) => r.values(),
+        Value::Null => vec![],
+        Value::String(bytes) if s.elem == Type::Byte => bytes.iter().map(|b| Value::Integer(*b as i64)).collect(),
+        _ => return Err(format!("cannot use {} (type {}) as []{} value in argument to append", rest.inspect(), rest.ty(), s.elem)),
+    };
+    Ok(Value::Slice(s.append(values)?))
+}
+
+/// The most `make` allocates, as Go's limit on a single allocation: 1<<48
+/// bytes on 64-bit platforms, and elsewhere the most a `Vec` can hold.
+#[cfg(target_pointer_width = "64")]
+const MAX_ALLOC: usize = 1 << 48;
+#[cfg(not(target_pointer_width = "64"))]
+const MAX_ALLOC: usize = isize::MAX as usize;
+
+/// Whether `n` slots fit in one allocation.
+fn fits(n: usize) -> bool {
+    n.checked_mul(heap::SLOT).is_some_and(|bytes| bytes <= MAX_ALLOC)
+}
+
+/// `make(T, sizes...)`. The compiler passes the type as a value.
+fn make(args: Vec<Value>) -> Result<Value, String> {
+    let ty = match args.first() {
+        Some(Value::Type(ty)) => ty.clone(),
+        Some(arg) => return Err(format!("{} is not a type", arg.inspect())),
+        None => return Err("not enough arguments for make: want at least 1, got 0".to_string()),
+    };
+    let sizes = args[1..]
+        .iter()
+        .map(|arg| match arg {
+            Value::Integer(n) if *n >= 0 => Ok(*n as usize),
+            Value::Integer(n) => Err(format!("invalid argument: index {} must not be negative", n)),
+            _ => Err(format!("cannot convert {} (type {}) to type int", arg.inspect(), arg.ty())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let (min, max) = if matches!(ty, Type::Slice(_)) { (1, 2) } else { (0, 1) };
+    if sizes.len() < min {
+        return Err(format!("invalid operation: make({}) expects {} or {} arguments; found {}", ty, min + 1, max + 1, args.len()));
+    }
+    if sizes.len() > max {
+        return Err(format!("invalid operation: make({}, ...) expects {} or {} arguments; found {}", ty, min + 1, max + 1, args.len()));
+    }
+    match &ty {
+        Type::Slice(elem) => {
+            let len = sizes[0];
+            let cap = sizes.get(1).copied().unwrap_or(len);
+            if len > cap {
+                return Err(format!("invalid argument: length and capacity swapped ({} > {})", len, cap));
+            }
+            if !fits(len) {
+                return Err("makeslice: len out of range".to_string());
+            }
+            if !fits(cap) {
+                return Err("makeslice: cap out of range".to_string());
+            }
+            heap::charge_slots(cap)?;
+            Ok(Value::Slice(Slice::make(elem.as_ref().clone(), len, cap)))
+        }
+        Type::Map(key, elem) => Ok(Value::Map(Map::new(key.as_ref().clone(), elem.as_ref().clone()))),
+        Type::Chan(elem) => {
+            let cap = sizes.first().copied().unwrap_or(0);
+            if !fits(cap) {
+                return Err("makechan: size out of range".to_string());
+            }
+            heap::charge_slots(cap)?;
+            Ok(Value::Chan(Chan::new(elem.as_ref().clone(), cap)))
+        }
+        _ => Err(format!("invalid argument: cannot make {}; type must be slice, map, or channel", ty)),
+    }
+}
+
+fn close(args: Vec<Value>) -> Result<Value, String> {
+    want_args("close", &args, 1)?;
+    match &args[0] {
+        Value::Chan(c) => {
+            c.close()?;
+            Ok(Value::Null)
+        }
+        arg => Err(format!("invalid operation: non-chan argument {} (type {}) for built-in close", arg.inspect(), arg.ty())),
+    }
+}
+
+/// `copy(dst, src)`: copies as many elements as both have and returns
+/// how many. A string copies its bytes into a `[]byte`.
+fn copy(args: Vec<Value>) -> Result<Value, String> {
+    want_args("copy", &args, 2)?;
+    let (dst, src) = (&args[0], &args[1]);
+    let d = match dst {
+        Value::Slice(d) => d,
+        _ => {
+            return Err(format!(
+                "invalid argument: copy expects slice arguments; found {} (type {}) and {} (type {})",
+                dst.inspect(),
+                dst.ty(),
+                src.inspect(),
+                src.ty()
+            ))
+        }
+    };
+    // read before writing, as the two may share a backing array
+    let values = match src {
+        Value::Slice(s) if s.elem.identical(&d.elem) => s.values(),
+        Value::String(bytes) if d.elem == Type::Byte => bytes.iter().map(|b| Value::Integer(*b as i64)).collect(),
+        Value::Slice(_) | Value::String(_) => {
+            let elem = match src {
+                Value::Slice(s) => s.elem.clone(),
+                _ => Type::Byte,
+            };
+            return Err(format!(
+                "invalid argument: arguments to copy {} (type {}) and {} (type {}) have different element types {} and {}",
+                dst.inspect(),
+                dst.ty(),
+                src.inspect(),
+                src.ty(),
+                d.elem,
+                elem
+            ));
+        }
+        _ => {
+            return Err(format!(
+                "invalid argument: copy expects slice arguments; found {} (type {}) and {} (type {})",
+                dst.inspect(),
+                dst.ty(),
+                src.inspect(),
+                src.ty()
+            ))
+        }
+    };
+    let n = d.len.min(values.len());
+    for (i, v) in values.into_iter().take(n).enumerate() {
+        d.set(i, v)?;
+    }
+    Ok(Value::Integer(n as i64))
+}
+
+/// `delete(m, key)`; deleting from a nil map does nothing.
+fn delete(args: Vec<Value>) -> Result<Value, String> {
+    want_args("delete", &args, 2)?;
+    match &args[0] {
+        Value::Map(m) => {
+            m.delete(&args[1])?;
+            Ok(Value::Null)
+        }
+        arg => Err(format!("invalid argument: {} (type {}) is not a map", arg.inspect(), arg.ty())),
+    }
+}
+
+/// `new(T)`: a pointer to a new zero value of type T.
+fn new(args: Vec<Value>) -> Result<Value, String> {
+    want_args("new", &args, 1)?;
+    match &args[0] {
+        Value::Type(ty) => {
+            heap::charge_slots(1)?;
+            let var = new_var(ty.zero());
+            heap::track_var(&var);
+            let place = Place { root: Root::Var(var), path: vec![] };
+            Ok(Value::Pointer(Pointer { elem: ty.clone(), target: Some(place) }))
+        }
+        arg => Err(format!("{} is not a type", arg.inspect())),
+    }
+}
+
+fn min(args: Vec<Value>) -> Result<Value, String> {
+    extreme("min", args, Ordering::Less)
+}
+
+fn max(args: Vec<Value>) -> Result<Value, String> {
+    extreme("max", args, Ordering::Greater)
+}
+
+/// The argument that compares `wanted` to all the others; the first of
+/// equal ones. Arguments must all be numbers or all strings; a NaN
+/// wins over everything, as in Go.
+fn extreme(name: &str, args: Vec<Value>, wanted: Ordering) -> Result<Value, String> {
+    let mut best = match args.first() {
+        Some(first) => first.clone(),
+        None => return Err(format!("not enough arguments for {}: want at least 1, got 0", name)),
+    };
+    for arg in &args {
+        let order = match (arg, &best) {
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Float(a), _) if a.is_nan() => return Ok(arg.clone()),
+            (Value::Float(_) | Value::Integer(_), Value::Float(_) | Value::Integer(_)) => {
+                let (a, b) = (float(arg), float(&best));
+                if b.is_nan() {
+                    continue;
+                }
+                a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+            }
+            (Value::Integer(_) | Value::String(_) | Value::Float(_), _) => {
+                return Err(format!("invalid argument: mismatched types {} and {}", best.ty(), arg.ty()))
+            }
+            _ => return Err(format!("invalid argument: {} (type {}) cannot be ordered", arg.inspect(), arg.ty())),
+        };
+        if order == wanted {
+            best = arg.clone();
+        }
+    }
+    Ok(best)
+}
+
+fn float(value: &Value) -> f64 {
+    match value {
+        Value::Float(f) => *f,
+        Value::Integer(i) => *i as f64,
+        _ => 0.0,
+    }
+}
+
+/// `clear(x)`: deletes every entry of a map, or zeroes the elements of
+/// a slice.
+fn clear(args: Vec<Value>) -> Result<Value, String> {
+    want_args("clear", &args, 1)?;
+    match &args[0] {
+        Value::Map(m) => m.clear(),
+        Value::Slice(s) => {
+            for i in 0..s.len {
+                s.set(i, s.elem.zero())?;
+            }
+        }
+        arg => return Err(format!("invalid argument: {} (type {}) must be a map or slice", arg.inspect(), arg.ty())),
+    }
+    Ok(Value::Null)
+}
+
+/// `print(args...)`: writes the arguments to standard error with nothing
+/// between them.
+fn print(args: Vec<Value>) -> Result<Value, String> {
+    let text = print_operands("print", &args)?;
+    stdlib::write_stderr(text.concat().as_bytes())?;
+    Ok(Value::Null)
+}
+
+/// `println(args...)`: writes the arguments to standard error separated
+/// by spaces, then a newline.
+fn println(args: Vec<Value>) -> Result<Value, String> {
+    let text = print_operands("println", &args)?;
+    stdlib::write_stderr(format!("{}\n", text.join(" ")).as_bytes())?;
+    Ok(Value::Null)
+}
+
+/// The operands of print and println as they print them: basic values
+/// as themselves, references as addresses.
+fn print_operands(name: &str, args: &[Value]) -> Result<Vec<String>, String> {
+    args.iter()
+        .map(|arg| match arg {
+            Value::Integer(_) | Value::String(_) | Value::Boolean(_) | Value::Null => Ok(arg.inspect()),
+            Value::Float(f) => Ok(print_float(*f)),
+            Value::Pointer(p) => Ok(format!("0x{:x}", p.target.as_ref().map_or(0, |place| place.address()))),
+            Value::Chan(c) => Ok(format!("0x{:x}", c.address())),
+            Value::Map(m) => Ok(format!("0x{:x}", m.table.as_ref().map_or(0, |t| std::rc::Rc::as_ptr(t) as usize))),
+            Value::Interface(i) => match &i.value {
+                Some(v) => Ok(format!("({}) {}", v.type_name(), v.inspect())),
+                None => Ok("(0x0,0x0)".to_string()),
+            },
+            _ => Err(format!("illegal types for operand: {} {} (type {})", name, arg.inspect(), arg.ty())),
+        })
+        .collect()
+}
+
+/// `panic(v)` where no goroutine unwinds, as for `go panic(v)`. The VM
+/// handles ordinary calls itself.
+fn panic(args: Vec<Value>) -> Result<Value, String> {
+    want_args("panic", &args, 1)?;
+    Err(panic_message(&args[0]))
+}
+
+/// `recover()` other than directly in a deferred function, which never
+/// stops a panic.
+fn recover(args: Vec<Value>) -> Result<Value, String> {
+    want_args("recover", &args, 0)?;
+    convert(Value::Null, &Type::any())
+}
+
+/// A float as print and println write it: `+1.500000e+000`.
+pub fn print_float(f: f64) -> String {
+    if f.is_nan() {
+        return "NaN".to_string();
+    }
+    if f.is_infinite() {
+        return if f > 0.0 { "+Inf" } else { "-Inf" }.to_string();
+    }
+    let sci = format!("{:.6e}", f.abs());
+    let (mantissa, exp) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let exp: i32 = exp.parse().unwrap_or(0);
+    let sign = if f.is_sign_negative() { '-' } else { '+' };
+    format!("{}{}e{}{:03}", sign, mantissa, if exp < 0 { '-' } else { '+' }, exp.abs())
+}
+
+/// How a goroutine that dies of a panic reports its value: basic values
+/// as themselves, errors by their message, others after their type.
+pub fn panic_message(value: &Value) -> String {
+    if let Some(msg) = crate::stdlib::errors::message(value) {
+        return msg;
+    }
+    match value {
+        Value::Interface(Interface { value: Some(v), .. }) => panic_message(v),
+        Value::Float(f) => print_float(*f),
+        Value::String(_) | Value::Integer(_) | Value::Boolean(_) => value.inspect(),
+        _ => format!("({}) {}", value.type_name(), value.inspect()),
+    }
+}
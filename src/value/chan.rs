@@ -0,0 +1,240 @@
+use crate::types::Type;
+use crate::value::{convert, heap, Value};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::rc::Rc;
+
+/// A channel: its element type and the state the goroutines using it
+/// share.
+#[derive(Clone)]
+pub struct Chan {
+    pub elem: Type,
+    /// None for a nil channel, on which sends and receives block forever.
+    pub state: Option<Rc<RefCell<ChanState>>>,
+}
+
+/// Buffered values and the goroutines blocked on a channel. Values move
+/// from a sender straight to a waiting receiver, so the buffer is never
+/// non-empty while receivers wait.
+pub struct ChanState {
+    cap: usize,
+    buffer: VecDeque<Value>,
+    closed: bool,
+    recvq: VecDeque<Waiter>,
+    sendq: VecDeque<(Waiter, Value)>,
+}
+
+/// Where a blocked goroutine waits to be woken. A select registers the
+/// same `wake` on every channel it waits on; once one of them fills it,
+/// the others find it taken and skip the waiter.
+#[derive(Clone)]
+pub struct Waiter {
+    /// Index of the select case, 0 outside a select.
+    pub case: usize,
+    pub wake: Rc<RefCell<Option<Wakeup>>>,
+}
+
+/// What woke a goroutine: the case that completed, the value received,
+/// and false when the channel was closed.
+#[derive(Debug, Clone)]
+pub struct Wakeup {
+    pub case: usize,
+    pub value: Value,
+    pub ok: bool,
+}
+
+impl Waiter {
+    pub fn new(case: usize, wake: &Rc<RefCell<Option<Wakeup>>>) -> Waiter {
+        Waiter { case, wake: Rc::clone(wake) }
+    }
+
+    fn is_stale(&self) -> bool {
+        self.wake.borrow().is_some()
+    }
+
+    fn wake(&self, value: Value, ok: bool) {
+        *self.wake.borrow_mut() = Some(Wakeup { case: self.case, value, ok });
+    }
+}
+
+impl ChanState {
+    pub(crate) fn held(&self) -> impl Iterator<Item = &Value> {
+        self.buffer.iter().chain(self.sendq.iter().map(|(_, v)| v))
+    }
+
+    /// Takes out the values the channel holds, leaving nil in place of
+    /// those senders wait with, for the collector to free a channel no
+    /// goroutine can reach.
+    pub(crate) fn take_held(&mut self) -> Vec<Value> {
+        let mut values: Vec<Value> = self.buffer.drain(..).collect();
+        values.extend(self.sendq.iter_mut().map(|(_, v)| std::mem::replace(v, Value::Null)));
+        values
+    }
+}
+
+impl Chan {
+    pub fn nil(elem: Type) -> Chan {
+        Chan { elem, state: None }
+    }
+
+    /// `make(chan T, cap)`.
+    pub fn new(elem: Type, cap: usize) -> Chan {
+        let state = ChanState {
+            cap,
+            buffer: VecDeque::new(),
+            closed: false,
+            recvq: VecDeque::new(),
+            sendq: VecDeque::new(),
+        };
+        let state = Rc::new(RefCell::new(state));
+        heap::track_chan(&state);
+        Chan { elem, state: Some(state) }
+    }
+
+    /// Number of values in the buffer.
+    pub fn len(&self) -> usize {
+        self.state.as_ref().map_or(0, |s| s.borrow().buffer.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn cap(&self) -> usize {
+        self.state.as_ref().map_or(0, |s| s.borrow().cap)
+    }
+
+    /// The values the channel holds: those in the buffer and those
+    /// blocked senders are waiting to send.
+    pub fn held(&self) -> Vec<Value> {
+        match &self.state {
+            Some(state) => state.borrow().held().cloned().collect(),
+            None => vec![],
+        }
+    }
+
+    /// Sends `value` if that can be done without blocking: to a waiting
+    /// receiver, else into the buffer. Reports whether it was sent.
+    pub fn try_send(&self, value: Value) -> Result<bool, String> {
+        let value = convert(value, &self.elem)?;
+        let state = match &self.state {
+            Some(state) => state,
+            None => return Ok(false),
+        };
+        let mut state = state.borrow_mut();
+        if state.closed {
+            return Err("send on closed channel".to_string());
+        }
+        while let Some(receiver) = state.recvq.pop_front() {
+            if !receiver.is_stale() {
+                receiver.wake(value, true);
+                return Ok(true);
+            }
+        }
+        if state.buffer.len() < state.cap {
+            state.buffer.push_back(value);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Receives a value if that can be done without blocking. The flag is
+    /// false for the zero value a closed, drained channel gives.
+    pub fn try_recv(&self) -> Option<(Value, bool)> {
+        let mut state = self.state.as_ref()?.borrow_mut();
+        let sender = loop {
+            match state.sendq.pop_front() {
+                Some((sender, _)) if sender.is_stale() => continue,
+                sender => break sender,
+            }
+        };
+        if let Some(value) = state.buffer.pop_front() {
+            // a blocked sender takes the place freed in the buffer
+            if let Some((sender, v)) = sender {
+                state.buffer.push_back(v);
+                sender.wake(Value::Null, true);
+            }
+            return Some((value, true));
+        }
+        if let Some((sender, value)) = sender {
+            sender.wake(Value::Null, true);
+            return Some((value, true));
+        }
+        if state.closed {
+            return Some((self.elem.zero(), false));
+        }
+        None
+    }
+
+    /// Blocks `waiter` until a value can be sent. It is woken with `ok`
+    /// false if the channel is closed first.
+    pub fn wait_send(&self, waiter: Waiter, value: Value) -> Result<(), String> {
+        let value = convert(value, &self.elem)?;
+        if let Some(state) = &self.state {
+            state.borrow_mut().sendq.push_back((waiter, value));
+        }
+        Ok(())
+    }
+
+    /// Blocks `waiter` until a value arrives or the channel is closed.
+    pub fn wait_recv(&self, waiter: Waiter) {
+        if let Some(state) = &self.state {
+            state.borrow_mut().recvq.push_back(waiter);
+        }
+    }
+
+    /// `close(ch)`: wakes every blocked receiver with the zero value, and
+    /// every blocked sender to panic.
+    pub fn close(&self) -> Result<(), String> {
+        let state = match &self.state {
+            Some(state) => state,
+            None => return Err("close of nil channel".to_string()),
+        };
+        let mut state = state.borrow_mut();
+        if state.closed {
+            return Err("close of closed channel".to_string());
+        }
+        state.closed = true;
+        for receiver in state.recvq.drain(..) {
+            if !receiver.is_stale() {
+                receiver.wake(self.elem.zero(), false);
+            }
+        }
+        for (sender, _) in state.sendq.drain(..) {
+            if !sender.is_stale() {
+                sender.wake(Value::Null, false);
+            }
+        }
+        Ok(())
+    }
+
+    /// An address for printing and hashing; 0 for a nil channel.
+    pub fn address(&self) -> usize {
+        self.state.as_ref().map_or(0, |s| Rc::as_ptr(s) as usize)
+    }
+}
+
+impl Drop for Chan {
+    fn drop(&mut self) {
+        if let Some(state) = &self.state {
+            heap::release_chan(state);
+        }
+    }
+}
+
+impl PartialEq for Chan {
+    fn eq(&self, other: &Chan) -> bool {
+        self.address() == other.address()
+    }
+}
+
+// Written by hand: buffered values may refer back to the channel.
+impl fmt::Debug for Chan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.state {
+            Some(_) => write!(f, "Chan(chan {} 0x{:x})", self.elem, self.address()),
+            None => write!(f, "Chan(chan {} nil)", self.elem),
+        }
+    }
+}
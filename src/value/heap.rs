@@ -0,0 +1,634 @@
+//! The memory a run's values take up, and the collector that frees what
+//! counting references cannot.
+//!
+//! Memory counts against the limit `vm::Limits` puts on it. Allocations
+//! are charged as they are made, before the memory is taken, so one too
+//! large for the limit fails without taking it. Values are reference
+//! counted and go away without telling anyone, so once the charges say
+//! the limit may have been passed the VM measures what is still
+//! reachable with a `Tracer`, and only that counts.
+//!
+//! Counting references does not free values that refer to each other in
+//! a cycle, as a struct pointing to itself does, or a closure stored in a
+//! variable it captures. Cycles run through shared storage: that of
+//! variables pointers or closures refer to, of slices, maps and channels,
+//! and closures themselves. Those objects are tracked as they are made,
+//! and a collection marks the ones the VM's roots reach: its globals and
+//! the stacks of its goroutines. Of the rest, one something outside them
+//! refers to, as the embedding program may hold a value, is kept with
+//! all it refers to. What is left only the cycles keep alive, and
+//! emptying it breaks them, so that counting references frees it.
+//!
+//! Nor can counting references free a long chain, as a linked list is,
+//! by dropping each value inside the one that held it: that would take
+//! as much stack as the chain is long. The last reference to storage
+//! empties it instead, and what it held is `release`d a value at a time.
+
+use crate::value::chan::ChanState;
+use crate::value::{Array, Closure, Key, MapTable, Place, Root, Value, Var};
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant};
+
+/// Bytes a value takes up in a slot: of a slice's backing array, a map
+/// entry, a struct field, a variable.
+pub const SLOT: usize = mem::size_of::<Value>();
+
+/// Bytes allocated before the first collection, and at least between
+/// any two, as Go's minimum heap size.
+const MIN_HEAP: usize = 4 << 20;
+
+/// Objects tracked before the first collection, and at least between
+/// any two. Variables and closures are not charged for, so counting
+/// bytes alone would let them pile up.
+const MIN_OBJECTS: usize = 1 << 14;
+
+/// What the run on this thread has allocated, and may.
+#[derive(Default)]
+pub struct Account {
+    pub limit: Option<usize>,
+    /// Bytes reachable when last measured.
+    pub live: usize,
+    /// Bytes allocated since.
+    pub allocated: usize,
+    /// An allocation went past the limit and was refused.
+    pub refused: bool,
+    /// Objects are tracked for collection, as they are in a VM's account
+    /// but not in the one allocations outside a run are charged to.
+    pub collecting: bool,
+    /// Collect after every allocation, to flush out code that depends on
+    /// when collections happen.
+    pub stress: bool,
+    /// Tracked objects by address, which the weak reference keeps from
+    /// being reused while the object is tracked.
+    objects: HashMap<usize, Object>,
+    /// Objects tracked since the last collection.
+    tracked: usize,
+    /// Objects still tracked after it.
+    kept: usize,
+    stats: Stats,
+}
+
+impl Account {
+    /// The account of a run, which tracks objects for collection.
+    pub fn for_run() -> Account {
+        Account { collecting: true, ..Account::default() }
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats { heap_alloc: (self.live + self.allocated) as u64, ..self.stats }
+    }
+}
+
+/// What a run has allocated and collected, as `runtime.ReadMemStats`
+/// reports it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    /// Bytes reachable when last measured, and allocated since.
+    pub heap_alloc: u64,
+    /// Bytes allocated over the run, freed or not.
+    pub total_alloc: u64,
+    /// Objects tracked for collection.
+    pub mallocs: u64,
+    /// Tracked objects that have been freed, by a collection or as the
+    /// last reference to them went.
+    pub frees: u64,
+    /// Collections run.
+    pub num_gc: u64,
+    /// Time collections took. wasm32 has no clock, so there it is zero.
+    pub pause_total: Duration,
+}
+
+thread_local! {
+    static ACCOUNT: RefCell<Account> = RefCell::new(Account::default());
+    /// Values waiting to be dropped while `release` drops others.
+    static RELEASED: RefCell<Option<Vec<Value>>> = const { RefCell::new(None) };
+}
+
+/// Makes `account` the one allocations are charged to, returning the one
+/// they were, for a VM to switch to its own while it runs.
+pub fn swap(account: Account) -> Account {
+    ACCOUNT.with(|a| a.replace(account))
+}
+
+/// Charges an allocation of `bytes`, or refuses it when it would take
+/// the memory in use past the limit.
+pub fn charge(bytes: usize) -> Result<(), String> {
+    ACCOUNT.with(|a| {
+        let mut account = a.borrow_mut();
+        if let Some(limit) = account.limit {
+            if account.live.saturating_add(bytes) > limit {
+                account.refused = true;
+                return Err(format!("out of memory: heap limit of {} bytes exceeded", limit));
+            }
+        }
+        account.allocated = account.allocated.saturating_add(bytes);
+        account.stats.total_alloc = account.stats.total_alloc.saturating_add(bytes as u64);
+        Ok(())
+    })
+}
+
+/// Reports whether an allocation was refused, which ends the run.
+pub fn refused() -> bool {
+    ACCOUNT.with(|a| a.borrow().refused)
+}
+
+/// Charges `n` slots.
+pub fn charge_slots(n: usize) -> Result<(), String> {
+    charge(n.saturating_mul(SLOT))
+}
+
+/// The statistics of the account allocations are charged to.
+pub fn stats() -> Stats {
+    ACCOUNT.with(|a| a.borrow().stats())
+}
+
+/// Reports whether it is time to collect: enough has been allocated
+/// since the last collection that it pays to, or that the limit may have
+/// been passed. Both take time in proportion to what is reachable, so
+/// collections wait for the heap to double, and measures for at least a
+/// sixteenth of the limit.
+pub fn due() -> bool {
+    ACCOUNT.with(|a| {
+        let account = a.borrow();
+        if account.stress {
+            return account.allocated > 0 || account.tracked > 0;
+        }
+        let limited = account.limit.is_some_and(|limit| account.allocated >= limit.saturating_sub(account.live).max(limit / 16));
+        let grown = account.allocated >= account.live.max(MIN_HEAP) || account.tracked >= account.kept.max(MIN_OBJECTS);
+        limited || account.collecting && grown
+    })
+}
+
+/// Records that `live` bytes are reachable, and reports whether that is
+/// within the limit.
+pub fn measured(live: usize) -> bool {
+    ACCOUNT.with(|a| {
+        let mut account = a.borrow_mut();
+        account.live = live;
+        account.allocated = 0;
+        account.limit.is_none_or(|limit| live <= limit)
+    })
+}
+
+fn address<T: ?Sized>(ptr: *const T) -> usize {
+    ptr as *const u8 as usize
+}
+
+fn track(addr: usize, object: impl FnOnce() -> Object) {
+    ACCOUNT.with(|a| {
+        let account = &mut *a.borrow_mut();
+        if !account.collecting {
+            return;
+        }
+        if let Entry::Vacant(entry) = account.objects.entry(addr) {
+            entry.insert(object());
+            account.tracked += 1;
+            account.stats.mallocs += 1;
+        }
+    })
+}
+
+/// Tracks a variable a pointer or closure is about to refer to.
+pub fn track_var(var: &Var) {
+    track(address(Rc::as_ptr(var)), || Object::Var(Rc::downgrade(var)));
+}
+
+/// Tracks the backing array of a slice.
+pub fn track_array(array: &Array) {
+    track(address(Rc::as_ptr(array)), || Object::Array(Rc::downgrade(array)));
+}
+
+pub(crate) fn track_map(table: &Rc<RefCell<MapTable>>) {
+    track(address(Rc::as_ptr(table)), || Object::Map(Rc::downgrade(table)));
+}
+
+pub(crate) fn track_chan(state: &Rc<RefCell<ChanState>>) {
+    track(address(Rc::as_ptr(state)), || Object::Chan(Rc::downgrade(state)));
+}
+
+pub fn track_closure(closure: &Rc<Closure>) {
+    track(address(Rc::as_ptr(closure)), || Object::Closure(Rc::downgrade(closure)));
+}
+
+/// Frees the tracked objects only cycles keep alive, given the ones the
+/// roots reach, which `marked` has traced. Returns how many it freed.
+pub fn collect(marked: &Tracer) -> usize {
+    let start = if cfg!(target_arch = "wasm32") { None } else { Some(Instant::now()) };
+    let mut objects = ACCOUNT.with(|a| mem::take(&mut a.borrow_mut().objects));
+    let unmarked: Vec<Held> =
+        objects.values().filter_map(Object::upgrade).filter(|o| !marked.seen.contains(&o.address())).collect();
+    let index: HashMap<usize, usize> = unmarked.iter().enumerate().map(|(i, o)| (o.address(), i)).collect();
+
+    // an object more references go to than come from the unmarked, one
+    // of them `unmarked`'s own, is referred to from elsewhere
+    let mut inner = vec![0; unmarked.len()];
+    for object in &unmarked {
+        object.refs(&mut |addr| {
+            if let Some(&i) = index.get(&addr) {
+                inner[i] += 1;
+            }
+        });
+    }
+    let mut kept: Vec<bool> = unmarked.iter().zip(&inner).map(|(o, &inner)| o.count() > inner + 1).collect();
+    let mut work: Vec<usize> = (0..unmarked.len()).filter(|&i| kept[i]).collect();
+    while let Some(i) = work.pop() {
+        unmarked[i].refs(&mut |addr| {
+            if let Some(&j) = index.get(&addr) {
+                if !kept[j] {
+                    kept[j] = true;
+                    work.push(j);
+                }
+            }
+        });
+    }
+
+    // every object of a cycle is emptied before any is dropped, so what
+    // they held goes without dropping the rest of the cycle in turn
+    let mut trash = vec![];
+    let mut freed = 0;
+    for (object, _) in unmarked.iter().zip(&kept).filter(|(_, &kept)| !kept) {
+        object.clear(&mut trash);
+        freed += 1;
+    }
+    drop(trash);
+    drop(unmarked);
+
+    let before = objects.len();
+    objects.retain(|_, o| o.alive());
+    ACCOUNT.with(|a| {
+        let account = &mut *a.borrow_mut();
+        account.stats.frees += (before - objects.len()) as u64;
+        objects.extend(account.objects.drain());
+        account.objects = objects;
+        account.tracked = 0;
+        account.kept = account.objects.len();
+        account.stats.num_gc += 1;
+        if let Some(start) = start {
+            account.stats.pause_total += start.elapsed();
+        }
+    });
+    freed
+}
+
+/// Drops `values` without dropping what they hold inside their drops.
+/// Dropping a value releases what it held in turn, which waits in a list
+/// until the value is gone, so the stack stays flat however deep a chain
+/// of values goes.
+pub(crate) fn release(values: Vec<Value>) {
+    if !values.iter().any(holds_storage) {
+        return;
+    }
+    let mut work = match RELEASED.try_with(|r| {
+        let mut released = r.borrow_mut();
+        match &mut *released {
+            Some(waiting) => {
+                waiting.extend(values);
+                None
+            }
+            None => {
+                *released = Some(vec![]);
+                Some(values)
+            }
+        }
+    }) {
+        Ok(Some(work)) => work,
+        // already releasing, or the thread is going away
+        Ok(None) | Err(_) => return,
+    };
+    loop {
+        while let Some(value) = work.pop() {
+            drop(value);
+        }
+        work = RELEASED.with(|r| mem::take(r.borrow_mut().as_mut().unwrap()));
+        if work.is_empty() {
+            break;
+        }
+    }
+    RELEASED.with(|r| *r.borrow_mut() = None);
+}
+
+/// `release` of a single value.
+pub(crate) fn release_value(value: Value) {
+    if holds_storage(&value) {
+        release(vec![value]);
+    }
+}
+
+pub(crate) fn release_var(var: &Var) {
+    if Rc::strong_count(var) == 1 {
+        release_last(Held::Var(Rc::clone(var)));
+    }
+}
+
+pub(crate) fn release_array(array: &Array) {
+    if Rc::strong_count(array) == 1 {
+        release_last(Held::Array(Rc::clone(array)));
+    }
+}
+
+pub(crate) fn release_map(table: &Rc<RefCell<MapTable>>) {
+    if Rc::strong_count(table) == 1 {
+        release_last(Held::Map(Rc::clone(table)));
+    }
+}
+
+pub(crate) fn release_chan(state: &Rc<RefCell<ChanState>>) {
+    if Rc::strong_count(state) == 1 {
+        release_last(Held::Chan(Rc::clone(state)));
+    }
+}
+
+/// Empties storage whose last reference, besides `held`'s own, is going
+/// away, and releases what it held.
+fn release_last(held: Held) {
+    let mut trash = vec![];
+    held.clear(&mut trash);
+    release(trash);
+}
+
+/// Whether dropping the value may drop other values, which `release`
+/// then has to keep from recursing.
+fn holds_storage(value: &Value) -> bool {
+    match value {
+        Value::Slice(s) => s.array.is_some(),
+        Value::Map(m) => m.table.is_some(),
+        Value::Struct(_) | Value::Closure(_) | Value::Method(_) | Value::Tuple(_) | Value::Iterator(_) => true,
+        Value::Pointer(p) => p.target.is_some(),
+        Value::Interface(i) => i.value.is_some(),
+        Value::Chan(c) => c.state.is_some(),
+        _ => false,
+    }
+}
+
+/// A tracked object, which tracking does not keep alive.
+enum Object {
+    Var(Weak<RefCell<Value>>),
+    Array(Weak<RefCell<Vec<Value>>>),
+    Map(Weak<RefCell<MapTable>>),
+    Chan(Weak<RefCell<ChanState>>),
+    Closure(Weak<Closure>),
+}
+
+impl Object {
+    fn alive(&self) -> bool {
+        match self {
+            Object::Var(w) => w.strong_count() > 0,
+            Object::Array(w) => w.strong_count() > 0,
+            Object::Map(w) => w.strong_count() > 0,
+            Object::Chan(w) => w.strong_count() > 0,
+            Object::Closure(w) => w.strong_count() > 0,
+        }
+    }
+
+    fn upgrade(&self) -> Option<Held> {
+        match self {
+            Object::Var(w) => w.upgrade().map(Held::Var),
+            Object::Array(w) => w.upgrade().map(Held::Array),
+            Object::Map(w) => w.upgrade().map(Held::Map),
+            Object::Chan(w) => w.upgrade().map(Held::Chan),
+            Object::Closure(w) => w.upgrade().map(Held::Closure),
+        }
+    }
+}
+
+/// A tracked object a collection holds on to while it looks at it.
+enum Held {
+    Var(Var),
+    Array(Array),
+    Map(Rc<RefCell<MapTable>>),
+    Chan(Rc<RefCell<ChanState>>),
+    Closure(Rc<Closure>),
+}
+
+impl Held {
+    fn address(&self) -> usize {
+        match self {
+            Held::Var(rc) => address(Rc::as_ptr(rc)),
+            Held::Array(rc) => address(Rc::as_ptr(rc)),
+            Held::Map(rc) => address(Rc::as_ptr(rc)),
+            Held::Chan(rc) => address(Rc::as_ptr(rc)),
+            Held::Closure(rc) => address(Rc::as_ptr(rc)),
+        }
+    }
+
+    /// References to the object.
+    fn count(&self) -> usize {
+        match self {
+            Held::Var(rc) => Rc::strong_count(rc),
+            Held::Array(rc) => Rc::strong_count(rc),
+            Held::Map(rc) => Rc::strong_count(rc),
+            Held::Chan(rc) => Rc::strong_count(rc),
+            Held::Closure(rc) => Rc::strong_count(rc),
+        }
+    }
+
+    /// Calls `f` with the address of what each reference the object holds
+    /// refers to. References it holds through values that share storage
+    /// without it being tracked, such as tuples, are left out, so what
+    /// they refer to looks referred to from elsewhere and is kept.
+    fn refs(&self, f: &mut dyn FnMut(usize)) {
+        match self {
+            Held::Var(var) => {
+                if let Ok(value) = var.try_borrow() {
+                    refs(&value, f);
+                }
+            }
+            Held::Array(array) => {
+                if let Ok(values) = array.try_borrow() {
+                    values.iter().for_each(|v| refs(v, f));
+                }
+            }
+            Held::Map(table) => {
+                if let Ok(table) = table.try_borrow() {
+                    for (k, v) in table.entries.iter().flatten() {
+                        refs(k, f);
+                        refs(v, f);
+                    }
+                    table.index.keys().for_each(|key| key_refs(key, f));
+                }
+            }
+            Held::Chan(state) => {
+                if let Ok(state) = state.try_borrow() {
+                    state.held().for_each(|v| refs(v, f));
+                }
+            }
+            Held::Closure(cl) => cl.free.iter().for_each(|var| f(address(Rc::as_ptr(var)))),
+        }
+    }
+
+    /// Empties the object, moving what it held to `trash`.
+    fn clear(&self, trash: &mut Vec<Value>) {
+        match self {
+            Held::Var(var) => {
+                if let Ok(mut value) = var.try_borrow_mut() {
+                    trash.push(mem::replace(&mut *value, Value::Null));
+                }
+            }
+            Held::Array(array) => {
+                if let Ok(mut values) = array.try_borrow_mut() {
+                    trash.append(&mut values);
+                }
+            }
+            Held::Map(table) => {
+                if let Ok(mut table) = table.try_borrow_mut() {
+                    let table = mem::take(&mut *table);
+                    trash.extend(table.entries.into_iter().flatten().flat_map(|(k, v)| vec![k, v]));
+                }
+            }
+            Held::Chan(state) => {
+                if let Ok(mut state) = state.try_borrow_mut() {
+                    trash.extend(state.take_held());
+                }
+            }
+            Held::Closure(_) => {}
+        }
+    }
+}
+
+fn refs(value: &Value, f: &mut dyn FnMut(usize)) {
+    match value {
+        Value::Slice(s) => s.array.iter().for_each(|a| f(address(Rc::as_ptr(a)))),
+        Value::Map(m) => m.table.iter().for_each(|t| f(address(Rc::as_ptr(t)))),
+        Value::Chan(c) => c.state.iter().for_each(|s| f(address(Rc::as_ptr(s)))),
+        Value::Closure(cl) => f(address(Rc::as_ptr(cl))),
+        Value::Pointer(p) => p.target.iter().for_each(|place| place_refs(place, f)),
+        Value::Struct(s) => s.fields.iter().for_each(|v| refs(v, f)),
+        Value::Interface(i) => i.value.iter().for_each(|v| refs(v, f)),
+        _ => {}
+    }
+}
+
+fn place_refs(place: &Place, f: &mut dyn FnMut(usize)) {
+    match &place.root {
+        Root::Var(var) => f(address(Rc::as_ptr(var))),
+        Root::Elem(array, _) => f(address(Rc::as_ptr(array))),
+    }
+}
+
+fn key_refs(key: &Key, f: &mut dyn FnMut(usize)) {
+    match key {
+        Key::Ptr(place) => place_refs(place, f),
+        Key::Struct(keys) => keys.iter().for_each(|k| key_refs(k, f)),
+        Key::Iface(Some((_, key))) => key_refs(key, f),
+        _ => {}
+    }
+}
+
+/// Sums the bytes values take up, counting storage several of them share
+/// once. Values are visited from a work list, so long chains of pointers
+/// do not run the Rust stack out.
+#[derive(Default)]
+pub struct Tracer {
+    seen: HashSet<usize>,
+    work: Vec<Value>,
+    pub bytes: usize,
+}
+
+impl Tracer {
+    pub fn new() -> Tracer {
+        Tracer::default()
+    }
+
+    /// Reports whether the storage at `ptr` is seen for the first time.
+    fn first<T: ?Sized>(&mut self, ptr: *const T) -> bool {
+        self.seen.insert(address(ptr))
+    }
+
+    /// Adds the storage `value` refers to, and what that refers to in turn.
+    pub fn value(&mut self, value: &Value) {
+        self.work.push(value.clone());
+        self.drain();
+    }
+
+    /// Adds a variable and what its value refers to.
+    pub fn var(&mut self, var: &Var) {
+        self.push_var(var);
+        self.drain();
+    }
+
+    fn drain(&mut self) {
+        while let Some(value) = self.work.pop() {
+            self.visit(value);
+        }
+    }
+
+    fn push_var(&mut self, var: &Var) {
+        if self.first(Rc::as_ptr(var)) {
+            self.bytes += SLOT;
+            // a variable borrowed for writing is being stored to, and
+            // holds nothing yet worth counting
+            if let Ok(value) = var.try_borrow() {
+                self.work.push(value.clone());
+            }
+        }
+    }
+
+    fn visit(&mut self, value: Value) {
+        match value {
+            Value::String(s) if self.first(Rc::as_ptr(&s)) => self.bytes += s.len(),
+            Value::Slice(s) => {
+                if let Some(array) = &s.array {
+                    self.array(array);
+                }
+            }
+            Value::Map(m) => {
+                if let Some(table) = &m.table {
+                    if self.first(Rc::as_ptr(table)) {
+                        let table = table.borrow();
+                        self.bytes += table.entries.capacity() * 2 * SLOT;
+                        for (k, v) in table.entries.iter().flatten() {
+                            self.work.push(k.clone());
+                            self.work.push(v.clone());
+                        }
+                    }
+                }
+            }
+            Value::Struct(mut s) => {
+                self.bytes += s.fields.len() * SLOT;
+                self.work.append(&mut s.fields);
+            }
+            Value::Pointer(p) => match p.target.as_ref().map(|place| &place.root) {
+                Some(Root::Var(var)) => self.push_var(var),
+                Some(Root::Elem(array, _)) => self.array(array),
+                _ => {}
+            },
+            Value::Interface(mut i) => {
+                if let Some(v) = i.value.take() {
+                    self.bytes += SLOT;
+                    self.work.push(*v);
+                }
+            }
+            Value::Chan(c) => {
+                if let Some(state) = &c.state {
+                    if self.first(Rc::as_ptr(state)) {
+                        let held = c.held();
+                        self.bytes = self.bytes.saturating_add(c.cap().saturating_add(1).saturating_mul(SLOT));
+                        self.work.extend(held);
+                    }
+                }
+            }
+            Value::Closure(cl) if self.first(Rc::as_ptr(&cl)) => {
+                self.bytes += cl.free.len() * mem::size_of::<Var>();
+                for var in &cl.free {
+                    self.push_var(var);
+                }
+            }
+            Value::Method(m) => self.work.push(m.recv.clone()),
+            Value::Tuple(values) => self.work.extend(values.iter().cloned()),
+            Value::Opaque(o) if self.first(Rc::as_ptr(&o.data)) => self.bytes += mem::size_of_val(o.data.as_ref()),
+            _ => {}
+        }
+    }
+
+    fn array(&mut self, array: &Array) {
+        if self.first(Rc::as_ptr(array)) {
+            let values = array.borrow();
+            self.bytes += values.capacity() * SLOT;
+            self.work.extend(values.iter().cloned());
+        }
+    }
+}
@@ -0,0 +1,1112 @@
+use crate::code::{Instructions, SourcePos};
+use crate::types::{InterfaceType, StructType, Type};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::rc::Rc;
+
+pub mod builtins;
+mod chan;
+pub mod heap;
+
+pub use chan::{Chan, Waiter, Wakeup};
+
+#[cfg(test)]
+mod test;
+
+/// A variable: a cell values are stored in, shared by everything that
+/// refers to the variable (closures, pointers).
+pub type Var = Rc<RefCell<Value>>;
+
+/// The backing array of one or more slices.
+pub type Array = Rc<RefCell<Vec<Value>>>;
+
+pub fn new_var(value: Value) -> Var {
+    Rc::new(RefCell::new(value))
+}
+
+/// A runtime value. Strings, slices, maps, pointers and channels are references
+/// into shared storage; structs are copied along with the value.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    /// The untyped `nil`, and the nil of function types.
+    Null,
+    /// Go strings are immutable byte sequences, not necessarily UTF-8.
+    String(Rc<[u8]>),
+    Slice(Slice),
+    Map(Map),
+    Struct(Box<Struct>),
+    Pointer(Pointer),
+    Interface(Interface),
+    Chan(Chan),
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    Builtin(builtins::Builtin),
+    /// A function of the program embedding the interpreter.
+    Host(Rc<Host>),
+    /// A value of a Rust type the embedding program exposes to scripts.
+    Opaque(Opaque),
+    /// A method of a native type bound to its receiver, such as `err.Error`.
+    Method(Rc<Method>),
+    /// The results of a call to a function with more than one, which
+    /// `OpUnpack` spreads over the stack.
+    Tuple(Rc<[Value]>),
+    /// A type in the constant pool, for the instructions that need one.
+    Type(Type),
+    /// State of a `for range` loop.
+    Iterator(Rc<RefCell<RangeIter>>),
+}
+
+/// A function the program embedding the interpreter registers, which
+/// scripts call as any other; see `Interpreter::register`.
+pub struct Host {
+    pub name: String,
+    pub func: Box<dyn Fn(Vec<Value>) -> Result<Value, String>>,
+}
+
+impl fmt::Debug for Host {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Host({})", self.name)
+    }
+}
+
+/// Rust data scripts hold and pass around, and call the methods of, but
+/// cannot look inside. Copies share the data, as pointers do.
+#[derive(Clone)]
+pub struct Opaque {
+    pub ty: Rc<OpaqueType>,
+    pub data: Rc<dyn Any>,
+}
+
+/// The type of `Opaque` values; see `interpreter::Package::new_type`.
+pub struct OpaqueType {
+    /// The named struct without fields scripts know the type as.
+    pub ty: Rc<StructType>,
+    /// Methods, which are called with the receiver before their
+    /// arguments.
+    pub methods: RefCell<HashMap<String, Rc<Host>>>,
+}
+
+impl Opaque {
+    fn address(&self) -> usize {
+        Rc::as_ptr(&self.data) as *const u8 as usize
+    }
+
+    /// The method `name` bound to the value.
+    pub fn method(&self, name: &str) -> Option<Value> {
+        let method = Rc::clone(self.ty.methods.borrow().get(name)?);
+        let recv = Value::Opaque(self.clone());
+        let name = format!("{}.{}", self.ty.ty.name.as_deref().unwrap_or_default(), name);
+        let func = move |args: Vec<Value>| {
+            let mut all = Vec::with_capacity(args.len() + 1);
+            all.push(recv.clone());
+            all.extend(args);
+            (method.func)(all)
+        };
+        Some(Value::Host(Rc::new(Host { name, func: Box::new(func) })))
+    }
+}
+
+impl fmt::Debug for Opaque {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Opaque({:?})", self.ty.ty)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Method {
+    pub recv: Value,
+    pub func: builtins::Builtin,
+}
+
+/// A window onto a backing array: elements `offset..offset+len` are in
+/// the slice, and it may grow in place up to `offset+cap`.
+#[derive(Debug, Clone)]
+pub struct Slice {
+    pub elem: Type,
+    /// None for a nil slice.
+    pub array: Option<Array>,
+    pub offset: usize,
+    pub len: usize,
+    pub cap: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Map {
+    pub key: Type,
+    pub elem: Type,
+    /// None for a nil map.
+    pub table: Option<Rc<RefCell<MapTable>>>,
+}
+
+/// Entries of a map in insertion order. Deleted entries leave a hole
+/// until there are more holes than entries.
+#[derive(Debug, Default)]
+pub struct MapTable {
+    entries: Vec<Option<(Value, Value)>>,
+    index: HashMap<Key, usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Struct {
+    pub ty: Rc<StructType>,
+    pub fields: Vec<Value>,
+}
+
+impl Drop for Struct {
+    fn drop(&mut self) {
+        heap::release(mem::take(&mut self.fields));
+    }
+}
+
+#[derive(Clone)]
+pub struct Pointer {
+    pub elem: Type,
+    /// None for a nil pointer.
+    pub target: Option<Place>,
+}
+
+/// Somewhere a value is stored: a variable or an element of a backing
+/// array, then a path of struct field indexes into it.
+#[derive(Clone)]
+pub struct Place {
+    pub root: Root,
+    pub path: Vec<usize>,
+}
+
+#[derive(Clone)]
+pub enum Root {
+    Var(Var),
+    Elem(Array, usize),
+}
+
+/// An interface value: a dynamic type and value, or neither for a nil
+/// interface. The value is never itself an interface.
+#[derive(Debug, Clone)]
+pub struct Interface {
+    pub ty: Rc<InterfaceType>,
+    pub value: Option<Box<Value>>,
+}
+
+impl Drop for Interface {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            heap::release_value(*value);
+        }
+    }
+}
+
+/// A value as a map key. Keys are equal exactly when the values are `==`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    /// The bits of a float, with -0 taken as 0.
+    Float(u64),
+    /// A NaN, which is not `==` to anything: each is numbered afresh, so
+    /// every insert adds an entry and every lookup misses.
+    NaN(u64),
+    Str(Rc<[u8]>),
+    Ptr(Place),
+    /// A channel, by address.
+    Chan(usize),
+    /// An opaque value, by the address of its data.
+    Opaque(usize),
+    Struct(Vec<Key>),
+    /// The dynamic type, by name, and value of an interface.
+    Iface(Option<(String, Box<Key>)>),
+}
+
+/// Where a `for range` loop is.
+#[derive(Debug)]
+pub enum RangeIter {
+    Slice(Slice, usize),
+    /// Byte offset of the next rune.
+    String(Rc<[u8]>, usize),
+    /// The keys as they were when the loop started, in iteration order.
+    Map(Map, Vec<Value>, usize),
+    Int(i64, i64),
+    /// Receives until the channel is closed; the VM runs it, as receiving
+    /// may block.
+    Chan(Chan),
+}
+
+impl Value {
+    pub fn string(s: &str) -> Value {
+        Value::String(Rc::from(s.as_bytes()))
+    }
+
+    /// The type of the value; for an interface, the interface type.
+    pub fn ty(&self) -> Type {
+        match self {
+            Value::Integer(_) => Type::Int,
+            Value::Float(_) => Type::Float64,
+            Value::Boolean(_) => Type::Bool,
+            Value::String(_) => Type::String,
+            Value::Slice(s) => Type::Slice(Rc::new(s.elem.clone())),
+            Value::Map(m) => Type::Map(Rc::new(m.key.clone()), Rc::new(m.elem.clone())),
+            Value::Struct(s) => Type::Struct(Rc::clone(&s.ty)),
+            Value::Pointer(p) => Type::Pointer(Rc::new(p.elem.clone())),
+            Value::Interface(i) => Type::Interface(Rc::clone(&i.ty)),
+            Value::Chan(c) => Type::Chan(Rc::new(c.elem.clone())),
+            Value::CompiledFunction(_) | Value::Closure(_) | Value::Builtin(_) | Value::Host(_) | Value::Method(_) => Type::Func,
+            Value::Opaque(o) => Type::Struct(Rc::clone(&o.ty.ty)),
+            Value::Null | Value::Tuple(_) | Value::Type(_) | Value::Iterator(_) => Type::Nil,
+        }
+    }
+
+    /// Name of the value's dynamic type as shown to users.
+    pub fn type_name(&self) -> String {
+        match self {
+            Value::Interface(Interface { value: Some(v), .. }) => v.type_name(),
+            Value::Interface(Interface { value: None, .. }) => "nil".to_string(),
+            _ => self.ty().to_string(),
+        }
+    }
+
+    /// The value formatted as `%v` would.
+    pub fn inspect(&self) -> String {
+        self.format(true, false)
+    }
+
+    /// The value formatted as `%+v` would, with the names of struct fields.
+    pub fn inspect_fields(&self) -> String {
+        self.format(true, true)
+    }
+
+    fn format(&self, top: bool, names: bool) -> String {
+        if let Some(msg) = crate::stdlib::errors::message(self) {
+            return msg;
+        }
+        match self {
+            Value::Integer(i) => i.to_string(),
+            Value::Float(f) => format_float(*f, 'g', None),
+            Value::Boolean(b) => b.to_string(),
+            Value::Null => "nil".to_string(),
+            Value::String(s) => String::from_utf8_lossy(s).into_owned(),
+            Value::Slice(s) => {
+                let elems: Vec<String> = s.values().iter().map(|v| v.format(false, names)).collect();
+                format!("[{}]", elems.join(" "))
+            }
+            Value::Map(m) => {
+                let entries: Vec<String> = m
+                    .sorted_entries()
+                    .iter().map(|(k, v)| format!("{}:{}", k.format(false, names), v.format(false, names))).collect();
+                format!("map[{}]", entries.join(" "))
+            }
+            Value::Struct(s) if names => {
+                let st = s.ty.fields.borrow();
+                let fields: Vec<String> =
+                    s.fields.iter().zip(st.iter()).map(|(v, f)| format!("{}:{}", f.name, v.format(false, names))).collect();
+                format!("{{{}}}", fields.join(" "))
+            }
+            Value::Struct(s) => {
+                let fields: Vec<String> = s.fields.iter().map(|v| v.format(false, names)).collect();
+                format!("{{{}}}", fields.join(" "))
+            }
+            Value::Pointer(p) => match &p.target {
+                None => "<nil>".to_string(),
+                Some(place) => match place.load() {
+                    Value::Struct(s) if top => format!("&{}", Value::Struct(s).format(false, names)),
+                    _ => format!("0x{:x}", place.address()),
+                },
+            },
+            Value::Interface(i) => match &i.value {
+                Some(v) => v.format(top, names),
+                None => "<nil>".to_string(),
+            },
+            Value::Chan(c) => match &c.state {
+                Some(_) => format!("0x{:x}", c.address()),
+                None => "<nil>".to_string(),
+            },
+            Value::CompiledFunction(f) => f.signature(),
+            Value::Closure(c) => c.func.signature(),
+            Value::Builtin(b) => format!("builtin {}", b.name),
+            Value::Host(h) => format!("host {}", h.name),
+            Value::Opaque(o) => format!("<{}>", Type::Struct(Rc::clone(&o.ty.ty))),
+            Value::Method(m) => format!("method {}", m.func.name),
+            Value::Tuple(values) => {
+                let values: Vec<String> = values.iter().map(|v| v.format(top, names)).collect();
+                values.join(" ")
+            }
+            Value::Type(t) => t.to_string(),
+            Value::Iterator(_) => "iterator".to_string(),
+        }
+    }
+
+    /// Go's `==`. Comparing values whose type has no `==`, other than to
+    /// nil, is an error.
+    pub fn equals(&self, other: &Value) -> Result<bool, String> {
+        match (self, other) {
+            (Value::Interface(a), Value::Interface(b)) => match (&a.value, &b.value) {
+                (None, None) => Ok(true),
+                (Some(x), Some(y)) => dynamic_equals(x, y),
+                _ => Ok(false),
+            },
+            (Value::Interface(i), Value::Null) | (Value::Null, Value::Interface(i)) => Ok(i.value.is_none()),
+            (Value::Interface(i), v) | (v, Value::Interface(i)) => match &i.value {
+                Some(x) => dynamic_equals(x, v),
+                None => Ok(false),
+            },
+            (Value::Integer(a), Value::Integer(b)) => Ok(a == b),
+            (Value::Float(a), Value::Float(b)) => Ok(a == b),
+            // the integer is taken as an untyped constant
+            (Value::Float(a), Value::Integer(b)) | (Value::Integer(b), Value::Float(a)) => Ok(*a == *b as f64),
+            (Value::Boolean(a), Value::Boolean(b)) => Ok(a == b),
+            (Value::String(a), Value::String(b)) => Ok(a == b),
+            (Value::Null, Value::Null) => Ok(true),
+            (Value::Slice(s), Value::Null) | (Value::Null, Value::Slice(s)) => Ok(s.array.is_none()),
+            (Value::Map(m), Value::Null) | (Value::Null, Value::Map(m)) => Ok(m.table.is_none()),
+            (Value::Pointer(p), Value::Null) | (Value::Null, Value::Pointer(p)) => Ok(p.target.is_none()),
+            (Value::Chan(c), Value::Null) | (Value::Null, Value::Chan(c)) => Ok(c.state.is_none()),
+            (Value::Closure(_), Value::Null) | (Value::Null, Value::Closure(_)) => Ok(false),
+            (Value::Pointer(a), Value::Pointer(b)) if a.elem.identical(&b.elem) => Ok(a.target == b.target),
+            (Value::Opaque(a), Value::Opaque(b)) if Rc::ptr_eq(&a.ty, &b.ty) => Ok(a.address() == b.address()),
+            (Value::Chan(a), Value::Chan(b)) if a.elem.identical(&b.elem) => Ok(a == b),
+            (Value::Struct(a), Value::Struct(b)) if self.ty().identical(&other.ty()) => {
+                if !self.ty().comparable() {
+                    return Err(format!("invalid operation: struct containing uncomparable fields cannot be compared ({})", self.ty()));
+                }
+                for (x, y) in a.fields.iter().zip(b.fields.iter()) {
+                    if !x.equals(y)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            (Value::Slice(_), Value::Slice(_)) => Err("invalid operation: slice can only be compared to nil".to_string()),
+            (Value::Map(_), Value::Map(_)) => Err("invalid operation: map can only be compared to nil".to_string()),
+            (Value::Closure(_), Value::Closure(_)) => Err("invalid operation: func can only be compared to nil".to_string()),
+            _ => Err(format!("invalid operation: mismatched types {} and {}", self.ty(), other.ty())),
+        }
+    }
+
+    /// The value as a map key, or an error for types that cannot be keys.
+    pub fn key(&self) -> Result<Key, String> {
+        match self {
+            Value::Null => Ok(Key::Nil),
+            Value::Boolean(b) => Ok(Key::Bool(*b)),
+            Value::Integer(i) => Ok(Key::Int(*i)),
+            Value::Float(f) if f.is_nan() => Ok(Key::NaN(next_nan())),
+            Value::Float(f) => Ok(Key::Float(if *f == 0.0 { 0 } else { f.to_bits() })),
+            Value::String(s) => Ok(Key::Str(Rc::clone(s))),
+            Value::Pointer(p) => match &p.target {
+                Some(place) => Ok(Key::Ptr(place.clone())),
+                None => Ok(Key::Nil),
+            },
+            Value::Chan(c) => match &c.state {
+                Some(_) => Ok(Key::Chan(c.address())),
+                None => Ok(Key::Nil),
+            },
+            Value::Opaque(o) => Ok(Key::Opaque(o.address())),
+            Value::Struct(s) if self.ty().comparable() => Ok(Key::Struct(s.fields.iter().map(|f| f.key()).collect::<Result<_, _>>()?)),
+            Value::Interface(i) => match &i.value {
+                Some(v) => Ok(Key::Iface(Some((v.ty().to_string(), Box::new(v.key()?))))),
+                None => Ok(Key::Iface(None)),
+            },
+            _ => Err(format!("hash of unhashable type {}", self.type_name())),
+        }
+    }
+}
+
+/// A number no NaN key had before.
+fn next_nan() -> u64 {
+    thread_local! {
+        static NANS: Cell<u64> = const { Cell::new(0) };
+    }
+    NANS.with(|n| {
+        n.set(n.get() + 1);
+        n.get()
+    })
+}
+
+/// `==` on the dynamic values of interfaces.
+fn dynamic_equals(x: &Value, y: &Value) -> Result<bool, String> {
+    let ty = x.ty();
+    if !ty.identical(&y.ty()) {
+        return Ok(false);
+    }
+    if !ty.comparable() {
+        return Err(format!("comparing uncomparable type {}", ty));
+    }
+    x.equals(y)
+}
+
+/// The order fmt prints map keys in: numbers and strings ascending,
+/// false before true, anything else as found.
+fn sort_order(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::Float(a), Value::Float(b)) => a.total_cmp(b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+        (Value::Interface(a), Value::Interface(b)) => match (&a.value, &b.value) {
+            (Some(x), Some(y)) => x.type_name().cmp(&y.type_name()).then_with(|| sort_order(x, y)),
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+        _ => Ordering::Equal,
+    }
+}
+
+impl PartialEq for Value {
+    /// Structural equality, for tests: unlike `equals` it is defined on
+    /// slices and maps, comparing their elements.
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Slice(a), Value::Slice(b)) => a.elem == b.elem && a.values() == b.values(),
+            (Value::Map(a), Value::Map(b)) => {
+                let (mut x, mut y) = (a.entries(), b.entries());
+                x.sort_by(|a, b| sort_order(&a.0, &b.0));
+                y.sort_by(|a, b| sort_order(&a.0, &b.0));
+                a.table.is_some() == b.table.is_some() && x == y
+            }
+            (Value::Struct(a), Value::Struct(b)) => Rc::ptr_eq(&a.ty, &b.ty) && a.fields == b.fields,
+            (Value::Interface(a), Value::Interface(b)) => a.value == b.value,
+            (Value::CompiledFunction(a), Value::CompiledFunction(b)) => Rc::ptr_eq(a, b),
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            (Value::Builtin(a), Value::Builtin(b)) => a.name == b.name,
+            (Value::Host(a), Value::Host(b)) => Rc::ptr_eq(a, b),
+            (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            (Value::Type(a), Value::Type(b)) => a.identical(b),
+            _ => self.equals(other) == Ok(true),
+        }
+    }
+}
+
+/// Converts `value` for storing where a value of type `ty` is expected:
+/// wraps it in an interface, gives a nil its type, or reports that it
+/// does not fit.
+pub fn convert(value: Value, ty: &Type) -> Result<Value, String> {
+    match (ty, value) {
+        (Type::Interface(it), Value::Interface(mut i)) => Ok(Value::Interface(Interface { ty: Rc::clone(it), value: i.value.take() })),
+        (Type::Interface(it), Value::Null) => Ok(Value::Interface(Interface { ty: Rc::clone(it), value: None })),
+        (Type::Interface(it), v) => Ok(Value::Interface(Interface { ty: Rc::clone(it), value: Some(Box::new(v)) })),
+        (Type::Slice(_) | Type::Map(_, _) | Type::Pointer(_) | Type::Chan(_) | Type::Func, Value::Null) => Ok(ty.zero()),
+        (Type::Byte, Value::Integer(i)) => Ok(Value::Integer(i as u8 as i64)),
+        // an integer stored as a float is taken as an untyped constant
+        (Type::Float64, Value::Integer(i)) => Ok(Value::Float(i as f64)),
+        (Type::Nil, v) => Ok(v),
+        (ty, v) if v.ty().identical(ty) => Ok(v),
+        (ty, v @ Value::Interface(_)) => Err(format!("cannot use {} value as {} value: need type assertion", v.ty(), ty)),
+        (ty, v) => Err(format!("cannot use {} value as {} value", v.ty(), ty)),
+    }
+}
+
+/// A conversion `T(x)`: everything `convert` allows, plus the numeric,
+/// string and byte slice conversions.
+pub fn conversion(value: Value, ty: &Type) -> Result<Value, String> {
+    match (ty, &value) {
+        (Type::Int, Value::Integer(_)) => Ok(value),
+        (Type::Int, Value::Float(f)) => Ok(Value::Integer(*f as i64)),
+        (Type::Byte, Value::Float(f)) => Ok(Value::Integer(*f as i64 as u8 as i64)),
+        (Type::String, Value::Integer(i)) => {
+            let c = rune_char(*i);
+            Ok(Value::string(&c.to_string()))
+        }
+        (Type::String, Value::Slice(s)) if matches!(s.elem, Type::Byte | Type::Int) => {
+            let mut bytes = vec![];
+            for v in s.values() {
+                let i = match v {
+                    Value::Integer(i) => i,
+                    _ => 0,
+                };
+                if s.elem == Type::Byte {
+                    bytes.push(i as u8);
+                } else {
+                    let c = rune_char(i);
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+            heap::charge(bytes.len())?;
+            Ok(Value::String(Rc::from(bytes)))
+        }
+        (Type::Slice(elem), Value::String(s)) if matches!(elem.as_ref(), Type::Byte | Type::Int) => {
+            let values = if **elem == Type::Byte {
+                heap::charge_slots(s.len())?;
+                s.iter().map(|b| Value::Integer(*b as i64)).collect()
+            } else {
+                let text = String::from_utf8_lossy(s);
+                heap::charge_slots(text.chars().count())?;
+                text.chars().map(|c| Value::Integer(c as i64)).collect()
+            };
+            Ok(Value::Slice(Slice::from_values(elem.as_ref().clone(), values)))
+        }
+        (Type::Slice(a), Value::Slice(s)) if a.identical(&s.elem) => Ok(value),
+        (Type::Pointer(a), Value::Pointer(p)) if a.identical(&p.elem) => Ok(value),
+        (Type::Struct(a), Value::Struct(s)) => {
+            // identical field lists convert, whatever the names of the types
+            let same = {
+                let (af, bf) = (a.fields.borrow(), s.ty.fields.borrow());
+                af.len() == bf.len() && af.iter().zip(bf.iter()).all(|(x, y)| x.name == y.name && x.ty.identical(&y.ty))
+            };
+            if same {
+                Ok(Value::Struct(Box::new(Struct { ty: Rc::clone(a), fields: s.fields.clone() })))
+            } else {
+                Err(format!("cannot convert {} value to type {}", value.ty(), ty))
+            }
+        }
+        _ => {
+            let from = value.ty();
+            convert(value, ty).map_err(|_| format!("cannot convert {} value to type {}", from, ty))
+        }
+    }
+}
+
+/// A value stored over `current`, as assignment does: an interface
+/// variable keeps its interface type, a nil takes the variable's type.
+pub fn assign(current: &Value, value: Value) -> Result<Value, String> {
+    match (current, &value) {
+        (Value::Interface(_), _)
+        | (Value::Slice(_) | Value::Map(_) | Value::Pointer(_) | Value::Chan(_), Value::Null)
+        | (Value::Float(_), Value::Integer(_)) => convert(value, &current.ty()),
+        _ => Ok(value),
+    }
+}
+
+impl Drop for Slice {
+    fn drop(&mut self) {
+        if let Some(array) = &self.array {
+            heap::release_array(array);
+        }
+    }
+}
+
+impl Slice {
+    pub fn nil(elem: Type) -> Slice {
+        Slice { elem, array: None, offset: 0, len: 0, cap: 0 }
+    }
+
+    /// A slice of `values` on a new backing array of the same length.
+    pub fn from_values(elem: Type, values: Vec<Value>) -> Slice {
+        let len = values.len();
+        let array = Rc::new(RefCell::new(values));
+        heap::track_array(&array);
+        Slice { elem, array: Some(array), offset: 0, len, cap: len }
+    }
+
+    /// A new slice of `len` zero values with room for `cap`.
+    pub fn make(elem: Type, len: usize, cap: usize) -> Slice {
+        let values = (0..cap).map(|_| elem.zero()).collect();
+        let mut s = Slice::from_values(elem, values);
+        s.len = len;
+        s
+    }
+
+    pub fn values(&self) -> Vec<Value> {
+        match &self.array {
+            Some(array) => array.borrow()[self.offset..self.offset + self.len].to_vec(),
+            None => vec![],
+        }
+    }
+
+    /// Element `i`, which must be below `len`.
+    pub fn get(&self, i: usize) -> Value {
+        self.array.as_ref().unwrap().borrow()[self.offset + i].clone()
+    }
+
+    pub fn set(&self, i: usize, value: Value) -> Result<(), String> {
+        let value = convert(value, &self.elem)?;
+        self.array.as_ref().unwrap().borrow_mut()[self.offset + i] = value;
+        Ok(())
+    }
+
+    /// Where element `i` is stored.
+    pub fn place(&self, i: usize) -> Place {
+        Place { root: Root::Elem(Rc::clone(self.array.as_ref().unwrap()), self.offset + i), path: vec![] }
+    }
+
+    /// `s[low:high]`, sharing the backing array.
+    pub fn slice(&self, low: usize, high: usize) -> Slice {
+        Slice {
+            elem: self.elem.clone(),
+            array: self.array.clone(),
+            offset: self.offset + low,
+            len: high - low,
+            cap: self.cap - low,
+        }
+    }
+
+    /// `append(s, values...)`: writes in place while there is capacity,
+    /// else copies to a new, larger backing array.
+    pub fn append(&self, values: Vec<Value>) -> Result<Slice, String> {
+        let values = values.into_iter().map(|v| convert(v, &self.elem)).collect::<Result<Vec<_>, _>>()?;
+        if values.is_empty() {
+            return Ok(self.clone());
+        }
+        let needed = self.len + values.len();
+        if needed <= self.cap {
+            let array = self.array.as_ref().unwrap();
+            let mut array = array.borrow_mut();
+            for (i, v) in values.into_iter().enumerate() {
+                array[self.offset + self.len + i] = v;
+            }
+            let mut slice = self.clone();
+            slice.len = needed;
+            return Ok(slice);
+        }
+        let cap = grow_cap(self.cap, needed);
+        heap::charge_slots(cap)?;
+        let mut array = self.values();
+        array.extend(values);
+        array.resize_with(cap, || self.elem.zero());
+        let array = Rc::new(RefCell::new(array));
+        heap::track_array(&array);
+        Ok(Slice { elem: self.elem.clone(), array: Some(array), offset: 0, len: needed, cap })
+    }
+}
+
+/// The capacity `append` gives a slice that must hold `needed` elements
+/// and has `old_cap`: Go's growth rule for word-sized elements, rounded
+/// up to an allocator size class.
+pub fn grow_cap(old_cap: usize, needed: usize) -> usize {
+    const THRESHOLD: usize = 256;
+    let mut cap = old_cap;
+    let double = cap + cap;
+    if needed > double {
+        cap = needed;
+    } else if old_cap < THRESHOLD {
+        cap = double;
+    } else {
+        while cap < needed {
+            cap += (cap + 3 * THRESHOLD) >> 2;
+        }
+    }
+    round_up_size(cap * 8) / 8
+}
+
+fn round_up_size(size: usize) -> usize {
+    const SIZE_CLASSES: &[usize] = &[
+        0, 8, 16, 24, 32, 48, 64, 80, 96, 112, 128, 144, 160, 176, 192, 208, 224, 240, 256, 288, 320, 352, 384, 416,
+        448, 480, 512, 576, 640, 704, 768, 896, 1024, 1152, 1280, 1408, 1536, 1792, 2048, 2304, 2688, 3072, 3200, 3456,
+        4096, 4864, 5376, 6144, 6528, 6784, 6912, 8192, 9472, 9728, 10240, 10880, 12288, 13568, 14336, 16384, 18432,
+        19072, 20480, 21760, 24576, 27264, 28672, 32768,
+    ];
+    match SIZE_CLASSES.iter().find(|&&class| class >= size) {
+        Some(class) => *class,
+        None => size.div_ceil(8192) * 8192,
+    }
+}
+
+impl Drop for Map {
+    fn drop(&mut self) {
+        if let Some(table) = &self.table {
+            heap::release_map(table);
+        }
+    }
+}
+
+impl Map {
+    pub fn nil(key: Type, elem: Type) -> Map {
+        Map { key, elem, table: None }
+    }
+
+    pub fn new(key: Type, elem: Type) -> Map {
+        let table = Rc::new(RefCell::new(MapTable::default()));
+        heap::track_map(&table);
+        Map { key, elem, table: Some(table) }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.table {
+            Some(table) => table.borrow().index.len(),
+            None => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The element stored under `key`, if any. A nil map has none.
+    pub fn get(&self, key: &Value) -> Result<Option<Value>, String> {
+        let key = convert(key.clone(), &self.key)?.key()?;
+        match &self.table {
+            Some(table) => {
+                let table = table.borrow();
+                Ok(table.index.get(&key).and_then(|&i| table.entries[i].as_ref()).map(|(_, v)| v.clone()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn insert(&self, key: Value, value: Value) -> Result<(), String> {
+        let key = convert(key, &self.key)?;
+        let value = convert(value, &self.elem)?;
+        let hashed = key.key()?;
+        let table = match &self.table {
+            Some(table) => table,
+            None => return Err("assignment to entry in nil map".to_string()),
+        };
+        let mut table = table.borrow_mut();
+        match table.index.get(&hashed) {
+            Some(&i) => table.entries[i] = Some((key, value)),
+            None => {
+                heap::charge_slots(2)?;
+                let i = table.entries.len();
+                table.entries.push(Some((key, value)));
+                table.index.insert(hashed, i);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn delete(&self, key: &Value) -> Result<(), String> {
+        let hashed = convert(key.clone(), &self.key)?.key()?;
+        if let Some(table) = &self.table {
+            let mut table = table.borrow_mut();
+            if let Some(i) = table.index.remove(&hashed) {
+                table.entries[i] = None;
+                if table.entries.len() > 2 * table.index.len() + 8 {
+                    table.compact();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `clear(m)`.
+    pub fn clear(&self) {
+        if let Some(table) = &self.table {
+            *table.borrow_mut() = MapTable::default();
+        }
+    }
+
+    /// Keys and elements in insertion order.
+    pub fn entries(&self) -> Vec<(Value, Value)> {
+        match &self.table {
+            Some(table) => table.borrow().entries.iter().flatten().cloned().collect(),
+            None => vec![],
+        }
+    }
+
+    /// Keys and elements in the order fmt prints them.
+    pub fn sorted_entries(&self) -> Vec<(Value, Value)> {
+        let mut entries = self.entries();
+        entries.sort_by(|a, b| sort_order(&a.0, &b.0));
+        entries
+    }
+
+    /// The keys in iteration order, which starts at entry `start` modulo
+    /// the number of entries so that programs cannot depend on it.
+    pub fn keys_from(&self, start: usize) -> Vec<Value> {
+        let mut keys: Vec<Value> = self.entries().into_iter().map(|(k, _)| k).collect();
+        if !keys.is_empty() {
+            let n = keys.len();
+            keys.rotate_left(start % n);
+        }
+        keys
+    }
+}
+
+impl MapTable {
+    fn compact(&mut self) {
+        self.entries.retain(|e| e.is_some());
+        self.index.clear();
+        for (i, entry) in self.entries.iter().enumerate() {
+            if let Some((k, _)) = entry {
+                if let Ok(key) = k.key() {
+                    self.index.insert(key, i);
+                }
+            }
+        }
+    }
+}
+
+impl Pointer {
+    pub fn nil(elem: Type) -> Pointer {
+        Pointer { elem, target: None }
+    }
+
+    /// A pointer to `var`, typed after its current value.
+    pub fn to_var(var: Var) -> Pointer {
+        let elem = var.borrow().ty();
+        heap::track_var(&var);
+        Pointer { elem, target: Some(Place { root: Root::Var(var), path: vec![] }) }
+    }
+
+    fn place(&self) -> Result<&Place, String> {
+        self.target.as_ref().ok_or_else(|| "invalid memory address or nil pointer dereference".to_string())
+    }
+
+    /// `*p`.
+    pub fn load(&self) -> Result<Value, String> {
+        Ok(self.place()?.load())
+    }
+
+    /// `*p = value`.
+    pub fn store(&self, value: Value) -> Result<(), String> {
+        let value = convert(value, &self.elem)?;
+        self.place()?.store(value);
+        Ok(())
+    }
+
+    /// `&p.f` for the field at `index` of the struct `p` points to.
+    pub fn field(&self, index: usize) -> Result<Pointer, String> {
+        let place = self.place()?;
+        let elem = match &self.elem {
+            Type::Struct(st) => st.fields.borrow()[index].ty.clone(),
+            _ => Type::Nil,
+        };
+        let mut path = place.path.clone();
+        path.push(index);
+        Ok(Pointer { elem, target: Some(Place { root: place.root.clone(), path }) })
+    }
+}
+
+impl Place {
+    pub fn load(&self) -> Value {
+        let root = match &self.root {
+            Root::Var(var) => var.borrow(),
+            Root::Elem(array, i) => return walk(&array.borrow()[*i], &self.path).clone(),
+        };
+        walk(&root, &self.path).clone()
+    }
+
+    pub fn store(&self, value: Value) {
+        match &self.root {
+            Root::Var(var) => *walk_mut(&mut var.borrow_mut(), &self.path) = value,
+            Root::Elem(array, i) => *walk_mut(&mut array.borrow_mut()[*i], &self.path) = value,
+        }
+    }
+
+    /// An address for printing and hashing.
+    pub fn address(&self) -> usize {
+        let base = match &self.root {
+            Root::Var(var) => Rc::as_ptr(var) as usize,
+            Root::Elem(array, i) => Rc::as_ptr(array) as usize + 16 + i * 8,
+        };
+        base + self.path.iter().sum::<usize>() * 8
+    }
+}
+
+fn walk<'a>(mut value: &'a Value, path: &[usize]) -> &'a Value {
+    for &i in path {
+        value = match value {
+            Value::Struct(s) => &s.fields[i],
+            _ => return value,
+        };
+    }
+    value
+}
+
+fn walk_mut<'a>(mut value: &'a mut Value, path: &[usize]) -> &'a mut Value {
+    for &i in path {
+        value = match value {
+            Value::Struct(s) => &mut s.fields[i],
+            _ => return value,
+        };
+    }
+    value
+}
+
+impl Drop for Place {
+    fn drop(&mut self) {
+        match &self.root {
+            Root::Var(var) => heap::release_var(var),
+            Root::Elem(array, _) => heap::release_array(array),
+        }
+    }
+}
+
+impl PartialEq for Place {
+    fn eq(&self, other: &Place) -> bool {
+        let same_root = match (&self.root, &other.root) {
+            (Root::Var(a), Root::Var(b)) => Rc::ptr_eq(a, b),
+            (Root::Elem(a, i), Root::Elem(b, j)) => Rc::ptr_eq(a, b) && i == j,
+            _ => false,
+        };
+        same_root && self.path == other.path
+    }
+}
+
+impl Eq for Place {}
+
+impl Hash for Place {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address().hash(state);
+        self.path.hash(state);
+    }
+}
+
+// Written by hand: pointers can form cycles through the values they
+// point to.
+impl fmt::Debug for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.target {
+            Some(place) => write!(f, "Pointer(*{} 0x{:x})", self.elem, place.address()),
+            None => write!(f, "Pointer(*{} nil)", self.elem),
+        }
+    }
+}
+
+impl fmt::Debug for Place {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Place(0x{:x})", self.address())
+    }
+}
+
+/// Body of a function literal after compilation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub num_locals: usize,
+    pub parameters: Vec<String>,
+    /// Name in stack traces, such as `main.f` or `main.f.func1`.
+    pub name: String,
+    /// Local holding the named result, which the function returns once
+    /// its deferred calls have run.
+    pub result: Option<usize>,
+    pub positions: Vec<SourcePos>,
+}
+
+impl CompiledFunction {
+    pub fn num_parameters(&self) -> usize {
+        self.parameters.len()
+    }
+
+    fn signature(&self) -> String {
+        format!("fn({})", self.parameters.join(", "))
+    }
+}
+
+/// A compiled function together with the variables it captured.
+#[derive(Clone)]
+pub struct Closure {
+    pub func: Rc<CompiledFunction>,
+    pub free: Vec<Var>,
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Closure({}, {} free)", self.func.signature(), self.free.len())
+    }
+}
+
+impl Drop for Closure {
+    fn drop(&mut self) {
+        self.free.iter().for_each(heap::release_var);
+    }
+}
+
+/// Go's `strconv.FormatFloat(f, fmt, prec, 64)` for the formats 'e', 'f'
+/// and 'g'. No precision means the fewest digits that read back as `f`;
+/// 'g' then switches to an exponent from 1e+06 up and below 1e-04, as
+/// `%v` prints floats.
+pub fn format_float(f: f64, fmt: char, prec: Option<usize>) -> String {
+    if f.is_nan() {
+        return "NaN".to_string();
+    }
+    if f.is_infinite() {
+        return if f > 0.0 { "+Inf" } else { "-Inf" }.to_string();
+    }
+    let sign = if f.is_sign_negative() { "-" } else { "" };
+    let a = f.abs();
+    if let ('f', Some(prec)) = (fmt, prec) {
+        return format!("{}{:.*}", sign, prec, a);
+    }
+    // significant digits without trailing zeros, and the position of the
+    // decimal point relative to them
+    let sci = match (fmt, prec) {
+        ('e', Some(prec)) => format!("{:.*e}", prec, a),
+        ('g', Some(prec)) => format!("{:.*e}", prec.max(1) - 1, a),
+        _ => format!("{:e}", a),
+    };
+    let (mantissa, exp) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let digits: Vec<u8> = mantissa.bytes().filter(u8::is_ascii_digit).collect();
+    let nd = digits.iter().rposition(|&d| d != b'0').map_or(0, |i| i + 1);
+    let digits = &digits[..nd];
+    let dp = if nd == 0 { 0 } else { exp.parse::<i64>().unwrap_or(0) + 1 };
+    let nd = nd as i64;
+    let body = match fmt {
+        'e' => format_exponent(digits, dp, prec.map_or(nd - 1, |p| p as i64)),
+        'f' => format_fixed(digits, dp, (nd - dp).max(0)),
+        _ => {
+            let shortest = prec.is_none();
+            let mut prec = prec.map_or(nd, |p| p.max(1) as i64);
+            let mut eprec = prec;
+            if eprec > nd && nd >= dp {
+                eprec = nd;
+            }
+            if shortest {
+                eprec = 6;
+            }
+            let exp = dp - 1;
+            if exp < -4 || exp >= eprec {
+                if prec > nd {
+                    prec = nd;
+                }
+                format_exponent(digits, dp, prec - 1)
+            } else {
+                if prec > dp {
+                    prec = nd;
+                }
+                format_fixed(digits, dp, (prec - dp).max(0))
+            }
+        }
+    };
+    [sign, &body].concat()
+}
+
+/// `d.ddde±dd` with `prec` digits after the point.
+fn format_exponent(digits: &[u8], dp: i64, prec: i64) -> String {
+    let digit = |i: i64| if i >= 0 && (i as usize) < digits.len() { digits[i as usize] as char } else { '0' };
+    let mut out = String::new();
+    out.push(digit(0));
+    if prec > 0 {
+        out.push('.');
+        for i in 1..=prec {
+            out.push(digit(i));
+        }
+    }
+    let exp = if digits.is_empty() { 0 } else { dp - 1 };
+    out.push_str(&format!("e{}{:02}", if exp < 0 { '-' } else { '+' }, exp.abs()));
+    out
+}
+
+/// `ddd.ddd` with `prec` digits after the point.
+fn format_fixed(digits: &[u8], dp: i64, prec: i64) -> String {
+    let digit = |i: i64| if i >= 0 && (i as usize) < digits.len() { digits[i as usize] as char } else { '0' };
+    let mut out = String::new();
+    if dp > 0 {
+        for i in 0..dp {
+            out.push(digit(i));
+        }
+    } else {
+        out.push('0');
+    }
+    if prec > 0 {
+        out.push('.');
+        for i in 0..prec {
+            out.push(digit(dp + i));
+        }
+    }
+    out
+}
+
+/// The character with code point `i`; U+FFFD when there is none.
+pub fn rune_char(i: i64) -> char {
+    u32::try_from(i).ok().and_then(char::from_u32).unwrap_or('\u{FFFD}')
+}
+
+/// The first rune of `bytes` and its length in bytes. Invalid UTF-8
+/// decodes as U+FFFD one byte at a time, as Go's range over a string does.
+pub fn decode_rune(bytes: &[u8]) -> (char, usize) {
+    for len in 1..=bytes.len().min(4) {
+        if let Ok(s) = std::str::from_utf8(&bytes[..len]) {
+            return (s.chars().next().unwrap(), len);
+        }
+    }
+    ('\u{FFFD}', 1)
+}
@@ -0,0 +1,152 @@
+use crate::types::Type;
+use crate::value::*;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ints(values: &[i64]) -> Slice {
+    Slice::from_values(Type::Int, values.iter().map(|i| Value::Integer(*i)).collect())
+  }
+
+  #[test]
+  fn test_grow_cap() {
+    let tests = [(0, 1, 1), (3, 4, 6), (0, 5, 6), (4, 5, 8), (256, 257, 512), (512, 513, 848), (1024, 1025, 1536)];
+    for (old_cap, needed, expected) in tests.iter() {
+      let got = grow_cap(*old_cap, *needed);
+      if got != *expected {
+        panic!("grow_cap({}, {}) expected={}, got={}", old_cap, needed, expected, got);
+      }
+    }
+  }
+
+  #[test]
+  fn test_append_writes_in_place_while_there_is_room() {
+    let s = Slice::make(Type::Int, 1, 4);
+    let t = s.append(vec![Value::Integer(7)]).unwrap();
+    let u = s.append(vec![Value::Integer(8)]).unwrap();
+    // t and u share the element after s
+    if t.get(1) != Value::Integer(8) || u.cap != 4 {
+      panic!("expected shared backing array, got t={:?}", t.values());
+    }
+    let v = ints(&[1, 2]).append(vec![Value::Integer(3)]).unwrap();
+    if v.values() != vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)] || v.cap != 4 {
+      panic!("wrong grown slice {:?} cap={}", v.values(), v.cap);
+    }
+  }
+
+  #[test]
+  fn test_equals() {
+    let any = Type::any();
+    let boxed = |v: Value| convert(v, &any).unwrap();
+    let nil_ptr = Value::Pointer(Pointer::nil(Type::Int));
+    let tests = [
+      (Value::Integer(1), Value::Integer(1), Ok(true)),
+      (Value::string("a"), Value::string("b"), Ok(false)),
+      (boxed(Value::Integer(1)), Value::Integer(1), Ok(true)),
+      (boxed(Value::Integer(1)), boxed(Value::Boolean(true)), Ok(false)),
+      (boxed(Value::Null), Value::Null, Ok(true)),
+      (boxed(nil_ptr.clone()), Value::Null, Ok(false)),
+      (nil_ptr, Value::Null, Ok(true)),
+      (Value::Slice(Slice::nil(Type::Int)), Value::Null, Ok(true)),
+      (
+        Value::Slice(ints(&[1])),
+        Value::Slice(ints(&[1])),
+        Err("invalid operation: slice can only be compared to nil".to_string()),
+      ),
+      (
+        boxed(Value::Slice(ints(&[1]))),
+        boxed(Value::Slice(ints(&[1]))),
+        Err("comparing uncomparable type []int".to_string()),
+      ),
+      (Value::Integer(1), Value::Boolean(true), Err("invalid operation: mismatched types int and bool".to_string())),
+    ];
+    for (a, b, expected) in tests.iter() {
+      let got = a.equals(b);
+      if got != *expected {
+        panic!("{:?} == {:?}: expected={:?}, got={:?}", a, b, expected, got);
+      }
+    }
+  }
+
+  #[test]
+  fn test_pointers_share_variables() {
+    let var = new_var(Value::Integer(1));
+    let p = Pointer::to_var(Rc::clone(&var));
+    let q = Pointer::to_var(Rc::clone(&var));
+    p.store(Value::Integer(2)).unwrap();
+    if q.load() != Ok(Value::Integer(2)) || Value::Pointer(p.clone()).equals(&Value::Pointer(q)) != Ok(true) {
+      panic!("pointers to one variable disagree");
+    }
+    if p.store(Value::Boolean(true)).is_ok() {
+      panic!("stored a bool through *int");
+    }
+  }
+
+  #[test]
+  fn test_map_keys() {
+    let m = Map::new(Type::any(), Type::Int);
+    m.insert(Value::Integer(1), Value::Integer(10)).unwrap();
+    m.insert(Value::string("1"), Value::Integer(20)).unwrap();
+    m.insert(Value::Integer(1), Value::Integer(30)).unwrap();
+    if m.len() != 2 || m.get(&Value::Integer(1)) != Ok(Some(Value::Integer(30))) {
+      panic!("wrong map {:?}", m.entries());
+    }
+    m.delete(&Value::Integer(1)).unwrap();
+    if m.keys_from(5) != vec![convert(Value::string("1"), &Type::any()).unwrap()] {
+      panic!("wrong keys after delete {:?}", m.keys_from(0));
+    }
+    let err = m.insert(Value::Slice(ints(&[])), Value::Integer(0));
+    if err != Err("hash of unhashable type []int".to_string()) {
+      panic!("expected unhashable key error, got={:?}", err);
+    }
+
+    let m = Map::new(Type::Float64, Type::Int);
+    m.insert(Value::Float(f64::NAN), Value::Integer(1)).unwrap();
+    m.insert(Value::Float(f64::NAN), Value::Integer(2)).unwrap();
+    m.insert(Value::Float(-0.0), Value::Integer(3)).unwrap();
+    m.insert(Value::Float(0.0), Value::Integer(4)).unwrap();
+    if m.len() != 3 || m.get(&Value::Float(f64::NAN)) != Ok(None) || m.get(&Value::Float(-0.0)) != Ok(Some(Value::Integer(4))) {
+      panic!("wrong NaN keys {:?}", m.entries());
+    }
+    m.delete(&Value::Float(f64::NAN)).unwrap();
+    if m.len() != 3 {
+      panic!("deleted a NaN key {:?}", m.entries());
+    }
+  }
+
+  #[test]
+  fn test_inspect() {
+    let m = Map::new(Type::String, Type::Int);
+    m.insert(Value::string("b"), Value::Integer(2)).unwrap();
+    m.insert(Value::string("a"), Value::Integer(1)).unwrap();
+    let tests = [
+      (Value::Slice(ints(&[1, 2, 3])), "[1 2 3]"),
+      (Value::Slice(Slice::nil(Type::Int)), "[]"),
+      (Value::Map(m), "map[a:1 b:2]"),
+      (Value::Pointer(Pointer::nil(Type::Int)), "<nil>"),
+      (Type::any().zero(), "<nil>"),
+      (Value::Null, "nil"),
+    ];
+    for (value, expected) in tests.iter() {
+      if value.inspect() != *expected {
+        panic!("expected={:?}, got={:?}", expected, value.inspect());
+      }
+    }
+  }
+
+  #[test]
+  fn test_conversion() {
+    let bytes = conversion(Value::string("hé"), &Type::Slice(Rc::new(Type::Byte))).unwrap();
+    if bytes.inspect() != "[104 195 169]" {
+      panic!("wrong []byte conversion {}", bytes.inspect());
+    }
+    let back = conversion(bytes, &Type::String).unwrap();
+    if back != Value::string("hé") {
+      panic!("wrong string conversion {:?}", back);
+    }
+    if conversion(Value::Integer(65), &Type::String) != Ok(Value::string("A")) {
+      panic!("wrong rune conversion");
+    }
+  }
+}
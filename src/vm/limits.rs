@@ -0,0 +1,44 @@
+//! Bounds on what a run may use, for running code that is not trusted.
+
+use std::fmt;
+use std::time::Duration;
+
+/// What a run of the VM may use; None is no bound. A run that goes past
+/// one stops there, with the `Limit` it went past: unlike a panic,
+/// `recover` does not stop it, and deferred calls do not run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    /// Instructions it may run, in all its goroutines.
+    pub instructions: Option<u64>,
+    /// Bytes its values may take up at once, as `value::heap` counts them.
+    pub heap: Option<usize>,
+    /// Goroutines that may exist at once, counting the first.
+    pub goroutines: Option<usize>,
+    /// Calls that may be in progress in a goroutine at once.
+    pub call_depth: Option<usize>,
+    /// How long it may take from when the limits are set. wasm32 has no
+    /// clock, so there it is not checked.
+    pub timeout: Option<Duration>,
+}
+
+/// The bound of `Limits` a run went past.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Instructions(u64),
+    Heap(usize),
+    Goroutines(usize),
+    CallDepth(usize),
+    Timeout(Duration),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::Instructions(n) => write!(f, "instruction limit of {} exceeded", n),
+            Limit::Heap(n) => write!(f, "heap limit of {} bytes exceeded", n),
+            Limit::Goroutines(n) => write!(f, "goroutine limit of {} exceeded", n),
+            Limit::CallDepth(n) => write!(f, "call depth limit of {} exceeded", n),
+            Limit::Timeout(d) => write!(f, "time limit of {:?} exceeded", d),
+        }
+    }
+}
@@ -0,0 +1,1510 @@
+use crate::code::*;
+use crate::compiler::{Bytecode, TOP_LEVEL_NAME};
+use crate::types::Type;
+use crate::value::builtins::{append_spread, panic_message, Builtin, BUILTINS};
+use crate::value::*;
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+mod limits;
+#[cfg(test)]
+pub(crate) mod test;
+
+pub use limits::{Limit, Limits};
+
+pub const STACK_SIZE: usize = 2048;
+pub const MAX_FRAMES: usize = 1024;
+/// Instructions a goroutine runs before the scheduler may switch to
+/// another.
+pub const TIME_SLICE: usize = 1000;
+/// Instructions run between looks at the clock, for `Limits::timeout`.
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// One function activation: the closure being run, the offset of the next
+/// instruction, where its arguments started on the stack, and its local
+/// variables.
+#[derive(Debug, Clone)]
+struct Frame {
+    cl: Rc<Closure>,
+    ip: usize,
+    base_pointer: usize,
+    locals: Vec<Var>,
+    /// Calls set aside by `defer`, run last first once the function
+    /// returns or panics.
+    defers: Vec<(Value, Vec<Value>)>,
+    /// The function has returned or panicked and is running its deferred
+    /// calls.
+    unwinding: bool,
+    /// What the function returns; None while a panic unwinds it.
+    returning: Option<Value>,
+    /// The frame runs a deferred call, whose result is dropped.
+    deferred: bool,
+    /// The frame runs a call deferred by a function a panic is unwinding,
+    /// so `recover` in it stops the panic.
+    recovers: bool,
+}
+
+/// A panic on its way up a goroutine's stack, with the stack as it was
+/// when the panic began.
+#[derive(Debug, Clone)]
+struct Panic {
+    value: Value,
+    trace: String,
+}
+
+/// A goroutine set aside while another one runs.
+struct Goroutine {
+    id: usize,
+    stack: Vec<Value>,
+    sp: usize,
+    frames: Vec<Frame>,
+    blocked: Option<Blocked>,
+    panicking: Option<Panic>,
+}
+
+/// A channel operation a goroutine is blocked in. It can run again once
+/// `wake` is filled, and then finishes the operation.
+struct Blocked {
+    op: Opcode,
+    wake: Rc<RefCell<Option<Wakeup>>>,
+    /// Which cases send, for a select.
+    sends: Vec<bool>,
+    /// Where `OpNext` jumps once a ranged-over channel is closed.
+    target: usize,
+}
+
+/// A xorshift generator for the choices Go leaves to chance, such as
+/// where map iteration starts and which goroutine runs next.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // a zero state would stay zero
+        Rng((seed ^ 0x9E37_79B9_7F4A_7C15).max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+}
+
+pub struct Vm {
+    constants: Vec<Value>,
+    stack: Vec<Value>,
+    /// Points to the next free slot; the top of the stack is `stack[sp - 1]`.
+    sp: usize,
+    /// Grows as the program defines globals.
+    pub globals: Vec<Var>,
+    frames: Vec<Frame>,
+    rng: Rng,
+    /// The running goroutine; 0 is the one the program started in.
+    goroutine: usize,
+    /// Every other goroutine, blocked or ready to run.
+    parked: Vec<Goroutine>,
+    next_goroutine: usize,
+    /// Instructions run since the last switch.
+    ticks: usize,
+    /// The running goroutine's panic, if one is unwinding it.
+    panicking: Option<Panic>,
+    /// Stack trace of the panic that ended the run.
+    trace: Option<String>,
+    /// `os.Args`.
+    args: Vec<String>,
+    /// The status `os.Exit` ended the run with.
+    exit: Option<i32>,
+    limits: Limits,
+    /// Instructions run, in all goroutines.
+    executed: u64,
+    deadline: Option<Instant>,
+    /// The limit that ended the run.
+    exceeded: Option<Limit>,
+    /// What the run has allocated and collected, which the heap charges
+    /// go to while it runs.
+    account: heap::Account,
+}
+
+impl Vm {
+    pub fn new(bytecode: Bytecode) -> Vm {
+        Vm::new_with_globals(bytecode, vec![])
+    }
+
+    /// A VM that keeps the globals of an earlier run, as the REPL does
+    /// between lines.
+    pub fn new_with_globals(bytecode: Bytecode, globals: Vec<Var>) -> Vm {
+        let main_fn = CompiledFunction {
+            instructions: bytecode.instructions,
+            num_locals: 0,
+            parameters: vec![],
+            name: TOP_LEVEL_NAME.to_string(),
+            result: None,
+            positions: bytecode.positions,
+        };
+        let main_closure = Rc::new(Closure { func: Rc::new(main_fn), free: vec![] });
+        // wasm32 has no clock to seed from; runs there repeat
+        let seed = match cfg!(target_arch = "wasm32") {
+            true => 0,
+            false => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64),
+        };
+        Vm {
+            constants: bytecode.constants,
+            stack: vec![],
+            sp: 0,
+            globals,
+            frames: vec![new_frame(main_closure, vec![], 0).unwrap()],
+            rng: Rng::new(seed),
+            goroutine: 0,
+            parked: vec![],
+            next_goroutine: 1,
+            ticks: 0,
+            panicking: None,
+            trace: None,
+            args: vec![],
+            exit: None,
+            limits: Limits::default(),
+            executed: 0,
+            deadline: None,
+            exceeded: None,
+            account: heap::Account::for_run(),
+        }
+    }
+
+    /// Makes the run repeatable: the same seed gives the same map
+    /// iteration orders and the same goroutine schedule.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Sets what the program sees as `os.Args`: its name, then its
+    /// arguments.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    /// Bounds what the run may use from now on.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.deadline = match limits.timeout {
+            Some(timeout) if !cfg!(target_arch = "wasm32") => Some(Instant::now() + timeout),
+            _ => None,
+        };
+        self.account.limit = limits.heap;
+        self.limits = limits;
+    }
+
+    /// Makes the collector run after every instruction that allocates,
+    /// for testing that programs and the VM do not depend on when it runs.
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.account.stress = stress;
+    }
+
+    /// What the run has allocated and collected so far.
+    pub fn mem_stats(&self) -> heap::Stats {
+        self.account.stats()
+    }
+
+    /// The limit the run went past, if it stopped at one.
+    pub fn exceeded(&self) -> Option<Limit> {
+        self.exceeded
+    }
+
+    /// The status the program passed to `os.Exit`, if it called it.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit
+    }
+
+    /// The value of global `index`; nil before it is defined.
+    pub fn global(&self, index: usize) -> Value {
+        match self.globals.get(index) {
+            Some(var) => var.borrow().clone(),
+            None => Value::Null,
+        }
+    }
+
+    fn global_var(&mut self, index: usize) -> Var {
+        if index >= self.globals.len() {
+            self.globals.resize_with(index + 1, || new_var(Value::Null));
+        }
+        Rc::clone(&self.globals[index])
+    }
+
+    /// Runs the top-level program to its end.
+    pub fn run(&mut self) -> Result<(), String> {
+        self.metered(|vm| vm.execute(0))
+    }
+
+    /// Runs a program as `namu run` does: its top-level statements, then
+    /// `main`, the closure in global `main` when it defines one. Gives the
+    /// status it exits with, that `os.Exit` set or zero, or how it failed,
+    /// as Go reports a panic and its trace or a fatal error.
+    pub fn run_main(&mut self, main: Option<usize>) -> Result<i32, String> {
+        let mut result = self.run();
+        if let (Ok(()), None, Some(index)) = (&result, self.exit_code(), main) {
+            if let main @ Value::Closure(_) = self.global(index) {
+                result = self.call(main, vec![]).map(|_| ());
+            }
+        }
+        if let Some(code) = self.exit_code() {
+            return Ok(code);
+        }
+        result.map(|()| 0).map_err(|msg| match self.trace() {
+            Some(trace) => format!("panic: {}\n\n{}", msg, trace.trim_end()),
+            None => format!("fatal error: {}", msg),
+        })
+    }
+
+    /// Runs `f` with the heap charged to the run's account.
+    fn metered<R>(&mut self, f: impl FnOnce(&mut Vm) -> Result<R, String>) -> Result<R, String> {
+        let outer = heap::swap(mem::take(&mut self.account));
+        let result = f(self);
+        self.account = heap::swap(outer);
+        result
+    }
+
+    /// The stack trace of the panic that ended the run, if one did; a
+    /// deadlock has none.
+    pub fn trace(&self) -> Option<&str> {
+        self.trace.as_deref()
+    }
+
+    /// Calls a function value with `args` and runs it to completion.
+    pub fn call(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, String> {
+        self.metered(|vm| {
+            let depth = vm.frames.len();
+            let num_args = args.len();
+            vm.push(callee)?;
+            for arg in args {
+                vm.push(arg)?;
+            }
+            let is_builtin = matches!(vm.stack[vm.sp - 1 - num_args], Value::Builtin(_) | Value::Host(_) | Value::Method(_));
+            vm.call_function(num_args)?;
+            if !is_builtin {
+                vm.execute(depth)?;
+            }
+            vm.pop()
+        })
+    }
+
+    /// The value most recently removed by `OpPop`, i.e. the value of the
+    /// last expression statement.
+    pub fn last_popped_stack_elem(&self) -> Value {
+        self.stack.get(self.sp).cloned().unwrap_or(Value::Null)
+    }
+
+    /// Stacks grow as they are used, so idle goroutines stay small.
+    fn push(&mut self, value: Value) -> Result<(), String> {
+        if self.sp >= STACK_SIZE {
+            return Err("stack overflow".to_string());
+        }
+        if self.sp == self.stack.len() {
+            self.stack.push(value);
+        } else {
+            self.stack[self.sp] = value;
+        }
+        self.sp += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Value, String> {
+        if self.sp == 0 {
+            return Err("stack underflow".to_string());
+        }
+        self.sp -= 1;
+        Ok(self.stack[self.sp].clone())
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    /// Reads a two-byte operand at the frame's ip and advances past it.
+    fn read_u16_operand(&mut self) -> usize {
+        let frame = self.frame();
+        let operand = read_u16(&frame.cl.func.instructions[frame.ip..]) as usize;
+        frame.ip += 2;
+        operand
+    }
+
+    fn read_u8_operand(&mut self) -> usize {
+        let frame = self.frame();
+        let operand = frame.cl.func.instructions[frame.ip] as usize;
+        frame.ip += 1;
+        operand
+    }
+
+    fn constant_type(&self, index: usize) -> Result<Type, String> {
+        match &self.constants[index] {
+            Value::Type(ty) => Ok(ty.clone()),
+            value => Err(format!("not a type: {}", value.type_name())),
+        }
+    }
+
+    fn constant_name(&self, index: usize) -> String {
+        self.constants[index].inspect()
+    }
+
+    /// Runs instructions until the first goroutine's frame stack shrinks
+    /// to `depth` or the top-level program runs out of instructions.
+    /// Other goroutines run in between as the scheduler picks them. A
+    /// failed instruction panics; the error returned is that of a panic
+    /// nothing recovered, or of a deadlock.
+    fn execute(&mut self, depth: usize) -> Result<(), String> {
+        loop {
+            if self.exit.is_some() {
+                // deferred calls do not run
+                break;
+            }
+            if self.frames.is_empty() {
+                // a goroutine returned from its function or blocked
+                self.resume()?;
+                continue;
+            }
+            if self.goroutine == 0 && self.frames.len() <= depth {
+                break;
+            }
+            if self.frame().unwinding {
+                self.unwind(depth)?;
+                continue;
+            }
+            self.ticks += 1;
+            if self.ticks >= TIME_SLICE && !self.parked.is_empty() {
+                self.park(None);
+                self.resume()?;
+                continue;
+            }
+            let frame = self.frame();
+            if frame.ip >= frame.cl.func.instructions.len() {
+                break;
+            }
+            let op = frame.cl.func.instructions[frame.ip];
+            frame.ip += 1;
+            self.check_limits()?;
+            if let Err(msg) = self.step(op) {
+                if heap::refused() {
+                    return Err(self.exceed(Limit::Heap(self.limits.heap.unwrap_or_default())));
+                }
+                if self.exceeded.is_some() {
+                    return Err(msg);
+                }
+                self.throw(runtime_panic(msg));
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops the run at the limit it went past.
+    fn exceed(&mut self, limit: Limit) -> String {
+        self.exceeded = Some(limit);
+        limit.to_string()
+    }
+
+    /// Counts the instruction about to run, and stops the run if it has
+    /// gone past its instructions, time or heap. Collects when the heap
+    /// has grown enough since the last collection.
+    fn check_limits(&mut self) -> Result<(), String> {
+        self.executed += 1;
+        if let Some(max) = self.limits.instructions.filter(|&max| self.executed > max) {
+            return Err(self.exceed(Limit::Instructions(max)));
+        }
+        if let (Some(deadline), 0) = (self.deadline, self.executed % CLOCK_INTERVAL) {
+            if Instant::now() >= deadline {
+                return Err(self.exceed(Limit::Timeout(self.limits.timeout.unwrap_or_default())));
+            }
+        }
+        if heap::due() {
+            self.collect()?;
+        }
+        Ok(())
+    }
+
+    /// Frees what only cycles of references keep alive, and stops the
+    /// run if what is left is past its heap limit.
+    fn collect(&mut self) -> Result<(), String> {
+        let reachable = self.reachable();
+        heap::collect(&reachable);
+        if !heap::measured(reachable.bytes) {
+            return Err(self.exceed(Limit::Heap(self.limits.heap.unwrap_or_default())));
+        }
+        Ok(())
+    }
+
+    /// Traces the values the program can still reach: those of the
+    /// globals, and those on the stacks of its goroutines.
+    fn reachable(&self) -> heap::Tracer {
+        let mut tracer = heap::Tracer::new();
+        for var in &self.globals {
+            tracer.var(var);
+        }
+        let running = (&self.stack[..self.sp], &self.frames, &self.panicking);
+        let parked = self.parked.iter().map(|g| (&g.stack[..g.sp], &g.frames, &g.panicking));
+        for (stack, frames, panicking) in std::iter::once(running).chain(parked) {
+            tracer.bytes += stack.len() * heap::SLOT;
+            for value in stack {
+                tracer.value(value);
+            }
+            for frame in frames {
+                tracer.value(&Value::Closure(Rc::clone(&frame.cl)));
+                for var in &frame.locals {
+                    tracer.var(var);
+                }
+                for (callee, args) in &frame.defers {
+                    tracer.value(callee);
+                    args.iter().for_each(|arg| tracer.value(arg));
+                }
+                if let Some(value) = &frame.returning {
+                    tracer.value(value);
+                }
+            }
+            if let Some(panic) = panicking {
+                tracer.value(&panic.value);
+            }
+        }
+        tracer
+    }
+
+    /// Runs the instruction `op`, whose operands follow at the frame's ip.
+    fn step(&mut self, op: Opcode) -> Result<(), String> {
+        match op {
+            OP_CONSTANT => {
+                let index = self.read_u16_operand();
+                self.push(self.constants[index].clone())?;
+            }
+            OP_POP => {
+                self.pop()?;
+            }
+            OP_ADD | OP_SUB | OP_MUL | OP_DIV | OP_MOD => self.execute_binary_operation(op)?,
+            OP_EQUAL | OP_NOT_EQUAL | OP_GREATER_THAN | OP_GREATER_EQUAL => self.execute_comparison(op)?,
+            OP_TRUE => self.push(Value::Boolean(true))?,
+            OP_FALSE => self.push(Value::Boolean(false))?,
+            OP_NULL => self.push(Value::Null)?,
+            OP_BANG => match self.pop()? {
+                Value::Boolean(b) => self.push(Value::Boolean(!b))?,
+                value => return Err(format!("invalid operation: operator ! not defined on {}", value.ty())),
+            },
+            OP_MINUS => match self.pop()? {
+                Value::Integer(i) => self.push(Value::Integer(i.wrapping_neg()))?,
+                Value::Float(f) => self.push(Value::Float(-f))?,
+                value => return Err(format!("invalid operation: operator - not defined on {}", value.ty())),
+            },
+            OP_JUMP => {
+                let target = self.read_u16_operand();
+                self.frame().ip = target;
+            }
+            OP_JUMP_NOT_TRUTHY => {
+                let target = self.read_u16_operand();
+                match self.pop()? {
+                    Value::Boolean(true) => {}
+                    Value::Boolean(false) => self.frame().ip = target,
+                    value => return Err(format!("non-boolean condition in if expression: {}", value.ty())),
+                }
+            }
+            OP_SET_GLOBAL => {
+                let index = self.read_u16_operand();
+                let value = self.pop()?;
+                self.global_var(index);
+                self.globals[index] = new_var(value);
+            }
+            OP_GET_GLOBAL => {
+                let index = self.read_u16_operand();
+                self.push(self.global(index))?;
+            }
+            OP_ASSIGN_GLOBAL => {
+                let index = self.read_u16_operand();
+                let var = self.global_var(index);
+                self.assign(&var)?;
+            }
+            OP_ADDR_GLOBAL => {
+                let index = self.read_u16_operand();
+                let var = self.global_var(index);
+                self.push(Value::Pointer(Pointer::to_var(var)))?;
+            }
+            OP_SET_LOCAL => {
+                let index = self.read_u8_operand();
+                let value = self.pop()?;
+                self.frame().locals[index] = new_var(value);
+            }
+            OP_GET_LOCAL => {
+                let index = self.read_u8_operand();
+                let value = self.frame().locals[index].borrow().clone();
+                self.push(value)?;
+            }
+            OP_ASSIGN_LOCAL => {
+                let index = self.read_u8_operand();
+                let var = Rc::clone(&self.frame().locals[index]);
+                self.assign(&var)?;
+            }
+            OP_ADDR_LOCAL => {
+                let index = self.read_u8_operand();
+                let var = Rc::clone(&self.frame().locals[index]);
+                self.push(Value::Pointer(Pointer::to_var(var)))?;
+            }
+            OP_GET_FREE => {
+                let index = self.read_u8_operand();
+                let value = self.frame().cl.free[index].borrow().clone();
+                self.push(value)?;
+            }
+            OP_ASSIGN_FREE => {
+                let index = self.read_u8_operand();
+                let var = Rc::clone(&self.frame().cl.free[index]);
+                self.assign(&var)?;
+            }
+            OP_ADDR_FREE => {
+                let index = self.read_u8_operand();
+                let var = Rc::clone(&self.frame().cl.free[index]);
+                self.push(Value::Pointer(Pointer::to_var(var)))?;
+            }
+            OP_GET_BUILTIN => {
+                let index = self.read_u8_operand();
+                self.push(Value::Builtin(BUILTINS[index]))?;
+            }
+            OP_CURRENT_CLOSURE => {
+                let cl = Rc::clone(&self.frame().cl);
+                self.push(Value::Closure(cl))?;
+            }
+            OP_CLOSURE => {
+                let index = self.read_u16_operand();
+                let num_free = self.read_u8_operand();
+                self.push_closure(index, num_free)?;
+            }
+            OP_CALL => {
+                let num_args = self.read_u8_operand();
+                self.call_function(num_args)?;
+            }
+            OP_UNPACK => {
+                let n = self.read_u8_operand();
+                match self.pop()? {
+                    Value::Tuple(values) if values.len() == n => {
+                        for value in values.iter() {
+                            self.push(value.clone())?;
+                        }
+                    }
+                    value => return Err(format!("cannot unpack {} into {} values", value.type_name(), n)),
+                }
+            }
+            OP_CALL_SPREAD => {
+                let num_args = self.read_u8_operand();
+                let rest = self.pop()?;
+                // the elements go to the callee as they are, not onto the
+                // stack, which a long slice would overflow
+                let base = self.sp - num_args;
+                let result = match &self.stack[base] {
+                    // f(args, s...) for a variadic function of the embedding program
+                    Value::Host(h) => {
+                        let h = Rc::clone(h);
+                        let mut args = self.stack[base + 1..self.sp].to_vec();
+                        match rest {
+                            Value::Slice(s) => args.extend(s.values()),
+                            Value::Null => {}
+                            rest => return Err(format!("cannot use {} value as slice value in argument to {}", rest.ty(), h.name)),
+                        }
+                        (h.func)(args)?
+                    }
+                    // only append takes ...
+                    _ => append_spread(&self.stack[base + 1], &rest)?,
+                };
+                self.sp = base;
+                self.push(result)?;
+            }
+            OP_RETURN_VALUE => {
+                let value = self.pop()?;
+                self.start_return(Some(value))?;
+            }
+            OP_RETURN => self.start_return(None)?,
+            OP_INDEX => {
+                let key = self.pop()?;
+                let x = self.pop()?;
+                self.push(index(&x, &key)?)?;
+            }
+            OP_INDEX_OK => {
+                let key = self.pop()?;
+                let m = match self.pop()? {
+                    Value::Map(m) => m,
+                    x => return Err(format!("invalid operation: comma-ok index of {} value", x.ty())),
+                };
+                let found = m.get(&key)?;
+                let ok = found.is_some();
+                self.push(found.unwrap_or_else(|| m.elem.zero()))?;
+                self.push(Value::Boolean(ok))?;
+            }
+            OP_SET_INDEX => {
+                let key = self.pop()?;
+                let x = self.pop()?;
+                let value = self.pop()?;
+                match &x {
+                    Value::Slice(s) => {
+                        let i = bounds_check(&key, s.len)?;
+                        s.set(i, value)?;
+                    }
+                    Value::Map(m) => m.insert(key, value)?,
+                    _ => return Err(format!("cannot assign to element of {} value", x.ty())),
+                }
+            }
+            OP_INDEX_ADDR => {
+                let key = self.pop()?;
+                match self.pop()? {
+                    Value::Slice(s) => {
+                        let i = bounds_check(&key, s.len)?;
+                        self.push(Value::Pointer(Pointer { elem: s.elem.clone(), target: Some(s.place(i)) }))?;
+                    }
+                    Value::Map(_) => return Err("cannot take the address of a map element".to_string()),
+                    x => return Err(format!("cannot take the address of an element of {} value", x.ty())),
+                }
+            }
+            OP_SLICE => {
+                let high = self.pop()?;
+                let low = self.pop()?;
+                let x = self.pop()?;
+                self.push(slice(&x, &low, &high)?)?;
+            }
+            OP_FIELD => {
+                let name = self.read_u16_operand();
+                let name = self.constant_name(name);
+                let x = self.pop()?;
+                if let Some(method) = crate::stdlib::method(&x, &name) {
+                    return self.push(method);
+                }
+                let dynamic = match &x {
+                    Value::Interface(Interface { value: Some(v), .. }) => v.as_ref(),
+                    x => x,
+                };
+                if let Value::Opaque(o) = dynamic {
+                    return match o.method(&name) {
+                        Some(method) => self.push(method),
+                        None => Err(no_field(&dynamic.ty(), &name)),
+                    };
+                }
+                let x = match x {
+                    Value::Pointer(p) => p.load()?,
+                    x => x,
+                };
+                match &x {
+                    Value::Struct(s) => match s.ty.field_index(&name) {
+                        Some(i) => self.push(s.fields[i].clone())?,
+                        None => return Err(no_field(&x.ty(), &name)),
+                    },
+                    _ => return Err(no_field(&x.ty(), &name)),
+                }
+            }
+            OP_FIELD_ADDR => {
+                let name = self.read_u16_operand();
+                let name = self.constant_name(name);
+                let p = match self.pop()? {
+                    Value::Pointer(p) => p,
+                    x => return Err(format!("cannot assign to a field of a non-addressable {} value", x.ty())),
+                };
+                // one automatic dereference: p.f for a pointer p
+                let p = match p.load()? {
+                    Value::Pointer(inner) => inner,
+                    _ => p,
+                };
+                let i = match p.load()? {
+                    Value::Struct(s) => s.ty.field_index(&name),
+                    _ => None,
+                };
+                match i {
+                    Some(i) => self.push(Value::Pointer(p.field(i)?))?,
+                    None => return Err(no_field(&p.elem, &name)),
+                }
+            }
+            OP_DEREF => match self.pop()? {
+                Value::Pointer(p) => self.push(p.load()?)?,
+                x => return Err(format!("invalid operation: cannot indirect {} value", x.ty())),
+            },
+            OP_STORE => {
+                let p = self.pop()?;
+                let value = self.pop()?;
+                match p {
+                    Value::Pointer(p) => p.store(value)?,
+                    x => return Err(format!("invalid operation: cannot indirect {} value", x.ty())),
+                }
+            }
+            OP_NEW_POINTER => {
+                heap::charge_slots(1)?;
+                let value = self.pop()?;
+                self.push(Value::Pointer(Pointer::to_var(new_var(value))))?;
+            }
+            OP_COMPOSITE => {
+                let ty = self.read_u16_operand();
+                let pairs = self.read_u16_operand();
+                let ty = self.constant_type(ty)?;
+                let values = self.stack[self.sp - 2 * pairs..self.sp].to_vec();
+                self.sp -= 2 * pairs;
+                self.push(composite(&ty, values)?)?;
+            }
+            OP_ASSERT | OP_ASSERT_OK => {
+                let ty = self.read_u16_operand();
+                let ty = self.constant_type(ty)?;
+                let x = self.pop()?;
+                let asserted = assert(&x, &ty);
+                if op == OP_ASSERT {
+                    self.push(asserted?)?;
+                } else {
+                    let ok = asserted.is_ok();
+                    self.push(asserted.unwrap_or_else(|_| ty.zero()))?;
+                    self.push(Value::Boolean(ok))?;
+                }
+            }
+            OP_CONVERT => {
+                let ty = self.read_u16_operand();
+                let explicit = self.read_u8_operand() == 1;
+                let ty = self.constant_type(ty)?;
+                let x = self.pop()?;
+                let converted = if explicit { conversion(x, &ty)? } else { convert(x, &ty)? };
+                self.push(converted)?;
+            }
+            OP_RANGE => {
+                let iter = match self.pop()? {
+                    Value::Slice(s) => RangeIter::Slice(s, 0),
+                    Value::String(s) => RangeIter::String(s, 0),
+                    Value::Map(m) => {
+                        let start = self.rng.next_u64() as usize;
+                        let keys = m.keys_from(start);
+                        RangeIter::Map(m, keys, 0)
+                    }
+                    Value::Integer(n) => RangeIter::Int(n, 0),
+                    Value::Chan(c) => RangeIter::Chan(c),
+                    x => return Err(format!("cannot range over {} value", x.ty())),
+                };
+                self.push(Value::Iterator(Rc::new(RefCell::new(iter))))?;
+            }
+            OP_NEXT => {
+                let target = self.read_u16_operand();
+                let iter = match self.pop()? {
+                    Value::Iterator(iter) => iter,
+                    x => return Err(format!("not a range iterator: {}", x.type_name())),
+                };
+                let chan = match &*iter.borrow() {
+                    RangeIter::Chan(c) => Some(c.clone()),
+                    _ => None,
+                };
+                if let Some(c) = chan {
+                    match c.try_recv() {
+                        Some((value, true)) => {
+                            self.push(value)?;
+                            self.push(Value::Null)?;
+                        }
+                        Some((_, false)) => self.frame().ip = target,
+                        None => {
+                            let wake = Rc::new(RefCell::new(None));
+                            c.wait_recv(Waiter::new(0, &wake));
+                            self.block(Blocked { op, wake, sends: vec![false], target })?;
+                        }
+                    }
+                    return Ok(());
+                }
+                let next = next(&mut iter.borrow_mut())?;
+                match next {
+                    Some((key, value)) => {
+                        self.push(key)?;
+                        self.push(value)?;
+                    }
+                    None => self.frame().ip = target,
+                }
+            }
+            OP_GO => {
+                let num_args = self.read_u8_operand();
+                let args = self.stack[self.sp - num_args..self.sp].to_vec();
+                let callee = self.stack[self.sp - num_args - 1].clone();
+                self.sp -= num_args + 1;
+                match callee {
+                    Value::Closure(cl) => {
+                        if let Some(max) = self.limits.goroutines.filter(|&max| self.parked.len() + 1 >= max) {
+                            return Err(self.exceed(Limit::Goroutines(max)));
+                        }
+                        // the callee's slot stays below the new frame, as for a call
+                        let frame = new_frame(cl, args, 1)?;
+                        let id = self.next_goroutine;
+                        self.next_goroutine += 1;
+                        self.parked.push(Goroutine {
+                            id,
+                            stack: vec![Value::Null],
+                            sp: 1,
+                            frames: vec![frame],
+                            blocked: None,
+                            panicking: None,
+                        });
+                    }
+                    Value::Builtin(builtin) => {
+                        (builtin.func)(args)?;
+                    }
+                    Value::Host(host) => {
+                        (host.func)(args)?;
+                    }
+                    value => return Err(format!("cannot call non-function (type {})", value.ty())),
+                }
+            }
+            OP_SEND => {
+                let value = self.pop()?;
+                let c = self.pop_chan("send to")?;
+                if !c.try_send(value.clone())? {
+                    let wake = Rc::new(RefCell::new(None));
+                    c.wait_send(Waiter::new(0, &wake), value)?;
+                    self.block(Blocked { op, wake, sends: vec![true], target: 0 })?;
+                }
+            }
+            OP_RECV | OP_RECV_OK => {
+                let c = self.pop_chan("receive from")?;
+                match c.try_recv() {
+                    Some((value, ok)) => {
+                        self.push(value)?;
+                        if op == OP_RECV_OK {
+                            self.push(Value::Boolean(ok))?;
+                        }
+                    }
+                    None => {
+                        let wake = Rc::new(RefCell::new(None));
+                        c.wait_recv(Waiter::new(0, &wake));
+                        self.block(Blocked { op, wake, sends: vec![false], target: 0 })?;
+                    }
+                }
+            }
+            OP_SELECT => {
+                let num_cases = self.read_u8_operand();
+                let has_default = self.read_u8_operand() == 1;
+                self.select(num_cases, has_default)?;
+            }
+            OP_DEFER => {
+                let num_args = self.read_u8_operand();
+                let args = self.stack[self.sp - num_args..self.sp].to_vec();
+                let callee = self.stack[self.sp - num_args - 1].clone();
+                self.sp -= num_args + 1;
+                self.frame().defers.push((callee, args));
+            }
+            _ => return Err(format!("unknown opcode {}", op)),
+        }
+        Ok(())
+    }
+
+    /// Pops a value and stores it into `var` as assignment does.
+    fn assign(&mut self, var: &Var) -> Result<(), String> {
+        let value = self.pop()?;
+        let value = assign(&var.borrow(), value)?;
+        *var.borrow_mut() = value;
+        Ok(())
+    }
+
+    fn execute_binary_operation(&mut self, op: Opcode) -> Result<(), String> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        let (l, r) = match (&left, &right) {
+            (Value::Integer(l), Value::Integer(r)) => (*l, *r),
+            // an integer with a float is taken as an untyped constant
+            (Value::Float(_), Value::Float(_) | Value::Integer(_)) | (Value::Integer(_), Value::Float(_)) if op != OP_MOD => {
+                let (l, r) = (float(&left), float(&right));
+                let result = match op {
+                    OP_ADD => l + r,
+                    OP_SUB => l - r,
+                    OP_MUL => l * r,
+                    _ => l / r,
+                };
+                return self.push(Value::Float(result));
+            }
+            (Value::String(l), Value::String(r)) if op == OP_ADD => {
+                heap::charge(l.len() + r.len())?;
+                let mut s = l.to_vec();
+                s.extend_from_slice(r);
+                return self.push(Value::String(Rc::from(s)));
+            }
+            _ if left.ty().identical(&right.ty()) => {
+                let symbol = match op {
+                    OP_ADD => "+",
+                    OP_SUB => "-",
+                    OP_MUL => "*",
+                    OP_DIV => "/",
+                    _ => "%",
+                };
+                return Err(format!("invalid operation: operator {} not defined on {}", symbol, left.ty()));
+            }
+            _ => return Err(format!("invalid operation: mismatched types {} and {}", left.ty(), right.ty())),
+        };
+        let result = match op {
+            OP_ADD => l.wrapping_add(r),
+            OP_SUB => l.wrapping_sub(r),
+            OP_MUL => l.wrapping_mul(r),
+            _ => {
+                if r == 0 {
+                    return Err("integer divide by zero".to_string());
+                }
+                if op == OP_DIV {
+                    l.wrapping_div(r)
+                } else {
+                    l.wrapping_rem(r)
+                }
+            }
+        };
+        self.push(Value::Integer(result))
+    }
+
+    fn execute_comparison(&mut self, op: Opcode) -> Result<(), String> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        let result = match (op, &left, &right) {
+            (OP_EQUAL, _, _) => left.equals(&right)?,
+            (OP_NOT_EQUAL, _, _) => !left.equals(&right)?,
+            (OP_GREATER_THAN, Value::Integer(l), Value::Integer(r)) => l > r,
+            (OP_GREATER_EQUAL, Value::Integer(l), Value::Integer(r)) => l >= r,
+            (OP_GREATER_THAN, Value::Float(_) | Value::Integer(_), Value::Float(_) | Value::Integer(_)) => float(&left) > float(&right),
+            (OP_GREATER_EQUAL, Value::Float(_) | Value::Integer(_), Value::Float(_) | Value::Integer(_)) => {
+                float(&left) >= float(&right)
+            }
+            (OP_GREATER_THAN, Value::String(l), Value::String(r)) => l > r,
+            (OP_GREATER_EQUAL, Value::String(l), Value::String(r)) => l >= r,
+            _ if left.ty().identical(&right.ty()) => {
+                return Err(format!("invalid operation: ordering not defined on {}", left.ty()))
+            }
+            _ => return Err(format!("invalid operation: mismatched types {} and {}", left.ty(), right.ty())),
+        };
+        self.push(Value::Boolean(result))
+    }
+
+    /// Sets the running goroutine aside, blocked in `blocked` if given.
+    fn park(&mut self, blocked: Option<Blocked>) {
+        let g = Goroutine {
+            id: self.goroutine,
+            stack: std::mem::take(&mut self.stack),
+            sp: self.sp,
+            frames: std::mem::take(&mut self.frames),
+            blocked,
+            panicking: self.panicking.take(),
+        };
+        self.parked.push(g);
+    }
+
+    /// Switches to a goroutine picked at random from those that can run,
+    /// finishing the channel operation it was blocked in. Only a deadlock
+    /// is an error; the operation failing panics the goroutine.
+    fn resume(&mut self) -> Result<(), String> {
+        let runnable: Vec<usize> = (0..self.parked.len())
+            .filter(|&i| self.parked[i].blocked.as_ref().is_none_or(|b| b.wake.borrow().is_some()))
+            .collect();
+        if runnable.is_empty() {
+            return Err("all goroutines are asleep - deadlock!".to_string());
+        }
+        let i = runnable[(self.rng.next_u64() % runnable.len() as u64) as usize];
+        let g = self.parked.swap_remove(i);
+        self.goroutine = g.id;
+        self.stack = g.stack;
+        self.sp = g.sp;
+        self.frames = g.frames;
+        self.panicking = g.panicking;
+        self.ticks = 0;
+        if let Some(blocked) = g.blocked {
+            if let Err(msg) = self.complete(blocked) {
+                self.throw(runtime_panic(msg));
+            }
+        }
+        Ok(())
+    }
+
+    /// Finishes the channel operation a goroutine that was woken is
+    /// blocked in.
+    fn complete(&mut self, blocked: Blocked) -> Result<(), String> {
+        // other channels of a select still hold the wake, so it is left
+        // filled for them to see
+        let Wakeup { case, value, ok } = blocked.wake.borrow().clone().unwrap();
+        if blocked.sends[case] && !ok {
+            return Err("send on closed channel".to_string());
+        }
+        match blocked.op {
+            OP_RECV => self.push(value),
+            OP_RECV_OK => {
+                self.push(value)?;
+                self.push(Value::Boolean(ok))
+            }
+            OP_SELECT => {
+                self.push(value)?;
+                self.push(Value::Boolean(ok))?;
+                self.push(Value::Integer(case as i64))
+            }
+            OP_NEXT if ok => {
+                self.push(value)?;
+                self.push(Value::Null)
+            }
+            OP_NEXT => {
+                self.frame().ip = blocked.target;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Blocks the running goroutine; another runs from the next
+    /// instruction on.
+    fn block(&mut self, blocked: Blocked) -> Result<(), String> {
+        self.park(Some(blocked));
+        Ok(())
+    }
+
+    fn pop_chan(&mut self, action: &str) -> Result<Chan, String> {
+        match self.pop()? {
+            Value::Chan(c) => Ok(c),
+            x => Err(format!("invalid operation: cannot {} non-channel {} (type {})", action, x.inspect(), x.ty())),
+        }
+    }
+
+    /// `select` over `num_cases` (is send, channel, value) triples on the
+    /// stack. Pushes the value received, whether it was, and the index of
+    /// the case chosen; the default case comes after the others.
+    fn select(&mut self, num_cases: usize, has_default: bool) -> Result<(), String> {
+        let operands = self.stack[self.sp - 3 * num_cases..self.sp].to_vec();
+        self.sp -= 3 * num_cases;
+        let mut cases = vec![];
+        for case in operands.chunks(3) {
+            let send = matches!(case[0], Value::Boolean(true));
+            let c = match &case[1] {
+                Value::Chan(c) => c.clone(),
+                x => {
+                    let action = if send { "send to" } else { "receive from" };
+                    return Err(format!("invalid operation: cannot {} non-channel {} (type {})", action, x.inspect(), x.ty()));
+                }
+            };
+            cases.push((send, c, case[2].clone()));
+        }
+
+        // ready cases are taken in a random order
+        let mut order: Vec<usize> = (0..num_cases).collect();
+        for i in (1..num_cases).rev() {
+            order.swap(i, (self.rng.next_u64() % (i as u64 + 1)) as usize);
+        }
+        for &i in &order {
+            let (send, c, value) = &cases[i];
+            let done = if *send { c.try_send(value.clone())?.then_some((Value::Null, true)) } else { c.try_recv() };
+            if let Some((value, ok)) = done {
+                self.push(value)?;
+                self.push(Value::Boolean(ok))?;
+                return self.push(Value::Integer(i as i64));
+            }
+        }
+        if has_default {
+            self.push(Value::Null)?;
+            self.push(Value::Boolean(false))?;
+            return self.push(Value::Integer(num_cases as i64));
+        }
+
+        let wake = Rc::new(RefCell::new(None));
+        for (i, (send, c, value)) in cases.iter().enumerate() {
+            if *send {
+                c.wait_send(Waiter::new(i, &wake), value.clone())?;
+            } else {
+                c.wait_recv(Waiter::new(i, &wake));
+            }
+        }
+        let sends = cases.iter().map(|(send, _, _)| *send).collect();
+        self.block(Blocked { op: OP_SELECT, wake, sends, target: 0 })
+    }
+
+    fn call_function(&mut self, mut num_args: usize) -> Result<(), String> {
+        if num_args == 1 {
+            if let Value::Tuple(values) = self.stack[self.sp - 1].clone() {
+                // f(g()) passes each result of g
+                self.sp -= 1;
+                for value in values.iter() {
+                    self.push(value.clone())?;
+                }
+                num_args = values.len();
+            }
+        }
+        let cl = match &self.stack[self.sp - 1 - num_args] {
+            Value::Closure(cl) => Rc::clone(cl),
+            Value::Method(method) => {
+                let method = Rc::clone(method);
+                let mut args = vec![method.recv.clone()];
+                args.extend_from_slice(&self.stack[self.sp - num_args..self.sp]);
+                let result = self.call_builtin(method.func, args)?;
+                self.sp -= num_args + 1;
+                return self.push(result);
+            }
+            Value::Builtin(builtin) => {
+                let builtin = *builtin;
+                let args = self.stack[self.sp - num_args..self.sp].to_vec();
+                let result = self.call_builtin(builtin, args)?;
+                self.sp -= num_args + 1;
+                return self.push(result);
+            }
+            Value::Host(host) => {
+                let host = Rc::clone(host);
+                let args = self.stack[self.sp - num_args..self.sp].to_vec();
+                let result = (host.func)(args)?;
+                self.sp -= num_args + 1;
+                return self.push(result);
+            }
+            value => return Err(format!("cannot call non-function (type {})", value.ty())),
+        };
+        self.check_depth()?;
+        let base_pointer = self.sp - num_args;
+        let frame = new_frame(cl, self.stack[base_pointer..self.sp].to_vec(), base_pointer)?;
+        self.frames.push(frame);
+        self.sp = base_pointer;
+        Ok(())
+    }
+
+    /// Calls a builtin. `panic` and `recover` act on the running
+    /// goroutine, and `os.Args`, `os.Exit` and `runtime.GC` on the VM, so
+    /// they are carried out here.
+    fn call_builtin(&mut self, builtin: Builtin, args: Vec<Value>) -> Result<Value, String> {
+        match (builtin.name, args.as_slice()) {
+            ("os.Args", []) => {
+                let args = self.args.iter().map(|arg| Value::string(arg)).collect();
+                Ok(Value::Slice(Slice::from_values(Type::String, args)))
+            }
+            ("os.Exit", _) => {
+                if let Value::Integer(code) = (builtin.func)(args)? {
+                    self.exit = Some(code as i32);
+                }
+                Ok(Value::Null)
+            }
+            ("runtime.GC", []) => {
+                self.collect()?;
+                Ok(Value::Null)
+            }
+            ("panic", [value]) => {
+                let value = match value {
+                    Value::Null | Value::Interface(Interface { value: None, .. }) => {
+                        Value::string("panic called with nil argument")
+                    }
+                    value => value.clone(),
+                };
+                self.throw(value);
+                Ok(Value::Null)
+            }
+            ("recover", []) => {
+                let frame = self.frames.last().unwrap();
+                let value = if frame.recovers { self.panicking.take().map(|p| p.value) } else { None };
+                convert(value.unwrap_or(Value::Null), &Type::any())
+            }
+            _ => (builtin.func)(args),
+        }
+    }
+
+    /// Starts returning `value` from the top frame. A named result takes
+    /// the value now, where deferred calls can still change it.
+    fn start_return(&mut self, value: Option<Value>) -> Result<(), String> {
+        let frame = self.frame();
+        if let (Some(i), Some(value)) = (frame.cl.func.result, &value) {
+            let var = Rc::clone(&frame.locals[i]);
+            let value = assign(&var.borrow(), value.clone())?;
+            *var.borrow_mut() = value;
+        }
+        let frame = self.frame();
+        frame.returning = Some(value.unwrap_or(Value::Null));
+        frame.unwinding = true;
+        Ok(())
+    }
+
+    /// Starts a panic with `value` in the running goroutine, in place of
+    /// any panic already unwinding it.
+    fn throw(&mut self, value: Value) {
+        let trace = self.traceback();
+        self.panicking = Some(Panic { value, trace });
+        let frame = self.frame();
+        frame.unwinding = true;
+        frame.returning = None;
+    }
+
+    /// Takes one step in leaving the top frame, which has returned or
+    /// panicked: starts its next deferred call, or pops it once there are
+    /// none. A panic that unwinds the whole goroutine is the error.
+    fn unwind(&mut self, depth: usize) -> Result<(), String> {
+        // A frame that returned has its value; a panic leaves it none. The
+        // functions a deferred call makes return normally, panic or not.
+        let panicking = self.panicking.is_some() && self.frame().returning.is_none();
+        let frame = self.frame();
+        if let Some((callee, args)) = frame.defers.pop() {
+            let recovers = panicking;
+            if let Err(msg) = self.call_deferred(callee, args, recovers) {
+                self.throw(runtime_panic(msg));
+            }
+            return Ok(());
+        }
+
+        let frame = self.frames.pop().unwrap();
+        self.sp = frame.base_pointer.saturating_sub(1);
+        if panicking {
+            if self.frames.is_empty() || (self.goroutine == 0 && self.frames.len() <= depth) {
+                let panic = self.panicking.take().unwrap();
+                self.trace = Some(panic.trace);
+                return Err(panic_message(&panic.value));
+            }
+            // the panic goes on in the caller
+            let caller = self.frame();
+            caller.unwinding = true;
+            caller.returning = None;
+            return Ok(());
+        }
+        if frame.deferred {
+            // the function that deferred the call goes on unwinding
+            return Ok(());
+        }
+        let value = match frame.cl.func.result {
+            Some(i) => frame.locals[i].borrow().clone(),
+            None => frame.returning.unwrap_or(Value::Null),
+        };
+        self.push(value)
+    }
+
+    fn call_deferred(&mut self, callee: Value, args: Vec<Value>, recovers: bool) -> Result<(), String> {
+        match callee {
+            Value::Closure(cl) => {
+                self.check_depth()?;
+                // the callee's slot stays below the new frame, as for a call
+                self.push(Value::Closure(Rc::clone(&cl)))?;
+                let mut frame = new_frame(cl, args, self.sp)?;
+                frame.deferred = true;
+                frame.recovers = recovers;
+                self.frames.push(frame);
+                Ok(())
+            }
+            // recover stops a panic only when the deferred function calls it
+            Value::Builtin(builtin) if builtin.name == "recover" => Ok(()),
+            Value::Builtin(builtin) => self.call_builtin(builtin, args).map(|_| ()),
+            Value::Host(host) => (host.func)(args).map(|_| ()),
+            value => Err(format!("cannot call non-function (type {})", value.ty())),
+        }
+    }
+
+    /// Fails a call that would go past the frames a goroutine may have.
+    fn check_depth(&mut self) -> Result<(), String> {
+        if let Some(max) = self.limits.call_depth.filter(|&max| self.frames.len() > max) {
+            return Err(self.exceed(Limit::CallDepth(max)));
+        }
+        if self.frames.len() >= MAX_FRAMES {
+            return Err("stack overflow".to_string());
+        }
+        Ok(())
+    }
+
+    /// The running goroutine's calls, innermost first, as Go prints them.
+    fn traceback(&self) -> String {
+        let mut out = format!("goroutine {} [running]:\n", self.goroutine + 1);
+        // the top-level program stays below main once it has run
+        for frame in self.frames.iter().rev().filter(|f| f.ip < f.cl.func.instructions.len()) {
+            let func = &frame.cl.func;
+            let args = if func.parameters.is_empty() { "()" } else { "(...)" };
+            out.push_str(&format!("{}{}\n", func.name, args));
+            // the ip is past the instruction running, or the call made
+            if let Some(pos) = position_at(&func.positions, frame.ip.saturating_sub(1)) {
+                out.push_str(&format!("\t{}:{}:{}\n", pos.file, pos.line, pos.column));
+            }
+        }
+        out
+    }
+
+    fn push_closure(&mut self, index: usize, num_free: usize) -> Result<(), String> {
+        let func = match &self.constants[index] {
+            Value::CompiledFunction(func) => Rc::clone(func),
+            value => return Err(format!("not a function: {}", value.type_name())),
+        };
+        // the captured variables arrive as pointers to them
+        let mut free = vec![];
+        for value in &self.stack[self.sp - num_free..self.sp] {
+            match value {
+                Value::Pointer(Pointer { target: Some(Place { root: Root::Var(var), path }), .. }) if path.is_empty() => {
+                    free.push(Rc::clone(var))
+                }
+                value => return Err(format!("cannot capture {}", value.type_name())),
+            }
+        }
+        self.sp -= num_free;
+        let closure = Rc::new(Closure { func, free });
+        heap::track_closure(&closure);
+        self.push(Value::Closure(closure))
+    }
+}
+
+/// A frame calling `cl` with `args`, whose stack starts at `base_pointer`.
+fn new_frame(cl: Rc<Closure>, args: Vec<Value>, base_pointer: usize) -> Result<Frame, String> {
+    let num_args = args.len();
+    if num_args != cl.func.num_parameters() {
+        return Err(format!("wrong number of arguments: want={}, got={}", cl.func.num_parameters(), num_args));
+    }
+    // each argument moves into a variable of its own
+    let mut locals: Vec<Var> = args.into_iter().map(new_var).collect();
+    locals.resize_with(cl.func.num_locals.max(num_args), || new_var(Value::Null));
+    Ok(Frame {
+        cl,
+        ip: 0,
+        base_pointer,
+        locals,
+        defers: vec![],
+        unwinding: false,
+        returning: None,
+        deferred: false,
+        recovers: false,
+    })
+}
+
+/// The value a failed instruction panics with. Go's runtime errors say
+/// so; its other run-time panics do not.
+fn runtime_panic(msg: String) -> Value {
+    const PLAIN: &[&str] = &[
+        "assignment to entry in nil map",
+        "send on closed channel",
+        "close of nil channel",
+        "close of closed channel",
+        "interface conversion:",
+        // panics of the packages
+        "strings: ",
+        "strconv: ",
+        "errors: ",
+    ];
+    if PLAIN.iter().any(|p| msg.starts_with(p)) {
+        Value::string(&msg)
+    } else {
+        Value::string(&format!("runtime error: {}", msg))
+    }
+}
+
+/// A number as a float, for arithmetic mixing floats and integers.
+fn float(value: &Value) -> f64 {
+    match value {
+        Value::Float(f) => *f,
+        Value::Integer(i) => *i as f64,
+        _ => 0.0,
+    }
+}
+
+/// Checks `key` is an index below `len`.
+fn bounds_check(key: &Value, len: usize) -> Result<usize, String> {
+    match key {
+        Value::Integer(i) if *i >= 0 && (*i as usize) < len => Ok(*i as usize),
+        Value::Integer(i) => Err(format!("index out of range [{}] with length {}", i, len)),
+        _ => Err(format!("invalid argument: index of type {} must be integer", key.ty())),
+    }
+}
+
+fn index(x: &Value, key: &Value) -> Result<Value, String> {
+    match x {
+        Value::Slice(s) => Ok(s.get(bounds_check(key, s.len)?)),
+        Value::String(s) => Ok(Value::Integer(s[bounds_check(key, s.len())?] as i64)),
+        Value::Map(m) => Ok(m.get(key)?.unwrap_or_else(|| m.elem.zero())),
+        _ => Err(format!("invalid operation: cannot index {} value", x.ty())),
+    }
+}
+
+/// `x[low:high]`; nil bounds are left out.
+fn slice(x: &Value, low: &Value, high: &Value) -> Result<Value, String> {
+    let (len, cap) = match x {
+        Value::Slice(s) => (s.len, s.cap),
+        Value::String(s) => (s.len(), s.len()),
+        _ => return Err(format!("cannot slice {} value", x.ty())),
+    };
+    let bound = |v: &Value, default: usize| match v {
+        Value::Null => Ok(default as i64),
+        Value::Integer(i) => Ok(*i),
+        _ => Err(format!("invalid argument: index of type {} must be integer", v.ty())),
+    };
+    let (lo, hi) = (bound(low, 0)?, bound(high, len)?);
+    let limit = if matches!(x, Value::String(_)) { ("length", len) } else { ("capacity", cap) };
+    if hi < 0 || hi as usize > limit.1 {
+        return Err(format!("slice bounds out of range [:{}] with {} {}", hi, limit.0, limit.1));
+    }
+    if lo < 0 || lo > hi {
+        return Err(format!("slice bounds out of range [{}:{}]", lo, hi));
+    }
+    let (lo, hi) = (lo as usize, hi as usize);
+    match x {
+        Value::Slice(s) => Ok(Value::Slice(s.slice(lo, hi))),
+        Value::String(s) => Ok(Value::String(Rc::from(&s[lo..hi]))),
+        _ => unreachable!(),
+    }
+}
+
+fn no_field(ty: &Type, name: &str) -> String {
+    format!("type {} has no field or method {}", ty, name)
+}
+
+/// Builds a composite literal of type `ty` from (key, value) pairs:
+/// field indexes for structs, element indexes for slices, keys for maps.
+fn composite(ty: &Type, values: Vec<Value>) -> Result<Value, String> {
+    let pairs = values.chunks(2).map(|pair| (pair[0].clone(), pair[1].clone()));
+    match ty {
+        Type::Struct(st) => {
+            heap::charge_slots(st.fields.borrow().len())?;
+            let mut value = ty.zero();
+            if let Value::Struct(s) = &mut value {
+                for (index, v) in pairs {
+                    if let Value::Integer(i) = index {
+                        let field_ty = st.fields.borrow()[i as usize].ty.clone();
+                        s.fields[i as usize] = convert(v, &field_ty)?;
+                    }
+                }
+            }
+            Ok(value)
+        }
+        Type::Slice(elem) => {
+            let pairs: Vec<(usize, Value)> = pairs
+                .map(|(index, v)| match index {
+                    Value::Integer(i) => (i as usize, v),
+                    _ => (0, v),
+                })
+                .collect();
+            let len = pairs.iter().map(|(i, _)| i + 1).max().unwrap_or(0);
+            heap::charge_slots(len)?;
+            let s = Slice::make(elem.as_ref().clone(), len, len);
+            for (i, v) in pairs {
+                s.set(i, v)?;
+            }
+            Ok(Value::Slice(s))
+        }
+        Type::Map(key, elem) => {
+            let m = Map::new(key.as_ref().clone(), elem.as_ref().clone());
+            for (k, v) in pairs {
+                m.insert(k, v)?;
+            }
+            Ok(Value::Map(m))
+        }
+        _ => Err(format!("invalid composite literal type {}", ty)),
+    }
+}
+
+/// `x.(ty)`.
+fn assert(x: &Value, ty: &Type) -> Result<Value, String> {
+    let i = match x {
+        Value::Interface(i) => i,
+        _ => return Err(format!("invalid operation: {} value is not an interface", x.ty())),
+    };
+    match &i.value {
+        None => Err(format!("interface conversion: interface is nil, not {}", ty)),
+        Some(v) if ty.is_interface() => convert(v.as_ref().clone(), ty),
+        Some(v) if v.ty().identical(ty) => Ok(v.as_ref().clone()),
+        Some(v) => Err(format!("interface conversion: {} is {}, not {}", x.ty(), v.type_name(), ty)),
+    }
+}
+
+/// The next key and value of a range loop, or None when it is done.
+fn next(iter: &mut RangeIter) -> Result<Option<(Value, Value)>, String> {
+    match iter {
+        RangeIter::Slice(s, i) if *i < s.len => {
+            *i += 1;
+            Ok(Some((Value::Integer(*i as i64 - 1), s.get(*i - 1))))
+        }
+        RangeIter::String(s, i) if *i < s.len() => {
+            let (c, size) = decode_rune(&s[*i..]);
+            let at = *i;
+            *i += size;
+            Ok(Some((Value::Integer(at as i64), Value::Integer(c as i64))))
+        }
+        RangeIter::Map(m, keys, i) => {
+            // keys deleted since the loop started are skipped
+            while *i < keys.len() {
+                let key = keys[*i].clone();
+                *i += 1;
+                if let Some(value) = m.get(&key)? {
+                    return Ok(Some((key, value)));
+                }
+            }
+            Ok(None)
+        }
+        RangeIter::Int(n, i) if *i < *n => {
+            *i += 1;
+            Ok(Some((Value::Integer(*i - 1), Value::Null)))
+        }
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,595 @@
+use crate::compiler::Compiler;
+use crate::lexer::Lexer;
+use crate::value::{heap, Value};
+use crate::parser::Parser;
+use crate::vm::*;
+use std::time::Duration;
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+
+  pub(crate) fn run(input: &str) -> Result<Value, String> {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    if !p.errors().is_empty() {
+      panic!("parser errors: {:?}", p.errors());
+    }
+    let mut c = Compiler::new();
+    if let Err(errors) = c.compile(&program) {
+      panic!("compiler errors: {:?}", errors);
+    }
+    let mut vm = Vm::new(c.bytecode());
+    vm.run()?;
+    Ok(vm.last_popped_stack_elem())
+  }
+
+  /// Compares the printed form of each input's last value.
+  pub(crate) fn check_inspect(tests: &[(&str, &str)]) {
+    for (input, expected) in tests {
+      match run(input) {
+        Ok(got) if got.inspect() == *expected => {}
+        got => panic!("{:?}: expected={:?}, got={:?}", input, expected, got.map(|v| v.inspect())),
+      }
+    }
+  }
+
+  pub(crate) fn check_error(tests: &[(&str, &str)]) {
+    for (input, expected) in tests.iter() {
+      match run(input) {
+        Err(msg) if msg == *expected => {}
+        got => panic!("{:?}: expected error {:?}, got={:?}", input, expected, got),
+      }
+    }
+  }
+
+  fn check(tests: &[(&str, Value)]) {
+    for (input, expected) in tests {
+      match run(input) {
+        Ok(got) if got == *expected => {}
+        got => panic!("{:?}: expected={:?}, got={:?}", input, expected, got),
+      }
+    }
+  }
+
+  #[test]
+  fn test_integer_arithmetic() {
+    check(&[
+      ("1 + 2", Value::Integer(3)),
+      ("50 / 2 * 2 + 10 - 5", Value::Integer(55)),
+      ("5 * (2 + 10)", Value::Integer(60)),
+      ("-50 + 100 + -50", Value::Integer(0)),
+      ("-7 / 2", Value::Integer(-3)),
+    ]);
+  }
+
+  #[test]
+  fn test_boolean_expressions() {
+    check(&[
+      ("1 < 2", Value::Boolean(true)),
+      ("1 > 2", Value::Boolean(false)),
+      ("1 == 1", Value::Boolean(true)),
+      ("true != false", Value::Boolean(true)),
+      ("(1 < 2) == true", Value::Boolean(true)),
+      ("!true", Value::Boolean(false)),
+      ("!!true", Value::Boolean(true)),
+    ]);
+  }
+
+  #[test]
+  fn test_conditionals() {
+    check(&[
+      ("if true { 10 }", Value::Integer(10)),
+      ("if 1 > 2 { 10 } else { 20 }", Value::Integer(20)),
+      ("if 1 > 2 { 10 }", Value::Null),
+    ]);
+  }
+
+  #[test]
+  fn test_global_let_statements() {
+    check(&[
+      ("let one = 1; one", Value::Integer(1)),
+      ("let one = 1; let two = one + one; one + two", Value::Integer(3)),
+      ("let x = 1; let x = x + 1; x", Value::Integer(2)),
+    ]);
+  }
+
+  #[test]
+  fn test_functions() {
+    check(&[
+      ("let f = fn() { 5 + 10 }; f()", Value::Integer(15)),
+      ("let f = fn() { return 1; 2 }; f()", Value::Integer(1)),
+      ("let f = fn() { }; f()", Value::Null),
+      ("let sum = fn(a, b) { let c = a + b; c }; sum(1, 2) + sum(3, 4)", Value::Integer(10)),
+      ("let g = 50; let f = fn() { let g = 1; g }; f() + g", Value::Integer(51)),
+    ]);
+  }
+
+  #[test]
+  fn test_closures_and_recursion() {
+    check(&[
+      ("let adder = fn(a) { fn(b) { a + b } }; let add2 = adder(2); add2(8)", Value::Integer(10)),
+      (
+        "let fib = fn(n) { if n < 2 { return n; } fib(n - 1) + fib(n - 2) }; fib(15)",
+        Value::Integer(610),
+      ),
+      (
+        "let wrapper = fn() { let count = fn(x) { if x == 0 { return 0; } count(x - 1) }; count(3) }; wrapper()",
+        Value::Integer(0),
+      ),
+    ]);
+  }
+
+  #[test]
+  fn test_runtime_errors() {
+    let tests = [
+      ("1 / 0", "runtime error: integer divide by zero"),
+      ("fn(a) { a }()", "runtime error: wrong number of arguments: want=1, got=0"),
+      ("1(2)", "runtime error: cannot call non-function (type int)"),
+      ("if 1 { 2 }", "runtime error: non-boolean condition in if expression: int"),
+      ("let f = fn() { f() }; f()", "runtime error: stack overflow"),
+    ];
+    for (input, expected) in tests.iter() {
+      match run(input) {
+        Err(msg) if msg == *expected => {}
+        got => panic!("{:?}: expected error {:?}, got={:?}", input, expected, got),
+      }
+    }
+  }
+
+  #[test]
+  fn test_call_from_host() {
+    let mut p = Parser::new(Lexer::new("let double = fn(x) { x * 2 };".to_string()));
+    let program = p.parse_program();
+    let mut c = Compiler::new();
+    c.compile(&program).unwrap();
+    let index = c.symbol_table.resolve("double").unwrap().index;
+    let mut vm = Vm::new(c.bytecode());
+    vm.run().unwrap();
+    let double = vm.global(index);
+    let got = vm.call(double, vec![Value::Integer(21)]);
+    if got != Ok(Value::Integer(42)) {
+      panic!("expected=42, got={:?}", got);
+    }
+  }
+
+  #[test]
+  fn test_strings() {
+    check_inspect(&[
+      (r#""a" + "b""#, "ab"),
+      (r#"len("héllo")"#, "6"),
+      (r#""héllo"[1]"#, "195"),
+      (r#""hello"[1:3]"#, "el"),
+      (r#""abc" < "abd""#, "true"),
+      (r#"let n = 0; for i, r := range "hé!" { n = n * 10 + i }; n"#, "13"),
+    ]);
+  }
+
+  #[test]
+  fn test_slices_share_backing_arrays() {
+    check_inspect(&[
+      ("s := []int{1, 2, 3}; t := s[:2]; t = append(t, 9); s", "[1 2 9]"),
+      ("s := []int{1, 2, 3}; u := append(s, 4); u[0] = 100; s", "[1 2 3]"),
+      ("s := []int{1, 2, 3}; u := append(s, 4); cap(u)", "6"),
+      ("var s []int; s == nil", "true"),
+      ("var s []int; s = append(s, 1, 2, 3, 4, 5); cap(s)", "6"),
+      ("s := []int{5: 1}; len(s)", "6"),
+      ("s := []int{1, 2, 3, 4}; t := s[1:2]; cap(t)", "3"),
+      ("s := [][]int{{1}, {2, 3}}; s[1][1]", "3"),
+    ]);
+  }
+
+  #[test]
+  fn test_maps() {
+    check_inspect(&[
+      (r#"m := map[string]int{"b": 2, "a": 1}; m["c"] = 3; m"#, "map[a:1 b:2 c:3]"),
+      (r#"m := map[string]int{}; m["x"]"#, "0"),
+      (r#"m := map[string]int{"a": 1}; v, ok := m["a"]; ok"#, "true"),
+      (r#"m := map[string]int{"a": 1}; v, ok := m["b"]; ok"#, "false"),
+      (r#"m := map[string]int{"a": 1, "b": 2}; len(m)"#, "2"),
+      ("m := map[any]int{1: 1}; m[1]", "1"),
+      ("var m map[int]int; m[3]", "0"),
+      ("m := map[int]int{1: 10, 2: 20, 3: 30}; s := 0; for k, v := range m { s += k * v }; s", "140"),
+    ]);
+  }
+
+  #[test]
+  fn test_map_iteration_order_varies() {
+    let input = "m := map[int]int{}; for i := 0; i < 20; i++ { m[i] = i }; f := -1; for k := range m { if f < 0 { f = k } }; f";
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    let mut c = Compiler::new();
+    c.compile(&program).unwrap();
+    let mut firsts = std::collections::HashSet::new();
+    for seed in 0..20 {
+      let mut vm = Vm::new(c.bytecode());
+      vm.set_seed(seed);
+      vm.run().unwrap();
+      firsts.insert(vm.last_popped_stack_elem().inspect());
+    }
+    if firsts.len() < 2 {
+      panic!("iteration always started at {:?}", firsts);
+    }
+  }
+
+  #[test]
+  fn test_structs_and_pointers() {
+    let point = "type Point struct { X, Y int }; ";
+    let tests: Vec<(String, &str)> = vec![
+      ("p := Point{X: 1, Y: 2}; q := p; q.X = 5; p", "{1 2}"),
+      ("p := Point{1, 2}; pp := &p; pp.Y = 7; p", "{1 7}"),
+      ("p := &Point{X: 1}; p", "&{1 0}"),
+      ("p := Point{}; q := &p.X; *q = 4; p.X", "4"),
+      ("x := 1; p := &x; *p = 2; x", "2"),
+      ("ps := []*Point{{X: 1}, {Y: 2}}; ps[1].Y", "2"),
+      ("ps := []Point{{1, 2}}; ps[0].X = 9; ps", "[{9 2}]"),
+      ("ps := []Point{{1, 2}}; p := &ps[0]; p.Y = 3; ps[0].Y", "3"),
+      ("Point{1, 2} == Point{1, 2}", "true"),
+      ("var p *Point; p == nil", "true"),
+      ("m := map[Point]string{{1, 2}: \"a\"}; m[Point{1, 2}]", "a"),
+      ("type Node struct { next *Node; v int }; n := &Node{v: 1}; n.next = &Node{v: 2}; n.next.v", "2"),
+    ]
+    .into_iter()
+    .map(|(input, expected)| (format!("{}{}", point, input), expected))
+    .collect();
+    let tests: Vec<(&str, &str)> = tests.iter().map(|(i, e)| (i.as_str(), *e)).collect();
+    check_inspect(&tests);
+  }
+
+  #[test]
+  fn test_interfaces() {
+    check_inspect(&[
+      ("var e interface{}; e == nil", "true"),
+      ("type T struct{}; var p *T; var e any = p; e == nil", "false"),
+      ("var e any = 1; e == 1", "true"),
+      (r#"var e any = 1; e == "1""#, "false"),
+      ("var e any = 1; e = true; e", "true"),
+      ("var e any = 1; v, ok := e.(int); ok", "true"),
+      ("var e any = 1; v, ok := e.(string); v", ""),
+      ("var e any = 1; e.(int) + 1", "2"),
+      ("var a any = 1; var b any = 1; a == b", "true"),
+      ("s := []any{1, true, nil}; s", "[1 true <nil>]"),
+    ]);
+  }
+
+  #[test]
+  fn test_loops_and_closures() {
+    check_inspect(&[
+      ("s := 0; for i := 0; i < 10; i++ { if i == 5 { break }; s += i }; s", "10"),
+      ("s := 0; for i := 0; i < 5; i++ { if i % 2 == 0 { continue }; s += i }; s", "4"),
+      ("n := 1; for n < 100 { n *= 2 }; n", "128"),
+      (
+        "let f = fn() { g := fn() { 0 }; for i := 0; i < 3; i++ { if i == 1 { g = fn() { i } } }; g() }; f()",
+        "1",
+      ),
+      ("x := 1; f := fn() { x = x + 1 }; f(); f(); x", "3"),
+      ("x := 0; for i := range 4 { x += i }; x", "6"),
+      ("a, b := 1, 2; a, b = b, a; a * 10 + b", "21"),
+      ("true && false || true", "true"),
+      ("let f = fn() { 1 / 0 }; false && f()", "false"),
+      ("7 % 3 <= 1", "true"),
+    ]);
+  }
+
+  #[test]
+  fn test_value_runtime_errors() {
+    check_error(&[
+      ("s := []int{1}; s[3]", "runtime error: index out of range [3] with length 1"),
+      ("s := []int{1}; s[0:3]", "runtime error: slice bounds out of range [:3] with capacity 1"),
+      ("var m map[string]int; m[\"a\"] = 1", "assignment to entry in nil map"),
+      ("type T struct{ X int }; var p *T; p.X", "runtime error: invalid memory address or nil pointer dereference"),
+      ("var e any = \"s\"; e.(int)", "interface conversion: interface {} is string, not int"),
+      ("var e any; e.(int)", "interface conversion: interface is nil, not int"),
+      ("m := map[any]int{}; m[[]int{1}] = 1", "runtime error: hash of unhashable type []int"),
+      ("var a any = []int{1}; a == a", "runtime error: comparing uncomparable type []int"),
+    ]);
+  }
+
+  #[test]
+  fn test_goroutines_and_channels() {
+    check_inspect(&[
+      ("ch := make(chan int, 2); ch <- 1; ch <- 2; a := <-ch; b := <-ch; a * 10 + b", "12"),
+      ("ch := make(chan int); go fn(c) { c <- 42 }(ch); <-ch", "42"),
+      ("ch := make(chan int, 3); len(ch) * 10 + cap(ch)", "3"),
+      ("ch := make(chan int, 1); ch <- 1; close(ch); a, ok1 := <-ch; b, ok2 := <-ch; a * 10 + b", "10"),
+      ("ch := make(chan int); close(ch); v, ok := <-ch; ok", "false"),
+      (
+        "ch := make(chan int); go fn() { for i := 0; i < 5; i++ { ch <- i }; close(ch) }(); s := 0; for v := range ch { s += v }; s",
+        "10",
+      ),
+      (
+        "let worker = fn(jobs, results) { for j := range jobs { results <- j * j } }; \
+         jobs := make(chan int); results := make(chan int); \
+         for w := 0; w < 3; w++ { go worker(jobs, results) }; \
+         go fn() { for i := 1; i <= 4; i++ { jobs <- i }; close(jobs) }(); \
+         s := 0; for i := 0; i < 4; i++ { s += <-results }; s",
+        "30",
+      ),
+      ("done := make(chan bool); x := 0; go fn() { x = 7; done <- true }(); <-done; x", "7"),
+      ("var c chan int; c == nil", "true"),
+      ("a := make(chan int); b := a; a == b", "true"),
+      ("make(chan int) == make(chan int)", "false"),
+      ("var c chan []int; c", "<nil>"),
+    ]);
+  }
+
+  #[test]
+  fn test_select() {
+    check_inspect(&[
+      ("ch := make(chan int); r := 0; select { case v := <-ch: r = v; default: r = -1 }; r", "-1"),
+      ("ch := make(chan int, 1); ch <- 5; r := 0; select { case v := <-ch: r = v; default: r = -1 }; r", "5"),
+      ("ch := make(chan int, 1); select { case ch <- 3: }; <-ch", "3"),
+      (
+        "a := make(chan int); b := make(chan string); go fn() { b <- \"hi\" }(); r := \"\"; \
+         select { case v := <-a: r = \"a\"; case s, ok := <-b: if ok { r = s } }; r",
+        "hi",
+      ),
+      ("ch := make(chan int); close(ch); r := 0; select { case v, ok := <-ch: if !ok { r = 9 } }; r", "9"),
+      ("ch := make(chan int); n := 0; for i := 0; i < 3; i++ { select { case <-ch: default: continue }; n++ }; n", "0"),
+      ("n := 0; for i := 0; i < 3; i++ { select { default: break }; n++ }; n", "3"),
+      (
+        "quit := make(chan bool); data := make(chan int); \
+         go fn() { for i := 0; i < 3; i++ { data <- i }; quit <- true }(); \
+         s := 0; for { r := false; select { case v := <-data: s += v; case <-quit: r = true }; if r { break } }; s",
+        "3",
+      ),
+    ]);
+  }
+
+  #[test]
+  fn test_goroutine_errors() {
+    check_error(&[
+      ("ch := make(chan int); ch <- 1", "all goroutines are asleep - deadlock!"),
+      ("ch := make(chan int); <-ch", "all goroutines are asleep - deadlock!"),
+      ("var ch chan int; go fn() { ch <- 1 }(); <-ch", "all goroutines are asleep - deadlock!"),
+      ("select {}", "all goroutines are asleep - deadlock!"),
+      ("ch := make(chan int); close(ch); close(ch)", "close of closed channel"),
+      ("var ch chan int; close(ch)", "close of nil channel"),
+      ("ch := make(chan int, 1); close(ch); ch <- 1", "send on closed channel"),
+      ("ch := make(chan int); go fn() { ch <- 1 }(); close(ch); <-make(chan int)", "send on closed channel"),
+      ("ch := make(chan int, 1); ch <- \"s\"", "runtime error: cannot use string value as int value"),
+      ("x := 1; <-x", "runtime error: invalid operation: cannot receive from non-channel 1 (type int)"),
+    ]);
+  }
+
+  #[test]
+  fn test_seeded_schedule_is_repeatable() {
+    let input = "out := make(chan int, 10); done := make(chan bool); \
+      for g := 0; g < 3; g++ { go fn(id) { for i := 0; i < 3; i++ { out <- id; select { default: } ; for j := 0; j < 400; j++ {} }; done <- true }(g) }; \
+      <-done; <-done; <-done; close(out); s := []int{}; for v := range out { s = append(s, v) }; s";
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    let mut c = Compiler::new();
+    c.compile(&program).unwrap();
+    let run_seed = |seed| {
+      let mut vm = Vm::new(c.bytecode());
+      vm.set_seed(seed);
+      vm.run().unwrap();
+      vm.last_popped_stack_elem().inspect()
+    };
+    let mut orders = std::collections::HashSet::new();
+    for seed in 0..10 {
+      let order = run_seed(seed);
+      assert_eq!(order, run_seed(seed), "seed {} gave different schedules", seed);
+      orders.insert(order);
+    }
+    if orders.len() < 2 {
+      panic!("every seed gave the schedule {:?}", orders);
+    }
+  }
+
+  #[test]
+  fn test_builtins() {
+    check_inspect(&[
+      ("s := []int{1}; t := []int{2, 3}; append(s, t...)", "[1 2 3]"),
+      ("var s []int; var t []int; append(s, t...) == nil", "true"),
+      ("b := []byte{104}; string(append(b, \"i!\"...))", "hi!"),
+      ("len(append([]int{}, make([]int, 5000)...))", "5000"),
+      ("dst := make([]int, 2); n := copy(dst, []int{7, 8, 9}); n * 100 + dst[0] * 10 + dst[1]", "278"),
+      ("b := make([]byte, 3); copy(b, \"hello\"); string(b)", "hel"),
+      // overlapping slices copy as if through a buffer
+      ("s := []int{1, 2, 3, 4}; copy(s[1:], s); s", "[1 1 2 3]"),
+      ("m := map[string]int{\"a\": 1, \"b\": 2}; delete(m, \"a\"); delete(m, \"z\"); m", "map[b:2]"),
+      ("var m map[string]int; delete(m, \"a\"); len(m)", "0"),
+      ("p := new(int); *p = 3; *p + 1", "4"),
+      ("type T struct{ X int }; p := new(T); p.X = 2; p", "&{2}"),
+      ("min(3, 1, 2) * 10 + max(3, 1, 2)", "13"),
+      ("min(\"b\", \"a\", \"c\") + max(\"b\")", "ab"),
+      ("m := map[int]int{1: 1}; clear(m); len(m)", "0"),
+      ("s := []int{1, 2, 3}; clear(s[1:]); s", "[1 0 0]"),
+      ("println(1, \"a\", true)", "nil"),
+    ]);
+    check_error(&[
+      ("min()", "runtime error: not enough arguments for min: want at least 1, got 0"),
+      ("max(1, \"a\")", "runtime error: invalid argument: mismatched types int and string"),
+      ("min(true, false)", "runtime error: invalid argument: true (type bool) cannot be ordered"),
+      ("append([]int{}, []string{\"a\"}...)", "runtime error: cannot use [a] (type []string) as []int value in argument to append"),
+      ("copy([]int{1}, \"a\")", "runtime error: invalid argument: arguments to copy [1] (type []int) and a (type string) have different element types int and uint8"),
+      ("copy(1, []int{})", "runtime error: invalid argument: copy expects slice arguments; found 1 (type int) and [] (type []int)"),
+      ("delete([]int{}, 0)", "runtime error: invalid argument: [] (type []int) is not a map"),
+      ("clear(1)", "runtime error: invalid argument: 1 (type int) must be a map or slice"),
+      ("println([]int{1})", "runtime error: illegal types for operand: println [1] (type []int)"),
+      ("make([]int, 3, 1)", "runtime error: invalid argument: length and capacity swapped (3 > 1)"),
+      ("make([]int, 9223372036854775807)", "runtime error: makeslice: len out of range"),
+      ("make([]int, 0, 9223372036854775807)", "runtime error: makeslice: cap out of range"),
+      ("make(chan int, 9223372036854775807)", "runtime error: makechan: size out of range"),
+    ]);
+  }
+
+  #[test]
+  fn test_defer() {
+    check_inspect(&[
+      // arguments are evaluated at the defer, calls run last first
+      ("s := []int{}; let f = fn() { for i := 0; i < 3; i++ { defer fn(i) { s = append(s, i) }(i) }; s = append(s, 9) }; f(); s", "[9 2 1 0]"),
+      ("let f = fn() { x := 1; defer fn(v) { x = v }(x); x = 2; return x }; f()", "2"),
+      // deferred calls can change a named result
+      ("let f = fn() (n int) { defer fn() { n = n * 2 }(); return 21 }; f()", "42"),
+      ("let f = fn() (n int) { n = 5; return }; f()", "5"),
+      ("let f = fn() (s string) { }; f() == \"\"", "true"),
+      ("ch := make(chan int, 1); let f = fn() { defer close(ch); ch <- 1 }; f(); a, ok1 := <-ch; b, ok2 := <-ch; ok2", "false"),
+    ]);
+  }
+
+  #[test]
+  fn test_panic_and_recover() {
+    check_inspect(&[
+      ("let f = fn() (r int) { defer fn() { if recover() != nil { r = -1 } }(); panic(\"boom\") }; f()", "-1"),
+      ("let f = fn() (v any) { defer fn() { v = recover() }(); panic(\"boom\") }; f()", "boom"),
+      ("let f = fn() (v any) { defer fn() { v = recover() }(); s := []int{}; s[1] }; f()", "runtime error: index out of range [1] with length 0"),
+      ("let f = fn() (v any) { defer fn() { v = recover() }(); var m map[int]int; m[1] = 1 }; f()", "assignment to entry in nil map"),
+      // recover only stops a panic when a deferred call makes it
+      ("let f = fn() (v any) { v = recover(); return }; f()", "<nil>"),
+      ("let f = fn() { defer fn() { recover() }(); panic(1) }; f(); 7", "7"),
+      // the panic goes through functions without deferred calls
+      ("let g = fn() { panic(\"deep\") }; let f = fn() (v any) { defer fn() { v = recover() }(); g() }; f()", "deep"),
+      // calls a deferred function makes while a panic unwinds return to it
+      ("log := []string{}; let cleanup = fn(s) { log = append(log, s) }; let f = fn() { defer fn() { cleanup(\"a\"); cleanup(\"b\"); recover() }(); panic(\"boom\") }; f(); log", "[a b]"),
+      ("let helper = fn() { return recover() }; let f = fn() (v any) { defer fn() { recover() }(); defer fn() { v = helper() }(); panic(1) }; f()", "<nil>"),
+      // a goroutine that recovers does not stop the program
+      ("done := make(chan bool); go fn() { defer fn() { recover(); done <- true }(); panic(1) }(); <-done", "true"),
+    ]);
+    check_error(&[
+      ("panic(\"boom\")", "boom"),
+      ("panic(42)", "42"),
+      ("panic(nil)", "panic called with nil argument"),
+      ("type T struct{ X int }; panic(T{1})", "(T) {1}"),
+      ("let f = fn() { defer recover(); panic(1) }; f()", "1"),
+      ("let f = fn() { defer fn() { panic(\"second\") }(); panic(\"first\") }; f()", "second"),
+      ("go fn() { panic(\"in goroutine\") }(); for {}", "in goroutine"),
+    ]);
+  }
+
+  #[test]
+  fn test_panic_trace() {
+    let input = "let f = fn(s) {\n\treturn s[5]\n}\nlet main = fn() {\n\tf([]int{1})\n}\nmain()";
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    let mut c = Compiler::new();
+    c.set_file("main.nm");
+    c.compile(&program).unwrap();
+    let mut vm = Vm::new(c.bytecode());
+    assert_eq!(vm.run(), Err("runtime error: index out of range [5] with length 1".to_string()));
+    let expected = "goroutine 1 [running]:\nmain.f(...)\n\tmain.nm:2:10\nmain.main()\n\tmain.nm:5:3\nmain.init()\n\tmain.nm:7:5\n";
+    assert_eq!(vm.trace(), Some(expected));
+  }
+
+  fn run_limited(input: &str, limits: Limits) -> (Result<(), String>, Option<Limit>) {
+    let program = Parser::new(Lexer::new(input.to_string())).parse_program();
+    let mut c = Compiler::new();
+    c.compile(&program).unwrap();
+    let mut vm = Vm::new(c.bytecode());
+    vm.set_limits(limits);
+    let result = vm.run();
+    (result, vm.exceeded())
+  }
+
+  #[test]
+  fn test_limits() {
+    let spin = "let f = fn() { defer fn() { recover() }(); for {} }; f()";
+    let tests = [
+      (spin, Limits { instructions: Some(10_000), ..Default::default() }, Limit::Instructions(10_000)),
+      (spin, Limits { timeout: Some(Duration::from_millis(20)), ..Default::default() }, Limit::Timeout(Duration::from_millis(20))),
+      ("let f = fn(n) { return f(n + 1) }; f(0)", Limits { call_depth: Some(50), ..Default::default() }, Limit::CallDepth(50)),
+      (
+        "for i := 0; i < 10; i++ { go fn() { for {} }() }",
+        Limits { goroutines: Some(4), ..Default::default() },
+        Limit::Goroutines(4),
+      ),
+      ("var s []int; for { s = append(s, 1) }", Limits { heap: Some(1 << 20), ..Default::default() }, Limit::Heap(1 << 20)),
+      ("s := make([]int, 1099511627776); len(s)", Limits { heap: Some(1 << 20), ..Default::default() }, Limit::Heap(1 << 20)),
+      (
+        "type Node struct { next *Node }; var n *Node; for { n = &Node{n} }",
+        Limits { heap: Some(1 << 16), ..Default::default() },
+        Limit::Heap(1 << 16),
+      ),
+    ];
+    for (input, limits, expected) in tests.iter() {
+      match run_limited(input, limits.clone()) {
+        (Err(msg), Some(limit)) if limit == *expected && msg == expected.to_string() => {}
+        got => panic!("{:?}: expected {:?}, got {:?}", input, expected, got),
+      }
+    }
+
+    // what can no longer be reached does not count
+    let garbage = "n := 0; for i := 0; i < 20000; i++ { s := make([]int, 100); n += len(s) }; n";
+    let (result, exceeded) = run_limited(garbage, Limits { heap: Some(1 << 20), ..Default::default() });
+    assert_eq!((result, exceeded), (Ok(()), None));
+    assert_eq!(Limit::Heap(64).to_string(), "heap limit of 64 bytes exceeded");
+  }
+
+  fn run_collected(input: &str, stress: bool) -> (Result<Value, String>, heap::Stats) {
+    let program = Parser::new(Lexer::new(input.to_string())).parse_program();
+    let mut c = Compiler::new();
+    c.compile(&program).unwrap();
+    let mut vm = Vm::new(c.bytecode());
+    vm.set_gc_stress(stress);
+    let result = vm.run().map(|_| vm.last_popped_stack_elem());
+    (result, vm.mem_stats())
+  }
+
+  #[test]
+  fn test_collect_cycles() {
+    let cycles = [
+      "type Node struct { next *Node }; for i := 0; i < 100; i++ { n := &Node{}; n.next = n }",
+      "for i := 0; i < 100; i++ { s := make([]any, 1); s[0] = s }",
+      "for i := 0; i < 100; i++ { m := map[string]any{}; m[\"m\"] = m }",
+      "for i := 0; i < 100; i++ { c := make(chan any, 1); c <- c }",
+      "for i := 0; i < 100; i++ { var f any; f = fn() { return f } }",
+      "type Node struct { next *Node }; for i := 0; i < 100; i++ { a := &Node{}; b := &Node{a}; a.next = b }",
+      "for i := 0; i < 100; i++ { x := 0; m := map[*int]any{}; m[&x] = m }",
+    ];
+    for input in cycles.iter() {
+      match run_collected(input, true) {
+        (Ok(_), stats) if stats.frees >= 90 && stats.num_gc > 0 => {}
+        got => panic!("{:?}: expected the cycles freed, got {:?}", input, got),
+      }
+    }
+
+    // collections come as objects pile up, without stress
+    let input = "type Node struct { next *Node }; for i := 0; i < 40000; i++ { n := &Node{}; n.next = n }";
+    let (result, stats) = run_collected(input, false);
+    assert!(result.is_ok());
+    assert!(stats.num_gc > 0 && stats.mallocs - stats.frees < 20000, "{:?}", stats);
+  }
+
+  #[test]
+  fn test_collect_keeps_reachable() {
+    let tests = [
+      ("type Node struct { next *Node; v int }; n := &Node{nil, 7}; n.next = n; for i := 0; i < 50; i++ { m := &Node{}; m.next = m }; n.next.next.v", "7"),
+      ("s := make([]any, 1); s[0] = s; for i := 0; i < 50; i++ { t := []int{i} }; len(s[0].([]any))", "1"),
+      ("let f = fn(n) { if n == 0 { return 0 }; return n + f(n - 1) }; f(30)", "465"),
+      ("c := make(chan int, 3); go fn() { for i := 0; i < 3; i++ { c <- i * i } }(); <-c + <-c + <-c", "5"),
+      ("m := map[string][]int{}; for i := 0; i < 20; i++ { m[\"k\"] = append(m[\"k\"], i) }; len(m[\"k\"])", "20"),
+      ("let f = fn() { defer fn() { recover() }(); panic([]int{1, 2}) }; f(); x := []int{3}; x[0]", "3"),
+    ];
+    for (input, expected) in tests.iter() {
+      match run_collected(input, true) {
+        (Ok(got), _) if got.inspect() == *expected => {}
+        got => panic!("{:?}: expected={:?}, got={:?}", input, expected, got),
+      }
+    }
+
+    // a cycle the host holds a value of stays whole
+    let input = "type Node struct { next *Node; v int }
+let build = fn() { n := &Node{nil, 7}; n.next = n; return n }
+let churn = fn() { for i := 0; i < 10; i++ { m := &Node{}; m.next = m } }";
+    let program = Parser::new(Lexer::new(input.to_string())).parse_program();
+    let mut c = Compiler::new();
+    c.compile(&program).unwrap();
+    let (build, churn) = (c.symbol_table.resolve("build").unwrap().index, c.symbol_table.resolve("churn").unwrap().index);
+    let mut vm = Vm::new(c.bytecode());
+    vm.set_gc_stress(true);
+    vm.run().unwrap();
+    let node = vm.call(vm.global(build), vec![]).unwrap();
+    vm.call(vm.global(churn), vec![]).unwrap();
+    assert!(vm.mem_stats().frees >= 10);
+    let next = match &node {
+      Value::Pointer(p) => match p.load() {
+        Ok(Value::Struct(s)) => s.fields.clone(),
+        got => panic!("expected a struct, got {:?}", got),
+      },
+      got => panic!("expected a pointer, got {:?}", got),
+    };
+    assert_eq!((&next[0], &next[1]), (&node, &Value::Integer(7)));
+  }
+}